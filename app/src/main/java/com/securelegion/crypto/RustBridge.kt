@@ -226,11 +226,12 @@ object RustBridge {
      * After encrypting with deferred mode, store the uncommitted ratchet state
      * Will be committed when PING_ACK arrives, or rolled back on failure
      *
-     * @param contactId Contact identifier
+     * @param contactId Canonical contact id from deriveContactId (sl_…)
      * @param messageId Message identifier
      * @param nextChainKey The next chain key from encryptMessageDeferred
      * @param nextSequence The next sequence from encryptMessageDeferred
      * @return True if stored successfully
+     * @throws SecurityException "IDENTITY_KEY_CHANGED" while a verified contact's key change is unapproved
      */
     external fun storePendingRatchetAdvancement(
         contactId: String,
@@ -244,7 +245,7 @@ object RustBridge {
      * Call this when PING_ACK arrives to finalize the ratchet advancement
      * Returns the stored next ratchet state so caller can update their chain key
      *
-     * @param contactId Contact identifier
+     * @param contactId Canonical contact id from deriveContactId (sl_…)
      * @return JSON string: {"nextChainKey":"base64","nextSequence":123}, or null if not found
      */
     external fun commitRatchetAdvancement(contactId: String): String?
@@ -254,11 +255,36 @@ object RustBridge {
      * Call this when message send fails (no PING_ACK received within timeout)
      * This discards the uncommitted ratchet state, keeping the old chain key
      *
-     * @param contactId Contact identifier
+     * @param contactId Canonical contact id from deriveContactId (sl_…)
      * @return True if rollback succeeded
      */
     external fun rollbackRatchetAdvancement(contactId: String): Boolean
 
+    // ===== Send Intents =====
+
+    /**
+     * Open the crash-recovery log of pending sends; call at startup before the first send.
+     * Apply the returned actions: advanceRatchet (fastForwardSendChain the persisted chain),
+     * resend (send the exact ciphertext again), markDelivered; then completeSendIntent(intentId).
+     *
+     * @param dir Directory for the log's encrypted files
+     * @param key 32-byte key sealing the log
+     * @return JSON array of {type, contactId, nextSequence | intentId, messageId, ciphertext}
+     */
+    external fun openSendIntentLog(dir: String, key: ByteArray): String?
+
+    /** Log a ciphertext encrypted at [sequence] before sending it; throws SecurityException BELOW_RECOVERED_FLOOR on a stale chain. */
+    external fun recordSendIntent(contactId: String, messageId: String, sequence: Long, ciphertext: ByteArray): Boolean
+
+    /** Move a persisted send chain up to the recovered floor; JSON {"chainKey":"base64","sequence":n}. */
+    external fun fastForwardSendChain(contactId: String, chainKey: ByteArray, sequence: Long): String?
+
+    /** The chain returned by commitRatchetAdvancement has been persisted. */
+    external fun sendChainPersisted(contactId: String): Boolean
+
+    /** A recovered resend was acknowledged or a recovered delivered mark stored. */
+    external fun completeSendIntent(intentId: Long): Boolean
+
     /**
      * FIX #9: Check if PING is a replay attack
      * Uses Blake3 hash of PING wire bytes + sender pubkey for deduplication
@@ -278,7 +304,7 @@ object RustBridge {
      * Enforces state machine: PING_ACK → PONG_ACK → MESSAGE_ACK
      * Rejects out-of-order ACKs to prevent protocol violations
      *
-     * @param contactId Contact identifier
+     * @param contactId Canonical contact id from deriveContactId (sl_…)
      * @param ackType ACK type: 0=PING_ACK, 1=PONG_ACK, 2=MESSAGE_ACK
      * @return True if ACK is valid and accepted, False if rejected
      */
@@ -291,7 +317,7 @@ object RustBridge {
      * FIX #7: Reset ACK state for a contact
      * Call this after completing a message exchange to reset the state machine
      *
-     * @param contactId Contact identifier
+     * @param contactId Canonical contact id from deriveContactId (sl_…)
      */
     external fun resetAckState(contactId: String)
 
@@ -430,6 +456,33 @@ object RustBridge {
         messageKey: ByteArray
     ): String?
 
+    // ==================== PROTOCOL CONTEXTS ====================
+
+    /**
+     * Create an independent protocol context (own TorManager, receive queues and CRDT groups),
+     * e.g. one per profile. Every call without a handle acts on the active context.
+     * @return Opaque handle, or 0 on failure
+     */
+    external fun createContext(): Long
+
+    /**
+     * Route all following calls to the context behind [handle].
+     * Only one context should run a hidden-service listener at a time.
+     * @return False if the handle is unknown or destroyed
+     */
+    external fun activateContext(handle: Long): Boolean
+
+    /**
+     * Handle of the active context (the default context until [activateContext] is called)
+     */
+    external fun activeContext(): Long
+
+    /**
+     * Release a context and its state. The active context cannot be destroyed.
+     * @return False if nothing was released
+     */
+    external fun destroyContext(handle: Long): Boolean
+
     // ==================== TOR NETWORK & MESSAGING ====================
 
     /**
@@ -1161,6 +1214,12 @@ object RustBridge {
      */
     external fun cleanupExpiredSessions()
 
+    /**
+     * Run one background maintenance pass (expired sessions, groups due for crdtCompactOps)
+     * @return JSON report: expiredPings, expiredPongs, expiredAcks, compactionDue, ...
+     */
+    external fun runMaintenance(): String
+
     /**
      * Send encrypted message blob after Pong is received
      * Used for persistent messaging - after Pong arrives, send the actual message
@@ -1535,11 +1594,81 @@ object RustBridge {
 
     /**
      * Query derived state for a loaded group.
-     * queryType: "members", "messages", "messages_after", "metadata", "heads", "state_hash", "limit_status"
+     * queryType: "members", "messages", "messages_after", "messages_page", "receipts", "concurrent_edits", "membership_events", "metadata", "heads", "state_hash", "limit_status"
+     * Messages carry "delivered_count" / "read_count"; "receipts" takes {"msg_id_hex": "..."}.
+     * "messages_page" takes one of {"before"|"after": op_id}, {"unread_since": N, "reader_hex"} or
+     * {"from_ms", "to_ms"} plus "limit" / "include_deleted", and returns
+     * {"messages": [...], "has_more", "oldest", "newest"} (oldest first).
+     * Messages also carry "edited" and "concurrent_edit_count"; "concurrent_edits" takes {"msg_id_hex"} and
+     * returns the edits that lost to a concurrent one as [{"op_id", "ciphertext_b64", "nonce_b64"}].
+     * "membership_events" takes {"cursor": N} and returns {"events": [...], "cursor": next}; each event has
+     * "event_id" (same on every device), "kind", "actor", "subject" and, where relevant, "role" / "from_role".
+     * The cursor resets when the group is reloaded — re-read from 0 and skip known event_ids.
      * @return JSON (varies by queryType)
      */
     external fun crdtQuery(groupIdHex: String, queryType: String, paramsJson: String): String
 
+    /**
+     * Local receipt privacy for all groups. When a kind is disabled,
+     * crdtCreateOp("ReceiptSet", {"msg_id_hex", "status": "Delivered"|"Read"}) throws
+     * IllegalStateException("RECEIPTS_DISABLED").
+     */
+    external fun crdtSetReceiptPolicy(sendDelivered: Boolean, sendRead: Boolean)
+
+    /**
+     * Drop superseded ops (old edits, toggled reactions, overwritten metadata) from a
+     * group's full log. Delete the dropped ops from storage.
+     * @return JSON {"kept": N, "dropped": ["op_id", ...]}
+     */
+    external fun crdtCompactOps(groupIdHex: String, serializedOpsBytes: ByteArray): String
+
+    /**
+     * Seal a group avatar (jpeg/png/webp, max 256 KiB) and its thumbnail (max 16 KiB) under the GroupSecret.
+     * Upload both blobs, then crdtCreateOp("MetadataSet", {"key": "Avatar", "value_b64"}).
+     * @return JSON {"value_b64", "full_b64", "full_digest", "thumbnail_b64", "thumbnail_digest"}
+     */
+    external fun crdtSealAvatar(groupSecret: ByteArray, image: ByteArray, thumbnail: ByteArray, mime: String): String
+
+    /**
+     * Verify a downloaded avatar blob against the group's current avatar and decrypt it.
+     * Throws IllegalStateException on a stale or tampered blob.
+     */
+    external fun crdtOpenAvatar(groupIdHex: String, blob: ByteArray, groupSecret: ByteArray, thumbnail: Boolean): ByteArray
+
+    // Address book journal (synced between this account's linked devices)
+
+    /**
+     * Rebuild the address book from the persisted journal (length-prefixed like crdtLoadGroup).
+     * @param devicePubkeys concatenated 32-byte Ed25519 keys of all linked devices, this one included
+     * @return JSON {"contacts": [{"contact_id", "nickname"}], "entries": N, "rejected": M}
+     */
+    external fun crdtLoadAddressBook(devicePubkeys: ByteArray, serializedEntriesBytes: ByteArray): String
+
+    /** Link (after pairing) or unlink a device; false if the address book is not loaded */
+    external fun crdtLinkAddressBookDevice(devicePubkey: ByteArray, linked: Boolean): Boolean
+
+    /**
+     * Record a local change: {"Added": {"contact_id", "nickname"}}, {"Removed": {"contact_id"}}
+     * or {"Renamed": {"contact_id", "nickname"}}. Persist the entry and send it to the other devices.
+     * @return JSON {"entry_bytes_b64", "lamport"}
+     */
+    external fun crdtRecordContactChange(changeJson: String, authorPubkey: ByteArray, authorPrivkey: ByteArray): String
+
+    /** @return JSON {"contacts": [{"contact_id", "nickname"}], "entries": N} */
+    external fun crdtQueryAddressBook(): String
+
+    /** Journal digest to send to another linked device (a new device starts from an empty journal) */
+    external fun crdtAddressBookDigest(): ByteArray
+
+    /** Entries the device with [remoteDigest] lacks, length-prefixed for crdtApplyAddressBookEntries */
+    external fun crdtAddressBookMissingEntries(remoteDigest: ByteArray): ByteArray
+
+    /**
+     * Apply entries from another linked device; invalid ones are skipped and counted.
+     * @return JSON {"applied": N, "rejected": R, "contacts": [...], "entries": M}
+     */
+    external fun crdtApplyAddressBookEntries(serializedEntriesBytes: ByteArray): String
+
     // Sync stubs (Phase 6 — not implemented yet)
     external fun crdtGenerateSyncHello(peerDeviceIdHex: String): ByteArray
     external fun crdtProcessSyncHello(peerDeviceIdHex: String, helloBytes: ByteArray): ByteArray
@@ -1682,12 +1811,493 @@ object RustBridge {
         currentTheirIdentity: ByteArray
     ): String
 
+    /** Canonical contact id (sl_ + 40 hex) for an Ed25519 identity key. Every contactId parameter expects this form. */
+    external fun deriveContactId(ed25519PublicKey: ByteArray): String?
+
+    // ===== Pseudonymous Conversations =====
+
+    /** Random 32-byte tag for a new pseudonymous conversation; store it with the conversation. */
+    external fun generateConversationTag(): ByteArray?
+
+    /** Pseudonym keys for a conversation: [ed25519Pub:32][ed25519Sec:32][x25519Pub:32][x25519Sec:32][kemSeed:32]. */
+    external fun derivePseudonymKeys(seed: ByteArray, tag: ByteArray): ByteArray?
+
+    /** Link proof revealing our master identity behind a pseudonym to one contact. Null on invalid input. */
+    external fun createPseudonymLinkProof(seed: ByteArray, tag: ByteArray, masterPrivateKey: ByteArray, audiencePubkey: ByteArray): ByteArray?
+
+    /** Master Ed25519 key proven by a contact's link proof, or null if invalid or not addressed to us. */
+    external fun verifyPseudonymLinkProof(proof: ByteArray, pseudonymPubkey: ByteArray, ourPubkey: ByteArray): ByteArray?
+
     // ===== Sleep Mode (Tor Push) =====
 
     external fun setSleepModeEnabled(enabled: Boolean)
     external fun setSleepActive(active: Boolean)
     external fun isSleepActive(): Boolean
 
+    // ===== Network Timeout & Retry Policy =====
+
+    /** Set send-path timeouts in seconds. Returns false if any value is not positive. */
+    external fun setTimeoutPolicy(instantPongSecs: Long, messageDeliverySecs: Long, blobSendSecs: Long): Boolean
+
+    /** Set the friend request retry backoff. Delays in ms, jitter in [0.0, 1.0]. */
+    external fun setRetryPolicy(
+        maxAttempts: Int,
+        baseDelayMs: Long,
+        maxDelayMs: Long,
+        multiplier: Double,
+        jitter: Double
+    ): Boolean
+
+    /** Active timeout and retry policies as JSON. */
+    external fun getNetworkPolicyJson(): String
+
+    /** Outbound send queue metrics per priority lane (control, receipt, text, media) as JSON. */
+    external fun getSendLaneMetricsJson(): String
+
+    /** Upstream bandwidth cap counters as JSON (bytes sent and charged, cover packets, media waiting). */
+    external fun getBandwidthStatsJson(): String
+
+    // ===== First-Contact Proof-of-Work =====
+
+    /** Configure inbound PoW stamps for friend requests / first pings. baseDifficulty = 0 disables. */
+    external fun setFirstContactPowPolicy(
+        baseDifficulty: Int,
+        maxDifficulty: Int,
+        surgeThresholdPerMin: Int,
+        memoryKib: Int,
+        iterations: Int
+    )
+
+    /** The PoW advert served at GET /pow-policy, as JSON. */
+    external fun getFirstContactPowAdvert(): String
+
+    /** Exempt a contact's X25519 public key from PoW stamps. */
+    external fun addTrustedSender(x25519PublicKey: ByteArray): Boolean
+    external fun removeTrustedSender(x25519PublicKey: ByteArray): Boolean
+
+    /** Append a PoW stamp to an outgoing first-contact payload (null if no solution found). */
+    external fun attachPowStamp(
+        msgType: Int,
+        payload: ByteArray,
+        difficulty: Int,
+        memoryKib: Int,
+        iterations: Int
+    ): ByteArray?
+
+    // ===== Contact Presence =====
+
+    /** Configure presence sharing (off by default). Beacons are fuzzed by up to jitterSecs. */
+    external fun setPresenceConfig(
+        enabled: Boolean,
+        jitterSecs: Long,
+        minBeaconIntervalSecs: Long,
+        storageGranularitySecs: Long
+    )
+
+    /** Opt a contact in or out of presence sharing. Opting out drops their presence. */
+    external fun setPresenceSharing(contactId: String, sharing: Boolean): Boolean
+
+    /** Record local user activity (app foreground, typing, etc.). */
+    external fun notePresenceActivity()
+
+    /** Plaintext beacon to encrypt and send as type 0x10, or null if none is due. */
+    external fun buildPresenceBeacon(contactId: String): ByteArray?
+
+    /** Store a decrypted beacon received from a contact. */
+    external fun recordPresenceBeacon(contactId: String, beacon: ByteArray): Boolean
+
+    /** 0=active, 1=recent, 2=today, 3=this week, 4=away, -1=unknown. */
+    external fun getContactPresence(contactId: String): Int
+
+    /** Presence of all opted-in contacts as JSON. */
+    external fun getPresenceSnapshotJson(): String
+
+    /** Presence state blob for encrypted persistence, and its restore. */
+    external fun exportPresenceState(): ByteArray?
+    external fun importPresenceState(state: ByteArray): Boolean
+
+    // ===== Key-Change Enforcement =====
+
+    /** Pin a contact's identity key (trust: 0=Untrusted, 1=Encrypted, 2=Verified). Returns the contact id. */
+    external fun registerContactIdentity(ed25519PublicKey: ByteArray, trustLevel: Int): String?
+    external fun setContactTrustLevel(contactId: String, trustLevel: Int): Boolean
+
+    /**
+     * Compare a presented identity key with the pinned one.
+     * 0 = unchanged, 1 = changed (unverified, warn only), 2 = changed and blocked, -1 = error.
+     * While blocked, storePendingRatchetAdvancement throws SecurityException("IDENTITY_KEY_CHANGED").
+     */
+    external fun observeContactIdentity(contactId: String, ed25519PublicKey: ByteArray): Int
+    external fun isSendBlocked(contactId: String): Boolean
+
+    /** Returns true if the message may be shown now, false if it was quarantined. */
+    external fun admitIncomingMessage(contactId: String, data: ByteArray): Boolean
+
+    /** [{"id","receivedAt","data"(base64)}] held while a key change is pending. */
+    external fun getQuarantinedMessagesJson(contactId: String): String?
+
+    /** Accept the new key. Returns {"contactId": new id, "released": [...]}. */
+    external fun approveKeyChange(contactId: String): String?
+    external fun discardQuarantinedMessages(contactId: String): Int
+
+    /** Drop the cached X25519 shared secret for a contact (e.g. on delete). */
+    external fun invalidateSessionKey(contactId: String): Boolean
+
+    /** Identity chain head: genesis of identityKey if previousHead is null, else previousHead advanced to it. */
+    external fun identityChainHead(previousHead: ByteArray?, identityKey: ByteArray): ByteArray?
+
+    /** Set our own chain head; ordered messages then carry its continuity tag. */
+    external fun setLocalIdentityChainHead(head: ByteArray): Boolean
+
+    /** Pin the chain head a registered contact advertised (default: its key's genesis). */
+    external fun pinContactChainHead(contactId: String, head: ByteArray): Boolean
+
+    /** Pinned keys, pending changes and quarantine for encrypted persistence; restore at startup before the first send. */
+    external fun exportKeyChangeState(): ByteArray?
+    external fun importKeyChangeState(state: ByteArray): Boolean
+
+    // ===== Message Ordering =====
+
+    /** Configure the 1:1 reorder buffer: max held sequence numbers and gap timeout. */
+    external fun setOrderingConfig(window: Int, gapTimeoutMs: Long)
+
+    /** Add the conversation sequence number to a 1:1 plaintext. Call before encrypting. */
+    external fun wrapOrderedMessage(contactId: String, plaintext: ByteArray): ByteArray?
+
+    /** wrapOrderedMessage with annotations, a JSON object {"namespace:name": "value"}; '!'-prefixed keys are critical. Null if a key is malformed or the annotations are too large. */
+    external fun wrapAnnotatedMessage(contactId: String, plaintext: ByteArray, annotationsJson: String): ByteArray?
+
+    /** Declare the critical annotation keys this app understands (JSON array); messages with other critical keys are dropped. */
+    external fun setUnderstoodAnnotations(keysJson: String): Boolean
+
+    /**
+     * Feed a decrypted 1:1 plaintext. Returns a JSON array of events to apply in order:
+     * deliver/late {seq, body (base64), annotations} or gap {first, last}. Null if malformed,
+     * if the sender's continuity tag contradicts its pinned identity chain (security event raised),
+     * or if it carries a critical annotation not declared with setUnderstoodAnnotations.
+     */
+    external fun receiveOrderedMessage(contactId: String, plaintext: ByteArray): String?
+
+    /** Give up on timed-out gaps. Same events as receiveOrderedMessage, plus contactId. */
+    external fun pollOrderingTimeouts(): String
+
+    /** Unix ms at which pollOrderingTimeouts next has work, or -1. */
+    external fun getOrderingDeadline(): Long
+
+    /** Drop ordering state for a deleted contact or reset session. */
+    external fun forgetOrderingState(contactId: String)
+
+    /** Ordering state blob for encrypted persistence, and its restore. */
+    external fun exportOrderingState(): ByteArray?
+    external fun importOrderingState(state: ByteArray): Boolean
+
+    // ===== Cipher-Suite Negotiation =====
+
+    /** Suite id to use with a contact (1=classical, 2=hybrid-pq), from their card JSON; -1 if unknown. */
+    external fun negotiateCipherSuite(peerCardJson: String): Int
+
+    /** Check a contact's selected suite against their signed card. False raises a downgrade alarm. */
+    external fun checkCipherSuiteSelection(contactId: String, peerCardJson: String, selectedSuite: Int): Boolean
+
+    /** Drain pending downgrade alarms as a JSON array. */
+    external fun takeDowngradeAlarmsJson(): String
+
+    // ===== Security Events =====
+
+    /** Drain security events as a JSON array: {kind, severity (info|warning|critical), contactId, timestamp, suppressed, ...}. */
+    external fun takeSecurityEventsJson(): String?
+
+    /** Report a message from a contact that failed to decrypt; replay = true if it was a replayed sequence. */
+    external fun reportDecryptionFailure(contactId: String, replay: Boolean)
+
+    /** Tune security event thresholds; values <= 0 keep the current setting. */
+    external fun setSecurityEventPolicy(decryptionFailureThreshold: Int, decryptionFailureWindowSecs: Long, coverPacketsPerMinute: Int, minIntervalSecs: Long)
+
+    // ===== Emergency Wipe =====
+
+    /** Arm the panic wipe; returns a hex confirmation token valid for 60 seconds. */
+    external fun armEmergencyWipe(): String?
+
+    /** Withdraw an armed panic wipe. */
+    external fun disarmEmergencyWipe()
+
+    /** Wipe all core state (throws on a bad or expired token); JSON report {wipedAt, steps:[{step, cleared, error}]}. Delete app databases afterwards. */
+    external fun emergencyWipe(confirmationToken: String): String?
+
+    // ===== Duress PIN Validation =====
+
+    /** Check a proposed duress PIN against the real PIN; JSON {"acceptable", "issues":[{code, severity, message}]}. */
+    external fun validateDuressPin(duressPin: String, realPin: String): String?
+
+    // ===== Network Silence =====
+
+    /** Enter network silence; only [wakeContactId] can end it remotely. Public keys are Ed25519 identity keys. */
+    external fun enterNetworkSilence(wakeContactId: String, wakeContactPublicKey: ByteArray, ownPublicKey: ByteArray): Boolean
+
+    /** End network silence locally. */
+    external fun endNetworkSilence()
+
+    /** Whether network silence is active (a wake message may have ended it). */
+    external fun isNetworkSilent(): Boolean
+
+    /** Send a wake message ending a contact's network silence. There is never a reply. */
+    external fun sendWakeMessage(recipientOnion: String, signingKey: ByteArray, recipientPublicKey: ByteArray): Boolean
+
+    // ===== Receipt Batching =====
+
+    /** Queue a receipt for a received 1:1 message by its ordering sequence number (duplicates too). */
+    external fun recordReceivedSeq(contactId: String, seq: Long)
+
+    /** Piggyback pending receipts on the connection a contact's message arrived on. False if none were sent. */
+    external fun sendAckBatchOnConnection(connectionId: Long, contactId: String, recipientX25519PublicKey: ByteArray): Boolean
+
+    /** Overdue receipts as a JSON array of {contactId, batch (base64)}; send each with sendAckBatch. */
+    external fun pollDueAckBatches(): String
+
+    /** Send one batch to the contact's ACK port. Failed batches are queued again. */
+    external fun sendAckBatch(contactId: String, batch: ByteArray, recipientX25519PublicKey: ByteArray, recipientOnion: String): Boolean
+
+    /** Unix ms at which pollDueAckBatches next has work, or -1. */
+    external fun getAckBatchDeadline(): Long
+
+    /** Decrypt a polled ACK batch (type 0x12): {senderX25519 (hex), ranges: [[first, last], ...]}. */
+    external fun decryptAckBatch(ackWire: ByteArray): String?
+
+    // ===== Message Reactions =====
+
+    /** Set our reaction to a 1:1 message, or remove it with null. Returns plaintext to encrypt and send as type 0x13. */
+    external fun setMessageReaction(contactId: String, messageId: String, emoji: String?): ByteArray?
+
+    /** Apply a decrypted reaction from a contact: {messageId, changed}. Null if malformed. */
+    external fun receiveReaction(contactId: String, plaintext: ByteArray): String?
+
+    /** Reactions for the given message ids (JSON array): [{messageId, counts:[{emoji, count}], local}]. */
+    external fun getReactionsJson(contactId: String, messageIdsJson: String): String?
+
+    /** Drop reactions to a deleted message. */
+    external fun forgetMessageReactions(contactId: String, messageId: String)
+
+    /** Reaction state blob for encrypted persistence, and its restore. */
+    external fun exportReactionState(): ByteArray?
+    external fun importReactionState(state: ByteArray): Boolean
+
+    // ===== Message Recall =====
+
+    /** How long after sending a message can be recalled, in seconds (max 24 h). */
+    external fun setRecallWindow(windowSecs: Int): Boolean
+
+    /** Recall messages [{messageId, sentAt}] sent to a contact. Returns plaintext to send as type 0x18, null if past the window. */
+    external fun requestMessageRecall(contactId: String, messagesJson: String): ByteArray?
+
+    /** Handle a decrypted recall: {kind:"request", messageIds} or {kind:"ack", outcome, verdicts, evidence}. Null if invalid. */
+    external fun receiveRecall(contactId: String, plaintext: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray): String?
+
+    /** Answer a recall request given {messageId: {displayed, sentAt}}: {delete, verdicts, ack}; send ack as type 0x18. */
+    external fun answerMessageRecall(contactId: String, plaintext: ByteArray, statesJson: String, signingKey: ByteArray): String?
+
+    /** True if an arriving message was recalled before it got here; drop it unseen. */
+    external fun isMessageRecalled(contactId: String, messageId: String): Boolean
+
+    // ===== Message IDs =====
+
+    /** New per-contact message ID sequence blob for our Ed25519 key; store it encrypted. */
+    external fun newMessageIdSequence(senderPubkey: ByteArray): ByteArray?
+
+    /** Next message ID: {messageId, sequence (base64)}. Persist the new sequence before sending. */
+    external fun issueMessageId(sequence: ByteArray): String?
+
+    /** Audit a message ID: 0 new, 1 duplicate, 2 collision, -1 invalid input. */
+    external fun auditMessageId(messageId: String, senderPubkey: ByteArray, content: ByteArray): Int
+
+    /** Collisions since the last call (JSON array): [{messageId, firstSender, secondSender, sameSender}]. */
+    external fun takeMessageIdCollisions(): String?
+
+    // ===== Auxiliary RPC =====
+
+    /** Start an RPC call to a contact: {requestId, frame (base64)}; send the frame as type 0x14. Null if rejected. */
+    external fun rpcCall(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, method: String, payload: ByteArray, timeoutSecs: Long): String?
+
+    /** Handle a type 0x14 frame: {kind: reply|respond|request|dropped, ...}. Null if it does not decrypt. */
+    external fun rpcReceive(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, frame: ByteArray): String?
+
+    /** Answer a request handed over by rpcReceive (status 0 ok .. 5 internal). Returns the frame to send. */
+    external fun rpcRespond(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, requestId: String, status: Int, payload: ByteArray): ByteArray?
+
+    // ===== Topics =====
+
+    /** Sign a token letting a contact subscribe to one of our topics until expiresAt (Unix seconds). Deliver it to the contact; null if rejected. */
+    external fun topicGrant(signingKey: ByteArray, subscriberPubkey: ByteArray, topic: String, expiresAt: Long): ByteArray?
+
+    /** Subscribe to a contact's topic with the token it granted. Returns the frame to send as type 0x17, null if the token is invalid. */
+    external fun topicSubscribe(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, token: ByteArray): ByteArray?
+
+    /** Leave a contact's topic. Returns the frame to send, null if not subscribed. */
+    external fun topicUnsubscribe(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, topic: String): ByteArray?
+
+    /** Seal a publication (up to 4 KiB) for one subscriber; call once per topicSubscribers entry. Null if the contact is not subscribed. */
+    external fun topicPublish(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, topic: String, payload: ByteArray): ByteArray?
+
+    /** Handle a type 0x17 frame: {kind: subscribed|unsubscribed|message|dropped, ...}. Null if it does not decrypt or is not allowed. */
+    external fun topicReceive(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, frame: ByteArray): String?
+
+    /** Current subscribers of one of our topics (JSON array of hex identity keys). */
+    external fun topicSubscribers(topic: String): String?
+
+    /** Drop one subscriber of our topic; false if it was not subscribed. */
+    external fun topicRevoke(topic: String, subscriberPubkey: ByteArray): Boolean
+
+    /** Stop publishing a topic; returns how many subscribers were dropped. */
+    external fun topicWithdraw(topic: String): Int
+
+    // ===== File Transfer =====
+
+    /** Offer a file to a contact; blocks while it is hashed and the offer sent, then chunks go out in the background once accepted. Returns the transfer ID, null on failure. Replaces pushing whole files through sendMessageBlob. */
+    external fun fileTransferOffer(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, onionAddress: String, path: String, mime: String): String?
+
+    /** Handle a type 0x15 frame (bytes after type and sender key): {kind: offer, transferId, name, mime, size} for a new offer, {kind: handled} otherwise. Null if rejected. */
+    external fun fileTransferReceive(sharedSecret: ByteArray, ourPubkey: ByteArray, theirPubkey: ByteArray, onionAddress: String, frame: ByteArray): String?
+
+    /** Accept an offer into destPath, continuing after any whole chunks an earlier attempt left there. */
+    external fun fileTransferAccept(transferId: String, destPath: String): Boolean
+
+    external fun fileTransferDecline(transferId: String): Boolean
+
+    /** Cancel in either direction; a partial download is deleted. */
+    external fun fileTransferCancel(transferId: String): Boolean
+
+    /** Retry a paused upload now instead of at the next automatic attempt. */
+    external fun fileTransferResume(transferId: String): Boolean
+
+    /** Unfinished transfers as a JSON array of {transferId, direction, name, mime, size, bytesDone, state, retransmissions}. */
+    external fun getFileTransfers(): String?
+
+    /** Progress updates (one getFileTransfers entry as JSON); finished transfers are reported once with their final state. Null to clear. */
+    external fun setFileTransferCallback(callback: FileTransferCallback?)
+
+    interface FileTransferCallback {
+        fun onFileTransferProgress(json: String)
+    }
+
+    // ===== Relay Federation =====
+
+    /** Verify and add a signed relay descriptor: 1 added, 0 not newer than the known one, -1 invalid. */
+    external fun addRelayDescriptor(descriptor: ByteArray): Int
+
+    /** Relay for the next message (JSON: relayKey, onion, protocolVersion, limits), or null if none can take it now. */
+    external fun selectRelay(messageLen: Int): String?
+
+    /** All known relays (JSON array, same fields as selectRelay). */
+    external fun getRelaysJson(): String?
+
+    /** Stop using a relay, by hex-encoded relay key. */
+    external fun removeRelay(relayKeyHex: String): Boolean
+
+    /** Mix a group fan-out with dummy envelopes and assign relays. dropsJson: [{mailbox (hex), envelope (base64)}] of equal size; decoysJson: other contacts' mailboxes (hex). Returns [{relayKey, onion, mailbox, envelope}] in submission order, "null" if the relays cannot carry it now, null on invalid input. */
+    external fun planGroupFanout(dropsJson: String, decoysJson: String): String?
+
+    /** Pad a group plaintext before sealing; longestLen is the longest plaintext in the fan-out so all envelopes match in size. */
+    external fun padFanoutPlaintext(plaintext: ByteArray, longestLen: Int): ByteArray?
+
+    /** Strip fan-out padding from an opened group envelope. */
+    external fun unpadFanoutPlaintext(padded: ByteArray): ByteArray?
+
+    // ===== Contact Backup =====
+
+    /** Merge the contact snapshot JSON with the relay backup and store it; returns {"snapshot","stored","replicas"} JSON or null. */
+    external fun backupContacts(masterSeed: ByteArray, snapshotJson: String): String?
+
+    /** Fetch the contact backup after reinstall (re-add relay descriptors first); "null" if none, null on failure. */
+    external fun restoreContacts(masterSeed: ByteArray): String?
+
+    /** Delete the contact backup from the relays; returns replicas cleared or -1. */
+    external fun deleteContactBackup(masterSeed: ByteArray): Int
+
+    // ===== SDK Configuration =====
+
+    /** Validate and apply a ShieldConfig file ("toml" or "json") before any network I/O, including the hidden-service ports ([ports], e.g. single_port = true) and the upstream cap ([bandwidth] max_bytes_per_sec). Returns false if invalid. */
+    external fun applyShieldConfig(configText: String, format: String): Boolean
+
+    /** The applied ShieldConfig as JSON; "ports" holds the ports to pass to createHiddenService and the start*Listener calls. */
+    external fun getShieldConfigJson(): String?
+
+    // ===== Crypto Self-Test =====
+
+    /** Run the crypto known-answer tests; JSON report with a "passed" flag per check. Refuse to start on any failure. */
+    external fun runCryptoSelfTest(): String?
+
+    // ===== Crypto Inventory =====
+
+    /** Compiled-in algorithms, parameters, versions and features as JSON, with a build fingerprint for audit logs. */
+    external fun getCryptoInventoryJson(): String?
+
+    // ===== Delivery Diagnostics =====
+
+    /** Drain delivery outcomes as a JSON array; each failure has a "kind" (tor_unreachable, onion_offline, handshake_rejected, recipient_declined, timeout, connection_lost), a "stage" and "retryable". */
+    external fun drainOutboxEventsJson(): String?
+
+    /** Delivery estimate for a contact: {reachability (likely, uncertain, likely_unreachable, tor_not_ready, unknown), probability, expectedLatencyMs, p90LatencyMs, lastFailure, tor, ...}. */
+    external fun getDeliveryEstimateJson(recipientOnion: String): String?
+
+    /** Network health: {tor: {bootstrapPercent, circuitsEstablished}, contactsTracked, recentDelivered, recentFailed, medianHandshakeMs}. */
+    external fun getNetworkHealthJson(): String?
+
+    /** Drop a deleted contact's delivery history. */
+    external fun forgetDeliveryHistory(recipientOnion: String)
+
+    // ===== Message Processing Plugins =====
+
+    /** Run registered message processors on a decrypted payload (inbound after decrypt, outbound before encrypt). JSON: deliver, body (base64), annotations, droppedBy, reason, violations. */
+    external fun processMessagePayload(inbound: Boolean, contactId: String, messageId: String, timestamp: Long, body: ByteArray): String?
+
+    // ===== Inbound Event Queue =====
+
+    /** Route all inbound traffic to one queue instead of the pollIncoming* calls. storePath keeps unacknowledged events across restarts ("" = memory only). */
+    external fun enableInboundQueue(storePath: String): Boolean
+
+    /** Return to the per-type pollIncoming* channels. */
+    external fun disableInboundQueue()
+
+    /** Events after cursor as JSON [{seq, kind, connection_id, received_at_ms, payload (base64)}]; blocks up to timeoutMs. kind: ping, pong, message, call_signaling, tap, ack, friend_request. */
+    external fun pollInboundEvents(cursor: Long, maxEvents: Int, timeoutMs: Long): String?
+
+    /** Forget events up to and including seq once they are handled. */
+    external fun ackInboundEvents(seq: Long)
+
+    // ===== Ping/Pong Session Persistence =====
+
+    /** Keep pending Ping/Pong/ACK sessions in storePath so delayed Pongs and ACKs survive process death, and load what it kept. Call once at startup. Returns JSON {restored, expired, duplicates}, or null on failure. */
+    external fun restorePingPongSessions(storePath: String): String?
+
+    /** Stop writing sessions to the store; sessions already in memory are kept. */
+    external fun detachPingPongSessionStore()
+
+    // ===== Inbound Dispatcher =====
+
+    /** Start the hidden service listener with routing done in Rust: frames are checked once (framing, sender key, replayed pings/pongs/taps/ACKs) and delivered to the existing pollIncoming* calls. Replaces startHiddenServiceListener and the start*Listener calls. */
+    external fun startInboundDispatcher(port: Int): Boolean
+
+    /** Return routing to the per-type channels; the listener keeps running. */
+    external fun stopInboundDispatcher()
+
+    /** Dispatcher counters as JSON: {delivered, unclaimed, duplicates, rejected}. */
+    external fun getInboundDispatcherStats(): String?
+
+    // ===== Hidden Service Health =====
+
+    /** Check every intervalSecs (<= 0 for the 10 min default) that the hidden service from createHiddenService is published and reachable by dialing it over a fresh circuit; re-publishes it when not. Returns false if no hidden service exists yet. */
+    external fun startHiddenServiceHealthMonitor(intervalSecs: Int): Boolean
+
+    external fun stopHiddenServiceHealthMonitor()
+
+    /** Health as JSON: {status, running, descriptorUploads, descriptorFailures, consecutiveFailures, lastCheckMs, lastReachableMs, lastDialMs, lastError, republishCount, republishPending}. status: unknown, tor_not_ready, publishing, reachable, degraded, unreachable, unpublished. */
+    external fun getHiddenServiceHealth(): String?
+
+    /** Run a check now instead of waiting for the interval. */
+    external fun checkHiddenServiceHealthNow()
+
+    /** Re-publish the hidden service descriptor now; needs the monitor running. */
+    external fun requestHiddenServiceRepublish()
+
     // ===== AetherNet Multi-Transport Mesh Networking =====
 
     /** Initialize AetherNet with user's Ed25519 public key and master encryption key. */
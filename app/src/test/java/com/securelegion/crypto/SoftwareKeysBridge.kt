@@ -0,0 +1,29 @@
+package com.securelegion.crypto
+
+/**
+ * Test-only JNI calls that swap the active context's identity keys.
+ *
+ * The native side exists only in libraries built with the `software-keys`
+ * cargo feature, which app builds never enable. Keeping the declarations in
+ * the test source set keeps them out of the app as well.
+ */
+object SoftwareKeysBridge {
+
+    init {
+        // RustBridge's initializer loads the native library
+        check(RustBridge.isLibraryLoaded()) { "libshieldmessenger is not loaded" }
+    }
+
+    /**
+     * Serve the active context's identity keys from [seed] in process memory instead of
+     * KeyManager, using the same derivation as KeyManager. Call before any key-using function.
+     * @param seed Wallet seed (at least 32 bytes, e.g. a 64-byte BIP39 seed)
+     * @return False if the seed is too short
+     */
+    external fun useSoftwareKeys(seed: ByteArray): Boolean
+
+    /**
+     * Go back to reading the active context's keys from KeyManager
+     */
+    external fun useKeyManagerKeys()
+}
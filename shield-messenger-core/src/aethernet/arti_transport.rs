@@ -0,0 +1,308 @@
+//! Arti Transport — AetherNet's Tor transport with Tor embedded in-process.
+//!
+//! Same wire behaviour as [`TorTransport`](super::tor_transport::TorTransport)
+//! (envelopes POSTed to the peer's onion service), but circuits, onion
+//! services and bootstrap come from an [`ArtiRuntime`] instead of a tor
+//! daemon managed by the platform. Each peer gets its own isolation token,
+//! so envelopes to different contacts never share a circuit.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::transport::*;
+use crate::network::arti::IsolationToken;
+use crate::network::arti_runtime::ArtiRuntime;
+use crate::network::tor_runtime::{OnionServiceSpec, TorRuntime};
+use shield_protocol::protocol::ContactId;
+
+/// Onion port peers accept envelopes on.
+const ENVELOPE_PORT: u16 = 8080;
+
+/// Give up on a send after this long.
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Largest HTTP response read back from a peer.
+const MAX_RESPONSE_BYTES: u64 = 16 * 1024;
+
+/// Arti-backed Tor transport for AetherNet.
+pub struct ArtiTransport {
+    runtime: Arc<ArtiRuntime>,
+    /// Tokio runtime the Arti client runs on; the trait methods are sync.
+    handle: tokio::runtime::Handle,
+    local_onion: Mutex<Option<String>>,
+    /// Known peers: Ed25519 pubkey → onion address.
+    peers: Mutex<HashMap<[u8; 32], PeerAddress>>,
+    /// Inbound message queue.
+    inbox: Mutex<Vec<Envelope>>,
+    metrics: Mutex<ArtiMetricsState>,
+    started: AtomicBool,
+}
+
+struct ArtiMetricsState {
+    messages_sent: u64,
+    messages_delivered: u64,
+    send_latencies: Vec<Duration>,
+}
+
+impl ArtiTransport {
+    /// `handle` must belong to a multi-threaded runtime that outlives the
+    /// transport.
+    pub fn new(runtime: Arc<ArtiRuntime>, handle: tokio::runtime::Handle) -> Self {
+        Self {
+            runtime,
+            handle,
+            local_onion: Mutex::new(None),
+            peers: Mutex::new(HashMap::new()),
+            inbox: Mutex::new(Vec::new()),
+            metrics: Mutex::new(ArtiMetricsState {
+                messages_sent: 0,
+                messages_delivered: 0,
+                send_latencies: Vec::new(),
+            }),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Publish the onion service peers deliver to, forwarding to the local
+    /// envelope listener on `local_port`.
+    pub async fn publish(&self, service_id: &str, local_port: u16) -> TransportResult<String> {
+        let spec = OnionServiceSpec {
+            virtual_port: ENVELOPE_PORT,
+            local_port,
+            key_seed: None,
+        };
+        let service = self
+            .runtime
+            .add_onion_service(service_id, &spec)
+            .await
+            .map_err(|e| TransportError::Unavailable(e.to_string()))?;
+        if let Ok(mut addr) = self.local_onion.lock() {
+            *addr = Some(service.onion_address.clone());
+        }
+        Ok(service.onion_address)
+    }
+
+    /// Queue an inbound envelope (called from the local listener).
+    pub fn queue_inbound(&self, envelope: Envelope) {
+        if let Ok(mut inbox) = self.inbox.lock() {
+            inbox.push(envelope);
+        }
+    }
+
+    /// Run `future` on the Arti runtime and wait for it. A scoped thread
+    /// keeps this safe to call from inside a runtime worker too.
+    fn block_on<F>(&self, future: F) -> TransportResult<F::Output>
+    where
+        F: std::future::Future + Send,
+        F::Output: Send,
+    {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.handle.block_on(future))
+                .join()
+                .map_err(|_| TransportError::Internal("send task panicked".into()))
+        })
+    }
+
+    async fn post_envelope(
+        &self,
+        onion_addr: &str,
+        isolation: &IsolationToken,
+        payload: &[u8],
+    ) -> TransportResult<()> {
+        let host = format!("{}.onion", onion_addr.trim_end_matches(".onion"));
+        let mut stream = self
+            .runtime
+            .connect(&host, ENVELOPE_PORT, isolation)
+            .await
+            .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+
+        let headers = format!(
+            "POST /msg HTTP/1.1\r\n\
+             Host: {}\r\n\
+             User-Agent: ShieldMessenger/2.0\r\n\
+             Content-Type: application/octet-stream\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n",
+            host,
+            payload.len()
+        );
+        let io = |e: std::io::Error| TransportError::SendFailed(e.to_string());
+        stream.write_all(headers.as_bytes()).await.map_err(io)?;
+        stream.write_all(payload).await.map_err(io)?;
+        stream.flush().await.map_err(io)?;
+
+        let mut response = Vec::new();
+        (&mut stream)
+            .take(MAX_RESPONSE_BYTES)
+            .read_to_end(&mut response)
+            .await
+            .map_err(io)?;
+        let status = response.split(|b| *b == b' ').nth(1).unwrap_or_default();
+        if !status.starts_with(b"2") {
+            return Err(TransportError::SendFailed(format!(
+                "peer answered {}",
+                String::from_utf8_lossy(status)
+            )));
+        }
+        Ok(())
+    }
+
+    fn avg_latency(&self) -> Duration {
+        match self.metrics.lock() {
+            Ok(m) if !m.send_latencies.is_empty() => {
+                m.send_latencies.iter().sum::<Duration>() / m.send_latencies.len() as u32
+            }
+            _ => Duration::from_millis(800),
+        }
+    }
+}
+
+impl NetworkTransport for ArtiTransport {
+    fn transport_type(&self) -> TransportType {
+        TransportType::Tor
+    }
+
+    fn start(&self) -> TransportResult<()> {
+        let runtime = self.runtime.clone();
+        self.block_on(async move { runtime.bootstrap().await })?
+            .map_err(|e| TransportError::Unavailable(e.to_string()))?;
+        self.started.store(true, Ordering::Relaxed);
+        info!("[AetherNet/Arti] Transport started");
+        Ok(())
+    }
+
+    fn stop(&self) -> TransportResult<()> {
+        self.started.store(false, Ordering::Relaxed);
+        let runtime = self.runtime.clone();
+        self.block_on(async move { runtime.shutdown().await })?
+            .map_err(|e| TransportError::Internal(e.to_string()))?;
+        info!("[AetherNet/Arti] Transport stopped");
+        Ok(())
+    }
+
+    fn is_available(&self) -> bool {
+        self.started.load(Ordering::Relaxed) && self.runtime.bootstrap_progress() >= 100
+    }
+
+    fn send(&self, envelope: &Envelope, route: &Route) -> TransportResult<()> {
+        if !self.is_available() {
+            return Err(TransportError::Unavailable("Arti not bootstrapped".into()));
+        }
+        let onion_addr = &route.destination.transport_addr;
+        if onion_addr.is_empty() {
+            return Err(TransportError::PeerNotFound(
+                "no onion address for peer".into(),
+            ));
+        }
+        let contact_id = ContactId::from_identity_key(&route.destination.public_key)
+            .map_err(|e| TransportError::IdentityError(e.to_string()))?;
+        let isolation = IsolationToken::new(&contact_id);
+        let payload = bincode::serialize(envelope)
+            .map_err(|e| TransportError::SendFailed(format!("serialize: {}", e)))?;
+
+        let start = Instant::now();
+        self.block_on(async {
+            tokio::time::timeout(
+                SEND_TIMEOUT,
+                self.post_envelope(onion_addr, &isolation, &payload),
+            )
+            .await
+            .map_err(|_| TransportError::Timeout(SEND_TIMEOUT))?
+        })??;
+        let elapsed = start.elapsed();
+
+        if let Ok(mut m) = self.metrics.lock() {
+            m.messages_sent += 1;
+            m.messages_delivered += 1;
+            m.send_latencies.push(elapsed);
+            if m.send_latencies.len() > 100 {
+                m.send_latencies.remove(0);
+            }
+        }
+        debug!(
+            "[AetherNet/Arti] Sent envelope {} to {} in {:?}",
+            envelope.id, onion_addr, elapsed
+        );
+        Ok(())
+    }
+
+    fn receive(&self, max_count: usize) -> TransportResult<Vec<Envelope>> {
+        if let Ok(mut inbox) = self.inbox.lock() {
+            let count = max_count.min(inbox.len());
+            Ok(inbox.drain(..count).collect())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn local_address(&self) -> TransportResult<String> {
+        self.local_onion
+            .lock()
+            .map_err(|_| TransportError::Internal("lock poisoned".into()))?
+            .clone()
+            .ok_or_else(|| TransportError::Unavailable("onion service not published".into()))
+    }
+
+    fn metrics(&self) -> TransportMetrics {
+        let (sent, delivered) = self
+            .metrics
+            .lock()
+            .map(|m| (m.messages_sent, m.messages_delivered))
+            .unwrap_or((0, 0));
+        let available = self.is_available();
+
+        TransportMetrics {
+            transport_type: TransportType::Tor,
+            available,
+            latency: self.avg_latency(),
+            bandwidth_bps: 500_000,
+            reliability: if sent > 0 {
+                delivered as f64 / sent as f64
+            } else {
+                0.0
+            },
+            anonymity_score: 0.95,
+            // No separate tor process, but circuits still cost the same
+            battery_cost: 0.4,
+            active_connections: if available { 1 } else { 0 },
+            messages_sent: sent,
+            messages_delivered: delivered,
+            last_updated: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+
+    fn resolve_peer(&self, pubkey: &[u8; 32]) -> TransportResult<PeerAddress> {
+        self.peers
+            .lock()
+            .map_err(|_| TransportError::Internal("lock poisoned".into()))?
+            .get(pubkey)
+            .cloned()
+            .ok_or_else(|| TransportError::PeerNotFound("unknown peer".into()))
+    }
+
+    fn register_peer(&self, address: PeerAddress) -> TransportResult<()> {
+        self.peers
+            .lock()
+            .map_err(|_| TransportError::Internal("lock poisoned".into()))?
+            .insert(address.public_key, address);
+        Ok(())
+    }
+
+    fn peer_latency(&self, pubkey: &[u8; 32]) -> Option<Duration> {
+        if self.peers.lock().ok()?.contains_key(pubkey) {
+            Some(self.avg_latency())
+        } else {
+            None
+        }
+    }
+}
@@ -3,6 +3,9 @@
 //! Connects to a local I2P router's SAM (Simple Anonymous Messaging) protocol
 //! for garlic-routed, end-to-end encrypted message delivery.
 //! Optimized for bulk transfers and high-bandwidth scenarios.
+//!
+//! A SAM bridge on another host can be reached through an outbound proxy
+//! ([`I2PTransport::with_proxy`]).
 
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
@@ -14,6 +17,7 @@ use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use log::{debug, info, warn};
 
 use super::transport::*;
+use crate::network::proxy::{ProxyConfig, ProxyError};
 
 /// I2P SAM protocol version.
 const SAM_VERSION: &str = "3.1";
@@ -21,6 +25,8 @@ const SAM_VERSION: &str = "3.1";
 const DEFAULT_SAM_ADDR: &str = "127.0.0.1:7656";
 /// Session style for datagram messaging.
 const SESSION_STYLE: &str = "DATAGRAM";
+/// Give up connecting to the SAM bridge after this long.
+const SAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// I2P Transport implementation for AetherNet.
 ///
@@ -30,6 +36,8 @@ const SESSION_STYLE: &str = "DATAGRAM";
 pub struct I2PTransport {
     /// SAM bridge address.
     sam_addr: String,
+    /// How connections to the SAM bridge are made.
+    proxy: ProxyConfig,
     /// SAM session ID.
     session_id: Mutex<Option<String>>,
     /// Local I2P destination (base64).
@@ -55,6 +63,7 @@ impl I2PTransport {
     pub fn new(sam_addr: Option<&str>) -> Self {
         Self {
             sam_addr: sam_addr.unwrap_or(DEFAULT_SAM_ADDR).to_string(),
+            proxy: ProxyConfig::Direct,
             session_id: Mutex::new(None),
             local_destination: Mutex::new(None),
             peers: Mutex::new(HashMap::new()),
@@ -69,6 +78,12 @@ impl I2PTransport {
         }
     }
 
+    /// Reach the SAM bridge through `proxy` instead of connecting directly.
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
     /// Queue an inbound envelope from the SAM datagram listener.
     pub fn queue_inbound(&self, envelope: Envelope) {
         if let Ok(mut inbox) = self.inbox.lock() {
@@ -78,7 +93,8 @@ impl I2PTransport {
 
     /// Perform SAM handshake and create a session.
     fn sam_handshake(&self) -> TransportResult<()> {
-        let stream = TcpStream::connect(&self.sam_addr)
+        let stream = self
+            .connect_sam()
             .map_err(|e| TransportError::ConnectionFailed(format!("SAM connect: {}", e)))?;
         stream.set_read_timeout(Some(Duration::from_secs(10))).ok();
 
@@ -145,6 +161,10 @@ impl I2PTransport {
         Ok(())
     }
 
+    fn connect_sam(&self) -> Result<TcpStream, ProxyError> {
+        self.proxy.connect_addr(&self.sam_addr, SAM_CONNECT_TIMEOUT)
+    }
+
     fn avg_latency_ms(&self) -> u64 {
         let count = self.latency_count.load(Ordering::Relaxed);
         if count == 0 {
@@ -238,7 +258,8 @@ impl NetworkTransport for I2PTransport {
             .map_err(|e| TransportError::SendFailed(format!("serialize: {}", e)))?;
 
         // Open a data connection to SAM for sending
-        let data_stream = TcpStream::connect(&self.sam_addr)
+        let data_stream = self
+            .connect_sam()
             .map_err(|e| TransportError::SendFailed(format!("data connect: {}", e)))?;
         data_stream
             .set_write_timeout(Some(Duration::from_secs(30)))
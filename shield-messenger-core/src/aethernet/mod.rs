@@ -19,6 +19,8 @@
 //! └─────────────────────────────────────────────────┘
 //! ```
 
+#[cfg(feature = "arti")]
+pub mod arti_transport;
 pub mod crisis;
 pub mod crowd_mesh;
 pub mod i2p_transport;
@@ -32,6 +34,8 @@ pub mod transport;
 pub mod trust_map;
 
 // ── Re-exports ──────────────────────────────────────────────────────────────
+#[cfg(feature = "arti")]
+pub use arti_transport::ArtiTransport;
 pub use crisis::{CrisisConfig, CrisisController, CrisisTrigger};
 pub use crowd_mesh::{Cluster, CrowdMesh, CrowdPeer, EpidemicMessage};
 pub use i2p_transport::I2PTransport;
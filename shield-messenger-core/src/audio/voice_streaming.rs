@@ -1,7 +1,7 @@
 /// Voice Streaming Listener for ShieldMessenger (v2.0)
 ///
 /// Handles bidirectional voice streaming over Tor hidden services
-/// - Listens on port 9152 (voice .onion address)
+/// - Listens on the voice port (9152 by default, see `network::ports`)
 /// - Uses Opus codec for audio compression
 /// - P2P architecture: each device runs a listener, peers connect directly
 /// - Real-time audio packet streaming with encryption
@@ -106,7 +106,7 @@ impl VoiceSession {
 }
 
 /// Voice Streaming Listener
-/// Listens on port 9152 for incoming voice connections
+/// Listens on the local voice port for incoming voice connections
 /// Handles BOTH call signaling (HTTP POST) and audio streaming (raw TCP)
 pub struct VoiceStreamingListener {
     /// TCP listener
@@ -131,11 +131,16 @@ impl VoiceStreamingListener {
         }
     }
 
-    /// Start listener on port 9152
+    /// Start listener on the local voice port
     /// This also spawns a background task to accept incoming connections
     pub async fn start(&mut self) -> Result<(), Box<dyn Error>> {
-        let listener = TcpListener::bind("127.0.0.1:9152").await?;
-        log::info!("Voice streaming listener started on 127.0.0.1:9152");
+        let port = crate::network::ports().local_voice;
+        let listen = crate::network::listen::listen_config();
+        let listener = listen.bind(port).await?;
+        log::info!(
+            "Voice streaming listener started on {}",
+            listen.socket_addr(port)
+        );
 
         // Store listener temporarily
         self.listener = Some(listener);
@@ -285,12 +290,14 @@ impl VoiceStreamingListener {
                 circuit_index,
                 peer_onion
             );
+            let voice_port = crate::network::ports().voice;
             log::info!(
-                "[VOICE_DEBUG] About to call connect_via_socks5({}:9152)",
-                peer_onion
+                "[VOICE_DEBUG] About to call connect_via_socks5({}:{})",
+                peer_onion,
+                voice_port
             );
             let mut stream =
-                connect_via_socks5(peer_onion, 9152, circuit_index, &call_id, 0).await?;
+                connect_via_socks5(peer_onion, voice_port, circuit_index, &call_id, 0).await?;
             log::info!(
                 "[VOICE_DEBUG] connect_via_socks5 returned successfully for circuit {}",
                 circuit_index
@@ -444,8 +451,15 @@ impl VoiceStreamingListener {
             peer_onion,
             rebuild_epoch
         );
-        let mut stream =
-            connect_via_socks5(&peer_onion, 9152, circuit_index, call_id, rebuild_epoch).await?;
+        let voice_port = crate::network::ports().voice;
+        let mut stream = connect_via_socks5(
+            &peer_onion,
+            voice_port,
+            circuit_index,
+            call_id,
+            rebuild_epoch,
+        )
+        .await?;
 
         // Disable Nagle's algorithm for real-time audio
         if let Err(e) = stream.set_nodelay(true) {
@@ -0,0 +1,642 @@
+//! SDK Configuration
+//!
+//! `ShieldConfig` collects the protocol parameters an integrator is expected
+//! to tune: packet size, traffic shaping profile, timeout and retry policies,
+//! hidden-service ports, the listener bind address, the upstream bandwidth cap, the default security
+//! mode and feature toggles. Instead of calling `set_fixed_packet_size`,
+//! `set_timeout_policy` and friends one by one, an app ships one config file
+//! (the same on Android, iOS and desktop) and applies it at startup:
+//!
+//! ```toml
+//! packet_size = 8192
+//! security_mode = "Relay"
+//!
+//! [traffic]
+//! profile = "MaxPrivacy"
+//!
+//! [retry]
+//! max_attempts = 20
+//!
+//! [ports]
+//! single_port = true
+//! messaging = 443
+//!
+//! [listen]
+//! address = "::1"
+//!
+//! [bandwidth]
+//! max_bytes_per_sec = 65536
+//!
+//! [features]
+//! presence = false
+//! ```
+//!
+//! Omitted keys keep their defaults. Configs can also be built in code with
+//! `ShieldConfig::builder()`. Every path goes through `validate`, and `apply`
+//! pushes the values into the process-wide settings; the parts with no
+//! global of their own (traffic profile, security mode, features) are read
+//! back through `current()`.
+
+use crate::network::bandwidth;
+use crate::network::listen::{set_listen_config, ListenConfig, ListenError};
+use crate::network::ports::{set_ports, PortConfig, PortError};
+use crate::network::retry_policy::{
+    set_retry_policy, set_timeout_policy, PolicyError, RetryPolicy, TimeoutPolicy,
+};
+use crate::protocol::SecurityMode;
+use crate::transport::bandwidth::BandwidthConfig;
+use crate::transport::padding::{
+    set_fixed_packet_size, BurstPaddingConfig, TrafficProfile, DEFAULT_PACKET_SIZE,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid TOML config: {0}")]
+    Toml(String),
+
+    #[error("Invalid JSON config: {0}")]
+    Json(String),
+
+    #[error("Unknown config file format (expected .toml or .json): {0}")]
+    UnknownFormat(String),
+
+    #[error("packet_size must be 4096, 8192 or 16384, got {0}")]
+    PacketSize(usize),
+
+    #[error("Invalid traffic settings: {0}")]
+    Traffic(&'static str),
+
+    #[error("Invalid bandwidth settings: {0}")]
+    Bandwidth(&'static str),
+
+    #[error(transparent)]
+    Policy(#[from] PolicyError),
+
+    #[error(transparent)]
+    Ports(#[from] PortError),
+
+    #[error(transparent)]
+    Listen(#[from] ListenError),
+}
+
+/// Named traffic shaping profile (see `TrafficProfile`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum TrafficPreset {
+    LowLatency,
+    #[default]
+    Balanced,
+    MaxPrivacy,
+    /// Parameters from `[traffic.custom]`
+    Custom,
+}
+
+/// Parameters of a custom traffic profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomTraffic {
+    pub cover_interval_min_secs: u64,
+    pub cover_interval_max_secs: u64,
+    pub delay_min_ms: u64,
+    pub delay_max_ms: u64,
+    pub burst_padding: bool,
+    pub pre_burst_count: u8,
+    pub post_burst_count: u8,
+    pub burst_delay_min_ms: u64,
+    pub burst_delay_max_ms: u64,
+}
+
+impl Default for CustomTraffic {
+    /// The `Balanced` profile's values
+    fn default() -> Self {
+        let balanced = TrafficProfile::Balanced;
+        let (cover_min, cover_max) = balanced.cover_interval_range();
+        let (delay_min, delay_max) = balanced.delay_range_ms();
+        let burst = balanced.burst_config();
+        Self {
+            cover_interval_min_secs: cover_min,
+            cover_interval_max_secs: cover_max,
+            delay_min_ms: delay_min,
+            delay_max_ms: delay_max,
+            burst_padding: burst.enabled,
+            pre_burst_count: burst.pre_burst_count,
+            post_burst_count: burst.post_burst_count,
+            burst_delay_min_ms: burst.inter_packet_delay_min_ms,
+            burst_delay_max_ms: burst.inter_packet_delay_max_ms,
+        }
+    }
+}
+
+/// `[traffic]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrafficSettings {
+    pub profile: TrafficPreset,
+    /// Required when `profile` is `Custom`, ignored otherwise
+    pub custom: Option<CustomTraffic>,
+}
+
+/// `[timeouts]`, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutSettings {
+    pub instant_pong_ms: u64,
+    pub message_delivery_ms: u64,
+    pub blob_send_ms: u64,
+}
+
+impl Default for TimeoutSettings {
+    fn default() -> Self {
+        TimeoutPolicy::DEFAULT.into()
+    }
+}
+
+impl From<TimeoutPolicy> for TimeoutSettings {
+    fn from(policy: TimeoutPolicy) -> Self {
+        Self {
+            instant_pong_ms: policy.instant_pong.as_millis() as u64,
+            message_delivery_ms: policy.message_delivery.as_millis() as u64,
+            blob_send_ms: policy.blob_send.as_millis() as u64,
+        }
+    }
+}
+
+/// `[retry]`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySettings {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetryPolicy::DEFAULT.into()
+    }
+}
+
+impl From<RetryPolicy> for RetrySettings {
+    fn from(policy: RetryPolicy) -> Self {
+        Self {
+            max_attempts: policy.max_attempts,
+            base_delay_ms: policy.base_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+            multiplier: policy.multiplier,
+            jitter: policy.jitter,
+        }
+    }
+}
+
+/// `[bandwidth]`: upstream cap; `max_bytes_per_sec = 0` means unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BandwidthSettings {
+    pub max_bytes_per_sec: u64,
+    pub burst_bytes: u64,
+    /// Bytes each conversation may send per turn
+    pub quantum_bytes: u64,
+    /// Longest run of cover packets after a conversation's media drains
+    pub cover_tail_secs: u64,
+}
+
+impl Default for BandwidthSettings {
+    fn default() -> Self {
+        BandwidthConfig::UNLIMITED.into()
+    }
+}
+
+impl From<BandwidthConfig> for BandwidthSettings {
+    fn from(config: BandwidthConfig) -> Self {
+        Self {
+            max_bytes_per_sec: config.max_bytes_per_sec,
+            burst_bytes: config.burst_bytes,
+            quantum_bytes: config.quantum_bytes as u64,
+            cover_tail_secs: config.max_cover_tail.as_secs(),
+        }
+    }
+}
+
+/// `[features]`: optional behaviour the app can switch off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureToggles {
+    /// Periodic cover packets between real traffic
+    pub cover_traffic: bool,
+    /// Coarse presence beacons to contacts
+    pub presence: bool,
+    /// Batched delivery receipts instead of one ACK per message
+    pub receipt_batching: bool,
+    /// Coalescing small outgoing messages into one packet
+    pub message_coalescing: bool,
+}
+
+impl Default for FeatureToggles {
+    fn default() -> Self {
+        Self {
+            cover_traffic: true,
+            presence: true,
+            receipt_batching: true,
+            message_coalescing: true,
+        }
+    }
+}
+
+/// Declarative SDK configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShieldConfig {
+    /// Fixed padded packet size in bytes
+    pub packet_size: usize,
+    /// Security mode for contacts that do not set their own
+    pub security_mode: SecurityMode,
+    pub traffic: TrafficSettings,
+    pub timeouts: TimeoutSettings,
+    pub retry: RetrySettings,
+    pub ports: PortConfig,
+    pub listen: ListenConfig,
+    pub bandwidth: BandwidthSettings,
+    pub features: FeatureToggles,
+}
+
+impl Default for ShieldConfig {
+    fn default() -> Self {
+        Self {
+            packet_size: DEFAULT_PACKET_SIZE,
+            security_mode: SecurityMode::default(),
+            traffic: TrafficSettings::default(),
+            timeouts: TimeoutSettings::default(),
+            retry: RetrySettings::default(),
+            ports: PortConfig::default(),
+            listen: ListenConfig::default(),
+            bandwidth: BandwidthSettings::default(),
+            features: FeatureToggles::default(),
+        }
+    }
+}
+
+impl ShieldConfig {
+    pub fn builder() -> ShieldConfigBuilder {
+        ShieldConfigBuilder::default()
+    }
+
+    /// Parse and validate a TOML config
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(text).map_err(|e| ConfigError::Toml(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse and validate a JSON config
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_json::from_str(text).map_err(|e| ConfigError::Json(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a `.toml` or `.json` config file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(ConfigError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string_pretty(self).map_err(|e| ConfigError::Toml(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String, ConfigError> {
+        serde_json::to_string_pretty(self).map_err(|e| ConfigError::Json(e.to_string()))
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !matches!(self.packet_size, 4096 | 8192 | 16384) {
+            return Err(ConfigError::PacketSize(self.packet_size));
+        }
+        if self.traffic.profile == TrafficPreset::Custom {
+            let custom = self.traffic.custom.ok_or(ConfigError::Traffic(
+                "custom profile without [traffic.custom]",
+            ))?;
+            if custom.cover_interval_min_secs == 0
+                || custom.cover_interval_min_secs > custom.cover_interval_max_secs
+            {
+                return Err(ConfigError::Traffic("cover interval range"));
+            }
+            if custom.delay_min_ms > custom.delay_max_ms {
+                return Err(ConfigError::Traffic("delay range"));
+            }
+            if custom.burst_delay_min_ms > custom.burst_delay_max_ms {
+                return Err(ConfigError::Traffic("burst delay range"));
+            }
+        }
+        self.timeout_policy().validate()?;
+        self.retry_policy().validate()?;
+        self.ports.validate()?;
+        self.listen.validate()?;
+        if self.bandwidth.burst_bytes == 0 {
+            return Err(ConfigError::Bandwidth("burst_bytes must be positive"));
+        }
+        if self.bandwidth.quantum_bytes == 0 {
+            return Err(ConfigError::Bandwidth("quantum_bytes must be positive"));
+        }
+        Ok(())
+    }
+
+    pub fn traffic_profile(&self) -> TrafficProfile {
+        match (self.traffic.profile, self.traffic.custom) {
+            (TrafficPreset::LowLatency, _) => TrafficProfile::LowLatency,
+            (TrafficPreset::MaxPrivacy, _) => TrafficProfile::MaxPrivacy,
+            (TrafficPreset::Custom, Some(custom)) => TrafficProfile::Custom {
+                cover_interval_min_secs: custom.cover_interval_min_secs,
+                cover_interval_max_secs: custom.cover_interval_max_secs,
+                delay_min_ms: custom.delay_min_ms,
+                delay_max_ms: custom.delay_max_ms,
+                burst_config: BurstPaddingConfig {
+                    pre_burst_count: custom.pre_burst_count,
+                    post_burst_count: custom.post_burst_count,
+                    inter_packet_delay_min_ms: custom.burst_delay_min_ms,
+                    inter_packet_delay_max_ms: custom.burst_delay_max_ms,
+                    enabled: custom.burst_padding,
+                },
+            },
+            _ => TrafficProfile::Balanced,
+        }
+    }
+
+    pub fn timeout_policy(&self) -> TimeoutPolicy {
+        TimeoutPolicy {
+            instant_pong: Duration::from_millis(self.timeouts.instant_pong_ms),
+            message_delivery: Duration::from_millis(self.timeouts.message_delivery_ms),
+            blob_send: Duration::from_millis(self.timeouts.blob_send_ms),
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry.max_attempts,
+            base_delay: Duration::from_millis(self.retry.base_delay_ms),
+            max_delay: Duration::from_millis(self.retry.max_delay_ms),
+            multiplier: self.retry.multiplier,
+            jitter: self.retry.jitter,
+        }
+    }
+
+    pub fn bandwidth_config(&self) -> BandwidthConfig {
+        BandwidthConfig {
+            max_bytes_per_sec: self.bandwidth.max_bytes_per_sec,
+            burst_bytes: self.bandwidth.burst_bytes,
+            quantum_bytes: self.bandwidth.quantum_bytes as usize,
+            max_cover_tail: Duration::from_secs(self.bandwidth.cover_tail_secs),
+        }
+    }
+
+    /// Validate and install this config process-wide. Call before any I/O:
+    /// the packet size must not change while packets are in flight.
+    pub fn apply(&self) -> Result<(), ConfigError> {
+        self.validate()?;
+        set_fixed_packet_size(self.packet_size);
+        set_timeout_policy(self.timeout_policy())?;
+        set_retry_policy(self.retry_policy())?;
+        set_ports(self.ports)?;
+        set_listen_config(self.listen)?;
+        bandwidth::set_config(self.bandwidth_config());
+        *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = self.clone();
+        log::info!(
+            "Shield config applied: packet_size={} profile={:?} security_mode={:?}",
+            self.packet_size,
+            self.traffic.profile,
+            self.security_mode
+        );
+        Ok(())
+    }
+}
+
+/// Builds a `ShieldConfig` in code, starting from the defaults
+#[derive(Debug, Clone, Default)]
+pub struct ShieldConfigBuilder {
+    config: ShieldConfig,
+}
+
+impl ShieldConfigBuilder {
+    pub fn packet_size(mut self, size: usize) -> Self {
+        self.config.packet_size = size;
+        self
+    }
+
+    pub fn security_mode(mut self, mode: SecurityMode) -> Self {
+        self.config.security_mode = mode;
+        self
+    }
+
+    pub fn traffic_profile(mut self, profile: TrafficProfile) -> Self {
+        self.config.traffic = match profile {
+            TrafficProfile::LowLatency => TrafficSettings {
+                profile: TrafficPreset::LowLatency,
+                custom: None,
+            },
+            TrafficProfile::Balanced => TrafficSettings::default(),
+            TrafficProfile::MaxPrivacy => TrafficSettings {
+                profile: TrafficPreset::MaxPrivacy,
+                custom: None,
+            },
+            TrafficProfile::Custom {
+                cover_interval_min_secs,
+                cover_interval_max_secs,
+                delay_min_ms,
+                delay_max_ms,
+                burst_config,
+            } => TrafficSettings {
+                profile: TrafficPreset::Custom,
+                custom: Some(CustomTraffic {
+                    cover_interval_min_secs,
+                    cover_interval_max_secs,
+                    delay_min_ms,
+                    delay_max_ms,
+                    burst_padding: burst_config.enabled,
+                    pre_burst_count: burst_config.pre_burst_count,
+                    post_burst_count: burst_config.post_burst_count,
+                    burst_delay_min_ms: burst_config.inter_packet_delay_min_ms,
+                    burst_delay_max_ms: burst_config.inter_packet_delay_max_ms,
+                }),
+            },
+        };
+        self
+    }
+
+    pub fn timeouts(mut self, policy: TimeoutPolicy) -> Self {
+        self.config.timeouts = policy.into();
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy.into();
+        self
+    }
+
+    pub fn ports(mut self, ports: PortConfig) -> Self {
+        self.config.ports = ports;
+        self
+    }
+
+    pub fn listen(mut self, listen: ListenConfig) -> Self {
+        self.config.listen = listen;
+        self
+    }
+
+    pub fn bandwidth(mut self, config: BandwidthConfig) -> Self {
+        self.config.bandwidth = config.into();
+        self
+    }
+
+    pub fn features(mut self, features: FeatureToggles) -> Self {
+        self.config.features = features;
+        self
+    }
+
+    pub fn build(self) -> Result<ShieldConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+static CURRENT: Lazy<RwLock<ShieldConfig>> = Lazy::new(|| RwLock::new(ShieldConfig::default()));
+
+/// The config last applied (defaults until `apply` is called)
+pub fn current() -> ShieldConfig {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_toml_keeps_defaults() {
+        let config = ShieldConfig::from_toml(
+            r#"
+            packet_size = 8192
+            security_mode = "Relay"
+
+            [traffic]
+            profile = "Custom"
+            custom = { cover_interval_min_secs = 5, cover_interval_max_secs = 15 }
+
+            [retry]
+            max_attempts = 20
+
+            [ports]
+            single_port = true
+            messaging = 443
+
+            [listen]
+            address = "::1"
+
+            [bandwidth]
+            max_bytes_per_sec = 65536
+
+            [features]
+            presence = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.packet_size, 8192);
+        assert_eq!(config.security_mode, SecurityMode::Relay);
+        assert_eq!(config.timeout_policy(), TimeoutPolicy::DEFAULT);
+        assert_eq!(config.retry_policy().max_attempts, 20);
+        assert_eq!(
+            config.retry_policy().base_delay,
+            RetryPolicy::DEFAULT.base_delay
+        );
+        assert!(!config.features.presence && config.features.cover_traffic);
+        assert_eq!(config.ports, PortConfig::single(443));
+        assert_eq!(config.listen, ListenConfig::IPV6);
+        assert_eq!(config.bandwidth_config(), BandwidthConfig::limited(65536));
+        assert_eq!(config.traffic_profile().cover_interval_range(), (5, 15));
+        assert_eq!(
+            config.traffic_profile().delay_range_ms(),
+            TrafficProfile::Balanced.delay_range_ms()
+        );
+
+        // Both formats round-trip
+        assert_eq!(
+            ShieldConfig::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
+        assert_eq!(
+            ShieldConfig::from_json(&config.to_json().unwrap()).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_invalid_configs_rejected() {
+        assert!(matches!(
+            ShieldConfig::from_json(r#"{"packet_size": 5000}"#),
+            Err(ConfigError::PacketSize(5000))
+        ));
+        assert!(matches!(
+            ShieldConfig::from_json(r#"{"traffic": {"profile": "Custom"}}"#),
+            Err(ConfigError::Traffic(_))
+        ));
+        assert!(matches!(
+            ShieldConfig::from_toml("[bandwidth]\nburst_bytes = 0"),
+            Err(ConfigError::Bandwidth(_))
+        ));
+        assert!(matches!(
+            ShieldConfig::from_toml("[retry]\njitter = 2.0"),
+            Err(ConfigError::Policy(PolicyError::InvalidJitter(_)))
+        ));
+        assert!(matches!(
+            ShieldConfig::from_toml("[listen]\naddress = \"0.0.0.0\""),
+            Err(ConfigError::Listen(ListenError::Exposed(_)))
+        ));
+        // Typos are errors, not silently ignored keys
+        assert!(matches!(
+            ShieldConfig::from_toml("packet_sise = 8192"),
+            Err(ConfigError::Toml(_))
+        ));
+
+        let built = ShieldConfig::builder()
+            .packet_size(16384)
+            .traffic_profile(TrafficProfile::MaxPrivacy)
+            .timeouts(TimeoutPolicy {
+                blob_send: Duration::from_secs(300),
+                ..TimeoutPolicy::DEFAULT
+            })
+            .build()
+            .unwrap();
+        assert_eq!(built.traffic.profile, TrafficPreset::MaxPrivacy);
+        assert_eq!(built.timeout_policy().blob_send, Duration::from_secs(300));
+        assert!(matches!(
+            ShieldConfig::builder()
+                .ports(PortConfig {
+                    ack: PortConfig::DEFAULT.messaging,
+                    ..PortConfig::DEFAULT
+                })
+                .build(),
+            Err(ConfigError::Ports(PortError::Conflict(..)))
+        ));
+        assert!(ShieldConfig::builder()
+            .retry(RetryPolicy {
+                max_attempts: 0,
+                ..RetryPolicy::DEFAULT
+            })
+            .build()
+            .is_err());
+    }
+}
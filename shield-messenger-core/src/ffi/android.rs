@@ -1,6 +1,7 @@
 use jni::objects::{GlobalRef, JByteArray, JClass, JObject, JString};
 use jni::sys::{
-    jboolean, jbyte, jbyteArray, jint, jlong, jobjectArray, jstring, JNI_FALSE, JNI_TRUE,
+    jboolean, jbyte, jbyteArray, jdouble, jint, jlong, jobjectArray, jstring, JNI_FALSE,
+    JNI_TRUE,
 };
 use jni::JNIEnv;
 use once_cell::sync::{Lazy, OnceCell};
@@ -17,15 +18,21 @@ use crate::crypto::{
     encrypt_message_with_evolution, evolve_chain_key, generate_keypair, sign_data,
     verify_signature,
 };
+use crate::ffi::context::{
+    activate_context, active_context, active_handle, create_context, destroy_context,
+    PairReceiver, VecReceiver,
+};
+use crate::ffi::handles::INVALID_HANDLE;
+#[cfg(feature = "software-keys")]
+use crate::ffi::software_keys::SoftwareKeys;
 use crate::network::{TorManager, PENDING_CONNECTIONS};
+use crate::redact;
 use tokio::io::AsyncReadExt;
 use tokio::sync::mpsc;
 
-// ==================== PORT CONSTANTS (Single Source of Truth) ====================
-// These must match Kotlin side constants!
-const MAIN_LISTENER_PORT: u16 = 8080; // Main multiplexed listener (all message types: ping, pong, message, ack)
-const PING_PONG_HANDSHAKE_PORT: u16 = 9150; // Ping/Pong handshake port (used in sendPongToNewConnection)
-                                            // NOTE: Voice streaming uses separate ports defined in VoiceStreamingListener
+// ==================== PORTS ====================
+// Hidden-service and listener ports come from crate::network::ports(), set by
+// applyShieldConfig ([ports]); Kotlin reads them back from getShieldConfigJson.
 
 // ==================== STRESS TEST & DEBUG METRICS ====================
 // Thread-safe counters for diagnosing SOCKS timeout and MESSAGE_TX initialization race
@@ -93,6 +100,15 @@ fn jstring_to_string(env: &mut JNIEnv, string: JString) -> Result<String, String
         .map_err(|e| format!("Failed to convert string: {}", e))
 }
 
+/// Convert Java String to a canonical contact id (`sl_…`, see deriveContactId)
+fn jstring_to_contact_id(
+    env: &mut JNIEnv,
+    string: JString,
+) -> Result<crate::protocol::ContactId, String> {
+    let s = jstring_to_string(env, string)?;
+    crate::protocol::ContactId::parse(&s).map_err(|e| format!("Invalid contact id: {}", e))
+}
+
 /// Convert Rust String to Java String
 fn string_to_jstring<'a>(env: &mut JNIEnv<'a>, string: &str) -> Result<JString<'a>, String> {
     env.new_string(string)
@@ -122,6 +138,7 @@ fn is_valid_message_type(msg_type: u8) -> bool {
             | crate::network::tor::MSG_TYPE_VOICE
             | crate::network::tor::MSG_TYPE_TAP
             | crate::network::tor::MSG_TYPE_DELIVERY_CONFIRMATION
+            | crate::network::tor::MSG_TYPE_ACK_BATCH
             | crate::network::tor::MSG_TYPE_FRIEND_REQUEST
             | crate::network::tor::MSG_TYPE_FRIEND_REQUEST_ACCEPTED
             | crate::network::tor::MSG_TYPE_IMAGE
@@ -131,6 +148,12 @@ fn is_valid_message_type(msg_type: u8) -> bool {
             | crate::network::tor::MSG_TYPE_CALL_SIGNALING
             | crate::network::tor::MSG_TYPE_STICKER
             | crate::network::tor::MSG_TYPE_PROFILE_UPDATE
+            | crate::network::tor::MSG_TYPE_PRESENCE
+            | crate::network::tor::MSG_TYPE_REACTION
+            | crate::network::tor::MSG_TYPE_RPC
+            | crate::network::tor::MSG_TYPE_TOPIC
+            | crate::network::tor::MSG_TYPE_RECALL
+            | crate::network::tor::MSG_TYPE_FILE_TRANSFER
             | crate::network::tor::MSG_TYPE_CRDT_OPS
             | crate::network::tor::MSG_TYPE_SYNC_REQUEST
             | crate::network::tor::MSG_TYPE_SYNC_CHUNK
@@ -184,26 +207,6 @@ fn normalize_wire_bytes(expected_type: u8, wire_bytes: &[u8]) -> Vec<u8> {
     }
 }
 
-// ==================== GLOBAL TOR MANAGER ====================
-
-/// Global TorManager instance
-/// Using tokio::sync::Mutex instead of std::sync::Mutex to prevent deadlocks when holding locks across .await
-static GLOBAL_TOR_MANAGER: OnceCell<Arc<Mutex<TorManager>>> = OnceCell::new();
-static GLOBAL_PING_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>> =
-    OnceCell::new();
-static GLOBAL_TAP_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<Vec<u8>>>>> =
-    OnceCell::new();
-static GLOBAL_PONG_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>> =
-    OnceCell::new();
-static GLOBAL_ACK_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>> =
-    OnceCell::new();
-static GLOBAL_MESSAGE_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>> =
-    OnceCell::new();
-static GLOBAL_VOICE_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>> =
-    OnceCell::new();
-static GLOBAL_FRIEND_REQUEST_RECEIVER: OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<Vec<u8>>>>> =
-    OnceCell::new();
-
 /// Global Voice Streaming Listener (v2.0)
 static GLOBAL_VOICE_LISTENER: OnceCell<Arc<tokio::sync::Mutex<VoiceStreamingListener>>> =
     OnceCell::new();
@@ -229,11 +232,6 @@ static GLOBAL_TOR_EVENT_JVM: OnceCell<jni::JavaVM> = OnceCell::new();
 static GLOBAL_RUNTIME: Lazy<tokio::runtime::Runtime> =
     Lazy::new(|| tokio::runtime::Runtime::new().expect("Failed to create global Tokio runtime"));
 
-/// Concurrency cap for outbound Tor sends.
-/// Limits parallel SOCKS connections to avoid overwhelming Tor circuits.
-/// 6 concurrent sends is enough for group fanout without melting Tor.
-static SEND_SEMAPHORE: Lazy<tokio::sync::Semaphore> = Lazy::new(|| tokio::sync::Semaphore::new(6));
-
 /// Global storage for Pong response channels
 /// Maps ping_id -> oneshot sender for sending Pong bytes back to connection handler
 static GLOBAL_PONG_SENDERS: OnceCell<
@@ -247,9 +245,10 @@ fn get_pong_senders(
         .clone()
 }
 
-/// Get or initialize the global TorManager
+/// Get or initialize the active context's TorManager
 fn get_tor_manager() -> Arc<Mutex<TorManager>> {
-    GLOBAL_TOR_MANAGER
+    active_context()
+        .tor_manager
         .get_or_init(|| {
             let tor_manager = TorManager::new().expect("Failed to create TorManager");
             Arc::new(Mutex::new(tor_manager))
@@ -267,16 +266,146 @@ fn get_voice_listener() -> Arc<tokio::sync::Mutex<VoiceStreamingListener>> {
         .clone()
 }
 
+// ==================== CONTEXT HANDLES ====================
+
+/// Create an independent protocol context and return its handle (0 on failure).
+/// The new context is not used until activateContext selects it.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createContext(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    catch_panic!(
+        env,
+        create_context() as jlong,
+        INVALID_HANDLE as jlong
+    )
+}
+
+/// Make `handle` the context used by all handle-less entry points.
+/// Returns false for unknown or destroyed handles.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_activateContext(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            if !activate_context(handle as u64) {
+                log::warn!("activateContext: unknown handle {}", handle);
+                return JNI_FALSE;
+            }
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+/// Handle of the active context (creates the default context on first call).
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_activeContext(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    catch_panic!(
+        env,
+        active_handle() as jlong,
+        INVALID_HANDLE as jlong
+    )
+}
+
+/// Release a context and everything it owns. The active context cannot be
+/// destroyed; activate another one first. Returns false if nothing was released.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_destroyContext(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let handle = handle as u64;
+            if handle == active_handle() {
+                log::warn!("destroyContext: refusing to destroy the active context");
+                return JNI_FALSE;
+            }
+            if destroy_context(handle) {
+                JNI_TRUE
+            } else {
+                JNI_FALSE
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Serve the active context's identity keys from `seed` instead of the
+/// Android KeyManager, so the bridge can be driven without a device KeyStore
+/// (JVM unit tests). Call right after activating the context, before any
+/// key-using entry point. Returns false if the seed is shorter than 32 bytes.
+///
+/// Only built with the `software-keys` feature, which app builds never enable.
+#[cfg(feature = "software-keys")]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_SoftwareKeysBridge_useSoftwareKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    seed: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let mut seed = match jbytearray_to_vec(&mut env, seed) {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return JNI_FALSE;
+                }
+            };
+            let keys = SoftwareKeys::from_seed(&seed);
+            seed.zeroize();
+            match keys {
+                Ok(keys) => {
+                    *active_context().software_keys.write().unwrap() = Some(Arc::new(keys));
+                    log::warn!("useSoftwareKeys: active context now uses in-process keys");
+                    JNI_TRUE
+                }
+                Err(e) => {
+                    log::error!("useSoftwareKeys: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Drop any software keys so the active context reads keys from KeyManager again.
+#[cfg(feature = "software-keys")]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_SoftwareKeysBridge_useKeyManagerKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(
+        env,
+        {
+            active_context().software_keys.write().unwrap().take();
+        },
+        ()
+    )
+}
+
 // ==================== BLOCKING POLL HELPERS ====================
 
 /// Blocking recv on a (u64, Vec<u8>) channel with timeout.
 /// Parks the calling thread until data arrives or timeout elapses.
 /// Returns None on timeout, channel closure, or if receiver not initialized.
 /// SAFETY: Must be called from a non-tokio thread (JVM Dispatchers.IO is safe).
-fn blocking_recv_pair(
-    receiver: &OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>>,
-    timeout_secs: u64,
-) -> Option<(u64, Vec<u8>)> {
+fn blocking_recv_pair(receiver: &PairReceiver, timeout_secs: u64) -> Option<(u64, Vec<u8>)> {
     let rx_arc = receiver.get()?;
     let mut rx = rx_arc.lock().unwrap();
 
@@ -289,10 +418,7 @@ fn blocking_recv_pair(
 }
 
 /// Blocking recv on a Vec<u8> channel with timeout.
-fn blocking_recv_vec(
-    receiver: &OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<Vec<u8>>>>>,
-    timeout_secs: u64,
-) -> Option<Vec<u8>> {
+fn blocking_recv_vec(receiver: &VecReceiver, timeout_secs: u64) -> Option<Vec<u8>> {
     let rx_arc = receiver.get()?;
     let mut rx = rx_arc.lock().unwrap();
 
@@ -345,67 +471,48 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_encryptMessage(
                 return std::ptr::null_mut();
             }
 
-            // Get application context
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            // Get KeyManager instance
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get KeyManager instance
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our X25519 encryption private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get encryption private key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our X25519 encryption private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption private key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Get our X25519 public key (needed for wire format)
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption public key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get encryption public key: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // Derive shared secret using X25519 ECDH
-            let mut shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
+            let mut shared_secret = match crate::ffi::session_cache::cached_shared_secret_for_peer(
                 &our_x25519_private,
                 &recipient_x25519_bytes,
             ) {
-                Ok(secret) => secret,
+                Ok(secret) => *secret,
                 Err(e) => {
                     let _ =
                         env.throw_new("java/lang/RuntimeException", format!("ECDH failed: {}", e));
@@ -490,54 +597,36 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptMessage(
             // Extract encrypted message (remaining bytes)
             let encrypted_data = &wire_bytes[32..];
 
-            // Get application context
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            // Get KeyManager instance
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get KeyManager instance
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our X25519 encryption private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get encryption private key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our X25519 encryption private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption private key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Derive shared secret using X25519 ECDH
-            let mut shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
+            let mut shared_secret = match crate::ffi::session_cache::cached_shared_secret_for_peer(
                 &our_x25519_private,
                 &sender_x25519_public,
             ) {
-                Ok(secret) => secret,
+                Ok(secret) => *secret,
                 Err(e) => {
                     let _ =
                         env.throw_new("java/lang/RuntimeException", format!("ECDH failed: {}", e));
@@ -880,32 +969,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createHiddenService(
         env,
         {
             // Get KeyManager to retrieve seed-derived hidden service key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(val) => match val.l() {
-                    Ok(ctx) => ctx,
-                    Err(_) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            "currentApplication returned null or invalid object",
-                        );
-                        return std::ptr::null_mut();
-                    }
-                },
-                Err(e) => {
-                    let _ = env.throw_new(
-                        "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
-                    );
-                    return std::ptr::null_mut();
-                }
-            };
-
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
                 Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
@@ -917,10 +981,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createHiddenService(
             };
 
             // Get hidden service private key (deterministic from seed)
-            let hs_private_key = match crate::ffi::keystore::get_hidden_service_private_key(
-                &mut env,
-                &key_manager,
-            ) {
+            let hs_private_key = match key_manager.hidden_service_private_key(&mut env) {
                 Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
@@ -1111,6 +1172,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_onDuressPinEntered(
     catch_panic!(
         env,
         {
+            crate::wipe::clear_volatile_state();
             match crate::storage::on_duress_pin_entered() {
                 Ok(()) => {
                     log::info!(
@@ -1141,46 +1203,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createVoiceHiddenServi
         env,
         {
             // Get KeyManager to retrieve seed-derived voice service key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get voice service private key (deterministic from seed with domain separation)
+            let voice_private_key = match key_manager.voice_service_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get voice service key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get voice service private key (deterministic from seed with domain separation)
-            let voice_private_key =
-                match crate::ffi::keystore::get_voice_service_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get voice service key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             let tor_manager = get_tor_manager();
 
             // Run async voice hidden service creation using global runtime
@@ -1251,6 +1296,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceList
         env,
         {
             let tor_manager = get_tor_manager();
+            let ctx = active_context();
 
             // Run async listener start using the global runtime (persists for process lifetime)
             let result = GLOBAL_RUNTIME.block_on(async {
@@ -1261,16 +1307,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceList
             match result {
                 Ok((ping_receiver, pong_receiver)) => {
                     // Store both PING and PONG receivers globally (local channels, no globals in tor.rs)
-                    if let Err(_) = GLOBAL_PING_RECEIVER.set(Arc::new(Mutex::new(ping_receiver))) {
+                    if let Err(_) = ctx.ping_receiver.set(Arc::new(Mutex::new(ping_receiver))) {
                         log::warn!("PING_RECEIVER already initialized (listener restart?)");
                     }
-                    if let Err(_) = GLOBAL_PONG_RECEIVER.set(Arc::new(Mutex::new(pong_receiver))) {
+                    if let Err(_) = ctx.pong_receiver.set(Arc::new(Mutex::new(pong_receiver))) {
                         log::warn!("PONG_RECEIVER already initialized (listener restart?)");
                     }
 
                     // Initialize MESSAGE channel for TEXT/VOICE/IMAGE/PAYMENT routing
                     let (message_tx, message_rx) = mpsc::unbounded_channel::<(u64, Vec<u8>)>();
-                    if let Err(_) = GLOBAL_MESSAGE_RECEIVER.set(Arc::new(Mutex::new(message_rx))) {
+                    if let Err(_) = ctx.message_receiver.set(Arc::new(Mutex::new(message_rx))) {
                         log::warn!("MESSAGE_RECEIVER already initialized (listener restart?)");
                     }
                     let tx_arc = Arc::new(std::sync::Mutex::new(message_tx));
@@ -1281,7 +1327,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceList
 
                     // Initialize VOICE channel for CALL_SIGNALING routing
                     let (voice_tx, voice_rx) = mpsc::unbounded_channel::<(u64, Vec<u8>)>();
-                    if let Err(_) = GLOBAL_VOICE_RECEIVER.set(Arc::new(Mutex::new(voice_rx))) {
+                    if let Err(_) = ctx.voice_receiver.set(Arc::new(Mutex::new(voice_rx))) {
                         log::warn!("VOICE_RECEIVER already initialized (listener restart?)");
                     }
                     let voice_tx_arc = Arc::new(std::sync::Mutex::new(voice_tx));
@@ -1295,7 +1341,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceList
                     // Initialize FRIEND_REQUEST channel for 0x07/0x08 routing
                     // All friend request traffic now arrives on port 8080 (torrc routes 9151→8080)
                     let (fr_tx, fr_rx) = mpsc::unbounded_channel::<Vec<u8>>();
-                    if let Err(_) = GLOBAL_FRIEND_REQUEST_RECEIVER.set(Arc::new(Mutex::new(fr_rx))) {
+                    if let Err(_) = ctx.friend_request_receiver.set(Arc::new(Mutex::new(fr_rx))) {
                         log::warn!("FRIEND_REQUEST_RECEIVER already initialized (listener restart?)");
                     }
                     let fr_tx_arc = Arc::new(std::sync::Mutex::new(fr_tx));
@@ -1306,7 +1352,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceList
 
                     // Initialize TAP channel for 0x05 routing
                     let (tap_tx, tap_rx) = mpsc::unbounded_channel::<Vec<u8>>();
-                    if let Err(_) = GLOBAL_TAP_RECEIVER.set(Arc::new(Mutex::new(tap_rx))) {
+                    if let Err(_) = ctx.tap_receiver.set(Arc::new(Mutex::new(tap_rx))) {
                         log::warn!("TAP_RECEIVER already initialized (listener restart?)");
                     }
                     let tap_tx_arc = Arc::new(std::sync::Mutex::new(tap_tx));
@@ -1517,7 +1563,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPing(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_PING_RECEIVER.get() {
+            if let Some(receiver) = active_context().ping_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -1534,12 +1580,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPing(
 
                         const MSG_TYPE_PING: u8 = 0x01;
                         if ping_bytes[0] != MSG_TYPE_PING {
-                            let head_hex: String = ping_bytes
-                                .iter()
-                                .take(8)
-                                .map(|b| format!("{:02x}", b))
-                                .collect::<Vec<_>>()
-                                .join("");
+                            let head_hex = redact::key(&ping_bytes[..ping_bytes.len().min(8)]);
                             log::error!("FRAMING_VIOLATION: pollIncomingPing got type=0x{:02x} expected=0x{:02x} conn_id={} len={} head={}",
                             ping_bytes[0], MSG_TYPE_PING, connection_id, ping_bytes.len(), head_hex);
                             return std::ptr::null_mut();
@@ -1580,7 +1621,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingMessage(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_MESSAGE_RECEIVER.get() {
+            if let Some(receiver) = active_context().message_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -1622,7 +1663,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollVoiceMessage(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_VOICE_RECEIVER.get() {
+            if let Some(receiver) = active_context().voice_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -1786,7 +1827,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToNewConnectio
 
             log::info!(
                 "Sending Pong over NEW connection to {} ({} bytes)",
-                onion_address,
+                redact::onion(&onion_address),
                 pong_bytes.len()
             );
 
@@ -1798,7 +1839,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToNewConnectio
                 // Connect to sender's .onion address on handshake port (lock only during connect)
                 let mut conn = {
                     let tor = tor_manager.lock().unwrap();
-                    tor.connect(&onion_address, PING_PONG_HANDSHAKE_PORT)
+                    tor.connect(&onion_address, crate::network::ports().messaging)
                         .await?
                 }; // Lock released
 
@@ -1810,7 +1851,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToNewConnectio
 
                 log::info!(
                     "Pong sent successfully over new connection to {}",
-                    onion_address
+                    redact::onion(&onion_address)
                 );
                 Ok::<(), Box<dyn std::error::Error>>(())
             });
@@ -1858,8 +1899,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToListener(
 
             log::info!(
                 "Sending Pong to listener at {}:{} ({} bytes)",
-                onion_address,
-                PING_PONG_HANDSHAKE_PORT,
+                redact::onion(&onion_address),
+                crate::network::ports().messaging,
                 pong_bytes.len()
             );
 
@@ -1867,7 +1908,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToListener(
             let tor_manager = get_tor_manager();
 
             // Open connection to sender's messaging port (9150 handshake) and send Pong using global runtime
-            // NOTE: CRITICAL FIX - Use PING_PONG_HANDSHAKE_PORT (9150), NOT MAIN_LISTENER_PORT (8080)
+            // NOTE: CRITICAL FIX - Use the messaging port (9150), NOT the local listener port (8080)
             // The recipient's hidden service exposes 9150 for messaging, NOT 8080
             // 8080 is only the local port this instance listens on
             let result = GLOBAL_RUNTIME.block_on(async {
@@ -1879,7 +1920,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToListener(
                 // Connect to sender's messaging handshake port (9150) - NOT 8080
                 let mut conn = {
                     let tor = tor_manager.lock().unwrap();
-                    tor.connect(&onion_address, PING_PONG_HANDSHAKE_PORT)
+                    tor.connect(&onion_address, crate::network::ports().messaging)
                         .await?
                 }; // Lock released
 
@@ -1891,8 +1932,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPongToListener(
 
                 log::info!(
                     "Pong sent successfully to listener at {}:{}",
-                    onion_address,
-                    PING_PONG_HANDSHAKE_PORT
+                    redact::onion(&onion_address),
+                    crate::network::ports().messaging
                 );
                 Ok::<(), Box<dyn std::error::Error>>(())
             });
@@ -1960,55 +2001,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
             };
 
             // Get KeyManager for our keys
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(val) => match val.l() {
-                    Ok(ctx) => ctx,
-                    Err(_) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            "currentApplication returned null or invalid object",
-                        );
-                        return std::ptr::null_mut();
-                    }
-                },
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our signing private key (Ed25519)
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get signing key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our signing private key (Ed25519)
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get signing key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Validate Ed25519 private key length (must be 32 bytes)
             if our_signing_private.len() != 32 {
                 let _ = env.throw_new(
@@ -2051,17 +2066,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
             };
 
             // Get our X25519 public key (needed for PingToken)
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get our X25519 pubkey: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get our X25519 pubkey: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // Convert X25519 keys to fixed-size arrays
             let sender_x25519_pubkey: [u8; 32] = match our_x25519_public.as_slice().try_into() {
@@ -2174,24 +2188,24 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
             };
 
             // Get our X25519 encryption private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get encryption key: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // Derive shared secret using X25519 ECDH
-            let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
+            let shared_secret = match crate::ffi::session_cache::cached_shared_secret_for_identity(
+                &recipient_ed25519_bytes,
                 &our_x25519_private,
                 &recipient_x25519_bytes,
             ) {
-                Ok(secret) => secret,
+                Ok(secret) => *secret,
                 Err(e) => {
                     let _ =
                         env.throw_new("java/lang/RuntimeException", format!("ECDH failed: {}", e));
@@ -2219,18 +2233,18 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
             wire_message.extend_from_slice(&encrypted_ping);
 
             // Send encrypted Ping via Tor
-            // HYBRID MODE: Try instant Pong (30s timeout), fall back to delayed mode
+            // HYBRID MODE: Try instant Pong (policy timeout, default 30s), fall back to delayed mode
             let tor_manager = get_tor_manager();
             let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
-            const PING_PONG_PORT: u16 = 9150;
-            const INSTANT_PONG_TIMEOUT_SECS: u64 = 30;
+            let ping_pong_port = crate::network::ports().messaging;
+            let instant_pong_timeout = crate::network::timeout_policy().instant_pong;
 
             let result: Result<(), Box<dyn std::error::Error>> = runtime.block_on(async {
             // Connect and send Ping (lock only during operations)
             let mut conn = {
                 let manager = tor_manager.lock().unwrap();
-                manager.connect(&recipient_onion_str, PING_PONG_PORT).await?
+                manager.connect(&recipient_onion_str, ping_pong_port).await?
             }; // Lock released
 
             {
@@ -2238,12 +2252,12 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
                 manager.send(&mut conn, &wire_message).await?;
             } // Lock released
 
-            log::info!("Ping sent successfully ({}), waiting for instant Pong ({}s timeout)...", ping_id, INSTANT_PONG_TIMEOUT_SECS);
+            log::info!("Ping sent successfully ({}), waiting for instant Pong ({}s timeout)...", ping_id, instant_pong_timeout.as_secs());
 
             // Try to receive instant Pong response (recipient online and accepts immediately)
             // No lock needed - we own the connection
             let pong_result = tokio::time::timeout(
-                std::time::Duration::from_secs(INSTANT_PONG_TIMEOUT_SECS),
+                instant_pong_timeout,
                 async {
                     // Read length prefix
                     let mut len_buf = [0u8; 4];
@@ -2377,7 +2391,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
                     Ok(())
                 }
                 Err(_) => {
-                    log::info!("→ DELAYED MODE: Instant Pong timeout ({}s) - recipient may be offline or busy", INSTANT_PONG_TIMEOUT_SECS);
+                    log::info!("→ DELAYED MODE: Instant Pong timeout ({}s) - recipient may be offline or busy", instant_pong_timeout.as_secs());
                     log::info!("Pong will arrive later via port 8080 main listener when recipient comes online");
                     Ok(())
                 }
@@ -2386,7 +2400,11 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendPing(
 
             match result {
                 Ok(_) => {
-                    log::info!("Ping sent to {}: {}", recipient_onion_str, ping_id);
+                    log::info!(
+                        "Ping sent to {}: {}",
+                        redact::onion(&recipient_onion_str),
+                        ping_id
+                    );
 
                     // Encode wire_message as Base64 for storage and retry
                     let wire_bytes_base64 = base64::encode(&wire_message);
@@ -2468,22 +2486,22 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resendPingWithWireByte
             log::info!(
                 "Resending Ping with stored wire bytes ({} bytes) to {}",
                 wire_message.len(),
-                recipient_onion_str
+                redact::onion(&recipient_onion_str)
             );
 
             // Send encrypted Ping via Tor
-            // HYBRID MODE: Try instant Pong (30s timeout), fall back to delayed mode
+            // HYBRID MODE: Try instant Pong (policy timeout, default 30s), fall back to delayed mode
             let tor_manager = get_tor_manager();
             let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
-            const PING_PONG_PORT: u16 = 9150;
-            const INSTANT_PONG_TIMEOUT_SECS: u64 = 30;
+            let ping_pong_port = crate::network::ports().messaging;
+            let instant_pong_timeout = crate::network::timeout_policy().instant_pong;
 
             let result: Result<(), Box<dyn std::error::Error>> = runtime.block_on(async {
             // Connect and send Ping (lock only during operations)
             let mut conn = {
                 let manager = tor_manager.lock().unwrap();
-                manager.connect(&recipient_onion_str, PING_PONG_PORT).await?
+                manager.connect(&recipient_onion_str, ping_pong_port).await?
             }; // Lock released
 
             {
@@ -2491,13 +2509,13 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resendPingWithWireByte
                 manager.send(&mut conn, &wire_message).await?;
             } // Lock released
 
-            log::info!("Ping resent successfully, waiting for instant Pong ({}s timeout)...", INSTANT_PONG_TIMEOUT_SECS);
+            log::info!("Ping resent successfully, waiting for instant Pong ({}s timeout)...", instant_pong_timeout.as_secs());
 
             // Try to receive instant Pong response (recipient online and accepts immediately)
             // Note: This retry doesn't include the message payload - that's already been sent before
             // We're just resending the Ping to get acknowledgment
             let pong_result = tokio::time::timeout(
-                std::time::Duration::from_secs(INSTANT_PONG_TIMEOUT_SECS),
+                instant_pong_timeout,
                 async {
                     // Read length prefix
                     let mut len_buf = [0u8; 4];
@@ -2544,7 +2562,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resendPingWithWireByte
                     Ok(())
                 }
                 Err(_) => {
-                    log::info!("→ DELAYED MODE: Instant Pong timeout ({}s) for retry - recipient may be offline or busy", INSTANT_PONG_TIMEOUT_SECS);
+                    log::info!("→ DELAYED MODE: Instant Pong timeout ({}s) for retry - recipient may be offline or busy", instant_pong_timeout.as_secs());
                     log::info!("Pong will arrive later via port 8080 main listener when recipient comes online");
                     Ok(())
                 }
@@ -2553,7 +2571,10 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resendPingWithWireByte
 
             match result {
                 Ok(_) => {
-                    log::info!("Ping resent successfully to {}", recipient_onion_str);
+                    log::info!(
+                        "Ping resent successfully to {}",
+                        redact::onion(&recipient_onion_str)
+                    );
                     1 // success
                 }
                 Err(e) => {
@@ -2595,48 +2616,33 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendTap(
                 }
             };
 
-            log::info!("Sending tap to {}", recipient_onion_str);
+            log::info!("Sending tap to {}", redact::onion(&recipient_onion_str));
 
             // Get KeyManager for our keys
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return 0;
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
-                Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+            // Get our X25519 keys
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to get our X25519 pubkey: {}", e);
                     return 0;
                 }
             };
 
-            // Get our X25519 keys
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get our X25519 pubkey: {}", e);
-                        return 0;
-                    }
-                };
-
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get encryption key: {}", e);
-                        return 0;
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to get encryption key: {}", e);
+                    return 0;
+                }
+            };
 
             // Derive shared secret using X25519 ECDH
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
@@ -2694,24 +2700,34 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendTap(
                 }
             };
 
-            const TAP_PORT: u16 = 9151; // Different port from Ping-Pong (9150)
+            let tap_port = crate::network::ports().tap_port(); // Different port from Ping-Pong (9150) unless single-port
 
             let result = runtime.block_on(async {
                 let manager = tor_manager.lock().unwrap();
-                let mut conn = manager.connect(&recipient_onion_str, TAP_PORT).await?;
+                let mut conn = manager.connect(&recipient_onion_str, tap_port).await?;
                 manager.send(&mut conn, &wire_message).await?;
 
-                log::info!("Tap sent successfully to {}", recipient_onion_str);
+                log::info!(
+                    "Tap sent successfully to {}",
+                    redact::onion(&recipient_onion_str)
+                );
                 Ok::<(), Box<dyn std::error::Error>>(())
             });
 
             match result {
                 Ok(_) => {
-                    log::info!("Tap sent successfully to {}", recipient_onion_str);
+                    log::info!(
+                        "Tap sent successfully to {}",
+                        redact::onion(&recipient_onion_str)
+                    );
                     1 // success
                 }
                 Err(e) => {
-                    log::error!("Failed to send tap to {}: {}", recipient_onion_str, e);
+                    log::error!(
+                        "Failed to send tap to {}: {}",
+                        redact::onion(&recipient_onion_str),
+                        e
+                    );
                     0 // failure
                 }
             }
@@ -2751,7 +2767,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequest(
 
             log::info!(
                 "Sending friend request to {} ({} bytes)",
-                recipient_onion_str,
+                redact::onion(&recipient_onion_str),
                 friend_request_bytes.len()
             );
 
@@ -2777,17 +2793,20 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequest(
                 }
             };
 
-            const FRIEND_REQUEST_PORT: u16 = 9151; // Friend request .onion port (wire protocol)
-            const FALLBACK_PORT: u16 = 8080; // Fallback to main listener if 9151 fails
-            const MAX_RETRIES: u32 = 24; // 24 retries x 5s = 2 min for slow bridges (Snowflake)
-            const RETRY_DELAY_SECS: u64 = 5;
+            let ports = crate::network::ports();
+            let friend_request_port = ports.tap_port(); // Friend request .onion port (wire protocol)
+            let fallback_port = ports.fallback_port(); // Fallback to main listener if 9151 fails
+            // Jittered exponential backoff (see `RetryPolicy::DEFAULT` for the bounds)
+            let retry = crate::network::retry_policy();
+            let max_attempts = retry.max_attempts;
+            let mut rng = rand::thread_rng();
 
-            for attempt in 1..=MAX_RETRIES {
+            for attempt in 1..=max_attempts {
                 // Try port 9151 first
                 let result = runtime.block_on(async {
                     let manager = tor_manager.lock().unwrap();
                     let mut conn = manager
-                        .connect(&recipient_onion_str, FRIEND_REQUEST_PORT)
+                        .connect(&recipient_onion_str, friend_request_port)
                         .await?;
                     manager.send(&mut conn, &wire_message).await?;
                     Ok::<(), Box<dyn std::error::Error>>(())
@@ -2797,8 +2816,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequest(
                     Ok(_) => {
                         log::info!(
                             "Friend request sent successfully to {} on port {} (attempt {})",
-                            recipient_onion_str,
-                            FRIEND_REQUEST_PORT,
+                            redact::onion(&recipient_onion_str),
+                            friend_request_port,
                             attempt
                         );
                         return 1;
@@ -2806,18 +2825,18 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequest(
                     Err(e) => {
                         log::warn!(
                             "Port {} attempt {}/{} failed: {}. Trying fallback port {}...",
-                            FRIEND_REQUEST_PORT,
+                            friend_request_port,
                             attempt,
-                            MAX_RETRIES,
+                            max_attempts,
                             e,
-                            FALLBACK_PORT
+                            fallback_port
                         );
 
                         // Fallback to port 8080
                         let fallback_result = runtime.block_on(async {
                             let manager = tor_manager.lock().unwrap();
                             let mut conn =
-                                manager.connect(&recipient_onion_str, FALLBACK_PORT).await?;
+                                manager.connect(&recipient_onion_str, fallback_port).await?;
                             manager.send(&mut conn, &wire_message).await?;
                             Ok::<(), Box<dyn std::error::Error>>(())
                         });
@@ -2826,23 +2845,22 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequest(
                             Ok(_) => {
                                 log::info!(
                                     "Friend request sent via fallback port {} (attempt {})",
-                                    FALLBACK_PORT,
+                                    fallback_port,
                                     attempt
                                 );
                                 return 1;
                             }
                             Err(fallback_err) => {
-                                if attempt < MAX_RETRIES {
-                                    log::warn!("Friend request attempt {}/{} failed (both ports): {}. Retrying in {}s...",
-                                    attempt, MAX_RETRIES, fallback_err, RETRY_DELAY_SECS);
-                                    std::thread::sleep(std::time::Duration::from_secs(
-                                        RETRY_DELAY_SECS,
-                                    ));
+                                if retry.should_retry(attempt) {
+                                    let delay = retry.delay_for_attempt(attempt, &mut rng);
+                                    log::warn!("Friend request attempt {}/{} failed (both ports): {}. Retrying in {}ms...",
+                                    attempt, max_attempts, fallback_err, delay.as_millis());
+                                    std::thread::sleep(delay);
                                 } else {
                                     log::error!(
                                         "Failed to send friend request to {} after {} attempts: {}",
-                                        recipient_onion_str,
-                                        MAX_RETRIES,
+                                        redact::onion(&recipient_onion_str),
+                                        max_attempts,
                                         fallback_err
                                     );
                                 }
@@ -2893,7 +2911,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequestAccep
 
             log::info!(
                 "Sending friend request accepted to {} ({} bytes)",
-                recipient_onion_str,
+                redact::onion(&recipient_onion_str),
                 acceptance_bytes.len()
             );
 
@@ -2919,17 +2937,20 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequestAccep
                 }
             };
 
-            const FRIEND_REQUEST_PORT: u16 = 9151; // Friend request .onion port (wire protocol)
-            const FALLBACK_PORT: u16 = 8080; // Fallback to main listener if 9151 fails
-            const MAX_RETRIES: u32 = 24; // 24 retries x 5s = 2 min for slow bridges (Snowflake)
-            const RETRY_DELAY_SECS: u64 = 5;
+            let ports = crate::network::ports();
+            let friend_request_port = ports.tap_port(); // Friend request .onion port (wire protocol)
+            let fallback_port = ports.fallback_port(); // Fallback to main listener if 9151 fails
+            // Jittered exponential backoff (see `RetryPolicy::DEFAULT` for the bounds)
+            let retry = crate::network::retry_policy();
+            let max_attempts = retry.max_attempts;
+            let mut rng = rand::thread_rng();
 
-            for attempt in 1..=MAX_RETRIES {
+            for attempt in 1..=max_attempts {
                 // Try port 9151 first
                 let result = runtime.block_on(async {
                     let manager = tor_manager.lock().unwrap();
                     let mut conn = manager
-                        .connect(&recipient_onion_str, FRIEND_REQUEST_PORT)
+                        .connect(&recipient_onion_str, friend_request_port)
                         .await?;
                     manager.send(&mut conn, &wire_message).await?;
                     Ok::<(), Box<dyn std::error::Error>>(())
@@ -2939,8 +2960,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequestAccep
                     Ok(_) => {
                         log::info!(
                             "Friend request acceptance sent to {} on port {} (attempt {})",
-                            recipient_onion_str,
-                            FRIEND_REQUEST_PORT,
+                            redact::onion(&recipient_onion_str),
+                            friend_request_port,
                             attempt
                         );
                         return 1;
@@ -2948,37 +2969,36 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendFriendRequestAccep
                     Err(e) => {
                         log::warn!(
                             "Port {} attempt {}/{} failed: {}. Trying fallback port {}...",
-                            FRIEND_REQUEST_PORT,
+                            friend_request_port,
                             attempt,
-                            MAX_RETRIES,
+                            max_attempts,
                             e,
-                            FALLBACK_PORT
+                            fallback_port
                         );
 
                         // Fallback to port 8080
                         let fallback_result = runtime.block_on(async {
                             let manager = tor_manager.lock().unwrap();
                             let mut conn =
-                                manager.connect(&recipient_onion_str, FALLBACK_PORT).await?;
+                                manager.connect(&recipient_onion_str, fallback_port).await?;
                             manager.send(&mut conn, &wire_message).await?;
                             Ok::<(), Box<dyn std::error::Error>>(())
                         });
 
                         match fallback_result {
                             Ok(_) => {
-                                log::info!("Friend request acceptance sent via fallback port {} (attempt {})", FALLBACK_PORT, attempt);
+                                log::info!("Friend request acceptance sent via fallback port {} (attempt {})", fallback_port, attempt);
                                 return 1;
                             }
                             Err(fallback_err) => {
-                                if attempt < MAX_RETRIES {
-                                    log::warn!("Acceptance attempt {}/{} failed (both ports): {}. Retrying in {}s...",
-                                    attempt, MAX_RETRIES, fallback_err, RETRY_DELAY_SECS);
-                                    std::thread::sleep(std::time::Duration::from_secs(
-                                        RETRY_DELAY_SECS,
-                                    ));
+                                if retry.should_retry(attempt) {
+                                    let delay = retry.delay_for_attempt(attempt, &mut rng);
+                                    log::warn!("Acceptance attempt {}/{} failed (both ports): {}. Retrying in {}ms...",
+                                    attempt, max_attempts, fallback_err, delay.as_millis());
+                                    std::thread::sleep(delay);
                                 } else {
                                     log::error!("Failed to send friend request acceptance to {} after {} attempts: {}",
-                                    recipient_onion_str, MAX_RETRIES, fallback_err);
+                                    redact::onion(&recipient_onion_str), max_attempts, fallback_err);
                                 }
                             }
                         }
@@ -3165,8 +3185,12 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startTapListener(
                     let (fr_tx, fr_rx) = mpsc::unbounded_channel::<Vec<u8>>();
 
                     // Store receivers globally
-                    let _ = GLOBAL_TAP_RECEIVER.set(Arc::new(Mutex::new(tap_rx)));
-                    let _ = GLOBAL_FRIEND_REQUEST_RECEIVER.set(Arc::new(Mutex::new(fr_rx)));
+                    let _ = active_context()
+                        .tap_receiver
+                        .set(Arc::new(Mutex::new(tap_rx)));
+                    let _ = active_context()
+                        .friend_request_receiver
+                        .set(Arc::new(Mutex::new(fr_rx)));
 
                     // Store friend request sender in global FRIEND_REQUEST_TX for routing in tor.rs
                     let fr_tx_arc = Arc::new(std::sync::Mutex::new(fr_tx.clone()));
@@ -3239,7 +3263,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingTap(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_TAP_RECEIVER.get() {
+            if let Some(receiver) = active_context().tap_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -3281,7 +3305,9 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startFriendRequestList
             let (tx, rx) = mpsc::unbounded_channel::<Vec<u8>>();
 
             // Store receiver globally for polling
-            let _ = GLOBAL_FRIEND_REQUEST_RECEIVER.set(Arc::new(Mutex::new(rx)));
+            let _ = active_context()
+                .friend_request_receiver
+                .set(Arc::new(Mutex::new(rx)));
 
             // Store sender in global FRIEND_REQUEST_TX for routing in tor.rs
             let tx_arc = Arc::new(std::sync::Mutex::new(tx));
@@ -3335,40 +3361,26 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptIncomingTap(
 
             log::info!(
                 "Decrypting tap from sender X25519: {}",
-                hex::encode(&sender_x25519_pubkey)
+                redact::key(&sender_x25519_pubkey)
             );
 
             // Get our X25519 private key from KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+                    log::error!("Failed to get encryption private key: {}", e);
                     return std::ptr::null_mut();
                 }
             };
 
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get encryption private key: {}", e);
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -3441,7 +3453,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startPongListener(
 }
 
 /// Poll for an incoming pong (non-blocking)
-/// Reads from GLOBAL_PONG_RECEIVER (local channel stored in FFI, not global in tor.rs)
+/// Reads from the active context's pong receiver (local channel stored in FFI, not global in tor.rs)
 #[no_mangle]
 pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPong(
     mut env: JNIEnv,
@@ -3450,8 +3462,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPong(
     catch_panic!(
         env,
         {
-            // Read from GLOBAL_PONG_RECEIVER (same pattern as GLOBAL_PING_RECEIVER)
-            if let Some(receiver) = GLOBAL_PONG_RECEIVER.get() {
+            // Read from the pong receiver (same pattern as the ping receiver)
+            if let Some(receiver) = active_context().pong_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -3468,12 +3480,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPong(
 
                         const MSG_TYPE_PONG: u8 = 0x02;
                         if pong_bytes[0] != MSG_TYPE_PONG {
-                            let head_hex: String = pong_bytes
-                                .iter()
-                                .take(8)
-                                .map(|b| format!("{:02x}", b))
-                                .collect::<Vec<_>>()
-                                .join("");
+                            let head_hex = redact::key(&pong_bytes[..pong_bytes.len().min(8)]);
                             log::error!("FRAMING_VIOLATION: pollIncomingPong got type=0x{:02x} expected=0x{:02x} conn_id={} len={} head={}",
                             pong_bytes[0], MSG_TYPE_PONG, conn_id, pong_bytes.len(), head_hex);
                             return std::ptr::null_mut();
@@ -3500,7 +3507,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPong(
                     }
                 }
             } else {
-                // GLOBAL_PONG_RECEIVER not initialized - listener not started yet
+                // Pong receiver not initialized - listener not started yet
                 std::ptr::null_mut()
             }
         },
@@ -3549,40 +3556,26 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptAndStorePongFro
 
             log::info!(
                 "Decrypting pong from recipient X25519: {}",
-                hex::encode(&recipient_x25519_pubkey)
+                redact::key(&recipient_x25519_pubkey)
             );
 
             // Get our X25519 private key from KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return 0;
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+                    log::error!("Failed to get encryption private key: {}", e);
                     return 0;
                 }
             };
 
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get encryption private key: {}", e);
-                        return 0;
-                    }
-                };
-
             // Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -3835,42 +3828,28 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
 
             log::info!(
                 "Sending message blob to {} ({} bytes encrypted message)",
-                onion_address,
+                redact::onion(&onion_address),
                 message_bytes.len()
             );
 
             // Get KeyManager to access our X25519 public key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return 0;
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our X25519 public key
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+                    log::error!("Failed to get our X25519 public key: {}", e);
                     return 0;
                 }
             };
 
-            // Get our X25519 public key
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get our X25519 public key: {}", e);
-                        return 0;
-                    }
-                };
-
             // Strict boundary validation: X25519 pubkey must be exactly 32 bytes
             if our_x25519_public.len() != 32 {
                 log::error!(
@@ -3916,26 +3895,31 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
 
             // Send message blob via Tor using global runtime.
             // No TorManager lock — connect_to_onion goes straight to SOCKS.
-            // Semaphore caps concurrent sends to avoid overwhelming Tor circuits.
+            // Lane permits cap concurrent sends to avoid overwhelming Tor circuits.
             let result = GLOBAL_RUNTIME.block_on(async {
-                const FRIEND_REQUEST_PORT: u16 = 9151;
-                const MESSAGE_PORT: u16 = 9150;
+                let ports = crate::network::ports();
+                let friend_request_port = ports.tap_port();
+                let message_port = ports.messaging;
 
                 let port = match msg_type {
-                    0x07 | 0x08 => FRIEND_REQUEST_PORT,
-                    _ => MESSAGE_PORT,
+                    0x07 | 0x08 => friend_request_port,
+                    _ => message_port,
                 };
 
-                // 1) Acquire semaphore permit (caps at 6 concurrent sends)
-                let _permit = SEND_SEMAPHORE.acquire().await.map_err(|_| {
-                    Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Send semaphore closed",
-                    )) as Box<dyn std::error::Error>
-                })?;
+                // 1) Acquire send permit (caps at 6 concurrent sends; control
+                //    and receipts are served before text and media)
+                let ctx = active_context();
+                let _permit = ctx
+                    .send_permits
+                    .acquire(
+                        crate::network::send_lanes::lane_for_msg_type(msg_type),
+                        &onion_address,
+                        wire_message.len(),
+                    )
+                    .await;
 
                 // 2) Timeout starts AFTER permit — queue time doesn't burn the budget
-                let timeout_duration = std::time::Duration::from_secs(90);
+                let timeout_duration = crate::network::timeout_policy().blob_send;
 
                 tokio::time::timeout(timeout_duration, async {
                     // 3) Connect via SOCKS directly — no TorManager mutex
@@ -3951,12 +3935,18 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
                     // 4) Send wire message (length-prefixed via TorConnection::send)
                     conn.send(&wire_message).await?;
 
-                    log::info!("Message blob sent successfully to {}", onion_address);
+                    log::info!(
+                        "Message blob sent successfully to {}",
+                        redact::onion(&onion_address)
+                    );
                     Ok::<(), Box<dyn std::error::Error>>(())
                 })
                 .await
                 .map_err(|_| {
-                    log::warn!("Send message blob timed out after 90s to {}", onion_address);
+                    log::warn!(
+                        "Send message blob timed out after 90s to {}",
+                        redact::onion(&onion_address)
+                    );
                     Box::new(std::io::Error::new(
                         std::io::ErrorKind::TimedOut,
                         "Operation timed out",
@@ -3975,7 +3965,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
                         BLOB_FAIL_SOCKS_TIMEOUT.fetch_add(1, Ordering::Relaxed);
                         log::error!(
                             "BLOB_SEND_FAIL kind=SOCKS_TIMEOUT to {}: {}",
-                            onion_address,
+                            redact::onion(&onion_address),
                             e
                         );
                     } else if error_msg.contains("connection refused")
@@ -3984,7 +3974,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
                         BLOB_FAIL_CONNECT_ERR.fetch_add(1, Ordering::Relaxed);
                         log::error!(
                             "BLOB_SEND_FAIL kind=CONNECT_ERR to {}: {}",
-                            onion_address,
+                            redact::onion(&onion_address),
                             e
                         );
                     } else if error_msg.contains("tor")
@@ -3994,7 +3984,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
                         BLOB_FAIL_TOR_NOT_READY.fetch_add(1, Ordering::Relaxed);
                         log::error!(
                             "BLOB_SEND_FAIL kind=TOR_NOT_READY to {}: {}",
-                            onion_address,
+                            redact::onion(&onion_address),
                             e
                         );
                     } else if error_msg.contains("write")
@@ -4002,12 +3992,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendMessageBlob(
                         || error_msg.contains("broken pipe")
                     {
                         BLOB_FAIL_WRITE_ERR.fetch_add(1, Ordering::Relaxed);
-                        log::error!("BLOB_SEND_FAIL kind=WRITE_ERR to {}: {}", onion_address, e);
+                        log::error!(
+                            "BLOB_SEND_FAIL kind=WRITE_ERR to {}: {}",
+                            redact::onion(&onion_address),
+                            e
+                        );
                     } else {
                         BLOB_FAIL_UNKNOWN.fetch_add(1, Ordering::Relaxed);
                         log::error!(
                             "BLOB_SEND_FAIL kind=UNKNOWN_ERR to {}: {}",
-                            onion_address,
+                            redact::onion(&onion_address),
                             e
                         );
                     }
@@ -4060,7 +4054,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendHttpToVoiceOnion(
 
             log::info!(
                 "Sending call signaling via HTTP POST to voice onion {} ({} bytes encrypted)",
-                onion_address,
+                redact::onion(&onion_address),
                 message_bytes.len()
             );
 
@@ -4390,25 +4384,13 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptIncomingPing(
                 log::info!("type_byte: 0x{:02x}", wire_bytes[0]);
                 log::info!(
                     "first 8 bytes: {}",
-                    wire_bytes
-                        .iter()
-                        .take(8)
-                        .map(|b| format!("{:02x}", b))
-                        .collect::<Vec<_>>()
-                        .join(" ")
+                    redact::key(&wire_bytes[..wire_bytes.len().min(8)])
                 );
                 if wire_bytes.len() > 1 {
                     log::info!("second byte: 0x{:02x}", wire_bytes[1]);
                 }
                 if wire_bytes[0] == 0x01 && wire_bytes.len() >= 5 {
-                    log::info!(
-                        "PING pubkey_first4: {}",
-                        wire_bytes[1..5]
-                            .iter()
-                            .map(|b| format!("{:02x}", b))
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                    );
+                    log::info!("PING pubkey_first4: {}", redact::key(&wire_bytes[1..5]));
                 }
             }
             log::info!("");
@@ -4441,46 +4423,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptIncomingPing(
             );
 
             // Get KeyManager to access our X25519 private key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our X25519 private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get X25519 private key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our X25519 private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get X25519 private key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Derive shared secret using X25519 ECDH
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -4689,46 +4654,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_respondToPing(
             // For now, we'll derive shared secret using the same approach as in sendPing
 
             // Get KeyManager for our keys
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our signing private key
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get signing key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our signing private key
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get signing key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Create Ed25519 keypair
             let recipient_keypair = match ed25519_dalek::SigningKey::from_bytes(
                 &our_signing_private.as_slice().try_into().unwrap(),
@@ -4736,95 +4684,44 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_respondToPing(
                 signing_key => ed25519_dalek::SigningKey::from(signing_key),
             };
 
-            // Create PongToken
-            let pong_token =
-                match crate::network::PongToken::new(&ping_token, &recipient_keypair, true) {
-                    Ok(token) => token,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to create Pong: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
-            // Serialize PongToken
-            let pong_bytes = match pong_token.to_bytes() {
-                Ok(bytes) => bytes,
+            // Get our X25519 private key for encryption
+            let our_x25519_private: [u8; 32] = match key_manager
+                .encryption_private_key(&mut env)
+                .map_err(|e| e.to_string())
+                .and_then(|k| {
+                    k.as_slice()
+                        .try_into()
+                        .map_err(|_| "Invalid X25519 private key size".to_string())
+                }) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to serialize Pong: {}", e),
+                        format!("Failed to get X25519 private key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            log::info!(
-                "Pong created successfully for Ping {}",
-                hex::encode(&ping_token.nonce)
-            );
-
-            // Get our X25519 private key for encryption
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get X25519 private key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
-            // Get sender's X25519 public key from PingToken (now properly included)
-            let sender_x25519_pubkey = &ping_token.sender_x25519_pubkey;
-
-            // Derive shared secret
-            let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
-                &our_x25519_private,
-                sender_x25519_pubkey,
-            ) {
-                Ok(secret) => secret,
+            // Build, sign and encrypt the Pong
+            let identity =
+                crate::network::wake::WakeIdentity::new(recipient_keypair, our_x25519_private);
+            let reply = match crate::network::wake::respond_to_wake(&identity, &ping_token, true) {
+                Ok(reply) => reply,
                 Err(e) => {
-                    let _ =
-                        env.throw_new("java/lang/RuntimeException", format!("ECDH failed: {}", e));
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to create Pong: {}", e),
+                    );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Encrypt Pong token
-            let encrypted_pong =
-                match crate::crypto::encryption::encrypt_message(&pong_bytes, &shared_secret) {
-                    Ok(ciphertext) => ciphertext,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Pong encryption failed: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
-            // Get our X25519 public key to prepend
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get our X25519 pubkey: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            log::info!("Pong created successfully for Ping {}", reply.ping_id);
 
             // Wire format: [Our X25519 Public Key - 32 bytes][Encrypted Pong Token]
-            let mut wire_message = Vec::new();
-            wire_message.extend_from_slice(&our_x25519_public);
-            wire_message.extend_from_slice(&encrypted_pong);
+            // (send_pong_response adds the type byte)
+            let wire_message = reply.payload().to_vec();
 
             log::info!("Encrypted Pong: {} bytes wire message", wire_message.len());
 
@@ -4892,46 +4789,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptIncomingPong(
             );
 
             // Get KeyManager for our X25519 private key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our X25519 private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get encryption key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // Get our X25519 private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -5083,51 +4963,34 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
 
             log::info!(
                 "Sending message to {} ({} bytes)",
-                recipient_onion_str,
+                redact::onion(&recipient_onion_str),
                 message_bytes.len()
             );
 
             // Get KeyManager for our keys
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return 0;
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our signing private key (Ed25519)
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get signing key: {}", e),
                     );
                     return 0;
                 }
             };
 
-            // Get our signing private key (Ed25519)
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get signing key: {}", e),
-                        );
-                        return 0;
-                    }
-                };
-
             // Create Ed25519 signing keypair
             let sender_keypair = ed25519_dalek::SigningKey::from_bytes(
                 &our_signing_private.as_slice().try_into().unwrap(),
@@ -5147,17 +5010,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
             };
 
             // Get our X25519 public key (needed for PingToken)
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get our X25519 pubkey: {}", e),
-                        );
-                        return 0;
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get our X25519 pubkey: {}", e),
+                    );
+                    return 0;
+                }
+            };
 
             // Convert X25519 keys to fixed-size arrays
             let sender_x25519_pubkey: [u8; 32] = match our_x25519_public.as_slice().try_into() {
@@ -5212,17 +5074,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
             };
 
             // Get our X25519 encryption private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return 0;
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get encryption key: {}", e),
+                    );
+                    return 0;
+                }
+            };
 
             // Derive shared secret for Ping encryption
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
@@ -5251,17 +5112,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
                 };
 
             // Get our X25519 public key to prepend
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get our X25519 pubkey: {}", e),
-                        );
-                        return 0;
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get our X25519 pubkey: {}", e),
+                    );
+                    return 0;
+                }
+            };
 
             // Wire format for Ping: [Type Byte 0x01][Our X25519 Public Key - 32 bytes][Encrypted Ping Token]
             let mut ping_wire_message = Vec::new();
@@ -5279,32 +5139,47 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
                 return 0;
             }
 
-            const MESSAGE_PORT: u16 = 9150;
+            let message_port = crate::network::ports().messaging;
 
             let result = GLOBAL_RUNTIME.block_on(async {
+            use crate::network::delivery::{DeliveryFailure, DeliveryStage};
+
             // Connect to recipient's .onion address (lock only during connect)
+            let handshake_started = std::time::Instant::now();
             let mut conn = {
                 let manager = tor_manager.lock().unwrap();
-                manager.connect(&recipient_onion_str, MESSAGE_PORT).await?
+                manager
+                    .connect(&recipient_onion_str, message_port)
+                    .await
+                    .map_err(DeliveryFailure::from_connect_error)?
             }; // Lock released here - allows concurrent operations
 
             // Send Ping (lock only during send)
             {
                 let manager = tor_manager.lock().unwrap();
-                manager.send(&mut conn, &ping_wire_message).await?;
+                manager
+                    .send(&mut conn, &ping_wire_message)
+                    .await
+                    .map_err(|e| DeliveryFailure::connection_lost(DeliveryStage::Ping, e))?;
             } // Lock released here
             log::info!("Ping sent, waiting for Pong response...");
 
             // Wait for encrypted Pong response (with timeout)
             // No lock needed - TorConnection owns the socket
             let pong_response = tokio::time::timeout(
-                std::time::Duration::from_secs(60),
+                crate::network::timeout_policy().message_delivery,
                 conn.receive()
-            ).await.map_err(|_| "Pong timeout")??;
+            )
+            .await
+            .map_err(|_| DeliveryFailure::Timeout { stage: DeliveryStage::Pong })?
+            .map_err(|e| DeliveryFailure::connection_lost(DeliveryStage::Pong, e))?;
 
             // Verify Pong (decrypt and check authentication)
             if pong_response.len() < 2 {
-                return Err("Invalid Pong response".into());
+                return Err(DeliveryFailure::handshake_rejected(
+                    DeliveryStage::Pong,
+                    "Invalid Pong response",
+                ));
             }
 
             // Extract type byte
@@ -5313,30 +5188,44 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
                 if type_byte == crate::network::tor::MSG_TYPE_DELIVERY_CONFIRMATION {
                     log::warn!("Received PING_ACK (0x06) when expecting PONG - ignoring (should go to port 9153)");
                     log::warn!("→ This is a bug - PING_ACK was sent to wrong port. Continuing without instant pong.");
-                    return Err("PING_ACK received instead of PONG".into());
+                    return Err(DeliveryFailure::handshake_rejected(
+                        DeliveryStage::Pong,
+                        "PING_ACK received instead of PONG",
+                    ));
                 }
                 log::warn!("Expected PONG (0x02) but got type 0x{:02x}", type_byte);
-                return Err(format!("Wrong message type: expected PONG, got 0x{:02x}", type_byte).into());
+                return Err(DeliveryFailure::handshake_rejected(
+                    DeliveryStage::Pong,
+                    format!("Wrong message type: expected PONG, got 0x{:02x}", type_byte),
+                ));
             }
 
             // Encrypted Pong starts after type byte
             let encrypted_pong = &pong_response[1..];
 
             // Decrypt Pong
-            let decrypted_pong = crate::crypto::encryption::decrypt_message(encrypted_pong, &shared_secret)?;
+            let decrypted_pong = crate::crypto::encryption::decrypt_message(encrypted_pong, &shared_secret)
+                .map_err(|e| DeliveryFailure::handshake_rejected(DeliveryStage::Pong, format!("Pong decryption failed: {}", e)))?;
 
             // Parse Pong token
-            let pong_token = crate::network::PongToken::from_bytes(&decrypted_pong)?;
+            let pong_token = crate::network::PongToken::from_bytes(&decrypted_pong)
+                .map_err(|e| DeliveryFailure::handshake_rejected(DeliveryStage::Pong, format!("Malformed Pong: {}", e)))?;
 
             // Verify Pong signature and check authentication
-            pong_token.verify(&recipient_ed25519_verifying)?;
+            pong_token
+                .verify(&recipient_ed25519_verifying)
+                .map_err(|e| DeliveryFailure::handshake_rejected(DeliveryStage::Pong, format!("Pong verification failed: {}", e)))?;
 
             if !pong_token.authenticated {
                 log::warn!("Recipient declined message (not authenticated)");
-                return Err("Recipient declined message".into());
+                return Err(DeliveryFailure::RecipientDeclined);
             }
 
             log::info!("Pong received and authenticated! Sending message...");
+            crate::network::health::record_handshake(
+                &recipient_onion_str,
+                u32::try_from(handshake_started.elapsed().as_millis()).unwrap_or(u32::MAX),
+            );
 
             // Step 3: Send encrypted message (lock only during send)
             // Wire format for message: [Type Byte][Sender X25519 Public Key][Encrypted Message]
@@ -5348,25 +5237,30 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDirectMessage(
 
             {
                 let manager = tor_manager.lock().unwrap();
-                manager.send(&mut conn, &message_wire).await?;
+                manager
+                    .send(&mut conn, &message_wire)
+                    .await
+                    .map_err(|e| DeliveryFailure::connection_lost(DeliveryStage::Send, e))?;
             } // Lock released here
 
             log::info!("Message sent successfully ({} bytes)", message_bytes.len());
 
-            Ok::<(), Box<dyn std::error::Error>>(())
+            Ok::<(), DeliveryFailure>(())
         });
 
             match result {
                 Ok(_) => {
                     log::info!("Message delivery complete");
+                    crate::network::delivery::record_delivered(&recipient_onion_str);
                     1 // true
                 }
-                Err(e) => {
-                    log::error!("Message delivery failed: {}", e);
+                Err(failure) => {
+                    log::error!("Message delivery failed: {}", failure);
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Message send failed: {}", e),
+                        format!("Message send failed: {}", failure),
                     );
+                    crate::network::delivery::record_failure(&recipient_onion_str, failure);
                     0 // false
                 }
             }
@@ -5809,57 +5703,39 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPingToken(
             };
 
             // 2. Get KeyManager instance
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // 3. Get our signing keys from KeyStore
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get signing key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // 3. Get our signing keys from KeyStore
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get signing key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
-            let our_signing_public =
-                match crate::ffi::keystore::get_signing_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get public key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_signing_public = match key_manager.signing_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get public key: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // 4. Create Ed25519 keypair for signing
             let sender_keypair = match ed25519_dalek::SigningKey::from_bytes(
@@ -5882,17 +5758,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPingToken(
             };
 
             // Get our X25519 public key (needed for PingToken)
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get our X25519 pubkey: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get our X25519 pubkey: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // Convert X25519 keys to fixed-size arrays
             let sender_x25519_pubkey: [u8; 32] = match our_x25519_public.as_slice().try_into() {
@@ -5948,17 +5823,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPingToken(
             };
 
             // 7. Get our X25519 encryption key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get encryption key: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // 8. Derive shared secret using X25519 ECDH
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
@@ -6029,46 +5903,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_nativeDecryptPingToken
             };
 
             // 2. Get KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // 3. Get our X25519 private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get encryption key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // 3. Get our X25519 private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // 4. Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -6237,46 +6094,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPongToken(
             let ping_token = stored_session.ping_token;
 
             // 3. Get KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // 4. Get our signing keys
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get signing key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // 4. Get our signing keys
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get signing key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // 5. Create Ed25519 keypair for signing
             let recipient_keypair = match ed25519_dalek::SigningKey::from_bytes(
                 &our_signing_private.as_slice().try_into().unwrap(),
@@ -6310,17 +6150,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPongToken(
             };
 
             // 8. Get our X25519 encryption key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to get encryption key: {}", e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            };
 
             // 9. Derive shared secret using X25519 ECDH (with sender's X25519 public key)
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
@@ -6404,46 +6243,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_nativeDecryptPongToken
                 };
 
             // 2. Get KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
+                        format!("Failed to get KeyManager: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // 3. Get our X25519 private key
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
-                        format!("Failed to get KeyManager: {}", e),
+                        format!("Failed to get encryption key: {}", e),
                     );
                     return std::ptr::null_mut();
                 }
             };
 
-            // 3. Get our X25519 private key
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        let _ = env.throw_new(
-                            "java/lang/RuntimeException",
-                            format!("Failed to get encryption key: {}", e),
-                        );
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // 4. Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -7364,6 +7186,16 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptMessageWithEvol
                     }
                 }
                 Err(e) => {
+                    if let crate::crypto::encryption::EncryptionError::ReplayAttack { .. } = e {
+                        // No contact at this layer; the app can attribute it
+                        // with reportDecryptionFailure
+                        crate::network::security_events::raise(
+                            crate::network::security_events::SecurityEventKind::ReplayDetected {
+                                detail: e.to_string(),
+                            },
+                            None,
+                        );
+                    }
                     let _ = env.throw_new(
                         "java/lang/RuntimeException",
                         format!("Decryption failed: {}", e),
@@ -7479,11 +7311,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptWithMessageKey(
 
 // ==================== PROTOCOL SECURITY FIXES (FIX #6, #7, #9) ====================
 
-use crate::crypto::ack_state::{reset_ack_state, validate_and_record_ack};
-use crate::crypto::encryption::{
-    commit_ratchet_advancement, encrypt_message_deferred, rollback_ratchet_advancement,
-    store_pending_ratchet_advancement,
-};
+use crate::crypto::encryption::encrypt_message_deferred;
 use crate::crypto::replay_cache::check_ping_replay;
 
 /// FIX #6: Encrypt message with deferred ratchet commitment (Phase 1)
@@ -7575,8 +7403,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_storePendingRatchetAdv
     catch_panic!(
         env,
         {
-            let contact_id_str = match jstring_to_string(&mut env, contact_id) {
-                Ok(s) => s,
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
                 Err(_) => return 0,
             };
 
@@ -7597,13 +7425,17 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_storePendingRatchetAdv
             let mut chain_key_array = [0u8; 32];
             chain_key_array.copy_from_slice(&chain_key_vec);
 
-            match store_pending_ratchet_advancement(
-                &contact_id_str,
+            match active_context().store_pending_ratchet(
+                &contact_id,
                 &message_id_str,
                 chain_key_array,
                 next_sequence as u64,
             ) {
                 Ok(_) => 1,
+                Err(crate::crypto::encryption::EncryptionError::IdentityKeyChanged) => {
+                    let _ = env.throw_new("java/lang/SecurityException", "IDENTITY_KEY_CHANGED");
+                    0
+                }
                 Err(_) => 0,
             }
         },
@@ -7622,16 +7454,24 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_commitRatchetAdvanceme
     catch_panic!(
         env,
         {
-            let contact_id_str = match jstring_to_string(&mut env, contact_id) {
-                Ok(s) => s,
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
                 Err(e) => {
                     let _ = env.throw_new("java/lang/IllegalArgumentException", e);
                     return std::ptr::null_mut();
                 }
             };
 
-            match commit_ratchet_advancement(&contact_id_str) {
-                Ok(Some((next_key, next_seq))) => {
+            let committed = active_context()
+                .pending_ratchets
+                .lock()
+                .unwrap()
+                .commit(&contact_id);
+            match committed {
+                Some((next_key, next_seq)) => {
+                    if let Err(e) = crate::network::send_intents::acknowledge(&contact_id) {
+                        log::error!("Failed to log send acknowledgement: {}", e);
+                    }
                     let json = serde_json::json!({
                         "nextChainKey": base64::encode(&next_key),
                         "nextSequence": next_seq
@@ -7642,14 +7482,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_commitRatchetAdvanceme
                         Err(_) => std::ptr::null_mut(),
                     }
                 }
-                Ok(None) => std::ptr::null_mut(),
-                Err(e) => {
-                    let _ = env.throw_new(
-                        "java/lang/RuntimeException",
-                        format!("Commit failed: {}", e),
-                    );
-                    std::ptr::null_mut()
-                }
+                None => std::ptr::null_mut(),
             }
         },
         std::ptr::null_mut()
@@ -7666,121 +7499,364 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_rollbackRatchetAdvance
     catch_panic!(
         env,
         {
-            let contact_id_str = match jstring_to_string(&mut env, contact_id) {
-                Ok(s) => s,
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
                 Err(_) => return 0,
             };
 
-            match rollback_ratchet_advancement(&contact_id_str) {
-                Ok(_) => 1,
-                Err(_) => 0,
+            active_context()
+                .pending_ratchets
+                .lock()
+                .unwrap()
+                .rollback(&contact_id);
+            if let Err(e) = crate::network::send_intents::abandon(&contact_id) {
+                log::error!("Failed to drop send intent: {}", e);
             }
+            1
         },
         0
     )
 }
 
-/// FIX #9: Check if PING is a replay attack
-/// Returns true if PING should be processed (not a replay)
-/// Returns false if PING is a duplicate (should be dropped)
+// ==================== SEND INTENTS ====================
+
+/// Open the send intent log in `dir`, sealed under a 32-byte key. Call at
+/// startup before the first send.
+/// Returns: [{"type":"advanceRatchet","contactId":"sl_…","nextSequence":n}|
+///           {"type":"resend","intentId":n,"contactId":"sl_…","messageId":"…","ciphertext":"base64"}|
+///           {"type":"markDelivered","intentId":n,"contactId":"sl_…","messageId":"…"},...]
 #[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_checkPingReplay(
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_openSendIntentLog(
     mut env: JNIEnv,
     _class: JClass,
-    sender_pubkey: JByteArray,
-    ping_bytes: JByteArray,
-) -> jboolean {
+    dir: JString,
+    key: JByteArray,
+) -> jstring {
     catch_panic!(
         env,
         {
-            let pubkey_vec = match jbytearray_to_vec(&mut env, sender_pubkey) {
-                Ok(v) => v,
-                Err(_) => return 0,
+            let dir = match jstring_to_string(&mut env, dir) {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return std::ptr::null_mut();
+                }
             };
-
-            let ping_vec = match jbytearray_to_vec(&mut env, ping_bytes) {
-                Ok(v) => v,
-                Err(_) => return 0,
+            let key: [u8; 32] = match jbytearray_to_vec(&mut env, key)
+                .ok()
+                .and_then(|v| v.try_into().ok())
+            {
+                Some(k) => k,
+                None => {
+                    let _ = env.throw_new(
+                        "java/lang/IllegalArgumentException",
+                        "Intent log key must be 32 bytes",
+                    );
+                    return std::ptr::null_mut();
+                }
             };
 
-            if pubkey_vec.len() != 32 {
-                return 0;
-            }
-
-            let mut pubkey = [0u8; 32];
-            pubkey.copy_from_slice(&pubkey_vec);
-
-            let ping_hash = blake3::hash(&ping_vec);
-            let ping_hash_array: [u8; 32] = *ping_hash.as_bytes();
-
-            if check_ping_replay(pubkey, ping_hash_array) {
-                1 // Not a replay - process it
-            } else {
-                0 // Replay detected - drop it
+            let actions = crate::storage::FileIntentStore::open(dir, &key)
+                .map_err(crate::storage::IntentLogError::from)
+                .map_err(crate::network::send_intents::SendIntentError::from)
+                .and_then(|store| crate::network::send_intents::open(Box::new(store)));
+            match actions {
+                Ok(actions) => {
+                    let json: Vec<_> = actions
+                        .iter()
+                        .map(crate::network::send_intents::action_json)
+                        .collect();
+                    match string_to_jstring(&mut env, &serde_json::Value::from(json).to_string())
+                    {
+                        Ok(s) => s.into_raw(),
+                        Err(e) => {
+                            log::error!("Failed to create JSON string: {}", e);
+                            std::ptr::null_mut()
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Failed to open send intent log: {}", e),
+                    );
+                    std::ptr::null_mut()
+                }
             }
         },
-        0
+        std::ptr::null_mut()
     )
 }
 
-/// FIX #7: Validate ACK ordering according to protocol rules
-/// @param contactId Contact identifier
-/// @param ackType 0=PING_ACK, 1=PONG_ACK, 2=MESSAGE_ACK
-/// @return true if ACK is valid, false if it violates ordering
+/// Log a ciphertext encrypted at `sequence` before it is sent. False if it
+/// could not be logged; throws SecurityException "BELOW_RECOVERED_FLOOR" if
+/// the chain was not fast-forwarded after a crash (the key may be reused).
 #[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_validateAckOrdering(
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_recordSendIntent(
     mut env: JNIEnv,
     _class: JClass,
     contact_id: JString,
-    ack_type: jint,
+    message_id: JString,
+    sequence: jlong,
+    ciphertext: JByteArray,
 ) -> jboolean {
     catch_panic!(
         env,
         {
-            let contact_id_str = match jstring_to_string(&mut env, contact_id) {
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(_) => return 0,
+            };
+            let message_id = match jstring_to_string(&mut env, message_id) {
                 Ok(s) => s,
                 Err(_) => return 0,
             };
-
-            let ack_type_u8 = match ack_type {
-                0 | 1 | 2 => ack_type as u8,
-                _ => return 0,
+            let ciphertext = match jbytearray_to_vec(&mut env, ciphertext) {
+                Ok(v) => v,
+                Err(_) => return 0,
             };
 
-            if validate_and_record_ack(&contact_id_str, ack_type_u8) {
-                1 // Valid
-            } else {
-                0 // Invalid
+            match crate::network::send_intents::record_send(
+                &contact_id,
+                &message_id,
+                sequence as u64,
+                &ciphertext,
+            ) {
+                Ok(()) => 1,
+                Err(crate::network::send_intents::SendIntentError::BelowFloor { .. }) => {
+                    let _ = env.throw_new("java/lang/SecurityException", "BELOW_RECOVERED_FLOOR");
+                    0
+                }
+                Err(e) => {
+                    log::error!("Failed to log send intent: {}", e);
+                    0
+                }
             }
         },
         0
     )
 }
 
-/// FIX #7: Reset ACK state for a contact (after completing message exchange)
+/// Move a persisted send chain up to the floor recovered for the contact
+/// Returns JSON: {"chainKey": "base64", "sequence": 123}
 #[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resetAckState(
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fastForwardSendChain(
     mut env: JNIEnv,
     _class: JClass,
     contact_id: JString,
-) {
+    chain_key: JByteArray,
+    sequence: jlong,
+) -> jstring {
     catch_panic!(
         env,
         {
-            let contact_id_str = match jstring_to_string(&mut env, contact_id) {
-                Ok(s) => s,
-                Err(_) => return,
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let chain_key: [u8; 32] = match jbytearray_to_vec(&mut env, chain_key)
+                .ok()
+                .and_then(|v| v.try_into().ok())
+            {
+                Some(k) => k,
+                None => {
+                    let _ = env.throw_new(
+                        "java/lang/IllegalArgumentException",
+                        "Chain key must be 32 bytes",
+                    );
+                    return std::ptr::null_mut();
+                }
             };
 
-            reset_ack_state(&contact_id_str);
-        },
-        ()
-    )
-}
-
-// ==================== END PROTOCOL SECURITY FIXES ====================
-
-// ==================== DELIVERY ACK (CONFIRMATION) ====================
+            match crate::network::send_intents::fast_forward(
+                &contact_id,
+                chain_key,
+                sequence as u64,
+            ) {
+                Ok((key, seq)) => {
+                    let json = serde_json::json!({
+                        "chainKey": base64::encode(&key),
+                        "sequence": seq
+                    });
+                    match string_to_jstring(&mut env, &json.to_string()) {
+                        Ok(s) => s.into_raw(),
+                        Err(_) => std::ptr::null_mut(),
+                    }
+                }
+                Err(e) => {
+                    let _ = env.throw_new(
+                        "java/lang/RuntimeException",
+                        format!("Fast-forward failed: {}", e),
+                    );
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// The chain returned by commitRatchetAdvancement has been persisted
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendChainPersisted(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(_) => return 0,
+            };
+            match crate::network::send_intents::persisted(&contact_id) {
+                Ok(()) => 1,
+                Err(e) => {
+                    log::error!("Failed to drop commit intent: {}", e);
+                    0
+                }
+            }
+        },
+        0
+    )
+}
+
+/// A recovered resend was acknowledged, or a recovered delivered mark stored
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_completeSendIntent(
+    mut env: JNIEnv,
+    _class: JClass,
+    intent_id: jlong,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match crate::network::send_intents::complete(intent_id as u64) {
+                Ok(()) => 1,
+                Err(e) => {
+                    log::error!("Failed to complete send intent: {}", e);
+                    0
+                }
+            }
+        },
+        0
+    )
+}
+
+/// FIX #9: Check if PING is a replay attack
+/// Returns true if PING should be processed (not a replay)
+/// Returns false if PING is a duplicate (should be dropped)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_checkPingReplay(
+    mut env: JNIEnv,
+    _class: JClass,
+    sender_pubkey: JByteArray,
+    ping_bytes: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let pubkey_vec = match jbytearray_to_vec(&mut env, sender_pubkey) {
+                Ok(v) => v,
+                Err(_) => return 0,
+            };
+
+            let ping_vec = match jbytearray_to_vec(&mut env, ping_bytes) {
+                Ok(v) => v,
+                Err(_) => return 0,
+            };
+
+            if pubkey_vec.len() != 32 {
+                return 0;
+            }
+
+            let mut pubkey = [0u8; 32];
+            pubkey.copy_from_slice(&pubkey_vec);
+
+            let ping_hash = blake3::hash(&ping_vec);
+            let ping_hash_array: [u8; 32] = *ping_hash.as_bytes();
+
+            if check_ping_replay(pubkey, ping_hash_array) {
+                1 // Not a replay - process it
+            } else {
+                0 // Replay detected - drop it
+            }
+        },
+        0
+    )
+}
+
+/// FIX #7: Validate ACK ordering according to protocol rules
+/// @param contactId Canonical contact id (sl_…)
+/// @param ackType 0=PING_ACK, 1=PONG_ACK, 2=MESSAGE_ACK
+/// @return true if ACK is valid, false if it violates ordering
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_validateAckOrdering(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    ack_type: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(_) => return 0,
+            };
+
+            let ack_type_u8 = match ack_type {
+                0 | 1 | 2 => ack_type as u8,
+                _ => return 0,
+            };
+
+            let valid = active_context()
+                .ack_states
+                .lock()
+                .unwrap()
+                .validate_and_record(&contact_id, ack_type_u8);
+            if valid {
+                1 // Valid
+            } else {
+                0 // Invalid
+            }
+        },
+        0
+    )
+}
+
+/// FIX #7: Reset ACK state for a contact (after completing message exchange)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_resetAckState(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) {
+    catch_panic!(
+        env,
+        {
+            let contact_id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(_) => return,
+            };
+
+            active_context()
+                .ack_states
+                .lock()
+                .unwrap()
+                .reset(&contact_id);
+        },
+        ()
+    )
+}
+
+// ==================== END PROTOCOL SECURITY FIXES ====================
+
+// ==================== DELIVERY ACK (CONFIRMATION) ====================
 
 /// Send a delivery ACK (confirmation) to recipient
 /// ack_type: "PING_ACK" or "MESSAGE_ACK"
@@ -7845,41 +7921,27 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDeliveryAck(
             log::info!("SENDING DELIVERY ACK");
             log::info!("Item ID: {}", item_id_str);
             log::info!("ACK Type: {}", ack_type_str);
-            log::info!("Recipient: {}", recipient_onion_str);
+            log::info!("Recipient: {}", redact::onion(&recipient_onion_str));
             log::info!("");
 
             // Get KeyManager for our keys
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return 0;
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            // Get our signing private key (Ed25519)
+            let our_signing_private = match key_manager.signing_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+                    log::error!("Failed to get signing key: {}", e);
                     return 0;
                 }
             };
 
-            // Get our signing private key (Ed25519)
-            let our_signing_private =
-                match crate::ffi::keystore::get_signing_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get signing key: {}", e);
-                        return 0;
-                    }
-                };
-
             // Create Ed25519 signing keypair
             let sender_keypair = ed25519_dalek::SigningKey::from_bytes(
                 &our_signing_private.as_slice().try_into().unwrap(),
@@ -7908,30 +7970,29 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDeliveryAck(
             };
 
             // Get our X25519 encryption keys
-            let our_x25519_public =
-                match crate::ffi::keystore::get_encryption_public_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get our X25519 pubkey: {}", e);
-                        return 0;
-                    }
-                };
+            let our_x25519_public = match key_manager.encryption_public_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to get our X25519 pubkey: {}", e);
+                    return 0;
+                }
+            };
 
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get encryption key: {}", e);
-                        return 0;
-                    }
-                };
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to get encryption key: {}", e);
+                    return 0;
+                }
+            };
 
             // Derive shared secret using X25519 ECDH
-            let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
+            let shared_secret = match crate::ffi::session_cache::cached_shared_secret_for_identity(
+                &recipient_ed25519_bytes,
                 &our_x25519_private,
                 &recipient_x25519_bytes,
             ) {
-                Ok(secret) => secret,
+                Ok(secret) => *secret,
                 Err(e) => {
                     log::error!("ECDH failed: {}", e);
                     return 0;
@@ -7966,11 +8027,11 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendDeliveryAck(
             let tor_manager = get_tor_manager();
             let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
-            const ACK_PORT: u16 = 9153;
+            let ack_port = crate::network::ports().ack_port();
 
             let result: Result<(), Box<dyn std::error::Error>> = runtime.block_on(async {
                 let manager = tor_manager.lock().unwrap();
-                let mut conn = manager.connect(&recipient_onion_str, ACK_PORT).await?;
+                let mut conn = manager.connect(&recipient_onion_str, ack_port).await?;
                 manager.send(&mut conn, &wire_message).await?;
                 Ok(())
             });
@@ -8021,7 +8082,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startAckListener(
                     let (tx, rx) = mpsc::unbounded_channel::<(u64, Vec<u8>)>();
 
                     // Store receiver globally for polling
-                    let _ = GLOBAL_ACK_RECEIVER.set(Arc::new(Mutex::new(rx)));
+                    let _ = active_context().ack_receiver.set(Arc::new(Mutex::new(rx)));
 
                     // Store sender globally so port 8080 can route misrouted ACKs
                     let _ = crate::network::tor::ACK_TX
@@ -8070,7 +8131,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingAck(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_ACK_RECEIVER.get() {
+            if let Some(receiver) = active_context().ack_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -8137,41 +8198,27 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptAndStoreAckFrom
             log::info!(
                 "Decrypting ACK: offset={}, sender_x25519={}, encrypted_len={}",
                 OFFSET,
-                hex::encode(&sender_x25519_pubkey),
+                redact::key(&sender_x25519_pubkey),
                 encrypted_ack.len()
             );
 
             // Get our X25519 private key from KeyManager
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
                 Err(e) => {
-                    log::error!("Failed to get context: {}", e);
+                    log::error!("Failed to get KeyManager: {}", e);
                     return std::ptr::null_mut();
                 }
             };
 
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
-                Ok(km) => km,
+            let our_x25519_private = match key_manager.encryption_private_key(&mut env) {
+                Ok(k) => k,
                 Err(e) => {
-                    log::error!("Failed to get KeyManager: {}", e);
+                    log::error!("Failed to get encryption private key: {}", e);
                     return std::ptr::null_mut();
                 }
             };
 
-            let our_x25519_private =
-                match crate::ffi::keystore::get_encryption_private_key(&mut env, &key_manager) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        log::error!("Failed to get encryption private key: {}", e);
-                        return std::ptr::null_mut();
-                    }
-                };
-
             // Derive shared secret
             let shared_secret = match crate::crypto::key_exchange::derive_shared_secret(
                 &our_x25519_private,
@@ -8423,6 +8470,39 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_cleanupExpiredSessions
     crate::network::cleanup_expired_acks();
 }
 
+// ==================== MAINTENANCE ====================
+
+/// Run one background maintenance pass (call from a periodic WorkManager job)
+/// Expires stale sessions and lists loaded groups due for `crdtCompactOps`
+/// Returns the report as JSON (see `maintenance::MaintenanceReport`)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_runMaintenance(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let report = crate::maintenance::run(
+                now,
+                crate::maintenance::MaintenanceTargets {
+                    group_sizes: crate::ffi::crdt::loaded_group_sizes(),
+                    ..Default::default()
+                },
+            );
+            let json = serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string());
+            string_to_jstring(&mut env, &json)
+                .map(|s| s.into_raw())
+                .unwrap_or(std::ptr::null_mut())
+        },
+        std::ptr::null_mut()
+    )
+}
+
 // ==================== NLx402 PAYMENT PROTOCOL ====================
 
 /// Create a payment quote for NLx402 protocol
@@ -8769,23 +8849,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createFriendRequestHid
             );
 
             // Get KeyManager to retrieve friend request key
-            let context = match env.call_static_method(
-                "android/app/ActivityThread",
-                "currentApplication",
-                "()Landroid/app/Application;",
-                &[],
-            ) {
-                Ok(ctx) => ctx.l().unwrap(),
-                Err(e) => {
-                    let _ = env.throw_new(
-                        "java/lang/RuntimeException",
-                        format!("Failed to get context: {}", e),
-                    );
-                    return std::ptr::null_mut();
-                }
-            };
-
-            let key_manager = match crate::ffi::keystore::get_key_manager(&mut env, &context) {
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
                 Ok(km) => km,
                 Err(e) => {
                     let _ = env.throw_new(
@@ -8797,10 +8861,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createFriendRequestHid
             };
 
             // Get friend request private key (derived from seed with domain separation)
-            let fr_private_key = match crate::ffi::keystore::get_friend_request_private_key(
-                &mut env,
-                &key_manager,
-            ) {
+            let fr_private_key = match key_manager.friend_request_private_key(&mut env) {
                 Ok(k) => k,
                 Err(e) => {
                     let _ = env.throw_new(
@@ -8825,7 +8886,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createFriendRequestHid
                 Ok(onion_address) => {
                     log::info!(
                         "Friend request hidden service created successfully: {}",
-                        onion_address
+                        redact::onion(&onion_address)
                     );
 
                     match string_to_jstring(&mut env, &onion_address) {
@@ -9016,7 +9077,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollFriendRequest(
     catch_panic!(
         env,
         {
-            if let Some(receiver) = GLOBAL_FRIEND_REQUEST_RECEIVER.get() {
+            if let Some(receiver) = active_context().friend_request_receiver.get() {
                 let mut rx = receiver.lock().unwrap();
 
                 // Try to receive without blocking
@@ -9660,7 +9721,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createVoiceSession(
                 "Creating voice session with {} circuits for call: {} to {}",
                 num_circuits_usize,
                 call_id_str,
-                peer_onion_str
+                redact::onion(&peer_onion_str)
             );
 
             let voice_listener = get_voice_listener();
@@ -10239,7 +10300,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPingBlocki
     catch_panic!(
         env,
         {
-            match blocking_recv_pair(&GLOBAL_PING_RECEIVER, 5) {
+            match blocking_recv_pair(&active_context().ping_receiver, 5) {
                 Some((connection_id, ping_bytes)) => {
                     if ping_bytes.is_empty() {
                         log::error!("FRAMING_VIOLATION: pollIncomingPingBlocking got empty buffer, conn_id={}", connection_id);
@@ -10247,12 +10308,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPingBlocki
                     }
                     const MSG_TYPE_PING: u8 = 0x01;
                     if ping_bytes[0] != MSG_TYPE_PING {
-                        let head_hex: String = ping_bytes
-                            .iter()
-                            .take(8)
-                            .map(|b| format!("{:02x}", b))
-                            .collect::<Vec<_>>()
-                            .join("");
+                        let head_hex = redact::key(&ping_bytes[..ping_bytes.len().min(8)]);
                         log::error!("FRAMING_VIOLATION: pollIncomingPingBlocking got type=0x{:02x} expected=0x{:02x} conn_id={} len={} head={}",
                         ping_bytes[0], MSG_TYPE_PING, connection_id, ping_bytes.len(), head_hex);
                         return std::ptr::null_mut();
@@ -10281,7 +10337,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingMessageBlo
     catch_panic!(
         env,
         {
-            match blocking_recv_pair(&GLOBAL_MESSAGE_RECEIVER, 5) {
+            match blocking_recv_pair(&active_context().message_receiver, 5) {
                 Some((connection_id, message_bytes)) => {
                     let mut encoded = Vec::new();
                     encoded.extend_from_slice(&connection_id.to_le_bytes());
@@ -10307,7 +10363,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollVoiceMessageBlocki
     catch_panic!(
         env,
         {
-            match blocking_recv_pair(&GLOBAL_VOICE_RECEIVER, 5) {
+            match blocking_recv_pair(&active_context().voice_receiver, 5) {
                 Some((connection_id, message_bytes)) => {
                     let mut encoded = Vec::new();
                     encoded.extend_from_slice(&connection_id.to_le_bytes());
@@ -10333,7 +10389,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingTapBlockin
     catch_panic!(
         env,
         {
-            match blocking_recv_vec(&GLOBAL_TAP_RECEIVER, 5) {
+            match blocking_recv_vec(&active_context().tap_receiver, 5) {
                 Some(tap_bytes) => {
                     log::info!("Polled tap (blocking): {} bytes", tap_bytes.len());
                     match vec_to_jbytearray(&mut env, &tap_bytes) {
@@ -10357,7 +10413,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPongBlocki
     catch_panic!(
         env,
         {
-            match blocking_recv_pair(&GLOBAL_PONG_RECEIVER, 5) {
+            match blocking_recv_pair(&active_context().pong_receiver, 5) {
                 Some((conn_id, pong_bytes)) => {
                     if pong_bytes.is_empty() {
                         log::error!("FRAMING_VIOLATION: pollIncomingPongBlocking got empty buffer, conn_id={}", conn_id);
@@ -10365,12 +10421,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingPongBlocki
                     }
                     const MSG_TYPE_PONG: u8 = 0x02;
                     if pong_bytes[0] != MSG_TYPE_PONG {
-                        let head_hex: String = pong_bytes
-                            .iter()
-                            .take(8)
-                            .map(|b| format!("{:02x}", b))
-                            .collect::<Vec<_>>()
-                            .join("");
+                        let head_hex = redact::key(&pong_bytes[..pong_bytes.len().min(8)]);
                         log::error!("FRAMING_VIOLATION: pollIncomingPongBlocking got type=0x{:02x} expected=0x{:02x} conn_id={} len={} head={}",
                         pong_bytes[0], MSG_TYPE_PONG, conn_id, pong_bytes.len(), head_hex);
                         return std::ptr::null_mut();
@@ -10401,7 +10452,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollIncomingAckBlockin
     catch_panic!(
         env,
         {
-            match blocking_recv_pair(&GLOBAL_ACK_RECEIVER, 5) {
+            match blocking_recv_pair(&active_context().ack_receiver, 5) {
                 Some((_conn_id, ack_bytes)) => {
                     log::info!("Polled ACK (blocking): {} bytes", ack_bytes.len());
                     match vec_to_jbytearray(&mut env, &ack_bytes) {
@@ -10425,7 +10476,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollFriendRequestBlock
     catch_panic!(
         env,
         {
-            match blocking_recv_vec(&GLOBAL_FRIEND_REQUEST_RECEIVER, 5) {
+            match blocking_recv_vec(&active_context().friend_request_receiver, 5) {
                 Some(friend_request_bytes) => {
                     log::info!(
                         "Polled friend request (blocking): {} bytes",
@@ -10632,38 +10683,261 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_detectIdentityKeyChang
     )
 }
 
-// ==================== SLEEP MODE ====================
-
-/// Enable or disable Tor sleep mode
-/// When enabled, the Rust side reduces timer sensitivity and batches events
-#[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setSleepModeEnabled(
-    _env: JNIEnv,
-    _class: JClass,
-    enabled: jboolean,
-) {
-    let is_enabled = enabled != 0;
-    crate::network::sleep_mode::set_sleep_mode_enabled(is_enabled);
-}
-
-/// Set whether the device is currently in sleep state
-/// Controls Rust-side behavior: reduced polling, batched events
-#[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setSleepActive(
-    _env: JNIEnv,
-    _class: JClass,
-    active: jboolean,
-) {
-    let is_active = active != 0;
-    crate::network::sleep_mode::set_sleep_active(is_active);
-}
-
-/// Check if sleep mode is currently active on the Rust side
+/// Derive the canonical contact id (`sl_` + 40 hex) from a contact's Ed25519 identity key
+/// All JNI calls that take a contactId expect this form
 #[no_mangle]
-pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isSleepActive(
-    _env: JNIEnv,
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_deriveContactId(
+    mut env: JNIEnv,
     _class: JClass,
-) -> jboolean {
+    ed25519_public_key: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let key = match jbytearray_to_vec(&mut env, ed25519_public_key) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert identity key: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let id = match crate::protocol::ContactId::from_identity_key(&key) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to derive contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &id.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== PSEUDONYMOUS CONVERSATIONS ====================
+
+/// Random tag for a new pseudonymous conversation (32 bytes); store it with
+/// the conversation
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_generateConversationTag(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let tag = shield_protocol::crypto::ConversationTag::generate(&mut rand::rngs::OsRng);
+            match vec_to_jbytearray(&mut env, &tag.0) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Derive the keys of one conversation's pseudonym from the master seed
+/// @param seed BIP39 seed (at least 32 bytes)
+/// @param tag 32-byte conversation tag
+/// @return [ed25519_pub:32][ed25519_sec:32][x25519_pub:32][x25519_sec:32][kem_seed:32]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_derivePseudonymKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    seed: JByteArray,
+    tag: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let mut seed = match jbytearray_to_vec(&mut env, seed) {
+                Ok(v) => v,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let Some(tag) = jbytearray_to_vec(&mut env, tag)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                seed.zeroize();
+                let _ = env.throw_new(
+                    "java/lang/IllegalArgumentException",
+                    "Conversation tag must be 32 bytes",
+                );
+                return std::ptr::null_mut();
+            };
+            let derived = shield_protocol::crypto::derive_pseudonym(
+                &seed,
+                &shield_protocol::crypto::ConversationTag(tag),
+            );
+            seed.zeroize();
+            let keys = match derived {
+                Ok(keys) => keys,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e.to_string());
+                    return std::ptr::null_mut();
+                }
+            };
+            let mut serialized = Vec::with_capacity(5 * 32);
+            serialized.extend_from_slice(&keys.signing_public);
+            serialized.extend_from_slice(keys.signing_secret());
+            serialized.extend_from_slice(&keys.x25519_public);
+            serialized.extend_from_slice(keys.x25519_secret());
+            serialized.extend_from_slice(keys.kem_seed());
+            let result = vec_to_jbytearray(&mut env, &serialized);
+            serialized.zeroize();
+            match result {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Prove to a contact that a pseudonym is ours (deliberately links the two)
+/// @param audience_pubkey the contact's Ed25519 key in that conversation
+/// @return serialized link proof, or null on invalid input
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_createPseudonymLinkProof(
+    mut env: JNIEnv,
+    _class: JClass,
+    seed: JByteArray,
+    tag: JByteArray,
+    master_private_key: JByteArray,
+    audience_pubkey: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let to_key = |v: Vec<u8>| <[u8; 32]>::try_from(v.as_slice()).ok();
+            let mut seed = jbytearray_to_vec(&mut env, seed).unwrap_or_default();
+            let tag = jbytearray_to_vec(&mut env, tag).ok().and_then(to_key);
+            let mut master = jbytearray_to_vec(&mut env, master_private_key)
+                .ok()
+                .and_then(to_key);
+            let audience = jbytearray_to_vec(&mut env, audience_pubkey)
+                .ok()
+                .and_then(to_key);
+            let proof = match (tag, master.as_ref(), audience) {
+                (Some(tag), Some(master), Some(audience)) => {
+                    shield_protocol::crypto::derive_pseudonym(
+                        &seed,
+                        &shield_protocol::crypto::ConversationTag(tag),
+                    )
+                    .map(|keys| keys.link_proof(master, &audience))
+                    .ok()
+                }
+                _ => None,
+            };
+            seed.zeroize();
+            if let Some(master) = master.as_mut() {
+                master.zeroize();
+            }
+            let Some(proof) = proof else {
+                log::error!("Invalid input for pseudonym link proof");
+                return std::ptr::null_mut();
+            };
+            match vec_to_jbytearray(&mut env, &proof.to_bytes()) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Check a link proof received from a pseudonymous contact
+/// @return the contact's master Ed25519 key, or null if the proof is invalid,
+/// for another pseudonym, or addressed to someone else
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_verifyPseudonymLinkProof(
+    mut env: JNIEnv,
+    _class: JClass,
+    proof: JByteArray,
+    pseudonym_pubkey: JByteArray,
+    our_pubkey: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let to_key = |v: Vec<u8>| <[u8; 32]>::try_from(v.as_slice()).ok();
+            let proof = jbytearray_to_vec(&mut env, proof)
+                .ok()
+                .and_then(|v| shield_protocol::crypto::LinkProof::from_bytes(&v).ok());
+            let pseudonym = jbytearray_to_vec(&mut env, pseudonym_pubkey)
+                .ok()
+                .and_then(to_key);
+            let ours = jbytearray_to_vec(&mut env, our_pubkey)
+                .ok()
+                .and_then(to_key);
+            let master = match (proof, pseudonym, ours) {
+                (Some(proof), Some(pseudonym), Some(ours)) if proof.verify(&pseudonym, &ours) => {
+                    proof.master_public
+                }
+                _ => return std::ptr::null_mut(),
+            };
+            match vec_to_jbytearray(&mut env, &master) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== SLEEP MODE ====================
+
+/// Enable or disable Tor sleep mode
+/// When enabled, the Rust side reduces timer sensitivity and batches events
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setSleepModeEnabled(
+    _env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let is_enabled = enabled != 0;
+    crate::network::sleep_mode::set_sleep_mode_enabled(is_enabled);
+}
+
+/// Set whether the device is currently in sleep state
+/// Controls Rust-side behavior: reduced polling, batched events
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setSleepActive(
+    _env: JNIEnv,
+    _class: JClass,
+    active: jboolean,
+) {
+    let is_active = active != 0;
+    crate::network::sleep_mode::set_sleep_active(is_active);
+}
+
+/// Check if sleep mode is currently active on the Rust side
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isSleepActive(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
     if crate::network::sleep_mode::is_sleep_active() {
         JNI_TRUE
     } else {
@@ -10671,6 +10945,4844 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isSleepActive(
     }
 }
 
+// ==================== NETWORK TIMEOUT & RETRY POLICY ====================
+
+/// Set the send-path timeouts (seconds)
+/// Returns false (policy unchanged) if any value is zero or negative
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setTimeoutPolicy(
+    mut env: JNIEnv,
+    _class: JClass,
+    instant_pong_secs: jlong,
+    message_delivery_secs: jlong,
+    blob_send_secs: jlong,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let secs = |v: jlong| std::time::Duration::from_secs(v.max(0) as u64);
+            let policy = crate::network::TimeoutPolicy {
+                instant_pong: secs(instant_pong_secs),
+                message_delivery: secs(message_delivery_secs),
+                blob_send: secs(blob_send_secs),
+            };
+            match crate::network::set_timeout_policy(policy) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("Rejected timeout policy: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Set the retry policy for friend request delivery
+/// Delays are in milliseconds; jitter is a fraction in [0.0, 1.0]
+/// Returns false (policy unchanged) if the policy is invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setRetryPolicy(
+    mut env: JNIEnv,
+    _class: JClass,
+    max_attempts: jint,
+    base_delay_ms: jlong,
+    max_delay_ms: jlong,
+    multiplier: jdouble,
+    jitter: jdouble,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let millis = |v: jlong| std::time::Duration::from_millis(v.max(0) as u64);
+            let policy = crate::network::RetryPolicy {
+                max_attempts: max_attempts.max(0) as u32,
+                base_delay: millis(base_delay_ms),
+                max_delay: millis(max_delay_ms),
+                multiplier,
+                jitter,
+            };
+            match crate::network::set_retry_policy(policy) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("Rejected retry policy: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Get the active timeout and retry policies as JSON
+/// Returns: {"instantPongSecs":30,"messageDeliverySecs":60,"blobSendSecs":90,"maxAttempts":12,...}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getNetworkPolicyJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let timeouts = crate::network::timeout_policy();
+            let retry = crate::network::retry_policy();
+            let json = format!(
+                r#"{{"instantPongSecs":{},"messageDeliverySecs":{},"blobSendSecs":{},"maxAttempts":{},"baseDelayMs":{},"maxDelayMs":{},"multiplier":{},"jitter":{}}}"#,
+                timeouts.instant_pong.as_secs(),
+                timeouts.message_delivery.as_secs(),
+                timeouts.blob_send.as_secs(),
+                retry.max_attempts,
+                retry.base_delay.as_millis(),
+                retry.max_delay.as_millis(),
+                retry.multiplier,
+                retry.jitter,
+            );
+
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Get per-lane outbound send metrics as JSON
+/// Returns: {"control":{"enqueued":..,"dequeued":..,"bytes":..,"depth":..,"maxWaitMs":..,"meanWaitMs":..},"receipt":{..},"text":{..},"media":{..}}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getSendLaneMetricsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::send_lanes::metrics_json();
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Get upstream bandwidth cap counters as JSON
+/// Returns: {"maxBytesPerSec":..,"sentBytes":..,"chargedBytes":..,"coverPackets":..,"coverBytes":..,"delayed":..,"queued":..}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getBandwidthStatsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = match serde_json::to_string(&crate::network::bandwidth::stats()) {
+                Ok(json) => json,
+                Err(e) => {
+                    log::error!("Failed to serialize bandwidth stats: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== FIRST-CONTACT PROOF-OF-WORK ====================
+
+/// Configure the inbound PoW stamp policy for friend requests and first pings
+/// baseDifficulty = 0 disables stamps; difficulty rises one bit per doubling of
+/// first-contact traffic above surgeThresholdPerMin, capped at maxDifficulty
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setFirstContactPowPolicy(
+    mut env: JNIEnv,
+    _class: JClass,
+    base_difficulty: jint,
+    max_difficulty: jint,
+    surge_threshold_per_min: jint,
+    memory_kib: jint,
+    iterations: jint,
+) {
+    catch_panic!(
+        env,
+        {
+            let clamp = |v: jint| v.clamp(0, u8::MAX as jint) as u8;
+            crate::network::first_contact::set_pow_policy(
+                shield_protocol::protocol::PowPolicy {
+                    base_difficulty: clamp(base_difficulty),
+                    max_difficulty: clamp(max_difficulty),
+                    surge_threshold_per_min: surge_threshold_per_min.max(1) as u32,
+                    params: shield_protocol::protocol::PowParams {
+                        memory_kib: memory_kib.max(8) as u32,
+                        iterations: iterations.max(1) as u32,
+                    },
+                },
+            );
+        },
+        ()
+    )
+}
+
+/// Get the PoW advert this device currently publishes at GET /pow-policy
+/// Returns: {"difficulty":0,"params":{"memory_kib":4096,"iterations":1}}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getFirstContactPowAdvert(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::first_contact::current_advert()
+                .to_json()
+                .unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Mark a contact's X25519 public key as trusted (exempt from PoW stamps)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_addTrustedSender(
+    mut env: JNIEnv,
+    _class: JClass,
+    x25519_public_key: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match jbytearray_to_vec(&mut env, x25519_public_key)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            {
+                Some(key) => {
+                    crate::network::first_contact::add_trusted_sender(key);
+                    JNI_TRUE
+                }
+                None => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Remove a contact's X25519 public key from the trusted set
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_removeTrustedSender(
+    mut env: JNIEnv,
+    _class: JClass,
+    x25519_public_key: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match jbytearray_to_vec(&mut env, x25519_public_key)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            {
+                Some(key) => {
+                    crate::network::first_contact::remove_trusted_sender(&key);
+                    JNI_TRUE
+                }
+                None => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Mint a PoW stamp for an outgoing first-contact payload and append it
+/// Use the difficulty/params from the recipient's GET /pow-policy advert.
+/// `payload` is everything after the wire type byte (e.g. the bytes passed to
+/// sendFriendRequest). Returns the stamped payload, or null if no solution was found.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_attachPowStamp(
+    mut env: JNIEnv,
+    _class: JClass,
+    msg_type: jint,
+    payload: JByteArray,
+    difficulty: jint,
+    memory_kib: jint,
+    iterations: jint,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let payload = match jbytearray_to_vec(&mut env, payload) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert payload: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let max_difficulty = shield_protocol::protocol::pow_stamp::MAX_DIFFICULTY as jint;
+            let difficulty = difficulty.clamp(0, max_difficulty) as u8;
+            let params = shield_protocol::protocol::PowParams {
+                memory_kib: memory_kib.max(8) as u32,
+                iterations: iterations.max(1) as u32,
+            };
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            // 64x the expected work before giving up
+            let max_attempts = 1u64 << (difficulty as u32 + 6);
+
+            let stamp = match shield_protocol::protocol::PowStamp::mint(
+                msg_type as u8,
+                &payload,
+                difficulty,
+                now,
+                &params,
+                max_attempts,
+            ) {
+                Ok(stamp) => stamp,
+                Err(e) => {
+                    log::error!("Failed to mint PoW stamp: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            log::info!(
+                "Minted PoW stamp (difficulty {}, nonce {})",
+                difficulty,
+                stamp.nonce
+            );
+
+            let stamped = shield_protocol::protocol::attach_stamp(&payload, stamp);
+            match vec_to_jbytearray(&mut env, &stamped) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== CONTACT PRESENCE ====================
+
+/// Configure presence sharing (off by default)
+/// jitterSecs: max random delay added to our idle time before bucketing
+/// minBeaconIntervalSecs: at most one beacon per contact per interval
+/// storageGranularitySecs: stored timestamps are floored to this
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setPresenceConfig(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+    jitter_secs: jlong,
+    min_beacon_interval_secs: jlong,
+    storage_granularity_secs: jlong,
+) {
+    catch_panic!(
+        env,
+        {
+            crate::network::presence::set_config(shield_protocol::protocol::PresenceConfig {
+                enabled: enabled != 0,
+                jitter_secs: jitter_secs.max(0) as u64,
+                min_beacon_interval_secs: min_beacon_interval_secs.max(0) as u64,
+                storage_granularity_secs: storage_granularity_secs.max(1) as u64,
+            });
+        },
+        ()
+    )
+}
+
+/// Opt a contact in or out of presence sharing (opting out drops their presence)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setPresenceSharing(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    sharing: jboolean,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => {
+                    crate::network::presence::set_sharing(&id, sharing != 0);
+                    JNI_TRUE
+                }
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Record that the local user was active (call on app foreground / user input)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_notePresenceActivity(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::presence::note_activity() }, ())
+}
+
+/// Build a presence beacon for a contact
+/// Returns the plaintext beacon to encrypt and send as MSG_TYPE_PRESENCE (0x10),
+/// or null if presence is off for this contact or a beacon was sent recently
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_buildPresenceBeacon(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let Some(beacon) = crate::network::presence::beacon_for(&id) else {
+                return std::ptr::null_mut();
+            };
+            match vec_to_jbytearray(&mut env, &beacon) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Store a decrypted presence beacon from a contact
+/// Returns false if the beacon is malformed or the contact is not opted in
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_recordPresenceBeacon(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    beacon: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let beacon = match jbytearray_to_vec(&mut env, beacon) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert beacon: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            match crate::network::presence::record_beacon(&id, &beacon) {
+                Ok(true) => JNI_TRUE,
+                Ok(false) => JNI_FALSE,
+                Err(e) => {
+                    log::warn!("Dropping presence beacon: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Get a contact's presence bucket
+/// Returns: 0=active, 1=recent, 2=today, 3=this week, 4=away, -1=unknown / not shared
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getContactPresence(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => crate::network::presence::presence_of(&id)
+                    .map(|bucket| bucket as jint)
+                    .unwrap_or(-1),
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    -1
+                }
+            }
+        },
+        -1
+    )
+}
+
+/// Get presence of all opted-in contacts as JSON
+/// Returns: {"sl_…":"active"|"recent"|"today"|"this_week"|"away",...}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getPresenceSnapshotJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::presence::snapshot_json();
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Export presence state for the app to persist (store encrypted)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_exportPresenceState(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let state = match crate::network::presence::export_state() {
+                Ok(state) => state,
+                Err(e) => {
+                    log::error!("Failed to export presence state: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &state) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Restore presence state previously returned by exportPresenceState
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_importPresenceState(
+    mut env: JNIEnv,
+    _class: JClass,
+    state: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let state = match jbytearray_to_vec(&mut env, state) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert presence state: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            match crate::network::presence::import_state(&state) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to import presence state: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== KEY-CHANGE ENFORCEMENT ====================
+
+/// Pin a contact's identity key with its trust level (0=Untrusted, 1=Encrypted, 2=Verified)
+/// Call for every contact at startup and when a contact is added
+/// Returns the contact id, or null on error
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_registerContactIdentity(
+    mut env: JNIEnv,
+    _class: JClass,
+    ed25519_public_key: JByteArray,
+    trust_level: jint,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let key = match jbytearray_to_vec(&mut env, ed25519_public_key) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert identity key: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let trust = crate::crypto::TrustLevel::from_u8(trust_level as u8);
+            let id = match active_context().with_key_change_guard(|g| g.register(&key, trust)) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to register contact identity: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &id.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Update a registered contact's trust level (e.g. after a QR verification)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setContactTrustLevel(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    trust_level: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let trust = crate::crypto::TrustLevel::from_u8(trust_level as u8);
+            match active_context().with_key_change_guard(|g| g.set_trust(&id, trust)) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to set trust level: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Compare the identity key a contact presented against the pinned one
+/// Returns: 0=unchanged, 1=changed (unverified contact, warn only),
+///          2=changed and blocked (verified contact), -1=error
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_observeContactIdentity(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    ed25519_public_key: JByteArray,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return -1;
+                }
+            };
+            let key = match jbytearray_to_vec(&mut env, ed25519_public_key) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert identity key: {}", e);
+                    return -1;
+                }
+            };
+            let changed = |blocked| {
+                crate::network::security_events::raise(
+                    crate::network::security_events::SecurityEventKind::IdentityKeyChanged {
+                        blocked,
+                    },
+                    Some(id),
+                );
+            };
+            match active_context().with_key_change_guard(|g| g.observe(&id, &key)) {
+                Ok(crate::crypto::KeyObservation::Unchanged) => 0,
+                Ok(crate::crypto::KeyObservation::ChangedUnverified) => {
+                    changed(false);
+                    1
+                }
+                Ok(crate::crypto::KeyObservation::ChangedBlocked) => {
+                    changed(true);
+                    2
+                }
+                Err(e) => {
+                    log::error!("Failed to check identity key: {}", e);
+                    -1
+                }
+            }
+        },
+        -1
+    )
+}
+
+/// Whether sends to a contact are blocked pending key-change approval
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isSendBlocked(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => {
+                    if active_context()
+                        .with_key_change_guard(|g| g.check_send(&id))
+                        .is_err()
+                    {
+                        JNI_TRUE
+                    } else {
+                        JNI_FALSE
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Pass an incoming (decrypted) message through the key-change guard
+/// Returns true if the app may deliver it now; false if it was quarantined
+/// (list with getQuarantinedMessagesJson, release with approveKeyChange)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_admitIncomingMessage(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    data: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return JNI_FALSE;
+                }
+            };
+            let data = match jbytearray_to_vec(&mut env, data) {
+                Ok(v) => v,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return JNI_FALSE;
+                }
+            };
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            match active_context().with_key_change_guard(|g| g.admit(&id, data, now)) {
+                crate::crypto::Admission::Deliver(_) => JNI_TRUE,
+                crate::crypto::Admission::Quarantined(msg_id) => {
+                    log::warn!("Quarantined message {} from {}", msg_id, id);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+fn quarantined_to_json(messages: &[crate::crypto::QuarantinedMessage]) -> serde_json::Value {
+    serde_json::Value::Array(
+        messages
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "id": m.id,
+                    "receivedAt": m.received_at,
+                    "data": base64::encode(&m.data)
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Messages held for a contact while a key change is pending
+/// Returns: [{"id":1,"receivedAt":1700000000,"data":"<base64>"},...]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getQuarantinedMessagesJson(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let messages = active_context().with_key_change_guard(|g| g.quarantined(&id));
+            let json = quarantined_to_json(&messages);
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Approve a pending key change: pins the new key at Encrypted trust, unblocks sends
+/// and releases the quarantine. The contact id changes with the key.
+/// Returns: {"contactId":"sl_…","released":[...same shape as getQuarantinedMessagesJson]}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_approveKeyChange(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let (new_id, released) =
+                match active_context().with_key_change_guard(|g| g.approve(&id)) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        let _ = env.throw_new("java/lang/IllegalStateException", e.to_string());
+                        return std::ptr::null_mut();
+                    }
+                };
+            crate::ffi::session_cache::invalidate_session_key(&id);
+            crate::ffi::session_cache::invalidate_session_key(&new_id);
+            let json = serde_json::json!({
+                "contactId": new_id.to_string(),
+                "released": quarantined_to_json(&released)
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Forget the cached X25519 shared secret for a contact (contact deleted or
+/// its keys replaced out of band). Returns true if one was cached
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_invalidateSessionKey(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => crate::ffi::session_cache::invalidate_session_key(&id) as jboolean,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Drop a contact's quarantined messages (sends stay blocked until approval)
+/// Returns the number of messages discarded, or -1 on error
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_discardQuarantinedMessages(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => {
+                    active_context().with_key_change_guard(|g| g.discard_quarantined(&id)) as jint
+                }
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    -1
+                }
+            }
+        },
+        -1
+    )
+}
+
+/// Identity chain head for identity_key: the genesis head if previous_head is
+/// null, otherwise previous_head advanced to the new key (after a key change)
+/// Returns the 32-byte head to persist and to advertise on the contact card
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_identityChainHead(
+    mut env: JNIEnv,
+    _class: JClass,
+    previous_head: JByteArray,
+    identity_key: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            use crate::crypto::ChainHead;
+
+            let Some(key) = jbytearray_to_vec(&mut env, identity_key)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                log::error!("Identity key must be 32 bytes");
+                return std::ptr::null_mut();
+            };
+            let head = if previous_head.is_null() {
+                ChainHead::genesis(&key)
+            } else {
+                match jbytearray_to_vec(&mut env, previous_head)
+                    .ok()
+                    .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+                {
+                    Some(previous) => ChainHead(previous).advance(&key),
+                    None => {
+                        log::error!("Previous chain head must be 32 bytes");
+                        return std::ptr::null_mut();
+                    }
+                }
+            };
+            match vec_to_jbytearray(&mut env, &head.0) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Set our own identity chain head; outgoing ordered messages carry its tag
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setLocalIdentityChainHead(
+    mut env: JNIEnv,
+    _class: JClass,
+    head: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(head) = jbytearray_to_vec(&mut env, head)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                log::error!("Chain head must be 32 bytes");
+                return JNI_FALSE;
+            };
+            active_context().with_key_change_guard(|g| {
+                g.set_local_chain_head(crate::crypto::ChainHead(head))
+            });
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+/// Pin the chain head a registered contact advertised (its card carries one
+/// once its identity key has changed); registration pins the key's genesis
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pinContactChainHead(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    head: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let Some(head) = jbytearray_to_vec(&mut env, head)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                log::error!("Chain head must be 32 bytes");
+                return JNI_FALSE;
+            };
+            match active_context().with_key_change_guard(|g| {
+                g.pin_chain_head(&id, crate::crypto::ChainHead(head))
+            }) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to pin chain head: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Export pinned identities, pending key changes and quarantined messages
+/// for the app to persist (store encrypted). Without it a restart lifts the
+/// send block on a pending change.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_exportKeyChangeState(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let state = match active_context().with_key_change_guard(|g| g.to_bytes()) {
+                Ok(state) => state,
+                Err(e) => {
+                    log::error!("Failed to export key-change state: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &state) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Restore key-change state previously returned by exportKeyChangeState
+/// Call at startup, before the first send or receive.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_importKeyChangeState(
+    mut env: JNIEnv,
+    _class: JClass,
+    state: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let state = match jbytearray_to_vec(&mut env, state) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert key-change state: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            match crate::crypto::key_change::KeyChangeGuard::from_bytes(&state) {
+                Ok(guard) => {
+                    active_context().with_key_change_guard(|g| *g = guard);
+                    JNI_TRUE
+                }
+                Err(e) => {
+                    log::error!("Failed to import key-change state: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== MESSAGE ORDERING ====================
+
+/// Configure the 1:1 reorder buffer
+/// window: most sequence numbers held past a missing message before giving up on it
+/// gapTimeoutMs: how long held messages wait for a missing one
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setOrderingConfig(
+    mut env: JNIEnv,
+    _class: JClass,
+    window: jint,
+    gap_timeout_ms: jlong,
+) {
+    catch_panic!(
+        env,
+        {
+            crate::network::ordering::set_config(shield_protocol::protocol::OrderingConfig {
+                window: window.max(1) as u64,
+                gap_timeout_ms: gap_timeout_ms.max(0) as u64,
+            });
+        },
+        ()
+    )
+}
+
+/// Add the conversation sequence number to an outgoing 1:1 plaintext
+/// Call before encrypting; returns the bytes to encrypt, or null on error
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_wrapOrderedMessage(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let plaintext = match jbytearray_to_vec(&mut env, plaintext) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let wrapped = crate::network::ordering::wrap_outgoing(&id, &plaintext);
+            match vec_to_jbytearray(&mut env, &wrapped) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Wrap a 1:1 plaintext like wrapOrderedMessage, attaching annotations
+/// annotations_json: {"namespace:name": "value", ...}; keys prefixed with '!' are
+/// critical and make recipients that do not understand them drop the message.
+/// Null if a key is malformed or the annotations exceed their size limits
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_wrapAnnotatedMessage(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+    annotations_json: JString,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let plaintext = match jbytearray_to_vec(&mut env, plaintext) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            type Entries = std::collections::BTreeMap<String, String>;
+            let Some(entries) = jstring_to_string(&mut env, annotations_json)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Entries>(&json).ok())
+            else {
+                log::error!("Annotations must be a JSON object of strings");
+                return std::ptr::null_mut();
+            };
+            let mut annotations = shield_protocol::protocol::Annotations::new();
+            for (key, value) in entries {
+                if let Err(e) = annotations.insert(key, value) {
+                    log::warn!("Annotation rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            }
+            let wrapped = crate::network::ordering::wrap_annotated(&id, &plaintext, annotations);
+            match vec_to_jbytearray(&mut env, &wrapped) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Declare the critical annotation keys ('!'-prefixed) the app understands
+/// keys_json: JSON array of keys; replaces the previous set. Incoming messages
+/// with any other critical key are dropped. Returns false on invalid JSON
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setUnderstoodAnnotations(
+    mut env: JNIEnv,
+    _class: JClass,
+    keys_json: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(keys) = jstring_to_string(&mut env, keys_json)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+            else {
+                log::error!("Understood annotations must be a JSON array of strings");
+                return JNI_FALSE;
+            };
+            crate::network::ordering::set_understood_annotations(keys);
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+/// Feed a decrypted 1:1 plaintext into the reorder buffer
+/// Returns a JSON array of events to apply in order:
+/// [{"type":"deliver"|"late","seq":n,"body":"<base64>","annotations":{..}},{"type":"gap","first":n,"last":n},...]
+/// "late" is a message from a range already reported as a gap. Null if malformed,
+/// if the sender's identity continuity tag does not match (reported as a
+/// key_continuity_violation security event), or if it carries a critical
+/// annotation not declared with setUnderstoodAnnotations.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_receiveOrderedMessage(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let plaintext = match jbytearray_to_vec(&mut env, plaintext) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let events = match crate::network::ordering::receive(&id, &plaintext) {
+                Ok(events) => events,
+                Err(e) => {
+                    log::warn!("Dropping unordered message: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json: Vec<serde_json::Value> = events
+                .iter()
+                .map(crate::network::ordering::event_json)
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::Array(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Give up on gaps that have timed out
+/// Returns a JSON array like receiveOrderedMessage, each event with a "contactId" field
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollOrderingTimeouts(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json: Vec<serde_json::Value> = crate::network::ordering::poll_timeouts()
+                .iter()
+                .map(|(id, event)| {
+                    let mut value = crate::network::ordering::event_json(event);
+                    value["contactId"] = serde_json::Value::String(id.to_string());
+                    value
+                })
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::Array(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Unix time (ms) at which pollOrderingTimeouts next has work, or -1 if nothing is held
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getOrderingDeadline(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    catch_panic!(
+        env,
+        {
+            crate::network::ordering::next_deadline_ms()
+                .map(|ms| ms as jlong)
+                .unwrap_or(-1)
+        },
+        -1
+    )
+}
+
+/// Forget ordering state for a deleted contact or reset session
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_forgetOrderingState(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+) {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => crate::network::ordering::forget(&id),
+                Err(e) => log::error!("Failed to convert contact id: {}", e),
+            }
+        },
+        ()
+    )
+}
+
+/// Export ordering state for the app to persist (store encrypted)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_exportOrderingState(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let state = match crate::network::ordering::export_state() {
+                Ok(state) => state,
+                Err(e) => {
+                    log::error!("Failed to export ordering state: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &state) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Restore ordering state previously returned by exportOrderingState
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_importOrderingState(
+    mut env: JNIEnv,
+    _class: JClass,
+    state: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let state = match jbytearray_to_vec(&mut env, state) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert ordering state: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            match crate::network::ordering::import_state(&state) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to import ordering state: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== CIPHER-SUITE NEGOTIATION ====================
+
+/// Pick the cipher suite to use with a contact, from their signed card (JSON)
+/// Returns the suite id (1=classical, 2=hybrid-pq), or -1 for a legacy card,
+/// no suite in common or an unparseable card
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_negotiateCipherSuite(
+    mut env: JNIEnv,
+    _class: JClass,
+    peer_card_json: JString,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let card = match jstring_to_string(&mut env, peer_card_json).and_then(|json| {
+                crate::protocol::ContactCard::from_json(&json).map_err(|e| e.to_string())
+            }) {
+                Ok(card) => card,
+                Err(e) => {
+                    log::error!("Failed to parse contact card: {}", e);
+                    return -1;
+                }
+            };
+            crate::network::downgrade::negotiate_with(&card)
+                .map(|suite| suite.id() as jint)
+                .unwrap_or(-1)
+        },
+        -1
+    )
+}
+
+/// Check the cipher suite a contact selected against their signed card (JSON)
+/// Returns true if consistent; false raises a downgrade alarm (see takeDowngradeAlarmsJson)
+/// or means the input was invalid (including a card that belongs to someone else)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_checkCipherSuiteSelection(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    peer_card_json: JString,
+    selected_suite: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let card = match jstring_to_string(&mut env, peer_card_json).and_then(|json| {
+                crate::protocol::ContactCard::from_json(&json).map_err(|e| e.to_string())
+            }) {
+                Ok(card) => card,
+                Err(e) => {
+                    log::error!("Failed to parse contact card: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            if card.contact_id().ok() != Some(id) {
+                log::error!("Contact card does not belong to {}", id);
+                return JNI_FALSE;
+            }
+            let Some(selected) = u16::try_from(selected_suite)
+                .ok()
+                .and_then(crate::protocol::CipherSuite::from_id)
+            else {
+                log::error!("Unknown cipher suite id {}", selected_suite);
+                return JNI_FALSE;
+            };
+            match crate::network::downgrade::check_peer_selection(&id, &card, selected) {
+                Ok(()) => JNI_TRUE,
+                Err(_) => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Drain pending downgrade alarms
+/// Returns: [{"contactId":"sl_…","reason":"downgrade"|"not_advertised"|"unsupported"|"no_common_suite",
+///            "selected":"classical"|"hybrid-pq"|null,"expected":…|null,"timestamp":secs},...]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_takeDowngradeAlarmsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json: Vec<serde_json::Value> = crate::network::downgrade::take_alarms()
+                .iter()
+                .map(crate::network::downgrade::alarm_json)
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::Array(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== SECURITY EVENTS ====================
+
+/// Drain pending security events, oldest first
+/// Returns: [{"kind":"cipher_suite_downgrade"|"identity_key_changed"|"decryption_failures"|
+///            "replay_detected"|"cover_traffic_anomaly",...kind fields,
+///            "severity":"info"|"warning"|"critical","contactId":"sl_…"|null,
+///            "timestamp":secs,"suppressed":n},...]
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_takeSecurityEventsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::security_events::take_events_json();
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Report a message from a contact that failed to decrypt (replay = the
+/// failure was a replayed sequence number). Repeated failures raise a
+/// decryption_failures event; replays raise replay_detected.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_reportDecryptionFailure(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    replay: jboolean,
+) {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return;
+                }
+            };
+            if replay != JNI_FALSE {
+                crate::network::security_events::raise(
+                    crate::network::security_events::SecurityEventKind::ReplayDetected {
+                        detail: "replayed message sequence".to_string(),
+                    },
+                    Some(id),
+                );
+            } else {
+                crate::network::security_events::decryption_failure(id);
+            }
+        },
+        ()
+    )
+}
+
+/// Tune security event thresholds; values <= 0 keep the current setting
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setSecurityEventPolicy(
+    mut env: JNIEnv,
+    _class: JClass,
+    decryption_failure_threshold: jint,
+    decryption_failure_window_secs: jlong,
+    cover_packets_per_minute: jint,
+    min_interval_secs: jlong,
+) {
+    catch_panic!(
+        env,
+        {
+            let mut policy = crate::network::security_events::policy();
+            if decryption_failure_threshold > 0 {
+                policy.decryption_failure_threshold = decryption_failure_threshold as u32;
+            }
+            if decryption_failure_window_secs > 0 {
+                policy.decryption_failure_window_secs = decryption_failure_window_secs as u64;
+            }
+            if cover_packets_per_minute > 0 {
+                policy.cover_packets_per_minute = cover_packets_per_minute as u32;
+            }
+            if min_interval_secs > 0 {
+                policy.min_interval_secs = min_interval_secs as u64;
+            }
+            crate::network::security_events::set_policy(policy);
+        },
+        ()
+    )
+}
+
+// ==================== EMERGENCY WIPE ====================
+
+/// Arm the emergency wipe; returns the hex confirmation token to pass to
+/// emergencyWipe within 60 seconds
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_armEmergencyWipe(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let token = crate::wipe::arm();
+            match string_to_jstring(&mut env, &token.to_hex()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create token string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Withdraw an armed emergency wipe
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_disarmEmergencyWipe(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::wipe::disarm() }, ())
+}
+
+/// Wipe all core state; the app deletes its databases afterwards
+/// Returns: {"wipedAt":secs,"steps":[{"step":"outbox"|"inbox"|...,"cleared":n,"error":str|null}]}
+/// Throws if the token is missing, wrong or expired (nothing is wiped)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_emergencyWipe(
+    mut env: JNIEnv,
+    _class: JClass,
+    confirmation_token: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let token = jstring_to_string(&mut env, confirmation_token)
+                .ok()
+                .and_then(|s| crate::wipe::WipeToken::from_hex(&s));
+            let Some(token) = token else {
+                let _ = env.throw_new(
+                    "java/lang/IllegalArgumentException",
+                    "Malformed wipe confirmation token",
+                );
+                return std::ptr::null_mut();
+            };
+            let report = match crate::wipe::emergency_wipe(token, Default::default()) {
+                Ok(report) => report,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/SecurityException", e.to_string());
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &report.to_json()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== DURESS PIN VALIDATION ====================
+
+/// Check a proposed duress PIN against the real PIN and common PINs
+/// Returns: {"acceptable":bool,"issues":[{"code":"same_as_real"|"prefix_of_real"|"too_similar"|
+///           "shared_prefix"|"common_pin"|"trivial_pattern"|"invalid_length",
+///           "severity":"reject"|"warning","message":"…"},...]} (most severe first)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_validateDuressPin(
+    mut env: JNIEnv,
+    _class: JClass,
+    duress_pin: JString,
+    real_pin: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use zeroize::Zeroize;
+            let (mut duress, mut real) = match (
+                jstring_to_string(&mut env, duress_pin),
+                jstring_to_string(&mut env, real_pin),
+            ) {
+                (Ok(d), Ok(r)) => (d, r),
+                (Err(e), _) | (_, Err(e)) => {
+                    log::error!("Failed to convert PIN: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let validation = crate::crypto::duress::validate_duress_pin(&duress, &real);
+            duress.zeroize();
+            real.zeroize();
+
+            let issues: Vec<serde_json::Value> = validation
+                .issues
+                .iter()
+                .map(|issue| {
+                    let severity = match issue.severity() {
+                        crate::crypto::duress::PinIssueSeverity::Reject => "reject",
+                        crate::crypto::duress::PinIssueSeverity::Warning => "warning",
+                    };
+                    serde_json::json!({
+                        "code": issue.code(),
+                        "severity": severity,
+                        "message": issue.to_string(),
+                    })
+                })
+                .collect();
+            let json = serde_json::json!({
+                "acceptable": validation.is_acceptable(),
+                "issues": issues,
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== NETWORK SILENCE ====================
+
+/// Enter network silence (stealth mode). Only a wake message signed by the
+/// designated contact's identity key can end it remotely.
+/// own_public_key is this device's Ed25519 identity public key.
+/// Returns false if the wake key does not belong to wake_contact_id.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_enterNetworkSilence(
+    mut env: JNIEnv,
+    _class: JClass,
+    wake_contact_id: JString,
+    wake_public_key: JByteArray,
+    own_public_key: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, wake_contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let to_key = |v: Vec<u8>| <[u8; 32]>::try_from(v.as_slice()).ok();
+            let (wake_key, own_key) = match (
+                jbytearray_to_vec(&mut env, wake_public_key).map(to_key),
+                jbytearray_to_vec(&mut env, own_public_key).map(to_key),
+            ) {
+                (Ok(Some(w)), Ok(Some(o))) => (w, o),
+                _ => {
+                    log::error!("Network silence: public keys must be 32 bytes");
+                    return JNI_FALSE;
+                }
+            };
+            match crate::protocol::silence::SilenceSpec::new(id, wake_key) {
+                Ok(spec) => {
+                    crate::network::silence::enter(spec, own_key);
+                    JNI_TRUE
+                }
+                Err(e) => {
+                    log::error!("Network silence: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// End network silence locally
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_endNetworkSilence(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::silence::end() }, ())
+}
+
+/// Whether network silence is active (it may have been ended by a wake message)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isNetworkSilent(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            if crate::network::silence::is_silent() {
+                JNI_TRUE
+            } else {
+                JNI_FALSE
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Send a wake message ending a contact's network silence
+/// signing_key: our Ed25519 identity private key; recipient_public_key: their identity public key
+/// Returns true once delivered to the contact's onion service (there is never a reply)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendWakeMessage(
+    mut env: JNIEnv,
+    _class: JClass,
+    recipient_onion: JString,
+    signing_key: JByteArray,
+    recipient_public_key: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            use zeroize::Zeroize;
+            let onion_address = match jstring_to_string(&mut env, recipient_onion) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert onion address: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let recipient_key = match jbytearray_to_vec(&mut env, recipient_public_key)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            {
+                Some(k) => k,
+                None => {
+                    log::error!("Wake: recipient public key must be 32 bytes");
+                    return JNI_FALSE;
+                }
+            };
+            let mut key = match jbytearray_to_vec(&mut env, signing_key) {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to convert signing key: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let frame = crate::network::silence::wake_frame(&key, &recipient_key);
+            key.zeroize();
+            let frame = match frame {
+                Ok(f) => f,
+                Err(e) => {
+                    log::error!("Wake: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+
+            let result = GLOBAL_RUNTIME.block_on(async {
+                let timeout_duration = crate::network::timeout_policy().blob_send;
+                tokio::time::timeout(timeout_duration, async {
+                    let mut conn = crate::network::tor::connect_to_onion(
+                        &onion_address,
+                        crate::network::ports().messaging,
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+                    conn.send(&frame).await.map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|_| "timed out".to_string())?
+            });
+            match result {
+                Ok(()) => {
+                    log::info!("Wake message sent");
+                    JNI_TRUE
+                }
+                Err(e) => {
+                    log::warn!("Wake message not sent: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== RECEIPT BATCHING ====================
+
+/// Encrypt an ACK batch for the contact with X25519 key recipient_x25519
+/// Returns [our X25519 public key - 32 bytes][encrypted batch], the delivery ACK layout
+fn seal_ack_batch(
+    env: &mut JNIEnv,
+    batch: &[u8],
+    recipient_x25519: &[u8],
+) -> Result<Vec<u8>, String> {
+    use zeroize::Zeroize;
+    let key_manager = crate::ffi::keystore::key_provider(env)
+        .map_err(|e| format!("Failed to get KeyManager: {}", e))?;
+    let mut sealed = key_manager
+        .encryption_public_key(env)
+        .map_err(|e| format!("Failed to get our X25519 pubkey: {}", e))?;
+    let mut our_private = key_manager
+        .encryption_private_key(env)
+        .map_err(|e| format!("Failed to get encryption key: {}", e))?;
+
+    let shared_secret =
+        crate::crypto::key_exchange::derive_shared_secret(&our_private, recipient_x25519);
+    our_private.zeroize();
+    let mut shared_secret = shared_secret.map_err(|e| format!("ECDH failed: {}", e))?;
+    let encrypted = crate::crypto::encryption::encrypt_message(batch, &shared_secret);
+    shared_secret.zeroize();
+    sealed.extend_from_slice(&encrypted.map_err(|e| format!("Encryption failed: {}", e))?);
+    Ok(sealed)
+}
+
+/// Record a received 1:1 message (its ordering sequence number) for a batched receipt
+/// Call for duplicates too: a resend means the earlier receipt was lost
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_recordReceivedSeq(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    seq: jlong,
+) {
+    catch_panic!(
+        env,
+        {
+            match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => crate::network::receipts::record_received(id, seq as u64),
+                Err(e) => log::error!("Failed to convert contact id: {}", e),
+            }
+        },
+        ()
+    )
+}
+
+/// Piggyback pending receipts for a contact on a connection their message arrived on
+/// Replaces sendAckOnConnection for 1:1 messages. Returns false if nothing was pending
+/// or the send failed (receipts stay queued for pollDueAckBatches)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendAckBatchOnConnection(
+    mut env: JNIEnv,
+    _class: JClass,
+    connection_id: jlong,
+    contact_id: JString,
+    recipient_x25519_pubkey: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let recipient_x25519 = match jbytearray_to_vec(&mut env, recipient_x25519_pubkey) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert recipient X25519 pubkey: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let Some(batch) = crate::network::receipts::take_piggyback(&id) else {
+                return JNI_FALSE;
+            };
+            let sealed = match seal_ack_batch(&mut env, &batch, &recipient_x25519) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("ACK batch: {}", e);
+                    crate::network::receipts::requeue(id, &batch);
+                    return JNI_FALSE;
+                }
+            };
+
+            let result = GLOBAL_RUNTIME.block_on(async {
+                crate::network::TorManager::send_ack_on_connection(
+                    connection_id as u64,
+                    crate::network::tor::MSG_TYPE_ACK_BATCH,
+                    &sealed,
+                )
+                .await
+                .map_err(|e| e.to_string())
+            });
+            match result {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("ACK batch not sent on connection {}: {}", connection_id, e);
+                    crate::network::receipts::requeue(id, &batch);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Receipts that can no longer wait for a piggyback ride
+/// Returns a JSON array of {"contactId", "batch": "<base64>"}; send each with sendAckBatch
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollDueAckBatches(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use base64::Engine;
+            let json: Vec<serde_json::Value> = crate::network::receipts::poll_due()
+                .iter()
+                .map(|(id, batch)| {
+                    serde_json::json!({
+                        "contactId": id.to_string(),
+                        "batch": base64::engine::general_purpose::STANDARD.encode(batch),
+                    })
+                })
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::Array(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Send a batch from pollDueAckBatches on one connection to the contact's ACK port (9153)
+/// On failure the receipts are queued again
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_sendAckBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    batch: JByteArray,
+    recipient_x25519_pubkey: JByteArray,
+    recipient_onion: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let (batch, recipient_x25519, onion_address) = match (
+                jbytearray_to_vec(&mut env, batch),
+                jbytearray_to_vec(&mut env, recipient_x25519_pubkey),
+                jstring_to_string(&mut env, recipient_onion),
+            ) {
+                (Ok(b), Ok(k), Ok(o)) => (b, k, o),
+                _ => {
+                    log::error!("ACK batch: failed to convert arguments");
+                    return JNI_FALSE;
+                }
+            };
+            let sealed = match seal_ack_batch(&mut env, &batch, &recipient_x25519) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("ACK batch: {}", e);
+                    crate::network::receipts::requeue(id, &batch);
+                    return JNI_FALSE;
+                }
+            };
+            let mut frame = Vec::with_capacity(1 + sealed.len());
+            frame.push(crate::network::tor::MSG_TYPE_ACK_BATCH);
+            frame.extend_from_slice(&sealed);
+
+            let result = GLOBAL_RUNTIME.block_on(async {
+                let timeout_duration = crate::network::timeout_policy().blob_send;
+                tokio::time::timeout(timeout_duration, async {
+                    let mut conn = crate::network::tor::connect_to_onion(
+                        &onion_address,
+                        crate::network::ports().ack_port(),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+                    conn.send(&frame).await.map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|_| "timed out".to_string())?
+            });
+            match result {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("ACK batch not sent: {}", e);
+                    crate::network::receipts::requeue(id, &batch);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Unix time (ms) at which pollDueAckBatches next has work, or -1 if nothing is pending
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getAckBatchDeadline(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    catch_panic!(
+        env,
+        {
+            crate::network::receipts::next_deadline_ms()
+                .map(|ms| ms as jlong)
+                .unwrap_or(-1)
+        },
+        -1
+    )
+}
+
+/// Decrypt an ACK batch (type 0x12) polled from the ACK listener
+/// Returns {"senderX25519": "<hex>", "ranges": [[first, last], ...]} of acknowledged
+/// sequence numbers; the app maps senderX25519 to the contact. Null if invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_decryptAckBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    ack_wire: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use zeroize::Zeroize;
+            let wire_bytes = match jbytearray_to_vec(&mut env, ack_wire) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert ACK batch bytes: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            // [type][sender X25519 - 32 bytes][encrypted batch]
+            if wire_bytes.len() < 33 || wire_bytes[0] != crate::network::tor::MSG_TYPE_ACK_BATCH {
+                log::error!("Not an ACK batch ({} bytes)", wire_bytes.len());
+                return std::ptr::null_mut();
+            }
+            let sender_x25519 = &wire_bytes[1..33];
+
+            let mut our_private = match crate::ffi::keystore::key_provider(&mut env)
+                .and_then(|km| km.encryption_private_key(&mut env))
+            {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Failed to get encryption private key: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let shared_secret =
+                crate::crypto::key_exchange::derive_shared_secret(&our_private, sender_x25519);
+            our_private.zeroize();
+            let mut shared_secret = match shared_secret {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("ECDH failed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let decrypted =
+                crate::crypto::encryption::decrypt_message(&wire_bytes[33..], &shared_secret);
+            shared_secret.zeroize();
+
+            let batch = match decrypted.map_err(|e| e.to_string()).and_then(|b| {
+                crate::protocol::receipts::AckBatch::from_bytes(&b).map_err(|e| e.to_string())
+            }) {
+                Ok(b) => b,
+                Err(e) => {
+                    log::error!("Invalid ACK batch: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json = serde_json::json!({
+                "senderX25519": hex::encode(sender_x25519),
+                "ranges": crate::network::receipts::batch_json(&batch),
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+// ==================== MESSAGE REACTIONS ====================
+
+/// Set or remove our reaction to a 1:1 message (emoji null removes it)
+/// Returns the plaintext to encrypt and send as MSG_TYPE_REACTION (0x13), or null if invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setMessageReaction(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    message_id: JString,
+    emoji: JString,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let message_id = match jstring_to_string(&mut env, message_id) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert message id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let emoji = if emoji.is_null() {
+                None
+            } else {
+                match jstring_to_string(&mut env, emoji) {
+                    Ok(s) => Some(s),
+                    Err(e) => {
+                        log::error!("Failed to convert emoji: {}", e);
+                        return std::ptr::null_mut();
+                    }
+                }
+            };
+            let plaintext =
+                match crate::network::reactions::set_local(&id, &message_id, emoji.as_deref()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        log::warn!("Reaction rejected: {}", e);
+                        return std::ptr::null_mut();
+                    }
+                };
+            match vec_to_jbytearray(&mut env, &plaintext) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Apply a decrypted MSG_TYPE_REACTION plaintext from a contact
+/// Returns {"messageId":"..","changed":bool}; refresh that message's reactions when changed.
+/// Null if malformed
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_receiveReaction(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let plaintext = match jbytearray_to_vec(&mut env, plaintext) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let (message_id, changed) = match crate::network::reactions::receive(&id, &plaintext) {
+                Ok(result) => result,
+                Err(e) => {
+                    log::warn!("Dropping reaction: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json = serde_json::json!({
+                "messageId": message_id,
+                "changed": changed,
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Reactions of the given messages (JSON array of message ids), for the chat view
+/// Returns a JSON array of {"messageId","counts":[{"emoji","count"}],"local"}, in the
+/// order given; messages without reactions are left out
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getReactionsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    message_ids_json: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let message_ids: Vec<String> = match jstring_to_string(&mut env, message_ids_json)
+                .map_err(|e| e.to_string())
+                .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            {
+                Ok(ids) => ids,
+                Err(e) => {
+                    log::error!("Failed to parse message ids: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json: Vec<serde_json::Value> = crate::network::reactions::view(&id, &message_ids)
+                .iter()
+                .map(crate::network::reactions::view_json)
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::Array(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Drop reactions to a deleted 1:1 message
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_forgetMessageReactions(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    message_id: JString,
+) {
+    catch_panic!(
+        env,
+        {
+            let id = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return;
+                }
+            };
+            match jstring_to_string(&mut env, message_id) {
+                Ok(message_id) => crate::network::reactions::forget_message(&id, &message_id),
+                Err(e) => log::error!("Failed to convert message id: {}", e),
+            }
+        },
+        ()
+    )
+}
+
+/// Export reaction state for the app to persist (store encrypted)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_exportReactionState(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let state = match crate::network::reactions::export_state() {
+                Ok(state) => state,
+                Err(e) => {
+                    log::error!("Failed to export reaction state: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &state) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Restore reaction state previously returned by exportReactionState
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_importReactionState(
+    mut env: JNIEnv,
+    _class: JClass,
+    state: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let state = match jbytearray_to_vec(&mut env, state) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert reaction state: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            match crate::network::reactions::import_state(&state) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to import reaction state: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== MESSAGE RECALL ====================
+
+fn recall_verdicts_json(
+    verdicts: &[(String, shield_protocol::protocol::RecallVerdict)],
+) -> serde_json::Value {
+    use shield_protocol::protocol::RecallVerdict;
+    verdicts
+        .iter()
+        .map(|(message_id, verdict)| {
+            let verdict = match verdict {
+                RecallVerdict::Deleted => "deleted",
+                RecallVerdict::NotReceived => "not_received",
+                RecallVerdict::AlreadyDisplayed => "already_displayed",
+                RecallVerdict::Expired => "expired",
+            };
+            serde_json::json!({ "messageId": message_id, "verdict": verdict })
+        })
+        .collect()
+}
+
+/// Set how long after sending a message can be recalled (1 s to 24 h)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setRecallWindow(
+    mut env: JNIEnv,
+    _class: JClass,
+    window_secs: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            match crate::network::recall::set_window(window_secs.max(0) as u32) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("Recall window rejected: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Recall messages sent to a contact
+/// messages_json: [{"messageId":"..","sentAt":unix_secs}]
+/// Returns the plaintext to encrypt and send as MSG_TYPE_RECALL (0x18), or null if a
+/// message is past the recall window or the list is invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_requestMessageRecall(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    messages_json: JString,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Ok(id) = jstring_to_contact_id(&mut env, contact_id) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(messages_json) = jstring_to_string(&mut env, messages_json) else {
+                return std::ptr::null_mut();
+            };
+            let Some(messages) = serde_json::from_str::<Vec<serde_json::Value>>(&messages_json)
+                .ok()
+                .and_then(|list| {
+                    list.iter()
+                        .map(|m| {
+                            Some((m["messageId"].as_str()?.to_string(), m["sentAt"].as_u64()?))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                })
+            else {
+                log::error!("Invalid recall message list");
+                return std::ptr::null_mut();
+            };
+            let plaintext = match crate::network::recall::request(&id, &messages, unix_now()) {
+                Ok(p) => p,
+                Err(e) => {
+                    log::warn!("Recall rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &plaintext) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Handle a decrypted MSG_TYPE_RECALL plaintext from a contact
+/// our_pubkey / their_pubkey: Ed25519 identity public keys (used to verify acks)
+/// Returns one of:
+/// - {"kind":"request","messageIds":[..]}: answer with answerMessageRecall
+/// - {"kind":"ack","outcome":"honored"|"partially_honored"|"refused",
+///   "verdicts":[{"messageId","verdict"}],"evidence":"base64"}: the contact's signed
+///   answer to our recall; keep evidence (the signed ack) if needed
+/// Null if malformed, or an ack that does not match a recall of ours or is badly signed
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_receiveRecall(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use shield_protocol::protocol::{RecallFrame, RecallOutcome};
+
+            let Ok(id) = jstring_to_contact_id(&mut env, contact_id) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(plaintext) = jbytearray_to_vec(&mut env, plaintext) else {
+                return std::ptr::null_mut();
+            };
+            let json = match crate::network::recall::parse(&plaintext) {
+                Ok(RecallFrame::Request(request)) => serde_json::json!({
+                    "kind": "request",
+                    "messageIds": request.message_ids,
+                }),
+                Ok(RecallFrame::Ack(ack)) => {
+                    let Ok(our_pubkey) = jbytearray_to_vec(&mut env, our_pubkey) else {
+                        return std::ptr::null_mut();
+                    };
+                    let Ok(their_pubkey) = jbytearray_to_vec(&mut env, their_pubkey) else {
+                        return std::ptr::null_mut();
+                    };
+                    let Ok(us) = crate::protocol::ContactId::from_identity_key(&our_pubkey) else {
+                        log::error!("Our identity key must be 32 bytes");
+                        return std::ptr::null_mut();
+                    };
+                    if !id.matches_identity_key(&their_pubkey) {
+                        log::error!("Recall ack: identity key does not match {}", id);
+                        return std::ptr::null_mut();
+                    }
+                    let report =
+                        match crate::network::recall::receive_ack(&id, ack, &us, &their_pubkey) {
+                            Ok(r) => r,
+                            Err(e) => {
+                                log::warn!("Dropping recall ack: {}", e);
+                                return std::ptr::null_mut();
+                            }
+                        };
+                    let Ok(evidence) = RecallFrame::Ack(report.ack).to_bytes() else {
+                        return std::ptr::null_mut();
+                    };
+                    serde_json::json!({
+                        "kind": "ack",
+                        "outcome": match report.outcome {
+                            RecallOutcome::Honored => "honored",
+                            RecallOutcome::PartiallyHonored => "partially_honored",
+                            RecallOutcome::Refused => "refused",
+                        },
+                        "verdicts": recall_verdicts_json(&report.verdicts),
+                        "evidence": base64::encode(&evidence),
+                    })
+                }
+                Err(e) => {
+                    log::warn!("Dropping recall: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Answer a recall request (the plaintext receiveRecall reported as "request")
+/// states_json: {"<messageId>": {"displayed": bool, "sentAt": unix_secs}} for every
+/// recalled message we have; messages left out are treated as not received yet
+/// signing_key: our Ed25519 identity private key
+/// Returns {"delete":[messageIds],"verdicts":[{"messageId","verdict"}],"ack":"base64"}:
+/// delete those messages unseen, then encrypt and send ack as MSG_TYPE_RECALL.
+/// Null if the request is malformed or dated in the future
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_answerMessageRecall(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    plaintext: JByteArray,
+    states_json: JString,
+    signing_key: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use shield_protocol::protocol::{LocalMessageState, RecallFrame, RecallVerdict};
+            use zeroize::Zeroize;
+
+            let Ok(id) = jstring_to_contact_id(&mut env, contact_id) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(plaintext) = jbytearray_to_vec(&mut env, plaintext) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(RecallFrame::Request(request)) = crate::network::recall::parse(&plaintext)
+            else {
+                log::warn!("Not a recall request");
+                return std::ptr::null_mut();
+            };
+            let Some(states) = jstring_to_string(&mut env, states_json)
+                .ok()
+                .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            else {
+                log::error!("Invalid recall message states");
+                return std::ptr::null_mut();
+            };
+            let Ok(mut key) = jbytearray_to_vec(&mut env, signing_key) else {
+                return std::ptr::null_mut();
+            };
+            let state_of = |message_id: &str| {
+                let state = &states[message_id];
+                match (state["displayed"].as_bool(), state["sentAt"].as_u64()) {
+                    (Some(true), _) => LocalMessageState::Displayed,
+                    (Some(false), Some(sent_at)) => LocalMessageState::Undisplayed { sent_at },
+                    _ => LocalMessageState::NotReceived,
+                }
+            };
+            let answer = crate::network::recall::answer(&id, &request, unix_now(), state_of, &key);
+            key.zeroize();
+            let (ack, plaintext) = match answer {
+                Ok(a) => a,
+                Err(e) => {
+                    log::warn!("Recall request rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let delete: Vec<&str> = ack
+                .verdicts
+                .iter()
+                .filter(|(_, verdict)| *verdict == RecallVerdict::Deleted)
+                .map(|(message_id, _)| message_id.as_str())
+                .collect();
+            let json = serde_json::json!({
+                "delete": delete,
+                "verdicts": recall_verdicts_json(&ack.verdicts),
+                "ack": base64::encode(&plaintext),
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Whether an arriving message from a contact was recalled before it got here
+/// True means drop it unseen (the contact already has our ack)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_isMessageRecalled(
+    mut env: JNIEnv,
+    _class: JClass,
+    contact_id: JString,
+    message_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Ok(id) = jstring_to_contact_id(&mut env, contact_id) else {
+                return JNI_FALSE;
+            };
+            let Ok(message_id) = jstring_to_string(&mut env, message_id) else {
+                return JNI_FALSE;
+            };
+            if crate::network::recall::is_recalled(&id, &message_id, unix_now()) {
+                JNI_TRUE
+            } else {
+                JNI_FALSE
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+// ==================== MESSAGE IDS ====================
+
+/// New per-contact message ID sequence for our Ed25519 key; store the blob
+/// encrypted and pass it to issueMessageId. Returns null on invalid key.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_newMessageIdSequence(
+    mut env: JNIEnv,
+    _class: JClass,
+    sender_pubkey: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Some(sender) = jbytearray_to_vec(&mut env, sender_pubkey)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                log::error!("Invalid sender key for message ID sequence");
+                return std::ptr::null_mut();
+            };
+            let sequence = match crate::network::message_ids::new_sequence(sender) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to create message ID sequence: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &sequence) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Issue the next message ID from a sequence blob
+/// Returns {"messageId":"m1-..","sequence":"base64"}; persist the new sequence
+/// before sending. Null if the blob is invalid.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_issueMessageId(
+    mut env: JNIEnv,
+    _class: JClass,
+    sequence: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let sequence = match jbytearray_to_vec(&mut env, sequence) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert message ID sequence: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let (id, sequence) = match crate::network::message_ids::issue(&sequence) {
+                Ok(issued) => issued,
+                Err(e) => {
+                    log::error!("Failed to issue message ID: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json = serde_json::json!({
+                "messageId": id.to_string(),
+                "sequence": base64::encode(&sequence),
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Audit a sent or received message's ID against recent ones
+/// Returns 0 = new, 1 = duplicate (redelivery), 2 = collision, -1 = invalid input
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_auditMessageId(
+    mut env: JNIEnv,
+    _class: JClass,
+    message_id: JString,
+    sender_pubkey: JByteArray,
+    content: JByteArray,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let Ok(message_id) = jstring_to_string(&mut env, message_id) else {
+                return -1;
+            };
+            let Some(sender) = jbytearray_to_vec(&mut env, sender_pubkey)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                return -1;
+            };
+            let Ok(content) = jbytearray_to_vec(&mut env, content) else {
+                return -1;
+            };
+            match crate::network::message_ids::audit(&message_id, sender, &content) {
+                shield_protocol::protocol::IdAudit::New => 0,
+                shield_protocol::protocol::IdAudit::Duplicate => 1,
+                shield_protocol::protocol::IdAudit::Collision(_) => 2,
+            }
+        },
+        -1
+    )
+}
+
+/// Message ID collisions since the last call
+/// Returns a JSON array of {"messageId","firstSender","secondSender","sameSender"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_takeMessageIdCollisions(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json: Vec<serde_json::Value> = crate::network::message_ids::take_collisions()
+                .iter()
+                .map(crate::network::message_ids::collision_json)
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::Value::from(json).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== AUXILIARY RPC ====================
+
+/// RPC keys for one contact from its X25519 shared secret and both identity keys
+fn jni_rpc_keys(
+    env: &mut JNIEnv,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+) -> Option<(shield_protocol::protocol::RpcKeys, [u8; 32])> {
+    let mut to_key = |arr: JByteArray| {
+        jbytearray_to_vec(env, arr)
+            .ok()
+            .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+    };
+    let shared = to_key(shared_secret)?;
+    let ours = to_key(our_pubkey)?;
+    let theirs = to_key(their_pubkey)?;
+    Some((
+        shield_protocol::protocol::RpcKeys::derive(&shared, &ours, &theirs),
+        theirs,
+    ))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Start an RPC call to a contact
+/// Returns {"requestId":"<u64>","frame":"base64"}; send the frame as MSG_TYPE_RPC (0x14).
+/// Null on invalid keys or an oversized request
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_rpcCall(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    method: JString,
+    payload: JByteArray,
+    timeout_secs: jlong,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let Some((keys, peer)) =
+                jni_rpc_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid RPC keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(method) = jstring_to_string(&mut env, method) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(payload) = jbytearray_to_vec(&mut env, payload) else {
+                return std::ptr::null_mut();
+            };
+            let (request_id, frame) = match crate::network::rpc::call(
+                &keys,
+                peer,
+                &method,
+                payload,
+                unix_now(),
+                timeout_secs.max(0) as u64,
+            ) {
+                Ok(call) => call,
+                Err(e) => {
+                    log::warn!("RPC call {} rejected: {}", method, e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json = serde_json::json!({
+                "requestId": request_id.to_string(),
+                "frame": base64::encode(&frame),
+            });
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Handle a MSG_TYPE_RPC frame from a contact
+/// Returns one of:
+/// - {"kind":"reply","requestId","method","status":int,"payload":"base64"}
+/// - {"kind":"respond","frame":"base64"}: answered in Rust, send the frame back
+/// - {"kind":"request","requestId","method","payload":"base64"}: answer with rpcRespond
+/// - {"kind":"dropped"}
+/// Null if the frame does not decrypt or matches no call
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_rpcReceive(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    frame: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use crate::network::rpc::Incoming;
+
+            let Some((keys, peer)) =
+                jni_rpc_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid RPC keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(frame) = jbytearray_to_vec(&mut env, frame) else {
+                return std::ptr::null_mut();
+            };
+            let json = match crate::network::rpc::receive(&keys, peer, &frame, unix_now()) {
+                Ok(Incoming::Reply(reply)) => serde_json::json!({
+                    "kind": "reply",
+                    "requestId": reply.request_id.to_string(),
+                    "method": reply.method,
+                    "status": reply.status as u8,
+                    "payload": base64::encode(&reply.payload),
+                }),
+                Ok(Incoming::Respond(frame)) => serde_json::json!({
+                    "kind": "respond",
+                    "frame": base64::encode(&frame),
+                }),
+                Ok(Incoming::Request {
+                    request_id,
+                    method,
+                    payload,
+                }) => serde_json::json!({
+                    "kind": "request",
+                    "requestId": request_id.to_string(),
+                    "method": method,
+                    "payload": base64::encode(&payload),
+                }),
+                Ok(Incoming::Dropped) => serde_json::json!({ "kind": "dropped" }),
+                Err(e) => {
+                    log::warn!("Dropping RPC frame: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Answer an RPC request handed to the app by rpcReceive
+/// status: 0 = ok, 1 = not found, 2 = denied, 3 = bad request, 4 = unavailable, 5 = internal
+/// Returns the frame to send as MSG_TYPE_RPC, or null on invalid input
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_rpcRespond(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    request_id: JString,
+    status: jint,
+    payload: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Some((keys, _)) = jni_rpc_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid RPC keys");
+                return std::ptr::null_mut();
+            };
+            let Some(request_id) = jstring_to_string(&mut env, request_id)
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+            else {
+                return std::ptr::null_mut();
+            };
+            let Some(status) = u8::try_from(status)
+                .ok()
+                .and_then(shield_protocol::protocol::RpcStatus::from_code)
+            else {
+                return std::ptr::null_mut();
+            };
+            let Ok(payload) = jbytearray_to_vec(&mut env, payload) else {
+                return std::ptr::null_mut();
+            };
+            let frame = match crate::network::rpc::respond(&keys, request_id, status, payload) {
+                Ok(f) => f,
+                Err(e) => {
+                    log::warn!("RPC response rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &frame) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== PUB/SUB TOPICS ====================
+
+/// Topic keys for one contact from its X25519 shared secret and both identity keys
+fn jni_topic_keys(
+    env: &mut JNIEnv,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+) -> Option<shield_protocol::protocol::TopicKeys> {
+    let mut to_key = |arr: JByteArray| {
+        jbytearray_to_vec(env, arr)
+            .ok()
+            .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+    };
+    let mut shared = to_key(shared_secret)?;
+    let ours = to_key(our_pubkey)?;
+    let theirs = to_key(their_pubkey)?;
+    let keys = shield_protocol::protocol::TopicKeys::derive(&shared, &ours, &theirs);
+    zeroize::Zeroize::zeroize(&mut shared);
+    Some(keys)
+}
+
+/// Sign a capability token letting a contact subscribe to one of our topics
+/// signing_key: our Ed25519 identity private key; expires_at: Unix seconds
+/// Returns the token to deliver to the contact (e.g. inside a message), or null
+/// on an invalid topic name or key
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicGrant(
+    mut env: JNIEnv,
+    _class: JClass,
+    signing_key: JByteArray,
+    subscriber_pubkey: JByteArray,
+    topic: JString,
+    expires_at: jlong,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            use zeroize::Zeroize;
+            let Some(subscriber) = jbytearray_to_vec(&mut env, subscriber_pubkey)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                log::error!("Topic grant: subscriber key must be 32 bytes");
+                return std::ptr::null_mut();
+            };
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(mut key) = jbytearray_to_vec(&mut env, signing_key) else {
+                return std::ptr::null_mut();
+            };
+            let token =
+                crate::network::topics::grant(&key, subscriber, &topic, expires_at.max(0) as u64);
+            key.zeroize();
+            let token = match token {
+                Ok(t) => t,
+                Err(e) => {
+                    log::warn!("Topic grant rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &token) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Subscribe to a contact's topic with a token it granted us
+/// Returns the frame to send as MSG_TYPE_TOPIC (0x17), or null if the token
+/// is malformed, expired or not meant for this contact pair
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicSubscribe(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    token: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Some(keys) = jni_topic_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid topic keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(token) = jbytearray_to_vec(&mut env, token) else {
+                return std::ptr::null_mut();
+            };
+            let frame = match crate::network::topics::subscribe(&keys, &token, unix_now()) {
+                Ok(f) => f,
+                Err(e) => {
+                    log::warn!("Topic subscription rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &frame) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Leave a contact's topic
+/// Returns the frame to send as MSG_TYPE_TOPIC, or null if we were not subscribed
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicUnsubscribe(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    topic: JString,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Some(keys) = jni_topic_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid topic keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return std::ptr::null_mut();
+            };
+            let frame = match crate::network::topics::unsubscribe(&keys, &topic, unix_now()) {
+                Ok(f) => f,
+                Err(e) => {
+                    log::warn!("Topic unsubscribe rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &frame) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Seal a publication on one of our topics for one subscriber
+/// Call once per entry of topicSubscribers. Returns the frame to send as
+/// MSG_TYPE_TOPIC, or null if the contact is not subscribed or the payload
+/// exceeds 4 KiB
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicPublish(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    topic: JString,
+    payload: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let Some(keys) = jni_topic_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid topic keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return std::ptr::null_mut();
+            };
+            let Ok(payload) = jbytearray_to_vec(&mut env, payload) else {
+                return std::ptr::null_mut();
+            };
+            let frame = match crate::network::topics::publish(&keys, &topic, payload, unix_now()) {
+                Ok(f) => f,
+                Err(e) => {
+                    log::warn!("Topic publication rejected: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match vec_to_jbytearray(&mut env, &frame) {
+                Ok(arr) => arr.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Handle a MSG_TYPE_TOPIC frame from a contact
+/// Returns one of:
+/// - {"kind":"subscribed","topic","expiresAt"}: the contact subscribed to our topic
+/// - {"kind":"unsubscribed","topic"}
+/// - {"kind":"message","topic","payload":"base64"}: publication on a topic we follow
+/// - {"kind":"dropped"}: replayed or stale
+/// Null if the frame does not decrypt, names no shared topic or carries a bad token
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicReceive(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    frame: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use shield_protocol::protocol::TopicEvent;
+
+            let Some(keys) = jni_topic_keys(&mut env, shared_secret, our_pubkey, their_pubkey)
+            else {
+                log::error!("Invalid topic keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(frame) = jbytearray_to_vec(&mut env, frame) else {
+                return std::ptr::null_mut();
+            };
+            let json = match crate::network::topics::receive(&keys, &frame, unix_now()) {
+                Ok(TopicEvent::Subscribed { topic, expires_at }) => serde_json::json!({
+                    "kind": "subscribed",
+                    "topic": topic,
+                    "expiresAt": expires_at,
+                }),
+                Ok(TopicEvent::Unsubscribed { topic }) => serde_json::json!({
+                    "kind": "unsubscribed",
+                    "topic": topic,
+                }),
+                Ok(TopicEvent::Message { topic, payload }) => serde_json::json!({
+                    "kind": "message",
+                    "topic": topic,
+                    "payload": base64::encode(&payload),
+                }),
+                Ok(TopicEvent::Dropped) => serde_json::json!({ "kind": "dropped" }),
+                Err(e) => {
+                    log::warn!("Dropping topic frame: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Current subscribers of one of our topics
+/// Returns a JSON array of hex identity public keys
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicSubscribers(
+    mut env: JNIEnv,
+    _class: JClass,
+    topic: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return std::ptr::null_mut();
+            };
+            let subscribers: Vec<String> = crate::network::topics::subscribers(&topic, unix_now())
+                .iter()
+                .map(hex::encode)
+                .collect();
+            match string_to_jstring(&mut env, &serde_json::json!(subscribers).to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Drop one subscriber of our topic; it needs a new token to come back
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicRevoke(
+    mut env: JNIEnv,
+    _class: JClass,
+    topic: JString,
+    subscriber_pubkey: JByteArray,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return JNI_FALSE;
+            };
+            let Some(subscriber) = jbytearray_to_vec(&mut env, subscriber_pubkey)
+                .ok()
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+            else {
+                return JNI_FALSE;
+            };
+            if crate::network::topics::revoke(&topic, &subscriber) {
+                JNI_TRUE
+            } else {
+                JNI_FALSE
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Stop publishing a topic; returns how many subscribers were dropped
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_topicWithdraw(
+    mut env: JNIEnv,
+    _class: JClass,
+    topic: JString,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let Ok(topic) = jstring_to_string(&mut env, topic) else {
+                return 0;
+            };
+            crate::network::topics::withdraw(&topic) as jint
+        },
+        0
+    )
+}
+
+// ==================== FILE TRANSFER ====================
+
+/// Contact address and file transfer keys from its X25519 shared secret and
+/// both identity keys
+fn jni_file_peer(
+    env: &mut JNIEnv,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    onion_address: JString,
+) -> Option<crate::network::file_transfer::Peer> {
+    let mut to_key = |arr: JByteArray| {
+        jbytearray_to_vec(env, arr)
+            .ok()
+            .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).ok())
+    };
+    let shared = zeroize::Zeroizing::new(to_key(shared_secret)?);
+    let ours = to_key(our_pubkey)?;
+    let theirs = to_key(their_pubkey)?;
+    let onion = jstring_to_string(env, onion_address).ok()?;
+    Some(crate::network::file_transfer::Peer {
+        onion,
+        our_pubkey: ours,
+        their_pubkey: theirs,
+        keys: Arc::new(shield_protocol::protocol::FileKeys::derive(
+            &shared, &ours, &theirs,
+        )),
+    })
+}
+
+fn jni_transfer_id(
+    env: &mut JNIEnv,
+    transfer_id: JString,
+) -> Option<shield_protocol::protocol::TransferId> {
+    let hex_id = jstring_to_string(env, transfer_id).ok()?;
+    hex::decode(hex_id).ok()?.try_into().ok()
+}
+
+/// Offer a file to a contact; blocks while the file is hashed and the offer
+/// sent. Chunks follow in the background once the contact accepts.
+/// Returns the hex transfer ID, or null on invalid input or a failed send
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferOffer(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    onion_address: JString,
+    path: JString,
+    mime: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let Some(peer) = jni_file_peer(
+                &mut env,
+                shared_secret,
+                our_pubkey,
+                their_pubkey,
+                onion_address,
+            ) else {
+                log::error!("Invalid file transfer keys");
+                return std::ptr::null_mut();
+            };
+            let (Ok(path), Ok(mime)) = (
+                jstring_to_string(&mut env, path),
+                jstring_to_string(&mut env, mime),
+            ) else {
+                return std::ptr::null_mut();
+            };
+            let offered = GLOBAL_RUNTIME.block_on(crate::network::file_transfer::offer(
+                peer,
+                std::path::PathBuf::from(path),
+                &mime,
+            ));
+            let transfer_id = match offered {
+                Ok(id) => hex::encode(id),
+                Err(e) => {
+                    log::warn!("File offer failed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &transfer_id) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Handle a MSG_TYPE_FILE_TRANSFER frame (0x15, wire bytes after the type
+/// byte and sender key) from a contact
+/// Returns {"kind":"offer","transferId","name","mime","size"} for a new offer
+/// to answer with fileTransferAccept/fileTransferDecline, {"kind":"handled"}
+/// for everything else, or null if the frame is rejected
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferReceive(
+    mut env: JNIEnv,
+    _class: JClass,
+    shared_secret: JByteArray,
+    our_pubkey: JByteArray,
+    their_pubkey: JByteArray,
+    onion_address: JString,
+    frame: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let Some(peer) = jni_file_peer(
+                &mut env,
+                shared_secret,
+                our_pubkey,
+                their_pubkey,
+                onion_address,
+            ) else {
+                log::error!("Invalid file transfer keys");
+                return std::ptr::null_mut();
+            };
+            let Ok(frame) = jbytearray_to_vec(&mut env, frame) else {
+                return std::ptr::null_mut();
+            };
+            let _guard = GLOBAL_RUNTIME.enter();
+            let json = match crate::network::file_transfer::receive(peer, &frame) {
+                Ok(Some(offer)) => serde_json::json!({
+                    "kind": "offer",
+                    "transferId": hex::encode(offer.transfer_id),
+                    "name": offer.name,
+                    "mime": offer.mime,
+                    "size": offer.size,
+                }),
+                Ok(None) => serde_json::json!({ "kind": "handled" }),
+                Err(e) => {
+                    log::warn!("Dropping file transfer frame: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Accept an offer into destPath; whole chunks already in the file from an
+/// earlier attempt are kept and the transfer continues after them
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferAccept(
+    mut env: JNIEnv,
+    _class: JClass,
+    transfer_id: JString,
+    dest_path: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(id) = jni_transfer_id(&mut env, transfer_id) else {
+                return JNI_FALSE;
+            };
+            let Ok(dest) = jstring_to_string(&mut env, dest_path) else {
+                return JNI_FALSE;
+            };
+            let _guard = GLOBAL_RUNTIME.enter();
+            match crate::network::file_transfer::accept(&id, std::path::PathBuf::from(dest)) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("File transfer accept failed: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferDecline(
+    mut env: JNIEnv,
+    _class: JClass,
+    transfer_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(id) = jni_transfer_id(&mut env, transfer_id) else {
+                return JNI_FALSE;
+            };
+            let _guard = GLOBAL_RUNTIME.enter();
+            match crate::network::file_transfer::decline(&id) {
+                Ok(()) => JNI_TRUE,
+                Err(_) => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Cancel a transfer in either direction; a partial download is deleted
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferCancel(
+    mut env: JNIEnv,
+    _class: JClass,
+    transfer_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(id) = jni_transfer_id(&mut env, transfer_id) else {
+                return JNI_FALSE;
+            };
+            let _guard = GLOBAL_RUNTIME.enter();
+            match crate::network::file_transfer::cancel(&id) {
+                Ok(()) => JNI_TRUE,
+                Err(_) => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Retry a paused upload now (e.g. when the contact comes online) instead of
+/// waiting for the next automatic attempt
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_fileTransferResume(
+    mut env: JNIEnv,
+    _class: JClass,
+    transfer_id: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let Some(id) = jni_transfer_id(&mut env, transfer_id) else {
+                return JNI_FALSE;
+            };
+            let _guard = GLOBAL_RUNTIME.enter();
+            match crate::network::file_transfer::resume(&id) {
+                Ok(()) => JNI_TRUE,
+                Err(_) => JNI_FALSE,
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Unfinished transfers as a JSON array of {"transferId","direction","name",
+/// "mime","size","bytesDone","state","retransmissions"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getFileTransfers(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = serde_json::to_string(&crate::network::file_transfer::list())
+                .unwrap_or_else(|_| "[]".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/RuntimeException", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Register (or clear, with null) the progress callback
+/// Callback signature: onFileTransferProgress(json: String), same JSON object
+/// as one getFileTransfers entry; finished transfers are reported once more
+/// with their final state
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_setFileTransferCallback(
+    mut env: JNIEnv,
+    _class: JClass,
+    callback: JObject,
+) {
+    catch_panic!(
+        env,
+        {
+            if callback.is_null() {
+                crate::network::file_transfer::set_progress_callback(None);
+                return;
+            }
+            let global_callback = match env.new_global_ref(callback) {
+                Ok(global) => global,
+                Err(e) => {
+                    log::error!("Failed to reference file transfer callback: {}", e);
+                    return;
+                }
+            };
+            let jvm = env.get_java_vm().expect("Failed to get JavaVM");
+
+            crate::network::file_transfer::set_progress_callback(Some(Arc::new(
+                move |progress: &crate::network::TransferProgress| {
+                    let Ok(json) = serde_json::to_string(progress) else {
+                        return;
+                    };
+                    let mut env = match jvm.attach_current_thread() {
+                        Ok(env) => env,
+                        Err(e) => {
+                            log::error!(
+                                "Failed to attach thread for file transfer callback: {}",
+                                e
+                            );
+                            return;
+                        }
+                    };
+                    let Ok(json) = env.new_string(json) else {
+                        return;
+                    };
+                    if let Err(e) = env.call_method(
+                        global_callback.as_obj(),
+                        "onFileTransferProgress",
+                        "(Ljava/lang/String;)V",
+                        &[(&json).into()],
+                    ) {
+                        log::error!("File transfer callback failed: {}", e);
+                    }
+                },
+            )));
+            log::info!("File transfer callback registered");
+        },
+        ()
+    )
+}
+
+// ==================== RELAY FEDERATION ====================
+
+/// Verify and add a signed relay descriptor
+/// Returns 1 if added, 0 if an equally new descriptor for that relay is known, -1 if invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_addRelayDescriptor(
+    mut env: JNIEnv,
+    _class: JClass,
+    descriptor: JByteArray,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let descriptor = match jbytearray_to_vec(&mut env, descriptor) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert relay descriptor: {}", e);
+                    return -1;
+                }
+            };
+            match crate::network::relays::add_descriptor(&descriptor) {
+                Ok(true) => 1,
+                Ok(false) => 0,
+                Err(e) => {
+                    log::warn!("Relay descriptor rejected: {}", e);
+                    -1
+                }
+            }
+        },
+        -1
+    )
+}
+
+/// Pick the relay for the next message of `message_len` bytes
+/// Returns {"relayKey","onion","protocolVersion",...} or null if no relay can take it now
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_selectRelay(
+    mut env: JNIEnv,
+    _class: JClass,
+    message_len: jint,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let Some(relay) = crate::network::relays::select(message_len.max(0) as usize) else {
+                return std::ptr::null_mut();
+            };
+            let json = crate::network::relays::relay_json(&relay);
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// All known relays as a JSON array
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getRelaysJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::relays::relays_json();
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Stop using the relay with the given hex-encoded key
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_removeRelay(
+    mut env: JNIEnv,
+    _class: JClass,
+    relay_key_hex: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let relay_key: [u8; 32] = match jstring_to_string(&mut env, relay_key_hex)
+                .map_err(|e| e.to_string())
+                .and_then(|s| hex::decode(s).map_err(|e| e.to_string()))
+                .and_then(|v| v.try_into().map_err(|_| "wrong length".to_string()))
+            {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("Invalid relay key: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            if crate::network::relays::remove(&relay_key) {
+                JNI_TRUE
+            } else {
+                JNI_FALSE
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Mix a group fan-out with dummy envelopes and assign relays, in submission order
+/// drops_json: [{"mailbox":"hex","envelope":b64}], all envelopes the same size
+/// (pad each plaintext with padFanoutPlaintext before sealing)
+/// decoys_json: ["hex", ...] mailboxes of other contacts to receive dummies
+/// Returns [{"relayKey","onion","mailbox","envelope"}], "null" if the relays
+/// cannot carry the batch now, or null on invalid input
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_planGroupFanout(
+    mut env: JNIEnv,
+    _class: JClass,
+    drops_json: JString,
+    decoys_json: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let (drops_json, decoys_json) = match (
+                jstring_to_string(&mut env, drops_json),
+                jstring_to_string(&mut env, decoys_json),
+            ) {
+                (Ok(d), Ok(c)) => (d, c),
+                _ => {
+                    log::error!("Failed to convert fan-out arguments");
+                    return std::ptr::null_mut();
+                }
+            };
+            let json =
+                match crate::network::relays::plan_group_fanout_json(&drops_json, &decoys_json) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        log::error!("Group fan-out rejected: {}", e);
+                        return std::ptr::null_mut();
+                    }
+                };
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Pad a group message plaintext before sealing it for one recipient
+/// longest_len is the longest plaintext in the fan-out, so every recipient's
+/// envelope comes out the same size
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_padFanoutPlaintext(
+    mut env: JNIEnv,
+    _class: JClass,
+    plaintext: JByteArray,
+    longest_len: jint,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            use shield_protocol::protocol::fanout::{pad_plaintext, padded_len};
+
+            let plaintext = match jbytearray_to_vec(&mut env, plaintext) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let padded = padded_len(plaintext.len().max(longest_len.max(0) as usize))
+                .and_then(|len| pad_plaintext(&plaintext, len, &mut rand::rngs::OsRng));
+            match padded {
+                Ok(padded) => match vec_to_jbytearray(&mut env, &padded) {
+                    Ok(arr) => arr.into_raw(),
+                    Err(e) => {
+                        log::error!("Failed to create byte array: {}", e);
+                        std::ptr::null_mut()
+                    }
+                },
+                Err(e) => {
+                    log::error!("Failed to pad fan-out plaintext: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Strip fan-out padding from an opened group envelope
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_unpadFanoutPlaintext(
+    mut env: JNIEnv,
+    _class: JClass,
+    padded: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let padded = match jbytearray_to_vec(&mut env, padded) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert padded plaintext: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match shield_protocol::protocol::fanout::unpad_plaintext(&padded) {
+                Ok(plaintext) => match vec_to_jbytearray(&mut env, &plaintext) {
+                    Ok(arr) => arr.into_raw(),
+                    Err(e) => {
+                        log::error!("Failed to create byte array: {}", e);
+                        std::ptr::null_mut()
+                    }
+                },
+                Err(e) => {
+                    log::warn!("Invalid fan-out padding: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== CONTACT BACKUP ====================
+
+/// Merge the contact list with the backup on the relays and store the result
+/// snapshot_json is a ContactSnapshot ({"version":n,"contacts":[..]})
+/// Returns {"snapshot":{..},"stored":n,"replicas":n} with the merged list to
+/// apply locally, or null on failure
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_backupContacts(
+    mut env: JNIEnv,
+    _class: JClass,
+    master_seed: JByteArray,
+    snapshot_json: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let mut seed = match jbytearray_to_vec(&mut env, master_seed) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert seed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let snapshot = match jstring_to_string(&mut env, snapshot_json)
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    seed.zeroize();
+                    log::error!("Invalid contact snapshot: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let result =
+                GLOBAL_RUNTIME.block_on(crate::network::contact_backup::backup(&seed, snapshot));
+            seed.zeroize();
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    log::warn!("Contact backup failed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match serde_json::to_string(&outcome)
+                .map_err(|e| e.to_string())
+                .and_then(|json| string_to_jstring(&mut env, &json))
+            {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to encode backup outcome: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Fetch the contact backup after a reinstall (relay descriptors must be
+/// re-added first)
+/// Returns the ContactSnapshot JSON, "null" if no backup exists, or null on
+/// failure
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_restoreContacts(
+    mut env: JNIEnv,
+    _class: JClass,
+    master_seed: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let mut seed = match jbytearray_to_vec(&mut env, master_seed) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert seed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let result = GLOBAL_RUNTIME.block_on(crate::network::contact_backup::restore(&seed));
+            seed.zeroize();
+            let snapshot = match result {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    log::warn!("Contact restore failed: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match serde_json::to_string(&snapshot)
+                .map_err(|e| e.to_string())
+                .and_then(|json| string_to_jstring(&mut env, &json))
+            {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to encode contact snapshot: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Delete the contact backup from the relays
+/// Returns how many replicas were cleared, or -1 on failure
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_deleteContactBackup(
+    mut env: JNIEnv,
+    _class: JClass,
+    master_seed: JByteArray,
+) -> jint {
+    catch_panic!(
+        env,
+        {
+            let mut seed = match jbytearray_to_vec(&mut env, master_seed) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert seed: {}", e);
+                    return -1;
+                }
+            };
+            let result = GLOBAL_RUNTIME.block_on(crate::network::contact_backup::delete(&seed));
+            seed.zeroize();
+            match result {
+                Ok(cleared) => cleared as jint,
+                Err(e) => {
+                    log::warn!("Contact backup deletion failed: {}", e);
+                    -1
+                }
+            }
+        },
+        -1
+    )
+}
+
+// ==================== SDK CONFIGURATION ====================
+
+/// Parse, validate and apply a ShieldConfig ("toml" or "json" format)
+/// Must run before any network I/O since it may change the packet size
+/// Returns false (nothing applied) if the config is invalid
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_applyShieldConfig(
+    mut env: JNIEnv,
+    _class: JClass,
+    config_text: JString,
+    format: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let text = match jstring_to_string(&mut env, config_text) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert config text: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let format = match jstring_to_string(&mut env, format) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert config format: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let config = match format.to_ascii_lowercase().as_str() {
+                "toml" => crate::config::ShieldConfig::from_toml(&text),
+                "json" => crate::config::ShieldConfig::from_json(&text),
+                other => Err(crate::config::ConfigError::UnknownFormat(other.to_string())),
+            };
+            match config.and_then(|c| c.apply()) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::warn!("Rejected Shield config: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// The applied ShieldConfig as JSON (defaults if none was applied)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getShieldConfigJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = match crate::config::current().to_json() {
+                Ok(json) => json,
+                Err(e) => {
+                    log::error!("Failed to encode config: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== CRYPTO SELF-TEST ====================
+
+/// Run the Shield Protocol known-answer tests and return the report as JSON
+/// ({"checks":[{"algorithm","name","passed","detail"}]}). Callers must refuse
+/// to start if any check has passed=false. Returns null only on JNI failure.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_runCryptoSelfTest(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let report = shield_protocol::selftest();
+            if !report.passed() {
+                log::error!("Crypto self-test failed");
+            }
+            let json = match serde_json::to_string(&report) {
+                Ok(json) => json,
+                Err(e) => {
+                    log::error!("Failed to encode self-test report: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== CRYPTO INVENTORY ====================
+
+/// Algorithms, parameter sets, versions and features compiled into this
+/// build as JSON, plus its "fingerprint" for audit logs
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getCryptoInventoryJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let inventory = shield_protocol::inventory();
+            let json = serde_json::json!({
+                "fingerprint": inventory.fingerprint(),
+                "inventory": inventory,
+            })
+            .to_string();
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== DELIVERY DIAGNOSTICS ====================
+
+/// Drain queued outbox events as a JSON array, oldest first:
+/// [{"recipient","at_ms","failure":null|{"kind",...},"retryable"}]
+/// failure.kind is tor_unreachable, onion_offline, handshake_rejected,
+/// recipient_declined, timeout or connection_lost; most carry a "stage"
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_drainOutboxEventsJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::delivery::drain_events_json();
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Delivery estimate for a contact, for status text instead of a spinner:
+/// {"recipient","reachability","probability","expectedLatencyMs","p90LatencyMs",
+///  "rttSamples","recentAttempts","lastDeliveredMs","lastFailure","tor":{..}}
+/// reachability is likely, uncertain, likely_unreachable, tor_not_ready or unknown
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getDeliveryEstimateJson(
+    mut env: JNIEnv,
+    _class: JClass,
+    recipient_onion: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let recipient = match jstring_to_string(&mut env, recipient_onion) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert recipient: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let estimate = crate::network::health::estimate(&recipient);
+            let json = serde_json::to_string(&estimate).unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Overall network health:
+/// {"tor":{"bootstrapPercent","circuitsEstablished"},"contactsTracked",
+///  "recentDelivered","recentFailed","medianHandshakeMs"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getNetworkHealthJson(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let health = crate::network::health::network_health();
+            let json = serde_json::to_string(&health).unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Drop a deleted contact's delivery history
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_forgetDeliveryHistory(
+    mut env: JNIEnv,
+    _class: JClass,
+    recipient_onion: JString,
+) {
+    catch_panic!(
+        env,
+        {
+            if let Ok(recipient) = jstring_to_string(&mut env, recipient_onion) {
+                crate::network::health::forget(&recipient);
+            }
+        },
+        ()
+    )
+}
+
+// ==================== MESSAGE PROCESSING PLUGINS ====================
+
+/// Run the registered message processors over a decrypted payload
+/// Call with inbound=true after decrypting (before storing) and
+/// inbound=false before encrypting an outgoing message
+/// Returns JSON {"deliver":true,"body":b64,"annotations":{..},"violations":[..]}
+/// or {"deliver":false,"droppedBy","reason","violations"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_processMessagePayload(
+    mut env: JNIEnv,
+    _class: JClass,
+    inbound: jboolean,
+    contact_id: JString,
+    message_id: JString,
+    timestamp: jlong,
+    body: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            use shield_protocol::protocol::middleware::{Direction, MessageContext};
+
+            let contact = match jstring_to_contact_id(&mut env, contact_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to convert contact id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let message_id = match jstring_to_string(&mut env, message_id) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert message id: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let body = match jbytearray_to_vec(&mut env, body) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to convert payload: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let ctx = MessageContext {
+                direction: if inbound == JNI_TRUE {
+                    Direction::Inbound
+                } else {
+                    Direction::Outbound
+                },
+                contact,
+                message_id,
+                timestamp: timestamp.max(0) as u64,
+            };
+            let (outcome, violations) = crate::plugins::process(&ctx, body);
+            let json = crate::plugins::outcome_json(&outcome, &violations);
+            match string_to_jstring(&mut env, &json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== INBOUND EVENT QUEUE ====================
+
+/// Route inbound traffic to the unified queue instead of the pollIncoming*
+/// channels. store_path is a file for unacknowledged events (empty = memory
+/// only); events still in it from a previous run are reloaded
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_enableInboundQueue(
+    mut env: JNIEnv,
+    _class: JClass,
+    store_path: JString,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let path = match jstring_to_string(&mut env, store_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert store path: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let store: Option<Box<dyn crate::network::inbox::InboxStore>> = if path.is_empty() {
+                None
+            } else {
+                Some(Box::new(crate::network::inbox::FileInboxStore::new(path)))
+            };
+            match crate::network::inbox::enable(store) {
+                Ok(()) => JNI_TRUE,
+                Err(e) => {
+                    log::error!("Failed to enable inbound queue: {}", e);
+                    JNI_FALSE
+                }
+            }
+        },
+        JNI_FALSE
+    )
+}
+
+/// Go back to the per-type pollIncoming* channels
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_disableInboundQueue(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::inbox::disable() }, ())
+}
+
+/// Events after cursor as a JSON array, oldest first:
+/// [{"seq","kind","connection_id","received_at_ms","payload":b64}]
+/// Blocks up to timeout_ms when nothing is pending; call from a worker thread.
+/// Events are not removed until ackInboundEvents
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_pollInboundEvents(
+    mut env: JNIEnv,
+    _class: JClass,
+    cursor: jlong,
+    max_events: jint,
+    timeout_ms: jlong,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = crate::network::inbox::wait_after_json(
+                cursor.max(0) as u64,
+                max_events.max(1) as usize,
+                std::time::Duration::from_millis(timeout_ms.max(0) as u64),
+            );
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Events up to and including seq have been handled and can be forgotten
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_ackInboundEvents(
+    mut env: JNIEnv,
+    _class: JClass,
+    seq: jlong,
+) {
+    catch_panic!(
+        env,
+        { crate::network::inbox::ack_through(seq.max(0) as u64) },
+        ()
+    )
+}
+
+// ==================== PING/PONG SESSION PERSISTENCE ====================
+
+/// Mirror pending Ping/Pong/ACK sessions to store_path and load what it kept
+/// from before the last restart (expired entries dropped, newest copy wins).
+/// Call once at startup, before listeners run.
+/// Returns JSON {"restored","expired","duplicates"}, or null on failure
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_restorePingPongSessions(
+    mut env: JNIEnv,
+    _class: JClass,
+    store_path: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let path = match jstring_to_string(&mut env, store_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to convert store path: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let store = Box::new(crate::network::pingpong::FileSessionStore::new(path));
+            let stats = match crate::network::pingpong::restore_sessions(store) {
+                Ok(stats) => stats,
+                Err(e) => {
+                    log::error!("Failed to restore ping/pong sessions: {}", e);
+                    return std::ptr::null_mut();
+                }
+            };
+            let json = serde_json::to_string(&stats).unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Stop writing sessions to the store; sessions stay in memory
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_detachPingPongSessionStore(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(
+        env,
+        { crate::network::pingpong::detach_session_store() },
+        ()
+    )
+}
+
+// ==================== INBOUND DISPATCHER ====================
+
+/// Feed a dispatcher subscription into one of the context's poll receivers
+fn forward_frames<T: Send + 'static>(
+    kind: crate::network::InboundKind,
+    receiver: &OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<T>>>>,
+    map: impl Fn(crate::network::InboundFrame) -> T + Send + 'static,
+) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    if receiver.set(Arc::new(Mutex::new(rx))).is_err() {
+        log::warn!("{:?} receiver already initialized, not subscribing", kind);
+        return;
+    }
+    let mut frames = crate::network::dispatcher::subscribe(kind);
+    GLOBAL_RUNTIME.spawn(async move {
+        while let Some(frame) = frames.recv().await {
+            if tx.send(map(frame)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Start the hidden service listener with core-side routing: frames are
+/// checked once (framing, sender key, replays) and delivered to the active
+/// context's pollIncoming* queues. Replaces startHiddenServiceListener plus
+/// startTapListener/startPongListener/startAckListener/startFriendRequestListener
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startInboundDispatcher(
+    mut env: JNIEnv,
+    _class: JClass,
+    port: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            use crate::network::InboundKind;
+
+            let tor_manager = get_tor_manager();
+            let result = GLOBAL_RUNTIME.block_on(async {
+                let mut manager = tor_manager.lock().unwrap();
+                crate::network::dispatcher::start(&mut manager, port as u16).await
+            });
+            if let Err(e) = result {
+                let _ = env.throw_new(
+                    "java/lang/RuntimeException",
+                    format!("Failed to start inbound dispatcher: {}", e),
+                );
+                return JNI_FALSE;
+            }
+
+            let ctx = active_context();
+            let pair = |f: crate::network::InboundFrame| (f.connection_id, f.wire);
+            let bytes = |f: crate::network::InboundFrame| f.wire;
+            forward_frames(InboundKind::Ping, &ctx.ping_receiver, pair);
+            forward_frames(InboundKind::Pong, &ctx.pong_receiver, pair);
+            forward_frames(InboundKind::Message, &ctx.message_receiver, pair);
+            forward_frames(InboundKind::CallSignaling, &ctx.voice_receiver, pair);
+            forward_frames(InboundKind::Ack, &ctx.ack_receiver, pair);
+            forward_frames(InboundKind::Tap, &ctx.tap_receiver, bytes);
+            forward_frames(
+                InboundKind::FriendRequest,
+                &ctx.friend_request_receiver,
+                bytes,
+            );
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+/// Hand routing back to the per-type channels (the listener keeps running)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_stopInboundDispatcher(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::dispatcher::stop() }, ())
+}
+
+/// Dispatcher counters as JSON: {"delivered","unclaimed","duplicates","rejected"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getInboundDispatcherStats(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = serde_json::to_string(&crate::network::dispatcher::stats())
+                .unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ==================== HIDDEN SERVICE HEALTH ====================
+
+/// Periodically check that the message hidden service (createHiddenService)
+/// is published and reachable by dialing it through a fresh circuit, and
+/// re-publish it when it is not. intervalSecs <= 0 uses the default (10 min)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_startHiddenServiceHealthMonitor(
+    mut env: JNIEnv,
+    _class: JClass,
+    interval_secs: jint,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let tor_manager = get_tor_manager();
+            let onion_address = match tor_manager.lock().unwrap().get_hidden_service_address() {
+                Some(address) => address,
+                None => {
+                    log::error!("No hidden service to monitor - call createHiddenService first");
+                    return JNI_FALSE;
+                }
+            };
+
+            let key_manager = match crate::ffi::keystore::key_provider(&mut env) {
+                Ok(km) => km,
+                Err(e) => {
+                    log::error!("Failed to get KeyManager: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let hs_private_key = match key_manager.hidden_service_private_key(&mut env) {
+                Ok(k) => zeroize::Zeroizing::new(k),
+                Err(e) => {
+                    log::error!("Failed to get hidden service key: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+
+            let republish: crate::network::hs_health::RepublishFn = Arc::new(move || {
+                GLOBAL_RUNTIME
+                    .block_on(async {
+                        let mut manager = tor_manager.lock().unwrap();
+                        manager.republish_hidden_service(&hs_private_key).await
+                    })
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            });
+
+            let mut policy = crate::network::HsHealthPolicy::DEFAULT;
+            if interval_secs > 0 {
+                policy.interval_secs = interval_secs as u64;
+            }
+            let _guard = GLOBAL_RUNTIME.enter();
+            crate::network::hs_health::start(onion_address, policy, republish);
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_stopHiddenServiceHealthMonitor(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::hs_health::stop() }, ())
+}
+
+/// Hidden service health as JSON: {"status","running","descriptorUploads",
+/// "descriptorFailures","consecutiveFailures","lastCheckMs","lastReachableMs",
+/// "lastDialMs","lastError","republishCount","republishPending"}
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_getHiddenServiceHealth(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = serde_json::to_string(&crate::network::hs_health::report())
+                .unwrap_or_else(|_| "{}".to_string());
+            match string_to_jstring(&mut env, &json) {
+                Ok(s) => s.into_raw(),
+                Err(e) => {
+                    log::error!("Failed to create JSON string: {}", e);
+                    std::ptr::null_mut()
+                }
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Run a health check now instead of waiting for the interval
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_checkHiddenServiceHealthNow(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::hs_health::check_now() }, ())
+}
+
+/// Re-publish the hidden service descriptor now (the monitor must be running)
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_requestHiddenServiceRepublish(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    catch_panic!(env, { crate::network::hs_health::request_republish() }, ())
+}
+
 // ==================== AETHERNET MULTI-TRANSPORT MESH NETWORKING ====================
 
 static AETHERNET: once_cell::sync::OnceCell<Mutex<crate::aethernet::AetherNet>> =
@@ -0,0 +1,312 @@
+/// Per-profile protocol contexts.
+///
+/// Everything that belongs to one profile lives in a [`ProtocolContext`]:
+/// its TorManager, the receive channels the listener feeds, its CRDT groups,
+/// where its identity keys come from, its inbound dispatcher, its
+/// per-contact protocol state (reactions, receipts, trust, health, silence,
+/// relays, RPC calls, ...) and the alarms and events queued for its app.
+/// Foreign code holds contexts as opaque handles (see `handles`); entry
+/// points without a handle act on the *active* context, which is a default
+/// instance until [`activate_context`] picks another.
+///
+/// Process-wide resources stay global: the Tokio runtime, the voice
+/// listener, JVM callbacks, the routing senders and Tor control state in
+/// `network::tor`, the ping/pong session tables and PING replay cache, and
+/// app-wide settings (ports, timeouts, config, plugins). So only one context
+/// should run a hidden-service listener at a time.
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "software-keys")]
+use std::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::crypto::ack_state::AckStates;
+use crate::crypto::encryption::{EncryptionError, PendingRatchets};
+use crate::crypto::key_change::KeyChangeGuard;
+use crate::ffi::handles::{HandleRegistry, INVALID_HANDLE};
+use crate::ffi::session_cache::SessionKeyCache;
+#[cfg(feature = "software-keys")]
+use crate::ffi::software_keys::SoftwareKeys;
+use crate::network::bandwidth::BandwidthState;
+use crate::network::delivery::OutboxEvent;
+use crate::network::downgrade::DowngradeAlarm;
+use crate::network::health::HealthBook;
+use crate::network::pingpong::SessionStore;
+use crate::network::security_events::{SecurityEventPolicy, SecurityMonitor};
+use crate::network::send_lanes::{LanePermits, MAX_CONCURRENT_SENDS};
+use crate::network::TorManager;
+use crate::protocol::presence::{PresenceBook, PresenceConfig};
+use crate::protocol::reaction::ReactionBook;
+use crate::protocol::receipts::{ReceiptBatcher, ReceiptConfig};
+use crate::protocol::relay::RelaySelector;
+use crate::protocol::silence::NetworkSilence;
+use crate::protocol::topic::TopicHub;
+use crate::protocol::ContactId;
+
+pub(crate) type PairReceiver = OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<(u64, Vec<u8>)>>>>;
+pub(crate) type VecReceiver = OnceCell<Arc<Mutex<mpsc::UnboundedReceiver<Vec<u8>>>>>;
+
+/// One independent protocol instance (e.g. one profile).
+pub(crate) struct ProtocolContext {
+    pub(crate) tor_manager: OnceCell<Arc<Mutex<TorManager>>>,
+    pub(crate) ping_receiver: PairReceiver,
+    pub(crate) tap_receiver: VecReceiver,
+    pub(crate) pong_receiver: PairReceiver,
+    pub(crate) ack_receiver: PairReceiver,
+    pub(crate) message_receiver: PairReceiver,
+    pub(crate) voice_receiver: PairReceiver,
+    pub(crate) friend_request_receiver: VecReceiver,
+    #[cfg(target_os = "android")]
+    pub(crate) crdt: crate::ffi::crdt::CrdtState,
+    /// Set by useSoftwareKeys; `None` means keys come from KeyManager.
+    #[cfg(feature = "software-keys")]
+    pub(crate) software_keys: RwLock<Option<Arc<SoftwareKeys>>>,
+    /// Pinned identity keys and quarantined messages.
+    key_change: Mutex<KeyChangeGuard>,
+    pub(crate) ack_states: Mutex<AckStates>,
+    pub(crate) pending_ratchets: Mutex<PendingRatchets>,
+    pub(crate) inbox: crate::network::inbox::InboxState,
+    pub(crate) presence: Mutex<PresenceBook>,
+    pub(crate) dispatcher: crate::network::dispatcher::DispatcherState,
+    pub(crate) hs_health: crate::network::hs_health::HsHealthState,
+    pub(crate) file_transfers: Arc<crate::network::file_transfer::FileTransferState>,
+    pub(crate) bandwidth: Arc<BandwidthState>,
+    /// Outbound send permits, shaped by `bandwidth`.
+    pub(crate) send_permits: LanePermits,
+    /// Where ping/pong/ACK sessions are mirrored (see `restore_sessions`).
+    pub(crate) session_store: Mutex<Option<Box<dyn SessionStore>>>,
+    pub(crate) topics: Mutex<TopicHub>,
+    pub(crate) ordering: crate::network::ordering::OrderingState,
+    pub(crate) recall: crate::network::recall::RecallState,
+    pub(crate) send_intents: Mutex<crate::network::send_intents::SendIntents>,
+    pub(crate) session_keys: Mutex<SessionKeyCache>,
+    pub(crate) reactions: Mutex<ReactionBook>,
+    pub(crate) receipts: Mutex<ReceiptBatcher>,
+    pub(crate) health: Mutex<HealthBook>,
+    pub(crate) security_events: Mutex<SecurityMonitor>,
+    /// Delivery outcomes the app has not drained yet.
+    pub(crate) outbox_events: Mutex<VecDeque<OutboxEvent>>,
+    pub(crate) downgrade_alarms: Mutex<VecDeque<DowngradeAlarm>>,
+    pub(crate) first_contact: crate::network::first_contact::FirstContactState,
+    pub(crate) silence: Mutex<NetworkSilence>,
+    pub(crate) relays: Mutex<RelaySelector>,
+    pub(crate) rpc: crate::network::rpc::RpcState,
+    pub(crate) message_ids: crate::network::message_ids::MessageIdState,
+}
+
+impl ProtocolContext {
+    fn new() -> Self {
+        let bandwidth = Arc::new(BandwidthState::default());
+        ProtocolContext {
+            tor_manager: OnceCell::new(),
+            ping_receiver: OnceCell::new(),
+            tap_receiver: OnceCell::new(),
+            pong_receiver: OnceCell::new(),
+            ack_receiver: OnceCell::new(),
+            message_receiver: OnceCell::new(),
+            voice_receiver: OnceCell::new(),
+            friend_request_receiver: OnceCell::new(),
+            #[cfg(target_os = "android")]
+            crdt: crate::ffi::crdt::CrdtState::default(),
+            #[cfg(feature = "software-keys")]
+            software_keys: RwLock::new(None),
+            key_change: Mutex::new(KeyChangeGuard::new()),
+            ack_states: Mutex::new(AckStates::new()),
+            pending_ratchets: Mutex::new(PendingRatchets::new()),
+            inbox: Default::default(),
+            presence: Mutex::new(PresenceBook::new(PresenceConfig::default())),
+            dispatcher: Default::default(),
+            hs_health: Default::default(),
+            file_transfers: Default::default(),
+            send_permits: LanePermits::new(MAX_CONCURRENT_SENDS)
+                .with_bandwidth_cap(bandwidth.clone()),
+            bandwidth,
+            topics: Mutex::new(TopicHub::new()),
+            ordering: Default::default(),
+            recall: Default::default(),
+            send_intents: Default::default(),
+            session_keys: Default::default(),
+            reactions: Mutex::new(ReactionBook::new()),
+            receipts: Mutex::new(ReceiptBatcher::new(ReceiptConfig::default())),
+            health: Mutex::new(HealthBook::new()),
+            security_events: Mutex::new(SecurityMonitor::new(SecurityEventPolicy::default())),
+            outbox_events: Default::default(),
+            downgrade_alarms: Default::default(),
+            first_contact: Default::default(),
+            silence: Mutex::new(NetworkSilence::new()),
+            relays: Mutex::new(RelaySelector::new()),
+            rpc: Default::default(),
+            message_ids: Default::default(),
+            session_store: Mutex::new(None),
+        }
+    }
+
+    /// Run `f` against this context's key change guard.
+    pub(crate) fn with_key_change_guard<R>(&self, f: impl FnOnce(&mut KeyChangeGuard) -> R) -> R {
+        f(&mut self.key_change.lock().unwrap())
+    }
+
+    /// Hold back a ratchet step until the PING_ACK arrives. Refused while a
+    /// verified contact's key change is unapproved.
+    pub(crate) fn store_pending_ratchet(
+        &self,
+        contact_id: &ContactId,
+        message_id: &str,
+        next_chain_key: [u8; 32],
+        next_sequence: u64,
+    ) -> Result<(), EncryptionError> {
+        self.with_key_change_guard(|guard| guard.check_send(contact_id))
+            .map_err(|_| EncryptionError::IdentityKeyChanged)?;
+        self.pending_ratchets.lock().unwrap().store(
+            contact_id,
+            message_id,
+            next_chain_key,
+            next_sequence,
+        );
+        Ok(())
+    }
+}
+
+static CONTEXTS: Lazy<HandleRegistry<ProtocolContext>> = Lazy::new(HandleRegistry::new);
+
+/// Handle used by handle-less entry points; 0 until the default is created.
+static ACTIVE_CONTEXT: AtomicU64 = AtomicU64::new(INVALID_HANDLE);
+
+/// The context handle-less entry points act on (created on first use).
+pub(crate) fn active_context() -> Arc<ProtocolContext> {
+    let handle = ACTIVE_CONTEXT.load(Ordering::Acquire);
+    if let Some(ctx) = CONTEXTS.get(handle) {
+        return ctx;
+    }
+    // First use: register the default instance. A racing caller that loses
+    // the swap drops its instance and uses the winner's.
+    let default = CONTEXTS.insert(ProtocolContext::new());
+    match ACTIVE_CONTEXT.compare_exchange(handle, default, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => CONTEXTS
+            .get(default)
+            .expect("default context just registered"),
+        Err(winner) => {
+            CONTEXTS.remove(default);
+            CONTEXTS
+                .get(winner)
+                .expect("active context is never destroyed")
+        }
+    }
+}
+
+/// Handle of the active context (creates the default context on first call).
+pub(crate) fn active_handle() -> u64 {
+    active_context();
+    ACTIVE_CONTEXT.load(Ordering::Acquire)
+}
+
+/// Register a new context; it is not used until activated.
+pub(crate) fn create_context() -> u64 {
+    CONTEXTS.insert(ProtocolContext::new())
+}
+
+/// Make `handle` the active context. False for unknown or destroyed handles.
+pub(crate) fn activate_context(handle: u64) -> bool {
+    if !CONTEXTS.contains(handle) {
+        return false;
+    }
+    ACTIVE_CONTEXT.store(handle, Ordering::Release);
+    true
+}
+
+/// Release a context and everything it owns. The active context cannot be
+/// destroyed. False if nothing was released.
+pub(crate) fn destroy_context(handle: u64) -> bool {
+    handle != ACTIVE_CONTEXT.load(Ordering::Acquire) && CONTEXTS.remove(handle).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_exchange::generate_static_keypair;
+    use crate::crypto::pqc::TrustLevel;
+    use crate::network::security_events::SecurityEventKind;
+    use crate::protocol::silence::SilenceSpec;
+
+    #[test]
+    fn test_contexts_keep_per_contact_state_apart() {
+        let a = CONTEXTS.get(create_context()).unwrap();
+        let b = CONTEXTS.get(create_context()).unwrap();
+
+        let id = a
+            .with_key_change_guard(|g| g.register(&[9u8; 32], TrustLevel::Verified))
+            .unwrap();
+        a.with_key_change_guard(|g| g.observe(&id, &[8u8; 32]))
+            .unwrap();
+        assert!(matches!(
+            a.store_pending_ratchet(&id, "m1", [1u8; 32], 1),
+            Err(EncryptionError::IdentityKeyChanged)
+        ));
+        // The other profile never pinned this key
+        b.store_pending_ratchet(&id, "m1", [1u8; 32], 1).unwrap();
+        assert_eq!(b.pending_ratchets.lock().unwrap().len(), 1);
+        assert!(a.pending_ratchets.lock().unwrap().is_empty());
+
+        b.presence.lock().unwrap().set_sharing(&id, true);
+        assert!(!a.presence.lock().unwrap().is_sharing(&id));
+
+        let (_, ours) = generate_static_keypair();
+        let (theirs, _) = generate_static_keypair();
+        a.session_keys
+            .lock()
+            .unwrap()
+            .shared_secret(&id, &ours, &theirs)
+            .unwrap();
+        assert!(b.session_keys.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_contexts_keep_network_books_apart() {
+        let a = CONTEXTS.get(create_context()).unwrap();
+        let b = CONTEXTS.get(create_context()).unwrap();
+        let id = crate::test_contact(42);
+
+        a.reactions
+            .lock()
+            .unwrap()
+            .set_local(&id, "m1", Some("👍"))
+            .unwrap();
+        let views = b.reactions.lock().unwrap().fold(&id, ["m1"]);
+        assert!(views
+            .iter()
+            .all(|v| v.local.is_none() && v.counts.is_empty()));
+
+        a.receipts.lock().unwrap().record(id, 1, 0);
+        assert!(a.receipts.lock().unwrap().next_deadline().is_some());
+        assert!(b.receipts.lock().unwrap().next_deadline().is_none());
+
+        let spec = SilenceSpec {
+            wake_contact: id,
+            wake_public_key: [1; 32],
+            max_clock_skew_secs: 60,
+        };
+        a.silence.lock().unwrap().enter(spec, [2; 32], 0);
+        assert!(a.silence.lock().unwrap().is_silent());
+        assert!(!b.silence.lock().unwrap().is_silent());
+
+        let kind = SecurityEventKind::ReplayDetected {
+            detail: "ping".into(),
+        };
+        assert!(a.security_events.lock().unwrap().raise(kind, Some(id), 0));
+        assert!(b.security_events.lock().unwrap().take().is_empty());
+        assert_eq!(a.security_events.lock().unwrap().take().len(), 1);
+    }
+
+    #[test]
+    fn test_active_context_cannot_be_destroyed() {
+        let active = active_handle();
+        assert!(!destroy_context(active));
+        let other = create_context();
+        assert!(destroy_context(other));
+        assert!(!destroy_context(other));
+        assert!(!activate_context(other));
+        assert_eq!(active_handle(), active);
+    }
+}
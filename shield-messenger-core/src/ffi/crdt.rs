@@ -1,7 +1,8 @@
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
-/// CRDT group JNI bridge — 5 core + 4 sync (stub) entry points.
+/// CRDT group JNI bridge — 6 core + 4 sync (stub) entry points.
 ///
-/// In-memory group state lives behind `GROUPS` (Mutex<HashMap>).
+/// In-memory group state lives in the active `ProtocolContext`'s
+/// `CrdtState` (Mutex<HashMap>), so each context has its own groups.
 /// Kotlin persists ops in Room/SQLCipher; Rust owns the derived state.
 ///
 /// **Core API:**
@@ -10,6 +11,15 @@ use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 /// - `crdtApplyOps` — apply batch of received ops → JSON result
 /// - `crdtCreateOp` — create + sign + apply → JSON with op bytes + metadata
 /// - `crdtQuery` — query derived state → JSON
+/// - `crdtSetReceiptPolicy` — local privacy switch for outgoing receipts
+/// - `crdtCompactOps` — drop superseded ops from a group's log → JSON
+/// - `crdtSealAvatar` / `crdtOpenAvatar` — encrypt and verify avatar blobs
+///
+/// **Address book journal** (personal data, synced between own devices):
+/// - `crdtLoadAddressBook`, `crdtLinkAddressBookDevice`,
+///   `crdtRecordContactChange`, `crdtQueryAddressBook`
+/// - `crdtAddressBookDigest`, `crdtAddressBookMissingEntries`,
+///   `crdtApplyAddressBookEntries` — differential catch-up of a linked device
 ///
 /// **Sync stubs (Phase 6):**
 /// - `crdtGenerateSyncHello`, `crdtProcessSyncHello`,
@@ -17,20 +27,28 @@ use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use jni::objects::{JByteArray, JClass, JString};
 use jni::sys::{jboolean, jbyteArray, jstring, JNI_FALSE, JNI_TRUE};
 use jni::JNIEnv;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use std::sync::OnceLock;
 
+use crate::crdt::address_book::{AddressBook, ContactChange, JournalDigest, JournalEntry};
 use crate::crdt::apply::GroupState;
+use crate::crdt::avatar::AvatarVariant;
+use crate::crdt::clock::{ClockError, LamportClock, MemoryClockStore, OpBuilder};
+use crate::crdt::feed::MembershipEventKind;
 use crate::crdt::ids::{DeviceID, GroupID, OpID};
 use crate::crdt::limits::{HARD_CAP_OPS_PER_GROUP, MAX_OP_PAYLOAD_BYTES};
 use crate::crdt::messages::MessageEntry;
 use crate::crdt::ops::{
-    generate_msg_id, GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload,
-    MemberRemovePayload, MetadataKey, MetadataSetPayload, MsgAddPayload, MsgDeletePayload,
-    MsgEditPayload, OpEnvelope, OpType, ReactionSetPayload, RemoveReason, Role, RoleSetPayload,
+    group_msg_id, AnonKeyRegisterPayload, GroupCreatePayload, MemberAcceptPayload,
+    MemberInvitePayload, MemberRemovePayload, MetadataKey, MetadataSetPayload, MsgAddPayload,
+    MsgDeletePayload, MsgEditPayload, OpEnvelope, OpError, OpType, OwnerTransferPayload,
+    ReactionSetPayload, ReceiptSetPayload, ReceiptStatus, RemoveReason, Role, RoleSetPayload,
 };
+use crate::crdt::query::{MessageQuery, MessageRange};
+use crate::ffi::context::active_context;
 
 // ---------------------------------------------------------------------------
 // Constants
@@ -42,22 +60,58 @@ const MAX_SERIALIZED_OP_BYTES: usize = MAX_OP_PAYLOAD_BYTES + 1024; // ~65 KB
 /// Max ops per crdtApplyOps call (sync receive / incremental apply).
 const MAX_OPS_PER_APPLY_BATCH: usize = 2_000;
 
+/// Max serialized journal entry size: nickname limit + signature and ids.
+const MAX_SERIALIZED_JOURNAL_ENTRY_BYTES: usize = 1024;
+
+/// Max entries in a full address book journal (crdtLoadAddressBook).
+const MAX_JOURNAL_ENTRIES: usize = 100_000;
+
 // ---------------------------------------------------------------------------
-// Global state
+// Per-context state
 // ---------------------------------------------------------------------------
 
-/// In-memory group states, keyed by GroupID.
-static GROUPS: OnceLock<Mutex<HashMap<GroupID, GroupState>>> = OnceLock::new();
+/// CRDT state owned by one `ProtocolContext`.
+pub(crate) struct CrdtState {
+    /// In-memory group states, keyed by GroupID.
+    groups: Mutex<HashMap<GroupID, GroupState>>,
+    /// The local device's lamport clock per loaded group.
+    clocks: Mutex<LocalClocks>,
+    /// Local receipt privacy: whether this device sends Delivered / Read receipts.
+    send_delivered_receipts: AtomicBool,
+    send_read_receipts: AtomicBool,
+    /// The account's address book journal, shared by its linked devices.
+    address_book: Mutex<Option<AddressBook>>,
+}
 
-/// Per-group lamport counter for the local device.
-static MY_LAMPORT: OnceLock<Mutex<HashMap<GroupID, u64>>> = OnceLock::new();
+impl Default for CrdtState {
+    fn default() -> Self {
+        CrdtState {
+            groups: Mutex::new(HashMap::new()),
+            clocks: Mutex::new(LocalClocks::default()),
+            send_delivered_receipts: AtomicBool::new(true),
+            send_read_receipts: AtomicBool::new(true),
+            address_book: Mutex::new(None),
+        }
+    }
+}
 
-fn get_groups() -> &'static Mutex<HashMap<GroupID, GroupState>> {
-    GROUPS.get_or_init(|| Mutex::new(HashMap::new()))
+#[derive(Default)]
+struct LocalClocks {
+    clocks: HashMap<GroupID, LamportClock>,
+    /// Outlives unloads, so a reloaded group resumes past every value issued.
+    store: MemoryClockStore,
 }
 
-fn get_lamport_map() -> &'static Mutex<HashMap<GroupID, u64>> {
-    MY_LAMPORT.get_or_init(|| Mutex::new(HashMap::new()))
+/// Applied op count of every group loaded in the active context (for maintenance).
+pub(crate) fn loaded_group_sizes() -> Vec<(GroupID, usize)> {
+    active_context()
+        .crdt
+        .groups
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(gid, state)| (*gid, state.op_count))
+        .collect()
 }
 
 // ---------------------------------------------------------------------------
@@ -108,11 +162,15 @@ fn parse_op_type(s: &str) -> Result<OpType, String> {
         "MemberAccept" => Ok(OpType::MemberAccept),
         "MemberRemove" => Ok(OpType::MemberRemove),
         "RoleSet" => Ok(OpType::RoleSet),
+        "OwnerTransfer" => Ok(OpType::OwnerTransfer),
         "MsgAdd" => Ok(OpType::MsgAdd),
         "MsgEdit" => Ok(OpType::MsgEdit),
         "MsgDelete" => Ok(OpType::MsgDelete),
         "ReactionSet" => Ok(OpType::ReactionSet),
+        "ReceiptSet" => Ok(OpType::ReceiptSet),
         "MetadataSet" => Ok(OpType::MetadataSet),
+        "AnonKeyRegister" => Ok(OpType::AnonKeyRegister),
+        "AnonMsgAdd" => Ok(OpType::AnonMsgAdd),
         other => Err(format!("Unknown op type: {}", other)),
     }
 }
@@ -135,11 +193,21 @@ fn parse_remove_reason(s: &str) -> Result<RemoveReason, String> {
     }
 }
 
+fn parse_receipt_status(s: &str) -> Result<ReceiptStatus, String> {
+    match s {
+        "Delivered" => Ok(ReceiptStatus::Delivered),
+        "Read" => Ok(ReceiptStatus::Read),
+        other => Err(format!("Unknown receipt status: {}", other)),
+    }
+}
+
 fn parse_metadata_key(s: &str) -> Result<MetadataKey, String> {
     match s {
         "Name" => Ok(MetadataKey::Name),
         "Avatar" => Ok(MetadataKey::Avatar),
         "Topic" => Ok(MetadataKey::Topic),
+        "AnonymousPosting" => Ok(MetadataKey::AnonymousPosting),
+        "JoinRequirement" => Ok(MetadataKey::JoinRequirement),
         other => Err(format!("Unknown metadata key: {}", other)),
     }
 }
@@ -184,6 +252,16 @@ fn decode_length_prefixed_ops(
     max_ops: usize,
     max_op_size: usize,
 ) -> Result<Vec<OpEnvelope>, String> {
+    decode_length_prefixed(data, max_ops, max_op_size, OpEnvelope::from_bytes)
+}
+
+/// Same framing for any record type (address book journal entries).
+fn decode_length_prefixed<T>(
+    data: &[u8],
+    max_ops: usize,
+    max_op_size: usize,
+    decode: fn(&[u8]) -> Result<T, OpError>,
+) -> Result<Vec<T>, String> {
     let mut ops = Vec::new();
     let mut offset = 0;
 
@@ -211,7 +289,7 @@ fn decode_length_prefixed_ops(
             ));
         }
 
-        let op = OpEnvelope::from_bytes(&data[offset..offset + len])
+        let op = decode(&data[offset..offset + len])
             .map_err(|e| format!("Op decode at offset {}: {}", offset, e))?;
         ops.push(op);
         offset += len;
@@ -220,16 +298,40 @@ fn decode_length_prefixed_ops(
     Ok(ops)
 }
 
-/// Compute the next lamport for this device in a group.
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Inverse of [`decode_length_prefixed`].
+fn encode_length_prefixed(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(records.iter().map(|r| 4 + r.len()).sum());
+    for record in records {
+        out.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        out.extend_from_slice(record);
+    }
+    out
+}
+
+/// Reserve the next lamport for this device in a group.
 ///
 /// Ensures causal ordering: always greater than any seen lamport.
-fn next_lamport(group_id: &GroupID, state: &GroupState) -> u64 {
-    let mut lmap = get_lamport_map().lock().unwrap();
-    let group_max = state.max_lamport.values().max().copied().unwrap_or(0);
-    let my_current = *lmap.get(group_id).unwrap_or(&0);
-    let next = std::cmp::max(group_max, my_current) + 1;
-    lmap.insert(*group_id, next);
-    next
+fn next_op(
+    crdt: &CrdtState,
+    group_id: &GroupID,
+    state: &GroupState,
+    otype: OpType,
+) -> Result<OpBuilder, ClockError> {
+    let mut lmap = crdt.clocks.lock().unwrap();
+    let LocalClocks { clocks, store } = &mut *lmap;
+    let clock = match clocks.entry(*group_id) {
+        Entry::Occupied(e) => e.into_mut(),
+        Entry::Vacant(e) => e.insert(LamportClock::open(*group_id, &*store)?),
+    };
+    clock.observe_state(state)?;
+    clock.op_builder(otype, store, &mut rand::rngs::OsRng)
 }
 
 /// Throw IllegalArgumentException and return null.
@@ -312,7 +414,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtLoadGroup(
                 }
             };
 
-            let mut groups = get_groups().lock().unwrap();
+            let ctx = active_context();
+            let mut groups = ctx.crdt.groups.lock().unwrap();
             groups.insert(gid, state);
 
             log::info!("crdtLoadGroup: loaded {} ops for {}", op_count, gid);
@@ -344,13 +447,14 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtUnloadGroup(
                 }
             };
 
+            let ctx = active_context();
             {
-                let mut groups = get_groups().lock().unwrap();
+                let mut groups = ctx.crdt.groups.lock().unwrap();
                 groups.remove(&gid);
             }
             {
-                let mut lmap = get_lamport_map().lock().unwrap();
-                lmap.remove(&gid);
+                let mut lmap = ctx.crdt.clocks.lock().unwrap();
+                lmap.clocks.remove(&gid);
             }
 
             log::info!("crdtUnloadGroup: {}", gid);
@@ -396,7 +500,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtApplyOps(
                 Err(e) => throw_arg!(env, e),
             };
 
-            let mut groups = get_groups().lock().unwrap();
+            let ctx = active_context();
+            let mut groups = ctx.crdt.groups.lock().unwrap();
             let state = match groups.get_mut(&gid) {
                 Some(s) => s,
                 None => throw_state!(env, "Group not loaded — call crdtLoadGroup first"),
@@ -405,8 +510,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtApplyOps(
             let mut applied = 0u64;
             let mut rejected = 0u64;
 
-            for op in &ops {
-                match state.apply_op(op) {
+            for (op, result) in ops.iter().zip(state.apply_ops_batch(&ops, now_ms())) {
+                match result {
                     Ok(true) => applied += 1,
                     Ok(false) => {} // duplicate — silently skip
                     Err(e) => {
@@ -506,7 +611,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCreateOp(
             };
 
             // --- Get/create group state ---
-            let mut groups = get_groups().lock().unwrap();
+            let ctx = active_context();
+            let mut groups = ctx.crdt.groups.lock().unwrap();
             if otype == OpType::GroupCreate && !groups.contains_key(&gid) {
                 groups.insert(gid, GroupState::new(gid));
             }
@@ -516,25 +622,15 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCreateOp(
             };
 
             // --- Lamport + nonce ---
-            let lamport = if otype == OpType::GroupCreate {
-                1
-            } else {
-                next_lamport(&gid, state)
+            let builder = match next_op(&ctx.crdt, &gid, state, otype) {
+                Ok(b) => b,
+                Err(e) => throw_state!(env, format!("Lamport clock: {}", e)),
             };
-            let op_nonce: u64 = rand::random();
-            let author_device = DeviceID::from_pubkey(&pub_key);
+            let (lamport, op_nonce) = (builder.lamport(), builder.nonce());
 
             // --- Build payload and create signed op ---
             let envelope = match build_op_envelope(
-                &mut env,
-                gid,
-                otype,
-                &params,
-                lamport,
-                op_nonce,
-                pub_key,
-                &priv_key,
-                &author_device,
+                &mut env, &ctx.crdt, state, otype, &params, builder, pub_key, &priv_key,
             ) {
                 Some(op) => op,
                 None => return std::ptr::null_mut(), // exception already thrown
@@ -546,14 +642,6 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCreateOp(
                 throw_rt!(env, format!("Op apply failed: {}", e));
             }
 
-            // --- Update my lamport tracker ---
-            {
-                let mut lmap = get_lamport_map().lock().unwrap();
-                lmap.entry(gid)
-                    .and_modify(|l| *l = (*l).max(lamport))
-                    .or_insert(lamport);
-            }
-
             // --- Serialize and return JSON ---
             let op_bytes = match envelope.to_bytes() {
                 Ok(b) => b,
@@ -569,7 +657,7 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCreateOp(
 
             // Include auto-generated msg_id for MsgAdd
             if otype == OpType::MsgAdd {
-                let msg_id = generate_msg_id(&author_device, lamport, op_nonce);
+                let msg_id = group_msg_id(&gid, &pub_key, lamport, op_nonce);
                 json["msg_id_hex"] = serde_json::Value::String(hex::encode(msg_id));
             }
 
@@ -586,15 +674,15 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCreateOp(
 /// exception was thrown (caller should return null).
 fn build_op_envelope(
     env: &mut JNIEnv,
-    gid: GroupID,
+    crdt: &CrdtState,
+    state: &GroupState,
     otype: OpType,
     params: &serde_json::Value,
-    lamport: u64,
-    op_nonce: u64,
+    builder: OpBuilder,
     pub_key: [u8; 32],
     priv_key: &[u8; 32],
-    author_device: &DeviceID,
 ) -> Option<OpEnvelope> {
+    let gid = state.group_id;
     let result = match otype {
         OpType::GroupCreate => {
             let group_name = params["group_name"].as_str().unwrap_or("").to_string();
@@ -605,7 +693,7 @@ fn build_op_envelope(
                 group_name,
                 encrypted_group_secret: secret,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MemberInvite => {
             let pk_hex = params["invited_pubkey_hex"].as_str().unwrap_or("");
@@ -633,7 +721,7 @@ fn build_op_envelope(
                 role,
                 encrypted_group_secret: secret,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MemberAccept => {
             let id_hex = params["invite_op_id_hex"].as_str().unwrap_or("");
@@ -644,8 +732,16 @@ fn build_op_envelope(
                     return None;
                 }
             };
-            let payload = MemberAcceptPayload { invite_op_id };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            // Gated groups: CBOR AttributePresentation from the app
+            let attribute_proof = params["attribute_proof_b64"]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(|s| B64.decode(s).unwrap_or_default());
+            let payload = MemberAcceptPayload {
+                invite_op_id,
+                attribute_proof,
+            };
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MemberRemove => {
             let pk_hex = params["target_pubkey_hex"].as_str().unwrap_or("");
@@ -668,7 +764,7 @@ fn build_op_envelope(
                 target_device_id,
                 reason,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::RoleSet => {
             let pk_hex = params["target_pubkey_hex"].as_str().unwrap_or("");
@@ -691,10 +787,29 @@ fn build_op_envelope(
                 target_device_id,
                 new_role,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
+        }
+        OpType::OwnerTransfer => {
+            let pk_hex = params["new_owner_pubkey_hex"].as_str().unwrap_or("");
+            let new_owner_pubkey = match hex_to_32(pk_hex, "new_owner_pubkey") {
+                Ok(a) => a,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", &*e);
+                    return None;
+                }
+            };
+            let secret = B64
+                .decode(params["encrypted_group_secret_b64"].as_str().unwrap_or(""))
+                .unwrap_or_default();
+            let payload = OwnerTransferPayload {
+                new_owner_device_id: DeviceID::from_pubkey(&new_owner_pubkey),
+                new_owner_pubkey,
+                encrypted_group_secret: secret,
+            };
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MsgAdd => {
-            let msg_id = generate_msg_id(author_device, lamport, op_nonce);
+            let msg_id = group_msg_id(&gid, &pub_key, builder.lamport(), builder.nonce());
             let ciphertext = B64
                 .decode(params["ciphertext_b64"].as_str().unwrap_or(""))
                 .unwrap_or_default();
@@ -710,7 +825,7 @@ fn build_op_envelope(
                 ciphertext,
                 nonce: enc_nonce,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MsgEdit => {
             let msg_id = match hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id") {
@@ -734,8 +849,12 @@ fn build_op_envelope(
                 msg_id,
                 new_ciphertext,
                 nonce: enc_nonce,
+                base: state
+                    .messages
+                    .get_message(&msg_id)
+                    .map(MessageEntry::current_version),
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MsgDelete => {
             let msg_id = match hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id") {
@@ -746,7 +865,7 @@ fn build_op_envelope(
                 }
             };
             let payload = MsgDeletePayload { msg_id };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::ReactionSet => {
             let msg_id = match hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id") {
@@ -763,7 +882,33 @@ fn build_op_envelope(
                 emoji,
                 present,
             };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
+        }
+        OpType::ReceiptSet => {
+            let msg_id = match hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id") {
+                Ok(a) => a,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", &*e);
+                    return None;
+                }
+            };
+            let status = match parse_receipt_status(params["status"].as_str().unwrap_or("")) {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ = env.throw_new("java/lang/IllegalArgumentException", &*e);
+                    return None;
+                }
+            };
+            let allowed = match status {
+                ReceiptStatus::Delivered => crdt.send_delivered_receipts.load(Ordering::Relaxed),
+                ReceiptStatus::Read => crdt.send_read_receipts.load(Ordering::Relaxed),
+            };
+            if !allowed {
+                let _ = env.throw_new("java/lang/IllegalStateException", "RECEIPTS_DISABLED");
+                return None;
+            }
+            let payload = ReceiptSetPayload { msg_id, status };
+            builder.sign(&payload, pub_key, priv_key)
         }
         OpType::MetadataSet => {
             let key = match parse_metadata_key(params["key"].as_str().unwrap_or("")) {
@@ -777,7 +922,20 @@ fn build_op_envelope(
                 .decode(params["value_b64"].as_str().unwrap_or(""))
                 .unwrap_or_default();
             let payload = MetadataSetPayload { key, value };
-            OpEnvelope::create_signed(gid, otype, &payload, lamport, op_nonce, pub_key, priv_key)
+            builder.sign(&payload, pub_key, priv_key)
+        }
+        OpType::AnonKeyRegister => {
+            let (_, anon_pubkey) = crate::crdt::anonymous::anon_keypair(&gid, priv_key);
+            let payload = AnonKeyRegisterPayload { anon_pubkey };
+            builder.sign(&payload, pub_key, priv_key)
+        }
+        OpType::AnonMsgAdd => {
+            // Must not be signed with the device key or carry its lamport.
+            let _ = env.throw_new(
+                "java/lang/IllegalArgumentException",
+                "AnonMsgAdd is not supported via crdtCreateOp",
+            );
+            return None;
         }
     };
 
@@ -803,6 +961,13 @@ fn build_op_envelope(
 /// - `"members"` — all members with role/status
 /// - `"messages"` — renderable messages (membership-gated, not deleted)
 /// - `"messages_after"` — cursor-based: `paramsJson={"after_lamport":N,"limit":50}`
+/// - `"messages_page"` — one page by OpID cursor, time or unread:
+///   `paramsJson={"before"|"after":"op_id", "from_ms":N,"to_ms":N,
+///   "unread_since":N,"reader_hex":"...", "limit":50, "include_deleted":false}`
+/// - `"receipts"` — per-reader receipts: `paramsJson={"msg_id_hex":"..."}`
+/// - `"concurrent_edits"` — edits that lost to a concurrent one:
+///   `paramsJson={"msg_id_hex":"..."}`
+/// - `"membership_events"` — system-message feed: `paramsJson={"cursor":N}`
 /// - `"metadata"` — group name, topic, avatar
 /// - `"heads"` — DAG heads + per-author lamport
 /// - `"state_hash"` — BLAKE3 convergence hash
@@ -831,7 +996,8 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtQuery(
                 Err(e) => throw_arg!(env, e),
             };
 
-            let groups = get_groups().lock().unwrap();
+            let ctx = active_context();
+            let groups = ctx.crdt.groups.lock().unwrap();
             let state = match groups.get(&gid) {
                 Some(s) => s,
                 None => throw_state!(env, "Group not loaded"),
@@ -841,6 +1007,19 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtQuery(
                 "members" => query_members(state),
                 "messages" => query_messages(state),
                 "messages_after" => query_messages_after(state, &params_str),
+                "messages_page" => match query_messages_page(state, &params_str) {
+                    Ok(v) => v,
+                    Err(e) => throw_arg!(env, e),
+                },
+                "receipts" => match query_receipts(state, &params_str) {
+                    Ok(v) => v,
+                    Err(e) => throw_arg!(env, e),
+                },
+                "concurrent_edits" => match query_concurrent_edits(state, &params_str) {
+                    Ok(v) => v,
+                    Err(e) => throw_arg!(env, e),
+                },
+                "membership_events" => query_membership_events(state, &params_str),
                 "metadata" => query_metadata(state),
                 "heads" => query_heads(state),
                 "state_hash" => query_state_hash(state),
@@ -875,7 +1054,7 @@ fn query_members(state: &GroupState) -> serde_json::Value {
                 "removed": entry.removed,
                 "rekey_required": entry.rekey_required,
                 "invited_by_op_id": entry.invited_by.to_hex(),
-                "encrypted_group_secret_b64": B64.encode(&entry.encrypted_group_secret),
+                "encrypted_group_secret_b64": entry.encrypted_group_secret.as_ref().map(|s| B64.encode(s)),
             })
         })
         .collect();
@@ -908,6 +1087,134 @@ fn query_messages_after(state: &GroupState, params_str: &str) -> serde_json::Val
     serde_json::Value::Array(result)
 }
 
+fn query_messages_page(state: &GroupState, params_str: &str) -> Result<serde_json::Value, String> {
+    let params: serde_json::Value = serde_json::from_str(params_str).unwrap_or_default();
+    let range = if let Some(before) = params["before"].as_str() {
+        MessageRange::Before(OpID::from_hex(before)?)
+    } else if let Some(after) = params["after"].as_str() {
+        MessageRange::After(OpID::from_hex(after)?)
+    } else if let Some(lamport) = params["unread_since"].as_u64() {
+        let reader = DeviceID::from_hex(params["reader_hex"].as_str().unwrap_or(""))
+            .map_err(|e| format!("Bad reader hex: {}", e))?;
+        MessageRange::UnreadSince { lamport, reader }
+    } else if let Some(to_ms) = params["to_ms"].as_u64() {
+        MessageRange::TimeRange {
+            from_ms: params["from_ms"].as_u64().unwrap_or(0),
+            to_ms,
+        }
+    } else {
+        MessageRange::Latest
+    };
+    let mut query = MessageQuery::new(range);
+    if let Some(limit) = params["limit"].as_u64() {
+        query = query.limit(limit as usize);
+    }
+    if params["include_deleted"].as_bool().unwrap_or(false) {
+        query = query.include_deleted();
+    }
+
+    let page = state.query_messages(&query);
+    let msgs: Vec<serde_json::Value> = page
+        .messages
+        .iter()
+        .map(|view| message_to_json(view.entry))
+        .collect();
+    Ok(serde_json::json!({
+        "messages": msgs,
+        "has_more": page.has_more,
+        "oldest": page.oldest().map(|c| c.to_hex()),
+        "newest": page.newest().map(|c| c.to_hex()),
+    }))
+}
+
+fn query_receipts(state: &GroupState, params_str: &str) -> Result<serde_json::Value, String> {
+    let params: serde_json::Value = serde_json::from_str(params_str).unwrap_or_default();
+    let msg_id = hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id")?;
+    let msg = state
+        .messages
+        .get_message(&msg_id)
+        .ok_or_else(|| "Message not found".to_string())?;
+    let readers: Vec<serde_json::Value> = msg
+        .receipts
+        .iter()
+        .filter(|(reader, _)| **reader != msg.author)
+        .map(|(reader, status)| {
+            serde_json::json!({
+                "reader": reader.to_hex(),
+                "status": format!("{:?}", status),
+            })
+        })
+        .collect();
+    Ok(serde_json::Value::Array(readers))
+}
+
+fn query_concurrent_edits(
+    state: &GroupState,
+    params_str: &str,
+) -> Result<serde_json::Value, String> {
+    let params: serde_json::Value = serde_json::from_str(params_str).unwrap_or_default();
+    let msg_id = hex_to_32(params["msg_id_hex"].as_str().unwrap_or(""), "msg_id")?;
+    let msg = state
+        .messages
+        .get_message(&msg_id)
+        .ok_or_else(|| "Message not found".to_string())?;
+    let edits: Vec<serde_json::Value> = msg
+        .concurrent_edits
+        .iter()
+        .map(|e| {
+            serde_json::json!({
+                "op_id": e.op_id.to_hex(),
+                "ciphertext_b64": B64.encode(&e.ciphertext),
+                "nonce_b64": B64.encode(e.nonce),
+            })
+        })
+        .collect();
+    Ok(serde_json::Value::Array(edits))
+}
+
+fn query_membership_events(state: &GroupState, params_str: &str) -> serde_json::Value {
+    let params: serde_json::Value = serde_json::from_str(params_str).unwrap_or_default();
+    let cursor = params["cursor"].as_u64().unwrap_or(0);
+
+    let events: Vec<serde_json::Value> = state
+        .membership_feed
+        .since(cursor)
+        .iter()
+        .map(|e| {
+            let mut json = serde_json::json!({
+                "event_id": hex::encode(e.id),
+                "op_id": e.op_id.to_hex(),
+                "actor": e.actor.to_hex(),
+                "subject": e.subject.to_hex(),
+                "timestamp_ms": e.timestamp_ms,
+            });
+            let (kind, role, from) = match e.kind {
+                MembershipEventKind::Created => ("Created", None, None),
+                MembershipEventKind::Invited { role } => ("Invited", Some(role), None),
+                MembershipEventKind::Joined { role } => ("Joined", Some(role), None),
+                MembershipEventKind::Left => ("Left", None, None),
+                MembershipEventKind::Kicked => ("Kicked", None, None),
+                MembershipEventKind::RoleChanged { from, to } => {
+                    ("RoleChanged", Some(to), Some(from))
+                }
+                MembershipEventKind::RekeyRequired => ("RekeyRequired", None, None),
+            };
+            json["kind"] = kind.into();
+            if let Some(role) = role {
+                json["role"] = format!("{:?}", role).into();
+            }
+            if let Some(from) = from {
+                json["from_role"] = format!("{:?}", from).into();
+            }
+            json
+        })
+        .collect();
+    serde_json::json!({
+        "events": events,
+        "cursor": state.membership_feed.cursor(),
+    })
+}
+
 fn query_metadata(state: &GroupState) -> serde_json::Value {
     let mut obj = serde_json::Map::new();
     if let Some(name) = state.metadata.name() {
@@ -922,6 +1229,18 @@ fn query_metadata(state: &GroupState) -> serde_json::Value {
             serde_json::Value::String(B64.encode(&avatar.value)),
         );
     }
+    if let Some(avatar) = state.metadata.avatar() {
+        obj.insert(
+            "avatar".into(),
+            serde_json::json!({
+                "mime": avatar.mime,
+                "full_digest": hex::encode(avatar.full.digest),
+                "full_size": avatar.full.size,
+                "thumbnail_digest": hex::encode(avatar.thumbnail.digest),
+                "thumbnail_size": avatar.thumbnail.size,
+            }),
+        );
+    }
     serde_json::Value::Object(obj)
 }
 
@@ -963,15 +1282,22 @@ fn message_to_json(msg: &MessageEntry) -> serde_json::Value {
             })
         })
         .collect();
+    let receipts = msg.receipt_counts();
 
     serde_json::json!({
         "msg_id": hex::encode(msg.msg_id),
+        "op_id": msg.create_op.to_hex(),
         "author": msg.author.to_hex(),
+        "anonymous": msg.anonymous,
         "ciphertext_b64": B64.encode(&msg.ciphertext),
         "nonce_b64": B64.encode(&msg.nonce),
         "timestamp_ms": msg.timestamp_ms,
         "deleted": msg.deleted,
+        "edited": msg.last_edit_op.is_some(),
+        "concurrent_edit_count": msg.concurrent_edits.len(),
         "reactions": reactions,
+        "delivered_count": receipts.delivered,
+        "read_count": receipts.read,
     })
 }
 
@@ -1055,3 +1381,598 @@ pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtApplySyncChunk(
         std::ptr::null_mut()
     )
 }
+
+// ===========================================================================
+// 10. crdtSetReceiptPolicy
+// ===========================================================================
+
+/// Set which receipts this device sends (applies to all groups).
+///
+/// With a kind disabled, `crdtCreateOp("ReceiptSet", …)` for it throws
+/// `IllegalStateException("RECEIPTS_DISABLED")`. Receipts from others are
+/// always applied, so counters still converge.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtSetReceiptPolicy(
+    mut env: JNIEnv,
+    _class: JClass,
+    send_delivered: jboolean,
+    send_read: jboolean,
+) {
+    catch_panic!(
+        env,
+        {
+            let ctx = active_context();
+            ctx.crdt
+                .send_delivered_receipts
+                .store(send_delivered != 0, Ordering::Relaxed);
+            ctx.crdt
+                .send_read_receipts
+                .store(send_read != 0, Ordering::Relaxed);
+            log::info!(
+                "crdtSetReceiptPolicy: delivered={} read={}",
+                send_delivered != 0,
+                send_read != 0
+            );
+        },
+        ()
+    )
+}
+
+// ===========================================================================
+// 11. crdtCompactOps
+// ===========================================================================
+
+/// Compact a group's full op log (same encoding as `crdtLoadGroup`).
+///
+/// Kotlin deletes the dropped ops from Room; if the group is loaded, its
+/// in-memory state is rebuilt from the kept ops (the state hash is unchanged).
+///
+/// Returns JSON: `{"kept": N, "dropped": ["author_hex:lamport_hex:nonce_hex", ...]}`
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtCompactOps(
+    mut env: JNIEnv,
+    _class: JClass,
+    group_id_hex: JString,
+    serialized_ops_bytes: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let gid = match parse_group_id(&mut env, group_id_hex) {
+                Ok(g) => g,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let data = match jbytearray_to_vec(&mut env, serialized_ops_bytes) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let ops = match decode_length_prefixed_ops(
+                &data,
+                HARD_CAP_OPS_PER_GROUP,
+                MAX_SERIALIZED_OP_BYTES,
+            ) {
+                Ok(o) => o,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let compaction = match crate::crdt::compact_ops(gid, &ops) {
+                Ok(c) => c,
+                Err(e) => throw_state!(env, format!("Log does not replay: {}", e)),
+            };
+
+            if !compaction.dropped.is_empty() {
+                let ctx = active_context();
+                let mut groups = ctx.crdt.groups.lock().unwrap();
+                if groups.contains_key(&gid) {
+                    match GroupState::rebuild_from_ops(gid, &compaction.kept) {
+                        Ok(state) => {
+                            groups.insert(gid, state);
+                        }
+                        Err(e) => throw_state!(env, format!("Rebuild failed: {}", e)),
+                    }
+                }
+            }
+
+            log::info!(
+                "crdtCompactOps: {} kept, {} dropped for {}",
+                compaction.kept.len(),
+                compaction.dropped.len(),
+                gid
+            );
+            let json = serde_json::json!({
+                "kept": compaction.kept.len(),
+                "dropped": compaction.dropped.iter().map(OpID::to_hex).collect::<Vec<_>>(),
+            });
+
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ===========================================================================
+// 12. crdtSealAvatar
+// ===========================================================================
+
+/// Seal a group avatar image and its thumbnail under the GroupSecret.
+///
+/// Kotlin uploads both blobs through the attachment path, then sets the
+/// avatar with `crdtCreateOp("MetadataSet", {"key": "Avatar", "value_b64"})`.
+///
+/// Returns JSON: `{"value_b64", "full_b64", "full_digest", "thumbnail_b64",
+/// "thumbnail_digest"}` (digests hex).
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtSealAvatar(
+    mut env: JNIEnv,
+    _class: JClass,
+    group_secret: JByteArray,
+    image: JByteArray,
+    thumbnail: JByteArray,
+    mime: JString,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let secret = match jbytearray_to_vec(&mut env, group_secret)
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).map_err(|e| e.to_string()))
+            {
+                Ok(s) => s,
+                Err(e) => throw_arg!(env, format!("Bad group secret: {}", e)),
+            };
+            let image = match jbytearray_to_vec(&mut env, image) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+            let thumbnail = match jbytearray_to_vec(&mut env, thumbnail) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+            let mime = match jstring_to_string(&mut env, mime) {
+                Ok(s) => s,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let sealed = match crate::crdt::seal_avatar(
+                &image,
+                &thumbnail,
+                &mime,
+                &secret,
+                &mut rand::rngs::OsRng,
+            ) {
+                Ok(s) => s,
+                Err(e) => throw_arg!(env, e),
+            };
+            let value = match sealed.avatar_ref.to_bytes() {
+                Ok(v) => v,
+                Err(e) => throw_rt!(env, e),
+            };
+            let json = serde_json::json!({
+                "value_b64": B64.encode(value),
+                "full_b64": B64.encode(&sealed.full),
+                "full_digest": hex::encode(sealed.avatar_ref.full.digest),
+                "thumbnail_b64": B64.encode(&sealed.thumbnail),
+                "thumbnail_digest": hex::encode(sealed.avatar_ref.thumbnail.digest),
+            });
+
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ===========================================================================
+// 13. crdtOpenAvatar
+// ===========================================================================
+
+/// Verify a fetched avatar blob against a loaded group's current avatar and
+/// decrypt it. `thumbnail` selects which of the two blobs `blob` is.
+///
+/// Throws IllegalStateException if the group has no avatar, or the blob does
+/// not match it (stale or tampered download).
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtOpenAvatar(
+    mut env: JNIEnv,
+    _class: JClass,
+    group_id_hex: JString,
+    blob: JByteArray,
+    group_secret: JByteArray,
+    thumbnail: jboolean,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let gid = match parse_group_id(&mut env, group_id_hex) {
+                Ok(g) => g,
+                Err(e) => throw_arg!(env, e),
+            };
+            let blob = match jbytearray_to_vec(&mut env, blob) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+            let secret = match jbytearray_to_vec(&mut env, group_secret)
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).map_err(|e| e.to_string()))
+            {
+                Ok(s) => s,
+                Err(e) => throw_arg!(env, format!("Bad group secret: {}", e)),
+            };
+
+            let avatar = {
+                let ctx = active_context();
+                let groups = ctx.crdt.groups.lock().unwrap();
+                match groups.get(&gid) {
+                    Some(state) => state.metadata.avatar(),
+                    None => throw_state!(env, "Group not loaded"),
+                }
+            };
+            let Some(avatar) = avatar else {
+                throw_state!(env, "Group has no avatar");
+            };
+            let variant = if thumbnail != JNI_FALSE {
+                AvatarVariant::Thumbnail
+            } else {
+                AvatarVariant::Full
+            };
+            let image = match avatar.open(variant, &blob, &secret) {
+                Ok(i) => i,
+                Err(e) => throw_state!(env, e),
+            };
+
+            match vec_to_jbytearray(&mut env, &image) {
+                Ok(a) => a.into_raw(),
+                Err(e) => throw_rt!(env, e),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+// ===========================================================================
+// 14-20. Address book journal
+// ===========================================================================
+
+fn address_book_json(book: &AddressBook) -> serde_json::Value {
+    let contacts: Vec<serde_json::Value> = book
+        .contacts()
+        .map(|c| {
+            serde_json::json!({
+                "contact_id": c.contact_id.to_string(),
+                "nickname": c.nickname,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "contacts": contacts,
+        "entries": book.len(),
+    })
+}
+
+/// Rebuild the address book from the persisted journal (call on app startup).
+///
+/// `device_pubkeys` is the concatenated 32-byte Ed25519 keys of the
+/// account's linked devices, this one included; `serialized_entries_bytes`
+/// uses the `crdtLoadGroup` framing.
+///
+/// Returns JSON: `{"contacts": [{"contact_id", "nickname"}], "entries": N, "rejected": M}`
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtLoadAddressBook(
+    mut env: JNIEnv,
+    _class: JClass,
+    device_pubkeys: JByteArray,
+    serialized_entries_bytes: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let devices = match jbytearray_to_vec(&mut env, device_pubkeys) {
+                Ok(v) if v.len() % 32 == 0 => v
+                    .chunks_exact(32)
+                    .map(|k| <[u8; 32]>::try_from(k).unwrap())
+                    .collect::<Vec<_>>(),
+                Ok(v) => throw_arg!(
+                    env,
+                    format!("Pubkeys must be 32-byte multiples, got {}", v.len())
+                ),
+                Err(e) => throw_arg!(env, e),
+            };
+            let data = match jbytearray_to_vec(&mut env, serialized_entries_bytes) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+            let entries = match decode_length_prefixed(
+                &data,
+                MAX_JOURNAL_ENTRIES,
+                MAX_SERIALIZED_JOURNAL_ENTRY_BYTES,
+                JournalEntry::from_bytes,
+            ) {
+                Ok(e) => e,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let (book, rejected) = AddressBook::rebuild(&devices, entries);
+            if rejected > 0 {
+                log::warn!("crdtLoadAddressBook: skipped {} invalid entries", rejected);
+            }
+            let mut json = address_book_json(&book);
+            json["rejected"] = rejected.into();
+            *active_context().crdt.address_book.lock().unwrap() = Some(book);
+
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Link (after pairing) or unlink one of the account's devices. Entries
+/// from unlinked devices are rejected; what they wrote earlier stays.
+///
+/// Returns false if the address book is not loaded.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtLinkAddressBookDevice(
+    mut env: JNIEnv,
+    _class: JClass,
+    device_pubkey: JByteArray,
+    linked: jboolean,
+) -> jboolean {
+    catch_panic!(
+        env,
+        {
+            let pubkey = match jbytearray_to_vec(&mut env, device_pubkey)
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).map_err(|e| e.to_string()))
+            {
+                Ok(k) => k,
+                Err(e) => {
+                    log::error!("crdtLinkAddressBookDevice: {}", e);
+                    return JNI_FALSE;
+                }
+            };
+            let ctx = active_context();
+            let mut guard = ctx.crdt.address_book.lock().unwrap();
+            let Some(book) = guard.as_mut() else {
+                return JNI_FALSE;
+            };
+            if linked != JNI_FALSE {
+                book.link_device(&pubkey);
+            } else {
+                book.unlink_device(&pubkey);
+            }
+            JNI_TRUE
+        },
+        JNI_FALSE
+    )
+}
+
+/// Record a local contact change, sign it and apply it.
+///
+/// `change_json` is one of `{"Added": {"contact_id", "nickname"}}`,
+/// `{"Removed": {"contact_id"}}`, `{"Renamed": {"contact_id", "nickname"}}`.
+/// Kotlin persists the entry and sends it to the other linked devices.
+///
+/// Returns JSON: `{"entry_bytes_b64": "...", "lamport": 5}`
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtRecordContactChange(
+    mut env: JNIEnv,
+    _class: JClass,
+    change_json: JString,
+    author_pubkey: JByteArray,
+    author_privkey: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let change: ContactChange = match jstring_to_string(&mut env, change_json)
+                .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            {
+                Ok(c) => c,
+                Err(e) => throw_arg!(env, format!("Bad contact change: {}", e)),
+            };
+            let pub_key = match jbytearray_to_vec(&mut env, author_pubkey)
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).map_err(|e| e.to_string()))
+            {
+                Ok(k) => k,
+                Err(e) => throw_arg!(env, format!("Bad pubkey: {}", e)),
+            };
+            let priv_key = match jbytearray_to_vec(&mut env, author_privkey)
+                .and_then(|v| <[u8; 32]>::try_from(v.as_slice()).map_err(|e| e.to_string()))
+            {
+                Ok(k) => k,
+                Err(e) => throw_arg!(env, format!("Bad privkey: {}", e)),
+            };
+
+            let entry = {
+                let ctx = active_context();
+                let mut guard = ctx.crdt.address_book.lock().unwrap();
+                let Some(book) = guard.as_mut() else {
+                    throw_state!(env, "Address book not loaded");
+                };
+                match book.record(change, pub_key, &priv_key) {
+                    Ok(e) => e,
+                    Err(e) => throw_state!(env, e),
+                }
+            };
+            let entry_bytes = match entry.to_bytes() {
+                Ok(b) => b,
+                Err(e) => throw_rt!(env, e),
+            };
+
+            let json = serde_json::json!({
+                "entry_bytes_b64": B64.encode(&entry_bytes),
+                "lamport": entry.lamport,
+            });
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Current address book, same JSON as `crdtLoadAddressBook` without `rejected`.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtQueryAddressBook(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let json = {
+                let ctx = active_context();
+                let guard = ctx.crdt.address_book.lock().unwrap();
+                let Some(book) = guard.as_ref() else {
+                    throw_state!(env, "Address book not loaded");
+                };
+                address_book_json(book)
+            };
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Journal digest (highest lamport per device) to send to another linked
+/// device; a freshly linked device sends the digest of an empty journal.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtAddressBookDigest(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let digest = {
+                let ctx = active_context();
+                let guard = ctx.crdt.address_book.lock().unwrap();
+                let Some(book) = guard.as_ref() else {
+                    throw_state!(env, "Address book not loaded");
+                };
+                book.digest()
+            };
+            let bytes = match digest.to_bytes() {
+                Ok(b) => b,
+                Err(e) => throw_rt!(env, e),
+            };
+            match vec_to_jbytearray(&mut env, &bytes) {
+                Ok(a) => a.into_raw(),
+                Err(e) => throw_rt!(env, e),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Entries a linked device lacks according to its digest, framed for
+/// `crdtApplyAddressBookEntries` on that device.
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtAddressBookMissingEntries(
+    mut env: JNIEnv,
+    _class: JClass,
+    remote_digest: JByteArray,
+) -> jbyteArray {
+    catch_panic!(
+        env,
+        {
+            let digest = match jbytearray_to_vec(&mut env, remote_digest)
+                .and_then(|v| JournalDigest::from_bytes(&v).map_err(|e| e.to_string()))
+            {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, format!("Bad digest: {}", e)),
+            };
+            let missing = {
+                let ctx = active_context();
+                let guard = ctx.crdt.address_book.lock().unwrap();
+                let Some(book) = guard.as_ref() else {
+                    throw_state!(env, "Address book not loaded");
+                };
+                book.missing_from(&digest)
+            };
+            let records = match missing
+                .iter()
+                .map(JournalEntry::to_bytes)
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(r) => r,
+                Err(e) => throw_rt!(env, e),
+            };
+            match vec_to_jbytearray(&mut env, &encode_length_prefixed(&records)) {
+                Ok(a) => a.into_raw(),
+                Err(e) => throw_rt!(env, e),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
+
+/// Apply entries received from another linked device (a live change or a
+/// `crdtAddressBookMissingEntries` batch). Invalid entries (unlinked
+/// device, bad signature) are skipped and counted; `crdtLoadAddressBook`
+/// skips them again, so Kotlin can persist the batch as received.
+///
+/// Returns JSON: `{"applied": N, "rejected": R, "contacts": [...], "entries": M}`
+#[no_mangle]
+pub extern "C" fn Java_com_securelegion_crypto_RustBridge_crdtApplyAddressBookEntries(
+    mut env: JNIEnv,
+    _class: JClass,
+    serialized_entries_bytes: JByteArray,
+) -> jstring {
+    catch_panic!(
+        env,
+        {
+            let data = match jbytearray_to_vec(&mut env, serialized_entries_bytes) {
+                Ok(d) => d,
+                Err(e) => throw_arg!(env, e),
+            };
+            let entries = match decode_length_prefixed(
+                &data,
+                MAX_OPS_PER_APPLY_BATCH,
+                MAX_SERIALIZED_JOURNAL_ENTRY_BYTES,
+                JournalEntry::from_bytes,
+            ) {
+                Ok(e) => e,
+                Err(e) => throw_arg!(env, e),
+            };
+
+            let json = {
+                let ctx = active_context();
+                let mut guard = ctx.crdt.address_book.lock().unwrap();
+                let Some(book) = guard.as_mut() else {
+                    throw_state!(env, "Address book not loaded");
+                };
+                let (mut applied, mut rejected) = (0, 0);
+                for entry in entries {
+                    match book.apply(entry) {
+                        Ok(true) => applied += 1,
+                        Ok(false) => {}
+                        Err(e) => {
+                            log::warn!("crdtApplyAddressBookEntries: {}", e);
+                            rejected += 1;
+                        }
+                    }
+                }
+                let mut json = address_book_json(book);
+                json["applied"] = applied.into();
+                json["rejected"] = rejected.into();
+                json
+            };
+            match env.new_string(json.to_string()) {
+                Ok(s) => s.into_raw(),
+                Err(e) => throw_rt!(env, format!("JSON creation failed: {}", e)),
+            }
+        },
+        std::ptr::null_mut()
+    )
+}
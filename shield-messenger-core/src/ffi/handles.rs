@@ -0,0 +1,102 @@
+/// Opaque-handle registry for objects owned by Rust and named across FFI.
+///
+/// Foreign code only ever sees a non-zero `u64` (a `jlong` on Android);
+/// the object itself stays in the registry until it is removed. Handles are
+/// never reused, so a stale handle from a destroyed instance resolves to
+/// `None` instead of silently reaching whatever was created after it.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Value foreign code receives when no instance could be created.
+pub const INVALID_HANDLE: u64 = 0;
+
+pub struct HandleRegistry<T> {
+    next: AtomicU64,
+    entries: RwLock<HashMap<u64, Arc<T>>>,
+}
+
+impl<T> Default for HandleRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HandleRegistry<T> {
+    pub fn new() -> Self {
+        HandleRegistry {
+            next: AtomicU64::new(1),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Take ownership of `value` and return its handle.
+    pub fn insert(&self, value: T) -> u64 {
+        let handle = self.next.fetch_add(1, Ordering::Relaxed);
+        self.entries
+            .write()
+            .unwrap()
+            .insert(handle, Arc::new(value));
+        handle
+    }
+
+    /// Resolve a handle. The returned `Arc` keeps the instance alive for the
+    /// caller even if the handle is removed meanwhile.
+    pub fn get(&self, handle: u64) -> Option<Arc<T>> {
+        self.entries.read().unwrap().get(&handle).cloned()
+    }
+
+    /// Invalidate a handle, returning the instance if it existed.
+    pub fn remove(&self, handle: u64) -> Option<Arc<T>> {
+        self.entries.write().unwrap().remove(&handle)
+    }
+
+    pub fn contains(&self, handle: u64) -> bool {
+        self.entries.read().unwrap().contains_key(&handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handles_resolve_independently() {
+        let registry = HandleRegistry::new();
+        let a = registry.insert(String::from("profile-a"));
+        let b = registry.insert(String::from("profile-b"));
+
+        assert_ne!(a, INVALID_HANDLE);
+        assert_ne!(a, b);
+        assert_eq!(registry.get(a).unwrap().as_str(), "profile-a");
+        assert_eq!(registry.get(b).unwrap().as_str(), "profile-b");
+        assert!(registry.get(INVALID_HANDLE).is_none());
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn test_removed_handles_are_never_reused() {
+        let registry = HandleRegistry::new();
+        let a = registry.insert(1u32);
+        let held = registry.get(a).unwrap();
+
+        assert_eq!(registry.remove(a).as_deref(), Some(&1));
+        assert!(registry.get(a).is_none());
+        assert!(registry.remove(a).is_none());
+        // Outstanding references stay valid after removal.
+        assert_eq!(*held, 1);
+
+        let b = registry.insert(2u32);
+        assert_ne!(a, b);
+        assert!(registry.get(a).is_none());
+        assert!(!registry.is_empty() && registry.contains(b));
+    }
+}
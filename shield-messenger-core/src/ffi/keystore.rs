@@ -4,6 +4,11 @@ use jni::objects::{JByteArray, JObject};
 /// Provides secure access to Android KeyStore from Rust via JNI callbacks.
 /// This ensures private keys never leave the hardware-backed secure storage.
 use jni::JNIEnv;
+#[cfg(feature = "software-keys")]
+use std::sync::Arc;
+
+#[cfg(feature = "software-keys")]
+use crate::ffi::software_keys::SoftwareKeys;
 
 /// KeyStore access errors
 #[derive(Debug)]
@@ -337,6 +342,116 @@ pub fn get_key_manager<'a>(
         .map_err(|e| KeyStoreError::JniError(format!("Failed to cast KeyManager: {}", e)))?)
 }
 
+/// Source of the local identity keys for a JNI call
+///
+/// Production builds always resolve to the Android KeyManager. Builds with
+/// the `software-keys` feature let JVM tests install software keys on the
+/// active context (SoftwareKeysBridge.useSoftwareKeys) to exercise the same
+/// entry points without a device KeyStore.
+pub enum KeyProvider<'a> {
+    KeyManager(JObject<'a>),
+    #[cfg(feature = "software-keys")]
+    Software(Arc<SoftwareKeys>),
+}
+
+impl KeyProvider<'_> {
+    pub fn signing_private_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_signing_private_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.signing_private_key().to_vec()),
+        }
+    }
+
+    pub fn signing_public_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_signing_public_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.signing_public_key().to_vec()),
+        }
+    }
+
+    pub fn encryption_private_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_encryption_private_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.encryption_private_key().to_vec()),
+        }
+    }
+
+    pub fn encryption_public_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_encryption_public_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.encryption_public_key().to_vec()),
+        }
+    }
+
+    pub fn hidden_service_private_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_hidden_service_private_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.hidden_service_private_key().to_vec()),
+        }
+    }
+
+    pub fn friend_request_private_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_friend_request_private_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.friend_request_private_key().to_vec()),
+        }
+    }
+
+    pub fn voice_service_private_key(&self, env: &mut JNIEnv) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => get_voice_service_private_key(env, km),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => Ok(keys.voice_service_private_key().to_vec()),
+        }
+    }
+
+    pub fn sign(&self, env: &mut JNIEnv, data: &[u8]) -> Result<Vec<u8>, KeyStoreError> {
+        match self {
+            Self::KeyManager(km) => sign_with_keystore(env, km, data),
+            #[cfg(feature = "software-keys")]
+            Self::Software(keys) => keys
+                .sign(data)
+                .map(|sig| sig.to_vec())
+                .map_err(|_| KeyStoreError::SigningFailed),
+        }
+    }
+}
+
+/// Resolve the key provider for the active context
+///
+/// Returns the context's software keys if the `software-keys` feature is on
+/// and a test installed them, otherwise looks up the application context and
+/// its KeyManager singleton.
+pub fn key_provider<'a>(env: &mut JNIEnv<'a>) -> Result<KeyProvider<'a>, KeyStoreError> {
+    #[cfg(feature = "software-keys")]
+    if let Some(keys) = crate::ffi::context::active_context()
+        .software_keys
+        .read()
+        .unwrap()
+        .clone()
+    {
+        return Ok(KeyProvider::Software(keys));
+    }
+
+    let context = env
+        .call_static_method(
+            "android/app/ActivityThread",
+            "currentApplication",
+            "()Landroid/app/Application;",
+            &[],
+        )
+        .and_then(|ctx| ctx.l())
+        .map_err(|e| KeyStoreError::JniError(format!("Failed to get context: {}", e)))?;
+
+    Ok(KeyProvider::KeyManager(get_key_manager(env, &context)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
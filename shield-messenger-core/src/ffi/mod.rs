@@ -1,3 +1,18 @@
+// Opaque handles for per-instance state shared by the platform bindings
+pub mod handles;
+
+// Per-profile protocol contexts and the active-context registry
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub mod context;
+
+// LRU of X25519 shared secrets so encrypt/decrypt skip repeated ECDH
+pub mod session_cache;
+
+// In-process identity keys for running the bindings without a device KeyStore.
+// Test builds only: the JNI calls that install them replace the identity keys.
+#[cfg(feature = "software-keys")]
+pub mod software_keys;
+
 // Platform-specific FFI modules
 #[cfg(target_os = "android")]
 pub mod android;
@@ -0,0 +1,304 @@
+/// Cache of X25519 shared secrets for the FFI crypto paths.
+///
+/// Every encrypt and decrypt call across the bindings used to run a fresh
+/// ECDH against the peer's X25519 key, which dominates per-message CPU on
+/// low-end phones. Secrets are now kept per [`ContactId`] in a bounded LRU.
+/// An entry is only reused while both keys it was derived from are
+/// unchanged: a different peer X25519 key replaces it, and so does a change
+/// of our own key (entries carry a keyed tag of our private key, never the
+/// key itself). Evicted, replaced and invalidated secrets are zeroized.
+///
+/// Paths that only see the peer's X25519 key (wire formats carry no
+/// identity key) hit the cache once some call has tied that key to a
+/// contact; until then they derive the secret without caching it.
+///
+/// Each protocol context has its own cache, so two profiles never share a
+/// secret for the same contact; the free functions use the active one.
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::crypto::key_exchange::{derive_shared_secret, KeyExchangeError};
+use crate::protocol::ContactId;
+
+/// Contacts whose secrets are kept.
+pub const DEFAULT_SESSION_CACHE_CAPACITY: usize = 256;
+
+const OUR_KEY_TAG_CONTEXT: &str = "ShieldMessenger session cache v1 local key tag";
+
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct CachedSecret {
+    our_key_tag: [u8; 32],
+    their_public: [u8; 32],
+    secret: [u8; 32],
+}
+
+/// Hit and miss counters, for tuning the capacity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct SessionKeyCache {
+    entries: LruCache<ContactId, CachedSecret>,
+    /// Peer X25519 key → contact, for callers without a contact id
+    by_peer_key: HashMap<[u8; 32], ContactId>,
+    stats: SessionCacheStats,
+}
+
+impl SessionKeyCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+            by_peer_key: HashMap::new(),
+            stats: SessionCacheStats::default(),
+        }
+    }
+
+    /// Shared secret between `our_private` and `contact`'s X25519 key
+    /// `their_public`, derived at most once while both keys stay the same.
+    pub fn shared_secret(
+        &mut self,
+        contact: &ContactId,
+        our_private: &[u8],
+        their_public: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, KeyExchangeError> {
+        let their_public: [u8; 32] = their_public
+            .try_into()
+            .map_err(|_| KeyExchangeError::InvalidKeyLength)?;
+        let our_key_tag = blake3::derive_key(OUR_KEY_TAG_CONTEXT, our_private);
+
+        if let Some(entry) = self.entries.get(contact) {
+            if entry.our_key_tag == our_key_tag && entry.their_public == their_public {
+                self.stats.hits += 1;
+                return Ok(Zeroizing::new(entry.secret));
+            }
+        }
+        self.stats.misses += 1;
+
+        let secret = Zeroizing::new(derive_shared_secret(our_private, &their_public)?);
+        if let Some(stale) = self.entries.pop(contact) {
+            if stale.their_public != their_public {
+                log::info!("X25519 key of {} changed; session key replaced", contact);
+            }
+            self.by_peer_key.remove(&stale.their_public);
+        }
+        let entry = CachedSecret {
+            our_key_tag,
+            their_public,
+            secret: *secret,
+        };
+        if let Some((evicted, entry)) = self.entries.push(*contact, entry) {
+            if evicted != *contact {
+                self.by_peer_key.remove(&entry.their_public);
+            }
+        }
+        self.by_peer_key.insert(their_public, *contact);
+        Ok(secret)
+    }
+
+    /// Like [`shared_secret`](Self::shared_secret) for callers that only
+    /// know the peer's X25519 key. Uncached unless the key was seen with a
+    /// contact id before.
+    pub fn shared_secret_for_peer(
+        &mut self,
+        our_private: &[u8],
+        their_public: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, KeyExchangeError> {
+        let contact = <[u8; 32]>::try_from(their_public)
+            .ok()
+            .and_then(|key| self.by_peer_key.get(&key).copied());
+        match contact {
+            Some(contact) => self.shared_secret(&contact, our_private, their_public),
+            None => {
+                self.stats.misses += 1;
+                derive_shared_secret(our_private, their_public).map(Zeroizing::new)
+            }
+        }
+    }
+
+    /// Drop `contact`'s secret (key change approved, contact deleted).
+    pub fn invalidate(&mut self, contact: &ContactId) -> bool {
+        match self.entries.pop(contact) {
+            Some(entry) => {
+                self.by_peer_key.remove(&entry.their_public);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.by_peer_key.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> SessionCacheStats {
+        self.stats
+    }
+}
+
+impl Default for SessionKeyCache {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(DEFAULT_SESSION_CACHE_CAPACITY).unwrap())
+    }
+}
+
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+fn with_cache<R>(f: impl FnOnce(&mut SessionKeyCache) -> R) -> R {
+    let ctx = crate::ffi::context::active_context();
+    let mut cache = ctx.session_keys.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut cache)
+}
+
+/// [`SessionKeyCache::shared_secret`] on the active context's cache
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn cached_shared_secret(
+    contact: &ContactId,
+    our_private: &[u8],
+    their_public: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, KeyExchangeError> {
+    with_cache(|cache| cache.shared_secret(contact, our_private, their_public))
+}
+
+/// [`SessionKeyCache::shared_secret_for_peer`] on the active context's cache
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn cached_shared_secret_for_peer(
+    our_private: &[u8],
+    their_public: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, KeyExchangeError> {
+    with_cache(|cache| cache.shared_secret_for_peer(our_private, their_public))
+}
+
+/// Cache under the contact whose Ed25519 identity key is `identity_key`
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn cached_shared_secret_for_identity(
+    identity_key: &[u8],
+    our_private: &[u8],
+    their_public: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, KeyExchangeError> {
+    match ContactId::from_identity_key(identity_key) {
+        Ok(contact) => cached_shared_secret(&contact, our_private, their_public),
+        Err(_) => cached_shared_secret_for_peer(our_private, their_public),
+    }
+}
+
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn invalidate_session_key(contact: &ContactId) -> bool {
+    with_cache(|cache| cache.invalidate(contact))
+}
+
+/// Zeroize every cached secret (wipe, duress PIN, our key rotated)
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn clear_session_keys() {
+    with_cache(SessionKeyCache::clear);
+}
+
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub fn session_cache_stats() -> SessionCacheStats {
+    with_cache(|cache| cache.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_exchange::generate_static_keypair;
+    use crate::test_contact;
+
+    fn cache(capacity: usize) -> SessionKeyCache {
+        SessionKeyCache::new(NonZeroUsize::new(capacity).unwrap())
+    }
+
+    #[test]
+    fn test_reuses_secret_until_a_key_changes() {
+        let mut cache = cache(4);
+        let (_, ours) = generate_static_keypair();
+        let (theirs, _) = generate_static_keypair();
+        let alice = test_contact(1);
+
+        let first = cache.shared_secret(&alice, &ours, &theirs).unwrap();
+        let second = cache.shared_secret(&alice, &ours, &theirs).unwrap();
+        assert_eq!(*first, *second);
+        assert_eq!(*first, derive_shared_secret(&ours, &theirs).unwrap());
+        assert_eq!(cache.stats(), SessionCacheStats { hits: 1, misses: 1 });
+
+        // Peer rotated their X25519 key: the old secret is not reused
+        let (rotated, _) = generate_static_keypair();
+        let after = cache.shared_secret(&alice, &ours, &rotated).unwrap();
+        assert_eq!(*after, derive_shared_secret(&ours, &rotated).unwrap());
+        assert_eq!(cache.len(), 1);
+
+        // So did we
+        let (_, new_ours) = generate_static_keypair();
+        let mine = cache.shared_secret(&alice, &new_ours, &rotated).unwrap();
+        assert_eq!(*mine, derive_shared_secret(&new_ours, &rotated).unwrap());
+        assert_eq!(cache.stats().misses, 3);
+    }
+
+    #[test]
+    fn test_peer_key_lookup_and_invalidation() {
+        let mut cache = cache(4);
+        let (_, ours) = generate_static_keypair();
+        let (theirs, _) = generate_static_keypair();
+        let alice = test_contact(1);
+
+        // Unknown X25519 key: derived, not cached
+        cache.shared_secret_for_peer(&ours, &theirs).unwrap();
+        assert!(cache.is_empty());
+
+        cache.shared_secret(&alice, &ours, &theirs).unwrap();
+        cache.shared_secret_for_peer(&ours, &theirs).unwrap();
+        assert_eq!(cache.stats().hits, 1);
+
+        assert!(cache.invalidate(&alice));
+        assert!(!cache.invalidate(&alice));
+        cache.shared_secret_for_peer(&ours, &theirs).unwrap();
+        assert_eq!(cache.stats().hits, 1);
+        assert!(matches!(
+            cache.shared_secret(&alice, &ours, &theirs[..16]),
+            Err(KeyExchangeError::InvalidKeyLength)
+        ));
+    }
+
+    #[test]
+    fn test_lru_bound_evicts_least_recent() {
+        let mut cache = cache(2);
+        let (_, ours) = generate_static_keypair();
+        let peers: Vec<_> = (1..=3)
+            .map(|i| (test_contact(i), generate_static_keypair().0))
+            .collect();
+
+        cache
+            .shared_secret(&peers[0].0, &ours, &peers[0].1)
+            .unwrap();
+        cache
+            .shared_secret(&peers[1].0, &ours, &peers[1].1)
+            .unwrap();
+        // Touch the first so the second is least recently used
+        cache
+            .shared_secret(&peers[0].0, &ours, &peers[0].1)
+            .unwrap();
+        cache
+            .shared_secret(&peers[2].0, &ours, &peers[2].1)
+            .unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.invalidate(&peers[1].0));
+        // The evicted contact's X25519 key no longer resolves
+        let hits = cache.stats().hits;
+        cache.shared_secret_for_peer(&ours, &peers[1].1).unwrap();
+        assert_eq!(cache.stats().hits, hits);
+        cache.shared_secret_for_peer(&ours, &peers[2].1).unwrap();
+        assert_eq!(cache.stats().hits, hits + 1);
+    }
+}
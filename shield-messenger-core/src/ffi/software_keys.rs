@@ -0,0 +1,154 @@
+/// Software-backed identity keys for exercising the FFI surface without a
+/// device KeyStore.
+///
+/// Keys are derived from a wallet seed exactly as `KeyManager.kt` derives
+/// them (first 32 bytes for the signing key, `SHA-256(seed || label)` for the
+/// rest), so a test seeded with a known BIP39 seed sees the same identity and
+/// .onion addresses the app would. Everything is held in process memory and
+/// zeroized on drop; this is never meant for production builds.
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::crypto::{key_exchange, signing};
+
+/// Shortest seed accepted; BIP39 seeds are 64 bytes.
+pub const MIN_SEED_LEN: usize = 32;
+
+#[derive(Debug)]
+pub enum SoftwareKeyError {
+    SeedTooShort(usize),
+    Derivation(String),
+}
+
+impl std::fmt::Display for SoftwareKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SeedTooShort(len) => write!(
+                f,
+                "Seed too short: {} bytes (need at least {})",
+                len, MIN_SEED_LEN
+            ),
+            Self::Derivation(msg) => write!(f, "Key derivation failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SoftwareKeyError {}
+
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct SoftwareKeys {
+    signing_private: [u8; 32],
+    signing_public: [u8; 32],
+    encryption_private: [u8; 32],
+    encryption_public: [u8; 32],
+    hidden_service_private: [u8; 32],
+    friend_request_private: [u8; 32],
+    voice_service_private: [u8; 32],
+}
+
+/// `SHA-256(seed || label)`, matching KeyManager's domain separation.
+fn derive_labeled(seed: &[u8], label: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(seed);
+    hasher.update(label.as_bytes());
+    hasher.finalize().into()
+}
+
+impl SoftwareKeys {
+    pub fn from_seed(seed: &[u8]) -> Result<Self, SoftwareKeyError> {
+        if seed.len() < MIN_SEED_LEN {
+            return Err(SoftwareKeyError::SeedTooShort(seed.len()));
+        }
+
+        let mut signing_private = [0u8; 32];
+        signing_private.copy_from_slice(&seed[..32]);
+        let encryption_private = derive_labeled(seed, "x25519");
+
+        let keys = Self {
+            signing_public: signing::derive_public_key(&signing_private)
+                .map_err(|e| SoftwareKeyError::Derivation(e.to_string()))?,
+            encryption_public: key_exchange::derive_public_key(&encryption_private)
+                .map_err(|e| SoftwareKeyError::Derivation(e.to_string()))?,
+            signing_private,
+            encryption_private,
+            hidden_service_private: derive_labeled(seed, "tor_hs"),
+            friend_request_private: derive_labeled(seed, "friend_req"),
+            voice_service_private: derive_labeled(seed, "tor_voice"),
+        };
+        Ok(keys)
+    }
+
+    pub fn signing_private_key(&self) -> &[u8; 32] {
+        &self.signing_private
+    }
+
+    pub fn signing_public_key(&self) -> &[u8; 32] {
+        &self.signing_public
+    }
+
+    pub fn encryption_private_key(&self) -> &[u8; 32] {
+        &self.encryption_private
+    }
+
+    pub fn encryption_public_key(&self) -> &[u8; 32] {
+        &self.encryption_public
+    }
+
+    pub fn hidden_service_private_key(&self) -> &[u8; 32] {
+        &self.hidden_service_private
+    }
+
+    pub fn friend_request_private_key(&self) -> &[u8; 32] {
+        &self.friend_request_private
+    }
+
+    pub fn voice_service_private_key(&self) -> &[u8; 32] {
+        &self.voice_service_private
+    }
+
+    /// Ed25519 signature with the signing key, as `KeyManager.signData` does.
+    pub fn sign(&self, data: &[u8]) -> Result<[u8; 64], SoftwareKeyError> {
+        signing::sign_data(data, &self.signing_private)
+            .map_err(|e| SoftwareKeyError::Derivation(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_deterministic_and_domain_separated() {
+        let seed = [7u8; 64];
+        let a = SoftwareKeys::from_seed(&seed).unwrap();
+        let b = SoftwareKeys::from_seed(&seed).unwrap();
+
+        assert_eq!(a.signing_public_key(), b.signing_public_key());
+        assert_eq!(a.encryption_public_key(), b.encryption_public_key());
+        assert_eq!(a.signing_private_key(), &[7u8; 32]);
+
+        let privates = [
+            a.encryption_private_key(),
+            a.hidden_service_private_key(),
+            a.friend_request_private_key(),
+            a.voice_service_private_key(),
+        ];
+        for (i, x) in privates.iter().enumerate() {
+            for y in &privates[i + 1..] {
+                assert_ne!(x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_signatures_verify_and_short_seeds_rejected() {
+        let keys = SoftwareKeys::from_seed(&[3u8; 32]).unwrap();
+        let sig = keys.sign(b"ping").unwrap();
+        assert!(signing::verify_signature(b"ping", &sig, keys.signing_public_key()).unwrap());
+
+        assert!(matches!(
+            SoftwareKeys::from_seed(&[0u8; 16]),
+            Err(SoftwareKeyError::SeedTooShort(16))
+        ));
+    }
+}
@@ -622,6 +622,207 @@ pub fn detect_identity_key_change(
     Ok(json)
 }
 
+// ─────────────────────── Mailbox Polling ───────────────────────
+
+#[cfg(target_arch = "wasm32")]
+fn decode_key32(b64: &str, what: &str) -> Result<[u8; 32], JsValue> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(b64)
+        .map_err(|e| JsValue::from_str(&format!("Invalid base64 ({}): {}", what, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| JsValue::from_str(&format!("{} must be 32 bytes", what)))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn check_result_json(result: crate::protocol::mailbox::CheckResult) -> String {
+    use crate::protocol::mailbox::CheckResult;
+    let result = match result {
+        CheckResult::HasMail => "HasMail",
+        CheckResult::Empty => "Empty",
+        CheckResult::Unknown => "Unknown",
+    };
+    serde_json::json!({ "result": result }).to_string()
+}
+
+/// Build the next mailbox check in the best mode the relays allow
+/// `config_json`: { "mode": "Pir" | "Bucketed" | "Plain", "bucket_bits": n, "poll_interval_ms": n }
+/// `index_relays`: relays serving the mailbox index (relay protocol 2)
+/// Returns JSON, one of:
+///   { "mode": "Pir", "bucketBits": n, "queries": ["base64...", "base64..."] }  (one per relay)
+///   { "mode": "Bucketed", "bucketBits": n, "bucket": n }
+///   { "mode": "Plain", "poll": "base64..." }
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn mailbox_check_request(
+    config_json: &str,
+    index_relays: u32,
+    public_key_b64: &str,
+    private_key_b64: &str,
+    now_secs: u64,
+) -> Result<String, JsValue> {
+    use crate::protocol::mailbox::{check_request, CheckRequest, MailboxPollConfig};
+
+    let config: MailboxPollConfig = serde_json::from_str(config_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid config: {}", e)))?;
+    let public_key = decode_key32(public_key_b64, "public key")?;
+    let private_key = base64::engine::general_purpose::STANDARD
+        .decode(private_key_b64)
+        .map_err(|e| JsValue::from_str(&format!("Invalid base64 (private key): {}", e)))?;
+
+    let request = check_request(
+        &config,
+        index_relays as usize,
+        &private_key,
+        &public_key,
+        now_secs,
+        &mut rand::rngs::OsRng,
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let b64 = &base64::engine::general_purpose::STANDARD;
+    let json = match request {
+        CheckRequest::Pir([a, b]) => serde_json::json!({
+            "mode": "Pir",
+            "bucketBits": config.bucket_bits,
+            "queries": [b64.encode(a.to_bytes()), b64.encode(b.to_bytes())],
+        }),
+        CheckRequest::Bucketed {
+            bucket_bits,
+            bucket,
+        } => serde_json::json!({
+            "mode": "Bucketed",
+            "bucketBits": bucket_bits,
+            "bucket": bucket,
+        }),
+        CheckRequest::Plain(poll) => serde_json::json!({
+            "mode": "Plain",
+            "poll": b64.encode(poll.to_bytes()),
+        }),
+    };
+    Ok(json.to_string())
+}
+
+/// Read the bucket record a relay returned for a bucketed check
+/// Returns JSON: { "result": "HasMail" | "Empty" | "Unknown" } (Unknown: poll plainly)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn mailbox_read_bucket(public_key_b64: &str, record_b64: &str) -> Result<String, JsValue> {
+    use crate::protocol::mailbox::{BucketRecord, MailboxId};
+
+    let public_key = decode_key32(public_key_b64, "public key")?;
+    let record = base64::engine::general_purpose::STANDARD
+        .decode(record_b64)
+        .map_err(|e| JsValue::from_str(&format!("Invalid base64 (record): {}", e)))?;
+    let record =
+        BucketRecord::from_bytes(&record).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(check_result_json(
+        record.check(&MailboxId::from_public_key(&public_key)),
+    ))
+}
+
+/// Combine the two relays' answers to a PIR check
+/// Returns JSON: { "result": "HasMail" | "Empty" | "Unknown" }
+/// Errors if the relays answered from different indexes; fall back to a bucketed check then
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn mailbox_read_pir(
+    public_key_b64: &str,
+    answer_a_b64: &str,
+    answer_b_b64: &str,
+) -> Result<String, JsValue> {
+    use crate::protocol::mailbox::{MailboxId, PirAnswer};
+
+    let public_key = decode_key32(public_key_b64, "public key")?;
+    let mut answers = Vec::with_capacity(2);
+    for answer_b64 in [answer_a_b64, answer_b_b64] {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(answer_b64)
+            .map_err(|e| JsValue::from_str(&format!("Invalid base64 (answer): {}", e)))?;
+        answers.push(PirAnswer::from_bytes(&bytes).map_err(|e| JsValue::from_str(&e.to_string()))?);
+    }
+    let record = answers[0]
+        .combine(&answers[1])
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(check_result_json(
+        record.check(&MailboxId::from_public_key(&public_key)),
+    ))
+}
+
+// ─────────────────────── Transport (WebSocket / WebRTC) ───────────────────────
+
+/// Link to a peer for browsers, which cannot use Tor's SOCKS port: a
+/// WebSocket to a user-chosen gateway, or a negotiated WebRTC data channel
+/// Poll `recv()` from the app's event loop; frames are padded packets
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub struct WebTransport {
+    channel: Box<dyn crate::transport::Channel>,
+    received: std::collections::VecDeque<Vec<u8>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebTransport {
+    fn wrap(channel: impl crate::transport::Channel + 'static) -> Self {
+        Self {
+            channel: Box::new(channel),
+            received: std::collections::VecDeque::new(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl WebTransport {
+    /// Connect to a gateway (`wss://…`; `ws://` only to localhost)
+    pub fn connect_gateway(url: &str) -> Result<WebTransport, JsValue> {
+        crate::transport::WebSocketChannel::connect(url)
+            .map(Self::wrap)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Take over an RTCDataChannel created with `{ ordered: true }` and no
+    /// retransmit or lifetime limit
+    pub fn from_data_channel(channel: web_sys::RtcDataChannel) -> Result<WebTransport, JsValue> {
+        crate::transport::RtcChannel::from_data_channel(channel)
+            .map(Self::wrap)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// "Connecting" | "Open" | "Closed"
+    pub fn state(&self) -> String {
+        format!("{:?}", self.channel.state())
+    }
+
+    pub fn max_frame_len(&self) -> usize {
+        self.channel.max_frame_len()
+    }
+
+    /// Send one frame; throws "Send buffer full…" under backpressure (retry later)
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), JsValue> {
+        self.channel
+            .send(frame)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Next received frame, or undefined when none is waiting
+    pub fn recv(&mut self) -> Option<Vec<u8>> {
+        if self.received.is_empty() {
+            self.received.extend(self.channel.poll_recv());
+        }
+        self.received.pop_front()
+    }
+
+    /// Why the link closed, once it has
+    pub fn close_reason(&self) -> Option<String> {
+        self.channel.close_reason()
+    }
+
+    pub fn close(&mut self) {
+        self.channel.close();
+    }
+}
+
 // ─────────────────────── Utility ───────────────────────
 
 /// Get library version
@@ -22,19 +22,38 @@ pub use shield_protocol::protocol;
 pub use shield_protocol::storage;
 pub use shield_protocol::transport;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "groups", not(target_arch = "wasm32")))]
 pub use shield_protocol::crdt;
 
+// Legal-hold key escrow: compiled in only with the `escrow` feature.
+#[cfg(feature = "escrow")]
+pub use shield_protocol::escrow;
+
 // ── Local modules (app-layer, not part of the standalone protocol) ──────────
-#[cfg(not(target_arch = "wasm32"))]
+// Without `transport-tor`, `payments` and `groups` only the crypto and
+// protocol layers above are built (see the feature list in Cargo.toml).
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
 pub mod aethernet;
 #[cfg(feature = "audio-codec")]
 pub mod audio;
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub mod config;
 pub mod ffi;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(
+    feature = "transport-tor",
+    feature = "groups",
+    not(target_arch = "wasm32")
+))]
+pub mod maintenance;
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
 pub mod network;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "payments", not(target_arch = "wasm32")))]
 pub mod nlx402;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugins;
+pub mod redact;
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub mod wipe;
 
 // ── Re-export main types (backward-compatible) ─────────────────────────────
 pub use crypto::{
@@ -42,18 +61,22 @@ pub use crypto::{
     hash_password, sign_data, verify_signature,
 };
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
 pub use network::{PingPongManager, PingToken, PongToken, TorManager};
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "payments", not(target_arch = "wasm32")))]
 pub use nlx402::{
     create_quote, extract_quote_hash_from_memo, verify_payment, verify_payment_simple,
     PaymentQuote, VerificationResult,
 };
+#[cfg(all(feature = "transport-tor", not(target_arch = "wasm32")))]
+pub use config::{ConfigError, ShieldConfig};
 pub use protocol::{ContactCard, Message, MessageType, SecurityMode};
+#[cfg(feature = "decoys")]
 pub use storage::{
-    generate_decoy_data, on_duress_pin_entered, DecoyConfig, DecoyContact, DecoyMessage,
-    DuressPinSpec, StealthModeSpec, StorageError,
+    generate_decoy_data, DecoyConfig, DecoyContact, DecoyDelivery, DecoyDeliveryState, DecoyLocale,
+    DecoyMessage,
 };
+pub use storage::{on_duress_pin_entered, DuressPinSpec, StealthModeSpec, StorageError};
 
 // Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -63,6 +86,12 @@ pub fn get_version() -> &'static str {
     VERSION
 }
 
+/// Contact id fixture for this crate's tests: the id of identity key `[seed; 32]`.
+#[cfg(test)]
+pub(crate) fn test_contact(seed: u8) -> protocol::ContactId {
+    protocol::ContactId::from_identity_key(&[seed; 32]).expect("32-byte key")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,289 @@
+//! Background Maintenance
+//!
+//! Housekeeping that used to be scattered over ad-hoc timers runs as one
+//! pass: the app calls `run` periodically (Android WorkManager, or `spawn`
+//! on desktop) and gets back a `MaintenanceReport` of what was done.
+//!
+//! A pass:
+//! - rotates ratchet sessions whose health exceeds the rotation policy.
+//!   There are no published signed prekeys in this protocol; each session's
+//!   KEM encapsulation key plays that role, and `force_rotation` replaces it
+//!   with the next outgoing message
+//! - re-derives cover-flow slot lengths from the current traffic profile, so
+//!   a profile change reaches flows that started before it
+//! - expires stale ping/pong/ACK sessions, pending ratchet advancements and
+//!   unanswered RPC calls
+//! - compacts the op logs handed in (`crdt::compact_ops`) and lists loaded
+//!   groups large enough to be worth compacting
+//!
+//! Sessions, cover flows and op logs are owned by the caller and lent to the
+//! pass through `MaintenanceTargets`; anything left out is skipped.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::crdt::{compact_ops, GroupID, OpEnvelope, OpID};
+use crate::crypto::{PQDoubleRatchet, RotationPolicy};
+use crate::transport::{CoverFlowConfig, CoverFlows};
+
+/// When a pass acts
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaintenancePolicy {
+    /// Sessions past these limits are rotated
+    pub rotation: RotationPolicy,
+    /// Loaded groups with more applied ops than this are reported as due
+    /// for compaction
+    pub compact_after_ops: usize,
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self {
+            rotation: RotationPolicy {
+                max_messages_since_dh_step: None,
+                max_messages_since_pq_step: Some(500),
+                max_secs_since_pq_step: Some(7 * 24 * 3600),
+                max_skipped_keys: Some(200),
+            },
+            compact_after_ops: 10_000,
+        }
+    }
+}
+
+static POLICY: Lazy<RwLock<MaintenancePolicy>> =
+    Lazy::new(|| RwLock::new(MaintenancePolicy::default()));
+
+pub fn policy() -> MaintenancePolicy {
+    POLICY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn set_policy(policy: MaintenancePolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+/// State the caller lends to one pass
+#[derive(Default)]
+pub struct MaintenanceTargets<'a> {
+    /// Live ratchet sessions by contact id
+    pub sessions: Vec<(&'a str, &'a mut PQDoubleRatchet)>,
+    /// The transport loop's per-contact cover flows
+    pub cover: Option<&'a mut CoverFlows<String>>,
+    /// Full op logs to compact; each is replaced by its compacted form,
+    /// which the caller persists
+    pub group_logs: Vec<(GroupID, &'a mut Vec<OpEnvelope>)>,
+    /// Applied op count of each loaded group
+    pub group_sizes: Vec<(GroupID, usize)>,
+}
+
+/// Ops dropped from one group's log
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupCompaction {
+    pub group_id: String,
+    pub kept: usize,
+    /// Op ids (`OpID::to_hex`) the caller may delete from storage
+    pub dropped: Vec<String>,
+}
+
+/// What one pass did
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub ran_at: u64,
+    /// Contacts whose session will carry a fresh KEM step with its next message
+    pub rotated_sessions: Vec<String>,
+    /// Contacts due for rotation that cannot rotate yet (no peer KEM key)
+    pub rotation_unavailable: Vec<String>,
+    /// Cover slot range now in effect, if cover flows were lent
+    pub cover_interval_secs: Option<(u64, u64)>,
+    pub expired_pings: usize,
+    pub expired_pongs: usize,
+    pub expired_acks: usize,
+    pub expired_pending_ratchets: usize,
+    pub expired_rpc_calls: usize,
+    pub compacted_groups: Vec<GroupCompaction>,
+    /// Loaded groups over `compact_after_ops`; pass their logs next time
+    pub compaction_due: Vec<String>,
+}
+
+impl MaintenanceReport {
+    /// Whether the pass changed anything
+    pub fn is_empty(&self) -> bool {
+        self.rotated_sessions.is_empty()
+            && self.expired_pings == 0
+            && self.expired_pongs == 0
+            && self.expired_acks == 0
+            && self.expired_pending_ratchets == 0
+            && self.expired_rpc_calls == 0
+            && self.compacted_groups.iter().all(|g| g.dropped.is_empty())
+    }
+}
+
+/// Run one maintenance pass at `now` (Unix seconds)
+pub fn run(now: u64, targets: MaintenanceTargets<'_>) -> MaintenanceReport {
+    let policy = policy();
+    let mut report = MaintenanceReport {
+        ran_at: now,
+        ..Default::default()
+    };
+
+    for (contact_id, session) in targets.sessions {
+        let health = session.health(now);
+        if health.rotation_pending || !health.needs_rotation(&policy.rotation) {
+            continue;
+        }
+        match session.force_rotation() {
+            Ok(()) => report.rotated_sessions.push(contact_id.to_string()),
+            Err(_) => report.rotation_unavailable.push(contact_id.to_string()),
+        }
+    }
+
+    if let Some(cover) = targets.cover {
+        let profile = crate::config::current().traffic_profile();
+        cover.set_config(CoverFlowConfig {
+            max_flows: cover.config().max_flows,
+            ..CoverFlowConfig::for_profile(&profile)
+        });
+        report.cover_interval_secs = Some(profile.cover_interval_range());
+    }
+
+    report.expired_pings = crate::network::cleanup_expired_pings();
+    report.expired_pongs = crate::network::cleanup_expired_pongs();
+    report.expired_acks = crate::network::cleanup_expired_acks();
+    report.expired_pending_ratchets = crate::ffi::context::active_context()
+        .pending_ratchets
+        .lock()
+        .unwrap()
+        .remove_expired();
+    report.expired_rpc_calls = crate::network::rpc::expire(now);
+
+    for (group_id, ops) in targets.group_logs {
+        match compact_ops(group_id, ops) {
+            Ok(compaction) => {
+                report.compacted_groups.push(GroupCompaction {
+                    group_id: group_id.to_string(),
+                    kept: compaction.kept.len(),
+                    dropped: compaction.dropped.iter().map(OpID::to_hex).collect(),
+                });
+                *ops = compaction.kept;
+            }
+            Err(e) => log::warn!("Maintenance: not compacting {}: {}", group_id, e),
+        }
+    }
+    report.compaction_due = targets
+        .group_sizes
+        .into_iter()
+        .filter(|&(_, ops)| ops > policy.compact_after_ops)
+        .map(|(group_id, _)| group_id.to_string())
+        .collect();
+
+    if !report.is_empty() {
+        log::info!(
+            "Maintenance: rotated {} sessions, expired {} sessions, compacted {} groups",
+            report.rotated_sessions.len(),
+            report.expired_pings
+                + report.expired_pongs
+                + report.expired_acks
+                + report.expired_pending_ratchets
+                + report.expired_rpc_calls,
+            report.compacted_groups.len()
+        );
+    }
+    report
+}
+
+/// Run a pass over the global state every `every` on the tokio runtime,
+/// handing each report to `on_report`. State owned elsewhere (sessions,
+/// cover flows, op logs) needs a direct `run` by its owner.
+pub fn spawn<F>(every: Duration, on_report: F) -> tokio::task::JoinHandle<()>
+where
+    F: Fn(MaintenanceReport) + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            on_report(run(now, MaintenanceTargets::default()));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{GroupCreatePayload, MetadataKey, MetadataSetPayload, OpType};
+    use crate::crdt::DeviceID;
+
+    #[test]
+    fn test_pass_compacts_logs_and_flags_large_groups() {
+        let (pub_k, priv_k) = crate::crypto::signing::generate_keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&pub_k), &[1; 32]);
+        let create = GroupCreatePayload {
+            group_name: "Test".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let topic = |t: &[u8]| MetadataSetPayload {
+            key: MetadataKey::Topic,
+            value: t.to_vec(),
+        };
+        let mut log = vec![
+            OpEnvelope::create_signed(gid, OpType::GroupCreate, &create, 1, 1, pub_k, &priv_k)
+                .unwrap(),
+            OpEnvelope::create_signed(gid, OpType::MetadataSet, &topic(b"a"), 2, 2, pub_k, &priv_k)
+                .unwrap(),
+            OpEnvelope::create_signed(gid, OpType::MetadataSet, &topic(b"b"), 3, 3, pub_k, &priv_k)
+                .unwrap(),
+        ];
+        let superseded = log[1].op_id.to_hex();
+        let other = GroupID::new(&DeviceID::from_pubkey(&pub_k), &[2; 32]);
+
+        let report = run(
+            1_000,
+            MaintenanceTargets {
+                group_logs: vec![(gid, &mut log)],
+                group_sizes: vec![(gid, 3), (other, policy().compact_after_ops + 1)],
+                ..Default::default()
+            },
+        );
+        assert_eq!(report.ran_at, 1_000);
+        assert_eq!(report.compacted_groups.len(), 1);
+        assert_eq!(report.compacted_groups[0].dropped, vec![superseded]);
+        assert_eq!(report.compacted_groups[0].kept, 2);
+        assert_eq!(log.len(), 2);
+        assert_eq!(report.compaction_due, vec![other.to_string()]);
+        assert!(!report.is_empty());
+    }
+
+    #[test]
+    fn test_pass_refreshes_cover_flows() {
+        let mut cover = CoverFlows::new(CoverFlowConfig {
+            min_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(2),
+            max_flows: 8,
+        });
+        let report = run(
+            0,
+            MaintenanceTargets {
+                cover: Some(&mut cover),
+                ..Default::default()
+            },
+        );
+        let (min, max) = crate::config::current()
+            .traffic_profile()
+            .cover_interval_range();
+        assert_eq!(report.cover_interval_secs, Some((min, max)));
+        assert_eq!(cover.config().min_interval, Duration::from_secs(min));
+        assert_eq!(cover.config().max_flows, 8);
+        assert!(report.rotated_sessions.is_empty());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json.get("compactionDue").is_some());
+    }
+}
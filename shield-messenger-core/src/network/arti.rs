@@ -13,10 +13,10 @@ use rand::{RngCore, SeedableRng};
 /// - Circuit health monitoring and automatic rotation
 /// - Vanguard-style guard node pinning
 ///
-/// NOTE: This module uses `arti-client` and `arti-hyper` when available.
-/// On Android, the existing C Tor (from tor-android) is preferred.
-/// This module is used on desktop/server environments where a pure-Rust
-/// Tor implementation is advantageous for reproducible builds.
+/// NOTE: The manager itself simulates Tor; the real backends live behind
+/// `tor_runtime::TorRuntime` (`SystemTor`, and `arti_runtime::ArtiRuntime`
+/// with the `arti` feature). On Android, the existing C Tor (from
+/// tor-android) is preferred.
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -25,6 +25,8 @@ use tokio::sync::Mutex;
 use tokio::time;
 
 use super::tor_dos_protection::{ConnectionDecision, HsDoSConfig, HsDoSProtection};
+use crate::redact;
+use shield_protocol::protocol::ContactId;
 
 #[derive(Error, Debug)]
 pub enum ArtiError {
@@ -54,16 +56,16 @@ pub type Result<T> = std::result::Result<T, ArtiError>;
 /// to prevent Tor from reusing circuits across different contacts.
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct IsolationToken {
-    /// Unique per-contact identifier (derived from contact's public key hash)
-    pub contact_id: String,
+    /// Canonical contact identifier (derived from the contact's identity key)
+    pub contact_id: ContactId,
     /// Monotonically increasing token to force new circuits
     pub generation: u64,
 }
 
 impl IsolationToken {
-    pub fn new(contact_id: &str) -> Self {
+    pub fn new(contact_id: &ContactId) -> Self {
         Self {
-            contact_id: contact_id.to_string(),
+            contact_id: *contact_id,
             generation: 0,
         }
     }
@@ -135,7 +137,7 @@ pub struct EphemeralOnionService {
 #[derive(Clone, Debug)]
 pub struct CircuitHealth {
     /// Contact ID this circuit serves
-    pub contact_id: String,
+    pub contact_id: ContactId,
     /// When the circuit was established
     pub established_at: Instant,
     /// Number of bytes sent through this circuit
@@ -180,7 +182,7 @@ pub struct ArtiTorManager {
     /// Active onion services
     onion_services: Arc<Mutex<HashMap<String, EphemeralOnionService>>>,
     /// Per-contact circuit isolation tokens
-    isolation_tokens: Arc<Mutex<HashMap<String, IsolationToken>>>,
+    isolation_tokens: Arc<Mutex<HashMap<ContactId, IsolationToken>>>,
     /// Whether the manager is running
     running: Arc<Mutex<bool>>,
     /// Cover traffic task handle
@@ -188,7 +190,7 @@ pub struct ArtiTorManager {
     /// DoS protection for hidden services
     dos_protection: Arc<HsDoSProtection>,
     /// Circuit health tracking
-    circuit_health: Arc<Mutex<HashMap<String, CircuitHealth>>>,
+    circuit_health: Arc<Mutex<HashMap<ContactId, CircuitHealth>>>,
     /// Health monitor task handle
     health_monitor_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     /// DoS cleanup task handle
@@ -337,8 +339,8 @@ impl ArtiTorManager {
             .insert(service_id.to_string(), service.clone());
 
         log::info!(
-            "Created ephemeral onion service: {}.onion:{} → 127.0.0.1:{}",
-            &onion_address[..16],
+            "Created ephemeral onion service: {}:{} → 127.0.0.1:{}",
+            redact::onion(&onion_address),
             virtual_port,
             local_port
         );
@@ -351,7 +353,7 @@ impl ArtiTorManager {
         let mut services = self.onion_services.lock().await;
         if let Some(mut svc) = services.remove(service_id) {
             svc.active = false;
-            log::info!("Removed onion service: {}", service_id);
+            log::info!("Removed onion service: {}", redact::onion(&service_id));
         }
         Ok(())
     }
@@ -361,10 +363,10 @@ impl ArtiTorManager {
     /// Each contact gets a unique circuit isolation token so that Tor circuits
     /// are not reused across different contacts. This prevents a malicious guard
     /// node from correlating traffic between different conversations.
-    pub async fn get_isolation_token(&self, contact_id: &str) -> IsolationToken {
+    pub async fn get_isolation_token(&self, contact_id: &ContactId) -> IsolationToken {
         let mut tokens = self.isolation_tokens.lock().await;
         tokens
-            .entry(contact_id.to_string())
+            .entry(*contact_id)
             .or_insert_with(|| IsolationToken::new(contact_id))
             .clone()
     }
@@ -372,19 +374,19 @@ impl ArtiTorManager {
     /// Rotate a contact's circuit isolation (force new Tor circuits)
     ///
     /// Call this periodically or when suspicious activity is detected.
-    pub async fn rotate_circuit(&self, contact_id: &str) -> Result<()> {
+    pub async fn rotate_circuit(&self, contact_id: &ContactId) -> Result<()> {
         let mut tokens = self.isolation_tokens.lock().await;
         if let Some(token) = tokens.get_mut(contact_id) {
             token.rotate();
             log::info!(
                 "Rotated circuit for contact {}: generation {}",
-                contact_id,
+                redact::id(&contact_id.to_string()),
                 token.generation
             );
         } else {
             let mut token = IsolationToken::new(contact_id);
             token.rotate();
-            tokens.insert(contact_id.to_string(), token);
+            tokens.insert(*contact_id, token);
         }
 
         // Reset circuit health tracking for this contact
@@ -465,7 +467,7 @@ impl ArtiTorManager {
         &self,
         onion_address: &str,
         port: u16,
-        contact_id: &str,
+        contact_id: &ContactId,
     ) -> Result<String> {
         if !self.is_running().await {
             return Err(ArtiError::NotInitialized);
@@ -484,24 +486,22 @@ impl ArtiTorManager {
         // Initialize circuit health tracking
         {
             let mut health = self.circuit_health.lock().await;
-            health
-                .entry(contact_id.to_string())
-                .or_insert(CircuitHealth {
-                    contact_id: contact_id.to_string(),
-                    established_at: Instant::now(),
-                    bytes_sent: 0,
-                    bytes_received: 0,
-                    latency_ms: 0,
-                    healthy: true,
-                });
+            health.entry(*contact_id).or_insert(CircuitHealth {
+                contact_id: *contact_id,
+                established_at: Instant::now(),
+                bytes_sent: 0,
+                bytes_received: 0,
+                latency_ms: 0,
+                healthy: true,
+            });
         }
 
         let proxy_addr = format!("socks5://127.0.0.1:{}", self.config.socks_port);
         log::debug!(
-            "Isolated connection to {}.onion:{} for contact {}",
-            &onion_address[..16.min(onion_address.len())],
+            "Isolated connection to {}:{} for contact {}",
+            redact::onion(&onion_address),
             port,
-            contact_id
+            redact::id(&contact_id.to_string())
         );
 
         Ok(proxy_addr)
@@ -510,7 +510,7 @@ impl ArtiTorManager {
     /// Update circuit health metrics after data transfer
     pub async fn update_circuit_metrics(
         &self,
-        contact_id: &str,
+        contact_id: &ContactId,
         bytes_sent: u64,
         bytes_received: u64,
         latency_ms: u32,
@@ -529,7 +529,7 @@ impl ArtiTorManager {
     }
 
     /// Get circuit health for a contact
-    pub async fn circuit_health(&self, contact_id: &str) -> Option<CircuitHealth> {
+    pub async fn circuit_health(&self, contact_id: &ContactId) -> Option<CircuitHealth> {
         self.circuit_health.lock().await.get(contact_id).cloned()
     }
 
@@ -602,10 +602,10 @@ impl ArtiTorManager {
                     if age > max_age {
                         log::info!(
                             "Circuit for {} exceeded max age ({:?}), scheduling rotation",
-                            contact_id,
+                            redact::id(&contact_id.to_string()),
                             age
                         );
-                        to_rotate.push(contact_id.clone());
+                        to_rotate.push(*contact_id);
                         continue;
                     }
 
@@ -614,10 +614,10 @@ impl ArtiTorManager {
                         ch.healthy = false;
                         log::warn!(
                             "Circuit for {} has high latency ({}ms), marking unhealthy",
-                            contact_id,
+                            redact::id(&contact_id.to_string()),
                             ch.latency_ms
                         );
-                        to_rotate.push(contact_id.clone());
+                        to_rotate.push(*contact_id);
                     }
                 }
 
@@ -629,7 +629,7 @@ impl ArtiTorManager {
                             token.rotate();
                             log::info!(
                                 "Auto-rotated circuit for {}: generation {}",
-                                contact_id,
+                                redact::id(&contact_id.to_string()),
                                 token.generation
                             );
                         }
@@ -724,6 +724,7 @@ impl ArtiTorManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_contact;
 
     fn test_config() -> ArtiConfig {
         ArtiConfig {
@@ -776,13 +777,13 @@ mod tests {
         let manager = ArtiTorManager::new(test_config());
         manager.bootstrap().await.unwrap();
 
-        let t1 = manager.get_isolation_token("alice").await;
-        let t2 = manager.get_isolation_token("bob").await;
+        let t1 = manager.get_isolation_token(&test_contact(1)).await;
+        let t2 = manager.get_isolation_token(&test_contact(2)).await;
         assert_ne!(t1.contact_id, t2.contact_id);
         assert_eq!(t1.generation, 0);
 
-        manager.rotate_circuit("alice").await.unwrap();
-        let t1_rotated = manager.get_isolation_token("alice").await;
+        manager.rotate_circuit(&test_contact(1)).await.unwrap();
+        let t1_rotated = manager.get_isolation_token(&test_contact(1)).await;
         assert_eq!(t1_rotated.generation, 1);
 
         manager.shutdown().await.unwrap();
@@ -800,13 +801,13 @@ mod tests {
         manager.bootstrap().await.unwrap();
 
         let addr = manager
-            .connect_isolated("abcdef1234567890", 9150, "contact1")
+            .connect_isolated("abcdef1234567890", 9150, &test_contact(1))
             .await
             .unwrap();
         assert!(addr.contains("19050"));
 
         // Verify circuit health tracking was initialized
-        let health = manager.circuit_health("contact1").await;
+        let health = manager.circuit_health(&test_contact(1)).await;
         assert!(health.is_some());
         assert!(health.unwrap().healthy);
 
@@ -854,23 +855,25 @@ mod tests {
 
         // Connect to establish health tracking
         manager
-            .connect_isolated("test_onion", 9150, "alice")
+            .connect_isolated("test_onion", 9150, &test_contact(1))
             .await
             .unwrap();
 
         // Update metrics
         manager
-            .update_circuit_metrics("alice", 1024, 2048, 500)
+            .update_circuit_metrics(&test_contact(1), 1024, 2048, 500)
             .await;
 
-        let health = manager.circuit_health("alice").await.unwrap();
+        let health = manager.circuit_health(&test_contact(1)).await.unwrap();
         assert_eq!(health.bytes_sent, 1024);
         assert_eq!(health.bytes_received, 2048);
         assert_eq!(health.latency_ms, 500);
 
         // Update again — latency should use EMA
-        manager.update_circuit_metrics("alice", 512, 256, 300).await;
-        let health = manager.circuit_health("alice").await.unwrap();
+        manager
+            .update_circuit_metrics(&test_contact(1), 512, 256, 300)
+            .await;
+        let health = manager.circuit_health(&test_contact(1)).await.unwrap();
         assert_eq!(health.bytes_sent, 1536);
         assert_eq!(health.bytes_received, 2304);
         // EMA: (500*7 + 300) / 8 = 475
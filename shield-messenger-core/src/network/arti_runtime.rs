@@ -0,0 +1,245 @@
+//! Embedded Tor (Arti)
+//!
+//! [`ArtiRuntime`] implements [`TorRuntime`] with Tor running inside the
+//! process, built on `arti-client` and `tor-hsservice`. Desktop and server
+//! builds enable the `arti` feature and need neither a tor binary nor the
+//! Java `OnionProxyManager`; Android keeps using C Tor through
+//! [`SystemTor`](super::tor_runtime::SystemTor) until Arti has been
+//! measured on mobile.
+//!
+//! - Streams: each [`IsolationToken`] maps to its own Arti isolation token,
+//!   so rotating a contact's generation moves it onto fresh circuits
+//! - Onion services: launched by nickname (the service id) and served by
+//!   forwarding every accepted stream on the virtual port to
+//!   `127.0.0.1:local_port`, so listeners work the same as with C Tor
+//!
+//! Arti keeps onion service identity keys in its own keystore under
+//! `data_dir`, one per nickname, so a service keeps its address across
+//! restarts but the address is not derived from our seed. Specs with a
+//! `key_seed` are rejected until Arti's key import API is stable.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use arti_client::config::{BoolOrAuto, TorClientConfigBuilder};
+use arti_client::{DataStream, StreamPrefs, TorClient};
+use futures::StreamExt;
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::task::JoinHandle;
+use tor_cell::relaycell::msg::Connected;
+use tor_hsservice::config::OnionServiceConfigBuilder;
+use tor_hsservice::{handle_rend_requests, HsNickname, RunningOnionService};
+use tor_proto::stream::IncomingStreamRequest;
+use tor_rtcompat::PreferredRuntime;
+
+use super::arti::{ArtiConfig, EphemeralOnionService, IsolationToken};
+use super::tor_runtime::{OnionServiceSpec, Result, TorRuntime, TorRuntimeError};
+
+fn backend(e: impl std::fmt::Display) -> TorRuntimeError {
+    TorRuntimeError::Backend(e.to_string())
+}
+
+/// A published service and the task forwarding its streams
+struct RunningService {
+    info: EphemeralOnionService,
+    /// Dropping the handle takes the service down
+    _service: Arc<RunningOnionService>,
+    forwarder: JoinHandle<()>,
+}
+
+/// Tor in-process via Arti
+pub struct ArtiRuntime {
+    config: ArtiConfig,
+    client: OnceCell<TorClient<PreferredRuntime>>,
+    progress: Arc<AtomicU8>,
+    isolation: std::sync::Mutex<HashMap<IsolationToken, arti_client::IsolationToken>>,
+    services: Mutex<HashMap<String, RunningService>>,
+}
+
+impl ArtiRuntime {
+    /// Uses `config.data_dir` for state (guards, keystore) and
+    /// `config.cache_dir` for directory documents
+    pub fn new(config: ArtiConfig) -> Self {
+        Self {
+            config,
+            client: OnceCell::new(),
+            progress: Arc::new(AtomicU8::new(0)),
+            isolation: std::sync::Mutex::new(HashMap::new()),
+            services: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn client(&self) -> Result<&TorClient<PreferredRuntime>> {
+        self.client.get().ok_or(TorRuntimeError::NotBootstrapped)
+    }
+
+    /// The Arti token standing for `token`, created on first use
+    fn arti_isolation(&self, token: &IsolationToken) -> arti_client::IsolationToken {
+        let mut tokens = self.isolation.lock().unwrap_or_else(|e| e.into_inner());
+        // Older generations of the same contact are never used again
+        tokens.retain(|known, _| {
+            known.contact_id != token.contact_id || known.generation >= token.generation
+        });
+        *tokens
+            .entry(token.clone())
+            .or_insert_with(arti_client::IsolationToken::new)
+    }
+
+    async fn create_client(&self) -> Result<TorClient<PreferredRuntime>> {
+        let config =
+            TorClientConfigBuilder::from_directories(&self.config.data_dir, &self.config.cache_dir)
+                .build()
+                .map_err(backend)?;
+        let client = TorClient::builder()
+            .config(config)
+            .create_unbootstrapped()
+            .map_err(backend)?;
+
+        let mut events = client.bootstrap_events();
+        let progress = self.progress.clone();
+        tokio::spawn(async move {
+            while let Some(status) = events.next().await {
+                let percent = (status.as_frac() * 100.0).clamp(0.0, 100.0) as u8;
+                progress.store(percent, Ordering::Relaxed);
+            }
+        });
+
+        client.bootstrap().await.map_err(backend)?;
+        self.progress.store(100, Ordering::Relaxed);
+        log::info!("Arti bootstrapped");
+        Ok(client)
+    }
+}
+
+impl TorRuntime for ArtiRuntime {
+    type Stream = DataStream;
+
+    async fn bootstrap(&self) -> Result<()> {
+        self.client
+            .get_or_try_init(|| self.create_client())
+            .await
+            .map(|_| ())
+    }
+
+    fn bootstrap_progress(&self) -> u8 {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    async fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        isolation: &IsolationToken,
+    ) -> Result<DataStream> {
+        let client = self.client()?;
+        let mut prefs = StreamPrefs::new();
+        prefs.connect_to_onion_services(BoolOrAuto::Explicit(true));
+        prefs.set_isolation(self.arti_isolation(isolation));
+        client
+            .connect_with_prefs((host, port), &prefs)
+            .await
+            .map_err(|e| TorRuntimeError::Socks(e.to_string()))
+    }
+
+    async fn add_onion_service(
+        &self,
+        service_id: &str,
+        spec: &OnionServiceSpec,
+    ) -> Result<EphemeralOnionService> {
+        if spec.key_seed.is_some() {
+            return Err(TorRuntimeError::Unsupported(
+                "Arti onion services use keys from its own keystore",
+            ));
+        }
+        let client = self.client()?;
+        let mut services = self.services.lock().await;
+        if services.contains_key(service_id) {
+            return Err(TorRuntimeError::OnionService(format!(
+                "{} is already published",
+                service_id
+            )));
+        }
+
+        let nickname: HsNickname = service_id.parse().map_err(backend)?;
+        let service_config = OnionServiceConfigBuilder::default()
+            .nickname(nickname)
+            .build()
+            .map_err(backend)?;
+        let (service, requests) = client
+            .launch_onion_service(service_config)
+            .map_err(|e| TorRuntimeError::OnionService(e.to_string()))?;
+        let onion_address = service
+            .onion_name()
+            .map(|id| id.to_string().trim_end_matches(".onion").to_string())
+            .ok_or_else(|| TorRuntimeError::OnionService("service has no address".into()))?;
+
+        let (virtual_port, local_port) = (spec.virtual_port, spec.local_port);
+        let forwarder = tokio::spawn(async move {
+            let mut streams = Box::pin(handle_rend_requests(requests));
+            while let Some(request) = streams.next().await {
+                let wanted = matches!(
+                    request.request(),
+                    IncomingStreamRequest::Begin(begin) if begin.port() == virtual_port
+                );
+                if !wanted {
+                    let _ = request.shutdown_circuit();
+                    continue;
+                }
+                tokio::spawn(async move {
+                    let mut onion = match request.accept(Connected::new_empty()).await {
+                        Ok(stream) => stream,
+                        Err(e) => return log::debug!("Onion stream not accepted: {}", e),
+                    };
+                    let target = super::listen::listen_config().socket_addr(local_port);
+                    match TcpStream::connect(target).await {
+                        Ok(mut local) => {
+                            let _ = tokio::io::copy_bidirectional(&mut onion, &mut local).await;
+                        }
+                        Err(e) => log::warn!("Local port {} unreachable: {}", local_port, e),
+                    }
+                });
+            }
+        });
+
+        let info = EphemeralOnionService {
+            onion_address,
+            local_port,
+            virtual_port,
+            active: true,
+            created_at: Instant::now(),
+        };
+        services.insert(
+            service_id.to_string(),
+            RunningService {
+                info: info.clone(),
+                _service: service,
+                forwarder,
+            },
+        );
+        Ok(info)
+    }
+
+    async fn remove_onion_service(&self, service_id: &str) -> Result<()> {
+        if let Some(running) = self.services.lock().await.remove(service_id) {
+            running.forwarder.abort();
+            log::info!("Removed onion service {}", running.info.onion_address);
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        for (_, running) in self.services.lock().await.drain() {
+            running.forwarder.abort();
+        }
+        self.isolation
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        // The client lives until the runtime is dropped; without services
+        // or streams it only keeps its directory up to date
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! Upstream Bandwidth Cap
+//!
+//! Every send takes a permit from the active context's `send_permits`, and
+//! `acquire` first calls [`reserve`] on that context's shaper. Media
+//! (images, voice, file-transfer chunks, sync) then waits in a
+//! `BandwidthShaper` until the aggregate upstream rate allows it, with
+//! conversations taking turns so one upload cannot starve another. Control,
+//! receipts and text never wait; their bytes are only charged against the
+//! budget. With `max_bytes_per_sec` 0 (the default) nothing is shaped.
+//!
+//! When a conversation's media queue drains while the cap is on, the shaper
+//! keeps releasing slots at that conversation's pace for a short random
+//! tail. With cover traffic enabled each slot becomes a cover packet to the
+//! same onion, so the gaps between file-transfer windows and the end of an
+//! upload look like the upload itself. With cover traffic off the slots are
+//! skipped.
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use shield_protocol::transport::bandwidth::{BandwidthConfig, BandwidthShaper, Release};
+use shield_protocol::transport::padding::MSG_TYPE_COVER;
+use shield_protocol::transport::priority::Lane;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+
+use crate::ffi::context::active_context;
+use crate::redact;
+
+/// Random body of a cover packet; padding brings it to the fixed size
+const COVER_BODY_LEN: usize = 24;
+
+/// Per-context shaper (lives in `ffi::context::ProtocolContext`). A new
+/// context starts with the cap of the config last applied.
+pub(crate) struct BandwidthState {
+    shaper: Mutex<BandwidthShaper<String, oneshot::Sender<()>>>,
+    pump: Mutex<Option<JoinHandle<()>>>,
+    wake: Arc<Notify>,
+}
+
+impl Default for BandwidthState {
+    fn default() -> Self {
+        BandwidthState {
+            shaper: Mutex::new(BandwidthShaper::new(
+                crate::config::current().bandwidth_config(),
+                Instant::now(),
+            )),
+            pump: Mutex::new(None),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl Drop for BandwidthState {
+    fn drop(&mut self) {
+        let pump = self.pump.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(task) = pump.take() {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthStats {
+    /// 0 when unlimited
+    pub max_bytes_per_sec: u64,
+    pub sent_bytes: u64,
+    pub charged_bytes: u64,
+    pub cover_packets: u64,
+    pub cover_bytes: u64,
+    pub delayed: u64,
+    pub queued: usize,
+}
+
+/// Install a new cap. Media already waiting is released under the new one.
+pub fn set_config(config: BandwidthConfig) {
+    let limited = config.is_limited();
+    let ctx = active_context();
+    ctx.bandwidth
+        .shaper
+        .lock()
+        .unwrap()
+        .set_config(config, Instant::now());
+    ctx.bandwidth.wake.notify_one();
+    log::info!(
+        "Upstream bandwidth cap: {}",
+        if limited { "on" } else { "off" }
+    );
+}
+
+pub fn config() -> BandwidthConfig {
+    active_context()
+        .bandwidth
+        .shaper
+        .lock()
+        .unwrap()
+        .config()
+        .clone()
+}
+
+pub fn stats() -> BandwidthStats {
+    let ctx = active_context();
+    let shaper = ctx.bandwidth.shaper.lock().unwrap();
+    let stats = shaper.stats();
+    BandwidthStats {
+        max_bytes_per_sec: shaper.config().max_bytes_per_sec,
+        sent_bytes: stats.sent_bytes,
+        charged_bytes: stats.charged_bytes,
+        cover_packets: stats.cover_packets,
+        cover_bytes: stats.cover_bytes,
+        delayed: stats.delayed,
+        queued: stats.queued,
+    }
+}
+
+/// Wait until `bytes` for `conversation` fit under the active context's
+/// cap. Only the media lane waits; other lanes are charged and return at
+/// once.
+pub async fn reserve(lane: Lane, conversation: &str, bytes: usize) {
+    let state = active_context().bandwidth.clone();
+    reserve_in(&state, lane, conversation, bytes).await
+}
+
+pub(crate) async fn reserve_in(
+    state: &Arc<BandwidthState>,
+    lane: Lane,
+    conversation: &str,
+    bytes: usize,
+) {
+    let (rx, releases) = {
+        let mut shaper = state.shaper.lock().unwrap();
+        if !shaper.config().is_limited() {
+            return;
+        }
+        let now = Instant::now();
+        if lane != Lane::Media {
+            shaper.charge(bytes, now);
+            return;
+        }
+        let (tx, rx) = oneshot::channel();
+        shaper.enqueue(conversation.to_string(), tx, bytes);
+        (rx, shaper.poll(now, &mut OsRng))
+    };
+    dispatch(releases);
+    ensure_pump(state);
+    state.wake.notify_one();
+    // Dropped only if the queue is cleared; the send then goes ahead
+    let _ = rx.await;
+}
+
+fn ensure_pump(state: &Arc<BandwidthState>) {
+    let mut pump = state.pump.lock().unwrap();
+    if pump.as_ref().is_some_and(|task| !task.is_finished()) {
+        return;
+    }
+    *pump = Some(tokio::spawn(run_pump(
+        Arc::downgrade(state),
+        state.wake.clone(),
+    )));
+}
+
+/// Releases waiting media and cover slots as the budget refills; ends with
+/// the context that owns the shaper
+async fn run_pump(state: Weak<BandwidthState>, wake: Arc<Notify>) {
+    loop {
+        let Some(state) = state.upgrade() else {
+            break;
+        };
+        let (releases, wake_at) = {
+            let mut shaper = state.shaper.lock().unwrap();
+            let now = Instant::now();
+            let releases = shaper.poll(now, &mut OsRng);
+            (releases, shaper.next_wakeup(now))
+        };
+        drop(state);
+        dispatch(releases);
+        match wake_at {
+            Some(at) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(at.into()) => {}
+                    _ = wake.notified() => {}
+                }
+            }
+            None => wake.notified().await,
+        }
+    }
+}
+
+fn dispatch(releases: Vec<Release<String, oneshot::Sender<()>>>) {
+    for release in releases {
+        match release {
+            Release::Send { item, .. } => {
+                // Waiter gone (send cancelled); its budget is spent anyway
+                let _ = item.send(());
+            }
+            Release::Cover { flow } => {
+                if crate::config::current().features.cover_traffic {
+                    tokio::spawn(send_cover(flow));
+                }
+            }
+        }
+    }
+}
+
+async fn send_cover(onion: String) {
+    let mut packet = vec![0u8; 1 + COVER_BODY_LEN];
+    packet[0] = MSG_TYPE_COVER;
+    OsRng.fill_bytes(&mut packet[1..]);
+
+    let sent = tokio::time::timeout(super::timeout_policy().blob_send, async {
+        let mut conn = super::tor::connect_to_onion(&onion, super::ports().messaging)
+            .await
+            .map_err(|e| e.to_string())?;
+        conn.send(&packet).await.map_err(|e| e.to_string())
+    })
+    .await;
+    match sent {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::debug!("Cover packet to {} not sent: {}", redact::onion(&onion), e),
+        Err(_) => log::debug!("Cover packet to {} timed out", redact::onion(&onion)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_only_media_waits_for_budget() {
+        // Unlimited: nothing is queued or charged
+        reserve(Lane::Media, "alice", 100_000).await;
+        assert_eq!(stats().charged_bytes, 0);
+
+        set_config(BandwidthConfig {
+            burst_bytes: 4096,
+            max_cover_tail: Duration::ZERO,
+            ..BandwidthConfig::limited(4096)
+        });
+        // Overdraws the burst; the next media send has to wait
+        reserve(Lane::Media, "alice", 8192).await;
+        let waiting = tokio::spawn(reserve(Lane::Media, "bob", 4096));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        assert_eq!(stats().queued, 1);
+
+        // A receipt goes straight out
+        tokio::time::timeout(Duration::from_millis(50), reserve(Lane::Receipt, "bob", 64))
+            .await
+            .unwrap();
+        assert_eq!(stats().charged_bytes, 64);
+
+        // Lifting the cap releases the waiter
+        set_config(BandwidthConfig::UNLIMITED);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stats().queued, 0);
+    }
+}
@@ -0,0 +1,358 @@
+//! Contact List Backup
+//!
+//! Client side of `shield_protocol::protocol::contact_backup`. The app hands
+//! over its contact list and trust records as a `ContactSnapshot`;
+//! [`backup`] seals it under the master seed and writes it to the replica
+//! relays, [`restore`] reads it back after a reinstall. Replicas are picked
+//! from the relays registered through `relays::add_descriptor`, so the app
+//! must have re-added its relay descriptors before restoring.
+//!
+//! Each request is one connection to the relay's onion: `[0x16][request]`
+//! out, one response back.
+//!
+//! [`backup`] first reads every replica and merges what it finds into the
+//! local snapshot, then writes the result as the next version. A replica
+//! that changed in between (another device backing up) answers with its
+//! copy instead; that is merged in and the write repeated, for at most
+//! `MAX_WRITE_ROUNDS` rounds. The merged snapshot comes back in the
+//! [`BackupOutcome`], so the app can apply contacts added or removed on its
+//! other devices.
+
+use rand::rngs::OsRng;
+use serde::Serialize;
+use shield_protocol::protocol::contact_backup::{
+    BackupKeys, BackupOp, BackupRejection, BackupRequest, BackupResponse, ContactBackupError,
+    ContactSnapshot,
+};
+use shield_protocol::protocol::relay::RelayDescriptor;
+use thiserror::Error;
+
+use super::ports::PortConfig;
+use super::tor::MSG_TYPE_CONTACT_BACKUP;
+use crate::ffi::context::active_context;
+use crate::redact;
+
+/// Relays serve backup slots on the default messaging port
+const RELAY_PORT: u16 = PortConfig::DEFAULT.messaging;
+
+/// Write rounds before giving up on replicas that keep changing
+const MAX_WRITE_ROUNDS: usize = 3;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error(transparent)]
+    Backup(#[from] ContactBackupError),
+
+    #[error("No known relay offers contact backups")]
+    NoRelays,
+
+    #[error("Relay rejected the backup request: {0:?}")]
+    Rejected(BackupRejection),
+
+    #[error("No backup relay reachable: {0}")]
+    Unreachable(String),
+
+    #[error("Backup replicas kept changing; try again")]
+    Contended,
+}
+
+/// Result of a [`backup`]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupOutcome {
+    /// Local snapshot merged with the replicas, as stored
+    pub snapshot: ContactSnapshot,
+    /// Replicas now holding `snapshot.version`
+    pub stored: usize,
+    pub replicas: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Replica {
+    Unreachable,
+    /// Holds this version; the next write must name it
+    At(u64),
+    /// Holds the snapshot being written
+    Written(u64),
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+async fn exchange(
+    relay: &RelayDescriptor,
+    request: &BackupRequest,
+) -> Result<BackupResponse, String> {
+    let onion = &relay.info.onion_address;
+    let mut wire = vec![MSG_TYPE_CONTACT_BACKUP];
+    wire.extend_from_slice(&request.to_bytes().map_err(|e| e.to_string())?);
+
+    let ctx = active_context();
+    let _permit = ctx
+        .send_permits
+        .acquire(
+            super::lane_for_msg_type(MSG_TYPE_CONTACT_BACKUP),
+            onion,
+            wire.len(),
+        )
+        .await;
+    let answered = tokio::time::timeout(super::timeout_policy().blob_send, async {
+        let mut conn = super::tor::connect_to_onion(onion, RELAY_PORT)
+            .await
+            .map_err(|e| e.to_string())?;
+        conn.send(&wire).await.map_err(|e| e.to_string())?;
+        let response = conn.receive().await.map_err(|e| e.to_string())?;
+        BackupResponse::from_bytes(&response).map_err(|e| e.to_string())
+    })
+    .await;
+    answered.unwrap_or_else(|_| Err("timed out".into()))
+}
+
+async fn request(
+    relay: &RelayDescriptor,
+    keys: &BackupKeys,
+    op: BackupOp,
+) -> Result<Option<BackupResponse>, BackupError> {
+    let request = BackupRequest::new(keys, op, now_secs())?;
+    match exchange(relay, &request).await {
+        Ok(BackupResponse::Rejected(rejection)) => Err(BackupError::Rejected(rejection)),
+        Ok(response) => Ok(Some(response)),
+        Err(e) => {
+            log::warn!(
+                "Backup relay {} unreachable: {}",
+                redact::onion(&relay.info.onion_address),
+                e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Merge a replica's copy into `snapshot`; returns the version it holds
+fn absorb(keys: &BackupKeys, response: BackupResponse, snapshot: &mut ContactSnapshot) -> u64 {
+    match response {
+        BackupResponse::Current { version, blob } => {
+            match ContactSnapshot::open(&blob, keys) {
+                Ok(remote) => *snapshot = snapshot.merge(&remote),
+                // Only the seed holder can write the slot; an unreadable
+                // copy is damaged and gets overwritten
+                Err(e) => log::warn!("Stored contact backup v{} unreadable: {}", version, e),
+            }
+            version
+        }
+        BackupResponse::Stored { version } => version,
+        BackupResponse::Empty | BackupResponse::Rejected(_) => 0,
+    }
+}
+
+fn replicas_for(keys: &BackupKeys) -> Result<Vec<RelayDescriptor>, BackupError> {
+    let relays = super::relays::backup_replicas(&keys.slot);
+    if relays.is_empty() {
+        return Err(BackupError::NoRelays);
+    }
+    Ok(relays)
+}
+
+/// Merge `local` with the stored copies and store the result on every
+/// reachable replica
+pub async fn backup(
+    master_seed: &[u8],
+    local: ContactSnapshot,
+) -> Result<BackupOutcome, BackupError> {
+    let keys = BackupKeys::derive(master_seed)?;
+    let relays = replicas_for(&keys)?;
+    let mut snapshot = local;
+
+    let mut replicas = Vec::with_capacity(relays.len());
+    for relay in &relays {
+        replicas.push(match request(relay, &keys, BackupOp::Get).await? {
+            Some(response) => Replica::At(absorb(&keys, response, &mut snapshot)),
+            None => Replica::Unreachable,
+        });
+    }
+    if replicas.iter().all(|r| *r == Replica::Unreachable) {
+        return Err(BackupError::Unreachable(format!(
+            "{} replicas",
+            relays.len()
+        )));
+    }
+    snapshot.prune_tombstones(now_millis());
+
+    for _ in 0..MAX_WRITE_ROUNDS {
+        let newest = replicas
+            .iter()
+            .filter_map(|r| match r {
+                Replica::At(v) | Replica::Written(v) => Some(*v),
+                Replica::Unreachable => None,
+            })
+            .max()
+            .unwrap_or(0);
+        snapshot.version = snapshot.version.max(newest) + 1;
+        let blob = snapshot.seal(&keys, &mut OsRng)?;
+
+        let mut changed = false;
+        for (relay, replica) in relays.iter().zip(replicas.iter_mut()) {
+            let base_version = match *replica {
+                Replica::At(v) | Replica::Written(v) => v,
+                Replica::Unreachable => continue,
+            };
+            let op = BackupOp::Put {
+                base_version,
+                version: snapshot.version,
+                blob: blob.clone(),
+            };
+            *replica = match request(relay, &keys, op).await? {
+                Some(BackupResponse::Stored { version }) => Replica::Written(version),
+                Some(response) => {
+                    changed = true;
+                    Replica::At(absorb(&keys, response, &mut snapshot))
+                }
+                None => Replica::Unreachable,
+            };
+        }
+
+        if !changed {
+            let stored = replicas
+                .iter()
+                .filter(|r| matches!(r, Replica::Written(_)))
+                .count();
+            if stored == 0 {
+                return Err(BackupError::Unreachable("all replicas failed".into()));
+            }
+            log::info!(
+                "Contact backup v{} stored on {}/{} relays ({} contacts)",
+                snapshot.version,
+                stored,
+                relays.len(),
+                snapshot.live().count()
+            );
+            return Ok(BackupOutcome {
+                snapshot,
+                stored,
+                replicas: relays.len(),
+            });
+        }
+        // Another device wrote meanwhile: write the merged result everywhere
+    }
+    Err(BackupError::Contended)
+}
+
+/// Read the backup from every replica and merge the copies. `None` if no
+/// replica holds one
+pub async fn restore(master_seed: &[u8]) -> Result<Option<ContactSnapshot>, BackupError> {
+    let keys = BackupKeys::derive(master_seed)?;
+    let relays = replicas_for(&keys)?;
+    let mut snapshot = ContactSnapshot::default();
+    let mut reached = 0;
+    let mut found = false;
+    for relay in &relays {
+        let Some(response) = request(relay, &keys, BackupOp::Get).await? else {
+            continue;
+        };
+        reached += 1;
+        if let BackupResponse::Current { blob, .. } = &response {
+            match ContactSnapshot::open(blob, &keys) {
+                Ok(remote) => {
+                    snapshot = snapshot.merge(&remote);
+                    found = true;
+                }
+                Err(e) => log::warn!("Stored contact backup unreadable: {}", e),
+            }
+        }
+    }
+    if reached == 0 {
+        return Err(BackupError::Unreachable(format!(
+            "{} replicas",
+            relays.len()
+        )));
+    }
+    Ok(found.then_some(snapshot))
+}
+
+/// Clear the backup on every reachable replica; returns how many were cleared
+pub async fn delete(master_seed: &[u8]) -> Result<usize, BackupError> {
+    let keys = BackupKeys::derive(master_seed)?;
+    let relays = replicas_for(&keys)?;
+    let mut cleared = 0;
+    for relay in &relays {
+        let mut base_version = match request(relay, &keys, BackupOp::Get).await? {
+            Some(BackupResponse::Current { version, .. }) => version,
+            Some(_) => {
+                cleared += 1;
+                continue;
+            }
+            None => continue,
+        };
+        for _ in 0..MAX_WRITE_ROUNDS {
+            match request(relay, &keys, BackupOp::Delete { base_version }).await? {
+                Some(BackupResponse::Stored { .. }) | Some(BackupResponse::Empty) => {
+                    cleared += 1;
+                    break;
+                }
+                Some(BackupResponse::Current { version, .. }) => base_version = version,
+                Some(BackupResponse::Rejected(_)) | None => break,
+            }
+        }
+    }
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_contact;
+    use shield_protocol::crypto::pqc::{ContactVerificationRecord, TrustLevel};
+    use shield_protocol::protocol::contact_backup::{BackupContact, BackupVault};
+
+    fn contact(n: u8, updated_at: u64) -> BackupContact {
+        let record = ContactVerificationRecord {
+            contact_id: test_contact(n),
+            trust_level: TrustLevel::Verified,
+            verified_at: 0,
+            safety_number: String::new(),
+        };
+        BackupContact::new(&record, String::new(), updated_at)
+    }
+
+    #[test]
+    fn test_absorb_merges_conflicting_copy() {
+        let keys = BackupKeys::derive(&[3u8; 64]).unwrap();
+        let mut vault = BackupVault::new(4);
+        let now = now_secs();
+
+        // Another device stored contact 1 as v1
+        let theirs = ContactSnapshot {
+            version: 1,
+            ..ContactSnapshot::new(vec![contact(1, 10)])
+        };
+        let put = BackupOp::Put {
+            base_version: 0,
+            version: 1,
+            blob: theirs.seal(&keys, &mut OsRng).unwrap(),
+        };
+        vault.handle(&BackupRequest::new(&keys, put, now).unwrap(), now);
+
+        // Our write based on the empty slot is answered with their copy
+        let mut ours = ContactSnapshot::new(vec![contact(2, 20)]);
+        let put = BackupOp::Put {
+            base_version: 0,
+            version: 1,
+            blob: ours.seal(&keys, &mut OsRng).unwrap(),
+        };
+        let response = vault.handle(&BackupRequest::new(&keys, put, now).unwrap(), now);
+        assert_eq!(absorb(&keys, response, &mut ours), 1);
+        assert_eq!(ours.live().count(), 2);
+        assert_eq!(ours.version, 1);
+    }
+}
@@ -0,0 +1,238 @@
+//! Delivery Failure Diagnostics
+//!
+//! Send paths used to collapse every failure into a log line and an
+//! exception string, leaving the app unable to tell "Tor is down" from "the
+//! contact is offline" from "the contact declined". They now report a typed
+//! [`DeliveryFailure`] that says what went wrong and at which stage of the
+//! Ping → Pong → message exchange, and every attempt's outcome is queued as
+//! an [`OutboxEvent`] that the app drains (`drain_events_json`) to update
+//! message status and show an actionable error. Outcomes also feed the
+//! per-contact estimates in `health`.
+//!
+//! SOCKS replies are how Tor reports onion-service failures: "host
+//! unreachable" (no descriptor), "TTL expired" (introduction/rendezvous
+//! failed) and friends all mean the recipient is offline, while failing to
+//! reach the SOCKS port at all means our own Tor is not running.
+
+use serde::Serialize;
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::ffi::context::active_context;
+
+/// Oldest events are dropped beyond this many undrained ones.
+pub const MAX_QUEUED_EVENTS: usize = 256;
+
+/// Step of a direct delivery at which a failure happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStage {
+    /// Reaching the recipient's onion service through Tor
+    Connect,
+    /// Sending the Ping
+    Ping,
+    /// Waiting for and checking the Pong
+    Pong,
+    /// Sending the message itself
+    Send,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeliveryFailure {
+    #[error("Tor is not reachable: {detail}")]
+    TorUnreachable { detail: String },
+
+    #[error("Recipient's onion service is offline: {detail}")]
+    OnionOffline { detail: String },
+
+    #[error("Handshake rejected at {stage:?}: {reason}")]
+    HandshakeRejected {
+        stage: DeliveryStage,
+        reason: String,
+    },
+
+    #[error("Recipient declined the message")]
+    RecipientDeclined,
+
+    #[error("Timed out at {stage:?}")]
+    Timeout { stage: DeliveryStage },
+
+    #[error("Connection lost at {stage:?}: {detail}")]
+    ConnectionLost {
+        stage: DeliveryStage,
+        detail: String,
+    },
+}
+
+impl DeliveryFailure {
+    /// Classify an error from connecting to an onion service
+    pub fn from_connect_error(error: impl Display) -> Self {
+        let detail = error.to_string();
+        let lower = detail.to_ascii_lowercase();
+        if lower.contains("socks proxy unreachable") {
+            DeliveryFailure::TorUnreachable { detail }
+        } else if [
+            "host unreachable",
+            "ttl expired",
+            "connection refused",
+            "general socks server failure",
+            "network unreachable",
+        ]
+        .iter()
+        .any(|s| lower.contains(s))
+        {
+            DeliveryFailure::OnionOffline { detail }
+        } else {
+            DeliveryFailure::ConnectionLost {
+                stage: DeliveryStage::Connect,
+                detail,
+            }
+        }
+    }
+
+    pub fn connection_lost(stage: DeliveryStage, error: impl Display) -> Self {
+        DeliveryFailure::ConnectionLost {
+            stage,
+            detail: error.to_string(),
+        }
+    }
+
+    pub fn handshake_rejected(stage: DeliveryStage, reason: impl Display) -> Self {
+        DeliveryFailure::HandshakeRejected {
+            stage,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Stage the failure happened at
+    pub fn stage(&self) -> DeliveryStage {
+        match self {
+            DeliveryFailure::TorUnreachable { .. } | DeliveryFailure::OnionOffline { .. } => {
+                DeliveryStage::Connect
+            }
+            DeliveryFailure::HandshakeRejected { stage, .. }
+            | DeliveryFailure::Timeout { stage }
+            | DeliveryFailure::ConnectionLost { stage, .. } => *stage,
+            DeliveryFailure::RecipientDeclined => DeliveryStage::Pong,
+        }
+    }
+
+    /// Whether retrying later can succeed without user action. A declined
+    /// message or a rejected handshake will fail the same way again.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            DeliveryFailure::RecipientDeclined | DeliveryFailure::HandshakeRejected { .. }
+        )
+    }
+}
+
+/// Outcome of one delivery attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutboxEvent {
+    /// Recipient .onion address
+    pub recipient: String,
+    pub at_ms: u64,
+    /// `None` if the message was delivered
+    pub failure: Option<DeliveryFailure>,
+    pub retryable: bool,
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn push(event: OutboxEvent) {
+    let ctx = active_context();
+    let mut events = ctx.outbox_events.lock().unwrap();
+    if events.len() >= MAX_QUEUED_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Record a successful delivery to `recipient`
+pub fn record_delivered(recipient: &str) {
+    super::health::record_outcome(recipient, None);
+    push(OutboxEvent {
+        recipient: recipient.to_string(),
+        at_ms: now_millis(),
+        failure: None,
+        retryable: false,
+    });
+}
+
+/// Record a failed delivery to `recipient`
+pub fn record_failure(recipient: &str, failure: DeliveryFailure) {
+    log::warn!("Delivery to {} failed: {}", recipient, failure);
+    super::health::record_outcome(recipient, Some(&failure));
+    push(OutboxEvent {
+        recipient: recipient.to_string(),
+        at_ms: now_millis(),
+        retryable: failure.is_retryable(),
+        failure: Some(failure),
+    });
+}
+
+/// Take all queued events, oldest first
+pub fn drain_events() -> Vec<OutboxEvent> {
+    active_context()
+        .outbox_events
+        .lock()
+        .unwrap()
+        .drain(..)
+        .collect()
+}
+
+/// [`drain_events`] as a JSON array
+pub fn drain_events_json() -> String {
+    serde_json::to_string(&drain_events()).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Drop all queued events
+pub fn clear() {
+    active_context().outbox_events.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_connect_errors() {
+        assert!(matches!(
+            DeliveryFailure::from_connect_error("SOCKS proxy unreachable: Connection refused"),
+            DeliveryFailure::TorUnreachable { .. }
+        ));
+        assert!(matches!(
+            DeliveryFailure::from_connect_error("SOCKS5 connection failed: Host unreachable"),
+            DeliveryFailure::OnionOffline { .. }
+        ));
+        let other = DeliveryFailure::from_connect_error("early eof");
+        assert_eq!(other.stage(), DeliveryStage::Connect);
+        assert!(other.is_retryable());
+        assert!(!DeliveryFailure::RecipientDeclined.is_retryable());
+    }
+
+    #[test]
+    fn test_events_are_typed_json() {
+        clear();
+        record_delivered("a.onion");
+        record_failure(
+            "b.onion",
+            DeliveryFailure::Timeout {
+                stage: DeliveryStage::Pong,
+            },
+        );
+        let events: serde_json::Value = serde_json::from_str(&drain_events_json()).unwrap();
+        assert_eq!(events[0]["failure"], serde_json::Value::Null);
+        assert_eq!(events[1]["failure"]["kind"], "timeout");
+        assert_eq!(events[1]["failure"]["stage"], "pong");
+        assert_eq!(events[1]["retryable"], true);
+        assert!(drain_events().is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+//! Inbound Dispatcher
+//!
+//! The hidden-service listener used to be assembled from Java: each of
+//! `startHiddenServiceListener`, `startTapListener`, `startPongListener` and
+//! `startAckListener` (ports 9150–9153, all forwarded to the local listener)
+//! started it again and installed some of the per-type routing channels, so
+//! which traffic got through depended on which calls had been made and in
+//! what order. Once [`start`]ed, the dispatcher owns that job instead: the
+//! listener hands it every admitted wire message, it checks the frame once,
+//! and fans it out by [`InboundKind`] to whoever subscribed.
+//!
+//! Checks, after the listener's framing, padding, silence and PoW gates:
+//! - **Framing:** a known type byte followed by a 32-byte sender key; an
+//!   all-zero key (which makes the X25519 shared secret zero) is rejected.
+//! - **Replay:** pings, pongs, taps and ACKs repeated byte-for-byte within
+//!   the replay window are dropped, since a repeat carries nothing new.
+//!   Messages, call signaling and friend requests are not: senders resend the
+//!   same stored bytes when an ACK goes missing and must be answered again.
+//!
+//! Subscribers get typed [`InboundFrame`]s through channels ([`subscribe`])
+//! or callbacks ([`set_callback`]). Frames are delivered still encrypted;
+//! the per-type decrypt calls own the contact key chains. While the unified
+//! inbound queue (`network::inbox`) is enabled it takes precedence.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use super::inbox::InboundKind;
+use super::tor::TorManager;
+use crate::crypto::replay_cache::{ReplayCache, ReplayCacheConfig};
+use crate::ffi::context::active_context;
+
+/// One checked inbound frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboundFrame {
+    pub kind: InboundKind,
+    pub msg_type: u8,
+    /// Listener connection, for replies (see `PENDING_CONNECTIONS`)
+    pub connection_id: u64,
+    /// Sender's X25519 public key (wire bytes 1..33)
+    pub sender: [u8; 32],
+    /// Wire bytes including the type byte
+    pub wire: Vec<u8>,
+}
+
+/// Why a frame was not delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    UnknownType(u8),
+    TooShort(usize),
+    InvalidSender,
+    Duplicate,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownType(t) => write!(f, "unknown message type 0x{:02x}", t),
+            Self::TooShort(len) => write!(f, "frame too short ({} bytes)", len),
+            Self::InvalidSender => write!(f, "invalid sender key"),
+            Self::Duplicate => write!(f, "duplicate frame"),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DispatchStats {
+    pub delivered: u64,
+    /// Checked frames nobody was subscribed to
+    pub unclaimed: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+}
+
+pub type FrameCallback = Arc<dyn Fn(&InboundFrame) + Send + Sync>;
+
+/// Whether byte-identical repeats of `kind` are dropped
+pub fn drops_duplicates(kind: InboundKind) -> bool {
+    matches!(
+        kind,
+        InboundKind::Ping | InboundKind::Pong | InboundKind::Tap | InboundKind::Ack
+    )
+}
+
+/// Checks and subscriber lists; the active context's instance is behind
+/// [`dispatch`], [`subscribe`] and [`set_callback`].
+pub struct Dispatcher {
+    replay: ReplayCache,
+    channels: HashMap<InboundKind, Vec<mpsc::UnboundedSender<InboundFrame>>>,
+    callbacks: HashMap<InboundKind, FrameCallback>,
+    stats: DispatchStats,
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Dispatcher {
+            replay: ReplayCache::new(ReplayCacheConfig::default())
+                .expect("default config is valid"),
+            channels: HashMap::new(),
+            callbacks: HashMap::new(),
+            stats: DispatchStats::default(),
+        }
+    }
+
+    /// New channel receiving every frame of `kind` from now on
+    pub fn subscribe(&mut self, kind: InboundKind) -> mpsc::UnboundedReceiver<InboundFrame> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.channels.entry(kind).or_default().push(tx);
+        rx
+    }
+
+    /// Install (or with `None`, remove) the callback for `kind`
+    pub fn set_callback(&mut self, kind: InboundKind, callback: Option<FrameCallback>) {
+        match callback {
+            Some(cb) => self.callbacks.insert(kind, cb),
+            None => self.callbacks.remove(&kind),
+        };
+    }
+
+    pub fn stats(&self) -> DispatchStats {
+        self.stats
+    }
+
+    /// Run the checks on a wire message
+    pub fn check(
+        &mut self,
+        connection_id: u64,
+        wire: Vec<u8>,
+        now_secs: u64,
+    ) -> Result<InboundFrame, Rejection> {
+        let result = self.check_frame(connection_id, wire, now_secs);
+        match result {
+            Err(Rejection::Duplicate) => self.stats.duplicates += 1,
+            Err(_) => self.stats.rejected += 1,
+            Ok(_) => {}
+        }
+        result
+    }
+
+    fn check_frame(
+        &mut self,
+        connection_id: u64,
+        wire: Vec<u8>,
+        now_secs: u64,
+    ) -> Result<InboundFrame, Rejection> {
+        let msg_type = *wire.first().ok_or(Rejection::TooShort(0))?;
+        let kind = InboundKind::from_wire_type(msg_type).ok_or(Rejection::UnknownType(msg_type))?;
+        let sender: [u8; 32] = wire
+            .get(1..33)
+            .and_then(|k| k.try_into().ok())
+            .ok_or(Rejection::TooShort(wire.len()))?;
+        if sender == [0u8; 32] {
+            return Err(Rejection::InvalidSender);
+        }
+        if drops_duplicates(kind) && !self.replay.check_and_insert(&wire, now_secs) {
+            return Err(Rejection::Duplicate);
+        }
+        Ok(InboundFrame {
+            kind,
+            msg_type,
+            connection_id,
+            sender,
+            wire,
+        })
+    }
+
+    /// Send `frame` to the channels for its kind, dropping closed ones.
+    /// Returns the kind's callback, to be run by the caller outside any lock.
+    pub fn deliver(&mut self, frame: &InboundFrame) -> Option<FrameCallback> {
+        let mut sent = 0;
+        if let Some(channels) = self.channels.get_mut(&frame.kind) {
+            channels.retain(|tx| tx.send(frame.clone()).is_ok());
+            sent = channels.len();
+        }
+        let callback = self.callbacks.get(&frame.kind).cloned();
+        if sent > 0 || callback.is_some() {
+            self.stats.delivered += 1;
+        } else {
+            self.stats.unclaimed += 1;
+        }
+        callback
+    }
+}
+
+/// Per-context dispatcher (lives in `ffi::context::ProtocolContext`)
+#[derive(Default)]
+pub(crate) struct DispatcherState {
+    running: AtomicBool,
+    dispatcher: Mutex<Dispatcher>,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Start the hidden-service listener on `port` and route what it accepts
+/// here instead of the per-type channels
+pub async fn start(tor_manager: &mut TorManager, port: u16) -> Result<(), Box<dyn Error>> {
+    // The listener's own ping/pong receivers go unused: frames are taken
+    // over before the per-type routing that feeds them.
+    let _ = tor_manager.start_listener(Some(port)).await?;
+    active_context()
+        .dispatcher
+        .running
+        .store(true, Ordering::SeqCst);
+    log::info!("Inbound dispatcher running on port {}", port);
+    Ok(())
+}
+
+/// Hand routing back to the per-type channels (the listener keeps running)
+pub fn stop() {
+    active_context()
+        .dispatcher
+        .running
+        .store(false, Ordering::SeqCst);
+}
+
+pub fn is_running() -> bool {
+    active_context().dispatcher.running.load(Ordering::SeqCst)
+}
+
+pub fn subscribe(kind: InboundKind) -> mpsc::UnboundedReceiver<InboundFrame> {
+    active_context()
+        .dispatcher
+        .dispatcher
+        .lock()
+        .unwrap()
+        .subscribe(kind)
+}
+
+pub fn set_callback(kind: InboundKind, callback: Option<FrameCallback>) {
+    active_context()
+        .dispatcher
+        .dispatcher
+        .lock()
+        .unwrap()
+        .set_callback(kind, callback);
+}
+
+pub fn stats() -> DispatchStats {
+    active_context()
+        .dispatcher
+        .dispatcher
+        .lock()
+        .unwrap()
+        .stats()
+}
+
+/// Check a wire message from the listener and deliver it
+pub fn dispatch(connection_id: u64, wire: Vec<u8>) -> Result<InboundKind, Rejection> {
+    let (frame, callback) = {
+        let ctx = active_context();
+        let mut dispatcher = ctx.dispatcher.dispatcher.lock().unwrap();
+        let frame = dispatcher.check(connection_id, wire, now_secs())?;
+        let callback = dispatcher.deliver(&frame);
+        (frame, callback)
+    };
+    if let Some(callback) = callback {
+        callback(&frame);
+    }
+    Ok(frame.kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tor::{MSG_TYPE_PING, MSG_TYPE_TEXT};
+    use std::sync::atomic::AtomicUsize;
+
+    fn wire(msg_type: u8, fill: u8) -> Vec<u8> {
+        let mut wire = vec![msg_type];
+        wire.extend_from_slice(&[fill; 48]);
+        wire
+    }
+
+    #[test]
+    fn test_frames_fan_out_by_kind() {
+        let mut dispatcher = Dispatcher::new();
+        let mut pings = dispatcher.subscribe(InboundKind::Ping);
+        let mut messages = dispatcher.subscribe(InboundKind::Message);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        dispatcher.set_callback(
+            InboundKind::Message,
+            Some(Arc::new(move |frame: &InboundFrame| {
+                assert_eq!(frame.msg_type, MSG_TYPE_TEXT);
+                counter.fetch_add(1, Ordering::SeqCst);
+            })),
+        );
+
+        let frame = dispatcher.check(7, wire(MSG_TYPE_TEXT, 1), 100).unwrap();
+        assert_eq!(frame.sender, [1; 32]);
+        dispatcher.deliver(&frame).unwrap()(&frame);
+        let frame = dispatcher.check(8, wire(MSG_TYPE_PING, 2), 100).unwrap();
+        assert!(dispatcher.deliver(&frame).is_none());
+
+        assert_eq!(messages.try_recv().unwrap().connection_id, 7);
+        assert_eq!(pings.try_recv().unwrap().kind, InboundKind::Ping);
+        assert!(messages.try_recv().is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Closed subscribers are dropped; nobody left means unclaimed
+        drop(pings);
+        let frame = dispatcher.check(9, wire(MSG_TYPE_PING, 3), 100).unwrap();
+        dispatcher.deliver(&frame);
+        assert_eq!(dispatcher.stats().delivered, 2);
+        assert_eq!(dispatcher.stats().unclaimed, 1);
+    }
+
+    #[test]
+    fn test_replays_and_malformed_frames_rejected() {
+        let mut dispatcher = Dispatcher::new();
+
+        assert!(dispatcher.check(1, wire(MSG_TYPE_PING, 5), 100).is_ok());
+        assert_eq!(
+            dispatcher.check(2, wire(MSG_TYPE_PING, 5), 160),
+            Err(Rejection::Duplicate)
+        );
+        // Resent messages must reach the app again to be re-acknowledged
+        assert!(dispatcher.check(3, wire(MSG_TYPE_TEXT, 5), 100).is_ok());
+        assert!(dispatcher.check(4, wire(MSG_TYPE_TEXT, 5), 100).is_ok());
+
+        assert_eq!(
+            dispatcher.check(5, wire(MSG_TYPE_PING, 0), 100),
+            Err(Rejection::InvalidSender)
+        );
+        assert_eq!(
+            dispatcher.check(6, vec![0xEE, 1, 2], 100),
+            Err(Rejection::UnknownType(0xEE))
+        );
+        assert_eq!(
+            dispatcher.check(7, vec![MSG_TYPE_TEXT, 1, 2], 100),
+            Err(Rejection::TooShort(3))
+        );
+
+        let stats = dispatcher.stats();
+        assert_eq!((stats.duplicates, stats.rejected), (1, 3));
+    }
+}
@@ -0,0 +1,198 @@
+//! Cipher-Suite Downgrade Detection
+//!
+//! Negotiation helpers over signed contact cards (see
+//! `shield_protocol::protocol::ciphersuite`). The initiator picks a suite with
+//! `negotiate_with`. The responder passes the suite the initiator selected to
+//! `check_peer_selection`. Any inconsistency with the peer's card is logged
+//! and queued as an alarm, which the app collects with `take_alarms` and
+//! shows like a safety-number warning. Alarms are also reported as security
+//! events (see `security_events`).
+//!
+//! Cards that predate suite advertisement cannot be checked and pass with a
+//! warning in the log.
+
+use shield_protocol::protocol::ciphersuite::{
+    check_selection, negotiate, CipherSuite, DowngradeError, SUPPORTED_SUITES,
+};
+use shield_protocol::protocol::{ContactCard, ContactId};
+
+use crate::ffi::context::active_context;
+use crate::redact;
+
+/// Alarms kept until the app collects them; the oldest is dropped beyond this.
+pub const MAX_PENDING_ALARMS: usize = 64;
+
+/// An inconsistent suite selection by a contact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DowngradeAlarm {
+    pub contact_id: ContactId,
+    pub error: DowngradeError,
+    pub timestamp: u64,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Suite to select when opening a session with the card's owner.
+/// None for legacy cards or when there is no suite in common.
+pub fn negotiate_with(card: &ContactCard) -> Option<CipherSuite> {
+    negotiate(SUPPORTED_SUITES, &card.advertised_suites()?)
+}
+
+/// Check the suite a contact selected against its signed card.
+/// Raises an alarm and returns the error on any inconsistency.
+pub fn check_peer_selection(
+    contact_id: &ContactId,
+    card: &ContactCard,
+    selected: CipherSuite,
+) -> Result<(), DowngradeError> {
+    let Some(advertised) = card.advertised_suites() else {
+        log::warn!(
+            "Contact {} has a legacy card; cannot check suite {}",
+            redact::id(&contact_id.to_string()),
+            selected.name()
+        );
+        return Ok(());
+    };
+    let result = check_selection(SUPPORTED_SUITES, &advertised, selected);
+    if let Err(error) = result {
+        log::error!(
+            "Cipher-suite alarm for {}: {}",
+            redact::id(&contact_id.to_string()),
+            error
+        );
+        super::security_events::raise(
+            super::security_events::SecurityEventKind::CipherSuiteDowngrade {
+                detail: error.to_string(),
+            },
+            Some(*contact_id),
+        );
+        let ctx = active_context();
+        let mut alarms = ctx.downgrade_alarms.lock().unwrap();
+        alarms.push_back(DowngradeAlarm {
+            contact_id: *contact_id,
+            error,
+            timestamp: now_secs(),
+        });
+        while alarms.len() > MAX_PENDING_ALARMS {
+            alarms.pop_front();
+        }
+    }
+    result
+}
+
+/// Drain pending alarms, oldest first
+pub fn take_alarms() -> Vec<DowngradeAlarm> {
+    active_context()
+        .downgrade_alarms
+        .lock()
+        .unwrap()
+        .drain(..)
+        .collect()
+}
+
+/// JSON object for one alarm
+/// {"contactId":"sl_…","reason":"downgrade"|"not_advertised"|"unsupported"|"no_common_suite",
+///  "selected":"classical"|"hybrid-pq"|null,"expected":…|null,"timestamp":secs}
+pub fn alarm_json(alarm: &DowngradeAlarm) -> serde_json::Value {
+    let (reason, selected, expected) = match alarm.error {
+        DowngradeError::Downgrade { selected, expected } => {
+            ("downgrade", Some(selected), Some(expected))
+        }
+        DowngradeError::NotAdvertised(selected) => ("not_advertised", Some(selected), None),
+        DowngradeError::Unsupported(selected) => ("unsupported", Some(selected), None),
+        DowngradeError::NoCommonSuite => ("no_common_suite", None, None),
+    };
+    serde_json::json!({
+        "contactId": alarm.contact_id.to_string(),
+        "reason": reason,
+        "selected": selected.map(CipherSuite::name),
+        "expected": expected.map(CipherSuite::name),
+        "timestamp": alarm.timestamp,
+    })
+}
+
+/// Drop pending alarms (e.g. on duress wipe)
+pub fn clear() {
+    active_context().downgrade_alarms.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_contact;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Tests share the active context's alarm queue and run one at a time
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn card(suites: &[CipherSuite]) -> ContactCard {
+        let mut card = ContactCard::new(vec![1; 32], "SoL".into(), "peer".into(), None);
+        card.cipher_suites = suites.iter().map(|s| s.id()).collect();
+        card
+    }
+
+    #[test]
+    fn test_suite_missing_from_card_raises_alarm() {
+        let _serial = serial();
+        clear();
+        let peer = test_contact(1);
+        let pq_only = card(&[CipherSuite::HybridPq]);
+
+        assert_eq!(negotiate_with(&pq_only), Some(CipherSuite::HybridPq));
+        assert!(check_peer_selection(&peer, &pq_only, CipherSuite::HybridPq).is_ok());
+        assert!(take_alarms().is_empty());
+
+        // The card never offered classical, so selecting it is an attack
+        assert_eq!(
+            check_peer_selection(&peer, &pq_only, CipherSuite::Classical),
+            Err(DowngradeError::NotAdvertised(CipherSuite::Classical))
+        );
+        let alarms = take_alarms();
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].contact_id, peer);
+        assert_eq!(
+            alarm_json(&alarms[0])["reason"],
+            serde_json::json!("not_advertised")
+        );
+
+        // Legacy cards cannot be checked and pass without an alarm
+        let legacy = card(&[]);
+        assert_eq!(negotiate_with(&legacy), None);
+        assert!(check_peer_selection(&peer, &legacy, CipherSuite::Classical).is_ok());
+        assert!(take_alarms().is_empty());
+    }
+
+    #[test]
+    fn test_take_alarms_drains_and_clear_drops() {
+        let _serial = serial();
+        clear();
+        let full = card(SUPPORTED_SUITES);
+        for seed in 0..=MAX_PENDING_ALARMS as u8 {
+            let downgraded =
+                check_peer_selection(&test_contact(seed), &full, CipherSuite::Classical);
+            assert!(matches!(downgraded, Err(DowngradeError::Downgrade { .. })));
+        }
+
+        // Oldest first, with the one beyond the cap dropped
+        let alarms = take_alarms();
+        assert_eq!(alarms.len(), MAX_PENDING_ALARMS);
+        assert_eq!(alarms[0].contact_id, test_contact(1));
+        assert_eq!(
+            alarms.last().unwrap().contact_id,
+            test_contact(MAX_PENDING_ALARMS as u8)
+        );
+        assert!(take_alarms().is_empty());
+
+        let _ = check_peer_selection(&test_contact(1), &full, CipherSuite::Classical);
+        clear();
+        assert!(take_alarms().is_empty());
+    }
+}
@@ -0,0 +1,828 @@
+//! File Transfer
+//!
+//! Runs `shield_protocol::protocol::file_transfer` for the app, which used to
+//! push whole files through `sendMessageBlob`. Once a transfer is offered or
+//! accepted, Rust drives it: hashing and reading the source file, sealing
+//! every frame as `MSG_TYPE_FILE_TRANSFER` to the contact's hidden service on
+//! the media send lane, writing received chunks in place and checking the
+//! hash at the end. The app offers, accepts or declines, hands every inbound
+//! `MSG_TYPE_FILE_TRANSFER` frame to [`receive`], and follows progress
+//! through [`set_progress_callback`].
+//!
+//! A failed chunk send pauses the transfer. While any outgoing transfer is
+//! unfinished a ticker retransmits timed-out chunks and asks the receivers
+//! of paused transfers where to resume, so a transfer picks up from the last
+//! acknowledged chunk once the contact is reachable again.
+//!
+//! Transfers live in memory, per protocol context, and are forgotten once
+//! finished. After a restart the sender offers the file again; a receiver
+//! that accepts into the same destination continues from the whole chunks
+//! already in it.
+
+use serde::Serialize;
+use shield_protocol::protocol::file_transfer::{
+    FileFrame, FileKeys, FileOffer, FileTransferError, IncomingTransfer, OutgoingTransfer,
+    TransferId, TransferState, DEFAULT_CHUNK_SIZE, DEFAULT_WINDOW, RETRANSMIT_SECS,
+};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+use super::tor::MSG_TYPE_FILE_TRANSFER;
+use crate::ffi::context::active_context;
+use crate::redact;
+
+/// Incoming offers awaiting an answer before new ones are refused
+pub const MAX_PENDING_OFFERS: usize = 32;
+
+/// Ticker period for retransmissions and resume requests
+const TICK_SECS: u64 = 10;
+
+#[derive(Error, Debug)]
+pub enum TransferError {
+    #[error(transparent)]
+    Protocol(#[from] FileTransferError),
+
+    #[error("File I/O failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Unknown file transfer")]
+    UnknownTransfer,
+
+    #[error("Too many pending file offers")]
+    TooManyOffers,
+
+    #[error("File transfer send failed: {0}")]
+    Send(String),
+}
+
+/// Where and how to reach the contact on the other end
+#[derive(Clone)]
+pub struct Peer {
+    pub onion: String,
+    pub our_pubkey: [u8; 32],
+    pub their_pubkey: [u8; 32],
+    pub keys: Arc<FileKeys>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+/// Snapshot passed to the progress callback and returned by [`list`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+    /// Hex transfer ID
+    pub transfer_id: String,
+    pub direction: Direction,
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    /// Acknowledged (outgoing) or stored (incoming) bytes
+    pub bytes_done: u64,
+    pub state: TransferState,
+    pub retransmissions: u32,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&TransferProgress) + Send + Sync>;
+
+enum Side {
+    Outgoing(OutgoingTransfer),
+    Incoming(IncomingTransfer),
+}
+
+struct Transfer {
+    side: Side,
+    peer: Peer,
+    /// Source file, or the destination once accepted
+    path: Option<PathBuf>,
+    /// Last resume request for a paused outgoing transfer
+    last_resume: u64,
+}
+
+impl Transfer {
+    fn offer(&self) -> &FileOffer {
+        match &self.side {
+            Side::Outgoing(t) => t.offer(),
+            Side::Incoming(t) => t.offer(),
+        }
+    }
+
+    fn state(&self) -> TransferState {
+        match &self.side {
+            Side::Outgoing(t) => t.state(),
+            Side::Incoming(t) => t.state(),
+        }
+    }
+
+    fn progress(&self) -> TransferProgress {
+        let offer = self.offer();
+        let (direction, chunks, retransmissions) = match &self.side {
+            Side::Outgoing(t) => (Direction::Outgoing, t.acked_chunks(), t.retransmissions()),
+            Side::Incoming(t) => (Direction::Incoming, t.stored_chunks(), 0),
+        };
+        TransferProgress {
+            transfer_id: hex::encode(offer.transfer_id),
+            direction,
+            name: offer.name.clone(),
+            mime: offer.mime.clone(),
+            size: offer.size,
+            bytes_done: offer.bytes_in(chunks),
+            state: self.state(),
+            retransmissions,
+        }
+    }
+}
+
+/// Per-context transfers (lives in `ffi::context::ProtocolContext`); tasks
+/// keep their own reference so they finish against the context they
+/// started in
+#[derive(Default)]
+pub(crate) struct FileTransferState {
+    transfers: Mutex<HashMap<TransferId, Transfer>>,
+    callback: Mutex<Option<ProgressCallback>>,
+    ticker_running: AtomicBool,
+}
+
+impl FileTransferState {
+    fn has_unfinished_outgoing(&self) -> bool {
+        self.transfers
+            .lock()
+            .unwrap()
+            .values()
+            .any(|t| matches!(t.side, Side::Outgoing(_)) && !t.state().is_finished())
+    }
+}
+
+fn active_state() -> Arc<FileTransferState> {
+    active_context().file_transfers.clone()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Called on every state change and acknowledged window
+pub fn set_progress_callback(callback: Option<ProgressCallback>) {
+    *active_state().callback.lock().unwrap() = callback;
+}
+
+pub fn progress(id: &TransferId) -> Option<TransferProgress> {
+    active_state()
+        .transfers
+        .lock()
+        .unwrap()
+        .get(id)
+        .map(Transfer::progress)
+}
+
+/// Unfinished transfers in both directions
+pub fn list() -> Vec<TransferProgress> {
+    active_state()
+        .transfers
+        .lock()
+        .unwrap()
+        .values()
+        .map(Transfer::progress)
+        .collect()
+}
+
+/// Report `id` to the callback; a finished transfer is reported one last
+/// time and forgotten, deleting the partial file of a failed download
+fn report(state: &FileTransferState, id: &TransferId) {
+    let progress = {
+        let mut transfers = state.transfers.lock().unwrap();
+        let Some(transfer) = transfers.get(id) else {
+            return;
+        };
+        let progress = transfer.progress();
+        if progress.state.is_finished() {
+            let transfer = transfers.remove(id).expect("present");
+            if let (Side::Incoming(_), Some(path)) = (&transfer.side, &transfer.path) {
+                if progress.state != TransferState::Completed {
+                    let _ = fs::remove_file(path);
+                }
+            }
+            log::info!(
+                "File transfer {} finished: {:?}",
+                redact::key(id),
+                progress.state
+            );
+        }
+        progress
+    };
+    let callback = state.callback.lock().unwrap().clone();
+    if let Some(callback) = callback {
+        callback(&progress);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// File access
+// ---------------------------------------------------------------------------
+
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(*hasher.finalize().as_bytes())
+}
+
+async fn hash_file_blocking(path: PathBuf) -> io::Result<[u8; 32]> {
+    tokio::task::spawn_blocking(move || hash_file(&path))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn read_chunk(path: &Path, offer: &FileOffer, index: u32) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offer.offset(index)))?;
+    let mut data = vec![0u8; offer.chunk_len(index)];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn write_chunk(path: &Path, offset: u64, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(data)
+}
+
+/// Whole chunks of `offer` already in a destination file of `len` bytes
+fn resume_point(offer: &FileOffer, len: u64) -> u32 {
+    if len >= offer.size {
+        offer.chunk_count()
+    } else {
+        (len / u64::from(offer.chunk_size)) as u32
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sending
+// ---------------------------------------------------------------------------
+
+async fn send_frame(peer: &Peer, frame: &FileFrame) -> Result<(), TransferError> {
+    let sealed = frame.seal(&peer.keys, &mut rand::rngs::OsRng)?;
+    let mut wire = Vec::with_capacity(1 + 32 + sealed.len());
+    wire.push(MSG_TYPE_FILE_TRANSFER);
+    wire.extend_from_slice(&peer.our_pubkey);
+    wire.extend_from_slice(&sealed);
+
+    let ctx = active_context();
+    let _permit = ctx
+        .send_permits
+        .acquire(
+            super::lane_for_msg_type(MSG_TYPE_FILE_TRANSFER),
+            &peer.onion,
+            wire.len(),
+        )
+        .await;
+    let sent = tokio::time::timeout(super::timeout_policy().blob_send, async {
+        let mut conn = super::tor::connect_to_onion(&peer.onion, super::ports().messaging)
+            .await
+            .map_err(|e| e.to_string())?;
+        conn.send(&wire).await.map_err(|e| e.to_string())
+    })
+    .await;
+    match sent {
+        Ok(result) => result.map_err(TransferError::Send),
+        Err(_) => Err(TransferError::Send("timed out".into())),
+    }
+}
+
+/// Send a control frame in the background; lost ones are covered by
+/// retransmission and resume
+fn spawn_send(peer: Peer, frame: FileFrame) {
+    tokio::spawn(async move {
+        if let Err(e) = send_frame(&peer, &frame).await {
+            log::warn!(
+                "File transfer {} frame to {} not sent: {}",
+                redact::key(frame.transfer_id()),
+                redact::onion(&peer.onion),
+                e
+            );
+        }
+    });
+}
+
+/// Send every chunk the window allows; pauses the transfer if any fails
+async fn pump(state: Arc<FileTransferState>, id: TransferId) {
+    let (peer, path, offer, chunks) = {
+        let mut transfers = state.transfers.lock().unwrap();
+        let Some(Transfer {
+            side: Side::Outgoing(transfer),
+            peer,
+            path: Some(path),
+            ..
+        }) = transfers.get_mut(&id)
+        else {
+            return;
+        };
+        let chunks = transfer.next_chunks(now_secs());
+        (peer.clone(), path.clone(), transfer.offer().clone(), chunks)
+    };
+    if chunks.is_empty() {
+        return;
+    }
+
+    let mut sends = tokio::task::JoinSet::new();
+    for index in chunks {
+        let data = match read_chunk(&path, &offer, index) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!(
+                    "File transfer {} source unreadable: {}",
+                    redact::key(&id),
+                    e
+                );
+                cancel_locally(&state, &id);
+                return;
+            }
+        };
+        let peer = peer.clone();
+        sends.spawn(async move {
+            let frame = FileFrame::Chunk {
+                transfer_id: id,
+                index,
+                data,
+            };
+            send_frame(&peer, &frame).await
+        });
+    }
+    let mut failed = None;
+    while let Some(result) = sends.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => failed = Some(e.to_string()),
+            Err(e) => failed = Some(e.to_string()),
+        }
+    }
+    if let Some(e) = failed {
+        log::warn!(
+            "File transfer {} paused, chunk send failed: {}",
+            redact::key(&id),
+            e
+        );
+        if let Some(Transfer {
+            side: Side::Outgoing(transfer),
+            last_resume,
+            ..
+        }) = state.transfers.lock().unwrap().get_mut(&id)
+        {
+            transfer.pause();
+            *last_resume = now_secs();
+        }
+        report(&state, &id);
+    }
+}
+
+/// Cancel `id` and tell the peer; returns false if it was already gone
+fn cancel_locally(state: &FileTransferState, id: &TransferId) -> bool {
+    let cancelled = {
+        let mut transfers = state.transfers.lock().unwrap();
+        transfers.get_mut(id).map(|t| {
+            let frame = match &mut t.side {
+                Side::Outgoing(transfer) => transfer.cancel(),
+                Side::Incoming(transfer) => transfer.cancel(),
+            };
+            (t.peer.clone(), frame)
+        })
+    };
+    let Some((peer, frame)) = cancelled else {
+        return false;
+    };
+    spawn_send(peer, frame);
+    report(state, id);
+    true
+}
+
+/// Retransmit timed-out chunks and ask paused transfers to resume
+fn tick(state: &Arc<FileTransferState>, now: u64) {
+    let mut pumps = Vec::new();
+    let mut resumes = Vec::new();
+    for (id, transfer) in state.transfers.lock().unwrap().iter_mut() {
+        let Side::Outgoing(outgoing) = &transfer.side else {
+            continue;
+        };
+        match outgoing.state() {
+            TransferState::Active => pumps.push(*id),
+            TransferState::Paused
+                if now.saturating_sub(transfer.last_resume) >= RETRANSMIT_SECS =>
+            {
+                transfer.last_resume = now;
+                resumes.push((transfer.peer.clone(), outgoing.resume_frame()));
+            }
+            _ => {}
+        }
+    }
+    for id in pumps {
+        tokio::spawn(pump(state.clone(), id));
+    }
+    for (peer, frame) in resumes {
+        spawn_send(peer, frame);
+    }
+}
+
+fn ensure_ticker(state: &Arc<FileTransferState>) {
+    if state.ticker_running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    // Ends with the context that owns the transfers
+    let state = Arc::downgrade(state);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(TICK_SECS)).await;
+            let Some(state) = state.upgrade() else {
+                break;
+            };
+            if state.has_unfinished_outgoing() {
+                tick(&state, now_secs());
+                continue;
+            }
+            state.ticker_running.store(false, Ordering::SeqCst);
+            // An offer made between the check and the store found the flag set
+            if !state.has_unfinished_outgoing() || state.ticker_running.swap(true, Ordering::SeqCst)
+            {
+                break;
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// API
+// ---------------------------------------------------------------------------
+
+/// Offer the file at `path` to `peer`; returns the transfer ID once the
+/// offer is sent. Chunks follow when the peer accepts.
+pub async fn offer(peer: Peer, path: PathBuf, mime: &str) -> Result<TransferId, TransferError> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let size = fs::metadata(&path)?.len();
+    let hash = hash_file_blocking(path.clone()).await?;
+    let transfer = OutgoingTransfer::new(FileOffer {
+        transfer_id: rand::random(),
+        name,
+        mime: mime.to_string(),
+        size,
+        chunk_size: DEFAULT_CHUNK_SIZE,
+        hash,
+    })?;
+    let id = transfer.offer().transfer_id;
+    let frame = transfer.offer_frame();
+
+    let chunks = transfer.offer().chunk_count();
+
+    // Registered first so an early Accept finds it
+    let state = active_state();
+    state.transfers.lock().unwrap().insert(
+        id,
+        Transfer {
+            side: Side::Outgoing(transfer),
+            peer: peer.clone(),
+            path: Some(path),
+            last_resume: 0,
+        },
+    );
+    if let Err(e) = send_frame(&peer, &frame).await {
+        state.transfers.lock().unwrap().remove(&id);
+        return Err(e);
+    }
+    log::info!(
+        "File transfer {} offered to {}: {} bytes in {} chunks",
+        redact::key(&id),
+        redact::onion(&peer.onion),
+        size,
+        chunks
+    );
+    ensure_ticker(&state);
+    report(&state, &id);
+    Ok(id)
+}
+
+/// Handle a `MSG_TYPE_FILE_TRANSFER` frame (without type byte and sender
+/// key) from `peer`. Returns a new offer for the app to accept or decline;
+/// everything else is handled here and reported through the callback.
+pub fn receive(peer: Peer, sealed: &[u8]) -> Result<Option<FileOffer>, TransferError> {
+    let frame = FileFrame::open(sealed, &peer.keys)?;
+    let id = *frame.transfer_id();
+    let state = active_state();
+    if let FileFrame::Offer(offer) = frame {
+        return on_offer(&state, peer, offer);
+    }
+
+    let mut verify = false;
+    let mut reply = None;
+    let mut pump_now = false;
+    let mut write_failed = None;
+    {
+        let mut transfers = state.transfers.lock().unwrap();
+        let transfer = transfers
+            .get_mut(&id)
+            .filter(|t| t.peer.their_pubkey == peer.their_pubkey)
+            .ok_or(TransferError::UnknownTransfer)?;
+        match (&mut transfer.side, frame) {
+            (Side::Outgoing(outgoing), frame) => {
+                pump_now = outgoing.on_frame(&frame)?;
+            }
+            (Side::Incoming(incoming), FileFrame::Resume { .. }) => {
+                reply = Some(incoming.on_resume()?);
+            }
+            (Side::Incoming(incoming), FileFrame::Cancel { .. }) => incoming.on_cancel(),
+            (Side::Incoming(incoming), FileFrame::Chunk { index, data, .. }) => {
+                let receipt = incoming.on_chunk(index, data.len())?;
+                if let (Some(offset), Some(path)) = (receipt.store_at, &transfer.path) {
+                    write_failed = write_chunk(path, offset, &data).err();
+                }
+                // The final ack is superseded by Complete
+                verify = receipt.complete;
+                if !verify {
+                    reply = receipt.ack;
+                }
+            }
+            (Side::Incoming(incoming), _) => {
+                return Err(FileTransferError::UnexpectedFrame(incoming.state()).into());
+            }
+        }
+        if let Some(frame) = reply {
+            spawn_send(transfer.peer.clone(), frame);
+        }
+    }
+
+    if let Some(e) = write_failed {
+        cancel_locally(&state, &id);
+        return Err(e.into());
+    }
+    if verify {
+        tokio::spawn(verify_download(state.clone(), id));
+    }
+    if pump_now {
+        tokio::spawn(pump(state.clone(), id));
+    }
+    report(&state, &id);
+    Ok(None)
+}
+
+fn on_offer(
+    state: &FileTransferState,
+    peer: Peer,
+    offer: FileOffer,
+) -> Result<Option<FileOffer>, TransferError> {
+    let id = offer.transfer_id;
+    let mut transfers = state.transfers.lock().unwrap();
+    if let Some(existing) = transfers.get_mut(&id) {
+        if existing.peer.their_pubkey != peer.their_pubkey {
+            return Err(TransferError::UnknownTransfer);
+        }
+        // A repeated offer for a download in progress is a resume
+        if let Side::Incoming(incoming) = &mut existing.side {
+            if incoming.state() == TransferState::Active {
+                spawn_send(existing.peer.clone(), incoming.on_resume()?);
+            }
+        }
+        return Ok(None);
+    }
+    let pending = transfers
+        .values()
+        .filter(|t| matches!(t.side, Side::Incoming(_)) && t.state() == TransferState::Offered)
+        .count();
+    if pending >= MAX_PENDING_OFFERS {
+        return Err(TransferError::TooManyOffers);
+    }
+    let incoming = IncomingTransfer::new(offer.clone())?;
+    log::info!(
+        "File transfer {} offered by {}: {} bytes",
+        redact::key(&id),
+        redact::onion(&peer.onion),
+        offer.size
+    );
+    transfers.insert(
+        id,
+        Transfer {
+            side: Side::Incoming(incoming),
+            peer,
+            path: None,
+            last_resume: 0,
+        },
+    );
+    drop(transfers);
+    report(state, &id);
+    Ok(Some(offer))
+}
+
+/// Check a fully received file against the offered hash and tell the sender
+async fn verify_download(state: Arc<FileTransferState>, id: TransferId) {
+    let Some((path, expected)) = state
+        .transfers
+        .lock()
+        .unwrap()
+        .get(&id)
+        .and_then(|t| Some((t.path.clone()?, t.offer().hash)))
+    else {
+        return;
+    };
+    let hash_ok = match hash_file_blocking(path).await {
+        Ok(hash) => hash == expected,
+        Err(e) => {
+            log::error!("File transfer {} not verified: {}", redact::key(&id), e);
+            false
+        }
+    };
+    if !hash_ok {
+        log::warn!("File transfer {} failed its hash check", redact::key(&id));
+    }
+    let done = match state.transfers.lock().unwrap().get_mut(&id) {
+        Some(Transfer {
+            side: Side::Incoming(incoming),
+            peer,
+            ..
+        }) => Some((peer.clone(), incoming.finish(hash_ok))),
+        _ => None,
+    };
+    if let Some((peer, frame)) = done {
+        spawn_send(peer, frame);
+        report(&state, &id);
+    }
+}
+
+/// Accept an incoming offer into `dest`, continuing after any whole chunks
+/// a previous attempt left there
+pub fn accept(id: &TransferId, dest: PathBuf) -> Result<(), TransferError> {
+    let state = active_state();
+    let (peer, frame, complete) = {
+        let mut transfers = state.transfers.lock().unwrap();
+        let transfer = transfers
+            .get_mut(id)
+            .ok_or(TransferError::UnknownTransfer)?;
+        let Side::Incoming(incoming) = &mut transfer.side else {
+            return Err(TransferError::UnknownTransfer);
+        };
+        if incoming.state() != TransferState::Offered {
+            return Err(FileTransferError::UnexpectedFrame(incoming.state()).into());
+        }
+        let offer = incoming.offer().clone();
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&dest)?;
+        let stored = resume_point(&offer, file.metadata()?.len());
+        file.set_len(offer.bytes_in(stored))?;
+        if stored > 0 {
+            log::info!(
+                "File transfer {} resuming at chunk {}/{}",
+                redact::key(id),
+                stored,
+                offer.chunk_count()
+            );
+        }
+        let frame = incoming.accept(stored, DEFAULT_WINDOW);
+        transfer.path = Some(dest);
+        (transfer.peer.clone(), frame, stored == offer.chunk_count())
+    };
+    spawn_send(peer, frame);
+    if complete {
+        tokio::spawn(verify_download(state.clone(), *id));
+    }
+    report(&state, id);
+    Ok(())
+}
+
+pub fn decline(id: &TransferId) -> Result<(), TransferError> {
+    let state = active_state();
+    let (peer, frame) = {
+        let mut transfers = state.transfers.lock().unwrap();
+        match transfers.get_mut(id) {
+            Some(Transfer {
+                side: Side::Incoming(incoming),
+                peer,
+                ..
+            }) if incoming.state() == TransferState::Offered => (peer.clone(), incoming.decline()),
+            _ => return Err(TransferError::UnknownTransfer),
+        }
+    };
+    spawn_send(peer, frame);
+    report(&state, id);
+    Ok(())
+}
+
+/// Abandon a transfer in either direction
+pub fn cancel(id: &TransferId) -> Result<(), TransferError> {
+    if cancel_locally(&active_state(), id) {
+        Ok(())
+    } else {
+        Err(TransferError::UnknownTransfer)
+    }
+}
+
+/// Ask the receiver of a paused upload to resume now instead of at the
+/// next tick
+pub fn resume(id: &TransferId) -> Result<(), TransferError> {
+    let state = active_state();
+    let (peer, frame) = {
+        let mut transfers = state.transfers.lock().unwrap();
+        match transfers.get_mut(id) {
+            Some(Transfer {
+                side: Side::Outgoing(outgoing),
+                peer,
+                last_resume,
+                ..
+            }) if outgoing.state() == TransferState::Paused => {
+                *last_resume = now_secs();
+                (peer.clone(), outgoing.resume_frame())
+            }
+            _ => return Err(TransferError::UnknownTransfer),
+        }
+    };
+    spawn_send(peer, frame);
+    Ok(())
+}
+
+/// Forget every transfer and delete unfinished downloads (panic wipe);
+/// returns how many transfers there were
+pub fn purge() -> io::Result<usize> {
+    let drained: Vec<Transfer> = active_state()
+        .transfers
+        .lock()
+        .unwrap()
+        .drain()
+        .map(|(_, t)| t)
+        .collect();
+    let mut result = Ok(drained.len());
+    for transfer in &drained {
+        if let (Side::Incoming(_), Some(path)) = (&transfer.side, &transfer.path) {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => result = Err(e),
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(size: u64, chunk_size: u32) -> FileOffer {
+        FileOffer {
+            transfer_id: [3; 16],
+            name: "notes.txt".into(),
+            mime: "text/plain".into(),
+            size,
+            chunk_size,
+            hash: [0; 32],
+        }
+    }
+
+    #[test]
+    fn test_resume_point_counts_whole_chunks() {
+        let offer = offer(1000, 300);
+        assert_eq!(resume_point(&offer, 0), 0);
+        assert_eq!(resume_point(&offer, 299), 0);
+        assert_eq!(resume_point(&offer, 650), 2);
+        // The short last chunk only counts once the file is complete
+        assert_eq!(resume_point(&offer, 999), 3);
+        assert_eq!(resume_point(&offer, 1000), 4);
+        assert_eq!(resume_point(&offer, 4096), 4);
+    }
+
+    #[test]
+    fn test_chunks_reassemble_out_of_order() {
+        let dir = std::env::temp_dir();
+        let source = dir.join(format!("ft-src-{}.bin", std::process::id()));
+        let dest = dir.join(format!("ft-dst-{}.bin", std::process::id()));
+        let content: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        fs::write(&source, &content).unwrap();
+        let offer = FileOffer {
+            hash: hash_file(&source).unwrap(),
+            ..offer(1000, 300)
+        };
+
+        File::create(&dest).unwrap();
+        for index in [3, 1, 0, 2] {
+            let data = read_chunk(&source, &offer, index).unwrap();
+            assert_eq!(data.len(), offer.chunk_len(index));
+            write_chunk(&dest, offer.offset(index), &data).unwrap();
+        }
+        assert_eq!(fs::read(&dest).unwrap(), content);
+        assert_eq!(hash_file(&dest).unwrap(), offer.hash);
+
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(&dest).unwrap();
+    }
+}
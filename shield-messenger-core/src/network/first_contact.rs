@@ -0,0 +1,281 @@
+//! First-Contact Proof-of-Work Gate
+//!
+//! The active protocol context's wrapper around
+//! `shield_protocol::protocol::pow_stamp`, used by the inbound router. Friend requests (0x07) and pings (0x01) from senders
+//! that are not in the trusted set must carry a PoW stamp once the policy is
+//! enabled. Trusted senders (existing contacts, registered from the app) are
+//! exempt. The current difficulty is published to prospective senders on the
+//! contact exchange endpoint as `GET /pow-policy`.
+//!
+//! Disabled by default: with `base_difficulty == 0` every message passes
+//! through untouched, so peers that do not stamp keep working.
+
+use shield_protocol::protocol::pow_stamp::{
+    FirstContactGate, GateDecision, PowAdvert, PowError, PowPolicy,
+};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::ffi::context::active_context;
+
+/// Per-context first-contact gate (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct FirstContactState {
+    gate: Mutex<FirstContactGate>,
+    /// X25519 public keys of contacts exempt from stamps
+    trusted: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl Default for FirstContactState {
+    fn default() -> Self {
+        FirstContactState {
+            gate: Mutex::new(FirstContactGate::new(PowPolicy::default())),
+            trusted: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Replace the stamp policy (called from JNI)
+pub fn set_pow_policy(policy: PowPolicy) {
+    active_context()
+        .first_contact
+        .gate
+        .lock()
+        .unwrap()
+        .set_policy(policy);
+    log::info!(
+        "First-contact PoW policy: base={} max={} surge={}/min",
+        policy.base_difficulty,
+        policy.max_difficulty,
+        policy.surge_threshold_per_min
+    );
+}
+
+/// Current stamp policy
+pub fn pow_policy() -> PowPolicy {
+    *active_context().first_contact.gate.lock().unwrap().policy()
+}
+
+/// Advert to publish to prospective senders
+pub fn current_advert() -> PowAdvert {
+    active_context()
+        .first_contact
+        .gate
+        .lock()
+        .unwrap()
+        .advert(now_secs())
+}
+
+/// Mark a sender's X25519 key as trusted (stamp-exempt)
+pub fn add_trusted_sender(x25519_pubkey: [u8; 32]) {
+    active_context()
+        .first_contact
+        .trusted
+        .lock()
+        .unwrap()
+        .insert(x25519_pubkey);
+}
+
+/// Remove a sender from the trusted set (e.g. contact deleted)
+pub fn remove_trusted_sender(x25519_pubkey: &[u8; 32]) {
+    active_context()
+        .first_contact
+        .trusted
+        .lock()
+        .unwrap()
+        .remove(x25519_pubkey);
+}
+
+/// Drop all trusted senders (e.g. on duress wipe)
+pub fn clear_trusted_senders() {
+    active_context()
+        .first_contact
+        .trusted
+        .lock()
+        .unwrap()
+        .clear();
+}
+
+/// Whether a message type is subject to the first-contact gate
+pub fn is_gated_type(msg_type: u8) -> bool {
+    msg_type == super::tor::MSG_TYPE_FRIEND_REQUEST || msg_type == super::tor::MSG_TYPE_PING
+}
+
+/// Admit an inbound wire message (`[type][sender X25519 32][payload...]`).
+///
+/// Returns the wire message with any stamp trailer removed, or the reason
+/// it was rejected. Non-gated types and a disabled policy pass unchanged.
+pub fn admit(wire: Vec<u8>) -> Result<Vec<u8>, PowError> {
+    let Some(&msg_type) = wire.first() else {
+        return Ok(wire);
+    };
+    if !is_gated_type(msg_type) {
+        return Ok(wire);
+    }
+
+    let ctx = active_context();
+
+    let mut gate = ctx.first_contact.gate.lock().unwrap();
+    if !gate.policy().enabled() {
+        return Ok(wire);
+    }
+
+    let sender = wire.get(1..33).unwrap_or_default();
+    let trusted = <[u8; 32]>::try_from(sender)
+        .map(|k| ctx.first_contact.trusted.lock().unwrap().contains(&k))
+        .unwrap_or(false);
+
+    let payload_len = match gate.check(msg_type, sender, &wire[1..], trusted, now_secs())? {
+        GateDecision::Accept(payload) | GateDecision::Exempt(payload) => payload.len(),
+    };
+    let mut wire = wire;
+    wire.truncate(1 + payload_len);
+    Ok(wire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tor::{MSG_TYPE_FRIEND_REQUEST, MSG_TYPE_PING, MSG_TYPE_TEXT};
+    use shield_protocol::protocol::pow_stamp::{
+        attach_stamp, PowParams, PowStamp, MAX_DIFFICULTY, MAX_VERIFIES_PER_SENDER_PER_MIN,
+        STAMP_TRAILER_LEN,
+    };
+    use std::sync::MutexGuard;
+
+    /// Tests share the active context's gate and run one at a time
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cheap Argon2 params so tests stay fast.
+    fn fast() -> PowParams {
+        PowParams {
+            memory_kib: 8,
+            iterations: 1,
+        }
+    }
+
+    fn enable(params: PowParams) {
+        set_pow_policy(PowPolicy {
+            base_difficulty: 2,
+            max_difficulty: 8,
+            surge_threshold_per_min: 10,
+            params,
+        });
+    }
+
+    fn wire(msg_type: u8, sender: [u8; 32], payload: &[u8], stamp: Option<PowStamp>) -> Vec<u8> {
+        let mut body = sender.to_vec();
+        body.extend_from_slice(payload);
+        let mut wire = vec![msg_type];
+        match stamp {
+            Some(stamp) => wire.extend(attach_stamp(&body, stamp)),
+            None => wire.extend(body),
+        }
+        wire
+    }
+
+    fn minted(msg_type: u8, sender: [u8; 32], payload: &[u8]) -> Vec<u8> {
+        let unstamped = wire(msg_type, sender, payload, None);
+        let stamp =
+            PowStamp::mint(msg_type, &unstamped[1..], 2, now_secs(), &fast(), 10_000).unwrap();
+        wire(msg_type, sender, payload, Some(stamp))
+    }
+
+    /// Claims any difficulty without having solved it
+    fn forged(difficulty: u8) -> PowStamp {
+        PowStamp {
+            difficulty,
+            timestamp: now_secs(),
+            nonce: 0,
+        }
+    }
+
+    #[test]
+    fn test_disabled_gate_and_ungated_types_pass_through() {
+        let _serial = serial();
+        let wire = vec![MSG_TYPE_TEXT, 1, 2, 3];
+        assert_eq!(admit(wire.clone()).unwrap(), wire);
+        // Default policy is disabled.
+        let ping = vec![crate::network::tor::MSG_TYPE_PING; 64];
+        assert_eq!(admit(ping.clone()).unwrap(), ping);
+    }
+
+    #[test]
+    fn test_valid_stamp_trailer_is_stripped() {
+        let _serial = serial();
+        enable(fast());
+        let stamped = minted(MSG_TYPE_FRIEND_REQUEST, [0x11; 32], b"hello");
+        let admitted = admit(stamped.clone());
+        let replayed = admit(stamped.clone());
+        set_pow_policy(PowPolicy::default());
+
+        assert_eq!(
+            admitted.unwrap(),
+            stamped[..stamped.len() - STAMP_TRAILER_LEN]
+        );
+        assert_eq!(replayed, Err(PowError::Replayed));
+    }
+
+    #[test]
+    fn test_trusted_sender_needs_no_stamp() {
+        let _serial = serial();
+        enable(fast());
+        let plain = wire(MSG_TYPE_PING, [0x22; 32], b"ping", None);
+        add_trusted_sender([0x22; 32]);
+        let trusted = admit(plain.clone());
+        remove_trusted_sender(&[0x22; 32]);
+        let stranger = admit(plain.clone());
+        set_pow_policy(PowPolicy::default());
+
+        assert_eq!(trusted.unwrap(), plain);
+        assert_eq!(stranger, Err(PowError::StampRequired(2)));
+    }
+
+    #[test]
+    fn test_stranger_without_valid_stamp_is_rejected() {
+        let _serial = serial();
+        enable(fast());
+        let sender = [0x33; 32];
+        let missing = admit(wire(MSG_TYPE_FRIEND_REQUEST, sender, b"hi", None));
+        // At the top difficulty an unsolved nonce passes by chance 1 in 2^24
+        let unsolved = admit(wire(
+            MSG_TYPE_FRIEND_REQUEST,
+            sender,
+            b"hi",
+            Some(forged(MAX_DIFFICULTY)),
+        ));
+        set_pow_policy(PowPolicy::default());
+
+        assert_eq!(missing, Err(PowError::StampRequired(2)));
+        assert_eq!(unsolved, Err(PowError::InvalidSolution));
+    }
+
+    #[test]
+    fn test_rate_limit_applies_before_argon2() {
+        let _serial = serial();
+        // Argon2 refuses 1 KiB, so every verification that reaches it fails
+        // with InvalidParams; a rejection without that error never ran it.
+        enable(PowParams {
+            memory_kib: 1,
+            iterations: 1,
+        });
+        let sender = [0x44; 32];
+        let results: Vec<_> = (0..=MAX_VERIFIES_PER_SENDER_PER_MIN as u8)
+            .map(|i| admit(wire(MSG_TYPE_PING, sender, &[i], Some(forged(2)))))
+            .collect();
+        set_pow_policy(PowPolicy::default());
+
+        let (verified, limited) = results.split_at(MAX_VERIFIES_PER_SENDER_PER_MIN);
+        assert!(verified.iter().all(|r| *r == Err(PowError::InvalidParams)));
+        assert_eq!(limited, [Err(PowError::RateLimited)]);
+    }
+}
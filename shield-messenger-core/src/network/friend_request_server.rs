@@ -5,6 +5,7 @@ use std::collections::HashMap;
 /// - GET /contact-card — Returns encrypted contact card
 /// - GET /contact-list/{cid} — Returns encrypted contact list (v5 architecture)
 /// - GET /recovery/beacon — Returns whether this device is in recovery mode
+/// - GET /pow-policy — Returns the first-contact proof-of-work advert (JSON)
 /// - POST /recovery/push/{cid} — Accept raw encrypted contact list bytes from a friend
 ///
 /// This endpoint is accessible via the friend request .onion address.
@@ -12,7 +13,6 @@ use std::collections::HashMap;
 /// Actual friend request wire protocol messages (0x07/0x08) go over the messaging .onion.
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 
 /// Recovery state for push-based contact list recovery on new devices.
@@ -128,8 +128,9 @@ impl ContactExchangeEndpoint {
 
     /// Start the contact exchange listener on the specified port
     pub async fn start(&self, port: u16) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let addr = format!("127.0.0.1:{}", port);
-        let listener = TcpListener::bind(&addr).await?;
+        let listen = super::listen::listen_config();
+        let addr = listen.socket_addr(port);
+        let listener = listen.bind(port).await?;
 
         log::info!("Contact exchange endpoint listening on {}", addr);
 
@@ -160,6 +161,12 @@ impl ContactExchangeEndpoint {
                     }
                 };
 
+                // Network silence: close without a reply, like a dead service
+                if crate::network::silence::is_silent() {
+                    drop(socket);
+                    continue;
+                }
+
                 log::debug!("Incoming connection from {}", addr);
 
                 // Clone Arc pointers for this connection
@@ -388,6 +395,24 @@ async fn handle_connection(
             log::debug!("Served recovery beacon (recovering: {})", state.mode);
         }
 
+        ("GET", "/pow-policy") => {
+            // Difficulty senders must stamp friend requests with (0 = no stamp needed)
+            let body = crate::network::first_contact::current_advert()
+                .to_json()
+                .unwrap_or_else(|_| "{}".to_string());
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 Content-Length: {}\r\n\
+                 \r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await?;
+            log::debug!("Served PoW policy: {}", body);
+        }
+
         ("POST", p) if p.starts_with("/recovery/push/") => {
             let pushed_cid = &p[16..]; // Skip "/recovery/push/"
 
@@ -0,0 +1,406 @@
+//! Delivery Estimates and Network Health
+//!
+//! A spinner tells the user nothing about whether a message will arrive in
+//! five seconds or never. Every direct delivery now feeds this module: the
+//! time from connecting to the recipient's onion service to an authenticated
+//! Pong (the handshake RTT), and the outcome recorded in `delivery`. Combined
+//! with our own Tor status, that yields a per-contact [`DeliveryEstimate`]
+//! the app can show as "delivers in ~4 s" or "recipient likely unreachable".
+//!
+//! Reachability is a Beta(1, 1) estimate over recent outcomes, each weighted
+//! by `0.5^(age / OUTCOME_HALF_LIFE_MS)`, so one failure an hour ago counts
+//! for less than a success a minute ago. Failures that say nothing about the
+//! recipient (our own Tor being down) are not counted against it. Latency is
+//! the median and 90th percentile of recent handshakes; the message itself
+//! follows on the already open connection.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+
+use super::delivery::DeliveryFailure;
+use crate::ffi::context::active_context;
+
+/// Handshake RTTs kept per contact
+pub const RTT_SAMPLES: usize = 16;
+
+/// Delivery outcomes kept per contact
+pub const OUTCOME_HISTORY: usize = 32;
+
+/// Age at which an outcome counts half as much as a fresh one
+pub const OUTCOME_HALF_LIFE_MS: u64 = 30 * 60 * 1000;
+
+/// Contacts tracked at once; the least recently active is dropped beyond
+const MAX_TRACKED: usize = 512;
+
+/// Below this much outcome weight there is too little evidence to judge
+const MIN_EVIDENCE: f64 = 0.5;
+
+/// Window of `NetworkHealth`'s recent counters
+const RECENT_WINDOW_MS: u64 = 60 * 60 * 1000;
+
+/// Our own Tor client's state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TorHealth {
+    pub bootstrap_percent: u32,
+    pub circuits_established: bool,
+}
+
+impl TorHealth {
+    /// Read the status maintained by the bootstrap event listener
+    pub fn current() -> Self {
+        Self {
+            bootstrap_percent: super::tor::BOOTSTRAP_STATUS.load(Ordering::SeqCst),
+            circuits_established: super::tor::CIRCUIT_ESTABLISHED.load(Ordering::SeqCst) != 0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.bootstrap_percent >= 100 && self.circuits_established
+    }
+}
+
+/// What the app should tell the user about a contact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    /// Recent deliveries mostly succeeded
+    Likely,
+    /// Mixed or stale evidence
+    Uncertain,
+    /// Recent deliveries mostly failed with the recipient offline
+    LikelyUnreachable,
+    /// Nothing can be delivered until our own Tor is up
+    TorNotReady,
+    /// No recent deliveries to this contact
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryEstimate {
+    /// Recipient .onion address
+    pub recipient: String,
+    pub reachability: Reachability,
+    /// Chance that a delivery attempted now succeeds, 0.0..=1.0
+    pub probability: f64,
+    /// Median handshake time, if any handshake completed recently
+    pub expected_latency_ms: Option<u64>,
+    pub p90_latency_ms: Option<u64>,
+    pub rtt_samples: usize,
+    pub recent_attempts: usize,
+    pub last_delivered_ms: Option<u64>,
+    /// `kind` of the most recent failure (see `DeliveryFailure`)
+    pub last_failure: Option<String>,
+    pub tor: TorHealth,
+}
+
+/// Aggregate view over all tracked contacts
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkHealth {
+    pub tor: TorHealth,
+    pub contacts_tracked: usize,
+    /// Deliveries and failures in the last hour
+    pub recent_delivered: usize,
+    pub recent_failed: usize,
+    /// Median of every contact's latest handshake
+    pub median_handshake_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Outcome {
+    at_ms: u64,
+    delivered: bool,
+    failure_kind: Option<&'static str>,
+}
+
+#[derive(Debug, Default)]
+struct ContactHealth {
+    rtts: VecDeque<u32>,
+    outcomes: VecDeque<Outcome>,
+    last_active_ms: u64,
+}
+
+fn failure_kind(failure: &DeliveryFailure) -> &'static str {
+    match failure {
+        DeliveryFailure::TorUnreachable { .. } => "tor_unreachable",
+        DeliveryFailure::OnionOffline { .. } => "onion_offline",
+        DeliveryFailure::HandshakeRejected { .. } => "handshake_rejected",
+        DeliveryFailure::RecipientDeclined => "recipient_declined",
+        DeliveryFailure::Timeout { .. } => "timeout",
+        DeliveryFailure::ConnectionLost { .. } => "connection_lost",
+    }
+}
+
+/// Value at quantile `q` of `samples`, nearest-rank
+fn quantile(samples: impl Iterator<Item = u32>, q: f64) -> Option<u64> {
+    let mut sorted: Vec<u32> = samples.collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_unstable();
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    Some(u64::from(sorted[rank - 1]))
+}
+
+/// Per-contact delivery history
+#[derive(Debug, Default)]
+pub struct HealthBook {
+    contacts: HashMap<String, ContactHealth>,
+}
+
+impl HealthBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, recipient: &str, now_ms: u64) -> &mut ContactHealth {
+        if !self.contacts.contains_key(recipient) && self.contacts.len() >= MAX_TRACKED {
+            let stalest = self
+                .contacts
+                .iter()
+                .min_by_key(|(_, c)| c.last_active_ms)
+                .map(|(k, _)| k.clone());
+            if let Some(key) = stalest {
+                self.contacts.remove(&key);
+            }
+        }
+        let entry = self.contacts.entry(recipient.to_string()).or_default();
+        entry.last_active_ms = entry.last_active_ms.max(now_ms);
+        entry
+    }
+
+    /// Time from connecting to an authenticated Pong
+    pub fn record_handshake(&mut self, recipient: &str, rtt_ms: u32, now_ms: u64) {
+        let rtts = &mut self.entry(recipient, now_ms).rtts;
+        if rtts.len() == RTT_SAMPLES {
+            rtts.pop_front();
+        }
+        rtts.push_back(rtt_ms);
+    }
+
+    /// Outcome of one delivery attempt; `None` if it was delivered
+    pub fn record_outcome(
+        &mut self,
+        recipient: &str,
+        failure: Option<&DeliveryFailure>,
+        now_ms: u64,
+    ) {
+        let outcomes = &mut self.entry(recipient, now_ms).outcomes;
+        if outcomes.len() == OUTCOME_HISTORY {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(Outcome {
+            at_ms: now_ms,
+            delivered: failure.is_none(),
+            failure_kind: failure.map(failure_kind),
+        });
+    }
+
+    pub fn forget(&mut self, recipient: &str) {
+        self.contacts.remove(recipient);
+    }
+
+    /// Estimate for a delivery to `recipient` attempted at `now_ms`
+    pub fn estimate(&self, recipient: &str, now_ms: u64, tor: TorHealth) -> DeliveryEstimate {
+        let contact = self.contacts.get(recipient);
+        let outcomes = contact.map(|c| &c.outcomes);
+
+        // Weighted successes and total weight; our own Tor failures are not
+        // evidence about the recipient
+        let (mut delivered, mut weight) = (0.0, 0.0);
+        for outcome in outcomes.into_iter().flatten() {
+            if outcome.failure_kind == Some("tor_unreachable") {
+                continue;
+            }
+            let age = now_ms.saturating_sub(outcome.at_ms) as f64;
+            let w = 0.5f64.powf(age / OUTCOME_HALF_LIFE_MS as f64);
+            weight += w;
+            if outcome.delivered {
+                delivered += w;
+            }
+        }
+        let mut probability = (1.0 + delivered) / (2.0 + weight);
+
+        let reachability = if !tor.is_ready() {
+            let circuits = if tor.circuits_established { 1.0 } else { 0.5 };
+            probability *= f64::from(tor.bootstrap_percent.min(100)) / 100.0 * circuits;
+            Reachability::TorNotReady
+        } else if weight < MIN_EVIDENCE {
+            Reachability::Unknown
+        } else if probability >= 0.7 {
+            Reachability::Likely
+        } else if probability <= 0.3 {
+            Reachability::LikelyUnreachable
+        } else {
+            Reachability::Uncertain
+        };
+
+        let rtts = contact.map(|c| c.rtts.iter().copied());
+        DeliveryEstimate {
+            recipient: recipient.to_string(),
+            reachability,
+            probability,
+            expected_latency_ms: rtts.clone().and_then(|r| quantile(r, 0.5)),
+            p90_latency_ms: rtts.and_then(|r| quantile(r, 0.9)),
+            rtt_samples: contact.map_or(0, |c| c.rtts.len()),
+            recent_attempts: outcomes.map_or(0, VecDeque::len),
+            last_delivered_ms: outcomes
+                .and_then(|o| o.iter().filter(|o| o.delivered).map(|o| o.at_ms).max()),
+            last_failure: outcomes
+                .and_then(|o| o.iter().rev().find_map(|o| o.failure_kind))
+                .map(str::to_string),
+            tor,
+        }
+    }
+
+    pub fn network_health(&self, now_ms: u64, tor: TorHealth) -> NetworkHealth {
+        let recent = || {
+            self.contacts
+                .values()
+                .flat_map(|c| c.outcomes.iter())
+                .filter(|o| now_ms.saturating_sub(o.at_ms) <= RECENT_WINDOW_MS)
+        };
+        NetworkHealth {
+            tor,
+            contacts_tracked: self.contacts.len(),
+            recent_delivered: recent().filter(|o| o.delivered).count(),
+            recent_failed: recent().filter(|o| !o.delivered).count(),
+            median_handshake_ms: quantile(
+                self.contacts
+                    .values()
+                    .filter_map(|c| c.rtts.back().copied()),
+                0.5,
+            ),
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Record a completed handshake with `recipient`
+pub fn record_handshake(recipient: &str, rtt_ms: u32) {
+    active_context()
+        .health
+        .lock()
+        .unwrap()
+        .record_handshake(recipient, rtt_ms, now_millis());
+}
+
+/// Record a delivery outcome; called by `delivery::record_*`
+pub fn record_outcome(recipient: &str, failure: Option<&DeliveryFailure>) {
+    active_context()
+        .health
+        .lock()
+        .unwrap()
+        .record_outcome(recipient, failure, now_millis());
+}
+
+pub fn estimate(recipient: &str) -> DeliveryEstimate {
+    active_context()
+        .health
+        .lock()
+        .unwrap()
+        .estimate(recipient, now_millis(), TorHealth::current())
+}
+
+pub fn network_health() -> NetworkHealth {
+    active_context()
+        .health
+        .lock()
+        .unwrap()
+        .network_health(now_millis(), TorHealth::current())
+}
+
+/// Drop a deleted contact's history
+pub fn forget(recipient: &str) {
+    active_context().health.lock().unwrap().forget(recipient);
+}
+
+/// Drop all history
+pub fn clear() {
+    *active_context().health.lock().unwrap() = HealthBook::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READY: TorHealth = TorHealth {
+        bootstrap_percent: 100,
+        circuits_established: true,
+    };
+
+    #[test]
+    fn test_estimate_follows_recent_outcomes() {
+        let mut book = HealthBook::new();
+        let now = 10 * OUTCOME_HALF_LIFE_MS;
+        assert_eq!(
+            book.estimate("a.onion", now, READY).reachability,
+            Reachability::Unknown
+        );
+
+        for (i, rtt) in [3_000, 4_000, 5_000, 20_000].into_iter().enumerate() {
+            book.record_handshake("a.onion", rtt, now);
+            book.record_outcome("a.onion", None, now - i as u64);
+        }
+        let good = book.estimate("a.onion", now, READY);
+        assert_eq!(good.reachability, Reachability::Likely);
+        assert_eq!(good.expected_latency_ms, Some(4_000));
+        assert_eq!(good.p90_latency_ms, Some(20_000));
+        assert_eq!(good.last_failure, None);
+
+        // Old successes fade behind fresh failures
+        let offline = DeliveryFailure::OnionOffline {
+            detail: "Host unreachable".into(),
+        };
+        let later = now + 4 * OUTCOME_HALF_LIFE_MS;
+        for _ in 0..3 {
+            book.record_outcome("a.onion", Some(&offline), later);
+        }
+        let bad = book.estimate("a.onion", later, READY);
+        assert_eq!(bad.reachability, Reachability::LikelyUnreachable);
+        assert_eq!(bad.last_failure.as_deref(), Some("onion_offline"));
+        assert_eq!(bad.last_delivered_ms, Some(now));
+
+        let json = serde_json::to_value(&bad).unwrap();
+        assert_eq!(json["reachability"], "likely_unreachable");
+        assert_eq!(json["tor"]["bootstrapPercent"], 100);
+    }
+
+    #[test]
+    fn test_own_tor_failures_do_not_blame_the_recipient() {
+        let mut book = HealthBook::new();
+        let tor_down = DeliveryFailure::TorUnreachable {
+            detail: "SOCKS proxy unreachable".into(),
+        };
+        book.record_outcome("b.onion", None, 1_000);
+        book.record_outcome("b.onion", None, 1_000);
+        for _ in 0..5 {
+            book.record_outcome("b.onion", Some(&tor_down), 1_000);
+        }
+        let estimate = book.estimate("b.onion", 1_000, READY);
+        assert_eq!(estimate.reachability, Reachability::Likely);
+        assert_eq!(estimate.recent_attempts, 7);
+
+        let bootstrapping = TorHealth {
+            bootstrap_percent: 50,
+            circuits_established: false,
+        };
+        let waiting = book.estimate("b.onion", 1_000, bootstrapping);
+        assert_eq!(waiting.reachability, Reachability::TorNotReady);
+        assert!(waiting.probability < estimate.probability / 2.0);
+
+        let health = book.network_health(1_000, READY);
+        assert_eq!(health.contacts_tracked, 1);
+        assert_eq!((health.recent_delivered, health.recent_failed), (2, 5));
+        assert_eq!(health.median_handshake_ms, None);
+    }
+}
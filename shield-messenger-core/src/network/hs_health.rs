@@ -0,0 +1,457 @@
+//! Hidden Service Publication Health
+//!
+//! When contacts cannot reach us, the cause is usually on our side: the
+//! descriptor never made it to the HSDirs, or it expired and Tor did not
+//! re-upload it after a network change. Nothing in the app noticed, because
+//! our own outbound traffic kept working.
+//!
+//! The monitor started with [`start`] checks the message hidden service
+//! every `interval_secs`. It reads the HS_DESC counters kept by the event
+//! listener, then dials our own .onion on a fresh, isolated circuit. That
+//! dial performs the same descriptor fetch and introduction/rendezvous a
+//! contact would, so a completed connect proves we are reachable. After
+//! `failure_threshold` failed dials in a row, or with no descriptor
+//! uploaded once `publish_grace_secs` have passed, the service is reported
+//! unreachable or unpublished and, with `auto_republish`, re-published.
+//!
+//! [`report`] returns the current [`HsHealthReport`]; [`check_now`] runs a
+//! check without waiting for the interval and [`request_republish`]
+//! re-publishes right away, whatever the status.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use super::health::TorHealth;
+use crate::ffi::context::active_context;
+use crate::redact;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HsHealthPolicy {
+    /// Time between checks
+    pub interval_secs: u64,
+    /// Time a self-dial may take before it counts as failed
+    pub dial_timeout_secs: u64,
+    /// How long a new (or re-published) service may go without an uploaded
+    /// descriptor before it counts as unpublished
+    pub publish_grace_secs: u64,
+    /// Failed self-dials in a row before the service counts as unreachable
+    pub failure_threshold: u32,
+    /// Re-publish automatically when unreachable or unpublished
+    pub auto_republish: bool,
+}
+
+impl HsHealthPolicy {
+    pub const DEFAULT: Self = Self {
+        interval_secs: 10 * 60,
+        dial_timeout_secs: 90,
+        publish_grace_secs: 5 * 60,
+        failure_threshold: 3,
+        auto_republish: true,
+    };
+}
+
+impl Default for HsHealthPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HsHealthStatus {
+    /// No check has run yet
+    Unknown,
+    /// Our own Tor is not bootstrapped; nothing can be checked
+    TorNotReady,
+    /// Descriptor not uploaded yet, still within the grace period
+    Publishing,
+    /// Last self-dial succeeded
+    Reachable,
+    /// Self-dials failing, below the threshold
+    Degraded,
+    /// `failure_threshold` failed self-dials in a row
+    Unreachable,
+    /// No descriptor uploaded after the grace period and we cannot dial ourselves
+    Unpublished,
+}
+
+impl HsHealthStatus {
+    fn needs_republish(self) -> bool {
+        matches!(self, Self::Unreachable | Self::Unpublished)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HsHealthReport {
+    pub status: HsHealthStatus,
+    pub running: bool,
+    /// HS_DESC UPLOADED / FAILED events since the service was (re)created
+    pub descriptor_uploads: u32,
+    pub descriptor_failures: u32,
+    pub consecutive_failures: u32,
+    pub last_check_ms: Option<u64>,
+    pub last_reachable_ms: Option<u64>,
+    /// Time to connect to ourselves on the last successful self-dial
+    pub last_dial_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub republish_count: u32,
+    pub republish_pending: bool,
+}
+
+/// State of the checks so far; the monitor task feeds it, tests drive it directly
+#[derive(Debug)]
+pub struct HsHealth {
+    status: HsHealthStatus,
+    published_at_ms: u64,
+    descriptor_uploads: u32,
+    descriptor_failures: u32,
+    consecutive_failures: u32,
+    last_check_ms: Option<u64>,
+    last_reachable_ms: Option<u64>,
+    last_dial_ms: Option<u64>,
+    last_error: Option<String>,
+    republish_count: u32,
+    republish_requested: bool,
+}
+
+impl HsHealth {
+    /// Health of a service published at `now_ms`
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            status: HsHealthStatus::Unknown,
+            published_at_ms: now_ms,
+            descriptor_uploads: 0,
+            descriptor_failures: 0,
+            consecutive_failures: 0,
+            last_check_ms: None,
+            last_reachable_ms: None,
+            last_dial_ms: None,
+            last_error: None,
+            republish_count: 0,
+            republish_requested: false,
+        }
+    }
+
+    pub fn status(&self) -> HsHealthStatus {
+        self.status
+    }
+
+    pub fn tor_not_ready(&mut self, now_ms: u64) {
+        self.status = HsHealthStatus::TorNotReady;
+        self.last_check_ms = Some(now_ms);
+    }
+
+    /// Record one check: the descriptor counters and the self-dial result
+    /// (connect time or error). Returns true if the service should be
+    /// re-published automatically.
+    pub fn record_check(
+        &mut self,
+        now_ms: u64,
+        policy: &HsHealthPolicy,
+        uploads: u32,
+        failures: u32,
+        dial: Result<u64, String>,
+    ) -> bool {
+        self.descriptor_uploads = uploads;
+        self.descriptor_failures = failures;
+        self.last_check_ms = Some(now_ms);
+
+        let in_grace =
+            now_ms.saturating_sub(self.published_at_ms) < policy.publish_grace_secs * 1000;
+        self.status = match dial {
+            Ok(dial_ms) => {
+                self.consecutive_failures = 0;
+                self.last_reachable_ms = Some(now_ms);
+                self.last_dial_ms = Some(dial_ms);
+                self.last_error = None;
+                HsHealthStatus::Reachable
+            }
+            Err(e) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(e);
+                if uploads == 0 && in_grace {
+                    HsHealthStatus::Publishing
+                } else if uploads == 0 {
+                    HsHealthStatus::Unpublished
+                } else if self.consecutive_failures >= policy.failure_threshold {
+                    HsHealthStatus::Unreachable
+                } else {
+                    HsHealthStatus::Degraded
+                }
+            }
+        };
+
+        // Give a re-published service its grace period before trying again
+        policy.auto_republish && self.status.needs_republish() && !in_grace
+    }
+
+    pub fn request_republish(&mut self) {
+        self.republish_requested = true;
+    }
+
+    pub fn republish_requested(&self) -> bool {
+        self.republish_requested
+    }
+
+    /// The service was re-published at `now_ms`
+    pub fn republished(&mut self, now_ms: u64) {
+        self.published_at_ms = now_ms;
+        self.consecutive_failures = 0;
+        self.republish_count += 1;
+        self.republish_requested = false;
+        self.status = HsHealthStatus::Publishing;
+    }
+
+    pub fn report(&self, running: bool) -> HsHealthReport {
+        HsHealthReport {
+            status: self.status,
+            running,
+            descriptor_uploads: self.descriptor_uploads,
+            descriptor_failures: self.descriptor_failures,
+            consecutive_failures: self.consecutive_failures,
+            last_check_ms: self.last_check_ms,
+            last_reachable_ms: self.last_reachable_ms,
+            last_dial_ms: self.last_dial_ms,
+            last_error: self.last_error.clone(),
+            republish_count: self.republish_count,
+            republish_pending: self.republish_requested,
+        }
+    }
+}
+
+/// Re-creates the message hidden service (blocking; run off the async workers)
+pub type RepublishFn = Arc<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+/// Per-context monitor state (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct HsHealthState {
+    health: Mutex<HsHealth>,
+    monitor: Mutex<Option<JoinHandle<()>>>,
+    wake: Arc<Notify>,
+}
+
+impl Default for HsHealthState {
+    fn default() -> Self {
+        HsHealthState {
+            health: Mutex::new(HsHealth::new(now_millis())),
+            monitor: Mutex::new(None),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl Drop for HsHealthState {
+    fn drop(&mut self) {
+        let monitor = self.monitor.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(task) = monitor.take() {
+            task.abort();
+        }
+    }
+}
+
+impl HsHealthState {
+    fn is_running(&self) -> bool {
+        self.monitor
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
+    fn report(&self) -> HsHealthReport {
+        let running = self.is_running();
+        self.health.lock().unwrap().report(running)
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Connect to our own service on a circuit no other stream uses
+async fn self_dial(onion_address: &str, timeout: Duration) -> Result<u64, String> {
+    let isolation = format!("hs-health-{}", hex::encode(rand::random::<[u8; 8]>()));
+    let port = super::ports::ports().messaging;
+    let started = Instant::now();
+    let dial = super::tor::connect_to_onion_isolated(onion_address, port, &isolation);
+    match tokio::time::timeout(timeout, dial).await {
+        // Dropping the connection closes it; the listener sees an empty stream
+        Ok(Ok(_conn)) => Ok(started.elapsed().as_millis() as u64),
+        Ok(Err(e)) => Err(redact::scrub(&e.to_string()).into_owned()),
+        Err(_) => Err(format!("self-dial timed out after {}s", timeout.as_secs())),
+    }
+}
+
+async fn republish_now(state: &HsHealthState, republish: &RepublishFn) {
+    let republish = republish.clone();
+    match tokio::task::spawn_blocking(move || republish()).await {
+        Ok(Ok(())) => {
+            state.health.lock().unwrap().republished(now_millis());
+            log::info!("Hidden service re-published");
+        }
+        Ok(Err(e)) => log::warn!("Hidden service re-publish failed: {}", redact::scrub(&e)),
+        Err(e) => log::error!("Hidden service re-publish task failed: {}", e),
+    }
+}
+
+async fn run_check(
+    state: &HsHealthState,
+    onion_address: &str,
+    policy: &HsHealthPolicy,
+    republish: &RepublishFn,
+) {
+    if !TorHealth::current().is_ready() {
+        state.health.lock().unwrap().tor_not_ready(now_millis());
+        return;
+    }
+    if state.health.lock().unwrap().republish_requested() {
+        republish_now(state, republish).await;
+        return;
+    }
+
+    let dial = self_dial(onion_address, Duration::from_secs(policy.dial_timeout_secs)).await;
+    let wants_republish = state.health.lock().unwrap().record_check(
+        now_millis(),
+        policy,
+        super::tor::get_hs_desc_upload_count(),
+        super::tor::get_hs_desc_failure_count(),
+        dial,
+    );
+    let report = state.report();
+    log::info!(
+        "HS health: {:?} ({} uploads, {} failures, {} failed dials)",
+        report.status,
+        report.descriptor_uploads,
+        report.descriptor_failures,
+        report.consecutive_failures
+    );
+    if wants_republish {
+        republish_now(state, republish).await;
+    }
+}
+
+/// Start checking `onion_address` (replacing any running monitor). Must be
+/// called from within a Tokio runtime; the first check runs one interval in.
+/// The monitor belongs to the active context and keeps reporting there
+/// until stopped or the context is destroyed.
+pub fn start(onion_address: String, policy: HsHealthPolicy, republish: RepublishFn) {
+    let ctx = active_context();
+    *ctx.hs_health.health.lock().unwrap() = HsHealth::new(now_millis());
+    // Holds the context weakly so destroying it ends the monitor
+    let task_ctx = Arc::downgrade(&ctx);
+    let wake = ctx.hs_health.wake.clone();
+    let task = tokio::spawn(async move {
+        let interval = Duration::from_secs(policy.interval_secs.max(1));
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = wake.notified() => {}
+            }
+            let Some(ctx) = task_ctx.upgrade() else {
+                break;
+            };
+            run_check(&ctx.hs_health, &onion_address, &policy, &republish).await;
+        }
+    });
+
+    let previous = ctx.hs_health.monitor.lock().unwrap().replace(task);
+    if let Some(previous) = previous {
+        previous.abort();
+    }
+    log::info!(
+        "HS health monitor started (every {}s)",
+        policy.interval_secs
+    );
+}
+
+pub fn stop() {
+    let task = active_context().hs_health.monitor.lock().unwrap().take();
+    if let Some(task) = task {
+        task.abort();
+        log::info!("HS health monitor stopped");
+    }
+}
+
+pub fn is_running() -> bool {
+    active_context().hs_health.is_running()
+}
+
+pub fn report() -> HsHealthReport {
+    active_context().hs_health.report()
+}
+
+/// Run a check now instead of waiting for the interval
+pub fn check_now() {
+    active_context().hs_health.wake.notify_one();
+}
+
+/// Re-publish the service now (once Tor is ready), whatever its status
+pub fn request_republish() {
+    let ctx = active_context();
+    ctx.hs_health.health.lock().unwrap().request_republish();
+    ctx.hs_health.wake.notify_one();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: u64 = 60 * 1000;
+
+    #[test]
+    fn test_failed_dials_escalate_to_republish() {
+        let policy = HsHealthPolicy::DEFAULT;
+        let mut health = HsHealth::new(0);
+
+        // Inside the grace period a missing descriptor is just slow publishing
+        assert!(!health.record_check(MIN, &policy, 0, 0, Err("timeout".into())));
+        assert_eq!(health.status(), HsHealthStatus::Publishing);
+
+        assert!(!health.record_check(6 * MIN, &policy, 4, 0, Ok(2500)));
+        assert_eq!(health.status(), HsHealthStatus::Reachable);
+        assert_eq!(health.report(true).last_dial_ms, Some(2500));
+
+        assert!(!health.record_check(16 * MIN, &policy, 4, 1, Err("timeout".into())));
+        assert!(!health.record_check(26 * MIN, &policy, 4, 1, Err("timeout".into())));
+        assert_eq!(health.status(), HsHealthStatus::Degraded);
+        assert!(health.record_check(36 * MIN, &policy, 4, 1, Err("timeout".into())));
+        assert_eq!(health.status(), HsHealthStatus::Unreachable);
+
+        health.republished(37 * MIN);
+        let report = health.report(true);
+        assert_eq!(report.status, HsHealthStatus::Publishing);
+        assert_eq!(
+            (report.republish_count, report.consecutive_failures),
+            (1, 0)
+        );
+        assert_eq!(report.last_reachable_ms, Some(6 * MIN));
+    }
+
+    #[test]
+    fn test_unpublished_and_manual_republish() {
+        let policy = HsHealthPolicy {
+            auto_republish: false,
+            ..HsHealthPolicy::DEFAULT
+        };
+        let mut health = HsHealth::new(0);
+
+        // Past the grace period with no upload, but auto re-publish is off
+        assert!(!health.record_check(10 * MIN, &policy, 0, 6, Err("failed".into())));
+        assert_eq!(health.status(), HsHealthStatus::Unpublished);
+
+        health.request_republish();
+        assert!(health.report(false).republish_pending);
+        health.republished(21 * MIN);
+        assert!(!health.republish_requested());
+
+        // A working dial proves publication even if HS_DESC events were missed
+        assert!(!health.record_check(22 * MIN, &policy, 0, 0, Ok(900)));
+        assert_eq!(health.status(), HsHealthStatus::Reachable);
+    }
+}
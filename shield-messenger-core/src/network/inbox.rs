@@ -0,0 +1,423 @@
+//! Unified Inbound Event Queue
+//!
+//! Inbound traffic used to be handed to the app through one channel per type
+//! (`pollIncomingPing`, `pollIncomingTap`, `pollIncomingPong`,
+//! `pollIncomingAck`, ...). Each poller duplicated the same framing checks,
+//! and anything received while the app was not polling sat in memory until
+//! the process died. Once [`enable`]d, the listener routes every recognised
+//! wire message here instead, as a typed [`InboundEvent`] with a sequence
+//! number:
+//!
+//! - **Cursor-based:** [`read_after`] returns events after a cursor without
+//!   removing them; the app calls [`ack_through`] once it has durably handled
+//!   them. A crash between the two replays the events instead of losing them.
+//! - **Persistent:** an [`InboxStore`] (e.g. [`FileInboxStore`]) sees every
+//!   append and acknowledgement, and its contents are reloaded on [`enable`].
+//!   Payloads are the wire bytes as received, still encrypted.
+//! - **One surface:** [`wait_after`] blocks until events arrive, so a single
+//!   FFI call replaces the per-type pollers.
+//!
+//! While disabled, the legacy per-type channels are used unchanged. Each
+//! protocol context has its own queue; these functions act on the active one.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::ffi::context::active_context;
+
+use super::tor::{
+    MSG_TYPE_ACK_BATCH, MSG_TYPE_CALL_SIGNALING, MSG_TYPE_CRDT_OPS, MSG_TYPE_DELIVERY_CONFIRMATION,
+    MSG_TYPE_FILE_TRANSFER, MSG_TYPE_FRIEND_REQUEST, MSG_TYPE_FRIEND_REQUEST_ACCEPTED,
+    MSG_TYPE_IMAGE, MSG_TYPE_PAYMENT_ACCEPTED, MSG_TYPE_PAYMENT_REQUEST, MSG_TYPE_PAYMENT_SENT,
+    MSG_TYPE_PING, MSG_TYPE_PONG, MSG_TYPE_PRESENCE, MSG_TYPE_PROFILE_UPDATE, MSG_TYPE_REACTION,
+    MSG_TYPE_RECALL, MSG_TYPE_ROUTING_REQUEST, MSG_TYPE_ROUTING_UPDATE, MSG_TYPE_RPC,
+    MSG_TYPE_SYNC_CHUNK, MSG_TYPE_SYNC_REQUEST, MSG_TYPE_TAP, MSG_TYPE_TEXT, MSG_TYPE_TOPIC,
+    MSG_TYPE_VOICE,
+};
+
+/// Unacknowledged events kept before the oldest are dropped.
+pub const MAX_QUEUED_INBOUND: usize = 4096;
+
+/// What arrived, by the channel it used to be delivered on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InboundKind {
+    Ping,
+    Pong,
+    /// Text, voice, image, payment, presence, reaction, RPC, file transfer,
+    /// group sync, ...
+    Message,
+    CallSignaling,
+    Tap,
+    /// Delivery confirmation or ACK batch
+    Ack,
+    FriendRequest,
+}
+
+impl InboundKind {
+    /// Kind for a wire type byte, `None` for types not queued
+    pub fn from_wire_type(msg_type: u8) -> Option<Self> {
+        Some(match msg_type {
+            MSG_TYPE_PING => InboundKind::Ping,
+            MSG_TYPE_PONG => InboundKind::Pong,
+            MSG_TYPE_TEXT
+            | MSG_TYPE_VOICE
+            | MSG_TYPE_IMAGE
+            | MSG_TYPE_PAYMENT_REQUEST
+            | MSG_TYPE_PAYMENT_SENT
+            | MSG_TYPE_PAYMENT_ACCEPTED
+            | MSG_TYPE_PROFILE_UPDATE
+            | MSG_TYPE_PRESENCE
+            | MSG_TYPE_REACTION
+            | MSG_TYPE_RPC
+            | MSG_TYPE_TOPIC
+            | MSG_TYPE_RECALL
+            | MSG_TYPE_FILE_TRANSFER
+            | MSG_TYPE_CRDT_OPS
+            | MSG_TYPE_SYNC_REQUEST
+            | MSG_TYPE_SYNC_CHUNK
+            | MSG_TYPE_ROUTING_UPDATE
+            | MSG_TYPE_ROUTING_REQUEST => InboundKind::Message,
+            MSG_TYPE_CALL_SIGNALING => InboundKind::CallSignaling,
+            MSG_TYPE_TAP => InboundKind::Tap,
+            MSG_TYPE_DELIVERY_CONFIRMATION | MSG_TYPE_ACK_BATCH => InboundKind::Ack,
+            MSG_TYPE_FRIEND_REQUEST | MSG_TYPE_FRIEND_REQUEST_ACCEPTED => {
+                InboundKind::FriendRequest
+            }
+            _ => return None,
+        })
+    }
+
+    /// Whether the sender's connection is kept open for a reply
+    /// (see `PENDING_CONNECTIONS`)
+    pub fn keeps_connection(self) -> bool {
+        matches!(
+            self,
+            InboundKind::Ping | InboundKind::Message | InboundKind::CallSignaling
+        )
+    }
+}
+
+/// One received wire message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboundEvent {
+    /// Increasing sequence number; the consumption cursor
+    pub seq: u64,
+    pub kind: InboundKind,
+    /// Listener connection, for replies; meaningless after a restart
+    pub connection_id: u64,
+    pub received_at_ms: u64,
+    /// Wire bytes including the type byte
+    #[serde(with = "payload_base64")]
+    pub payload: Vec<u8>,
+}
+
+mod payload_base64 {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(d)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Persistence hook for the queue
+pub trait InboxStore: Send {
+    /// Events not yet acknowledged, oldest first
+    fn load(&mut self) -> io::Result<Vec<InboundEvent>>;
+    fn append(&mut self, event: &InboundEvent) -> io::Result<()>;
+    /// Forget events up to and including `seq`
+    fn remove_through(&mut self, seq: u64) -> io::Result<()>;
+}
+
+/// JSON-lines file store. Appends are cheap; acknowledgements rewrite the
+/// file with what is left.
+pub struct FileInboxStore {
+    path: PathBuf,
+}
+
+impl FileInboxStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl InboxStore for FileInboxStore {
+    fn load(&mut self) -> io::Result<Vec<InboundEvent>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut events = Vec::new();
+        for line in io::BufReader::new(file).lines() {
+            // A torn final line from a crash mid-append is skipped
+            match serde_json::from_str(&line?) {
+                Ok(event) => events.push(event),
+                Err(e) => log::warn!("Skipping unreadable inbox entry: {}", e),
+            }
+        }
+        Ok(events)
+    }
+
+    fn append(&mut self, event: &InboundEvent) -> io::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let line = serde_json::to_string(event).map_err(io::Error::other)?;
+        writeln!(file, "{}", line)
+    }
+
+    fn remove_through(&mut self, seq: u64) -> io::Result<()> {
+        let remaining: Vec<InboundEvent> =
+            self.load()?.into_iter().filter(|e| e.seq > seq).collect();
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp)?;
+            for event in &remaining {
+                let line = serde_json::to_string(event).map_err(io::Error::other)?;
+                writeln!(file, "{}", line)?;
+            }
+            file.sync_all()?;
+        }
+        std::fs::rename(tmp, &self.path)
+    }
+}
+
+struct Inbox {
+    events: VecDeque<InboundEvent>,
+    next_seq: u64,
+    store: Option<Box<dyn InboxStore>>,
+}
+
+/// Inbound queue owned by one `ProtocolContext`.
+pub(crate) struct InboxState {
+    enabled: AtomicBool,
+    inbox: Mutex<Inbox>,
+    arrived: Condvar,
+}
+
+impl Default for InboxState {
+    fn default() -> Self {
+        InboxState {
+            enabled: AtomicBool::new(false),
+            inbox: Mutex::new(Inbox {
+                events: VecDeque::new(),
+                next_seq: 1,
+                store: None,
+            }),
+            arrived: Condvar::new(),
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Route inbound traffic to the queue, reloading anything `store` kept
+pub fn enable(store: Option<Box<dyn InboxStore>>) -> io::Result<()> {
+    let ctx = active_context();
+    let mut inbox = ctx.inbox.inbox.lock().unwrap();
+    if let Some(mut store) = store {
+        let persisted = store.load()?;
+        if let Some(last) = persisted.last() {
+            inbox.next_seq = inbox.next_seq.max(last.seq + 1);
+        }
+        // Keep anything queued in memory that the store has not seen
+        let mut merged: VecDeque<InboundEvent> = persisted.into();
+        for event in inbox.events.drain(..) {
+            if merged.back().is_none_or(|last| event.seq > last.seq) {
+                merged.push_back(event);
+            }
+        }
+        inbox.events = merged;
+        inbox.store = Some(store);
+    }
+    ctx.inbox.enabled.store(true, Ordering::SeqCst);
+    log::info!("Inbound queue enabled ({} pending)", inbox.events.len());
+    Ok(())
+}
+
+/// Go back to the per-type channels. Queued events stay readable.
+pub fn disable() {
+    active_context()
+        .inbox
+        .enabled
+        .store(false, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    active_context().inbox.enabled.load(Ordering::SeqCst)
+}
+
+/// Queue a received wire message. Returns its sequence number.
+pub fn publish(kind: InboundKind, connection_id: u64, payload: Vec<u8>) -> u64 {
+    let ctx = active_context();
+    let mut inbox = ctx.inbox.inbox.lock().unwrap();
+    let event = InboundEvent {
+        seq: inbox.next_seq,
+        kind,
+        connection_id,
+        received_at_ms: now_millis(),
+        payload,
+    };
+    inbox.next_seq += 1;
+    if let Some(store) = inbox.store.as_mut() {
+        if let Err(e) = store.append(&event) {
+            log::error!("Inbox store append failed (seq {}): {}", event.seq, e);
+        }
+    }
+    if inbox.events.len() >= MAX_QUEUED_INBOUND {
+        if let Some(dropped) = inbox.events.pop_front() {
+            log::error!(
+                "Inbound queue full, dropping unacknowledged seq {}",
+                dropped.seq
+            );
+        }
+    }
+    let seq = event.seq;
+    inbox.events.push_back(event);
+    ctx.inbox.arrived.notify_all();
+    seq
+}
+
+/// Up to `max` events after `cursor`, oldest first
+pub fn read_after(cursor: u64, max: usize) -> Vec<InboundEvent> {
+    let ctx = active_context();
+    let inbox = ctx.inbox.inbox.lock().unwrap();
+    collect_after(&inbox, cursor, max)
+}
+
+fn collect_after(inbox: &Inbox, cursor: u64, max: usize) -> Vec<InboundEvent> {
+    inbox
+        .events
+        .iter()
+        .filter(|e| e.seq > cursor)
+        .take(max)
+        .cloned()
+        .collect()
+}
+
+/// [`read_after`], waiting up to `timeout` for something to arrive
+pub fn wait_after(cursor: u64, max: usize, timeout: Duration) -> Vec<InboundEvent> {
+    let ctx = active_context();
+    let inbox = ctx.inbox.inbox.lock().unwrap();
+    let (inbox, _) = ctx
+        .inbox
+        .arrived
+        .wait_timeout_while(inbox, timeout, |inbox| {
+            inbox.events.back().is_none_or(|last| last.seq <= cursor)
+        })
+        .unwrap();
+    collect_after(&inbox, cursor, max)
+}
+
+/// The app has handled everything up to and including `seq`
+pub fn ack_through(seq: u64) {
+    let ctx = active_context();
+    let mut inbox = ctx.inbox.inbox.lock().unwrap();
+    while inbox.events.front().is_some_and(|e| e.seq <= seq) {
+        inbox.events.pop_front();
+    }
+    if let Some(store) = inbox.store.as_mut() {
+        if let Err(e) = store.remove_through(seq) {
+            log::error!("Inbox store trim failed (through {}): {}", seq, e);
+        }
+    }
+}
+
+/// [`wait_after`] as JSON for the FFI layers
+pub fn wait_after_json(cursor: u64, max: usize, timeout: Duration) -> String {
+    serde_json::to_string(&wait_after(cursor, max, timeout)).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Drop all queued events, delete everything the store kept and detach it
+/// (panic wipe); returns how many events were queued
+pub fn purge() -> io::Result<usize> {
+    let ctx = active_context();
+    let mut inbox = ctx.inbox.inbox.lock().unwrap();
+    let purged = inbox.events.len();
+    inbox.events.clear();
+    ctx.inbox.enabled.store(false, Ordering::SeqCst);
+    match inbox.store.take() {
+        Some(mut store) => store.remove_through(u64::MAX).map(|_| purged),
+        None => Ok(purged),
+    }
+}
+
+/// Drop all queued events and detach the store (does not touch its data)
+pub fn clear() {
+    let ctx = active_context();
+    let mut inbox = ctx.inbox.inbox.lock().unwrap();
+    inbox.events.clear();
+    inbox.store = None;
+    ctx.inbox.enabled.store(false, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The active context's queue is shared; keep everything touching it in one test
+    #[test]
+    fn test_cursor_consumption_and_file_store() {
+        clear();
+        let path = std::env::temp_dir().join(format!("inbox-test-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        enable(Some(Box::new(FileInboxStore::new(&path)))).unwrap();
+        assert!(is_enabled());
+        let first = publish(InboundKind::Ping, 7, vec![MSG_TYPE_PING, 1, 2]);
+        let second = publish(InboundKind::Tap, 8, vec![MSG_TYPE_TAP]);
+
+        // Reading does not consume
+        assert_eq!(read_after(0, 10).len(), 2);
+        assert_eq!(read_after(first, 10)[0].kind, InboundKind::Tap);
+        ack_through(first);
+        assert_eq!(read_after(0, 10).len(), 1);
+
+        // A restart reloads what was not acknowledged
+        clear();
+        enable(Some(Box::new(FileInboxStore::new(&path)))).unwrap();
+        let events = wait_after(0, 10, Duration::from_millis(1));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].seq, second);
+        assert_eq!(events[0].payload, vec![MSG_TYPE_TAP]);
+        assert!(publish(InboundKind::Ack, 9, vec![MSG_TYPE_ACK_BATCH]) > second);
+
+        clear();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_wire_type_mapping() {
+        assert_eq!(
+            InboundKind::from_wire_type(MSG_TYPE_REACTION),
+            Some(InboundKind::Message)
+        );
+        assert_eq!(
+            InboundKind::from_wire_type(MSG_TYPE_ACK_BATCH),
+            Some(InboundKind::Ack)
+        );
+        assert_eq!(InboundKind::from_wire_type(0xEE), None);
+        assert!(InboundKind::Ping.keeps_connection());
+        assert!(!InboundKind::Pong.keeps_connection());
+    }
+}
@@ -0,0 +1,176 @@
+//! Listener Bind Address
+//!
+//! Every local listener (main message listener, contact exchange endpoint,
+//! voice) sits behind a Tor hidden service: Tor is the only thing that
+//! should ever connect to it. They used to bind `127.0.0.1` at each call
+//! site; `ListenConfig` now holds the address for all of them, and the
+//! hidden-service port mappings forward to the same address, so IPv6-only
+//! hosts can use `::1`.
+//!
+//! A non-loopback address would publish the listener on the LAN (or the
+//! internet) next to the onion service, which deanonymizes the host to
+//! anyone who scans it. That is rejected unless `allow_exposed` is set
+//! explicitly (for setups where Tor runs on another machine or in another
+//! network namespace), and each listener checks the address it actually
+//! bound before accepting connections.
+//!
+//! Set it once at startup via `ShieldConfig::apply` (`[listen]`) or
+//! [`set_listen_config`], before the listeners are started.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::RwLock;
+use thiserror::Error;
+use tokio::net::{TcpListener, TcpSocket};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ListenError {
+    #[error("Listener address {0} is not loopback (set allow_exposed to bind it anyway)")]
+    Exposed(IpAddr),
+}
+
+/// Where local listeners bind; `[listen]` in a `ShieldConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    /// Bind address for every listener (default 127.0.0.1)
+    pub address: IpAddr,
+    /// Permit a non-loopback `address`
+    pub allow_exposed: bool,
+}
+
+impl ListenConfig {
+    pub const DEFAULT: Self = Self {
+        address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        allow_exposed: false,
+    };
+
+    /// Loopback on IPv6 (`::1`)
+    pub const IPV6: Self = Self {
+        address: IpAddr::V6(Ipv6Addr::LOCALHOST),
+        allow_exposed: false,
+    };
+
+    pub fn validate(&self) -> Result<(), ListenError> {
+        self.check(self.address)
+    }
+
+    /// Reject `ip` unless it is loopback or exposure is allowed
+    pub fn check(&self, ip: IpAddr) -> Result<(), ListenError> {
+        if ip.is_loopback() || self.allow_exposed {
+            Ok(())
+        } else {
+            Err(ListenError::Exposed(ip))
+        }
+    }
+
+    pub fn socket_addr(&self, port: u16) -> SocketAddr {
+        SocketAddr::new(self.address, port)
+    }
+
+    /// Target of a hidden-service port mapping (`127.0.0.1:8080`, `[::1]:8080`)
+    pub fn onion_target(&self, port: u16) -> String {
+        self.socket_addr(port).to_string()
+    }
+
+    /// Unbound socket of the right family for `address`
+    pub fn socket(&self) -> io::Result<TcpSocket> {
+        match self.address {
+            IpAddr::V4(_) => TcpSocket::new_v4(),
+            IpAddr::V6(_) => TcpSocket::new_v6(),
+        }
+    }
+
+    /// Bind `port` and confirm the bound address passes [`check`](Self::check)
+    pub async fn bind(&self, port: u16) -> io::Result<TcpListener> {
+        let listener = TcpListener::bind(self.socket_addr(port)).await?;
+        self.check_bound(&listener)?;
+        Ok(listener)
+    }
+
+    /// Refuse to use `listener` if it ended up on a non-loopback address
+    pub fn check_bound(&self, listener: &TcpListener) -> io::Result<()> {
+        let local = listener.local_addr()?;
+        self.check(local.ip())
+            .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))
+    }
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Global bind address (read when a listener starts)
+static LISTEN: RwLock<ListenConfig> = RwLock::new(ListenConfig::DEFAULT);
+
+/// Current bind address
+pub fn listen_config() -> ListenConfig {
+    *LISTEN.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the bind address (rejected if it would expose listeners).
+/// Listeners that are already up stay where they are until restarted.
+pub fn set_listen_config(config: ListenConfig) -> Result<(), ListenError> {
+    config.validate()?;
+    *LISTEN.write().unwrap_or_else(|e| e.into_inner()) = config;
+    log::info!("Listener bind address: {}", config.address);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_loopback_requires_opt_in() {
+        assert!(ListenConfig::DEFAULT.validate().is_ok());
+        assert!(ListenConfig::IPV6.validate().is_ok());
+
+        let lan: IpAddr = "192.168.1.20".parse().unwrap();
+        let any: IpAddr = "::".parse().unwrap();
+        for address in [lan, any] {
+            let config = ListenConfig {
+                address,
+                allow_exposed: false,
+            };
+            assert_eq!(config.validate(), Err(ListenError::Exposed(address)));
+            assert!(ListenConfig {
+                allow_exposed: true,
+                ..config
+            }
+            .validate()
+            .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_onion_targets() {
+        assert_eq!(ListenConfig::DEFAULT.onion_target(8080), "127.0.0.1:8080");
+        assert_eq!(ListenConfig::IPV6.onion_target(8080), "[::1]:8080");
+    }
+
+    #[tokio::test]
+    async fn test_binds_loopback_v4_and_v6() {
+        let listener = ListenConfig::DEFAULT.bind(0).await.unwrap();
+        assert!(listener.local_addr().unwrap().ip().is_loopback());
+
+        // IPv6 may be disabled on the host; only check the address if it binds
+        if let Ok(listener) = ListenConfig::IPV6.bind(0).await {
+            assert_eq!(
+                listener.local_addr().unwrap().ip(),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            );
+        }
+
+        // Bound to every interface without opting in: refused after bind
+        let exposed = ListenConfig {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            allow_exposed: false,
+        };
+        let err = exposed.bind(0).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,99 @@
+//! Message IDs for Direct Chats
+//!
+//! New direct messages take their ID from the shared scheme in
+//! `shield_protocol::protocol::message_id`: the app keeps one
+//! `MessageIdSequence` per contact as an opaque blob in its encrypted
+//! database and calls `issue` before each send. Stored IDs from the old
+//! SHA-256/UUID schemes stay as they are; `normalize` maps them when a fixed
+//! 32-byte key is needed.
+//!
+//! Every incoming and outgoing message also passes through `audit`, which
+//! remembers recent IDs in the active protocol context. Collisions are logged and queued for the app,
+//! which drains them with `take_collisions` (e.g. to flag the sender).
+
+use shield_protocol::protocol::message_id::{
+    CollisionAudit, IdAudit, IdClaim, IdCollision, MessageId, MessageIdError, MessageIdSequence,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::ffi::context::active_context;
+
+/// Recent IDs remembered by the audit
+pub const AUDIT_CAPACITY: usize = 8192;
+
+/// Collisions kept until the app drains them; the oldest are dropped beyond
+const MAX_PENDING_COLLISIONS: usize = 64;
+
+/// Per-context ID audit (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct MessageIdState {
+    audit: Mutex<CollisionAudit>,
+    /// Collisions the audit reported, until the app drains them
+    collisions: Arc<Mutex<Vec<IdCollision>>>,
+}
+
+impl Default for MessageIdState {
+    fn default() -> Self {
+        let collisions = Arc::new(Mutex::new(Vec::new()));
+        let mut audit = CollisionAudit::new(AUDIT_CAPACITY);
+        let pending = Arc::clone(&collisions);
+        audit.on_collision(move |collision| {
+            let mut pending = pending.lock().unwrap();
+            if pending.len() == MAX_PENDING_COLLISIONS {
+                pending.remove(0);
+            }
+            pending.push(*collision);
+        });
+        MessageIdState {
+            audit: Mutex::new(audit),
+            collisions,
+        }
+    }
+}
+
+/// Fresh sequence blob for a new contact (or after restoring a backup)
+pub fn new_sequence(sender_pubkey: [u8; 32]) -> Result<Vec<u8>, MessageIdError> {
+    MessageIdSequence::new(sender_pubkey, &mut rand::rngs::OsRng).to_bytes()
+}
+
+/// Issue the next ID from a sequence blob; returns the ID and the updated
+/// blob, which must be stored before the message is sent
+pub fn issue(sequence: &[u8]) -> Result<(MessageId, Vec<u8>), MessageIdError> {
+    let mut sequence = MessageIdSequence::from_bytes(sequence)?;
+    let (_, id) = sequence.issue()?;
+    Ok((id, sequence.to_bytes()?))
+}
+
+/// Parse an ID, mapping legacy IDs into their own domain
+pub fn normalize(id: &str) -> MessageId {
+    MessageId::normalize(id)
+}
+
+/// Record that `id` was seen for `content` from `sender_pubkey`
+pub fn audit(id: &str, sender_pubkey: [u8; 32], content: &[u8]) -> IdAudit {
+    let claim = IdClaim {
+        sender_pubkey,
+        content_digest: *blake3::hash(content).as_bytes(),
+    };
+    active_context()
+        .message_ids
+        .audit
+        .lock()
+        .unwrap()
+        .observe(normalize(id), claim)
+}
+
+/// Collisions since the last call, oldest first
+pub fn take_collisions() -> Vec<IdCollision> {
+    std::mem::take(&mut *active_context().message_ids.collisions.lock().unwrap())
+}
+
+/// JSON object for one collision:
+/// {"messageId":"..","firstSender":"hex","secondSender":"hex","sameSender":bool}
+pub fn collision_json(collision: &IdCollision) -> serde_json::Value {
+    serde_json::json!({
+        "messageId": collision.id.to_string(),
+        "firstSender": hex::encode(collision.first.sender_pubkey),
+        "secondSender": hex::encode(collision.second.sender_pubkey),
+        "sameSender": collision.first.sender_pubkey == collision.second.sender_pubkey,
+    })
+}
@@ -1,10 +1,42 @@
 pub mod arti;
+#[cfg(feature = "arti")]
+pub mod arti_runtime;
+pub mod bandwidth;
+pub mod contact_backup;
+pub mod delivery;
+pub mod dispatcher;
+pub mod downgrade;
+pub mod file_transfer;
+pub mod first_contact;
 pub mod friend_request_server;
+pub mod health;
+pub mod hs_health;
+pub mod inbox;
+pub mod listen;
+pub mod message_ids;
+pub mod ordering;
 pub mod pingpong;
+pub mod ports;
+pub mod presence;
+pub mod proxy;
+pub mod reactions;
+pub mod recall;
+pub mod receipts;
+pub mod relays;
+pub mod retry_policy;
+pub mod rpc;
+pub mod security_events;
+pub mod send_intents;
+pub mod send_lanes;
+pub mod silence;
 pub mod sleep_mode;
 pub mod socks5_client;
+pub mod topics;
 pub mod tor;
+pub mod tor_control;
 pub mod tor_dos_protection;
+pub mod tor_runtime;
+pub mod wake;
 
 // Re-export transport-layer types from shield-protocol for backward compatibility
 pub use shield_protocol::transport::padding::{
@@ -19,16 +51,40 @@ pub use shield_protocol::transport::padding::{
 pub use shield_protocol::transport::packet::{Packet, PacketType, MAX_PAYLOAD, PACKET_SIZE};
 
 pub use arti::{ArtiConfig, ArtiTorManager, EphemeralOnionService, IsolationToken};
+#[cfg(feature = "arti")]
+pub use arti_runtime::ArtiRuntime;
+pub use bandwidth::BandwidthStats;
+pub use contact_backup::{BackupError, BackupOutcome};
+pub use delivery::{DeliveryFailure, DeliveryStage, OutboxEvent};
+pub use dispatcher::{DispatchStats, InboundFrame};
+pub use file_transfer::{TransferError, TransferProgress};
 pub use friend_request_server::{get_endpoint, ContactExchangeEndpoint};
+pub use hs_health::{HsHealthPolicy, HsHealthReport, HsHealthStatus};
+pub use inbox::{FileInboxStore, InboundEvent, InboundKind, InboxStore};
+pub use listen::{listen_config, set_listen_config, ListenConfig, ListenError};
 pub use pingpong::{
     cleanup_expired_acks, cleanup_expired_pings, cleanup_expired_pongs, get_ping_session,
-    remove_ack_session, remove_ping_session, remove_pong_session, store_ping_session,
-    PingPongManager, PingToken, PongToken,
+    remove_ack_session, remove_ping_session, remove_pong_session, restore_sessions,
+    store_ping_session, FileSessionStore, PingPongManager, PingToken, PongToken, RestoreStats,
+    SessionStore,
 };
+pub use ports::{ports, set_ports, PortConfig, PortError};
+pub use proxy::{ProxyAuth, ProxyConfig, ProxyError};
+pub use retry_policy::{
+    retry_policy, set_retry_policy, set_timeout_policy, timeout_policy, PolicyError, RetryPolicy,
+    TimeoutPolicy,
+};
+pub use send_lanes::{lane_for_msg_type, LanePermit, LanePermits};
 pub use socks5_client::Socks5Client;
 pub use tor::{
     compute_onion_address_from_ed25519_seed, PendingConnection, TorManager, PENDING_CONNECTIONS,
 };
+pub use tor_control::{ControlAddress, ControlAuth, TorControl, TorControlConfig, TorControlError};
 pub use tor_dos_protection::{
     verify_pow_solution_public, ConnectionDecision, DoSStats, HsDoSConfig, HsDoSProtection,
 };
+pub use tor_runtime::{OnionServiceSpec, SystemTor, TorRuntime, TorRuntimeError};
+pub use wake::{
+    complete_wake, initiate_wake, receive_wake, respond_to_wake, CompletedWake, IncomingWake,
+    OutgoingWake, WakeError, WakeIdentity, WakeReply,
+};
@@ -0,0 +1,229 @@
+//! Message Ordering
+//!
+//! The active protocol context's `ConversationOrdering` (see
+//! `shield_protocol::protocol::ordering`) behind the JNI ordering API. For 1:1 messages the app passes the plaintext
+//! through `wrap_outgoing` before encrypting, and every decrypted plaintext
+//! through `receive`. Messages come back in sequence order, together with
+//! explicit gap events for messages that never arrived.
+//!
+//! Outgoing envelopes carry our identity continuity tag once the app has set
+//! our chain head in the key-change guard. `receive` checks the tag against
+//! the head pinned for the sender; a mismatch, or a missing tag from a
+//! contact that sent tags before, drops the message with
+//! `OrderingError::Continuity` and raises a critical security event.
+//!
+//! Messages can carry annotations (`wrap_annotated`); they come back on the
+//! message's deliver or late event. A received message with a critical
+//! annotation the app has not declared through `set_understood_annotations`
+//! is dropped with `OrderingError::Annotation`; its sequence number is later
+//! reported as a gap.
+//!
+//! Held messages wait at most `gap_timeout_ms`. The app should call
+//! `poll_timeouts` at `next_deadline_ms`, or on its regular tick.
+//!
+//! Per-contact counters and held messages exist only in this process. The
+//! app saves the `export_state` blob with its encrypted message store and
+//! passes it to `import_state` at startup, before the first `receive`.
+
+use base64::Engine;
+use shield_protocol::protocol::annotation::Annotations;
+use shield_protocol::protocol::ordering::{
+    ConversationOrdering, OrderingConfig, OrderingError, OrderingEvent, SequencedEnvelope,
+};
+use shield_protocol::protocol::ContactId;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use super::security_events::{self, SecurityEventKind};
+use crate::ffi::context::active_context;
+
+/// Per-context ordering (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct OrderingState {
+    ordering: Mutex<ConversationOrdering>,
+    /// Critical annotation keys the app handles
+    understood: Mutex<HashSet<String>>,
+}
+
+impl Default for OrderingState {
+    fn default() -> Self {
+        OrderingState {
+            ordering: Mutex::new(ConversationOrdering::new(OrderingConfig::default())),
+            understood: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Current ordering configuration
+pub fn config() -> OrderingConfig {
+    *active_context().ordering.ordering.lock().unwrap().config()
+}
+
+/// Replace the ordering configuration
+pub fn set_config(config: OrderingConfig) {
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .set_config(config);
+    log::info!(
+        "Message ordering: window={} gap_timeout={}ms",
+        config.window,
+        config.gap_timeout_ms
+    );
+}
+
+/// Prefix an outgoing plaintext with the conversation's next sequence number
+/// and our continuity tag
+pub fn wrap_outgoing(contact_id: &ContactId, body: &[u8]) -> Vec<u8> {
+    let continuity = active_context().with_key_change_guard(|g| g.local_continuity_tag());
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .wrap_outgoing(contact_id, body, continuity)
+}
+
+/// [`wrap_outgoing`] with annotations for the recipient
+pub fn wrap_annotated(contact_id: &ContactId, body: &[u8], annotations: Annotations) -> Vec<u8> {
+    let continuity = active_context().with_key_change_guard(|g| g.local_continuity_tag());
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .wrap_annotated(contact_id, body, continuity, annotations)
+}
+
+/// Declare the critical annotation keys the app understands, replacing the
+/// previous set
+pub fn set_understood_annotations(keys: impl IntoIterator<Item = String>) {
+    *active_context().ordering.understood.lock().unwrap() = keys.into_iter().collect();
+}
+
+/// Feed a decrypted plaintext; returns the events it releases
+pub fn receive(
+    contact_id: &ContactId,
+    envelope: &[u8],
+) -> Result<Vec<OrderingEvent>, OrderingError> {
+    let envelope = SequencedEnvelope::from_bytes(envelope)?;
+    if let Err(e) = active_context()
+        .with_key_change_guard(|g| g.check_continuity(contact_id, envelope.continuity))
+    {
+        security_events::raise(
+            SecurityEventKind::KeyContinuityViolation {
+                detail: e.to_string(),
+            },
+            Some(*contact_id),
+        );
+        return Err(e.into());
+    }
+    let ctx = active_context();
+    {
+        let understood = ctx.ordering.understood.lock().unwrap();
+        envelope
+            .annotations
+            .check_understood(|key| understood.contains(key))?;
+    }
+    let events =
+        ctx.ordering
+            .ordering
+            .lock()
+            .unwrap()
+            .receive_envelope(contact_id, envelope, now_millis());
+    Ok(events)
+}
+
+/// Give up on gaps that have timed out in any conversation
+pub fn poll_timeouts() -> Vec<(ContactId, OrderingEvent)> {
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .poll(now_millis())
+}
+
+/// Unix time (ms) at which `poll_timeouts` next has work, if any
+pub fn next_deadline_ms() -> Option<u64> {
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .next_deadline()
+}
+
+/// Forget a deleted contact or reset session
+pub fn forget(contact_id: &ContactId) {
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .forget(contact_id);
+}
+
+/// JSON object for one event
+/// deliver/late: {"type":"deliver"|"late","seq":n,"body":"<base64>","annotations":{..}}
+/// gap: {"type":"gap","first":n,"last":n}
+pub fn event_json(event: &OrderingEvent) -> serde_json::Value {
+    match event {
+        OrderingEvent::Deliver {
+            seq,
+            body,
+            annotations,
+        } => serde_json::json!({
+            "type": "deliver",
+            "seq": seq,
+            "body": base64::engine::general_purpose::STANDARD.encode(body),
+            "annotations": annotations,
+        }),
+        OrderingEvent::Late {
+            seq,
+            body,
+            annotations,
+        } => serde_json::json!({
+            "type": "late",
+            "seq": seq,
+            "body": base64::engine::general_purpose::STANDARD.encode(body),
+            "annotations": annotations,
+        }),
+        OrderingEvent::Gap { first, last } => serde_json::json!({
+            "type": "gap",
+            "first": first,
+            "last": last,
+        }),
+    }
+}
+
+/// Serialized state for the app to persist
+pub fn export_state() -> Result<Vec<u8>, OrderingError> {
+    active_context()
+        .ordering
+        .ordering
+        .lock()
+        .unwrap()
+        .to_bytes()
+}
+
+/// Restore state persisted by `export_state`
+pub fn import_state(data: &[u8]) -> Result<(), OrderingError> {
+    let ordering = ConversationOrdering::from_bytes(data)?;
+    *active_context().ordering.ordering.lock().unwrap() = ordering;
+    Ok(())
+}
+
+/// Drop all ordering state (e.g. on duress wipe)
+pub fn clear() {
+    *active_context().ordering.ordering.lock().unwrap() =
+        ConversationOrdering::new(OrderingConfig::default());
+}
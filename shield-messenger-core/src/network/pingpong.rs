@@ -1,8 +1,11 @@
 use super::tor::TorManager;
+use crate::ffi::context::active_context;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
 use std::sync::OnceLock;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -145,7 +148,14 @@ pub fn store_ping_session(ping_id: &str, ping_token: PingToken) {
             .as_secs() as i64,
     };
 
+    let persisted = PersistedSession::Ping {
+        id: ping_id.to_string(),
+        received_at: session.received_at,
+        token: session.ping_token.clone(),
+    };
     sessions_lock.insert(ping_id.to_string(), session);
+    drop(sessions_lock);
+    persist(&persisted);
 }
 
 /// Retrieve a stored Ping token by ping_id
@@ -159,11 +169,14 @@ pub fn get_ping_session(ping_id: &str) -> Option<StoredPingSession> {
 pub fn remove_ping_session(ping_id: &str) {
     let sessions = get_ping_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
-    sessions_lock.remove(ping_id);
+    if sessions_lock.remove(ping_id).is_some() {
+        drop(sessions_lock);
+        forget(&[(SessionKind::Ping, ping_id)]);
+    }
 }
 
-/// Clean up expired Ping sessions (older than 5 minutes)
-pub fn cleanup_expired_pings() {
+/// Clean up expired Ping sessions (see [`SessionKind::ttl_secs`]); returns how many were removed
+pub fn cleanup_expired_pings() -> usize {
     let sessions = get_ping_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
 
@@ -172,12 +185,23 @@ pub fn cleanup_expired_pings() {
         .unwrap()
         .as_secs() as i64;
 
-    const MAX_AGE_SECONDS: i64 = 300; // 5 minutes
-
-    sessions_lock.retain(|_, session| {
+    let mut expired = Vec::new();
+    sessions_lock.retain(|id, session| {
         let age = now - session.received_at;
-        age < MAX_AGE_SECONDS
+        let keep = age < SessionKind::Ping.ttl_secs();
+        if !keep {
+            expired.push(id.clone());
+        }
+        keep
     });
+    drop(sessions_lock);
+
+    let keys: Vec<(SessionKind, &str)> = expired
+        .iter()
+        .map(|id| (SessionKind::Ping, id.as_str()))
+        .collect();
+    forget(&keys);
+    expired.len()
 }
 
 // ==================== GLOBAL PONG SESSION STORAGE ====================
@@ -220,11 +244,18 @@ pub fn store_pong_session(ping_id: &str, pong_token: PongToken) {
             .as_secs() as i64,
     };
 
+    let persisted = PersistedSession::Pong {
+        id: ping_id.to_string(),
+        received_at: session.received_at,
+        token: session.pong_token.clone(),
+    };
     sessions_lock.insert(ping_id.to_string(), session);
     log::info!(
         "Pong stored successfully. Total Pongs in storage: {}",
         sessions_lock.len()
     );
+    drop(sessions_lock);
+    persist(&persisted);
 }
 
 /// Retrieve a stored Pong token by ping_id
@@ -250,11 +281,14 @@ pub fn get_pong_session(ping_id: &str) -> Option<StoredPongSession> {
 pub fn remove_pong_session(ping_id: &str) {
     let sessions = get_pong_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
-    sessions_lock.remove(ping_id);
+    if sessions_lock.remove(ping_id).is_some() {
+        drop(sessions_lock);
+        forget(&[(SessionKind::Pong, ping_id)]);
+    }
 }
 
-/// Clean up expired Pong sessions (older than 5 minutes)
-pub fn cleanup_expired_pongs() {
+/// Clean up expired Pong sessions (see [`SessionKind::ttl_secs`]); returns how many were removed
+pub fn cleanup_expired_pongs() -> usize {
     let sessions = get_pong_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
 
@@ -263,12 +297,23 @@ pub fn cleanup_expired_pongs() {
         .unwrap()
         .as_secs() as i64;
 
-    const MAX_AGE_SECONDS: i64 = 300; // 5 minutes
-
-    sessions_lock.retain(|_, session| {
+    let mut expired = Vec::new();
+    sessions_lock.retain(|id, session| {
         let age = now - session.received_at;
-        age < MAX_AGE_SECONDS
+        let keep = age < SessionKind::Pong.ttl_secs();
+        if !keep {
+            expired.push(id.clone());
+        }
+        keep
     });
+    drop(sessions_lock);
+
+    let keys: Vec<(SessionKind, &str)> = expired
+        .iter()
+        .map(|id| (SessionKind::Pong, id.as_str()))
+        .collect();
+    forget(&keys);
+    expired.len()
 }
 
 // ==================== GLOBAL ACK SESSION STORAGE ====================
@@ -311,11 +356,18 @@ pub fn store_ack_session(item_id: &str, ack_token: DeliveryAck) {
             .as_secs() as i64,
     };
 
+    let persisted = PersistedSession::Ack {
+        id: item_id.to_string(),
+        received_at: session.received_at,
+        token: session.ack_token.clone(),
+    };
     sessions_lock.insert(item_id.to_string(), session);
     log::info!(
         "ACK stored successfully. Total ACKs in storage: {}",
         sessions_lock.len()
     );
+    drop(sessions_lock);
+    persist(&persisted);
 }
 
 /// Retrieve a stored ACK token by item_id
@@ -341,11 +393,14 @@ pub fn get_ack_session(item_id: &str) -> Option<StoredAckSession> {
 pub fn remove_ack_session(item_id: &str) {
     let sessions = get_ack_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
-    sessions_lock.remove(item_id);
+    if sessions_lock.remove(item_id).is_some() {
+        drop(sessions_lock);
+        forget(&[(SessionKind::Ack, item_id)]);
+    }
 }
 
-/// Clean up expired ACK sessions (older than 5 minutes)
-pub fn cleanup_expired_acks() {
+/// Clean up expired ACK sessions (see [`SessionKind::ttl_secs`]); returns how many were removed
+pub fn cleanup_expired_acks() -> usize {
     let sessions = get_ack_sessions();
     let mut sessions_lock = sessions.lock().unwrap();
 
@@ -354,12 +409,387 @@ pub fn cleanup_expired_acks() {
         .unwrap()
         .as_secs() as i64;
 
-    const MAX_AGE_SECONDS: i64 = 300; // 5 minutes
-
-    sessions_lock.retain(|_, session| {
+    let mut expired = Vec::new();
+    sessions_lock.retain(|id, session| {
         let age = now - session.received_at;
-        age < MAX_AGE_SECONDS
+        let keep = age < SessionKind::Ack.ttl_secs();
+        if !keep {
+            expired.push(id.clone());
+        }
+        keep
     });
+    drop(sessions_lock);
+
+    let keys: Vec<(SessionKind, &str)> = expired
+        .iter()
+        .map(|id| (SessionKind::Ack, id.as_str()))
+        .collect();
+    forget(&keys);
+    expired.len()
+}
+
+// ==================== SESSION PERSISTENCE ====================
+//
+// The three session maps above only live as long as the process. Delayed-mode
+// Pongs (the recipient authenticates minutes after the Ping arrived) and
+// pending ACKs are lost when Android kills the app in between. Once a
+// SessionStore is attached with restore_sessions, every store_*/remove_*/
+// cleanup_* call is mirrored to it, and whatever it kept from the previous
+// run is loaded back into the maps. The store belongs to the active
+// protocol context.
+
+/// Which session map an entry belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionKind {
+    Ping,
+    Pong,
+    Ack,
+}
+
+impl SessionKind {
+    /// Seconds after `received_at` that a session is still worth keeping
+    pub fn ttl_secs(self) -> i64 {
+        match self {
+            // The recipient has this long to authenticate and answer
+            SessionKind::Ping => 300, // 5 minutes
+            SessionKind::Pong | SessionKind::Ack => 300,
+        }
+    }
+}
+
+/// A session as written to a [`SessionStore`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PersistedSession {
+    Ping {
+        id: String,
+        received_at: i64,
+        token: PingToken,
+    },
+    Pong {
+        id: String,
+        received_at: i64,
+        token: PongToken,
+    },
+    Ack {
+        id: String,
+        received_at: i64,
+        token: DeliveryAck,
+    },
+}
+
+impl PersistedSession {
+    pub fn kind(&self) -> SessionKind {
+        match self {
+            PersistedSession::Ping { .. } => SessionKind::Ping,
+            PersistedSession::Pong { .. } => SessionKind::Pong,
+            PersistedSession::Ack { .. } => SessionKind::Ack,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            PersistedSession::Ping { id, .. }
+            | PersistedSession::Pong { id, .. }
+            | PersistedSession::Ack { id, .. } => id,
+        }
+    }
+
+    pub fn received_at(&self) -> i64 {
+        match self {
+            PersistedSession::Ping { received_at, .. }
+            | PersistedSession::Pong { received_at, .. }
+            | PersistedSession::Ack { received_at, .. } => *received_at,
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now - self.received_at() >= self.kind().ttl_secs()
+    }
+}
+
+/// Persistence hook for the session maps
+pub trait SessionStore: Send {
+    /// Every session kept, in any order; may contain several entries for
+    /// the same kind and id
+    fn load(&mut self) -> io::Result<Vec<PersistedSession>>;
+    fn put(&mut self, session: &PersistedSession) -> io::Result<()>;
+    fn remove(&mut self, keys: &[(SessionKind, &str)]) -> io::Result<()>;
+    /// Replace everything kept with `sessions`, atomically if possible
+    fn replace_all(&mut self, sessions: &[PersistedSession]) -> io::Result<()>;
+}
+
+/// JSON-lines file store. `put` appends; `remove` rewrites the file with
+/// the newest entry per session that is left.
+pub struct FileSessionStore {
+    path: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&mut self) -> io::Result<Vec<PersistedSession>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut sessions = Vec::new();
+        for line in io::BufReader::new(file).lines() {
+            // A torn final line from a crash mid-append is skipped
+            match serde_json::from_str(&line?) {
+                Ok(session) => sessions.push(session),
+                Err(e) => log::warn!("Skipping unreadable session entry: {}", e),
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn put(&mut self, session: &PersistedSession) -> io::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let line = serde_json::to_string(session).map_err(io::Error::other)?;
+        writeln!(file, "{}", line)
+    }
+
+    fn remove(&mut self, keys: &[(SessionKind, &str)]) -> io::Result<()> {
+        let remaining: Vec<PersistedSession> = newest_per_session(self.load()?)
+            .into_iter()
+            .filter(|s| !keys.contains(&(s.kind(), s.id())))
+            .collect();
+        self.replace_all(&remaining)
+    }
+
+    fn replace_all(&mut self, sessions: &[PersistedSession]) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp)?;
+            for session in sessions {
+                let line = serde_json::to_string(session).map_err(io::Error::other)?;
+                writeln!(file, "{}", line)?;
+            }
+            file.sync_all()?;
+        }
+        std::fs::rename(tmp, &self.path)
+    }
+}
+
+/// Collapse repeated entries for one session to the most recently received
+fn newest_per_session(sessions: Vec<PersistedSession>) -> Vec<PersistedSession> {
+    let mut newest: HashMap<(SessionKind, String), PersistedSession> = HashMap::new();
+    for session in sessions {
+        let key = (session.kind(), session.id().to_string());
+        match newest.get(&key) {
+            Some(kept) if kept.received_at() > session.received_at() => {}
+            _ => {
+                newest.insert(key, session);
+            }
+        }
+    }
+    newest.into_values().collect()
+}
+
+fn persist(session: &PersistedSession) {
+    let ctx = active_context();
+    let mut store = ctx.session_store.lock().unwrap();
+    if let Some(store) = store.as_mut() {
+        if let Err(e) = store.put(session) {
+            log::error!("Session store write failed ({:?}): {}", session.kind(), e);
+        }
+    }
+}
+
+fn forget(keys: &[(SessionKind, &str)]) {
+    if keys.is_empty() {
+        return;
+    }
+    let ctx = active_context();
+    let mut store = ctx.session_store.lock().unwrap();
+    if let Some(store) = store.as_mut() {
+        if let Err(e) = store.remove(keys) {
+            log::error!("Session store removal failed: {}", e);
+        }
+    }
+}
+
+/// What [`restore_sessions`] did with the stored entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RestoreStats {
+    /// Sessions put back into the maps
+    pub restored: usize,
+    /// Entries past their TTL, dropped from the store
+    pub expired: usize,
+    /// Entries superseded by a newer copy of the same session, stored or
+    /// already in memory
+    pub duplicates: usize,
+}
+
+/// Insert `session` unless the map already has a newer copy; returns
+/// whether it was inserted
+fn merge_into<T>(
+    map: &mut HashMap<String, T>,
+    id: &str,
+    received_at: i64,
+    stored_at: impl Fn(&T) -> i64,
+    value: T,
+) -> bool {
+    if map
+        .get(id)
+        .is_some_and(|existing| stored_at(existing) >= received_at)
+    {
+        return false;
+    }
+    map.insert(id.to_string(), value);
+    true
+}
+
+/// Attach `store` and load the sessions it kept from a previous run.
+///
+/// Expired entries are dropped. When a session is stored more than once, or
+/// was already received again in this run, the most recently received copy
+/// wins. Sessions already in memory are written to the store so it ends up
+/// matching the maps.
+pub fn restore_sessions(mut store: Box<dyn SessionStore>) -> io::Result<RestoreStats> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let loaded = store.load()?;
+    let mut stats = RestoreStats::default();
+
+    let mut live = Vec::new();
+    for session in loaded {
+        if session.is_expired(now) {
+            stats.expired += 1;
+        } else {
+            live.push(session);
+        }
+    }
+    let total_live = live.len();
+    let live = newest_per_session(live);
+    stats.duplicates = total_live - live.len();
+
+    let pings = get_ping_sessions();
+    let pongs = get_pong_sessions();
+    let acks = get_ack_sessions();
+    let mut pings = pings.lock().unwrap();
+    let mut pongs = pongs.lock().unwrap();
+    let mut acks = acks.lock().unwrap();
+
+    for session in live {
+        let inserted = match session {
+            PersistedSession::Ping {
+                id,
+                received_at,
+                token,
+            } => merge_into(
+                &mut pings,
+                &id,
+                received_at,
+                |s| s.received_at,
+                StoredPingSession {
+                    ping_token: token,
+                    received_at,
+                },
+            ),
+            PersistedSession::Pong {
+                id,
+                received_at,
+                token,
+            } => merge_into(
+                &mut pongs,
+                &id,
+                received_at,
+                |s| s.received_at,
+                StoredPongSession {
+                    pong_token: token,
+                    received_at,
+                },
+            ),
+            PersistedSession::Ack {
+                id,
+                received_at,
+                token,
+            } => merge_into(
+                &mut acks,
+                &id,
+                received_at,
+                |s| s.received_at,
+                StoredAckSession {
+                    ack_token: token,
+                    received_at,
+                },
+            ),
+        };
+        if inserted {
+            stats.restored += 1;
+        } else {
+            stats.duplicates += 1;
+        }
+    }
+
+    // The merged maps are now the truth: this drops expired and superseded
+    // entries and picks up sessions received before the store was attached
+    store.replace_all(&snapshot(&pings, &pongs, &acks))?;
+    // Attached before the maps are released so nothing stored meanwhile is
+    // missed
+    *active_context().session_store.lock().unwrap() = Some(store);
+    log::info!(
+        "Restored {} ping/pong/ACK sessions ({} expired, {} duplicates)",
+        stats.restored,
+        stats.expired,
+        stats.duplicates
+    );
+    Ok(stats)
+}
+
+/// Stop mirroring sessions to the store (does not touch its data)
+pub fn detach_session_store() {
+    *active_context().session_store.lock().unwrap() = None;
+}
+
+fn snapshot(
+    pings: &HashMap<String, StoredPingSession>,
+    pongs: &HashMap<String, StoredPongSession>,
+    acks: &HashMap<String, StoredAckSession>,
+) -> Vec<PersistedSession> {
+    let pings = pings.iter().map(|(id, s)| PersistedSession::Ping {
+        id: id.clone(),
+        received_at: s.received_at,
+        token: s.ping_token.clone(),
+    });
+    let pongs = pongs.iter().map(|(id, s)| PersistedSession::Pong {
+        id: id.clone(),
+        received_at: s.received_at,
+        token: s.pong_token.clone(),
+    });
+    let acks = acks.iter().map(|(id, s)| PersistedSession::Ack {
+        id: id.clone(),
+        received_at: s.received_at,
+        token: s.ack_token.clone(),
+    });
+    pings.chain(pongs).chain(acks).collect()
+}
+
+/// Drop every pending session, empty the store and detach it (panic wipe);
+/// returns how many sessions were in memory
+pub fn purge_sessions() -> io::Result<usize> {
+    let mut purged = 0;
+    purged += std::mem::take(&mut *get_ping_sessions().lock().unwrap()).len();
+    purged += std::mem::take(&mut *get_pong_sessions().lock().unwrap()).len();
+    purged += std::mem::take(&mut *get_ack_sessions().lock().unwrap()).len();
+    let store = active_context().session_store.lock().unwrap().take();
+    match store {
+        Some(mut store) => store.replace_all(&[]).map(|_| purged),
+        None => Ok(purged),
+    }
 }
 
 impl PingToken {
@@ -652,10 +1082,10 @@ impl PingPongManager {
         let tor_manager = self.tor_manager.lock().unwrap();
 
         // Default Ping-Pong port
-        const PING_PONG_PORT: u16 = 9150;
+        let ping_pong_port = super::ports::ports().messaging;
 
         // Connect to recipient via Tor
-        let mut conn = tor_manager.connect(recipient_onion, PING_PONG_PORT).await?;
+        let mut conn = tor_manager.connect(recipient_onion, ping_pong_port).await?;
 
         // Send Ping token
         tor_manager.send(&mut conn, &ping_bytes).await?;
@@ -856,4 +1286,127 @@ mod tests {
         assert_eq!(ping.timestamp, ping_deserialized.timestamp);
         assert_eq!(ping.protocol_version, ping_deserialized.protocol_version);
     }
+
+    fn now_secs() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn temp_store_path(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pingpong-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_file_store_keeps_newest_copy() {
+        let (sender, recipient, sx, rx) = test_keys();
+        let ping = PingToken::new(&sender, &recipient.verifying_key(), &sx, &rx).unwrap();
+        let path = temp_store_path("file");
+        let mut store = FileSessionStore::new(&path);
+
+        for received_at in [10, 30, 20] {
+            store
+                .put(&PersistedSession::Ping {
+                    id: "a".into(),
+                    received_at,
+                    token: ping.clone(),
+                })
+                .unwrap();
+        }
+        let pong = PongToken::new(&ping, &recipient, false).unwrap();
+        store
+            .put(&PersistedSession::Pong {
+                id: "a".into(),
+                received_at: 5,
+                token: pong,
+            })
+            .unwrap();
+        assert_eq!(store.load().unwrap().len(), 4);
+
+        // Compaction keeps one entry per (kind, id), the newest
+        store.remove(&[(SessionKind::Pong, "a")]).unwrap();
+        let left = store.load().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].kind(), SessionKind::Ping);
+        assert_eq!(left[0].received_at(), 30);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // The session maps are process-wide; keep everything touching them in one test
+    #[test]
+    fn test_restore_survives_restart_and_reconciles() {
+        let (sender, recipient, sx, rx) = test_keys();
+        let ping = PingToken::new(&sender, &recipient.verifying_key(), &sx, &rx).unwrap();
+        let pong = PongToken::new(&ping, &recipient, true).unwrap();
+        let now = now_secs();
+        let path = temp_store_path("restore");
+
+        // What the previous process left behind
+        let mut previous = FileSessionStore::new(&path);
+        let stored = [
+            PersistedSession::Pong {
+                id: "restore-live".into(),
+                received_at: now - 10,
+                token: pong.clone(),
+            },
+            PersistedSession::Pong {
+                id: "restore-live".into(),
+                received_at: now - 20,
+                token: pong.clone(),
+            },
+            PersistedSession::Ping {
+                id: "restore-stale".into(),
+                received_at: now - SessionKind::Ping.ttl_secs() - 1,
+                token: ping.clone(),
+            },
+            PersistedSession::Ping {
+                id: "restore-seen".into(),
+                received_at: now - 50,
+                token: ping.clone(),
+            },
+        ];
+        for session in &stored {
+            previous.put(session).unwrap();
+        }
+
+        // Received again in this run before the store was attached
+        store_ping_session("restore-seen", ping.clone());
+
+        let stats = restore_sessions(Box::new(FileSessionStore::new(&path))).unwrap();
+        assert_eq!(
+            stats,
+            RestoreStats {
+                restored: 1,
+                expired: 1,
+                duplicates: 2,
+            }
+        );
+        assert_eq!(
+            get_pong_session("restore-live").unwrap().received_at,
+            now - 10
+        );
+        assert!(get_ping_session("restore-stale").is_none());
+        assert!(get_ping_session("restore-seen").unwrap().received_at >= now);
+
+        // The store now mirrors the maps
+        remove_pong_session("restore-live");
+        let mut ids: Vec<String> = FileSessionStore::new(&path)
+            .load()
+            .unwrap()
+            .iter()
+            .map(|s| s.id().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["restore-seen"]);
+
+        assert!(purge_sessions().unwrap() >= 1);
+        assert!(get_ping_session("restore-seen").is_none());
+        assert!(FileSessionStore::new(&path).load().unwrap().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,251 @@
+//! Hidden Service Port Mapping
+//!
+//! The message hidden service used to expose four fixed ports (9150
+//! messaging, 8080 fallback, 9151 taps and friend requests, 9153 ACKs) and
+//! the voice service 9152, each hard-coded at its call site. `PortConfig`
+//! is now the single source for all of them: `TorManager` builds the
+//! `ADD_ONION` port list from it, the senders pick their destination port
+//! from it, and the listeners bind its local ports.
+//!
+//! With `single_port` set, the message service exposes only `messaging`,
+//! mapped to the main local listener, and every sender targets that port.
+//! This works because the main listener already routes by the type byte;
+//! it suits deployments (bridges, relays, restrictive hosting) where only
+//! one hidden-service port can be published. Both ends must agree: a peer
+//! in multi-port mode still dials 9151/9153, so single-port deployments
+//! are for setups where every client is configured the same way.
+//!
+//! Set it once at startup via `ShieldConfig::apply` (`[ports]`) or
+//! [`set_ports`], before the hidden service and listeners are created.
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use thiserror::Error;
+
+use super::tor::{
+    PORT_HS_ACK, PORT_HS_PING_PONG, PORT_HS_PONG_LOCAL, PORT_HS_TAP, PORT_HS_VOICE, PORT_LOCAL_HS,
+    PORT_VOICE_LOCAL,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PortError {
+    #[error("Port must be non-zero: {0}")]
+    Zero(&'static str),
+
+    #[error("Port {0} is assigned to more than one {1} role")]
+    Conflict(u16, &'static str),
+}
+
+/// Virtual (.onion) and local ports; `[ports]` in a `ShieldConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PortConfig {
+    /// Expose only `messaging` on the message hidden service
+    pub single_port: bool,
+    /// PING/PONG and messages (default 9150)
+    pub messaging: u16,
+    /// Fallback port mapped to the main listener (default 8080)
+    pub fallback: u16,
+    /// Taps and friend requests (default 9151)
+    pub tap: u16,
+    /// Delivery confirmations (default 9153)
+    pub ack: u16,
+    /// Voice hidden service (default 9152)
+    pub voice: u16,
+    /// Main multiplexed listener all message types can arrive on (default 8080)
+    pub local_listener: u16,
+    /// Local side of `tap` (default 9151)
+    pub local_tap: u16,
+    /// Local side of `ack` (default 9153)
+    pub local_ack: u16,
+    /// Voice streaming listener (default 9152)
+    pub local_voice: u16,
+}
+
+impl PortConfig {
+    pub const DEFAULT: Self = Self {
+        single_port: false,
+        messaging: PORT_HS_PING_PONG,
+        fallback: PORT_HS_PONG_LOCAL,
+        tap: PORT_HS_TAP,
+        ack: PORT_HS_ACK,
+        voice: PORT_HS_VOICE,
+        local_listener: PORT_LOCAL_HS,
+        local_tap: PORT_HS_TAP,
+        local_ack: PORT_HS_ACK,
+        local_voice: PORT_VOICE_LOCAL,
+    };
+
+    /// Default local ports with everything on one published port
+    pub fn single(messaging: u16) -> Self {
+        Self {
+            single_port: true,
+            messaging,
+            ..Self::DEFAULT
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), PortError> {
+        let named = [
+            (self.messaging, "messaging"),
+            (self.fallback, "fallback"),
+            (self.tap, "tap"),
+            (self.ack, "ack"),
+            (self.voice, "voice"),
+            (self.local_listener, "local_listener"),
+            (self.local_tap, "local_tap"),
+            (self.local_ack, "local_ack"),
+            (self.local_voice, "local_voice"),
+        ];
+        if let Some((_, name)) = named.iter().find(|(port, _)| *port == 0) {
+            return Err(PortError::Zero(name));
+        }
+        if !self.single_port {
+            check_distinct(
+                &[self.messaging, self.fallback, self.tap, self.ack],
+                "virtual",
+            )?;
+            check_distinct(
+                &[
+                    self.local_listener,
+                    self.local_tap,
+                    self.local_ack,
+                    self.local_voice,
+                ],
+                "local",
+            )?;
+        } else if self.local_listener == self.local_voice {
+            return Err(PortError::Conflict(self.local_listener, "local"));
+        }
+        Ok(())
+    }
+
+    /// Destination port for taps and friend requests
+    pub fn tap_port(&self) -> u16 {
+        if self.single_port {
+            self.messaging
+        } else {
+            self.tap
+        }
+    }
+
+    /// Destination port for ACKs
+    pub fn ack_port(&self) -> u16 {
+        if self.single_port {
+            self.messaging
+        } else {
+            self.ack
+        }
+    }
+
+    /// Destination port to retry on when `tap_port` fails
+    pub fn fallback_port(&self) -> u16 {
+        if self.single_port {
+            self.messaging
+        } else {
+            self.fallback
+        }
+    }
+
+    /// `(virtual, local)` mappings for a message hidden service whose primary
+    /// mapping is `service_port -> local_port`, primary first
+    pub fn hidden_service_mappings(&self, service_port: u16, local_port: u16) -> Vec<(u16, u16)> {
+        let mut mappings = vec![(service_port, local_port)];
+        if self.single_port {
+            return mappings;
+        }
+        if self.fallback != service_port && self.fallback != local_port {
+            mappings.push((self.fallback, self.local_listener));
+        }
+        for extra in [(self.tap, self.local_tap), (self.ack, self.local_ack)] {
+            if extra.0 != service_port {
+                mappings.push(extra);
+            }
+        }
+        mappings
+    }
+}
+
+impl Default for PortConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+fn check_distinct(ports: &[u16], role: &'static str) -> Result<(), PortError> {
+    for (i, port) in ports.iter().enumerate() {
+        if ports[i + 1..].contains(port) {
+            return Err(PortError::Conflict(*port, role));
+        }
+    }
+    Ok(())
+}
+
+/// Global port mapping (read when connecting or binding)
+static PORTS: RwLock<PortConfig> = RwLock::new(PortConfig::DEFAULT);
+
+/// Current port mapping
+pub fn ports() -> PortConfig {
+    *PORTS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the port mapping (rejected if invalid). Services and listeners
+/// that are already up keep their ports until recreated.
+pub fn set_ports(config: PortConfig) -> Result<(), PortError> {
+    config.validate()?;
+    *PORTS.write().unwrap_or_else(|e| e.into_inner()) = config;
+    log::info!("Port mapping updated: {:?}", config);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_mapping_matches_legacy_ports() {
+        let ports = PortConfig::DEFAULT;
+        assert!(ports.validate().is_ok());
+        assert_eq!(
+            ports.hidden_service_mappings(9150, 8080),
+            vec![(9150, 8080), (9151, 9151), (9153, 9153)]
+        );
+        // Friend request service: 9151 is the primary mapping
+        assert_eq!(
+            ports.hidden_service_mappings(9151, 9151),
+            vec![(9151, 9151), (8080, 8080), (9153, 9153)]
+        );
+        assert_eq!(
+            (ports.tap_port(), ports.ack_port(), ports.fallback_port()),
+            (9151, 9153, 8080)
+        );
+    }
+
+    #[test]
+    fn test_single_port_mode() {
+        let ports = PortConfig::single(443);
+        assert!(ports.validate().is_ok());
+        assert_eq!(ports.hidden_service_mappings(443, 8080), vec![(443, 8080)]);
+        assert_eq!(
+            (ports.tap_port(), ports.ack_port(), ports.fallback_port()),
+            (443, 443, 443)
+        );
+
+        assert_eq!(
+            PortConfig {
+                tap: 9150,
+                ..PortConfig::DEFAULT
+            }
+            .validate(),
+            Err(PortError::Conflict(9150, "virtual"))
+        );
+        assert_eq!(
+            PortConfig {
+                ack: 0,
+                ..PortConfig::single(443)
+            }
+            .validate(),
+            Err(PortError::Zero("ack"))
+        );
+    }
+}
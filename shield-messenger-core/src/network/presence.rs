@@ -0,0 +1,123 @@
+//! Contact Presence
+//!
+//! The active protocol context's `PresenceBook` (see
+//! `shield_protocol::protocol::presence`) behind the JNI presence API. Beacons travel as `MSG_TYPE_PRESENCE` (0x10)
+//! messages: the app encrypts the beacon bytes from `beacon_for` with the
+//! contact's ratchet like any other message and hands decrypted beacons back
+//! to `record_beacon`. Presence never appears in the chat history.
+//!
+//! Our own coarsened last-active time and what each contact last reported
+//! are held in memory; `export_state` / `import_state` carry them through
+//! the app's encrypted storage across restarts.
+
+use shield_protocol::protocol::presence::{
+    PresenceBeacon, PresenceBook, PresenceBucket, PresenceConfig, PresenceError,
+};
+use shield_protocol::protocol::silence::TrafficClass;
+use shield_protocol::protocol::ContactId;
+
+use crate::ffi::context::active_context;
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Current presence configuration
+pub fn config() -> PresenceConfig {
+    *active_context().presence.lock().unwrap().config()
+}
+
+/// Replace the presence configuration (disabling drops received presence)
+pub fn set_config(config: PresenceConfig) {
+    active_context().presence.lock().unwrap().set_config(config);
+    log::info!("Presence sharing enabled: {}", config.enabled);
+}
+
+/// Opt a contact in or out of presence sharing
+pub fn set_sharing(contact_id: &ContactId, sharing: bool) {
+    active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .set_sharing(contact_id, sharing);
+}
+
+/// Forget a deleted contact
+pub fn forget(contact_id: &ContactId) {
+    active_context().presence.lock().unwrap().forget(contact_id);
+}
+
+/// Record that the local user was active just now
+pub fn note_activity() {
+    active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .note_activity(now_secs());
+}
+
+/// Plaintext beacon for a contact, if one is due (never during network silence)
+pub fn beacon_for(contact_id: &ContactId) -> Option<[u8; 2]> {
+    if !super::silence::allows(TrafficClass::Presence) {
+        return None;
+    }
+    active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .beacon_for(contact_id, now_secs(), &mut rand::rngs::OsRng)
+        .map(|b| b.to_bytes())
+}
+
+/// Store a decrypted beacon. Returns false if the contact is not opted in.
+pub fn record_beacon(contact_id: &ContactId, beacon: &[u8]) -> Result<bool, PresenceError> {
+    let beacon = PresenceBeacon::from_bytes(beacon)?;
+    Ok(active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .record_beacon(contact_id, beacon, now_secs()))
+}
+
+/// Current presence of a contact
+pub fn presence_of(contact_id: &ContactId) -> Option<PresenceBucket> {
+    active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .presence_of(contact_id, now_secs())
+}
+
+/// Presence of all opted-in contacts as JSON
+/// Returns: {"sl_…":"active"|"recent"|"today"|"this_week"|"away",...}
+pub fn snapshot_json() -> String {
+    let entries: Vec<String> = active_context()
+        .presence
+        .lock()
+        .unwrap()
+        .snapshot(now_secs())
+        .into_iter()
+        .map(|(id, bucket)| format!("\"{}\":\"{}\"", id, bucket.label()))
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+/// Serialized state for the app to persist
+pub fn export_state() -> Result<Vec<u8>, PresenceError> {
+    active_context().presence.lock().unwrap().to_bytes()
+}
+
+/// Restore state persisted by `export_state`
+pub fn import_state(data: &[u8]) -> Result<(), PresenceError> {
+    let book = PresenceBook::from_bytes(data)?;
+    *active_context().presence.lock().unwrap() = book;
+    Ok(())
+}
+
+/// Drop all presence state (e.g. on duress wipe)
+pub fn clear() {
+    *active_context().presence.lock().unwrap() = PresenceBook::new(PresenceConfig::default());
+}
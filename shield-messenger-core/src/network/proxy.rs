@@ -0,0 +1,482 @@
+//! Outbound Proxies
+//!
+//! Deployments without Tor (corporate networks, research testbeds) often
+//! reach the outside only through a proxy of their own. [`ProxyConfig`]
+//! says how one transport instance opens its TCP connections:
+//!
+//! - [`ProxyConfig::Direct`]: plain `connect`, the default
+//! - [`ProxyConfig::Socks5`]: RFC 1928 `CONNECT`, with RFC 1929
+//!   username/password authentication when [`ProxyAuth`] is set
+//! - [`ProxyConfig::HttpConnect`]: an HTTP proxy tunnelling with `CONNECT`,
+//!   with `Proxy-Authorization: Basic` when [`ProxyAuth`] is set
+//!
+//! Each transport holds its own config (e.g.
+//! [`I2PTransport::with_proxy`](crate::aethernet::I2PTransport::with_proxy)),
+//! so one can go through a proxy while another connects directly. This is
+//! unrelated to Tor's SOCKS port: [`Socks5Client`](super::Socks5Client)
+//! and [`SystemTor`](super::SystemTor) keep talking to Tor as before.
+//!
+//! Streams are blocking `std` sockets, returned with the handshake timeouts
+//! cleared so callers set their own.
+
+use base64::Engine;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use thiserror::Error;
+
+const SOCKS5_VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USER_PASS: u8 = 0x02;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xFF;
+const USER_PASS_VERSION: u8 = 0x01;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Largest response header accepted from an HTTP proxy
+const MAX_HTTP_HEADER_BYTES: usize = 8 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("Proxy connection error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Cannot resolve {0}")]
+    Resolve(String),
+
+    #[error("Invalid proxy setting: {0}")]
+    InvalidConfig(String),
+
+    #[error("Proxy requires authentication")]
+    AuthRequired,
+
+    #[error("Proxy rejected the credentials")]
+    AuthRejected,
+
+    #[error("Proxy refused the connection: {0}")]
+    Refused(String),
+
+    #[error("Malformed proxy reply: {0}")]
+    Malformed(String),
+}
+
+pub type Result<T> = std::result::Result<T, ProxyError>;
+
+/// Username and password for the proxy
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// How a transport reaches the network
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxyConfig {
+    #[default]
+    Direct,
+    /// SOCKS5 proxy at `addr` (`host:port`)
+    Socks5 {
+        addr: String,
+        auth: Option<ProxyAuth>,
+    },
+    /// HTTP proxy at `addr` (`host:port`) that supports `CONNECT`
+    HttpConnect {
+        addr: String,
+        auth: Option<ProxyAuth>,
+    },
+}
+
+impl ProxyConfig {
+    /// `direct`, `socks5://[user:pass@]host:port` or
+    /// `http://[user:pass@]host:port`. Credentials are taken literally, not
+    /// percent-decoded.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("direct") {
+            return Ok(ProxyConfig::Direct);
+        }
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| ProxyError::InvalidConfig(format!("missing scheme in {}", s)))?;
+        let (auth, addr) = match rest.rsplit_once('@') {
+            Some((creds, addr)) => {
+                let (username, password) = creds.split_once(':').unwrap_or((creds, ""));
+                let auth = ProxyAuth {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                };
+                (Some(auth), addr.trim_end_matches('/'))
+            }
+            None => (None, rest.trim_end_matches('/')),
+        };
+        split_host_port(addr)?;
+        let addr = addr.to_string();
+        match scheme.to_ascii_lowercase().as_str() {
+            "socks5" | "socks5h" => Ok(ProxyConfig::Socks5 { addr, auth }),
+            "http" => Ok(ProxyConfig::HttpConnect { addr, auth }),
+            other => Err(ProxyError::InvalidConfig(format!(
+                "unsupported proxy scheme {}",
+                other
+            ))),
+        }
+    }
+
+    /// Open a TCP connection to `host:port`, through the proxy if there is
+    /// one. Hostnames are resolved by the proxy, not locally.
+    pub fn connect(&self, host: &str, port: u16, timeout: Duration) -> Result<TcpStream> {
+        let stream = match self {
+            ProxyConfig::Direct => return connect_tcp(&format_host_port(host, port), timeout),
+            ProxyConfig::Socks5 { addr, auth } => {
+                let mut stream = connect_tcp(addr, timeout)?;
+                set_timeouts(&stream, Some(timeout))?;
+                socks5_handshake(&mut stream, host, port, auth.as_ref())?;
+                stream
+            }
+            ProxyConfig::HttpConnect { addr, auth } => {
+                let mut stream = connect_tcp(addr, timeout)?;
+                set_timeouts(&stream, Some(timeout))?;
+                http_connect_handshake(&mut stream, host, port, auth.as_ref())?;
+                stream
+            }
+        };
+        set_timeouts(&stream, None)?;
+        log::debug!("Proxied connection to {}:{} established", host, port);
+        Ok(stream)
+    }
+
+    /// [`connect`](Self::connect) to a `host:port` string
+    pub fn connect_addr(&self, addr: &str, timeout: Duration) -> Result<TcpStream> {
+        let (host, port) = split_host_port(addr)?;
+        self.connect(host, port, timeout)
+    }
+}
+
+/// `host:port` or `[v6]:port`
+fn split_host_port(addr: &str) -> Result<(&str, u16)> {
+    let invalid = || ProxyError::InvalidConfig(format!("expected host:port, got {}", addr));
+    let (host, port) = addr.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    let port = port.parse().map_err(|_| invalid())?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host, port))
+}
+
+fn format_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn connect_tcp(addr: &str, timeout: Duration) -> Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = addr
+        .to_socket_addrs()
+        .map_err(|_| ProxyError::Resolve(addr.to_string()))?
+        .collect();
+    let mut last_err = None;
+    for sock_addr in addrs {
+        match TcpStream::connect_timeout(&sock_addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.map_or_else(|| ProxyError::Resolve(addr.to_string()), ProxyError::Io))
+}
+
+fn set_timeouts(stream: &TcpStream, timeout: Option<Duration>) -> Result<()> {
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    Ok(())
+}
+
+fn socks5_handshake(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&ProxyAuth>,
+) -> Result<()> {
+    let greeting: &[u8] = match auth {
+        Some(_) => &[SOCKS5_VERSION, 2, METHOD_NO_AUTH, METHOD_USER_PASS],
+        None => &[SOCKS5_VERSION, 1, METHOD_NO_AUTH],
+    };
+    stream.write_all(greeting)?;
+
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice)?;
+    if choice[0] != SOCKS5_VERSION {
+        return Err(ProxyError::Malformed(format!(
+            "SOCKS version {}",
+            choice[0]
+        )));
+    }
+    match (choice[1], auth) {
+        (METHOD_NO_AUTH, _) => {}
+        (METHOD_USER_PASS, Some(auth)) => socks5_authenticate(stream, auth)?,
+        (METHOD_NONE_ACCEPTABLE, _) | (METHOD_USER_PASS, None) => {
+            return Err(ProxyError::AuthRequired)
+        }
+        (method, _) => {
+            return Err(ProxyError::Malformed(format!(
+                "unrequested SOCKS method {}",
+                method
+            )))
+        }
+    }
+
+    let mut request = vec![SOCKS5_VERSION, CMD_CONNECT, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let name = host.as_bytes();
+            let len = u8::try_from(name.len())
+                .map_err(|_| ProxyError::InvalidConfig("hostname too long".into()))?;
+            request.push(ATYP_DOMAIN);
+            request.push(len);
+            request.extend_from_slice(name);
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != SOCKS5_VERSION {
+        return Err(ProxyError::Malformed(format!("SOCKS version {}", reply[0])));
+    }
+    if reply[1] != 0x00 {
+        let reason = match reply[1] {
+            0x01 => "general failure",
+            0x02 => "not allowed by ruleset",
+            0x03 => "network unreachable",
+            0x04 => "host unreachable",
+            0x05 => "connection refused",
+            0x06 => "TTL expired",
+            0x07 => "command not supported",
+            0x08 => "address type not supported",
+            _ => "unknown error",
+        };
+        return Err(ProxyError::Refused(reason.to_string()));
+    }
+    // Bound address and port, unused
+    let bound_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        atyp => return Err(ProxyError::Malformed(format!("address type {}", atyp))),
+    };
+    let mut bound = vec![0u8; bound_len + 2];
+    stream.read_exact(&mut bound)?;
+    Ok(())
+}
+
+fn socks5_authenticate(stream: &mut TcpStream, auth: &ProxyAuth) -> Result<()> {
+    let too_long = || ProxyError::InvalidConfig("SOCKS5 credentials over 255 bytes".into());
+    let user = auth.username.as_bytes();
+    let pass = auth.password.as_bytes();
+    let mut request = vec![USER_PASS_VERSION];
+    request.push(u8::try_from(user.len()).map_err(|_| too_long())?);
+    request.extend_from_slice(user);
+    request.push(u8::try_from(pass.len()).map_err(|_| too_long())?);
+    request.extend_from_slice(pass);
+    stream.write_all(&request)?;
+
+    let mut status = [0u8; 2];
+    stream.read_exact(&mut status)?;
+    if status[1] != 0x00 {
+        return Err(ProxyError::AuthRejected);
+    }
+    Ok(())
+}
+
+fn http_connect_handshake(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&ProxyAuth>,
+) -> Result<()> {
+    let target = format_host_port(host, port);
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(auth) = auth {
+        let credentials = base64::engine::general_purpose::STANDARD
+            .encode(format!("{}:{}", auth.username, auth.password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    // Byte by byte, so nothing after the header is taken from the tunnel
+    let mut header = Vec::new();
+    let mut byte = [0u8; 1];
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_HTTP_HEADER_BYTES {
+            return Err(ProxyError::Malformed("response header too long".into()));
+        }
+        stream.read_exact(&mut byte)?;
+        header.push(byte[0]);
+    }
+
+    let header = String::from_utf8_lossy(&header);
+    let status_line = header.lines().next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| ProxyError::Malformed(status_line.to_string()))?;
+    match status {
+        200..=299 => Ok(()),
+        407 if auth.is_some() => Err(ProxyError::AuthRejected),
+        407 => Err(ProxyError::AuthRequired),
+        _ => Err(ProxyError::Refused(status_line.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// One-connection proxy on localhost running `serve`
+    fn fake_proxy(serve: impl FnOnce(TcpStream) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream);
+        });
+        addr
+    }
+
+    #[test]
+    fn test_socks5_with_auth() {
+        let addr = fake_proxy(|mut s| {
+            let mut greeting = [0u8; 4];
+            s.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [5, 2, METHOD_NO_AUTH, METHOD_USER_PASS]);
+            s.write_all(&[5, METHOD_USER_PASS]).unwrap();
+
+            let mut creds = [0u8; 12];
+            s.read_exact(&mut creds).unwrap();
+            assert_eq!(&creds, b"\x01\x05alice\x04pass");
+            s.write_all(&[1, 0]).unwrap();
+
+            let mut request = [0u8; 5 + 11 + 2];
+            s.read_exact(&mut request).unwrap();
+            assert_eq!(&request[..5], &[5, CMD_CONNECT, 0, ATYP_DOMAIN, 11]);
+            assert_eq!(&request[5..16], b"example.org");
+            assert_eq!(&request[16..], &443u16.to_be_bytes());
+            s.write_all(&[5, 0, 0, ATYP_IPV4, 10, 0, 0, 1, 0x01, 0xBB])
+                .unwrap();
+            s.write_all(b"tunnel").unwrap();
+        });
+
+        let proxy = ProxyConfig::parse(&format!("socks5://alice:pass@{}", addr)).unwrap();
+        let mut stream = proxy.connect("example.org", 443, TIMEOUT).unwrap();
+        let mut data = [0u8; 6];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"tunnel");
+
+        // Without credentials the proxy's demand for them is an error
+        let addr = fake_proxy(|mut s| {
+            let mut greeting = [0u8; 3];
+            s.read_exact(&mut greeting).unwrap();
+            s.write_all(&[5, METHOD_NONE_ACCEPTABLE]).unwrap();
+        });
+        let proxy = ProxyConfig::Socks5 { addr, auth: None };
+        assert!(matches!(
+            proxy.connect("10.1.2.3", 80, TIMEOUT),
+            Err(ProxyError::AuthRequired)
+        ));
+    }
+
+    #[test]
+    fn test_http_connect() {
+        let addr = fake_proxy(|mut s| {
+            let mut request = Vec::new();
+            let mut byte = [0u8; 1];
+            while !request.ends_with(b"\r\n\r\n") {
+                s.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with("CONNECT [2001:db8::1]:8443 HTTP/1.1\r\n"));
+            // "bob:secret"
+            assert!(request.contains("Proxy-Authorization: Basic Ym9iOnNlY3JldA==\r\n"));
+            s.write_all(b"HTTP/1.1 200 Connection established\r\n\r\nhello")
+                .unwrap();
+        });
+
+        let proxy = ProxyConfig::parse(&format!("http://bob:secret@{}", addr)).unwrap();
+        let mut stream = proxy.connect("2001:db8::1", 8443, TIMEOUT).unwrap();
+        let mut data = [0u8; 5];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
+
+        let addr = fake_proxy(|mut s| {
+            let mut buf = [0u8; 256];
+            let _ = s.read(&mut buf).unwrap();
+            s.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .unwrap();
+        });
+        let proxy = ProxyConfig::HttpConnect { addr, auth: None };
+        assert!(matches!(
+            proxy.connect_addr("example.org:443", TIMEOUT),
+            Err(ProxyError::AuthRequired)
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ProxyConfig::parse("direct").unwrap(), ProxyConfig::Direct);
+        assert_eq!(ProxyConfig::parse("").unwrap(), ProxyConfig::Direct);
+        assert_eq!(
+            ProxyConfig::parse("socks5://proxy.corp:1080").unwrap(),
+            ProxyConfig::Socks5 {
+                addr: "proxy.corp:1080".into(),
+                auth: None
+            }
+        );
+        let ProxyConfig::HttpConnect { addr, auth } =
+            ProxyConfig::parse("http://u:p:w@[::1]:3128/").unwrap()
+        else {
+            panic!("expected an HTTP proxy");
+        };
+        assert_eq!(addr, "[::1]:3128");
+        assert_eq!(auth.as_ref().unwrap().password, "p:w");
+        assert!(!format!("{:?}", auth).contains("p:w"));
+
+        assert!(ProxyConfig::parse("ftp://host:21").is_err());
+        assert!(ProxyConfig::parse("socks5://host").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Direct-Chat Reactions
+//!
+//! The active protocol context's `ReactionBook` (see
+//! `shield_protocol::protocol::reaction`) behind the JNI reaction API. `set_local` returns the plaintext to encrypt
+//! and send to the contact as `MSG_TYPE_REACTION`; every decrypted reaction
+//! from a contact goes through `receive`. The chat screen asks `view_json`
+//! for the reactions of the messages it shows.
+//!
+//! Reactions are not written to disk here; the app keeps the `export_state`
+//! blob beside the chat history it annotates and reloads it with
+//! `import_state`.
+
+use shield_protocol::protocol::reaction::{Reaction, ReactionBook, ReactionError, ReactionView};
+use shield_protocol::protocol::ContactId;
+
+use crate::ffi::context::active_context;
+
+/// Set (`Some`) or remove (`None`) our reaction; returns the plaintext to send
+pub fn set_local(
+    contact_id: &ContactId,
+    message_id: &str,
+    emoji: Option<&str>,
+) -> Result<Vec<u8>, ReactionError> {
+    active_context()
+        .reactions
+        .lock()
+        .unwrap()
+        .set_local(contact_id, message_id, emoji)?
+        .to_bytes()
+}
+
+/// Apply a decrypted reaction from `contact_id`; returns the target message
+/// ID and whether its reactions changed
+pub fn receive(contact_id: &ContactId, plaintext: &[u8]) -> Result<(String, bool), ReactionError> {
+    let reaction = Reaction::from_bytes(plaintext)?;
+    let changed = active_context()
+        .reactions
+        .lock()
+        .unwrap()
+        .apply_remote(contact_id, &reaction);
+    Ok((reaction.message_id, changed))
+}
+
+/// Reaction views for the given messages, in order
+pub fn view(contact_id: &ContactId, message_ids: &[String]) -> Vec<ReactionView> {
+    active_context()
+        .reactions
+        .lock()
+        .unwrap()
+        .fold(contact_id, message_ids.iter().map(String::as_str))
+}
+
+/// JSON object for one view:
+/// {"messageId":"..","counts":[{"emoji":"..","count":n}],"local":".."|null}
+pub fn view_json(view: &ReactionView) -> serde_json::Value {
+    serde_json::json!({
+        "messageId": view.message_id,
+        "counts": view
+            .counts
+            .iter()
+            .map(|(emoji, count)| serde_json::json!({ "emoji": emoji, "count": count }))
+            .collect::<Vec<_>>(),
+        "local": view.local,
+    })
+}
+
+/// Drop reactions to a deleted message
+pub fn forget_message(contact_id: &ContactId, message_id: &str) {
+    active_context()
+        .reactions
+        .lock()
+        .unwrap()
+        .forget_message(contact_id, message_id);
+}
+
+/// Forget a deleted contact or cleared history
+pub fn forget(contact_id: &ContactId) {
+    active_context()
+        .reactions
+        .lock()
+        .unwrap()
+        .forget(contact_id);
+}
+
+/// Serialized state for the app to persist
+pub fn export_state() -> Result<Vec<u8>, ReactionError> {
+    active_context().reactions.lock().unwrap().to_bytes()
+}
+
+/// Restore state persisted by `export_state`
+pub fn import_state(data: &[u8]) -> Result<(), ReactionError> {
+    let book = ReactionBook::from_bytes(data)?;
+    *active_context().reactions.lock().unwrap() = book;
+    Ok(())
+}
+
+/// Drop all reaction state (e.g. on duress wipe)
+pub fn clear() {
+    *active_context().reactions.lock().unwrap() = ReactionBook::new();
+}
@@ -0,0 +1,134 @@
+//! Message Recall
+//!
+//! The active protocol context's `RecallTracker` (our outgoing recalls) and
+//! `RecallGuard` (recalls received for messages not here yet), see
+//! `shield_protocol::protocol::recall`. Frames are plaintext the app
+//! encrypts and sends as `MSG_TYPE_RECALL`, like reactions.
+//!
+//! Sender: [`request`] builds the request; the contact's ack goes through
+//! [`receive_ack`], which reports whether the recall was honored.
+//! Recipient: [`answer`] decides each message from the state the app reports,
+//! returning the messages to delete and the signed ack to send back; every
+//! arriving message is checked with [`is_recalled`] first.
+
+use shield_protocol::protocol::recall::{
+    LocalMessageState, RecallAck, RecallError, RecallFrame, RecallGuard, RecallReport,
+    RecallRequest, RecallTracker,
+};
+use shield_protocol::protocol::ContactId;
+use std::sync::Mutex;
+
+use crate::ffi::context::active_context;
+
+/// Per-context recall state (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct RecallState {
+    tracker: Mutex<RecallTracker>,
+    guard: Mutex<RecallGuard>,
+}
+
+impl Default for RecallState {
+    fn default() -> Self {
+        RecallState {
+            tracker: Mutex::new(RecallTracker::new()),
+            guard: Mutex::new(RecallGuard::new()),
+        }
+    }
+}
+
+/// Set how long after sending a message can be recalled
+pub fn set_window(window_secs: u32) -> Result<(), RecallError> {
+    active_context()
+        .recall
+        .tracker
+        .lock()
+        .unwrap()
+        .set_window(window_secs)
+}
+
+/// Recall `messages` (`(message ID, sent_at)`) sent to `contact_id`;
+/// returns the plaintext to send
+pub fn request(
+    contact_id: &ContactId,
+    messages: &[(String, u64)],
+    now: u64,
+) -> Result<Vec<u8>, RecallError> {
+    let request = active_context().recall.tracker.lock().unwrap().request(
+        contact_id,
+        messages,
+        now,
+        &mut rand::rngs::OsRng,
+    )?;
+    RecallFrame::Request(request).to_bytes()
+}
+
+/// Parse a decrypted `MSG_TYPE_RECALL` plaintext
+pub fn parse(plaintext: &[u8]) -> Result<RecallFrame, RecallError> {
+    RecallFrame::from_bytes(plaintext)
+}
+
+/// Decide a recall request from `contact_id` and sign the answer with our
+/// identity key; returns the ack (with the verdicts) and its plaintext to
+/// send back
+pub fn answer(
+    contact_id: &ContactId,
+    request: &RecallRequest,
+    now: u64,
+    state_of: impl Fn(&str) -> LocalMessageState,
+    identity_private_key: &[u8],
+) -> Result<(RecallAck, Vec<u8>), RecallError> {
+    let verdicts = active_context()
+        .recall
+        .guard
+        .lock()
+        .unwrap()
+        .handle(contact_id, request, now, state_of)?;
+    let ack = RecallAck::sign(
+        contact_id,
+        request.recall_id,
+        verdicts,
+        identity_private_key,
+    )?;
+    let plaintext = RecallFrame::Ack(ack.clone()).to_bytes()?;
+    Ok((ack, plaintext))
+}
+
+/// Check an ack from `contact_id` against our recall; `us` is our own
+/// contact ID
+pub fn receive_ack(
+    contact_id: &ContactId,
+    ack: RecallAck,
+    us: &ContactId,
+    contact_public_key: &[u8],
+) -> Result<RecallReport, RecallError> {
+    let report = active_context().recall.tracker.lock().unwrap().on_ack(
+        contact_id,
+        ack,
+        us,
+        contact_public_key,
+    )?;
+    log::info!("Recall to {} answered: {:?}", contact_id, report.outcome);
+    Ok(report)
+}
+
+/// Whether an arriving message from `contact_id` was recalled before it
+/// got here (the caller drops it unseen)
+pub fn is_recalled(contact_id: &ContactId, message_id: &str, now: u64) -> bool {
+    let ctx = active_context();
+    let mut guard = ctx.recall.guard.lock().unwrap();
+    guard.prune(now);
+    guard.is_recalled(contact_id, message_id, now)
+}
+
+/// Forget a deleted contact
+pub fn forget(contact_id: &ContactId) {
+    let ctx = active_context();
+    ctx.recall.tracker.lock().unwrap().forget(contact_id);
+    ctx.recall.guard.lock().unwrap().forget(contact_id);
+}
+
+/// Drop all recall state (e.g. on duress wipe)
+pub fn clear() {
+    let ctx = active_context();
+    *ctx.recall.tracker.lock().unwrap() = RecallTracker::new();
+    *ctx.recall.guard.lock().unwrap() = RecallGuard::new();
+}
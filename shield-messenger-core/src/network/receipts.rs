@@ -0,0 +1,122 @@
+//! Receipt Batching
+//!
+//! The active protocol context's `ReceiptBatcher` (see
+//! `shield_protocol::protocol::receipts`) replacing one-connection-per-ACK delivery confirmations. The app records
+//! the sequence number of every 1:1 message it receives (duplicates
+//! included, since a resend means the earlier receipt was lost), then:
+//!
+//! - When a message from that contact arrives on a connection that is kept
+//!   open for a reply, `take_piggyback` returns everything pending for the
+//!   contact to send back on it as `MSG_TYPE_ACK_BATCH`.
+//! - At `next_deadline_ms` (or on its regular tick) it calls `poll_due` and
+//!   sends each returned batch on one connection to the contact's ACK port.
+//!
+//! Batches are receipts, so network silence holds them back until it ends.
+
+use shield_protocol::protocol::receipts::{AckBatch, ReceiptBatcher, ReceiptConfig};
+use shield_protocol::protocol::silence::TrafficClass;
+use shield_protocol::protocol::ContactId;
+
+use crate::ffi::context::active_context;
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Current batching configuration
+pub fn config() -> ReceiptConfig {
+    *active_context().receipts.lock().unwrap().config()
+}
+
+/// Replace the batching configuration
+pub fn set_config(config: ReceiptConfig) {
+    active_context().receipts.lock().unwrap().set_config(config);
+    log::info!(
+        "Receipt batching: max_delay={}ms max_pending={}",
+        config.max_delay_ms,
+        config.max_pending
+    );
+}
+
+/// Queue a receipt for message `seq` from `contact_id`
+pub fn record_received(contact_id: ContactId, seq: u64) {
+    active_context()
+        .receipts
+        .lock()
+        .unwrap()
+        .record(contact_id, seq, now_millis());
+}
+
+/// Pending receipts for `contact_id`, encoded, to send on an open connection
+pub fn take_piggyback(contact_id: &ContactId) -> Option<Vec<u8>> {
+    if !super::silence::allows(TrafficClass::Receipt) {
+        return None;
+    }
+    active_context()
+        .receipts
+        .lock()
+        .unwrap()
+        .take(contact_id)
+        .map(|batch| batch.to_bytes())
+}
+
+/// Encoded batches that need a connection of their own now
+pub fn poll_due() -> Vec<(ContactId, Vec<u8>)> {
+    if !super::silence::allows(TrafficClass::Receipt) {
+        return Vec::new();
+    }
+    active_context()
+        .receipts
+        .lock()
+        .unwrap()
+        .poll(now_millis())
+        .into_iter()
+        .map(|(contact_id, batch)| (contact_id, batch.to_bytes()))
+        .collect()
+}
+
+/// Put back an encoded batch that could not be sent
+pub fn requeue(contact_id: ContactId, batch: &[u8]) {
+    let Ok(batch) = AckBatch::from_bytes(batch) else {
+        return;
+    };
+    let now = now_millis();
+    let ctx = active_context();
+    let mut receipts = ctx.receipts.lock().unwrap();
+    for range in batch.ranges() {
+        for seq in range.first..=range.last {
+            receipts.record(contact_id, seq, now);
+        }
+    }
+}
+
+/// Unix time (ms) at which `poll_due` next has work, if any
+pub fn next_deadline_ms() -> Option<u64> {
+    active_context().receipts.lock().unwrap().next_deadline()
+}
+
+/// Forget a deleted contact
+pub fn forget(contact_id: &ContactId) {
+    active_context().receipts.lock().unwrap().forget(contact_id);
+}
+
+/// JSON array of `[first, last]` pairs for a received batch
+pub fn batch_json(batch: &AckBatch) -> serde_json::Value {
+    serde_json::Value::Array(
+        batch
+            .ranges()
+            .iter()
+            .map(|r| serde_json::json!([r.first, r.last]))
+            .collect(),
+    )
+}
+
+/// Drop all pending receipts (e.g. on duress wipe)
+pub fn clear() {
+    let ctx = active_context();
+    let mut receipts = ctx.receipts.lock().unwrap();
+    *receipts = ReceiptBatcher::new(ReceiptConfig::default());
+}
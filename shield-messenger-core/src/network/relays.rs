@@ -0,0 +1,192 @@
+//! Relay Federation
+//!
+//! The active protocol context's `RelaySelector` (see
+//! `shield_protocol::protocol::relay`).
+//! The app feeds in every relay descriptor it learns about through
+//! `add_descriptor`; only descriptors that verify are kept. Before handing a
+//! message to a relay it asks `select` which one to use, so consecutive
+//! messages spread over all known relays instead of revealing the user's
+//! whole traffic pattern to one operator.
+//!
+//! Descriptors are public, so the app stores the bytes it received as-is
+//! and re-adds them at startup; expired ones are rejected then.
+//!
+//! Group messages go through `plan_group_fanout`, which pads the batch with
+//! dummy envelopes and shuffles it before spreading it over the relays, so no
+//! relay sees the group's size or member order.
+
+use shield_protocol::protocol::contact_backup;
+use shield_protocol::protocol::fanout::{self, FanoutConfig, FanoutError, RelayDrop};
+use shield_protocol::protocol::mailbox::MailboxId;
+use shield_protocol::protocol::relay::{RelayDescriptor, RelayError, RelaySelector};
+
+use crate::ffi::context::active_context;
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Verify and add an encoded descriptor; `false` if it is not newer than the
+/// one already known for that relay
+pub fn add_descriptor(data: &[u8]) -> Result<bool, RelayError> {
+    let descriptor = RelayDescriptor::from_bytes(data)?;
+    let added = active_context()
+        .relays
+        .lock()
+        .unwrap()
+        .add(descriptor, now_secs())?;
+    if added {
+        log::info!("Relay descriptor accepted");
+    }
+    Ok(added)
+}
+
+/// Relay to carry the next message of `message_len` bytes
+pub fn select(message_len: usize) -> Option<RelayDescriptor> {
+    let now = now_secs();
+    let ctx = active_context();
+    let mut relays = ctx.relays.lock().unwrap();
+    relays.prune(now);
+    relays
+        .next(message_len, now, &mut rand::rngs::OsRng)
+        .cloned()
+}
+
+/// Mix a group fan-out of equally sized sealed envelopes with dummies and
+/// pick a relay for every drop, in submission order. `None` if the known
+/// relays cannot carry the whole batch right now; send none of it then
+pub fn plan_group_fanout(
+    real: Vec<RelayDrop>,
+    decoys: &[MailboxId],
+) -> Result<Option<Vec<(RelayDescriptor, RelayDrop)>>, FanoutError> {
+    let mut rng = rand::rngs::OsRng;
+    let drops = fanout::mix(real, decoys, &FanoutConfig::default(), &mut rng)?;
+    let now = now_secs();
+    let ctx = active_context();
+    let mut relays = ctx.relays.lock().unwrap();
+    relays.prune(now);
+    let total = drops.len();
+    let mut planned = Vec::with_capacity(total);
+    for drop in drops {
+        let Some(relay) = relays.next(drop.envelope.len(), now, &mut rng) else {
+            log::warn!(
+                "Relays cannot carry the group fan-out now ({} of {} placed)",
+                planned.len(),
+                total
+            );
+            return Ok(None);
+        };
+        planned.push((relay.clone(), drop));
+    }
+    Ok(Some(planned))
+}
+
+/// `plan_group_fanout` over JSON for the FFI layers. Takes
+/// [{"mailbox":"hex","envelope":"b64"}] and ["hex", ...] decoy mailboxes;
+/// returns [{"relayKey","onion","mailbox","envelope"}] in submission order,
+/// or null if the relays cannot carry the batch now
+pub fn plan_group_fanout_json(
+    drops_json: &str,
+    decoys_json: &str,
+) -> Result<serde_json::Value, String> {
+    use base64::Engine;
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let mailbox = |hex_str: &str| -> Result<MailboxId, String> {
+        let bytes: [u8; 32] = hex::decode(hex_str)
+            .map_err(|e| e.to_string())?
+            .try_into()
+            .map_err(|_| "mailbox must be 32 bytes".to_string())?;
+        Ok(MailboxId(bytes))
+    };
+
+    let drops: Vec<serde_json::Value> =
+        serde_json::from_str(drops_json).map_err(|e| e.to_string())?;
+    let real = drops
+        .iter()
+        .map(|d| {
+            Ok(RelayDrop {
+                mailbox: mailbox(d["mailbox"].as_str().unwrap_or(""))?,
+                envelope: b64
+                    .decode(d["envelope"].as_str().unwrap_or(""))
+                    .map_err(|e| e.to_string())?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let decoys: Vec<String> = serde_json::from_str(decoys_json).map_err(|e| e.to_string())?;
+    let decoys = decoys
+        .iter()
+        .map(|d| mailbox(d))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let Some(planned) = plan_group_fanout(real, &decoys).map_err(|e| e.to_string())? else {
+        return Ok(serde_json::Value::Null);
+    };
+    Ok(serde_json::Value::Array(
+        planned
+            .iter()
+            .map(|(relay, drop)| {
+                serde_json::json!({
+                    "relayKey": hex::encode(relay.info.relay_key),
+                    "onion": relay.info.onion_address,
+                    "mailbox": hex::encode(drop.mailbox.0),
+                    "envelope": b64.encode(&drop.envelope),
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Relays holding the contact backup stored under `slot`, most preferred
+/// first
+pub fn backup_replicas(slot: &[u8; 32]) -> Vec<RelayDescriptor> {
+    let ctx = active_context();
+    let mut relays = ctx.relays.lock().unwrap();
+    relays.prune(now_secs());
+    contact_backup::replica_relays(slot, relays.relays())
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+/// Stop using a relay
+pub fn remove(relay_key: &[u8; 32]) -> bool {
+    active_context().relays.lock().unwrap().remove(relay_key)
+}
+
+/// JSON object for one relay:
+/// {"relayKey":"hex","onion":"..","protocolVersion":n,"retentionSecs":n,
+///  "deleteOnFetch":bool,"messagesPerMinute":n,"maxMessageBytes":n,"expiresAt":n}
+pub fn relay_json(descriptor: &RelayDescriptor) -> serde_json::Value {
+    let info = &descriptor.info;
+    serde_json::json!({
+        "relayKey": hex::encode(info.relay_key),
+        "onion": info.onion_address,
+        "protocolVersion": descriptor.protocol_version(),
+        "retentionSecs": info.retention.max_retention_secs,
+        "deleteOnFetch": info.retention.delete_on_fetch,
+        "messagesPerMinute": info.rate_limits.messages_per_minute,
+        "maxMessageBytes": info.rate_limits.max_message_bytes,
+        "expiresAt": info.expires_at,
+    })
+}
+
+/// JSON array of all known relays
+pub fn relays_json() -> serde_json::Value {
+    serde_json::Value::Array(
+        active_context()
+            .relays
+            .lock()
+            .unwrap()
+            .relays()
+            .map(relay_json)
+            .collect(),
+    )
+}
+
+/// Forget all relays
+pub fn clear() {
+    *active_context().relays.lock().unwrap() = RelaySelector::new();
+}
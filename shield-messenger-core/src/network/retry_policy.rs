@@ -0,0 +1,279 @@
+//! Network Timeout & Retry Policies
+//!
+//! Central configuration for the timeouts and retry loops used by the
+//! Tor send paths (instant Pong wait, direct message delivery, blob send,
+//! friend request delivery). Previously these were constants scattered
+//! through the FFI layer; they now live in two process-wide policies that
+//! the app can tune at runtime (e.g. longer timeouts on Snowflake bridges).
+//!
+//! Retries use jittered exponential backoff: the nominal delay for attempt
+//! `n` is `base_delay * multiplier^(n-1)`, capped at `max_delay`, and a
+//! random fraction (`jitter`) of it is shaved off so that many clients
+//! retrying against the same onion service do not synchronize.
+
+use rand::Rng;
+use std::sync::RwLock;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PolicyError {
+    #[error("Timeout must be non-zero: {0}")]
+    ZeroTimeout(&'static str),
+
+    #[error("max_attempts must be at least 1")]
+    ZeroAttempts,
+
+    #[error("base_delay exceeds max_delay")]
+    DelayRange,
+
+    #[error("multiplier must be >= 1.0, got {0}")]
+    InvalidMultiplier(f64),
+
+    #[error("jitter must be within [0.0, 1.0], got {0}")]
+    InvalidJitter(f64),
+}
+
+/// Timeouts for the core send paths
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeoutPolicy {
+    /// How long to wait for an instant Pong before falling back to delayed mode (default: 30s)
+    pub instant_pong: Duration,
+    /// How long a direct message waits for the recipient's Pong (default: 60s)
+    pub message_delivery: Duration,
+    /// Budget for a single blob send, starting after the send permit is acquired (default: 90s)
+    pub blob_send: Duration,
+}
+
+impl TimeoutPolicy {
+    pub const DEFAULT: Self = Self {
+        instant_pong: Duration::from_secs(30),
+        message_delivery: Duration::from_secs(60),
+        blob_send: Duration::from_secs(90),
+    };
+
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        if self.instant_pong.is_zero() {
+            return Err(PolicyError::ZeroTimeout("instant_pong"));
+        }
+        if self.message_delivery.is_zero() {
+            return Err(PolicyError::ZeroTimeout("message_delivery"));
+        }
+        if self.blob_send.is_zero() {
+            return Err(PolicyError::ZeroTimeout("blob_send"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Retry schedule for fire-and-forget deliveries (friend requests, acceptances)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the second attempt
+    pub base_delay: Duration,
+    /// Upper bound on any single delay
+    pub max_delay: Duration,
+    /// Growth factor between consecutive delays
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized away (0.0 = none, 1.0 = full jitter)
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// 24 attempts like the old fixed schedule, 2s doubling to an 8s cap with
+    /// up to a quarter shaved off: 130-174s of waiting in total, never less
+    /// than the old 24 x 5s window that slow bridges (Snowflake) relied on.
+    pub const DEFAULT: Self = Self {
+        max_attempts: 24,
+        base_delay: Duration::from_secs(2),
+        max_delay: Duration::from_secs(8),
+        multiplier: 2.0,
+        jitter: 0.25,
+    };
+
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        if self.max_attempts == 0 {
+            return Err(PolicyError::ZeroAttempts);
+        }
+        if self.base_delay > self.max_delay {
+            return Err(PolicyError::DelayRange);
+        }
+        if !(self.multiplier >= 1.0 && self.multiplier.is_finite()) {
+            return Err(PolicyError::InvalidMultiplier(self.multiplier));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(PolicyError::InvalidJitter(self.jitter));
+        }
+        Ok(())
+    }
+
+    /// Nominal (un-jittered) delay after failed attempt `attempt` (1-based)
+    pub fn nominal_delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(63) as i32;
+        let secs = self.base_delay.as_secs_f64() * self.multiplier.powi(exp);
+        if !secs.is_finite() || secs >= self.max_delay.as_secs_f64() {
+            self.max_delay
+        } else {
+            Duration::from_secs_f64(secs)
+        }
+    }
+
+    /// Delay to sleep after failed attempt `attempt` (1-based), with jitter applied
+    pub fn delay_for_attempt<R: Rng + ?Sized>(&self, attempt: u32, rng: &mut R) -> Duration {
+        let nominal = self.nominal_delay(attempt);
+        if self.jitter == 0.0 {
+            return nominal;
+        }
+        let cut = self.jitter * rng.gen::<f64>();
+        nominal.mul_f64(1.0 - cut)
+    }
+
+    /// Whether another attempt is allowed after `attempt` has failed
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Worst-case total time spent sleeping between attempts
+    pub fn max_total_delay(&self) -> Duration {
+        (1..self.max_attempts).map(|a| self.nominal_delay(a)).sum()
+    }
+
+    /// Least total time spent sleeping between attempts (every delay fully jittered)
+    pub fn min_total_delay(&self) -> Duration {
+        self.max_total_delay().mul_f64(1.0 - self.jitter)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Global policies (read on every send, written from JNI)
+static TIMEOUT_POLICY: RwLock<TimeoutPolicy> = RwLock::new(TimeoutPolicy::DEFAULT);
+static RETRY_POLICY: RwLock<RetryPolicy> = RwLock::new(RetryPolicy::DEFAULT);
+
+/// Current timeout policy
+pub fn timeout_policy() -> TimeoutPolicy {
+    *TIMEOUT_POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the timeout policy (rejected if invalid)
+pub fn set_timeout_policy(policy: TimeoutPolicy) -> Result<(), PolicyError> {
+    policy.validate()?;
+    *TIMEOUT_POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    log::info!("Timeout policy updated: {:?}", policy);
+    Ok(())
+}
+
+/// Current retry policy
+pub fn retry_policy() -> RetryPolicy {
+    *RETRY_POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the retry policy (rejected if invalid)
+pub fn set_retry_policy(policy: RetryPolicy) -> Result<(), PolicyError> {
+    policy.validate()?;
+    *RETRY_POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    log::info!("Retry policy updated: {:?}", policy);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::DEFAULT
+        };
+        let mut rng = rand::thread_rng();
+        let delays: Vec<u64> = (1..=6)
+            .map(|a| policy.delay_for_attempt(a, &mut rng).as_secs())
+            .collect();
+        assert_eq!(delays, vec![2, 4, 8, 8, 8, 8]);
+        assert!(policy.should_retry(23));
+        assert!(!policy.should_retry(24));
+    }
+
+    #[test]
+    fn test_default_covers_old_fixed_window() {
+        // Before backoff: 24 attempts, 5s apart
+        let old_window = Duration::from_secs(24 * 5);
+        let policy = RetryPolicy::DEFAULT;
+        assert_eq!(policy.max_attempts, 24);
+
+        // 2 + 4 + 21 * 8 between the 24 attempts
+        assert_eq!(policy.max_total_delay(), Duration::from_secs(174));
+        assert_eq!(policy.min_total_delay(), Duration::from_millis(130_500));
+        assert!(policy.min_total_delay() >= old_window);
+
+        let mut rng = rand::thread_rng();
+        let sampled: Duration = (1..policy.max_attempts)
+            .map(|a| policy.delay_for_attempt(a, &mut rng))
+            .sum();
+        assert!(sampled >= policy.min_total_delay());
+        assert!(sampled <= policy.max_total_delay());
+    }
+
+    #[test]
+    fn test_jitter_stays_in_range() {
+        let policy = RetryPolicy::DEFAULT;
+        let mut rng = rand::thread_rng();
+        for attempt in 1..=20 {
+            let nominal = policy.nominal_delay(attempt);
+            let d = policy.delay_for_attempt(attempt, &mut rng);
+            assert!(d <= nominal);
+            assert!(d >= nominal.mul_f64(1.0 - policy.jitter));
+        }
+        // Huge attempt counts must not overflow.
+        assert_eq!(policy.nominal_delay(u32::MAX), policy.max_delay);
+    }
+
+    #[test]
+    fn test_invalid_policies_rejected() {
+        let zero = TimeoutPolicy {
+            instant_pong: Duration::ZERO,
+            ..TimeoutPolicy::DEFAULT
+        };
+        assert_eq!(
+            zero.validate(),
+            Err(PolicyError::ZeroTimeout("instant_pong"))
+        );
+
+        let bad = [
+            RetryPolicy {
+                max_attempts: 0,
+                ..RetryPolicy::DEFAULT
+            },
+            RetryPolicy {
+                base_delay: Duration::from_secs(30),
+                ..RetryPolicy::DEFAULT
+            },
+            RetryPolicy {
+                multiplier: 0.5,
+                ..RetryPolicy::DEFAULT
+            },
+            RetryPolicy {
+                jitter: 1.5,
+                ..RetryPolicy::DEFAULT
+            },
+        ];
+        for policy in bad {
+            assert!(policy.validate().is_err());
+            assert!(set_retry_policy(policy).is_err());
+        }
+        assert_eq!(retry_policy(), RetryPolicy::DEFAULT);
+    }
+}
@@ -0,0 +1,166 @@
+//! Auxiliary RPC
+//!
+//! The active protocol context's `RpcClient` and `RpcServer` (see
+//! `shield_protocol::protocol::rpc`) behind the JNI RPC API. Sealed frames
+//! travel as `MSG_TYPE_RPC` over the contact's existing connection and never
+//! touch the message ratchet; the app derives `RpcKeys` from the contact's
+//! shared secret for every call.
+//!
+//! Requests for methods registered in Rust are answered here. Anything else
+//! goes up to the app as `Incoming::Request`, which answers with `respond`
+//! (an app that does not know the method answers `NotFound`).
+
+use shield_protocol::protocol::rpc::{
+    RpcBody, RpcClient, RpcDispatch, RpcError, RpcFrame, RpcKeys, RpcReply, RpcServer, RpcStatus,
+};
+use std::sync::Mutex;
+
+use crate::ffi::context::active_context;
+
+/// Per-context RPC endpoints (lives in `ffi::context::ProtocolContext`)
+pub(crate) struct RpcState {
+    client: Mutex<RpcClient>,
+    server: Mutex<RpcServer>,
+}
+
+impl Default for RpcState {
+    fn default() -> Self {
+        RpcState {
+            client: Mutex::new(RpcClient::new(&mut rand::rngs::OsRng)),
+            server: Mutex::new(RpcServer::new()),
+        }
+    }
+}
+
+/// A decrypted `MSG_TYPE_RPC` frame, sorted by who has to act on it
+#[derive(Debug, PartialEq)]
+pub enum Incoming {
+    /// Answer to one of our calls
+    Reply(RpcReply),
+    /// A Rust handler answered; send this sealed response back
+    Respond(Vec<u8>),
+    /// Request for the app to answer with `respond`
+    Request {
+        request_id: u64,
+        method: String,
+        payload: Vec<u8>,
+    },
+    /// Replayed or stale request; send nothing
+    Dropped,
+}
+
+/// Answer `method` in Rust for every contact
+pub fn register<F>(method: &str, handler: F)
+where
+    F: Fn(&[u8; 32], &[u8]) -> Result<Vec<u8>, RpcStatus> + Send + Sync + 'static,
+{
+    active_context()
+        .rpc
+        .server
+        .lock()
+        .unwrap()
+        .register(method, handler);
+}
+
+pub fn unregister(method: &str) -> bool {
+    active_context()
+        .rpc
+        .server
+        .lock()
+        .unwrap()
+        .unregister(method)
+}
+
+/// Start a call to `peer`; returns the request ID and the sealed frame to
+/// send as `MSG_TYPE_RPC`
+pub fn call(
+    keys: &RpcKeys,
+    peer: [u8; 32],
+    method: &str,
+    payload: Vec<u8>,
+    now: u64,
+    timeout_secs: u64,
+) -> Result<(u64, Vec<u8>), RpcError> {
+    let frame = active_context().rpc.client.lock().unwrap().request(
+        peer,
+        method,
+        payload,
+        now,
+        timeout_secs,
+    )?;
+    Ok((frame.request_id, frame.seal(keys, &mut rand::rngs::OsRng)?))
+}
+
+/// Open and route a sealed frame received from `peer`
+pub fn receive(
+    keys: &RpcKeys,
+    peer: [u8; 32],
+    sealed: &[u8],
+    now: u64,
+) -> Result<Incoming, RpcError> {
+    let frame = RpcFrame::open(sealed, keys)?;
+    let method = match &frame.body {
+        RpcBody::Response { .. } => {
+            return Ok(Incoming::Reply(
+                active_context()
+                    .rpc
+                    .client
+                    .lock()
+                    .unwrap()
+                    .on_response(peer, frame)?,
+            ));
+        }
+        RpcBody::Request { method, .. } => method.clone(),
+    };
+
+    let ctx = active_context();
+
+    let mut server = ctx.rpc.server.lock().unwrap();
+    if server.has_method(&method) {
+        return match server.dispatch(peer, &frame, now)? {
+            RpcDispatch::Reply(response) => Ok(Incoming::Respond(
+                response.seal(keys, &mut rand::rngs::OsRng)?,
+            )),
+            RpcDispatch::Dropped => Ok(Incoming::Dropped),
+        };
+    }
+    if !server.admit(peer, &frame, now)? {
+        return Ok(Incoming::Dropped);
+    }
+    let RpcBody::Request { payload, .. } = frame.body else {
+        return Err(RpcError::Malformed);
+    };
+    Ok(Incoming::Request {
+        request_id: frame.request_id,
+        method,
+        payload,
+    })
+}
+
+/// Seal the app's answer to an `Incoming::Request`
+pub fn respond(
+    keys: &RpcKeys,
+    request_id: u64,
+    status: RpcStatus,
+    payload: Vec<u8>,
+) -> Result<Vec<u8>, RpcError> {
+    RpcFrame::response(request_id, status, payload).seal(keys, &mut rand::rngs::OsRng)
+}
+
+/// Forget calls past their timeout; returns how many were dropped
+pub fn expire(now: u64) -> usize {
+    let expired = active_context().rpc.client.lock().unwrap().expire(now);
+    for id in &expired {
+        log::debug!("RPC call {} timed out", id);
+    }
+    expired.len()
+}
+
+/// Abandon every outstanding call (panic wipe); returns how many there were
+pub fn clear() -> usize {
+    let ctx = active_context();
+    let mut client = ctx.rpc.client.lock().unwrap();
+    let abandoned = client.pending_len();
+    *client = RpcClient::new(&mut rand::rngs::OsRng);
+    abandoned
+}
@@ -0,0 +1,395 @@
+//! Security Events
+//!
+//! One queue for everything that should make the user look twice: a contact
+//! selecting a weaker cipher suite than its card advertises, an identity key
+//! change, a message whose identity continuity tag does not match the
+//! contact's pinned key chain, a burst of messages from a contact that fail
+//! to decrypt, replayed ciphertexts, and a cover-traffic rate no legitimate
+//! traffic profile produces. Each event carries a severity; the app drains
+//! the queue with `take_events_json` and decides what to surface.
+//!
+//! Emission is rate-limited per (kind, contact): after an event, the same
+//! kind for the same contact is only counted (`suppressed`) until
+//! `min_interval_secs` has passed, so a peer hammering us with bad
+//! ciphertexts produces one alarm a minute rather than thousands.
+//!
+//! The existing downgrade alarms (`downgrade::take_alarms`) are unchanged;
+//! downgrades are additionally reported here.
+
+use serde::Serialize;
+use shield_protocol::protocol::ContactId;
+use std::collections::{HashMap, VecDeque};
+
+use crate::ffi::context::active_context;
+
+/// Events kept until the app drains them; the oldest is dropped beyond this
+pub const MAX_PENDING_EVENTS: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    /// A contact selected a suite inconsistent with its signed card
+    CipherSuiteDowngrade { detail: String },
+    /// A contact's identity key changed; `blocked` if it was verified and
+    /// sends are now held until the user approves
+    IdentityKeyChanged { blocked: bool },
+    /// A message's continuity tag contradicts the contact's pinned identity
+    /// chain (see `crypto::key_continuity`)
+    KeyContinuityViolation { detail: String },
+    /// `count` messages from a contact failed to decrypt within `window_secs`
+    DecryptionFailures { count: u32, window_secs: u64 },
+    /// A ciphertext or request was seen before
+    ReplayDetected { detail: String },
+    /// `packets` cover packets arrived within `window_secs`
+    CoverTrafficAnomaly { packets: u32, window_secs: u64 },
+}
+
+impl SecurityEventKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::CipherSuiteDowngrade { .. } => Severity::Critical,
+            Self::IdentityKeyChanged { blocked: true } => Severity::Critical,
+            Self::IdentityKeyChanged { blocked: false } => Severity::Warning,
+            Self::KeyContinuityViolation { .. } => Severity::Critical,
+            Self::DecryptionFailures { .. } | Self::ReplayDetected { .. } => Severity::Warning,
+            Self::CoverTrafficAnomaly { .. } => Severity::Info,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::CipherSuiteDowngrade { .. } => "cipher_suite_downgrade",
+            Self::IdentityKeyChanged { .. } => "identity_key_changed",
+            Self::KeyContinuityViolation { .. } => "key_continuity_violation",
+            Self::DecryptionFailures { .. } => "decryption_failures",
+            Self::ReplayDetected { .. } => "replay_detected",
+            Self::CoverTrafficAnomaly { .. } => "cover_traffic_anomaly",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityEvent {
+    #[serde(flatten)]
+    pub kind: SecurityEventKind,
+    pub severity: Severity,
+    /// `None` for events not tied to one contact (e.g. cover traffic)
+    pub contact_id: Option<ContactId>,
+    pub timestamp: u64,
+    /// Events of this kind for this contact held back by rate limiting
+    /// since the previous one
+    pub suppressed: u32,
+}
+
+/// Thresholds and rate limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityEventPolicy {
+    /// Failed decryptions from one contact that raise an event...
+    pub decryption_failure_threshold: u32,
+    /// ...within this many seconds
+    pub decryption_failure_window_secs: u64,
+    /// Inbound cover packets per minute above which the rate is anomalous.
+    /// The paranoid profile sends at most one per contact every few seconds.
+    pub cover_packets_per_minute: u32,
+    /// Minimum spacing of events of one kind for one contact
+    pub min_interval_secs: u64,
+}
+
+impl Default for SecurityEventPolicy {
+    fn default() -> Self {
+        Self {
+            decryption_failure_threshold: 5,
+            decryption_failure_window_secs: 300,
+            cover_packets_per_minute: 600,
+            min_interval_secs: 60,
+        }
+    }
+}
+
+type RateKey = (&'static str, Option<ContactId>);
+
+/// Event queue with rate limiting and the counters behind threshold events
+#[derive(Debug, Default)]
+pub struct SecurityMonitor {
+    policy: SecurityEventPolicy,
+    pending: VecDeque<SecurityEvent>,
+    /// Last emission time and suppressed count per (kind, contact)
+    last_emitted: HashMap<RateKey, (u64, u32)>,
+    decryption_failures: HashMap<ContactId, VecDeque<u64>>,
+    /// Start of the current one-minute cover window and packets in it
+    cover_window: (u64, u32),
+}
+
+impl SecurityMonitor {
+    pub fn new(policy: SecurityEventPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> &SecurityEventPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SecurityEventPolicy) {
+        self.policy = policy;
+    }
+
+    /// Queue an event unless one of the same kind for the same contact was
+    /// emitted within `min_interval_secs`; returns whether it was queued
+    pub fn raise(
+        &mut self,
+        kind: SecurityEventKind,
+        contact_id: Option<ContactId>,
+        now: u64,
+    ) -> bool {
+        let key = (kind.name(), contact_id);
+        let suppressed = match self.last_emitted.get_mut(&key) {
+            Some((at, suppressed)) if now.saturating_sub(*at) < self.policy.min_interval_secs => {
+                *suppressed += 1;
+                return false;
+            }
+            Some((_, suppressed)) => *suppressed,
+            None => 0,
+        };
+        self.last_emitted.insert(key, (now, 0));
+
+        let event = SecurityEvent {
+            severity: kind.severity(),
+            kind,
+            contact_id,
+            timestamp: now,
+            suppressed,
+        };
+        match event.severity {
+            Severity::Critical => log::error!("Security event: {:?}", event),
+            Severity::Warning => log::warn!("Security event: {:?}", event),
+            Severity::Info => log::info!("Security event: {:?}", event),
+        }
+        if self.pending.len() == MAX_PENDING_EVENTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(event);
+        true
+    }
+
+    /// Count a message from `contact_id` that failed to decrypt
+    pub fn decryption_failure(&mut self, contact_id: ContactId, now: u64) {
+        let window = self.policy.decryption_failure_window_secs;
+        let failures = self.decryption_failures.entry(contact_id).or_default();
+        failures.push_back(now);
+        while failures
+            .front()
+            .is_some_and(|&at| now.saturating_sub(at) > window)
+        {
+            failures.pop_front();
+        }
+        let count = failures.len() as u32;
+        if count >= self.policy.decryption_failure_threshold {
+            failures.clear();
+            self.raise(
+                SecurityEventKind::DecryptionFailures {
+                    count,
+                    window_secs: window,
+                },
+                Some(contact_id),
+                now,
+            );
+        }
+    }
+
+    /// Count an inbound cover packet
+    pub fn cover_packet(&mut self, now: u64) {
+        let (start, packets) = &mut self.cover_window;
+        if now.saturating_sub(*start) >= 60 {
+            *start = now;
+            *packets = 0;
+        }
+        *packets += 1;
+        if *packets == self.policy.cover_packets_per_minute + 1 {
+            let packets = *packets;
+            self.raise(
+                SecurityEventKind::CoverTrafficAnomaly {
+                    packets,
+                    window_secs: 60,
+                },
+                None,
+                now,
+            );
+        }
+    }
+
+    /// Pending events, oldest first
+    pub fn take(&mut self) -> Vec<SecurityEvent> {
+        self.pending.drain(..).collect()
+    }
+
+    /// Forget a deleted contact's counters
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.decryption_failures.remove(contact_id);
+        self.last_emitted
+            .retain(|(_, c), _| c.as_ref() != Some(contact_id));
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.policy.clone());
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn policy() -> SecurityEventPolicy {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .policy()
+        .clone()
+}
+
+pub fn set_policy(policy: SecurityEventPolicy) {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .set_policy(policy);
+}
+
+pub fn raise(kind: SecurityEventKind, contact_id: Option<ContactId>) -> bool {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .raise(kind, contact_id, now_secs())
+}
+
+pub fn decryption_failure(contact_id: ContactId) {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .decryption_failure(contact_id, now_secs());
+}
+
+pub fn cover_packet() {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .cover_packet(now_secs());
+}
+
+pub fn take_events() -> Vec<SecurityEvent> {
+    active_context().security_events.lock().unwrap().take()
+}
+
+/// [`take_events`] as a JSON array:
+/// [{"kind",...,"severity","contactId","timestamp","suppressed"}]
+pub fn take_events_json() -> String {
+    serde_json::to_string(&take_events()).unwrap_or_else(|_| "[]".to_string())
+}
+
+pub fn forget(contact_id: &ContactId) {
+    active_context()
+        .security_events
+        .lock()
+        .unwrap()
+        .forget(contact_id);
+}
+
+/// Drop pending events and counters (e.g. on duress wipe)
+pub fn clear() {
+    active_context().security_events.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_contact;
+
+    #[test]
+    fn test_events_are_rate_limited_per_kind_and_contact() {
+        let mut monitor = SecurityMonitor::new(SecurityEventPolicy::default());
+        let changed = |blocked| SecurityEventKind::IdentityKeyChanged { blocked };
+
+        assert!(monitor.raise(changed(true), Some(test_contact(1)), 100));
+        assert!(!monitor.raise(changed(true), Some(test_contact(1)), 110));
+        assert!(!monitor.raise(changed(false), Some(test_contact(1)), 120));
+        // Other contacts and kinds are limited separately
+        assert!(monitor.raise(changed(false), Some(test_contact(2)), 120));
+        assert!(monitor.raise(
+            SecurityEventKind::ReplayDetected {
+                detail: "sequence 3 < 7".into()
+            },
+            Some(test_contact(1)),
+            120
+        ));
+        assert!(monitor.raise(changed(false), Some(test_contact(1)), 160));
+
+        let events = monitor.take();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].severity, Severity::Critical);
+        assert_eq!(events[1].severity, Severity::Warning);
+        assert_eq!(events[3].suppressed, 2);
+        assert!(monitor.take().is_empty());
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["kind"], "identity_key_changed");
+        assert_eq!(json["blocked"], true);
+        assert_eq!(json["severity"], "critical");
+        assert_eq!(json["contactId"], test_contact(1).to_string());
+    }
+
+    #[test]
+    fn test_threshold_events() {
+        let mut monitor = SecurityMonitor::new(SecurityEventPolicy {
+            decryption_failure_threshold: 3,
+            cover_packets_per_minute: 5,
+            ..Default::default()
+        });
+        // Failures spread beyond the window never add up
+        for t in [0, 400, 800, 1_200] {
+            monitor.decryption_failure(test_contact(1), t);
+        }
+        assert!(monitor.take().is_empty());
+        for t in [1_300, 1_301, 1_302] {
+            monitor.decryption_failure(test_contact(1), t);
+        }
+        let events = monitor.take();
+        assert_eq!(
+            events[0].kind,
+            SecurityEventKind::DecryptionFailures {
+                count: 3,
+                window_secs: 300
+            }
+        );
+
+        for _ in 0..20 {
+            monitor.cover_packet(1_000);
+        }
+        let events = monitor.take();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].contact_id, None);
+        assert_eq!(events[0].severity, Severity::Info);
+        // A new window starts from zero
+        for _ in 0..5 {
+            monitor.cover_packet(1_060);
+        }
+        assert!(monitor.take().is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Send Intents
+//!
+//! The active protocol context's write-ahead `IntentLog` (see
+//! `shield_protocol::storage::intent_log`) around the two-phase ratchet
+//! commit. The JNI send path records each ciphertext with
+//! [`record_send`] before it leaves the device, turns it into a commit
+//! intent on PING_ACK ([`acknowledge`]), and drops that once the app has
+//! persisted the advanced chain ([`persisted`]). A rolled-back send is
+//! dropped by [`abandon`].
+//!
+//! The app calls [`open`] at startup, before the first send, and gets back
+//! what a crash left behind:
+//! - `AdvanceRatchet`: the contact's chain must not resume below
+//!   `next_sequence`. The floor is kept here; [`record_send`] refuses sends
+//!   below it, and [`fast_forward`] moves a persisted chain up to it.
+//! - `Resend`: the exact ciphertext goes out again (never re-encrypted).
+//! - `MarkDelivered`: the peer had acknowledged; the app marks the message.
+//!
+//! The app passes the intent id of a resend or delivered mark to
+//! [`complete`] once it is done. Until [`open`] is called nothing is logged.
+
+use base64::Engine;
+use shield_protocol::protocol::ContactId;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::ffi::context::active_context;
+use crate::storage::{fast_forward_chain, Intent, IntentLog, IntentLogError, IntentStore};
+pub use crate::storage::RecoveryAction;
+
+#[derive(Error, Debug)]
+pub enum SendIntentError {
+    #[error(transparent)]
+    Log(#[from] IntentLogError),
+
+    #[error("Send chain of {contact_id} at {sequence} is below the recovered floor {floor}")]
+    BelowFloor {
+        contact_id: ContactId,
+        sequence: u64,
+        floor: u64,
+    },
+}
+
+type Log = IntentLog<Box<dyn IntentStore + Send>>;
+
+/// Per-context intent log (lives in `ffi::context::ProtocolContext`)
+#[derive(Default)]
+pub(crate) struct SendIntents {
+    log: Option<Log>,
+    /// Lowest sequence each contact's chain may resume at, from recovery
+    floors: HashMap<ContactId, u64>,
+    /// Send intent of the one pending advancement per contact
+    sends: HashMap<ContactId, u64>,
+    /// Commit intents waiting for the app to persist the chain
+    commits: HashMap<ContactId, u64>,
+}
+
+/// Attach `store` and return the recovery actions for intents it kept
+pub fn open(store: Box<dyn IntentStore + Send>) -> Result<Vec<RecoveryAction>, SendIntentError> {
+    let (log, actions) = IntentLog::open(store)?;
+    let mut floors = HashMap::new();
+    for action in &actions {
+        if let RecoveryAction::AdvanceRatchet {
+            contact_id,
+            next_sequence,
+        } = action
+        {
+            floors.insert(*contact_id, *next_sequence);
+        }
+    }
+    *active_context().send_intents.lock().unwrap() = SendIntents {
+        log: Some(log),
+        floors,
+        ..SendIntents::default()
+    };
+    Ok(actions)
+}
+
+/// Move a persisted send chain at `sequence` up to the contact's recovered
+/// floor; returns it unchanged if there is none or it is already there
+pub fn fast_forward(
+    contact_id: &ContactId,
+    chain_key: [u8; 32],
+    sequence: u64,
+) -> Result<([u8; 32], u64), SendIntentError> {
+    let floor = active_context()
+        .send_intents
+        .lock()
+        .unwrap()
+        .floors
+        .get(contact_id)
+        .copied()
+        .unwrap_or(0);
+    Ok(fast_forward_chain(chain_key, sequence, floor)?)
+}
+
+/// Record `ciphertext`, encrypted at `sequence`, before it is sent.
+/// Replaces the contact's previous send intent, as the pending advancement
+/// it belonged to is replaced too.
+pub fn record_send(
+    contact_id: &ContactId,
+    message_id: &str,
+    sequence: u64,
+    ciphertext: &[u8],
+) -> Result<(), SendIntentError> {
+    let ctx = active_context();
+    let mut intents = ctx.send_intents.lock().unwrap();
+    if let Some(&floor) = intents.floors.get(contact_id) {
+        if sequence < floor {
+            return Err(SendIntentError::BelowFloor {
+                contact_id: *contact_id,
+                sequence,
+                floor,
+            });
+        }
+    }
+    let Some(log) = intents.log.as_mut() else {
+        return Ok(());
+    };
+    let id = log.begin(Intent::SendMessage {
+        contact_id: *contact_id,
+        message_id: message_id.to_string(),
+        sequence,
+        ciphertext: ciphertext.to_vec(),
+    })?;
+    if let Some(previous) = intents.sends.insert(*contact_id, id) {
+        complete_logged(&mut intents, previous)?;
+    }
+    Ok(())
+}
+
+/// The peer acknowledged the pending send: keep a commit intent until
+/// [`persisted`]
+pub fn acknowledge(contact_id: &ContactId) -> Result<(), SendIntentError> {
+    let ctx = active_context();
+    let mut intents = ctx.send_intents.lock().unwrap();
+    let Some(send_id) = intents.sends.remove(contact_id) else {
+        return Ok(());
+    };
+    let Some(log) = intents.log.as_mut() else {
+        return Ok(());
+    };
+    let commit_id = log.acknowledge(send_id)?;
+    if let Some(previous) = intents.commits.insert(*contact_id, commit_id) {
+        complete_logged(&mut intents, previous)?;
+    }
+    Ok(())
+}
+
+/// The app persisted the chain returned by the commit
+pub fn persisted(contact_id: &ContactId) -> Result<(), SendIntentError> {
+    let ctx = active_context();
+    let mut intents = ctx.send_intents.lock().unwrap();
+    match intents.commits.remove(contact_id) {
+        Some(id) => complete_logged(&mut intents, id),
+        None => Ok(()),
+    }
+}
+
+/// The pending send was rolled back and will not be resent
+pub fn abandon(contact_id: &ContactId) -> Result<(), SendIntentError> {
+    let ctx = active_context();
+    let mut intents = ctx.send_intents.lock().unwrap();
+    match intents.sends.remove(contact_id) {
+        Some(id) => complete_logged(&mut intents, id),
+        None => Ok(()),
+    }
+}
+
+/// A recovered resend was acknowledged, or a delivered mark stored
+pub fn complete(intent_id: u64) -> Result<(), SendIntentError> {
+    let ctx = active_context();
+    let mut intents = ctx.send_intents.lock().unwrap();
+    complete_logged(&mut intents, intent_id)
+}
+
+fn complete_logged(intents: &mut SendIntents, id: u64) -> Result<(), SendIntentError> {
+    match intents.log.as_mut() {
+        Some(log) => Ok(log.complete(id)?),
+        None => Ok(()),
+    }
+}
+
+/// JSON for one recovery action; ciphertexts are base64
+pub fn action_json(action: &RecoveryAction) -> serde_json::Value {
+    match action {
+        RecoveryAction::AdvanceRatchet {
+            contact_id,
+            next_sequence,
+        } => serde_json::json!({
+            "type": "advanceRatchet",
+            "contactId": contact_id.to_string(),
+            "nextSequence": next_sequence,
+        }),
+        RecoveryAction::Resend {
+            intent_id,
+            contact_id,
+            message_id,
+            ciphertext,
+        } => serde_json::json!({
+            "type": "resend",
+            "intentId": intent_id,
+            "contactId": contact_id.to_string(),
+            "messageId": message_id,
+            "ciphertext": base64::engine::general_purpose::STANDARD.encode(ciphertext),
+        }),
+        RecoveryAction::MarkDelivered {
+            intent_id,
+            contact_id,
+            message_id,
+        } => serde_json::json!({
+            "type": "markDelivered",
+            "intentId": intent_id,
+            "contactId": contact_id.to_string(),
+            "messageId": message_id,
+        }),
+    }
+}
+
+/// Detach the log and forget floors and pending ids; stored intents stay
+pub fn clear() {
+    *active_context().send_intents.lock().unwrap() = SendIntents::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryIntentStore;
+    use crate::test_contact;
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    /// Memory store shared with the test, so it outlives a simulated crash
+    #[derive(Clone, Default)]
+    struct SharedStore(Arc<Mutex<MemoryIntentStore>>);
+
+    impl IntentStore for SharedStore {
+        fn append(&mut self, record: &crate::storage::IntentRecord) -> crate::storage::Result<()> {
+            self.0.lock().unwrap().append(record)
+        }
+
+        fn remove(&mut self, id: u64) -> crate::storage::Result<()> {
+            self.0.lock().unwrap().remove(id)
+        }
+
+        fn load_all(&self) -> crate::storage::Result<Vec<crate::storage::IntentRecord>> {
+            self.0.lock().unwrap().load_all()
+        }
+    }
+
+    /// The log belongs to the shared active context; tests touching it run
+    /// one at a time
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_crash_before_ack_resends_and_raises_floor() {
+        let _serial = serial();
+        let store = SharedStore::default();
+        assert!(open(Box::new(store.clone())).unwrap().is_empty());
+        let contact = test_contact(1);
+        record_send(&contact, "m1", 4, b"sealed").unwrap();
+
+        // Crash: the in-memory state is gone, the store is not.
+        clear();
+        let actions = open(Box::new(store.clone())).unwrap();
+        assert_eq!(
+            actions[0],
+            RecoveryAction::AdvanceRatchet {
+                contact_id: contact,
+                next_sequence: 5,
+            }
+        );
+        let json = action_json(&actions[1]);
+        assert_eq!(json["type"], "resend");
+        assert_eq!(
+            json["ciphertext"],
+            base64::engine::general_purpose::STANDARD.encode(b"sealed")
+        );
+
+        assert!(matches!(
+            record_send(&contact, "m2", 4, b"reused key"),
+            Err(SendIntentError::BelowFloor { floor: 5, .. })
+        ));
+        let (key, seq) = fast_forward(&contact, [9; 32], 4).unwrap();
+        assert_eq!(seq, 5);
+        assert_eq!((key, seq), fast_forward_chain([9; 32], 4, 5).unwrap());
+        record_send(&contact, "m2", seq, b"fresh").unwrap();
+
+        complete(json["intentId"].as_u64().unwrap()).unwrap();
+        assert_eq!(store.load_all().unwrap().len(), 1);
+        clear();
+    }
+
+    #[test]
+    fn test_ack_keeps_commit_until_persisted() {
+        let _serial = serial();
+        let store = SharedStore::default();
+        open(Box::new(store.clone())).unwrap();
+        let contact = test_contact(2);
+        record_send(&contact, "m1", 0, b"c1").unwrap();
+        acknowledge(&contact).unwrap();
+
+        clear();
+        let actions = open(Box::new(store.clone())).unwrap();
+        assert!(matches!(
+            &actions[1],
+            RecoveryAction::MarkDelivered { message_id, .. } if message_id == "m1"
+        ));
+
+        record_send(&contact, "m2", 1, b"c2").unwrap();
+        acknowledge(&contact).unwrap();
+        persisted(&contact).unwrap();
+        record_send(&contact, "m3", 2, b"c3").unwrap();
+        abandon(&contact).unwrap();
+        // Only the recovered delivered mark is left.
+        assert_eq!(store.load_all().unwrap().len(), 1);
+        clear();
+    }
+}
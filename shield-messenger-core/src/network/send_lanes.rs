@@ -0,0 +1,215 @@
+//! Priority-Aware Send Permits
+//!
+//! Replaces the flat send semaphore: outbound sends still share a fixed
+//! number of concurrent Tor connections, but when all permits are taken the
+//! next free one goes to the highest-priority waiter (see
+//! `shield_protocol::transport::priority`). A queue of image uploads no
+//! longer holds back delivery ACKs and pongs queued after it, and content
+//! for one conversation is dispatched in the order it was submitted.
+
+use shield_protocol::transport::priority::{Lane, LaneMetrics, OutboxConfig, PriorityOutbox};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+use super::bandwidth::BandwidthState;
+
+use super::tor::{
+    MSG_TYPE_ACK_BATCH, MSG_TYPE_CALL_SIGNALING, MSG_TYPE_CONTACT_BACKUP,
+    MSG_TYPE_DELIVERY_CONFIRMATION, MSG_TYPE_FILE_TRANSFER, MSG_TYPE_FRIEND_REQUEST,
+    MSG_TYPE_FRIEND_REQUEST_ACCEPTED, MSG_TYPE_IMAGE, MSG_TYPE_PING, MSG_TYPE_PONG,
+    MSG_TYPE_PROFILE_UPDATE, MSG_TYPE_ROUTING_REQUEST, MSG_TYPE_ROUTING_UPDATE,
+    MSG_TYPE_SYNC_CHUNK, MSG_TYPE_SYNC_REQUEST, MSG_TYPE_TAP, MSG_TYPE_VOICE,
+};
+use crate::ffi::context::active_context;
+
+/// Concurrent outbound sends (same cap as the previous semaphore)
+pub const MAX_CONCURRENT_SENDS: usize = 6;
+
+/// Lane for a wire message type
+pub fn lane_for_msg_type(msg_type: u8) -> Lane {
+    match msg_type {
+        MSG_TYPE_PING
+        | MSG_TYPE_PONG
+        | MSG_TYPE_CALL_SIGNALING
+        | MSG_TYPE_FRIEND_REQUEST
+        | MSG_TYPE_FRIEND_REQUEST_ACCEPTED
+        | MSG_TYPE_SYNC_REQUEST
+        | MSG_TYPE_ROUTING_REQUEST
+        | MSG_TYPE_ROUTING_UPDATE => Lane::Control,
+        MSG_TYPE_DELIVERY_CONFIRMATION | MSG_TYPE_ACK_BATCH | MSG_TYPE_TAP => Lane::Receipt,
+        MSG_TYPE_VOICE
+        | MSG_TYPE_IMAGE
+        | MSG_TYPE_PROFILE_UPDATE
+        | MSG_TYPE_SYNC_CHUNK
+        | MSG_TYPE_FILE_TRANSFER
+        | MSG_TYPE_CONTACT_BACKUP => Lane::Media,
+        // Text, stickers, payments, CRDT ops
+        _ => Lane::Text,
+    }
+}
+
+struct Inner {
+    available: usize,
+    waiters: PriorityOutbox<String, oneshot::Sender<()>>,
+}
+
+/// Pool of send permits handed out by lane priority
+pub struct LanePermits {
+    inner: Mutex<Inner>,
+    /// Pass sends through this upstream bandwidth cap first
+    cap: Option<Arc<BandwidthState>>,
+}
+
+/// Held for the duration of one send; returns the permit on drop
+pub struct LanePermit<'a> {
+    pool: &'a LanePermits,
+}
+
+impl Drop for LanePermit<'_> {
+    fn drop(&mut self) {
+        self.pool.release();
+    }
+}
+
+impl LanePermits {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                available: permits,
+                waiters: PriorityOutbox::new(OutboxConfig::default()),
+            }),
+            cap: None,
+        }
+    }
+
+    /// Make `acquire` wait for the upstream bandwidth cap (see `bandwidth`)
+    pub(crate) fn with_bandwidth_cap(mut self, cap: Arc<BandwidthState>) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Wait for a permit. `conversation` is the recipient (onion address);
+    /// `bytes` is the wire size, counted against the bandwidth cap and
+    /// reported in the metrics.
+    pub async fn acquire(&self, lane: Lane, conversation: &str, bytes: usize) -> LanePermit<'_> {
+        // Shaped before queueing for a permit, so a capped upload does not
+        // hold a connection slot while it waits for budget
+        if let Some(cap) = &self.cap {
+            super::bandwidth::reserve_in(cap, lane, conversation, bytes).await;
+        }
+        let rx = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.available > 0 && inner.waiters.is_empty() {
+                inner.available -= 1;
+                inner.waiters.record_bypass(lane, bytes);
+                return LanePermit { pool: self };
+            }
+            let (tx, rx) = oneshot::channel();
+            inner
+                .waiters
+                .push(lane, conversation.to_string(), tx, bytes, Instant::now());
+            rx
+        };
+        // The sender is only dropped after handing us the permit
+        let _ = rx.await;
+        LanePermit { pool: self }
+    }
+
+    fn release(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        while let Some((_, tx)) = inner.waiters.pop(Instant::now()) {
+            if tx.send(()).is_ok() {
+                return;
+            }
+            // Waiter went away; offer the permit to the next one
+        }
+        inner.available += 1;
+    }
+
+    /// Snapshot of per-lane metrics
+    pub fn metrics(&self) -> Vec<(Lane, LaneMetrics)> {
+        let inner = self.inner.lock().unwrap();
+        Lane::ALL
+            .iter()
+            .map(|&lane| (lane, inner.waiters.metrics(lane).clone()))
+            .collect()
+    }
+}
+
+/// Per-lane metrics of the active context's send permits as JSON
+/// Returns: {"control":{"enqueued":..,"dequeued":..,"bytes":..,"depth":..,"maxWaitMs":..,"meanWaitMs":..},...}
+pub fn metrics_json() -> String {
+    let lanes: Vec<String> = active_context()
+        .send_permits
+        .metrics()
+        .into_iter()
+        .map(|(lane, m)| {
+            format!(
+                r#""{}":{{"enqueued":{},"dequeued":{},"bytes":{},"depth":{},"maxWaitMs":{},"meanWaitMs":{}}}"#,
+                lane.name(),
+                m.enqueued,
+                m.dequeued,
+                m.bytes,
+                m.depth,
+                m.max_wait.as_millis(),
+                m.mean_wait().as_millis()
+            )
+        })
+        .collect();
+    format!("{{{}}}", lanes.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tor::MSG_TYPE_TEXT;
+
+    #[test]
+    fn test_lane_mapping() {
+        assert_eq!(lane_for_msg_type(MSG_TYPE_PONG), Lane::Control);
+        assert_eq!(
+            lane_for_msg_type(MSG_TYPE_DELIVERY_CONFIRMATION),
+            Lane::Receipt
+        );
+        assert_eq!(lane_for_msg_type(MSG_TYPE_TEXT), Lane::Text);
+        assert_eq!(lane_for_msg_type(MSG_TYPE_IMAGE), Lane::Media);
+        assert_eq!(lane_for_msg_type(MSG_TYPE_FILE_TRANSFER), Lane::Media);
+        assert_eq!(lane_for_msg_type(MSG_TYPE_CONTACT_BACKUP), Lane::Media);
+    }
+
+    #[tokio::test]
+    async fn test_ack_overtakes_queued_media() {
+        let pool = LanePermits::new(1);
+        let held = pool.acquire(Lane::Media, "alice", 1_000).await;
+
+        let order = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let (media, ack) = {
+            let (o1, o2) = (order.clone(), order.clone());
+            let pool = &pool;
+            (
+                async move {
+                    let _p = pool.acquire(Lane::Media, "alice", 1_000).await;
+                    o1.lock().unwrap().push("media");
+                },
+                async move {
+                    tokio::task::yield_now().await;
+                    let _p = pool.acquire(Lane::Receipt, "bob", 8).await;
+                    o2.lock().unwrap().push("ack");
+                },
+            )
+        };
+        let release = async move {
+            // Let both waiters queue up before the permit is returned
+            tokio::task::yield_now().await;
+            tokio::task::yield_now().await;
+            drop(held);
+        };
+        tokio::join!(media, ack, release);
+
+        assert_eq!(*order.lock().unwrap(), vec!["ack", "media"]);
+        let metrics = pool.metrics();
+        assert_eq!(metrics[Lane::Receipt.index()].1.dequeued, 1);
+        assert_eq!(metrics[Lane::Media.index()].1.enqueued, 2);
+    }
+}
@@ -0,0 +1,200 @@
+//! Network Silence
+//!
+//! The active protocol context's `NetworkSilence` (see
+//! `shield_protocol::protocol::silence`) enforced by the core, for stealth mode's `network_silence`. While silent:
+//!
+//! - `TorConnection::send` refuses everything but user-initiated message
+//!   types, and the cover-traffic and presence paths produce nothing.
+//! - The message listener reads each inbound frame and closes the
+//!   connection without a reply, as if the service were dead. The only frame
+//!   it looks at is a `MSG_TYPE_WAKE` from the designated contact, which
+//!   ends the silence (also without a reply).
+//!
+//! The onion service itself stays published so the wake message can arrive.
+//! Silence is not persisted: after a restart the app enters it again.
+
+use shield_protocol::protocol::silence::{SilenceError, SilenceSpec, TrafficClass, WakeMessage};
+use shield_protocol::transport::padding::MSG_TYPE_COVER;
+
+use super::tor::{
+    MSG_TYPE_ACK_BATCH, MSG_TYPE_DELIVERY_CONFIRMATION, MSG_TYPE_PONG, MSG_TYPE_PRESENCE,
+    MSG_TYPE_PROFILE_UPDATE, MSG_TYPE_ROUTING_REQUEST, MSG_TYPE_ROUTING_UPDATE, MSG_TYPE_RPC,
+    MSG_TYPE_SYNC_CHUNK, MSG_TYPE_SYNC_REQUEST, MSG_TYPE_TAP, MSG_TYPE_TOPIC, MSG_TYPE_WAKE,
+};
+use crate::ffi::context::active_context;
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Go silent until `end` or a valid wake from `spec.wake_contact`
+pub fn enter(spec: SilenceSpec, local_identity_key: [u8; 32]) {
+    log::info!("Network silence: entered");
+    active_context()
+        .silence
+        .lock()
+        .unwrap()
+        .enter(spec, local_identity_key, now_secs());
+}
+
+/// End silence locally
+pub fn end() {
+    active_context().silence.lock().unwrap().end();
+    log::info!("Network silence: ended locally");
+}
+
+pub fn is_silent() -> bool {
+    active_context().silence.lock().unwrap().is_silent()
+}
+
+/// Traffic class of an outbound wire message type
+pub fn class_for_msg_type(msg_type: u8) -> TrafficClass {
+    match msg_type {
+        MSG_TYPE_COVER => TrafficClass::Cover,
+        MSG_TYPE_DELIVERY_CONFIRMATION | MSG_TYPE_ACK_BATCH | MSG_TYPE_TAP | MSG_TYPE_PONG => {
+            TrafficClass::Receipt
+        }
+        MSG_TYPE_PRESENCE => TrafficClass::Presence,
+        MSG_TYPE_PROFILE_UPDATE
+        | MSG_TYPE_RPC
+        | MSG_TYPE_TOPIC
+        | MSG_TYPE_SYNC_REQUEST
+        | MSG_TYPE_SYNC_CHUNK
+        | MSG_TYPE_ROUTING_UPDATE
+        | MSG_TYPE_ROUTING_REQUEST => TrafficClass::Background,
+        // Messages, media, calls, payments, friend requests, group ops, wakes
+        _ => TrafficClass::UserInitiated,
+    }
+}
+
+/// Whether a message of this type may go out right now
+pub fn allows_msg_type(msg_type: u8) -> bool {
+    active_context()
+        .silence
+        .lock()
+        .unwrap()
+        .allows(class_for_msg_type(msg_type))
+}
+
+/// Whether traffic of this class may go out right now
+pub fn allows(class: TrafficClass) -> bool {
+    active_context().silence.lock().unwrap().allows(class)
+}
+
+/// Handle a wake payload (frame without its type byte) from the listener.
+/// Never answered; failures are logged at debug level only.
+pub fn try_wake(payload: &[u8]) -> bool {
+    match active_context()
+        .silence
+        .lock()
+        .unwrap()
+        .try_wake(payload, now_secs())
+    {
+        Ok(()) => {
+            log::info!("Network silence: ended by wake message");
+            true
+        }
+        Err(SilenceError::NotSilent) => false,
+        Err(e) => {
+            log::debug!("Network silence: wake rejected: {}", e);
+            false
+        }
+    }
+}
+
+/// Wire frame `[MSG_TYPE_WAKE][wake message]` waking the node whose
+/// identity key is `recipient_identity_key`
+pub fn wake_frame(
+    signing_key: &[u8],
+    recipient_identity_key: &[u8; 32],
+) -> Result<Vec<u8>, SilenceError> {
+    let wake = WakeMessage::create(
+        signing_key,
+        recipient_identity_key,
+        now_secs(),
+        &mut rand::rngs::OsRng,
+    )?
+    .to_bytes();
+    let mut frame = Vec::with_capacity(1 + wake.len());
+    frame.push(MSG_TYPE_WAKE);
+    frame.extend_from_slice(&wake);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair;
+    use crate::network::receipts;
+    use crate::network::tor::{MSG_TYPE_PING, MSG_TYPE_TEXT};
+    use crate::test_contact;
+    use shield_protocol::protocol::ContactId;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Tests share the active context's silence and run one at a time
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Silence woken by the returned (public, private) identity
+    fn enter_silence(local_identity_key: [u8; 32]) -> ([u8; 32], [u8; 32]) {
+        let (wake_public, wake_private) = generate_keypair();
+        let wake_contact = ContactId::from_identity_key(&wake_public).unwrap();
+        enter(
+            SilenceSpec::new(wake_contact, wake_public).unwrap(),
+            local_identity_key,
+        );
+        (wake_public, wake_private)
+    }
+
+    #[test]
+    fn test_gated_traffic_suppressed_while_silent() {
+        let _serial = serial();
+        let contact = test_contact(71);
+        receipts::record_received(contact, 1);
+
+        enter_silence(generate_keypair().0);
+        assert!(is_silent());
+        assert!(!allows(TrafficClass::Cover));
+        assert!(!allows_msg_type(MSG_TYPE_COVER));
+        assert!(!allows_msg_type(MSG_TYPE_PONG));
+        assert!(!allows_msg_type(MSG_TYPE_SYNC_REQUEST));
+        assert!(allows_msg_type(MSG_TYPE_TEXT));
+        assert!(allows_msg_type(MSG_TYPE_PING));
+        // Receipts check `allows` and stay queued
+        assert_eq!(receipts::take_piggyback(&contact), None);
+
+        // Held back, not dropped
+        end();
+        assert!(allows(TrafficClass::Receipt));
+        assert!(receipts::take_piggyback(&contact).is_some());
+    }
+
+    #[test]
+    fn test_wake_frame_ends_silence() {
+        let _serial = serial();
+        let (local_public, _) = generate_keypair();
+        let (_, wake_private) = enter_silence(local_public);
+
+        // Addressed to another node, or signed by someone else
+        let (other_public, other_private) = generate_keypair();
+        let misaddressed = wake_frame(&wake_private, &other_public).unwrap();
+        assert!(!try_wake(&misaddressed[1..]));
+        let forged = wake_frame(&other_private, &local_public).unwrap();
+        assert!(!try_wake(&forged[1..]));
+        assert!(is_silent());
+
+        // What the designated contact's app sends; the listener strips the
+        // type byte before handing it over
+        let frame = wake_frame(&wake_private, &local_public).unwrap();
+        assert_eq!(frame[0], MSG_TYPE_WAKE);
+        assert!(try_wake(&frame[1..]));
+        assert!(!is_silent());
+        assert!(allows(TrafficClass::Presence));
+        assert!(!try_wake(&frame[1..]));
+    }
+}
@@ -235,7 +235,9 @@ impl SleepModeManager {
 
     /// Whether cover traffic should be sent right now
     pub fn should_send_cover_traffic(&self) -> bool {
-        if !self.config.cover_traffic_enabled {
+        if !self.config.cover_traffic_enabled
+            || !super::silence::allows(shield_protocol::protocol::TrafficClass::Cover)
+        {
             return false;
         }
         // Send cover traffic in all states to maintain consistent traffic pattern
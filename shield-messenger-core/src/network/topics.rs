@@ -0,0 +1,119 @@
+//! Pub/Sub Topics
+//!
+//! The active protocol context's `TopicHub` (see
+//! `shield_protocol::protocol::topic`) behind the JNI topic API. Sealed
+//! frames travel as `MSG_TYPE_TOPIC` over the contact's existing connection;
+//! like RPC, the app derives `TopicKeys` from the contact's shared secret
+//! for every call.
+//!
+//! A publisher calls [`grant`] for each contact it admits and hands the
+//! token over (e.g. inside a message). The contact passes it to
+//! [`subscribe`] and sends the resulting frame back; from then on
+//! [`subscribers`] lists it and [`publish`] seals frames for it, one per
+//! subscriber.
+
+use shield_protocol::protocol::topic::{
+    CapabilityToken, TopicError, TopicEvent, TopicHub, TopicKeys,
+};
+
+use crate::ffi::context::active_context;
+
+/// Sign a capability token letting `subscriber` subscribe to `topic` until
+/// `expires_at`; returns the encoded token for the app to deliver
+pub fn grant(
+    signing_key: &[u8],
+    subscriber: [u8; 32],
+    topic: &str,
+    expires_at: u64,
+) -> Result<Vec<u8>, TopicError> {
+    active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .grant(signing_key, subscriber, topic, expires_at)?
+        .to_bytes()
+}
+
+/// Stop publishing `topic`; returns how many subscribers were dropped
+pub fn withdraw(topic: &str) -> usize {
+    active_context().topics.lock().unwrap().withdraw(topic)
+}
+
+/// Drop one subscriber of `topic`
+pub fn revoke(topic: &str, subscriber: &[u8; 32]) -> bool {
+    active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .revoke(topic, subscriber)
+}
+
+/// Current subscribers of `topic`, by identity key
+pub fn subscribers(topic: &str, now: u64) -> Vec<[u8; 32]> {
+    active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .subscribers(topic, now)
+}
+
+/// Subscribe with an encoded token from the publisher `keys` belong to;
+/// returns the sealed frame to send as `MSG_TYPE_TOPIC`
+pub fn subscribe(keys: &TopicKeys, token: &[u8], now: u64) -> Result<Vec<u8>, TopicError> {
+    let token = CapabilityToken::from_bytes(token)?;
+    active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .subscribe(keys, token, now, &mut rand::rngs::OsRng)
+}
+
+/// Leave `topic`; returns the sealed frame telling the publisher
+pub fn unsubscribe(keys: &TopicKeys, topic: &str, now: u64) -> Result<Vec<u8>, TopicError> {
+    active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .unsubscribe(keys, topic, now, &mut rand::rngs::OsRng)
+}
+
+/// Seal `payload` on `topic` for the subscriber `keys` belong to
+pub fn publish(
+    keys: &TopicKeys,
+    topic: &str,
+    payload: Vec<u8>,
+    now: u64,
+) -> Result<Vec<u8>, TopicError> {
+    active_context().topics.lock().unwrap().publish(
+        keys,
+        topic,
+        payload,
+        now,
+        &mut rand::rngs::OsRng,
+    )
+}
+
+/// Open and apply a `MSG_TYPE_TOPIC` frame from the contact `keys` belong to
+pub fn receive(keys: &TopicKeys, sealed: &[u8], now: u64) -> Result<TopicEvent, TopicError> {
+    let event = active_context()
+        .topics
+        .lock()
+        .unwrap()
+        .receive(keys, sealed, now)?;
+    match &event {
+        TopicEvent::Subscribed { topic, .. } => log::debug!("New subscriber on topic {}", topic),
+        TopicEvent::Unsubscribed { topic } => log::debug!("Subscriber left topic {}", topic),
+        TopicEvent::Message { .. } | TopicEvent::Dropped => {}
+    }
+    Ok(event)
+}
+
+/// Forget a deleted contact as subscriber and publisher
+pub fn forget(peer: &[u8; 32]) {
+    active_context().topics.lock().unwrap().forget(peer);
+}
+
+/// Drop every topic, subscriber and subscription (e.g. on duress wipe)
+pub fn clear() {
+    *active_context().topics.lock().unwrap() = TopicHub::new();
+}
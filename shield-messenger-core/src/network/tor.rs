@@ -27,6 +27,9 @@ use std::collections::HashMap;
 use std::sync::Mutex as StdMutex;
 use tokio::sync::Mutex;
 
+use super::tor_control::{self, ControlAuth, TorControlError};
+use crate::redact;
+
 /// Global bootstrap status (0-100%) - updated by event listener
 pub static BOOTSTRAP_STATUS: AtomicU32 = AtomicU32::new(0);
 
@@ -96,8 +99,8 @@ pub fn get_bootstrap_status_fast() -> u32 {
 /// then queries GETINFO status/bootstrap-phase every 500ms until 100%.
 #[cfg(unix)]
 fn bootstrap_direct_poll_loop() {
-    use std::io::{Read, Write};
     use std::time::Duration;
+    use tor_control::{ControlAddress, TorControl, TorControlConfig};
 
     // Get ControlSocket path from global (set by Kotlin via JNI)
     let socket_path = match CONTROL_SOCKET_PATH.lock().ok().and_then(|g| g.clone()) {
@@ -108,101 +111,76 @@ fn bootstrap_direct_poll_loop() {
         }
     };
 
-    // Try to connect (retry up to 30 times = 6 seconds at 200ms)
-    let mut stream = None;
-    for _ in 0..30 {
-        match std::os::unix::net::UnixStream::connect(&socket_path) {
-            Ok(s) => {
-                stream = Some(s);
-                break;
-            }
-            Err(_) => {
-                std::thread::sleep(Duration::from_millis(200));
-            }
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            log::warn!("Direct poll: Failed to create runtime: {}", e);
+            return;
         }
-    }
-
-    let mut stream = match stream {
-        Some(s) => s,
-        None => return, // ControlSocket not available
     };
 
-    let _ = stream.set_read_timeout(Some(Duration::from_millis(500)));
-    let _ = stream.set_write_timeout(Some(Duration::from_millis(300)));
+    runtime.block_on(async {
+        let config = TorControlConfig {
+            address: ControlAddress::Unix(socket_path.into()),
+            auth: control_auth(),
+            timeout: Duration::from_millis(500),
+            reconnect_attempts: 1,
+            ..TorControlConfig::default()
+        };
 
-    // Authenticate — GP tor-android 0.4.9.5 uses CookieAuthentication 0 (empty auth)
-    if stream.write_all(b"AUTHENTICATE\r\n").is_err() {
-        return;
-    }
-    let mut buf = vec![0u8; 2048];
-    let n = match stream.read(&mut buf) {
-        Ok(n) => n,
-        Err(_) => return,
-    };
-    let response = String::from_utf8_lossy(&buf[..n]);
-    if !response.contains("250 OK") {
-        return;
-    }
+        // Try to connect and authenticate (up to 30 times = 6 seconds at 200ms)
+        let mut control = None;
+        for _ in 0..30 {
+            match TorControl::connect(config.clone()).await {
+                Ok(c) => {
+                    control = Some(c);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+        let Some(mut control) = control else {
+            return; // ControlSocket not available
+        };
 
-    log::info!("Direct bootstrap poller: connected and authenticated");
+        log::info!("Direct bootstrap poller: connected and authenticated");
 
-    // Poll loop: query every 500ms until bootstrap >= 100 or 20 iterations (10s)
-    for i in 0..20 {
-        // If the event listener has already updated the atomic, we can stop
-        let current = BOOTSTRAP_STATUS.load(Ordering::SeqCst);
-        if current >= 100 {
-            log::info!(
-                "Direct bootstrap poller: event listener caught up ({}%), stopping",
-                current
-            );
-            break;
-        }
+        // Poll loop: query every 500ms until bootstrap >= 100 or 20 iterations (10s)
+        for i in 0..20 {
+            // If the event listener has already updated the atomic, we can stop
+            let current = BOOTSTRAP_STATUS.load(Ordering::SeqCst);
+            if current >= 100 {
+                log::info!(
+                    "Direct bootstrap poller: event listener caught up ({}%), stopping",
+                    current
+                );
+                break;
+            }
 
-        // Send GETINFO
-        if stream
-            .write_all(b"GETINFO status/bootstrap-phase\r\n")
-            .is_err()
-        {
-            break;
-        }
+            let phase = match control.get_info(&["status/bootstrap-phase"]).await {
+                Ok(mut values) => values.remove("status/bootstrap-phase").unwrap_or_default(),
+                Err(_) => break,
+            };
 
-        // Accumulate response until "250 OK"
-        let mut resp = String::new();
-        loop {
-            let n = match stream.read(&mut buf) {
-                Ok(0) => {
-                    resp.clear();
-                    break;
+            if let Some(progress) = parse_bootstrap_progress(&phase) {
+                let old = BOOTSTRAP_STATUS.swap(progress, Ordering::SeqCst);
+                if progress != old {
+                    log::info!("Direct bootstrap poller: {}% (poll #{})", progress, i + 1);
                 }
-                Ok(n) => n,
-                Err(_) => {
+                if progress >= 100 {
                     break;
                 }
-            };
-            resp.push_str(&String::from_utf8_lossy(&buf[..n]));
-            if resp.contains("\r\n250 OK\r\n") || resp.ends_with("250 OK\r\n") {
-                break;
-            }
-            if resp.len() > 8192 {
-                break;
             }
-        }
 
-        if let Some(progress) = parse_bootstrap_progress(&resp) {
-            let old = BOOTSTRAP_STATUS.swap(progress, Ordering::SeqCst);
-            if progress != old {
-                log::info!("Direct bootstrap poller: {}% (poll #{})", progress, i + 1);
-            }
-            if progress >= 100 {
-                break;
-            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
         }
 
-        std::thread::sleep(Duration::from_millis(500));
-    }
-
-    let _ = stream.write_all(b"QUIT\r\n");
-    log::info!("Direct bootstrap poller: done");
+        let _ = control.command("QUIT").await;
+        log::info!("Direct bootstrap poller: done");
+    });
 }
 
 /// Get circuit established status from the global atomic (fast, no control port query)
@@ -215,6 +193,9 @@ pub fn get_circuit_established_fast() -> u8 {
 /// v3 onion services upload to multiple HSDirs (typically 6-8), so count >= 1 means partially reachable
 pub static HS_DESC_UPLOAD_COUNT: AtomicU32 = AtomicU32::new(0);
 
+/// HS descriptor upload failures since the last reset (one per HSDir that rejected or timed out)
+pub static HS_DESC_FAILURE_COUNT: AtomicU32 = AtomicU32::new(0);
+
 /// Guard: ensures only one event listener is spawned at a time
 /// swap(true) returns the old value — if it was already true, a listener is running
 static EVENT_LISTENER_RUNNING: AtomicBool = AtomicBool::new(false);
@@ -254,14 +235,68 @@ static CONTROL_COOKIE_HEX: StdMutex<Option<String>> = StdMutex::new(None);
 /// GP tor-android 0.4.9.5 uses --ControlSocket instead of TCP ControlPort
 static CONTROL_SOCKET_PATH: StdMutex<Option<String>> = StdMutex::new(None);
 
+/// How ControlSocket connections authenticate. `Auto` reads PROTOCOLINFO, so
+/// tor-android's empty auth and a system tor's SAFECOOKIE both work
+/// unconfigured; a COOKIE-only daemon needs `ControlAuth::Cookie(Some(path))`.
+static CONTROL_AUTH: StdMutex<ControlAuth> = StdMutex::new(ControlAuth::Auto);
+
+/// Budget for the PROTOCOLINFO / AUTHENTICATE exchange on a new connection
+const CONTROL_AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Override ControlSocket authentication (e.g. a HashedControlPassword daemon)
+pub fn set_control_auth(auth: ControlAuth) {
+    log::info!("ControlSocket auth set to: {:?}", auth);
+    *CONTROL_AUTH.lock().unwrap_or_else(|e| e.into_inner()) = auth;
+}
+
+fn control_auth() -> ControlAuth {
+    CONTROL_AUTH
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// `host:port` of the voice Tor daemon's control port, if not the default
+/// `127.0.0.1:PORT_CONTROL_VOICE`
+static VOICE_CONTROL_ADDRESS: StdMutex<Option<String>> = StdMutex::new(None);
+
+/// Override the voice Tor control address (`host:port`)
+pub fn set_voice_control_address(address: &str) {
+    log::info!("Voice control address set to: {}", address);
+    *VOICE_CONTROL_ADDRESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(address.to_string());
+}
+
+fn voice_control_address() -> String {
+    VOICE_CONTROL_ADDRESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| format!("127.0.0.1:{}", PORT_CONTROL_VOICE))
+}
+
+/// Authenticate a freshly opened ControlSocket connection
+async fn authenticate_control(stream: &mut ControlStream) -> Result<(), TorControlError> {
+    tor_control::authenticate_stream(stream, &control_auth(), CONTROL_AUTH_TIMEOUT)
+        .await
+        .map(|_| ())
+}
+
 /// Get HS descriptor upload count (fast, no control port query)
 pub fn get_hs_desc_upload_count() -> u32 {
     HS_DESC_UPLOAD_COUNT.load(Ordering::SeqCst)
 }
 
-/// Reset HS descriptor upload counter (call before creating a new hidden service)
+/// Get HS descriptor upload failure count (fast, no control port query)
+pub fn get_hs_desc_failure_count() -> u32 {
+    HS_DESC_FAILURE_COUNT.load(Ordering::SeqCst)
+}
+
+/// Reset HS descriptor upload counters (call before creating a new hidden service)
 pub fn reset_hs_desc_upload_count() {
     HS_DESC_UPLOAD_COUNT.store(0, Ordering::SeqCst);
+    HS_DESC_FAILURE_COUNT.store(0, Ordering::SeqCst);
 }
 
 /// Tor event types for ControlPort event monitoring
@@ -444,20 +479,9 @@ async fn bootstrap_event_listener_task() -> Result<(), Box<dyn Error + Send + Sy
             }
         };
 
-        // ----- Phase 2: Authenticate (empty auth — GP CookieAuthentication 0) -----
-        if let Err(e) = control.write_all(b"AUTHENTICATE\r\n").await {
-            log::error!("Event listener: Failed to send auth command: {}", e);
-            invalidate_listener_state();
-            sleep(Duration::from_millis(backoff_ms)).await;
-            backoff_ms = apply_backoff_with_jitter(backoff_ms);
-            consecutive_failures += 1;
-            continue;
-        }
-
-        let mut buf = vec![0u8; 1024];
-        let auth_ok = read_until_250_ok(&mut control, &mut buf, Duration::from_secs(3)).await;
-        if !auth_ok {
-            log::error!("Event listener: Auth failed, will retry");
+        // ----- Phase 2: Authenticate (method picked from PROTOCOLINFO) -----
+        if let Err(e) = authenticate_control(&mut control).await {
+            log::error!("Event listener: Auth failed ({}), will retry", e);
             invalidate_listener_state();
             sleep(Duration::from_millis(backoff_ms)).await;
             backoff_ms = apply_backoff_with_jitter(backoff_ms);
@@ -466,6 +490,7 @@ async fn bootstrap_event_listener_task() -> Result<(), Box<dyn Error + Send + Sy
         }
 
         log::info!("Event listener: Authenticated to control port");
+        let mut buf = vec![0u8; 1024];
 
         // ----- Phase 3: Subscribe to events -----
         if let Err(e) = control
@@ -660,10 +685,11 @@ async fn bootstrap_event_listener_task() -> Result<(), Box<dyn Error + Send + Sy
                                             }
                                             TorEventType::HsDescUploaded { address } => {
                                                 let count = HS_DESC_UPLOAD_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
-                                                log::info!("HS descriptor UPLOADED: {} (total: {}/6 HSDirs)", address, count);
+                                                log::info!("HS descriptor UPLOADED: {} (total: {}/6 HSDirs)", redact::onion(&address), count);
                                             }
                                             TorEventType::HsDescUploadFailed { address, reason } => {
-                                                log::warn!("HS descriptor UPLOAD FAILED: {} (reason: {})", address, reason);
+                                                HS_DESC_FAILURE_COUNT.fetch_add(1, Ordering::SeqCst);
+                                                log::warn!("HS descriptor UPLOAD FAILED: {} (reason: {})", redact::onion(&address), reason);
                                             }
                                             TorEventType::StatusGeneral { severity, message } => {
                                                 log::info!("STATUS_GENERAL [{}]: {}", severity, message);
@@ -705,12 +731,6 @@ async fn bootstrap_event_listener_task() -> Result<(), Box<dyn Error + Send + Sy
     Ok(())
 }
 
-/// Build the auth command for main Tor control connection.
-/// GP tor-android 0.4.9.5 uses --CookieAuthentication 0 (empty auth).
-fn build_control_auth_command() -> String {
-    "AUTHENTICATE\r\n".to_string()
-}
-
 /// Clear stale atomics when the control port connection is lost.
 /// Clears CIRCUIT_ESTABLISHED (health-critical) and heartbeat (staleness detection).
 /// Does NOT clear BOOTSTRAP_STATUS — Tor daemon may still be running fine;
@@ -847,7 +867,7 @@ fn apply_backoff_with_jitter(current_ms: u64) -> u64 {
 }
 
 /// Parse bootstrap progress percentage from Tor control response/event
-fn parse_bootstrap_progress(response: &str) -> Option<u32> {
+pub(super) fn parse_bootstrap_progress(response: &str) -> Option<u32> {
     // Look for PROGRESS=XX in the response
     if let Some(progress_str) = response.split("PROGRESS=").nth(1) {
         if let Some(percentage_str) = progress_str.split_whitespace().next() {
@@ -995,6 +1015,15 @@ pub const MSG_TYPE_PAYMENT_ACCEPTED: u8 = 0x0C;
 pub const MSG_TYPE_CALL_SIGNALING: u8 = 0x0D; // Voice call signaling (OFFER/ANSWER/REJECT/END/BUSY)
 pub const MSG_TYPE_STICKER: u8 = 0x0E; // Sticker/GIF message (asset path as payload)
 pub const MSG_TYPE_PROFILE_UPDATE: u8 = 0x0F; // Profile photo update (hidden, not shown in chat)
+pub const MSG_TYPE_PRESENCE: u8 = 0x10; // Presence beacon (hidden, opt-in; see network::presence)
+pub const MSG_TYPE_WAKE: u8 = 0x11; // Wake from network silence (see network::silence)
+pub const MSG_TYPE_ACK_BATCH: u8 = 0x12; // Batched delivery receipts (see network::receipts)
+pub const MSG_TYPE_REACTION: u8 = 0x13; // 1:1 reaction to a message (see network::reactions)
+pub const MSG_TYPE_RPC: u8 = 0x14; // Auxiliary RPC request/response (see network::rpc)
+pub const MSG_TYPE_FILE_TRANSFER: u8 = 0x15; // File offer/chunk/ack frame (see network::file_transfer)
+pub const MSG_TYPE_CONTACT_BACKUP: u8 = 0x16; // Contact backup request to a relay (see network::contact_backup)
+pub const MSG_TYPE_TOPIC: u8 = 0x17; // Pub/sub topic frame (see network::topics)
+pub const MSG_TYPE_RECALL: u8 = 0x18; // Message recall request/ack (see network::recall)
 
 // CRDT group wire types (not per-member encrypted — ops are Ed25519-signed, content is XChaCha20 group-secret encrypted)
 pub const MSG_TYPE_CRDT_OPS: u8 = 0x30; // CRDT op bundle: [groupId:32][packedOps]
@@ -1030,7 +1059,29 @@ pub async fn connect_to_onion(
         format!("SOCKS proxy unreachable: {}", e)
     })?;
 
-    socks5_handshake(&mut stream, onion_address, port).await?;
+    socks5_handshake(&mut stream, onion_address, port, None).await?;
+
+    Ok(TorConnection {
+        stream,
+        onion_address: onion_address.to_string(),
+        port,
+    })
+}
+
+/// Like `connect_to_onion`, but on a circuit of its own: Tor isolates
+/// streams by SOCKS credentials (IsolateSOCKSAuth is on by default), so a
+/// fresh `isolation` string never shares a circuit with earlier connections.
+pub async fn connect_to_onion_isolated(
+    onion_address: &str,
+    port: u16,
+    isolation: &str,
+) -> Result<TorConnection, Box<dyn Error + Send + Sync>> {
+    let socks_addr = format!("127.0.0.1:{}", PORT_SOCKS);
+    let mut stream = TcpStream::connect(&socks_addr)
+        .await
+        .map_err(|e| format!("SOCKS proxy unreachable: {}", e))?;
+
+    socks5_handshake(&mut stream, onion_address, port, Some(isolation)).await?;
 
     Ok(TorConnection {
         stream,
@@ -1040,20 +1091,37 @@ pub async fn connect_to_onion(
 }
 
 /// Standalone SOCKS5 handshake — pure protocol, no TorManager state.
+/// `isolation` is sent as the username/password pair (RFC 1929).
 async fn socks5_handshake(
     stream: &mut TcpStream,
     addr: &str,
     port: u16,
+    isolation: Option<&str>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // SOCKS5 greeting: version 5, 1 method, no auth
-    stream.write_all(&[0x05, 0x01, 0x00]).await?;
+    // SOCKS5 greeting: version 5, 1 method, no auth or username/password
+    let method = if isolation.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await?;
 
     let mut buf = [0u8; 2];
     stream.read_exact(&mut buf).await?;
-    if buf[0] != 0x05 || buf[1] != 0x00 {
+    if buf[0] != 0x05 || buf[1] != method {
         return Err(format!("SOCKS5 auth failed: version={}, method={}", buf[0], buf[1]).into());
     }
 
+    if let Some(isolation) = isolation {
+        let credential = &isolation.as_bytes()[..isolation.len().min(255)];
+        let mut auth = vec![0x01, credential.len() as u8];
+        auth.extend_from_slice(credential);
+        auth.push(credential.len() as u8);
+        auth.extend_from_slice(credential);
+        stream.write_all(&auth).await?;
+
+        stream.read_exact(&mut buf).await?;
+        if buf[1] != 0x00 {
+            return Err(format!("SOCKS5 isolation auth rejected: status={}", buf[1]).into());
+        }
+    }
+
     // SOCKS5 CONNECT request: VER CMD RSV ATYP(domain) LEN ADDR PORT
     let mut request = vec![0x05, 0x01, 0x00, 0x03];
     request.push(addr.len() as u8);
@@ -1294,6 +1362,7 @@ pub struct TorManager {
 impl TorManager {
     /// Initialize Tor manager
     pub fn new() -> Result<Self, Box<dyn Error>> {
+        let ports = super::ports::ports();
         Ok(TorManager {
             control_stream: None,
             voice_control_stream: None, // VOICE TOR initialized separately
@@ -1307,10 +1376,10 @@ impl TorManager {
                 voice_hs_address: None,
                 subscribed_events: Vec::new(),
             },
-            hs_service_port: PORT_HS_PING_PONG, // 9150: PING/PONG/ACK
-            hs_local_port: PORT_LOCAL_HS,       // 8080: Local listener
-            socks_port: PORT_SOCKS,             // 9050: SOCKS proxy
-            bound_port: None,                   // No port bound initially
+            hs_service_port: ports.messaging, // 9150 by default: PING/PONG/ACK
+            hs_local_port: ports.local_listener, // 8080 by default: Local listener
+            socks_port: PORT_SOCKS,           // 9050: SOCKS proxy
+            bound_port: None,                 // No port bound initially
         })
     }
 
@@ -1368,24 +1437,14 @@ impl TorManager {
         // Connect to ControlSocket (Unix domain socket)
         let mut control = ControlStream::connect(&socket_path).await?;
 
-        // Authenticate with empty auth (GP CookieAuthentication 0)
-        control.write_all(b"AUTHENTICATE\r\n").await?;
-
-        let mut buf = vec![0u8; 1024];
-        let n = control.read(&mut buf).await?;
-        let response = String::from_utf8_lossy(&buf[..n]);
-
-        if !response.contains("250 OK") {
-            return Err(format!(
-                "ControlSocket authentication failed after reconnect: {}",
-                response
-            )
-            .into());
-        }
+        authenticate_control(&mut control)
+            .await
+            .map_err(|e| format!("ControlSocket authentication failed after reconnect: {}", e))?;
 
         log::info!("Reconnected to Tor ControlSocket");
 
         // Re-subscribe to previously subscribed events
+        let mut buf = vec![0u8; 1024];
         for event in &self.hs_state.subscribed_events.clone() {
             let command = format!("SETEVENTS {}\r\n", event);
             control.write_all(command.as_bytes()).await?;
@@ -1426,7 +1485,7 @@ impl TorManager {
         let n = stream.read(&mut buf).await?;
         let response = String::from_utf8_lossy(&buf[..n]);
 
-        log::info!("Existing onion services: {}", response);
+        log::info!("Existing onion services: {}", redact::scrub(&response));
 
         // Parse service IDs from response
         let mut existing_services = Vec::new();
@@ -1469,7 +1528,10 @@ impl TorManager {
 
         // Delete orphaned services
         for service_id in orphaned {
-            log::warn!("Deleting orphaned hidden service: {}", service_id);
+            log::warn!(
+                "Deleting orphaned hidden service: {}",
+                redact::onion(&service_id)
+            );
             let del_command = format!("DEL_ONION {}\r\n", service_id);
             stream.write_all(del_command.as_bytes()).await?;
 
@@ -1477,7 +1539,7 @@ impl TorManager {
             let del_response = String::from_utf8_lossy(&buf[..n]);
 
             if del_response.contains("250 OK") {
-                log::info!("Deleted orphaned service: {}", service_id);
+                log::info!("Deleted orphaned service: {}", redact::onion(&service_id));
                 // Guess whether it was message or voice based on expected (heuristic)
                 if service_id.len() > 16 {
                     // v3 onion addresses are 56 chars
@@ -1486,7 +1548,11 @@ impl TorManager {
                     voice_deleted += 1;
                 }
             } else {
-                log::warn!("Failed to delete service {}: {}", service_id, del_response);
+                log::warn!(
+                    "Failed to delete service {}: {}",
+                    redact::onion(&service_id),
+                    redact::scrub(&del_response)
+                );
             }
         }
 
@@ -1518,16 +1584,9 @@ impl TorManager {
         // Connect to ControlSocket (GP tor-android creates this Unix domain socket)
         let mut control = ControlStream::connect(&socket_path).await?;
 
-        // Authenticate with empty auth (GP uses CookieAuthentication 0)
-        control.write_all(b"AUTHENTICATE\r\n").await?;
-
-        let mut buf = vec![0u8; 1024];
-        let n = control.read(&mut buf).await?;
-        let response = String::from_utf8_lossy(&buf[..n]);
-
-        if !response.contains("250 OK") {
-            return Err(format!("ControlSocket authentication failed: {}", response).into());
-        }
+        authenticate_control(&mut control)
+            .await
+            .map_err(|e| format!("ControlSocket authentication failed: {}", e))?;
 
         self.control_stream = Some(Arc::new(Mutex::new(control)));
 
@@ -1558,42 +1617,34 @@ impl TorManager {
     /// CRITICAL: Failures in one should not affect the other
     /// ============================================================================
 
-    /// Connect to VOICE Tor control port (port 9052 - Single Onion Service instance)
+    /// Connect to VOICE Tor control port (port 9052 by default, see
+    /// `set_voice_control_address` - Single Onion Service instance)
     /// This is a separate Tor daemon specifically for voice hidden service
     /// Must be called AFTER voice Tor daemon is started by TorManager.kt
     pub async fn initialize_voice_control(
         &mut self,
         cookie_path: &str,
     ) -> Result<String, Box<dyn Error>> {
-        log::info!("Connecting to VOICE Tor control port (9052)...");
+        let address = voice_control_address();
+        log::info!("Connecting to VOICE Tor control port ({})...", address);
 
         // Connect to voice Tor control port
-        let mut control = TcpStream::connect("127.0.0.1:9052").await?;
-
-        // Read voice Tor cookie file (path provided by Kotlin caller)
-        log::info!("Reading voice Tor cookie from: {}", cookie_path);
-        let cookie = std::fs::read(cookie_path)?;
-
-        // Hex-encode the cookie
-        let cookie_hex = hex::encode(&cookie);
+        let mut control = TcpStream::connect(&address).await?;
 
-        // Authenticate with cookie
-        let auth_cmd = format!("AUTHENTICATE {}\r\n", cookie_hex);
-        control.write_all(auth_cmd.as_bytes()).await?;
-
-        let mut buf = vec![0u8; 1024];
-        let n = control.read(&mut buf).await?;
-        let response = String::from_utf8_lossy(&buf[..n]);
-
-        if !response.contains("250 OK") {
-            return Err(
-                format!("Voice Tor control port authentication failed: {}", response).into(),
-            );
-        }
+        // Authenticate with the voice Tor cookie (path provided by Kotlin caller);
+        // SAFECOOKIE is used whenever the daemon offers it
+        log::info!("Using voice Tor cookie from: {}", cookie_path);
+        let auth = ControlAuth::Cookie(Some(cookie_path.into()));
+        tor_control::authenticate_stream(&mut control, &auth, CONTROL_AUTH_TIMEOUT)
+            .await
+            .map_err(|e| format!("Voice Tor control port authentication failed: {}", e))?;
 
         self.voice_control_stream = Some(Arc::new(Mutex::new(control)));
 
-        log::info!("Connected to VOICE Tor control port (9052) successfully");
+        log::info!(
+            "Connected to VOICE Tor control port ({}) successfully",
+            address
+        );
         Ok("Voice Tor control ready (Single Onion Service mode)".to_string())
     }
 
@@ -1737,17 +1788,19 @@ impl TorManager {
             let mut stream = control.lock().await;
 
             // Build port mappings, deduplicating any that match the dynamic port
-            let mut ports = vec![format!("Port={},127.0.0.1:{}", service_port, local_port)];
-            if service_port != 8080 && local_port != 8080 {
-                ports.push("Port=8080,127.0.0.1:8080".to_string());
-            }
-            if service_port != 9151 {
-                ports.push("Port=9151,127.0.0.1:9151".to_string());
-            }
-            if service_port != 9153 {
-                ports.push("Port=9153,127.0.0.1:9153".to_string());
-            }
-            let port_spec = ports.join(" ");
+            // (only the primary mapping in single-port mode)
+            let port_spec = super::ports::ports()
+                .hidden_service_mappings(service_port, local_port)
+                .iter()
+                .map(|(virt, local)| {
+                    format!(
+                        "Port={},{}",
+                        virt,
+                        super::listen::listen_config().onion_target(*local)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
 
             // Create ephemeral hidden service with Detach flag
             // Detach allows the service to persist beyond the control connection and be deleted from any connection
@@ -1763,13 +1816,13 @@ impl TorManager {
             let n = stream.read(&mut buf).await?;
             let mut response = String::from_utf8_lossy(&buf[..n]).to_string();
 
-            log::info!("ADD_ONION response: {}", response);
+            log::info!("ADD_ONION response: {}", redact::scrub(&response));
 
             // Key format verification logging (Issue 5.4.2: ed25519_dalek keypair format)
             if response.contains("512") || response.contains("513") {
                 log::error!(
                     "ADD_ONION key format rejected: {} — key was {} bytes (64 expected for ED25519-V3 keypair)",
-                    response.trim(),
+                    redact::scrub(&response).trim(),
                     expanded_key.len()
                 );
             }
@@ -1788,13 +1841,13 @@ impl TorManager {
                     let mut del_buf = vec![0u8; 2048];
                     let del_n = stream.read(&mut del_buf).await?;
                     let del_response = String::from_utf8_lossy(&del_buf[..del_n]);
-                    log::info!("DEL_ONION response: {}", del_response);
+                    log::info!("DEL_ONION response: {}", redact::scrub(&del_response));
 
                     // Retry ADD_ONION
                     stream.write_all(command.as_bytes()).await?;
                     let retry_n = stream.read(&mut buf).await?;
                     response = String::from_utf8_lossy(&buf[..retry_n]).to_string();
-                    log::info!("ADD_ONION retry response: {}", response);
+                    log::info!("ADD_ONION retry response: {}", redact::scrub(&response));
 
                     if !response.contains("250 OK") {
                         return Err(format!(
@@ -1823,7 +1876,10 @@ impl TorManager {
 
             self.hidden_service_address = Some(actual_onion.clone());
             self.hs_state.message_hs_address = Some(actual_onion.clone()); // Track for crash recovery
-            log::info!("Hidden service registered: {}", actual_onion);
+            log::info!(
+                "Hidden service registered: {}",
+                redact::onion(&actual_onion)
+            );
             log::info!(
                 "Service port: {}, Local forward: 127.0.0.1:{}",
                 service_port,
@@ -1840,11 +1896,27 @@ impl TorManager {
         // Tor will publish descriptors in the background automatically
         // Waiting for HS_DESC UPLOADED events doesn't work reliably with ephemeral services
         log::info!("Ephemeral hidden service created - Tor will publish descriptors in background");
-        log::info!("Hidden service is now reachable: {}", full_address);
+        log::info!(
+            "Hidden service is now reachable: {}",
+            redact::onion(&full_address)
+        );
 
         Ok(full_address)
     }
 
+    /// Force a fresh descriptor upload for the message hidden service.
+    /// ADD_ONION with the same key collides with the running service, which
+    /// `create_hidden_service` resolves by deleting and re-adding it.
+    pub async fn republish_hidden_service(
+        &mut self,
+        hs_private_key: &[u8],
+    ) -> Result<String, Box<dyn Error>> {
+        log::info!("Re-publishing message hidden service descriptor");
+        let ports = super::ports::ports();
+        self.create_hidden_service(ports.messaging, ports.local_listener, hs_private_key)
+            .await
+    }
+
     /// Create voice hidden service for voice calling (port 9152 only)
     /// This is a dedicated hidden service separate from messaging
     pub async fn create_voice_hidden_service(
@@ -1898,12 +1970,15 @@ impl TorManager {
         let actual_onion_address = {
             let mut stream = control.lock().await;
 
-            // Create ephemeral voice hidden service with Detach flag and only port 9152
+            // Create ephemeral voice hidden service with Detach flag and only the voice port
             // Single Onion mode is configured in voice torrc (HiddenServiceNonAnonymousMode 1)
             // Detach allows cleanup of orphaned services from previous crashes
+            let ports = super::ports::ports();
             let command = format!(
-                "ADD_ONION ED25519-V3:{} Flags=Detach Port=9152,127.0.0.1:9152\r\n",
-                key_base64
+                "ADD_ONION ED25519-V3:{} Flags=Detach Port={},{}\r\n",
+                key_base64,
+                ports.voice,
+                super::listen::listen_config().onion_target(ports.local_voice)
             );
 
             stream.write_all(command.as_bytes()).await?;
@@ -1912,7 +1987,7 @@ impl TorManager {
             let n = stream.read(&mut buf).await?;
             let response = String::from_utf8_lossy(&buf[..n]);
 
-            log::info!("ADD_ONION (voice) response: {}", response);
+            log::info!("ADD_ONION (voice) response: {}", redact::scrub(&response));
 
             // Check if service was created successfully
             if !response.contains("250 OK") {
@@ -1938,9 +2013,14 @@ impl TorManager {
 
         log::info!(
             "VOICE SINGLE ONION SERVICE registered: {}",
-            actual_onion_address
+            redact::onion(&actual_onion_address)
+        );
+        let ports = super::ports::ports();
+        log::info!(
+            "Voice service port: {} → local {} (voice streaming)",
+            ports.voice,
+            ports.local_voice
         );
-        log::info!("Voice service port: 9152 → local 9152 (voice streaming)");
         log::info!("Service mode: Single Onion (3-hop latency, service location visible)");
 
         Ok(actual_onion_address)
@@ -1951,7 +2031,10 @@ impl TorManager {
         &self,
         onion_address: &str,
     ) -> Result<(), Box<dyn Error>> {
-        log::info!("Waiting for UPLOADED events for {}", onion_address);
+        log::info!(
+            "Waiting for UPLOADED events for {}",
+            redact::onion(&onion_address)
+        );
 
         let control = self
             .control_stream
@@ -2041,7 +2124,7 @@ impl TorManager {
     ) -> Result<TorConnection, Box<dyn Error>> {
         log::info!(
             "Connecting to {}:{} via Tor SOCKS5 proxy",
-            onion_address,
+            redact::onion(&onion_address),
             port
         );
 
@@ -2066,13 +2149,16 @@ impl TorManager {
         // Perform SOCKS5 handshake
         log::info!(
             "Performing SOCKS5 handshake for {}:{}...",
-            onion_address,
+            redact::onion(&onion_address),
             port
         );
         self.socks5_connect(&mut stream, onion_address, port)
             .await?;
 
-        log::info!("Successfully connected to {}", onion_address);
+        log::info!(
+            "Successfully connected to {}",
+            redact::onion(&onion_address)
+        );
 
         Ok(TorConnection {
             stream,
@@ -2215,7 +2301,8 @@ impl TorManager {
         // self.stop_listener().await;
         // }
 
-        let bind_addr = format!("127.0.0.1:{}", port);
+        let listen = super::listen::listen_config();
+        let bind_addr = listen.socket_addr(port).to_string();
         log::info!(
             "Starting hidden service listener on {} (requested port: {})",
             bind_addr,
@@ -2228,6 +2315,7 @@ impl TorManager {
             log::error!("FATAL: Failed to bind listener on {}: {:?}", bind_addr, e);
             e
         })?;
+        listen.check_bound(&listener)?;
 
         log::info!("Successfully bound to {}", bind_addr);
 
@@ -2307,9 +2395,14 @@ impl TorManager {
             log::info!("Bind attempt {}/{} to {}", attempt, MAX_ATTEMPTS, addr);
 
             // Try binding with SO_REUSEADDR
-            match tokio::net::TcpSocket::new_v4() {
+            let socket = if addr.is_ipv4() {
+                tokio::net::TcpSocket::new_v4()
+            } else {
+                tokio::net::TcpSocket::new_v6()
+            };
+            match socket {
                 Ok(socket) => {
-                    log::debug!("TcpSocket::new_v4() succeeded");
+                    log::debug!("TcpSocket created for {}", addr);
 
                     if let Err(e) = socket.set_reuseaddr(true) {
                         log::error!("set_reuseaddr() failed: {:?}", e);
@@ -2360,7 +2453,7 @@ impl TorManager {
                     }
                 }
                 Err(e) => {
-                    log::error!("TcpSocket::new_v4() failed: {:?}", e);
+                    log::error!("TcpSocket creation failed: {:?}", e);
                     return Err(e.into());
                 }
             }
@@ -2398,6 +2491,7 @@ impl TorManager {
         if matches!(total_len, 4096 | 8192 | 16384) {
             if let Ok(stripped) = padding::strip_padding(&buf) {
                 if padding::is_cover_packet(&stripped) {
+                    super::security_events::cover_packet();
                     log::debug!("Discarding cover traffic packet (conn {})", conn_id);
                     return Ok(());
                 }
@@ -2405,31 +2499,27 @@ impl TorManager {
             }
         }
 
+        // Network silence: behave like a dead service. Nothing is answered or
+        // logged; only a wake message is looked at (see network::silence).
+        if buf.first() == Some(&MSG_TYPE_WAKE) {
+            super::silence::try_wake(&buf[1..]);
+            return Ok(());
+        }
+        if super::silence::is_silent() {
+            return Ok(());
+        }
+
         // DIAGNOSTIC: Log raw wire bytes at earliest receive point
         log::info!("EARLIEST RECEIVE POINT (connection {}) ", conn_id);
         log::info!("len: {} bytes", buf.len());
         if !buf.is_empty() {
             log::info!("type_byte: 0x{:02x}", buf[0]);
-            log::info!(
-                "first 8 bytes: {}",
-                buf.iter()
-                    .take(8)
-                    .map(|b| format!("{:02x}", b))
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            );
+            log::info!("first 8 bytes: {}", redact::key(&buf[..buf.len().min(8)]));
             if buf.len() > 1 {
                 log::info!("second byte: 0x{:02x}", buf[1]);
             }
             if buf[0] == MSG_TYPE_PING && buf.len() >= 5 {
-                log::info!(
-                    "PING pubkey_first4: {}",
-                    buf[1..5]
-                        .iter()
-                        .map(|b| format!("{:02x}", b))
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                );
+                log::info!("PING pubkey_first4: {}", redact::key(&buf[1..5]));
             }
         }
         log::info!("");
@@ -2452,6 +2542,7 @@ impl TorManager {
             | MSG_TYPE_VOICE
             | MSG_TYPE_TAP
             | MSG_TYPE_DELIVERY_CONFIRMATION
+            | MSG_TYPE_ACK_BATCH
             | MSG_TYPE_FRIEND_REQUEST
             | MSG_TYPE_FRIEND_REQUEST_ACCEPTED
             | MSG_TYPE_IMAGE
@@ -2460,6 +2551,12 @@ impl TorManager {
             | MSG_TYPE_PAYMENT_ACCEPTED
             | MSG_TYPE_CALL_SIGNALING
             | MSG_TYPE_PROFILE_UPDATE
+            | MSG_TYPE_PRESENCE
+            | MSG_TYPE_REACTION
+            | MSG_TYPE_RPC
+            | MSG_TYPE_TOPIC
+            | MSG_TYPE_RECALL
+            | MSG_TYPE_FILE_TRANSFER
             | MSG_TYPE_CRDT_OPS
             | MSG_TYPE_SYNC_REQUEST
             | MSG_TYPE_SYNC_CHUNK
@@ -2525,12 +2622,7 @@ impl TorManager {
         log::info!("");
 
         // ROUTER INVARIANT: Log route decision for debugging
-        let head_hex: String = buf
-            .iter()
-            .take(8)
-            .map(|b| format!("{:02x}", b))
-            .collect::<Vec<_>>()
-            .join("");
+        let head_hex = redact::key(&buf[..buf.len().min(8)]).to_string();
         log::info!(
             "ROUTE: type=0x{:02x} conn={} len={} head={}",
             msg_type,
@@ -2539,6 +2631,78 @@ impl TorManager {
             head_hex
         );
 
+        // First-contact PoW gate: friend requests and pings from untrusted senders
+        // must carry a stamp when the policy is enabled (no-op otherwise)
+        let buf = match super::first_contact::admit(buf) {
+            Ok(admitted) => admitted,
+            Err(e) => {
+                log::warn!(
+                    "POW_GATE_DROP: type=0x{:02x} conn={}: {}",
+                    msg_type,
+                    conn_id,
+                    e
+                );
+                return Ok(());
+            }
+        };
+
+        // Unified inbound queue: one typed, persisted stream instead of the
+        // per-type channels below (see network::inbox)
+        if super::inbox::is_enabled() {
+            if let Some(kind) = super::inbox::InboundKind::from_wire_type(msg_type) {
+                if kind.keeps_connection() {
+                    let mut pending = PENDING_CONNECTIONS.lock().unwrap();
+                    pending.insert(
+                        conn_id,
+                        PendingConnection {
+                            socket,
+                            encrypted_ping: buf.clone(),
+                        },
+                    );
+                }
+                let seq = super::inbox::publish(kind, conn_id, buf);
+                log::info!(
+                    "ROUTER: queued {:?} as inbound seq {}, conn={}",
+                    kind,
+                    seq,
+                    conn_id
+                );
+                return Ok(());
+            }
+        }
+
+        // Core-side dispatcher: checked, typed frames for its subscribers
+        // instead of the per-type channels below (see network::dispatcher)
+        if super::dispatcher::is_running() {
+            let keeps_connection = super::inbox::InboundKind::from_wire_type(msg_type)
+                .is_some_and(|kind| kind.keeps_connection());
+            if keeps_connection {
+                let mut pending = PENDING_CONNECTIONS.lock().unwrap();
+                pending.insert(
+                    conn_id,
+                    PendingConnection {
+                        socket,
+                        encrypted_ping: buf.clone(),
+                    },
+                );
+            }
+            match super::dispatcher::dispatch(conn_id, buf) {
+                Ok(kind) => log::info!("ROUTER: dispatched {:?}, conn={}", kind, conn_id),
+                Err(e) => {
+                    log::warn!(
+                        "DISPATCH_DROP: type=0x{:02x} conn={}: {}",
+                        msg_type,
+                        conn_id,
+                        e
+                    );
+                    if keeps_connection {
+                        PENDING_CONNECTIONS.lock().unwrap().remove(&conn_id);
+                    }
+                }
+            }
+            return Ok(());
+        }
+
         // Route based on message type (buf INCLUDES type byte at offset 0)
         match msg_type {
             MSG_TYPE_PING => {
@@ -2589,6 +2753,12 @@ impl TorManager {
             | MSG_TYPE_PAYMENT_SENT
             | MSG_TYPE_PAYMENT_ACCEPTED
             | MSG_TYPE_PROFILE_UPDATE
+            | MSG_TYPE_PRESENCE
+            | MSG_TYPE_REACTION
+            | MSG_TYPE_RPC
+            | MSG_TYPE_TOPIC
+            | MSG_TYPE_RECALL
+            | MSG_TYPE_FILE_TRANSFER
             | MSG_TYPE_CRDT_OPS
             | MSG_TYPE_SYNC_REQUEST
             | MSG_TYPE_SYNC_CHUNK
@@ -2604,6 +2774,12 @@ impl TorManager {
                         MSG_TYPE_PAYMENT_SENT => "PAYMENT_SENT",
                         MSG_TYPE_PAYMENT_ACCEPTED => "PAYMENT_ACCEPTED",
                         MSG_TYPE_PROFILE_UPDATE => "PROFILE_UPDATE",
+                        MSG_TYPE_PRESENCE => "PRESENCE",
+                        MSG_TYPE_REACTION => "REACTION",
+                        MSG_TYPE_RPC => "RPC",
+                        MSG_TYPE_TOPIC => "TOPIC",
+                        MSG_TYPE_RECALL => "RECALL",
+                        MSG_TYPE_FILE_TRANSFER => "FILE_TRANSFER",
                         MSG_TYPE_CRDT_OPS => "CRDT_OPS",
                         MSG_TYPE_SYNC_REQUEST => "SYNC_REQUEST",
                         MSG_TYPE_SYNC_CHUNK => "SYNC_CHUNK",
@@ -2685,8 +2861,14 @@ impl TorManager {
                     ping_tx.send((conn_id, buf)).ok();
                 }
             }
-            MSG_TYPE_DELIVERY_CONFIRMATION => {
-                log::warn!("Received ACK on main listener (port 8080) - should go to port 9153!");
+            MSG_TYPE_DELIVERY_CONFIRMATION | MSG_TYPE_ACK_BATCH => {
+                if super::ports::ports().single_port {
+                    log::debug!("Received ACK on main listener (single-port mode)");
+                } else {
+                    log::warn!(
+                        "Received ACK on main listener (port 8080) - should go to port 9153!"
+                    );
+                }
                 log::info!("→ Routing to ACK channel (error recovery - ensures no message loss)");
 
                 // ERROR RECOVERY: ACK arrived on wrong port, but we MUST NOT drop it!
@@ -2815,7 +2997,10 @@ impl TorManager {
         let n = stream.read(&mut buf).await?;
         let response = String::from_utf8_lossy(&buf[..n]);
 
-        log::info!("GETINFO onions/current response: {}", response);
+        log::info!(
+            "GETINFO onions/current response: {}",
+            redact::scrub(&response)
+        );
 
         // Parse service IDs from response
         // Format: 250-onions/current=service1 service2 service3
@@ -2839,7 +3024,7 @@ impl TorManager {
         log::info!(
             "Found {} ephemeral service(s) to delete: {:?}",
             service_ids.len(),
-            service_ids
+            service_ids.iter().map(redact::onion).collect::<Vec<_>>()
         );
 
         // Delete each service
@@ -2852,10 +3037,14 @@ impl TorManager {
             let del_response = String::from_utf8_lossy(&buf[..n]);
 
             if del_response.contains("250 OK") {
-                log::info!("Deleted ephemeral service: {}", service_id);
+                log::info!("Deleted ephemeral service: {}", redact::onion(&service_id));
                 deleted_count += 1;
             } else {
-                log::warn!("Failed to delete service {}: {}", service_id, del_response);
+                log::warn!(
+                    "Failed to delete service {}: {}",
+                    redact::onion(&service_id),
+                    redact::scrub(&del_response)
+                );
             }
         }
 
@@ -3015,28 +3204,8 @@ impl TorManager {
             }
         };
 
-        // Authenticate — GP tor-android 0.4.9.5 uses CookieAuthentication 0 (empty auth)
-        if let Err(e) = stream.write_all(b"AUTHENTICATE\r\n").await {
-            log::error!("Tor ControlSocket: Auth write failed - {}", e);
-            return false;
-        }
-
-        let mut auth_response = vec![0u8; 512];
-        let n = match timeout(Duration::from_secs(2), stream.read(&mut auth_response)).await {
-            Ok(Ok(n)) => n,
-            Ok(Err(e)) => {
-                log::error!("Tor ControlSocket: Auth read failed - {}", e);
-                return false;
-            }
-            Err(_) => {
-                log::error!("Tor ControlSocket: Auth timeout");
-                return false;
-            }
-        };
-
-        let auth_str = String::from_utf8_lossy(&auth_response[..n]);
-        if !auth_str.starts_with("250") {
-            log::error!("Tor ControlSocket: Auth failed - {}", auth_str.trim());
+        if let Err(e) = authenticate_control(&mut stream).await {
+            log::error!("Tor ControlSocket: Auth failed - {}", e);
             return false;
         }
 
@@ -3291,6 +3460,11 @@ pub struct TorConnection {
 
 impl TorConnection {
     pub async fn send(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        if let Some(&msg_type) = data.first() {
+            if !super::silence::allows_msg_type(msg_type) {
+                return Err(format!("Network silence: type 0x{:02x} not sent", msg_type).into());
+            }
+        }
         let padded_buf = padding::pad_to_fixed_size(data).ok();
         let payload: &[u8] = padded_buf.as_deref().unwrap_or(data);
         let len = payload.len() as u32;
@@ -3315,6 +3489,7 @@ impl TorConnection {
         if matches!(data_len, 4096 | 8192 | 16384) {
             if let Ok(stripped) = padding::strip_padding(&data) {
                 if padding::is_cover_packet(&stripped) {
+                    super::security_events::cover_packet();
                     return Err("Cover traffic (discard)".into());
                 }
                 return Ok(stripped);
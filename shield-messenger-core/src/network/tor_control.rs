@@ -0,0 +1,1034 @@
+//! Tor Control Port Client
+//!
+//! The tor-android daemon accepts empty (NULL) authentication on its
+//! ControlSocket, but a system tor, or any tor started by someone else,
+//! normally listens on a TCP control port and requires cookie or password
+//! authentication. `TorControl` handles those setups:
+//!
+//! - Connects to a TCP address or a Unix socket ([`ControlAddress`])
+//! - Authenticates with NULL, SAFECOOKIE / COOKIE, or HASHEDPASSWORD; with
+//!   [`ControlAuth::Auto`] the method is picked from the daemon's
+//!   `PROTOCOLINFO` reply. Auto only uses SAFECOOKIE, which proves the
+//!   daemon knows the cookie before ours is revealed; plain COOKIE sends
+//!   the file's contents to whoever answers, so it needs a caller-given path
+//! - Parses replies into [`Reply`] (multi-line and `+` data lines
+//!   included) and queues asynchronous `650` events that arrive in between
+//! - Reconnects with backoff when the connection drops, re-authenticating
+//!   and restoring the `SETEVENTS` subscription
+//!
+//! Code that speaks the protocol over its own socket (`TorManager`'s
+//! ControlSocket paths) authenticates it with [`authenticate_stream`],
+//! which runs the same method selection.
+//!
+//! A command is only re-sent automatically if writing it failed. Once it
+//! was written, a lost reply is returned as an error, because the daemon
+//! may already have acted on it (e.g. `ADD_ONION`).
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// Default control port of a system tor
+pub const DEFAULT_CONTROL_ADDRESS: &str = "127.0.0.1:9051";
+
+/// Largest reply accepted from the daemon
+const MAX_REPLY_BYTES: usize = 1 << 20;
+
+/// SAFECOOKIE HMAC keys (control-spec 3.24)
+const SERVER_HASH_KEY: &[u8] = b"Tor safe cookie authentication server-to-controller hash";
+const CLIENT_HASH_KEY: &[u8] = b"Tor safe cookie authentication controller-to-server hash";
+
+const COOKIE_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum TorControlError {
+    #[error("Control connection error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Timed out talking to the control port")]
+    Timeout,
+
+    #[error("Malformed control reply: {0}")]
+    Malformed(String),
+
+    #[error("Control command failed: {code} {message}")]
+    Command { code: u16, message: String },
+
+    #[error("No usable authentication method (daemon offers: {0})")]
+    NoAuthMethod(String),
+
+    #[error("Cannot use auth cookie {path}: {reason}")]
+    Cookie { path: String, reason: String },
+
+    #[error("Daemon failed the SAFECOOKIE challenge")]
+    ServerHashMismatch,
+
+    #[error("Unix control sockets are not supported on this platform")]
+    UnixUnsupported,
+
+    #[error("Control argument cannot contain CR or LF")]
+    LineBreakInArgument,
+}
+
+pub type Result<T> = std::result::Result<T, TorControlError>;
+
+/// Where the control port listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlAddress {
+    /// `host:port`
+    Tcp(String),
+    /// ControlSocket path
+    Unix(PathBuf),
+}
+
+impl ControlAddress {
+    /// `unix:/path/to/socket` or `host:port`
+    pub fn parse(s: &str) -> Self {
+        match s.strip_prefix("unix:") {
+            Some(path) => ControlAddress::Unix(PathBuf::from(path)),
+            None => ControlAddress::Tcp(s.to_string()),
+        }
+    }
+}
+
+impl Default for ControlAddress {
+    fn default() -> Self {
+        ControlAddress::Tcp(DEFAULT_CONTROL_ADDRESS.to_string())
+    }
+}
+
+impl fmt::Display for ControlAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlAddress::Tcp(addr) => f.write_str(addr),
+            ControlAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// How to authenticate to the control port
+#[derive(Clone, PartialEq, Eq)]
+pub enum ControlAuth {
+    /// Whatever the daemon offers that needs no secret from us: NULL, else
+    /// SAFECOOKIE with the cookie file it names. Never plain COOKIE.
+    Auto,
+    /// No authentication (`CookieAuthentication 0`, no password)
+    Null,
+    /// SAFECOOKIE if offered, else COOKIE. `None` reads the cookie file
+    /// named in `PROTOCOLINFO` and only works with SAFECOOKIE: plain COOKIE
+    /// is only sent from a file the caller named.
+    Cookie(Option<PathBuf>),
+    /// The password whose hash is in `HashedControlPassword`
+    Password(String),
+}
+
+impl fmt::Debug for ControlAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlAuth::Auto => f.write_str("Auto"),
+            ControlAuth::Null => f.write_str("Null"),
+            ControlAuth::Cookie(path) => f.debug_tuple("Cookie").field(path).finish(),
+            ControlAuth::Password(_) => f.write_str("Password(<redacted>)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TorControlConfig {
+    pub address: ControlAddress,
+    pub auth: ControlAuth,
+    /// Per connect attempt and per reply
+    pub timeout: Duration,
+    /// Connection attempts per reconnect
+    pub reconnect_attempts: u32,
+    /// Wait before the second attempt; doubles after each failure
+    pub reconnect_backoff: Duration,
+}
+
+impl Default for TorControlConfig {
+    fn default() -> Self {
+        Self {
+            address: ControlAddress::default(),
+            auth: ControlAuth::Auto,
+            timeout: Duration::from_secs(10),
+            reconnect_attempts: 5,
+            reconnect_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// One line of a reply, with the data block if it was a `+` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyLine {
+    pub text: String,
+    pub data: Option<String>,
+}
+
+/// A complete reply (or asynchronous event) from the daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// Status code of the final line
+    pub code: u16,
+    pub lines: Vec<ReplyLine>,
+}
+
+impl Reply {
+    pub fn is_ok(&self) -> bool {
+        (200..300).contains(&self.code)
+    }
+
+    pub fn is_event(&self) -> bool {
+        (600..700).contains(&self.code)
+    }
+
+    /// Text of the final line, e.g. "OK"
+    pub fn message(&self) -> &str {
+        self.lines.last().map_or("", |line| line.text.as_str())
+    }
+
+    /// `Err(Command)` unless this is a 2xx reply
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(TorControlError::Command {
+                code: self.code,
+                message: self.message().to_string(),
+            })
+        }
+    }
+
+    /// `key=value` lines as a map; a data block is the value of its line
+    pub fn values(&self) -> HashMap<String, String> {
+        self.lines
+            .iter()
+            .filter_map(|line| {
+                let (key, value) = line.text.split_once('=')?;
+                let value = line.data.clone().unwrap_or_else(|| value.to_string());
+                Some((key.to_string(), value))
+            })
+            .collect()
+    }
+}
+
+/// What the daemon told us in `PROTOCOLINFO`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtocolInfo {
+    /// e.g. `["COOKIE", "SAFECOOKIE"]`
+    pub auth_methods: Vec<String>,
+    pub cookie_file: Option<PathBuf>,
+    pub tor_version: Option<String>,
+}
+
+impl ProtocolInfo {
+    fn from_reply(reply: &Reply) -> Self {
+        let mut info = ProtocolInfo::default();
+        for line in &reply.lines {
+            if let Some(rest) = line.text.strip_prefix("AUTH ") {
+                for (key, value) in split_arguments(rest) {
+                    match key.as_str() {
+                        "METHODS" => {
+                            info.auth_methods = value.split(',').map(str::to_string).collect()
+                        }
+                        "COOKIEFILE" => info.cookie_file = Some(PathBuf::from(value)),
+                        _ => {}
+                    }
+                }
+            } else if let Some(rest) = line.text.strip_prefix("VERSION ") {
+                info.tor_version = split_arguments(rest)
+                    .into_iter()
+                    .find(|(key, _)| key == "Tor")
+                    .map(|(_, value)| value);
+            }
+        }
+        info
+    }
+
+    fn offers(&self, method: &str) -> bool {
+        self.auth_methods.iter().any(|m| m == method)
+    }
+}
+
+/// `SIGNAL` arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Switch to clean circuits for new connections
+    NewNym,
+    Reload,
+    Dormant,
+    Active,
+    Shutdown,
+}
+
+impl Signal {
+    fn as_str(self) -> &'static str {
+        match self {
+            Signal::NewNym => "NEWNYM",
+            Signal::Reload => "RELOAD",
+            Signal::Dormant => "DORMANT",
+            Signal::Active => "ACTIVE",
+            Signal::Shutdown => "SHUTDOWN",
+        }
+    }
+}
+
+trait ControlIo: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ControlIo for T {}
+
+type Connection = BufReader<Box<dyn ControlIo>>;
+
+/// Authenticated control connection that reconnects on demand
+pub struct TorControl {
+    config: TorControlConfig,
+    connection: Option<Connection>,
+    info: ProtocolInfo,
+    /// Events read while waiting for a command's reply
+    events: VecDeque<Reply>,
+    /// Current `SETEVENTS` subscription, restored after reconnecting
+    subscribed: Vec<String>,
+}
+
+impl TorControl {
+    /// Connect and authenticate (one attempt)
+    pub async fn connect(config: TorControlConfig) -> Result<Self> {
+        let mut control = Self {
+            config,
+            connection: None,
+            info: ProtocolInfo::default(),
+            events: VecDeque::new(),
+            subscribed: Vec::new(),
+        };
+        control.open().await?;
+        Ok(control)
+    }
+
+    pub fn config(&self) -> &TorControlConfig {
+        &self.config
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// `PROTOCOLINFO` from the most recent connection
+    pub fn protocol_info(&self) -> &ProtocolInfo {
+        &self.info
+    }
+
+    /// Drop the connection and open a new one, retrying with backoff
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.connection = None;
+        let mut backoff = self.config.reconnect_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.open().await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.config.reconnect_attempts.max(1) => return Err(e),
+                Err(e) => {
+                    log::warn!(
+                        "Tor control: reconnect attempt {} to {} failed: {}",
+                        attempt,
+                        self.config.address,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Send one command line and return its reply, reconnecting first if
+    /// the connection is down. Error replies are returned as `Ok`; see
+    /// [`Reply::into_result`].
+    pub async fn command(&mut self, line: &str) -> Result<Reply> {
+        if self.connection.is_none() {
+            self.reconnect().await?;
+        }
+        if let Err(e) = self.write_line(line).await {
+            log::warn!("Tor control: write failed ({}), reconnecting", e);
+            self.reconnect().await?;
+            self.write_line(line).await?;
+        }
+        self.read_command_reply().await
+    }
+
+    /// `GETINFO` for the given keys
+    pub async fn get_info(&mut self, keys: &[&str]) -> Result<HashMap<String, String>> {
+        let reply = self
+            .command(&format!("GETINFO {}", keys.join(" ")))
+            .await?
+            .into_result()?;
+        Ok(reply.values())
+    }
+
+    /// Replace the event subscription (empty to unsubscribe)
+    pub async fn set_events(&mut self, events: &[&str]) -> Result<()> {
+        self.command(&format!("SETEVENTS {}", events.join(" ")))
+            .await?
+            .into_result()?;
+        self.subscribed = events.iter().map(|e| e.to_string()).collect();
+        Ok(())
+    }
+
+    pub async fn signal(&mut self, signal: Signal) -> Result<()> {
+        self.command(&format!("SIGNAL {}", signal.as_str()))
+            .await?
+            .into_result()?;
+        Ok(())
+    }
+
+    /// Next asynchronous event, waiting up to `timeout`; `None` on timeout
+    pub async fn next_event(&mut self, timeout: Duration) -> Result<Option<Reply>> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+        if self.connection.is_none() {
+            self.reconnect().await?;
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+        match tokio::time::timeout(timeout, read_reply(connection)).await {
+            Err(_) => Ok(None),
+            Ok(Ok(reply)) if reply.is_event() => Ok(Some(reply)),
+            Ok(Ok(reply)) => Err(TorControlError::Malformed(format!(
+                "unsolicited {} reply",
+                reply.code
+            ))),
+            Ok(Err(e)) => {
+                self.connection = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// One connect + authenticate + resubscribe attempt
+    async fn open(&mut self) -> Result<()> {
+        let stream = tokio::time::timeout(self.config.timeout, connect(&self.config.address))
+            .await
+            .map_err(|_| TorControlError::Timeout)??;
+        let mut connection = BufReader::new(stream);
+        self.info = handshake(&mut connection, &self.config.auth, self.config.timeout).await?;
+        self.connection = Some(connection);
+
+        if !self.subscribed.is_empty() {
+            let events = self.subscribed.join(" ");
+            self.exchange(&format!("SETEVENTS {}", events))
+                .await?
+                .into_result()?;
+        }
+        log::info!("Tor control: connected to {}", self.config.address);
+        Ok(())
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        let connection = self
+            .connection
+            .as_mut()
+            .ok_or_else(|| TorControlError::Io(std::io::ErrorKind::NotConnected.into()))?;
+        let stream = connection.get_mut();
+        let written = async {
+            stream.write_all(line.as_bytes()).await?;
+            stream.write_all(b"\r\n").await?;
+            stream.flush().await
+        };
+        match tokio::time::timeout(self.config.timeout, written).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => {
+                self.connection = None;
+                Err(e.into())
+            }
+            Err(_) => {
+                self.connection = None;
+                Err(TorControlError::Timeout)
+            }
+        }
+    }
+
+    /// Reply to the command just written, queueing events read before it
+    async fn read_command_reply(&mut self) -> Result<Reply> {
+        let timeout = self.config.timeout;
+        let events = &mut self.events;
+        let connection = self
+            .connection
+            .as_mut()
+            .ok_or_else(|| TorControlError::Io(std::io::ErrorKind::NotConnected.into()))?;
+        let result = tokio::time::timeout(timeout, async {
+            loop {
+                let reply = read_reply(connection).await?;
+                if !reply.is_event() {
+                    return Ok(reply);
+                }
+                events.push_back(reply);
+            }
+        })
+        .await
+        .unwrap_or(Err(TorControlError::Timeout));
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+
+    /// Write then read, without reconnecting (used while opening)
+    async fn exchange(&mut self, line: &str) -> Result<Reply> {
+        self.write_line(line).await?;
+        self.read_command_reply().await
+    }
+}
+
+/// Authenticate a control connection that the caller goes on to drive
+/// itself, choosing the method from `auth` exactly as [`TorControl`] does.
+/// Must run before anything else is sent; the daemon writes nothing after
+/// the `AUTHENTICATE` reply until the next command, so no input is lost.
+pub async fn authenticate_stream<S>(
+    stream: &mut S,
+    auth: &ControlAuth,
+    timeout: Duration,
+) -> Result<ProtocolInfo>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    handshake(&mut BufReader::new(stream), auth, timeout).await
+}
+
+/// `PROTOCOLINFO`, then whichever `AUTHENTICATE` `auth` calls for
+async fn handshake<S>(
+    connection: &mut BufReader<S>,
+    auth: &ControlAuth,
+    timeout: Duration,
+) -> Result<ProtocolInfo>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let reply = exchange_on(connection, "PROTOCOLINFO 1", timeout)
+        .await?
+        .into_result()?;
+    let info = ProtocolInfo::from_reply(&reply);
+
+    let command = match auth {
+        ControlAuth::Null => "AUTHENTICATE".to_string(),
+        ControlAuth::Password(password) => format!("AUTHENTICATE {}", quote(password)?),
+        ControlAuth::Cookie(path) => {
+            cookie_command(connection, &info, path.as_deref(), timeout).await?
+        }
+        ControlAuth::Auto if info.offers("NULL") => "AUTHENTICATE".to_string(),
+        ControlAuth::Auto if info.offers("SAFECOOKIE") => {
+            cookie_command(connection, &info, None, timeout).await?
+        }
+        ControlAuth::Auto => {
+            return Err(TorControlError::NoAuthMethod(info.auth_methods.join(",")))
+        }
+    };
+    exchange_on(connection, &command, timeout)
+        .await?
+        .into_result()?;
+    Ok(info)
+}
+
+/// `AUTHENTICATE` line for cookie auth, running the SAFECOOKIE
+/// challenge first when the daemon offers it
+async fn cookie_command<S>(
+    connection: &mut BufReader<S>,
+    info: &ProtocolInfo,
+    path: Option<&Path>,
+    timeout: Duration,
+) -> Result<String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let no_method = || TorControlError::NoAuthMethod(info.auth_methods.join(","));
+    if !info.offers("SAFECOOKIE") {
+        // The daemon gets the file's contents verbatim, so never read a
+        // path it chose: that would leak any 32-byte file to the port.
+        let path = path.ok_or_else(no_method)?;
+        return Ok(format!("AUTHENTICATE {}", hex::encode(read_cookie(path)?)));
+    }
+    let path = path.or(info.cookie_file.as_deref()).ok_or_else(no_method)?;
+    let cookie = read_cookie(path)?;
+
+    let mut client_nonce = [0u8; 32];
+    getrandom::getrandom(&mut client_nonce)
+        .map_err(|e| TorControlError::Io(std::io::Error::other(e.to_string())))?;
+    let reply = exchange_on(
+        connection,
+        &format!("AUTHCHALLENGE SAFECOOKIE {}", hex::encode(client_nonce)),
+        timeout,
+    )
+    .await?
+    .into_result()?;
+    let text = reply.message().strip_prefix("AUTHCHALLENGE ").unwrap_or("");
+    let arguments: HashMap<String, String> = split_arguments(text).into_iter().collect();
+    let decode = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(|v| hex::decode(v).ok())
+            .ok_or_else(|| TorControlError::Malformed(format!("AUTHCHALLENGE without {}", key)))
+    };
+    let server_hash = decode("SERVERHASH")?;
+    let server_nonce = decode("SERVERNONCE")?;
+
+    let mut message = cookie.to_vec();
+    message.extend_from_slice(&client_nonce);
+    message.extend_from_slice(&server_nonce);
+    safecookie_mac(SERVER_HASH_KEY, &message)
+        .verify_slice(&server_hash)
+        .map_err(|_| TorControlError::ServerHashMismatch)?;
+    let client_hash = safecookie_mac(CLIENT_HASH_KEY, &message).finalize();
+    Ok(format!(
+        "AUTHENTICATE {}",
+        hex::encode(client_hash.into_bytes())
+    ))
+}
+
+/// Write one line and read its reply during the handshake, before any
+/// `SETEVENTS`, so there are no events to queue
+async fn exchange_on<S>(
+    connection: &mut BufReader<S>,
+    line: &str,
+    timeout: Duration,
+) -> Result<Reply>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::time::timeout(timeout, async {
+        let stream = connection.get_mut();
+        stream.write_all(line.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        read_reply(connection).await
+    })
+    .await
+    .unwrap_or(Err(TorControlError::Timeout))
+}
+
+async fn connect(address: &ControlAddress) -> Result<Box<dyn ControlIo>> {
+    match address {
+        ControlAddress::Tcp(addr) => Ok(Box::new(TcpStream::connect(addr).await?)),
+        #[cfg(unix)]
+        ControlAddress::Unix(path) => Ok(Box::new(tokio::net::UnixStream::connect(path).await?)),
+        #[cfg(not(unix))]
+        ControlAddress::Unix(_) => Err(TorControlError::UnixUnsupported),
+    }
+}
+
+fn read_cookie(path: &Path) -> Result<[u8; COOKIE_LEN]> {
+    let error = |reason: String| TorControlError::Cookie {
+        path: path.display().to_string(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|e| error(e.to_string()))?;
+    bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| error(format!("{} bytes, expected {}", b.len(), COOKIE_LEN)))
+}
+
+fn safecookie_mac(key: &[u8], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(message);
+    mac
+}
+
+/// Read one reply: lines up to the first with a space after the code
+async fn read_reply<R: AsyncBufRead + Unpin>(connection: &mut R) -> Result<Reply> {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let line = read_line(connection, &mut total).await?;
+        let parsed = line
+            .get(..3)
+            .and_then(|code| code.parse::<u16>().ok())
+            .zip(line.get(4..));
+        let Some((code, text)) = parsed else {
+            return Err(TorControlError::Malformed(line));
+        };
+        let text = text.to_string();
+        match line.as_bytes()[3] {
+            b' ' => {
+                lines.push(ReplyLine { text, data: None });
+                return Ok(Reply { code, lines });
+            }
+            b'-' => lines.push(ReplyLine { text, data: None }),
+            b'+' => {
+                let mut data = Vec::new();
+                loop {
+                    let data_line = read_line(connection, &mut total).await?;
+                    if data_line == "." {
+                        break;
+                    }
+                    // Dot-stuffing: a leading "." is doubled on the wire
+                    let unstuffed = data_line.strip_prefix('.').unwrap_or(&data_line);
+                    data.push(unstuffed.to_string());
+                }
+                // The line reads "key=" with the value in the data block
+                let text = text.trim_end_matches('=').to_string() + "=";
+                lines.push(ReplyLine {
+                    text,
+                    data: Some(data.join("\n")),
+                });
+            }
+            _ => return Err(TorControlError::Malformed(line)),
+        }
+    }
+}
+
+/// One CRLF-terminated line, without the terminator
+async fn read_line<R: AsyncBufRead + Unpin>(
+    connection: &mut R,
+    total: &mut usize,
+) -> Result<String> {
+    let mut line = String::new();
+    let n = connection.read_line(&mut line).await?;
+    if n == 0 {
+        return Err(TorControlError::Io(
+            std::io::ErrorKind::UnexpectedEof.into(),
+        ));
+    }
+    *total += n;
+    if *total > MAX_REPLY_BYTES {
+        return Err(TorControlError::Malformed("reply too large".into()));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Quote a string argument (control-spec QuotedString). A line break would
+/// end the command and start another, so it is refused.
+fn quote(s: &str) -> Result<String> {
+    if s.contains(['\r', '\n']) {
+        return Err(TorControlError::LineBreakInArgument);
+    }
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    Ok(out)
+}
+
+/// `KEY=value KEY="quoted value"` pairs; bare words are skipped
+fn split_arguments(s: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ').is_some() {}
+        let key: String =
+            std::iter::from_fn(|| chars.next_if(|c| *c != '=' && *c != ' ')).collect();
+        if key.is_empty() {
+            break;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+        } else {
+            value.extend(std::iter::from_fn(|| chars.next_if(|c| *c != ' ')));
+        }
+        out.push((key, value));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Scripted daemon: answers each expected command line with a reply.
+    /// `None` as the reply closes the connection instead.
+    async fn fake_tor(script: Vec<Vec<(String, Option<String>)>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for session in script {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let mut lines = tokio::io::BufReader::new(read).lines();
+                for (expected, reply) in session {
+                    let Some(line) = lines.next_line().await.unwrap() else {
+                        break;
+                    };
+                    assert!(line.starts_with(&expected), "got {:?}", line);
+                    match reply {
+                        Some(reply) => write.write_all(reply.as_bytes()).await.unwrap(),
+                        None => break,
+                    }
+                }
+            }
+        });
+        addr
+    }
+
+    fn step(expected: &str, reply: &str) -> (String, Option<String>) {
+        (expected.to_string(), Some(reply.to_string()))
+    }
+
+    fn config(addr: String, auth: ControlAuth) -> TorControlConfig {
+        TorControlConfig {
+            address: ControlAddress::Tcp(addr),
+            auth,
+            timeout: Duration::from_secs(5),
+            reconnect_attempts: 2,
+            reconnect_backoff: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_parsing_helpers() {
+        assert_eq!(
+            ControlAddress::parse("unix:/run/tor/control"),
+            ControlAddress::Unix(PathBuf::from("/run/tor/control"))
+        );
+        assert_eq!(
+            ControlAddress::parse("127.0.0.1:9051"),
+            ControlAddress::default()
+        );
+        assert_eq!(quote(r#"pa"ss\word"#).unwrap(), r#""pa\"ss\\word""#);
+        assert!(matches!(
+            quote("pw\r\nSIGNAL SHUTDOWN"),
+            Err(TorControlError::LineBreakInArgument)
+        ));
+        assert_eq!(
+            split_arguments(r#"METHODS=COOKIE,SAFECOOKIE COOKIEFILE="/var/lib/tor/control auth""#),
+            vec![
+                ("METHODS".to_string(), "COOKIE,SAFECOOKIE".to_string()),
+                (
+                    "COOKIEFILE".to_string(),
+                    "/var/lib/tor/control auth".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            format!("{:?}", ControlAuth::Password("hunter2".into())),
+            "Password(<redacted>)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_safecookie_auth_replies_and_events() {
+        let cookie = [0x42u8; COOKIE_LEN];
+        let path = std::env::temp_dir().join(format!("tor-cookie-{}", std::process::id()));
+        std::fs::write(&path, cookie).unwrap();
+
+        // The daemon's side of SAFECOOKIE needs the client nonce, so this
+        // session is served by hand
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let cookie_path = path.display().to_string();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = tokio::io::BufReader::new(read).lines();
+
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "PROTOCOLINFO 1");
+            let info = format!(
+                "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=COOKIE,SAFECOOKIE COOKIEFILE=\"{}\"\r\n250-VERSION Tor=\"0.4.8.9\"\r\n250 OK\r\n",
+                cookie_path
+            );
+            write.write_all(info.as_bytes()).await.unwrap();
+
+            let challenge = lines.next_line().await.unwrap().unwrap();
+            let client_nonce = hex::decode(challenge.rsplit(' ').next().unwrap()).unwrap();
+            let server_nonce = [7u8; 32];
+            let mut message = cookie.to_vec();
+            message.extend_from_slice(&client_nonce);
+            message.extend_from_slice(&server_nonce);
+            let server_hash = safecookie_mac(SERVER_HASH_KEY, &message).finalize();
+            let reply = format!(
+                "250 AUTHCHALLENGE SERVERHASH={} SERVERNONCE={}\r\n",
+                hex::encode(server_hash.into_bytes()),
+                hex::encode(server_nonce)
+            );
+            write.write_all(reply.as_bytes()).await.unwrap();
+
+            let client_hash = safecookie_mac(CLIENT_HASH_KEY, &message).finalize();
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                format!("AUTHENTICATE {}", hex::encode(client_hash.into_bytes()))
+            );
+            write.write_all(b"250 OK\r\n").await.unwrap();
+
+            // An event arrives ahead of the GETINFO reply
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "GETINFO version config-text"
+            );
+            write
+                .write_all(
+                    b"650 STATUS_CLIENT NOTICE CIRCUIT_ESTABLISHED\r\n\
+                      250-version=0.4.8.9\r\n\
+                      250+config-text=\r\nSocksPort 9050\r\n..dotted\r\n.\r\n\
+                      250 OK\r\n",
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "SIGNAL NEWNYM");
+            write
+                .write_all(b"552 Unrecognized signal\r\n")
+                .await
+                .unwrap();
+        });
+
+        let mut control = TorControl::connect(config(addr, ControlAuth::Auto))
+            .await
+            .unwrap();
+        assert_eq!(
+            control.protocol_info().tor_version.as_deref(),
+            Some("0.4.8.9")
+        );
+
+        let info = control.get_info(&["version", "config-text"]).await.unwrap();
+        assert_eq!(info["version"], "0.4.8.9");
+        assert_eq!(info["config-text"], "SocksPort 9050\n.dotted");
+        let event = control
+            .next_event(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.code, 650);
+        assert_eq!(event.message(), "STATUS_CLIENT NOTICE CIRCUIT_ESTABLISHED");
+
+        assert!(matches!(
+            control.signal(Signal::NewNym).await,
+            Err(TorControlError::Command { code: 552, .. })
+        ));
+        server.await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_password_auth_and_reconnect() {
+        let protocol_info = "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=HASHEDPASSWORD\r\n250 OK\r\n";
+        let addr = fake_tor(vec![
+            vec![step("PROTOCOLINFO 1", protocol_info)],
+            vec![
+                step("PROTOCOLINFO 1", protocol_info),
+                step(r#"AUTHENTICATE "s3cret""#, "250 OK\r\n"),
+                step("SETEVENTS STATUS_CLIENT HS_DESC", "250 OK\r\n"),
+                // The daemon restarts before answering
+                ("GETINFO".to_string(), None),
+            ],
+            vec![
+                step("PROTOCOLINFO 1", protocol_info),
+                step(r#"AUTHENTICATE "s3cret""#, "250 OK\r\n"),
+                step("SETEVENTS STATUS_CLIENT HS_DESC", "250 OK\r\n"),
+                step(
+                    "GETINFO status/circuit-established",
+                    "250-status/circuit-established=1\r\n250 OK\r\n",
+                ),
+            ],
+        ])
+        .await;
+
+        // Auto cannot use a password it was not given
+        let auto = TorControl::connect(config(addr.clone(), ControlAuth::Auto)).await;
+        assert!(matches!(auto, Err(TorControlError::NoAuthMethod(m)) if m == "HASHEDPASSWORD"));
+
+        let mut control = TorControl::connect(config(addr, ControlAuth::Password("s3cret".into())))
+            .await
+            .unwrap();
+        control
+            .set_events(&["STATUS_CLIENT", "HS_DESC"])
+            .await
+            .unwrap();
+
+        // The reply is lost, so the command is not retried...
+        let key = ["status/circuit-established"];
+        assert!(matches!(
+            control.get_info(&key).await,
+            Err(TorControlError::Io(_))
+        ));
+        assert!(!control.is_connected());
+        // ...but the next one reconnects and restores the subscription
+        let info = control.get_info(&key).await.unwrap();
+        assert_eq!(info["status/circuit-established"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_caller_owned_stream() {
+        let cookie = [0x17u8; COOKIE_LEN];
+        let path = std::env::temp_dir().join(format!("tor-raw-cookie-{}", std::process::id()));
+        std::fs::write(&path, cookie).unwrap();
+        let protocol_info = format!(
+            "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=COOKIE COOKIEFILE=\"{}\"\r\n250 OK\r\n",
+            path.display()
+        );
+        let addr = fake_tor(vec![
+            vec![step("PROTOCOLINFO 1", &protocol_info)],
+            vec![step("PROTOCOLINFO 1", &protocol_info)],
+            vec![
+                step("PROTOCOLINFO 1", &protocol_info),
+                step(
+                    &format!("AUTHENTICATE {}", hex::encode(cookie)),
+                    "250 OK\r\n",
+                ),
+                step("GETINFO version", "250-version=0.4.8.9\r\n250 OK\r\n"),
+            ],
+            vec![
+                step("PROTOCOLINFO 1", &protocol_info),
+                step("AUTHENTICATE", "515 Authentication failed\r\n"),
+            ],
+        ])
+        .await;
+        let timeout = Duration::from_secs(5);
+
+        // Plain COOKIE never reads the file the daemon names
+        for auth in [ControlAuth::Auto, ControlAuth::Cookie(None)] {
+            let mut stream = TcpStream::connect(&addr).await.unwrap();
+            let refused = authenticate_stream(&mut stream, &auth, timeout).await;
+            assert!(matches!(refused, Err(TorControlError::NoAuthMethod(m)) if m == "COOKIE"));
+        }
+
+        // The caller keeps speaking the protocol on its own socket afterwards
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+        let info = authenticate_stream(
+            &mut stream,
+            &ControlAuth::Cookie(Some(path.clone())),
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert_eq!(info.auth_methods, vec!["COOKIE"]);
+        stream.write_all(b"GETINFO version\r\n").await.unwrap();
+        let reply = read_reply(&mut BufReader::new(&mut stream)).await.unwrap();
+        assert_eq!(reply.values()["version"], "0.4.8.9");
+
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+        let rejected = authenticate_stream(&mut stream, &ControlAuth::Null, timeout).await;
+        assert!(matches!(
+            rejected,
+            Err(TorControlError::Command { code: 515, .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_malformed_reply_lines() {
+        for wire in ["12\u{e9} x\r\n", "250\r\n", "abc OK\r\n", "250*OK\r\n"] {
+            assert!(matches!(
+                read_reply(&mut wire.as_bytes()).await,
+                Err(TorControlError::Malformed(_))
+            ));
+        }
+        let reply = read_reply(&mut "250 \r\n".as_bytes()).await.unwrap();
+        assert_eq!((reply.code, reply.message()), (250, ""));
+    }
+}
@@ -0,0 +1,524 @@
+//! Tor Runtime Abstraction
+//!
+//! Everything above the Tor layer needs the same four things from it:
+//! bootstrap, open an isolated stream to `host:port`, and publish or
+//! withdraw a v3 onion service that forwards to a local port.
+//! [`TorRuntime`] is that surface, so the backend can be swapped without
+//! touching Ping-Pong, packet handling or padding:
+//!
+//! - [`SystemTor`] drives an external tor daemon (a system service, or one
+//!   started by the platform) through its SOCKS port and control port
+//! - `ArtiRuntime` (feature `arti`) runs Tor in-process with the Arti
+//!   crates, so desktop and server builds need no tor binary and no Java
+//!   `OnionProxyManager`
+//!
+//! Stream isolation follows [`IsolationToken`]: streams with different
+//! tokens never share a circuit. With a system tor this relies on
+//! `IsolateSOCKSAuth` (on by default for every `SocksPort`) and sends the
+//! token as the SOCKS5 username and password.
+
+use base64::Engine;
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::arti::{EphemeralOnionService, IsolationToken};
+use super::tor::{compute_onion_address_from_ed25519_seed, parse_bootstrap_progress};
+use super::tor_control::{Reply, TorControl, TorControlConfig, TorControlError};
+
+/// How long [`TorRuntime::bootstrap`] waits for 100%
+pub const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(120);
+
+const BOOTSTRAP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const SOCKS5_VERSION: u8 = 0x05;
+const SOCKS5_AUTH_USERPASS: u8 = 0x02;
+const SOCKS5_CMD_CONNECT: u8 = 0x01;
+const SOCKS5_ATYP_IPV4: u8 = 0x01;
+const SOCKS5_ATYP_DOMAIN: u8 = 0x03;
+const SOCKS5_ATYP_IPV6: u8 = 0x04;
+
+#[derive(Error, Debug)]
+pub enum TorRuntimeError {
+    #[error("Tor is not bootstrapped")]
+    NotBootstrapped,
+
+    #[error("Bootstrap did not finish within {0:?}")]
+    BootstrapTimeout(Duration),
+
+    #[error("SOCKS5 error: {0}")]
+    Socks(String),
+
+    #[error("Control port error: {0}")]
+    Control(#[from] TorControlError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Onion service error: {0}")]
+    OnionService(String),
+
+    #[error("Not supported by this backend: {0}")]
+    Unsupported(&'static str),
+
+    #[error("Tor backend error: {0}")]
+    Backend(String),
+}
+
+pub type Result<T> = std::result::Result<T, TorRuntimeError>;
+
+/// An onion service to publish
+#[derive(Clone)]
+pub struct OnionServiceSpec {
+    /// Port clients connect to on the onion address
+    pub virtual_port: u16,
+    /// Port on 127.0.0.1 that connections are forwarded to
+    pub local_port: u16,
+    /// Ed25519 seed of the service identity. `None` asks the backend for
+    /// a fresh key, giving a new address each time.
+    pub key_seed: Option<zeroize::Zeroizing<[u8; 32]>>,
+}
+
+/// A Tor backend: bootstrap, isolated streams, onion services
+pub trait TorRuntime: Send + Sync {
+    /// A connected stream through Tor
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Start Tor (if needed) and wait until it can build circuits
+    fn bootstrap(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Last known bootstrap progress, 0-100
+    fn bootstrap_progress(&self) -> u8;
+
+    /// Open a stream to `host:port` (`.onion` included) on circuits
+    /// reserved for `isolation`
+    fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        isolation: &IsolationToken,
+    ) -> impl Future<Output = Result<Self::Stream>> + Send;
+
+    /// Publish an onion service under `service_id`
+    fn add_onion_service(
+        &self,
+        service_id: &str,
+        spec: &OnionServiceSpec,
+    ) -> impl Future<Output = Result<EphemeralOnionService>> + Send;
+
+    /// Withdraw the onion service published under `service_id`
+    fn remove_onion_service(&self, service_id: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Withdraw every onion service and stop using Tor
+    fn shutdown(&self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// An external tor daemon, reached through its SOCKS and control ports
+pub struct SystemTor {
+    socks_addr: String,
+    control_config: TorControlConfig,
+    control: Mutex<Option<TorControl>>,
+    progress: AtomicU8,
+    services: Mutex<HashMap<String, EphemeralOnionService>>,
+}
+
+impl SystemTor {
+    /// `socks_addr` is e.g. `127.0.0.1:9050`
+    pub fn new(socks_addr: impl Into<String>, control_config: TorControlConfig) -> Self {
+        Self {
+            socks_addr: socks_addr.into(),
+            control_config,
+            control: Mutex::new(None),
+            progress: AtomicU8::new(0),
+            services: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send one control command, connecting on first use
+    async fn control_command(&self, line: &str) -> Result<Reply> {
+        let mut control = self.control.lock().await;
+        if control.is_none() {
+            *control = Some(TorControl::connect(self.control_config.clone()).await?);
+        }
+        let control = control.as_mut().expect("connected above");
+        Ok(control.command(line).await?.into_result()?)
+    }
+
+    async fn poll_bootstrap(&self) -> Result<()> {
+        loop {
+            let reply = self
+                .control_command("GETINFO status/bootstrap-phase")
+                .await?;
+            let progress = reply
+                .values()
+                .get("status/bootstrap-phase")
+                .and_then(|phase| parse_bootstrap_progress(phase))
+                .unwrap_or(0)
+                .min(100) as u8;
+            self.progress.store(progress, Ordering::Relaxed);
+            if progress == 100 {
+                return Ok(());
+            }
+            tokio::time::sleep(BOOTSTRAP_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl TorRuntime for SystemTor {
+    type Stream = TcpStream;
+
+    async fn bootstrap(&self) -> Result<()> {
+        tokio::time::timeout(BOOTSTRAP_TIMEOUT, self.poll_bootstrap())
+            .await
+            .map_err(|_| TorRuntimeError::BootstrapTimeout(BOOTSTRAP_TIMEOUT))?
+    }
+
+    fn bootstrap_progress(&self) -> u8 {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    async fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        isolation: &IsolationToken,
+    ) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.socks_addr).await?;
+        let (username, password) = socks_credentials(isolation);
+        socks5_connect(&mut stream, host, port, &username, &password).await?;
+        Ok(stream)
+    }
+
+    async fn add_onion_service(
+        &self,
+        service_id: &str,
+        spec: &OnionServiceSpec,
+    ) -> Result<EphemeralOnionService> {
+        if self.services.lock().await.contains_key(service_id) {
+            return Err(TorRuntimeError::OnionService(format!(
+                "{} is already published",
+                service_id
+            )));
+        }
+        let key = match &spec.key_seed {
+            Some(seed) => format!(
+                "ED25519-V3:{}",
+                base64::engine::general_purpose::STANDARD.encode(expand_ed25519_seed(seed))
+            ),
+            None => "NEW:ED25519-V3".to_string(),
+        };
+        // Detached, so the service outlives a control connection that
+        // drops and is reconnected; shutdown() removes it explicitly
+        let reply = self
+            .control_command(&format!(
+                "ADD_ONION {} Flags=Detach,DiscardPK Port={},{}",
+                key,
+                spec.virtual_port,
+                super::listen::listen_config().onion_target(spec.local_port)
+            ))
+            .await?;
+        let onion_address = reply.values().remove("ServiceID").ok_or_else(|| {
+            TorRuntimeError::OnionService("ADD_ONION reply has no ServiceID".into())
+        })?;
+
+        if let Some(seed) = &spec.key_seed {
+            let expected = compute_onion_address_from_ed25519_seed(seed);
+            if expected.trim_end_matches(".onion") != onion_address {
+                let _ = self
+                    .control_command(&format!("DEL_ONION {}", onion_address))
+                    .await;
+                return Err(TorRuntimeError::OnionService(
+                    "tor published a different address than the key's".into(),
+                ));
+            }
+        }
+
+        let service = EphemeralOnionService {
+            onion_address,
+            local_port: spec.local_port,
+            virtual_port: spec.virtual_port,
+            active: true,
+            created_at: Instant::now(),
+        };
+        self.services
+            .lock()
+            .await
+            .insert(service_id.to_string(), service.clone());
+        Ok(service)
+    }
+
+    async fn remove_onion_service(&self, service_id: &str) -> Result<()> {
+        let Some(service) = self.services.lock().await.remove(service_id) else {
+            return Ok(());
+        };
+        self.control_command(&format!("DEL_ONION {}", service.onion_address))
+            .await?;
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        let services: Vec<_> = self.services.lock().await.drain().collect();
+        for (service_id, service) in services {
+            if let Err(e) = self
+                .control_command(&format!("DEL_ONION {}", service.onion_address))
+                .await
+            {
+                log::warn!("Failed to remove onion service {}: {}", service_id, e);
+            }
+        }
+        *self.control.lock().await = None;
+        self.progress.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Tor's `ED25519-V3` key blob is the expanded secret key (clamped scalar
+/// followed by the nonce prefix), i.e. SHA-512 of the seed, not the seed
+/// itself.
+fn expand_ed25519_seed(seed: &[u8; 32]) -> zeroize::Zeroizing<[u8; 64]> {
+    let mut expanded = zeroize::Zeroizing::new([0u8; 64]);
+    expanded.copy_from_slice(&Sha512::digest(seed));
+    expanded[0] &= 248;
+    expanded[31] &= 127;
+    expanded[31] |= 64;
+    expanded
+}
+
+/// SOCKS5 credentials that put a token's streams on their own circuits
+fn socks_credentials(isolation: &IsolationToken) -> (String, String) {
+    (
+        hex::encode(isolation.contact_id.as_bytes()),
+        isolation.generation.to_string(),
+    )
+}
+
+/// SOCKS5 CONNECT with username/password authentication (RFC 1928, 1929).
+/// The hostname is sent unresolved so Tor does the lookup.
+async fn socks5_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    if host.len() > 255 || username.len() > 255 || password.len() > 255 {
+        return Err(TorRuntimeError::Socks(
+            "host or credentials longer than 255 bytes".into(),
+        ));
+    }
+
+    stream
+        .write_all(&[SOCKS5_VERSION, 1, SOCKS5_AUTH_USERPASS])
+        .await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice != [SOCKS5_VERSION, SOCKS5_AUTH_USERPASS] {
+        return Err(TorRuntimeError::Socks(format!(
+            "proxy refused username/password auth ({:02x?})",
+            choice
+        )));
+    }
+
+    let mut auth = vec![0x01, username.len() as u8];
+    auth.extend_from_slice(username.as_bytes());
+    auth.push(password.len() as u8);
+    auth.extend_from_slice(password.as_bytes());
+    stream.write_all(&auth).await?;
+    let mut status = [0u8; 2];
+    stream.read_exact(&mut status).await?;
+    if status[1] != 0 {
+        return Err(TorRuntimeError::Socks("proxy rejected credentials".into()));
+    }
+
+    let mut request = vec![
+        SOCKS5_VERSION,
+        SOCKS5_CMD_CONNECT,
+        0x00,
+        SOCKS5_ATYP_DOMAIN,
+        host.len() as u8,
+    ];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(TorRuntimeError::Socks(format!(
+            "CONNECT to {}:{} failed with reply {:#04x}",
+            host, port, reply[1]
+        )));
+    }
+    // Skip the bound address
+    let address_len = match reply[3] {
+        SOCKS5_ATYP_IPV4 => 4,
+        SOCKS5_ATYP_IPV6 => 16,
+        SOCKS5_ATYP_DOMAIN => stream.read_u8().await? as usize,
+        other => {
+            return Err(TorRuntimeError::Socks(format!(
+                "unknown address type {}",
+                other
+            )))
+        }
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tor_control::{ControlAddress, ControlAuth};
+    use crate::test_contact;
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::TcpListener;
+
+    fn control_config(addr: String) -> TorControlConfig {
+        TorControlConfig {
+            address: ControlAddress::Tcp(addr),
+            auth: ControlAuth::Null,
+            timeout: Duration::from_secs(5),
+            reconnect_attempts: 1,
+            reconnect_backoff: Duration::from_millis(10),
+        }
+    }
+
+    /// Control port that authenticates anyone and answers the commands in
+    /// `script` in order, recording what it was sent
+    async fn fake_control(
+        script: Vec<&'static str>,
+    ) -> (String, tokio::sync::mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = tokio::io::BufReader::new(read).lines();
+            let mut replies = script.into_iter();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let reply = if line.starts_with("PROTOCOLINFO") {
+                    "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=NULL\r\n250 OK\r\n"
+                } else if line.starts_with("AUTHENTICATE") {
+                    "250 OK\r\n"
+                } else {
+                    tx.send(line).await.unwrap();
+                    replies.next().unwrap()
+                };
+                write.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_socks_connect_isolates_by_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socks_addr = listener.local_addr().unwrap().to_string();
+        let proxy = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut greeting = [0u8; 3];
+                stream.read_exact(&mut greeting).await.unwrap();
+                assert_eq!(greeting, [5, 1, 2]);
+                stream.write_all(&[5, 2]).await.unwrap();
+
+                let mut auth = [0u8; 2];
+                stream.read_exact(&mut auth).await.unwrap();
+                let mut username = vec![0u8; auth[1] as usize];
+                stream.read_exact(&mut username).await.unwrap();
+                let mut password = vec![0u8; stream.read_u8().await.unwrap() as usize];
+                stream.read_exact(&mut password).await.unwrap();
+                stream.write_all(&[1, 0]).await.unwrap();
+
+                let mut head = [0u8; 5];
+                stream.read_exact(&mut head).await.unwrap();
+                assert_eq!(&head[..4], &[5, 1, 0, 3]);
+                let mut target = vec![0u8; head[4] as usize + 2];
+                stream.read_exact(&mut target).await.unwrap();
+                assert_eq!(&target[..head[4] as usize], b"example.onion");
+                assert_eq!(&target[head[4] as usize..], &80u16.to_be_bytes());
+                stream
+                    .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+                    .await
+                    .unwrap();
+                stream.write_all(b"hello").await.unwrap();
+                seen.push((username, password));
+            }
+            seen
+        });
+
+        let tor = SystemTor::new(socks_addr, control_config("127.0.0.1:1".into()));
+        let contact = test_contact(9);
+        let mut token = IsolationToken::new(&contact);
+        let mut stream = tor.connect("example.onion", 80, &token).await.unwrap();
+        let mut greeting = [0u8; 5];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(&greeting, b"hello");
+
+        token.rotate();
+        tor.connect("example.onion", 80, &token).await.unwrap();
+        let seen = proxy.await.unwrap();
+        // Same contact, new generation: different credentials, new circuit
+        assert_eq!(seen[0].0, hex::encode(contact.as_bytes()).into_bytes());
+        assert_eq!(seen[0].0, seen[1].0);
+        assert_eq!(seen[0].1, b"0");
+        assert_eq!(seen[1].1, b"1");
+    }
+
+    #[tokio::test]
+    async fn test_system_tor_bootstrap_and_onion_services() {
+        let seed = [3u8; 32];
+        let address = compute_onion_address_from_ed25519_seed(&seed);
+        let id = address.trim_end_matches(".onion").to_string();
+        let reply = Box::leak(format!("250-ServiceID={}\r\n250 OK\r\n", id).into_boxed_str());
+        let (addr, mut sent) = fake_control(vec![
+            "250-status/bootstrap-phase=NOTICE BOOTSTRAP PROGRESS=50 TAG=loading_descriptors\r\n250 OK\r\n",
+            "250-status/bootstrap-phase=NOTICE BOOTSTRAP PROGRESS=100 TAG=done SUMMARY=\"Done\"\r\n250 OK\r\n",
+            reply,
+            "250 OK\r\n",
+            "250-ServiceID=wrongaddress\r\n250 OK\r\n",
+            "250 OK\r\n",
+        ])
+        .await;
+
+        let tor = SystemTor::new("127.0.0.1:1", control_config(addr));
+        tor.bootstrap().await.unwrap();
+        assert_eq!(tor.bootstrap_progress(), 100);
+
+        let spec = OnionServiceSpec {
+            virtual_port: 80,
+            local_port: 8080,
+            key_seed: Some(zeroize::Zeroizing::new(seed)),
+        };
+        let service = tor.add_onion_service("messaging", &spec).await.unwrap();
+        assert_eq!(service.onion_address, id);
+        tor.remove_onion_service("messaging").await.unwrap();
+
+        // A key tor didn't honour is withdrawn rather than reported
+        assert!(tor.add_onion_service("voice", &spec).await.is_err());
+
+        let mut commands = Vec::new();
+        while let Ok(line) = sent.try_recv() {
+            commands.push(line);
+        }
+        let key = base64::engine::general_purpose::STANDARD.encode(expand_ed25519_seed(&seed));
+        assert_eq!(
+            commands[2],
+            format!(
+                "ADD_ONION ED25519-V3:{} Flags=Detach,DiscardPK Port=80,127.0.0.1:8080",
+                key
+            )
+        );
+        assert_eq!(commands[3], format!("DEL_ONION {}", id));
+        assert_eq!(commands[5], "DEL_ONION wrongaddress");
+    }
+}
@@ -0,0 +1,377 @@
+//! Wake Protocol
+//!
+//! Typed construction and parsing of the Ping → Pong wake handshake, with no
+//! JNI or global session state involved. The Android bridge keeps its own
+//! bookkeeping (stored pings, signer lookups, Room persistence); desktop
+//! apps call these functions directly and keep whatever state they need.
+//!
+//! ```text
+//! sender                                   recipient
+//! initiate_wake()   ── [0x01][x25519][enc PingToken] ──▶  receive_wake()
+//!                                                          respond_to_wake()
+//! complete_wake()  ◀── [0x02][x25519][enc PongToken] ──
+//! ```
+//!
+//! Tokens are encrypted with the X25519 shared secret of the two parties; the
+//! sender's X25519 public key travels in clear after the type byte so the
+//! recipient can derive the same secret.
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::pingpong::{PingToken, PongToken};
+use super::tor::{MSG_TYPE_PING, MSG_TYPE_PONG, P2P_PROTOCOL_VERSION};
+use crate::crypto::{encryption, key_exchange};
+
+/// Type byte plus the sender's X25519 public key.
+const FRAME_HEADER_LEN: usize = 1 + 32;
+
+#[derive(Error, Debug)]
+pub enum WakeError {
+    #[error("Frame too short: {0} bytes")]
+    Truncated(usize),
+
+    #[error("Unexpected message type 0x{0:02x}")]
+    WrongType(u8),
+
+    #[error("Key exchange failed: {0}")]
+    KeyExchange(String),
+
+    #[error("Token encryption failed: {0}")]
+    Encryption(String),
+
+    #[error("Token decryption failed")]
+    Decryption,
+
+    #[error("Malformed token: {0}")]
+    Malformed(String),
+
+    #[error("Invalid token signature")]
+    BadSignature,
+
+    #[error("Protocol version mismatch: peer={peer} ours={ours}")]
+    ProtocolMismatch { peer: u8, ours: u8 },
+
+    #[error("Ping is addressed to another identity")]
+    NotForUs,
+
+    #[error("No outgoing ping with id {0}")]
+    UnknownPing(String),
+}
+
+pub type Result<T> = std::result::Result<T, WakeError>;
+
+/// Our keys as used by the wake protocol.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct WakeIdentity {
+    #[zeroize(skip)]
+    signing_key: SigningKey,
+    x25519_secret: [u8; 32],
+    x25519_public: [u8; 32],
+}
+
+impl WakeIdentity {
+    pub fn new(signing_key: SigningKey, x25519_secret: [u8; 32]) -> Self {
+        let x25519_public =
+            x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(x25519_secret))
+                .to_bytes();
+        Self {
+            signing_key,
+            x25519_secret,
+            x25519_public,
+        }
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    pub fn x25519_public(&self) -> [u8; 32] {
+        self.x25519_public
+    }
+
+    fn shared_secret(&self, their_x25519: &[u8]) -> Result<[u8; 32]> {
+        key_exchange::derive_shared_secret(&self.x25519_secret, their_x25519)
+            .map_err(|e| WakeError::KeyExchange(e.to_string()))
+    }
+
+    /// `[type][our x25519 pubkey][token encrypted to the peer]`
+    fn seal(&self, msg_type: u8, peer_x25519: &[u8; 32], token: &[u8]) -> Result<Vec<u8>> {
+        let secret = self.shared_secret(peer_x25519)?;
+        let encrypted = encryption::encrypt_message(token, &secret)
+            .map_err(|e| WakeError::Encryption(e.to_string()))?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + encrypted.len());
+        frame.push(msg_type);
+        frame.extend_from_slice(&self.x25519_public);
+        frame.extend_from_slice(&encrypted);
+        Ok(frame)
+    }
+
+    fn open(&self, msg_type: u8, frame: &[u8]) -> Result<Vec<u8>> {
+        if frame.len() < FRAME_HEADER_LEN {
+            return Err(WakeError::Truncated(frame.len()));
+        }
+        if frame[0] != msg_type {
+            return Err(WakeError::WrongType(frame[0]));
+        }
+        let secret = self.shared_secret(&frame[1..FRAME_HEADER_LEN])?;
+        encryption::decrypt_message(&frame[FRAME_HEADER_LEN..], &secret)
+            .map_err(|_| WakeError::Decryption)
+    }
+}
+
+/// A ping ready to send, plus what the sender must keep to check the pong.
+#[derive(Clone, Debug)]
+pub struct OutgoingWake {
+    /// Hex of the ping nonce; the pong echoes it
+    pub ping_id: String,
+    pub token: PingToken,
+    /// Frame to send to the recipient's messaging port
+    pub frame: Vec<u8>,
+}
+
+impl OutgoingWake {
+    /// Key the pong must be signed with.
+    pub fn expected_signer(&self) -> [u8; 32] {
+        self.token.recipient_pubkey
+    }
+}
+
+/// A verified ping received from a peer.
+#[derive(Clone, Debug)]
+pub struct IncomingWake {
+    pub ping_id: String,
+    pub token: PingToken,
+}
+
+impl IncomingWake {
+    /// Sender's Ed25519 identity key.
+    pub fn sender(&self) -> [u8; 32] {
+        self.token.sender_pubkey
+    }
+}
+
+/// A pong ready to send back to the pinging peer.
+#[derive(Clone, Debug)]
+pub struct WakeReply {
+    pub ping_id: String,
+    pub token: PongToken,
+    pub frame: Vec<u8>,
+}
+
+impl WakeReply {
+    /// The frame without its type byte, as
+    /// [`TorManager::send_pong_response`](super::tor::TorManager::send_pong_response)
+    /// adds that itself.
+    pub fn payload(&self) -> &[u8] {
+        &self.frame[1..]
+    }
+}
+
+/// A verified pong answering one of our pings.
+#[derive(Clone, Debug)]
+pub struct CompletedWake {
+    pub ping_id: String,
+    pub token: PongToken,
+    /// Whether the recipient accepted the wake
+    pub authenticated: bool,
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn check_version(peer: u8) -> Result<()> {
+    if peer != P2P_PROTOCOL_VERSION {
+        return Err(WakeError::ProtocolMismatch {
+            peer,
+            ours: P2P_PROTOCOL_VERSION,
+        });
+    }
+    Ok(())
+}
+
+/// Build a ping to `recipient` with a fresh nonce.
+pub fn initiate_wake(
+    identity: &WakeIdentity,
+    recipient: &VerifyingKey,
+    recipient_x25519: &[u8; 32],
+) -> Result<OutgoingWake> {
+    let mut nonce = [0u8; 24];
+    getrandom::getrandom(&mut nonce).map_err(|e| WakeError::Encryption(e.to_string()))?;
+    initiate_wake_with_nonce(identity, recipient, recipient_x25519, nonce, now_secs())
+}
+
+/// Build a ping with a fixed nonce and timestamp (seconds), so retries of the
+/// same ping carry the same id.
+pub fn initiate_wake_with_nonce(
+    identity: &WakeIdentity,
+    recipient: &VerifyingKey,
+    recipient_x25519: &[u8; 32],
+    nonce: [u8; 24],
+    timestamp: i64,
+) -> Result<OutgoingWake> {
+    let token = PingToken::with_nonce(
+        &identity.signing_key,
+        recipient,
+        &identity.x25519_public,
+        recipient_x25519,
+        nonce,
+        timestamp,
+    )
+    .map_err(|e| WakeError::Malformed(e.to_string()))?;
+    let bytes = token
+        .to_bytes()
+        .map_err(|e| WakeError::Malformed(e.to_string()))?;
+    let frame = identity.seal(MSG_TYPE_PING, recipient_x25519, &bytes)?;
+    Ok(OutgoingWake {
+        ping_id: hex::encode(nonce),
+        token,
+        frame,
+    })
+}
+
+/// Decrypt and verify a ping frame addressed to us.
+pub fn receive_wake(identity: &WakeIdentity, frame: &[u8]) -> Result<IncomingWake> {
+    let bytes = identity.open(MSG_TYPE_PING, frame)?;
+    let token = PingToken::from_bytes(&bytes).map_err(|e| WakeError::Malformed(e.to_string()))?;
+    if !token.verify().unwrap_or(false) {
+        return Err(WakeError::BadSignature);
+    }
+    check_version(token.protocol_version)?;
+    if token.recipient_pubkey != identity.verifying_key().to_bytes() {
+        return Err(WakeError::NotForUs);
+    }
+    Ok(IncomingWake {
+        ping_id: hex::encode(token.nonce),
+        token,
+    })
+}
+
+/// Answer a received ping. `authenticated` is false when the user declined.
+pub fn respond_to_wake(
+    identity: &WakeIdentity,
+    ping: &PingToken,
+    authenticated: bool,
+) -> Result<WakeReply> {
+    let token = PongToken::new(ping, &identity.signing_key, authenticated)
+        .map_err(|e| WakeError::Malformed(e.to_string()))?;
+    let bytes = token
+        .to_bytes()
+        .map_err(|e| WakeError::Malformed(e.to_string()))?;
+    let frame = identity.seal(MSG_TYPE_PONG, &ping.sender_x25519_pubkey, &bytes)?;
+    Ok(WakeReply {
+        ping_id: hex::encode(ping.nonce),
+        token,
+        frame,
+    })
+}
+
+/// Decrypt a pong frame and verify it against the recipient of the ping it
+/// answers. `signer_for` maps a ping id to that recipient's Ed25519 key, or
+/// `None` if we never sent that ping.
+pub fn complete_wake(
+    identity: &WakeIdentity,
+    frame: &[u8],
+    signer_for: impl FnOnce(&str) -> Option<[u8; 32]>,
+) -> Result<CompletedWake> {
+    let bytes = identity.open(MSG_TYPE_PONG, frame)?;
+    let token = PongToken::from_bytes(&bytes).map_err(|e| WakeError::Malformed(e.to_string()))?;
+    check_version(token.protocol_version)?;
+    let ping_id = hex::encode(token.ping_nonce);
+    let signer = signer_for(&ping_id).ok_or_else(|| WakeError::UnknownPing(ping_id.clone()))?;
+    let signer = VerifyingKey::from_bytes(&signer).map_err(|_| WakeError::BadSignature)?;
+    if !token.verify(&signer).unwrap_or(false) {
+        return Err(WakeError::BadSignature);
+    }
+    Ok(CompletedWake {
+        ping_id,
+        authenticated: token.authenticated,
+        token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(seed: u8) -> WakeIdentity {
+        WakeIdentity::new(SigningKey::from_bytes(&[seed; 32]), [seed ^ 0x5a; 32])
+    }
+
+    #[test]
+    fn test_full_handshake() {
+        let alice = identity(1);
+        let bob = identity(2);
+
+        let ping = initiate_wake(&alice, &bob.verifying_key(), &bob.x25519_public()).unwrap();
+        assert_eq!(ping.frame[0], MSG_TYPE_PING);
+
+        let incoming = receive_wake(&bob, &ping.frame).unwrap();
+        assert_eq!(incoming.ping_id, ping.ping_id);
+        assert_eq!(incoming.sender(), alice.verifying_key().to_bytes());
+
+        let reply = respond_to_wake(&bob, &incoming.token, true).unwrap();
+        assert_eq!(reply.payload(), &reply.frame[1..]);
+
+        let done = complete_wake(&alice, &reply.frame, |id| {
+            (id == ping.ping_id).then(|| ping.expected_signer())
+        })
+        .unwrap();
+        assert_eq!(done.ping_id, ping.ping_id);
+        assert!(done.authenticated);
+    }
+
+    #[test]
+    fn test_retries_reuse_the_ping_id() {
+        let alice = identity(1);
+        let bob = identity(2);
+        let ping = || {
+            initiate_wake_with_nonce(
+                &alice,
+                &bob.verifying_key(),
+                &bob.x25519_public(),
+                [7; 24],
+                1,
+            )
+            .unwrap()
+        };
+        let (first, retry) = (ping(), ping());
+        assert_eq!(first.ping_id, retry.ping_id);
+        assert_eq!(first.ping_id, hex::encode([7u8; 24]));
+    }
+
+    #[test]
+    fn test_rejects_misdirected_and_forged_frames() {
+        let alice = identity(1);
+        let bob = identity(2);
+        let carol = identity(3);
+
+        let ping = initiate_wake(&alice, &bob.verifying_key(), &bob.x25519_public()).unwrap();
+        assert!(matches!(
+            receive_wake(&carol, &ping.frame),
+            Err(WakeError::Decryption)
+        ));
+        assert!(matches!(
+            receive_wake(&bob, &ping.frame[..10]),
+            Err(WakeError::Truncated(10))
+        ));
+
+        // A pong signed by someone other than the pinged peer
+        let incoming = receive_wake(&bob, &ping.frame).unwrap();
+        let forged = respond_to_wake(&carol, &incoming.token, true).unwrap();
+        assert!(matches!(
+            complete_wake(&alice, &forged.frame, |_| Some(ping.expected_signer())),
+            Err(WakeError::BadSignature)
+        ));
+        let reply = respond_to_wake(&bob, &incoming.token, true).unwrap();
+        assert!(matches!(
+            complete_wake(&alice, &reply.frame, |_| None),
+            Err(WakeError::UnknownPing(_))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+//! Message Processing Plugins
+//!
+//! Process-wide `MessagePipeline` (see `shield_protocol::protocol::middleware`).
+//! Integrators register their processors once at startup with `register`;
+//! the dispatch path calls `process` on every decrypted inbound payload
+//! before it is stored, and on every outbound payload before it is
+//! encrypted. With nothing registered, payloads pass through unchanged.
+
+use once_cell::sync::Lazy;
+use shield_protocol::protocol::middleware::{
+    MessageContext, MessagePipeline, MessageProcessor, MiddlewareError, PipelineOutcome,
+    ProcessorInfo, Violation,
+};
+use std::sync::RwLock;
+
+static PIPELINE: Lazy<RwLock<MessagePipeline>> = Lazy::new(|| RwLock::new(MessagePipeline::new()));
+
+/// Append a processor to the pipeline
+pub fn register(processor: Box<dyn MessageProcessor>) -> Result<(), MiddlewareError> {
+    PIPELINE.write().unwrap().register(processor)
+}
+
+/// Remove a processor by name
+pub fn unregister(name: &str) -> bool {
+    PIPELINE.write().unwrap().unregister(name)
+}
+
+/// Registered processors, in run order
+pub fn processors() -> Vec<ProcessorInfo> {
+    PIPELINE.read().unwrap().processors().cloned().collect()
+}
+
+/// Run the pipeline over one decrypted payload
+pub fn process(ctx: &MessageContext, body: Vec<u8>) -> (PipelineOutcome, Vec<Violation>) {
+    PIPELINE.read().unwrap().run(ctx, body)
+}
+
+/// [`process`] result as JSON for the FFI layers
+pub fn outcome_json(outcome: &PipelineOutcome, violations: &[Violation]) -> serde_json::Value {
+    use base64::Engine;
+    let violations: Vec<_> = violations
+        .iter()
+        .map(|v| serde_json::json!({ "processor": v.processor, "detail": v.detail }))
+        .collect();
+    match outcome {
+        PipelineOutcome::Deliver { body, annotations } => serde_json::json!({
+            "deliver": true,
+            "body": base64::engine::general_purpose::STANDARD.encode(body),
+            "annotations": annotations,
+            "violations": violations,
+        }),
+        PipelineOutcome::Dropped { by, reason } => serde_json::json!({
+            "deliver": false,
+            "droppedBy": by,
+            "reason": reason,
+            "violations": violations,
+        }),
+    }
+}
@@ -0,0 +1,191 @@
+//! Log Redaction
+//!
+//! Public keys, .onion addresses, contact ids and Tor service ids used to be
+//! logged verbatim at info level, which hands anyone with logcat access the
+//! user's contact graph. Log call sites in `network` and `ffi` wrap such
+//! values instead:
+//!
+//! ```ignore
+//! log::info!("Ping sent to {}", redact::onion(&recipient_onion));
+//! ```
+//!
+//! A redacted value prints as a tag such as `onion#3f9a01c2`: the first
+//! four bytes of a BLAKE3 hash keyed with a per-process random key. The same
+//! identifier gets the same tag for the whole run, so log lines can still be
+//! followed, but tags cannot be matched against a known key or address and
+//! change with every process.
+//!
+//! Debug builds with the `debug-logs` feature print full values. Release
+//! builds always redact, whatever features are enabled.
+
+use once_cell::sync::Lazy;
+use std::borrow::Cow;
+use std::fmt;
+
+/// Whether identifiers are logged in full
+pub const FULL_LOGGING: bool = cfg!(all(debug_assertions, feature = "debug-logs"));
+
+static TAG_KEY: Lazy<[u8; 32]> = Lazy::new(rand::random);
+
+enum Value<'a> {
+    Bytes(&'a [u8]),
+    Text(&'a str),
+}
+
+/// An identifier formatted for logs; see the module docs
+pub struct Redacted<'a> {
+    kind: &'static str,
+    value: Value<'a>,
+}
+
+impl Redacted<'_> {
+    fn tag_bytes(&self) -> &[u8] {
+        match self.value {
+            Value::Bytes(b) => b,
+            Value::Text(t) => t.as_bytes(),
+        }
+    }
+}
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if FULL_LOGGING {
+            return match self.value {
+                Value::Bytes(b) => f.write_str(&hex::encode(b)),
+                Value::Text(t) => f.write_str(t),
+            };
+        }
+        let digest = blake3::keyed_hash(&TAG_KEY, self.tag_bytes());
+        write!(f, "{}#{}", self.kind, hex::encode(&digest.as_bytes()[..4]))
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Key material or any other binary identifier (public keys, nonces, frame heads)
+pub fn key<T: AsRef<[u8]> + ?Sized>(bytes: &T) -> Redacted<'_> {
+    Redacted {
+        kind: "key",
+        value: Value::Bytes(bytes.as_ref()),
+    }
+}
+
+/// A .onion address or bare service id; both forms get the same tag
+pub fn onion<T: AsRef<str> + ?Sized>(address: &T) -> Redacted<'_> {
+    let address = address.as_ref();
+    Redacted {
+        kind: "onion",
+        value: Value::Text(address.strip_suffix(".onion").unwrap_or(address)),
+    }
+}
+
+/// A textual identifier such as a contact id
+pub fn id<T: AsRef<str> + ?Sized>(id: &T) -> Redacted<'_> {
+    Redacted {
+        kind: "id",
+        value: Value::Text(id.as_ref()),
+    }
+}
+
+fn is_service_id(token: &str) -> bool {
+    token.len() == 56
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || (b'2'..=b'7').contains(&b))
+}
+
+fn is_long_hex(token: &str) -> bool {
+    token.len() >= 32 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Free text that may embed identifiers (e.g. Tor control replies): v3
+/// service ids and hex runs of 32+ digits are replaced by their tags
+pub fn scrub(text: &str) -> Cow<'_, str> {
+    if FULL_LOGGING {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut tokens = text.char_indices().peekable();
+    while let Some((start, c)) = tokens.next() {
+        if !c.is_ascii_alphanumeric() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, c)) = tokens.peek() {
+            if !c.is_ascii_alphanumeric() {
+                break;
+            }
+            end = i + c.len_utf8();
+            tokens.next();
+        }
+        let token = &text[start..end];
+        let tag = if is_service_id(token) {
+            onion(token).to_string()
+        } else if is_long_hex(token) {
+            // Same tag as logging the decoded bytes with `key`
+            match hex::decode(token) {
+                Ok(bytes) => key(&bytes).to_string(),
+                Err(_) => key(token).to_string(),
+            }
+        } else {
+            continue;
+        };
+        out.push_str(&text[copied..start]);
+        out.push_str(&tag);
+        copied = end;
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(&text[copied..]);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE_ID: &str = "abcdefghijklmnopqrstuvwxyz234567abcdefghijklmnopqrstuvwx";
+
+    #[test]
+    fn test_tags_are_stable_and_hide_the_value() {
+        let pubkey = [0x42u8; 32];
+        let tag = key(&pubkey).to_string();
+        if FULL_LOGGING {
+            assert_eq!(tag, hex::encode(pubkey));
+            return;
+        }
+        assert!(tag.starts_with("key#") && tag.len() == 12);
+        assert_eq!(tag, key(&pubkey[..]).to_string());
+        assert_ne!(tag, key(&[0x43u8; 32]).to_string());
+        assert!(!tag.contains("4242"));
+
+        let bare = onion(SERVICE_ID).to_string();
+        assert_eq!(bare, onion(&format!("{}.onion", SERVICE_ID)).to_string());
+        assert!(bare.starts_with("onion#"));
+        assert!(id("sl_contact").to_string().starts_with("id#"));
+    }
+
+    #[test]
+    fn test_scrub_masks_embedded_identifiers() {
+        let reply = format!("250-ServiceID={}\r\n250 OK", SERVICE_ID);
+        let scrubbed = scrub(&reply);
+        if FULL_LOGGING {
+            assert_eq!(scrubbed, reply);
+            return;
+        }
+        assert!(!scrubbed.contains(SERVICE_ID));
+        assert!(scrubbed.starts_with("250-ServiceID=onion#"));
+        assert!(scrubbed.ends_with("\r\n250 OK"));
+
+        let hex_key = "ab".repeat(32);
+        let line = format!("key {} end", hex_key);
+        assert_eq!(scrub(&line), format!("key {} end", key(&[0xab; 32])));
+        assert!(matches!(scrub("nothing to hide"), Cow::Borrowed(_)));
+    }
+}
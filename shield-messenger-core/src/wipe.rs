@@ -0,0 +1,326 @@
+//! Emergency Wipe
+//!
+//! The single code path for "panic: wipe everything". The app first calls
+//! `arm` from its confirmation UI and gets a `WipeToken`; `emergency_wipe`
+//! refuses to run without that token, so a stray call cannot destroy data
+//! without the user having confirmed. A token is single-use and lapses
+//! after `TOKEN_TTL_SECS`.
+//!
+//! Once confirmed, every step runs even if an earlier one failed, and the
+//! outcome of each is returned in a `WipeReport`:
+//! - outbox: queued delivery events and outstanding RPC calls
+//! - inbox: queued inbound events and the store behind them
+//! - sessions: ratchets lent by the caller are dropped (zeroizing their
+//!   keys), together with pending ratchet advancements and resumption tickets
+//! - replay caches: the global PING cache and any the caller lends
+//! - trust store: `ContactTrustStore::delete_all`
+//! - volatile state: the per-contact books and cached session keys the
+//!   duress PIN also clears
+//!
+//! Databases the app owns (SQLCipher messages, contacts, keys) are not
+//! reachable from here; the app deletes them after the report comes back.
+//! Per-profile state is wiped in the active protocol context only; the app
+//! destroys the other contexts.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+use crate::crypto::replay_cache::ReplayCache;
+use crate::crypto::{PQDoubleRatchet, ResumptionBook};
+use crate::ffi::context::active_context;
+use crate::storage::ContactTrustStore;
+
+/// How long an armed token stays valid
+pub const TOKEN_TTL_SECS: u64 = 60;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum WipeError {
+    #[error("Emergency wipe was not armed")]
+    NotArmed,
+    #[error("Wipe confirmation token does not match")]
+    InvalidToken,
+    #[error("Wipe confirmation token expired")]
+    TokenExpired,
+}
+
+/// Confirmation that the user asked for a wipe, from `arm`
+#[derive(Clone, PartialEq, Eq)]
+pub struct WipeToken([u8; 16]);
+
+impl WipeToken {
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let bytes = hex::decode(s).ok()?;
+        Some(Self(bytes.try_into().ok()?))
+    }
+}
+
+impl std::fmt::Debug for WipeToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WipeToken(..)")
+    }
+}
+
+/// The one outstanding token, if any
+#[derive(Default)]
+struct Arming {
+    armed: Option<(WipeToken, u64)>,
+}
+
+impl Arming {
+    fn arm(&mut self, now: u64) -> WipeToken {
+        let mut bytes = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+        let token = WipeToken(bytes);
+        self.armed = Some((token.clone(), now));
+        token
+    }
+
+    /// Consume the armed token. A wrong token leaves it armed, so a
+    /// mistyped confirmation can be retried until it lapses.
+    fn confirm(&mut self, token: &WipeToken, now: u64) -> Result<(), WipeError> {
+        let (armed, armed_at) = self.armed.as_ref().ok_or(WipeError::NotArmed)?;
+        if now.saturating_sub(*armed_at) > TOKEN_TTL_SECS {
+            self.armed = None;
+            return Err(WipeError::TokenExpired);
+        }
+        if !bool::from(armed.0.ct_eq(&token.0)) {
+            return Err(WipeError::InvalidToken);
+        }
+        self.armed = None;
+        Ok(())
+    }
+}
+
+static ARMING: Lazy<Mutex<Arming>> = Lazy::new(|| Mutex::new(Arming::default()));
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Issue a fresh token, replacing any outstanding one
+pub fn arm() -> WipeToken {
+    log::warn!("Emergency wipe armed");
+    ARMING.lock().unwrap().arm(now_secs())
+}
+
+/// Withdraw the outstanding token (the user backed out)
+pub fn disarm() {
+    ARMING.lock().unwrap().armed = None;
+}
+
+/// State the caller hands over to be destroyed
+#[derive(Default)]
+pub struct WipeTargets<'a> {
+    /// Live ratchet sessions; dropped, which zeroizes their keys
+    pub sessions: Vec<PQDoubleRatchet>,
+    pub resumption: Option<&'a mut ResumptionBook>,
+    /// Replay caches besides the global PING cache
+    pub replay_caches: Vec<&'a mut ReplayCache>,
+    pub trust_store: Option<&'a mut dyn ContactTrustStore>,
+}
+
+/// Outcome of one wipe step
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WipeStep {
+    pub step: &'static str,
+    /// Items destroyed, where the step can count them
+    pub cleared: usize,
+    pub error: Option<String>,
+}
+
+/// What a wipe did
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WipeReport {
+    pub wiped_at: u64,
+    pub steps: Vec<WipeStep>,
+}
+
+impl WipeReport {
+    /// Whether every step succeeded
+    pub fn is_complete(&self) -> bool {
+        self.steps.iter().all(|s| s.error.is_none())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    fn record<E: std::fmt::Display>(&mut self, step: &'static str, result: Result<usize, E>) {
+        let (cleared, error) = match result {
+            Ok(n) => (n, None),
+            Err(e) => {
+                log::error!("Emergency wipe: {} failed: {}", step, e);
+                (0, Some(e.to_string()))
+            }
+        };
+        self.steps.push(WipeStep {
+            step,
+            cleared,
+            error,
+        });
+    }
+}
+
+/// Wipe everything reachable from core, once `confirmation` checks out
+pub fn emergency_wipe(
+    confirmation: WipeToken,
+    targets: WipeTargets<'_>,
+) -> Result<WipeReport, WipeError> {
+    let now = now_secs();
+    ARMING.lock().unwrap().confirm(&confirmation, now)?;
+    log::warn!("Emergency wipe confirmed; wiping");
+
+    let mut report = WipeReport {
+        wiped_at: now,
+        ..Default::default()
+    };
+    report.record::<String>(
+        "outbox",
+        Ok(crate::network::delivery::drain_events().len() + crate::network::rpc::clear()),
+    );
+    report.record("inbox", crate::network::inbox::purge());
+    report.record(
+        "pingPongSessions",
+        crate::network::pingpong::purge_sessions(),
+    );
+    report.record("fileTransfers", crate::network::file_transfer::purge());
+    {
+        let ctx = active_context();
+        let mut pending = ctx.pending_ratchets.lock().unwrap();
+        report.record::<String>("pendingRatchets", Ok(pending.len()));
+        pending.clear();
+    }
+    crate::crypto::replay_cache::clear_replay_cache();
+    wipe_targets(targets, &mut report);
+    clear_volatile_state();
+    report.record::<String>("volatileState", Ok(0));
+
+    log::warn!(
+        "Emergency wipe finished: {} steps, complete: {}",
+        report.steps.len(),
+        report.is_complete()
+    );
+    Ok(report)
+}
+
+fn wipe_targets(targets: WipeTargets<'_>, report: &mut WipeReport) {
+    let sessions = targets.sessions.len();
+    drop(targets.sessions);
+    report.record::<String>("sessions", Ok(sessions));
+    report.record::<String>(
+        "resumptionTickets",
+        Ok(targets.resumption.map_or(0, ResumptionBook::clear)),
+    );
+
+    let caches = targets.replay_caches.len();
+    for cache in targets.replay_caches {
+        cache.clear();
+    }
+    report.record::<String>("replayCaches", Ok(caches));
+
+    if let Some(store) = targets.trust_store {
+        report.record("trustStore", store.delete_all());
+    }
+}
+
+/// Clear the active context's in-memory per-contact state, shared by the
+/// duress PIN and the emergency wipe
+pub fn clear_volatile_state() {
+    let clears: [fn(); 14] = [
+        crate::network::presence::clear,
+        crate::network::ordering::clear,
+        crate::network::reactions::clear,
+        crate::network::recall::clear,
+        crate::network::downgrade::clear,
+        crate::network::security_events::clear,
+        crate::network::receipts::clear,
+        crate::network::health::clear,
+        crate::network::topics::clear,
+        crate::network::send_intents::clear,
+        crate::network::first_contact::clear_trusted_senders,
+        || active_context().with_key_change_guard(|g| g.clear()),
+        || active_context().ack_states.lock().unwrap().clear(),
+        crate::ffi::session_cache::clear_session_keys,
+    ];
+    for clear in clears {
+        clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::pqc::{ContactVerificationRecord, TrustLevel};
+    use crate::crypto::replay_cache::ReplayCacheConfig;
+    use crate::crypto::ResumptionConfig;
+    use crate::storage::FileTrustStore;
+    use crate::test_contact;
+
+    #[test]
+    fn test_token_is_single_use_and_lapses() {
+        let mut arming = Arming::default();
+        let stray = WipeToken([0; 16]);
+        assert_eq!(arming.confirm(&stray, 0), Err(WipeError::NotArmed));
+
+        let token = arming.arm(100);
+        assert_eq!(WipeToken::from_hex(&token.to_hex()), Some(token.clone()));
+        assert_eq!(arming.confirm(&stray, 101), Err(WipeError::InvalidToken));
+        assert_eq!(arming.confirm(&token, 102), Ok(()));
+        assert_eq!(arming.confirm(&token, 103), Err(WipeError::NotArmed));
+
+        let token = arming.arm(200);
+        assert_eq!(
+            arming.confirm(&token, 201 + TOKEN_TTL_SECS),
+            Err(WipeError::TokenExpired)
+        );
+    }
+
+    #[test]
+    fn test_lent_targets_are_destroyed() {
+        let path = std::env::temp_dir().join(format!("wipe-{}.trust", std::process::id()));
+        let mut store = FileTrustStore::open(&path, &[3; 32]).unwrap();
+        for b in 1..=3u8 {
+            store
+                .save_trust(&ContactVerificationRecord {
+                    contact_id: test_contact(b),
+                    trust_level: TrustLevel::Encrypted,
+                    verified_at: 0,
+                    safety_number: String::new(),
+                })
+                .unwrap();
+        }
+        let mut cache = ReplayCache::new(ReplayCacheConfig::default()).unwrap();
+        assert!(cache.check_and_insert(b"ping", 10));
+        let mut resumption = ResumptionBook::new(ResumptionConfig::default());
+
+        let mut report = WipeReport::default();
+        wipe_targets(
+            WipeTargets {
+                sessions: Vec::new(),
+                resumption: Some(&mut resumption),
+                replay_caches: vec![&mut cache],
+                trust_store: Some(&mut store),
+            },
+            &mut report,
+        );
+
+        assert!(report.is_complete());
+        let trust = report.steps.iter().find(|s| s.step == "trustStore");
+        assert_eq!(trust.map(|s| s.cleared), Some(3));
+        assert!(!path.exists());
+        assert!(store.list_verified().unwrap().is_empty());
+        assert!(!cache.contains(b"ping", 10));
+    }
+}
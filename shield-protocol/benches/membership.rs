@@ -0,0 +1,160 @@
+//! Membership benchmarks — 1k-member groups.
+//!
+//! Run with `cargo bench --bench membership`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use shield_protocol::crdt::ids::{DeviceID, GroupID};
+use shield_protocol::crdt::membership::MembershipState;
+use shield_protocol::crdt::ops::{
+    GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, OpEnvelope, OpType, Role,
+    RoleSetPayload,
+};
+use shield_protocol::crypto::signing::generate_keypair;
+
+const MEMBERS: u64 = 1_000;
+
+/// Roughly an ML-KEM-768 ciphertext plus AEAD overhead.
+const SECRET_BYTES: usize = 1_120;
+
+struct Fixture {
+    gid: GroupID,
+    owner: ([u8; 32], [u8; 32]),
+    create: OpEnvelope,
+    /// Invite/accept pairs, in apply order.
+    joins: Vec<OpEnvelope>,
+    /// Device of the first invited member.
+    first_member: DeviceID,
+}
+
+/// Owner creates a group and admits `MEMBERS` devices.
+fn fixture() -> Fixture {
+    let owner = generate_keypair();
+    let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[9; 32]);
+    let create = OpEnvelope::create_signed(
+        gid,
+        OpType::GroupCreate,
+        &GroupCreatePayload {
+            group_name: "bench".into(),
+            encrypted_group_secret: vec![0xAB; SECRET_BYTES],
+        },
+        1,
+        1,
+        owner.0,
+        &owner.1,
+    )
+    .unwrap();
+
+    let mut joins = Vec::with_capacity(2 * MEMBERS as usize);
+    let mut first_member = None;
+    for i in 0..MEMBERS {
+        let (member_pub, member_priv) = generate_keypair();
+        let device = DeviceID::from_pubkey(&member_pub);
+        first_member.get_or_insert(device);
+        let invite = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: device,
+                invited_pubkey: member_pub,
+                role: Role::Member,
+                encrypted_group_secret: vec![0xCD; SECRET_BYTES],
+            },
+            2 + 2 * i,
+            i,
+            owner.0,
+            &owner.1,
+        )
+        .unwrap();
+        let accept = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberAccept,
+            &MemberAcceptPayload {
+                invite_op_id: invite.op_id,
+                attribute_proof: None,
+            },
+            3 + 2 * i,
+            i,
+            member_pub,
+            &member_priv,
+        )
+        .unwrap();
+        joins.push(invite);
+        joins.push(accept);
+    }
+
+    Fixture {
+        gid,
+        owner,
+        create,
+        joins,
+        first_member: first_member.unwrap(),
+    }
+}
+
+fn build(f: &Fixture, lazy: bool) -> MembershipState {
+    let mut state = MembershipState::new();
+    state.set_lazy_secrets(lazy);
+    state.apply_group_create(&f.create).unwrap();
+    for op in &f.joins {
+        match op.op_type {
+            OpType::MemberInvite => state.apply_member_invite(op).unwrap(),
+            _ => state.apply_member_accept(op).unwrap(),
+        }
+    }
+    state
+}
+
+/// Apply 1k invite/accept pairs, retaining secrets vs. decoding them lazily.
+fn bench_apply(c: &mut Criterion) {
+    let f = fixture();
+    let mut group = c.benchmark_group("membership");
+    group.throughput(Throughput::Elements(MEMBERS));
+    group.sample_size(20);
+    group.bench_function("apply_1k_members", |b| b.iter(|| build(&f, false)));
+    group.bench_function("apply_1k_members_lazy_secrets", |b| {
+        b.iter(|| build(&f, true))
+    });
+    group.finish();
+}
+
+/// Snapshot a 1k-member state, then change one role: only the touched
+/// entry and the map spine are copied.
+fn bench_clone_on_write(c: &mut Criterion) {
+    let f = fixture();
+    let state = build(&f, false);
+    let role_set = OpEnvelope::create_signed(
+        f.gid,
+        OpType::RoleSet,
+        &RoleSetPayload {
+            target_device_id: f.first_member,
+            new_role: Role::Admin,
+        },
+        2 + 2 * MEMBERS,
+        0,
+        f.owner.0,
+        &f.owner.1,
+    )
+    .unwrap();
+
+    let mut group = c.benchmark_group("membership");
+    group.bench_function("clone_1k_members", |b| b.iter(|| black_box(state.clone())));
+    group.bench_function("clone_and_role_set_1k_members", |b| {
+        b.iter_batched(
+            || state.clone(),
+            |mut snapshot| {
+                snapshot.apply_role_set(&role_set).unwrap();
+                snapshot
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("secret_lookup_1k_members_lazy", |b| {
+        let lazy = build(&f, true);
+        let entry = &lazy.members()[&f.first_member];
+        b.iter(|| black_box(entry.group_secret_from(&f.joins[0]).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_apply, bench_clone_on_write);
+criterion_main!(benches);
@@ -0,0 +1,102 @@
+//! Replay cache benchmarks — 1M-message workloads.
+//!
+//! Run with `cargo bench --bench replay_cache`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use shield_protocol::crypto::replay_cache::{ReplayCache, ReplayCacheConfig};
+
+const MESSAGES: u64 = 1_000_000;
+
+/// 10 buckets × 100k entries: holds the whole 1M workload in one window.
+fn config_1m() -> ReplayCacheConfig {
+    ReplayCacheConfig {
+        buckets: 10,
+        bucket_duration_secs: 60,
+        items_per_bucket: 100_000,
+        false_positive_rate: 1e-6,
+    }
+}
+
+fn key(i: u64) -> [u8; 64] {
+    let mut k = [0u8; 64];
+    k[..8].copy_from_slice(&i.to_le_bytes());
+    k[32..40].copy_from_slice(&(!i).to_le_bytes());
+    k
+}
+
+/// Insert 1M unique messages, one bucket's worth per bucket duration.
+fn bench_insert_unique(c: &mut Criterion) {
+    let mut group = c.benchmark_group("replay_cache");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.sample_size(10);
+    group.bench_function("insert_1m_unique", |b| {
+        b.iter_batched(
+            || ReplayCache::new(config_1m()).unwrap(),
+            |mut cache| {
+                for i in 0..MESSAGES {
+                    black_box(cache.check_and_insert(&key(i), i / 100_000 * 60));
+                }
+                cache
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Replay all 1M messages against a full cache (every lookup is a hit).
+fn bench_detect_replays(c: &mut Criterion) {
+    let mut cache = ReplayCache::new(config_1m()).unwrap();
+    for i in 0..MESSAGES {
+        cache.check_and_insert(&key(i), i / 100_000 * 60);
+    }
+    let now = 9 * 60;
+
+    let mut group = c.benchmark_group("replay_cache");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.sample_size(10);
+    group.bench_function("lookup_1m_replays", |b| {
+        b.iter(|| {
+            for i in 0..MESSAGES {
+                black_box(cache.contains(&key(i), now));
+            }
+        })
+    });
+    group.bench_function("lookup_1m_fresh", |b| {
+        b.iter(|| {
+            for i in MESSAGES..2 * MESSAGES {
+                black_box(cache.contains(&key(i), now));
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Sustained overload: 1M inserts into the default (60k-entry) cache at a
+/// single timestamp, forcing continuous early rotation.
+fn bench_overload_rotation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("replay_cache");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.sample_size(10);
+    group.bench_function("overload_1m_default_config", |b| {
+        b.iter_batched(
+            || ReplayCache::new(ReplayCacheConfig::default()).unwrap(),
+            |mut cache| {
+                for i in 0..MESSAGES {
+                    black_box(cache.check_and_insert(&key(i), 0));
+                }
+                cache
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_insert_unique,
+    bench_detect_replays,
+    bench_overload_rotation
+);
+criterion_main!(benches);
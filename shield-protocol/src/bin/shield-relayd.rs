@@ -0,0 +1,221 @@
+//! Reference relay daemon.
+//!
+//! Serves [`Relay`] over TCP, one length-prefixed request/response frame at
+//! a time. Run it behind a Tor onion service and pass that service's address
+//! with `--onion`; the daemon itself never talks to Tor.
+//!
+//! ```text
+//! cargo run --release --features relayd --bin shield-relayd -- \
+//!     --onion <56 chars>.onion --listen 127.0.0.1:7700
+//! ```
+//!
+//! The relay key is kept in `--key` (created on first start) and the signed
+//! descriptor is written to `--descriptor` whenever it is re-signed, for
+//! publishing wherever clients learn about relays.
+
+use shield_protocol::crypto::signing::generate_keypair;
+use shield_protocol::protocol::relay_api::{read_frame, write_frame};
+use shield_protocol::relayd::{Relay, RelayConfig};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, thread};
+
+/// Connections served at once; further ones are closed right away.
+const MAX_CONNECTIONS: usize = 256;
+
+/// A connection with no request for this long is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often expired mail is deleted and the descriptor refreshed.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+const USAGE: &str = "usage: shield-relayd --onion <address.onion> [options]
+
+  --listen <addr>             listen address (default 127.0.0.1:7700)
+  --key <path>                relay key file (default relay.key)
+  --descriptor <path>         where to write the signed descriptor (default relay.descriptor)
+  --retention-days <n>        days to hold undelivered mail (default 7)
+  --keep-after-fetch          keep mail until it expires instead of deleting it on fetch
+  --messages-per-minute <n>   deposits allowed per connection (default 60)
+  --bucket-bits <n>           mailbox index size, 2^n buckets (default 8)
+  --max-store-mb <n>          total mail held (default 1024)";
+
+struct Options {
+    listen: String,
+    key: PathBuf,
+    descriptor: PathBuf,
+    config: RelayConfig,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        listen: "127.0.0.1:7700".into(),
+        key: "relay.key".into(),
+        descriptor: "relay.descriptor".into(),
+        config: RelayConfig::default(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--keep-after-fetch" {
+            options.config.retention.delete_on_fetch = false;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("{}: not a number: {}", flag, value))
+        };
+        match flag.as_str() {
+            "--onion" => options.config.onion_address = value.clone(),
+            "--listen" => options.listen = value.clone(),
+            "--key" => options.key = value.clone().into(),
+            "--descriptor" => options.descriptor = value.clone().into(),
+            "--retention-days" => {
+                options.config.retention.max_retention_secs = number()? * 24 * 60 * 60
+            }
+            "--messages-per-minute" => {
+                options.config.rate_limits.messages_per_minute = number()? as u32
+            }
+            "--bucket-bits" => options.config.bucket_bits = number()? as u8,
+            "--max-store-mb" => options.config.max_store_bytes = (number()? as usize) << 20,
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    if options.config.onion_address.is_empty() {
+        return Err("--onion is required".into());
+    }
+    Ok(options)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Read the relay's Ed25519 seed, creating it (owner-only) on first start.
+fn load_or_create_key(path: &Path) -> Result<[u8; 32], String> {
+    match fs::read(path) {
+        Ok(bytes) => bytes
+            .try_into()
+            .map_err(|_| format!("{}: not a 32-byte key", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let (_, secret) = generate_keypair();
+            let mut file = fs::OpenOptions::new();
+            file.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+            std::io::Write::write_all(&mut file.open(path).map_err(|e| e.to_string())?, &secret)
+                .map_err(|e| e.to_string())?;
+            Ok(secret)
+        }
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Write the descriptor if it changed since `last`.
+fn publish_descriptor(relay: &Mutex<Relay>, path: &Path, last: &mut Vec<u8>) {
+    let descriptor = match relay.lock().unwrap().descriptor(now_secs()) {
+        Ok(descriptor) => descriptor.to_vec(),
+        Err(e) => {
+            eprintln!("shield-relayd: failed to sign descriptor: {}", e);
+            return;
+        }
+    };
+    if descriptor != *last {
+        match fs::write(path, &descriptor) {
+            Ok(()) => *last = descriptor,
+            Err(e) => eprintln!("shield-relayd: {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn serve(relay: &Mutex<Relay>, client: u64, mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+    while let Ok(Some(frame)) = read_frame(&mut stream) {
+        let response = relay
+            .lock()
+            .unwrap()
+            .handle_frame(client, &frame, now_secs());
+        if write_frame(&mut stream, &response).is_err() {
+            break;
+        }
+    }
+    relay.lock().unwrap().disconnect(client);
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("shield-relayd: {}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let key = match load_or_create_key(&options.key) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("shield-relayd: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let relay = match Relay::new(options.config, key, now_secs()) {
+        Ok(relay) => Arc::new(Mutex::new(relay)),
+        Err(e) => {
+            eprintln!("shield-relayd: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match TcpListener::bind(&options.listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("shield-relayd: {}: {}", options.listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut published = Vec::new();
+    publish_descriptor(&relay, &options.descriptor, &mut published);
+    println!(
+        "shield-relayd: relay {} listening on {}",
+        hex::encode(relay.lock().unwrap().public_key()),
+        options.listen
+    );
+
+    let upkeep = relay.clone();
+    thread::spawn(move || loop {
+        thread::sleep(UPKEEP_INTERVAL);
+        let expired = upkeep.lock().unwrap().expire(now_secs());
+        if expired > 0 {
+            println!("shield-relayd: deleted {} expired envelopes", expired);
+        }
+        publish_descriptor(&upkeep, &options.descriptor, &mut published);
+    });
+
+    let next_client = AtomicU64::new(1);
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+        let client = next_client.fetch_add(1, Ordering::Relaxed);
+        let (relay, open) = (relay.clone(), open.clone());
+        thread::spawn(move || {
+            serve(&relay, client, stream);
+            open.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    ExitCode::SUCCESS
+}
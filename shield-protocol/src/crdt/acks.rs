@@ -0,0 +1,414 @@
+/// Op receipt acknowledgments — per-member delivery tracking for group ops.
+///
+/// Anti-entropy (`sync`) repairs every gap eventually, but only when a round
+/// runs. Acks tell an author sooner which members are behind:
+///
+/// - Receivers record each op they apply in an `AckBatcher`. Once an author's
+///   batch has waited `ACK_FLUSH_INTERVAL_MS`, or holds `MAX_OPS_PER_ACK` ids,
+///   it goes back to that author as one `OpAck`.
+/// - The author's `DeliveryTracker` remembers which active members each
+///   broadcast op was meant for and crosses them off as acks arrive.
+///   `lagging` lists members still missing ops after a grace period, with
+///   the op ids, so the app can push exactly those ops to exactly that member
+///   instead of waiting for the next full round.
+///
+/// An `OpAck` names no sender: it travels over the pairwise message layer,
+/// which already authenticates the peer, and the caller passes that peer to
+/// `DeliveryTracker::on_ack`.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+use crate::crdt::apply::GroupState;
+use crate::crdt::ids::{DeviceID, GroupID, OpID};
+use crate::crdt::limits::MAX_OPS_PER_ACK;
+use crate::crdt::ops::{OpEnvelope, OpError};
+
+/// Longest a received op waits before it is acknowledged.
+pub const ACK_FLUSH_INTERVAL_MS: u64 = 2_000;
+
+/// Unacknowledged ops remembered per group; older ones are left to
+/// anti-entropy.
+pub const MAX_TRACKED_OPS: usize = 4_096;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum AckError {
+    #[error("Ack targets wrong group")]
+    WrongGroup,
+
+    #[error("Ack lists {0} ops (max {MAX_OPS_PER_ACK})")]
+    TooLarge(usize),
+}
+
+// ---------------------------------------------------------------------------
+// Wire message
+// ---------------------------------------------------------------------------
+
+/// Ops of one author that a member has received.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OpAck {
+    pub group_id: GroupID,
+    pub received: Vec<OpID>,
+}
+
+impl OpAck {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Receiver side
+// ---------------------------------------------------------------------------
+
+struct PendingAcks {
+    ops: Vec<OpID>,
+    /// When the oldest unsent entry was recorded.
+    since_ms: u64,
+}
+
+/// Collects received op ids into per-author acks.
+pub struct AckBatcher {
+    group_id: GroupID,
+    local: DeviceID,
+    pending: BTreeMap<DeviceID, PendingAcks>,
+}
+
+impl AckBatcher {
+    /// `local` is this device; its own ops are never acknowledged.
+    pub fn new(group_id: GroupID, local: DeviceID) -> Self {
+        AckBatcher {
+            group_id,
+            local,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Note an op received from the group, by broadcast or by sync.
+    pub fn record(&mut self, op: &OpEnvelope, now_ms: u64) {
+        let author = op.op_id.author;
+        if op.group_id != self.group_id || author == self.local {
+            return;
+        }
+        self.pending
+            .entry(author)
+            .or_insert_with(|| PendingAcks {
+                ops: Vec::new(),
+                since_ms: now_ms,
+            })
+            .ops
+            .push(op.op_id);
+    }
+
+    /// Acks to send now, each with the author it goes to: batches that are
+    /// full or have waited `ACK_FLUSH_INTERVAL_MS`.
+    pub fn take_due(&mut self, now_ms: u64) -> Vec<(DeviceID, OpAck)> {
+        let due: Vec<DeviceID> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| {
+                p.ops.len() >= MAX_OPS_PER_ACK
+                    || now_ms.saturating_sub(p.since_ms) >= ACK_FLUSH_INTERVAL_MS
+            })
+            .map(|(author, _)| *author)
+            .collect();
+        let batches: Vec<(DeviceID, Vec<OpID>)> = due
+            .into_iter()
+            .filter_map(|author| self.pending.remove(&author).map(|p| (author, p.ops)))
+            .collect();
+        batches
+            .into_iter()
+            .flat_map(|(author, ops)| self.split(author, ops))
+            .collect()
+    }
+
+    /// Every pending ack regardless of age, e.g. before going offline.
+    pub fn flush(&mut self) -> Vec<(DeviceID, OpAck)> {
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .flat_map(|(author, pending)| self.split(author, pending.ops))
+            .collect()
+    }
+
+    fn split(&self, author: DeviceID, mut ops: Vec<OpID>) -> Vec<(DeviceID, OpAck)> {
+        ops.sort();
+        ops.dedup();
+        ops.chunks(MAX_OPS_PER_ACK)
+            .map(|chunk| {
+                let ack = OpAck {
+                    group_id: self.group_id,
+                    received: chunk.to_vec(),
+                };
+                (author, ack)
+            })
+            .collect()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Author side
+// ---------------------------------------------------------------------------
+
+/// A member that has not acknowledged some of our ops in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberLag {
+    pub member: DeviceID,
+    /// In `(lamport, author, nonce)` order, ready to resend.
+    pub missing: Vec<OpID>,
+    /// When the longest-waiting of them was last sent.
+    pub oldest_sent_ms: u64,
+}
+
+/// Which members have acknowledged which of our broadcast ops.
+pub struct DeliveryTracker {
+    group_id: GroupID,
+    /// Op → members yet to ack it, with when it was last sent to each.
+    outstanding: BTreeMap<OpID, BTreeMap<DeviceID, u64>>,
+}
+
+impl DeliveryTracker {
+    pub fn new(group_id: GroupID) -> Self {
+        DeliveryTracker {
+            group_id,
+            outstanding: BTreeMap::new(),
+        }
+    }
+
+    /// Expect acks for `op` from every active member except its author.
+    pub fn track(&mut self, op: &OpEnvelope, state: &GroupState, now_ms: u64) {
+        let recipients: Vec<DeviceID> = state
+            .membership
+            .members()
+            .values()
+            .filter(|m| m.accepted && !m.removed && m.device_id != op.op_id.author)
+            .map(|m| m.device_id)
+            .collect();
+        self.track_for(op.op_id, recipients, now_ms);
+    }
+
+    /// Expect acks for `op_id` from `recipients`.
+    pub fn track_for(
+        &mut self,
+        op_id: OpID,
+        recipients: impl IntoIterator<Item = DeviceID>,
+        now_ms: u64,
+    ) {
+        let waiting: BTreeMap<DeviceID, u64> = recipients
+            .into_iter()
+            .map(|member| (member, now_ms))
+            .collect();
+        if waiting.is_empty() {
+            return;
+        }
+        self.outstanding.insert(op_id, waiting);
+        while self.outstanding.len() > MAX_TRACKED_OPS {
+            self.outstanding.pop_first();
+        }
+    }
+
+    /// Apply an ack from `member` (the authenticated sender). Returns how
+    /// many tracked ops it confirmed; ids we are not waiting on are ignored.
+    pub fn on_ack(&mut self, member: DeviceID, ack: &OpAck) -> Result<usize, AckError> {
+        if ack.group_id != self.group_id {
+            return Err(AckError::WrongGroup);
+        }
+        if ack.received.len() > MAX_OPS_PER_ACK {
+            return Err(AckError::TooLarge(ack.received.len()));
+        }
+        let mut confirmed = 0;
+        for op_id in &ack.received {
+            if let Some(waiting) = self.outstanding.get_mut(op_id) {
+                if waiting.remove(&member).is_some() {
+                    confirmed += 1;
+                }
+                if waiting.is_empty() {
+                    self.outstanding.remove(op_id);
+                }
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Stop waiting on a member, e.g. after it left the group.
+    pub fn forget_member(&mut self, member: &DeviceID) {
+        self.outstanding.retain(|_, waiting| {
+            waiting.remove(member);
+            !waiting.is_empty()
+        });
+    }
+
+    /// Members with ops unacknowledged for at least `grace_ms`, most
+    /// missing first.
+    pub fn lagging(&self, now_ms: u64, grace_ms: u64) -> Vec<MemberLag> {
+        let mut lags: BTreeMap<DeviceID, MemberLag> = BTreeMap::new();
+        for (op_id, waiting) in &self.outstanding {
+            for (member, sent_ms) in waiting {
+                if now_ms.saturating_sub(*sent_ms) < grace_ms {
+                    continue;
+                }
+                let lag = lags.entry(*member).or_insert_with(|| MemberLag {
+                    member: *member,
+                    missing: Vec::new(),
+                    oldest_sent_ms: *sent_ms,
+                });
+                lag.missing.push(*op_id);
+                lag.oldest_sent_ms = lag.oldest_sent_ms.min(*sent_ms);
+            }
+        }
+        let mut lags: Vec<MemberLag> = lags.into_values().collect();
+        lags.sort_by_key(|lag| std::cmp::Reverse(lag.missing.len()));
+        lags
+    }
+
+    /// Restart the grace period for `ops` resent to `member`.
+    pub fn mark_resent(&mut self, member: &DeviceID, ops: &[OpID], now_ms: u64) {
+        for op_id in ops {
+            if let Some(sent_ms) = self
+                .outstanding
+                .get_mut(op_id)
+                .and_then(|waiting| waiting.get_mut(member))
+            {
+                *sent_ms = now_ms;
+            }
+        }
+    }
+
+    /// Members still owing an ack for `op_id`.
+    pub fn awaiting(&self, op_id: &OpID) -> BTreeSet<DeviceID> {
+        self.outstanding
+            .get(op_id)
+            .map(|waiting| waiting.keys().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{MsgAddPayload, OpType};
+
+    fn device(b: u8) -> DeviceID {
+        DeviceID::from_bytes([b; 16])
+    }
+
+    fn group() -> GroupID {
+        GroupID::from_bytes([0xA5; 32])
+    }
+
+    fn msg_op(lamport: u64) -> (OpEnvelope, DeviceID) {
+        let (pub_k, priv_k) = crate::crypto::signing::generate_keypair();
+        let payload = MsgAddPayload {
+            msg_id: [lamport as u8; 32],
+            ciphertext: vec![1],
+            nonce: [0; 24],
+        };
+        let op = OpEnvelope::create_signed(
+            group(),
+            OpType::MsgAdd,
+            &payload,
+            lamport,
+            lamport,
+            pub_k,
+            &priv_k,
+        )
+        .unwrap();
+        let author = op.op_id.author;
+        (op, author)
+    }
+
+    #[test]
+    fn test_batcher_flushes_per_author() {
+        let (op1, author) = msg_op(1);
+        let (other, other_author) = msg_op(2);
+        let mut batcher = AckBatcher::new(group(), device(9));
+
+        batcher.record(&op1, 1_000);
+        batcher.record(&op1, 1_100);
+        batcher.record(&other, 2_500);
+        assert!(batcher.take_due(2_999).is_empty());
+
+        // Only the first author's batch has waited long enough
+        let due = batcher.take_due(3_000);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, author);
+        assert_eq!(due[0].1.received, vec![op1.op_id]);
+
+        let rest = batcher.flush();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].0, other_author);
+        let ack = OpAck::from_bytes(&rest[0].1.to_bytes().unwrap()).unwrap();
+        assert_eq!(ack.received, vec![other.op_id]);
+
+        // Own ops are not acknowledged
+        let mut own = AckBatcher::new(group(), author);
+        own.record(&op1, 0);
+        assert!(own.flush().is_empty());
+    }
+
+    #[test]
+    fn test_full_batch_is_due_at_once_and_split() {
+        let mut batcher = AckBatcher::new(group(), device(9));
+        let (op, author) = msg_op(1);
+        for nonce in 0..(MAX_OPS_PER_ACK as u64 + 1) {
+            let mut op = op.clone();
+            op.op_id.nonce = nonce;
+            batcher.record(&op, 0);
+        }
+        let due = batcher.take_due(0);
+        assert_eq!(due.len(), 2);
+        assert!(due.iter().all(|(to, _)| *to == author));
+        assert_eq!(due[0].1.received.len(), MAX_OPS_PER_ACK);
+        assert_eq!(due[1].1.received.len(), 1);
+    }
+
+    #[test]
+    fn test_tracker_reports_lagging_members() {
+        let (op1, _) = msg_op(1);
+        let (op2, _) = msg_op(2);
+        let (fast, slow, gone) = (device(1), device(2), device(3));
+        let mut tracker = DeliveryTracker::new(group());
+        tracker.track_for(op1.op_id, [fast, slow, gone], 0);
+        tracker.track_for(op2.op_id, [fast, slow, gone], 500);
+
+        let ack = OpAck {
+            group_id: group(),
+            received: vec![op1.op_id, op2.op_id],
+        };
+        assert_eq!(tracker.on_ack(fast, &ack).unwrap(), 2);
+        assert_eq!(tracker.on_ack(fast, &ack).unwrap(), 0);
+        tracker.forget_member(&gone);
+        assert_eq!(tracker.awaiting(&op1.op_id), BTreeSet::from([slow]));
+
+        assert!(tracker.lagging(4_000, 5_000).is_empty());
+        let lags = tracker.lagging(5_000, 5_000);
+        assert_eq!(lags.len(), 1);
+        assert_eq!(lags[0].member, slow);
+        assert_eq!(lags[0].missing, vec![op1.op_id]);
+        assert_eq!(lags[0].oldest_sent_ms, 0);
+
+        // After a targeted resend the member gets a fresh grace period
+        tracker.mark_resent(&slow, &[op1.op_id], 5_000);
+        assert_eq!(tracker.lagging(6_000, 5_000)[0].missing, vec![op2.op_id]);
+
+        let foreign = OpAck {
+            group_id: GroupID::from_bytes([0; 32]),
+            received: vec![],
+        };
+        assert!(matches!(
+            tracker.on_ack(slow, &foreign),
+            Err(AckError::WrongGroup)
+        ));
+    }
+}
@@ -0,0 +1,533 @@
+/// Address book journal — the account's personal-data CRDT.
+///
+/// Contact additions, removals and nickname edits are recorded as signed,
+/// immutable journal entries, separate from any message or group log. The
+/// account's linked devices replicate the journal among themselves exactly
+/// like group ops: each device stamps its entries with its lamport clock,
+/// and a sync exchanges a `JournalDigest` (highest lamport seen per device)
+/// followed by the entries the other side lacks. A newly linked device
+/// starts from an empty digest and receives the whole journal; afterwards
+/// only the difference travels, so no full backup restore is needed.
+///
+/// The derived address book is order independent. Per contact, listing
+/// (added / removed) and nickname are separate last-writer-wins registers
+/// stamped `(lamport, author)`: an `Added` sets both, `Removed` only the
+/// listing, `Renamed` only the nickname, so a rename racing a removal on
+/// another device does not bring the contact back.
+///
+/// Only entries signed by a device linked to the account are applied; the
+/// app links its own devices when pairing them.
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+use crate::crdt::ids::DeviceID;
+use crate::crdt::ops::{now_ms, OpError};
+use crate::protocol::contact_id::ContactId;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Nickname exceeds {max} bytes ({size})")]
+    NicknameTooLong { size: usize, max: usize },
+
+    #[error("Journal entry from unlinked device {0}")]
+    UnlinkedDevice(DeviceID),
+
+    #[error("Author DeviceID does not match pubkey")]
+    AuthorMismatch,
+
+    #[error("Invalid journal entry signature")]
+    InvalidSignature,
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+/// Longest nickname, in bytes.
+pub const MAX_NICKNAME_BYTES: usize = 128;
+
+const SIGNING_DOMAIN: &[u8] = b"SM-ADDRESS-JOURNAL-v1";
+
+// ---------------------------------------------------------------------------
+// Entries
+// ---------------------------------------------------------------------------
+
+/// One change to the address book.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ContactChange {
+    Added {
+        contact_id: ContactId,
+        nickname: String,
+    },
+    Removed {
+        contact_id: ContactId,
+    },
+    Renamed {
+        contact_id: ContactId,
+        nickname: String,
+    },
+}
+
+impl ContactChange {
+    pub fn contact_id(&self) -> &ContactId {
+        match self {
+            ContactChange::Added { contact_id, .. }
+            | ContactChange::Removed { contact_id }
+            | ContactChange::Renamed { contact_id, .. } => contact_id,
+        }
+    }
+
+    fn nickname(&self) -> Option<&str> {
+        match self {
+            ContactChange::Added { nickname, .. } | ContactChange::Renamed { nickname, .. } => {
+                Some(nickname)
+            }
+            ContactChange::Removed { .. } => None,
+        }
+    }
+}
+
+/// Signed journal entry, replicated between the account's devices.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub author: DeviceID,
+    pub lamport: u64,
+    /// Wall clock in milliseconds — UX only, not used for ordering.
+    pub timestamp_ms: u64,
+    pub change: ContactChange,
+    /// Ed25519 public key of the authoring device.
+    pub author_pubkey: [u8; 32],
+    /// Ed25519 signature over BLAKE3(signable_bytes).
+    #[serde(with = "BigArray")]
+    pub signature: [u8; 64],
+}
+
+impl JournalEntry {
+    /// Create and sign an entry at `lamport`.
+    pub fn create_signed(
+        change: ContactChange,
+        lamport: u64,
+        author_pubkey: [u8; 32],
+        author_privkey: &[u8; 32],
+    ) -> Result<Self, JournalError> {
+        if let Some(nickname) = change.nickname() {
+            if nickname.len() > MAX_NICKNAME_BYTES {
+                return Err(JournalError::NicknameTooLong {
+                    size: nickname.len(),
+                    max: MAX_NICKNAME_BYTES,
+                });
+            }
+        }
+        let mut entry = JournalEntry {
+            author: DeviceID::from_pubkey(&author_pubkey),
+            lamport,
+            timestamp_ms: now_ms(),
+            change,
+            author_pubkey,
+            signature: [0u8; 64],
+        };
+        let hash = blake3::hash(&entry.signable_bytes()?);
+        entry.signature = crate::crypto::signing::sign_data(hash.as_bytes(), author_privkey)
+            .map_err(|e| OpError::SigningFailed(e.to_string()))?;
+        Ok(entry)
+    }
+
+    /// Check the author binding, signature and nickname bound.
+    pub fn verify(&self) -> Result<(), JournalError> {
+        if DeviceID::from_pubkey(&self.author_pubkey) != self.author {
+            return Err(JournalError::AuthorMismatch);
+        }
+        if let Some(nickname) = self.change.nickname() {
+            if nickname.len() > MAX_NICKNAME_BYTES {
+                return Err(JournalError::NicknameTooLong {
+                    size: nickname.len(),
+                    max: MAX_NICKNAME_BYTES,
+                });
+            }
+        }
+        let hash = blake3::hash(&self.signable_bytes()?);
+        match crate::crypto::signing::verify_signature(
+            hash.as_bytes(),
+            &self.signature,
+            &self.author_pubkey,
+        ) {
+            Ok(true) => Ok(()),
+            _ => Err(JournalError::InvalidSignature),
+        }
+    }
+
+    fn signable_bytes(&self) -> Result<Vec<u8>, OpError> {
+        let signable = (
+            SIGNING_DOMAIN,
+            &self.author,
+            self.lamport,
+            self.timestamp_ms,
+            &self.change,
+            &self.author_pubkey,
+        );
+        bincode::serialize(&signable).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    fn stamp(&self) -> Stamp {
+        (self.lamport, self.author)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+}
+
+/// Highest lamport seen from each device.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct JournalDigest {
+    pub per_device_lamport: BTreeMap<DeviceID, u64>,
+}
+
+impl JournalDigest {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Address book
+// ---------------------------------------------------------------------------
+
+/// LWW stamp: lamport, ties broken by author.
+type Stamp = (u64, DeviceID);
+
+/// Derived state of one contact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressBookContact {
+    pub contact_id: ContactId,
+    pub nickname: String,
+    /// False once removed (and not re-added since).
+    pub listed: bool,
+    listed_at: Stamp,
+    named_at: Option<Stamp>,
+}
+
+/// The journal and the address book derived from it.
+#[derive(Clone, Debug, Default)]
+pub struct AddressBook {
+    devices: BTreeSet<DeviceID>,
+    entries: BTreeMap<Stamp, JournalEntry>,
+    contacts: BTreeMap<ContactId, AddressBookContact>,
+    max_lamport: BTreeMap<DeviceID, u64>,
+}
+
+impl AddressBook {
+    /// Empty journal writable by the devices with these identity keys.
+    pub fn new(device_pubkeys: &[[u8; 32]]) -> Self {
+        let mut book = Self::default();
+        for pubkey in device_pubkeys {
+            book.link_device(pubkey);
+        }
+        book
+    }
+
+    /// Rebuild from persisted entries; entries that fail verification are
+    /// skipped and counted.
+    pub fn rebuild(device_pubkeys: &[[u8; 32]], entries: Vec<JournalEntry>) -> (Self, usize) {
+        let mut book = Self::new(device_pubkeys);
+        let rejected = entries
+            .into_iter()
+            .filter(|entry| book.apply(entry.clone()).is_err())
+            .count();
+        (book, rejected)
+    }
+
+    /// Accept entries from another device of this account.
+    pub fn link_device(&mut self, pubkey: &[u8; 32]) {
+        self.devices.insert(DeviceID::from_pubkey(pubkey));
+    }
+
+    /// Stop accepting new entries from a device; what it wrote stays.
+    pub fn unlink_device(&mut self, pubkey: &[u8; 32]) -> bool {
+        self.devices.remove(&DeviceID::from_pubkey(pubkey))
+    }
+
+    /// Record a local change: sign it past every lamport seen and apply it.
+    /// Returns the entry to send to the other devices.
+    pub fn record(
+        &mut self,
+        change: ContactChange,
+        author_pubkey: [u8; 32],
+        author_privkey: &[u8; 32],
+    ) -> Result<JournalEntry, JournalError> {
+        let lamport = self.max_lamport.values().max().copied().unwrap_or(0) + 1;
+        let entry = JournalEntry::create_signed(change, lamport, author_pubkey, author_privkey)?;
+        self.apply(entry.clone())?;
+        Ok(entry)
+    }
+
+    /// Apply an entry from any device; `Ok(false)` if it was already known.
+    pub fn apply(&mut self, entry: JournalEntry) -> Result<bool, JournalError> {
+        if !self.devices.contains(&entry.author) {
+            return Err(JournalError::UnlinkedDevice(entry.author));
+        }
+        if self.entries.contains_key(&entry.stamp()) {
+            return Ok(false);
+        }
+        entry.verify()?;
+
+        let stamp = entry.stamp();
+        let contact = self
+            .contacts
+            .entry(*entry.change.contact_id())
+            .or_insert_with(|| AddressBookContact {
+                contact_id: *entry.change.contact_id(),
+                nickname: String::new(),
+                listed: false,
+                listed_at: (0, entry.author),
+                named_at: None,
+            });
+        let lists = match &entry.change {
+            ContactChange::Added { .. } => Some(true),
+            ContactChange::Removed { .. } => Some(false),
+            ContactChange::Renamed { .. } => None,
+        };
+        if let Some(listed) = lists {
+            if stamp > contact.listed_at {
+                contact.listed = listed;
+                contact.listed_at = stamp;
+            }
+        }
+        if let Some(nickname) = entry.change.nickname() {
+            if contact.named_at.is_none_or(|named_at| stamp > named_at) {
+                contact.nickname = nickname.to_string();
+                contact.named_at = Some(stamp);
+            }
+        }
+
+        let max = self.max_lamport.entry(entry.author).or_insert(0);
+        *max = (*max).max(entry.lamport);
+        self.entries.insert(stamp, entry);
+        Ok(true)
+    }
+
+    /// Apply a batch from a sync; returns how many entries were new.
+    pub fn apply_batch(&mut self, entries: Vec<JournalEntry>) -> Result<usize, JournalError> {
+        let mut applied = 0;
+        for entry in entries {
+            if self.apply(entry)? {
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+
+    pub fn digest(&self) -> JournalDigest {
+        JournalDigest {
+            per_device_lamport: self.max_lamport.clone(),
+        }
+    }
+
+    /// Entries the peer lacks according to its digest, in lamport order.
+    pub fn missing_from(&self, remote: &JournalDigest) -> Vec<JournalEntry> {
+        let mut missing: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| {
+                let seen = remote.per_device_lamport.get(&entry.author).copied();
+                !matches!(seen, Some(max) if entry.lamport <= max)
+            })
+            .cloned()
+            .collect();
+        missing.sort_by_key(JournalEntry::stamp);
+        missing
+    }
+
+    /// Contacts currently in the address book.
+    pub fn contacts(&self) -> impl Iterator<Item = &AddressBookContact> {
+        self.contacts.values().filter(|c| c.listed)
+    }
+
+    pub fn contact(&self, contact_id: &ContactId) -> Option<&AddressBookContact> {
+        self.contacts.get(contact_id).filter(|c| c.listed)
+    }
+
+    /// Every entry, for persistence.
+    pub fn entries(&self) -> impl Iterator<Item = &JournalEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair;
+    use crate::protocol::contact_id::test_contact;
+
+    #[test]
+    fn test_devices_converge_and_new_device_catches_up() {
+        let (phone_pub, phone_priv) = generate_keypair();
+        let (laptop_pub, laptop_priv) = generate_keypair();
+        let (tablet_pub, _) = generate_keypair();
+        let devices = [phone_pub, laptop_pub, tablet_pub];
+        let mut phone = AddressBook::new(&devices);
+        let mut laptop = AddressBook::new(&devices);
+
+        let added = phone
+            .record(
+                ContactChange::Added {
+                    contact_id: test_contact(1),
+                    nickname: "Sam".into(),
+                },
+                phone_pub,
+                &phone_priv,
+            )
+            .unwrap();
+        laptop.apply(added.clone()).unwrap();
+
+        // Concurrent: the phone removes contact 1 while the laptop renames it
+        let removed = phone
+            .record(
+                ContactChange::Removed {
+                    contact_id: test_contact(1),
+                },
+                phone_pub,
+                &phone_priv,
+            )
+            .unwrap();
+        let renamed = laptop
+            .record(
+                ContactChange::Renamed {
+                    contact_id: test_contact(1),
+                    nickname: "Sam (work)".into(),
+                },
+                laptop_pub,
+                &laptop_priv,
+            )
+            .unwrap();
+        assert_eq!(removed.lamport, renamed.lamport);
+
+        assert_eq!(
+            phone
+                .apply_batch(laptop.missing_from(&phone.digest()))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            laptop
+                .apply_batch(phone.missing_from(&laptop.digest()))
+                .unwrap(),
+            1
+        );
+        assert!(phone.contact(&test_contact(1)).is_none());
+        assert_eq!(
+            phone.contacts().collect::<Vec<_>>(),
+            laptop.contacts().collect::<Vec<_>>()
+        );
+
+        // Re-added later, with the nickname given on re-add
+        let readded = phone
+            .record(
+                ContactChange::Added {
+                    contact_id: test_contact(1),
+                    nickname: "Sam".into(),
+                },
+                phone_pub,
+                &phone_priv,
+            )
+            .unwrap();
+        assert_eq!(phone.contact(&test_contact(1)).unwrap().nickname, "Sam");
+
+        // A new device receives everything in lamport order, then only the rest
+        let mut tablet = AddressBook::new(&devices);
+        let everything = phone.missing_from(&tablet.digest());
+        assert_eq!(everything.len(), 4);
+        tablet.apply_batch(everything[..3].to_vec()).unwrap();
+        assert_eq!(phone.missing_from(&tablet.digest()), vec![readded]);
+        tablet
+            .apply_batch(phone.missing_from(&tablet.digest()))
+            .unwrap();
+        assert_eq!(
+            tablet
+                .contact(&test_contact(1))
+                .map(|c| c.nickname.as_str()),
+            Some("Sam")
+        );
+        assert!(phone.missing_from(&tablet.digest()).is_empty());
+    }
+
+    #[test]
+    fn test_rejects_unlinked_and_tampered_entries() {
+        let (phone_pub, phone_priv) = generate_keypair();
+        let (stranger_pub, stranger_priv) = generate_keypair();
+        let mut book = AddressBook::new(&[phone_pub]);
+
+        let foreign = JournalEntry::create_signed(
+            ContactChange::Removed {
+                contact_id: test_contact(2),
+            },
+            1,
+            stranger_pub,
+            &stranger_priv,
+        )
+        .unwrap();
+        assert!(matches!(
+            book.apply(foreign),
+            Err(JournalError::UnlinkedDevice(_))
+        ));
+
+        let mut entry = JournalEntry::create_signed(
+            ContactChange::Added {
+                contact_id: test_contact(2),
+                nickname: "Kim".into(),
+            },
+            1,
+            phone_pub,
+            &phone_priv,
+        )
+        .unwrap();
+        let bytes = entry.to_bytes().unwrap();
+        entry.change = ContactChange::Removed {
+            contact_id: test_contact(2),
+        };
+        assert!(matches!(
+            book.apply(entry),
+            Err(JournalError::InvalidSignature)
+        ));
+        let (rebuilt, rejected) = AddressBook::rebuild(
+            &[phone_pub],
+            vec![JournalEntry::from_bytes(&bytes).unwrap()],
+        );
+        assert_eq!(rejected, 0);
+        assert_eq!(rebuilt.contacts().count(), 1);
+
+        assert!(matches!(
+            JournalEntry::create_signed(
+                ContactChange::Renamed {
+                    contact_id: test_contact(2),
+                    nickname: "x".repeat(MAX_NICKNAME_BYTES + 1),
+                },
+                2,
+                phone_pub,
+                &phone_priv,
+            ),
+            Err(JournalError::NicknameTooLong { .. })
+        ));
+    }
+}
@@ -0,0 +1,411 @@
+/// Attribute-gated admission — join a group by proving an attribute without
+/// revealing it.
+///
+/// A community sets the `JoinRequirement` metadata register to a CBOR
+/// [`JoinRequirement`]: the attribute name (e.g. `"age"`), the issuer trusted
+/// to certify it, and a predicate (`AtLeast(18)`, `OneOf([..])`, ...). The
+/// invite flow is unchanged up to the accept: the invitee answers the
+/// `MemberInvite` with a `MemberAccept` whose `attribute_proof` carries an
+/// [`AttributePresentation`] — their issuer-signed credential plus a
+/// zero-knowledge proof that its hidden value satisfies the predicate.
+///
+/// The proof is bound to the group, the invite op and the accepting device
+/// key, and the credential must have been issued to that device, so a
+/// presentation cannot be lent to another invitee or replayed into another
+/// group. Every replica checks it when applying the accept; an accept without
+/// a valid presentation is rejected and the invitee never becomes active.
+///
+/// The requirement in force is the one at the accept's position in replay
+/// order, so invites sent before a requirement was set must satisfy it too.
+/// `OwnerTransfer` is not gated.
+use thiserror::Error;
+
+use crate::crdt::ids::{GroupID, OpID};
+use crate::crdt::ops::{MemberAcceptPayload, OpEnvelope, OpError};
+
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+use crate::crdt::ops::{cbor_decode, cbor_encode};
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+use crate::crypto::zkproofs::{
+    prove_attribute_predicate, verify_attribute_predicate, AttributeCredential, AttributeOpening,
+    AttributePredicate, AttributeProof,
+};
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+use serde::{Deserialize, Serialize};
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum AdmissionError {
+    #[error("Group requires an attribute proof to join")]
+    MissingProof,
+
+    #[error("Invalid join requirement: {0}")]
+    InvalidRequirement(String),
+
+    #[error("Credential is for attribute {0:?}, not the required one")]
+    WrongAttribute(String),
+
+    #[error("Credential is not from the required issuer")]
+    WrongIssuer,
+
+    #[error("Credential was issued to a different device")]
+    HolderMismatch,
+
+    #[error("Invalid credential signature")]
+    InvalidCredential,
+
+    #[error("Attribute proof rejected: {0}")]
+    InvalidProof(String),
+
+    #[error("Attribute proofs are not available in this build")]
+    Unsupported,
+
+    #[error("Payload decode error: {0}")]
+    PayloadDecode(String),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// Wire types
+// ---------------------------------------------------------------------------
+
+/// Value of the `JoinRequirement` metadata register.
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JoinRequirement {
+    pub attribute: String,
+    pub issuer_pubkey: [u8; 32],
+    pub predicate: AttributePredicate,
+}
+
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+impl JoinRequirement {
+    /// CBOR bytes for a `MetadataSet` op.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AdmissionError> {
+        Ok(cbor_encode(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AdmissionError> {
+        cbor_decode(bytes).map_err(|e| AdmissionError::InvalidRequirement(e.to_string()))
+    }
+}
+
+/// What an invitee attaches to `MemberAccept` for a gated group.
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AttributePresentation {
+    pub credential: AttributeCredential,
+    pub proof: AttributeProof,
+}
+
+/// Context an admission proof is bound to: group, invite and joining device.
+pub fn admission_context(
+    group_id: &GroupID,
+    invite_op_id: &OpID,
+    holder_pubkey: &[u8; 32],
+) -> Vec<u8> {
+    let mut ctx = Vec::with_capacity(8 + 32 + 16 + 16 + 32);
+    ctx.extend_from_slice(b"SL-ADMIT");
+    ctx.extend_from_slice(group_id.as_bytes());
+    ctx.extend_from_slice(invite_op_id.author.as_bytes());
+    ctx.extend_from_slice(&invite_op_id.lamport.to_le_bytes());
+    ctx.extend_from_slice(&invite_op_id.nonce.to_le_bytes());
+    ctx.extend_from_slice(holder_pubkey);
+    ctx
+}
+
+// ---------------------------------------------------------------------------
+// Check
+// ---------------------------------------------------------------------------
+
+/// Check a `MemberAccept` op against the group's encoded join requirement.
+///
+/// Ungated groups (`requirement` is `None`) accept everything.
+pub fn check_accept(op: &OpEnvelope, requirement: Option<&[u8]>) -> Result<(), AdmissionError> {
+    let Some(requirement) = requirement else {
+        return Ok(());
+    };
+    let payload: MemberAcceptPayload = op
+        .decode_payload()
+        .map_err(|e| AdmissionError::PayloadDecode(e.to_string()))?;
+    let presentation = payload
+        .attribute_proof
+        .as_deref()
+        .ok_or(AdmissionError::MissingProof)?;
+    verify_presentation(op, &payload.invite_op_id, requirement, presentation)
+}
+
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+fn verify_presentation(
+    op: &OpEnvelope,
+    invite_op_id: &OpID,
+    requirement: &[u8],
+    presentation: &[u8],
+) -> Result<(), AdmissionError> {
+    let requirement = JoinRequirement::from_bytes(requirement)?;
+    let presentation: AttributePresentation =
+        cbor_decode(presentation).map_err(|e| AdmissionError::PayloadDecode(e.to_string()))?;
+    let credential = &presentation.credential;
+
+    if credential.attribute != requirement.attribute {
+        return Err(AdmissionError::WrongAttribute(credential.attribute.clone()));
+    }
+    if credential.issuer_pubkey != requirement.issuer_pubkey {
+        return Err(AdmissionError::WrongIssuer);
+    }
+    if credential.holder_pubkey != op.author_pubkey {
+        return Err(AdmissionError::HolderMismatch);
+    }
+    if !credential.verify_issuer() {
+        return Err(AdmissionError::InvalidCredential);
+    }
+
+    let context = admission_context(&op.group_id, invite_op_id, &op.author_pubkey);
+    match verify_attribute_predicate(
+        credential,
+        &requirement.predicate,
+        &presentation.proof,
+        &context,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(AdmissionError::InvalidProof(
+            "predicate not satisfied".into(),
+        )),
+        Err(e) => Err(AdmissionError::InvalidProof(e)),
+    }
+}
+
+#[cfg(any(not(feature = "zkproofs"), target_arch = "wasm32"))]
+fn verify_presentation(
+    _op: &OpEnvelope,
+    _invite_op_id: &OpID,
+    _requirement: &[u8],
+    _presentation: &[u8],
+) -> Result<(), AdmissionError> {
+    Err(AdmissionError::Unsupported)
+}
+
+// ---------------------------------------------------------------------------
+// Builders
+// ---------------------------------------------------------------------------
+
+/// Build the `attribute_proof` bytes for accepting `invite_op_id` into a
+/// group gated by `requirement`, as the device `holder_pubkey`.
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub fn present_attribute(
+    group_id: &GroupID,
+    invite_op_id: &OpID,
+    holder_pubkey: &[u8; 32],
+    requirement: &JoinRequirement,
+    credential: &AttributeCredential,
+    opening: &AttributeOpening,
+) -> Result<Vec<u8>, AdmissionError> {
+    let context = admission_context(group_id, invite_op_id, holder_pubkey);
+    let proof = prove_attribute_predicate(credential, opening, &requirement.predicate, &context)
+        .map_err(AdmissionError::InvalidProof)?;
+    Ok(cbor_encode(&AttributePresentation {
+        credential: credential.clone(),
+        proof,
+    })?)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "zkproofs", not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::crdt::apply::{ApplyError, GroupState};
+    use crate::crdt::ids::DeviceID;
+    use crate::crdt::ops::{
+        GroupCreatePayload, MemberInvitePayload, MetadataKey, MetadataSetPayload, OpType, Role,
+    };
+    use crate::crypto::zkproofs::issue_attribute_credential;
+
+    fn keypair() -> ([u8; 32], [u8; 32]) {
+        crate::crypto::signing::generate_keypair()
+    }
+
+    fn signed<P: serde::Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        pub_k: [u8; 32],
+        priv_k: &[u8; 32],
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport * 100, pub_k, priv_k)
+            .unwrap()
+    }
+
+    /// Gated group with a pending invite for `invitee`.
+    /// Returns (state, gid, requirement, invite op id).
+    fn gated_group(
+        owner: &([u8; 32], [u8; 32]),
+        invitee: &[u8; 32],
+        issuer_pubkey: [u8; 32],
+    ) -> (GroupState, GroupID, JoinRequirement, OpID) {
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0x18; 32]);
+        let mut state = GroupState::new(gid);
+        let create = GroupCreatePayload {
+            group_name: "Adults only".into(),
+            encrypted_group_secret: vec![1],
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::GroupCreate,
+                &create,
+                1,
+                owner.0,
+                &owner.1,
+            ))
+            .unwrap();
+
+        let requirement = JoinRequirement {
+            attribute: "age".into(),
+            issuer_pubkey,
+            predicate: AttributePredicate::AtLeast(18),
+        };
+        let gate = MetadataSetPayload {
+            key: MetadataKey::JoinRequirement,
+            value: requirement.to_bytes().unwrap(),
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::MetadataSet,
+                &gate,
+                2,
+                owner.0,
+                &owner.1,
+            ))
+            .unwrap();
+
+        let invite = signed(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: DeviceID::from_pubkey(invitee),
+                invited_pubkey: *invitee,
+                role: Role::Member,
+                encrypted_group_secret: vec![2],
+            },
+            3,
+            owner.0,
+            &owner.1,
+        );
+        state.apply_op(&invite).unwrap();
+        (state, gid, requirement, invite.op_id)
+    }
+
+    fn accept(
+        gid: GroupID,
+        invite_op_id: OpID,
+        attribute_proof: Option<Vec<u8>>,
+        keys: &([u8; 32], [u8; 32]),
+    ) -> OpEnvelope {
+        let payload = MemberAcceptPayload {
+            invite_op_id,
+            attribute_proof,
+        };
+        signed(gid, OpType::MemberAccept, &payload, 4, keys.0, &keys.1)
+    }
+
+    #[test]
+    fn test_gated_accept_requires_valid_presentation() {
+        let owner = keypair();
+        let alice = keypair();
+        let issuer = keypair();
+        let (mut state, gid, requirement, invite_id) = gated_group(&owner, &alice.0, issuer.0);
+
+        let err = state
+            .apply_op(&accept(gid, invite_id, None, &alice))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::Admission(AdmissionError::MissingProof)
+        ));
+
+        let (credential, opening) =
+            issue_attribute_credential("age", 21, &alice.0, &issuer.1).unwrap();
+        let proof = present_attribute(
+            &gid,
+            &invite_id,
+            &alice.0,
+            &requirement,
+            &credential,
+            &opening,
+        )
+        .unwrap();
+        assert!(state
+            .apply_op(&accept(gid, invite_id, Some(proof), &alice))
+            .unwrap());
+        assert!(state
+            .membership
+            .get_active_member(&DeviceID::from_pubkey(&alice.0))
+            .is_some());
+    }
+
+    #[test]
+    fn test_gated_accept_rejects_foreign_credentials() {
+        let owner = keypair();
+        let alice = keypair();
+        let bob = keypair();
+        let issuer = keypair();
+        let (mut state, gid, requirement, invite_id) = gated_group(&owner, &alice.0, issuer.0);
+
+        // Bob's credential and proof, replayed by Alice
+        let (credential, opening) =
+            issue_attribute_credential("age", 30, &bob.0, &issuer.1).unwrap();
+        let lent = present_attribute(
+            &gid,
+            &invite_id,
+            &bob.0,
+            &requirement,
+            &credential,
+            &opening,
+        )
+        .unwrap();
+        assert!(matches!(
+            state.apply_op(&accept(gid, invite_id, Some(lent), &alice)),
+            Err(ApplyError::Admission(AdmissionError::HolderMismatch))
+        ));
+
+        // A self-issued credential
+        let (credential, opening) =
+            issue_attribute_credential("age", 30, &alice.0, &alice.1).unwrap();
+        let self_issued = present_attribute(
+            &gid,
+            &invite_id,
+            &alice.0,
+            &requirement,
+            &credential,
+            &opening,
+        )
+        .unwrap();
+        assert!(matches!(
+            state.apply_op(&accept(gid, invite_id, Some(self_issued), &alice)),
+            Err(ApplyError::Admission(AdmissionError::WrongIssuer))
+        ));
+
+        // An honest credential that does not meet the bound cannot be proven
+        let (credential, opening) =
+            issue_attribute_credential("age", 16, &alice.0, &issuer.1).unwrap();
+        assert!(present_attribute(
+            &gid,
+            &invite_id,
+            &alice.0,
+            &requirement,
+            &credential,
+            &opening
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,782 @@
+/// Anonymous posting — suggestion-box style groups.
+///
+/// A member opts in by registering a group-scoped anonymous key
+/// (`AnonKeyRegister`, signed normally with their device key). An `AnonMsgAdd`
+/// op is instead signed by a one-time Ed25519 key and carries a linkable ring
+/// proof over the registered keys of the listed ring members:
+/// - Only active members with a registered key can appear in the ring, so
+///   non-members cannot post.
+/// - The proof's link tag is stable per (member, epoch), so each hidden member
+///   is limited to `MAX_ANON_POSTS_PER_EPOCH` posts per epoch.
+/// - One-time keys give posts no per-author order, so the quota cannot be
+///   charged in arrival order. Each (epoch, tag) keeps the posts with the
+///   lowest `(lamport, op_id)`: a lower post arriving at a full budget evicts
+///   the highest one, and a post above the cut is skipped. Every device ends
+///   up with the same posts whatever order they came in.
+/// - Tags from different epochs are unlinkable.
+///
+/// Disabled unless the group's `AnonymousPosting` metadata register is `[1]`.
+///
+/// **Epochs:** `epoch` must equal `timestamp_ms / ANON_POST_EPOCH_MS`. Timestamps
+/// are author-chosen, so `GroupState::apply_remote_op` refuses posts dated more
+/// than `MAX_OP_CLOCK_SKEW_MS` ahead of or `MAX_QUOTA_OP_AGE_MS` behind the
+/// receiver's clock. Dating posts into other epochs then buys at most the
+/// epochs inside that window, not a fresh quota per post.
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+use crate::crdt::ids::{DeviceID, GroupID, OpID};
+use crate::crdt::limits::{ANON_POST_EPOCH_MS, MAX_ANON_POSTS_PER_EPOCH, MAX_ANON_RING_SIZE};
+use crate::crdt::membership::MembershipState;
+use crate::crdt::ops::{AnonKeyRegisterPayload, AnonMsgAddPayload, OpEnvelope, OpError};
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum AnonymousError {
+    #[error("Anonymous posting is disabled for this group")]
+    Disabled,
+
+    #[error("Ring size must be 1..={max}, got {size}")]
+    InvalidRingSize { size: usize, max: usize },
+
+    #[error("Ring member {0} is not an active member with a registered anonymous key")]
+    UnknownRingMember(String),
+
+    #[error("Duplicate ring member")]
+    DuplicateRingMember,
+
+    #[error("Epoch does not match op timestamp")]
+    EpochMismatch,
+
+    #[error("Membership proof rejected: {0}")]
+    InvalidProof(String),
+
+    #[error("Payload decode error: {0}")]
+    PayloadDecode(String),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// AnonymousState
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct AnonKeyEntry {
+    pub anon_pubkey: [u8; 32],
+    /// Lamport of the registering op (LWW on re-registration).
+    pub lamport: u64,
+    pub writer_op: OpID,
+}
+
+/// Outcome of [`AnonymousState::admit_post`].
+#[derive(Debug)]
+pub enum AnonAdmission {
+    /// Within the tag's budget. `evicted` is the msg_id and create op of a
+    /// higher post this one pushed out; the caller removes that message.
+    Admitted {
+        payload: Box<AnonMsgAddPayload>,
+        evicted: Option<([u8; 32], OpID)>,
+    },
+    /// Valid, but the tag's budget is held by lower posts.
+    OverQuota,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnonymousState {
+    keys: BTreeMap<DeviceID, AnonKeyEntry>,
+    /// (epoch, link_tag) → admitted posts by (lamport, op_id), with msg_id.
+    admitted: BTreeMap<(u64, [u8; 32]), BTreeMap<(u64, OpID), [u8; 32]>>,
+    /// msg_ids of valid posts skipped over quota or evicted. A dropped post
+    /// ranks above a full budget, so it is never admitted again.
+    dropped: BTreeSet<[u8; 32]>,
+}
+
+impl Default for AnonymousState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnonymousState {
+    pub fn new() -> Self {
+        AnonymousState {
+            keys: BTreeMap::new(),
+            admitted: BTreeMap::new(),
+            dropped: BTreeSet::new(),
+        }
+    }
+
+    /// Whether no key has been registered and no anonymous post accepted.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.admitted.is_empty()
+    }
+
+    /// Read-only access to registered anonymous keys.
+    pub fn keys(&self) -> &BTreeMap<DeviceID, AnonKeyEntry> {
+        &self.keys
+    }
+
+    /// Admitted post count per (epoch, link tag).
+    pub fn tag_counts(&self) -> impl Iterator<Item = (&(u64, [u8; 32]), u32)> {
+        self.admitted
+            .iter()
+            .map(|(key, posts)| (key, posts.len() as u32))
+    }
+
+    /// Whether `msg_id` is a valid post that was skipped over quota or
+    /// evicted, i.e. one that every replica ends up without.
+    pub fn is_dropped(&self, msg_id: &[u8; 32]) -> bool {
+        self.dropped.contains(msg_id)
+    }
+
+    /// Every active member with a registered key — the widest possible ring.
+    pub fn eligible_ring(&self, membership: &MembershipState) -> Vec<(DeviceID, [u8; 32])> {
+        self.keys
+            .iter()
+            .filter(|(did, _)| membership.get_active_member(did).is_some())
+            .map(|(did, entry)| (*did, entry.anon_pubkey))
+            .collect()
+    }
+
+    // -----------------------------------------------------------------------
+    // Apply functions
+    // -----------------------------------------------------------------------
+
+    /// Apply an AnonKeyRegister op. LWW by lamport, tie-break by OpID.
+    pub fn apply_anon_key_register(&mut self, op: &OpEnvelope) -> Result<(), AnonymousError> {
+        let payload: AnonKeyRegisterPayload = op
+            .decode_payload()
+            .map_err(|e| AnonymousError::PayloadDecode(e.to_string()))?;
+
+        let author = DeviceID::from_pubkey(&op.author_pubkey);
+        let should_update = match self.keys.get(&author) {
+            None => true,
+            Some(entry) => {
+                op.lamport > entry.lamport
+                    || (op.lamport == entry.lamport && op.op_id > entry.writer_op)
+            }
+        };
+
+        if should_update {
+            self.keys.insert(
+                author,
+                AnonKeyEntry {
+                    anon_pubkey: payload.anon_pubkey,
+                    lamport: op.lamport,
+                    writer_op: op.op_id,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Validate an AnonMsgAdd op and charge it against its link tag's budget.
+    ///
+    /// An admitted payload goes to `MessageState::apply_anon_msg_add`.
+    pub fn admit_post(
+        &mut self,
+        op: &OpEnvelope,
+        membership: &MembershipState,
+        enabled: bool,
+    ) -> Result<AnonAdmission, AnonymousError> {
+        if !enabled {
+            return Err(AnonymousError::Disabled);
+        }
+
+        let payload: AnonMsgAddPayload = op
+            .decode_payload()
+            .map_err(|e| AnonymousError::PayloadDecode(e.to_string()))?;
+
+        if payload.ring.is_empty() || payload.ring.len() > MAX_ANON_RING_SIZE {
+            return Err(AnonymousError::InvalidRingSize {
+                size: payload.ring.len(),
+                max: MAX_ANON_RING_SIZE,
+            });
+        }
+        if op.timestamp_ms / ANON_POST_EPOCH_MS != payload.epoch {
+            return Err(AnonymousError::EpochMismatch);
+        }
+
+        let mut ring_keys = Vec::with_capacity(payload.ring.len());
+        let mut seen = std::collections::BTreeSet::new();
+        for did in &payload.ring {
+            if !seen.insert(*did) {
+                return Err(AnonymousError::DuplicateRingMember);
+            }
+            let key = self
+                .keys
+                .get(did)
+                .filter(|_| membership.get_active_member(did).is_some())
+                .ok_or_else(|| AnonymousError::UnknownRingMember(did.to_hex()))?;
+            ring_keys.push(key.anon_pubkey);
+        }
+
+        let message = proof_message(
+            &op.group_id,
+            &op.author_pubkey,
+            &payload.msg_id,
+            payload.epoch,
+            &payload.ciphertext,
+            &payload.nonce,
+        );
+        let context = link_context(&op.group_id, payload.epoch);
+        match verify_ring_proof(&ring_keys, &payload, &context, &message) {
+            Ok(true) => {}
+            Ok(false) => return Err(AnonymousError::InvalidProof("ring does not close".into())),
+            Err(e) => return Err(AnonymousError::InvalidProof(e)),
+        }
+
+        let posts = self
+            .admitted
+            .entry((payload.epoch, payload.link_tag))
+            .or_default();
+        let rank = (op.lamport, op.op_id);
+        let mut evicted = None;
+        if posts.len() >= MAX_ANON_POSTS_PER_EPOCH as usize {
+            let highest = *posts.keys().next_back().expect("budget is non-zero");
+            if rank > highest {
+                self.dropped.insert(payload.msg_id);
+                return Ok(AnonAdmission::OverQuota);
+            }
+            let msg_id = posts.remove(&highest).expect("key just read");
+            self.dropped.insert(msg_id);
+            evicted = Some((msg_id, highest.1));
+        }
+        posts.insert(rank, payload.msg_id);
+
+        Ok(AnonAdmission::Admitted {
+            payload: Box::new(payload),
+            evicted,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Proof binding
+// ---------------------------------------------------------------------------
+
+/// Linking context for a group + epoch — the tag is unique per member within it.
+pub fn link_context(group_id: &GroupID, epoch: u64) -> Vec<u8> {
+    let mut ctx = Vec::with_capacity(8 + 32 + 8);
+    ctx.extend_from_slice(b"SL-ANON1");
+    ctx.extend_from_slice(group_id.as_bytes());
+    ctx.extend_from_slice(&epoch.to_le_bytes());
+    ctx
+}
+
+/// Data the ring proof authenticates. Binding the one-time envelope key means a
+/// proof cannot be lifted onto a different envelope or ciphertext.
+pub fn proof_message(
+    group_id: &GroupID,
+    ephemeral_pubkey: &[u8; 32],
+    msg_id: &[u8; 32],
+    epoch: u64,
+    ciphertext: &[u8],
+    nonce: &[u8; 24],
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32 * 4 + 8 + 24);
+    msg.extend_from_slice(group_id.as_bytes());
+    msg.extend_from_slice(ephemeral_pubkey);
+    msg.extend_from_slice(msg_id);
+    msg.extend_from_slice(&epoch.to_le_bytes());
+    msg.extend_from_slice(blake3::hash(ciphertext).as_bytes());
+    msg.extend_from_slice(nonce);
+    msg
+}
+
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+fn verify_ring_proof(
+    ring_keys: &[[u8; 32]],
+    payload: &AnonMsgAddPayload,
+    context: &[u8],
+    message: &[u8],
+) -> Result<bool, String> {
+    let proof = crate::crypto::zkproofs::MembershipProof {
+        c0: payload.proof_c0,
+        responses: payload.proof_responses.clone(),
+        link_tag: payload.link_tag,
+    };
+    crate::crypto::zkproofs::verify_membership_proof(ring_keys, &proof, context, message)
+}
+
+#[cfg(any(not(feature = "zkproofs"), target_arch = "wasm32"))]
+fn verify_ring_proof(
+    _ring_keys: &[[u8; 32]],
+    _payload: &AnonMsgAddPayload,
+    _context: &[u8],
+    _message: &[u8],
+) -> Result<bool, String> {
+    Err("membership proofs are not available in this build".into())
+}
+
+// ---------------------------------------------------------------------------
+// Builders
+// ---------------------------------------------------------------------------
+
+/// Derive this device's anonymous keypair for a group.
+///
+/// Returns (secret, pubkey); publish `pubkey` via an `AnonKeyRegister` op.
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub fn anon_keypair(group_id: &GroupID, device_privkey: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    crate::crypto::zkproofs::derive_membership_keypair(device_privkey, group_id.as_bytes())
+}
+
+/// Create an AnonMsgAdd op signed by a fresh one-time key.
+///
+/// `ring` is typically `AnonymousState::eligible_ring`; `signer_index` is the
+/// caller's own position in it. `lamport` should come from the group-wide
+/// maximum (not the caller's per-device counter, which would link posts).
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub fn create_anon_msg_add(
+    group_id: GroupID,
+    ring: &[(DeviceID, [u8; 32])],
+    signer_index: usize,
+    anon_secret: &[u8; 32],
+    ciphertext: Vec<u8>,
+    nonce: [u8; 24],
+    lamport: u64,
+) -> Result<OpEnvelope, AnonymousError> {
+    create_anon_msg_add_with_rng(
+        group_id,
+        ring,
+        signer_index,
+        anon_secret,
+        ciphertext,
+        nonce,
+        lamport,
+        &mut crate::rng::OsRng,
+    )
+}
+
+/// [`create_anon_msg_add`] drawing the one-time key, op nonce and proof
+/// randomness from `rng`.
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub fn create_anon_msg_add_with_rng(
+    group_id: GroupID,
+    ring: &[(DeviceID, [u8; 32])],
+    signer_index: usize,
+    anon_secret: &[u8; 32],
+    ciphertext: Vec<u8>,
+    nonce: [u8; 24],
+    lamport: u64,
+    rng: &mut impl crate::rng::SecureRng,
+) -> Result<OpEnvelope, AnonymousError> {
+    use crate::crdt::ops::{group_msg_id, now_ms, OpType};
+
+    let (eph_pub, eph_priv) = crate::crypto::signing::generate_keypair_with_rng(rng);
+    let op_nonce = rng.next_u64();
+    let msg_id = group_msg_id(&group_id, &eph_pub, lamport, op_nonce);
+    let ring_keys: Vec<[u8; 32]> = ring.iter().map(|(_, k)| *k).collect();
+
+    // The envelope timestamp is taken inside create_signed; retry once if it
+    // lands in the next epoch.
+    for _ in 0..2 {
+        let epoch = now_ms() / ANON_POST_EPOCH_MS;
+        let message = proof_message(&group_id, &eph_pub, &msg_id, epoch, &ciphertext, &nonce);
+        let proof = crate::crypto::zkproofs::generate_membership_proof_with_rng(
+            &ring_keys,
+            signer_index,
+            anon_secret,
+            &link_context(&group_id, epoch),
+            &message,
+            rng,
+        )
+        .map_err(AnonymousError::InvalidProof)?;
+
+        let payload = AnonMsgAddPayload {
+            msg_id,
+            ciphertext: ciphertext.clone(),
+            nonce,
+            epoch,
+            ring: ring.iter().map(|(did, _)| *did).collect(),
+            proof_c0: proof.c0,
+            proof_responses: proof.responses,
+            link_tag: proof.link_tag,
+        };
+        let op = OpEnvelope::create_signed(
+            group_id,
+            OpType::AnonMsgAdd,
+            &payload,
+            lamport,
+            op_nonce,
+            eph_pub,
+            &eph_priv,
+        )?;
+        if op.timestamp_ms / ANON_POST_EPOCH_MS == epoch {
+            return Ok(op);
+        }
+    }
+
+    Err(AnonymousError::EpochMismatch)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "zkproofs", not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::crdt::apply::{ApplyError, GroupState};
+    use crate::crdt::limits::MAX_QUOTA_OP_AGE_MS;
+    use crate::crdt::messages::MessageError;
+    use crate::crdt::ops::{
+        GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, MetadataKey,
+        MetadataSetPayload, MsgDeletePayload, OpType, Role,
+    };
+
+    fn keypair() -> ([u8; 32], [u8; 32]) {
+        crate::crypto::signing::generate_keypair()
+    }
+
+    fn signed<P: serde::Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        pub_k: [u8; 32],
+        priv_k: &[u8; 32],
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport * 100, pub_k, priv_k)
+            .unwrap()
+    }
+
+    /// Owner + alice, anonymous posting enabled, both keys registered.
+    /// Returns (state, gid, owner keys, alice keys).
+    fn setup() -> (
+        GroupState,
+        GroupID,
+        ([u8; 32], [u8; 32]),
+        ([u8; 32], [u8; 32]),
+    ) {
+        let owner = keypair();
+        let alice = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0xA7; 32]);
+        let mut state = GroupState::new(gid);
+
+        let create = GroupCreatePayload {
+            group_name: "Suggestions".into(),
+            encrypted_group_secret: vec![1],
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::GroupCreate,
+                &create,
+                1,
+                owner.0,
+                &owner.1,
+            ))
+            .unwrap();
+
+        let invite = signed(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: DeviceID::from_pubkey(&alice.0),
+                invited_pubkey: alice.0,
+                role: Role::Member,
+                encrypted_group_secret: vec![2],
+            },
+            2,
+            owner.0,
+            &owner.1,
+        );
+        state.apply_op(&invite).unwrap();
+        let accept = MemberAcceptPayload {
+            invite_op_id: invite.op_id,
+            attribute_proof: None,
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::MemberAccept,
+                &accept,
+                3,
+                alice.0,
+                &alice.1,
+            ))
+            .unwrap();
+
+        let enable = MetadataSetPayload {
+            key: MetadataKey::AnonymousPosting,
+            value: vec![1],
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::MetadataSet,
+                &enable,
+                4,
+                owner.0,
+                &owner.1,
+            ))
+            .unwrap();
+
+        for (lamport, (pub_k, priv_k)) in [(5, owner), (6, alice)] {
+            let (_, anon_pub) = anon_keypair(&gid, &priv_k);
+            let reg = AnonKeyRegisterPayload {
+                anon_pubkey: anon_pub,
+            };
+            state
+                .apply_op(&signed(
+                    gid,
+                    OpType::AnonKeyRegister,
+                    &reg,
+                    lamport,
+                    pub_k,
+                    &priv_k,
+                ))
+                .unwrap();
+        }
+
+        (state, gid, owner, alice)
+    }
+
+    fn post_as(
+        state: &GroupState,
+        gid: GroupID,
+        poster_priv: &[u8; 32],
+        poster_pub: &[u8; 32],
+        lamport: u64,
+    ) -> OpEnvelope {
+        let ring = state.anonymous.eligible_ring(&state.membership);
+        let me = DeviceID::from_pubkey(poster_pub);
+        let idx = ring.iter().position(|(did, _)| *did == me).unwrap();
+        let (secret, _) = anon_keypair(&gid, poster_priv);
+        create_anon_msg_add(gid, &ring, idx, &secret, vec![0xAB; 16], [7; 24], lamport).unwrap()
+    }
+
+    #[test]
+    fn test_anonymous_post_applied_and_rendered() {
+        let (mut state, gid, _owner, alice) = setup();
+        let op = post_as(&state, gid, &alice.1, &alice.0, 7);
+
+        assert!(state.apply_op(&op).unwrap());
+        let rendered = state.renderable_messages();
+        assert_eq!(rendered.len(), 1);
+        assert!(rendered[0].anonymous);
+        assert_ne!(rendered[0].author, DeviceID::from_pubkey(&alice.0));
+    }
+
+    #[test]
+    fn test_anonymous_post_rejected_when_disabled() {
+        let (mut state, gid, owner, alice) = setup();
+        let disable = MetadataSetPayload {
+            key: MetadataKey::AnonymousPosting,
+            value: vec![0],
+        };
+        state
+            .apply_op(&signed(
+                gid,
+                OpType::MetadataSet,
+                &disable,
+                7,
+                owner.0,
+                &owner.1,
+            ))
+            .unwrap();
+
+        let op = post_as(&state, gid, &alice.1, &alice.0, 8);
+        let err = state.apply_op(&op).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::Anonymous(AnonymousError::Disabled)
+        ));
+    }
+
+    #[test]
+    fn test_non_member_cannot_post() {
+        let (mut state, gid, _owner, _alice) = setup();
+        let outsider = keypair();
+        let (secret, outsider_anon) = anon_keypair(&gid, &outsider.1);
+
+        let mut ring = state.anonymous.eligible_ring(&state.membership);
+        ring.push((DeviceID::from_pubkey(&outsider.0), outsider_anon));
+        let idx = ring.len() - 1;
+        let op = create_anon_msg_add(gid, &ring, idx, &secret, vec![1, 2, 3], [0; 24], 7).unwrap();
+
+        let err = state.apply_op(&op).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::Anonymous(AnonymousError::UnknownRingMember(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_rejected() {
+        let (mut state, gid, _owner, alice) = setup();
+        let op = post_as(&state, gid, &alice.1, &alice.0, 7);
+
+        // Re-sign a modified payload with a different one-time key: the proof
+        // no longer matches the envelope key or ciphertext.
+        let mut payload: AnonMsgAddPayload = op.decode_payload().unwrap();
+        payload.ciphertext = vec![0xFF; 16];
+        let (eph_pub, eph_priv) = keypair();
+        let forged = signed(gid, OpType::AnonMsgAdd, &payload, 7, eph_pub, &eph_priv);
+
+        let err = state.apply_op(&forged).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::Anonymous(AnonymousError::InvalidProof(_))
+                | ApplyError::Anonymous(AnonymousError::EpochMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_rate_limit_per_epoch() {
+        let (mut state, gid, _owner, alice) = setup();
+        let mut lamport = 7;
+        for _ in 0..MAX_ANON_POSTS_PER_EPOCH {
+            let op = post_as(&state, gid, &alice.1, &alice.0, lamport);
+            state.apply_op(&op).unwrap();
+            lamport += 1;
+        }
+
+        // Over budget: skipped, not an error, so replaying the log still works.
+        // A post that straddled an epoch boundary would get a fresh budget;
+        // the window here is far too short for that.
+        let op = post_as(&state, gid, &alice.1, &alice.0, lamport);
+        assert!(state.apply_op(&op).unwrap());
+        assert!(!state.apply_op(&op).unwrap());
+        assert_eq!(
+            state.renderable_messages().len(),
+            MAX_ANON_POSTS_PER_EPOCH as usize
+        );
+        assert!(state
+            .messages
+            .get_message(&op.decode_payload::<AnonMsgAddPayload>().unwrap().msg_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_quota_admission_is_order_independent() {
+        let (base, gid, _owner, alice) = setup();
+        let posts: Vec<OpEnvelope> = (0..MAX_ANON_POSTS_PER_EPOCH as u64 + 2)
+            .map(|i| post_as(&base, gid, &alice.1, &alice.0, 7 + i))
+            .collect();
+
+        let mut forward = base.clone();
+        for op in &posts {
+            forward.apply_op(op).unwrap();
+        }
+        // Highest posts first, so every low one evicts an admitted post
+        let mut reverse = base.clone();
+        for op in posts.iter().rev() {
+            reverse.apply_op(op).unwrap();
+        }
+        assert_eq!(forward.state_hash(), reverse.state_hash());
+
+        let admitted: Vec<[u8; 32]> = reverse
+            .renderable_messages()
+            .iter()
+            .map(|msg| msg.msg_id)
+            .collect();
+        assert_eq!(admitted.len(), MAX_ANON_POSTS_PER_EPOCH as usize);
+        let mut ranked = posts.clone();
+        ranked.sort_by_key(|op| (op.lamport, op.op_id));
+        for op in &ranked[MAX_ANON_POSTS_PER_EPOCH as usize..] {
+            let msg_id = op.decode_payload::<AnonMsgAddPayload>().unwrap().msg_id;
+            assert!(!admitted.contains(&msg_id));
+        }
+    }
+
+    #[test]
+    fn test_delete_of_evicted_post_is_order_independent() {
+        let (base, gid, owner, alice) = setup();
+        let mut posts: Vec<OpEnvelope> = (0..MAX_ANON_POSTS_PER_EPOCH as u64 + 1)
+            .map(|i| post_as(&base, gid, &alice.1, &alice.0, 7 + i))
+            .collect();
+        posts.sort_by_key(|op| (op.lamport, op.op_id));
+        let top = posts
+            .last()
+            .unwrap()
+            .decode_payload::<AnonMsgAddPayload>()
+            .unwrap()
+            .msg_id;
+        let del = |pub_k: [u8; 32], priv_k: &[u8; 32]| {
+            signed(
+                gid,
+                OpType::MsgDelete,
+                &MsgDeletePayload { msg_id: top },
+                50,
+                pub_k,
+                priv_k,
+            )
+        };
+        let by_owner = del(owner.0, &owner.1);
+        let by_alice = del(alice.0, &alice.1);
+
+        // Delete lands while the top post is held; the lowest post evicts it later
+        let mut held = base.clone();
+        for op in &posts[1..] {
+            held.apply_op(op).unwrap();
+        }
+        assert!(matches!(
+            held.apply_op(&by_alice),
+            Err(ApplyError::Message(MessageError::DeleteNotAuthorized))
+        ));
+        assert!(held.apply_op(&by_owner).unwrap());
+        held.apply_op(&posts[0]).unwrap();
+
+        // Top post was already over quota when the delete arrives
+        let mut dropped = base.clone();
+        for op in &posts {
+            dropped.apply_op(op).unwrap();
+        }
+        assert!(matches!(
+            dropped.apply_op(&by_alice),
+            Err(ApplyError::Message(MessageError::DeleteNotAuthorized))
+        ));
+        assert!(dropped.apply_op(&by_owner).unwrap());
+
+        assert_eq!(held.state_hash(), dropped.state_hash());
+        assert!(dropped
+            .renderable_messages()
+            .iter()
+            .all(|msg| msg.msg_id != top));
+    }
+
+    #[test]
+    fn test_post_dated_outside_the_clock_window_is_refused() {
+        let (mut state, gid, _owner, alice) = setup();
+        let op = post_as(&state, gid, &alice.1, &alice.0, 7);
+        let sent = op.timestamp_ms;
+
+        // Back-dated, as seen by a receiver a day and a bit later
+        assert!(matches!(
+            state.apply_remote_op(&op, sent + MAX_QUOTA_OP_AGE_MS + 1),
+            Err(ApplyError::ImplausibleTimestamp { .. })
+        ));
+        // Dated into a future epoch
+        assert!(matches!(
+            state.apply_remote_op(&op, sent - ANON_POST_EPOCH_MS),
+            Err(ApplyError::ImplausibleTimestamp { .. })
+        ));
+        assert!(state.renderable_messages().is_empty());
+
+        assert!(state.apply_remote_op(&op, sent).unwrap());
+        // Already applied: a late redelivery is a duplicate, not an error
+        assert!(!state
+            .apply_remote_op(&op, sent + MAX_QUOTA_OP_AGE_MS + 1)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_state_hash_converges_with_anonymous_ops() {
+        let (mut state, gid, _owner, alice) = setup();
+        let op = post_as(&state, gid, &alice.1, &alice.0, 7);
+        state.apply_op(&op).unwrap();
+        let hash = state.state_hash();
+
+        let mut clone = state.clone();
+        assert!(!clone.apply_op(&op).unwrap()); // idempotent
+        assert_eq!(clone.state_hash(), hash);
+    }
+}
@@ -10,12 +10,19 @@
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use thiserror::Error;
 
+use crate::crdt::admission::{self, AdmissionError};
+use crate::crdt::anonymous::{AnonAdmission, AnonymousError, AnonymousState};
+use crate::crdt::avatar::{self, AvatarError};
+use crate::crdt::feed::{self, MembershipFeed};
 use crate::crdt::ids::{DeviceID, GroupID, OpID};
-use crate::crdt::limits::{check_op_limits, OpLimitStatus};
+use crate::crdt::invites::InviteLog;
+use crate::crdt::limits::{check_op_limits, timestamp_plausible, OpLimitStatus, MAX_LAMPORT};
 use crate::crdt::membership::{MembershipError, MembershipState};
 use crate::crdt::messages::{MessageEntry, MessageError, MessageState};
 use crate::crdt::metadata::{MetadataError, MetadataState};
-use crate::crdt::ops::{OpEnvelope, OpError, OpType};
+use crate::crdt::ops::{
+    verify_ops_batch, MemberAcceptPayload, MsgDeletePayload, OpEnvelope, OpError, OpType,
+};
 
 // ---------------------------------------------------------------------------
 // Errors
@@ -29,6 +36,12 @@ pub enum ApplyError {
     #[error("Op targets wrong group")]
     WrongGroup,
 
+    #[error("Lamport {0} out of range")]
+    LamportOutOfRange(u64),
+
+    #[error("Timestamp {timestamp_ms} implausible at {now_ms}")]
+    ImplausibleTimestamp { timestamp_ms: u64, now_ms: u64 },
+
     #[error("Hard op limit reached — only membership ops allowed")]
     OpLimitReached,
 
@@ -44,6 +57,15 @@ pub enum ApplyError {
     #[error("Metadata error: {0}")]
     Metadata(#[from] MetadataError),
 
+    #[error("Anonymous posting error: {0}")]
+    Anonymous(#[from] AnonymousError),
+
+    #[error("Admission error: {0}")]
+    Admission(#[from] AdmissionError),
+
+    #[error("Avatar error: {0}")]
+    Avatar(#[from] AvatarError),
+
     #[error("Op error: {0}")]
     Op(#[from] OpError),
 }
@@ -58,6 +80,11 @@ pub struct GroupState {
     pub membership: MembershipState,
     pub messages: MessageState,
     pub metadata: MetadataState,
+    pub anonymous: AnonymousState,
+    /// Membership changes in apply order, for system messages.
+    pub membership_feed: MembershipFeed,
+    /// Invite quotas and audit trail.
+    pub invites: InviteLog,
     /// Current DAG heads (all ops until parent_heads is populated in Phase 7).
     pub heads: BTreeSet<OpID>,
     /// Per-author maximum lamport (for sync gap detection).
@@ -76,6 +103,9 @@ impl GroupState {
             membership: MembershipState::new(),
             messages: MessageState::new(),
             metadata: MetadataState::new(),
+            anonymous: AnonymousState::new(),
+            membership_feed: MembershipFeed::new(),
+            invites: InviteLog::new(),
             heads: BTreeSet::new(),
             max_lamport: BTreeMap::new(),
             applied_ops: HashSet::new(),
@@ -91,11 +121,14 @@ impl GroupState {
             Ok(false) => return Err(ApplyError::InvalidSignature),
             Err(_) => return Err(ApplyError::InvalidSignature),
         }
-
         // 2. Check group_id
         if op.group_id != self.group_id {
             return Err(ApplyError::WrongGroup);
         }
+        // A lamport near u64::MAX would exhaust every member's clock.
+        if op.lamport > MAX_LAMPORT {
+            return Err(ApplyError::LamportOutOfRange(op.lamport));
+        }
 
         // 3. Idempotency check
         if self.applied_ops.contains(&op.op_id) {
@@ -112,8 +145,11 @@ impl GroupState {
         // 5. Authorization check
         //    GroupCreate is the founding op — no members exist yet.
         //    Its own apply function validates lamport=1 and no prior creation.
+        //    AnonMsgAdd is signed by a one-time key; its ring proof is the
+        //    authorization and is checked during dispatch.
         let author_device = DeviceID::from_pubkey(&op.author_pubkey);
         if op.op_type != OpType::GroupCreate
+            && op.op_type != OpType::AnonMsgAdd
             && !self.membership.can_author_op(&author_device, &op.op_type)
         {
             return Err(ApplyError::Unauthorized(format!(
@@ -123,20 +159,64 @@ impl GroupState {
         }
 
         // 6. Dispatch to sub-CRDT
+        let before = feed::snapshot(&self.membership, op);
         match op.op_type {
             OpType::GroupCreate => self.membership.apply_group_create(op)?,
-            OpType::MemberInvite => self.membership.apply_member_invite(op)?,
-            OpType::MemberAccept => self.membership.apply_member_accept(op)?,
+            OpType::MemberInvite => self.invites.apply_invite(op, &mut self.membership)?,
+            OpType::MemberAccept => {
+                let payload: MemberAcceptPayload = op
+                    .decode_payload()
+                    .map_err(|e| MembershipError::PayloadDecode(e.to_string()))?;
+                // Recorded as applied so it stays a no-op on every device
+                if !self.invites.is_excluded(&payload.invite_op_id) {
+                    admission::check_accept(op, self.metadata.join_requirement())?;
+                    self.membership.apply_member_accept(op)?
+                }
+            }
             OpType::MemberRemove => self.membership.apply_member_remove(op)?,
             OpType::RoleSet => self.membership.apply_role_set(op)?,
+            OpType::OwnerTransfer => self.membership.apply_owner_transfer(op)?,
             OpType::MsgAdd => self.messages.apply_msg_add(op)?,
             OpType::MsgEdit => self.messages.apply_msg_edit(op)?,
-            OpType::MsgDelete => self.messages.apply_msg_delete(op, &self.membership)?,
+            OpType::MsgDelete => {
+                let payload: MsgDeletePayload = op
+                    .decode_payload()
+                    .map_err(|e| MessageError::PayloadDecode(e.to_string()))?;
+                // A dropped anonymous post is gone (or going) on every device:
+                // recorded as applied so the verdict does not depend on order
+                if self.anonymous.is_dropped(&payload.msg_id) {
+                    self.messages
+                        .check_dropped_post_delete(op, &self.membership)?
+                } else {
+                    self.messages.apply_msg_delete(op, &self.membership)?
+                }
+            }
             OpType::ReactionSet => self.messages.apply_reaction_set(op)?,
-            OpType::MetadataSet => self.metadata.apply_metadata_set(op)?,
+            OpType::ReceiptSet => self.messages.apply_receipt_set(op)?,
+            OpType::MetadataSet => {
+                avatar::check_metadata_set(op)?;
+                self.metadata.apply_metadata_set(op)?
+            }
+            OpType::AnonKeyRegister => self.anonymous.apply_anon_key_register(op)?,
+            OpType::AnonMsgAdd => match self.anonymous.admit_post(
+                op,
+                &self.membership,
+                self.metadata.anonymous_posting_enabled(),
+            )? {
+                AnonAdmission::Admitted { payload, evicted } => {
+                    if let Some((msg_id, create_op)) = evicted {
+                        self.messages.evict_anon_msg(&msg_id, &create_op);
+                    }
+                    self.messages.apply_anon_msg_add(op, *payload)?
+                }
+                // Recorded as applied so redelivery stays a no-op
+                AnonAdmission::OverQuota => {}
+            },
         }
 
         // 7. Bookkeeping
+        self.membership_feed.record(op, before, &self.membership);
+        self.invites.record(op, &self.membership);
         self.applied_ops.insert(op.op_id);
         self.update_heads(op);
         self.op_count += 1;
@@ -148,10 +228,49 @@ impl GroupState {
         Ok(true)
     }
 
+    /// Apply an op received from another device, at the receiver's `now_ms`.
+    ///
+    /// The quota epoch of an anonymous post or an invite comes from its
+    /// author-chosen timestamp, so one dated outside `MAX_OP_CLOCK_SKEW_MS`
+    /// ahead of or `MAX_QUOTA_OP_AGE_MS` behind `now_ms` is refused before
+    /// [`apply_op`](Self::apply_op); otherwise an author could pick a fresh
+    /// epoch for every op. A refused future-dated op applies when delivered
+    /// again in range. Ops already in the local log are replayed with
+    /// `apply_op` / `rebuild_from_ops`, which do not look at the clock.
+    pub fn apply_remote_op(&mut self, op: &OpEnvelope, now_ms: u64) -> Result<bool, ApplyError> {
+        if self.applied_ops.contains(&op.op_id) {
+            return Ok(false);
+        }
+        let quota_charged = matches!(op.op_type, OpType::AnonMsgAdd | OpType::MemberInvite);
+        if quota_charged && !timestamp_plausible(op.timestamp_ms, now_ms) {
+            return Err(ApplyError::ImplausibleTimestamp {
+                timestamp_ms: op.timestamp_ms,
+                now_ms,
+            });
+        }
+        self.apply_op(op)
+    }
+
+    /// Apply ops received together (a sync response, a batch from the app)
+    /// in the given order, one result per op.
+    ///
+    /// Every op goes through [`apply_remote_op`](Self::apply_remote_op), so
+    /// an op gets the same verdict here as when it arrives live.
+    pub fn apply_ops_batch(
+        &mut self,
+        ops: &[OpEnvelope],
+        now_ms: u64,
+    ) -> Vec<Result<bool, ApplyError>> {
+        ops.iter()
+            .map(|op| self.apply_remote_op(op, now_ms))
+            .collect()
+    }
+
     /// Rebuild state from a complete op set (startup / verification).
     ///
     /// Sorts ops by `(lamport, op_id)` for deterministic replay. Any input
-    /// order produces the same final state.
+    /// order produces the same final state. A batch signature check rejects a
+    /// set with a bad op up front; each op is still verified by `apply_op`.
     pub fn rebuild_from_ops(group_id: GroupID, ops: &[OpEnvelope]) -> Result<Self, ApplyError> {
         let mut state = GroupState::new(group_id);
         let mut sorted = ops.to_vec();
@@ -160,6 +279,7 @@ impl GroupState {
                 .cmp(&b.lamport)
                 .then_with(|| a.op_id.cmp(&b.op_id))
         });
+        verify_ops_batch(&sorted).map_err(|_| ApplyError::InvalidSignature)?;
         for op in &sorted {
             state.apply_op(op)?;
         }
@@ -201,6 +321,46 @@ impl GroupState {
                 hasher.update(emoji.as_bytes());
                 hasher.update(&[*present as u8]);
             }
+            // Receipts (omitted when empty so existing hashes are unchanged)
+            if !entry.receipts.is_empty() {
+                hasher.update(b"R");
+                for (reader, status) in &entry.receipts {
+                    hasher.update(reader.as_bytes());
+                    hasher.update(&[*status as u8]);
+                }
+            }
+            // Edits that lost LWW and the edit → base links that decide which
+            // edits count as lost; neither shows in the ciphertext above
+            if !entry.concurrent_edits.is_empty() {
+                hasher.update(b"C");
+                for edit in &entry.concurrent_edits {
+                    hash_op_id(&mut hasher, &edit.op_id);
+                    hasher.update(&(edit.ciphertext.len() as u64).to_le_bytes());
+                    hasher.update(&edit.ciphertext);
+                    hasher.update(&edit.nonce);
+                }
+            }
+            if !entry.edit_bases().is_empty() {
+                hasher.update(b"B");
+                for (edit, base) in entry.edit_bases() {
+                    hash_op_id(&mut hasher, edit);
+                    hash_op_id(&mut hasher, base);
+                }
+            }
+        }
+
+        // --- Receipts waiting for their message (omitted when none) ---
+        let pending = self.messages.pending_receipts();
+        if !pending.is_empty() {
+            hasher.update(b"P");
+            for (msg_id, receipts) in pending {
+                hasher.update(msg_id);
+                hasher.update(&(receipts.len() as u64).to_le_bytes());
+                for (reader, status) in receipts {
+                    hasher.update(reader.as_bytes());
+                    hasher.update(&[*status as u8]);
+                }
+            }
         }
 
         // --- Metadata ---
@@ -212,19 +372,35 @@ impl GroupState {
             hasher.update(&reg.lamport.to_le_bytes());
         }
 
+        // --- Anonymous posting (omitted when unused so existing hashes are unchanged) ---
+        if !self.anonymous.is_empty() {
+            hasher.update(b"A");
+            for (device_id, entry) in self.anonymous.keys() {
+                hasher.update(device_id.as_bytes());
+                hasher.update(&entry.anon_pubkey);
+                hasher.update(&entry.lamport.to_le_bytes());
+            }
+            for ((epoch, tag), count) in self.anonymous.tag_counts() {
+                hasher.update(&epoch.to_le_bytes());
+                hasher.update(tag);
+                hasher.update(&count.to_le_bytes());
+            }
+        }
+
         *hasher.finalize().as_bytes()
     }
 
     /// Get renderable messages — membership-gated.
     ///
     /// Returns only messages whose author is currently an active member and
-    /// that are not tombstoned.
+    /// that are not tombstoned. Anonymous messages have no member author; their
+    /// ring proof was checked against active members at apply time.
     pub fn renderable_messages(&self) -> Vec<&MessageEntry> {
         self.messages
             .messages()
             .values()
             .filter(|msg| !msg.deleted)
-            .filter(|msg| self.membership.is_author_active_for_render(&msg.author))
+            .filter(|msg| msg.anonymous || self.membership.is_author_active_for_render(&msg.author))
             .collect()
     }
 
@@ -255,13 +431,19 @@ impl GroupState {
 // Tests
 // ---------------------------------------------------------------------------
 
+fn hash_op_id(hasher: &mut blake3::Hasher, op_id: &OpID) {
+    hasher.update(op_id.author.as_bytes());
+    hasher.update(&op_id.lamport.to_le_bytes());
+    hasher.update(&op_id.nonce.to_le_bytes());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::crdt::ops::{
         GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, MemberRemovePayload,
-        MetadataKey, MetadataSetPayload, MsgAddPayload, MsgDeletePayload, ReactionSetPayload,
-        RemoveReason, Role,
+        MetadataKey, MetadataSetPayload, MsgAddPayload, MsgDeletePayload, MsgEditPayload,
+        ReactionSetPayload, ReceiptSetPayload, ReceiptStatus, RemoveReason, Role,
     };
 
     fn keypair() -> ([u8; 32], [u8; 32]) {
@@ -319,7 +501,10 @@ mod tests {
         lamport: u64,
         nonce: u64,
     ) -> OpEnvelope {
-        let payload = MemberAcceptPayload { invite_op_id };
+        let payload = MemberAcceptPayload {
+            invite_op_id,
+            attribute_proof: None,
+        };
         OpEnvelope::create_signed(
             gid,
             OpType::MemberAccept,
@@ -349,6 +534,33 @@ mod tests {
             .unwrap()
     }
 
+    fn op_msg_edit(
+        gid: GroupID,
+        pub_k: [u8; 32],
+        priv_k: &[u8; 32],
+        msg_id: [u8; 32],
+        byte: u8,
+        base: OpID,
+        lamport: u64,
+    ) -> OpEnvelope {
+        let payload = MsgEditPayload {
+            msg_id,
+            new_ciphertext: vec![byte],
+            nonce: [byte; 24],
+            base: Some(base),
+        };
+        OpEnvelope::create_signed(
+            gid,
+            OpType::MsgEdit,
+            &payload,
+            lamport,
+            byte as u64,
+            pub_k,
+            priv_k,
+        )
+        .unwrap()
+    }
+
     fn op_remove(
         gid: GroupID,
         author_pub: [u8; 32],
@@ -491,6 +703,91 @@ mod tests {
         assert!(matches!(err, ApplyError::WrongGroup));
     }
 
+    #[test]
+    fn test_apply_ops_batch_rejects_only_bad_signature() {
+        let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, ops) = setup_group();
+        let mut state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        let good = op_msg_add(gid, alice_pub, &alice_priv, [0x01; 32], 4, 400);
+        let mut bad = op_msg_add(gid, alice_pub, &alice_priv, [0x02; 32], 5, 500);
+        bad.signature[0] ^= 0xFF;
+        let results = state.apply_ops_batch(&[good.clone(), bad], 600);
+        assert!(matches!(results[0], Ok(true)));
+        assert!(matches!(results[1], Err(ApplyError::InvalidSignature)));
+
+        // A batch that verifies still goes through the idempotency check
+        let again = state.apply_ops_batch(&[good], 600);
+        assert!(matches!(again[0], Ok(false)));
+    }
+
+    #[test]
+    fn test_rebuild_rejects_bad_signature() {
+        let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, mut ops) = setup_group();
+        let mut bad = op_msg_add(gid, alice_pub, &alice_priv, [0x01; 32], 4, 400);
+        bad.signature[0] ^= 0xFF;
+        ops.push(bad);
+        assert!(matches!(
+            GroupState::rebuild_from_ops(gid, &ops),
+            Err(ApplyError::InvalidSignature)
+        ));
+    }
+
+    /// Re-sign `op` with an order-8 point added to `R`. `VerifyingKey::verify`
+    /// rejects it; the randomized batch equation passes it about 1 time in 8.
+    fn resign_with_torsion(op: &mut OpEnvelope, priv_k: &[u8; 32], r_seed: u8) {
+        use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT, EIGHT_TORSION};
+        use curve25519_dalek::Scalar;
+        use sha2::{Digest, Sha512};
+
+        let expanded = Sha512::digest(priv_k);
+        let mut a_bytes = [0u8; 32];
+        a_bytes.copy_from_slice(&expanded[..32]);
+        a_bytes[0] &= 248;
+        a_bytes[31] &= 127;
+        a_bytes[31] |= 64;
+        let a = Scalar::from_bytes_mod_order(a_bytes);
+
+        let r = Scalar::from_bytes_mod_order([r_seed; 32]);
+        let big_r = (ED25519_BASEPOINT_POINT * r + EIGHT_TORSION[1]).compress();
+        let msg = blake3::hash(&op.signable_bytes().unwrap());
+        let digest = Sha512::new()
+            .chain_update(big_r.as_bytes())
+            .chain_update(op.author_pubkey)
+            .chain_update(msg.as_bytes())
+            .finalize();
+        let mut wide = [0u8; 64];
+        wide.copy_from_slice(&digest);
+        let s = r + Scalar::from_bytes_mod_order_wide(&wide) * a;
+        op.signature[..32].copy_from_slice(big_r.as_bytes());
+        op.signature[32..].copy_from_slice(s.as_bytes());
+    }
+
+    #[test]
+    fn test_torsion_signature_same_verdict_on_every_path() {
+        let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, ops) = setup_group();
+        let mut live = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let mut synced = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        // Enough tries that a path trusting the batch check would admit one.
+        for seed in 1..=32u8 {
+            let mut crafted = op_msg_add(gid, alice_pub, &alice_priv, [seed; 32], 4, seed as u64);
+            resign_with_torsion(&mut crafted, &alice_priv, seed);
+
+            assert!(matches!(
+                live.apply_op(&crafted),
+                Err(ApplyError::InvalidSignature)
+            ));
+            assert!(matches!(
+                synced.apply_ops_batch(std::slice::from_ref(&crafted), 600)[0],
+                Err(ApplyError::InvalidSignature)
+            ));
+            let mut all = ops.clone();
+            all.push(crafted);
+            assert!(GroupState::rebuild_from_ops(gid, &all).is_err());
+        }
+        assert_eq!(live.state_hash(), synced.state_hash());
+    }
+
     #[test]
     fn test_apply_full_lifecycle() {
         let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, ops) = setup_group();
@@ -800,6 +1097,88 @@ mod tests {
         assert_ne!(h1, state.state_hash());
     }
 
+    #[test]
+    fn test_state_hash_covers_receipts() {
+        let (gid, owner_pub, owner_priv, alice_pub, alice_priv, ops) = setup_group();
+        let mut state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let msg_id = [0x01; 32];
+        state
+            .apply_op(&op_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400))
+            .unwrap();
+        let h1 = state.state_hash();
+
+        let payload = ReceiptSetPayload {
+            msg_id,
+            status: ReceiptStatus::Read,
+        };
+        let receipt = OpEnvelope::create_signed(
+            gid,
+            OpType::ReceiptSet,
+            &payload,
+            5,
+            500,
+            owner_pub,
+            &owner_priv,
+        )
+        .unwrap();
+        state.apply_op(&receipt).unwrap();
+
+        assert_ne!(h1, state.state_hash());
+        let counts = state
+            .messages
+            .get_message(&msg_id)
+            .unwrap()
+            .receipt_counts();
+        assert_eq!((counts.delivered, counts.read), (1, 1));
+    }
+
+    #[test]
+    fn test_state_hash_covers_pending_receipts() {
+        let (gid, owner_pub, owner_priv, _alice_pub, _alice_priv, ops) = setup_group();
+        let without = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        // A receipt for a message this replica has not received yet
+        let payload = ReceiptSetPayload {
+            msg_id: [0x09; 32],
+            status: ReceiptStatus::Delivered,
+        };
+        let receipt = OpEnvelope::create_signed(
+            gid,
+            OpType::ReceiptSet,
+            &payload,
+            4,
+            400,
+            owner_pub,
+            &owner_priv,
+        )
+        .unwrap();
+        let mut with = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        with.apply_op(&receipt).unwrap();
+
+        assert!(with.messages.messages().is_empty());
+        assert_ne!(without.state_hash(), with.state_hash());
+    }
+
+    #[test]
+    fn test_state_hash_covers_losing_edits() {
+        let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, mut ops) = setup_group();
+        let msg_id = [0x01; 32];
+        let add = op_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+        // Both edits are made on the original; the later one wins LWW
+        let winner = op_msg_edit(gid, alice_pub, &alice_priv, msg_id, 0xB2, add.op_id, 6);
+        let loser = op_msg_edit(gid, alice_pub, &alice_priv, msg_id, 0xA1, add.op_id, 5);
+        ops.extend([add, winner]);
+
+        let without = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        ops.push(loser);
+        let with = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        let shown = |s: &GroupState| s.messages.get_message(&msg_id).unwrap().ciphertext.clone();
+        assert_eq!(shown(&without), vec![0xB2]);
+        assert_eq!(shown(&with), vec![0xB2]);
+        assert_ne!(without.state_hash(), with.state_hash());
+    }
+
     #[test]
     fn test_state_hash_stable_after_idempotent() {
         let (gid, _owner_pub, _owner_priv, alice_pub, alice_priv, ops) = setup_group();
@@ -865,5 +1244,16 @@ mod tests {
             ))
             .unwrap();
         assert_eq!(state.max_lamport[&alice_dev], 10);
+
+        let result = state.apply_op(&op_msg_add(
+            gid,
+            alice_pub,
+            &alice_priv,
+            [0x02; 32],
+            MAX_LAMPORT + 1,
+            1001,
+        ));
+        assert!(matches!(result, Err(ApplyError::LamportOutOfRange(_))));
+        assert_eq!(state.max_lamport[&alice_dev], 10);
     }
 }
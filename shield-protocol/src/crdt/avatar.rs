@@ -0,0 +1,397 @@
+/// Group avatars — encrypted, content-addressed image blobs referenced from
+/// the `Avatar` metadata register.
+///
+/// The image never enters the op log: an op payload is capped well below a
+/// photo, and every member would replay it forever. Instead the setter seals
+/// the image and a small thumbnail under a fresh blob key, ships both blobs
+/// through the attachment path, and writes an [`AvatarRef`] to the register:
+///
+/// ```text
+/// blob       = version | nonce (24) | XChaCha20-Poly1305(image)
+/// AvatarRef  = CBOR { version, mime, full: {digest, size}, thumbnail: {digest, size},
+///                     wrapped_key = XChaCha20-Poly1305(blob key) under the GroupSecret }
+/// ```
+///
+/// Blobs are addressed by the BLAKE3 digest of their sealed bytes, so any
+/// member or relay can serve them and the fetcher checks what it got before
+/// decrypting. The ref rides in a signed `MetadataSet` op and the register is
+/// LWW, so members converge on the same ref; the apply engine rejects refs
+/// that are malformed or over the size limits, on every replica alike. An
+/// empty value clears the avatar.
+///
+/// The blob key is wrapped to the GroupSecret current when the avatar was
+/// set; after a rekey, an admin re-sets the avatar for new members to read it.
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::crdt::ops::{
+    cbor_decode, cbor_encode, MetadataKey, MetadataSetPayload, OpEnvelope, OpError,
+};
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::rng::SecureRng;
+
+pub const AVATAR_VERSION: u8 = 1;
+
+/// Largest accepted avatar image, before sealing.
+pub const MAX_AVATAR_BYTES: usize = 256 * 1024;
+
+/// Largest accepted thumbnail, before sealing.
+pub const MAX_THUMBNAIL_BYTES: usize = 16 * 1024;
+
+/// Image formats members are expected to render.
+pub const AVATAR_MIME_TYPES: &[&str] = &["image/jpeg", "image/png", "image/webp"];
+
+/// Version byte + XChaCha20 nonce + Poly1305 tag.
+const SEAL_OVERHEAD: usize = 1 + 24 + 16;
+
+/// Wrapped blob key: nonce + key + tag.
+const WRAPPED_KEY_LEN: usize = 24 + 32 + 16;
+
+const WRAP_KEY_CONTEXT: &str = "ShieldMessenger group avatar key wrap v1";
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum AvatarError {
+    #[error("Unsupported avatar version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported avatar type: {0}")]
+    UnsupportedMime(String),
+
+    #[error("Avatar {0} exceeds its size limit")]
+    TooLarge(&'static str),
+
+    #[error("Malformed avatar reference")]
+    MalformedRef,
+
+    #[error("Blob does not match the avatar reference")]
+    DigestMismatch,
+
+    #[error("Avatar decryption failed")]
+    DecryptionFailed,
+
+    #[error("Avatar encryption failed")]
+    EncryptionFailed,
+
+    #[error("Payload decode error: {0}")]
+    PayloadDecode(String),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// Wire types
+// ---------------------------------------------------------------------------
+
+/// Which of the two blobs of an avatar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvatarVariant {
+    Full,
+    Thumbnail,
+}
+
+/// Content address of one sealed blob.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlobRef {
+    /// BLAKE3 of the sealed blob.
+    pub digest: [u8; 32],
+    /// Sealed length in bytes.
+    pub size: u32,
+}
+
+/// Value of the `Avatar` metadata register.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AvatarRef {
+    pub version: u8,
+    pub mime: String,
+    pub full: BlobRef,
+    pub thumbnail: BlobRef,
+    /// Blob key encrypted under a key derived from the GroupSecret.
+    pub wrapped_key: Vec<u8>,
+}
+
+/// Output of [`seal_avatar`]: the register value plus the two blobs to upload.
+#[derive(Clone, Debug)]
+pub struct SealedAvatar {
+    pub avatar_ref: AvatarRef,
+    pub full: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+}
+
+impl AvatarRef {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AvatarError> {
+        Ok(cbor_encode(self)?)
+    }
+
+    /// Decode and validate a register value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AvatarError> {
+        let avatar: AvatarRef = cbor_decode(bytes).map_err(|_| AvatarError::MalformedRef)?;
+        avatar.validate()?;
+        Ok(avatar)
+    }
+
+    /// Structural checks every replica runs before accepting the ref.
+    pub fn validate(&self) -> Result<(), AvatarError> {
+        if self.version != AVATAR_VERSION {
+            return Err(AvatarError::UnsupportedVersion(self.version));
+        }
+        if !AVATAR_MIME_TYPES.contains(&self.mime.as_str()) {
+            return Err(AvatarError::UnsupportedMime(self.mime.clone()));
+        }
+        if self.full.size as usize > MAX_AVATAR_BYTES + SEAL_OVERHEAD {
+            return Err(AvatarError::TooLarge("image"));
+        }
+        if self.thumbnail.size as usize > MAX_THUMBNAIL_BYTES + SEAL_OVERHEAD {
+            return Err(AvatarError::TooLarge("thumbnail"));
+        }
+        if (self.full.size as usize) < SEAL_OVERHEAD
+            || (self.thumbnail.size as usize) < SEAL_OVERHEAD
+            || self.wrapped_key.len() != WRAPPED_KEY_LEN
+        {
+            return Err(AvatarError::MalformedRef);
+        }
+        Ok(())
+    }
+
+    pub fn blob_ref(&self, variant: AvatarVariant) -> &BlobRef {
+        match variant {
+            AvatarVariant::Full => &self.full,
+            AvatarVariant::Thumbnail => &self.thumbnail,
+        }
+    }
+
+    /// Check a fetched blob against its content address and decrypt it.
+    pub fn open(
+        &self,
+        variant: AvatarVariant,
+        blob: &[u8],
+        group_secret: &[u8; 32],
+    ) -> Result<Vec<u8>, AvatarError> {
+        let expected = self.blob_ref(variant);
+        if blob.len() != expected.size as usize || blake3::hash(blob).as_bytes() != &expected.digest
+        {
+            return Err(AvatarError::DigestMismatch);
+        }
+        let (&version, sealed) = blob.split_first().ok_or(AvatarError::MalformedRef)?;
+        if version != AVATAR_VERSION {
+            return Err(AvatarError::UnsupportedVersion(version));
+        }
+        let key = Zeroizing::new(
+            decrypt_message(&self.wrapped_key, &wrap_key(group_secret)[..])
+                .map_err(|_| AvatarError::DecryptionFailed)?,
+        );
+        decrypt_message(sealed, &key[..]).map_err(|_| AvatarError::DecryptionFailed)
+    }
+}
+
+fn wrap_key(group_secret: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(blake3::derive_key(WRAP_KEY_CONTEXT, group_secret))
+}
+
+fn seal_blob(
+    plaintext: &[u8],
+    key: &[u8; 32],
+    rng: &mut impl SecureRng,
+) -> Result<(Vec<u8>, BlobRef), AvatarError> {
+    let sealed =
+        encrypt_message_with_rng(plaintext, key, rng).map_err(|_| AvatarError::EncryptionFailed)?;
+    let mut blob = Vec::with_capacity(1 + sealed.len());
+    blob.push(AVATAR_VERSION);
+    blob.extend_from_slice(&sealed);
+    let blob_ref = BlobRef {
+        digest: *blake3::hash(&blob).as_bytes(),
+        size: blob.len() as u32,
+    };
+    Ok((blob, blob_ref))
+}
+
+// ---------------------------------------------------------------------------
+// Seal
+// ---------------------------------------------------------------------------
+
+/// Seal an avatar image and its thumbnail for the group. Scaling the
+/// thumbnail is the app's job; only sizes are checked here.
+pub fn seal_avatar(
+    image: &[u8],
+    thumbnail: &[u8],
+    mime: &str,
+    group_secret: &[u8; 32],
+    rng: &mut impl SecureRng,
+) -> Result<SealedAvatar, AvatarError> {
+    if image.len() > MAX_AVATAR_BYTES {
+        return Err(AvatarError::TooLarge("image"));
+    }
+    if thumbnail.len() > MAX_THUMBNAIL_BYTES {
+        return Err(AvatarError::TooLarge("thumbnail"));
+    }
+    if !AVATAR_MIME_TYPES.contains(&mime) {
+        return Err(AvatarError::UnsupportedMime(mime.to_string()));
+    }
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    rng.fill_bytes(&mut key[..]);
+    let (full, full_ref) = seal_blob(image, &key, rng)?;
+    let (thumb, thumb_ref) = seal_blob(thumbnail, &key, rng)?;
+    let wrapped_key = encrypt_message_with_rng(&key[..], &wrap_key(group_secret)[..], rng)
+        .map_err(|_| AvatarError::EncryptionFailed)?;
+
+    Ok(SealedAvatar {
+        avatar_ref: AvatarRef {
+            version: AVATAR_VERSION,
+            mime: mime.to_string(),
+            full: full_ref,
+            thumbnail: thumb_ref,
+            wrapped_key,
+        },
+        full,
+        thumbnail: thumb,
+    })
+}
+
+/// `MetadataSet` payload pointing the group at `avatar`, or clearing it.
+pub fn avatar_payload(avatar: Option<&AvatarRef>) -> Result<MetadataSetPayload, AvatarError> {
+    Ok(MetadataSetPayload {
+        key: MetadataKey::Avatar,
+        value: avatar
+            .map(AvatarRef::to_bytes)
+            .transpose()?
+            .unwrap_or_default(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Check
+// ---------------------------------------------------------------------------
+
+/// Check a `MetadataSet` op before it is applied. Only the `Avatar` key is
+/// constrained: its value must be empty or a valid [`AvatarRef`].
+pub fn check_metadata_set(op: &OpEnvelope) -> Result<(), AvatarError> {
+    let payload: MetadataSetPayload = op
+        .decode_payload()
+        .map_err(|e| AvatarError::PayloadDecode(e.to_string()))?;
+    if payload.key != MetadataKey::Avatar || payload.value.is_empty() {
+        return Ok(());
+    }
+    AvatarRef::from_bytes(&payload.value).map(|_| ())
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::apply::{ApplyError, GroupState};
+    use crate::crdt::ids::{DeviceID, GroupID};
+    use crate::crdt::ops::{GroupCreatePayload, OpType};
+    use crate::rng::seeded;
+
+    #[test]
+    fn test_seal_open_roundtrip_and_tamper() {
+        let secret = [7u8; 32];
+        let image = vec![0xAB; 50_000];
+        let thumb = vec![0xCD; 2_000];
+        let sealed = seal_avatar(&image, &thumb, "image/webp", &secret, &mut seeded(1)).unwrap();
+        let avatar = AvatarRef::from_bytes(&sealed.avatar_ref.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(
+            avatar
+                .open(AvatarVariant::Full, &sealed.full, &secret)
+                .unwrap(),
+            image
+        );
+        assert_eq!(
+            avatar
+                .open(AvatarVariant::Thumbnail, &sealed.thumbnail, &secret)
+                .unwrap(),
+            thumb
+        );
+
+        // Served the wrong blob, a tampered blob or the wrong group's secret
+        assert!(matches!(
+            avatar.open(AvatarVariant::Full, &sealed.thumbnail, &secret),
+            Err(AvatarError::DigestMismatch)
+        ));
+        let mut tampered = sealed.full.clone();
+        tampered[100] ^= 1;
+        assert!(matches!(
+            avatar.open(AvatarVariant::Full, &tampered, &secret),
+            Err(AvatarError::DigestMismatch)
+        ));
+        assert!(matches!(
+            avatar.open(AvatarVariant::Full, &sealed.full, &[8u8; 32]),
+            Err(AvatarError::DecryptionFailed)
+        ));
+
+        assert!(matches!(
+            seal_avatar(&image, &image, "image/webp", &secret, &mut seeded(1)),
+            Err(AvatarError::TooLarge("thumbnail"))
+        ));
+    }
+
+    #[test]
+    fn test_apply_engine_validates_avatar_refs() {
+        let (pub_k, priv_k) = crate::crypto::signing::generate_keypair_with_rng(&mut seeded(2));
+        let gid = GroupID::new(&DeviceID::from_pubkey(&pub_k), &[0xCC; 32]);
+        let create = GroupCreatePayload {
+            group_name: "g".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let metadata_op = |lamport: u64, payload: &MetadataSetPayload| {
+            OpEnvelope::create_signed(
+                gid,
+                OpType::MetadataSet,
+                payload,
+                lamport,
+                lamport,
+                pub_k,
+                &priv_k,
+            )
+            .unwrap()
+        };
+        let mut state = GroupState::new(gid);
+        state
+            .apply_op(
+                &OpEnvelope::create_signed(gid, OpType::GroupCreate, &create, 1, 1, pub_k, &priv_k)
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let sealed = seal_avatar(b"img", b"th", "image/png", &[7; 32], &mut seeded(3)).unwrap();
+        let set = avatar_payload(Some(&sealed.avatar_ref)).unwrap();
+        assert!(state.apply_op(&metadata_op(2, &set)).unwrap());
+        assert_eq!(state.metadata.avatar(), Some(sealed.avatar_ref.clone()));
+
+        // Raw image bytes and oversized refs are rejected by every replica
+        let raw = MetadataSetPayload {
+            key: MetadataKey::Avatar,
+            value: vec![0xFF, 0xD8, 0xFF, 0xE0],
+        };
+        assert!(matches!(
+            state.apply_op(&metadata_op(3, &raw)),
+            Err(ApplyError::Avatar(AvatarError::MalformedRef))
+        ));
+        let mut huge = sealed.avatar_ref.clone();
+        huge.full.size = u32::MAX;
+        let huge = MetadataSetPayload {
+            key: MetadataKey::Avatar,
+            value: cbor_encode(&huge).unwrap(),
+        };
+        assert!(matches!(
+            state.apply_op(&metadata_op(4, &huge)),
+            Err(ApplyError::Avatar(AvatarError::TooLarge("image")))
+        ));
+        assert_eq!(state.metadata.avatar(), Some(sealed.avatar_ref));
+
+        assert!(state
+            .apply_op(&metadata_op(5, &avatar_payload(None).unwrap()))
+            .unwrap());
+        assert_eq!(state.metadata.avatar(), None);
+    }
+}
@@ -0,0 +1,295 @@
+/// Managed per-group Lamport clock for locally authored ops.
+///
+/// A device must never reuse or lower its own lamport in a group, and each
+/// new op must sort after everything it has seen. `LamportClock` owns that
+/// rule: it merges every lamport observed on receive, ticks once per local
+/// op, and writes the new value to a `ClockStore` *before* the op is signed,
+/// so a crash between signing and persisting cannot hand out the same value
+/// twice.
+///
+/// Ops are only created through `OpBuilder`, which takes its lamport and
+/// nonce from the clock — callers never choose a lamport themselves.
+use serde::Serialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::crdt::apply::GroupState;
+use crate::crdt::ids::GroupID;
+use crate::crdt::limits::MAX_LAMPORT;
+use crate::crdt::ops::{OpEnvelope, OpError, OpType};
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug)]
+pub enum ClockError {
+    #[error("Lamport clock exhausted")]
+    Overflow,
+    #[error("Lamport {0} is beyond the allowed range")]
+    LamportTooLarge(u64),
+    #[error("Clock at {clock} is behind the persisted value {persisted}")]
+    Regression { clock: u64, persisted: u64 },
+    #[error("GroupCreate must be the first op, clock is at {0}")]
+    GroupAlreadyStarted(u64),
+    #[error("Clock store failed: {0}")]
+    Store(String),
+    #[error(transparent)]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------
+
+/// Where clock values survive restarts. Implementations must make `save`
+/// durable before returning.
+pub trait ClockStore {
+    /// Last saved value for the group, if any.
+    fn load(&self, group_id: &GroupID) -> Result<Option<u64>, ClockError>;
+
+    /// Save a new value. Must refuse to go backwards.
+    fn save(&mut self, group_id: &GroupID, lamport: u64) -> Result<(), ClockError>;
+}
+
+/// In-memory `ClockStore`, for tests and for hosts that rebuild the clock
+/// from the op log on every start.
+#[derive(Debug, Default)]
+pub struct MemoryClockStore {
+    values: HashMap<GroupID, u64>,
+}
+
+impl ClockStore for MemoryClockStore {
+    fn load(&self, group_id: &GroupID) -> Result<Option<u64>, ClockError> {
+        Ok(self.values.get(group_id).copied())
+    }
+
+    fn save(&mut self, group_id: &GroupID, lamport: u64) -> Result<(), ClockError> {
+        let entry = self.values.entry(*group_id).or_insert(0);
+        if lamport < *entry {
+            return Err(ClockError::Regression {
+                clock: lamport,
+                persisted: *entry,
+            });
+        }
+        *entry = lamport;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Clock
+// ---------------------------------------------------------------------------
+
+/// This device's lamport clock in one group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LamportClock {
+    group_id: GroupID,
+    current: u64,
+}
+
+impl LamportClock {
+    /// Resume the clock from its persisted value (0 for a new group).
+    pub fn open(group_id: GroupID, store: &dyn ClockStore) -> Result<Self, ClockError> {
+        let current = store.load(&group_id)?.unwrap_or(0);
+        Ok(Self { group_id, current })
+    }
+
+    pub fn group_id(&self) -> GroupID {
+        self.group_id
+    }
+
+    /// Highest lamport issued or observed so far.
+    pub fn current(&self) -> u64 {
+        self.current
+    }
+
+    /// Merge a lamport seen on a received op.
+    ///
+    /// Values above `MAX_LAMPORT` are refused: one hostile op could
+    /// otherwise push every member's clock to the end of its range.
+    pub fn observe(&mut self, lamport: u64) -> Result<(), ClockError> {
+        if lamport > MAX_LAMPORT {
+            return Err(ClockError::LamportTooLarge(lamport));
+        }
+        self.current = self.current.max(lamport);
+        Ok(())
+    }
+
+    /// Merge everything a group's state has seen (after load or rebuild).
+    pub fn observe_state(&mut self, state: &GroupState) -> Result<(), ClockError> {
+        match state.max_lamport.values().max() {
+            Some(&max) => self.observe(max),
+            None => Ok(()),
+        }
+    }
+
+    /// Issue the next lamport, persisting it first.
+    ///
+    /// Fails with `Regression` if the store is already past this clock,
+    /// which means another handle for the same group issued values this
+    /// one has not seen.
+    fn tick(&mut self, store: &mut dyn ClockStore) -> Result<u64, ClockError> {
+        if let Some(persisted) = store.load(&self.group_id)? {
+            if persisted > self.current {
+                return Err(ClockError::Regression {
+                    clock: self.current,
+                    persisted,
+                });
+            }
+        }
+        let next = self.current.checked_add(1).ok_or(ClockError::Overflow)?;
+        store.save(&self.group_id, next)?;
+        self.current = next;
+        Ok(next)
+    }
+
+    /// Reserve the lamport and nonce for one new op.
+    pub fn op_builder(
+        &mut self,
+        op_type: OpType,
+        store: &mut dyn ClockStore,
+        rng: &mut impl SecureRng,
+    ) -> Result<OpBuilder, ClockError> {
+        if op_type == OpType::GroupCreate && self.current != 0 {
+            return Err(ClockError::GroupAlreadyStarted(self.current));
+        }
+        let lamport = self.tick(store)?;
+        Ok(OpBuilder {
+            group_id: self.group_id,
+            op_type,
+            lamport,
+            nonce: rng.next_u64(),
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Op builder
+// ---------------------------------------------------------------------------
+
+/// A reserved slot for one op. The lamport is already persisted; dropping
+/// the builder without signing just leaves a gap, which is harmless.
+#[derive(Debug)]
+pub struct OpBuilder {
+    group_id: GroupID,
+    op_type: OpType,
+    lamport: u64,
+    nonce: u64,
+}
+
+impl OpBuilder {
+    pub fn lamport(&self) -> u64 {
+        self.lamport
+    }
+
+    /// OpID nonce, needed up front to derive a MsgAdd `msg_id`.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn op_type(&self) -> OpType {
+        self.op_type
+    }
+
+    /// Encode, sign and seal the op.
+    pub fn sign<P: Serialize>(
+        self,
+        payload: &P,
+        author_pubkey: [u8; 32],
+        author_privkey: &[u8; 32],
+    ) -> Result<OpEnvelope, OpError> {
+        OpEnvelope::create_signed(
+            self.group_id,
+            self.op_type,
+            payload,
+            self.lamport,
+            self.nonce,
+            author_pubkey,
+            author_privkey,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{GroupCreatePayload, MsgDeletePayload};
+    use crate::rng::seeded;
+
+    #[test]
+    fn test_ticks_persist_and_merge_received() {
+        let gid = GroupID::from_bytes([1; 32]);
+        let (pub_k, priv_k) = crate::crypto::signing::generate_keypair();
+        let mut store = MemoryClockStore::default();
+        let mut rng = seeded(1);
+
+        let mut clock = LamportClock::open(gid, &store).unwrap();
+        let create = clock
+            .op_builder(OpType::GroupCreate, &mut store, &mut rng)
+            .unwrap()
+            .sign(
+                &GroupCreatePayload {
+                    group_name: "g".into(),
+                    encrypted_group_secret: vec![],
+                },
+                pub_k,
+                &priv_k,
+            )
+            .unwrap();
+        assert_eq!(create.lamport, 1);
+        assert_eq!(store.load(&gid).unwrap(), Some(1));
+
+        clock.observe(7).unwrap();
+        let builder = clock
+            .op_builder(OpType::MsgDelete, &mut store, &mut rng)
+            .unwrap();
+        assert_eq!(builder.lamport(), 8);
+        let op = builder
+            .sign(&MsgDeletePayload { msg_id: [0; 32] }, pub_k, &priv_k)
+            .unwrap();
+        assert_eq!(op.op_id.lamport, 8);
+
+        // Reopened clocks resume where the store left off.
+        let reopened = LamportClock::open(gid, &store).unwrap();
+        assert_eq!(reopened.current(), 8);
+        assert!(matches!(
+            clock.op_builder(OpType::GroupCreate, &mut store, &mut rng),
+            Err(ClockError::GroupAlreadyStarted(8))
+        ));
+    }
+
+    #[test]
+    fn test_regression_and_overflow_detected() {
+        let gid = GroupID::from_bytes([2; 32]);
+        let mut store = MemoryClockStore::default();
+        let mut rng = seeded(2);
+
+        let mut stale = LamportClock::open(gid, &store).unwrap();
+        let mut fresh = LamportClock::open(gid, &store).unwrap();
+        fresh
+            .op_builder(OpType::MsgDelete, &mut store, &mut rng)
+            .unwrap();
+        fresh
+            .op_builder(OpType::MsgDelete, &mut store, &mut rng)
+            .unwrap();
+        stale.observe(1).unwrap();
+        assert!(matches!(
+            stale.op_builder(OpType::MsgDelete, &mut store, &mut rng),
+            Err(ClockError::Regression {
+                clock: 1,
+                persisted: 2
+            })
+        ));
+
+        assert!(matches!(
+            stale.observe(MAX_LAMPORT + 1),
+            Err(ClockError::LamportTooLarge(_))
+        ));
+        let mut spent = LamportClock {
+            group_id: gid,
+            current: u64::MAX,
+        };
+        assert!(matches!(
+            spent.tick(&mut MemoryClockStore::default()),
+            Err(ClockError::Overflow)
+        ));
+    }
+}
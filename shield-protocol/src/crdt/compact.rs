@@ -0,0 +1,329 @@
+/// Op log compaction — drop ops whose effect a later op fully overrides.
+///
+/// The log is append-only, so a busy group accumulates ops that no longer
+/// contribute to state: every edit but the last, every toggle of the same
+/// reaction but the last, `Delivered` receipts followed by `Read`, and
+/// overwritten metadata. `compact_ops` finds them and returns the log without
+/// them; the app deletes the dropped ops from storage.
+///
+/// Only edits from older clients, which do not name their base version, are
+/// dropped. An edit with a base is part of state even after a later edit
+/// wins: it is either kept as a concurrent edit or links the winner to the
+/// versions it superseded.
+///
+/// Two rules keep compaction invisible to peers:
+/// - An author's highest-lamport op is always kept, so the per-author max
+///   lamport vector used by sync digests does not move backwards.
+/// - The compacted log is replayed and must produce the same `state_hash` as
+///   the full log; otherwise nothing is dropped.
+///
+/// A peer that still holds a dropped op may send it again during sync; it
+/// re-applies as a no-op because the op that superseded it is still present.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::crdt::apply::{ApplyError, GroupState};
+use crate::crdt::ids::{DeviceID, GroupID, OpID};
+use crate::crdt::ops::{
+    MetadataSetPayload, MsgEditPayload, OpEnvelope, OpType, ReactionSetPayload, ReceiptSetPayload,
+};
+
+/// Result of compacting one group's log.
+#[derive(Clone, Debug)]
+pub struct Compaction {
+    /// Ops to keep, in deterministic replay order.
+    pub kept: Vec<OpEnvelope>,
+    /// Ops the app may delete from storage.
+    pub dropped: Vec<OpID>,
+}
+
+/// What a superseding op overrides. Ops with equal keys compete; the winner
+/// is the same one the sub-CRDT would keep.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Slot {
+    Edit([u8; 32]),
+    Reaction([u8; 32], DeviceID, String),
+    Receipt([u8; 32], DeviceID),
+    Metadata(u8),
+}
+
+fn slot(op: &OpEnvelope) -> Option<Slot> {
+    let author = DeviceID::from_pubkey(&op.author_pubkey);
+    match op.op_type {
+        OpType::MsgEdit => {
+            let p: MsgEditPayload = op.decode_payload().ok()?;
+            p.base.is_none().then_some(Slot::Edit(p.msg_id))
+        }
+        OpType::ReactionSet => {
+            let p: ReactionSetPayload = op.decode_payload().ok()?;
+            Some(Slot::Reaction(p.msg_id, author, p.emoji))
+        }
+        OpType::ReceiptSet => {
+            let p: ReceiptSetPayload = op.decode_payload().ok()?;
+            Some(Slot::Receipt(p.msg_id, author))
+        }
+        OpType::MetadataSet => {
+            let p: MetadataSetPayload = op.decode_payload().ok()?;
+            Some(Slot::Metadata(p.key as u8))
+        }
+        _ => None,
+    }
+}
+
+/// Receipt rank: `Read` beats `Delivered` regardless of order (max-merge).
+fn receipt_rank(op: &OpEnvelope) -> u8 {
+    op.decode_payload::<ReceiptSetPayload>()
+        .map(|p| p.status as u8)
+        .unwrap_or(0)
+}
+
+/// Compact a group's full op log.
+///
+/// Fails only if the log itself does not replay, exactly like
+/// [`GroupState::rebuild_from_ops`].
+pub fn compact_ops(group_id: GroupID, ops: &[OpEnvelope]) -> Result<Compaction, ApplyError> {
+    let full = GroupState::rebuild_from_ops(group_id, ops)?;
+
+    let mut sorted = ops.to_vec();
+    sorted.sort_by(|a, b| {
+        a.lamport
+            .cmp(&b.lamport)
+            .then_with(|| a.op_id.cmp(&b.op_id))
+    });
+    sorted.dedup_by(|a, b| a.op_id == b.op_id);
+
+    // Winner per slot: the last op in replay order, except receipts where
+    // the highest status wins and replay order breaks ties
+    let mut winners: HashMap<Slot, usize> = HashMap::new();
+    for (i, op) in sorted.iter().enumerate() {
+        let Some(slot) = slot(op) else { continue };
+        match winners.get(&slot) {
+            Some(&w)
+                if op.op_type == OpType::ReceiptSet
+                    && receipt_rank(op) < receipt_rank(&sorted[w]) => {}
+            _ => {
+                winners.insert(slot, i);
+            }
+        }
+    }
+
+    let mut latest_by_author: BTreeMap<DeviceID, usize> = BTreeMap::new();
+    for (i, op) in sorted.iter().enumerate() {
+        latest_by_author.insert(DeviceID::from_pubkey(&op.author_pubkey), i);
+    }
+    let protected: HashSet<usize> = latest_by_author.into_values().collect();
+
+    let (kept, dropped): (Vec<_>, Vec<_>) = sorted.into_iter().enumerate().partition(|(i, op)| {
+        protected.contains(i) || slot(op).is_none_or(|s| winners.get(&s) == Some(i))
+    });
+    let kept: Vec<OpEnvelope> = kept.into_iter().map(|(_, op)| op).collect();
+    let dropped: Vec<OpID> = dropped.into_iter().map(|(_, op)| op.op_id).collect();
+
+    if dropped.is_empty() {
+        return Ok(Compaction { kept, dropped });
+    }
+    let compacted = GroupState::rebuild_from_ops(group_id, &kept)?;
+    if compacted.state_hash() != full.state_hash() {
+        log::warn!(
+            "Compaction of {} would change state; keeping all {} ops",
+            group_id,
+            ops.len()
+        );
+        return Ok(Compaction {
+            kept: ops.to_vec(),
+            dropped: Vec::new(),
+        });
+    }
+    Ok(Compaction { kept, dropped })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{GroupCreatePayload, MetadataKey, MsgAddPayload, ReceiptStatus};
+
+    fn keypair() -> ([u8; 32], [u8; 32]) {
+        crate::crypto::signing::generate_keypair()
+    }
+
+    fn op<P: serde::Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        (pub_k, priv_k): &([u8; 32], [u8; 32]),
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport, *pub_k, priv_k).unwrap()
+    }
+
+    fn topic(value: &[u8]) -> MetadataSetPayload {
+        MetadataSetPayload {
+            key: MetadataKey::Topic,
+            value: value.to_vec(),
+        }
+    }
+
+    fn reaction(msg_id: [u8; 32], present: bool) -> ReactionSetPayload {
+        ReactionSetPayload {
+            msg_id,
+            emoji: "👍".into(),
+            present,
+        }
+    }
+
+    #[test]
+    fn test_compaction_drops_superseded_ops_and_keeps_state() {
+        let owner = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0xCC; 32]);
+        let msg_id = [7u8; 32];
+        let create = GroupCreatePayload {
+            group_name: "Test".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let add = MsgAddPayload {
+            msg_id,
+            ciphertext: vec![1],
+            nonce: [0; 24],
+        };
+        let edit = |b: u8| MsgEditPayload {
+            msg_id,
+            new_ciphertext: vec![b],
+            nonce: [b; 24],
+            base: None,
+        };
+        let receipt = |status| ReceiptSetPayload { msg_id, status };
+
+        let ops = vec![
+            op(gid, OpType::GroupCreate, &create, 1, &owner),
+            op(gid, OpType::MsgAdd, &add, 2, &owner),
+            op(gid, OpType::MsgEdit, &edit(2), 3, &owner),
+            op(gid, OpType::MsgEdit, &edit(3), 4, &owner),
+            op(gid, OpType::ReactionSet, &reaction(msg_id, true), 5, &owner),
+            op(
+                gid,
+                OpType::ReactionSet,
+                &reaction(msg_id, false),
+                6,
+                &owner,
+            ),
+            op(gid, OpType::ReactionSet, &reaction(msg_id, true), 7, &owner),
+            op(gid, OpType::MetadataSet, &topic(b"old"), 8, &owner),
+            op(gid, OpType::MetadataSet, &topic(b"new"), 9, &owner),
+            op(
+                gid,
+                OpType::ReceiptSet,
+                &receipt(ReceiptStatus::Read),
+                10,
+                &owner,
+            ),
+            op(
+                gid,
+                OpType::ReceiptSet,
+                &receipt(ReceiptStatus::Delivered),
+                11,
+                &owner,
+            ),
+        ];
+        let full = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        let compaction = compact_ops(gid, &ops).unwrap();
+        // Edit 3, reactions 5-6 and topic 8 are superseded. The Delivered
+        // receipt is dominated by Read but is the author's latest op.
+        let expected: Vec<OpID> = [2, 4, 5, 7].iter().map(|&i| ops[i].op_id).collect();
+        assert_eq!(compaction.dropped, expected);
+        assert_eq!(compaction.kept.len(), ops.len() - 4);
+
+        let compacted = GroupState::rebuild_from_ops(gid, &compaction.kept).unwrap();
+        assert_eq!(compacted.state_hash(), full.state_hash());
+        assert_eq!(compacted.max_lamport, full.max_lamport);
+        assert_eq!(compacted.metadata.topic(), Some("new"));
+
+        // A compacted log has nothing left to drop
+        assert!(compact_ops(gid, &compaction.kept)
+            .unwrap()
+            .dropped
+            .is_empty());
+    }
+
+    #[test]
+    fn test_compaction_keeps_edits_with_a_base() {
+        let owner = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0xCE; 32]);
+        let msg_id = [8u8; 32];
+        let create = GroupCreatePayload {
+            group_name: "Test".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let add = MsgAddPayload {
+            msg_id,
+            ciphertext: vec![1],
+            nonce: [0; 24],
+        };
+        let add_op = op(gid, OpType::MsgAdd, &add, 2, &owner);
+        let edit = |b: u8| MsgEditPayload {
+            msg_id,
+            new_ciphertext: vec![b],
+            nonce: [b; 24],
+            base: Some(add_op.op_id),
+        };
+        let ops = vec![
+            op(gid, OpType::GroupCreate, &create, 1, &owner),
+            add_op.clone(),
+            // Concurrent edits of the original: the first loses but stays visible
+            op(gid, OpType::MsgEdit, &edit(3), 3, &owner),
+            op(gid, OpType::MsgEdit, &edit(4), 4, &owner),
+            op(gid, OpType::MetadataSet, &topic(b"old"), 5, &owner),
+            op(gid, OpType::MetadataSet, &topic(b"new"), 6, &owner),
+        ];
+
+        let compaction = compact_ops(gid, &ops).unwrap();
+        assert_eq!(compaction.dropped, vec![ops[4].op_id]);
+        let full = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let compacted = GroupState::rebuild_from_ops(gid, &compaction.kept).unwrap();
+        assert_eq!(compacted.state_hash(), full.state_hash());
+    }
+
+    #[test]
+    fn test_compaction_keeps_independent_slots() {
+        let owner = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0xCD; 32]);
+        let create = GroupCreatePayload {
+            group_name: "Test".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let add = |id: u8| MsgAddPayload {
+            msg_id: [id; 32],
+            ciphertext: vec![id],
+            nonce: [0; 24],
+        };
+        let ops = vec![
+            op(gid, OpType::GroupCreate, &create, 1, &owner),
+            op(gid, OpType::MsgAdd, &add(1), 2, &owner),
+            op(gid, OpType::MsgAdd, &add(2), 3, &owner),
+            // Same emoji on different messages: independent slots
+            op(
+                gid,
+                OpType::ReactionSet,
+                &reaction([1; 32], true),
+                4,
+                &owner,
+            ),
+            op(
+                gid,
+                OpType::ReactionSet,
+                &reaction([2; 32], true),
+                5,
+                &owner,
+            ),
+        ];
+        let compaction = compact_ops(gid, &ops).unwrap();
+        assert!(compaction.dropped.is_empty());
+        assert_eq!(compaction.kept.len(), ops.len());
+
+        // Invalid logs fail exactly like a rebuild
+        assert!(compact_ops(gid, &ops[1..]).is_err());
+    }
+}
@@ -0,0 +1,396 @@
+/// Membership change feed — the system messages of a group conversation.
+///
+/// The apply engine records a `MembershipEvent` for every membership change
+/// an op actually causes (an invite dropped as stale, or a role set that
+/// loses LWW, records nothing). Clients render the feed instead of diffing
+/// `MembershipState` snapshots themselves.
+///
+/// Event IDs are derived from the causing op, so every device names the same
+/// event the same way. The `seq` cursor is local: it follows this replica's
+/// apply order, which differs between incremental receive and a rebuild.
+/// After reloading a group, read from 0 and skip IDs already rendered.
+use crate::crdt::ids::{DeviceID, OpID};
+use crate::crdt::membership::MembershipState;
+use crate::crdt::ops::{
+    MemberInvitePayload, MemberRemovePayload, OpEnvelope, OpType, OwnerTransferPayload, Role,
+    RoleSetPayload,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MembershipEventKind {
+    /// The group was created; `subject` is the creator.
+    Created,
+    /// `subject` was invited with `role` and has not accepted yet.
+    Invited {
+        role: Role,
+    },
+    /// `subject` became an active member.
+    Joined {
+        role: Role,
+    },
+    /// `subject` removed themselves (or declined a pending invite).
+    Left,
+    /// `actor` removed `subject`.
+    Kicked,
+    RoleChanged {
+        from: Role,
+        to: Role,
+    },
+    /// A kick left the GroupSecret with a removed member; rotate it.
+    RekeyRequired,
+}
+
+impl MembershipEventKind {
+    fn tag(&self) -> u8 {
+        match self {
+            MembershipEventKind::Created => 0,
+            MembershipEventKind::Invited { .. } => 1,
+            MembershipEventKind::Joined { .. } => 2,
+            MembershipEventKind::Left => 3,
+            MembershipEventKind::Kicked => 4,
+            MembershipEventKind::RoleChanged { .. } => 5,
+            MembershipEventKind::RekeyRequired => 6,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MembershipEvent {
+    /// BLAKE3(op_id || subject || kind) — identical on every replica.
+    pub id: [u8; 32],
+    /// Position in this replica's feed.
+    pub seq: u64,
+    /// The op that caused the change.
+    pub op_id: OpID,
+    pub actor: DeviceID,
+    pub subject: DeviceID,
+    pub kind: MembershipEventKind,
+    /// Wall-clock timestamp from the op (UX only).
+    pub timestamp_ms: u64,
+}
+
+/// Membership standing of one device, before or after an op.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Standing {
+    accepted: bool,
+    removed: bool,
+    role: Role,
+}
+
+impl Standing {
+    fn active(&self) -> bool {
+        self.accepted && !self.removed
+    }
+}
+
+/// Standings of the devices an op can affect, taken before it is applied.
+pub(crate) struct Snapshot(Vec<(DeviceID, Option<Standing>)>);
+
+/// Snapshot the devices `op` touches. Non-membership ops touch none, so
+/// this costs nothing on the message path.
+pub(crate) fn snapshot(membership: &MembershipState, op: &OpEnvelope) -> Snapshot {
+    let author = DeviceID::from_pubkey(&op.author_pubkey);
+    let devices = match op.op_type {
+        OpType::GroupCreate | OpType::MemberAccept => vec![author],
+        OpType::MemberInvite => op
+            .decode_payload::<MemberInvitePayload>()
+            .map(|p| vec![p.invited_device_id])
+            .unwrap_or_default(),
+        OpType::MemberRemove => op
+            .decode_payload::<MemberRemovePayload>()
+            .map(|p| vec![p.target_device_id])
+            .unwrap_or_default(),
+        OpType::RoleSet => op
+            .decode_payload::<RoleSetPayload>()
+            .map(|p| vec![p.target_device_id])
+            .unwrap_or_default(),
+        OpType::OwnerTransfer => op
+            .decode_payload::<OwnerTransferPayload>()
+            .map(|p| vec![p.new_owner_device_id, author])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    Snapshot(
+        devices
+            .into_iter()
+            .map(|d| (d, standing(membership, &d)))
+            .collect(),
+    )
+}
+
+fn standing(membership: &MembershipState, device: &DeviceID) -> Option<Standing> {
+    membership.members().get(device).map(|m| Standing {
+        accepted: m.accepted,
+        removed: m.removed,
+        role: m.role,
+    })
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MembershipFeed {
+    events: Vec<MembershipEvent>,
+}
+
+impl MembershipFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events at or after `cursor`.
+    pub fn since(&self, cursor: u64) -> &[MembershipEvent] {
+        let start = (cursor as usize).min(self.events.len());
+        &self.events[start..]
+    }
+
+    /// Cursor to pass next time: one past the newest event.
+    pub fn cursor(&self) -> u64 {
+        self.events.len() as u64
+    }
+
+    /// Record what an applied op changed, comparing against `before`.
+    pub(crate) fn record(&mut self, op: &OpEnvelope, before: Snapshot, after: &MembershipState) {
+        let actor = DeviceID::from_pubkey(&op.author_pubkey);
+        for (subject, was) in before.0 {
+            let Some(now) = standing(after, &subject) else {
+                continue;
+            };
+            if was == Some(now) {
+                continue;
+            }
+            let was_active = was.is_some_and(|w| w.active());
+            let was_present = was.is_some_and(|w| !w.removed);
+
+            let kinds: &[MembershipEventKind] = if op.op_type == OpType::GroupCreate {
+                &[MembershipEventKind::Created]
+            } else if now.removed {
+                if !was_present {
+                    continue;
+                } else if subject == actor {
+                    &[MembershipEventKind::Left]
+                } else if was_active {
+                    &[
+                        MembershipEventKind::Kicked,
+                        MembershipEventKind::RekeyRequired,
+                    ]
+                } else {
+                    &[MembershipEventKind::Kicked]
+                }
+            } else if now.active() && !was_active {
+                &[MembershipEventKind::Joined { role: now.role }]
+            } else if !now.accepted && !was_present {
+                &[MembershipEventKind::Invited { role: now.role }]
+            } else if let Some(w) = was.filter(|w| w.role != now.role) {
+                &[MembershipEventKind::RoleChanged {
+                    from: w.role,
+                    to: now.role,
+                }]
+            } else {
+                continue;
+            };
+            for kind in kinds {
+                self.push(op, actor, subject, *kind);
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        op: &OpEnvelope,
+        actor: DeviceID,
+        subject: DeviceID,
+        kind: MembershipEventKind,
+    ) {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(op.op_id.author.as_bytes());
+        hasher.update(&op.op_id.lamport.to_le_bytes());
+        hasher.update(&op.op_id.nonce.to_le_bytes());
+        hasher.update(subject.as_bytes());
+        hasher.update(&[kind.tag()]);
+        self.events.push(MembershipEvent {
+            id: *hasher.finalize().as_bytes(),
+            seq: self.events.len() as u64,
+            op_id: op.op_id,
+            actor,
+            subject,
+            kind,
+            timestamp_ms: op.timestamp_ms,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::apply::GroupState;
+    use crate::crdt::ids::GroupID;
+    use crate::crdt::ops::{GroupCreatePayload, MemberAcceptPayload, RemoveReason};
+    use serde::Serialize;
+
+    fn op<P: Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        key: &([u8; 32], [u8; 32]),
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport, key.0, &key.1).unwrap()
+    }
+
+    /// Owner creates the group, invites Alice and Bob; both accept.
+    fn group_ops() -> (GroupID, Vec<OpEnvelope>, [([u8; 32], [u8; 32]); 3]) {
+        let owner = crate::crypto::signing::generate_keypair();
+        let alice = crate::crypto::signing::generate_keypair();
+        let bob = crate::crypto::signing::generate_keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[5; 32]);
+        let mut ops = vec![op(
+            gid,
+            OpType::GroupCreate,
+            &GroupCreatePayload {
+                group_name: "g".into(),
+                encrypted_group_secret: vec![],
+            },
+            1,
+            &owner,
+        )];
+        for (i, member) in [alice, bob].iter().enumerate() {
+            let invite = op(
+                gid,
+                OpType::MemberInvite,
+                &MemberInvitePayload {
+                    invited_device_id: DeviceID::from_pubkey(&member.0),
+                    invited_pubkey: member.0,
+                    role: Role::Member,
+                    encrypted_group_secret: vec![],
+                },
+                2 + 2 * i as u64,
+                &owner,
+            );
+            let accept = op(
+                gid,
+                OpType::MemberAccept,
+                &MemberAcceptPayload {
+                    invite_op_id: invite.op_id,
+                    attribute_proof: None,
+                },
+                3 + 2 * i as u64,
+                member,
+            );
+            ops.extend([invite, accept]);
+        }
+        (gid, ops, [owner, alice, bob])
+    }
+
+    #[test]
+    fn test_feed_records_membership_changes() {
+        let (gid, mut ops, [owner, alice, bob]) = group_ops();
+        let alice_dev = DeviceID::from_pubkey(&alice.0);
+        let bob_dev = DeviceID::from_pubkey(&bob.0);
+        ops.push(op(
+            gid,
+            OpType::RoleSet,
+            &RoleSetPayload {
+                target_device_id: alice_dev,
+                new_role: Role::Admin,
+            },
+            6,
+            &owner,
+        ));
+        ops.push(op(
+            gid,
+            OpType::MemberRemove,
+            &MemberRemovePayload {
+                target_device_id: bob_dev,
+                reason: RemoveReason::Kick,
+            },
+            7,
+            &alice,
+        ));
+
+        let mut state = GroupState::new(gid);
+        for o in &ops[..5] {
+            state.apply_op(o).unwrap();
+        }
+        let cursor = state.membership_feed.cursor();
+        let kinds: Vec<_> = state
+            .membership_feed
+            .since(0)
+            .iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                MembershipEventKind::Created,
+                MembershipEventKind::Invited { role: Role::Member },
+                MembershipEventKind::Joined { role: Role::Member },
+                MembershipEventKind::Invited { role: Role::Member },
+                MembershipEventKind::Joined { role: Role::Member },
+            ]
+        );
+
+        for o in &ops[5..] {
+            state.apply_op(o).unwrap();
+        }
+        // Redelivery adds nothing.
+        assert!(!state.apply_op(&ops[6]).unwrap());
+        let new: Vec<_> = state
+            .membership_feed
+            .since(cursor)
+            .iter()
+            .map(|e| (e.subject, e.kind))
+            .collect();
+        assert_eq!(
+            new,
+            vec![
+                (
+                    alice_dev,
+                    MembershipEventKind::RoleChanged {
+                        from: Role::Member,
+                        to: Role::Admin
+                    }
+                ),
+                (bob_dev, MembershipEventKind::Kicked),
+                (bob_dev, MembershipEventKind::RekeyRequired),
+            ]
+        );
+        assert!(state
+            .membership_feed
+            .since(state.membership_feed.cursor())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_event_ids_match_across_replicas() {
+        let (gid, mut ops, [owner, alice, _]) = group_ops();
+        ops.push(op(
+            gid,
+            OpType::MemberRemove,
+            &MemberRemovePayload {
+                target_device_id: DeviceID::from_pubkey(&alice.0),
+                reason: RemoveReason::Kick,
+            },
+            6,
+            &owner,
+        ));
+        let rebuilt = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        // A replica that hears about Bob before Alice.
+        let mut incremental = GroupState::new(gid);
+        for i in [0, 3, 4, 1, 2, 5] {
+            incremental.apply_op(&ops[i]).unwrap();
+        }
+
+        let ids = |s: &GroupState| {
+            let mut ids: Vec<_> = s.membership_feed.since(0).iter().map(|e| e.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&rebuilt), ids(&incremental));
+        let last = rebuilt.membership_feed.since(0).last().unwrap();
+        assert_eq!(last.kind, MembershipEventKind::RekeyRequired);
+        assert_ne!(
+            rebuilt.membership_feed.since(0)[1].id,
+            incremental.membership_feed.since(0)[1].id
+        );
+    }
+}
@@ -0,0 +1,512 @@
+/// Invite quotas and the invite audit trail.
+///
+/// In a large group every admin can invite, and each invite lands in every
+/// member's op log. To bound invite spam, an inviter may send at most
+/// `MAX_INVITES_PER_EPOCH` `MemberInvite` ops per epoch, where an op's epoch
+/// is `timestamp_ms / INVITE_EPOCH_MS`. `GroupWriter` refuses to author an
+/// invite past the quota, and the apply engine enforces it on every device.
+///
+/// Arrival order differs between devices, so the quota is not charged in
+/// it. Each (inviter, epoch) keeps the invites with the lowest
+/// `(lamport, op_id)`, as anonymous posts do: a lower invite arriving at a
+/// full budget evicts the highest one, putting back the member entry that
+/// invite replaced, and an invite above the cut is skipped. An accept of a
+/// skipped or evicted invite is a no-op. Every device ends up with the same
+/// membership whatever order the invites and accepts came in. Ops the
+/// invitee wrote while admitted are kept but, like a removed member's, no
+/// longer render. Timestamps are author-chosen, so
+/// `GroupState::apply_remote_op` refuses invites dated outside the receiver's
+/// clock window (`MAX_OP_CLOCK_SKEW_MS` ahead, `MAX_QUOTA_OP_AGE_MS` behind);
+/// forged epochs then buy at most the quotas of the epochs in that window.
+///
+/// `InviteLog` also keeps an `InviteRecord` per invite that took effect: who
+/// invited whom with which role and when, and whether the invitee accepted
+/// or the invite was revoked (the invitee was removed or invited again
+/// before accepting). Expiry is a read-time view: a pending invite older
+/// than `INVITE_TTL_MS` reports `InviteStatus::Expired`. Invites dropped as
+/// stale still count against the quota but leave no record.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::crdt::ids::{DeviceID, OpID};
+use crate::crdt::limits::{INVITE_EPOCH_MS, INVITE_TTL_MS, MAX_INVITES_PER_EPOCH};
+use crate::crdt::membership::{MemberEntry, MembershipError, MembershipState};
+use crate::crdt::ops::{
+    MemberAcceptPayload, MemberInvitePayload, MemberRemovePayload, OpEnvelope, OpType, Role,
+};
+
+// ---------------------------------------------------------------------------
+// Records
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteStatus {
+    /// Not yet accepted and younger than `INVITE_TTL_MS`.
+    Pending,
+    Accepted {
+        at_ms: u64,
+    },
+    /// The invitee was removed or invited again before accepting.
+    Revoked {
+        by: OpID,
+    },
+    /// Not accepted within `INVITE_TTL_MS`.
+    Expired,
+}
+
+/// One invite that took effect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InviteRecord {
+    pub invite_op_id: OpID,
+    pub inviter: DeviceID,
+    pub invitee: DeviceID,
+    pub role: Role,
+    /// Wall-clock timestamp from the invite op (UX only).
+    pub invited_at_ms: u64,
+    /// The `MemberAccept` op and its timestamp.
+    pub accepted: Option<(OpID, u64)>,
+    /// The op that revoked the invite before it was accepted.
+    pub revoked_by: Option<OpID>,
+}
+
+impl InviteRecord {
+    /// Status as of `now_ms`.
+    pub fn status(&self, now_ms: u64) -> InviteStatus {
+        if let Some((_, at_ms)) = self.accepted {
+            InviteStatus::Accepted { at_ms }
+        } else if let Some(by) = self.revoked_by {
+            InviteStatus::Revoked { by }
+        } else if now_ms.saturating_sub(self.invited_at_ms) >= INVITE_TTL_MS {
+            InviteStatus::Expired
+        } else {
+            InviteStatus::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// InviteLog
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+pub struct InviteLog {
+    /// Keyed by invite op, so iteration follows replay order.
+    records: BTreeMap<OpID, InviteRecord>,
+    /// Invitee → their invite that is neither accepted nor revoked.
+    open: BTreeMap<DeviceID, OpID>,
+    /// (inviter, epoch) → admitted invites by (lamport, op_id).
+    admitted: BTreeMap<(DeviceID, u64), BTreeSet<(u64, OpID)>>,
+    /// Invites skipped or evicted by the quota.
+    excluded: BTreeSet<OpID>,
+    /// Admitted invite → its invitee and the entry the invite replaced.
+    displaced: BTreeMap<OpID, (DeviceID, Option<Arc<MemberEntry>>)>,
+}
+
+impl InviteLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All invites that took effect, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &InviteRecord> {
+        self.records.values()
+    }
+
+    pub fn get(&self, invite_op_id: &OpID) -> Option<&InviteRecord> {
+        self.records.get(invite_op_id)
+    }
+
+    /// Invites sent by `inviter`, oldest first.
+    pub fn by_inviter<'a>(
+        &'a self,
+        inviter: &'a DeviceID,
+    ) -> impl Iterator<Item = &'a InviteRecord> + 'a {
+        self.records().filter(move |r| r.inviter == *inviter)
+    }
+
+    /// Invites `invitee` received, oldest first.
+    pub fn for_invitee<'a>(
+        &'a self,
+        invitee: &'a DeviceID,
+    ) -> impl Iterator<Item = &'a InviteRecord> + 'a {
+        self.records().filter(move |r| r.invitee == *invitee)
+    }
+
+    /// Invites `inviter` may still send in the epoch containing `now_ms`.
+    pub fn remaining(&self, inviter: &DeviceID, now_ms: u64) -> u32 {
+        let used = self
+            .admitted
+            .get(&(*inviter, now_ms / INVITE_EPOCH_MS))
+            .map_or(0, BTreeSet::len);
+        MAX_INVITES_PER_EPOCH.saturating_sub(used as u32)
+    }
+
+    /// Whether the quota skipped or evicted this invite.
+    pub fn is_excluded(&self, invite_op_id: &OpID) -> bool {
+        self.excluded.contains(invite_op_id)
+    }
+
+    /// Apply a MemberInvite under its inviter's quota for the epoch. An
+    /// invite above the cut is skipped (`Ok`, nothing changes); one below it
+    /// is applied to `membership` and may evict the highest admitted invite.
+    pub(crate) fn apply_invite(
+        &mut self,
+        op: &OpEnvelope,
+        membership: &mut MembershipState,
+    ) -> Result<(), MembershipError> {
+        let payload: MemberInvitePayload = op
+            .decode_payload()
+            .map_err(|e| MembershipError::PayloadDecode(e.to_string()))?;
+        let key = (
+            DeviceID::from_pubkey(&op.author_pubkey),
+            op.timestamp_ms / INVITE_EPOCH_MS,
+        );
+        let rank = (op.lamport, op.op_id);
+        let mut evicted = None;
+        if let Some(admitted) = self.admitted.get(&key) {
+            if admitted.len() >= MAX_INVITES_PER_EPOCH as usize {
+                let highest = *admitted.last().expect("budget is non-zero");
+                if rank > highest {
+                    self.excluded.insert(op.op_id);
+                    return Ok(());
+                }
+                evicted = Some(highest);
+            }
+        }
+
+        let invitee = payload.invited_device_id;
+        let previous = membership.members().get(&invitee).cloned();
+        membership.apply_member_invite(op)?;
+        self.displaced.insert(op.op_id, (invitee, previous));
+        let admitted = self.admitted.entry(key).or_default();
+        admitted.insert(rank);
+        if let Some(highest) = evicted {
+            admitted.remove(&highest);
+            self.evict(highest.1, membership);
+        }
+        Ok(())
+    }
+
+    /// Undo an admitted invite that lost its place under the quota.
+    fn evict(&mut self, invite_op_id: OpID, membership: &mut MembershipState) {
+        self.excluded.insert(invite_op_id);
+        self.records.remove(&invite_op_id);
+        let Some((invitee, mut previous)) = self.displaced.get(&invite_op_id).cloned() else {
+            return;
+        };
+        if self.open.get(&invitee) == Some(&invite_op_id) {
+            self.open.remove(&invitee);
+        }
+        // The replaced entry may itself come from an excluded invite
+        while let Some(entry) = previous
+            .as_ref()
+            .filter(|e| self.excluded.contains(&e.invited_by))
+        {
+            previous = self
+                .displaced
+                .get(&entry.invited_by)
+                .and_then(|(_, p)| p.clone());
+        }
+        membership.restore_entry(&invitee, &invite_op_id, previous);
+    }
+
+    /// Record what an applied membership op did to invites.
+    pub(crate) fn record(&mut self, op: &OpEnvelope, after: &MembershipState) {
+        let author = DeviceID::from_pubkey(&op.author_pubkey);
+        match op.op_type {
+            OpType::MemberInvite => {
+                let Ok(payload) = op.decode_payload::<MemberInvitePayload>() else {
+                    return;
+                };
+                let invitee = payload.invited_device_id;
+                // Dropped as stale: the remove it lost to still stands
+                if after.members().get(&invitee).map(|m| m.invited_by) != Some(op.op_id) {
+                    return;
+                }
+                self.revoke_open(&invitee, op.op_id);
+                self.records.insert(
+                    op.op_id,
+                    InviteRecord {
+                        invite_op_id: op.op_id,
+                        inviter: author,
+                        invitee,
+                        role: payload.role,
+                        invited_at_ms: op.timestamp_ms,
+                        accepted: None,
+                        revoked_by: None,
+                    },
+                );
+                self.open.insert(invitee, op.op_id);
+            }
+            OpType::MemberAccept => {
+                let Ok(payload) = op.decode_payload::<MemberAcceptPayload>() else {
+                    return;
+                };
+                if self.excluded.contains(&payload.invite_op_id) {
+                    return;
+                }
+                if let Some(record) = self.records.get_mut(&payload.invite_op_id) {
+                    record.accepted = Some((op.op_id, op.timestamp_ms));
+                }
+                self.open.remove(&author);
+            }
+            OpType::MemberRemove => {
+                if let Ok(payload) = op.decode_payload::<MemberRemovePayload>() {
+                    self.revoke_open(&payload.target_device_id, op.op_id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn revoke_open(&mut self, invitee: &DeviceID, by: OpID) {
+        if let Some(open) = self.open.remove(invitee) {
+            if let Some(record) = self.records.get_mut(&open) {
+                record.revoked_by = Some(by);
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::apply::{ApplyError, GroupState};
+    use crate::crdt::ids::GroupID;
+    use crate::crdt::limits::MAX_QUOTA_OP_AGE_MS;
+    use crate::crdt::ops::{GroupCreatePayload, RemoveReason};
+    use serde::Serialize;
+
+    type Key = ([u8; 32], [u8; 32]);
+
+    const T0: u64 = 1_700_000_000_000;
+
+    fn op<P: Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        timestamp_ms: u64,
+        key: &Key,
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport, key.0, &key.1)
+            .unwrap()
+            .redated(timestamp_ms, &key.1)
+    }
+
+    fn invite(gid: GroupID, inviter: &Key, invitee: &Key, lamport: u64, at: u64) -> OpEnvelope {
+        op(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: DeviceID::from_pubkey(&invitee.0),
+                invited_pubkey: invitee.0,
+                role: Role::Member,
+                encrypted_group_secret: vec![],
+            },
+            lamport,
+            at,
+            inviter,
+        )
+    }
+
+    /// Group created by a new owner; returns its ops so far.
+    fn group() -> (GroupID, Key, Vec<OpEnvelope>) {
+        let owner = crate::crypto::signing::generate_keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[3; 32]);
+        let create = op(
+            gid,
+            OpType::GroupCreate,
+            &GroupCreatePayload {
+                group_name: "g".into(),
+                encrypted_group_secret: vec![],
+            },
+            1,
+            T0,
+            &owner,
+        );
+        (gid, owner, vec![create])
+    }
+
+    #[test]
+    fn test_quota_per_inviter_and_epoch() {
+        let (gid, owner, ops) = group();
+        let owner_device = DeviceID::from_pubkey(&owner.0);
+        let mut state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        for i in 0..MAX_INVITES_PER_EPOCH as u64 {
+            let invitee = crate::crypto::signing::generate_keypair();
+            state
+                .apply_op(&invite(gid, &owner, &invitee, 2 + i, T0 + i))
+                .unwrap();
+        }
+        // What GroupWriter checks before authoring another invite
+        assert_eq!(state.invites.remaining(&owner_device, T0), 0);
+
+        // The next epoch has a fresh quota
+        let next = (T0 / INVITE_EPOCH_MS + 1) * INVITE_EPOCH_MS;
+        assert_eq!(
+            state.invites.remaining(&owner_device, next),
+            MAX_INVITES_PER_EPOCH
+        );
+    }
+
+    #[test]
+    fn test_invites_in_forged_epochs_are_capped() {
+        let (gid, owner, ops) = group();
+        let mut state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let now = T0 + 100 * INVITE_EPOCH_MS;
+
+        // One invite dated into each of 200 epochs around the receiver's clock
+        let mut applied = 0;
+        for i in 0..200u64 {
+            let invitee = crate::crypto::signing::generate_keypair();
+            let at = T0 + i * INVITE_EPOCH_MS;
+            match state.apply_remote_op(&invite(gid, &owner, &invitee, 2 + i, at), now) {
+                Ok(true) => applied += 1,
+                Err(ApplyError::ImplausibleTimestamp { timestamp_ms, .. }) => {
+                    assert_eq!(timestamp_ms, at)
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        // Only the epochs inside the window, not one quota per forged epoch
+        assert_eq!(applied, MAX_QUOTA_OP_AGE_MS / INVITE_EPOCH_MS + 1);
+    }
+
+    fn accept(gid: GroupID, invitee: &Key, invite: &OpEnvelope, at: u64) -> OpEnvelope {
+        op(
+            gid,
+            OpType::MemberAccept,
+            &MemberAcceptPayload {
+                invite_op_id: invite.op_id,
+                attribute_proof: None,
+            },
+            invite.lamport + 1,
+            at,
+            invitee,
+        )
+    }
+
+    #[test]
+    fn test_over_quota_invites_converge_in_any_order() {
+        let (gid, owner, ops) = group();
+        let owner_device = DeviceID::from_pubkey(&owner.0);
+        let base = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        // Each invite is followed by its invitee's accept
+        let pairs: Vec<[OpEnvelope; 2]> = (0..MAX_INVITES_PER_EPOCH as u64 + 3)
+            .map(|i| {
+                let invitee = crate::crypto::signing::generate_keypair();
+                let invite = invite(gid, &owner, &invitee, 2 + i, T0 + i);
+                let accept = accept(gid, &invitee, &invite, T0 + 100 + i);
+                [invite, accept]
+            })
+            .collect();
+
+        let apply_all = |order: &mut dyn Iterator<Item = &[OpEnvelope; 2]>| {
+            let mut state = base.clone();
+            for pair in order {
+                for op in pair {
+                    assert!(state.apply_op(op).unwrap());
+                }
+            }
+            state
+        };
+        let forward = apply_all(&mut pairs.iter());
+        // Highest invites first, so every low one evicts an accepted member
+        let reverse = apply_all(&mut pairs.iter().rev());
+        let (even, odd): (Vec<_>, Vec<_>) = pairs.iter().enumerate().partition(|(i, _)| i % 2 == 0);
+        let shuffled = apply_all(&mut odd.into_iter().chain(even).map(|(_, pair)| pair));
+
+        for other in [&reverse, &shuffled] {
+            assert_eq!(other.state_hash(), forward.state_hash());
+            assert!(other.invites.records().eq(forward.invites.records()));
+            assert_eq!(other.invites.remaining(&owner_device, T0), 0);
+        }
+        // The owner plus the invitees of the lowest invites
+        assert_eq!(
+            forward.membership.active_member_count(),
+            MAX_INVITES_PER_EPOCH as usize + 1
+        );
+        assert_eq!(
+            forward.invites.records().count(),
+            MAX_INVITES_PER_EPOCH as usize
+        );
+        for [invite, _] in &pairs[MAX_INVITES_PER_EPOCH as usize..] {
+            assert!(reverse.invites.is_excluded(&invite.op_id));
+            let invitee = invite
+                .decode_payload::<MemberInvitePayload>()
+                .unwrap()
+                .invited_device_id;
+            assert!(!reverse.membership.members().contains_key(&invitee));
+        }
+    }
+
+    #[test]
+    fn test_audit_trail_statuses() {
+        let (gid, owner, ops) = group();
+        let mut state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let owner_device = DeviceID::from_pubkey(&owner.0);
+        let alice = crate::crypto::signing::generate_keypair();
+        let bob = crate::crypto::signing::generate_keypair();
+        let carol = crate::crypto::signing::generate_keypair();
+
+        let invite_alice = invite(gid, &owner, &alice, 2, T0);
+        let invite_bob = invite(gid, &owner, &bob, 3, T0 + 1);
+        let invite_carol = invite(gid, &owner, &carol, 4, T0 + 2);
+        for invite in [&invite_alice, &invite_bob, &invite_carol] {
+            state.apply_op(invite).unwrap();
+        }
+        state
+            .apply_op(&op(
+                gid,
+                OpType::MemberAccept,
+                &MemberAcceptPayload {
+                    invite_op_id: invite_alice.op_id,
+                    attribute_proof: None,
+                },
+                5,
+                T0 + 10,
+                &alice,
+            ))
+            .unwrap();
+        let remove_bob = op(
+            gid,
+            OpType::MemberRemove,
+            &MemberRemovePayload {
+                target_device_id: DeviceID::from_pubkey(&bob.0),
+                reason: RemoveReason::Kick,
+            },
+            6,
+            T0 + 20,
+            &owner,
+        );
+        state.apply_op(&remove_bob).unwrap();
+
+        let log = &state.invites;
+        let status = |invite: &OpEnvelope, now| log.get(&invite.op_id).unwrap().status(now);
+        let later = T0 + INVITE_TTL_MS;
+        assert_eq!(
+            status(&invite_alice, later),
+            InviteStatus::Accepted { at_ms: T0 + 10 }
+        );
+        assert_eq!(
+            status(&invite_bob, T0),
+            InviteStatus::Revoked {
+                by: remove_bob.op_id
+            }
+        );
+        assert_eq!(status(&invite_carol, T0 + 3), InviteStatus::Pending);
+        assert_eq!(status(&invite_carol, later + 2), InviteStatus::Expired);
+
+        assert_eq!(log.by_inviter(&owner_device).count(), 3);
+        let carol_device = DeviceID::from_pubkey(&carol.0);
+        let received: Vec<_> = log.for_invitee(&carol_device).collect();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].inviter, owner_device);
+    }
+}
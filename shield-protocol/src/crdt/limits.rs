@@ -31,6 +31,43 @@ pub const MAX_CONCURRENT_SYNCS: usize = 2;
 /// Max ops per sync chunk.
 pub const MAX_OPS_PER_CHUNK: usize = 256;
 
+/// Max op ids in one receipt acknowledgment.
+pub const MAX_OPS_PER_ACK: usize = 256;
+
+/// Max losing concurrent edits remembered per message.
+pub const MAX_CONCURRENT_EDITS: usize = 8;
+
+/// Highest lamport an op may carry. Far above any honest group's history,
+/// and far enough below `u64::MAX` that no op can exhaust a member's clock.
+pub const MAX_LAMPORT: u64 = 1 << 48;
+
+/// Length of one anonymous-posting rate-limit epoch.
+pub const ANON_POST_EPOCH_MS: u64 = 60 * 60 * 1000; // 1 hour
+
+/// Max anonymous posts per link tag (i.e. per hidden member) per epoch.
+pub const MAX_ANON_POSTS_PER_EPOCH: u32 = 10;
+
+/// How far ahead of the receiver's clock a quota-charged op may be dated.
+pub const MAX_OP_CLOCK_SKEW_MS: u64 = 5 * 60 * 1000; // 5 minutes
+
+/// How far behind the receiver's clock a quota-charged op may be dated.
+/// Quota epochs come from author-chosen timestamps; this window caps how many
+/// epochs one author can charge at once, so a burst is bounded by
+/// `MAX_QUOTA_OP_AGE_MS / epoch + 2` quotas and the sustained rate by one.
+pub const MAX_QUOTA_OP_AGE_MS: u64 = 24 * 60 * 60 * 1000; // 24 hours
+
+/// Max ring size for an anonymous post (bounds proof size and verify cost).
+pub const MAX_ANON_RING_SIZE: usize = 256;
+
+/// Length of one invite-quota epoch.
+pub const INVITE_EPOCH_MS: u64 = 60 * 60 * 1000; // 1 hour
+
+/// Max `MemberInvite` ops per inviter per epoch.
+pub const MAX_INVITES_PER_EPOCH: u32 = 50;
+
+/// Age after which an unaccepted invite shows as expired in the invite log.
+pub const INVITE_TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000; // 7 days
+
 /// Op limit status for a group.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpLimitStatus {
@@ -53,6 +90,13 @@ pub fn check_op_limits(op_count: usize) -> OpLimitStatus {
     }
 }
 
+/// Whether a quota-charged op dated `timestamp_ms` is plausible at the
+/// receiver's `now_ms`.
+pub fn timestamp_plausible(timestamp_ms: u64, now_ms: u64) -> bool {
+    timestamp_ms <= now_ms.saturating_add(MAX_OP_CLOCK_SKEW_MS)
+        && now_ms.saturating_sub(timestamp_ms) <= MAX_QUOTA_OP_AGE_MS
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
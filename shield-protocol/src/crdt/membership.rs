@@ -6,13 +6,22 @@
 ///   kicked members.
 /// - Role changes use LWW (Last-Writer-Wins) by lamport, tie-break by OpID.
 /// - `rekey_required` flag set on all active members after a Kick (rotation deferred to v2).
+/// - OwnerTransfer promotes the new owner and demotes the author, both via role LWW.
+///
+/// Entries are shared copy-on-write (`Arc` map of `Arc` entries), so cloning
+/// a state — e.g. to snapshot it before a speculative apply — costs one
+/// refcount bump, and a later write copies only the map spine and the entries
+/// it touches. Large groups can also drop the per-member GroupSecret with
+/// `set_lazy_secrets`; it is then decoded on demand from the op that added
+/// the member.
 use std::collections::BTreeMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 use crate::crdt::ids::{DeviceID, OpID};
 use crate::crdt::ops::{
     GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, MemberRemovePayload, OpEnvelope,
-    OpType, RemoveReason, Role, RoleSetPayload,
+    OpType, OwnerTransferPayload, RemoveReason, Role, RoleSetPayload,
 };
 
 // ---------------------------------------------------------------------------
@@ -57,8 +66,17 @@ pub enum MembershipError {
     #[error("Target is not an active member")]
     TargetNotActive,
 
+    #[error("New owner DeviceID does not match pubkey")]
+    NewOwnerMismatch,
+
+    #[error("Owner cannot transfer ownership to themselves")]
+    TransferToSelf,
+
     #[error("Payload decode error: {0}")]
     PayloadDecode(String),
+
+    #[error("Op is not the one that added this member")]
+    SecretOpMismatch,
 }
 
 // ---------------------------------------------------------------------------
@@ -82,21 +100,48 @@ pub struct MemberEntry {
     pub rekey_required: bool,
     /// GroupSecret from the MemberInvite (or GroupCreate) payload.
     /// Kotlin extracts this to store in the Group entity when processing an invite.
-    pub encrypted_group_secret: Vec<u8>,
+    /// `None` when the state does not retain secrets (`set_lazy_secrets`);
+    /// use `group_secret_from` with the `invited_by` op instead.
+    pub encrypted_group_secret: Option<Vec<u8>>,
     /// Lamport of the op that last set the role (for LWW).
     role_lamport: u64,
     /// OpID of the op that last set the role (for LWW tie-break).
     role_op: OpID,
 }
 
+impl MemberEntry {
+    /// Decode this member's GroupSecret from the op that added it
+    /// (`invited_by`: a GroupCreate, MemberInvite or OwnerTransfer).
+    pub fn group_secret_from(&self, op: &OpEnvelope) -> Result<Vec<u8>, MembershipError> {
+        if op.op_id != self.invited_by {
+            return Err(MembershipError::SecretOpMismatch);
+        }
+        let decoded = match op.op_type {
+            OpType::GroupCreate => op
+                .decode_payload::<GroupCreatePayload>()
+                .map(|p| p.encrypted_group_secret),
+            OpType::MemberInvite => op
+                .decode_payload::<MemberInvitePayload>()
+                .map(|p| p.encrypted_group_secret),
+            OpType::OwnerTransfer => op
+                .decode_payload::<OwnerTransferPayload>()
+                .map(|p| p.encrypted_group_secret),
+            _ => return Err(MembershipError::SecretOpMismatch),
+        };
+        decoded.map_err(|e| MembershipError::PayloadDecode(e.to_string()))
+    }
+}
+
 // ---------------------------------------------------------------------------
 // MembershipState
 // ---------------------------------------------------------------------------
 
 #[derive(Clone, Debug)]
 pub struct MembershipState {
-    members: BTreeMap<DeviceID, MemberEntry>,
+    members: Arc<BTreeMap<DeviceID, Arc<MemberEntry>>>,
     created: bool,
+    /// Drop GroupSecret bytes at apply time instead of keeping one per entry.
+    lazy_secrets: bool,
 }
 
 impl Default for MembershipState {
@@ -108,18 +153,40 @@ impl Default for MembershipState {
 impl MembershipState {
     pub fn new() -> Self {
         MembershipState {
-            members: BTreeMap::new(),
+            members: Arc::new(BTreeMap::new()),
             created: false,
+            lazy_secrets: false,
         }
     }
 
+    /// Stop retaining `encrypted_group_secret` on entries added from now on.
+    /// Set it before applying ops; it does not affect the state hash, so
+    /// peers may differ in this setting.
+    pub fn set_lazy_secrets(&mut self, lazy: bool) {
+        self.lazy_secrets = lazy;
+    }
+
+    fn retain_secret(&self, secret: Vec<u8>) -> Option<Vec<u8>> {
+        (!self.lazy_secrets).then_some(secret)
+    }
+
+    fn entry_mut(&mut self, device_id: &DeviceID) -> Option<&mut MemberEntry> {
+        Arc::make_mut(&mut self.members)
+            .get_mut(device_id)
+            .map(Arc::make_mut)
+    }
+
+    fn insert(&mut self, entry: MemberEntry) {
+        Arc::make_mut(&mut self.members).insert(entry.device_id, Arc::new(entry));
+    }
+
     /// Whether GroupCreate has been applied.
     pub fn is_created(&self) -> bool {
         self.created
     }
 
     /// Read-only access to the membership map.
-    pub fn members(&self) -> &BTreeMap<DeviceID, MemberEntry> {
+    pub fn members(&self) -> &BTreeMap<DeviceID, Arc<MemberEntry>> {
         &self.members
     }
 
@@ -159,12 +226,12 @@ impl MembershipState {
             removed: false,
             remove_op: None,
             rekey_required: false,
-            encrypted_group_secret: payload.encrypted_group_secret,
+            encrypted_group_secret: self.retain_secret(payload.encrypted_group_secret),
             role_lamport: op.lamport,
             role_op: op.op_id,
         };
 
-        self.members.insert(device_id, entry);
+        self.insert(entry);
         self.created = true;
         Ok(())
     }
@@ -206,15 +273,33 @@ impl MembershipState {
             removed: false,
             remove_op: None,
             rekey_required: false,
-            encrypted_group_secret: payload.encrypted_group_secret,
+            encrypted_group_secret: self.retain_secret(payload.encrypted_group_secret),
             role_lamport: op.lamport,
             role_op: op.op_id,
         };
 
-        self.members.insert(payload.invited_device_id, entry);
+        self.insert(entry);
         Ok(())
     }
 
+    /// Put back the entry `invite_op` replaced, if the invitee's entry still
+    /// comes from it (used when the invite quota evicts an invite).
+    pub(crate) fn restore_entry(
+        &mut self,
+        invitee: &DeviceID,
+        invite_op: &OpID,
+        previous: Option<Arc<MemberEntry>>,
+    ) {
+        if self.members.get(invitee).map(|m| m.invited_by) != Some(*invite_op) {
+            return;
+        }
+        let members = Arc::make_mut(&mut self.members);
+        match previous {
+            Some(entry) => members.insert(*invitee, entry),
+            None => members.remove(invitee),
+        };
+    }
+
     /// Apply a MemberAccept op. Sets accepted=true for the accepting device.
     pub fn apply_member_accept(&mut self, op: &OpEnvelope) -> Result<(), MembershipError> {
         let payload: MemberAcceptPayload = op
@@ -224,8 +309,7 @@ impl MembershipState {
         let author_device = DeviceID::from_pubkey(&op.author_pubkey);
 
         let entry = self
-            .members
-            .get_mut(&author_device)
+            .entry_mut(&author_device)
             .ok_or(MembershipError::NoPendingInvite)?;
 
         // Verify accept references the correct invite
@@ -283,7 +367,7 @@ impl MembershipState {
                 let target_device = payload.target_device_id;
 
                 // Mark target as removed
-                let target_mut = self.members.get_mut(&target_device).unwrap();
+                let target_mut = self.entry_mut(&target_device).unwrap();
                 target_mut.removed = true;
                 target_mut.remove_op = Some(op.op_id);
 
@@ -296,7 +380,7 @@ impl MembershipState {
                     .collect();
 
                 for did in active_devices {
-                    if let Some(m) = self.members.get_mut(&did) {
+                    if let Some(m) = self.entry_mut(&did) {
                         m.rekey_required = true;
                     }
                 }
@@ -306,7 +390,7 @@ impl MembershipState {
                     return Err(MembershipError::LeaveAuthorMismatch);
                 }
 
-                let target_mut = self.members.get_mut(&payload.target_device_id).unwrap();
+                let target_mut = self.entry_mut(&payload.target_device_id).unwrap();
                 target_mut.removed = true;
                 target_mut.remove_op = Some(op.op_id);
             }
@@ -341,7 +425,7 @@ impl MembershipState {
         }
 
         // Apply the update
-        let target = self.members.get_mut(&payload.target_device_id).unwrap();
+        let target = self.entry_mut(&payload.target_device_id).unwrap();
         target.role = payload.new_role;
         target.role_lamport = op.lamport;
         target.role_op = op.op_id;
@@ -349,6 +433,69 @@ impl MembershipState {
         Ok(())
     }
 
+    /// Apply an OwnerTransfer op.
+    ///
+    /// The new owner is added as an auto-accepted Owner (or promoted, if already
+    /// active) and the author is demoted to Admin. Both role writes follow the
+    /// same LWW rule as RoleSet, so concurrent transfers converge. A transfer to
+    /// a removed device only takes effect if it supersedes the remove.
+    pub fn apply_owner_transfer(&mut self, op: &OpEnvelope) -> Result<(), MembershipError> {
+        let payload: OwnerTransferPayload = op
+            .decode_payload()
+            .map_err(|e| MembershipError::PayloadDecode(e.to_string()))?;
+
+        if DeviceID::from_pubkey(&payload.new_owner_pubkey) != payload.new_owner_device_id {
+            return Err(MembershipError::NewOwnerMismatch);
+        }
+        let author_device = DeviceID::from_pubkey(&op.author_pubkey);
+        if author_device == payload.new_owner_device_id {
+            return Err(MembershipError::TransferToSelf);
+        }
+
+        match self.entry_mut(&payload.new_owner_device_id) {
+            Some(entry) if entry.accepted && !entry.removed => {
+                let dominated = op.lamport < entry.role_lamport
+                    || (op.lamport == entry.role_lamport && op.op_id < entry.role_op);
+                if !dominated {
+                    entry.role = Role::Owner;
+                    entry.role_lamport = op.lamport;
+                    entry.role_op = op.op_id;
+                }
+            }
+            Some(entry) if entry.removed && entry.remove_op.is_some_and(|r| op.op_id <= r) => {
+                return Ok(()); // stale transfer cannot undo a remove
+            }
+            _ => {
+                let entry = MemberEntry {
+                    device_id: payload.new_owner_device_id,
+                    pubkey: payload.new_owner_pubkey,
+                    role: Role::Owner,
+                    invited_by: op.op_id,
+                    accepted: true,
+                    removed: false,
+                    remove_op: None,
+                    rekey_required: false,
+                    encrypted_group_secret: self.retain_secret(payload.encrypted_group_secret),
+                    role_lamport: op.lamport,
+                    role_op: op.op_id,
+                };
+                self.insert(entry);
+            }
+        }
+
+        if let Some(author) = self.entry_mut(&author_device) {
+            let dominated = op.lamport < author.role_lamport
+                || (op.lamport == author.role_lamport && op.op_id < author.role_op);
+            if !dominated {
+                author.role = Role::Admin;
+                author.role_lamport = op.lamport;
+                author.role_op = op.op_id;
+            }
+        }
+
+        Ok(())
+    }
+
     // -----------------------------------------------------------------------
     // Query functions
     // -----------------------------------------------------------------------
@@ -376,8 +523,18 @@ impl MembershipState {
                     member.role == Role::Owner || member.role == Role::Admin
                 }
 
+                OpType::OwnerTransfer => member.role == Role::Owner,
+
+                OpType::AnonKeyRegister => member.role != Role::ReadOnly,
+
+                // Read-only members still receive and read messages
+                OpType::ReceiptSet => true,
+
                 OpType::GroupCreate => false, // only valid as the very first op
                 OpType::MemberAccept => true,
+
+                // Authorized by ring proof, never by a device identity
+                OpType::AnonMsgAdd => false,
             },
         }
     }
@@ -386,6 +543,7 @@ impl MembershipState {
     pub fn get_active_member(&self, device_id: &DeviceID) -> Option<&MemberEntry> {
         self.members
             .get(device_id)
+            .map(Arc::as_ref)
             .filter(|m| m.accepted && !m.removed)
     }
 
@@ -475,7 +633,10 @@ mod tests {
         lamport: u64,
         nonce: u64,
     ) -> OpEnvelope {
-        let payload = MemberAcceptPayload { invite_op_id };
+        let payload = MemberAcceptPayload {
+            invite_op_id,
+            attribute_proof: None,
+        };
         OpEnvelope::create_signed(
             gid,
             OpType::MemberAccept,
@@ -845,22 +1006,19 @@ mod tests {
         let owner_op_id = state.members().get(&owner_device).unwrap().invited_by;
 
         // Insert Alice as active Member for auth testing
-        state.members.insert(
-            alice_device,
-            MemberEntry {
-                device_id: alice_device,
-                pubkey: alice_pub,
-                role: Role::Member,
-                invited_by: owner_op_id,
-                accepted: true,
-                removed: false,
-                remove_op: None,
-                rekey_required: false,
-                encrypted_group_secret: vec![],
-                role_lamport: 2,
-                role_op: owner_op_id,
-            },
-        );
+        state.insert(MemberEntry {
+            device_id: alice_device,
+            pubkey: alice_pub,
+            role: Role::Member,
+            invited_by: owner_op_id,
+            accepted: true,
+            removed: false,
+            remove_op: None,
+            rekey_required: false,
+            encrypted_group_secret: None,
+            role_lamport: 2,
+            role_op: owner_op_id,
+        });
 
         assert!(state.can_author_op(&alice_device, &OpType::MsgAdd));
         assert!(state.can_author_op(&alice_device, &OpType::ReactionSet));
@@ -881,22 +1039,19 @@ mod tests {
         let owner_device = DeviceID::from_pubkey(&owner_pub);
         let owner_op_id = state.members().get(&owner_device).unwrap().invited_by;
 
-        state.members.insert(
-            ro_device,
-            MemberEntry {
-                device_id: ro_device,
-                pubkey: ro_pub,
-                role: Role::ReadOnly,
-                invited_by: owner_op_id,
-                accepted: true,
-                removed: false,
-                remove_op: None,
-                rekey_required: false,
-                encrypted_group_secret: vec![],
-                role_lamport: 2,
-                role_op: owner_op_id,
-            },
-        );
+        state.insert(MemberEntry {
+            device_id: ro_device,
+            pubkey: ro_pub,
+            role: Role::ReadOnly,
+            invited_by: owner_op_id,
+            accepted: true,
+            removed: false,
+            remove_op: None,
+            rekey_required: false,
+            encrypted_group_secret: None,
+            role_lamport: 2,
+            role_op: owner_op_id,
+        });
 
         assert!(!state.can_author_op(&ro_device, &OpType::MsgAdd));
         assert!(!state.can_author_op(&ro_device, &OpType::MsgEdit));
@@ -915,22 +1070,19 @@ mod tests {
         let owner_device = DeviceID::from_pubkey(&owner_pub);
         let owner_op_id = state.members().get(&owner_device).unwrap().invited_by;
 
-        state.members.insert(
-            admin_device,
-            MemberEntry {
-                device_id: admin_device,
-                pubkey: admin_pub,
-                role: Role::Admin,
-                invited_by: owner_op_id,
-                accepted: true,
-                removed: false,
-                remove_op: None,
-                rekey_required: false,
-                encrypted_group_secret: vec![],
-                role_lamport: 2,
-                role_op: owner_op_id,
-            },
-        );
+        state.insert(MemberEntry {
+            device_id: admin_device,
+            pubkey: admin_pub,
+            role: Role::Admin,
+            invited_by: owner_op_id,
+            accepted: true,
+            removed: false,
+            remove_op: None,
+            rekey_required: false,
+            encrypted_group_secret: None,
+            role_lamport: 2,
+            role_op: owner_op_id,
+        });
 
         assert!(state.can_author_op(&admin_device, &OpType::MemberInvite));
         assert!(state.can_author_op(&admin_device, &OpType::MemberRemove));
@@ -1412,4 +1564,56 @@ mod tests {
             .unwrap();
         assert!(state.is_author_active_for_render(&alice_device));
     }
+
+    #[test]
+    fn test_lazy_secrets_decoded_from_adding_op() {
+        let mut state = MembershipState::new();
+        state.set_lazy_secrets(true);
+        let (create_op, gid, owner_pub, owner_priv) = make_group_create();
+        state.apply_group_create(&create_op).unwrap();
+        let (alice_pub, _) = keypair();
+        let invite = make_invite(gid, owner_pub, &owner_priv, alice_pub, 2, 200, Role::Member);
+        state.apply_member_invite(&invite).unwrap();
+
+        let alice = &state.members()[&DeviceID::from_pubkey(&alice_pub)];
+        let owner = &state.members()[&DeviceID::from_pubkey(&owner_pub)];
+        assert!(alice.encrypted_group_secret.is_none());
+        assert_eq!(alice.group_secret_from(&invite).unwrap(), vec![10, 20, 30]);
+        assert_eq!(owner.group_secret_from(&create_op).unwrap(), vec![1, 2, 3]);
+        assert!(matches!(
+            alice.group_secret_from(&create_op),
+            Err(MembershipError::SecretOpMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_clone_shares_untouched_entries() {
+        let mut state = MembershipState::new();
+        let (create_op, gid, owner_pub, owner_priv) = make_group_create();
+        state.apply_group_create(&create_op).unwrap();
+        let (alice_pub, alice_priv) = keypair();
+        let alice_device = DeviceID::from_pubkey(&alice_pub);
+        let owner_device = DeviceID::from_pubkey(&owner_pub);
+        let invite = make_invite(gid, owner_pub, &owner_priv, alice_pub, 2, 200, Role::Member);
+        state.apply_member_invite(&invite).unwrap();
+
+        let snapshot = state.clone();
+        state
+            .apply_member_accept(&make_accept(
+                gid,
+                alice_pub,
+                &alice_priv,
+                invite.op_id,
+                3,
+                300,
+            ))
+            .unwrap();
+
+        assert!(!snapshot.members()[&alice_device].accepted);
+        assert!(state.members()[&alice_device].accepted);
+        assert!(Arc::ptr_eq(
+            &snapshot.members()[&owner_device],
+            &state.members()[&owner_device]
+        ));
+    }
 }
@@ -4,14 +4,26 @@
 /// (Last-Writer-Wins) semantics by lamport with OpID tie-break. Deletes are
 /// permanent tombstones — once deleted, edits are silently ignored.
 ///
+/// An edit that loses LWW to one made without seeing it (per the edits'
+/// `base` links) is kept in `concurrent_edits`, so the UI can flag the
+/// message and offer the losing text back. Log compaction drops losing
+/// edits, and with them this history.
+///
 /// Reactions are a per-(device, emoji) map with boolean present/absent state.
-use std::collections::BTreeMap;
+///
+/// Receipts are a per-reader max-register (`Delivered` < `Read`), so counters
+/// converge regardless of arrival order. Receipts for messages not yet seen
+/// are held and merged when the message arrives; receipts are kept on
+/// tombstoned messages too, so a delete never makes replicas diverge.
+use std::collections::{BTreeMap, BTreeSet};
 use thiserror::Error;
 
 use crate::crdt::ids::{DeviceID, OpID};
+use crate::crdt::limits::MAX_CONCURRENT_EDITS;
 use crate::crdt::membership::MembershipState;
 use crate::crdt::ops::{
-    MsgAddPayload, MsgDeletePayload, MsgEditPayload, OpEnvelope, ReactionSetPayload, Role,
+    AnonMsgAddPayload, MsgAddPayload, MsgDeletePayload, MsgEditPayload, OpEnvelope,
+    ReactionSetPayload, ReceiptSetPayload, ReceiptStatus, Role,
 };
 
 // ---------------------------------------------------------------------------
@@ -61,6 +73,101 @@ pub struct MessageEntry {
     pub last_edit_op: Option<OpID>,
     /// Reactions: (reactor DeviceID, emoji string) → present.
     pub reactions: BTreeMap<(DeviceID, String), bool>,
+    /// Posted via `AnonMsgAdd` — `author` is a one-time key, not a member.
+    pub anonymous: bool,
+    /// Receipts: reader DeviceID → highest status seen.
+    pub receipts: BTreeMap<DeviceID, ReceiptStatus>,
+    /// Edits that lost LWW to an edit not built on them, oldest first.
+    pub concurrent_edits: Vec<ConcurrentEdit>,
+    /// Edit op → the version it was made on, for edits that say.
+    edit_bases: BTreeMap<OpID, OpID>,
+}
+
+/// A valid edit that lost LWW to a concurrent one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConcurrentEdit {
+    pub op_id: OpID,
+    pub ciphertext: Vec<u8>,
+    pub nonce: [u8; 24],
+}
+
+/// Aggregated receipt counts for one message (author excluded).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReceiptCounts {
+    /// Readers with at least `Delivered` (includes those who read it).
+    pub delivered: usize,
+    pub read: usize,
+}
+
+impl MessageEntry {
+    /// The version currently shown; new edits name it as their `base`.
+    pub fn current_version(&self) -> OpID {
+        self.last_edit_op.unwrap_or(self.create_op)
+    }
+
+    /// Record `loser` (if any), then forget every remembered edit the
+    /// current version was built on — it was superseded, not lost.
+    fn settle_concurrent_edits(&mut self, loser: Option<ConcurrentEdit>) {
+        if let Some(loser) = loser {
+            if !self.concurrent_edits.iter().any(|e| e.op_id == loser.op_id) {
+                self.concurrent_edits.push(loser);
+            }
+        }
+        let mut ancestors = BTreeSet::new();
+        let mut version = self.last_edit_op;
+        while let Some(op_id) = version {
+            if !ancestors.insert(op_id) {
+                break;
+            }
+            version = self.edit_bases.get(&op_id).copied();
+        }
+        self.concurrent_edits
+            .retain(|e| !ancestors.contains(&e.op_id));
+        self.concurrent_edits.sort_by_key(|e| e.op_id);
+        let excess = self
+            .concurrent_edits
+            .len()
+            .saturating_sub(MAX_CONCURRENT_EDITS);
+        self.concurrent_edits.drain(..excess);
+    }
+
+    /// Edit op → the version it was made on, for edits that named one.
+    pub(crate) fn edit_bases(&self) -> &BTreeMap<OpID, OpID> {
+        &self.edit_bases
+    }
+
+    pub fn receipt_counts(&self) -> ReceiptCounts {
+        let mut counts = ReceiptCounts::default();
+        for (reader, status) in &self.receipts {
+            if *reader == self.author {
+                continue;
+            }
+            counts.delivered += 1;
+            if *status == ReceiptStatus::Read {
+                counts.read += 1;
+            }
+        }
+        counts
+    }
+}
+
+fn merge_receipt(
+    receipts: &mut BTreeMap<DeviceID, ReceiptStatus>,
+    reader: DeviceID,
+    status: ReceiptStatus,
+) {
+    receipts
+        .entry(reader)
+        .and_modify(|s| *s = (*s).max(status))
+        .or_insert(status);
+}
+
+/// Whether `author` is an active Owner or Admin, who may delete any message.
+fn is_privileged(membership: &MembershipState, author: &DeviceID) -> bool {
+    membership
+        .get_active_member(author)
+        .map(|m| m.role == Role::Owner || m.role == Role::Admin)
+        .unwrap_or(false)
 }
 
 // ---------------------------------------------------------------------------
@@ -70,6 +177,12 @@ pub struct MessageEntry {
 #[derive(Clone, Debug)]
 pub struct MessageState {
     pub(crate) messages: BTreeMap<[u8; 32], MessageEntry>,
+    /// Create OpID → msg_id, i.e. messages in conversation order.
+    by_create: BTreeMap<OpID, [u8; 32]>,
+    /// Receipts for msg_ids not seen yet, merged on MsgAdd/AnonMsgAdd.
+    pending_receipts: BTreeMap<[u8; 32], BTreeMap<DeviceID, ReceiptStatus>>,
+    /// Create ops skipped because another op already holds their msg_id.
+    id_collisions: Vec<([u8; 32], OpID)>,
 }
 
 impl Default for MessageState {
@@ -82,6 +195,9 @@ impl MessageState {
     pub fn new() -> Self {
         MessageState {
             messages: BTreeMap::new(),
+            by_create: BTreeMap::new(),
+            pending_receipts: BTreeMap::new(),
+            id_collisions: Vec::new(),
         }
     }
 
@@ -90,6 +206,43 @@ impl MessageState {
         &self.messages
     }
 
+    /// Create ops that reused a msg_id already taken by a different op, as
+    /// (msg_id, skipped op), in replay order. The first op keeps the id, so
+    /// a non-empty list means a message was shadowed: a buggy client or a
+    /// member forging someone else's id.
+    pub fn id_collisions(&self) -> &[([u8; 32], OpID)] {
+        &self.id_collisions
+    }
+
+    /// Receipts held for msg_ids that have not arrived yet.
+    pub(crate) fn pending_receipts(
+        &self,
+    ) -> &BTreeMap<[u8; 32], BTreeMap<DeviceID, ReceiptStatus>> {
+        &self.pending_receipts
+    }
+
+    /// Whether `msg_id` is still free for `op`. Redelivery of the op that
+    /// holds it is not a collision.
+    fn claim_msg_id(&mut self, msg_id: &[u8; 32], op: &OpEnvelope) -> bool {
+        let Some(existing) = self.messages.get(msg_id) else {
+            return true;
+        };
+        if existing.create_op != op.op_id {
+            log::warn!(
+                "Group msg_id collision on {}: op by {} skipped",
+                hex::encode(msg_id),
+                DeviceID::from_pubkey(&op.author_pubkey).to_hex()
+            );
+            self.id_collisions.push((*msg_id, op.op_id));
+        }
+        false
+    }
+
+    /// Messages in conversation order (by create OpID), for range queries.
+    pub(crate) fn by_create(&self) -> &BTreeMap<OpID, [u8; 32]> {
+        &self.by_create
+    }
+
     /// Get a message by its msg_id.
     pub fn get_message(&self, msg_id: &[u8; 32]) -> Option<&MessageEntry> {
         self.messages.get(msg_id)
@@ -114,7 +267,7 @@ impl MessageState {
             .map_err(|e| MessageError::PayloadDecode(e.to_string()))?;
 
         // Idempotent: skip if msg_id already exists
-        if self.messages.contains_key(&payload.msg_id) {
+        if !self.claim_msg_id(&payload.msg_id, op) {
             return Ok(());
         }
 
@@ -131,17 +284,82 @@ impl MessageState {
             last_edit_lamport: op.lamport,
             last_edit_op: None,
             reactions: BTreeMap::new(),
+            anonymous: false,
+            receipts: self
+                .pending_receipts
+                .remove(&payload.msg_id)
+                .unwrap_or_default(),
+            concurrent_edits: Vec::new(),
+            edit_bases: BTreeMap::new(),
         };
 
+        self.by_create.insert(op.op_id, payload.msg_id);
         self.messages.insert(payload.msg_id, entry);
         Ok(())
     }
 
+    /// Apply an already-validated AnonMsgAdd op (ring proof and rate limit are
+    /// checked by `AnonymousState::admit_post`).
+    ///
+    /// The entry's `author` is the op's one-time DeviceID, so the message can
+    /// never be edited; admins/owners can still delete it.
+    pub fn apply_anon_msg_add(
+        &mut self,
+        op: &OpEnvelope,
+        payload: AnonMsgAddPayload,
+    ) -> Result<(), MessageError> {
+        if !self.claim_msg_id(&payload.msg_id, op) {
+            return Ok(());
+        }
+
+        let entry = MessageEntry {
+            msg_id: payload.msg_id,
+            author: DeviceID::from_pubkey(&op.author_pubkey),
+            create_op: op.op_id,
+            ciphertext: payload.ciphertext,
+            nonce: payload.nonce,
+            timestamp_ms: op.timestamp_ms,
+            deleted: false,
+            last_edit_lamport: op.lamport,
+            last_edit_op: None,
+            reactions: BTreeMap::new(),
+            anonymous: true,
+            receipts: self
+                .pending_receipts
+                .remove(&payload.msg_id)
+                .unwrap_or_default(),
+            concurrent_edits: Vec::new(),
+            edit_bases: BTreeMap::new(),
+        };
+
+        self.by_create.insert(op.op_id, payload.msg_id);
+        self.messages.insert(payload.msg_id, entry);
+        Ok(())
+    }
+
+    /// Remove an anonymous message whose post lost its tag's budget to a
+    /// lower one, leaving the state it would have had if never admitted.
+    /// Receipts for it go back to waiting.
+    pub fn evict_anon_msg(&mut self, msg_id: &[u8; 32], create_op: &OpID) {
+        if self
+            .messages
+            .get(msg_id)
+            .is_some_and(|msg| msg.create_op == *create_op)
+        {
+            let msg = self.messages.remove(msg_id).expect("entry just read");
+            self.by_create.remove(create_op);
+            if !msg.receipts.is_empty() {
+                self.pending_receipts.insert(*msg_id, msg.receipts);
+            }
+        }
+    }
+
     /// Apply a MsgEdit op. LWW: only applies if this op supersedes the last edit.
     ///
     /// - Author must be the original message author.
     /// - Silently ignored if the message is deleted (tombstone is permanent).
-    /// - Silently ignored if this op is dominated by a newer edit.
+    /// - Silently ignored if this op is dominated by a newer edit, apart from
+    ///   being remembered as a concurrent edit.
     pub fn apply_msg_edit(&mut self, op: &OpEnvelope) -> Result<(), MessageError> {
         let payload: MsgEditPayload = op
             .decode_payload()
@@ -171,16 +389,35 @@ impl MessageState {
                     None => op.op_id <= msg.create_op,
                 });
 
-        if dominated {
-            return Ok(()); // silently ignore stale edit
+        let msg = self.messages.get_mut(&payload.msg_id).unwrap();
+        if let Some(base) = payload.base {
+            msg.edit_bases.insert(op.op_id, base);
         }
 
-        // Apply the edit
-        let msg = self.messages.get_mut(&payload.msg_id).unwrap();
-        msg.ciphertext = payload.new_ciphertext;
-        msg.nonce = payload.nonce;
-        msg.last_edit_lamport = op.lamport;
-        msg.last_edit_op = Some(op.op_id);
+        // Only edits that name their base can be told apart from sequential
+        // ones; older clients' edits are never reported as concurrent.
+        let loser = if dominated {
+            payload.base.map(|_| ConcurrentEdit {
+                op_id: op.op_id,
+                ciphertext: payload.new_ciphertext,
+                nonce: payload.nonce,
+            })
+        } else {
+            let replaced = msg
+                .last_edit_op
+                .filter(|prev| msg.edit_bases.contains_key(prev))
+                .map(|prev| ConcurrentEdit {
+                    op_id: prev,
+                    ciphertext: std::mem::take(&mut msg.ciphertext),
+                    nonce: msg.nonce,
+                });
+            msg.ciphertext = payload.new_ciphertext;
+            msg.nonce = payload.nonce;
+            msg.last_edit_lamport = op.lamport;
+            msg.last_edit_op = Some(op.op_id);
+            replaced
+        };
+        msg.settle_concurrent_edits(loser);
 
         Ok(())
     }
@@ -188,7 +425,7 @@ impl MessageState {
     /// Apply a MsgDelete op. Tombstones the message permanently.
     ///
     /// - Author must be original message author OR an admin/owner (via membership).
-    /// - Silently ignored if already deleted.
+    /// - An authorized repeat of a delete is a no-op.
     pub fn apply_msg_delete(
         &mut self,
         op: &OpEnvelope,
@@ -200,35 +437,42 @@ impl MessageState {
 
         let msg = self
             .messages
-            .get(&payload.msg_id)
+            .get_mut(&payload.msg_id)
             .ok_or_else(|| MessageError::MessageNotFound(hex::encode(payload.msg_id)))?;
 
-        // Already deleted — idempotent
-        if msg.deleted {
-            return Ok(());
-        }
-
-        let author = DeviceID::from_pubkey(&op.author_pubkey);
-
         // Authorization: original author can always delete their own messages.
-        // Admin/Owner can delete anyone's messages.
-        if author != msg.author {
-            let is_privileged = membership
-                .get_active_member(&author)
-                .map(|m| m.role == Role::Owner || m.role == Role::Admin)
-                .unwrap_or(false);
-
-            if !is_privileged {
-                return Err(MessageError::DeleteNotAuthorized);
-            }
+        // Admin/Owner can delete anyone's messages. Checked before the
+        // tombstone so a delete gets the same verdict whether or not another
+        // delete arrived first.
+        let author = DeviceID::from_pubkey(&op.author_pubkey);
+        if author != msg.author && !is_privileged(membership, &author) {
+            return Err(MessageError::DeleteNotAuthorized);
         }
 
-        let msg = self.messages.get_mut(&payload.msg_id).unwrap();
+        // Idempotent: a repeated delete leaves the tombstone as is
         msg.deleted = true;
 
         Ok(())
     }
 
+    /// Check a MsgDelete for an anonymous post this replica dropped (over its
+    /// tag's budget, or evicted by a lower post). There is nothing to
+    /// tombstone, and replicas that still hold the post drop it once the
+    /// lower post arrives, so an authorized delete is a no-op everywhere.
+    /// Anonymous posts are authored by one-time keys, so only an admin or
+    /// owner may delete them, as in [`apply_msg_delete`](Self::apply_msg_delete).
+    pub fn check_dropped_post_delete(
+        &self,
+        op: &OpEnvelope,
+        membership: &MembershipState,
+    ) -> Result<(), MessageError> {
+        if is_privileged(membership, &DeviceID::from_pubkey(&op.author_pubkey)) {
+            Ok(())
+        } else {
+            Err(MessageError::DeleteNotAuthorized)
+        }
+    }
+
     /// Apply a ReactionSet op. Upserts (author, emoji) → present.
     ///
     /// Silently ignored if the message doesn't exist or is deleted.
@@ -253,6 +497,25 @@ impl MessageState {
 
         Ok(())
     }
+
+    /// Apply a ReceiptSet op. Max-merges the reader's status.
+    ///
+    /// Unlike reactions, receipts for unknown messages are held until the
+    /// message arrives, and deleted messages still record them.
+    pub fn apply_receipt_set(&mut self, op: &OpEnvelope) -> Result<(), MessageError> {
+        let payload: ReceiptSetPayload = op
+            .decode_payload()
+            .map_err(|e| MessageError::PayloadDecode(e.to_string()))?;
+
+        let reader = DeviceID::from_pubkey(&op.author_pubkey);
+        let receipts = match self.messages.get_mut(&payload.msg_id) {
+            Some(msg) => &mut msg.receipts,
+            None => self.pending_receipts.entry(payload.msg_id).or_default(),
+        };
+        merge_receipt(receipts, reader, payload.status);
+
+        Ok(())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -328,6 +591,7 @@ mod tests {
 
         let accept_payload = MemberAcceptPayload {
             invite_op_id: invite_op.op_id,
+            attribute_proof: None,
         };
         let accept_op = OpEnvelope::create_signed(
             gid,
@@ -384,6 +648,7 @@ mod tests {
             msg_id,
             new_ciphertext,
             nonce: [0x22; 24],
+            base: None,
         };
         OpEnvelope::create_signed(
             gid,
@@ -445,6 +710,28 @@ mod tests {
         .unwrap()
     }
 
+    fn make_receipt(
+        gid: GroupID,
+        author_pub: [u8; 32],
+        author_priv: &[u8; 32],
+        msg_id: [u8; 32],
+        status: ReceiptStatus,
+        lamport: u64,
+        nonce: u64,
+    ) -> OpEnvelope {
+        let payload = ReceiptSetPayload { msg_id, status };
+        OpEnvelope::create_signed(
+            gid,
+            OpType::ReceiptSet,
+            &payload,
+            lamport,
+            nonce,
+            author_pub,
+            author_priv,
+        )
+        .unwrap()
+    }
+
     // -------------------------------------------------------------------
     // MsgAdd
     // -------------------------------------------------------------------
@@ -481,6 +768,26 @@ mod tests {
         assert_eq!(messages.message_count(), 1);
     }
 
+    #[test]
+    fn test_msg_add_id_collision_recorded() {
+        let (_membership, gid, owner_pub, owner_priv, alice_pub, alice_priv) =
+            setup_group_with_member();
+
+        let mut messages = MessageState::new();
+        let msg_id = [0x09; 32];
+        let first = make_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+        let forged = make_msg_add(gid, owner_pub, &owner_priv, msg_id, 5, 500);
+
+        messages.apply_msg_add(&first).unwrap();
+        messages.apply_msg_add(&first).unwrap();
+        assert!(messages.id_collisions().is_empty());
+
+        messages.apply_msg_add(&forged).unwrap();
+        assert_eq!(messages.id_collisions(), &[(msg_id, forged.op_id)]);
+        let msg = messages.get_message(&msg_id).unwrap();
+        assert_eq!(msg.author, DeviceID::from_pubkey(&alice_pub));
+    }
+
     // -------------------------------------------------------------------
     // MsgEdit — LWW
     // -------------------------------------------------------------------
@@ -565,6 +872,96 @@ mod tests {
         );
     }
 
+    fn make_msg_edit_on(
+        gid: GroupID,
+        author: &([u8; 32], [u8; 32]),
+        msg_id: [u8; 32],
+        byte: u8,
+        base: OpID,
+        lamport: u64,
+    ) -> OpEnvelope {
+        let payload = MsgEditPayload {
+            msg_id,
+            new_ciphertext: vec![byte],
+            nonce: [byte; 24],
+            base: Some(base),
+        };
+        OpEnvelope::create_signed(
+            gid,
+            OpType::MsgEdit,
+            &payload,
+            lamport,
+            byte as u64,
+            author.0,
+            &author.1,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_concurrent_edit_loser_kept_in_any_order() {
+        let (_membership, gid, _owner_pub, _owner_priv, alice_pub, alice_priv) =
+            setup_group_with_member();
+        let alice = (alice_pub, alice_priv);
+        let msg_id = [0x06; 32];
+        let add_op = make_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+
+        // Two devices edit the original without seeing each other.
+        let phone = make_msg_edit_on(gid, &alice, msg_id, 0xA1, add_op.op_id, 5);
+        let laptop = make_msg_edit_on(gid, &alice, msg_id, 0xB2, add_op.op_id, 6);
+
+        for order in [[&phone, &laptop], [&laptop, &phone]] {
+            let mut messages = MessageState::new();
+            messages.apply_msg_add(&add_op).unwrap();
+            for op in order {
+                messages.apply_msg_edit(op).unwrap();
+            }
+            let msg = messages.get_message(&msg_id).unwrap();
+            assert_eq!(msg.ciphertext, vec![0xB2]);
+            assert_eq!(
+                msg.concurrent_edits,
+                vec![ConcurrentEdit {
+                    op_id: phone.op_id,
+                    ciphertext: vec![0xA1],
+                    nonce: [0xA1; 24],
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequential_edits_not_reported_even_out_of_order() {
+        let (_membership, gid, _owner_pub, _owner_priv, alice_pub, alice_priv) =
+            setup_group_with_member();
+        let alice = (alice_pub, alice_priv);
+        let msg_id = [0x07; 32];
+        let add_op = make_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+        let first = make_msg_edit_on(gid, &alice, msg_id, 1, add_op.op_id, 5);
+        let second = make_msg_edit_on(gid, &alice, msg_id, 2, first.op_id, 6);
+        let third = make_msg_edit_on(gid, &alice, msg_id, 3, second.op_id, 7);
+
+        let mut messages = MessageState::new();
+        messages.apply_msg_add(&add_op).unwrap();
+        // The first edit arrives after the last one, before the middle one
+        // links them: briefly reported, then settled.
+        messages.apply_msg_edit(&third).unwrap();
+        messages.apply_msg_edit(&first).unwrap();
+        assert_eq!(
+            messages
+                .get_message(&msg_id)
+                .unwrap()
+                .concurrent_edits
+                .len(),
+            1
+        );
+        messages.apply_msg_edit(&second).unwrap();
+
+        let msg = messages.get_message(&msg_id).unwrap();
+        assert_eq!(msg.ciphertext, vec![3]);
+        assert!(msg.concurrent_edits.is_empty());
+        assert_eq!(msg.current_version(), third.op_id);
+    }
+
     #[test]
     fn test_msg_edit_wrong_author_rejected() {
         let (_membership, gid, owner_pub, owner_priv, alice_pub, alice_priv) =
@@ -836,4 +1233,110 @@ mod tests {
             Some(&true)
         );
     }
+
+    // -------------------------------------------------------------------
+    // Receipts
+    // -------------------------------------------------------------------
+
+    #[test]
+    fn test_receipt_counts_converge_in_any_order() {
+        let (_membership, gid, owner_pub, owner_priv, alice_pub, alice_priv) =
+            setup_group_with_member();
+
+        let msg_id = [0x0F; 32];
+        let add_op = make_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+        let ops = [
+            make_receipt(
+                gid,
+                owner_pub,
+                &owner_priv,
+                msg_id,
+                ReceiptStatus::Read,
+                6,
+                600,
+            ),
+            make_receipt(
+                gid,
+                owner_pub,
+                &owner_priv,
+                msg_id,
+                ReceiptStatus::Delivered,
+                5,
+                500,
+            ),
+            // Author's own receipt is not counted
+            make_receipt(
+                gid,
+                alice_pub,
+                &alice_priv,
+                msg_id,
+                ReceiptStatus::Read,
+                7,
+                700,
+            ),
+        ];
+
+        // Receipts before the message (held as pending), in reverse order
+        let mut early = MessageState::new();
+        for op in ops.iter().rev() {
+            early.apply_receipt_set(op).unwrap();
+        }
+        early.apply_msg_add(&add_op).unwrap();
+
+        // Message first, receipts in order
+        let mut late = MessageState::new();
+        late.apply_msg_add(&add_op).unwrap();
+        for op in &ops {
+            late.apply_receipt_set(op).unwrap();
+        }
+
+        let expected = ReceiptCounts {
+            delivered: 1,
+            read: 1,
+        };
+        assert_eq!(
+            early.get_message(&msg_id).unwrap().receipt_counts(),
+            expected
+        );
+        assert_eq!(
+            late.get_message(&msg_id).unwrap().receipt_counts(),
+            expected
+        );
+        assert_eq!(
+            early.get_message(&msg_id).unwrap().receipts,
+            late.get_message(&msg_id).unwrap().receipts
+        );
+    }
+
+    #[test]
+    fn test_receipt_kept_on_deleted_message() {
+        let (membership, gid, owner_pub, owner_priv, alice_pub, alice_priv) =
+            setup_group_with_member();
+
+        let mut messages = MessageState::new();
+        let msg_id = [0x10; 32];
+        let add_op = make_msg_add(gid, alice_pub, &alice_priv, msg_id, 4, 400);
+        messages.apply_msg_add(&add_op).unwrap();
+        let del_op = make_msg_delete(gid, alice_pub, &alice_priv, msg_id, 5, 500);
+        messages.apply_msg_delete(&del_op, &membership).unwrap();
+
+        let receipt = make_receipt(
+            gid,
+            owner_pub,
+            &owner_priv,
+            msg_id,
+            ReceiptStatus::Delivered,
+            6,
+            600,
+        );
+        messages.apply_receipt_set(&receipt).unwrap();
+        assert_eq!(
+            messages
+                .get_message(&msg_id)
+                .unwrap()
+                .receipt_counts()
+                .delivered,
+            1
+        );
+    }
 }
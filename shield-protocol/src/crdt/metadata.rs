@@ -6,6 +6,7 @@
 use std::collections::BTreeMap;
 use thiserror::Error;
 
+use crate::crdt::avatar::AvatarRef;
 use crate::crdt::ids::OpID;
 use crate::crdt::ops::{MetadataKey, MetadataSetPayload, OpEnvelope};
 
@@ -76,6 +77,31 @@ impl MetadataState {
             .and_then(|r| std::str::from_utf8(&r.value).ok())
     }
 
+    /// Current avatar reference, if one is set. Values that predate avatar
+    /// validation and do not decode read as no avatar.
+    pub fn avatar(&self) -> Option<AvatarRef> {
+        self.registers
+            .get(&MetadataKey::Avatar)
+            .filter(|r| !r.value.is_empty())
+            .and_then(|r| AvatarRef::from_bytes(&r.value).ok())
+    }
+
+    /// Whether anonymous posting (`AnonMsgAdd`) is enabled for the group.
+    pub fn anonymous_posting_enabled(&self) -> bool {
+        self.registers
+            .get(&MetadataKey::AnonymousPosting)
+            .map(|r| r.value == [1u8])
+            .unwrap_or(false)
+    }
+
+    /// Encoded join requirement, if the group gates admission on an attribute.
+    pub fn join_requirement(&self) -> Option<&[u8]> {
+        self.registers
+            .get(&MetadataKey::JoinRequirement)
+            .map(|r| r.value.as_slice())
+            .filter(|v| !v.is_empty())
+    }
+
     // -----------------------------------------------------------------------
     // Apply
     // -----------------------------------------------------------------------
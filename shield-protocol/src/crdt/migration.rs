@@ -0,0 +1,358 @@
+/// Group migration — hand a group to another account.
+///
+/// The current owner exports a `GroupTransferBundle`: the full op log plus a
+/// freshly signed `OwnerTransfer` op naming the new owner, the resulting
+/// `state_hash`, and an Ed25519 signature over all of it. The new account
+/// imports the bundle by replaying the log and checking the hash.
+///
+/// **Old members** need no special handling: the exporter also broadcasts the
+/// `OwnerTransfer` op (`GroupTransferBundle::transfer_op`) through normal sync.
+/// Every peer accepts it iff its author is an active Owner when the op is
+/// applied in `(lamport, op_id)` order, so all peers converge on the same
+/// owner set and the group keeps its `GroupID`.
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use thiserror::Error;
+
+use crate::crdt::apply::{ApplyError, GroupState};
+use crate::crdt::ids::{DeviceID, GroupID, OpID};
+use crate::crdt::ops::{OpEnvelope, OpError, OpType, OwnerTransferPayload, Role};
+
+const BUNDLE_DOMAIN: &[u8] = b"SL-GROUP-TRANSFER-V1";
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("Exporter is not an active owner of the group")]
+    NotOwner,
+
+    #[error("Invalid bundle signature")]
+    InvalidSignature,
+
+    #[error("Bundle does not contain its OwnerTransfer op")]
+    MissingTransferOp,
+
+    #[error("Replayed state hash does not match bundle snapshot")]
+    SnapshotMismatch,
+
+    #[error("Bundle transfers ownership to a different account")]
+    NewOwnerMismatch,
+
+    #[error("Apply error: {0}")]
+    Apply(#[from] ApplyError),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// GroupTransferBundle
+// ---------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GroupTransferBundle {
+    pub group_id: GroupID,
+    /// Complete op log, including the OwnerTransfer op.
+    pub ops: Vec<OpEnvelope>,
+    pub transfer_op_id: OpID,
+    /// `GroupState::state_hash` after replaying `ops`.
+    pub snapshot_hash: [u8; 32],
+    /// Ed25519 public key of the exporting owner.
+    pub exporter_pubkey: [u8; 32],
+    /// Ed25519 signature over `signable_hash()`.
+    #[serde(with = "BigArray")]
+    pub signature: [u8; 64],
+}
+
+impl GroupTransferBundle {
+    /// The OwnerTransfer op, for broadcasting to existing members.
+    pub fn transfer_op(&self) -> Option<&OpEnvelope> {
+        self.ops.iter().find(|op| op.op_id == self.transfer_op_id)
+    }
+
+    /// Serialize the bundle to bytes (for storage / out-of-band transfer).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    /// Deserialize a bundle from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    /// BLAKE3 over the bundle fields. Each op is committed via its own signature.
+    fn signable_hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(BUNDLE_DOMAIN);
+        hasher.update(self.group_id.as_bytes());
+        hasher.update(self.transfer_op_id.author.as_bytes());
+        hasher.update(&self.transfer_op_id.lamport.to_le_bytes());
+        hasher.update(&self.transfer_op_id.nonce.to_le_bytes());
+        hasher.update(&self.snapshot_hash);
+        hasher.update(&self.exporter_pubkey);
+        hasher.update(&(self.ops.len() as u64).to_le_bytes());
+        for op in &self.ops {
+            hasher.update(&op.signature);
+        }
+        *hasher.finalize().as_bytes()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Export / import
+// ---------------------------------------------------------------------------
+
+/// Export a group to a new owner account.
+///
+/// `ops` is the owner's full op log for `group_id`; `lamport` must be the
+/// owner's next lamport value. `encrypted_group_secret` is the GroupSecret
+/// encrypted to the new owner's X25519 key.
+pub fn export_group(
+    group_id: GroupID,
+    ops: &[OpEnvelope],
+    new_owner_pubkey: [u8; 32],
+    encrypted_group_secret: Vec<u8>,
+    lamport: u64,
+    nonce: u64,
+    owner_pubkey: [u8; 32],
+    owner_privkey: &[u8; 32],
+) -> Result<GroupTransferBundle, MigrationError> {
+    let mut state = GroupState::rebuild_from_ops(group_id, ops)?;
+
+    let owner_device = DeviceID::from_pubkey(&owner_pubkey);
+    match state.membership.get_active_member(&owner_device) {
+        Some(m) if m.role == Role::Owner => {}
+        _ => return Err(MigrationError::NotOwner),
+    }
+
+    let payload = OwnerTransferPayload {
+        new_owner_device_id: DeviceID::from_pubkey(&new_owner_pubkey),
+        new_owner_pubkey,
+        encrypted_group_secret,
+    };
+    let transfer = OpEnvelope::create_signed(
+        group_id,
+        OpType::OwnerTransfer,
+        &payload,
+        lamport,
+        nonce,
+        owner_pubkey,
+        owner_privkey,
+    )?;
+    state.apply_op(&transfer)?;
+
+    let mut all_ops = ops.to_vec();
+    let transfer_op_id = transfer.op_id;
+    all_ops.push(transfer);
+
+    let mut bundle = GroupTransferBundle {
+        group_id,
+        ops: all_ops,
+        transfer_op_id,
+        snapshot_hash: state.state_hash(),
+        exporter_pubkey: owner_pubkey,
+        signature: [0u8; 64],
+    };
+    bundle.signature = crate::crypto::signing::sign_data(&bundle.signable_hash(), owner_privkey)
+        .map_err(|e| OpError::SigningFailed(e.to_string()))?;
+
+    Ok(bundle)
+}
+
+/// Import a group on the new owner's account.
+///
+/// Verifies the bundle signature, replays the op log, checks the snapshot
+/// hash, and confirms `importer_pubkey` is the designated (and now active)
+/// owner. The importer's GroupSecret is in their `MemberEntry`.
+pub fn import_group(
+    bundle: &GroupTransferBundle,
+    importer_pubkey: &[u8; 32],
+) -> Result<GroupState, MigrationError> {
+    let valid = crate::crypto::signing::verify_signature(
+        &bundle.signable_hash(),
+        &bundle.signature,
+        &bundle.exporter_pubkey,
+    )
+    .unwrap_or(false);
+    if !valid {
+        return Err(MigrationError::InvalidSignature);
+    }
+
+    let transfer = bundle
+        .transfer_op()
+        .filter(|op| op.op_type == OpType::OwnerTransfer)
+        .ok_or(MigrationError::MissingTransferOp)?;
+    if transfer.author_pubkey != bundle.exporter_pubkey {
+        return Err(MigrationError::InvalidSignature);
+    }
+    let payload: OwnerTransferPayload = transfer.decode_payload()?;
+    if payload.new_owner_pubkey != *importer_pubkey {
+        return Err(MigrationError::NewOwnerMismatch);
+    }
+
+    let state = GroupState::rebuild_from_ops(bundle.group_id, &bundle.ops)?;
+    if state.state_hash() != bundle.snapshot_hash {
+        return Err(MigrationError::SnapshotMismatch);
+    }
+
+    let importer = DeviceID::from_pubkey(importer_pubkey);
+    match state.membership.get_active_member(&importer) {
+        Some(m) if m.role == Role::Owner => Ok(state),
+        // A later op in the log superseded the transfer.
+        _ => Err(MigrationError::NewOwnerMismatch),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{
+        GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, MsgAddPayload,
+    };
+
+    fn keypair() -> ([u8; 32], [u8; 32]) {
+        crate::crypto::signing::generate_keypair()
+    }
+
+    /// Owner creates a group, invites alice (accepted), posts one message.
+    /// Returns (ops, gid, owner keys, alice keys).
+    fn make_group() -> (
+        Vec<OpEnvelope>,
+        GroupID,
+        ([u8; 32], [u8; 32]),
+        ([u8; 32], [u8; 32]),
+    ) {
+        let owner = keypair();
+        let alice = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[0x4D; 32]);
+
+        let create = OpEnvelope::create_signed(
+            gid,
+            OpType::GroupCreate,
+            &GroupCreatePayload {
+                group_name: "Migrating".into(),
+                encrypted_group_secret: vec![1],
+            },
+            1,
+            100,
+            owner.0,
+            &owner.1,
+        )
+        .unwrap();
+        let invite = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: DeviceID::from_pubkey(&alice.0),
+                invited_pubkey: alice.0,
+                role: Role::Member,
+                encrypted_group_secret: vec![2],
+            },
+            2,
+            200,
+            owner.0,
+            &owner.1,
+        )
+        .unwrap();
+        let accept = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberAccept,
+            &MemberAcceptPayload {
+                invite_op_id: invite.op_id,
+                attribute_proof: None,
+            },
+            3,
+            300,
+            alice.0,
+            &alice.1,
+        )
+        .unwrap();
+        let msg = OpEnvelope::create_signed(
+            gid,
+            OpType::MsgAdd,
+            &MsgAddPayload {
+                msg_id: [9; 32],
+                ciphertext: vec![0xAB],
+                nonce: [0; 24],
+            },
+            4,
+            400,
+            owner.0,
+            &owner.1,
+        )
+        .unwrap();
+
+        (vec![create, invite, accept, msg], gid, owner, alice)
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let (ops, gid, owner, _alice) = make_group();
+        let new_owner = keypair();
+
+        let bundle =
+            export_group(gid, &ops, new_owner.0, vec![7], 5, 500, owner.0, &owner.1).unwrap();
+        let bytes = bundle.to_bytes().unwrap();
+        let decoded = GroupTransferBundle::from_bytes(&bytes).unwrap();
+
+        let state = import_group(&decoded, &new_owner.0).unwrap();
+        let new_dev = DeviceID::from_pubkey(&new_owner.0);
+        let entry = state.membership.get_active_member(&new_dev).unwrap();
+        assert_eq!(entry.role, Role::Owner);
+        assert_eq!(entry.encrypted_group_secret, Some(vec![7]));
+        assert_eq!(
+            state.membership.members()[&DeviceID::from_pubkey(&owner.0)].role,
+            Role::Admin
+        );
+        assert_eq!(state.renderable_messages().len(), 1);
+    }
+
+    #[test]
+    fn test_old_member_converges_on_transfer_op() {
+        let (ops, gid, owner, _alice) = make_group();
+        let new_owner = keypair();
+        let bundle =
+            export_group(gid, &ops, new_owner.0, vec![7], 5, 500, owner.0, &owner.1).unwrap();
+
+        // Alice already has the log and only receives the transfer op.
+        let mut alice_state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        alice_state.apply_op(bundle.transfer_op().unwrap()).unwrap();
+        assert_eq!(alice_state.state_hash(), bundle.snapshot_hash);
+    }
+
+    #[test]
+    fn test_non_owner_cannot_export() {
+        let (ops, gid, _owner, alice) = make_group();
+        let err =
+            export_group(gid, &ops, keypair().0, vec![], 5, 500, alice.0, &alice.1).unwrap_err();
+        assert!(matches!(err, MigrationError::NotOwner));
+    }
+
+    #[test]
+    fn test_import_rejects_tampering_and_wrong_account() {
+        let (ops, gid, owner, _alice) = make_group();
+        let new_owner = keypair();
+        let bundle =
+            export_group(gid, &ops, new_owner.0, vec![7], 5, 500, owner.0, &owner.1).unwrap();
+
+        let err = import_group(&bundle, &keypair().0).unwrap_err();
+        assert!(matches!(err, MigrationError::NewOwnerMismatch));
+
+        let mut dropped = bundle.clone();
+        dropped.ops.remove(3);
+        let err = import_group(&dropped, &new_owner.0).unwrap_err();
+        assert!(matches!(err, MigrationError::InvalidSignature));
+
+        let mut wrong_hash = bundle;
+        wrong_hash.snapshot_hash[0] ^= 1;
+        let err = import_group(&wrong_hash, &new_owner.0).unwrap_err();
+        assert!(matches!(err, MigrationError::InvalidSignature));
+    }
+}
@@ -1,4 +1,3 @@
-pub mod apply;
 /// CRDT group system — operation-based conflict-free replicated data types.
 ///
 /// Groups are represented as append-only operation logs. Every action (create,
@@ -11,26 +10,76 @@ pub mod apply;
 /// - `ops` — OpEnvelope, OpType, payload types, signing/verification
 /// - `limits` — Guardrail constants and op limit checking
 /// - `membership` — OR-Set membership CRDT with role-based authorization
-/// - `messages` — Message add/edit/delete/react with LWW edits and permanent tombstones
+/// - `messages` — Message add/edit/delete/react with LWW edits and permanent tombstones,
+///   plus per-reader delivery/read receipts
+/// - `feed` — Membership change events (joined, left, kicked, role changed) for UI
+/// - `invites` — Per-inviter invite quotas and the invite audit trail
 /// - `metadata` — LWW registers for group name, avatar, topic
+/// - `avatar` — Encrypted, content-addressed avatar blobs and their register value
+/// - `migration` — Owner-initiated group export/import between accounts
+/// - `anonymous` — Ring-proof anonymous posting with per-epoch rate limits
+/// - `admission` — Attribute-gated joins via zero-knowledge predicate proofs
 /// - `apply` — Unified apply engine (GroupState, rebuild, state_hash)
+/// - `query` — Cursor-paged message reads with edits, deletes and reactions folded in
+/// - `clock` — Managed per-group lamport clock and the op builder that uses it
+/// - `compact` — Op log compaction that drops superseded ops
+/// - `sync` — State-hash short-circuit and per-author digest exchange
+/// - `acks` — Batched op receipt acks and per-member delivery tracking
+/// - `stream` — Cursor-numbered stream of applied ops for incremental persistence
+/// - `writer` — Authorization-checked op authoring bound to a GroupState
+/// - `address_book` — Personal-data CRDT: signed contact change journal synced
+///   between the account's own devices
+pub mod acks;
+pub mod address_book;
+pub mod admission;
+pub mod anonymous;
+pub mod apply;
+pub mod avatar;
+pub mod clock;
+pub mod compact;
+pub mod feed;
 pub mod ids;
+pub mod invites;
 pub mod limits;
 pub mod membership;
 pub mod messages;
 pub mod metadata;
+pub mod migration;
 pub mod ops;
+pub mod query;
+pub mod stream;
+pub mod sync;
+pub mod writer;
 
 // Re-export core types for convenience
+pub use acks::{AckBatcher, AckError, DeliveryTracker, MemberLag, OpAck};
+pub use address_book::{
+    AddressBook, AddressBookContact, ContactChange, JournalDigest, JournalEntry, JournalError,
+};
+pub use admission::{admission_context, check_accept, AdmissionError};
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub use admission::{present_attribute, AttributePresentation, JoinRequirement};
+pub use anonymous::{AnonAdmission, AnonKeyEntry, AnonymousError, AnonymousState};
 pub use apply::{ApplyError, GroupState};
+pub use avatar::{seal_avatar, AvatarError, AvatarRef, AvatarVariant, SealedAvatar};
+pub use clock::{ClockError, ClockStore, LamportClock, MemoryClockStore, OpBuilder};
+pub use compact::{compact_ops, Compaction};
+pub use feed::{MembershipEvent, MembershipEventKind, MembershipFeed};
 pub use ids::{DeviceID, GroupID, OpID};
+pub use invites::{InviteLog, InviteRecord, InviteStatus};
 pub use limits::{check_op_limits, OpLimitStatus};
 pub use membership::{MemberEntry, MembershipError, MembershipState};
-pub use messages::{MessageEntry, MessageError, MessageState};
+pub use messages::{ConcurrentEdit, MessageEntry, MessageError, MessageState, ReceiptCounts};
 pub use metadata::{LWWRegister, MetadataError, MetadataState};
+pub use migration::{export_group, import_group, GroupTransferBundle, MigrationError};
 pub use ops::{
-    cbor_decode, cbor_encode, generate_msg_id, GroupCreatePayload, MemberAcceptPayload,
+    cbor_decode, cbor_encode, generate_msg_id, group_msg_id, verify_ops_batch,
+    AnonKeyRegisterPayload, AnonMsgAddPayload, GroupCreatePayload, MemberAcceptPayload,
     MemberInvitePayload, MemberRemovePayload, MetadataKey, MetadataSetPayload, MsgAddPayload,
-    MsgDeletePayload, MsgEditPayload, OpEnvelope, OpError, OpType, ReactionSetPayload,
-    RemoveReason, Role, RoleSetPayload,
+    MsgDeletePayload, MsgEditPayload, OpEnvelope, OpError, OpType, OwnerTransferPayload,
+    ReactionSetPayload, ReceiptSetPayload, ReceiptStatus, RemoveReason, Role, RoleSetPayload,
 };
+pub use query::{MessagePage, MessageQuery, MessageRange, MessageView};
+pub use stream::{OpCursor, OpStream, StreamError, StreamedOp, SubscriptionId};
+pub use sync::{SyncDigest, SyncError, SyncHello, SyncStep};
+pub use writer::{GroupWriter, WriterError};
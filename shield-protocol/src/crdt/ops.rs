@@ -12,6 +12,9 @@ use thiserror::Error;
 
 use crate::crdt::ids::{DeviceID, GroupID, OpID};
 use crate::crdt::limits::MAX_OP_PAYLOAD_BYTES;
+use crate::storage::versioned::{
+    decode_layout, Persisted, StateKind, VersionError, LEGACY_VERSION,
+};
 
 // ---------------------------------------------------------------------------
 // Errors
@@ -42,6 +45,9 @@ pub enum OpError {
 
     #[error("Invalid key length")]
     InvalidKeyLength,
+
+    #[error("Op at batch index {index} failed verification")]
+    BatchVerifyFailed { index: usize },
 }
 
 // ---------------------------------------------------------------------------
@@ -57,15 +63,21 @@ pub enum OpType {
     MemberAccept,
     MemberRemove,
     RoleSet,
+    OwnerTransfer,
 
     // Messages
     MsgAdd,
     MsgEdit,
     MsgDelete,
     ReactionSet,
+    ReceiptSet,
 
     // Metadata (LWW registers)
     MetadataSet,
+
+    // Anonymous posting (ring-proof authorized)
+    AnonKeyRegister,
+    AnonMsgAdd,
 }
 
 impl OpType {
@@ -78,6 +90,7 @@ impl OpType {
                 | OpType::MemberAccept
                 | OpType::MemberRemove
                 | OpType::RoleSet
+                | OpType::OwnerTransfer
         )
     }
 
@@ -89,17 +102,21 @@ impl OpType {
             OpType::MemberAccept => "MemberAccept",
             OpType::MemberRemove => "MemberRemove",
             OpType::RoleSet => "RoleSet",
+            OpType::OwnerTransfer => "OwnerTransfer",
             OpType::MsgAdd => "MsgAdd",
             OpType::MsgEdit => "MsgEdit",
             OpType::MsgDelete => "MsgDelete",
             OpType::ReactionSet => "ReactionSet",
+            OpType::ReceiptSet => "ReceiptSet",
             OpType::MetadataSet => "MetadataSet",
+            OpType::AnonKeyRegister => "AnonKeyRegister",
+            OpType::AnonMsgAdd => "AnonMsgAdd",
         }
     }
 }
 
 // ---------------------------------------------------------------------------
-// Role, RemoveReason, MetadataKey
+// Role, RemoveReason, MetadataKey, ReceiptStatus
 // ---------------------------------------------------------------------------
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -123,6 +140,18 @@ pub enum MetadataKey {
     Name = 0,
     Avatar = 1,
     Topic = 2,
+    /// `[1]` enables `AnonMsgAdd` ops for the group; any other value disables.
+    AnonymousPosting = 3,
+    /// CBOR `JoinRequirement` that accepts must prove; empty disables.
+    JoinRequirement = 4,
+}
+
+/// Delivery receipt level. Ordered: a later `Read` supersedes `Delivered`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum ReceiptStatus {
+    Delivered = 1,
+    Read = 2,
 }
 
 // ---------------------------------------------------------------------------
@@ -149,6 +178,10 @@ pub struct MemberInvitePayload {
 pub struct MemberAcceptPayload {
     /// References the MemberInvite op this accepts.
     pub invite_op_id: OpID,
+    /// CBOR `AttributePresentation` for groups with a join requirement
+    /// (see `crdt::admission`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute_proof: Option<Vec<u8>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -163,6 +196,16 @@ pub struct RoleSetPayload {
     pub new_role: Role,
 }
 
+/// Hands ownership to another account (group migration). The new owner is
+/// added auto-accepted; the transferring owner is demoted to Admin.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OwnerTransferPayload {
+    pub new_owner_device_id: DeviceID,
+    pub new_owner_pubkey: [u8; 32],
+    /// GroupSecret encrypted to the new owner's X25519 key.
+    pub encrypted_group_secret: Vec<u8>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MsgAddPayload {
     /// BLAKE3(author_device_id || lamport || nonce) — unique message identifier.
@@ -178,6 +221,11 @@ pub struct MsgEditPayload {
     pub msg_id: [u8; 32],
     pub new_ciphertext: Vec<u8>,
     pub nonce: [u8; 24],
+    /// The version this edit was made on (the create op or an earlier
+    /// edit), so replicas can tell a concurrent edit from a sequential one.
+    /// Absent from older clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<OpID>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -194,12 +242,46 @@ pub struct ReactionSetPayload {
     pub present: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReceiptSetPayload {
+    pub msg_id: [u8; 32],
+    pub status: ReceiptStatus,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MetadataSetPayload {
     pub key: MetadataKey,
     pub value: Vec<u8>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AnonKeyRegisterPayload {
+    /// Compressed Ristretto public key this member contributes to anonymous rings.
+    pub anon_pubkey: [u8; 32],
+}
+
+/// Anonymous message. The envelope is signed by a one-time Ed25519 key; the
+/// ring proof shows the poster owns one of the `ring` members' anonymous keys.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AnonMsgAddPayload {
+    /// BLAKE3(ephemeral_device_id || lamport || nonce).
+    pub msg_id: [u8; 32],
+    /// Message content encrypted with GroupSecret (XChaCha20-Poly1305).
+    pub ciphertext: Vec<u8>,
+    /// XChaCha20 nonce used for encryption.
+    pub nonce: [u8; 24],
+    /// Rate-limit epoch (`timestamp_ms / ANON_POST_EPOCH_MS`) the link tag is bound to.
+    pub epoch: u64,
+    /// Members whose registered anonymous keys form the ring, in proof order.
+    pub ring: Vec<DeviceID>,
+    /// Ring proof: initial challenge.
+    pub proof_c0: [u8; 32],
+    /// Ring proof: one response per ring member.
+    pub proof_responses: Vec<[u8; 32]>,
+    /// Ring proof: per-epoch linking tag (same poster + epoch → same tag).
+    pub link_tag: [u8; 32],
+}
+
 // ---------------------------------------------------------------------------
 // OpEnvelope
 // ---------------------------------------------------------------------------
@@ -305,11 +387,32 @@ impl OpEnvelope {
         .map_err(|e| OpError::SigningFailed(e.to_string()))
     }
 
+    /// Author check + signable hash, split out for batch verification.
+    fn prepare_verify(
+        &self,
+    ) -> Result<
+        (
+            [u8; 32],
+            ed25519_dalek::Signature,
+            ed25519_dalek::VerifyingKey,
+        ),
+        OpError,
+    > {
+        if DeviceID::from_pubkey(&self.author_pubkey) != self.op_id.author {
+            return Err(OpError::AuthorMismatch);
+        }
+        let hash = *blake3::hash(&self.signable_bytes()?).as_bytes();
+        let signature = ed25519_dalek::Signature::from_bytes(&self.signature);
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&self.author_pubkey)
+            .map_err(|e| OpError::SigningFailed(e.to_string()))?;
+        Ok((hash, signature, key))
+    }
+
     /// Produce the canonical bytes to sign/verify.
     ///
     /// Includes all fields except `signature`. Uses bincode for deterministic
     /// serialization (field order is fixed by struct definition).
-    fn signable_bytes(&self) -> Result<Vec<u8>, OpError> {
+    pub(crate) fn signable_bytes(&self) -> Result<Vec<u8>, OpError> {
         // Serialize a tuple of all fields except signature for determinism
         let signable = (
             &self.group_id,
@@ -324,7 +427,8 @@ impl OpEnvelope {
         bincode::serialize(&signable).map_err(|e| OpError::BincodeError(e.to_string()))
     }
 
-    /// Serialize the full envelope to bytes (for storage / wire transfer).
+    /// Serialize the full envelope to bytes (for wire transfer; op logs on
+    /// disk go through [`Versioned`](crate::storage::versioned::Versioned)).
     pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
         bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
     }
@@ -338,6 +442,95 @@ impl OpEnvelope {
     pub fn decode_payload<P: serde::de::DeserializeOwned>(&self) -> Result<P, OpError> {
         cbor_decode(&self.payload)
     }
+
+    /// Re-date and re-sign, for tests that depend on the timestamp.
+    #[cfg(test)]
+    pub(crate) fn redated(mut self, timestamp_ms: u64, author_privkey: &[u8; 32]) -> Self {
+        self.timestamp_ms = timestamp_ms;
+        let hash = blake3::hash(&self.signable_bytes().unwrap());
+        self.signature =
+            crate::crypto::signing::sign_data(hash.as_bytes(), author_privkey).unwrap();
+        self
+    }
+}
+
+/// The envelope layout has not changed since op logs were first stored, so
+/// unversioned entries are plain `to_bytes` output.
+impl Persisted for OpEnvelope {
+    const KIND: StateKind = StateKind::CrdtOp;
+    const VERSION: u16 = 1;
+
+    fn migrate(version: u16, body: &[u8]) -> Result<Self, VersionError> {
+        match version {
+            LEGACY_VERSION => decode_layout(body),
+            _ => Err(VersionError::NoMigration {
+                kind: Self::KIND,
+                version,
+            }),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Batch verification
+// ---------------------------------------------------------------------------
+
+/// Verify the signatures of a batch of ops (e.g. a sync response).
+///
+/// Hashing runs in parallel with the `parallel` feature, then all signatures
+/// are checked with a single Ed25519 batch verification. If the batch fails,
+/// each op is re-checked individually and the index of the first bad op is
+/// returned as `OpError::BatchVerifyFailed`.
+///
+/// This is an early-rejection filter only. The batch equation is randomized
+/// and may pass a deliberately malformed signature (torsion in `R`) that
+/// `verify()` rejects, so passing it never admits an op:
+/// `GroupState::apply_op` still verifies every op on its own.
+pub fn verify_ops_batch(ops: &[OpEnvelope]) -> Result<(), OpError> {
+    if ops.is_empty() {
+        return Ok(());
+    }
+
+    let prepared = map_ops(ops, OpEnvelope::prepare_verify);
+    let mut hashes = Vec::with_capacity(ops.len());
+    let mut signatures = Vec::with_capacity(ops.len());
+    let mut keys = Vec::with_capacity(ops.len());
+    for (index, item) in prepared.into_iter().enumerate() {
+        let (hash, signature, key) = item.map_err(|_| OpError::BatchVerifyFailed { index })?;
+        hashes.push(hash);
+        signatures.push(signature);
+        keys.push(key);
+    }
+
+    let messages: Vec<&[u8]> = hashes.iter().map(|h| &h[..]).collect();
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+        return Ok(());
+    }
+
+    // Fallback: find the culprit with the same check `apply_op` uses.
+    let results = map_ops(ops, |op| matches!(op.verify(), Ok(true)));
+    match results.iter().position(|ok| !ok) {
+        Some(index) => Err(OpError::BatchVerifyFailed { index }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn map_ops<T, F>(ops: &[OpEnvelope], f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&OpEnvelope) -> T + Sync + Send,
+{
+    use rayon::prelude::*;
+    ops.par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_ops<T, F>(ops: &[OpEnvelope], f: F) -> Vec<T>
+where
+    F: Fn(&OpEnvelope) -> T,
+{
+    ops.iter().map(f).collect()
 }
 
 // ---------------------------------------------------------------------------
@@ -361,14 +554,28 @@ pub fn cbor_decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, Op
 // ---------------------------------------------------------------------------
 
 /// Current time in milliseconds since Unix epoch.
-fn now_ms() -> u64 {
+pub(crate) fn now_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis() as u64
 }
 
-/// Generate a unique message ID: BLAKE3(author_device_id || lamport || nonce).
+/// Message ID for a new group message, in the scheme shared with direct
+/// messages ([`MessageId::group`](crate::protocol::MessageId::group)).
+pub fn group_msg_id(
+    group_id: &GroupID,
+    author_pubkey: &[u8; 32],
+    lamport: u64,
+    nonce: u64,
+) -> [u8; 32] {
+    crate::protocol::MessageId::group(author_pubkey, group_id.as_bytes(), lamport, nonce).0
+}
+
+/// Legacy message ID: BLAKE3(author_device_id || lamport || nonce).
+///
+/// Msg ids are carried in `MsgAdd` payloads and never recomputed, so ids in
+/// existing op logs stay valid; new messages use [`group_msg_id`].
 pub fn generate_msg_id(author: &DeviceID, lamport: u64, nonce: u64) -> [u8; 32] {
     let mut hasher = blake3::Hasher::new();
     hasher.update(author.as_bytes());
@@ -403,11 +610,15 @@ mod tests {
         assert!(OpType::MemberAccept.is_membership_op());
         assert!(OpType::MemberRemove.is_membership_op());
         assert!(OpType::RoleSet.is_membership_op());
+        assert!(OpType::OwnerTransfer.is_membership_op());
         assert!(!OpType::MsgAdd.is_membership_op());
         assert!(!OpType::MsgEdit.is_membership_op());
         assert!(!OpType::MsgDelete.is_membership_op());
         assert!(!OpType::ReactionSet.is_membership_op());
+        assert!(!OpType::ReceiptSet.is_membership_op());
         assert!(!OpType::MetadataSet.is_membership_op());
+        assert!(!OpType::AnonKeyRegister.is_membership_op());
+        assert!(!OpType::AnonMsgAdd.is_membership_op());
     }
 
     #[test]
@@ -466,6 +677,48 @@ mod tests {
         assert!(!op.verify().unwrap());
     }
 
+    #[test]
+    fn test_verify_ops_batch() {
+        let (pubkey, privkey) = test_keypair();
+        let group_id = test_group_id(&pubkey);
+
+        let mut ops: Vec<OpEnvelope> = (1..=8)
+            .map(|lamport| {
+                let payload = MsgAddPayload {
+                    msg_id: [lamport as u8; 32],
+                    ciphertext: vec![lamport as u8],
+                    nonce: [0; 24],
+                };
+                OpEnvelope::create_signed(
+                    group_id,
+                    OpType::MsgAdd,
+                    &payload,
+                    lamport,
+                    lamport,
+                    pubkey,
+                    &privkey,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        assert!(verify_ops_batch(&[]).is_ok());
+        assert!(verify_ops_batch(&ops).is_ok());
+
+        ops[5].payload.push(0xFF);
+        assert!(matches!(
+            verify_ops_batch(&ops),
+            Err(OpError::BatchVerifyFailed { index: 5 })
+        ));
+
+        // Author mismatch is caught before batch verification.
+        ops[2].author_pubkey = test_keypair().0;
+        assert!(matches!(
+            verify_ops_batch(&ops),
+            Err(OpError::BatchVerifyFailed { index: 2 })
+        ));
+    }
+
     #[test]
     fn test_verify_detects_wrong_pubkey() {
         let (pubkey, privkey) = test_keypair();
@@ -527,6 +780,34 @@ mod tests {
         assert_eq!(restored.signature, op.signature);
     }
 
+    #[test]
+    fn test_stored_ops_are_versioned() {
+        use crate::storage::versioned::{encode, Versioned};
+
+        let (pubkey, privkey) = test_keypair();
+        let op = OpEnvelope::create_signed(
+            test_group_id(&pubkey),
+            OpType::MsgDelete,
+            &MsgDeletePayload { msg_id: [0x22; 32] },
+            3,
+            7,
+            pubkey,
+            &privkey,
+        )
+        .unwrap();
+
+        // Op logs written before headers existed
+        let legacy = Versioned::<OpEnvelope>::decode(&op.to_bytes().unwrap()).unwrap();
+        assert!(legacy.is_upgraded());
+        assert!(legacy.value.verify().unwrap());
+
+        let blob = encode(&op).unwrap();
+        let stored = Versioned::<OpEnvelope>::decode(&blob).unwrap();
+        assert!(!stored.is_upgraded());
+        assert_eq!(stored.value.op_id, op.op_id);
+        assert!(stored.value.verify().unwrap());
+    }
+
     #[test]
     fn test_payload_too_large_rejected() {
         let (pubkey, privkey) = test_keypair();
@@ -629,6 +910,7 @@ mod tests {
         // MemberAccept
         let p = MemberAcceptPayload {
             invite_op_id: OpID::new(DeviceID::from_bytes([3; 16]), 1, 0),
+            attribute_proof: None,
         };
         let bytes = cbor_encode(&p).unwrap();
         let _: MemberAcceptPayload = cbor_decode(&bytes).unwrap();
@@ -663,6 +945,7 @@ mod tests {
             msg_id: [11; 32],
             new_ciphertext: vec![12, 13],
             nonce: [14; 24],
+            base: None,
         };
         let bytes = cbor_encode(&p).unwrap();
         let _: MsgEditPayload = cbor_decode(&bytes).unwrap();
@@ -0,0 +1,404 @@
+/// Read-side message queries for rendering a conversation.
+///
+/// `MessageState` keeps a second index of messages by create OpID, which is
+/// conversation order (lamport first). Pages are cut from that index, so a
+/// screen costs O(page) rather than a walk over every message in the group.
+/// Each result is a `MessageView` with edits, the tombstone and reactions
+/// already folded in.
+///
+/// Pages are always returned oldest first. Scroll up with
+/// `MessageRange::Before(page.oldest())` and catch up with
+/// `MessageRange::After(page.newest())`.
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use crate::crdt::apply::GroupState;
+use crate::crdt::ids::{DeviceID, OpID};
+use crate::crdt::messages::{MessageEntry, ReceiptCounts};
+use crate::crdt::ops::ReceiptStatus;
+
+/// Default page size for `MessageQuery::new`.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Upper bound on any page, whatever the caller asks for.
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Which messages a query selects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageRange {
+    /// The newest messages.
+    Latest,
+    /// Messages created strictly before the cursor (older).
+    Before(OpID),
+    /// Messages created strictly after the cursor (newer).
+    After(OpID),
+    /// Create timestamps in `[from_ms, to_ms)`. Timestamps are set by the
+    /// author and not indexed, so this walks the conversation.
+    TimeRange { from_ms: u64, to_ms: u64 },
+    /// Messages created after `lamport` that `reader` neither wrote nor has
+    /// marked `Read`.
+    UnreadSince { lamport: u64, reader: DeviceID },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageQuery {
+    pub range: MessageRange,
+    pub limit: usize,
+    /// Also return tombstones (rendered as "message deleted").
+    pub include_deleted: bool,
+}
+
+impl MessageQuery {
+    pub fn new(range: MessageRange) -> Self {
+        MessageQuery {
+            range,
+            limit: DEFAULT_PAGE_SIZE,
+            include_deleted: false,
+        }
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn include_deleted(mut self) -> Self {
+        self.include_deleted = true;
+        self
+    }
+}
+
+/// One message with its mutable state folded in.
+#[derive(Clone, Debug)]
+pub struct MessageView<'a> {
+    pub entry: &'a MessageEntry,
+    /// An edit has replaced the original ciphertext.
+    pub edited: bool,
+    /// Emoji → devices currently reacting with it. Empty for tombstones.
+    pub reactions: BTreeMap<&'a str, Vec<DeviceID>>,
+    pub receipts: ReceiptCounts,
+}
+
+impl<'a> MessageView<'a> {
+    fn new(entry: &'a MessageEntry) -> Self {
+        let mut reactions: BTreeMap<&str, Vec<DeviceID>> = BTreeMap::new();
+        if !entry.deleted {
+            for ((reactor, emoji), present) in &entry.reactions {
+                if *present {
+                    reactions.entry(emoji.as_str()).or_default().push(*reactor);
+                }
+            }
+        }
+        MessageView {
+            entry,
+            edited: entry.last_edit_op.is_some(),
+            reactions,
+            receipts: entry.receipt_counts(),
+        }
+    }
+
+    /// Cursor for this message.
+    pub fn cursor(&self) -> OpID {
+        self.entry.create_op
+    }
+}
+
+/// A page of messages, oldest first.
+#[derive(Clone, Debug, Default)]
+pub struct MessagePage<'a> {
+    pub messages: Vec<MessageView<'a>>,
+    /// More messages match beyond this page, in the direction of the query.
+    pub has_more: bool,
+}
+
+impl MessagePage<'_> {
+    pub fn oldest(&self) -> Option<OpID> {
+        self.messages.first().map(MessageView::cursor)
+    }
+
+    pub fn newest(&self) -> Option<OpID> {
+        self.messages.last().map(MessageView::cursor)
+    }
+}
+
+impl GroupState {
+    /// Run a message query. Membership gating matches
+    /// `renderable_messages`.
+    pub fn query_messages(&self, query: &MessageQuery) -> MessagePage<'_> {
+        let limit = query.limit.clamp(1, MAX_PAGE_SIZE);
+        let index = self.messages.by_create();
+        let visible = |msg_id| self.visible_message(msg_id, query.include_deleted);
+
+        let (entries, has_more, newest_first) = match &query.range {
+            MessageRange::Latest => {
+                let (e, more) = take(index.values().rev().filter_map(visible), limit);
+                (e, more, true)
+            }
+            MessageRange::Before(cursor) => {
+                let older = index.range(..*cursor).rev().map(|(_, id)| id);
+                let (e, more) = take(older.filter_map(visible), limit);
+                (e, more, true)
+            }
+            MessageRange::After(cursor) => {
+                let newer = index
+                    .range((Bound::Excluded(*cursor), Bound::Unbounded))
+                    .map(|(_, id)| id);
+                let (e, more) = take(newer.filter_map(visible), limit);
+                (e, more, false)
+            }
+            MessageRange::TimeRange { from_ms, to_ms } => {
+                let in_range = index
+                    .values()
+                    .filter_map(visible)
+                    .filter(|m| (*from_ms..*to_ms).contains(&m.timestamp_ms));
+                let (e, more) = take(in_range, limit);
+                (e, more, false)
+            }
+            MessageRange::UnreadSince { lamport, reader } => {
+                // Smallest OpID with a greater lamport: OpIDs sort lamport first.
+                let start = OpID::new(DeviceID::from_bytes([0; 16]), lamport.saturating_add(1), 0);
+                let unread = index
+                    .range(start..)
+                    .map(|(_, id)| id)
+                    .filter_map(visible)
+                    .filter(|m| {
+                        m.author != *reader && m.receipts.get(reader) != Some(&ReceiptStatus::Read)
+                    });
+                let (e, more) = take(unread, limit);
+                (e, more, false)
+            }
+        };
+
+        let mut messages: Vec<MessageView> = entries.into_iter().map(MessageView::new).collect();
+        if newest_first {
+            messages.reverse();
+        }
+        MessagePage { messages, has_more }
+    }
+
+    fn visible_message(&self, msg_id: &[u8; 32], include_deleted: bool) -> Option<&MessageEntry> {
+        let msg = self.messages.get_message(msg_id)?;
+        let shown = (include_deleted || !msg.deleted)
+            && (msg.anonymous || self.membership.is_author_active_for_render(&msg.author));
+        shown.then_some(msg)
+    }
+}
+
+/// Up to `limit` items, and whether any were left over.
+fn take<'a>(
+    iter: impl Iterator<Item = &'a MessageEntry>,
+    limit: usize,
+) -> (Vec<&'a MessageEntry>, bool) {
+    let mut items: Vec<_> = iter.take(limit + 1).collect();
+    let has_more = items.len() > limit;
+    items.truncate(limit);
+    (items, has_more)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::clock::{LamportClock, MemoryClockStore};
+    use crate::crdt::ids::GroupID;
+    use crate::crdt::ops::{
+        GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, MsgAddPayload, OpEnvelope,
+        OpType, Role,
+    };
+    use crate::crdt::writer::GroupWriter;
+    use crate::rng::seeded;
+
+    struct Chat {
+        state: GroupState,
+        clock: LamportClock,
+        store: MemoryClockStore,
+        owner: ([u8; 32], [u8; 32]),
+        member: ([u8; 32], [u8; 32]),
+    }
+
+    impl Chat {
+        fn new() -> Self {
+            let owner = crate::crypto::signing::generate_keypair();
+            let member = crate::crypto::signing::generate_keypair();
+            let gid = GroupID::new(&DeviceID::from_pubkey(&owner.0), &[3; 32]);
+            let mut state = GroupState::new(gid);
+            let create = OpEnvelope::create_signed(
+                gid,
+                OpType::GroupCreate,
+                &GroupCreatePayload {
+                    group_name: "chat".into(),
+                    encrypted_group_secret: vec![],
+                },
+                1,
+                1,
+                owner.0,
+                &owner.1,
+            )
+            .unwrap();
+            let invite = OpEnvelope::create_signed(
+                gid,
+                OpType::MemberInvite,
+                &MemberInvitePayload {
+                    invited_device_id: DeviceID::from_pubkey(&member.0),
+                    invited_pubkey: member.0,
+                    role: Role::Member,
+                    encrypted_group_secret: vec![],
+                },
+                2,
+                2,
+                owner.0,
+                &owner.1,
+            )
+            .unwrap();
+            let accept = OpEnvelope::create_signed(
+                gid,
+                OpType::MemberAccept,
+                &MemberAcceptPayload {
+                    invite_op_id: invite.op_id,
+                    attribute_proof: None,
+                },
+                3,
+                3,
+                member.0,
+                &member.1,
+            )
+            .unwrap();
+            for op in [create, invite, accept] {
+                state.apply_op(&op).unwrap();
+            }
+            let store = MemoryClockStore::default();
+            let clock = LamportClock::open(gid, &store).unwrap();
+            Chat {
+                state,
+                clock,
+                store,
+                owner,
+                member,
+            }
+        }
+
+        /// Author and apply one op as the owner (`true`) or the member.
+        fn write(
+            &mut self,
+            as_owner: bool,
+            f: impl FnOnce(&mut GroupWriter) -> OpEnvelope,
+        ) -> OpEnvelope {
+            let (pub_k, priv_k) = if as_owner { self.owner } else { self.member };
+            let op = f(&mut GroupWriter::new(
+                &self.state,
+                &mut self.clock,
+                &mut self.store,
+                pub_k,
+                &priv_k,
+            ));
+            self.state.apply_op(&op).unwrap();
+            op
+        }
+
+        fn post(&mut self, as_owner: bool, n: u8) -> [u8; 32] {
+            let mut rng = seeded(n as u64);
+            let op = self.write(as_owner, |w| w.msg_add(vec![n], [0; 24], &mut rng).unwrap());
+            op.decode_payload::<MsgAddPayload>().unwrap().msg_id
+        }
+    }
+
+    fn bodies(page: &MessagePage) -> Vec<u8> {
+        page.messages
+            .iter()
+            .map(|m| m.entry.ciphertext[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_cursor_pages_walk_the_conversation() {
+        let mut chat = Chat::new();
+        for n in 1..=7 {
+            chat.post(n % 2 == 0, n);
+        }
+
+        let latest = chat
+            .state
+            .query_messages(&MessageQuery::new(MessageRange::Latest).limit(3));
+        assert_eq!(bodies(&latest), vec![5, 6, 7]);
+        assert!(latest.has_more);
+
+        let older = chat.state.query_messages(
+            &MessageQuery::new(MessageRange::Before(latest.oldest().unwrap())).limit(3),
+        );
+        assert_eq!(bodies(&older), vec![2, 3, 4]);
+        let oldest = chat.state.query_messages(
+            &MessageQuery::new(MessageRange::Before(older.oldest().unwrap())).limit(3),
+        );
+        assert_eq!(bodies(&oldest), vec![1]);
+        assert!(!oldest.has_more);
+
+        let newer = chat.state.query_messages(
+            &MessageQuery::new(MessageRange::After(older.newest().unwrap())).limit(10),
+        );
+        assert_eq!(bodies(&newer), vec![5, 6, 7]);
+        assert!(!newer.has_more);
+
+        let all_time = MessageRange::TimeRange {
+            from_ms: 0,
+            to_ms: u64::MAX,
+        };
+        let page = chat.state.query_messages(&MessageQuery::new(all_time));
+        assert_eq!(page.messages.len(), 7);
+        let none = MessageRange::TimeRange {
+            from_ms: 0,
+            to_ms: 1,
+        };
+        assert!(chat
+            .state
+            .query_messages(&MessageQuery::new(none))
+            .messages
+            .is_empty());
+    }
+
+    #[test]
+    fn test_views_fold_edits_deletes_reactions_and_unread() {
+        let mut chat = Chat::new();
+        let seen_up_to = chat.state.max_lamport.values().max().copied().unwrap();
+        let first = chat.post(true, 1);
+        let second = chat.post(true, 2);
+        let third = chat.post(true, 3);
+        let mine = chat.post(false, 4);
+        let mut rng = seeded(9);
+
+        chat.write(true, |w| {
+            w.msg_edit(first, vec![10], [1; 24], &mut rng).unwrap()
+        });
+        chat.write(false, |w| w.react(first, "+1", true, &mut rng).unwrap());
+        chat.write(true, |w| w.react(first, "+1", true, &mut rng).unwrap());
+        chat.write(true, |w| w.msg_delete(second, &mut rng).unwrap());
+        chat.write(false, |w| {
+            w.receipt(third, ReceiptStatus::Read, &mut rng).unwrap()
+        });
+
+        let page = chat
+            .state
+            .query_messages(&MessageQuery::new(MessageRange::Latest).include_deleted());
+        assert_eq!(page.messages.len(), 4);
+        let view = &page.messages[0];
+        assert!(view.edited);
+        assert_eq!(view.entry.ciphertext, vec![10]);
+        assert_eq!(view.reactions["+1"].len(), 2);
+        assert!(page.messages[1].entry.deleted);
+        assert!(page.messages[1].reactions.is_empty());
+
+        let visible = chat
+            .state
+            .query_messages(&MessageQuery::new(MessageRange::Latest));
+        assert_eq!(visible.messages.len(), 3);
+
+        let member = DeviceID::from_pubkey(&chat.member.0);
+        let unread = chat
+            .state
+            .query_messages(&MessageQuery::new(MessageRange::UnreadSince {
+                lamport: seen_up_to,
+                reader: member,
+            }));
+        let ids: Vec<_> = unread.messages.iter().map(|m| m.entry.msg_id).collect();
+        assert_eq!(ids, vec![first]);
+        assert!(!ids.contains(&mine));
+    }
+}
@@ -0,0 +1,468 @@
+/// Op stream — incremental export of applied ops for persistence and
+/// replication.
+///
+/// Without it an app learns that the group changed but not which ops did,
+/// so it re-serializes the whole log after every apply. `OpStream` numbers
+/// each newly applied op with a strictly increasing `OpCursor` and keeps its
+/// canonical `to_bytes` encoding:
+///
+/// - Consumers (the on-disk log, a replica) `subscribe` at a cursor, read
+///   `pending` ops, store them and `ack` the last cursor they stored. Unacked
+///   ops are handed out again, so a consumer that persists its cursor in the
+///   same write as the ops sees every op exactly once, across crashes too.
+/// - `ops_since` answers "what came after cursor N" for a peer or a replica
+///   that tracks its own cursor.
+/// - Entries every subscriber has acked are dropped; a cursor older than the
+///   retained window gets `Truncated`, and the reader falls back to `sync`.
+///
+/// Duplicates (the same op by broadcast and by sync) never get a second
+/// cursor: `apply` relies on `GroupState`'s own idempotency, and `record`
+/// recognizes ops that are still retained. After a restart, `resume`
+/// continues numbering after the last stored cursor; the rebuilt
+/// `GroupState` already holds the ops on disk, so `apply` skips them.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use thiserror::Error;
+
+use crate::crdt::apply::{ApplyError, GroupState};
+use crate::crdt::ids::{GroupID, OpID};
+use crate::crdt::ops::{OpEnvelope, OpError};
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error("Op targets wrong group")]
+    WrongGroup,
+
+    #[error("Cursor {cursor} is ahead of the stream head {head}")]
+    CursorAhead { cursor: u64, head: u64 },
+
+    #[error("Ops after cursor {cursor} were dropped (oldest retained: {oldest})")]
+    Truncated { cursor: u64, oldest: u64 },
+
+    #[error("Unknown subscription {0}")]
+    UnknownSubscription(u64),
+
+    #[error("Apply error: {0}")]
+    Apply(#[from] ApplyError),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// Cursors and entries
+// ---------------------------------------------------------------------------
+
+/// Position in a group's op stream. `OpCursor::START` precedes every op;
+/// the first op is at 1.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct OpCursor(pub u64);
+
+impl OpCursor {
+    pub const START: OpCursor = OpCursor(0);
+
+    fn next(self) -> OpCursor {
+        OpCursor(self.0 + 1)
+    }
+}
+
+/// One applied op as handed to consumers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamedOp {
+    pub cursor: OpCursor,
+    pub op_id: OpID,
+    /// `OpEnvelope::to_bytes` of the op, exactly as applied.
+    pub bytes: Vec<u8>,
+}
+
+impl StreamedOp {
+    pub fn decode(&self) -> Result<OpEnvelope, OpError> {
+        OpEnvelope::from_bytes(&self.bytes)
+    }
+}
+
+/// Handle returned by [`OpStream::subscribe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionId(u64);
+
+// ---------------------------------------------------------------------------
+// OpStream
+// ---------------------------------------------------------------------------
+
+/// Cursor-numbered log of the ops applied to one group.
+pub struct OpStream {
+    group_id: GroupID,
+    head: OpCursor,
+    /// Retained entries, oldest first, with consecutive cursors.
+    entries: VecDeque<StreamedOp>,
+    /// Ids of `entries`, pruned with them.
+    retained_ids: HashSet<OpID>,
+    /// Last acked cursor per subscription.
+    subscriptions: BTreeMap<SubscriptionId, OpCursor>,
+    next_subscription: u64,
+}
+
+impl OpStream {
+    pub fn new(group_id: GroupID) -> Self {
+        Self::resume(group_id, OpCursor::START)
+    }
+
+    /// Continue a stream whose ops up to `head` were persisted earlier.
+    pub fn resume(group_id: GroupID, head: OpCursor) -> Self {
+        OpStream {
+            group_id,
+            head,
+            entries: VecDeque::new(),
+            retained_ids: HashSet::new(),
+            subscriptions: BTreeMap::new(),
+            next_subscription: 0,
+        }
+    }
+
+    /// Cursor of the newest op (`START` while empty).
+    pub fn head(&self) -> OpCursor {
+        self.head
+    }
+
+    /// Apply `op` to `state` and stream it if it was new.
+    /// Returns its cursor, or `None` for a duplicate.
+    pub fn apply(
+        &mut self,
+        state: &mut GroupState,
+        op: &OpEnvelope,
+    ) -> Result<Option<OpCursor>, StreamError> {
+        if state.group_id != self.group_id {
+            return Err(StreamError::WrongGroup);
+        }
+        // Encode first: an op that cannot be streamed is not applied either
+        let bytes = op.to_bytes()?;
+        if !state.apply_op(op)? {
+            return Ok(None);
+        }
+        Ok(self.push(op.op_id, bytes))
+    }
+
+    /// Stream an op that was applied some other way (e.g. during
+    /// `rebuild_from_ops`). Returns its cursor, or `None` if it is still
+    /// retained. Trimmed ops are not remembered; use `apply` for ops that
+    /// may be delivered again.
+    pub fn record(&mut self, op: &OpEnvelope) -> Result<Option<OpCursor>, StreamError> {
+        if op.group_id != self.group_id {
+            return Err(StreamError::WrongGroup);
+        }
+        if self.retained_ids.contains(&op.op_id) {
+            return Ok(None);
+        }
+        let bytes = op.to_bytes()?;
+        Ok(self.push(op.op_id, bytes))
+    }
+
+    fn push(&mut self, op_id: OpID, bytes: Vec<u8>) -> Option<OpCursor> {
+        if !self.retained_ids.insert(op_id) {
+            return None;
+        }
+        self.head = self.head.next();
+        self.entries.push_back(StreamedOp {
+            cursor: self.head,
+            op_id,
+            bytes,
+        });
+        Some(self.head)
+    }
+
+    /// Up to `limit` ops after `cursor`, oldest first.
+    pub fn ops_since(
+        &self,
+        cursor: OpCursor,
+        limit: usize,
+    ) -> Result<Vec<StreamedOp>, StreamError> {
+        if cursor > self.head {
+            return Err(StreamError::CursorAhead {
+                cursor: cursor.0,
+                head: self.head.0,
+            });
+        }
+        let oldest = self.oldest().0;
+        if cursor.0 < oldest {
+            return Err(StreamError::Truncated {
+                cursor: cursor.0,
+                oldest: oldest + 1,
+            });
+        }
+        let skip = (cursor.0 - oldest) as usize;
+        Ok(self
+            .entries
+            .iter()
+            .skip(skip)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    /// Start delivering ops after `from` (`START` for everything retained,
+    /// `head()` for new ops only, or a cursor the consumer stored).
+    pub fn subscribe(&mut self, from: OpCursor) -> Result<SubscriptionId, StreamError> {
+        let from = if from == OpCursor::START {
+            self.oldest()
+        } else {
+            from
+        };
+        self.ops_since(from, 0)?;
+        let id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscriptions.insert(id, from);
+        Ok(id)
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.subscriptions.remove(&id);
+        self.trim();
+    }
+
+    /// Up to `limit` ops the subscription has not acked yet.
+    pub fn pending(
+        &self,
+        id: SubscriptionId,
+        limit: usize,
+    ) -> Result<Vec<StreamedOp>, StreamError> {
+        self.ops_since(self.acked(id)?, limit)
+    }
+
+    /// Last cursor the subscription acked.
+    pub fn acked(&self, id: SubscriptionId) -> Result<OpCursor, StreamError> {
+        self.subscriptions
+            .get(&id)
+            .copied()
+            .ok_or(StreamError::UnknownSubscription(id.0))
+    }
+
+    /// Mark everything up to `cursor` as stored by the subscriber. Acks
+    /// are cumulative; an older cursor than the last ack is ignored.
+    pub fn ack(&mut self, id: SubscriptionId, cursor: OpCursor) -> Result<(), StreamError> {
+        if cursor > self.head {
+            return Err(StreamError::CursorAhead {
+                cursor: cursor.0,
+                head: self.head.0,
+            });
+        }
+        let acked = self
+            .subscriptions
+            .get_mut(&id)
+            .ok_or(StreamError::UnknownSubscription(id.0))?;
+        *acked = (*acked).max(cursor);
+        self.trim();
+        Ok(())
+    }
+
+    /// Drop entries every subscriber has acked. Without subscribers
+    /// everything is kept for `ops_since`.
+    fn trim(&mut self) {
+        let Some(&low) = self.subscriptions.values().min() else {
+            return;
+        };
+        while self.entries.front().is_some_and(|e| e.cursor <= low) {
+            if let Some(entry) = self.entries.pop_front() {
+                self.retained_ids.remove(&entry.op_id);
+            }
+        }
+    }
+
+    /// Cursor just before the oldest retained entry.
+    fn oldest(&self) -> OpCursor {
+        OpCursor(self.head.0 - self.entries.len() as u64)
+    }
+
+    /// Number of retained entries.
+    pub fn retained(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ids::DeviceID;
+    use crate::crdt::ops::{GroupCreatePayload, MsgAddPayload, OpType};
+
+    /// GroupCreate + `n` messages from the owner.
+    fn make_ops(n: u64) -> (Vec<OpEnvelope>, GroupID) {
+        let (pub_k, priv_k) = crate::crypto::signing::generate_keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&pub_k), &[0x57; 32]);
+        let create = GroupCreatePayload {
+            group_name: "Stream".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let mut ops = vec![OpEnvelope::create_signed(
+            gid,
+            OpType::GroupCreate,
+            &create,
+            1,
+            1,
+            pub_k,
+            &priv_k,
+        )
+        .unwrap()];
+        for lamport in 2..2 + n {
+            let payload = MsgAddPayload {
+                msg_id: [lamport as u8; 32],
+                ciphertext: vec![lamport as u8],
+                nonce: [0; 24],
+            };
+            ops.push(
+                OpEnvelope::create_signed(
+                    gid,
+                    OpType::MsgAdd,
+                    &payload,
+                    lamport,
+                    lamport,
+                    pub_k,
+                    &priv_k,
+                )
+                .unwrap(),
+            );
+        }
+        (ops, gid)
+    }
+
+    #[test]
+    fn test_applied_ops_stream_once_with_canonical_bytes() {
+        let (ops, gid) = make_ops(3);
+        let mut state = GroupState::new(gid);
+        let mut stream = OpStream::new(gid);
+
+        for (i, op) in ops.iter().enumerate() {
+            assert_eq!(
+                stream.apply(&mut state, op).unwrap(),
+                Some(OpCursor(i as u64 + 1))
+            );
+        }
+        // Redelivered by sync: applied and streamed only once
+        assert_eq!(stream.apply(&mut state, &ops[1]).unwrap(), None);
+        assert_eq!(stream.record(&ops[2]).unwrap(), None);
+        assert_eq!(stream.head(), OpCursor(4));
+
+        let since = stream.ops_since(OpCursor(2), 10).unwrap();
+        assert_eq!(since.len(), 2);
+        assert_eq!(since[0].cursor, OpCursor(3));
+        assert_eq!(since[0].bytes, ops[2].to_bytes().unwrap());
+        assert_eq!(since[1].decode().unwrap().op_id, ops[3].op_id);
+        assert_eq!(stream.ops_since(OpCursor(1), 1).unwrap().len(), 1);
+        assert!(stream.ops_since(stream.head(), 10).unwrap().is_empty());
+        assert!(matches!(
+            stream.ops_since(OpCursor(5), 10),
+            Err(StreamError::CursorAhead { cursor: 5, head: 4 })
+        ));
+
+        // A replica rebuilt from the streamed bytes converges
+        let replayed: Vec<OpEnvelope> = stream
+            .ops_since(OpCursor::START, 10)
+            .unwrap()
+            .iter()
+            .map(|s| s.decode().unwrap())
+            .collect();
+        let replica = GroupState::rebuild_from_ops(gid, &replayed).unwrap();
+        assert_eq!(replica.state_hash(), state.state_hash());
+
+        let (foreign, _) = make_ops(0);
+        assert!(matches!(
+            stream.record(&foreign[0]),
+            Err(StreamError::WrongGroup)
+        ));
+    }
+
+    #[test]
+    fn test_subscription_redelivers_until_acked_then_trims() {
+        let (ops, gid) = make_ops(3);
+        let mut state = GroupState::new(gid);
+        let mut stream = OpStream::new(gid);
+        stream.apply(&mut state, &ops[0]).unwrap();
+
+        let disk = stream.subscribe(OpCursor::START).unwrap();
+        let live = stream.subscribe(stream.head()).unwrap();
+        for op in &ops[1..] {
+            stream.apply(&mut state, op).unwrap();
+        }
+
+        // Not acked (crash before the write landed): handed out again
+        let first = stream.pending(disk, 2).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(stream.pending(disk, 2).unwrap(), first);
+        stream.ack(disk, first[1].cursor).unwrap();
+        assert_eq!(stream.pending(disk, 10).unwrap()[0].cursor, OpCursor(3));
+        assert_eq!(stream.pending(live, 10).unwrap().len(), 3);
+
+        // Entry 1 is acked by both; entry 2 still waits for `live`
+        assert_eq!(stream.retained(), 3);
+        assert!(matches!(
+            stream.ops_since(OpCursor::START, 10),
+            Err(StreamError::Truncated {
+                cursor: 0,
+                oldest: 2
+            })
+        ));
+        stream.ack(live, OpCursor(4)).unwrap();
+        stream.ack(live, OpCursor(2)).unwrap();
+        assert_eq!(stream.acked(live).unwrap(), OpCursor(4));
+        assert_eq!(stream.retained(), 2);
+
+        // START subscribes at the oldest retained entry, not before it
+        let late = stream.subscribe(OpCursor::START).unwrap();
+        assert_eq!(stream.acked(late).unwrap(), OpCursor(2));
+        assert_eq!(stream.pending(late, 10).unwrap()[0].cursor, OpCursor(3));
+        stream.unsubscribe(late);
+
+        stream.unsubscribe(disk);
+        assert_eq!(stream.retained(), 0);
+        // Dedup memory goes with the entries
+        assert!(stream.retained_ids.is_empty());
+        let fresh = stream.subscribe(OpCursor::START).unwrap();
+        assert!(stream.pending(fresh, 10).unwrap().is_empty());
+        assert!(matches!(
+            stream.pending(disk, 10),
+            Err(StreamError::UnknownSubscription(0))
+        ));
+        assert!(matches!(
+            stream.ack(live, OpCursor(9)),
+            Err(StreamError::CursorAhead { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resume_continues_after_persisted_cursor() {
+        let (ops, gid) = make_ops(2);
+        let mut state = GroupState::new(gid);
+        let mut stream = OpStream::new(gid);
+        stream.apply(&mut state, &ops[0]).unwrap();
+        stream.apply(&mut state, &ops[1]).unwrap();
+        let stored: Vec<StreamedOp> = stream.ops_since(OpCursor::START, 10).unwrap();
+
+        // Restart: state rebuilt from disk, numbering picks up at the head
+        let replayed: Vec<OpEnvelope> = stored.iter().map(|s| s.decode().unwrap()).collect();
+        let mut state = GroupState::rebuild_from_ops(gid, &replayed).unwrap();
+        let mut stream = OpStream::resume(gid, OpCursor(2));
+        assert_eq!(stream.apply(&mut state, &ops[1]).unwrap(), None);
+        assert_eq!(
+            stream.apply(&mut state, &ops[2]).unwrap(),
+            Some(OpCursor(3))
+        );
+
+        let sub = stream.subscribe(OpCursor(2)).unwrap();
+        let pending = stream.pending(sub, 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].op_id, ops[2].op_id);
+        assert!(matches!(
+            stream.subscribe(OpCursor(1)),
+            Err(StreamError::Truncated { .. })
+        ));
+    }
+}
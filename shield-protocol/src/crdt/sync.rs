@@ -0,0 +1,320 @@
+/// Sync handshake — state-hash short-circuit before digest exchange.
+///
+/// A sync round starts with each side sending a `SyncHello` carrying its
+/// `state_hash()`. Equal hashes mean both peers already render the same group
+/// and the round ends with no further traffic (the common case for idle
+/// groups). Otherwise the peers fall back to exchanging `SyncDigest`s — the
+/// per-author max lamport vector — and each sends the ops the other is missing.
+///
+/// `state_hash` covers derived state rather than the raw op set, but that
+/// state includes everything an applied op leaves for later ops to act on:
+/// receipts held for messages not seen yet, edits that lost LWW and the base
+/// each edit was made on. Peers holding different ops of that kind do not
+/// match. What two matching peers can still differ in are ops permanently
+/// overridden by an op both hold (a dominated RoleSet, an overwritten
+/// metadata value); no later op can bring those back, so skipping them is
+/// safe.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::crdt::apply::GroupState;
+use crate::crdt::ids::{DeviceID, GroupID};
+use crate::crdt::ops::{OpEnvelope, OpError};
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("Sync message targets wrong group")]
+    WrongGroup,
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+// ---------------------------------------------------------------------------
+// Messages
+// ---------------------------------------------------------------------------
+
+/// First message of a sync round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SyncHello {
+    pub group_id: GroupID,
+    pub state_hash: [u8; 32],
+    /// Informational — lets the UI show which side is behind.
+    pub op_count: u64,
+}
+
+/// Fallback digest: highest lamport seen from each author.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SyncDigest {
+    pub group_id: GroupID,
+    pub per_author_lamport: BTreeMap<DeviceID, u64>,
+}
+
+/// What to do after receiving the peer's `SyncHello`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncStep {
+    /// States match — end the round.
+    InSync,
+    /// States differ — send this digest to the peer.
+    ExchangeDigest(SyncDigest),
+}
+
+impl SyncHello {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+}
+
+impl SyncDigest {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OpError> {
+        bincode::serialize(self).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OpError> {
+        bincode::deserialize(bytes).map_err(|e| OpError::BincodeError(e.to_string()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Handshake
+// ---------------------------------------------------------------------------
+
+/// Build the opening `SyncHello` for a group.
+pub fn sync_hello(state: &GroupState) -> SyncHello {
+    SyncHello {
+        group_id: state.group_id,
+        state_hash: state.state_hash(),
+        op_count: state.op_count as u64,
+    }
+}
+
+/// Build the per-author digest for a group.
+pub fn sync_digest(state: &GroupState) -> SyncDigest {
+    SyncDigest {
+        group_id: state.group_id,
+        per_author_lamport: state.max_lamport.clone(),
+    }
+}
+
+/// Compare the peer's hello against local state.
+///
+/// `InSync` means neither side holds an op that could still change the
+/// other's state, not that both hold the same ops.
+pub fn on_sync_hello(state: &GroupState, remote: &SyncHello) -> Result<SyncStep, SyncError> {
+    if remote.group_id != state.group_id {
+        return Err(SyncError::WrongGroup);
+    }
+    if remote.state_hash == state.state_hash() {
+        Ok(SyncStep::InSync)
+    } else {
+        Ok(SyncStep::ExchangeDigest(sync_digest(state)))
+    }
+}
+
+/// Ops from `local_ops` the peer has not seen, according to its digest.
+///
+/// An op is sent when its lamport exceeds the peer's max for that author
+/// (or the peer has never seen the author). Returned in `(lamport, op_id)`
+/// order so the peer can apply them directly.
+pub fn ops_missing_from_peer(
+    local_ops: &[OpEnvelope],
+    remote: &SyncDigest,
+) -> Result<Vec<OpEnvelope>, SyncError> {
+    let mut missing = Vec::new();
+    for op in local_ops {
+        if op.group_id != remote.group_id {
+            return Err(SyncError::WrongGroup);
+        }
+        let seen = remote.per_author_lamport.get(&op.op_id.author).copied();
+        if !matches!(seen, Some(max) if op.lamport <= max) {
+            missing.push(op.clone());
+        }
+    }
+    missing.sort_by(|a, b| {
+        a.lamport
+            .cmp(&b.lamport)
+            .then_with(|| a.op_id.cmp(&b.op_id))
+    });
+    Ok(missing)
+}
+
+/// Authors the peer is ahead on, with our current max lamport for each
+/// (request ops after that lamport).
+pub fn authors_behind(local: &SyncDigest, remote: &SyncDigest) -> Vec<(DeviceID, u64)> {
+    remote
+        .per_author_lamport
+        .iter()
+        .filter_map(|(author, remote_max)| {
+            let local_max = local.per_author_lamport.get(author).copied().unwrap_or(0);
+            (*remote_max > local_max).then_some((*author, local_max))
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::ops::{
+        GroupCreatePayload, MsgAddPayload, MsgEditPayload, OpType, ReceiptSetPayload, ReceiptStatus,
+    };
+
+    fn keypair() -> ([u8; 32], [u8; 32]) {
+        crate::crypto::signing::generate_keypair()
+    }
+
+    fn signed<P: Serialize>(
+        gid: GroupID,
+        op_type: OpType,
+        payload: &P,
+        lamport: u64,
+        (pub_k, priv_k): &([u8; 32], [u8; 32]),
+    ) -> OpEnvelope {
+        OpEnvelope::create_signed(gid, op_type, payload, lamport, lamport, *pub_k, priv_k).unwrap()
+    }
+
+    /// GroupCreate + `n` messages from the owner, and the owner's keys.
+    fn make_ops(n: u64) -> (Vec<OpEnvelope>, GroupID, ([u8; 32], [u8; 32])) {
+        let (pub_k, priv_k) = keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&pub_k), &[0x5C; 32]);
+        let create = GroupCreatePayload {
+            group_name: "Sync".into(),
+            encrypted_group_secret: vec![1],
+        };
+        let mut ops = vec![OpEnvelope::create_signed(
+            gid,
+            OpType::GroupCreate,
+            &create,
+            1,
+            1,
+            pub_k,
+            &priv_k,
+        )
+        .unwrap()];
+        for lamport in 2..2 + n {
+            let payload = MsgAddPayload {
+                msg_id: [lamport as u8; 32],
+                ciphertext: vec![lamport as u8],
+                nonce: [0; 24],
+            };
+            ops.push(
+                OpEnvelope::create_signed(
+                    gid,
+                    OpType::MsgAdd,
+                    &payload,
+                    lamport,
+                    lamport,
+                    pub_k,
+                    &priv_k,
+                )
+                .unwrap(),
+            );
+        }
+        (ops, gid, (pub_k, priv_k))
+    }
+
+    #[test]
+    fn test_matching_hash_short_circuits() {
+        let (ops, gid, _) = make_ops(3);
+        let a = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let b = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+
+        let hello = SyncHello::from_bytes(&sync_hello(&b).to_bytes().unwrap()).unwrap();
+        assert_eq!(on_sync_hello(&a, &hello).unwrap(), SyncStep::InSync);
+    }
+
+    #[test]
+    fn test_divergent_states_fall_back_to_digest() {
+        let (ops, gid, _) = make_ops(5);
+        let full = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let mut behind = GroupState::rebuild_from_ops(gid, &ops[..3]).unwrap();
+
+        let digest = match on_sync_hello(&full, &sync_hello(&behind)).unwrap() {
+            SyncStep::ExchangeDigest(d) => d,
+            SyncStep::InSync => panic!("states differ"),
+        };
+        assert_eq!(authors_behind(&sync_digest(&behind), &digest).len(), 1);
+
+        let missing = ops_missing_from_peer(&ops, &sync_digest(&behind)).unwrap();
+        assert_eq!(missing.len(), 3);
+        for op in &missing {
+            behind.apply_op(op).unwrap();
+        }
+        assert_eq!(
+            on_sync_hello(&full, &sync_hello(&behind)).unwrap(),
+            SyncStep::InSync
+        );
+    }
+
+    #[test]
+    fn test_pending_and_losing_ops_are_not_in_sync() {
+        let (ops, gid, owner) = make_ops(1);
+        let msg_id = [2u8; 32];
+        let create_op = ops[1].op_id;
+        let state_with = |extra: &[OpEnvelope]| {
+            let all: Vec<OpEnvelope> = ops.iter().chain(extra).cloned().collect();
+            GroupState::rebuild_from_ops(gid, &all).unwrap()
+        };
+
+        // A receipt held for a message neither peer has received
+        let receipt = ReceiptSetPayload {
+            msg_id: [0xEE; 32],
+            status: ReceiptStatus::Read,
+        };
+        let receipt = signed(gid, OpType::ReceiptSet, &receipt, 3, &owner);
+        let plain = state_with(&[]);
+        assert!(matches!(
+            on_sync_hello(&plain, &sync_hello(&state_with(&[receipt]))).unwrap(),
+            SyncStep::ExchangeDigest(_)
+        ));
+
+        // Concurrent edits of the original: both peers show the winner, only
+        // one of them also holds the edit it beat
+        let edit = |byte: u8, lamport| {
+            let payload = MsgEditPayload {
+                msg_id,
+                new_ciphertext: vec![byte],
+                nonce: [byte; 24],
+                base: Some(create_op),
+            };
+            signed(gid, OpType::MsgEdit, &payload, lamport, &owner)
+        };
+        let winner_only = state_with(&[edit(0xB2, 5)]);
+        let with_loser = state_with(&[edit(0xB2, 5), edit(0xA1, 4)]);
+        let shown = |s: &GroupState| s.messages.get_message(&msg_id).unwrap().ciphertext.clone();
+        assert_eq!(shown(&winner_only), shown(&with_loser));
+        assert!(matches!(
+            on_sync_hello(&winner_only, &sync_hello(&with_loser)).unwrap(),
+            SyncStep::ExchangeDigest(_)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_group_rejected() {
+        let (ops, gid, _) = make_ops(1);
+        let (other_ops, other_gid, _) = make_ops(1);
+        let state = GroupState::rebuild_from_ops(gid, &ops).unwrap();
+        let other = GroupState::rebuild_from_ops(other_gid, &other_ops).unwrap();
+
+        assert!(matches!(
+            on_sync_hello(&state, &sync_hello(&other)),
+            Err(SyncError::WrongGroup)
+        ));
+        assert!(matches!(
+            ops_missing_from_peer(&ops, &sync_digest(&other)),
+            Err(SyncError::WrongGroup)
+        ));
+    }
+}
@@ -0,0 +1,338 @@
+/// Authoring ops against a loaded group.
+///
+/// `GroupWriter` is bound to the author's view of a `GroupState`. Before
+/// anything is signed it checks that the author may write the op at all
+/// (`MembershipState::can_author_op`), that the group is not at its hard
+/// op cap and, for invites, that the author has invite quota left, so
+/// illegal ops fail here instead of being broadcast and rejected
+/// by every peer's apply engine. Lamport and nonce come from the group's
+/// `LamportClock`; message IDs are derived from them.
+///
+/// Checks that depend on the target (editing someone else's message,
+/// kicking a higher role) still happen at apply time.
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::crdt::apply::GroupState;
+use crate::crdt::clock::{ClockError, ClockStore, LamportClock};
+use crate::crdt::ids::DeviceID;
+use crate::crdt::limits::{check_op_limits, OpLimitStatus};
+use crate::crdt::messages::MessageEntry;
+use crate::crdt::ops::{
+    group_msg_id, now_ms, MetadataKey, MetadataSetPayload, MsgAddPayload, MsgDeletePayload,
+    MsgEditPayload, OpEnvelope, OpError, OpType, ReactionSetPayload, ReceiptSetPayload,
+    ReceiptStatus,
+};
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug)]
+pub enum WriterError {
+    #[error("Author cannot write {0:?} in this group")]
+    Unauthorized(OpType),
+
+    #[error("{0:?} cannot be written through GroupWriter")]
+    Unsupported(OpType),
+
+    #[error("Hard op limit reached — only membership ops allowed")]
+    OpLimitReached,
+
+    #[error("Invite quota for this epoch used up")]
+    InviteQuotaExceeded,
+
+    #[error("Clock belongs to a different group")]
+    WrongGroup,
+
+    #[error("Clock error: {0}")]
+    Clock(#[from] ClockError),
+
+    #[error("Op error: {0}")]
+    Op(#[from] OpError),
+}
+
+/// Creates signed, ready-to-broadcast ops for one author in one group.
+pub struct GroupWriter<'a> {
+    state: &'a GroupState,
+    clock: &'a mut LamportClock,
+    store: &'a mut dyn ClockStore,
+    author_pubkey: [u8; 32],
+    author_privkey: &'a [u8; 32],
+}
+
+impl<'a> GroupWriter<'a> {
+    pub fn new(
+        state: &'a GroupState,
+        clock: &'a mut LamportClock,
+        store: &'a mut dyn ClockStore,
+        author_pubkey: [u8; 32],
+        author_privkey: &'a [u8; 32],
+    ) -> Self {
+        Self {
+            state,
+            clock,
+            store,
+            author_pubkey,
+            author_privkey,
+        }
+    }
+
+    pub fn author(&self) -> DeviceID {
+        DeviceID::from_pubkey(&self.author_pubkey)
+    }
+
+    /// Whether the author may currently write `op_type` (e.g. to grey out
+    /// UI actions). `write` runs the same check.
+    pub fn can_write(&self, op_type: OpType) -> bool {
+        self.check(op_type).is_ok()
+    }
+
+    fn check(&self, op_type: OpType) -> Result<(), WriterError> {
+        // GroupCreate starts a state rather than extending one; AnonMsgAdd
+        // must not carry the device key (see `anonymous::create_anon_msg_add`).
+        if matches!(op_type, OpType::GroupCreate | OpType::AnonMsgAdd) {
+            return Err(WriterError::Unsupported(op_type));
+        }
+        if self.clock.group_id() != self.state.group_id {
+            return Err(WriterError::WrongGroup);
+        }
+        if !op_type.is_membership_op() {
+            if let OpLimitStatus::HardCapReached = check_op_limits(self.state.op_count) {
+                return Err(WriterError::OpLimitReached);
+            }
+        }
+        if !self
+            .state
+            .membership
+            .can_author_op(&self.author(), &op_type)
+        {
+            return Err(WriterError::Unauthorized(op_type));
+        }
+        if op_type == OpType::MemberInvite
+            && self.state.invites.remaining(&self.author(), now_ms()) == 0
+        {
+            return Err(WriterError::InviteQuotaExceeded);
+        }
+        Ok(())
+    }
+
+    /// Check, stamp and sign an op with an explicit payload. `payload` must
+    /// be the payload type that belongs to `op_type`.
+    pub fn write<P: Serialize>(
+        &mut self,
+        op_type: OpType,
+        payload: &P,
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        self.check(op_type)?;
+        self.clock.observe_state(self.state)?;
+        let builder = self.clock.op_builder(op_type, self.store, rng)?;
+        Ok(builder.sign(payload, self.author_pubkey, self.author_privkey)?)
+    }
+
+    /// Post a message; its `msg_id` is derived from the op's lamport and
+    /// nonce.
+    pub fn msg_add(
+        &mut self,
+        ciphertext: Vec<u8>,
+        nonce: [u8; 24],
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        self.check(OpType::MsgAdd)?;
+        self.clock.observe_state(self.state)?;
+        let builder = self.clock.op_builder(OpType::MsgAdd, self.store, rng)?;
+        let payload = MsgAddPayload {
+            msg_id: group_msg_id(
+                &self.state.group_id,
+                &self.author_pubkey,
+                builder.lamport(),
+                builder.nonce(),
+            ),
+            ciphertext,
+            nonce,
+        };
+        Ok(builder.sign(&payload, self.author_pubkey, self.author_privkey)?)
+    }
+
+    /// Edit a message, naming the version on screen as the edit's base.
+    pub fn msg_edit(
+        &mut self,
+        msg_id: [u8; 32],
+        new_ciphertext: Vec<u8>,
+        nonce: [u8; 24],
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        let payload = MsgEditPayload {
+            msg_id,
+            new_ciphertext,
+            nonce,
+            base: self
+                .state
+                .messages
+                .get_message(&msg_id)
+                .map(MessageEntry::current_version),
+        };
+        self.write(OpType::MsgEdit, &payload, rng)
+    }
+
+    pub fn msg_delete(
+        &mut self,
+        msg_id: [u8; 32],
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        self.write(OpType::MsgDelete, &MsgDeletePayload { msg_id }, rng)
+    }
+
+    pub fn react(
+        &mut self,
+        msg_id: [u8; 32],
+        emoji: &str,
+        present: bool,
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        let payload = ReactionSetPayload {
+            msg_id,
+            emoji: emoji.to_string(),
+            present,
+        };
+        self.write(OpType::ReactionSet, &payload, rng)
+    }
+
+    pub fn receipt(
+        &mut self,
+        msg_id: [u8; 32],
+        status: ReceiptStatus,
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        self.write(
+            OpType::ReceiptSet,
+            &ReceiptSetPayload { msg_id, status },
+            rng,
+        )
+    }
+
+    pub fn set_metadata(
+        &mut self,
+        key: MetadataKey,
+        value: Vec<u8>,
+        rng: &mut impl SecureRng,
+    ) -> Result<OpEnvelope, WriterError> {
+        self.write(OpType::MetadataSet, &MetadataSetPayload { key, value }, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::clock::MemoryClockStore;
+    use crate::crdt::ids::GroupID;
+    use crate::crdt::ops::{GroupCreatePayload, MemberAcceptPayload, MemberInvitePayload, Role};
+    use crate::rng::seeded;
+
+    /// Owner creates the group and admits a read-only member.
+    fn setup() -> (GroupState, [u8; 32], [u8; 32], [u8; 32], [u8; 32]) {
+        let (owner_pub, owner_priv) = crate::crypto::signing::generate_keypair();
+        let (reader_pub, reader_priv) = crate::crypto::signing::generate_keypair();
+        let gid = GroupID::new(&DeviceID::from_pubkey(&owner_pub), &[7; 32]);
+        let mut state = GroupState::new(gid);
+
+        let create = OpEnvelope::create_signed(
+            gid,
+            OpType::GroupCreate,
+            &GroupCreatePayload {
+                group_name: "g".into(),
+                encrypted_group_secret: vec![],
+            },
+            1,
+            1,
+            owner_pub,
+            &owner_priv,
+        )
+        .unwrap();
+        let invite = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberInvite,
+            &MemberInvitePayload {
+                invited_device_id: DeviceID::from_pubkey(&reader_pub),
+                invited_pubkey: reader_pub,
+                role: Role::ReadOnly,
+                encrypted_group_secret: vec![],
+            },
+            2,
+            2,
+            owner_pub,
+            &owner_priv,
+        )
+        .unwrap();
+        let accept = OpEnvelope::create_signed(
+            gid,
+            OpType::MemberAccept,
+            &MemberAcceptPayload {
+                invite_op_id: invite.op_id,
+                attribute_proof: None,
+            },
+            3,
+            3,
+            reader_pub,
+            &reader_priv,
+        )
+        .unwrap();
+        for op in [create, invite, accept] {
+            state.apply_op(&op).unwrap();
+        }
+        (state, owner_pub, owner_priv, reader_pub, reader_priv)
+    }
+
+    #[test]
+    fn test_written_ops_apply_cleanly() {
+        let (mut state, owner_pub, owner_priv, _, _) = setup();
+        let mut store = MemoryClockStore::default();
+        let mut clock = LamportClock::open(state.group_id, &store).unwrap();
+        let mut rng = seeded(1);
+
+        let post = GroupWriter::new(&state, &mut clock, &mut store, owner_pub, &owner_priv)
+            .msg_add(vec![1, 2, 3], [0; 24], &mut rng)
+            .unwrap();
+        assert_eq!(post.lamport, 4);
+        state.apply_op(&post).unwrap();
+        let msg_id = post.decode_payload::<MsgAddPayload>().unwrap().msg_id;
+
+        let mut writer = GroupWriter::new(&state, &mut clock, &mut store, owner_pub, &owner_priv);
+        let react = writer.react(msg_id, "+1", true, &mut rng).unwrap();
+        let name = writer
+            .set_metadata(MetadataKey::Name, b"renamed".to_vec(), &mut rng)
+            .unwrap();
+        assert!(react.lamport < name.lamport);
+        for op in [react, name] {
+            assert!(state.apply_op(&op).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_unauthorized_ops_rejected_before_signing() {
+        let (state, _, _, reader_pub, reader_priv) = setup();
+        let mut store = MemoryClockStore::default();
+        let mut clock = LamportClock::open(state.group_id, &store).unwrap();
+        let mut rng = seeded(2);
+        let mut writer = GroupWriter::new(&state, &mut clock, &mut store, reader_pub, &reader_priv);
+
+        assert!(writer.can_write(OpType::ReceiptSet));
+        assert!(!writer.can_write(OpType::MsgAdd));
+        assert!(matches!(
+            writer.msg_add(vec![1], [0; 24], &mut rng),
+            Err(WriterError::Unauthorized(OpType::MsgAdd))
+        ));
+        assert!(matches!(
+            writer.set_metadata(MetadataKey::Topic, vec![], &mut rng),
+            Err(WriterError::Unauthorized(OpType::MetadataSet))
+        ));
+        assert!(matches!(
+            writer.write(OpType::GroupCreate, &(), &mut rng),
+            Err(WriterError::Unsupported(OpType::GroupCreate))
+        ));
+
+        // Rejected writes do not consume lamports.
+        assert!(writer
+            .receipt([0; 32], ReceiptStatus::Read, &mut rng)
+            .is_ok());
+        assert_eq!(clock.current(), 4);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::protocol::contact_id::ContactId;
 use once_cell::sync::Lazy;
 use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
@@ -12,93 +13,118 @@ pub struct AckState {
     pub processed_acks: HashSet<u8>,
 }
 
-/// Global ACK state manager
-static ACK_STATES: Lazy<Mutex<HashMap<String, AckState>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
-
 /// ACK type constants
 pub const ACK_TYPE_PING: u8 = 0;
 pub const ACK_TYPE_PONG: u8 = 1;
 pub const ACK_TYPE_MESSAGE: u8 = 2;
 
-/// Validate ACK ordering and update state with idempotent handling
-///
-/// Returns true if ACK is valid (new or duplicate)
-/// Returns false only for truly invalid ACKs
-///
-/// IDEMPOTENCY: Duplicates return true (ACK was successfully processed previously)
-/// FORWARD PROGRESS: Out-of-order ACKs are allowed if they advance state forward
-///
-/// # Arguments
-/// * `contact_id` - Contact identifier
-/// * `ack_type` - Type of ACK (0=PING_ACK, 1=PONG_ACK, 2=MESSAGE_ACK)
-pub fn validate_and_record_ack(contact_id: &str, ack_type: u8) -> bool {
-    let mut states = ACK_STATES.lock().unwrap();
-    let state = states.entry(contact_id.to_string()).or_default();
-
-    // Guard 1: Handle duplicate ACKs (idempotency)
-    if state.processed_acks.contains(&ack_type) {
-        log::debug!("Duplicate ACK (idempotent) for contact {} type {} - already processed, returning success", contact_id, ack_type);
-        return true;
+/// ACK states of all contacts of one profile
+#[derive(Debug, Default)]
+pub struct AckStates {
+    states: HashMap<ContactId, AckState>,
+}
+
+impl AckStates {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    match ack_type {
-        ACK_TYPE_PING => {
-            // PING_ACK always valid (first ACK in sequence)
-            state.ping_ack_received = true;
-            state.processed_acks.insert(ACK_TYPE_PING);
-            log::info!("PING_ACK accepted for contact {}", contact_id);
-            true
+    /// Validate ACK ordering and update state with idempotent handling
+    ///
+    /// Returns true if ACK is valid (new or duplicate)
+    /// Returns false only for truly invalid ACKs
+    ///
+    /// IDEMPOTENCY: Duplicates return true (ACK was successfully processed previously)
+    /// FORWARD PROGRESS: Out-of-order ACKs are allowed if they advance state forward
+    ///
+    /// # Arguments
+    /// * `contact_id` - Canonical contact identifier
+    /// * `ack_type` - Type of ACK (0=PING_ACK, 1=PONG_ACK, 2=MESSAGE_ACK)
+    pub fn validate_and_record(&mut self, contact_id: &ContactId, ack_type: u8) -> bool {
+        let state = self.states.entry(*contact_id).or_default();
+
+        // Guard 1: Handle duplicate ACKs (idempotency)
+        if state.processed_acks.contains(&ack_type) {
+            log::debug!("Duplicate ACK (idempotent) for contact {} type {} - already processed, returning success", contact_id, ack_type);
+            return true;
         }
-        ACK_TYPE_PONG => {
-            // PONG_ACK normally requires PING_ACK first, but allow forward progress after circuit churn
-            if !state.ping_ack_received {
-                log::warn!("Out-of-order PONG_ACK allowed (forward progress) for contact {} - PING_ACK not received", contact_id);
+
+        match ack_type {
+            ACK_TYPE_PING => {
+                // PING_ACK always valid (first ACK in sequence)
+                state.ping_ack_received = true;
+                state.processed_acks.insert(ACK_TYPE_PING);
+                log::info!("PING_ACK accepted for contact {}", contact_id);
+                true
             }
-            state.pong_ack_received = true;
-            state.processed_acks.insert(ACK_TYPE_PONG);
-            log::info!("PONG_ACK accepted for contact {}", contact_id);
-            true
-        }
-        ACK_TYPE_MESSAGE => {
-            // MESSAGE_ACK normally requires PONG_ACK first, but allow forward progress after circuit churn
-            if !state.pong_ack_received {
-                log::warn!("Out-of-order MESSAGE_ACK allowed (forward progress) for contact {} - PONG_ACK not received", contact_id);
+            ACK_TYPE_PONG => {
+                // PONG_ACK normally requires PING_ACK first, but allow forward progress after circuit churn
+                if !state.ping_ack_received {
+                    log::warn!("Out-of-order PONG_ACK allowed (forward progress) for contact {} - PING_ACK not received", contact_id);
+                }
+                state.pong_ack_received = true;
+                state.processed_acks.insert(ACK_TYPE_PONG);
+                log::info!("PONG_ACK accepted for contact {}", contact_id);
+                true
+            }
+            ACK_TYPE_MESSAGE => {
+                // MESSAGE_ACK normally requires PONG_ACK first, but allow forward progress after circuit churn
+                if !state.pong_ack_received {
+                    log::warn!("Out-of-order MESSAGE_ACK allowed (forward progress) for contact {} - PONG_ACK not received", contact_id);
+                }
+                state.message_ack_received = true;
+                state.processed_acks.insert(ACK_TYPE_MESSAGE);
+                log::info!("MESSAGE_ACK accepted for contact {}", contact_id);
+                true
+            }
+            _ => {
+                log::error!("Invalid ACK type: {}", ack_type);
+                false
             }
-            state.message_ack_received = true;
-            state.processed_acks.insert(ACK_TYPE_MESSAGE);
-            log::info!("MESSAGE_ACK accepted for contact {}", contact_id);
-            true
-        }
-        _ => {
-            log::error!("Invalid ACK type: {}", ack_type);
-            false
         }
     }
+
+    /// Reset ACK state for a contact (e.g., after completing a message exchange)
+    pub fn reset(&mut self, contact_id: &ContactId) {
+        self.states.remove(contact_id);
+        log::debug!("ACK state reset for contact {}", contact_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
+
+/// Global ACK state manager
+static ACK_STATES: Lazy<Mutex<AckStates>> = Lazy::new(|| Mutex::new(AckStates::new()));
+
+/// [`AckStates::validate_and_record`] against the process-wide states
+pub fn validate_and_record_ack(contact_id: &ContactId, ack_type: u8) -> bool {
+    ACK_STATES
+        .lock()
+        .unwrap()
+        .validate_and_record(contact_id, ack_type)
 }
 
 /// Reset ACK state for a contact (e.g., after completing a message exchange)
-pub fn reset_ack_state(contact_id: &str) {
-    let mut states = ACK_STATES.lock().unwrap();
-    states.remove(contact_id);
-    log::debug!("ACK state reset for contact {}", contact_id);
+pub fn reset_ack_state(contact_id: &ContactId) {
+    ACK_STATES.lock().unwrap().reset(contact_id);
 }
 
-/// Clear all ACK states (for testing)
-#[cfg(test)]
+/// Clear all ACK states (panic wipe, tests)
 pub fn clear_ack_states() {
-    let mut states = ACK_STATES.lock().unwrap();
-    states.clear();
+    ACK_STATES.lock().unwrap().clear();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::contact_id::test_contact;
 
     #[test]
     fn test_ack_ordering() {
         clear_ack_states();
-        let contact = "test_contact";
+        let contact = &test_contact(1);
 
         // PING_ACK should always be valid
         assert!(validate_and_record_ack(contact, ACK_TYPE_PING));
@@ -113,13 +139,13 @@ mod tests {
     #[test]
     fn test_ack_out_of_order_allowed() {
         clear_ack_states();
-        let contact = "test_contact2";
+        let contact = &test_contact(2);
 
         // MESSAGE_ACK should be ALLOWED (forward progress) even without PONG_ACK
         assert!(validate_and_record_ack(contact, ACK_TYPE_MESSAGE));
 
         clear_ack_states();
-        let contact2 = "test_contact3";
+        let contact2 = &test_contact(3);
 
         // PONG_ACK should be ALLOWED (forward progress) even without PING_ACK
         assert!(validate_and_record_ack(contact2, ACK_TYPE_PONG));
@@ -128,7 +154,7 @@ mod tests {
     #[test]
     fn test_ack_idempotency() {
         clear_ack_states();
-        let contact = "test_contact_dup";
+        let contact = &test_contact(4);
 
         // First PING_ACK should be accepted
         assert!(validate_and_record_ack(contact, ACK_TYPE_PING));
@@ -7,24 +7,37 @@
 /// 1. **Password-only**: Encrypt identity seed with Argon2id-derived key
 /// 2. **Social recovery**: Split seed into N shares, requiring K-of-N to reconstruct
 /// 3. **Combined**: Encrypt the seed, then split the encryption key into shares
+/// 4. **Device-wrapped**: Encrypt the seed under a random content key and
+///    wrap that key to the passphrase and/or to other devices' hybrid
+///    (X25519 + ML-KEM-1024) public keys, so those devices restore without
+///    the passphrase
 ///
 /// The backup blob format:
 /// ```text
-/// [version: 1][salt: 16][nonce: 24][ciphertext][tag: 16]
+/// password-only:  [version: 1][salt: 16][nonce: 24][ciphertext][tag: 16]
+/// device-wrapped: [version: 2][bincode(WrappedBackup)]
 /// ```
 ///
 /// Social recovery uses a simple (K, N) threshold scheme over GF(256).
 use crate::crypto::encryption;
+use crate::crypto::pqc::{
+    hybrid_decapsulate, hybrid_encapsulate_with_rng, HybridKEMKeypair, MLKEM1024_CT_BYTES,
+    MLKEM1024_EK_BYTES,
+};
+use crate::rng::{OsRng, SecureRng};
 use argon2::{Algorithm, Argon2, Params, Version};
-use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use zeroize::Zeroize;
 
 const BACKUP_VERSION: u8 = 0x01;
+const WRAPPED_BACKUP_VERSION: u8 = 0x02;
+/// Domain separation for the key that wraps the content key to a device.
+const DEVICE_WRAP_INFO: &[u8] = b"ShieldMessenger-BackupWrap-v1";
 const SALT_SIZE: usize = 16;
-const ARGON2_MEM_COST: u32 = 65536; // 64 MiB
-const ARGON2_TIME_COST: u32 = 4;
-const ARGON2_PARALLELISM: u32 = 2;
+pub(crate) const ARGON2_MEM_COST: u32 = 65536; // 64 MiB
+pub(crate) const ARGON2_TIME_COST: u32 = 4;
+pub(crate) const ARGON2_PARALLELISM: u32 = 2;
 
 #[derive(Error, Debug)]
 pub enum BackupError {
@@ -44,6 +57,12 @@ pub enum BackupError {
     NotEnoughShares { have: usize, need: usize },
     #[error("Share reconstruction failed")]
     ReconstructionFailed,
+    #[error("Backup needs a password or at least one recipient")]
+    NoKeySlots,
+    #[error("Invalid recipient public key")]
+    InvalidRecipient,
+    #[error("Backup is not wrapped to this key")]
+    NotARecipient,
 }
 
 pub type Result<T> = std::result::Result<T, BackupError>;
@@ -54,6 +73,136 @@ pub struct BackupBlob {
     pub data: Vec<u8>,
 }
 
+/// A device a backup's content key is wrapped to: the public half of its
+/// hybrid KEM keypair
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupRecipient {
+    pub x25519_public: [u8; 32],
+    /// ML-KEM-1024 encapsulation key (1568 bytes)
+    pub mlkem_public: Vec<u8>,
+}
+
+impl BackupRecipient {
+    pub fn from_keypair(keypair: &HybridKEMKeypair) -> Self {
+        Self {
+            x25519_public: keypair.x25519_public,
+            mlkem_public: keypair.kyber_public.clone(),
+        }
+    }
+}
+
+/// Content key wrapped under the passphrase
+#[derive(Clone, Serialize, Deserialize)]
+struct PasswordSlot {
+    salt: [u8; SALT_SIZE],
+    /// `nonce ‖ XChaCha20-Poly1305(content key)`
+    wrapped_key: Vec<u8>,
+}
+
+/// Content key wrapped to one device
+#[derive(Clone, Serialize, Deserialize)]
+struct RecipientSlot {
+    x25519_ephemeral_public: [u8; 32],
+    kem_ciphertext: Vec<u8>,
+    /// `nonce ‖ XChaCha20-Poly1305(content key)`
+    wrapped_key: Vec<u8>,
+}
+
+/// Body of a device-wrapped backup blob
+#[derive(Clone, Serialize, Deserialize)]
+struct WrappedBackup {
+    password: Option<PasswordSlot>,
+    recipients: Vec<RecipientSlot>,
+    /// `nonce ‖ XChaCha20-Poly1305(secret)` under the content key
+    ciphertext: Vec<u8>,
+}
+
+impl WrappedBackup {
+    fn parse(backup: &BackupBlob) -> Result<Self> {
+        let (&version, body) = backup
+            .data
+            .split_first()
+            .ok_or(BackupError::InvalidFormat)?;
+        if version != WRAPPED_BACKUP_VERSION {
+            return Err(BackupError::InvalidVersion(version));
+        }
+        let wrapped: Self = bincode::deserialize(body).map_err(|_| BackupError::InvalidFormat)?;
+        if wrapped
+            .recipients
+            .iter()
+            .any(|r| r.kem_ciphertext.len() != MLKEM1024_CT_BYTES)
+        {
+            return Err(BackupError::InvalidFormat);
+        }
+        Ok(wrapped)
+    }
+
+    fn to_blob(&self) -> Result<BackupBlob> {
+        let body =
+            bincode::serialize(self).map_err(|e| BackupError::EncryptionFailed(e.to_string()))?;
+        let mut data = Vec::with_capacity(1 + body.len());
+        data.push(WRAPPED_BACKUP_VERSION);
+        data.extend_from_slice(&body);
+        Ok(BackupBlob { data })
+    }
+
+    /// Open the secret with an unwrapped content key
+    fn open(&self, content_key: &[u8]) -> Result<Vec<u8>> {
+        encryption::decrypt_message(&self.ciphertext, content_key)
+            .map_err(|e| BackupError::DecryptionFailed(e.to_string()))
+    }
+
+    /// The content key, from whichever recipient slot `keypair` opens
+    fn unwrap_for(&self, keypair: &HybridKEMKeypair) -> Result<Vec<u8>> {
+        for slot in &self.recipients {
+            // ML-KEM decapsulation never fails on a foreign ciphertext; the
+            // AEAD on the wrapped key is what tells slots apart
+            let Ok(mut shared) = hybrid_decapsulate(
+                &slot.x25519_ephemeral_public,
+                &slot.kem_ciphertext,
+                &keypair.x25519_secret,
+                &keypair.kyber_secret,
+            ) else {
+                continue;
+            };
+            let kek = encryption::derive_root_key(&shared, DEVICE_WRAP_INFO);
+            shared.zeroize();
+            let mut kek = kek.map_err(|_| BackupError::KeyDerivationFailed)?;
+            let content_key = encryption::decrypt_message(&slot.wrapped_key, &kek);
+            kek.zeroize();
+            if let Ok(content_key) = content_key {
+                return Ok(content_key);
+            }
+        }
+        Err(BackupError::NotARecipient)
+    }
+}
+
+/// Wrap `content_key` to one device
+fn wrap_to_recipient(
+    content_key: &[u8],
+    recipient: &BackupRecipient,
+    rng: &mut impl SecureRng,
+) -> Result<RecipientSlot> {
+    if recipient.mlkem_public.len() != MLKEM1024_EK_BYTES {
+        return Err(BackupError::InvalidRecipient);
+    }
+    let mut encapsulated =
+        hybrid_encapsulate_with_rng(&recipient.x25519_public, &recipient.mlkem_public, rng)
+            .map_err(|_| BackupError::InvalidRecipient)?;
+    let kek = encryption::derive_root_key(&encapsulated.shared_secret, DEVICE_WRAP_INFO);
+    encapsulated.shared_secret.zeroize();
+    let mut kek = kek.map_err(|_| BackupError::KeyDerivationFailed)?;
+    let wrapped_key = encryption::encrypt_message_with_rng(content_key, &kek, rng)
+        .map_err(|e| BackupError::EncryptionFailed(e.to_string()));
+    kek.zeroize();
+    Ok(RecipientSlot {
+        x25519_ephemeral_public: encapsulated.x25519_ephemeral_public,
+        kem_ciphertext: std::mem::take(&mut encapsulated.kyber_ciphertext),
+        wrapped_key: wrapped_key?,
+    })
+}
+
 /// A share of a secret for social recovery
 #[derive(Clone, Debug)]
 pub struct SecretShare {
@@ -91,12 +240,21 @@ fn derive_key_from_password(password: &str, salt: &[u8]) -> Result<[u8; 32]> {
 /// # Returns
 /// Encrypted backup blob
 pub fn create_encrypted_backup(secret: &[u8], password: &str) -> Result<BackupBlob> {
+    create_encrypted_backup_with_rng(secret, password, &mut OsRng)
+}
+
+/// [`create_encrypted_backup`] drawing its salt and nonce from `rng`.
+pub fn create_encrypted_backup_with_rng(
+    secret: &[u8],
+    password: &str,
+    rng: &mut impl SecureRng,
+) -> Result<BackupBlob> {
     let mut salt = [0u8; SALT_SIZE];
-    rand::rngs::OsRng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut salt);
 
     let mut key = derive_key_from_password(password, &salt)?;
 
-    let encrypted = encryption::encrypt_message(secret, &key)
+    let encrypted = encryption::encrypt_message_with_rng(secret, &key, rng)
         .map_err(|e| BackupError::EncryptionFailed(e.to_string()))?;
 
     key.zeroize();
@@ -124,6 +282,9 @@ pub fn restore_encrypted_backup(backup: &BackupBlob, password: &str) -> Result<V
     }
 
     let version = backup.data[0];
+    if version == WRAPPED_BACKUP_VERSION {
+        return restore_wrapped_backup_with_password(backup, password);
+    }
     if version != BACKUP_VERSION {
         return Err(BackupError::InvalidVersion(version));
     }
@@ -141,6 +302,142 @@ pub fn restore_encrypted_backup(backup: &BackupBlob, password: &str) -> Result<V
     Ok(secret)
 }
 
+/// Create a backup whose content key is wrapped to the password and/or to
+/// other devices
+///
+/// Any recipient restores with [`restore_wrapped_backup`] and its own hybrid
+/// keypair; the password, if given, still works through
+/// [`restore_encrypted_backup`]. Wrapping is post-quantum: an adversary who
+/// stores the blob today needs both X25519 and ML-KEM-1024 broken to read it.
+///
+/// # Arguments
+/// * `secret` - The secret data to backup
+/// * `password` - Optional backup password
+/// * `recipients` - Devices that may restore without the password
+///
+/// # Returns
+/// Encrypted backup blob
+pub fn create_wrapped_backup(
+    secret: &[u8],
+    password: Option<&str>,
+    recipients: &[BackupRecipient],
+) -> Result<BackupBlob> {
+    create_wrapped_backup_with_rng(secret, password, recipients, &mut OsRng)
+}
+
+/// [`create_wrapped_backup`] drawing the content key, salt, nonces and
+/// encapsulations from `rng`.
+pub fn create_wrapped_backup_with_rng(
+    secret: &[u8],
+    password: Option<&str>,
+    recipients: &[BackupRecipient],
+    rng: &mut impl SecureRng,
+) -> Result<BackupBlob> {
+    if password.is_none() && recipients.is_empty() {
+        return Err(BackupError::NoKeySlots);
+    }
+
+    let mut content_key = encryption::generate_key_with_rng(rng);
+    let blob = seal_wrapped(secret, &content_key, password, recipients, rng);
+    content_key.zeroize();
+    blob
+}
+
+fn seal_wrapped(
+    secret: &[u8],
+    content_key: &[u8; 32],
+    password: Option<&str>,
+    recipients: &[BackupRecipient],
+    rng: &mut impl SecureRng,
+) -> Result<BackupBlob> {
+    let ciphertext = encryption::encrypt_message_with_rng(secret, content_key, rng)
+        .map_err(|e| BackupError::EncryptionFailed(e.to_string()))?;
+
+    let password = match password {
+        Some(password) => {
+            let mut salt = [0u8; SALT_SIZE];
+            rng.fill_bytes(&mut salt);
+            let mut key = derive_key_from_password(password, &salt)?;
+            let wrapped_key = encryption::encrypt_message_with_rng(content_key, &key, rng)
+                .map_err(|e| BackupError::EncryptionFailed(e.to_string()));
+            key.zeroize();
+            Some(PasswordSlot {
+                salt,
+                wrapped_key: wrapped_key?,
+            })
+        }
+        None => None,
+    };
+
+    let recipients = recipients
+        .iter()
+        .map(|r| wrap_to_recipient(content_key, r, rng))
+        .collect::<Result<Vec<_>>>()?;
+
+    WrappedBackup {
+        password,
+        recipients,
+        ciphertext,
+    }
+    .to_blob()
+}
+
+/// Restore a device-wrapped backup with one of its recipients' keypairs
+///
+/// # Arguments
+/// * `backup` - A blob from [`create_wrapped_backup`]
+/// * `keypair` - This device's hybrid KEM keypair
+///
+/// # Returns
+/// The original secret data
+pub fn restore_wrapped_backup(backup: &BackupBlob, keypair: &HybridKEMKeypair) -> Result<Vec<u8>> {
+    let wrapped = WrappedBackup::parse(backup)?;
+    let mut content_key = wrapped.unwrap_for(keypair)?;
+    let secret = wrapped.open(&content_key);
+    content_key.zeroize();
+    secret
+}
+
+/// Wrap an existing backup's content key to one more device
+///
+/// Run on a device that is already a recipient, e.g. when linking a new
+/// device; the secret itself is not re-encrypted.
+///
+/// # Arguments
+/// * `backup` - A blob from [`create_wrapped_backup`]
+/// * `keypair` - This device's hybrid KEM keypair (already a recipient)
+/// * `recipient` - The device to add
+///
+/// # Returns
+/// The backup blob with the extra recipient
+pub fn add_backup_recipient(
+    backup: &BackupBlob,
+    keypair: &HybridKEMKeypair,
+    recipient: &BackupRecipient,
+) -> Result<BackupBlob> {
+    let mut wrapped = WrappedBackup::parse(backup)?;
+    let mut content_key = wrapped.unwrap_for(keypair)?;
+    let slot = wrap_to_recipient(&content_key, recipient, &mut OsRng);
+    content_key.zeroize();
+    wrapped.recipients.push(slot?);
+    wrapped.to_blob()
+}
+
+fn restore_wrapped_backup_with_password(backup: &BackupBlob, password: &str) -> Result<Vec<u8>> {
+    let wrapped = WrappedBackup::parse(backup)?;
+    let slot = wrapped.password.as_ref().ok_or(BackupError::NoKeySlots)?;
+
+    let mut key = derive_key_from_password(password, &slot.salt)?;
+    let content_key = encryption::decrypt_message(&slot.wrapped_key, &key)
+        .map_err(|e| BackupError::DecryptionFailed(e.to_string()));
+    key.zeroize();
+
+    let mut content_key = content_key?;
+    let secret = wrapped.open(&content_key);
+    content_key.zeroize();
+    secret
+}
+
 // ── Shamir's Secret Sharing over GF(256) ──
 
 /// GF(256) multiplication using the irreducible polynomial x^8 + x^4 + x^3 + x + 1
@@ -198,6 +495,16 @@ fn gf256_eval_poly(coeffs: &[u8], x: u8) -> u8 {
 /// # Returns
 /// Vector of N SecretShares
 pub fn split_secret(secret: &[u8], k: usize, n: usize) -> Result<Vec<SecretShare>> {
+    split_secret_with_rng(secret, k, n, &mut OsRng)
+}
+
+/// [`split_secret`] drawing the polynomial coefficients from `rng`.
+pub fn split_secret_with_rng(
+    secret: &[u8],
+    k: usize,
+    n: usize,
+    rng: &mut impl SecureRng,
+) -> Result<Vec<SecretShare>> {
     if k < 2 || k > n || n > 255 {
         return Err(BackupError::InvalidThreshold { k, n });
     }
@@ -209,8 +516,6 @@ pub fn split_secret(secret: &[u8], k: usize, n: usize) -> Result<Vec<SecretShare
         })
         .collect();
 
-    let mut rng = rand::rngs::OsRng;
-
     for &byte in secret {
         // Build polynomial: coeffs[0] = secret_byte, coeffs[1..k] = random
         let mut coeffs = vec![0u8; k];
@@ -318,6 +623,7 @@ pub fn create_social_recovery_backup(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::pqc::generate_hybrid_keypair_from_seed;
 
     #[test]
     fn test_backup_restore() {
@@ -393,6 +699,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_wrapped_backup_restores_on_recipient_device() {
+        let secret = [7u8; 32];
+        let phone = generate_hybrid_keypair_from_seed(&[1; 32]).unwrap();
+        let laptop = generate_hybrid_keypair_from_seed(&[2; 32]).unwrap();
+        let stranger = generate_hybrid_keypair_from_seed(&[3; 32]).unwrap();
+
+        let backup = create_wrapped_backup(
+            &secret,
+            Some("passphrase"),
+            &[BackupRecipient::from_keypair(&phone)],
+        )
+        .unwrap();
+        assert_eq!(restore_wrapped_backup(&backup, &phone).unwrap(), secret);
+        assert_eq!(
+            restore_encrypted_backup(&backup, "passphrase").unwrap(),
+            secret
+        );
+        assert!(matches!(
+            restore_wrapped_backup(&backup, &laptop),
+            Err(BackupError::NotARecipient)
+        ));
+
+        // The phone enrolls the laptop without the passphrase
+        let backup =
+            add_backup_recipient(&backup, &phone, &BackupRecipient::from_keypair(&laptop)).unwrap();
+        assert_eq!(restore_wrapped_backup(&backup, &laptop).unwrap(), secret);
+        assert!(add_backup_recipient(
+            &backup,
+            &stranger,
+            &BackupRecipient::from_keypair(&stranger)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_wrapped_backup_without_password() {
+        let device = generate_hybrid_keypair_from_seed(&[4; 32]).unwrap();
+        assert!(matches!(
+            create_wrapped_backup(b"secret", None, &[]),
+            Err(BackupError::NoKeySlots)
+        ));
+
+        let backup =
+            create_wrapped_backup(b"secret", None, &[BackupRecipient::from_keypair(&device)])
+                .unwrap();
+        assert_eq!(restore_wrapped_backup(&backup, &device).unwrap(), b"secret");
+        assert!(matches!(
+            restore_encrypted_backup(&backup, "anything"),
+            Err(BackupError::NoKeySlots)
+        ));
+
+        // Password-only blobs are not device-wrapped
+        let legacy = create_encrypted_backup(b"secret", "pw").unwrap();
+        assert!(matches!(
+            restore_wrapped_backup(&legacy, &device),
+            Err(BackupError::InvalidVersion(BACKUP_VERSION))
+        ));
+    }
+
     #[test]
     fn test_social_recovery() {
         let secret = [42u8; 32];
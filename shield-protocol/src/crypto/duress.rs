@@ -23,6 +23,8 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use zeroize::Zeroize;
 
+use crate::rng::{OsRng, SecureRng};
+
 #[derive(Error, Debug)]
 pub enum DuressError {
     #[error("Duress PIN not configured")]
@@ -173,6 +175,17 @@ impl DuressManager {
         pin: &str,
         wipe_actions: WipeActions,
         decoy_profile: DecoyProfile,
+    ) -> Result<()> {
+        self.configure_with_rng(pin, wipe_actions, decoy_profile, &mut OsRng)
+    }
+
+    /// [`configure`](Self::configure) drawing the salt from `rng`.
+    pub fn configure_with_rng(
+        &mut self,
+        pin: &str,
+        wipe_actions: WipeActions,
+        decoy_profile: DecoyProfile,
+        rng: &mut impl SecureRng,
     ) -> Result<()> {
         // Validate PIN
         if pin.len() < 4 {
@@ -188,7 +201,8 @@ impl DuressManager {
 
         // Generate random salt
         let mut salt = [0u8; 32];
-        getrandom::getrandom(&mut salt).map_err(|e| DuressError::HashingFailed(e.to_string()))?;
+        rng.try_fill_bytes(&mut salt)
+            .map_err(|e| DuressError::HashingFailed(e.to_string()))?;
 
         // Hash PIN with Argon2id
         let pin_hash = hash_pin_argon2id(pin.as_bytes(), &salt)?;
@@ -338,6 +352,188 @@ impl Default for DuressManager {
     }
 }
 
+// ─── Duress PIN Validation ───────────────────────────────────────────────────
+
+/// PINs people pick most often. A duress PIN from this list is likely to be
+/// tried by anyone poking at the lock screen, wiping the real data.
+const COMMON_PINS: &[&str] = &[
+    "0000", "1111", "2222", "3333", "4444", "5555", "6666", "7777", "8888", "9999", "1234", "4321",
+    "1212", "1122", "1313", "1004", "2000", "2001", "1010", "6969", "1984", "2580", "0852", "1379",
+    "1470", "7410", "0123", "9876", "5683", "0007", "000000", "111111", "123456", "654321",
+    "123123", "121212", "112233", "123321", "159753", "147258", "666666", "696969", "789456",
+    "456789", "999999", "000007",
+];
+
+/// Edit distance at or below which two PINs count as one slip apart.
+const SLIP_DISTANCE: usize = 1;
+
+/// How serious a [`PinIssue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PinIssueSeverity {
+    /// Allowed, but the user should be told.
+    Warning,
+    /// Must not be configured.
+    Reject,
+}
+
+/// A problem with a proposed duress PIN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinIssue {
+    /// Outside the 4–32 character range accepted by `configure`.
+    InvalidLength,
+    /// Identical to the real PIN.
+    SameAsReal,
+    /// One PIN is a prefix of the other, so an extra or missing keypress
+    /// switches between them.
+    PrefixOfReal,
+    /// Within `distance` edits (insert, delete, substitute, swap adjacent)
+    /// of the real PIN.
+    TooSimilar { distance: usize },
+    /// Starts with the same `len` characters as the real PIN.
+    SharedPrefix { len: usize },
+    /// On the list of most commonly chosen PINs.
+    CommonPin,
+    /// Repeated or sequential characters, e.g. 1111, 2468, 9876, 1212.
+    TrivialPattern,
+}
+
+impl PinIssue {
+    pub fn severity(&self) -> PinIssueSeverity {
+        match self {
+            PinIssue::InvalidLength
+            | PinIssue::SameAsReal
+            | PinIssue::PrefixOfReal
+            | PinIssue::CommonPin => PinIssueSeverity::Reject,
+            PinIssue::TooSimilar { distance } if *distance <= SLIP_DISTANCE => {
+                PinIssueSeverity::Reject
+            }
+            PinIssue::TooSimilar { .. }
+            | PinIssue::SharedPrefix { .. }
+            | PinIssue::TrivialPattern => PinIssueSeverity::Warning,
+        }
+    }
+
+    /// Stable identifier for UI string lookup.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PinIssue::InvalidLength => "invalid_length",
+            PinIssue::SameAsReal => "same_as_real",
+            PinIssue::PrefixOfReal => "prefix_of_real",
+            PinIssue::TooSimilar { .. } => "too_similar",
+            PinIssue::SharedPrefix { .. } => "shared_prefix",
+            PinIssue::CommonPin => "common_pin",
+            PinIssue::TrivialPattern => "trivial_pattern",
+        }
+    }
+}
+
+impl std::fmt::Display for PinIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinIssue::InvalidLength => write!(f, "Duress PIN must be 4 to 32 characters"),
+            PinIssue::SameAsReal => write!(f, "Duress PIN must differ from the real PIN"),
+            PinIssue::PrefixOfReal => write!(
+                f,
+                "One PIN must not start with the other; a single extra or missing key would switch them"
+            ),
+            PinIssue::TooSimilar { distance } => write!(
+                f,
+                "Duress PIN is only {} keypress(es) away from the real PIN",
+                distance
+            ),
+            PinIssue::SharedPrefix { len } => write!(
+                f,
+                "Duress PIN starts with the same {} characters as the real PIN",
+                len
+            ),
+            PinIssue::CommonPin => write!(f, "Duress PIN is one of the most commonly used PINs"),
+            PinIssue::TrivialPattern => {
+                write!(f, "Duress PIN is a repeated or sequential pattern")
+            }
+        }
+    }
+}
+
+/// Outcome of [`validate_duress_pin`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinValidation {
+    /// Every issue found, most severe first.
+    pub issues: Vec<PinIssue>,
+}
+
+impl PinValidation {
+    /// No issue of `Reject` severity.
+    pub fn is_acceptable(&self) -> bool {
+        self.worst() != Some(PinIssueSeverity::Reject)
+    }
+
+    pub fn worst(&self) -> Option<PinIssueSeverity> {
+        self.issues.iter().map(PinIssue::severity).max()
+    }
+}
+
+/// Check a proposed duress PIN against the real PIN and common choices.
+///
+/// A duress PIN must be hard to enter by accident when typing the real one
+/// (and the other way round), and hard for someone trying likely PINs to
+/// hit. Neither PIN is stored or logged.
+pub fn validate_duress_pin(duress_pin: &str, real_pin: &str) -> PinValidation {
+    let duress = duress_pin.as_bytes();
+    let real = real_pin.as_bytes();
+    let mut issues = Vec::new();
+
+    if duress.len() < 4 || duress.len() > 32 {
+        issues.push(PinIssue::InvalidLength);
+    }
+    if constant_time_eq(duress, real) {
+        issues.push(PinIssue::SameAsReal);
+    } else {
+        if duress.starts_with(real) || real.starts_with(duress) {
+            issues.push(PinIssue::PrefixOfReal);
+        } else {
+            let shared = duress.iter().zip(real).take_while(|(a, b)| a == b).count();
+            if shared >= 2 && shared * 2 >= duress.len().min(real.len()) {
+                issues.push(PinIssue::SharedPrefix { len: shared });
+            }
+        }
+        let distance = edit_distance(duress, real);
+        if distance <= SLIP_DISTANCE + 1 {
+            issues.push(PinIssue::TooSimilar { distance });
+        }
+    }
+    if COMMON_PINS.iter().any(|p| p.as_bytes() == duress) {
+        issues.push(PinIssue::CommonPin);
+    }
+    if is_trivial_pattern(duress) {
+        issues.push(PinIssue::TrivialPattern);
+    }
+
+    issues.sort_by_key(|i| std::cmp::Reverse(i.severity()));
+    PinValidation { issues }
+}
+
+impl DuressManager {
+    /// [`configure`](Self::configure), refusing a duress PIN that
+    /// [`validate_duress_pin`] rejects against `real_pin`.
+    pub fn configure_checked(
+        &mut self,
+        pin: &str,
+        real_pin: &str,
+        wipe_actions: WipeActions,
+        decoy_profile: DecoyProfile,
+    ) -> Result<()> {
+        let validation = validate_duress_pin(pin, real_pin);
+        if let Some(issue) = validation
+            .issues
+            .iter()
+            .find(|i| i.severity() == PinIssueSeverity::Reject)
+        {
+            return Err(DuressError::InvalidPin(issue.to_string()));
+        }
+        self.configure(pin, wipe_actions, decoy_profile)
+    }
+}
+
 /// Execute emergency wipe based on WipeActions configuration.
 ///
 /// This function performs the actual data destruction. It should be called
@@ -397,15 +593,19 @@ pub fn execute_emergency_wipe(actions: &WipeActions, _data_dir: &str) -> Vec<Str
 
 // ─── Internal Helpers ────────────────────────────────────────────────────────
 
+// Argon2id parameters for PIN hashing (OWASP recommended for password hashing)
+pub(crate) const PIN_ARGON2_MEM_COST: u32 = 65536; // 64 MiB
+pub(crate) const PIN_ARGON2_TIME_COST: u32 = 3;
+pub(crate) const PIN_ARGON2_PARALLELISM: u32 = 1;
+
 /// Hash a PIN using Argon2id (memory-hard, GPU-resistant)
 fn hash_pin_argon2id(pin: &[u8], salt: &[u8]) -> Result<Vec<u8>> {
     use argon2::{Algorithm, Argon2, Params, Version};
 
-    // Argon2id parameters (OWASP recommended for password hashing)
     let params = Params::new(
-        65536,    // 64 MB memory
-        3,        // 3 iterations
-        1,        // 1 degree of parallelism
+        PIN_ARGON2_MEM_COST,
+        PIN_ARGON2_TIME_COST,
+        PIN_ARGON2_PARALLELISM,
         Some(32), // 32-byte output
     )
     .map_err(|e| DuressError::HashingFailed(e.to_string()))?;
@@ -420,6 +620,44 @@ fn hash_pin_argon2id(pin: &[u8], salt: &[u8]) -> Result<Vec<u8>> {
     Ok(output)
 }
 
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and swaps of adjacent characters each cost one.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let width = b.len() + 1;
+    let mut d = vec![0usize; (a.len() + 1) * width];
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for (j, cell) in d.iter_mut().enumerate().take(width) {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+    d[a.len() * width + b.len()]
+}
+
+/// All characters equal, a constant-step run (1234, 2468, 9876), or a
+/// repeated pair (1212).
+fn is_trivial_pattern(pin: &[u8]) -> bool {
+    if pin.len() < 3 {
+        return false;
+    }
+    let step = pin[1] as i16 - pin[0] as i16;
+    let arithmetic = step.abs() <= 2 && pin.windows(2).all(|w| w[1] as i16 - w[0] as i16 == step);
+    let alternating = pin.len() >= 4 && pin.iter().skip(2).zip(pin).all(|(a, b)| a == b);
+    arithmetic || alternating
+}
+
 /// Constant-time comparison of two byte slices
 fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
@@ -598,6 +836,66 @@ mod tests {
         assert!(!manager.is_configured());
     }
 
+    #[test]
+    fn test_validate_duress_pin_rejects_slips() {
+        let v = validate_duress_pin("482915", "482915");
+        assert_eq!(v.issues[0], PinIssue::SameAsReal);
+        assert!(!v.is_acceptable());
+
+        // One extra digit
+        let v = validate_duress_pin("4829150", "482915");
+        assert!(v.issues.contains(&PinIssue::PrefixOfReal));
+        assert!(!v.is_acceptable());
+
+        // Swapped adjacent digits
+        let v = validate_duress_pin("482951", "482915");
+        assert!(v.issues.contains(&PinIssue::TooSimilar { distance: 1 }));
+        assert!(!v.is_acceptable());
+
+        // Two edits away: allowed with a warning
+        let v = validate_duress_pin("4829", "4821");
+        assert!(!v.is_acceptable());
+        let v = validate_duress_pin("7305", "7391");
+        assert_eq!(
+            v.issues,
+            vec![
+                PinIssue::SharedPrefix { len: 2 },
+                PinIssue::TooSimilar { distance: 2 }
+            ]
+        );
+        assert!(v.is_acceptable());
+        assert_eq!(v.worst(), Some(PinIssueSeverity::Warning));
+    }
+
+    #[test]
+    fn test_validate_duress_pin_common_and_patterns() {
+        assert!(!validate_duress_pin("1234", "8351").is_acceptable());
+        assert!(!validate_duress_pin("12", "8351").is_acceptable());
+
+        let v = validate_duress_pin("2468", "8351");
+        assert_eq!(v.issues, vec![PinIssue::TrivialPattern]);
+        assert!(v.is_acceptable());
+        assert!(validate_duress_pin("3737", "8351")
+            .issues
+            .contains(&PinIssue::TrivialPattern));
+
+        let v = validate_duress_pin("602817", "8351");
+        assert!(v.issues.is_empty());
+        assert_eq!(v.worst(), None);
+
+        let mut manager = DuressManager::new();
+        assert!(matches!(
+            manager.configure_checked(
+                "83510",
+                "8351",
+                WipeActions::default(),
+                DecoyProfile::default()
+            ),
+            Err(DuressError::InvalidPin(_))
+        ));
+        assert!(!manager.is_configured());
+    }
+
     #[test]
     fn test_wipe_actions_custom() {
         let actions = WipeActions {
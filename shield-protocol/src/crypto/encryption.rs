@@ -4,11 +4,12 @@ use chacha20poly1305::{
 };
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
-use rand::RngCore;
 use sha2::Sha256;
 use thiserror::Error;
 use zeroize::Zeroize;
 
+use crate::rng::SecureRng;
+
 /// Maximum number of sequence numbers ahead that will be accepted
 /// Messages with sequence >= expected + WINDOW_SIZE will be rejected
 /// This prevents desync from packet loss while still protecting against replay attacks
@@ -48,6 +49,8 @@ pub enum EncryptionError {
     },
     #[error("Out of order message: received {received}, expected {expected}")]
     OutOfOrder { received: u64, expected: u64 },
+    #[error("Identity key changed; re-approval required before sending")]
+    IdentityKeyChanged,
 }
 
 pub type Result<T> = std::result::Result<T, EncryptionError>;
@@ -61,6 +64,15 @@ pub type Result<T> = std::result::Result<T, EncryptionError>;
 /// # Returns
 /// Encrypted message with prepended nonce (24 bytes + ciphertext)
 pub fn encrypt_message(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    encrypt_message_with_rng(plaintext, key, &mut OsRng)
+}
+
+/// [`encrypt_message`] drawing its nonce from `rng`.
+pub fn encrypt_message_with_rng(
+    plaintext: &[u8],
+    key: &[u8],
+    rng: &mut impl SecureRng,
+) -> Result<Vec<u8>> {
     // Validate key length
     if key.len() != 32 {
         return Err(EncryptionError::InvalidKeyLength);
@@ -72,7 +84,7 @@ pub fn encrypt_message(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
 
     // Generate random nonce
     let mut nonce_bytes = [0u8; 24];
-    OsRng.fill_bytes(&mut nonce_bytes);
+    rng.fill_bytes(&mut nonce_bytes);
     let nonce = XNonce::from_slice(&nonce_bytes);
 
     // Encrypt
@@ -125,8 +137,13 @@ pub fn decrypt_message(encrypted_data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
 
 /// Generate a random 32-byte key
 pub fn generate_key() -> [u8; 32] {
+    generate_key_with_rng(&mut OsRng)
+}
+
+/// [`generate_key`] drawing from `rng`.
+pub fn generate_key_with_rng(rng: &mut impl SecureRng) -> [u8; 32] {
     let mut key = [0u8; 32];
-    OsRng.fill_bytes(&mut key);
+    rng.fill_bytes(&mut key);
     key
 }
 
@@ -336,6 +353,16 @@ pub fn encrypt_message_with_evolution(
     plaintext: &[u8],
     chain_key: &mut [u8; 32],
     sequence: u64,
+) -> Result<EncryptionResult> {
+    encrypt_message_with_evolution_with_rng(plaintext, chain_key, sequence, &mut OsRng)
+}
+
+/// [`encrypt_message_with_evolution`] drawing its nonce from `rng`.
+pub fn encrypt_message_with_evolution_with_rng(
+    plaintext: &[u8],
+    chain_key: &mut [u8; 32],
+    sequence: u64,
+    rng: &mut impl SecureRng,
 ) -> Result<EncryptionResult> {
     // Derive message key from current chain key (zeroized after use for memory hardening)
     let mut message_key = derive_message_key(chain_key)?;
@@ -349,7 +376,7 @@ pub fn encrypt_message_with_evolution(
             .map_err(|_| EncryptionError::InvalidKeyLength)?;
 
         let mut nonce_bytes = [0u8; 24];
-        OsRng.fill_bytes(&mut nonce_bytes);
+        rng.fill_bytes(&mut nonce_bytes);
         let nonce = XNonce::from_slice(&nonce_bytes);
 
         let ciphertext = cipher
@@ -456,6 +483,7 @@ pub fn decrypt_message_with_evolution(
 
 // ==================== TWO-PHASE RATCHET COMMIT (Fix #6) ====================
 
+use crate::protocol::contact_id::ContactId;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
@@ -485,6 +513,16 @@ pub fn encrypt_message_deferred(
     plaintext: &[u8],
     chain_key: &[u8; 32], // Immutable borrow - does NOT modify
     sequence: u64,
+) -> Result<DeferredEncryptionResult> {
+    encrypt_message_deferred_with_rng(plaintext, chain_key, sequence, &mut OsRng)
+}
+
+/// [`encrypt_message_deferred`] drawing its nonce from `rng`.
+pub fn encrypt_message_deferred_with_rng(
+    plaintext: &[u8],
+    chain_key: &[u8; 32],
+    sequence: u64,
+    rng: &mut impl SecureRng,
 ) -> Result<DeferredEncryptionResult> {
     // Derive message key from current chain key
     let message_key = derive_message_key(chain_key)?;
@@ -501,7 +539,7 @@ pub fn encrypt_message_deferred(
 
     // Generate random nonce
     let mut nonce_bytes = [0u8; 24];
-    OsRng.fill_bytes(&mut nonce_bytes);
+    rng.fill_bytes(&mut nonce_bytes);
     let nonce = XNonce::from_slice(&nonce_bytes);
 
     // Encrypt
@@ -527,50 +565,152 @@ pub fn encrypt_message_deferred(
 /// Pending ratchet advancement waiting for PING_ACK
 #[derive(Clone)]
 struct PendingRatchetAdvancement {
-    contact_id: String,       // Contact identifier (pubkey or onion)
+    contact_id: ContactId,    // Canonical contact identifier
     message_id: String,       // Message ID (for tracking)
     next_chain_key: [u8; 32], // Next chain key (uncommitted)
     next_sequence: u64,       // Next sequence number (uncommitted)
     created_at: std::time::SystemTime,
 }
 
+/// Pending ratchet advancements of one profile, keyed by contact
+#[derive(Default)]
+pub struct PendingRatchets {
+    pending: HashMap<ContactId, PendingRatchetAdvancement>,
+}
+
+impl PendingRatchets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a pending ratchet advancement (waiting for PING_ACK). Does not
+    /// consult a key change guard; [`store_pending_ratchet_advancement`] does.
+    pub fn store(
+        &mut self,
+        contact_id: &ContactId,
+        message_id: &str,
+        next_chain_key: [u8; 32],
+        next_sequence: u64,
+    ) {
+        let advancement = PendingRatchetAdvancement {
+            contact_id: *contact_id,
+            message_id: message_id.to_string(),
+            next_chain_key,
+            next_sequence,
+            created_at: std::time::SystemTime::now(),
+        };
+
+        if let Some(mut replaced) = self.pending.insert(*contact_id, advancement) {
+            replaced.next_chain_key.zeroize();
+        }
+
+        log::info!(
+            "Stored pending ratchet advancement for contact {}, message {}",
+            contact_id,
+            message_id
+        );
+    }
+
+    /// Take the advancement for `contact_id`: the next chain key and
+    /// sequence to persist, or None if nothing is pending
+    pub fn commit(&mut self, contact_id: &ContactId) -> Option<([u8; 32], u64)> {
+        match self.pending.remove(contact_id) {
+            Some(mut advancement) => {
+                log::info!(
+                    "Committed ratchet advancement for contact {}, message {}",
+                    contact_id,
+                    advancement.message_id
+                );
+                let committed = (advancement.next_chain_key, advancement.next_sequence);
+                advancement.next_chain_key.zeroize();
+                Some(committed)
+            }
+            None => {
+                log::warn!(
+                    "No pending ratchet advancement found for contact {}",
+                    contact_id
+                );
+                None
+            }
+        }
+    }
+
+    /// Discard the advancement for `contact_id`, if any
+    pub fn rollback(&mut self, contact_id: &ContactId) {
+        if let Some(mut advancement) = self.pending.remove(contact_id) {
+            advancement.next_chain_key.zeroize();
+            log::info!(
+                "Rolled back ratchet advancement for contact {}, message {}",
+                contact_id,
+                advancement.message_id
+            );
+        }
+    }
+
+    /// Drop every advancement and zeroize its key
+    pub fn clear(&mut self) {
+        for (_, mut advancement) in self.pending.drain() {
+            advancement.next_chain_key.zeroize();
+        }
+    }
+
+    /// Drop advancements older than 5 minutes; returns how many were dropped
+    pub fn remove_expired(&mut self) -> usize {
+        let now = std::time::SystemTime::now();
+        const MAX_AGE: std::time::Duration = std::time::Duration::from_secs(300); // 5 minutes
+
+        let before = self.pending.len();
+        self.pending.retain(|contact_id, advancement| {
+            if let Ok(age) = now.duration_since(advancement.created_at) {
+                if age > MAX_AGE {
+                    log::warn!(
+                        "Expired pending ratchet for contact {} (age: {:?})",
+                        contact_id,
+                        age
+                    );
+                    advancement.next_chain_key.zeroize();
+                    return false;
+                }
+            }
+            true
+        });
+        before - self.pending.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
 /// Global storage for pending ratchet advancements
-static PENDING_RATCHETS: Lazy<Mutex<HashMap<String, PendingRatchetAdvancement>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+static PENDING_RATCHETS: Lazy<Mutex<PendingRatchets>> =
+    Lazy::new(|| Mutex::new(PendingRatchets::new()));
 
 /// Store a pending ratchet advancement (waiting for PING_ACK)
 ///
 /// # Arguments
-/// * `contact_id` - Unique contact identifier (pubkey or onion address)
+/// * `contact_id` - Canonical contact identifier
 /// * `message_id` - Message ID (for tracking which message this ratchet belongs to)
 /// * `next_chain_key` - The evolved chain key (not yet committed)
 /// * `next_sequence` - The next sequence number (not yet committed)
 pub fn store_pending_ratchet_advancement(
-    contact_id: &str,
+    contact_id: &ContactId,
     message_id: &str,
     next_chain_key: [u8; 32],
     next_sequence: u64,
 ) -> Result<()> {
-    let mut pending = PENDING_RATCHETS
-        .lock()
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-
-    let advancement = PendingRatchetAdvancement {
-        contact_id: contact_id.to_string(),
-        message_id: message_id.to_string(),
-        next_chain_key,
-        next_sequence,
-        created_at: std::time::SystemTime::now(),
-    };
-
-    pending.insert(contact_id.to_string(), advancement);
-
-    log::info!(
-        "Stored pending ratchet advancement for contact {}, message {}",
-        contact_id,
-        message_id
-    );
+    // Refuse to send while a verified contact's key change is unapproved
+    crate::crypto::key_change::check_send(contact_id)
+        .map_err(|_| EncryptionError::IdentityKeyChanged)?;
 
+    PENDING_RATCHETS
+        .lock()
+        .map_err(|_| EncryptionError::EncryptionFailed)?
+        .store(contact_id, message_id, next_chain_key, next_sequence);
     Ok(())
 }
 
@@ -580,33 +720,15 @@ pub fn store_pending_ratchet_advancement(
 /// Returns the next chain key and sequence to persist in database.
 ///
 /// # Arguments
-/// * `contact_id` - Contact identifier (must match store_pending_ratchet_advancement)
+/// * `contact_id` - Canonical contact identifier (must match store_pending_ratchet_advancement)
 ///
 /// # Returns
 /// (next_chain_key, next_sequence) to persist, or None if no pending advancement
-pub fn commit_ratchet_advancement(contact_id: &str) -> Result<Option<([u8; 32], u64)>> {
-    let mut pending = PENDING_RATCHETS
+pub fn commit_ratchet_advancement(contact_id: &ContactId) -> Result<Option<([u8; 32], u64)>> {
+    Ok(PENDING_RATCHETS
         .lock()
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-
-    if let Some(advancement) = pending.remove(contact_id) {
-        log::info!(
-            "Committed ratchet advancement for contact {}, message {}",
-            contact_id,
-            advancement.message_id
-        );
-
-        Ok(Some((
-            advancement.next_chain_key,
-            advancement.next_sequence,
-        )))
-    } else {
-        log::warn!(
-            "No pending ratchet advancement found for contact {}",
-            contact_id
-        );
-        Ok(None)
-    }
+        .map_err(|_| EncryptionError::EncryptionFailed)?
+        .commit(contact_id))
 }
 
 /// Rollback/discard pending ratchet advancement (if send permanently fails)
@@ -615,59 +737,32 @@ pub fn commit_ratchet_advancement(contact_id: &str) -> Result<Option<([u8; 32],
 ///
 /// # Arguments
 /// * `contact_id` - Contact identifier
-pub fn rollback_ratchet_advancement(contact_id: &str) -> Result<()> {
-    let mut pending = PENDING_RATCHETS
+pub fn rollback_ratchet_advancement(contact_id: &ContactId) -> Result<()> {
+    PENDING_RATCHETS
         .lock()
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-
-    if let Some(advancement) = pending.remove(contact_id) {
-        log::info!(
-            "Rolled back ratchet advancement for contact {}, message {}",
-            contact_id,
-            advancement.message_id
-        );
-    }
-
+        .map_err(|_| EncryptionError::EncryptionFailed)?
+        .rollback(contact_id);
     Ok(())
 }
 
 /// Clear all pending ratchet advancements and zeroize keys (for Duress PIN).
 /// Call this when the user enters the Duress PIN so no sensitive key material remains in memory.
 pub fn clear_all_pending_ratchets_for_duress() -> Result<()> {
-    let mut pending = PENDING_RATCHETS
+    PENDING_RATCHETS
         .lock()
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-    for (_, mut advancement) in pending.drain() {
-        advancement.next_chain_key.zeroize();
-    }
+        .map_err(|_| EncryptionError::EncryptionFailed)?
+        .clear();
     log::info!("Duress: cleared all pending ratchet state");
     Ok(())
 }
 
-/// Clean up expired pending ratchet advancements (older than 5 minutes)
-pub fn cleanup_expired_pending_ratchets() -> Result<()> {
-    let mut pending = PENDING_RATCHETS
+/// Clean up expired pending ratchet advancements (older than 5 minutes);
+/// returns how many were dropped
+pub fn cleanup_expired_pending_ratchets() -> Result<usize> {
+    Ok(PENDING_RATCHETS
         .lock()
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-
-    let now = std::time::SystemTime::now();
-    const MAX_AGE: std::time::Duration = std::time::Duration::from_secs(300); // 5 minutes
-
-    pending.retain(|contact_id, advancement| {
-        if let Ok(age) = now.duration_since(advancement.created_at) {
-            if age > MAX_AGE {
-                log::warn!(
-                    "Expired pending ratchet for contact {} (age: {:?})",
-                    contact_id,
-                    age
-                );
-                return false;
-            }
-        }
-        true
-    });
-
-    Ok(())
+        .map_err(|_| EncryptionError::EncryptionFailed)?
+        .remove_expired())
 }
 
 #[cfg(test)]
@@ -686,6 +781,40 @@ mod tests {
         assert_eq!(plaintext, decrypted.as_slice());
     }
 
+    #[test]
+    fn test_encrypt_with_injected_rng() {
+        use crate::rng::{seeded, RecordingRng};
+
+        let key = generate_key_with_rng(&mut seeded(1));
+        assert_eq!(key, generate_key_with_rng(&mut seeded(1)));
+
+        let mut rng = RecordingRng::new(seeded(2));
+        let a = encrypt_message_with_rng(b"same", &key, &mut rng).unwrap();
+        let b = encrypt_message_with_rng(b"same", &key, &mut seeded(2)).unwrap();
+        assert_eq!(a, b);
+        // Exactly one 24-byte nonce per message
+        assert_eq!(rng.draws(), &[24]);
+        assert_eq!(decrypt_message(&a, &key).unwrap(), b"same");
+    }
+
+    #[test]
+    fn test_ratchet_encryption_with_injected_rng() {
+        use crate::rng::{seeded, RecordingRng};
+
+        let chain_key = [7u8; 32];
+        let mut rng = RecordingRng::new(seeded(3));
+        let deferred = encrypt_message_deferred_with_rng(b"hi", &chain_key, 0, &mut rng).unwrap();
+        assert_eq!(rng.draws(), &[24]);
+
+        // The committed path draws the same nonce from the same stream
+        let mut evolving = chain_key;
+        let committed =
+            encrypt_message_with_evolution_with_rng(b"hi", &mut evolving, 0, &mut seeded(3))
+                .unwrap();
+        assert_eq!(committed.ciphertext, deferred.ciphertext);
+        assert_eq!(committed.evolved_chain_key, deferred.next_chain_key);
+    }
+
     #[test]
     fn test_decrypt_with_wrong_key() {
         let key1 = generate_key();
@@ -2,9 +2,10 @@ use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2, PasswordHash, PasswordVerifier,
 };
-use rand::rngs::OsRng;
 use thiserror::Error;
 
+use crate::rng::{OsRng, SecureRng};
+
 #[derive(Error, Debug)]
 pub enum HashingError {
     #[error("Hashing failed")]
@@ -25,7 +26,12 @@ pub type Result<T> = std::result::Result<T, HashingError>;
 /// # Returns
 /// Password hash string (PHC format)
 pub fn hash_password(password: &str) -> Result<String> {
-    let salt = SaltString::generate(&mut OsRng);
+    hash_password_with_rng(password, &mut OsRng)
+}
+
+/// [`hash_password`] drawing its salt from `rng`.
+pub fn hash_password_with_rng(password: &str, rng: &mut impl SecureRng) -> Result<String> {
+    let salt = SaltString::generate(rng);
     let argon2 = Argon2::default();
 
     let password_hash = argon2
@@ -95,9 +101,13 @@ pub fn hash_handle(handle: &str, salt: &[u8]) -> Result<[u8; 32]> {
 /// # Returns
 /// 16-byte salt
 pub fn generate_salt() -> [u8; 16] {
-    use rand::RngCore;
+    generate_salt_with_rng(&mut OsRng)
+}
+
+/// [`generate_salt`] drawing from `rng`.
+pub fn generate_salt_with_rng(rng: &mut impl SecureRng) -> [u8; 16] {
     let mut salt = [0u8; 16];
-    OsRng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut salt);
     salt
 }
 
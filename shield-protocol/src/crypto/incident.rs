@@ -0,0 +1,709 @@
+/// Key compromise response playbook.
+///
+/// When a key is known or suspected to have leaked, the user (or a support
+/// tool) declares what leaked as a [`CompromiseScope`].
+/// [`IncidentPlan::for_scope`] turns it into the actions the protocol
+/// requires, in the order they must run:
+///
+/// - `GroupSecret` (elevated): rekey the group.
+/// - `DeviceKey` (high): revoke the device, notify every contact and reset
+///   their sessions, rekey the groups the device was in.
+/// - `IdentityKey` (critical): rotate the identity key, notify every contact
+///   and reset their sessions, rekey every group.
+///
+/// Contacts are notified with a signed [`KeyRevocation`], always made with
+/// the identity key that was current when the incident was declared. For an
+/// identity compromise that is the leaked key itself: whoever holds it can
+/// revoke it, and nobody can take a revocation back. The successor key it
+/// names is only a hint — an attacker could publish a revocation naming
+/// their own key — so receivers route it through the usual key-change
+/// approval (see [`key_change`](super::key_change)) instead of trusting it.
+///
+/// [`IncidentPlan::execute`] runs the plan against the app through
+/// [`IncidentResponder`], carrying on past individual failures, and returns
+/// an [`IncidentReport`] listing who was notified and what risk remains.
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use serde_json::json;
+use thiserror::Error;
+
+use super::signing::{derive_public_key, sign_data, verify_signature};
+use crate::crdt::ids::{DeviceID, GroupID};
+use crate::protocol::contact_id::ContactId;
+
+/// Wire version of [`KeyRevocation::to_bytes`].
+pub const REVOCATION_VERSION: u8 = 1;
+
+const SIGN_CONTEXT: &[u8] = b"ShieldMessenger-KeyRevocation-v1";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum IncidentError {
+    #[error("Malformed revocation")]
+    Malformed,
+    #[error("Unsupported revocation version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid revocation signature")]
+    InvalidSignature,
+    #[error("Revocation of an identity key must be signed by that key")]
+    SignerMismatch,
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// What is known or suspected to have leaked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompromiseScope {
+    /// The key of one of our linked devices (the device is presumed lost,
+    /// along with the sessions and history stored on it).
+    DeviceKey { device_id: DeviceID },
+    /// Our long-term identity signing key.
+    IdentityKey,
+    /// The shared secret of one group.
+    GroupSecret { group_id: GroupID },
+}
+
+impl CompromiseScope {
+    pub fn severity(&self) -> IncidentSeverity {
+        match self {
+            CompromiseScope::GroupSecret { .. } => IncidentSeverity::Elevated,
+            CompromiseScope::DeviceKey { .. } => IncidentSeverity::High,
+            CompromiseScope::IdentityKey => IncidentSeverity::Critical,
+        }
+    }
+
+    /// Stable identifier for reports and UI string lookup.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompromiseScope::DeviceKey { .. } => "device_key",
+            CompromiseScope::IdentityKey => "identity_key",
+            CompromiseScope::GroupSecret { .. } => "group_secret",
+        }
+    }
+}
+
+/// How bad an incident is, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IncidentSeverity {
+    /// One group's traffic is readable; nobody can impersonate us.
+    Elevated,
+    /// A device's stored sessions and history are exposed.
+    High,
+    /// Anyone may impersonate us to every contact.
+    Critical,
+}
+
+impl IncidentSeverity {
+    pub fn code(&self) -> &'static str {
+        match self {
+            IncidentSeverity::Elevated => "elevated",
+            IncidentSeverity::High => "high",
+            IncidentSeverity::Critical => "critical",
+        }
+    }
+}
+
+/// A group we are in, and which of our devices hold its secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupHolding {
+    pub group_id: GroupID,
+    pub devices: Vec<DeviceID>,
+}
+
+/// What the app knows about the account the incident is declared for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IncidentContext {
+    pub contacts: Vec<ContactId>,
+    pub groups: Vec<GroupHolding>,
+}
+
+/// One step of a response, see [`IncidentPlan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncidentAction {
+    /// Replace our identity key with a fresh one.
+    RotateIdentityKey,
+    /// Unlink a device so it no longer receives keys or group traffic.
+    RevokeDevice { device_id: DeviceID },
+    /// Send a contact the signed [`KeyRevocation`].
+    NotifyContact { contact_id: ContactId },
+    /// Drop the ratchet session with a contact and start a fresh one.
+    ResetSession { contact_id: ContactId },
+    /// Generate and distribute a new group secret.
+    RekeyGroup { group_id: GroupID },
+}
+
+impl IncidentAction {
+    /// Position in the playbook: the new identity key must exist before
+    /// contacts are told about it, contacts must know the revocation before
+    /// the new session arrives, and groups are rekeyed once revoked devices
+    /// can no longer receive the new secret.
+    fn stage(&self) -> u8 {
+        match self {
+            IncidentAction::RotateIdentityKey => 0,
+            IncidentAction::RevokeDevice { .. } => 1,
+            IncidentAction::NotifyContact { .. } => 2,
+            IncidentAction::ResetSession { .. } => 3,
+            IncidentAction::RekeyGroup { .. } => 4,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            IncidentAction::RotateIdentityKey => "rotate_identity_key",
+            IncidentAction::RevokeDevice { .. } => "revoke_device",
+            IncidentAction::NotifyContact { .. } => "notify_contact",
+            IncidentAction::ResetSession { .. } => "reset_session",
+            IncidentAction::RekeyGroup { .. } => "rekey_group",
+        }
+    }
+
+    /// The contact, device or group the action applies to.
+    pub fn target(&self) -> Option<String> {
+        match self {
+            IncidentAction::RotateIdentityKey => None,
+            IncidentAction::RevokeDevice { device_id } => Some(device_id.to_string()),
+            IncidentAction::NotifyContact { contact_id }
+            | IncidentAction::ResetSession { contact_id } => Some(contact_id.to_string()),
+            IncidentAction::RekeyGroup { group_id } => Some(group_id.to_string()),
+        }
+    }
+}
+
+/// The key a [`KeyRevocation`] withdraws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RevokedKey {
+    /// An Ed25519 identity key.
+    Identity([u8; 32]),
+    /// One of the signer's devices.
+    Device(DeviceID),
+}
+
+/// Signed notice that a key must no longer be trusted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRevocation {
+    pub revoked: RevokedKey,
+    /// The identity key that replaces a revoked identity key, if the
+    /// rotation succeeded. Unauthenticated: verify it out of band.
+    pub successor: Option<[u8; 32]>,
+    pub severity: IncidentSeverity,
+    pub issued_at: u64,
+    #[serde(with = "BigArray")]
+    pub signature: [u8; 64],
+}
+
+impl KeyRevocation {
+    fn signed_bytes(
+        revoked: &RevokedKey,
+        successor: &Option<[u8; 32]>,
+        severity: IncidentSeverity,
+        issued_at: u64,
+    ) -> Result<Vec<u8>, IncidentError> {
+        let body = bincode::serialize(&(revoked, successor, severity, issued_at))
+            .map_err(|e| IncidentError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(SIGN_CONTEXT.len() + body.len());
+        out.extend_from_slice(SIGN_CONTEXT);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    pub fn sign(
+        revoked: RevokedKey,
+        successor: Option<[u8; 32]>,
+        severity: IncidentSeverity,
+        issued_at: u64,
+        identity_private_key: &[u8],
+    ) -> Result<Self, IncidentError> {
+        let data = Self::signed_bytes(&revoked, &successor, severity, issued_at)?;
+        let signature = sign_data(&data, identity_private_key)
+            .map_err(|e| IncidentError::Signing(e.to_string()))?;
+        Ok(Self {
+            revoked,
+            successor,
+            severity,
+            issued_at,
+            signature,
+        })
+    }
+
+    /// Check a revocation from the contact whose pinned identity key is
+    /// `identity_public_key`.
+    pub fn verify(&self, identity_public_key: &[u8]) -> Result<(), IncidentError> {
+        if let RevokedKey::Identity(key) = &self.revoked {
+            if key.as_slice() != identity_public_key {
+                return Err(IncidentError::SignerMismatch);
+            }
+        }
+        let data = Self::signed_bytes(
+            &self.revoked,
+            &self.successor,
+            self.severity,
+            self.issued_at,
+        )?;
+        match verify_signature(&data, &self.signature, identity_public_key) {
+            Ok(true) => Ok(()),
+            _ => Err(IncidentError::InvalidSignature),
+        }
+    }
+
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, IncidentError> {
+        let body = bincode::serialize(self).map_err(|e| IncidentError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(REVOCATION_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, IncidentError> {
+        let (&version, body) = data.split_first().ok_or(IncidentError::Malformed)?;
+        if version != REVOCATION_VERSION {
+            return Err(IncidentError::UnsupportedVersion(version));
+        }
+        bincode::deserialize(body).map_err(|_| IncidentError::Malformed)
+    }
+}
+
+/// The app side of a response. Each call performs one action; errors are
+/// reported, not retried.
+pub trait IncidentResponder {
+    /// Generate and install a new identity key, returning its public half.
+    /// The old key must stay usable until [`IncidentPlan::execute`] returns,
+    /// since it signs the revocation.
+    fn rotate_identity_key(&mut self) -> Result<[u8; 32], String>;
+    fn revoke_device(&mut self, device_id: &DeviceID) -> Result<(), String>;
+    /// Deliver an encoded [`KeyRevocation`].
+    fn send_revocation(&mut self, contact_id: &ContactId, revocation: &[u8]) -> Result<(), String>;
+    fn reset_session(&mut self, contact_id: &ContactId) -> Result<(), String>;
+    fn rekey_group(&mut self, group_id: &GroupID) -> Result<(), String>;
+}
+
+/// The actions a [`CompromiseScope`] requires, in execution order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncidentPlan {
+    pub scope: CompromiseScope,
+    pub actions: Vec<IncidentAction>,
+}
+
+impl IncidentPlan {
+    pub fn for_scope(scope: CompromiseScope, context: &IncidentContext) -> Self {
+        let mut actions = Vec::new();
+        let notify_everyone = |actions: &mut Vec<IncidentAction>| {
+            for &contact_id in &context.contacts {
+                actions.push(IncidentAction::NotifyContact { contact_id });
+                actions.push(IncidentAction::ResetSession { contact_id });
+            }
+        };
+        match scope {
+            CompromiseScope::IdentityKey => {
+                actions.push(IncidentAction::RotateIdentityKey);
+                notify_everyone(&mut actions);
+                for holding in &context.groups {
+                    actions.push(IncidentAction::RekeyGroup {
+                        group_id: holding.group_id,
+                    });
+                }
+            }
+            CompromiseScope::DeviceKey { device_id } => {
+                actions.push(IncidentAction::RevokeDevice { device_id });
+                notify_everyone(&mut actions);
+                for holding in context
+                    .groups
+                    .iter()
+                    .filter(|g| g.devices.contains(&device_id))
+                {
+                    actions.push(IncidentAction::RekeyGroup {
+                        group_id: holding.group_id,
+                    });
+                }
+            }
+            // Rekeyed even when the app does not list the group: the
+            // declared scope is what the user knows leaked
+            CompromiseScope::GroupSecret { group_id } => {
+                actions.push(IncidentAction::RekeyGroup { group_id });
+            }
+        }
+        actions.sort_by_key(IncidentAction::stage);
+        Self { scope, actions }
+    }
+
+    pub fn severity(&self) -> IncidentSeverity {
+        self.scope.severity()
+    }
+
+    /// Run every action through `responder`. `identity_private_key` is the
+    /// identity key current when the incident was declared (the leaked one,
+    /// for an identity compromise). Only failing to sign the revocation
+    /// aborts; everything else ends up in the report.
+    pub fn execute(
+        &self,
+        responder: &mut impl IncidentResponder,
+        identity_private_key: &[u8],
+        now: u64,
+    ) -> Result<IncidentReport, IncidentError> {
+        let mut report = IncidentReport {
+            scope: self.scope,
+            severity: self.severity(),
+            executed_at: now,
+            new_identity_key: None,
+            outcomes: Vec::with_capacity(self.actions.len()),
+        };
+        let mut revocation: Option<Vec<u8>> = None;
+
+        for action in &self.actions {
+            let result = match action {
+                IncidentAction::RotateIdentityKey => responder.rotate_identity_key().map(|key| {
+                    report.new_identity_key = Some(key);
+                }),
+                IncidentAction::RevokeDevice { device_id } => responder.revoke_device(device_id),
+                IncidentAction::NotifyContact { contact_id } => {
+                    let bytes = match &revocation {
+                        Some(bytes) => bytes,
+                        None => revocation.insert(
+                            self.revocation(identity_private_key, report.new_identity_key, now)?
+                                .to_bytes()?,
+                        ),
+                    };
+                    responder.send_revocation(contact_id, bytes)
+                }
+                IncidentAction::ResetSession { contact_id } => responder.reset_session(contact_id),
+                IncidentAction::RekeyGroup { group_id } => responder.rekey_group(group_id),
+            };
+            if let Err(error) = &result {
+                log::warn!("Incident action {} failed: {}", action.code(), error);
+            }
+            report.outcomes.push(ActionOutcome {
+                action: *action,
+                error: result.err(),
+            });
+        }
+        Ok(report)
+    }
+
+    fn revocation(
+        &self,
+        identity_private_key: &[u8],
+        successor: Option<[u8; 32]>,
+        now: u64,
+    ) -> Result<KeyRevocation, IncidentError> {
+        let revoked = match self.scope {
+            CompromiseScope::DeviceKey { device_id } => RevokedKey::Device(device_id),
+            _ => RevokedKey::Identity(
+                derive_public_key(identity_private_key)
+                    .map_err(|e| IncidentError::Signing(e.to_string()))?,
+            ),
+        };
+        KeyRevocation::sign(
+            revoked,
+            successor,
+            self.severity(),
+            now,
+            identity_private_key,
+        )
+    }
+}
+
+/// Result of one action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionOutcome {
+    pub action: IncidentAction,
+    /// `None` on success.
+    pub error: Option<String>,
+}
+
+/// Exposure left after a response, see [`IncidentReport::remaining_risks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemainingRisk {
+    /// The old identity key is still current; anyone holding it can
+    /// impersonate us until a rotation succeeds.
+    IdentityNotRotated,
+    /// The device still receives keys and group traffic.
+    DeviceNotRevoked(DeviceID),
+    /// The contact may still accept the compromised key.
+    ContactNotNotified(ContactId),
+    /// The session with the contact still runs on exposed ratchet state.
+    SessionNotReset(ContactId),
+    /// The group still uses the exposed secret.
+    GroupNotRekeyed(GroupID),
+    /// Messages stored on the lost device stay readable to whoever has it;
+    /// no key change can undo that.
+    DeviceHistoryExposed(DeviceID),
+    /// Group messages sent before the rekey were readable with the old secret.
+    GroupHistoryExposed(GroupID),
+}
+
+impl RemainingRisk {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RemainingRisk::IdentityNotRotated => "identity_not_rotated",
+            RemainingRisk::DeviceNotRevoked(_) => "device_not_revoked",
+            RemainingRisk::ContactNotNotified(_) => "contact_not_notified",
+            RemainingRisk::SessionNotReset(_) => "session_not_reset",
+            RemainingRisk::GroupNotRekeyed(_) => "group_not_rekeyed",
+            RemainingRisk::DeviceHistoryExposed(_) => "device_history_exposed",
+            RemainingRisk::GroupHistoryExposed(_) => "group_history_exposed",
+        }
+    }
+
+    fn target(&self) -> Option<String> {
+        match self {
+            RemainingRisk::IdentityNotRotated => None,
+            RemainingRisk::DeviceNotRevoked(device_id)
+            | RemainingRisk::DeviceHistoryExposed(device_id) => Some(device_id.to_string()),
+            RemainingRisk::ContactNotNotified(contact_id)
+            | RemainingRisk::SessionNotReset(contact_id) => Some(contact_id.to_string()),
+            RemainingRisk::GroupNotRekeyed(group_id)
+            | RemainingRisk::GroupHistoryExposed(group_id) => Some(group_id.to_string()),
+        }
+    }
+}
+
+/// What a response did, from [`IncidentPlan::execute`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncidentReport {
+    pub scope: CompromiseScope,
+    pub severity: IncidentSeverity,
+    pub executed_at: u64,
+    /// Public half of the new identity key, if one was installed.
+    pub new_identity_key: Option<[u8; 32]>,
+    /// One per planned action, in execution order.
+    pub outcomes: Vec<ActionOutcome>,
+}
+
+impl IncidentReport {
+    /// Contacts the revocation was delivered to.
+    pub fn notified_contacts(&self) -> Vec<ContactId> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| match outcome.action {
+                IncidentAction::NotifyContact { contact_id } if outcome.error.is_none() => {
+                    Some(contact_id)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether every action succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.error.is_none())
+    }
+
+    /// Exposure that remains: one entry per failed action, plus what no
+    /// action can repair.
+    pub fn remaining_risks(&self) -> Vec<RemainingRisk> {
+        let mut risks: Vec<RemainingRisk> = self
+            .outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+            .map(|outcome| match outcome.action {
+                IncidentAction::RotateIdentityKey => RemainingRisk::IdentityNotRotated,
+                IncidentAction::RevokeDevice { device_id } => {
+                    RemainingRisk::DeviceNotRevoked(device_id)
+                }
+                IncidentAction::NotifyContact { contact_id } => {
+                    RemainingRisk::ContactNotNotified(contact_id)
+                }
+                IncidentAction::ResetSession { contact_id } => {
+                    RemainingRisk::SessionNotReset(contact_id)
+                }
+                IncidentAction::RekeyGroup { group_id } => RemainingRisk::GroupNotRekeyed(group_id),
+            })
+            .collect();
+        match self.scope {
+            CompromiseScope::DeviceKey { device_id } => {
+                risks.push(RemainingRisk::DeviceHistoryExposed(device_id));
+            }
+            CompromiseScope::GroupSecret { group_id } => {
+                risks.push(RemainingRisk::GroupHistoryExposed(group_id));
+            }
+            CompromiseScope::IdentityKey => {}
+        }
+        risks
+    }
+
+    /// Machine-readable form for the app and support tooling.
+    pub fn to_json(&self) -> serde_json::Value {
+        let target = |scope: &CompromiseScope| match scope {
+            CompromiseScope::DeviceKey { device_id } => Some(device_id.to_string()),
+            CompromiseScope::IdentityKey => None,
+            CompromiseScope::GroupSecret { group_id } => Some(group_id.to_string()),
+        };
+        json!({
+            "scope": self.scope.code(),
+            "scopeTarget": target(&self.scope),
+            "severity": self.severity.code(),
+            "executedAt": self.executed_at,
+            "newIdentityKey": self.new_identity_key.map(hex::encode),
+            "complete": self.is_complete(),
+            "actions": self.outcomes.iter().map(|outcome| json!({
+                "action": outcome.action.code(),
+                "target": outcome.action.target(),
+                "error": outcome.error,
+            })).collect::<Vec<_>>(),
+            "notifiedContacts": self
+                .notified_contacts()
+                .iter()
+                .map(ContactId::to_string)
+                .collect::<Vec<_>>(),
+            "remainingRisks": self.remaining_risks().iter().map(|risk| json!({
+                "risk": risk.code(),
+                "target": risk.target(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair;
+    use crate::protocol::contact_id::test_contact;
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        revocations: Vec<Vec<u8>>,
+        unreachable: Option<ContactId>,
+        new_key: [u8; 32],
+    }
+
+    impl IncidentResponder for Recorder {
+        fn rotate_identity_key(&mut self) -> Result<[u8; 32], String> {
+            self.calls.push("rotate".into());
+            Ok(self.new_key)
+        }
+        fn revoke_device(&mut self, _: &DeviceID) -> Result<(), String> {
+            self.calls.push("revoke".into());
+            Ok(())
+        }
+        fn send_revocation(
+            &mut self,
+            contact_id: &ContactId,
+            revocation: &[u8],
+        ) -> Result<(), String> {
+            self.calls.push("notify".into());
+            if self.unreachable == Some(*contact_id) {
+                return Err("offline".into());
+            }
+            self.revocations.push(revocation.to_vec());
+            Ok(())
+        }
+        fn reset_session(&mut self, _: &ContactId) -> Result<(), String> {
+            self.calls.push("reset".into());
+            Ok(())
+        }
+        fn rekey_group(&mut self, _: &GroupID) -> Result<(), String> {
+            self.calls.push("rekey".into());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_identity_compromise_playbook() {
+        let (old_public, old_private) = generate_keypair();
+        let (new_public, _) = generate_keypair();
+        let context = IncidentContext {
+            contacts: vec![test_contact(1), test_contact(2)],
+            groups: vec![GroupHolding {
+                group_id: GroupID([9; 32]),
+                devices: vec![],
+            }],
+        };
+        let plan = IncidentPlan::for_scope(CompromiseScope::IdentityKey, &context);
+        assert_eq!(plan.severity(), IncidentSeverity::Critical);
+
+        let mut responder = Recorder {
+            unreachable: Some(test_contact(2)),
+            new_key: new_public,
+            ..Recorder::default()
+        };
+        let report = plan.execute(&mut responder, &old_private, 1_000).unwrap();
+        assert_eq!(
+            responder.calls,
+            ["rotate", "notify", "notify", "reset", "reset", "rekey"]
+        );
+        assert_eq!(report.new_identity_key, Some(new_public));
+        assert_eq!(report.notified_contacts(), vec![test_contact(1)]);
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.remaining_risks(),
+            vec![RemainingRisk::ContactNotNotified(test_contact(2))]
+        );
+        assert_eq!(report.to_json()["severity"], "critical");
+
+        // The revocation is signed by the revoked key and names the successor
+        let revocation = KeyRevocation::from_bytes(&responder.revocations[0]).unwrap();
+        assert_eq!(revocation.revoked, RevokedKey::Identity(old_public));
+        assert_eq!(revocation.successor, Some(new_public));
+        revocation.verify(&old_public).unwrap();
+        assert_eq!(
+            revocation.verify(&new_public),
+            Err(IncidentError::SignerMismatch)
+        );
+        let mut forged = revocation.clone();
+        forged.successor = Some([7; 32]);
+        assert_eq!(
+            forged.verify(&old_public),
+            Err(IncidentError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_device_and_group_scopes() {
+        let (identity_public, identity_private) = generate_keypair();
+        let lost = DeviceID([1; 16]);
+        let context = IncidentContext {
+            contacts: vec![test_contact(1)],
+            groups: vec![
+                GroupHolding {
+                    group_id: GroupID([1; 32]),
+                    devices: vec![lost, DeviceID([2; 16])],
+                },
+                GroupHolding {
+                    group_id: GroupID([2; 32]),
+                    devices: vec![DeviceID([2; 16])],
+                },
+            ],
+        };
+        let plan =
+            IncidentPlan::for_scope(CompromiseScope::DeviceKey { device_id: lost }, &context);
+        assert_eq!(
+            plan.actions,
+            vec![
+                IncidentAction::RevokeDevice { device_id: lost },
+                IncidentAction::NotifyContact {
+                    contact_id: test_contact(1)
+                },
+                IncidentAction::ResetSession {
+                    contact_id: test_contact(1)
+                },
+                IncidentAction::RekeyGroup {
+                    group_id: GroupID([1; 32])
+                },
+            ]
+        );
+        let mut responder = Recorder::default();
+        let report = plan
+            .execute(&mut responder, &identity_private, 1_000)
+            .unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.new_identity_key, None);
+        assert_eq!(
+            report.remaining_risks(),
+            vec![RemainingRisk::DeviceHistoryExposed(lost)]
+        );
+        let revocation = KeyRevocation::from_bytes(&responder.revocations[0]).unwrap();
+        assert_eq!(revocation.revoked, RevokedKey::Device(lost));
+        revocation.verify(&identity_public).unwrap();
+
+        let group = GroupID([5; 32]);
+        let plan =
+            IncidentPlan::for_scope(CompromiseScope::GroupSecret { group_id: group }, &context);
+        assert_eq!(plan.severity(), IncidentSeverity::Elevated);
+        assert_eq!(
+            plan.actions,
+            vec![IncidentAction::RekeyGroup { group_id: group }]
+        );
+    }
+}
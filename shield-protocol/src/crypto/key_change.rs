@@ -0,0 +1,452 @@
+/// Key-change blocking ("safety number changed" enforcement).
+///
+/// Each contact's Ed25519 identity key is pinned together with its trust
+/// level. When a different key shows up for a contact the user had
+/// **Verified**, the session is blocked until the app explicitly re-approves:
+///
+/// - outgoing sends fail with [`KeyChangeError::SendBlocked`] (checked by the
+///   two-phase ratchet commit, so nothing is queued for the new key);
+/// - incoming messages are held in a per-contact quarantine instead of being
+///   delivered, and can be listed, released or discarded through the API.
+///
+/// For contacts that were never verified a key change is reported but not
+/// blocked; the app shows its usual warning. Approving a change pins the new
+/// key at `Encrypted` (the user must re-verify to get back to `Verified`),
+/// moves the session to the new key's [`ContactId`] and releases the
+/// quarantine.
+///
+/// The guard also pins each contact's identity chain head (see
+/// [`key_continuity`](super::key_continuity)), advancing it on approval, and
+/// holds our own head for tagging outgoing envelopes.
+///
+/// A pending change must survive a restart, or restarting the app would lift
+/// the block. The app saves [`export_key_change_state`] in its encrypted store
+/// and passes it to [`import_key_change_state`] at startup, before the first
+/// send or receive. Quarantined plaintext is wiped from memory when it is
+/// released, discarded or dropped.
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::key_continuity::{ChainHead, ContinuityError, ContinuityTag, PinnedChain};
+use crate::crypto::pqc::TrustLevel;
+use crate::protocol::contact_id::{ContactId, ContactIdError};
+
+/// Held messages per contact; the oldest is dropped beyond this.
+pub const MAX_QUARANTINE_PER_CONTACT: usize = 256;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum KeyChangeError {
+    #[error("Identity key for {0} changed; sends are blocked until the change is approved")]
+    SendBlocked(ContactId),
+    #[error("Contact {0} is not registered")]
+    UnknownContact(ContactId),
+    #[error("No pending key change for {0}")]
+    NoPendingChange(ContactId),
+    #[error("Invalid identity key: {0}")]
+    InvalidKey(#[from] ContactIdError),
+    #[error("Encoding error: {0}")]
+    Encoding(String),
+}
+
+/// Result of comparing an observed identity key against the pinned one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyObservation {
+    Unchanged,
+    /// Key changed on an unverified contact; reported, not blocked.
+    ChangedUnverified,
+    /// Key changed on a verified contact; session is now blocked.
+    ChangedBlocked,
+}
+
+/// Whether an incoming message may be delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    Deliver(Vec<u8>),
+    /// Held in quarantine under this id.
+    Quarantined(u64),
+}
+
+/// An incoming message held while a key change is pending. Zeroed on drop.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct QuarantinedMessage {
+    pub id: u64,
+    pub received_at: u64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PinnedIdentity {
+    identity_key: [u8; 32],
+    trust: TrustLevel,
+    /// New key awaiting approval; set only for verified contacts.
+    pending_key: Option<[u8; 32]>,
+    quarantine: VecDeque<QuarantinedMessage>,
+    chain: PinnedChain,
+}
+
+/// Per-contact pinned identities and quarantines.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyChangeGuard {
+    contacts: HashMap<ContactId, PinnedIdentity>,
+    next_message_id: u64,
+    /// Our own identity chain head.
+    local_head: Option<ChainHead>,
+}
+
+fn to_key(key: &[u8]) -> Result<[u8; 32], KeyChangeError> {
+    <[u8; 32]>::try_from(key).map_err(|_| ContactIdError::InvalidKeyLength(key.len()).into())
+}
+
+impl KeyChangeGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin a contact's identity key at `trust`. Re-registering the same key
+    /// updates the trust level and keeps any pending change. The chain head
+    /// starts at the key's genesis; see [`pin_chain_head`](Self::pin_chain_head).
+    pub fn register(
+        &mut self,
+        identity_key: &[u8],
+        trust: TrustLevel,
+    ) -> Result<ContactId, KeyChangeError> {
+        let key = to_key(identity_key)?;
+        let id = ContactId::from_identity_key(&key)?;
+        self.contacts
+            .entry(id)
+            .and_modify(|c| c.trust = trust)
+            .or_insert(PinnedIdentity {
+                identity_key: key,
+                trust,
+                pending_key: None,
+                quarantine: VecDeque::new(),
+                chain: PinnedChain::new(ChainHead::genesis(&key)),
+            });
+        Ok(id)
+    }
+
+    /// Pin the chain head a contact advertised for a key with earlier
+    /// history (e.g. from its contact card).
+    pub fn pin_chain_head(
+        &mut self,
+        id: &ContactId,
+        head: ChainHead,
+    ) -> Result<(), KeyChangeError> {
+        self.contacts
+            .get_mut(id)
+            .map(|c| c.chain = PinnedChain::new(head))
+            .ok_or(KeyChangeError::UnknownContact(*id))
+    }
+
+    pub fn chain_head(&self, id: &ContactId) -> Option<ChainHead> {
+        self.contacts.get(id).map(|c| c.chain.head)
+    }
+
+    /// Compare the continuity tag of a decrypted envelope with the pinned
+    /// chain. Contacts that are not registered pass.
+    pub fn check_continuity(
+        &mut self,
+        id: &ContactId,
+        observed: Option<ContinuityTag>,
+    ) -> Result<(), ContinuityError> {
+        match self.contacts.get_mut(id) {
+            Some(contact) => contact.chain.check(id, observed),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_local_chain_head(&mut self, head: ChainHead) {
+        self.local_head = Some(head);
+    }
+
+    /// Tag for outgoing envelopes; `None` until our head is set.
+    pub fn local_continuity_tag(&self) -> Option<ContinuityTag> {
+        self.local_head.map(|head| head.tag())
+    }
+
+    pub fn set_trust(&mut self, id: &ContactId, trust: TrustLevel) -> Result<(), KeyChangeError> {
+        self.contacts
+            .get_mut(id)
+            .map(|c| c.trust = trust)
+            .ok_or(KeyChangeError::UnknownContact(*id))
+    }
+
+    pub fn trust(&self, id: &ContactId) -> Option<TrustLevel> {
+        self.contacts.get(id).map(|c| c.trust)
+    }
+
+    pub fn remove(&mut self, id: &ContactId) {
+        self.contacts.remove(id);
+    }
+
+    /// Compare the key a contact presented against the pinned one.
+    pub fn observe(
+        &mut self,
+        id: &ContactId,
+        current_key: &[u8],
+    ) -> Result<KeyObservation, KeyChangeError> {
+        let current = to_key(current_key)?;
+        let contact = self
+            .contacts
+            .get_mut(id)
+            .ok_or(KeyChangeError::UnknownContact(*id))?;
+        if bool::from(contact.identity_key.ct_eq(&current)) {
+            return Ok(KeyObservation::Unchanged);
+        }
+        if contact.trust == TrustLevel::Verified {
+            if contact.pending_key.is_none() {
+                log::warn!("Identity key changed for verified contact {}; blocking", id);
+            }
+            contact.pending_key = Some(current);
+            Ok(KeyObservation::ChangedBlocked)
+        } else {
+            Ok(KeyObservation::ChangedUnverified)
+        }
+    }
+
+    pub fn is_blocked(&self, id: &ContactId) -> bool {
+        self.contacts
+            .get(id)
+            .is_some_and(|c| c.pending_key.is_some())
+    }
+
+    /// Fails with `SendBlocked` while a key change awaits approval.
+    pub fn check_send(&self, id: &ContactId) -> Result<(), KeyChangeError> {
+        if self.is_blocked(id) {
+            Err(KeyChangeError::SendBlocked(*id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Deliver an incoming message, or hold it if the contact is blocked.
+    pub fn admit(&mut self, id: &ContactId, data: Vec<u8>, now: u64) -> Admission {
+        let Some(contact) = self.contacts.get_mut(id) else {
+            return Admission::Deliver(data);
+        };
+        if contact.pending_key.is_none() {
+            return Admission::Deliver(data);
+        }
+        self.next_message_id += 1;
+        let msg_id = self.next_message_id;
+        if contact.quarantine.len() >= MAX_QUARANTINE_PER_CONTACT {
+            contact.quarantine.pop_front();
+        }
+        contact.quarantine.push_back(QuarantinedMessage {
+            id: msg_id,
+            received_at: now,
+            data,
+        });
+        Admission::Quarantined(msg_id)
+    }
+
+    pub fn quarantined(&self, id: &ContactId) -> Vec<QuarantinedMessage> {
+        self.contacts
+            .get(id)
+            .map(|c| c.quarantine.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop held messages; returns how many were discarded.
+    pub fn discard_quarantined(&mut self, id: &ContactId) -> usize {
+        self.contacts
+            .get_mut(id)
+            .map(|c| c.quarantine.drain(..).count())
+            .unwrap_or(0)
+    }
+
+    /// Accept the new key: pin it at `Encrypted`, advance the chain to it,
+    /// move the session to the new key's id, and release the quarantine in
+    /// arrival order.
+    pub fn approve(
+        &mut self,
+        id: &ContactId,
+    ) -> Result<(ContactId, Vec<QuarantinedMessage>), KeyChangeError> {
+        let contact = self
+            .contacts
+            .get(id)
+            .ok_or(KeyChangeError::UnknownContact(*id))?;
+        let new_key = contact
+            .pending_key
+            .ok_or(KeyChangeError::NoPendingChange(*id))?;
+        let new_id = ContactId::from_identity_key(&new_key)?;
+
+        let mut contact = self.contacts.remove(id).expect("checked above");
+        let released = contact.quarantine.drain(..).collect();
+        self.contacts.insert(
+            new_id,
+            PinnedIdentity {
+                identity_key: new_key,
+                trust: TrustLevel::Encrypted,
+                pending_key: None,
+                quarantine: VecDeque::new(),
+                chain: PinnedChain::new(contact.chain.head.advance(&new_key)),
+            },
+        );
+        log::info!("Key change approved: {} -> {}", id, new_id);
+        Ok((new_id, released))
+    }
+
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+        self.local_head = None;
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, KeyChangeError> {
+        bincode::serialize(self).map_err(|e| KeyChangeError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, KeyChangeError> {
+        bincode::deserialize(data).map_err(|e| KeyChangeError::Encoding(e.to_string()))
+    }
+}
+
+// ==================== GLOBAL GUARD ====================
+
+static KEY_CHANGE_GUARD: Lazy<Mutex<KeyChangeGuard>> =
+    Lazy::new(|| Mutex::new(KeyChangeGuard::new()));
+
+/// Run `f` against the process-wide guard.
+pub fn with_guard<R>(f: impl FnOnce(&mut KeyChangeGuard) -> R) -> R {
+    f(&mut KEY_CHANGE_GUARD.lock().unwrap())
+}
+
+/// Send-path check against the process-wide guard.
+pub fn check_send(id: &ContactId) -> Result<(), KeyChangeError> {
+    KEY_CHANGE_GUARD.lock().unwrap().check_send(id)
+}
+
+/// Serialized process-wide guard, quarantine included, for the app to persist.
+pub fn export_key_change_state() -> Result<Vec<u8>, KeyChangeError> {
+    KEY_CHANGE_GUARD.lock().unwrap().to_bytes()
+}
+
+/// Replace the process-wide guard with one saved by [`export_key_change_state`].
+pub fn import_key_change_state(data: &[u8]) -> Result<(), KeyChangeError> {
+    let guard = KeyChangeGuard::from_bytes(data)?;
+    *KEY_CHANGE_GUARD.lock().unwrap() = guard;
+    Ok(())
+}
+
+/// Drop all pinned identities and quarantined messages (for Duress PIN).
+pub fn clear_key_change_guard() {
+    KEY_CHANGE_GUARD.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verified_key_change_blocks_and_quarantines() {
+        let mut guard = KeyChangeGuard::new();
+        let id = guard.register(&[1u8; 32], TrustLevel::Verified).unwrap();
+        assert_eq!(
+            guard.observe(&id, &[1u8; 32]),
+            Ok(KeyObservation::Unchanged)
+        );
+        assert_eq!(guard.admit(&id, vec![1], 10), Admission::Deliver(vec![1]));
+
+        assert_eq!(
+            guard.observe(&id, &[2u8; 32]),
+            Ok(KeyObservation::ChangedBlocked)
+        );
+        assert_eq!(guard.check_send(&id), Err(KeyChangeError::SendBlocked(id)));
+        assert!(matches!(
+            guard.admit(&id, vec![2], 11),
+            Admission::Quarantined(_)
+        ));
+        assert!(matches!(
+            guard.admit(&id, vec![3], 12),
+            Admission::Quarantined(_)
+        ));
+        assert_eq!(guard.quarantined(&id).len(), 2);
+
+        let (new_id, released) = guard.approve(&id).unwrap();
+        assert!(new_id.matches_identity_key(&[2u8; 32]));
+        assert_eq!(
+            guard.chain_head(&new_id),
+            Some(ChainHead::genesis(&[1u8; 32]).advance(&[2u8; 32]))
+        );
+        let data: Vec<_> = released.iter().map(|m| m.data.clone()).collect();
+        assert_eq!(data, vec![vec![2], vec![3]]);
+        assert_eq!(guard.check_send(&new_id), Ok(()));
+        assert_eq!(guard.trust(&new_id), Some(TrustLevel::Encrypted));
+        assert_eq!(guard.trust(&id), None);
+    }
+
+    #[test]
+    fn test_unverified_change_not_blocked() {
+        let mut guard = KeyChangeGuard::new();
+        let id = guard.register(&[3u8; 32], TrustLevel::Encrypted).unwrap();
+        assert_eq!(
+            guard.observe(&id, &[4u8; 32]),
+            Ok(KeyObservation::ChangedUnverified)
+        );
+        assert_eq!(guard.check_send(&id), Ok(()));
+        assert_eq!(guard.admit(&id, vec![9], 0), Admission::Deliver(vec![9]));
+        assert_eq!(guard.approve(&id), Err(KeyChangeError::NoPendingChange(id)));
+    }
+
+    #[test]
+    fn test_quarantine_is_bounded_and_discardable() {
+        let mut guard = KeyChangeGuard::new();
+        let id = guard.register(&[5u8; 32], TrustLevel::Verified).unwrap();
+        guard.observe(&id, &[6u8; 32]).unwrap();
+        for i in 0..MAX_QUARANTINE_PER_CONTACT + 3 {
+            guard.admit(&id, vec![i as u8], i as u64);
+        }
+        let held = guard.quarantined(&id);
+        assert_eq!(held.len(), MAX_QUARANTINE_PER_CONTACT);
+        assert_eq!(held[0].received_at, 3);
+        assert_eq!(guard.discard_quarantined(&id), MAX_QUARANTINE_PER_CONTACT);
+        // Still blocked until approved.
+        assert!(guard.is_blocked(&id));
+    }
+
+    #[test]
+    fn test_state_roundtrip_keeps_block_and_pins() {
+        let mut guard = KeyChangeGuard::new();
+        let id = guard.register(&[7u8; 32], TrustLevel::Verified).unwrap();
+        let head = ChainHead::genesis(&[0u8; 32]).advance(&[7u8; 32]);
+        guard.pin_chain_head(&id, head).unwrap();
+        guard.set_local_chain_head(ChainHead::genesis(&[9u8; 32]));
+        guard.observe(&id, &[8u8; 32]).unwrap();
+        guard.admit(&id, vec![42], 5);
+
+        let mut restored = KeyChangeGuard::from_bytes(&guard.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            restored.check_send(&id),
+            Err(KeyChangeError::SendBlocked(id))
+        );
+        assert_eq!(restored.quarantined(&id), guard.quarantined(&id));
+        assert_eq!(restored.chain_head(&id), Some(head));
+        assert_eq!(
+            restored.local_continuity_tag(),
+            guard.local_continuity_tag()
+        );
+        // Ids keep counting from where the saved guard stopped.
+        assert_eq!(restored.admit(&id, vec![43], 6), Admission::Quarantined(2));
+        assert!(matches!(
+            KeyChangeGuard::from_bytes(&[0xff; 3]),
+            Err(KeyChangeError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_quarantined_message_zeroizes() {
+        let mut msg = QuarantinedMessage {
+            id: 1,
+            received_at: 2,
+            data: vec![0xaa; 16],
+        };
+        msg.zeroize();
+        assert!(msg.data.is_empty());
+        assert_eq!((msg.id, msg.received_at), (0, 0));
+    }
+}
@@ -0,0 +1,153 @@
+/// Identity key continuity bound into the message envelope.
+///
+/// Every identity key a user has held forms a hash chain: the head starts as
+/// a hash of the first key and is re-hashed with each replacement key. The
+/// sender puts the first [`CONTINUITY_TAG_LEN`] bytes of its current head in
+/// every sequenced envelope (inside the AEAD, see
+/// [`SequencedEnvelope`](crate::protocol::ordering::SequencedEnvelope)), and
+/// the receiver compares them with the head it pinned for that contact in the
+/// [`KeyChangeGuard`](super::key_change::KeyChangeGuard).
+///
+/// If a contact's identity is replaced mid-conversation (a reinstall with a
+/// new key, or a session re-established by someone else), envelopes from the
+/// new identity carry a different tag and are reported as
+/// [`ContinuityError::Mismatch`], even when the contact-card update that
+/// should have announced the new key never arrived. Once a contact has sent
+/// tagged envelopes, an untagged one is [`ContinuityError::Missing`].
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+use crate::protocol::contact_id::ContactId;
+
+/// Bytes of the chain head carried in each envelope.
+pub const CONTINUITY_TAG_LEN: usize = 8;
+
+const GENESIS_CONTEXT: &str = "ShieldMessenger-IdentityChain-Genesis-v1";
+const LINK_CONTEXT: &str = "ShieldMessenger-IdentityChain-Link-v1";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ContinuityError {
+    #[error("Identity chain of {contact_id} changed: expected {expected}, got {observed}")]
+    Mismatch {
+        contact_id: ContactId,
+        expected: ContinuityTag,
+        observed: ContinuityTag,
+    },
+    #[error("Envelope from {0} carries no continuity tag although earlier ones did")]
+    Missing(ContactId),
+}
+
+/// Head of a user's identity key chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHead(pub [u8; 32]);
+
+impl ChainHead {
+    /// Head for a user whose first identity key is `identity_key`.
+    pub fn genesis(identity_key: &[u8; 32]) -> Self {
+        Self(blake3::derive_key(GENESIS_CONTEXT, identity_key))
+    }
+
+    /// Head after the identity key was replaced by `new_identity_key`.
+    pub fn advance(&self, new_identity_key: &[u8; 32]) -> Self {
+        let mut material = [0u8; 64];
+        material[..32].copy_from_slice(&self.0);
+        material[32..].copy_from_slice(new_identity_key);
+        Self(blake3::derive_key(LINK_CONTEXT, &material))
+    }
+
+    pub fn tag(&self) -> ContinuityTag {
+        let mut tag = [0u8; CONTINUITY_TAG_LEN];
+        tag.copy_from_slice(&self.0[..CONTINUITY_TAG_LEN]);
+        ContinuityTag(tag)
+    }
+}
+
+/// Short form of a [`ChainHead`] as carried in envelopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContinuityTag(pub [u8; CONTINUITY_TAG_LEN]);
+
+impl fmt::Display for ContinuityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+/// Pinned chain of one contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedChain {
+    pub head: ChainHead,
+    /// The contact has sent at least one tagged envelope.
+    pub tagged: bool,
+}
+
+impl PinnedChain {
+    pub fn new(head: ChainHead) -> Self {
+        Self {
+            head,
+            tagged: false,
+        }
+    }
+
+    /// Check the tag of an envelope from `contact_id`. Untagged envelopes
+    /// are accepted until the contact's first tagged one (older clients).
+    pub fn check(
+        &mut self,
+        contact_id: &ContactId,
+        observed: Option<ContinuityTag>,
+    ) -> Result<(), ContinuityError> {
+        let expected = self.head.tag();
+        match observed {
+            None if self.tagged => Err(ContinuityError::Missing(*contact_id)),
+            None => Ok(()),
+            Some(observed) if crate::crypto::eq_slices(&observed.0, &expected.0) => {
+                self.tagged = true;
+                Ok(())
+            }
+            Some(observed) => Err(ContinuityError::Mismatch {
+                contact_id: *contact_id,
+                expected,
+                observed,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    #[test]
+    fn test_chain_depends_on_whole_history() {
+        let (a, b, c) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let head = ChainHead::genesis(&a).advance(&b);
+        assert_eq!(head, ChainHead::genesis(&a).advance(&b));
+        // Same current key, different past
+        assert_ne!(head.tag(), ChainHead::genesis(&c).advance(&b).tag());
+        assert_ne!(head.tag(), ChainHead::genesis(&b).tag());
+        assert_eq!(head.tag().to_string().len(), 2 * CONTINUITY_TAG_LEN);
+    }
+
+    #[test]
+    fn test_pinned_chain_check() {
+        let id = test_contact(1);
+        let head = ChainHead::genesis(&[1u8; 32]);
+        let mut pinned = PinnedChain::new(head);
+
+        // Legacy envelopes pass until the first tagged one
+        assert_eq!(pinned.check(&id, None), Ok(()));
+        assert_eq!(pinned.check(&id, Some(head.tag())), Ok(()));
+        assert_eq!(pinned.check(&id, None), Err(ContinuityError::Missing(id)));
+
+        let swapped = ChainHead::genesis(&[9u8; 32]).tag();
+        assert_eq!(
+            pinned.check(&id, Some(swapped)),
+            Err(ContinuityError::Mismatch {
+                contact_id: id,
+                expected: head.tag(),
+                observed: swapped,
+            })
+        );
+    }
+}
@@ -2,6 +2,8 @@ use rand::rngs::OsRng;
 use thiserror::Error;
 use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
 
+use crate::rng::SecureRng;
+
 #[derive(Error, Debug)]
 pub enum KeyExchangeError {
     #[error("Invalid key length")]
@@ -28,7 +30,12 @@ pub fn generate_ephemeral_key() -> ([u8; 32], EphemeralSecret) {
 /// # Returns
 /// (public_key, secret_key) - Both as 32-byte arrays
 pub fn generate_static_keypair() -> ([u8; 32], [u8; 32]) {
-    let secret = StaticSecret::random_from_rng(OsRng);
+    generate_static_keypair_with_rng(&mut OsRng)
+}
+
+/// [`generate_static_keypair`] drawing from `rng`.
+pub fn generate_static_keypair_with_rng(rng: &mut impl SecureRng) -> ([u8; 32], [u8; 32]) {
+    let secret = StaticSecret::random_from_rng(rng);
     let public = PublicKey::from(&secret);
 
     (public.to_bytes(), secret.to_bytes())
@@ -0,0 +1,539 @@
+use crate::rng::{OsRng, SecureRng};
+/// Per-frame media encryption with an SFrame-like layout (RFC 9605).
+///
+/// Each encrypted frame is `header ‖ ciphertext ‖ tag`. The header carries the
+/// sender's key id (KID) and a frame counter (CTR) in SFrame's compact
+/// encoding, followed by the sender's 8-byte epoch, and is authenticated
+/// together with caller-supplied metadata. The nonce is `salt XOR CTR`, so a
+/// counter is never reused with one key.
+///
+/// Keys come from the session: each call participant derives its frame
+/// secret from the session key and its KID ([`derive_frame_secret`]), and
+/// receivers derive the same secret for every sender they expect. Every
+/// [`FrameSender`] draws a random epoch that is mixed into its key and salt,
+/// so a sender re-created after a restart, or two participants that picked
+/// the same KID, start their counters at 0 under different keys instead of
+/// reusing nonces. Suites with
+/// truncated tags (ChaCha20 + HMAC-SHA256, like SFrame's AES-CTR suites) keep
+/// per-frame overhead small for audio, where frames are tens of bytes.
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum FrameError {
+    #[error("Malformed frame")]
+    Malformed,
+    #[error("Unknown key id {0}")]
+    UnknownKey(u64),
+    #[error("Frame authentication failed")]
+    AuthenticationFailed,
+    #[error("Frame counter {0} already seen or too old")]
+    Replay(u64),
+    #[error("Frame counter exhausted; rekey the sender")]
+    CounterExhausted,
+    #[error("Key derivation failed")]
+    KeyDerivation,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SECRET_INFO: &[u8] = b"ShieldMessenger-SFrame-v1";
+
+/// Length of the sender epoch that follows the KID and CTR in the header.
+pub const EPOCH_LEN: usize = 8;
+
+/// Receivers accept counters up to this far behind the highest seen.
+pub const REPLAY_WINDOW: u64 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameSuite {
+    /// Full 16-byte Poly1305 tag.
+    ChaCha20Poly1305,
+    /// ChaCha20 + HMAC-SHA256 truncated to 10 bytes.
+    ChaCha20HmacSha256_80,
+    /// ChaCha20 + HMAC-SHA256 truncated to 8 bytes.
+    ChaCha20HmacSha256_64,
+    /// ChaCha20 + HMAC-SHA256 truncated to 4 bytes. Audio only.
+    ChaCha20HmacSha256_32,
+}
+
+impl FrameSuite {
+    pub fn id(self) -> u16 {
+        match self {
+            FrameSuite::ChaCha20Poly1305 => 0x0101,
+            FrameSuite::ChaCha20HmacSha256_80 => 0x0102,
+            FrameSuite::ChaCha20HmacSha256_64 => 0x0103,
+            FrameSuite::ChaCha20HmacSha256_32 => 0x0104,
+        }
+    }
+
+    pub fn tag_len(self) -> usize {
+        match self {
+            FrameSuite::ChaCha20Poly1305 => 16,
+            FrameSuite::ChaCha20HmacSha256_80 => 10,
+            FrameSuite::ChaCha20HmacSha256_64 => 8,
+            FrameSuite::ChaCha20HmacSha256_32 => 4,
+        }
+    }
+}
+
+/// Frame secret for sender `kid`, from a 32-byte session key.
+pub fn derive_frame_secret(session_key: &[u8; 32], kid: u64) -> [u8; 32] {
+    let hkdf = Hkdf::<Sha256>::new(Some(SECRET_INFO), session_key);
+    let mut secret = [0u8; 32];
+    hkdf.expand(&kid.to_be_bytes(), &mut secret)
+        .expect("HKDF output length valid");
+    secret
+}
+
+/// Header: config byte `X KKK Y CCC`, then the long KID and CTR if present,
+/// then the sender epoch. KID and CTR values below 8 fit in the config byte;
+/// larger ones are written big-endian in the fewest bytes, with the length
+/// minus one in the config byte. The epoch is always [`EPOCH_LEN`] bytes.
+pub fn encode_header(kid: u64, epoch: u64, ctr: u64) -> Vec<u8> {
+    fn field(value: u64, out: &mut Vec<u8>) -> u8 {
+        if value < 8 {
+            return value as u8;
+        }
+        let len = 8 - (value.leading_zeros() as usize / 8);
+        out.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+        0x08 | (len as u8 - 1)
+    }
+    let mut rest = Vec::with_capacity(16 + EPOCH_LEN);
+    let k = field(kid, &mut rest);
+    let c = field(ctr, &mut rest);
+    rest.extend_from_slice(&epoch.to_be_bytes());
+    let mut out = Vec::with_capacity(1 + rest.len());
+    out.push((k << 4) | c);
+    out.extend_from_slice(&rest);
+    out
+}
+
+/// Parse a header. Returns `(kid, epoch, ctr, header_len)`.
+pub fn decode_header(data: &[u8]) -> Result<(u64, u64, u64, usize), FrameError> {
+    let (&config, _) = data.split_first().ok_or(FrameError::Malformed)?;
+    let mut pos = 1;
+    let mut field = |bits: u8| -> Result<u64, FrameError> {
+        if bits & 0x08 == 0 {
+            return Ok(bits as u64);
+        }
+        let len = (bits & 0x07) as usize + 1;
+        let bytes = data.get(pos..pos + len).ok_or(FrameError::Malformed)?;
+        pos += len;
+        let mut buf = [0u8; 8];
+        buf[8 - len..].copy_from_slice(bytes);
+        Ok(u64::from_be_bytes(buf))
+    };
+    let kid = field(config >> 4)?;
+    let ctr = field(config & 0x0f)?;
+    let epoch = data
+        .get(pos..pos + EPOCH_LEN)
+        .ok_or(FrameError::Malformed)?;
+    let epoch = u64::from_be_bytes(epoch.try_into().expect("epoch length checked"));
+    Ok((kid, epoch, ctr, pos + EPOCH_LEN))
+}
+
+/// Per-sender key material.
+struct FrameKey {
+    suite: FrameSuite,
+    /// AEAD key, or the ChaCha20 key for HMAC suites.
+    enc_key: [u8; 32],
+    /// HMAC key for HMAC suites.
+    auth_key: [u8; 32],
+    salt: [u8; 12],
+}
+
+impl Drop for FrameKey {
+    fn drop(&mut self) {
+        self.enc_key.zeroize();
+        self.auth_key.zeroize();
+        self.salt.zeroize();
+    }
+}
+
+impl FrameKey {
+    fn new(secret: &[u8; 32], kid: u64, epoch: u64, suite: FrameSuite) -> Result<Self, FrameError> {
+        let hkdf = Hkdf::<Sha256>::from_prk(secret).map_err(|_| FrameError::KeyDerivation)?;
+        let mut context = Vec::with_capacity(18);
+        context.extend_from_slice(&kid.to_be_bytes());
+        context.extend_from_slice(&suite.id().to_be_bytes());
+        context.extend_from_slice(&epoch.to_be_bytes());
+        let expand = |label: &[u8], out: &mut [u8]| {
+            hkdf.expand_multi_info(&[label, &context], out)
+                .map_err(|_| FrameError::KeyDerivation)
+        };
+        let mut key = Self {
+            suite,
+            enc_key: [0u8; 32],
+            auth_key: [0u8; 32],
+            salt: [0u8; 12],
+        };
+        expand(b"SFrame 1.0 Secret key ", &mut key.enc_key)?;
+        expand(b"SFrame 1.0 Secret salt ", &mut key.salt)?;
+        expand(b"SFrame 1.0 HMAC auth key ", &mut key.auth_key)?;
+        Ok(key)
+    }
+
+    fn nonce(&self, ctr: u64) -> [u8; 12] {
+        let mut nonce = self.salt;
+        for (n, c) in nonce[4..].iter_mut().zip(ctr.to_be_bytes()) {
+            *n ^= c;
+        }
+        nonce
+    }
+
+    /// Truncated HMAC over lengths, nonce, AAD and ciphertext (RFC 9605 §4.5.1).
+    fn mac(&self, nonce: &[u8; 12], aad: &[u8], ct: &[u8]) -> Vec<u8> {
+        let tag_len = self.suite.tag_len();
+        let mut mac =
+            <HmacSha256 as Mac>::new_from_slice(&self.auth_key).expect("HMAC key length valid");
+        mac.update(&(aad.len() as u64).to_be_bytes());
+        mac.update(&(ct.len() as u64).to_be_bytes());
+        mac.update(&(tag_len as u64).to_be_bytes());
+        mac.update(nonce);
+        mac.update(aad);
+        mac.update(ct);
+        mac.finalize().into_bytes()[..tag_len].to_vec()
+    }
+
+    fn seal(&self, ctr: u64, aad: &[u8], frame: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let nonce = self.nonce(ctr);
+        if self.suite == FrameSuite::ChaCha20Poly1305 {
+            let cipher = ChaCha20Poly1305::new_from_slice(&self.enc_key)
+                .map_err(|_| FrameError::KeyDerivation)?;
+            return cipher
+                .encrypt(Nonce::from_slice(&nonce), Payload { msg: frame, aad })
+                .map_err(|_| FrameError::Malformed);
+        }
+        let mut ct = frame.to_vec();
+        ChaCha20::new(&self.enc_key.into(), &nonce.into()).apply_keystream(&mut ct);
+        let tag = self.mac(&nonce, aad, &ct);
+        ct.extend_from_slice(&tag);
+        Ok(ct)
+    }
+
+    fn open(&self, ctr: u64, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let nonce = self.nonce(ctr);
+        if self.suite == FrameSuite::ChaCha20Poly1305 {
+            let cipher = ChaCha20Poly1305::new_from_slice(&self.enc_key)
+                .map_err(|_| FrameError::KeyDerivation)?;
+            return cipher
+                .decrypt(Nonce::from_slice(&nonce), Payload { msg: sealed, aad })
+                .map_err(|_| FrameError::AuthenticationFailed);
+        }
+        let split = sealed
+            .len()
+            .checked_sub(self.suite.tag_len())
+            .ok_or(FrameError::Malformed)?;
+        let (ct, tag) = sealed.split_at(split);
+        if !crate::crypto::eq_slices(&self.mac(&nonce, aad, ct), tag) {
+            return Err(FrameError::AuthenticationFailed);
+        }
+        let mut pt = ct.to_vec();
+        ChaCha20::new(&self.enc_key.into(), &nonce.into()).apply_keystream(&mut pt);
+        Ok(pt)
+    }
+}
+
+fn aad(header: &[u8], metadata: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + metadata.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(metadata);
+    aad
+}
+
+/// Encrypts one participant's outgoing frames.
+pub struct FrameSender {
+    kid: u64,
+    epoch: u64,
+    key: FrameKey,
+    next_ctr: u64,
+}
+
+impl FrameSender {
+    /// New sender with a fresh random epoch.
+    pub fn new(secret: &[u8; 32], kid: u64, suite: FrameSuite) -> Result<Self, FrameError> {
+        Self::new_with_rng(secret, kid, suite, &mut OsRng)
+    }
+
+    /// [`new`](Self::new) drawing the epoch from `rng`.
+    pub fn new_with_rng(
+        secret: &[u8; 32],
+        kid: u64,
+        suite: FrameSuite,
+        rng: &mut impl SecureRng,
+    ) -> Result<Self, FrameError> {
+        let epoch = rng.next_u64();
+        Ok(Self {
+            kid,
+            epoch,
+            key: FrameKey::new(secret, kid, epoch, suite)?,
+            next_ctr: 0,
+        })
+    }
+
+    pub fn kid(&self) -> u64 {
+        self.kid
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Encrypt a frame. `metadata` (e.g. the RTP header) is authenticated but
+    /// not encrypted or included in the output.
+    pub fn encrypt(&mut self, metadata: &[u8], frame: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let ctr = self.next_ctr;
+        self.next_ctr = ctr.checked_add(1).ok_or(FrameError::CounterExhausted)?;
+        let mut out = encode_header(self.kid, self.epoch, ctr);
+        let sealed = self.key.seal(ctr, &aad(&out, metadata), frame)?;
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+}
+
+/// A decrypted frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptedFrame {
+    pub kid: u64,
+    pub epoch: u64,
+    pub ctr: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Replay window for one epoch of one sender.
+struct EpochState {
+    key: FrameKey,
+    /// Highest counter accepted, and a bitmap of the window below it.
+    highest: Option<u64>,
+    seen: u128,
+}
+
+impl EpochState {
+    fn check(&self, ctr: u64) -> Result<(), FrameError> {
+        match self.highest {
+            Some(high) if ctr <= high => {
+                let age = high - ctr;
+                if age >= REPLAY_WINDOW || self.seen & (1 << age) != 0 {
+                    return Err(FrameError::Replay(ctr));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn mark(&mut self, ctr: u64) {
+        match self.highest {
+            Some(high) if ctr <= high => self.seen |= 1 << (high - ctr),
+            Some(high) => {
+                let shift = ctr - high;
+                self.seen = if shift >= REPLAY_WINDOW {
+                    0
+                } else {
+                    self.seen << shift
+                } | 1;
+                self.highest = Some(ctr);
+            }
+            None => {
+                self.seen = 1;
+                self.highest = Some(ctr);
+            }
+        }
+    }
+}
+
+/// Everything received from one KID. Epochs are kept once seen, so frames
+/// from a sender's earlier epoch cannot be replayed into a fresh window.
+struct ReceiveState {
+    secret: [u8; 32],
+    suite: FrameSuite,
+    epochs: HashMap<u64, EpochState>,
+}
+
+impl Drop for ReceiveState {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// Decrypts frames from every sender in a call.
+#[derive(Default)]
+pub struct FrameReceiver {
+    senders: HashMap<u64, ReceiveState>,
+}
+
+impl FrameReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept frames from `kid`. Replaces any earlier key for it.
+    pub fn add_key(
+        &mut self,
+        secret: &[u8; 32],
+        kid: u64,
+        suite: FrameSuite,
+    ) -> Result<(), FrameError> {
+        self.senders.insert(
+            kid,
+            ReceiveState {
+                secret: *secret,
+                suite,
+                epochs: HashMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove_key(&mut self, kid: u64) -> bool {
+        self.senders.remove(&kid).is_some()
+    }
+
+    /// Decrypt a frame produced by [`FrameSender::encrypt`] with the same
+    /// `metadata`. Replayed counters are rejected. A new epoch is only
+    /// remembered once a frame in it authenticates.
+    pub fn decrypt(&mut self, metadata: &[u8], data: &[u8]) -> Result<DecryptedFrame, FrameError> {
+        let (kid, epoch, ctr, header_len) = decode_header(data)?;
+        let sender = self
+            .senders
+            .get_mut(&kid)
+            .ok_or(FrameError::UnknownKey(kid))?;
+        let (header, sealed) = data.split_at(header_len);
+        let aad = aad(header, metadata);
+        let payload = match sender.epochs.get_mut(&epoch) {
+            Some(state) => {
+                state.check(ctr)?;
+                let payload = state.key.open(ctr, &aad, sealed)?;
+                state.mark(ctr);
+                payload
+            }
+            None => {
+                let key = FrameKey::new(&sender.secret, kid, epoch, sender.suite)?;
+                let payload = key.open(ctr, &aad, sealed)?;
+                let mut state = EpochState {
+                    key,
+                    highest: None,
+                    seen: 0,
+                };
+                state.mark(ctr);
+                sender.epochs.insert(epoch, state);
+                payload
+            }
+        };
+        Ok(DecryptedFrame {
+            kid,
+            epoch,
+            ctr,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_encoding() {
+        for (kid, ctr, len) in [(0, 0, 9), (7, 5, 9), (8, 3, 10), (300, 70_000, 14)] {
+            let header = encode_header(kid, 42, ctr);
+            assert_eq!(header.len(), len);
+            assert_eq!(decode_header(&header), Ok((kid, 42, ctr, len)));
+        }
+        let max = encode_header(u64::MAX, u64::MAX, u64::MAX);
+        assert_eq!(decode_header(&max), Ok((u64::MAX, u64::MAX, u64::MAX, 25)));
+        assert_eq!(decode_header(&max[..20]), Err(FrameError::Malformed));
+    }
+
+    #[test]
+    fn test_round_trip_truncated_tags_and_replay() {
+        let session = [0x11; 32];
+        for suite in [
+            FrameSuite::ChaCha20Poly1305,
+            FrameSuite::ChaCha20HmacSha256_80,
+            FrameSuite::ChaCha20HmacSha256_32,
+        ] {
+            let secret = derive_frame_secret(&session, 9);
+            let mut sender = FrameSender::new(&secret, 9, suite).unwrap();
+            let mut receiver = FrameReceiver::new();
+            receiver.add_key(&secret, 9, suite).unwrap();
+
+            let first = sender.encrypt(b"rtp", b"opus frame").unwrap();
+            assert_eq!(first.len(), 2 + EPOCH_LEN + 10 + suite.tag_len());
+            let second = sender.encrypt(b"rtp", b"next").unwrap();
+
+            // Out of order within the window is fine; a replay is not
+            assert_eq!(receiver.decrypt(b"rtp", &second).unwrap().ctr, 1);
+            let frame = receiver.decrypt(b"rtp", &first).unwrap();
+            assert_eq!(frame.payload, b"opus frame");
+            assert_eq!(receiver.decrypt(b"rtp", &first), Err(FrameError::Replay(0)));
+
+            // Metadata is authenticated
+            let third = sender.encrypt(b"rtp", b"x").unwrap();
+            assert_eq!(
+                receiver.decrypt(b"other", &third),
+                Err(FrameError::AuthenticationFailed)
+            );
+        }
+
+        // Another sender's secret does not open the frame
+        let mut sender = FrameSender::new(
+            &derive_frame_secret(&session, 1),
+            1,
+            FrameSuite::ChaCha20Poly1305,
+        )
+        .unwrap();
+        let mut receiver = FrameReceiver::new();
+        receiver
+            .add_key(
+                &derive_frame_secret(&session, 2),
+                1,
+                FrameSuite::ChaCha20Poly1305,
+            )
+            .unwrap();
+        let frame = sender.encrypt(b"", b"video").unwrap();
+        assert_eq!(
+            receiver.decrypt(b"", &frame),
+            Err(FrameError::AuthenticationFailed)
+        );
+    }
+
+    #[test]
+    fn test_recreated_sender_never_reuses_a_nonce() {
+        let secret = derive_frame_secret(&[0x22; 32], 5);
+        let suite = FrameSuite::ChaCha20HmacSha256_32;
+        let mut first = FrameSender::new(&secret, 5, suite).unwrap();
+        let mut second = FrameSender::new(&secret, 5, suite).unwrap();
+        assert_ne!(first.epoch(), second.epoch());
+
+        // Same (session key, KID) and same counters, different nonces
+        let mut nonces = std::collections::HashSet::new();
+        for sender in [&first, &second] {
+            for ctr in 0..64 {
+                assert!(nonces.insert(sender.key.nonce(ctr)));
+            }
+        }
+        let a = first.encrypt(b"", b"same frame").unwrap();
+        let b = second.encrypt(b"", b"same frame").unwrap();
+        assert_ne!(a[1 + EPOCH_LEN..], b[1 + EPOCH_LEN..]);
+
+        // The receiver follows the restart, and the old epoch stays replay-protected
+        let mut receiver = FrameReceiver::new();
+        receiver.add_key(&secret, 5, suite).unwrap();
+        assert_eq!(receiver.decrypt(b"", &a).unwrap().payload, b"same frame");
+        let frame = receiver.decrypt(b"", &b).unwrap();
+        assert_eq!((frame.epoch, frame.ctr), (second.epoch(), 0));
+        assert_eq!(receiver.decrypt(b"", &a), Err(FrameError::Replay(0)));
+    }
+}
@@ -5,41 +5,81 @@ pub mod deadman;
 pub mod duress;
 pub mod encryption;
 pub mod hashing;
+// Incident plans re-key groups, so they need the CRDT types
+#[cfg(feature = "groups")]
+pub mod incident;
+pub mod key_change;
+pub mod key_continuity;
 pub mod key_exchange;
+pub mod media_frame;
 pub mod pq_ratchet;
 pub mod pqc;
+pub mod pseudonym;
 pub mod ratchet;
 pub mod replay_cache;
+pub mod resumption;
 pub mod signing;
-#[cfg(not(target_arch = "wasm32"))]
+pub mod state_machine;
+pub mod verification_code;
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
 pub mod zkproofs;
 
 pub use constant_time::{eq_24, eq_32, eq_64, eq_slices};
 pub use pq_ratchet::{ChainDirection, PQRatchetError, PQRatchetState};
 
 pub use backup::{
-    create_encrypted_backup, reconstruct_secret, restore_encrypted_backup, split_secret,
-    BackupBlob, SecretShare,
+    add_backup_recipient, create_encrypted_backup, create_wrapped_backup, reconstruct_secret,
+    restore_encrypted_backup, restore_wrapped_backup, split_secret, BackupBlob, BackupRecipient,
+    SecretShare,
 };
 pub use deadman::{CheckInResult, DeadManSwitch, WipeAction};
 pub use duress::{
-    execute_emergency_wipe, DecoyProfile, DuressConfig, DuressError, DuressManager,
-    PinVerifyResult, WipeActions as DuressWipeActions,
+    execute_emergency_wipe, validate_duress_pin, DecoyProfile, DuressConfig, DuressError,
+    DuressManager, PinIssue, PinIssueSeverity, PinValidation, PinVerifyResult,
+    WipeActions as DuressWipeActions,
 };
 pub use encryption::{
     decrypt_message, decrypt_message_with_evolution, derive_message_key,
     derive_receive_key_at_sequence, derive_root_key, encrypt_message,
-    encrypt_message_with_evolution, evolve_chain_key,
+    encrypt_message_with_evolution, encrypt_message_with_rng, evolve_chain_key,
 };
 pub use hashing::{hash_handle, hash_password};
+#[cfg(feature = "groups")]
+pub use incident::{
+    ActionOutcome, CompromiseScope, GroupHolding, IncidentAction, IncidentContext, IncidentError,
+    IncidentPlan, IncidentReport, IncidentResponder, IncidentSeverity, KeyRevocation,
+    RemainingRisk, RevokedKey,
+};
+pub use key_change::{
+    Admission, KeyChangeError, KeyChangeGuard, KeyObservation, QuarantinedMessage,
+};
+pub use key_continuity::{ChainHead, ContinuityError, ContinuityTag, CONTINUITY_TAG_LEN};
 pub use key_exchange::{derive_shared_secret, generate_ephemeral_key};
+pub use media_frame::{
+    derive_frame_secret, DecryptedFrame, FrameError, FrameReceiver, FrameSender, FrameSuite,
+};
 pub use pqc::{
     detect_identity_key_change, generate_hybrid_keypair_from_seed, generate_hybrid_keypair_random,
     generate_safety_number, hybrid_decapsulate, hybrid_encapsulate, verify_contact_fingerprint,
     verify_safety_number, ContactVerificationRecord, FingerprintQrPayload, HybridCiphertext,
     HybridKEMKeypair, IdentityKeyChangeResult, TrustLevel, VerificationStatus,
 };
-pub use ratchet::{PQDoubleRatchet, RatchetHeader, RatchetState};
+pub use pseudonym::{derive_pseudonym, ConversationTag, LinkProof, PseudonymError, PseudonymKeys};
+pub use ratchet::{PQDoubleRatchet, RatchetHeader, RatchetHealth, RatchetState, RotationPolicy};
+pub use resumption::{
+    ResumeAccept, ResumeRequest, ResumptionBook, ResumptionConfig, ResumptionError, SessionTicket,
+};
 pub use signing::{generate_keypair, sign_data, verify_signature};
-#[cfg(not(target_arch = "wasm32"))]
-pub use zkproofs::{generate_range_proof, verify_range_proof};
+pub use state_machine::{
+    Guard, GuardContext, SessionEvent, SessionMachine, SessionState, Transition, TransitionError,
+};
+pub use verification_code::{
+    seconds_remaining, VerificationCodeConfig, VerificationCodeError, VerificationKey,
+};
+#[cfg(all(feature = "zkproofs", not(target_arch = "wasm32")))]
+pub use zkproofs::{
+    derive_membership_keypair, generate_membership_proof, generate_range_proof,
+    issue_attribute_credential, prove_attribute_predicate, verify_attribute_predicate,
+    verify_membership_proof, verify_range_proof, AttributeCredential, AttributeOpening,
+    AttributePredicate, AttributeProof, MembershipProof, MAX_ATTRIBUTE_SET,
+};
@@ -14,13 +14,13 @@ use crate::crypto::encryption::{
     evolve_chain_key, EncryptionError,
 };
 use crate::crypto::pqc::{
-    hybrid_decapsulate, hybrid_encapsulate, HybridCiphertext, HybridKEMKeypair,
+    hybrid_decapsulate, hybrid_encapsulate_with_rng, HybridCiphertext, HybridKEMKeypair,
 };
+use crate::rng::{OsRng, SecureRng};
 use chacha20poly1305::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{Aead, KeyInit},
     XChaCha20Poly1305, XNonce,
 };
-use rand::RngCore;
 use std::collections::HashMap;
 use std::fmt;
 use thiserror::Error;
@@ -124,6 +124,15 @@ impl PQRatchetState {
     /// Encrypt a message and advance the sending chain.
     /// Wire format: [version:1][sequence:8][nonce:24][ciphertext+tag]
     pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, PQRatchetError> {
+        self.encrypt_with_rng(plaintext, &mut OsRng)
+    }
+
+    /// [`Self::encrypt`] drawing the nonce from `rng`.
+    pub fn encrypt_with_rng(
+        &mut self,
+        plaintext: &[u8],
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>, PQRatchetError> {
         let seq = self.sending_sequence;
         let mut message_key = derive_message_key(&self.sending_chain_key)?;
         let new_chain = evolve_chain_key(&mut self.sending_chain_key)?;
@@ -133,7 +142,7 @@ impl PQRatchetState {
         let cipher = XChaCha20Poly1305::new_from_slice(&message_key)
             .map_err(|_| EncryptionError::InvalidKeyLength)?;
         let mut nonce_bytes = [0u8; 24];
-        OsRng.fill_bytes(&mut nonce_bytes);
+        rng.fill_bytes(&mut nonce_bytes);
         let nonce = XNonce::from_slice(&nonce_bytes);
         let ciphertext = cipher
             .encrypt(nonce, plaintext)
@@ -227,7 +236,18 @@ impl PQRatchetState {
         peer_x25519_public: &[u8; 32],
         peer_kyber_public: &[u8; 1568],
     ) -> Result<HybridCiphertext, PQRatchetError> {
-        let ciphertext = hybrid_encapsulate(peer_x25519_public, peer_kyber_public)
+        self.kem_ratchet_send_with_rng(peer_x25519_public, peer_kyber_public, &mut OsRng)
+    }
+
+    /// [`Self::kem_ratchet_send`] drawing the encapsulation randomness from
+    /// `rng`.
+    pub fn kem_ratchet_send_with_rng(
+        &mut self,
+        peer_x25519_public: &[u8; 32],
+        peer_kyber_public: &[u8; 1568],
+        rng: &mut impl SecureRng,
+    ) -> Result<HybridCiphertext, PQRatchetError> {
+        let ciphertext = hybrid_encapsulate_with_rng(peer_x25519_public, peer_kyber_public, rng)
             .map_err(|_| PQRatchetError::Kem)?;
 
         self.apply_new_root(&ciphertext.shared_secret)?;
@@ -311,6 +331,7 @@ mod tests {
     use crate::crypto::pqc::{
         generate_hybrid_keypair_from_seed, hybrid_decapsulate, hybrid_encapsulate,
     };
+    use rand::RngCore;
 
     fn make_pair() -> (PQRatchetState, PQRatchetState) {
         let mut seed = [0u8; 32];
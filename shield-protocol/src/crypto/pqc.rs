@@ -15,14 +15,16 @@
 /// - Combined secret:             64 bytes (BLAKE3-KDF(X25519 ‖ ML-KEM))
 use ml_kem::kem::{Decapsulate, Encapsulate};
 use ml_kem::{Encoded, EncodedSizeUser, KemCore, MlKem1024, MlKem1024Params};
-use rand::rngs::OsRng;
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use x25519_dalek::{PublicKey, StaticSecret};
 use zeroize::Zeroize;
 
 use crate::crypto::key_exchange;
+use crate::protocol::contact_id::ContactId;
+use crate::rng::{OsRng, SecureRng};
 
 /// ML-KEM-1024 encapsulation key (public) size in bytes
 pub const MLKEM1024_EK_BYTES: usize = 1568;
@@ -127,9 +129,14 @@ pub fn generate_hybrid_keypair_from_seed(seed: &[u8; 32]) -> Result<HybridKEMKey
 
 /// Generate a hybrid keypair with random keys
 pub fn generate_hybrid_keypair_random() -> Result<HybridKEMKeypair> {
-    let (x25519_public, x25519_secret) = key_exchange::generate_static_keypair();
+    generate_hybrid_keypair_with_rng(&mut OsRng)
+}
+
+/// [`generate_hybrid_keypair_random`] drawing both keypairs from `rng`.
+pub fn generate_hybrid_keypair_with_rng(rng: &mut impl SecureRng) -> Result<HybridKEMKeypair> {
+    let (x25519_public, x25519_secret) = key_exchange::generate_static_keypair_with_rng(rng);
 
-    let (dk, ek) = MlKem1024::generate(&mut OsRng);
+    let (dk, ek) = MlKem1024::generate(rng);
     let ek_bytes = ek.as_bytes().to_vec();
     let dk_bytes = dk.as_bytes().to_vec();
 
@@ -148,6 +155,16 @@ pub fn generate_hybrid_keypair_random() -> Result<HybridKEMKeypair> {
 pub fn hybrid_encapsulate(
     recipient_x25519_public: &[u8],
     recipient_mlkem_public: &[u8],
+) -> Result<HybridCiphertext> {
+    hybrid_encapsulate_with_rng(recipient_x25519_public, recipient_mlkem_public, &mut OsRng)
+}
+
+/// [`hybrid_encapsulate`] drawing the ephemeral key and encapsulation
+/// randomness from `rng`.
+pub fn hybrid_encapsulate_with_rng(
+    recipient_x25519_public: &[u8],
+    recipient_mlkem_public: &[u8],
+    rng: &mut impl SecureRng,
 ) -> Result<HybridCiphertext> {
     if recipient_x25519_public.len() != 32 {
         return Err(PqcError::InvalidKeyLength);
@@ -157,7 +174,7 @@ pub fn hybrid_encapsulate(
     }
 
     // X25519 ephemeral key exchange
-    let (eph_public, eph_secret) = key_exchange::generate_static_keypair();
+    let (eph_public, eph_secret) = key_exchange::generate_static_keypair_with_rng(rng);
     let x25519_shared = key_exchange::derive_shared_secret(&eph_secret, recipient_x25519_public)
         .map_err(|e| PqcError::X25519Error(e.to_string()))?;
 
@@ -169,7 +186,7 @@ pub fn hybrid_encapsulate(
 
     // ML-KEM-1024 encapsulation
     let (ct, mlkem_ss) = ek
-        .encapsulate(&mut OsRng)
+        .encapsulate(rng)
         .map_err(|_| PqcError::EncapsulateFailed)?;
 
     // Combine shared secrets via BLAKE3-KDF
@@ -294,7 +311,7 @@ pub fn verify_safety_number(our_identity: &[u8], their_identity: &[u8], expected
 ///
 /// This value MUST be persisted in the encrypted database (SQLCipher / IndexedDB)
 /// so it survives app restarts.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum TrustLevel {
     /// Level 0 — new contact, no encryption negotiated yet
@@ -389,8 +406,8 @@ pub fn detect_identity_key_change(
 /// The application (SQLCipher on mobile, IndexedDB on web) serializes this.
 #[derive(Debug, Clone)]
 pub struct ContactVerificationRecord {
-    /// Unique contact identifier (e.g. `sl_xyz…`)
-    pub contact_id: String,
+    /// Canonical contact identifier (`sl_…`, see [`ContactId`])
+    pub contact_id: ContactId,
     /// Current trust level (0 / 1 / 2)
     pub trust_level: TrustLevel,
     /// Unix-ms timestamp when verification was performed (0 if never)
@@ -401,7 +418,7 @@ pub struct ContactVerificationRecord {
 
 impl ContactVerificationRecord {
     /// Create a new record for a freshly-added contact (Level 1 = encrypted).
-    pub fn new_encrypted(contact_id: String) -> Self {
+    pub fn new_encrypted(contact_id: ContactId) -> Self {
         Self {
             contact_id,
             trust_level: TrustLevel::Encrypted,
@@ -505,6 +522,7 @@ pub fn verify_contact_fingerprint(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::contact_id::test_contact;
 
     #[test]
     fn test_hybrid_keypair_from_seed() {
@@ -726,7 +744,8 @@ mod tests {
 
     #[test]
     fn test_contact_verification_record_lifecycle() {
-        let mut rec = ContactVerificationRecord::new_encrypted("sl_test123".to_string());
+        let contact_id = test_contact(3);
+        let mut rec = ContactVerificationRecord::new_encrypted(contact_id);
         assert_eq!(rec.trust_level, TrustLevel::Encrypted);
         assert_eq!(rec.verified_at, 0);
         assert!(rec.safety_number.is_empty());
@@ -0,0 +1,296 @@
+/// Conversation-scoped pseudonymous identities.
+///
+/// By default a user shows every contact the same Ed25519 identity key, so
+/// two contacts (or one compromised conversation and a relay log) can tell
+/// they are talking to the same person. A pseudonymous conversation instead
+/// uses keys derived from the master seed and a random per-conversation
+/// [`ConversationTag`]:
+///
+/// ```text
+/// ed25519_secret = BLAKE3-derive-key("ShieldMessenger-Pseudonym-Signing-v1", seed || tag)
+/// x25519_secret  = BLAKE3-derive-key("ShieldMessenger-Pseudonym-X25519-v1",  seed || tag)
+/// kem_seed       = BLAKE3-derive-key("ShieldMessenger-Pseudonym-KEM-v1",     seed || tag)
+/// ```
+///
+/// Only the tag is stored per conversation; the keys are re-derived from the
+/// seed, so a seed restore brings every pseudonym back. Keys of different
+/// conversations are unrelated without the seed: leaking one conversation's
+/// keys, card or safety number reveals nothing about the others.
+///
+/// The contact card of a pseudonymous conversation carries the pseudonym
+/// signing key as its identity key and is marked
+/// [`pseudonymous`](crate::protocol::ContactCard::pseudonymous); safety
+/// numbers and key-change pinning then apply to the pseudonym exactly as to
+/// a regular identity, one conversation at a time. A user who later wants a
+/// contact to know who they are hands over a [`LinkProof`], which is signed
+/// by both keys and names that contact's key as its audience.
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use std::fmt;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PseudonymError {
+    #[error("Master seed must be at least 32 bytes")]
+    SeedTooShort,
+    #[error("Invalid key")]
+    InvalidKey,
+    #[error("Malformed link proof")]
+    Malformed,
+    #[error("Unsupported link proof version {0}")]
+    UnsupportedVersion(u8),
+}
+
+pub type Result<T> = std::result::Result<T, PseudonymError>;
+
+/// Link proof wire version.
+pub const LINK_PROOF_VERSION: u8 = 1;
+
+const SIGNING_CONTEXT: &str = "ShieldMessenger-Pseudonym-Signing-v1";
+const X25519_CONTEXT: &str = "ShieldMessenger-Pseudonym-X25519-v1";
+const KEM_CONTEXT: &str = "ShieldMessenger-Pseudonym-KEM-v1";
+const LINK_DOMAIN: &[u8] = b"SM-PSEUDONYM-LINK-v1";
+
+/// Random label of one pseudonymous conversation. Not secret, but only
+/// meaningful to its owner; store it with the conversation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationTag(pub [u8; 32]);
+
+impl ConversationTag {
+    pub fn generate(rng: &mut impl SecureRng) -> Self {
+        let mut tag = [0u8; 32];
+        rng.fill_bytes(&mut tag);
+        Self(tag)
+    }
+}
+
+impl fmt::Debug for ConversationTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ConversationTag({})", hex::encode(self.0))
+    }
+}
+
+/// Signing and encryption keys of one pseudonym. Secrets are wiped on drop.
+pub struct PseudonymKeys {
+    pub signing_public: [u8; 32],
+    signing_secret: [u8; 32],
+    pub x25519_public: [u8; 32],
+    x25519_secret: [u8; 32],
+    kem_seed: [u8; 32],
+}
+
+impl PseudonymKeys {
+    /// Ed25519 secret, in the format of [`super::signing::sign_data`].
+    pub fn signing_secret(&self) -> &[u8; 32] {
+        &self.signing_secret
+    }
+
+    pub fn x25519_secret(&self) -> &[u8; 32] {
+        &self.x25519_secret
+    }
+
+    /// Seed for this pseudonym's hybrid KEM keypair
+    /// ([`generate_hybrid_keypair_from_seed`](super::pqc::generate_hybrid_keypair_from_seed)).
+    pub fn kem_seed(&self) -> &[u8; 32] {
+        &self.kem_seed
+    }
+
+    pub fn sign(&self, data: &[u8]) -> [u8; 64] {
+        SigningKey::from_bytes(&self.signing_secret)
+            .sign(data)
+            .to_bytes()
+    }
+
+    /// Prove to `audience` (the contact's identity key in this conversation)
+    /// that this pseudonym belongs to `master_secret`'s identity.
+    ///
+    /// The proof is transferable: the audience can show it to anyone. Hand it
+    /// out only where linking is wanted.
+    pub fn link_proof(&self, master_secret: &[u8; 32], audience: &[u8; 32]) -> LinkProof {
+        let master = SigningKey::from_bytes(master_secret);
+        let master_public = master.verifying_key().to_bytes();
+        let message = link_message(&master_public, &self.signing_public, audience);
+        LinkProof {
+            master_public,
+            pseudonym_public: self.signing_public,
+            audience: *audience,
+            master_signature: master.sign(&message).to_bytes(),
+            pseudonym_signature: self.sign(&message),
+        }
+    }
+}
+
+impl Drop for PseudonymKeys {
+    fn drop(&mut self) {
+        self.signing_secret.zeroize();
+        self.x25519_secret.zeroize();
+        self.kem_seed.zeroize();
+    }
+}
+
+impl fmt::Debug for PseudonymKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PseudonymKeys")
+            .field("signing_public", &hex::encode(self.signing_public))
+            .field("x25519_public", &hex::encode(self.x25519_public))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Keys of the pseudonym for `tag` under `master_seed` (e.g. the 64-byte
+/// BIP39 seed).
+pub fn derive_pseudonym(master_seed: &[u8], tag: &ConversationTag) -> Result<PseudonymKeys> {
+    if master_seed.len() < 32 {
+        return Err(PseudonymError::SeedTooShort);
+    }
+    let mut material = Vec::with_capacity(master_seed.len() + 32);
+    material.extend_from_slice(master_seed);
+    material.extend_from_slice(&tag.0);
+
+    let signing_secret = blake3::derive_key(SIGNING_CONTEXT, &material);
+    let x25519_secret = blake3::derive_key(X25519_CONTEXT, &material);
+    let kem_seed = blake3::derive_key(KEM_CONTEXT, &material);
+    material.zeroize();
+
+    let signing_public = SigningKey::from_bytes(&signing_secret)
+        .verifying_key()
+        .to_bytes();
+    let x25519_public = super::key_exchange::derive_public_key(&x25519_secret)
+        .map_err(|_| PseudonymError::InvalidKey)?;
+    Ok(PseudonymKeys {
+        signing_public,
+        signing_secret,
+        x25519_public,
+        x25519_secret,
+        kem_seed,
+    })
+}
+
+fn link_message(master: &[u8; 32], pseudonym: &[u8; 32], audience: &[u8; 32]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(LINK_DOMAIN.len() + 96);
+    message.extend_from_slice(LINK_DOMAIN);
+    message.extend_from_slice(master);
+    message.extend_from_slice(pseudonym);
+    message.extend_from_slice(audience);
+    message
+}
+
+// ---------------------------------------------------------------------------
+// Link proofs
+// ---------------------------------------------------------------------------
+
+/// Both keys vouching that a pseudonym and a master identity are the same
+/// person, addressed to one audience key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkProof {
+    pub master_public: [u8; 32],
+    pub pseudonym_public: [u8; 32],
+    pub audience: [u8; 32],
+    #[serde(with = "BigArray")]
+    master_signature: [u8; 64],
+    #[serde(with = "BigArray")]
+    pseudonym_signature: [u8; 64],
+}
+
+impl LinkProof {
+    /// Check both signatures and that the proof is addressed to `audience`
+    /// and made by the pseudonym we talk to.
+    pub fn verify(&self, pseudonym_public: &[u8; 32], audience: &[u8; 32]) -> bool {
+        if self.pseudonym_public != *pseudonym_public || self.audience != *audience {
+            return false;
+        }
+        let message = link_message(&self.master_public, &self.pseudonym_public, &self.audience);
+        let check = |key: &[u8; 32], signature: &[u8; 64]| {
+            VerifyingKey::from_bytes(key).is_ok_and(|key| {
+                key.verify(&message, &ed25519_dalek::Signature::from_bytes(signature))
+                    .is_ok()
+            })
+        };
+        check(&self.master_public, &self.master_signature)
+            && check(&self.pseudonym_public, &self.pseudonym_signature)
+    }
+
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![LINK_PROOF_VERSION];
+        // Fixed-size arrays only: serialization cannot fail
+        out.extend_from_slice(&bincode::serialize(self).unwrap_or_default());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (&version, body) = bytes.split_first().ok_or(PseudonymError::Malformed)?;
+        if version != LINK_PROOF_VERSION {
+            return Err(PseudonymError::UnsupportedVersion(version));
+        }
+        bincode::deserialize(body).map_err(|_| PseudonymError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::{derive_public_key, verify_signature};
+    use crate::rng::seeded;
+
+    #[test]
+    fn test_pseudonyms_are_deterministic_and_unlinked() {
+        let mut rng = seeded(1);
+        let seed = [7u8; 64];
+        let tag_a = ConversationTag::generate(&mut rng);
+        let tag_b = ConversationTag::generate(&mut rng);
+
+        let a = derive_pseudonym(&seed, &tag_a).unwrap();
+        let again = derive_pseudonym(&seed, &tag_a).unwrap();
+        let b = derive_pseudonym(&seed, &tag_b).unwrap();
+        assert_eq!(a.signing_public, again.signing_public);
+        assert_eq!(a.x25519_public, again.x25519_public);
+        assert_ne!(a.signing_public, b.signing_public);
+        assert_ne!(a.x25519_public, b.x25519_public);
+        assert_ne!(a.kem_seed(), b.kem_seed());
+
+        // The secrets are ordinary keys for the rest of the crate
+        assert_eq!(
+            derive_public_key(a.signing_secret()).unwrap(),
+            a.signing_public
+        );
+        let signature = a.sign(b"hello");
+        assert!(verify_signature(b"hello", &signature, &a.signing_public).unwrap());
+
+        assert_eq!(
+            derive_pseudonym(&[7u8; 16], &tag_a).unwrap_err(),
+            PseudonymError::SeedTooShort
+        );
+        assert!(!format!("{:?}", a).contains(&hex::encode(a.signing_secret())));
+    }
+
+    #[test]
+    fn test_link_proof_is_bound_to_audience() {
+        let mut rng = seeded(2);
+        let (master_public, master_secret) =
+            crate::crypto::signing::generate_keypair_with_rng(&mut rng);
+        let keys = derive_pseudonym(&[3u8; 32], &ConversationTag::generate(&mut rng)).unwrap();
+        let bob = [0xB0; 32];
+
+        let proof = keys.link_proof(&master_secret, &bob);
+        assert_eq!(proof.master_public, master_public);
+        let decoded = LinkProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(decoded.verify(&keys.signing_public, &bob));
+
+        // Shown to someone else, or for another pseudonym, it does not verify
+        assert!(!decoded.verify(&keys.signing_public, &[0xC0; 32]));
+        assert!(!decoded.verify(&[1u8; 32], &bob));
+
+        let mut forged = decoded.clone();
+        forged.master_public = crate::crypto::signing::generate_keypair_with_rng(&mut rng).0;
+        assert!(!forged.verify(&keys.signing_public, &bob));
+        assert_eq!(
+            LinkProof::from_bytes(&[9, 0]),
+            Err(PseudonymError::UnsupportedVersion(9))
+        );
+    }
+}
@@ -17,6 +17,11 @@ use zeroize::Zeroize;
 use crate::crypto::{
     encryption, key_exchange,
     pqc::{self, HybridKEMKeypair},
+    state_machine::GuardContext,
+};
+use crate::rng::{OsRng, SecureRng};
+use crate::storage::versioned::{
+    decode_layout, Persisted, StateKind, VersionError, LEGACY_VERSION,
 };
 
 type HmacSha256 = Hmac<Sha256>;
@@ -27,6 +32,70 @@ const KEM_RATCHET_INTERVAL: u64 = 50;
 /// Maximum number of skipped message keys to store (anti-DoS)
 const MAX_SKIP: usize = 200;
 
+/// Ratchet health at a point in time, from [`PQDoubleRatchet::health`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RatchetHealth {
+    /// Messages sent and received since the last DH ratchet step.
+    pub messages_since_dh_step: u64,
+    /// Seconds since the last DH ratchet step (or session start).
+    pub secs_since_dh_step: u64,
+    /// Messages sent and received since the last KEM ratchet step.
+    pub messages_since_pq_step: u64,
+    /// Seconds since the last KEM ratchet step (or the hybrid handshake).
+    pub secs_since_pq_step: u64,
+    /// KEM ratchet steps since session start.
+    pub pq_steps: u64,
+    /// Message keys held for out-of-order delivery.
+    pub skipped_keys: usize,
+    /// A forced rotation is waiting for the next outgoing message.
+    pub rotation_pending: bool,
+}
+
+/// Thresholds past which a session should be rotated. `None` disables a check.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    pub max_messages_since_dh_step: Option<u64>,
+    pub max_messages_since_pq_step: Option<u64>,
+    pub max_secs_since_pq_step: Option<u64>,
+    pub max_skipped_keys: Option<usize>,
+}
+
+impl RotationPolicy {
+    /// Tight limits for high-risk users: a fresh PQ step at least every 20
+    /// messages or hour, and few keys left lying around for late messages.
+    pub fn high_risk() -> Self {
+        Self {
+            max_messages_since_dh_step: Some(20),
+            max_messages_since_pq_step: Some(20),
+            max_secs_since_pq_step: Some(3600),
+            max_skipped_keys: Some(20),
+        }
+    }
+}
+
+impl RatchetHealth {
+    /// Whether any threshold in `policy` is exceeded.
+    pub fn needs_rotation(&self, policy: &RotationPolicy) -> bool {
+        [
+            (
+                self.messages_since_dh_step,
+                policy.max_messages_since_dh_step,
+            ),
+            (
+                self.messages_since_pq_step,
+                policy.max_messages_since_pq_step,
+            ),
+            (self.secs_since_pq_step, policy.max_secs_since_pq_step),
+            (
+                self.skipped_keys as u64,
+                policy.max_skipped_keys.map(|m| m as u64),
+            ),
+        ]
+        .iter()
+        .any(|&(value, max)| max.is_some_and(|m| value > m))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RatchetError {
     #[error("Ratchet not initialized")]
@@ -47,6 +116,8 @@ pub enum RatchetError {
     TooManySkipped,
     #[error("Duplicate message detected")]
     DuplicateMessage,
+    #[error("Peer KEM key unknown; cannot rotate yet")]
+    RotationUnavailable,
 }
 
 pub type Result<T> = std::result::Result<T, RatchetError>;
@@ -108,6 +179,14 @@ pub struct PQDoubleRatchet {
 
     // ── Skipped message keys for out-of-order delivery ──
     skipped_keys: Vec<SkippedKey>,
+
+    // ── Health ──
+    messages_since_dh_step: u64,
+    last_dh_step_at: u64,
+    messages_since_pq_step: u64,
+    last_pq_step_at: u64,
+    pq_steps: u64,
+    rotation_forced: bool,
 }
 
 impl Drop for PQDoubleRatchet {
@@ -136,12 +215,22 @@ impl PQDoubleRatchet {
         shared_secret: &[u8],
         their_dh_public: &[u8; 32],
         their_kem_ek: Option<&[u8]>,
+    ) -> Result<Self> {
+        Self::init_alice_with_rng(shared_secret, their_dh_public, their_kem_ek, &mut OsRng)
+    }
+
+    /// [`Self::init_alice`] drawing the DH and KEM keypairs from `rng`.
+    pub fn init_alice_with_rng(
+        shared_secret: &[u8],
+        their_dh_public: &[u8; 32],
+        their_kem_ek: Option<&[u8]>,
+        rng: &mut impl SecureRng,
     ) -> Result<Self> {
         // Derive root key from shared secret
         let root_key = kdf_root_init(shared_secret);
 
         // Generate our DH ratchet keypair
-        let (our_dh_public, our_dh_secret) = key_exchange::generate_static_keypair();
+        let (our_dh_public, our_dh_secret) = key_exchange::generate_static_keypair_with_rng(rng);
 
         // Perform initial DH ratchet step: root_key + DH(our_secret, their_public)
         let dh_output = key_exchange::derive_shared_secret(&our_dh_secret, their_dh_public)
@@ -149,7 +238,7 @@ impl PQDoubleRatchet {
         let (new_root_key, send_chain_key) = kdf_root(&root_key, &dh_output);
 
         // Generate KEM keypair for future KEM steps
-        let our_kem_keypair = pqc::generate_hybrid_keypair_random()
+        let our_kem_keypair = pqc::generate_hybrid_keypair_with_rng(rng)
             .map_err(|e: pqc::PqcError| RatchetError::KEMRatchetFailed(e.to_string()))?;
 
         Ok(PQDoubleRatchet {
@@ -166,6 +255,12 @@ impl PQDoubleRatchet {
             total_messages_sent: 0,
             previous_chain_length: 0,
             skipped_keys: Vec::new(),
+            messages_since_dh_step: 0,
+            last_dh_step_at: unix_now(),
+            messages_since_pq_step: 0,
+            last_pq_step_at: unix_now(),
+            pq_steps: 0,
+            rotation_forced: false,
         })
     }
 
@@ -177,10 +272,19 @@ impl PQDoubleRatchet {
     /// * `shared_secret` - Pre-shared secret from X3DH/hybrid handshake (64 bytes)
     /// * `our_dh_keypair` - Bob's DH ratchet keypair from handshake
     pub fn init_bob(shared_secret: &[u8], our_dh_keypair: ([u8; 32], [u8; 32])) -> Result<Self> {
+        Self::init_bob_with_rng(shared_secret, our_dh_keypair, &mut OsRng)
+    }
+
+    /// [`Self::init_bob`] drawing the KEM keypair from `rng`.
+    pub fn init_bob_with_rng(
+        shared_secret: &[u8],
+        our_dh_keypair: ([u8; 32], [u8; 32]),
+        rng: &mut impl SecureRng,
+    ) -> Result<Self> {
         let root_key = kdf_root_init(shared_secret);
         let (our_dh_public, our_dh_secret) = our_dh_keypair;
 
-        let our_kem_keypair = pqc::generate_hybrid_keypair_random()
+        let our_kem_keypair = pqc::generate_hybrid_keypair_with_rng(rng)
             .map_err(|e: pqc::PqcError| RatchetError::KEMRatchetFailed(e.to_string()))?;
 
         Ok(PQDoubleRatchet {
@@ -197,6 +301,12 @@ impl PQDoubleRatchet {
             total_messages_sent: 0,
             previous_chain_length: 0,
             skipped_keys: Vec::new(),
+            messages_since_dh_step: 0,
+            last_dh_step_at: unix_now(),
+            messages_since_pq_step: 0,
+            last_pq_step_at: unix_now(),
+            pq_steps: 0,
+            rotation_forced: false,
         })
     }
 
@@ -204,6 +314,16 @@ impl PQDoubleRatchet {
     ///
     /// Returns (header, ciphertext) to be sent to the peer.
     pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<(RatchetHeader, Vec<u8>)> {
+        self.encrypt_with_rng(plaintext, &mut OsRng)
+    }
+
+    /// [`Self::encrypt`] drawing the nonce and any KEM step randomness from
+    /// `rng`.
+    pub fn encrypt_with_rng(
+        &mut self,
+        plaintext: &[u8],
+        rng: &mut impl SecureRng,
+    ) -> Result<(RatchetHeader, Vec<u8>)> {
         let send_ck = self.send_chain_key.ok_or(RatchetError::NotInitialized)?;
 
         // Derive message key from chain key
@@ -211,7 +331,7 @@ impl PQDoubleRatchet {
         self.send_chain_key = Some(new_chain_key);
 
         // Encrypt plaintext with message key
-        let ciphertext = encryption::encrypt_message(plaintext, &message_key)
+        let ciphertext = encryption::encrypt_message_with_rng(plaintext, &message_key, rng)
             .map_err(|e| RatchetError::Encryption(e.to_string()))?;
 
         // Build header
@@ -223,18 +343,23 @@ impl PQDoubleRatchet {
             kem_encapsulation_key: None,
         };
 
-        // KEM ratchet step: periodically encapsulate to their KEM key
+        // KEM ratchet step: periodically, or when a rotation was forced,
+        // encapsulate to their KEM key
         self.total_messages_sent += 1;
+        self.messages_since_dh_step += 1;
+        self.messages_since_pq_step += 1;
         if self
             .total_messages_sent
             .is_multiple_of(KEM_RATCHET_INTERVAL)
+            || self.rotation_forced
         {
             if let Some(ref their_kem_ek) = self.their_kem_ek {
                 if let Some(ref our_kem) = self.our_kem_keypair {
                     // Encapsulate to their KEM key
-                    if let Ok(kem_ct) = pqc::hybrid_encapsulate(
+                    if let Ok(kem_ct) = pqc::hybrid_encapsulate_with_rng(
                         &[0u8; 32], // KEM-only: no X25519 component for the KEM step
                         their_kem_ek,
+                        rng,
                     ) {
                         // Mix KEM shared secret into root key
                         let (new_root, new_send_ck) =
@@ -246,9 +371,10 @@ impl PQDoubleRatchet {
                         header.kem_encapsulation_key = Some(our_kem.kyber_public.clone());
 
                         // Generate new KEM keypair for next round
-                        if let Ok(new_kem) = pqc::generate_hybrid_keypair_random() {
+                        if let Ok(new_kem) = pqc::generate_hybrid_keypair_with_rng(rng) {
                             self.our_kem_keypair = Some(new_kem);
                         }
+                        self.record_pq_step();
                     }
                 }
             }
@@ -261,6 +387,17 @@ impl PQDoubleRatchet {
 
     /// Decrypt a received message, performing ratchet steps as needed
     pub fn decrypt(&mut self, header: &RatchetHeader, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt_with_rng(header, ciphertext, &mut OsRng)
+    }
+
+    /// [`Self::decrypt`] drawing the keypairs of any DH or KEM ratchet step
+    /// from `rng`.
+    pub fn decrypt_with_rng(
+        &mut self,
+        header: &RatchetHeader,
+        ciphertext: &[u8],
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>> {
         // Check skipped message keys first
         if let Some(plaintext) = self.try_skipped_keys(header, ciphertext)? {
             return Ok(plaintext);
@@ -276,12 +413,12 @@ impl PQDoubleRatchet {
             }
 
             // Perform DH ratchet step
-            self.dh_ratchet_step(&header.dh_public)?;
+            self.dh_ratchet_step(&header.dh_public, rng)?;
         }
 
         // Process KEM ratchet if present
         if let Some(ref kem_ct) = header.kem_ciphertext {
-            self.process_kem_ratchet(kem_ct, header.kem_encapsulation_key.as_deref())?;
+            self.process_kem_ratchet(kem_ct, header.kem_encapsulation_key.as_deref(), rng)?;
         }
 
         // Skip messages up to the received message number
@@ -294,12 +431,19 @@ impl PQDoubleRatchet {
         self.recv_message_number += 1;
 
         // Decrypt
-        encryption::decrypt_message(ciphertext, &message_key)
-            .map_err(|e| RatchetError::Decryption(e.to_string()))
+        let plaintext = encryption::decrypt_message(ciphertext, &message_key)
+            .map_err(|e| RatchetError::Decryption(e.to_string()))?;
+        self.messages_since_dh_step += 1;
+        self.messages_since_pq_step += 1;
+        Ok(plaintext)
     }
 
     /// Perform a DH ratchet step upon receiving a new DH public key
-    fn dh_ratchet_step(&mut self, their_new_dh_public: &[u8; 32]) -> Result<()> {
+    fn dh_ratchet_step(
+        &mut self,
+        their_new_dh_public: &[u8; 32],
+        rng: &mut impl SecureRng,
+    ) -> Result<()> {
         self.previous_chain_length = self.send_message_number;
         self.send_message_number = 0;
         self.recv_message_number = 0;
@@ -313,7 +457,7 @@ impl PQDoubleRatchet {
         self.recv_chain_key = Some(recv_ck);
 
         // Generate new DH keypair for sending chain
-        let (new_pub, new_sec) = key_exchange::generate_static_keypair();
+        let (new_pub, new_sec) = key_exchange::generate_static_keypair_with_rng(rng);
         self.our_dh_public = new_pub;
         let mut old_secret = self.our_dh_secret;
         self.our_dh_secret = new_sec;
@@ -326,6 +470,8 @@ impl PQDoubleRatchet {
         self.root_key = new_root2;
         self.send_chain_key = Some(send_ck);
 
+        self.messages_since_dh_step = 0;
+        self.last_dh_step_at = unix_now();
         Ok(())
     }
 
@@ -334,6 +480,7 @@ impl PQDoubleRatchet {
         &mut self,
         kem_ciphertext: &[u8],
         their_new_kem_ek: Option<&[u8]>,
+        rng: &mut impl SecureRng,
     ) -> Result<()> {
         if let Some(ref our_kem) = self.our_kem_keypair {
             // Decapsulate using our KEM keypair
@@ -357,10 +504,47 @@ impl PQDoubleRatchet {
 
             // Regenerate our KEM keypair
             self.our_kem_keypair = Some(
-                pqc::generate_hybrid_keypair_random()
+                pqc::generate_hybrid_keypair_with_rng(rng)
                     .map_err(|e: pqc::PqcError| RatchetError::KEMRatchetFailed(e.to_string()))?,
             );
+            self.record_pq_step();
+        }
+        Ok(())
+    }
+
+    fn record_pq_step(&mut self) {
+        self.messages_since_pq_step = 0;
+        self.last_pq_step_at = unix_now();
+        self.pq_steps += 1;
+        self.rotation_forced = false;
+    }
+
+    /// Health metrics, with ages measured up to `now` (Unix seconds).
+    pub fn health(&self, now: u64) -> RatchetHealth {
+        RatchetHealth {
+            messages_since_dh_step: self.messages_since_dh_step,
+            secs_since_dh_step: now.saturating_sub(self.last_dh_step_at),
+            messages_since_pq_step: self.messages_since_pq_step,
+            secs_since_pq_step: now.saturating_sub(self.last_pq_step_at),
+            pq_steps: self.pq_steps,
+            skipped_keys: self.skipped_keys.len(),
+            rotation_pending: self.rotation_forced,
+        }
+    }
+
+    /// Rotate keys now: the next outgoing message carries a KEM ratchet step,
+    /// mixing fresh post-quantum secret material into the root key. Skipped
+    /// message keys are discarded, so late messages from before the rotation
+    /// can no longer be decrypted.
+    ///
+    /// DH steps need a message from the peer and follow on the next
+    /// round-trip.
+    pub fn force_rotation(&mut self) -> Result<()> {
+        if self.their_kem_ek.is_none() || self.send_chain_key.is_none() {
+            return Err(RatchetError::RotationUnavailable);
         }
+        self.skipped_keys.clear();
+        self.rotation_forced = true;
         Ok(())
     }
 
@@ -413,6 +597,31 @@ impl PQDoubleRatchet {
         }
     }
 
+    /// Guard inputs for the session state machine, evaluated against this
+    /// ratchet and, for a `Decrypt` event, the incoming header. The caller
+    /// fills in `peer_verified` from the contact's trust level.
+    pub fn guard_context(&self, incoming: Option<&RatchetHeader>) -> GuardContext {
+        GuardContext {
+            has_send_chain: self.send_chain_key.is_some(),
+            new_peer_dh_key: incoming.is_some_and(|h| self.their_dh_public != Some(h.dh_public)),
+            kem_due: self.their_kem_ek.is_some()
+                && ((self.total_messages_sent + 1).is_multiple_of(KEM_RATCHET_INTERVAL)
+                    || self.rotation_forced),
+            peer_verified: false,
+        }
+    }
+
+    /// Secret for a session resumption ticket. Derived one-way from the
+    /// current root key, so a leaked ticket reveals no message keys.
+    pub fn resumption_secret(&self, ticket_id: &[u8; 16]) -> [u8; 32] {
+        let mut input = [0u8; 48];
+        input[..32].copy_from_slice(&self.root_key);
+        input[32..].copy_from_slice(ticket_id);
+        let secret = blake3::derive_key("ShieldMessenger-Ratchet-Resumption-v1", &input);
+        input.zeroize();
+        secret
+    }
+
     /// Get our current DH ratchet public key (for sending in headers)
     pub fn our_dh_public_key(&self) -> &[u8; 32] {
         &self.our_dh_public
@@ -425,7 +634,9 @@ impl PQDoubleRatchet {
             .map(|kp| kp.kyber_public.clone())
     }
 
-    /// Serialize the ratchet state for persistent storage
+    /// Serialize the ratchet state for persistent storage; write it with
+    /// [`Versioned`](crate::storage::versioned::Versioned) so later builds
+    /// can read it back
     pub fn export_state(&self) -> RatchetState {
         RatchetState {
             root_key: self.root_key,
@@ -449,6 +660,12 @@ impl PQDoubleRatchet {
                 .map(|kp| kp.kyber_secret.clone()),
             our_kem_x25519_public: self.our_kem_keypair.as_ref().map(|kp| kp.x25519_public),
             our_kem_x25519_secret: self.our_kem_keypair.as_ref().map(|kp| kp.x25519_secret),
+            messages_since_dh_step: self.messages_since_dh_step,
+            last_dh_step_at: self.last_dh_step_at,
+            messages_since_pq_step: self.messages_since_pq_step,
+            last_pq_step_at: self.last_pq_step_at,
+            pq_steps: self.pq_steps,
+            rotation_forced: self.rotation_forced,
         }
     }
 
@@ -483,6 +700,12 @@ impl PQDoubleRatchet {
             total_messages_sent: state.total_messages_sent,
             previous_chain_length: state.previous_chain_length,
             skipped_keys: Vec::new(),
+            messages_since_dh_step: state.messages_since_dh_step,
+            last_dh_step_at: state.last_dh_step_at,
+            messages_since_pq_step: state.messages_since_pq_step,
+            last_pq_step_at: state.last_pq_step_at,
+            pq_steps: state.pq_steps,
+            rotation_forced: state.rotation_forced,
         }
     }
 }
@@ -505,6 +728,94 @@ pub struct RatchetState {
     pub our_kem_secret: Option<Vec<u8>>,
     pub our_kem_x25519_public: Option<[u8; 32]>,
     pub our_kem_x25519_secret: Option<[u8; 32]>,
+    #[serde(default)]
+    pub messages_since_dh_step: u64,
+    #[serde(default)]
+    pub last_dh_step_at: u64,
+    #[serde(default)]
+    pub messages_since_pq_step: u64,
+    #[serde(default)]
+    pub last_pq_step_at: u64,
+    #[serde(default)]
+    pub pq_steps: u64,
+    #[serde(default)]
+    pub rotation_forced: bool,
+}
+
+/// Layout 1: before ratchet health tracking. Bincode ignores
+/// `#[serde(default)]`, so these blobs do not decode as layout 2.
+#[derive(Deserialize)]
+struct RatchetStateV1 {
+    root_key: [u8; 32],
+    send_chain_key: Option<[u8; 32]>,
+    send_message_number: u64,
+    recv_chain_key: Option<[u8; 32]>,
+    recv_message_number: u64,
+    our_dh_secret: [u8; 32],
+    our_dh_public: [u8; 32],
+    their_dh_public: Option<[u8; 32]>,
+    their_kem_ek: Option<Vec<u8>>,
+    total_messages_sent: u64,
+    previous_chain_length: u64,
+    our_kem_public: Option<Vec<u8>>,
+    our_kem_secret: Option<Vec<u8>>,
+    our_kem_x25519_public: Option<[u8; 32]>,
+    our_kem_x25519_secret: Option<[u8; 32]>,
+}
+
+impl From<RatchetStateV1> for RatchetState {
+    fn from(v1: RatchetStateV1) -> Self {
+        RatchetState {
+            root_key: v1.root_key,
+            send_chain_key: v1.send_chain_key,
+            send_message_number: v1.send_message_number,
+            recv_chain_key: v1.recv_chain_key,
+            recv_message_number: v1.recv_message_number,
+            our_dh_secret: v1.our_dh_secret,
+            our_dh_public: v1.our_dh_public,
+            their_dh_public: v1.their_dh_public,
+            their_kem_ek: v1.their_kem_ek,
+            total_messages_sent: v1.total_messages_sent,
+            previous_chain_length: v1.previous_chain_length,
+            our_kem_public: v1.our_kem_public,
+            our_kem_secret: v1.our_kem_secret,
+            our_kem_x25519_public: v1.our_kem_x25519_public,
+            our_kem_x25519_secret: v1.our_kem_x25519_secret,
+            // Rotation counters restart; the next step happens on schedule
+            messages_since_dh_step: 0,
+            last_dh_step_at: unix_now(),
+            messages_since_pq_step: 0,
+            last_pq_step_at: unix_now(),
+            pq_steps: 0,
+            rotation_forced: false,
+        }
+    }
+}
+
+impl Persisted for RatchetState {
+    const KIND: StateKind = StateKind::RatchetState;
+    const VERSION: u16 = 2;
+
+    fn migrate(version: u16, body: &[u8]) -> std::result::Result<Self, VersionError> {
+        match version {
+            // Unversioned blobs are bincode of whichever layout was current;
+            // layout 1 is a prefix of layout 2, so try the longer one first
+            LEGACY_VERSION => decode_layout::<RatchetState>(body)
+                .or_else(|_| decode_layout::<RatchetStateV1>(body).map(Into::into)),
+            1 => decode_layout::<RatchetStateV1>(body).map(Into::into),
+            _ => Err(VersionError::NoMigration {
+                kind: Self::KIND,
+                version,
+            }),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 // ── KDF functions ──
@@ -658,6 +969,109 @@ mod tests {
         assert_eq!(pt2, b"after restore");
     }
 
+    /// `RatchetState` as stored before ratchet health tracking: layout 1,
+    /// bincode, no version header.
+    const RATCHET_STATE_V1_FIXTURE: &str = concat!(
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "0122222222222222222222222222222222222222222222222222222222222222",
+        "2203000000000000000000000000000000003333333333333333333333333333",
+        "3333333333333333333333333333333333334444444444444444444444444444",
+        "4444444444444444444444444444444444440155555555555555555555555555",
+        "5555555555555555555555555555555555555500030000000000000000000000",
+        "0000000000000000",
+    );
+
+    #[test]
+    fn test_ratchet_state_migrates_from_layout_1() {
+        use crate::storage::versioned::Versioned;
+
+        let legacy = hex::decode(RATCHET_STATE_V1_FIXTURE).unwrap();
+        let read = Versioned::<RatchetState>::decode(&legacy).unwrap();
+        assert_eq!(read.stored_version, LEGACY_VERSION);
+        assert!(read.is_upgraded());
+        let state = &read.value;
+        assert_eq!(state.root_key, [0x11; 32]);
+        assert_eq!(state.send_chain_key, Some([0x22; 32]));
+        assert_eq!(state.send_message_number, 3);
+        assert_eq!(state.their_dh_public, Some([0x55; 32]));
+        assert_eq!(state.total_messages_sent, 3);
+        assert_eq!(state.pq_steps, 0);
+        assert!(!state.rotation_forced);
+
+        // Rewritten at layout 2, it reads back unchanged
+        let blob = read.encode().unwrap();
+        let reread = Versioned::<RatchetState>::decode(&blob).unwrap();
+        assert_eq!(reread.stored_version, 2);
+        assert_eq!(reread.value.root_key, [0x11; 32]);
+        assert_eq!(reread.value.last_dh_step_at, state.last_dh_step_at);
+
+        // Header with layout 1 takes the same path
+        let mut v1 = b"SMVS\x01\x00\x01".to_vec();
+        v1.extend_from_slice(&legacy);
+        let from_v1 = Versioned::<RatchetState>::decode(&v1).unwrap();
+        assert_eq!(from_v1.value.recv_chain_key, None);
+        PQDoubleRatchet::import_state(from_v1.into_inner());
+    }
+
+    #[test]
+    fn test_unversioned_layout_2_still_decodes() {
+        let (bob_dh_pub, bob_dh_sec) = key_exchange::generate_static_keypair();
+        let bob = PQDoubleRatchet::init_bob(&[9u8; 64], (bob_dh_pub, bob_dh_sec)).unwrap();
+        let mut exported = bob.export_state();
+        exported.pq_steps = 4;
+        exported.rotation_forced = true;
+        let legacy = bincode::serialize(&exported).unwrap();
+
+        let state: RatchetState = crate::storage::versioned::decode(&legacy).unwrap();
+        assert_eq!(state.pq_steps, 4);
+        assert!(state.rotation_forced);
+        assert_eq!(state.our_dh_public, bob_dh_pub);
+    }
+
+    #[test]
+    fn test_health_and_forced_rotation() {
+        let (bob_dh_pub, bob_dh_sec) = key_exchange::generate_static_keypair();
+        let shared_64 = [7u8; 64];
+        let mut bob = PQDoubleRatchet::init_bob(&shared_64, (bob_dh_pub, bob_dh_sec)).unwrap();
+        let bob_kem = bob.our_kem_encapsulation_key();
+        let mut alice =
+            PQDoubleRatchet::init_alice(&shared_64, &bob_dh_pub, bob_kem.as_deref()).unwrap();
+
+        for _ in 0..3 {
+            let (h, ct) = alice.encrypt(b"hi").unwrap();
+            bob.decrypt(&h, &ct).unwrap();
+        }
+        let now = unix_now();
+        let health = alice.health(now + 90);
+        assert_eq!(health.messages_since_dh_step, 3);
+        assert_eq!(health.messages_since_pq_step, 3);
+        assert!(health.secs_since_pq_step >= 90);
+        assert_eq!(health.pq_steps, 0);
+
+        let policy = RotationPolicy {
+            max_messages_since_pq_step: Some(2),
+            ..RotationPolicy::default()
+        };
+        assert!(health.needs_rotation(&policy));
+        assert!(!health.needs_rotation(&RotationPolicy::default()));
+
+        // Bob has no KEM key for Alice yet
+        assert!(matches!(
+            bob.force_rotation(),
+            Err(RatchetError::RotationUnavailable)
+        ));
+
+        alice.force_rotation().unwrap();
+        assert!(alice.health(now).rotation_pending);
+        assert!(alice.guard_context(None).kem_due);
+        let (header, _) = alice.encrypt(b"rotate").unwrap();
+        assert!(header.kem_ciphertext.is_some());
+        let health = alice.health(now);
+        assert!(!health.rotation_pending);
+        assert_eq!(health.pq_steps, 1);
+        assert_eq!(health.messages_since_pq_step, 0);
+    }
+
     #[test]
     fn test_kdf_chain_deterministic() {
         let ck = [0xABu8; 32];
@@ -667,4 +1081,48 @@ mod tests {
         assert_eq!(mk1, mk2);
         assert_ne!(ck1, mk1); // chain key ≠ message key
     }
+
+    #[test]
+    fn test_same_seed_gives_identical_ciphertext() {
+        use crate::rng::seeded;
+
+        // Run a session through two DH round trips and a forced KEM step,
+        // and collect everything that goes on the wire.
+        fn run(seed: u64) -> Vec<Vec<u8>> {
+            let mut rng = seeded(seed);
+            let shared_64 = [7u8; 64];
+            let bob_dh = key_exchange::generate_static_keypair_with_rng(&mut rng);
+            let mut bob = PQDoubleRatchet::init_bob_with_rng(&shared_64, bob_dh, &mut rng).unwrap();
+            let bob_kem = bob.our_kem_encapsulation_key();
+            let mut alice = PQDoubleRatchet::init_alice_with_rng(
+                &shared_64,
+                &bob_dh.0,
+                bob_kem.as_deref(),
+                &mut rng,
+            )
+            .unwrap();
+
+            let mut wire = Vec::new();
+            for _ in 0..2 {
+                let (h, ct) = alice.encrypt_with_rng(b"ping", &mut rng).unwrap();
+                bob.decrypt_with_rng(&h, &ct, &mut rng).unwrap();
+                wire.push(bincode::serialize(&h).unwrap());
+                wire.push(ct);
+                let (h, ct) = bob.encrypt_with_rng(b"pong", &mut rng).unwrap();
+                alice.decrypt_with_rng(&h, &ct, &mut rng).unwrap();
+                wire.push(bincode::serialize(&h).unwrap());
+                wire.push(ct);
+            }
+            alice.force_rotation().unwrap();
+            let (h, ct) = alice.encrypt_with_rng(b"rotate", &mut rng).unwrap();
+            assert!(h.kem_ciphertext.is_some());
+            wire.push(bincode::serialize(&h).unwrap());
+            wire.push(ct);
+            wire
+        }
+
+        let first = run(11);
+        assert_eq!(first, run(11));
+        assert_ne!(first, run(12));
+    }
 }
@@ -1,16 +1,235 @@
-use lru::LruCache;
 use once_cell::sync::Lazy;
-use std::num::NonZeroUsize;
 use std::sync::Mutex;
+use thiserror::Error;
 
-/// Replay cache entry: (sender_pubkey, ping_hash) -> timestamp
-type CacheKey = ([u8; 32], [u8; 32]);
+use crate::rng::{OsRng, SecureRng};
 
-/// Global LRU replay cache for PING deduplication
-/// Capacity: 10,000 entries (most recent PINGs)
-static REPLAY_CACHE: Lazy<Mutex<LruCache<CacheKey, i64>>> = Lazy::new(|| {
-    let capacity = NonZeroUsize::new(10_000).unwrap();
-    Mutex::new(LruCache::new(capacity))
+/// Replay cache: time-bucketed ring of Bloom filters with fixed memory.
+///
+/// Each bucket is a Bloom filter covering `bucket_duration_secs` of traffic.
+/// Lookups check every live bucket; inserts go to the newest. When time moves
+/// past a bucket (or the newest bucket fills up) the oldest bucket is cleared
+/// and reused, giving sliding-window eviction without per-entry bookkeeping.
+///
+/// Guarantees, given a `ReplayCacheConfig`:
+/// - **Memory** is fixed at construction: `buckets × bits_per_bucket / 8` bytes
+///   (see `ReplayCache::memory_bytes`), regardless of traffic.
+/// - **No false negatives** within the window: an entry is remembered for at
+///   least `(buckets - 1) × bucket_duration_secs` while no bucket receives more
+///   than `items_per_bucket` entries. Above that rate buckets rotate early and
+///   the window shrinks, but memory does not grow.
+/// - **False positives** (fresh message reported as replay) stay below
+///   `false_positive_rate` while every bucket is within capacity.
+///
+/// Filter indices are derived from a per-process random BLAKE3 key, so remote
+/// peers cannot precompute inputs that collide in the filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayCacheConfig {
+    /// Number of time buckets in the ring (≥ 2).
+    pub buckets: usize,
+    /// Time span covered by one bucket.
+    pub bucket_duration_secs: u64,
+    /// Entries one bucket holds before rotating early.
+    pub items_per_bucket: usize,
+    /// Target false-positive rate across all live buckets (0 < p < 1).
+    pub false_positive_rate: f64,
+}
+
+impl Default for ReplayCacheConfig {
+    /// 30-minute window, 60k entries, 1-in-a-million false positives (~245 KB).
+    fn default() -> Self {
+        ReplayCacheConfig {
+            buckets: 6,
+            bucket_duration_secs: 300,
+            items_per_bucket: 10_000,
+            false_positive_rate: 1e-6,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ReplayCacheError {
+    #[error("Invalid replay cache config: {0}")]
+    InvalidConfig(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    /// `now / bucket_duration_secs` when this bucket was opened.
+    epoch: u64,
+    count: usize,
+    bits: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayCache {
+    config: ReplayCacheConfig,
+    buckets: Vec<Bucket>,
+    /// Index of the bucket receiving inserts.
+    head: usize,
+    bits_per_bucket: u64,
+    hash_count: u32,
+    hash_key: [u8; 32],
+}
+
+impl ReplayCache {
+    pub fn new(config: ReplayCacheConfig) -> Result<Self, ReplayCacheError> {
+        Self::new_with_rng(config, &mut OsRng)
+    }
+
+    /// [`Self::new`] drawing the filter hash key from `rng`.
+    pub fn new_with_rng(
+        config: ReplayCacheConfig,
+        rng: &mut impl SecureRng,
+    ) -> Result<Self, ReplayCacheError> {
+        if config.buckets < 2 {
+            return Err(ReplayCacheError::InvalidConfig("buckets must be >= 2"));
+        }
+        if config.bucket_duration_secs == 0 {
+            return Err(ReplayCacheError::InvalidConfig(
+                "bucket_duration_secs must be > 0",
+            ));
+        }
+        if config.items_per_bucket == 0 {
+            return Err(ReplayCacheError::InvalidConfig(
+                "items_per_bucket must be > 0",
+            ));
+        }
+        if !(config.false_positive_rate > 0.0 && config.false_positive_rate < 1.0) {
+            return Err(ReplayCacheError::InvalidConfig(
+                "false_positive_rate must be in (0, 1)",
+            ));
+        }
+
+        // Union bound: each of the `buckets` filters gets an equal share of p.
+        let per_filter_p = config.false_positive_rate / config.buckets as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let n = config.items_per_bucket as f64;
+        let bits = (-(n * per_filter_p.ln()) / (ln2 * ln2)).ceil() as u64;
+        let bits_per_bucket = bits.div_ceil(64).max(1) * 64;
+        let hash_count = ((bits_per_bucket as f64 / n) * ln2).round().max(1.0) as u32;
+
+        let words = (bits_per_bucket / 64) as usize;
+        let buckets = (0..config.buckets)
+            .map(|_| Bucket {
+                epoch: 0,
+                count: 0,
+                bits: vec![0u64; words],
+            })
+            .collect();
+
+        let mut hash_key = [0u8; 32];
+        rng.fill_bytes(&mut hash_key);
+
+        Ok(ReplayCache {
+            config,
+            buckets,
+            head: 0,
+            bits_per_bucket,
+            hash_count,
+            hash_key,
+        })
+    }
+
+    pub fn config(&self) -> &ReplayCacheConfig {
+        &self.config
+    }
+
+    /// Total filter memory in bytes (fixed for the life of the cache).
+    pub fn memory_bytes(&self) -> usize {
+        self.buckets.len() * (self.bits_per_bucket / 8) as usize
+    }
+
+    /// Minimum time an entry is remembered while buckets are within capacity.
+    pub fn guaranteed_window_secs(&self) -> u64 {
+        (self.config.buckets as u64 - 1) * self.config.bucket_duration_secs
+    }
+
+    /// Check `key` and record it. Returns `true` if NEW, `false` if a replay.
+    pub fn check_and_insert(&mut self, key: &[u8], now_secs: u64) -> bool {
+        let epoch = now_secs / self.config.bucket_duration_secs;
+        self.advance(epoch);
+
+        let (h1, h2) = self.hash_pair(key);
+        if self.contains_hashed(h1, h2, epoch) {
+            return false;
+        }
+
+        let bits_per_bucket = self.bits_per_bucket;
+        let hash_count = self.hash_count;
+        let head = &mut self.buckets[self.head];
+        for i in 0..hash_count {
+            let bit = Self::bit_index(h1, h2, i, bits_per_bucket);
+            head.bits[(bit / 64) as usize] |= 1u64 << (bit % 64);
+        }
+        head.count += 1;
+        true
+    }
+
+    /// Whether `key` is (probably) present, without inserting it.
+    pub fn contains(&self, key: &[u8], now_secs: u64) -> bool {
+        let epoch = now_secs / self.config.bucket_duration_secs;
+        let (h1, h2) = self.hash_pair(key);
+        self.contains_hashed(h1, h2, epoch)
+    }
+
+    /// Forget everything (keeps the allocated memory).
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.bits.iter_mut().for_each(|w| *w = 0);
+            bucket.count = 0;
+            bucket.epoch = 0;
+        }
+        self.head = 0;
+    }
+
+    /// Rotate to a fresh bucket if time moved on or the head bucket is full.
+    fn advance(&mut self, epoch: u64) {
+        let head = &self.buckets[self.head];
+        let stale_time = epoch > head.epoch;
+        let full = head.count >= self.config.items_per_bucket;
+        if !(stale_time || full) {
+            return;
+        }
+        // Clock going backwards keeps the head's epoch (never reopens the past).
+        let new_epoch = epoch.max(head.epoch);
+        self.head = (self.head + 1) % self.buckets.len();
+        let bucket = &mut self.buckets[self.head];
+        bucket.bits.iter_mut().for_each(|w| *w = 0);
+        bucket.count = 0;
+        bucket.epoch = new_epoch;
+    }
+
+    fn contains_hashed(&self, h1: u64, h2: u64, epoch: u64) -> bool {
+        let window = self.config.buckets as u64;
+        self.buckets
+            .iter()
+            .filter(|b| b.count > 0 && epoch.saturating_sub(b.epoch) < window)
+            .any(|b| {
+                (0..self.hash_count).all(|i| {
+                    let bit = Self::bit_index(h1, h2, i, self.bits_per_bucket);
+                    b.bits[(bit / 64) as usize] & (1u64 << (bit % 64)) != 0
+                })
+            })
+    }
+
+    /// Two independent 64-bit hashes for Kirsch–Mitzenmacher double hashing.
+    fn hash_pair(&self, key: &[u8]) -> (u64, u64) {
+        let digest = blake3::keyed_hash(&self.hash_key, key);
+        let bytes = digest.as_bytes();
+        let h1 = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) | 1;
+        (h1, h2)
+    }
+
+    fn bit_index(h1: u64, h2: u64, i: u32, bits: u64) -> u64 {
+        h1.wrapping_add((i as u64).wrapping_mul(h2)) % bits
+    }
+}
+
+/// Global replay cache for PING deduplication
+static REPLAY_CACHE: Lazy<Mutex<ReplayCache>> = Lazy::new(|| {
+    Mutex::new(ReplayCache::new(ReplayCacheConfig::default()).expect("default config is valid"))
 });
 
 /// Check if PING is a replay, and insert if not
@@ -23,9 +242,17 @@ static REPLAY_CACHE: Lazy<Mutex<LruCache<CacheKey, i64>>> = Lazy::new(|| {
 /// * `ping_hash` - 32-byte Blake3 hash of PING ciphertext
 pub fn check_ping_replay(sender_pubkey: [u8; 32], ping_hash: [u8; 32]) -> bool {
     let mut cache = REPLAY_CACHE.lock().unwrap();
-    let key = (sender_pubkey, ping_hash);
 
-    if cache.contains(&key) {
+    let mut key = [0u8; 64];
+    key[..32].copy_from_slice(&sender_pubkey);
+    key[32..].copy_from_slice(&ping_hash);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    if !cache.check_and_insert(&key, now) {
         // Replay detected!
         log::warn!(
             "REPLAY ATTACK: Duplicate PING detected from sender {}",
@@ -34,15 +261,7 @@ pub fn check_ping_replay(sender_pubkey: [u8; 32], ping_hash: [u8; 32]) -> bool {
         return false;
     }
 
-    // New PING - add to cache
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-
-    cache.put(key, now);
-
-    log::debug!("PING replay check passed (cache size: {})", cache.len());
+    log::debug!("PING replay check passed");
     true
 }
 
@@ -52,8 +271,7 @@ pub fn compute_ping_hash(ping_bytes: &[u8]) -> [u8; 32] {
     *hash.as_bytes()
 }
 
-/// Clear the replay cache (for testing)
-#[cfg(test)]
+/// Clear the global PING replay cache (panic wipe, tests)
 pub fn clear_replay_cache() {
     let mut cache = REPLAY_CACHE.lock().unwrap();
     cache.clear();
@@ -63,6 +281,15 @@ pub fn clear_replay_cache() {
 mod tests {
     use super::*;
 
+    fn small_config() -> ReplayCacheConfig {
+        ReplayCacheConfig {
+            buckets: 4,
+            bucket_duration_secs: 10,
+            items_per_bucket: 1_000,
+            false_positive_rate: 1e-3,
+        }
+    }
+
     #[test]
     fn test_replay_detection() {
         clear_replay_cache();
@@ -89,4 +316,63 @@ mod tests {
         assert!(check_ping_replay(sender, ping_hash_1));
         assert!(check_ping_replay(sender, ping_hash_2));
     }
+
+    #[test]
+    fn test_entries_survive_window_then_expire() {
+        let mut cache = ReplayCache::new(small_config()).unwrap();
+        assert!(cache.check_and_insert(b"msg", 1_000));
+
+        // Still remembered at the end of the guaranteed window.
+        let window = cache.guaranteed_window_secs();
+        assert!(!cache.check_and_insert(b"msg", 1_000 + window));
+
+        // Long after, the bucket holding it has been recycled.
+        let mut t = 1_000 + window;
+        for _ in 0..5 {
+            t += 10;
+            cache.check_and_insert(&t.to_le_bytes(), t);
+        }
+        assert!(!cache.contains(b"msg", t));
+    }
+
+    #[test]
+    fn test_memory_fixed_under_overload() {
+        let mut cache = ReplayCache::new(small_config()).unwrap();
+        let before = cache.memory_bytes();
+        for i in 0u64..20_000 {
+            cache.check_and_insert(&i.to_le_bytes(), 5);
+        }
+        assert_eq!(cache.memory_bytes(), before);
+        // Most recent entries are still detected after early rotation.
+        assert!(!cache.check_and_insert(&19_999u64.to_le_bytes(), 5));
+    }
+
+    #[test]
+    fn test_false_positive_rate_within_bound() {
+        let config = small_config();
+        let mut cache = ReplayCache::new(config).unwrap();
+        // Fill every bucket to capacity across distinct epochs.
+        for b in 0..config.buckets as u64 {
+            for i in 0..config.items_per_bucket as u64 {
+                cache.check_and_insert(&(b * 1_000_000 + i).to_le_bytes(), b * 10);
+            }
+        }
+        let now = (config.buckets as u64 - 1) * 10;
+        let probes = 100_000u64;
+        let false_positives = (0..probes)
+            .filter(|i| cache.contains(&(u64::MAX - i).to_le_bytes(), now))
+            .count();
+        // Expected ~100; allow generous slack for randomness.
+        assert!(false_positives < 300, "fp = {}", false_positives);
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        let mut config = small_config();
+        config.buckets = 1;
+        assert!(ReplayCache::new(config).is_err());
+        let mut config = small_config();
+        config.false_positive_rate = 1.0;
+        assert!(ReplayCache::new(config).is_err());
+    }
 }
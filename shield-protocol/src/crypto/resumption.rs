@@ -0,0 +1,657 @@
+/// Session resumption across transports.
+///
+/// The ratchet does not care which transport carries its messages, so moving
+/// a contact from Tor to a direct link (or reconnecting after a drop) should
+/// not cost a new handshake. What the new path needs is proof that the other
+/// end holds the same session. Resumption tickets provide it:
+///
+/// 1. Once a session is up, either side calls [`ResumptionBook::issue`]. The
+///    ticket secret is derived one-way from the ratchet's root key
+///    ([`PQDoubleRatchet::resumption_secret`]). The ticket travels to the peer
+///    inside an ordinary ratchet message and is stored there with
+///    [`ResumptionBook::accept_ticket`].
+/// 2. On the new transport, one side sends a [`ResumeRequest`] built by
+///    [`ResumptionBook::begin_resume`]: ticket id, counter, timestamp and a
+///    fresh nonce, MACed with the ticket secret.
+/// 3. The other side checks it with [`ResumptionBook::verify_resume`] and
+///    answers with a [`ResumeAccept`] MACed over the same nonce. The resumer
+///    checks that with [`ResumptionBook::complete_resume`]. Both sides then
+///    carry on with the existing ratchet over the new transport.
+///
+/// Replay protection:
+///
+/// - Every ticket keeps a per-direction counter that must strictly increase.
+/// - Requests timestamped further than `max_clock_skew_secs` from local time
+///   are refused, so a request captured in transit cannot be held back and
+///   used later.
+/// - The sender's role (issuer or holder) is part of the MAC, so a request
+///   cannot be reflected back at its sender.
+///
+/// Tickets belong to one session. Call [`ResumptionBook::invalidate`] when a
+/// contact's session is re-established by handshake or reset.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::crypto::constant_time::eq_32;
+use crate::crypto::ratchet::PQDoubleRatchet;
+use crate::protocol::contact_id::ContactId;
+use crate::rng::SecureRng;
+
+/// Wire version of tickets, requests and accepts.
+pub const RESUMPTION_VERSION: u8 = 1;
+
+/// Tickets kept per contact; the oldest is dropped beyond this.
+pub const MAX_TICKETS_PER_CONTACT: usize = 4;
+
+const TICKET_LEN: usize = 1 + 16 + 32 + 8 + 8;
+const REQUEST_LEN: usize = 1 + 16 + 8 + 8 + 16 + 32;
+const ACCEPT_LEN: usize = 1 + 16 + 8 + 32;
+
+const REQUEST_MAC_CONTEXT: &str = "ShieldMessenger-Resume-Request-v1";
+const ACCEPT_MAC_CONTEXT: &str = "ShieldMessenger-Resume-Accept-v1";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ResumptionError {
+    #[error("Malformed resumption message")]
+    Malformed,
+    #[error("Unsupported resumption version {0}")]
+    UnsupportedVersion(u8),
+    #[error("No usable resumption ticket for {0}")]
+    NoTicket(ContactId),
+    #[error("Unknown resumption ticket")]
+    UnknownTicket,
+    #[error("Resumption ticket expired")]
+    Expired,
+    #[error("Replayed resume request (counter {0})")]
+    Replay(u64),
+    #[error("Resume request timestamp outside allowed clock skew")]
+    ClockSkew,
+    #[error("Resumption MAC verification failed")]
+    BadMac,
+    #[error("No resume in progress for {0}")]
+    NoPendingResume(ContactId),
+    #[error("Resumption state encoding failed: {0}")]
+    Encoding(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumptionConfig {
+    /// How long an issued ticket may be used.
+    pub ticket_lifetime_secs: u64,
+    /// Largest accepted difference between a request's timestamp and local time.
+    pub max_clock_skew_secs: u64,
+}
+
+impl Default for ResumptionConfig {
+    fn default() -> Self {
+        Self {
+            ticket_lifetime_secs: 7 * 24 * 3600,
+            max_clock_skew_secs: 300,
+        }
+    }
+}
+
+/// A resumption ticket, held by both ends of a session.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTicket {
+    pub id: [u8; 16],
+    secret: [u8; 32],
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+impl Drop for SessionTicket {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl fmt::Debug for SessionTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTicket")
+            .field("id", &self.id)
+            .field("issued_at", &self.issued_at)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionTicket {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// `[version][id: 16][secret: 32][issued_at: u64 BE][expires_at: u64 BE]`.
+    /// Contains the secret: only send it inside an encrypted ratchet message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(TICKET_LEN);
+        out.push(RESUMPTION_VERSION);
+        out.extend_from_slice(&self.id);
+        out.extend_from_slice(&self.secret);
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&self.expires_at.to_be_bytes());
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ResumptionError> {
+        check_header(data, TICKET_LEN)?;
+        let mut at = 1;
+        Ok(Self {
+            id: take(data, &mut at),
+            secret: take(data, &mut at),
+            issued_at: u64::from_be_bytes(take(data, &mut at)),
+            expires_at: u64::from_be_bytes(take(data, &mut at)),
+        })
+    }
+}
+
+/// Sent on the new transport to resume a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeRequest {
+    pub ticket_id: [u8; 16],
+    pub counter: u64,
+    pub timestamp: u64,
+    pub nonce: [u8; 16],
+    pub mac: [u8; 32],
+}
+
+impl ResumeRequest {
+    /// `[version][ticket_id: 16][counter: u64 BE][timestamp: u64 BE][nonce: 16][mac: 32]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(REQUEST_LEN);
+        out.push(RESUMPTION_VERSION);
+        out.extend_from_slice(&self.ticket_id);
+        out.extend_from_slice(&self.counter.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&self.mac);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ResumptionError> {
+        check_header(data, REQUEST_LEN)?;
+        let mut at = 1;
+        Ok(Self {
+            ticket_id: take(data, &mut at),
+            counter: u64::from_be_bytes(take(data, &mut at)),
+            timestamp: u64::from_be_bytes(take(data, &mut at)),
+            nonce: take(data, &mut at),
+            mac: take(data, &mut at),
+        })
+    }
+}
+
+/// Answer to a verified [`ResumeRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeAccept {
+    pub ticket_id: [u8; 16],
+    pub counter: u64,
+    pub mac: [u8; 32],
+}
+
+impl ResumeAccept {
+    /// `[version][ticket_id: 16][counter: u64 BE][mac: 32]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(ACCEPT_LEN);
+        out.push(RESUMPTION_VERSION);
+        out.extend_from_slice(&self.ticket_id);
+        out.extend_from_slice(&self.counter.to_be_bytes());
+        out.extend_from_slice(&self.mac);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ResumptionError> {
+        check_header(data, ACCEPT_LEN)?;
+        let mut at = 1;
+        Ok(Self {
+            ticket_id: take(data, &mut at),
+            counter: u64::from_be_bytes(take(data, &mut at)),
+            mac: take(data, &mut at),
+        })
+    }
+}
+
+fn check_header(data: &[u8], len: usize) -> Result<(), ResumptionError> {
+    match data.first() {
+        None => Err(ResumptionError::Malformed),
+        Some(&v) if v != RESUMPTION_VERSION => Err(ResumptionError::UnsupportedVersion(v)),
+        _ if data.len() != len => Err(ResumptionError::Malformed),
+        _ => Ok(()),
+    }
+}
+
+/// Copy the next `N` bytes; the caller has checked the total length.
+fn take<const N: usize>(data: &[u8], at: &mut usize) -> [u8; N] {
+    let mut out = [0u8; N];
+    out.copy_from_slice(&data[*at..*at + N]);
+    *at += N;
+    out
+}
+
+fn request_mac(
+    ticket: &SessionTicket,
+    from_issuer: bool,
+    counter: u64,
+    timestamp: u64,
+    nonce: &[u8; 16],
+) -> [u8; 32] {
+    let mut key = blake3::derive_key(REQUEST_MAC_CONTEXT, &ticket.secret);
+    let mac = blake3::Hasher::new_keyed(&key)
+        .update(&[from_issuer as u8])
+        .update(&ticket.id)
+        .update(&counter.to_be_bytes())
+        .update(&timestamp.to_be_bytes())
+        .update(nonce)
+        .finalize();
+    key.zeroize();
+    *mac.as_bytes()
+}
+
+fn accept_mac(
+    ticket: &SessionTicket,
+    from_issuer: bool,
+    counter: u64,
+    nonce: &[u8; 16],
+) -> [u8; 32] {
+    let mut key = blake3::derive_key(ACCEPT_MAC_CONTEXT, &ticket.secret);
+    let mac = blake3::Hasher::new_keyed(&key)
+        .update(&[from_issuer as u8])
+        .update(&ticket.id)
+        .update(&counter.to_be_bytes())
+        .update(nonce)
+        .finalize();
+    key.zeroize();
+    *mac.as_bytes()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TicketEntry {
+    ticket: SessionTicket,
+    /// We minted this ticket; decides the role bit in MACs.
+    issued_by_us: bool,
+    /// Counter of our most recent request.
+    sent_counter: u64,
+    /// Highest counter accepted from the peer.
+    recv_counter: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingResume {
+    ticket_id: [u8; 16],
+    counter: u64,
+    nonce: [u8; 16],
+}
+
+/// Resumption tickets for all contacts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumptionBook {
+    config: ResumptionConfig,
+    tickets: HashMap<ContactId, Vec<TicketEntry>>,
+    /// In-progress resumes; not persisted.
+    #[serde(skip)]
+    pending: HashMap<ContactId, PendingResume>,
+}
+
+impl ResumptionBook {
+    pub fn new(config: ResumptionConfig) -> Self {
+        Self {
+            config,
+            tickets: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ResumptionConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ResumptionConfig) {
+        self.config = config;
+    }
+
+    /// Mint a ticket bound to `ratchet`'s current state. Send
+    /// `ticket.to_bytes()` to the contact inside a ratchet message.
+    pub fn issue(
+        &mut self,
+        contact_id: &ContactId,
+        ratchet: &PQDoubleRatchet,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> SessionTicket {
+        let mut id = [0u8; 16];
+        rng.fill_bytes(&mut id);
+        let ticket = SessionTicket {
+            id,
+            secret: ratchet.resumption_secret(&id),
+            issued_at: now,
+            expires_at: now.saturating_add(self.config.ticket_lifetime_secs),
+        };
+        self.insert(contact_id, ticket.clone(), true);
+        ticket
+    }
+
+    /// Store a ticket the contact issued. Duplicates are ignored.
+    pub fn accept_ticket(
+        &mut self,
+        contact_id: &ContactId,
+        ticket: &[u8],
+        now: u64,
+    ) -> Result<(), ResumptionError> {
+        let ticket = SessionTicket::from_bytes(ticket)?;
+        if ticket.is_expired(now) {
+            return Err(ResumptionError::Expired);
+        }
+        self.insert(contact_id, ticket, false);
+        Ok(())
+    }
+
+    /// Whether a resume with this contact is possible.
+    pub fn has_ticket(&self, contact_id: &ContactId, now: u64) -> bool {
+        self.tickets
+            .get(contact_id)
+            .is_some_and(|entries| entries.iter().any(|e| !e.ticket.is_expired(now)))
+    }
+
+    /// Build a request to resume the session on a new transport.
+    ///
+    /// Uses the newest unexpired ticket the contact issued, since the contact
+    /// certainly holds it; a ticket we issued may still be in flight, so our
+    /// own are only used when the contact has issued none.
+    pub fn begin_resume(
+        &mut self,
+        contact_id: &ContactId,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<ResumeRequest, ResumptionError> {
+        let entries = self
+            .tickets
+            .get_mut(contact_id)
+            .ok_or(ResumptionError::NoTicket(*contact_id))?;
+        let usable = |e: &TicketEntry| !e.ticket.is_expired(now);
+        let index = entries
+            .iter()
+            .rposition(|e| usable(e) && !e.issued_by_us)
+            .or_else(|| entries.iter().rposition(usable))
+            .ok_or(ResumptionError::NoTicket(*contact_id))?;
+        let entry = &mut entries[index];
+        entry.sent_counter += 1;
+        let mut nonce = [0u8; 16];
+        rng.fill_bytes(&mut nonce);
+        let request = ResumeRequest {
+            ticket_id: entry.ticket.id,
+            counter: entry.sent_counter,
+            timestamp: now,
+            nonce,
+            mac: request_mac(
+                &entry.ticket,
+                entry.issued_by_us,
+                entry.sent_counter,
+                now,
+                &nonce,
+            ),
+        };
+        self.pending.insert(
+            *contact_id,
+            PendingResume {
+                ticket_id: request.ticket_id,
+                counter: request.counter,
+                nonce,
+            },
+        );
+        Ok(request)
+    }
+
+    /// Check a contact's resume request. On success the contact has proven
+    /// it holds the session; send back the returned accept.
+    pub fn verify_resume(
+        &mut self,
+        contact_id: &ContactId,
+        request: &ResumeRequest,
+        now: u64,
+    ) -> Result<ResumeAccept, ResumptionError> {
+        let max_skew = self.config.max_clock_skew_secs;
+        let entry = self.entry_mut(contact_id, &request.ticket_id)?;
+        if entry.ticket.is_expired(now) {
+            return Err(ResumptionError::Expired);
+        }
+        let expected = request_mac(
+            &entry.ticket,
+            !entry.issued_by_us,
+            request.counter,
+            request.timestamp,
+            &request.nonce,
+        );
+        if !eq_32(&expected, &request.mac) {
+            return Err(ResumptionError::BadMac);
+        }
+        if now.abs_diff(request.timestamp) > max_skew {
+            return Err(ResumptionError::ClockSkew);
+        }
+        if request.counter <= entry.recv_counter {
+            return Err(ResumptionError::Replay(request.counter));
+        }
+        entry.recv_counter = request.counter;
+        Ok(ResumeAccept {
+            ticket_id: request.ticket_id,
+            counter: request.counter,
+            mac: accept_mac(
+                &entry.ticket,
+                entry.issued_by_us,
+                request.counter,
+                &request.nonce,
+            ),
+        })
+    }
+
+    /// Check the contact's answer to our latest request.
+    pub fn complete_resume(
+        &mut self,
+        contact_id: &ContactId,
+        accept: &ResumeAccept,
+    ) -> Result<(), ResumptionError> {
+        let pending = self
+            .pending
+            .get(contact_id)
+            .filter(|p| p.ticket_id == accept.ticket_id && p.counter == accept.counter)
+            .cloned()
+            .ok_or(ResumptionError::NoPendingResume(*contact_id))?;
+        let entry = self.entry_mut(contact_id, &accept.ticket_id)?;
+        let expected = accept_mac(
+            &entry.ticket,
+            !entry.issued_by_us,
+            accept.counter,
+            &pending.nonce,
+        );
+        if !eq_32(&expected, &accept.mac) {
+            return Err(ResumptionError::BadMac);
+        }
+        self.pending.remove(contact_id);
+        Ok(())
+    }
+
+    /// Drop all tickets for a contact (new handshake, session reset, contact deleted).
+    pub fn invalidate(&mut self, contact_id: &ContactId) {
+        self.tickets.remove(contact_id);
+        self.pending.remove(contact_id);
+    }
+
+    /// Drop every ticket and in-progress resume; returns how many tickets
+    /// were dropped. Ticket secrets are zeroized as they drop.
+    pub fn clear(&mut self) -> usize {
+        let dropped = self.tickets.values().map(Vec::len).sum();
+        self.tickets.clear();
+        self.pending.clear();
+        dropped
+    }
+
+    /// Drop expired tickets.
+    pub fn prune(&mut self, now: u64) {
+        self.tickets.retain(|_, entries| {
+            entries.retain(|e| !e.ticket.is_expired(now));
+            !entries.is_empty()
+        });
+    }
+
+    /// Serialized state, including ticket secrets: store encrypted.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ResumptionError> {
+        bincode::serialize(self).map_err(|e| ResumptionError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ResumptionError> {
+        bincode::deserialize(data).map_err(|e| ResumptionError::Encoding(e.to_string()))
+    }
+
+    fn insert(&mut self, contact_id: &ContactId, ticket: SessionTicket, issued_by_us: bool) {
+        let entries = self.tickets.entry(*contact_id).or_default();
+        if entries.iter().any(|e| e.ticket.id == ticket.id) {
+            return;
+        }
+        entries.push(TicketEntry {
+            ticket,
+            issued_by_us,
+            sent_counter: 0,
+            recv_counter: 0,
+        });
+        if entries.len() > MAX_TICKETS_PER_CONTACT {
+            entries.remove(0);
+        }
+    }
+
+    fn entry_mut(
+        &mut self,
+        contact_id: &ContactId,
+        ticket_id: &[u8; 16],
+    ) -> Result<&mut TicketEntry, ResumptionError> {
+        self.tickets
+            .get_mut(contact_id)
+            .and_then(|entries| entries.iter_mut().find(|e| &e.ticket.id == ticket_id))
+            .ok_or(ResumptionError::UnknownTicket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_exchange;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn ratchet() -> PQDoubleRatchet {
+        let (bob_public, _) = key_exchange::generate_static_keypair_with_rng(&mut rng::seeded(1));
+        PQDoubleRatchet::init_alice(&[7u8; 64], &bob_public, None).unwrap()
+    }
+
+    /// Alice issues a ticket and Bob stores it.
+    fn paired() -> (ResumptionBook, ResumptionBook) {
+        let (mut alice, mut bob) = (ResumptionBook::default(), ResumptionBook::default());
+        let ticket = alice.issue(&test_contact(2), &ratchet(), NOW, &mut rng::seeded(2));
+        bob.accept_ticket(&test_contact(1), &ticket.to_bytes(), NOW)
+            .unwrap();
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_resume_in_both_directions() {
+        let (mut alice, mut bob) = paired();
+        let mut rng = rng::seeded(3);
+
+        let request = bob
+            .begin_resume(&test_contact(1), NOW + 10, &mut rng)
+            .unwrap();
+        let wire = ResumeRequest::from_bytes(&request.to_bytes()).unwrap();
+        let accept = alice
+            .verify_resume(&test_contact(2), &wire, NOW + 11)
+            .unwrap();
+        let accept = ResumeAccept::from_bytes(&accept.to_bytes()).unwrap();
+        bob.complete_resume(&test_contact(1), &accept).unwrap();
+        assert_eq!(
+            bob.complete_resume(&test_contact(1), &accept),
+            Err(ResumptionError::NoPendingResume(test_contact(1)))
+        );
+
+        // The issuer can resume with the same ticket; counters are per direction
+        let request = alice
+            .begin_resume(&test_contact(2), NOW + 20, &mut rng)
+            .unwrap();
+        assert_eq!(request.counter, 1);
+        let accept = bob
+            .verify_resume(&test_contact(1), &request, NOW + 20)
+            .unwrap();
+        alice.complete_resume(&test_contact(2), &accept).unwrap();
+    }
+
+    #[test]
+    fn test_replay_reflection_and_tampering_rejected() {
+        let (mut alice, mut bob) = paired();
+        let mut rng = rng::seeded(4);
+        let request = bob.begin_resume(&test_contact(1), NOW, &mut rng).unwrap();
+
+        // Reflected back at its sender
+        assert_eq!(
+            bob.verify_resume(&test_contact(1), &request, NOW),
+            Err(ResumptionError::BadMac)
+        );
+        let mut tampered = request.clone();
+        tampered.counter += 1;
+        assert_eq!(
+            alice.verify_resume(&test_contact(2), &tampered, NOW),
+            Err(ResumptionError::BadMac)
+        );
+        assert_eq!(
+            alice.verify_resume(&test_contact(2), &request, NOW + 301),
+            Err(ResumptionError::ClockSkew)
+        );
+
+        let accept = alice
+            .verify_resume(&test_contact(2), &request, NOW)
+            .unwrap();
+        assert_eq!(
+            alice.verify_resume(&test_contact(2), &request, NOW),
+            Err(ResumptionError::Replay(1))
+        );
+        // An accept for a superseded request is refused
+        bob.begin_resume(&test_contact(1), NOW, &mut rng).unwrap();
+        assert_eq!(
+            bob.complete_resume(&test_contact(1), &accept),
+            Err(ResumptionError::NoPendingResume(test_contact(1)))
+        );
+    }
+
+    #[test]
+    fn test_persistence_expiry_and_invalidation() {
+        let (mut alice, mut bob) = paired();
+        let mut rng = rng::seeded(5);
+        let request = bob.begin_resume(&test_contact(1), NOW, &mut rng).unwrap();
+        alice
+            .verify_resume(&test_contact(2), &request, NOW)
+            .unwrap();
+
+        // Replay protection survives a restart
+        let mut restored = ResumptionBook::from_bytes(&alice.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            restored.verify_resume(&test_contact(2), &request, NOW),
+            Err(ResumptionError::Replay(1))
+        );
+
+        let expiry = NOW + ResumptionConfig::default().ticket_lifetime_secs;
+        assert!(bob.has_ticket(&test_contact(1), expiry - 1));
+        assert_eq!(
+            bob.begin_resume(&test_contact(1), expiry, &mut rng),
+            Err(ResumptionError::NoTicket(test_contact(1)))
+        );
+        restored.prune(expiry);
+        assert!(!restored.has_ticket(&test_contact(2), NOW));
+
+        bob.invalidate(&test_contact(1));
+        assert!(!bob.has_ticket(&test_contact(1), NOW));
+        assert_eq!(
+            SessionTicket::from_bytes(&[2; TICKET_LEN]),
+            Err(ResumptionError::UnsupportedVersion(2))
+        );
+    }
+}
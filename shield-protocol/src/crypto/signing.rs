@@ -2,6 +2,8 @@ use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use thiserror::Error;
 
+use crate::rng::SecureRng;
+
 #[derive(Error, Debug)]
 pub enum SigningError {
     #[error("Signing failed")]
@@ -21,7 +23,12 @@ pub type Result<T> = std::result::Result<T, SigningError>;
 /// # Returns
 /// (public_key, private_key) - Both as 32-byte arrays
 pub fn generate_keypair() -> ([u8; 32], [u8; 32]) {
-    let signing_key = SigningKey::generate(&mut OsRng);
+    generate_keypair_with_rng(&mut OsRng)
+}
+
+/// [`generate_keypair`] drawing from `rng`.
+pub fn generate_keypair_with_rng(rng: &mut impl SecureRng) -> ([u8; 32], [u8; 32]) {
+    let signing_key = SigningKey::generate(rng);
 
     let secret_key = signing_key.to_bytes();
     let public_key = signing_key.verifying_key().to_bytes();
@@ -0,0 +1,482 @@
+/// Session State Machine
+///
+/// The handshake and ratchet lifecycle of a pairwise session, written down as
+/// data: a fixed table of `(state, event, guard) -> state` transitions. The
+/// same table serves two purposes:
+///
+/// - **Runtime enforcement.** A [`SessionMachine`] tracks one session and
+///   rejects any event the table does not allow from its current state, or
+///   whose guard does not hold (e.g. encrypting without a send chain, or
+///   sending while an identity-key change is pending).
+/// - **Verification.** [`export_json`], [`export_dot`] and [`export_tla`]
+///   render the table for external tools. The TLA+ export models guards as
+///   free booleans, so TLC explores every guard outcome and properties proven
+///   there hold for any runtime guard evaluation.
+///
+/// Guard inputs come from [`PQDoubleRatchet::guard_context`]
+/// (chain/key state) plus the caller's view of contact trust.
+///
+/// [`PQDoubleRatchet::guard_context`]: crate::crypto::ratchet::PQDoubleRatchet::guard_context
+use serde::Serialize;
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Lifecycle state of a pairwise session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SessionState {
+    /// No session material.
+    Idle,
+    /// Initiator: send chain ready, no reply seen yet.
+    AwaitingReply,
+    /// Responder: waiting for the initiator's first message to open a send chain.
+    AwaitingFirstMessage,
+    /// Both chains established.
+    Established,
+    /// A verified contact's identity key changed; nothing moves until approved.
+    Blocked,
+    /// Session material wiped.
+    Closed,
+}
+
+/// Something that happens to a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SessionEvent {
+    InitAsInitiator,
+    InitAsResponder,
+    Encrypt,
+    Decrypt,
+    KemRatchetStep,
+    IdentityKeyChanged,
+    ApproveKeyChange,
+    Rekey,
+    Close,
+    Reset,
+}
+
+/// Condition that must hold for a transition to fire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Guard {
+    Always,
+    HasSendChain,
+    /// The incoming header carries a DH public key we have not ratcheted to.
+    NewPeerDhKey,
+    KemDue,
+    PeerVerified,
+    PeerUnverified,
+}
+
+/// Inputs for evaluating guards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuardContext {
+    pub has_send_chain: bool,
+    pub new_peer_dh_key: bool,
+    pub kem_due: bool,
+    pub peer_verified: bool,
+}
+
+impl Guard {
+    pub fn holds(self, ctx: &GuardContext) -> bool {
+        match self {
+            Guard::Always => true,
+            Guard::HasSendChain => ctx.has_send_chain,
+            Guard::NewPeerDhKey => ctx.new_peer_dh_key,
+            Guard::KemDue => ctx.kem_due,
+            Guard::PeerVerified => ctx.peer_verified,
+            Guard::PeerUnverified => !ctx.peer_verified,
+        }
+    }
+}
+
+/// One row of the transition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    pub from: SessionState,
+    pub event: SessionEvent,
+    pub guard: Guard,
+    pub to: SessionState,
+}
+
+const fn t(from: SessionState, event: SessionEvent, guard: Guard, to: SessionState) -> Transition {
+    Transition {
+        from,
+        event,
+        guard,
+        to,
+    }
+}
+
+use Guard as G;
+use SessionEvent as E;
+use SessionState as S;
+
+/// The complete transition table. For any `(from, event)`, at most one
+/// row's guard can hold at a time.
+pub static TRANSITIONS: &[Transition] = &[
+    // Handshake
+    t(S::Idle, E::InitAsInitiator, G::Always, S::AwaitingReply),
+    t(
+        S::Idle,
+        E::InitAsResponder,
+        G::Always,
+        S::AwaitingFirstMessage,
+    ),
+    t(
+        S::AwaitingReply,
+        E::Encrypt,
+        G::HasSendChain,
+        S::AwaitingReply,
+    ),
+    t(
+        S::AwaitingReply,
+        E::Decrypt,
+        G::NewPeerDhKey,
+        S::Established,
+    ),
+    t(
+        S::AwaitingFirstMessage,
+        E::Decrypt,
+        G::NewPeerDhKey,
+        S::Established,
+    ),
+    // Ratchet
+    t(S::Established, E::Encrypt, G::HasSendChain, S::Established),
+    t(S::Established, E::Decrypt, G::Always, S::Established),
+    t(S::Established, E::KemRatchetStep, G::KemDue, S::Established),
+    t(S::Established, E::Rekey, G::Always, S::AwaitingReply),
+    // Identity key changes
+    t(
+        S::AwaitingReply,
+        E::IdentityKeyChanged,
+        G::PeerVerified,
+        S::Blocked,
+    ),
+    t(
+        S::AwaitingReply,
+        E::IdentityKeyChanged,
+        G::PeerUnverified,
+        S::AwaitingReply,
+    ),
+    t(
+        S::AwaitingFirstMessage,
+        E::IdentityKeyChanged,
+        G::PeerVerified,
+        S::Blocked,
+    ),
+    t(
+        S::AwaitingFirstMessage,
+        E::IdentityKeyChanged,
+        G::PeerUnverified,
+        S::AwaitingFirstMessage,
+    ),
+    t(
+        S::Established,
+        E::IdentityKeyChanged,
+        G::PeerVerified,
+        S::Blocked,
+    ),
+    t(
+        S::Established,
+        E::IdentityKeyChanged,
+        G::PeerUnverified,
+        S::Established,
+    ),
+    t(S::Blocked, E::ApproveKeyChange, G::Always, S::Idle),
+    // Teardown
+    t(S::Idle, E::Close, G::Always, S::Closed),
+    t(S::AwaitingReply, E::Close, G::Always, S::Closed),
+    t(S::AwaitingFirstMessage, E::Close, G::Always, S::Closed),
+    t(S::Established, E::Close, G::Always, S::Closed),
+    t(S::Blocked, E::Close, G::Always, S::Closed),
+    t(S::Closed, E::Reset, G::Always, S::Idle),
+];
+
+pub const ALL_STATES: [SessionState; 6] = [
+    S::Idle,
+    S::AwaitingReply,
+    S::AwaitingFirstMessage,
+    S::Established,
+    S::Blocked,
+    S::Closed,
+];
+
+pub const ALL_GUARDS: [Guard; 6] = [
+    G::Always,
+    G::HasSendChain,
+    G::NewPeerDhKey,
+    G::KemDue,
+    G::PeerVerified,
+    G::PeerUnverified,
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TransitionError {
+    #[error("{event:?} is not allowed in state {state:?}")]
+    NotAllowed {
+        state: SessionState,
+        event: SessionEvent,
+    },
+    #[error("{event:?} in state {state:?} requires {guard:?}")]
+    GuardFailed {
+        state: SessionState,
+        event: SessionEvent,
+        guard: Guard,
+    },
+}
+
+/// Runtime tracker for one session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionMachine {
+    state: SessionState,
+}
+
+impl Default for SessionMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionMachine {
+    pub fn new() -> Self {
+        Self {
+            state: SessionState::Idle,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// The transition `event` would take, without applying it.
+    pub fn check(
+        &self,
+        event: SessionEvent,
+        ctx: &GuardContext,
+    ) -> Result<&'static Transition, TransitionError> {
+        let mut failed = None;
+        for row in TRANSITIONS
+            .iter()
+            .filter(|r| r.from == self.state && r.event == event)
+        {
+            if row.guard.holds(ctx) {
+                return Ok(row);
+            }
+            failed.get_or_insert(row.guard);
+        }
+        Err(match failed {
+            Some(guard) => TransitionError::GuardFailed {
+                state: self.state,
+                event,
+                guard,
+            },
+            None => TransitionError::NotAllowed {
+                state: self.state,
+                event,
+            },
+        })
+    }
+
+    /// Apply `event`, returning the new state. On error the state is unchanged.
+    pub fn fire(
+        &mut self,
+        event: SessionEvent,
+        ctx: &GuardContext,
+    ) -> Result<SessionState, TransitionError> {
+        let row = self.check(event, ctx)?;
+        self.state = row.to;
+        Ok(self.state)
+    }
+}
+
+// ==================== EXPORT ====================
+
+#[derive(Serialize)]
+struct Export<'a> {
+    initial: SessionState,
+    states: &'a [SessionState],
+    guards: &'a [Guard],
+    transitions: &'a [Transition],
+}
+
+/// The table as JSON: `{"initial", "states", "guards", "transitions"}`.
+pub fn export_json() -> String {
+    serde_json::to_string_pretty(&Export {
+        initial: SessionState::Idle,
+        states: &ALL_STATES,
+        guards: &ALL_GUARDS,
+        transitions: TRANSITIONS,
+    })
+    .expect("transition table serializes")
+}
+
+/// The table as a Graphviz digraph.
+pub fn export_dot() -> String {
+    let mut out = String::from("digraph session {\n  rankdir=LR;\n  Idle [shape=doublecircle];\n");
+    for row in TRANSITIONS {
+        let label = match row.guard {
+            Guard::Always => format!("{:?}", row.event),
+            guard => format!("{:?} [{:?}]", row.event, guard),
+        };
+        let _ = writeln!(
+            out,
+            "  {:?} -> {:?} [label=\"{}\"];",
+            row.from, row.to, label
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn quoted(value: impl std::fmt::Debug) -> String {
+    format!("\"{:?}\"", value)
+}
+
+/// The table as a TLA+ module (`ShieldSession`).
+///
+/// `guard` is re-chosen freely on every step, so the spec covers all guard
+/// outcomes. Check `TypeOK` as an invariant and `NoSendWhileBlocked` as an
+/// action property with TLC.
+pub fn export_tla() -> String {
+    let states: Vec<String> = ALL_STATES.iter().map(quoted).collect();
+    let guards: Vec<String> = ALL_GUARDS.iter().map(quoted).collect();
+
+    let mut out = String::new();
+    out.push_str("---- MODULE ShieldSession ----\n");
+    out.push_str("VARIABLES state, guard, lastEvent\n");
+    out.push_str("vars == <<state, guard, lastEvent>>\n\n");
+    let _ = writeln!(out, "States == {{{}}}", states.join(", "));
+    let _ = writeln!(out, "Guards == {{{}}}\n", guards.join(", "));
+    out.push_str(
+        "Holds(g) == g = \"Always\" \\/ guard[g]\n\n\
+         Init == /\\ state = \"Idle\"\n        \
+         /\\ guard \\in [Guards -> BOOLEAN]\n        \
+         /\\ lastEvent = \"None\"\n\n",
+    );
+
+    let mut actions = Vec::with_capacity(TRANSITIONS.len());
+    for (i, row) in TRANSITIONS.iter().enumerate() {
+        let name = format!("T{}_{:?}", i, row.event);
+        let _ = writeln!(
+            out,
+            "{name} == /\\ state = {} /\\ Holds({})\n{pad}/\\ state' = {} /\\ lastEvent' = {}\n{pad}/\\ guard' \\in [Guards -> BOOLEAN]",
+            quoted(row.from),
+            quoted(row.guard),
+            quoted(row.to),
+            quoted(row.event),
+            pad = " ".repeat(name.len() + 4),
+        );
+        actions.push(name);
+    }
+
+    let _ = writeln!(out, "\nNext == {}\n", actions.join("\n        \\/ "));
+    out.push_str("Spec == Init /\\ [][Next]_vars\n\n");
+    out.push_str("TypeOK == state \\in States\n");
+    out.push_str(
+        "NoSendWhileBlocked == [][state = \"Blocked\" => lastEvent' # \"Encrypt\"]_vars\n",
+    );
+    out.push_str("====\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_exchange;
+    use crate::crypto::ratchet::PQDoubleRatchet;
+
+    fn all_contexts() -> impl Iterator<Item = GuardContext> {
+        (0u8..16).map(|b| GuardContext {
+            has_send_chain: b & 1 != 0,
+            new_peer_dh_key: b & 2 != 0,
+            kem_due: b & 4 != 0,
+            peer_verified: b & 8 != 0,
+        })
+    }
+
+    #[test]
+    fn test_table_is_deterministic() {
+        for row in TRANSITIONS {
+            for ctx in all_contexts() {
+                let enabled = TRANSITIONS
+                    .iter()
+                    .filter(|r| r.from == row.from && r.event == row.event && r.guard.holds(&ctx))
+                    .count();
+                assert!(enabled <= 1, "{:?}/{:?} ambiguous", row.from, row.event);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocked_rejects_traffic() {
+        let mut m = SessionMachine::new();
+        let ctx = GuardContext {
+            has_send_chain: true,
+            new_peer_dh_key: true,
+            peer_verified: true,
+            ..Default::default()
+        };
+        m.fire(SessionEvent::InitAsInitiator, &ctx).unwrap();
+        m.fire(SessionEvent::IdentityKeyChanged, &ctx).unwrap();
+        assert_eq!(m.state(), SessionState::Blocked);
+        for event in [SessionEvent::Encrypt, SessionEvent::Decrypt] {
+            assert_eq!(
+                m.fire(event, &ctx),
+                Err(TransitionError::NotAllowed {
+                    state: SessionState::Blocked,
+                    event
+                })
+            );
+        }
+        assert_eq!(
+            m.fire(SessionEvent::ApproveKeyChange, &ctx),
+            Ok(SessionState::Idle)
+        );
+    }
+
+    #[test]
+    fn test_tracks_real_ratchet() {
+        let (bob_pub, bob_sec) = key_exchange::generate_static_keypair();
+        let shared = [7u8; 64];
+        let mut alice = PQDoubleRatchet::init_alice(&shared, &bob_pub, None).unwrap();
+        let mut bob = PQDoubleRatchet::init_bob(&shared, (bob_pub, bob_sec)).unwrap();
+        let (mut am, mut bm) = (SessionMachine::new(), SessionMachine::new());
+        am.fire(SessionEvent::InitAsInitiator, &alice.guard_context(None))
+            .unwrap();
+        bm.fire(SessionEvent::InitAsResponder, &bob.guard_context(None))
+            .unwrap();
+
+        // Responder cannot speak first
+        assert!(bm
+            .check(SessionEvent::Encrypt, &bob.guard_context(None))
+            .is_err());
+
+        am.fire(SessionEvent::Encrypt, &alice.guard_context(None))
+            .unwrap();
+        let (h, ct) = alice.encrypt(b"hi").unwrap();
+        bm.fire(SessionEvent::Decrypt, &bob.guard_context(Some(&h)))
+            .unwrap();
+        bob.decrypt(&h, &ct).unwrap();
+
+        bm.fire(SessionEvent::Encrypt, &bob.guard_context(None))
+            .unwrap();
+        let (h, ct) = bob.encrypt(b"hello").unwrap();
+        am.fire(SessionEvent::Decrypt, &alice.guard_context(Some(&h)))
+            .unwrap();
+        alice.decrypt(&h, &ct).unwrap();
+
+        assert_eq!(am.state(), SessionState::Established);
+        assert_eq!(bm.state(), SessionState::Established);
+    }
+
+    #[test]
+    fn test_exports_cover_table() {
+        let json = export_json();
+        let tla = export_tla();
+        let dot = export_dot();
+        for state in ALL_STATES {
+            let name = format!("{:?}", state);
+            assert!(json.contains(&name) && tla.contains(&name) && dot.contains(&name));
+        }
+        assert_eq!(tla.matches("/\\ state' = ").count(), TRANSITIONS.len());
+        assert!(tla.starts_with("---- MODULE ShieldSession ----"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Time-based short codes for out-of-band channel verification.
+//!
+//! Comparing a 60-digit safety number aloud is tedious, so two parties on a
+//! call can instead compare a short code that changes every `step_secs`
+//! seconds (TOTP-style, RFC 6238 dynamic truncation over HMAC-SHA256). The
+//! code is keyed from the pair's safety number plus a mandatory session
+//! binding (e.g. a hash of the current root key), so it only matches when
+//! both devices see the same identity keys *and* the same session.
+//!
+//! # Forgery resistance
+//!
+//! A man-in-the-middle runs two sessions, A↔M and M↔B, and therefore holds
+//! two different safety numbers. To go unnoticed the code A reads must equal
+//! the code B reads at the same moment.
+//!
+//! - M picks its key on *both* legs, so it only needs some pair of its own
+//!   keys whose codes agree. Over identity keys alone that is a birthday
+//!   search: about `10^(d/2)` keys per leg for one `d`-digit step, and
+//!   about `10^d` for two consecutive steps. That is cheap offline work at
+//!   any supported length, which is why the key cannot be derived without a
+//!   session binding.
+//! - With the binding taken from the live session (e.g. the root key, which
+//!   the honest peer contributes to), each leg's code is only fixed once
+//!   that session exists, so keys cannot be paired up in advance. M can at
+//!   best steer one leg towards the other, already fixed, leg: each try
+//!   matches with probability `10^-d` per compared step (1 in a million at
+//!   the default 6 digits, `10^-2d` when two consecutive steps are read).
+//!   If the handshake lets M pick its share after seeing the peer's, those
+//!   tries happen inside the handshake, so only `d` and the number of steps
+//!   compared bound them.
+//! - Codes are not secret once spoken. They reveal nothing about the key
+//!   (HMAC output), and a replayed code is only valid for its own step
+//!   (± `skew_steps`), so recording one call does not help forge another.
+//!
+//! The security argument assumes the voice channel itself is authentic
+//! (users recognize each other). It verifies the channel; it does not
+//! authenticate the people.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum VerificationCodeError {
+    #[error("Invalid verification code config: {0}")]
+    InvalidConfig(&'static str),
+    #[error("Verification codes need a non-empty session binding")]
+    MissingSessionBinding,
+}
+
+pub type Result<T> = std::result::Result<T, VerificationCodeError>;
+
+/// Default time step (seconds), same as common authenticator apps.
+pub const DEFAULT_STEP_SECS: u64 = 30;
+/// Default code length.
+pub const DEFAULT_DIGITS: u32 = 6;
+
+/// Code parameters. Both parties must use the same values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationCodeConfig {
+    /// Seconds each code stays current.
+    pub step_secs: u64,
+    /// Code length, 4–9 digits.
+    pub digits: u32,
+    /// Adjacent steps accepted on either side to absorb clock drift.
+    pub skew_steps: u32,
+}
+
+impl Default for VerificationCodeConfig {
+    fn default() -> Self {
+        Self {
+            step_secs: DEFAULT_STEP_SECS,
+            digits: DEFAULT_DIGITS,
+            skew_steps: 1,
+        }
+    }
+}
+
+impl VerificationCodeConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.step_secs == 0 {
+            return Err(VerificationCodeError::InvalidConfig(
+                "step_secs must be > 0",
+            ));
+        }
+        if !(4..=9).contains(&self.digits) {
+            return Err(VerificationCodeError::InvalidConfig(
+                "digits must be within 4..=9",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// HMAC key for a verified pair. Zeroized on drop.
+pub struct VerificationKey([u8; 32]);
+
+impl Drop for VerificationKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl VerificationKey {
+    /// Derive the code key from the safety number (see
+    /// [`generate_safety_number`](super::pqc::generate_safety_number)) and the
+    /// session binding. Both sides compute the same safety number, so the key
+    /// is symmetric. An empty `session_binding` is refused: identity keys
+    /// alone can be ground offline (see the module docs).
+    pub fn derive(safety_number: &str, session_binding: &[u8]) -> Result<Self> {
+        if session_binding.is_empty() {
+            return Err(VerificationCodeError::MissingSessionBinding);
+        }
+        let mut hasher = blake3::Hasher::new_derive_key("ShieldMessenger-OOB-VerifyCode-v1");
+        hasher.update(&(safety_number.len() as u64).to_le_bytes());
+        hasher.update(safety_number.as_bytes());
+        hasher.update(session_binding);
+        Ok(Self(*hasher.finalize().as_bytes()))
+    }
+
+    /// Code for the step containing `unix_secs`.
+    pub fn code_at(&self, unix_secs: u64, config: &VerificationCodeConfig) -> Result<String> {
+        config.validate()?;
+        Ok(self.code_for_step(unix_secs / config.step_secs, config.digits))
+    }
+
+    /// Check a code read by the peer. Returns the step offset it matched
+    /// (0 = current, -1 = previous, …) or `None`.
+    pub fn verify(
+        &self,
+        code: &str,
+        unix_secs: u64,
+        config: &VerificationCodeConfig,
+    ) -> Result<Option<i64>> {
+        config.validate()?;
+        let current = unix_secs / config.step_secs;
+        let skew = config.skew_steps as i64;
+        let mut matched = None;
+        // Check every candidate so timing does not reveal which step matched.
+        for offset in -skew..=skew {
+            let Some(step) = current.checked_add_signed(offset) else {
+                continue;
+            };
+            let expected = self.code_for_step(step, config.digits);
+            if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) && matched.is_none() {
+                matched = Some(offset);
+            }
+        }
+        Ok(matched)
+    }
+
+    fn code_for_step(&self, step: u64, digits: u32) -> String {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(&step.to_be_bytes());
+        let tag = mac.finalize().into_bytes();
+
+        // RFC 4226 dynamic truncation.
+        let offset = (tag[tag.len() - 1] & 0x0f) as usize;
+        let bin = u32::from_be_bytes([
+            tag[offset] & 0x7f,
+            tag[offset + 1],
+            tag[offset + 2],
+            tag[offset + 3],
+        ]);
+        let code = bin % 10u32.pow(digits);
+        format!("{:0width$}", code, width = digits as usize)
+    }
+}
+
+/// Seconds until the current code rolls over (for a UI countdown).
+pub fn seconds_remaining(unix_secs: u64, config: &VerificationCodeConfig) -> Result<u64> {
+    config.validate()?;
+    Ok(config.step_secs - unix_secs % config.step_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::pqc::generate_safety_number;
+
+    #[test]
+    fn test_both_sides_agree() {
+        let alice = [0xA1u8; 32];
+        let bob = [0xB0u8; 32];
+        let config = VerificationCodeConfig::default();
+        let ka = VerificationKey::derive(&generate_safety_number(&alice, &bob), b"root").unwrap();
+        let kb = VerificationKey::derive(&generate_safety_number(&bob, &alice), b"root").unwrap();
+
+        let code = ka.code_at(1_700_000_000, &config).unwrap();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(code, kb.code_at(1_700_000_000, &config).unwrap());
+        assert_eq!(kb.verify(&code, 1_700_000_000, &config).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_mitm_and_session_binding_change_code() {
+        let config = VerificationCodeConfig {
+            digits: 9,
+            ..Default::default()
+        };
+        let sn = generate_safety_number(&[1u8; 32], &[2u8; 32]);
+        let mitm = generate_safety_number(&[1u8; 32], &[3u8; 32]);
+        let t = 1_700_000_000;
+        let honest = VerificationKey::derive(&sn, b"s1")
+            .unwrap()
+            .code_at(t, &config)
+            .unwrap();
+        let forged = VerificationKey::derive(&mitm, b"s1")
+            .unwrap()
+            .code_at(t, &config)
+            .unwrap();
+        let other_session = VerificationKey::derive(&sn, b"s2")
+            .unwrap()
+            .code_at(t, &config)
+            .unwrap();
+        assert_ne!(honest, forged);
+        assert_ne!(honest, other_session);
+        assert!(matches!(
+            VerificationKey::derive(&sn, b""),
+            Err(VerificationCodeError::MissingSessionBinding)
+        ));
+    }
+
+    #[test]
+    fn test_skew_window_and_rollover() {
+        let config = VerificationCodeConfig {
+            step_secs: 60,
+            ..Default::default()
+        };
+        let key = VerificationKey::derive("12345 67890", b"root").unwrap();
+        let t = 6_000;
+        let prev = key.code_at(t - 60, &config).unwrap();
+        assert_eq!(key.verify(&prev, t, &config).unwrap(), Some(-1));
+        let stale = key.code_at(t - 120, &config).unwrap();
+        assert_eq!(key.verify(&stale, t, &config).unwrap(), None);
+
+        assert_eq!(seconds_remaining(6_015, &config).unwrap(), 45);
+        let bad = VerificationCodeConfig {
+            digits: 12,
+            ..Default::default()
+        };
+        assert!(key.code_at(t, &bad).is_err());
+    }
+}
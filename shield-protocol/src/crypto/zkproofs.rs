@@ -1,8 +1,13 @@
 use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
-use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
 use merlin::Transcript;
-use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::rng::{OsRng, SecureRng};
 
 /// Generate a Bulletproof range proof for a given amount.
 ///
@@ -16,6 +21,16 @@ use rand::thread_rng;
 pub fn generate_range_proof(
     amount: u64,
     bit_length: usize,
+) -> Result<(Vec<u8>, [u8; 32], [u8; 32]), String> {
+    generate_range_proof_with_rng(amount, bit_length, &mut OsRng)
+}
+
+/// [`generate_range_proof`] drawing the blinding and proof randomness from
+/// `rng`.
+pub fn generate_range_proof_with_rng(
+    amount: u64,
+    bit_length: usize,
+    rng: &mut impl SecureRng,
 ) -> Result<(Vec<u8>, [u8; 32], [u8; 32]), String> {
     if bit_length != 8 && bit_length != 16 && bit_length != 32 && bit_length != 64 {
         return Err(format!(
@@ -37,16 +52,16 @@ pub fn generate_range_proof(
     let pc_gens = PedersenGens::default();
     let bp_gens = BulletproofGens::new(bit_length, 1);
 
-    let mut rng = thread_rng();
-    let blinding = Scalar::random(&mut rng);
+    let blinding = Scalar::random(rng);
 
-    let (proof, commitment) = RangeProof::prove_single(
+    let (proof, commitment) = RangeProof::prove_single_with_rng(
         &bp_gens,
         &pc_gens,
         &mut Transcript::new(b"ShadowWire_RangeProof"),
         amount,
         &blinding,
         bit_length,
+        rng,
     )
     .map_err(|e| format!("Range proof generation failed: {:?}", e))?;
 
@@ -86,6 +101,662 @@ pub fn verify_range_proof(
         .map_err(|e| format!("Proof verification failed: {:?}", e))
 }
 
+// ---------------------------------------------------------------------------
+// Membership proofs (linkable ring signatures)
+// ---------------------------------------------------------------------------
+
+/// Domain separator for membership proof challenges.
+const MEMBERSHIP_PROOF_DOMAIN: &[u8] = b"ShieldMessenger-MembershipProof-v1";
+/// Domain separator for the per-context linking base point.
+const MEMBERSHIP_LINK_DOMAIN: &[u8] = b"ShieldMessenger-MembershipLink-v1";
+/// Domain separator for deterministic membership key derivation.
+const MEMBERSHIP_KEY_DOMAIN: &[u8] = b"ShieldMessenger-MembershipKey-v1";
+
+/// Maximum ring size accepted by `generate_membership_proof` / `verify_membership_proof`.
+///
+/// Proof size and verification cost are linear in the ring size (32 bytes and
+/// two scalar multiplications per member).
+pub const MAX_MEMBERSHIP_RING: usize = 1024;
+
+/// Zero-knowledge proof that the prover holds the secret key of *one* public key
+/// in a ring, without revealing which one (LSAG over Ristretto).
+///
+/// The `link_tag` is deterministic per (secret key, link context): two proofs
+/// made by the same member under the same context carry the same tag, which
+/// lets verifiers rate-limit anonymous actions without learning who acted.
+/// Tags under different contexts are unlinkable.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MembershipProof {
+    /// Initial ring challenge c₀.
+    pub c0: [u8; 32],
+    /// One response scalar per ring member, in ring order.
+    pub responses: Vec<[u8; 32]>,
+    /// Linking tag I = x·Hp(context).
+    pub link_tag: [u8; 32],
+}
+
+/// Derive a membership keypair from a device secret, scoped to `context`.
+///
+/// Scoping (e.g. by GroupID) keeps a device's ring keys unlinkable across groups.
+///
+/// Returns (secret_scalar_bytes, compressed_public_key).
+pub fn derive_membership_keypair(device_secret: &[u8], context: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let x = hash_to_scalar(&[MEMBERSHIP_KEY_DOMAIN, context, device_secret]);
+    let public = (x * RISTRETTO_BASEPOINT_POINT).compress().to_bytes();
+    (x.to_bytes(), public)
+}
+
+/// Prove knowledge of the secret key for `ring[signer_index]`, bound to `message`.
+///
+/// # Arguments
+/// * `ring` - compressed Ristretto public keys of all candidate signers
+/// * `signer_index` - position of the prover's own key in `ring`
+/// * `secret` - prover's secret scalar (from `derive_membership_keypair`)
+/// * `link_context` - context the linking tag is bound to (e.g. group + epoch)
+/// * `message` - data the proof authenticates
+pub fn generate_membership_proof(
+    ring: &[[u8; 32]],
+    signer_index: usize,
+    secret: &[u8; 32],
+    link_context: &[u8],
+    message: &[u8],
+) -> Result<MembershipProof, String> {
+    generate_membership_proof_with_rng(
+        ring,
+        signer_index,
+        secret,
+        link_context,
+        message,
+        &mut OsRng,
+    )
+}
+
+/// [`generate_membership_proof`] drawing the nonce and decoy responses from
+/// `rng`.
+pub fn generate_membership_proof_with_rng(
+    ring: &[[u8; 32]],
+    signer_index: usize,
+    secret: &[u8; 32],
+    link_context: &[u8],
+    message: &[u8],
+    rng: &mut impl SecureRng,
+) -> Result<MembershipProof, String> {
+    let n = ring.len();
+    if n == 0 || n > MAX_MEMBERSHIP_RING {
+        return Err(format!(
+            "ring size must be in 1..={}, got {}",
+            MAX_MEMBERSHIP_RING, n
+        ));
+    }
+    if signer_index >= n {
+        return Err(format!(
+            "signer index {} out of range for ring of {}",
+            signer_index, n
+        ));
+    }
+
+    let x: Scalar = Option::from(Scalar::from_canonical_bytes(*secret))
+        .ok_or_else(|| "Invalid secret scalar".to_string())?;
+    let points = decompress_ring(ring)?;
+    if points[signer_index] != x * RISTRETTO_BASEPOINT_POINT {
+        return Err("Secret does not match the ring entry at signer_index".to_string());
+    }
+
+    let hp = link_base(link_context);
+    let tag = x * hp;
+    let tag_bytes = tag.compress().to_bytes();
+    let prefix = transcript_prefix(ring, &tag_bytes, message);
+
+    let alpha = Scalar::random(rng);
+    let mut c = vec![Scalar::ZERO; n];
+    let mut r = vec![Scalar::ZERO; n];
+
+    let mut i = (signer_index + 1) % n;
+    c[i] = ring_challenge(&prefix, &(alpha * RISTRETTO_BASEPOINT_POINT), &(alpha * hp));
+    while i != signer_index {
+        r[i] = Scalar::random(rng);
+        let l = r[i] * RISTRETTO_BASEPOINT_POINT + c[i] * points[i];
+        let rr = r[i] * hp + c[i] * tag;
+        let next = (i + 1) % n;
+        c[next] = ring_challenge(&prefix, &l, &rr);
+        i = next;
+    }
+    r[signer_index] = alpha - c[signer_index] * x;
+
+    Ok(MembershipProof {
+        c0: c[0].to_bytes(),
+        responses: r.iter().map(|s| s.to_bytes()).collect(),
+        link_tag: tag_bytes,
+    })
+}
+
+/// Verify a membership proof against `ring`, `link_context`, and `message`.
+///
+/// Returns `Ok(false)` if the ring equation does not close, `Err` if any
+/// encoding is malformed.
+pub fn verify_membership_proof(
+    ring: &[[u8; 32]],
+    proof: &MembershipProof,
+    link_context: &[u8],
+    message: &[u8],
+) -> Result<bool, String> {
+    let n = ring.len();
+    if n == 0 || n > MAX_MEMBERSHIP_RING {
+        return Err(format!(
+            "ring size must be in 1..={}, got {}",
+            MAX_MEMBERSHIP_RING, n
+        ));
+    }
+    if proof.responses.len() != n {
+        return Err(format!(
+            "expected {} responses, got {}",
+            n,
+            proof.responses.len()
+        ));
+    }
+
+    let points = decompress_ring(ring)?;
+    let tag = CompressedRistretto::from_slice(&proof.link_tag)
+        .map_err(|e| format!("Invalid link tag bytes: {:?}", e))?
+        .decompress()
+        .ok_or_else(|| "Link tag is not a valid point".to_string())?;
+    if tag == RistrettoPoint::identity() {
+        return Err("Link tag is the identity point".to_string());
+    }
+    let c0: Scalar = Option::from(Scalar::from_canonical_bytes(proof.c0))
+        .ok_or_else(|| "Invalid challenge scalar".to_string())?;
+
+    let hp = link_base(link_context);
+    let prefix = transcript_prefix(ring, &proof.link_tag, message);
+
+    let mut c = c0;
+    for (point, response) in points.iter().zip(proof.responses.iter()) {
+        let r: Scalar = Option::from(Scalar::from_canonical_bytes(*response))
+            .ok_or_else(|| "Invalid response scalar".to_string())?;
+        let l = r * RISTRETTO_BASEPOINT_POINT + c * point;
+        let rr = r * hp + c * tag;
+        c = ring_challenge(&prefix, &l, &rr);
+    }
+
+    Ok(c == c0)
+}
+
+fn decompress_ring(ring: &[[u8; 32]]) -> Result<Vec<RistrettoPoint>, String> {
+    ring.iter()
+        .map(|bytes| {
+            let point = CompressedRistretto::from_slice(bytes)
+                .map_err(|e| format!("Invalid ring key bytes: {:?}", e))?
+                .decompress()
+                .ok_or_else(|| "Ring key is not a valid point".to_string())?;
+            if point == RistrettoPoint::identity() {
+                return Err("Ring key is the identity point".to_string());
+            }
+            Ok(point)
+        })
+        .collect()
+}
+
+fn sha512_wide(parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let mut wide = [0u8; 64];
+    wide.copy_from_slice(hasher.finalize().as_slice());
+    wide
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    Scalar::from_bytes_mod_order_wide(&sha512_wide(parts))
+}
+
+fn link_base(link_context: &[u8]) -> RistrettoPoint {
+    RistrettoPoint::from_uniform_bytes(&sha512_wide(&[MEMBERSHIP_LINK_DOMAIN, link_context]))
+}
+
+fn transcript_prefix(ring: &[[u8; 32]], tag: &[u8; 32], message: &[u8]) -> [u8; 64] {
+    let mut ring_bytes = Vec::with_capacity(ring.len() * 32);
+    for key in ring {
+        ring_bytes.extend_from_slice(key);
+    }
+    sha512_wide(&[MEMBERSHIP_PROOF_DOMAIN, &ring_bytes[..], &tag[..], message])
+}
+
+fn ring_challenge(prefix: &[u8; 64], l: &RistrettoPoint, r: &RistrettoPoint) -> Scalar {
+    let l_bytes = l.compress().to_bytes();
+    let r_bytes = r.compress().to_bytes();
+    hash_to_scalar(&[&prefix[..], &l_bytes[..], &r_bytes[..]])
+}
+
+// ---------------------------------------------------------------------------
+// Attribute predicate proofs
+// ---------------------------------------------------------------------------
+
+/// Domain separator for issuer signatures over attribute credentials.
+const ATTRIBUTE_CREDENTIAL_DOMAIN: &[u8] = b"ShieldMessenger-AttributeCredential-v1";
+/// Transcript label for attribute bound proofs.
+const ATTRIBUTE_RANGE_DOMAIN: &[u8] = b"ShieldMessenger-AttributeRange-v1";
+/// Domain separator for attribute set-membership challenges.
+const ATTRIBUTE_SET_DOMAIN: &[u8] = b"ShieldMessenger-AttributeSet-v1";
+
+/// Bound proofs cover the full `u64` range of `value − min` / `max − value`.
+const ATTRIBUTE_RANGE_BITS: usize = 64;
+
+/// Maximum number of allowed values in an [`AttributePredicate::OneOf`].
+///
+/// Set proofs cost 32 bytes and two scalar multiplications per value.
+pub const MAX_ATTRIBUTE_SET: usize = 64;
+
+/// An issuer's signature binding a Pedersen commitment to a numeric attribute
+/// (age, birth year, region code, ...) of one holder device.
+///
+/// The credential itself reveals nothing about the value; the holder keeps the
+/// [`AttributeOpening`] and proves predicates over it with
+/// [`prove_attribute_predicate`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AttributeCredential {
+    /// Attribute name, as agreed between issuer and verifiers (e.g. `"age"`).
+    pub attribute: String,
+    /// Ed25519 key of the holder device the credential was issued to.
+    pub holder_pubkey: [u8; 32],
+    /// Pedersen commitment `value·B + blinding·B_blinding`.
+    pub commitment: [u8; 32],
+    pub issuer_pubkey: [u8; 32],
+    /// Issuer's Ed25519 signature over the fields above.
+    #[serde(with = "serde_big_array::BigArray")]
+    pub signature: [u8; 64],
+}
+
+/// Secret opening of a credential's commitment. Stays on the holder's device.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AttributeOpening {
+    pub value: u64,
+    pub blinding: [u8; 32],
+}
+
+impl Drop for AttributeOpening {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+        self.value.zeroize();
+        self.blinding.zeroize();
+    }
+}
+
+impl std::fmt::Debug for AttributeOpening {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AttributeOpening(..)")
+    }
+}
+
+/// A statement about a hidden attribute value. Bounds are inclusive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AttributePredicate {
+    AtLeast(u64),
+    AtMost(u64),
+    Between(u64, u64),
+    OneOf(Vec<u64>),
+}
+
+impl AttributePredicate {
+    /// Whether `value` satisfies the predicate (holder-side check).
+    pub fn is_satisfied_by(&self, value: u64) -> bool {
+        match self {
+            AttributePredicate::AtLeast(min) => value >= *min,
+            AttributePredicate::AtMost(max) => value <= *max,
+            AttributePredicate::Between(min, max) => (*min..=*max).contains(&value),
+            AttributePredicate::OneOf(set) => set.contains(&value),
+        }
+    }
+
+    /// Lower and upper bound, for the range predicates.
+    fn bounds(&self) -> (Option<u64>, Option<u64>) {
+        match self {
+            AttributePredicate::AtLeast(min) => (Some(*min), None),
+            AttributePredicate::AtMost(max) => (None, Some(*max)),
+            AttributePredicate::Between(min, max) => (Some(*min), Some(*max)),
+            AttributePredicate::OneOf(_) => (None, None),
+        }
+    }
+}
+
+/// Zero-knowledge proof that a credential's hidden value satisfies an
+/// [`AttributePredicate`], bound to a verifier-chosen context.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AttributeProof {
+    /// Bulletproofs that `value − min` and `max − value` are non-negative;
+    /// `None` for a bound the predicate does not have.
+    Range {
+        lower: Option<Vec<u8>>,
+        upper: Option<Vec<u8>>,
+    },
+    /// Ring proof that `commitment − s·B` is a multiple of `B_blinding` for
+    /// one allowed value `s`, without revealing which.
+    OneOf {
+        c0: [u8; 32],
+        responses: Vec<[u8; 32]>,
+    },
+}
+
+fn credential_signed_bytes(
+    attribute: &str,
+    holder_pubkey: &[u8; 32],
+    commitment: &[u8; 32],
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(ATTRIBUTE_CREDENTIAL_DOMAIN.len() + 8 + attribute.len() + 64);
+    data.extend_from_slice(ATTRIBUTE_CREDENTIAL_DOMAIN);
+    data.extend_from_slice(&(attribute.len() as u64).to_le_bytes());
+    data.extend_from_slice(attribute.as_bytes());
+    data.extend_from_slice(holder_pubkey);
+    data.extend_from_slice(commitment);
+    data
+}
+
+/// Issue a credential committing to `value` for the device `holder_pubkey`.
+///
+/// The issuer has verified the value out of band. Returns the public
+/// credential and the opening to hand to the holder over a secure channel.
+pub fn issue_attribute_credential(
+    attribute: &str,
+    value: u64,
+    holder_pubkey: &[u8; 32],
+    issuer_private_key: &[u8; 32],
+) -> Result<(AttributeCredential, AttributeOpening), String> {
+    issue_attribute_credential_with_rng(
+        attribute,
+        value,
+        holder_pubkey,
+        issuer_private_key,
+        &mut OsRng,
+    )
+}
+
+/// [`issue_attribute_credential`] drawing the blinding from `rng`.
+pub fn issue_attribute_credential_with_rng(
+    attribute: &str,
+    value: u64,
+    holder_pubkey: &[u8; 32],
+    issuer_private_key: &[u8; 32],
+    rng: &mut impl SecureRng,
+) -> Result<(AttributeCredential, AttributeOpening), String> {
+    let blinding = Scalar::random(rng);
+    let commitment = PedersenGens::default()
+        .commit(Scalar::from(value), blinding)
+        .compress()
+        .to_bytes();
+    let issuer_pubkey = crate::crypto::signing::derive_public_key(issuer_private_key)
+        .map_err(|e| format!("Invalid issuer key: {}", e))?;
+    let signature = crate::crypto::signing::sign_data(
+        &credential_signed_bytes(attribute, holder_pubkey, &commitment),
+        issuer_private_key,
+    )
+    .map_err(|e| format!("Credential signing failed: {}", e))?;
+
+    Ok((
+        AttributeCredential {
+            attribute: attribute.to_string(),
+            holder_pubkey: *holder_pubkey,
+            commitment,
+            issuer_pubkey,
+            signature,
+        },
+        AttributeOpening {
+            value,
+            blinding: blinding.to_bytes(),
+        },
+    ))
+}
+
+impl AttributeCredential {
+    /// Check the issuer's signature. Says nothing about which issuer is
+    /// trusted; compare `issuer_pubkey` against the verifier's own list.
+    pub fn verify_issuer(&self) -> bool {
+        let data = credential_signed_bytes(&self.attribute, &self.holder_pubkey, &self.commitment);
+        matches!(
+            crate::crypto::signing::verify_signature(&data, &self.signature, &self.issuer_pubkey),
+            Ok(true)
+        )
+    }
+}
+
+/// Prove that the value behind `credential` satisfies `predicate`.
+///
+/// `context` should name what the proof is for (e.g. group + invite + joining
+/// device), so the proof cannot be replayed elsewhere.
+pub fn prove_attribute_predicate(
+    credential: &AttributeCredential,
+    opening: &AttributeOpening,
+    predicate: &AttributePredicate,
+    context: &[u8],
+) -> Result<AttributeProof, String> {
+    prove_attribute_predicate_with_rng(credential, opening, predicate, context, &mut OsRng)
+}
+
+/// [`prove_attribute_predicate`] drawing the proof randomness from `rng`.
+pub fn prove_attribute_predicate_with_rng(
+    credential: &AttributeCredential,
+    opening: &AttributeOpening,
+    predicate: &AttributePredicate,
+    context: &[u8],
+    rng: &mut impl SecureRng,
+) -> Result<AttributeProof, String> {
+    if !predicate.is_satisfied_by(opening.value) {
+        return Err("Attribute value does not satisfy the predicate".to_string());
+    }
+    let blinding: Scalar = Option::from(Scalar::from_canonical_bytes(opening.blinding))
+        .ok_or_else(|| "Invalid blinding scalar".to_string())?;
+    let pc_gens = PedersenGens::default();
+    let commitment = pc_gens.commit(Scalar::from(opening.value), blinding);
+    if commitment.compress().to_bytes() != credential.commitment {
+        return Err("Opening does not match the credential commitment".to_string());
+    }
+
+    if let AttributePredicate::OneOf(set) = predicate {
+        check_attribute_set(set)?;
+        let index = set
+            .iter()
+            .position(|s| *s == opening.value)
+            .expect("predicate is satisfied");
+        let (c0, responses) = prove_set_membership(
+            &pc_gens,
+            &set_points(&pc_gens, &commitment, set),
+            index,
+            &blinding,
+            &set_prefix(&credential.commitment, set, context),
+            rng,
+        );
+        return Ok(AttributeProof::OneOf { c0, responses });
+    }
+
+    let bp_gens = BulletproofGens::new(ATTRIBUTE_RANGE_BITS, 1);
+    let mut prove_bound = |side: &'static [u8], value: u64, blinding: Scalar| {
+        let mut transcript = range_transcript(side, &credential.commitment, context);
+        RangeProof::prove_single_with_rng(
+            &bp_gens,
+            &pc_gens,
+            &mut transcript,
+            value,
+            &blinding,
+            ATTRIBUTE_RANGE_BITS,
+            rng,
+        )
+        .map(|(proof, _)| proof.to_bytes())
+        .map_err(|e| format!("Range proof generation failed: {:?}", e))
+    };
+    let (min, max) = predicate.bounds();
+    // value − min is committed to by C − min·B with the same blinding;
+    // max − value by max·B − C with the negated blinding
+    let lower = min
+        .map(|min| prove_bound(b"lower", opening.value - min, blinding))
+        .transpose()?;
+    let upper = max
+        .map(|max| prove_bound(b"upper", max - opening.value, -blinding))
+        .transpose()?;
+    Ok(AttributeProof::Range { lower, upper })
+}
+
+/// Verify `proof` that the value behind `credential` satisfies `predicate`
+/// under `context`.
+///
+/// Only checks the proof; call [`AttributeCredential::verify_issuer`] and
+/// check the issuer, attribute and holder separately. Returns `Ok(false)` if
+/// the proof does not hold, `Err` if it is malformed or of the wrong shape.
+pub fn verify_attribute_predicate(
+    credential: &AttributeCredential,
+    predicate: &AttributePredicate,
+    proof: &AttributeProof,
+    context: &[u8],
+) -> Result<bool, String> {
+    let pc_gens = PedersenGens::default();
+    let commitment = CompressedRistretto::from_slice(&credential.commitment)
+        .map_err(|e| format!("Invalid commitment bytes: {:?}", e))?
+        .decompress()
+        .ok_or_else(|| "Commitment is not a valid point".to_string())?;
+
+    match (predicate, proof) {
+        (AttributePredicate::OneOf(set), AttributeProof::OneOf { c0, responses }) => {
+            check_attribute_set(set)?;
+            if responses.len() != set.len() {
+                return Err(format!(
+                    "expected {} responses, got {}",
+                    set.len(),
+                    responses.len()
+                ));
+            }
+            verify_set_membership(
+                &pc_gens,
+                &set_points(&pc_gens, &commitment, set),
+                c0,
+                responses,
+                &set_prefix(&credential.commitment, set, context),
+            )
+        }
+        (AttributePredicate::OneOf(_), _) | (_, AttributeProof::OneOf { .. }) => {
+            Err("Proof does not match the predicate".to_string())
+        }
+        (_, AttributeProof::Range { lower, upper }) => {
+            let bp_gens = BulletproofGens::new(ATTRIBUTE_RANGE_BITS, 1);
+            let verify_bound = |side: &'static [u8], target: RistrettoPoint, bytes: &[u8]| {
+                let proof = RangeProof::from_bytes(bytes)
+                    .map_err(|e| format!("Invalid proof bytes: {:?}", e))?;
+                let mut transcript = range_transcript(side, &credential.commitment, context);
+                Ok::<bool, String>(
+                    proof
+                        .verify_single(
+                            &bp_gens,
+                            &pc_gens,
+                            &mut transcript,
+                            &target.compress(),
+                            ATTRIBUTE_RANGE_BITS,
+                        )
+                        .is_ok(),
+                )
+            };
+            let (min, max) = predicate.bounds();
+            if min.is_some() != lower.is_some() || max.is_some() != upper.is_some() {
+                return Err("Proof does not match the predicate".to_string());
+            }
+            if let (Some(min), Some(bytes)) = (min, lower) {
+                if !verify_bound(b"lower", commitment - Scalar::from(min) * pc_gens.B, bytes)? {
+                    return Ok(false);
+                }
+            }
+            if let (Some(max), Some(bytes)) = (max, upper) {
+                if !verify_bound(b"upper", Scalar::from(max) * pc_gens.B - commitment, bytes)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+fn check_attribute_set(set: &[u64]) -> Result<(), String> {
+    if set.is_empty() || set.len() > MAX_ATTRIBUTE_SET {
+        return Err(format!(
+            "attribute set size must be in 1..={}, got {}",
+            MAX_ATTRIBUTE_SET,
+            set.len()
+        ));
+    }
+    Ok(())
+}
+
+fn range_transcript(side: &'static [u8], commitment: &[u8; 32], context: &[u8]) -> Transcript {
+    let mut transcript = Transcript::new(ATTRIBUTE_RANGE_DOMAIN);
+    transcript.append_message(b"side", side);
+    transcript.append_message(b"commitment", commitment);
+    transcript.append_message(b"context", context);
+    transcript
+}
+
+/// `commitment − s·B` for each allowed value; the prover knows the
+/// `B_blinding` discrete log of exactly the one where `s` is its value.
+fn set_points(
+    pc_gens: &PedersenGens,
+    commitment: &RistrettoPoint,
+    set: &[u64],
+) -> Vec<RistrettoPoint> {
+    set.iter()
+        .map(|s| commitment - Scalar::from(*s) * pc_gens.B)
+        .collect()
+}
+
+fn set_prefix(commitment: &[u8; 32], set: &[u64], context: &[u8]) -> [u8; 64] {
+    let set_bytes: Vec<u8> = set.iter().flat_map(|s| s.to_le_bytes()).collect();
+    sha512_wide(&[ATTRIBUTE_SET_DOMAIN, commitment, &set_bytes[..], context])
+}
+
+fn set_challenge(prefix: &[u8; 64], point: &RistrettoPoint) -> Scalar {
+    hash_to_scalar(&[&prefix[..], &point.compress().to_bytes()[..]])
+}
+
+/// Ring (AOS) proof of the `B_blinding` discrete log of `points[index]`.
+fn prove_set_membership(
+    pc_gens: &PedersenGens,
+    points: &[RistrettoPoint],
+    index: usize,
+    secret: &Scalar,
+    prefix: &[u8; 64],
+    rng: &mut impl SecureRng,
+) -> ([u8; 32], Vec<[u8; 32]>) {
+    let n = points.len();
+    let h = pc_gens.B_blinding;
+    let alpha = Scalar::random(rng);
+    let mut c = vec![Scalar::ZERO; n];
+    let mut r = vec![Scalar::ZERO; n];
+
+    let mut i = (index + 1) % n;
+    c[i] = set_challenge(prefix, &(alpha * h));
+    while i != index {
+        r[i] = Scalar::random(rng);
+        let next = (i + 1) % n;
+        c[next] = set_challenge(prefix, &(r[i] * h + c[i] * points[i]));
+        i = next;
+    }
+    r[index] = alpha - c[index] * secret;
+
+    (c[0].to_bytes(), r.iter().map(|s| s.to_bytes()).collect())
+}
+
+fn verify_set_membership(
+    pc_gens: &PedersenGens,
+    points: &[RistrettoPoint],
+    c0: &[u8; 32],
+    responses: &[[u8; 32]],
+    prefix: &[u8; 64],
+) -> Result<bool, String> {
+    let c0: Scalar = Option::from(Scalar::from_canonical_bytes(*c0))
+        .ok_or_else(|| "Invalid challenge scalar".to_string())?;
+    let mut c = c0;
+    for (point, response) in points.iter().zip(responses.iter()) {
+        let r: Scalar = Option::from(Scalar::from_canonical_bytes(*response))
+            .ok_or_else(|| "Invalid response scalar".to_string())?;
+        c = set_challenge(prefix, &(r * pc_gens.B_blinding + c * point));
+    }
+    Ok(c == c0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +781,103 @@ mod tests {
         let result = generate_range_proof(100, 12);
         assert!(result.is_err());
     }
+
+    fn test_ring(size: usize) -> Vec<([u8; 32], [u8; 32])> {
+        (0..size)
+            .map(|i| derive_membership_keypair(&[i as u8; 32], b"test-group"))
+            .collect()
+    }
+
+    #[test]
+    fn test_membership_proof_roundtrip() {
+        let members = test_ring(4);
+        let ring: Vec<[u8; 32]> = members.iter().map(|(_, pk)| *pk).collect();
+
+        for (idx, (sk, _)) in members.iter().enumerate() {
+            let proof = generate_membership_proof(&ring, idx, sk, b"epoch-1", b"hello").unwrap();
+            assert!(verify_membership_proof(&ring, &proof, b"epoch-1", b"hello").unwrap());
+        }
+    }
+
+    #[test]
+    fn test_membership_proof_rejects_wrong_message_or_context() {
+        let members = test_ring(3);
+        let ring: Vec<[u8; 32]> = members.iter().map(|(_, pk)| *pk).collect();
+        let proof = generate_membership_proof(&ring, 1, &members[1].0, b"ctx", b"msg").unwrap();
+
+        assert!(!verify_membership_proof(&ring, &proof, b"ctx", b"other").unwrap());
+        assert!(!verify_membership_proof(&ring, &proof, b"other-ctx", b"msg").unwrap());
+    }
+
+    #[test]
+    fn test_membership_proof_requires_ring_secret() {
+        let members = test_ring(3);
+        let ring: Vec<[u8; 32]> = members.iter().map(|(_, pk)| *pk).collect();
+        let (outsider_sk, _) = derive_membership_keypair(&[0xEE; 32], b"test-group");
+
+        assert!(generate_membership_proof(&ring, 0, &outsider_sk, b"ctx", b"msg").is_err());
+    }
+
+    #[test]
+    fn test_membership_link_tag_is_stable_per_context() {
+        let members = test_ring(3);
+        let ring: Vec<[u8; 32]> = members.iter().map(|(_, pk)| *pk).collect();
+        let sk = &members[2].0;
+
+        let a = generate_membership_proof(&ring, 2, sk, b"epoch-1", b"one").unwrap();
+        let b = generate_membership_proof(&ring, 2, sk, b"epoch-1", b"two").unwrap();
+        let c = generate_membership_proof(&ring, 2, sk, b"epoch-2", b"one").unwrap();
+
+        assert_eq!(a.link_tag, b.link_tag);
+        assert_ne!(a.link_tag, c.link_tag);
+    }
+
+    fn age_credential(value: u64) -> (AttributeCredential, AttributeOpening) {
+        let (holder, _) = crate::crypto::signing::generate_keypair();
+        let (_, issuer_private) = crate::crypto::signing::generate_keypair();
+        issue_attribute_credential("age", value, &holder, &issuer_private).unwrap()
+    }
+
+    #[test]
+    fn test_attribute_range_predicate_roundtrip() {
+        let (credential, opening) = age_credential(25);
+        assert!(credential.verify_issuer());
+
+        let adult = AttributePredicate::AtLeast(18);
+        let proof = prove_attribute_predicate(&credential, &opening, &adult, b"ctx").unwrap();
+        assert!(verify_attribute_predicate(&credential, &adult, &proof, b"ctx").unwrap());
+        assert!(!verify_attribute_predicate(&credential, &adult, &proof, b"other").unwrap());
+        // A proof for one bound does not carry over to a stricter one
+        let stricter = AttributePredicate::AtLeast(26);
+        assert!(!verify_attribute_predicate(&credential, &stricter, &proof, b"ctx").unwrap());
+        assert!(prove_attribute_predicate(&credential, &opening, &stricter, b"ctx").is_err());
+
+        let band = AttributePredicate::Between(18, 30);
+        let proof = prove_attribute_predicate(&credential, &opening, &band, b"ctx").unwrap();
+        assert!(verify_attribute_predicate(&credential, &band, &proof, b"ctx").unwrap());
+
+        let mut forged = credential.clone();
+        forged.holder_pubkey = [7; 32];
+        assert!(!forged.verify_issuer());
+    }
+
+    #[test]
+    fn test_attribute_set_predicate_roundtrip() {
+        let (credential, opening) = age_credential(3);
+        let allowed = AttributePredicate::OneOf(vec![1, 3, 7]);
+        let proof = prove_attribute_predicate(&credential, &opening, &allowed, b"ctx").unwrap();
+        assert!(verify_attribute_predicate(&credential, &allowed, &proof, b"ctx").unwrap());
+        assert!(!verify_attribute_predicate(&credential, &allowed, &proof, b"other").unwrap());
+
+        let narrower = AttributePredicate::OneOf(vec![1, 4, 7]);
+        assert!(!verify_attribute_predicate(&credential, &narrower, &proof, b"ctx").unwrap());
+        assert!(prove_attribute_predicate(&credential, &opening, &narrower, b"ctx").is_err());
+        assert!(verify_attribute_predicate(
+            &credential,
+            &AttributePredicate::AtLeast(1),
+            &proof,
+            b"ctx"
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,345 @@
+/// Legal-hold key escrow (feature `escrow`, off by default).
+///
+/// Some organizations are required to retain readable copies of business
+/// conversations. Rather than weakening the protocol, escrow mode hands the
+/// organization a copy of each message key, wrapped to its escrow public key
+/// (X25519 + ML-KEM-1024 hybrid), alongside the message as it is sent:
+///
+/// - **Explicit:** nothing is wrapped until a [`KeyEscrow`] is created from an
+///   [`EscrowPolicy`], and disabling it stops wrapping immediately.
+/// - **Scoped:** the policy names the conversations under hold. Others are
+///   never wrapped.
+/// - **Audited:** enabling, disabling and every wrapped key produce an
+///   [`EscrowAuditEvent`] the app must surface to the user and keep.
+/// - **Separate:** the sending side can only wrap ([`KeyEscrow`]); unwrapping
+///   needs the organization's secret key and lives in [`EscrowAuthority`].
+///
+/// Builds that must never contain escrow leave the `escrow` feature off and
+/// the whole module is compiled out.
+use crate::crypto::encryption::{decrypt_message, derive_root_key, encrypt_message_with_rng};
+use crate::crypto::pqc::{hybrid_decapsulate, hybrid_encapsulate, MLKEM1024_CT_BYTES};
+use crate::protocol::ContactId;
+use crate::rng::SecureRng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum EscrowError {
+    #[error("Malformed escrow record")]
+    Malformed,
+    #[error("Unsupported escrow record version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Escrow is disabled")]
+    Disabled,
+    #[error("Invalid escrow key")]
+    InvalidKey,
+    #[error("Key wrapping failed: {0}")]
+    Wrap(String),
+    #[error("Key unwrapping failed")]
+    Unwrap,
+    #[error("Escrow record encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Escrow record wire version.
+pub const ESCROW_RECORD_VERSION: u8 = 1;
+
+const KEK_INFO: &[u8] = b"ShieldMessenger-Escrow-v1";
+
+/// What the organization escrows and to which key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowPolicy {
+    /// Organization identifier, recorded in audit events.
+    pub org_id: String,
+    pub org_x25519_public: [u8; 32],
+    /// ML-KEM-1024 encapsulation key.
+    pub org_mlkem_public: Vec<u8>,
+    /// Conversations under hold.
+    pub conversations: BTreeSet<ContactId>,
+}
+
+/// Audit trail entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscrowAuditEvent {
+    Enabled {
+        org_id: String,
+        conversations: usize,
+        at: u64,
+    },
+    Disabled {
+        org_id: String,
+        at: u64,
+    },
+    KeyWrapped {
+        conversation: ContactId,
+        sequence: u64,
+        at: u64,
+    },
+}
+
+/// A message key wrapped to the organization.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowRecord {
+    pub conversation: ContactId,
+    /// Message sequence number within the conversation.
+    pub sequence: u64,
+    pub x25519_ephemeral_public: [u8; 32],
+    pub kem_ciphertext: Vec<u8>,
+    /// `nonce ‖ XChaCha20-Poly1305(message key)`
+    pub wrapped_key: Vec<u8>,
+}
+
+impl EscrowRecord {
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EscrowError> {
+        let body = bincode::serialize(self).map_err(|e| EscrowError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(ESCROW_RECORD_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, EscrowError> {
+        let (&version, body) = data.split_first().ok_or(EscrowError::Malformed)?;
+        if version != ESCROW_RECORD_VERSION {
+            return Err(EscrowError::UnsupportedVersion(version));
+        }
+        let record: Self = bincode::deserialize(body).map_err(|_| EscrowError::Malformed)?;
+        if record.kem_ciphertext.len() != MLKEM1024_CT_BYTES {
+            return Err(EscrowError::Malformed);
+        }
+        Ok(record)
+    }
+}
+
+/// Key-encryption key bound to the conversation and sequence, so a record
+/// cannot be relabelled as another message's key.
+fn derive_kek(
+    shared_secret: &[u8],
+    conversation: &ContactId,
+    sequence: u64,
+) -> Result<[u8; 32], EscrowError> {
+    let mut info = KEK_INFO.to_vec();
+    info.extend_from_slice(conversation.to_string().as_bytes());
+    info.extend_from_slice(&sequence.to_be_bytes());
+    derive_root_key(shared_secret, &info).map_err(|_| EscrowError::InvalidKey)
+}
+
+/// Sending side of escrow mode.
+pub struct KeyEscrow {
+    policy: Option<EscrowPolicy>,
+    audit: Vec<EscrowAuditEvent>,
+}
+
+impl KeyEscrow {
+    /// Turn escrow on for `policy`. Records an [`EscrowAuditEvent::Enabled`].
+    pub fn enable(policy: EscrowPolicy, now: u64) -> Result<Self, EscrowError> {
+        if policy.org_mlkem_public.len() != crate::crypto::pqc::MLKEM1024_EK_BYTES {
+            return Err(EscrowError::InvalidKey);
+        }
+        let audit = vec![EscrowAuditEvent::Enabled {
+            org_id: policy.org_id.clone(),
+            conversations: policy.conversations.len(),
+            at: now,
+        }];
+        log::warn!(
+            "Key escrow enabled for {} ({} conversations)",
+            policy.org_id,
+            policy.conversations.len()
+        );
+        Ok(Self {
+            policy: Some(policy),
+            audit,
+        })
+    }
+
+    /// Stop wrapping keys. Records an [`EscrowAuditEvent::Disabled`].
+    pub fn disable(&mut self, now: u64) {
+        if let Some(policy) = self.policy.take() {
+            log::warn!("Key escrow disabled for {}", policy.org_id);
+            self.audit.push(EscrowAuditEvent::Disabled {
+                org_id: policy.org_id,
+                at: now,
+            });
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.policy.is_some()
+    }
+
+    pub fn policy(&self) -> Option<&EscrowPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Whether messages in `conversation` must be escrowed.
+    pub fn covers(&self, conversation: &ContactId) -> bool {
+        self.policy
+            .as_ref()
+            .is_some_and(|p| p.conversations.contains(conversation))
+    }
+
+    /// Wrap the key of message `sequence` in `conversation`, at send time.
+    ///
+    /// `Ok(None)` if the conversation is not under hold.
+    pub fn wrap(
+        &mut self,
+        conversation: ContactId,
+        sequence: u64,
+        message_key: &[u8; 32],
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Option<EscrowRecord>, EscrowError> {
+        let policy = self.policy.as_ref().ok_or(EscrowError::Disabled)?;
+        if !policy.conversations.contains(&conversation) {
+            return Ok(None);
+        }
+        let encapsulated = hybrid_encapsulate(&policy.org_x25519_public, &policy.org_mlkem_public)
+            .map_err(|e| EscrowError::Wrap(e.to_string()))?;
+        let mut kek = derive_kek(&encapsulated.shared_secret, &conversation, sequence)?;
+        let wrapped_key = encrypt_message_with_rng(message_key, &kek, rng)
+            .map_err(|e| EscrowError::Wrap(e.to_string()));
+        kek.zeroize();
+        let record = EscrowRecord {
+            conversation,
+            sequence,
+            x25519_ephemeral_public: encapsulated.x25519_ephemeral_public,
+            kem_ciphertext: encapsulated.kyber_ciphertext,
+            wrapped_key: wrapped_key?,
+        };
+        self.audit.push(EscrowAuditEvent::KeyWrapped {
+            conversation,
+            sequence,
+            at: now,
+        });
+        Ok(Some(record))
+    }
+
+    /// Take the audit events recorded since the last call.
+    pub fn drain_audit(&mut self) -> Vec<EscrowAuditEvent> {
+        std::mem::take(&mut self.audit)
+    }
+}
+
+/// Organization side: recovers message keys from escrow records.
+pub struct EscrowAuthority {
+    x25519_secret: [u8; 32],
+    mlkem_secret: Vec<u8>,
+}
+
+impl Drop for EscrowAuthority {
+    fn drop(&mut self) {
+        self.x25519_secret.zeroize();
+        self.mlkem_secret.zeroize();
+    }
+}
+
+impl EscrowAuthority {
+    pub fn new(x25519_secret: [u8; 32], mlkem_secret: Vec<u8>) -> Self {
+        Self {
+            x25519_secret,
+            mlkem_secret,
+        }
+    }
+
+    /// The message key in `record`.
+    pub fn unwrap(&self, record: &EscrowRecord) -> Result<[u8; 32], EscrowError> {
+        let mut shared = hybrid_decapsulate(
+            &record.x25519_ephemeral_public,
+            &record.kem_ciphertext,
+            &self.x25519_secret,
+            &self.mlkem_secret,
+        )
+        .map_err(|_| EscrowError::Unwrap)?;
+        let kek = derive_kek(&shared, &record.conversation, record.sequence);
+        shared.zeroize();
+        let mut kek = kek?;
+        let key = decrypt_message(&record.wrapped_key, &kek);
+        kek.zeroize();
+        let mut key = key.map_err(|_| EscrowError::Unwrap)?;
+        let out: Result<[u8; 32], _> = key.as_slice().try_into();
+        key.zeroize();
+        out.map_err(|_| EscrowError::Unwrap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::pqc::generate_hybrid_keypair_from_seed;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn setup() -> (KeyEscrow, EscrowAuthority) {
+        let org = generate_hybrid_keypair_from_seed(&[9; 32]).unwrap();
+        let policy = EscrowPolicy {
+            org_id: "acme-legal".to_string(),
+            org_x25519_public: org.x25519_public,
+            org_mlkem_public: org.kyber_public.clone(),
+            conversations: [test_contact(1)].into_iter().collect(),
+        };
+        let escrow = KeyEscrow::enable(policy, NOW).unwrap();
+        let authority = EscrowAuthority::new(org.x25519_secret, org.kyber_secret.clone());
+        (escrow, authority)
+    }
+
+    #[test]
+    fn test_wrap_and_unwrap_only_held_conversations() {
+        let (mut escrow, authority) = setup();
+        let mut rng = seeded(3);
+        let key = [0x42; 32];
+
+        let record = escrow
+            .wrap(test_contact(1), 7, &key, NOW, &mut rng)
+            .unwrap()
+            .unwrap();
+        let record = EscrowRecord::from_bytes(&record.to_bytes().unwrap()).unwrap();
+        assert_eq!(authority.unwrap(&record).unwrap(), key);
+        assert_eq!(
+            escrow.wrap(test_contact(2), 1, &key, NOW, &mut rng),
+            Ok(None)
+        );
+
+        // A record relabelled to another message does not open
+        let mut moved = record.clone();
+        moved.sequence = 8;
+        assert_eq!(authority.unwrap(&moved), Err(EscrowError::Unwrap));
+    }
+
+    #[test]
+    fn test_audit_trail_and_disable() {
+        let (mut escrow, _) = setup();
+        let mut rng = seeded(4);
+        escrow
+            .wrap(test_contact(1), 1, &[1; 32], NOW + 1, &mut rng)
+            .unwrap();
+        escrow.disable(NOW + 2);
+        assert!(!escrow.covers(&test_contact(1)));
+        assert_eq!(
+            escrow.wrap(test_contact(1), 2, &[1; 32], NOW + 3, &mut rng),
+            Err(EscrowError::Disabled)
+        );
+        let audit = escrow.drain_audit();
+        assert!(matches!(
+            audit[0],
+            EscrowAuditEvent::Enabled {
+                conversations: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            audit[1],
+            EscrowAuditEvent::KeyWrapped {
+                conversation: test_contact(1),
+                sequence: 1,
+                at: NOW + 1
+            }
+        );
+        assert!(matches!(audit[2], EscrowAuditEvent::Disabled { .. }));
+        assert!(escrow.drain_audit().is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+/// Machine-readable inventory of the cryptography compiled into this build.
+///
+/// [`inventory`] lists the cipher suites, primitives, ML-KEM sizes, Argon2
+/// cost parameters, wire-format versions and Cargo features of the running
+/// library, read from the same constants the code uses, so what an app
+/// displays or logs for an audit cannot drift from what actually runs.
+///
+/// The output is deterministic for a given build: two binaries with the same
+/// [`BuildInventory::fingerprint`] run the same cryptographic configuration.
+use crate::crypto::pqc::{MLKEM1024_CT_BYTES, MLKEM1024_DK_BYTES, MLKEM1024_EK_BYTES};
+use crate::crypto::{backup, duress};
+use crate::protocol::{self, PowParams, SUPPORTED_SUITES};
+use crate::transport::padding::DEFAULT_PACKET_SIZE;
+use serde::Serialize;
+
+/// A primitive and what the protocol uses it for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Primitive {
+    pub purpose: &'static str,
+    pub algorithm: &'static str,
+    /// Defining standard.
+    pub standard: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuiteInfo {
+    pub id: u16,
+    pub name: &'static str,
+}
+
+/// ML-KEM parameter set and sizes, in bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KemInfo {
+    pub algorithm: &'static str,
+    pub encapsulation_key_bytes: usize,
+    pub decapsulation_key_bytes: usize,
+    pub ciphertext_bytes: usize,
+    /// Output of the X25519 + ML-KEM combiner.
+    pub hybrid_secret_bytes: usize,
+}
+
+/// Argon2id cost parameters for one use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Argon2Info {
+    pub usage: &'static str,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInventory {
+    pub sdk_version: &'static str,
+    pub target_arch: &'static str,
+    pub target_os: &'static str,
+    /// Enabled Cargo features of `shield-protocol`.
+    pub features: Vec<&'static str>,
+    /// Negotiable suites, strongest first.
+    pub cipher_suites: Vec<SuiteInfo>,
+    pub primitives: Vec<Primitive>,
+    pub kem: KemInfo,
+    pub argon2: Vec<Argon2Info>,
+    pub packet_size: usize,
+    /// Wire-format version of each versioned structure.
+    pub wire_versions: Vec<(&'static str, u8)>,
+    /// Relay protocol versions this build speaks.
+    pub relay_protocols: Vec<u16>,
+}
+
+impl BuildInventory {
+    /// Hex BLAKE3 digest of the inventory's JSON form.
+    pub fn fingerprint(&self) -> String {
+        blake3::hash(self.to_json().as_bytes()).to_hex().to_string()
+    }
+
+    pub fn to_json(&self) -> String {
+        // Plain structs of strings and integers: serialization cannot fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    let flags = [
+        ("std", cfg!(feature = "std")),
+        ("groups", cfg!(feature = "groups")),
+        ("zkproofs", cfg!(feature = "zkproofs")),
+        ("decoys", cfg!(feature = "decoys")),
+        ("parallel", cfg!(feature = "parallel")),
+        ("wasm", cfg!(feature = "wasm")),
+        ("testkit", cfg!(feature = "testkit")),
+        ("escrow", cfg!(feature = "escrow")),
+        ("discovery", cfg!(feature = "discovery")),
+        ("relayd", cfg!(feature = "relayd")),
+    ];
+    flags
+        .into_iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+fn primitives() -> Vec<Primitive> {
+    let mut list = vec![
+        Primitive {
+            purpose: "message encryption",
+            algorithm: "XChaCha20-Poly1305",
+            standard: "draft-irtf-cfrg-xchacha",
+        },
+        Primitive {
+            purpose: "signatures",
+            algorithm: "Ed25519",
+            standard: "RFC 8032",
+        },
+        Primitive {
+            purpose: "key agreement",
+            algorithm: "X25519",
+            standard: "RFC 7748",
+        },
+        Primitive {
+            purpose: "post-quantum key encapsulation",
+            algorithm: "ML-KEM-1024",
+            standard: "FIPS 203",
+        },
+        Primitive {
+            purpose: "root key derivation",
+            algorithm: "HKDF-SHA256",
+            standard: "RFC 5869",
+        },
+        Primitive {
+            purpose: "chain key evolution",
+            algorithm: "HMAC-SHA256",
+            standard: "RFC 2104",
+        },
+        Primitive {
+            purpose: "media frame encryption",
+            algorithm: "ChaCha20-Poly1305, ChaCha20 + truncated HMAC-SHA256",
+            standard: "RFC 8439, RFC 9605 layout",
+        },
+        Primitive {
+            purpose: "hashing and hybrid secret combiner",
+            algorithm: "BLAKE3",
+            standard: "BLAKE3 specification",
+        },
+        Primitive {
+            purpose: "password and PIN hashing",
+            algorithm: "Argon2id v0x13",
+            standard: "RFC 9106",
+        },
+    ];
+    if cfg!(feature = "zkproofs") {
+        list.push(Primitive {
+            purpose: "zero-knowledge range proofs",
+            algorithm: "Bulletproofs over Ristretto255",
+            standard: "Bünz et al. 2018",
+        });
+        list.push(Primitive {
+            purpose: "attribute credentials and predicate proofs",
+            algorithm: "Pedersen commitments, Bulletproofs and AOS ring proofs over Ristretto255",
+            standard: "Bünz et al. 2018; Abe-Ohkubo-Suzuki 2002",
+        });
+    }
+    list
+}
+
+fn argon2_profiles() -> Vec<Argon2Info> {
+    let password = argon2::Params::default();
+    let stamp = PowParams::default();
+    #[cfg_attr(not(feature = "discovery"), allow(unused_mut))]
+    let mut profiles = vec![
+        Argon2Info {
+            usage: "password hashing",
+            memory_kib: password.m_cost(),
+            iterations: password.t_cost(),
+            parallelism: password.p_cost(),
+        },
+        Argon2Info {
+            usage: "PIN hashing",
+            memory_kib: duress::PIN_ARGON2_MEM_COST,
+            iterations: duress::PIN_ARGON2_TIME_COST,
+            parallelism: duress::PIN_ARGON2_PARALLELISM,
+        },
+        Argon2Info {
+            usage: "encrypted backup",
+            memory_kib: backup::ARGON2_MEM_COST,
+            iterations: backup::ARGON2_TIME_COST,
+            parallelism: backup::ARGON2_PARALLELISM,
+        },
+        Argon2Info {
+            usage: "proof-of-work stamp",
+            memory_kib: stamp.memory_kib,
+            iterations: stamp.iterations,
+            parallelism: 1,
+        },
+    ];
+    #[cfg(feature = "discovery")]
+    {
+        let lookup = protocol::discovery::LookupKdfParams::default();
+        profiles.push(Argon2Info {
+            usage: "discovery lookup PIN",
+            memory_kib: lookup.memory_kib,
+            iterations: lookup.iterations,
+            parallelism: 1,
+        });
+    }
+    profiles
+}
+
+/// Inventory of this build.
+pub fn inventory() -> BuildInventory {
+    #[cfg_attr(not(feature = "discovery"), allow(unused_mut))]
+    let mut inventory = BuildInventory {
+        sdk_version: crate::VERSION,
+        target_arch: std::env::consts::ARCH,
+        target_os: std::env::consts::OS,
+        features: enabled_features(),
+        cipher_suites: SUPPORTED_SUITES
+            .iter()
+            .map(|s| SuiteInfo {
+                id: s.id(),
+                name: s.name(),
+            })
+            .collect(),
+        primitives: primitives(),
+        kem: KemInfo {
+            algorithm: "ML-KEM-1024",
+            encapsulation_key_bytes: MLKEM1024_EK_BYTES,
+            decapsulation_key_bytes: MLKEM1024_DK_BYTES,
+            ciphertext_bytes: MLKEM1024_CT_BYTES,
+            hybrid_secret_bytes: 64,
+        },
+        argon2: argon2_profiles(),
+        packet_size: DEFAULT_PACKET_SIZE,
+        wire_versions: vec![
+            ("ack_batch", protocol::receipts::ACK_BATCH_VERSION),
+            ("broadcast", protocol::broadcast::BROADCAST_VERSION),
+            ("call_signal", protocol::call::CALL_SIGNAL_VERSION),
+            (
+                "delivery_proof",
+                protocol::delivery_proof::DELIVERY_PROOF_VERSION,
+            ),
+            ("mailbox", protocol::mailbox::MAILBOX_VERSION),
+            ("message_id", protocol::message_id::MESSAGE_ID_VERSION),
+            (
+                "presence_beacon",
+                protocol::presence::PRESENCE_BEACON_VERSION,
+            ),
+            ("reaction", protocol::reaction::REACTION_VERSION),
+            (
+                "relay_descriptor",
+                protocol::relay::RELAY_DESCRIPTOR_VERSION,
+            ),
+            ("relay_api", protocol::relay_api::RELAY_API_VERSION),
+            ("rpc", protocol::rpc::RPC_VERSION),
+            (
+                "resumption_ticket",
+                crate::crypto::resumption::RESUMPTION_VERSION,
+            ),
+            (
+                "sequenced_envelope",
+                protocol::ordering::SEQUENCED_ENVELOPE_VERSION,
+            ),
+            ("archive_segment", crate::storage::archive::SEGMENT_VERSION),
+            ("wake_message", protocol::silence::WAKE_MESSAGE_VERSION),
+        ],
+        relay_protocols: protocol::relay::SUPPORTED_RELAY_PROTOCOLS.to_vec(),
+    };
+    #[cfg(feature = "discovery")]
+    inventory
+        .wire_versions
+        .push(("discovery", protocol::discovery::DISCOVERY_VERSION));
+    inventory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::pqc::generate_hybrid_keypair_from_seed;
+
+    #[test]
+    fn test_inventory_matches_runtime() {
+        let inv = inventory();
+        let keypair = generate_hybrid_keypair_from_seed(&[7; 32]).unwrap();
+        assert_eq!(keypair.kyber_public.len(), inv.kem.encapsulation_key_bytes);
+        assert_eq!(keypair.kyber_secret.len(), inv.kem.decapsulation_key_bytes);
+        assert_eq!(inv.cipher_suites[0].name, "hybrid-pq");
+        assert!(inv.features.contains(&"std"));
+        assert_eq!(inv.sdk_version, crate::version());
+    }
+
+    #[test]
+    fn test_fingerprint_is_deterministic() {
+        let a = inventory();
+        let mut b = inventory();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().len(), 64);
+        b.argon2[0].iterations += 1;
+        assert_ne!(a.fingerprint(), b.fingerprint());
+        assert!(a.to_json().contains("\"ML-KEM-1024\""));
+    }
+}
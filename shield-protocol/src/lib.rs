@@ -29,19 +29,36 @@
 //!
 //! | Module | Purpose |
 //! |--------|---------|
-//! | [`crypto`] | Encryption, signing, key exchange, PQ ratchet, replay cache, ZK proofs |
-//! | [`protocol`] | Message types, contact cards, security modes |
-//! | [`transport`] | Fixed-size packets, padding, cover traffic, traffic shaping |
-//! | [`storage`] | Deniable storage traits, duress PIN, decoy generation |
-//! | [`crdt`] | CRDT-based group messaging (operation log, membership, metadata) |
+//! | [`crypto`] | Encryption, signing, key exchange, PQ ratchet, session resumption, conversation-scoped pseudonyms, replay cache, media frame encryption, ZK proofs |
+//! | [`protocol`] | Message types, deterministic message IDs, contact cards, security modes, presence, ordering, reactions, receipt batching, delivery proofs, relay descriptors and client requests, auxiliary RPC framing, well-known HTTPS card discovery (feature-gated), private mailbox checks, mixed group fan-out, broadcast announcements, call signaling, message processing middleware, network silence |
+//! | [`transport`] | Fixed-size packets, padding, cover traffic (global and per-contact flows), traffic shaping, wire size and cover cost estimates |
+//! | [`storage`] | Deniable storage traits, duress PIN, decoy generation, crash-recovery intent log, message archive, per-conversation storage keys, attachment retention, encrypted file trust store |
+//! | [`crdt`] | CRDT-based group messaging (operation log, managed lamport clocks, authorization-checked op authoring, paged message reads, membership, metadata, log compaction) |
+//! | [`rng`] | Injectable randomness: OS default, seeded and recording sources |
+//! | [`inventory`](mod@inventory) | Audit inventory of compiled-in algorithms, parameters, versions and features |
+//! | [`selftest`](mod@selftest) | Startup known-answer tests for AEAD, KDF, signatures, X25519 and ML-KEM |
+//! | `escrow` | Legal-hold key escrow to an organization key (feature-gated) |
+//! | `testkit` | In-process endpoints on a simulated lossy network for end-to-end tests |
+//! | `relayd` | Reference relay (blind mail storage, expiry, rate limits, descriptors) and the `shield-relayd` binary |
 //!
 //! ## Feature Flags
 //!
 //! | Feature | Default | Description |
 //! |---------|---------|-------------|
 //! | `std` | Yes | Standard library support |
-//! | `groups` | Yes | CRDT group messaging (adds `ciborium` for CBOR encoding) |
+//! | `groups` | Yes | CRDT group messaging and incident response plans (adds `ciborium` for CBOR encoding) |
+//! | `zkproofs` | Yes | Bulletproof range, membership and attribute proofs (adds `bulletproofs`, `curve25519-dalek`, `merlin`); without it, anonymous posts and attribute-gated joins are rejected |
+//! | `decoys` | Yes | Decoy database generation and refresh for the duress PIN |
+//! | `parallel` | No | Parallel CRDT op batch verification (adds `rayon`) |
 //! | `wasm` | No | WebAssembly support (`getrandom/js`) |
+//! | `testkit` | No | End-to-end test harness with a simulated network |
+//! | `escrow` | No | Legal-hold key escrow; leave off for builds that must never contain it |
+//! | `discovery` | No | Contact card lookup via a self-hosted well-known HTTPS endpoint |
+//! | `relayd` | No | Reference relay and the `shield-relayd` daemon |
+//!
+//! `default-features = false, features = ["std"]` leaves the crypto,
+//! protocol, transport and storage layers only, for embedded integrations
+//! that watch their binary size.
 
 // Crate-level lint configuration — suppress stylistic warnings that don't affect correctness.
 // Security-relevant lints (unsafe, unchecked, etc.) remain enforced.
@@ -70,7 +87,10 @@ pub mod protocol;
 /// Transport-layer primitives: fixed-size packets, padding, cover traffic.
 pub mod transport;
 
-/// Deniable storage contract, duress PIN semantics, and decoy generation.
+/// Deniable storage contract, duress PIN semantics, decoy generation, the
+/// crash-recovery intent log, message archive compaction, per-conversation
+/// storage keys, attachment retention, a file-backed trust store, and
+/// format versions with migrations for persisted state.
 pub mod storage;
 
 /// CRDT-based group messaging — conflict-free replicated data types for
@@ -78,6 +98,29 @@ pub mod storage;
 #[cfg(feature = "groups")]
 pub mod crdt;
 
+/// Injectable random sources for reproducible tests and nonce audits.
+pub mod rng;
+
+/// Machine-readable inventory of the compiled-in cryptographic configuration.
+pub mod inventory;
+
+/// Power-on known-answer tests of the cryptographic stack.
+pub mod selftest;
+
+/// Opt-in legal-hold escrow of message keys to an organization key.
+#[cfg(feature = "escrow")]
+pub mod escrow;
+
+/// End-to-end test harness: in-process endpoints on a seeded, lossy,
+/// reordering network.
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+
+/// Reference store-and-forward relay: blind mail storage, expiry, rate
+/// limiting and descriptor publishing, served by the `shield-relayd` binary.
+#[cfg(any(test, feature = "relayd"))]
+pub mod relayd;
+
 // ── Re-exports for convenience ──────────────────────────────────────────────
 
 pub use crypto::{
@@ -85,11 +128,21 @@ pub use crypto::{
     hash_password, sign_data, verify_signature,
 };
 
+pub use inventory::{inventory, BuildInventory};
+
+pub use selftest::{selftest, SelfTestError, SelfTestReport};
+
 pub use protocol::{ContactCard, Message, MessageType, SecurityMode};
 
 pub use storage::{
-    generate_decoy_data, on_duress_pin_entered, DecoyConfig, DecoyContact, DecoyMessage,
-    DuressPinSpec, StealthModeSpec, StorageError,
+    on_duress_pin_entered, DuressPinSpec, Persisted, StealthModeSpec, StorageError, VersionError,
+    Versioned,
+};
+
+#[cfg(feature = "decoys")]
+pub use storage::{
+    generate_decoy_data, DecoyConfig, DecoyContact, DecoyDelivery, DecoyDeliveryState, DecoyLocale,
+    DecoyMessage,
 };
 
 pub use transport::{
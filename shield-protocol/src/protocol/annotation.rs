@@ -0,0 +1,326 @@
+/// Key-value annotations carried in the message envelope.
+///
+/// Apps built on the protocol attach their own metadata to a message (a
+/// thread ID, a client version hint) without changing the body format other
+/// implementations parse. Annotations travel in the sequenced envelope
+/// (see [`SequencedEnvelope`](super::ordering::SequencedEnvelope)), inside
+/// the message AEAD, so they are as authenticated and as private as the body.
+///
+/// Keys are namespaced, `namespace:name`:
+///
+/// - `namespace` is lowercase `[a-z0-9.-]`, conventionally a reverse domain
+///   (`org.example`); the `sm` namespace is reserved for Shield Messenger.
+/// - `name` is lowercase `[a-z0-9._-]`.
+/// - A leading `!` marks the key **critical**: a receiver that does not
+///   understand it must reject the whole message instead of showing it
+///   without the annotation ([`Annotations::check_understood`]). Unmarked
+///   keys a receiver does not know are kept and passed on unchanged.
+///
+/// Values are UTF-8 strings. A message carries at most [`MAX_ANNOTATIONS`]
+/// entries in at most [`MAX_ANNOTATIONS_LEN`] encoded bytes.
+///
+/// Parsing is strict: the section must be canonical (keys in ascending
+/// byte order, no duplicates), every key well formed and the whole section
+/// within bounds, otherwise the envelope is malformed. Two encoders can
+/// therefore never disagree on what a given section means.
+///
+/// ```text
+/// section = [count: u8] { [key len: u8][key][value len: u16 BE][value] }
+/// ```
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AnnotationError {
+    #[error("Invalid annotation key {0:?}")]
+    InvalidKey(String),
+    #[error("Too many annotations: {0}")]
+    TooMany(usize),
+    #[error("Annotations too large: {0} bytes")]
+    TooLarge(usize),
+    #[error("Malformed annotation section")]
+    Malformed,
+    #[error("Annotation section is not canonical")]
+    NotCanonical,
+    #[error("Critical annotation {0} not understood")]
+    UnknownCritical(String),
+}
+
+/// Most entries per message.
+pub const MAX_ANNOTATIONS: usize = 16;
+
+/// Largest encoded section, count byte included.
+pub const MAX_ANNOTATIONS_LEN: usize = 1024;
+
+/// Longest key, including a critical marker.
+pub const MAX_KEY_LEN: usize = 64;
+
+/// Prefix marking a key the receiver must understand.
+pub const CRITICAL_MARKER: char = '!';
+
+/// Namespace reserved for keys defined by this protocol.
+pub const RESERVED_NAMESPACE: &str = "sm";
+
+fn validate_key(key: &str) -> Result<(), AnnotationError> {
+    let invalid = || AnnotationError::InvalidKey(key.to_string());
+    if key.len() > MAX_KEY_LEN {
+        return Err(invalid());
+    }
+    let bare = key.strip_prefix(CRITICAL_MARKER).unwrap_or(key);
+    let (namespace, name) = bare.split_once(':').ok_or_else(invalid)?;
+    let namespace_ok = !namespace.is_empty()
+        && namespace
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-');
+    let name_ok = !name.is_empty()
+        && name.bytes().all(|b| {
+            b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'_' || b == b'-'
+        });
+    if namespace_ok && name_ok {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Annotations of one message, in canonical order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotations(BTreeMap<String, String>);
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `key`, replacing an earlier value. Fails if the key is malformed
+    /// or the section would exceed its bounds.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), AnnotationError> {
+        let key = key.into();
+        validate_key(&key)?;
+        let previous = self.0.insert(key.clone(), value.into());
+        let within = self.check_bounds();
+        if within.is_err() {
+            match previous {
+                Some(value) => self.0.insert(key, value),
+                None => self.0.remove(&key),
+            };
+        }
+        within
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_critical(key: &str) -> bool {
+        key.starts_with(CRITICAL_MARKER)
+    }
+
+    /// Reject the message if it carries a critical key `understood` does
+    /// not accept. Non-critical keys are never a reason to reject.
+    pub fn check_understood(
+        &self,
+        understood: impl Fn(&str) -> bool,
+    ) -> Result<(), AnnotationError> {
+        match self
+            .0
+            .keys()
+            .find(|key| Self::is_critical(key) && !understood(key))
+        {
+            Some(key) => Err(AnnotationError::UnknownCritical(key.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Size of the encoded section.
+    pub fn encoded_len(&self) -> usize {
+        1 + self
+            .0
+            .iter()
+            .map(|(k, v)| 1 + k.len() + 2 + v.len())
+            .sum::<usize>()
+    }
+
+    fn check_bounds(&self) -> Result<(), AnnotationError> {
+        if self.0.len() > MAX_ANNOTATIONS {
+            return Err(AnnotationError::TooMany(self.0.len()));
+        }
+        let len = self.encoded_len();
+        if len > MAX_ANNOTATIONS_LEN {
+            return Err(AnnotationError::TooLarge(len));
+        }
+        Ok(())
+    }
+
+    /// Append the encoded section to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        // insert() keeps every entry within the u8/u16 length fields
+        out.push(self.0.len() as u8);
+        for (key, value) in &self.0 {
+            out.push(key.len() as u8);
+            out.extend_from_slice(key.as_bytes());
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            out.extend_from_slice(value.as_bytes());
+        }
+    }
+
+    /// Parse a section from the front of `data`; returns it with the bytes
+    /// consumed.
+    pub fn decode(data: &[u8]) -> Result<(Self, usize), AnnotationError> {
+        let (&count, mut rest) = data.split_first().ok_or(AnnotationError::Malformed)?;
+        if count as usize > MAX_ANNOTATIONS {
+            return Err(AnnotationError::TooMany(count as usize));
+        }
+        let mut take = |n: usize| -> Result<&[u8], AnnotationError> {
+            if rest.len() < n {
+                return Err(AnnotationError::Malformed);
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+
+        let mut entries = BTreeMap::new();
+        let mut last: Option<String> = None;
+        for _ in 0..count {
+            let key_len = take(1)?[0] as usize;
+            let key = std::str::from_utf8(take(key_len)?)
+                .map_err(|_| AnnotationError::Malformed)?
+                .to_string();
+            let value_len = u16::from_be_bytes(take(2)?.try_into().expect("2 bytes")) as usize;
+            let value = std::str::from_utf8(take(value_len)?)
+                .map_err(|_| AnnotationError::Malformed)?
+                .to_string();
+            validate_key(&key)?;
+            if last.as_ref().is_some_and(|last| *last >= key) {
+                return Err(AnnotationError::NotCanonical);
+            }
+            last = Some(key.clone());
+            entries.insert(key, value);
+        }
+
+        let consumed = data.len() - rest.len();
+        if consumed > MAX_ANNOTATIONS_LEN {
+            return Err(AnnotationError::TooLarge(consumed));
+        }
+        Ok((Self(entries), consumed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_bounds() {
+        let mut annotations = Annotations::new();
+        annotations.insert("org.example:thread-id", "42").unwrap();
+        annotations.insert("!sm:client", "android/1.4").unwrap();
+        assert_eq!(
+            annotations.insert("no-namespace", "x"),
+            Err(AnnotationError::InvalidKey("no-namespace".into()))
+        );
+        assert!(annotations.insert("Org:Upper", "x").is_err());
+        assert!(annotations.insert("org::x", "x").is_err());
+
+        let mut bytes = Vec::new();
+        annotations.encode(&mut bytes);
+        bytes.extend_from_slice(b"body");
+        let (decoded, consumed) = Annotations::decode(&bytes).unwrap();
+        assert_eq!(decoded, annotations);
+        assert_eq!(consumed, annotations.encoded_len());
+        assert_eq!(&bytes[consumed..], b"body");
+
+        // A value that would push the section over the limit is refused
+        // and leaves the set unchanged
+        let big = "v".repeat(MAX_ANNOTATIONS_LEN);
+        assert!(matches!(
+            annotations.insert("org.example:big", big),
+            Err(AnnotationError::TooLarge(_))
+        ));
+        assert_eq!(annotations.len(), 2);
+        for i in 0..MAX_ANNOTATIONS - 2 {
+            annotations
+                .insert(format!("org.example:k{i:02}"), "")
+                .unwrap();
+        }
+        assert_eq!(
+            annotations.insert("org.example:one-more", ""),
+            Err(AnnotationError::TooMany(MAX_ANNOTATIONS + 1))
+        );
+    }
+
+    #[test]
+    fn test_strict_parsing_and_critical_keys() {
+        let entry = |key: &str, value: &str| {
+            let mut out = vec![key.len() as u8];
+            out.extend_from_slice(key.as_bytes());
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            out.extend_from_slice(value.as_bytes());
+            out
+        };
+        let section = |entries: &[Vec<u8>]| {
+            let mut out = vec![entries.len() as u8];
+            entries.iter().for_each(|e| out.extend_from_slice(e));
+            out
+        };
+
+        // Out of order and duplicate keys
+        let unsorted = section(&[entry("b.x:k", "1"), entry("a.x:k", "2")]);
+        assert_eq!(
+            Annotations::decode(&unsorted),
+            Err(AnnotationError::NotCanonical)
+        );
+        let duplicate = section(&[entry("a.x:k", "1"), entry("a.x:k", "2")]);
+        assert_eq!(
+            Annotations::decode(&duplicate),
+            Err(AnnotationError::NotCanonical)
+        );
+        // Truncated value, malformed key
+        let truncated = section(&[entry("a.x:k", "12345")]);
+        assert_eq!(
+            Annotations::decode(&truncated[..truncated.len() - 1]),
+            Err(AnnotationError::Malformed)
+        );
+        assert!(matches!(
+            Annotations::decode(&section(&[entry("A:k", "")])),
+            Err(AnnotationError::InvalidKey(_))
+        ));
+
+        // Unknown plain keys pass; unknown critical keys reject the message
+        let (annotations, _) = Annotations::decode(&section(&[
+            entry("!org.example:must", "1"),
+            entry("org.example:may", "2"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            annotations.check_understood(|k| k == "!org.example:must"),
+            Ok(())
+        );
+        assert_eq!(
+            annotations.check_understood(|_| false),
+            Err(AnnotationError::UnknownCritical("!org.example:must".into()))
+        );
+    }
+}
@@ -0,0 +1,483 @@
+/// One-to-many announcement channels.
+///
+/// An organization or user announcing to all contacts should not encrypt
+/// every announcement once per contact, and does not need the group CRDT:
+/// nobody but the owner writes, and there is nothing to merge. A broadcast
+/// channel works like a sender key:
+///
+/// - The owner creates a [`BroadcastSender`] holding a fresh channel id, a
+///   per-channel Ed25519 signing key and a symmetric chain key.
+/// - Its [`ChannelKey`] is sent to every subscribed contact over the existing
+///   pairwise encrypted session, which authenticates it. Each contact builds
+///   a [`BroadcastReceiver`] from it.
+/// - Each [`Announcement`] is encrypted once with the next key of the hash
+///   chain (`crypto::encryption::derive_message_key` / `evolve_chain_key`),
+///   numbered, timestamped and signed with the channel signing key.
+///
+/// Receivers accept only strictly increasing sequence numbers with
+/// non-decreasing timestamps, so a replayed or reordered announcement is
+/// rejected rather than shown again or out of place. Skipped sequence numbers
+/// are reported and can never be delivered later; the chain key only moves
+/// forward, which also gives forward secrecy across announcements.
+///
+/// Removing a subscriber requires [`BroadcastSender::rotate`] and sending the
+/// new [`ChannelKey`] to the remaining contacts.
+use crate::crypto::encryption::{
+    decrypt_message, derive_message_key, encrypt_message_with_rng, evolve_chain_key,
+};
+use crate::crypto::signing::{generate_keypair_with_rng, sign_data, verify_signature};
+use crate::rng::SecureRng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BroadcastError {
+    #[error("Malformed broadcast data")]
+    Malformed,
+    #[error("Unsupported broadcast version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Announcement belongs to another channel")]
+    WrongChannel,
+    #[error("Invalid announcement signature")]
+    BadSignature,
+    #[error("Replayed or reordered announcement {sequence} (expected at least {expected})")]
+    Replay { sequence: u64, expected: u64 },
+    #[error("Announcement timestamp goes backwards")]
+    TimestampRegression,
+    #[error("Announcement {0} is too far ahead of the channel")]
+    TooFarAhead(u64),
+    #[error("Announcement encryption failed: {0}")]
+    Encryption(String),
+    #[error("Announcement decryption failed")]
+    Decryption,
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Broadcast state encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Wire version of announcements, channel keys and receiver state.
+pub const BROADCAST_VERSION: u8 = 1;
+
+/// Most announcements a receiver will skip to catch up in one step.
+pub const MAX_SKIPPED_ANNOUNCEMENTS: u64 = 1024;
+
+const SIGN_CONTEXT: &[u8] = b"ShieldMessenger-Broadcast-v1";
+
+/// `[version][channel 16][sequence 8][timestamp 8]`
+const HEADER_LEN: usize = 33;
+
+pub type ChannelId = [u8; 16];
+
+/// Sender-key distribution message: everything a subscriber needs to read
+/// announcements from `next_sequence` on. Send only over an authenticated,
+/// encrypted pairwise session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelKey {
+    pub channel_id: ChannelId,
+    pub signing_public: [u8; 32],
+    pub chain_key: [u8; 32],
+    pub next_sequence: u64,
+}
+
+impl Drop for ChannelKey {
+    fn drop(&mut self) {
+        self.chain_key.zeroize();
+    }
+}
+
+impl ChannelKey {
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BroadcastError> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BroadcastError> {
+        decode(data)
+    }
+}
+
+/// A signed, encrypted announcement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub channel_id: ChannelId,
+    pub sequence: u64,
+    /// Sender's clock, seconds.
+    pub timestamp: u64,
+    /// `nonce ‖ XChaCha20-Poly1305 ciphertext`
+    pub ciphertext: Vec<u8>,
+    pub signature: [u8; 64],
+}
+
+impl Announcement {
+    fn header(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[0] = BROADCAST_VERSION;
+        out[1..17].copy_from_slice(&self.channel_id);
+        out[17..25].copy_from_slice(&self.sequence.to_be_bytes());
+        out[25..33].copy_from_slice(&self.timestamp.to_be_bytes());
+        out
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SIGN_CONTEXT.len() + HEADER_LEN + self.ciphertext.len());
+        out.extend_from_slice(SIGN_CONTEXT);
+        out.extend_from_slice(&self.header());
+        out.extend_from_slice(&self.ciphertext);
+        out
+    }
+
+    /// `[version][channel 16][sequence u64 BE][timestamp u64 BE][signature 64][ciphertext]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + 64 + self.ciphertext.len());
+        out.extend_from_slice(&self.header());
+        out.extend_from_slice(&self.signature);
+        out.extend_from_slice(&self.ciphertext);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BroadcastError> {
+        let (&version, _) = data.split_first().ok_or(BroadcastError::Malformed)?;
+        if version != BROADCAST_VERSION {
+            return Err(BroadcastError::UnsupportedVersion(version));
+        }
+        if data.len() < HEADER_LEN + 64 {
+            return Err(BroadcastError::Malformed);
+        }
+        let mut channel_id = [0u8; 16];
+        channel_id.copy_from_slice(&data[1..17]);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&data[HEADER_LEN..HEADER_LEN + 64]);
+        Ok(Self {
+            channel_id,
+            sequence: u64::from_be_bytes(data[17..25].try_into().unwrap()),
+            timestamp: u64::from_be_bytes(data[25..33].try_into().unwrap()),
+            ciphertext: data[HEADER_LEN + 64..].to_vec(),
+            signature,
+        })
+    }
+}
+
+/// The channel owner's side.
+#[derive(Serialize, Deserialize)]
+pub struct BroadcastSender {
+    channel_id: ChannelId,
+    signing_private: [u8; 32],
+    signing_public: [u8; 32],
+    chain_key: [u8; 32],
+    next_sequence: u64,
+}
+
+impl Drop for BroadcastSender {
+    fn drop(&mut self) {
+        self.signing_private.zeroize();
+        self.chain_key.zeroize();
+    }
+}
+
+impl BroadcastSender {
+    /// A new channel with fresh keys.
+    pub fn new(rng: &mut impl SecureRng) -> Self {
+        let mut channel_id = [0u8; 16];
+        rng.fill_bytes(&mut channel_id);
+        let (signing_public, signing_private) = generate_keypair_with_rng(rng);
+        let mut chain_key = [0u8; 32];
+        rng.fill_bytes(&mut chain_key);
+        Self {
+            channel_id,
+            signing_private,
+            signing_public,
+            chain_key,
+            next_sequence: 0,
+        }
+    }
+
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Sequence number the next announcement will carry.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Key for new subscribers. They can read announcements from now on,
+    /// not earlier ones.
+    pub fn channel_key(&self) -> ChannelKey {
+        ChannelKey {
+            channel_id: self.channel_id,
+            signing_public: self.signing_public,
+            chain_key: self.chain_key,
+            next_sequence: self.next_sequence,
+        }
+    }
+
+    /// Encrypt and sign `body` as the next announcement.
+    pub fn announce(
+        &mut self,
+        body: &[u8],
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Announcement, BroadcastError> {
+        let mut message_key = derive_message_key(&self.chain_key)
+            .map_err(|e| BroadcastError::KeyDerivation(e.to_string()))?;
+        let ciphertext = encrypt_message_with_rng(body, &message_key, rng)
+            .map_err(|e| BroadcastError::Encryption(e.to_string()));
+        message_key.zeroize();
+        let mut announcement = Announcement {
+            channel_id: self.channel_id,
+            sequence: self.next_sequence,
+            timestamp: now,
+            ciphertext: ciphertext?,
+            signature: [0u8; 64],
+        };
+        announcement.signature = sign_data(&announcement.signed_bytes(), &self.signing_private)
+            .map_err(|e| BroadcastError::Signing(e.to_string()))?;
+        self.chain_key = evolve_chain_key(&mut self.chain_key)
+            .map_err(|e| BroadcastError::KeyDerivation(e.to_string()))?;
+        self.next_sequence += 1;
+        Ok(announcement)
+    }
+
+    /// Replace all keys, e.g. after removing a subscriber. Returns the new
+    /// key to send to the remaining ones; old receivers stop working.
+    pub fn rotate(&mut self, rng: &mut impl SecureRng) -> ChannelKey {
+        *self = Self::new(rng);
+        self.channel_key()
+    }
+
+    /// `[version][bincode]`, for the app to keep encrypted at rest. Losing
+    /// this state means rotating to a new channel.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BroadcastError> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BroadcastError> {
+        decode(data)
+    }
+}
+
+/// An announcement that passed every check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivered {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub body: Vec<u8>,
+    /// Announcements skipped to reach this one; they are lost for good.
+    pub skipped: u64,
+}
+
+/// A subscriber's side of one channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastReceiver {
+    channel_id: ChannelId,
+    signing_public: [u8; 32],
+    chain_key: [u8; 32],
+    next_sequence: u64,
+    last_timestamp: u64,
+}
+
+impl Drop for BroadcastReceiver {
+    fn drop(&mut self) {
+        self.chain_key.zeroize();
+    }
+}
+
+impl BroadcastReceiver {
+    pub fn new(key: &ChannelKey) -> Self {
+        Self {
+            channel_id: key.channel_id,
+            signing_public: key.signing_public,
+            chain_key: key.chain_key,
+            next_sequence: key.next_sequence,
+            last_timestamp: 0,
+        }
+    }
+
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Lowest sequence number still acceptable.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Verify and decrypt. State only advances if every check passes.
+    pub fn open(&mut self, announcement: &Announcement) -> Result<Delivered, BroadcastError> {
+        if announcement.channel_id != self.channel_id {
+            return Err(BroadcastError::WrongChannel);
+        }
+        match verify_signature(
+            &announcement.signed_bytes(),
+            &announcement.signature,
+            &self.signing_public,
+        ) {
+            Ok(true) => {}
+            _ => return Err(BroadcastError::BadSignature),
+        }
+        if announcement.sequence < self.next_sequence {
+            return Err(BroadcastError::Replay {
+                sequence: announcement.sequence,
+                expected: self.next_sequence,
+            });
+        }
+        if announcement.timestamp < self.last_timestamp {
+            return Err(BroadcastError::TimestampRegression);
+        }
+        let skipped = announcement.sequence - self.next_sequence;
+        if skipped > MAX_SKIPPED_ANNOUNCEMENTS {
+            return Err(BroadcastError::TooFarAhead(announcement.sequence));
+        }
+
+        let evolve = |key: &mut [u8; 32]| {
+            evolve_chain_key(key).map_err(|e| BroadcastError::KeyDerivation(e.to_string()))
+        };
+        let mut chain_key = self.chain_key;
+        for _ in 0..skipped {
+            chain_key = evolve(&mut chain_key)?;
+        }
+        let mut message_key = derive_message_key(&chain_key)
+            .map_err(|e| BroadcastError::KeyDerivation(e.to_string()))?;
+        let body = decrypt_message(&announcement.ciphertext, &message_key);
+        message_key.zeroize();
+        let body = match body {
+            Ok(body) => body,
+            Err(_) => {
+                chain_key.zeroize();
+                return Err(BroadcastError::Decryption);
+            }
+        };
+
+        let next_chain_key = evolve(&mut chain_key)?;
+        self.chain_key.zeroize();
+        self.chain_key = next_chain_key;
+        self.next_sequence = announcement.sequence + 1;
+        self.last_timestamp = announcement.timestamp;
+        Ok(Delivered {
+            sequence: announcement.sequence,
+            timestamp: announcement.timestamp,
+            body,
+            skipped,
+        })
+    }
+
+    /// `[version][bincode]`, for the app to keep encrypted at rest.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BroadcastError> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BroadcastError> {
+        decode(data)
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, BroadcastError> {
+    let body = bincode::serialize(value).map_err(|e| BroadcastError::Encoding(e.to_string()))?;
+    let mut out = Vec::with_capacity(1 + body.len());
+    out.push(BROADCAST_VERSION);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, BroadcastError> {
+    let (&version, body) = data.split_first().ok_or(BroadcastError::Malformed)?;
+    if version != BROADCAST_VERSION {
+        return Err(BroadcastError::UnsupportedVersion(version));
+    }
+    bincode::deserialize(body).map_err(|_| BroadcastError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_announcements_reject_replay_reorder_and_forgery() {
+        let mut rng = seeded(1);
+        let mut sender = BroadcastSender::new(&mut rng);
+        let key = ChannelKey::from_bytes(&sender.channel_key().to_bytes().unwrap()).unwrap();
+        let mut alice = BroadcastReceiver::new(&key);
+        let mut bob = BroadcastReceiver::new(&key);
+
+        let first = sender.announce(b"office closed", NOW, &mut rng).unwrap();
+        let second = sender.announce(b"office open", NOW + 60, &mut rng).unwrap();
+        let wire = Announcement::from_bytes(&first.to_bytes()).unwrap();
+        assert_eq!(wire, first);
+
+        // Both subscribers read the same single ciphertext
+        assert_eq!(alice.open(&wire).unwrap().body, b"office closed");
+        assert_eq!(bob.open(&first).unwrap().body, b"office closed");
+        assert_eq!(
+            alice.open(&first),
+            Err(BroadcastError::Replay {
+                sequence: 0,
+                expected: 1
+            })
+        );
+
+        let mut forged = second.clone();
+        forged.ciphertext[30] ^= 1;
+        assert_eq!(alice.open(&forged), Err(BroadcastError::BadSignature));
+        let mut bumped = second.clone();
+        bumped.sequence += 5;
+        assert_eq!(alice.open(&bumped), Err(BroadcastError::BadSignature));
+        let delivered = alice.open(&second).unwrap();
+        assert_eq!((delivered.sequence, delivered.skipped), (1, 0));
+
+        // A rotated channel locks out old receivers
+        let new_key = sender.rotate(&mut rng);
+        let third = sender
+            .announce(b"new channel", NOW + 120, &mut rng)
+            .unwrap();
+        assert_eq!(alice.open(&third), Err(BroadcastError::WrongChannel));
+        assert_eq!(
+            BroadcastReceiver::new(&new_key).open(&third).unwrap().body,
+            b"new channel"
+        );
+    }
+
+    #[test]
+    fn test_gap_skips_and_late_joiner() {
+        let mut rng = seeded(2);
+        let mut sender = BroadcastSender::new(&mut rng);
+        let mut early = BroadcastReceiver::new(&sender.channel_key());
+        let announcements: Vec<_> = (0..4)
+            .map(|i| {
+                sender
+                    .announce(format!("#{}", i).as_bytes(), NOW + i, &mut rng)
+                    .unwrap()
+            })
+            .collect();
+
+        // Jump ahead: 1 and 2 are skipped and can never arrive afterwards
+        early.open(&announcements[0]).unwrap();
+        let delivered = early.open(&announcements[3]).unwrap();
+        assert_eq!(
+            (delivered.body.as_slice(), delivered.skipped),
+            (&b"#3"[..], 2)
+        );
+        assert!(matches!(
+            early.open(&announcements[1]),
+            Err(BroadcastError::Replay { .. })
+        ));
+
+        // Persisted state survives a restart on both ends
+        let mut restored = BroadcastReceiver::from_bytes(&early.to_bytes().unwrap()).unwrap();
+        let mut sender = BroadcastSender::from_bytes(&sender.to_bytes().unwrap()).unwrap();
+        let next = sender.announce(b"#4", NOW + 10, &mut rng).unwrap();
+        assert_eq!(restored.open(&next).unwrap().body, b"#4");
+
+        // A late joiner cannot read history
+        let mut late = BroadcastReceiver::new(&sender.channel_key());
+        assert!(late.open(&announcements[3]).is_err());
+        let latest = sender.announce(b"#5", NOW + 20, &mut rng).unwrap();
+        assert_eq!(late.open(&latest).unwrap().body, b"#5");
+    }
+}
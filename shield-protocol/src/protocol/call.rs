@@ -0,0 +1,611 @@
+/// Voice/video call signaling.
+///
+/// Call setup is a handful of small messages — offer, ringing, answer,
+/// candidate exchange, reject, hangup — that each side sends over the
+/// existing pairwise session as ordinary encrypted payloads. Everything an
+/// observer could use (signal kind, call id, media, participants, session
+/// description, timestamps) lives inside the ciphertext, and the transport
+/// pads the result to the fixed packet size, so signaling is
+/// indistinguishable from chat traffic.
+///
+/// Candidates name Tor onion services, relays or mesh peers only; a
+/// candidate that is an IP address is refused on both ends, so signaling can
+/// never leak a network location the way WebRTC ICE does.
+///
+/// Group calls use the caller as hub: the offer lists every invited
+/// participant, each one signals with the caller only, and the call stays
+/// up while at least one participant is connected. [`CallSession`] tracks
+/// one call from either side and rejects signals that do not fit its state.
+use super::contact_id::ContactId;
+use super::relay::is_valid_onion_v3;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CallError {
+    #[error("Malformed call signal")]
+    Malformed,
+    #[error("Unsupported call signal version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Signal for another call")]
+    WrongCall,
+    #[error("Unexpected signal from {0}")]
+    UnknownPeer(ContactId),
+    #[error("{signal} not allowed while {state:?}")]
+    InvalidState {
+        signal: &'static str,
+        state: CallState,
+    },
+    #[error("Too many participants ({0})")]
+    TooManyParticipants(usize),
+    #[error("Invalid candidate")]
+    InvalidCandidate,
+    #[error("Call signal encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Signal wire version.
+pub const CALL_SIGNAL_VERSION: u8 = 1;
+
+/// Most participants in one call, caller excluded.
+pub const MAX_CALL_PARTICIPANTS: usize = 8;
+
+/// Largest session description accepted, in bytes.
+pub const MAX_SESSION_DESCRIPTION: usize = 4096;
+
+/// Unanswered calls end after this long.
+pub const DEFAULT_RING_TIMEOUT_SECS: u64 = 60;
+
+pub type CallId = [u8; 16];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MediaKind {
+    Audio,
+    Video,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandidateTransport {
+    /// A `.onion` service of the peer.
+    Onion,
+    /// A relay, by its descriptor key (hex).
+    Relay,
+    /// An AetherNet mesh peer id.
+    Mesh,
+}
+
+/// A way to reach the sender for media.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallCandidate {
+    pub transport: CandidateTransport,
+    pub address: String,
+    pub port: u16,
+    /// Higher is tried first.
+    pub priority: u32,
+}
+
+impl CallCandidate {
+    fn validate(&self) -> Result<(), CallError> {
+        let address = self.address.as_str();
+        let ok = address.len() <= 128
+            && address.parse::<std::net::IpAddr>().is_err()
+            && match self.transport {
+                CandidateTransport::Onion => is_valid_onion_v3(address),
+                CandidateTransport::Relay => {
+                    address.len() == 64 && address.bytes().all(|b| b.is_ascii_hexdigit())
+                }
+                CandidateTransport::Mesh => {
+                    !address.is_empty() && address.bytes().all(|b| b.is_ascii_alphanumeric())
+                }
+            };
+        if ok {
+            Ok(())
+        } else {
+            Err(CallError::InvalidCandidate)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    Declined,
+    Busy,
+    Unsupported,
+}
+
+/// One signaling message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallSignal {
+    Offer {
+        call_id: CallId,
+        media: MediaKind,
+        /// Everyone invited, for the callee's UI; excludes the caller.
+        participants: Vec<ContactId>,
+        session_description: Vec<u8>,
+        sent_at: u64,
+    },
+    Ringing {
+        call_id: CallId,
+    },
+    Answer {
+        call_id: CallId,
+        session_description: Vec<u8>,
+    },
+    Candidate {
+        call_id: CallId,
+        candidate: CallCandidate,
+    },
+    Reject {
+        call_id: CallId,
+        reason: RejectReason,
+    },
+    Hangup {
+        call_id: CallId,
+    },
+}
+
+impl CallSignal {
+    pub fn call_id(&self) -> CallId {
+        match self {
+            CallSignal::Offer { call_id, .. }
+            | CallSignal::Ringing { call_id }
+            | CallSignal::Answer { call_id, .. }
+            | CallSignal::Candidate { call_id, .. }
+            | CallSignal::Reject { call_id, .. }
+            | CallSignal::Hangup { call_id } => *call_id,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CallSignal::Offer { .. } => "offer",
+            CallSignal::Ringing { .. } => "ringing",
+            CallSignal::Answer { .. } => "answer",
+            CallSignal::Candidate { .. } => "candidate",
+            CallSignal::Reject { .. } => "reject",
+            CallSignal::Hangup { .. } => "hangup",
+        }
+    }
+
+    fn validate(&self) -> Result<(), CallError> {
+        match self {
+            CallSignal::Offer {
+                participants,
+                session_description,
+                ..
+            } => {
+                if participants.is_empty() || participants.len() > MAX_CALL_PARTICIPANTS {
+                    return Err(CallError::TooManyParticipants(participants.len()));
+                }
+                if session_description.len() > MAX_SESSION_DESCRIPTION {
+                    return Err(CallError::Malformed);
+                }
+            }
+            CallSignal::Answer {
+                session_description,
+                ..
+            } if session_description.len() > MAX_SESSION_DESCRIPTION => {
+                return Err(CallError::Malformed);
+            }
+            CallSignal::Candidate { candidate, .. } => candidate.validate()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `[version][bincode]`, sent as an ordinary encrypted payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CallError> {
+        let body = bincode::serialize(self).map_err(|e| CallError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(CALL_SIGNAL_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CallError> {
+        let (&version, body) = data.split_first().ok_or(CallError::Malformed)?;
+        if version != CALL_SIGNAL_VERSION {
+            return Err(CallError::UnsupportedVersion(version));
+        }
+        let signal: Self = bincode::deserialize(body).map_err(|_| CallError::Malformed)?;
+        signal.validate()?;
+        Ok(signal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallState {
+    /// Caller: offer sent, nobody has answered.
+    Outgoing,
+    /// Callee: offer received, not yet accepted.
+    Incoming,
+    Connected,
+    Ended(EndReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndReason {
+    /// We hung up (or declined).
+    Local,
+    /// The other side hung up, or every participant left.
+    Remote,
+    Rejected(RejectReason),
+    /// Nobody answered in time.
+    Timeout,
+}
+
+/// Where a participant is in the call (caller's view).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerState {
+    Invited,
+    Ringing,
+    Joined,
+    Left,
+    Rejected(RejectReason),
+}
+
+/// Something the app should react to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallEvent {
+    PeerRinging(ContactId),
+    PeerJoined {
+        peer: ContactId,
+        session_description: Vec<u8>,
+    },
+    Candidate {
+        peer: ContactId,
+        candidate: CallCandidate,
+    },
+    PeerLeft(ContactId),
+    PeerRejected(ContactId, RejectReason),
+    Ended(EndReason),
+}
+
+/// One call, from the caller's or a callee's side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSession {
+    call_id: CallId,
+    media: MediaKind,
+    /// Caller: all participants. Callee: only the caller.
+    peers: BTreeMap<ContactId, PeerState>,
+    participants: Vec<ContactId>,
+    is_caller: bool,
+    state: CallState,
+    started_at: u64,
+    ring_timeout_secs: u64,
+}
+
+impl CallSession {
+    /// Place a call. Returns the session and the offer to send to every
+    /// participant.
+    pub fn start(
+        call_id: CallId,
+        media: MediaKind,
+        participants: Vec<ContactId>,
+        session_description: Vec<u8>,
+        now: u64,
+    ) -> Result<(Self, CallSignal), CallError> {
+        let offer = CallSignal::Offer {
+            call_id,
+            media,
+            participants: participants.clone(),
+            session_description,
+            sent_at: now,
+        };
+        offer.validate()?;
+        let session = Self {
+            call_id,
+            media,
+            peers: participants
+                .iter()
+                .map(|p| (*p, PeerState::Invited))
+                .collect(),
+            participants,
+            is_caller: true,
+            state: CallState::Outgoing,
+            started_at: now,
+            ring_timeout_secs: DEFAULT_RING_TIMEOUT_SECS,
+        };
+        Ok((session, offer))
+    }
+
+    /// Session for an offer received from `caller`, plus the ringing reply.
+    pub fn incoming(
+        caller: ContactId,
+        offer: &CallSignal,
+        now: u64,
+    ) -> Result<(Self, CallSignal), CallError> {
+        offer.validate()?;
+        let CallSignal::Offer {
+            call_id,
+            media,
+            participants,
+            ..
+        } = offer
+        else {
+            return Err(CallError::Malformed);
+        };
+        let session = Self {
+            call_id: *call_id,
+            media: *media,
+            peers: [(caller, PeerState::Joined)].into_iter().collect(),
+            participants: participants.clone(),
+            is_caller: false,
+            state: CallState::Incoming,
+            started_at: now,
+            ring_timeout_secs: DEFAULT_RING_TIMEOUT_SECS,
+        };
+        let ringing = CallSignal::Ringing { call_id: *call_id };
+        Ok((session, ringing))
+    }
+
+    pub fn with_ring_timeout(mut self, secs: u64) -> Self {
+        self.ring_timeout_secs = secs;
+        self
+    }
+
+    pub fn call_id(&self) -> CallId {
+        self.call_id
+    }
+
+    pub fn media(&self) -> MediaKind {
+        self.media
+    }
+
+    pub fn state(&self) -> CallState {
+        self.state
+    }
+
+    pub fn participants(&self) -> &[ContactId] {
+        &self.participants
+    }
+
+    pub fn peer_state(&self, peer: &ContactId) -> Option<PeerState> {
+        self.peers.get(peer).copied()
+    }
+
+    fn invalid(&self, signal: &'static str) -> CallError {
+        CallError::InvalidState {
+            signal,
+            state: self.state,
+        }
+    }
+
+    /// Callee: accept the incoming call.
+    pub fn accept(&mut self, session_description: Vec<u8>) -> Result<CallSignal, CallError> {
+        if self.is_caller || self.state != CallState::Incoming {
+            return Err(self.invalid("answer"));
+        }
+        let answer = CallSignal::Answer {
+            call_id: self.call_id,
+            session_description,
+        };
+        answer.validate()?;
+        self.state = CallState::Connected;
+        Ok(answer)
+    }
+
+    /// Callee: refuse the incoming call.
+    pub fn reject(&mut self, reason: RejectReason) -> Result<CallSignal, CallError> {
+        if self.is_caller || self.state != CallState::Incoming {
+            return Err(self.invalid("reject"));
+        }
+        self.state = CallState::Ended(EndReason::Local);
+        Ok(CallSignal::Reject {
+            call_id: self.call_id,
+            reason,
+        })
+    }
+
+    /// Leave the call. Send the hangup to every peer.
+    pub fn hangup(&mut self) -> Result<CallSignal, CallError> {
+        if matches!(self.state, CallState::Ended(_)) {
+            return Err(self.invalid("hangup"));
+        }
+        self.state = CallState::Ended(EndReason::Local);
+        Ok(CallSignal::Hangup {
+            call_id: self.call_id,
+        })
+    }
+
+    /// Advertise one of our media candidates.
+    pub fn candidate(&self, candidate: CallCandidate) -> Result<CallSignal, CallError> {
+        if matches!(self.state, CallState::Ended(_)) {
+            return Err(self.invalid("candidate"));
+        }
+        candidate.validate()?;
+        Ok(CallSignal::Candidate {
+            call_id: self.call_id,
+            candidate,
+        })
+    }
+
+    /// Apply a signal received from `from`.
+    pub fn handle(&mut self, from: ContactId, signal: CallSignal) -> Result<CallEvent, CallError> {
+        if signal.call_id() != self.call_id {
+            return Err(CallError::WrongCall);
+        }
+        signal.validate()?;
+        let peer = *self.peers.get(&from).ok_or(CallError::UnknownPeer(from))?;
+        let name = signal.name();
+        if matches!(self.state, CallState::Ended(_)) {
+            return Err(self.invalid(name));
+        }
+
+        let event = match (signal, self.is_caller) {
+            (CallSignal::Ringing { .. }, true) if peer == PeerState::Invited => {
+                self.peers.insert(from, PeerState::Ringing);
+                CallEvent::PeerRinging(from)
+            }
+            (
+                CallSignal::Answer {
+                    session_description,
+                    ..
+                },
+                true,
+            ) if matches!(peer, PeerState::Invited | PeerState::Ringing) => {
+                self.peers.insert(from, PeerState::Joined);
+                self.state = CallState::Connected;
+                CallEvent::PeerJoined {
+                    peer: from,
+                    session_description,
+                }
+            }
+            (CallSignal::Reject { reason, .. }, true)
+                if matches!(peer, PeerState::Invited | PeerState::Ringing) =>
+            {
+                self.peers.insert(from, PeerState::Rejected(reason));
+                if self.nobody_left() {
+                    self.state = CallState::Ended(if self.peers.len() == 1 {
+                        EndReason::Rejected(reason)
+                    } else {
+                        EndReason::Remote
+                    });
+                }
+                CallEvent::PeerRejected(from, reason)
+            }
+            (CallSignal::Candidate { candidate, .. }, _) if peer == PeerState::Joined => {
+                CallEvent::Candidate {
+                    peer: from,
+                    candidate,
+                }
+            }
+            (CallSignal::Hangup { .. }, true)
+                if !matches!(peer, PeerState::Left | PeerState::Rejected(_)) =>
+            {
+                self.peers.insert(from, PeerState::Left);
+                if self.nobody_left() {
+                    self.state = CallState::Ended(EndReason::Remote);
+                }
+                CallEvent::PeerLeft(from)
+            }
+            (CallSignal::Hangup { .. }, false) => {
+                self.state = CallState::Ended(EndReason::Remote);
+                CallEvent::Ended(EndReason::Remote)
+            }
+            _ => return Err(self.invalid(name)),
+        };
+        Ok(event)
+    }
+
+    /// Caller side: every participant rejected or left.
+    fn nobody_left(&self) -> bool {
+        self.peers
+            .values()
+            .all(|p| matches!(p, PeerState::Left | PeerState::Rejected(_)))
+    }
+
+    /// End an unanswered call once the ring timeout has passed.
+    pub fn poll(&mut self, now: u64) -> Option<CallEvent> {
+        let ringing = matches!(self.state, CallState::Outgoing | CallState::Incoming);
+        if ringing && now >= self.started_at.saturating_add(self.ring_timeout_secs) {
+            self.state = CallState::Ended(EndReason::Timeout);
+            return Some(CallEvent::Ended(EndReason::Timeout));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn onion_candidate() -> CallCandidate {
+        CallCandidate {
+            transport: CandidateTransport::Onion,
+            address: format!("{}d.onion", "a".repeat(55)),
+            port: 9152,
+            priority: 10,
+        }
+    }
+
+    #[test]
+    fn test_one_to_one_call_flow() {
+        let (alice, bob) = (test_contact(1), test_contact(2));
+        let (mut caller, offer) =
+            CallSession::start([7; 16], MediaKind::Video, vec![bob], b"sdp-a".to_vec(), NOW)
+                .unwrap();
+        let offer = CallSignal::from_bytes(&offer.to_bytes().unwrap()).unwrap();
+
+        let (mut callee, ringing) = CallSession::incoming(alice, &offer, NOW).unwrap();
+        assert_eq!(
+            caller.handle(bob, ringing).unwrap(),
+            CallEvent::PeerRinging(bob)
+        );
+        let answer = callee.accept(b"sdp-b".to_vec()).unwrap();
+        assert_eq!(
+            caller.handle(bob, answer.clone()).unwrap(),
+            CallEvent::PeerJoined {
+                peer: bob,
+                session_description: b"sdp-b".to_vec()
+            }
+        );
+        assert_eq!(caller.state(), CallState::Connected);
+        // A second answer does not fit the state
+        assert!(matches!(
+            caller.handle(bob, answer),
+            Err(CallError::InvalidState { .. })
+        ));
+
+        let candidate = callee.candidate(onion_candidate()).unwrap();
+        assert!(matches!(
+            caller.handle(bob, candidate),
+            Ok(CallEvent::Candidate { .. })
+        ));
+        // A stranger cannot inject signals
+        assert_eq!(
+            caller.handle(test_contact(9), CallSignal::Hangup { call_id: [7; 16] }),
+            Err(CallError::UnknownPeer(test_contact(9)))
+        );
+
+        let hangup = caller.hangup().unwrap();
+        assert_eq!(
+            callee.handle(alice, hangup).unwrap(),
+            CallEvent::Ended(EndReason::Remote)
+        );
+        assert_eq!(callee.state(), CallState::Ended(EndReason::Remote));
+    }
+
+    #[test]
+    fn test_group_call_rejections_timeouts_and_ip_candidates() {
+        let (bob, carol) = (test_contact(2), test_contact(3));
+        let (mut caller, _) =
+            CallSession::start([8; 16], MediaKind::Audio, vec![bob, carol], Vec::new(), NOW)
+                .unwrap();
+        let reject = |reason| CallSignal::Reject {
+            call_id: [8; 16],
+            reason,
+        };
+        caller.handle(bob, reject(RejectReason::Busy)).unwrap();
+        assert_eq!(caller.state(), CallState::Outgoing);
+        caller
+            .handle(carol, reject(RejectReason::Declined))
+            .unwrap();
+        assert_eq!(caller.state(), CallState::Ended(EndReason::Remote));
+        assert_eq!(
+            caller.peer_state(&bob),
+            Some(PeerState::Rejected(RejectReason::Busy))
+        );
+
+        let (unanswered, _) =
+            CallSession::start([9; 16], MediaKind::Audio, vec![bob], Vec::new(), NOW).unwrap();
+        let mut unanswered = unanswered.with_ring_timeout(30);
+        assert_eq!(unanswered.poll(NOW + 29), None);
+        assert_eq!(
+            unanswered.poll(NOW + 30),
+            Some(CallEvent::Ended(EndReason::Timeout))
+        );
+
+        // Candidates never carry IP addresses
+        let (live, _) =
+            CallSession::start([10; 16], MediaKind::Audio, vec![bob], Vec::new(), NOW).unwrap();
+        let mut ip = onion_candidate();
+        ip.address = "10.0.0.1".to_string();
+        assert_eq!(live.candidate(ip), Err(CallError::InvalidCandidate));
+        assert!(live.candidate(onion_candidate()).is_ok());
+    }
+}
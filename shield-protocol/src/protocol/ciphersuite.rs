@@ -0,0 +1,137 @@
+/// Cipher-suite advertisement and downgrade detection.
+///
+/// Every contact card lists, under its signature, the suites its owner
+/// supports. When a session is negotiated the initiator picks the strongest
+/// suite both sides support ([`negotiate`]). The other side checks that
+/// choice against the initiator's signed card with [`check_selection`].
+/// A suite missing from the card, or weaker than the best suite both cards
+/// share, means the negotiation was tampered with: callers raise an alarm
+/// instead of proceeding silently.
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A negotiable combination of key agreement and message encryption.
+///
+/// Variants are declared weakest first, so `Ord` compares strength.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[repr(u16)]
+pub enum CipherSuite {
+    /// X25519 + XChaCha20-Poly1305.
+    Classical = 0x0001,
+    /// X25519 + ML-KEM-1024 hybrid + XChaCha20-Poly1305.
+    HybridPq = 0x0002,
+}
+
+/// Suites this build supports, strongest first.
+pub const SUPPORTED_SUITES: &[CipherSuite] = &[CipherSuite::HybridPq, CipherSuite::Classical];
+
+impl CipherSuite {
+    /// Wire id, as listed in contact cards.
+    pub fn id(self) -> u16 {
+        self as u16
+    }
+
+    /// `None` for ids this build does not know (e.g. from a newer client).
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0x0001 => Some(CipherSuite::Classical),
+            0x0002 => Some(CipherSuite::HybridPq),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherSuite::Classical => "classical",
+            CipherSuite::HybridPq => "hybrid-pq",
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DowngradeError {
+    #[error("No cipher suite in common")]
+    NoCommonSuite,
+    #[error("Selected cipher suite {0:?} is not supported locally")]
+    Unsupported(CipherSuite),
+    #[error("Peer selected {0:?}, which its signed contact card does not advertise")]
+    NotAdvertised(CipherSuite),
+    #[error("Downgrade: {selected:?} selected although both sides support {expected:?}")]
+    Downgrade {
+        selected: CipherSuite,
+        expected: CipherSuite,
+    },
+}
+
+/// Strongest suite in both lists.
+pub fn negotiate(ours: &[CipherSuite], theirs: &[CipherSuite]) -> Option<CipherSuite> {
+    ours.iter().filter(|s| theirs.contains(s)).max().copied()
+}
+
+/// Check a peer's `selected` suite against the suites its card `advertised`.
+pub fn check_selection(
+    ours: &[CipherSuite],
+    advertised: &[CipherSuite],
+    selected: CipherSuite,
+) -> Result<(), DowngradeError> {
+    if !ours.contains(&selected) {
+        return Err(DowngradeError::Unsupported(selected));
+    }
+    if !advertised.contains(&selected) {
+        return Err(DowngradeError::NotAdvertised(selected));
+    }
+    let expected = negotiate(ours, advertised).ok_or(DowngradeError::NoCommonSuite)?;
+    if selected < expected {
+        return Err(DowngradeError::Downgrade { selected, expected });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_picks_strongest_common() {
+        use CipherSuite::*;
+        assert_eq!(
+            negotiate(SUPPORTED_SUITES, &[Classical, HybridPq]),
+            Some(HybridPq)
+        );
+        assert_eq!(negotiate(SUPPORTED_SUITES, &[Classical]), Some(Classical));
+        assert_eq!(negotiate(&[HybridPq], &[Classical]), None);
+        for suite in SUPPORTED_SUITES {
+            assert_eq!(CipherSuite::from_id(suite.id()), Some(*suite));
+        }
+        assert_eq!(CipherSuite::from_id(0xffff), None);
+    }
+
+    #[test]
+    fn test_check_selection_flags_downgrades() {
+        use CipherSuite::*;
+        assert_eq!(
+            check_selection(SUPPORTED_SUITES, &[HybridPq, Classical], HybridPq),
+            Ok(())
+        );
+        // Classical is fine when that is all the peer advertises
+        assert_eq!(
+            check_selection(SUPPORTED_SUITES, &[Classical], Classical),
+            Ok(())
+        );
+        assert_eq!(
+            check_selection(SUPPORTED_SUITES, &[HybridPq, Classical], Classical),
+            Err(DowngradeError::Downgrade {
+                selected: Classical,
+                expected: HybridPq
+            })
+        );
+        assert_eq!(
+            check_selection(SUPPORTED_SUITES, &[Classical], HybridPq),
+            Err(DowngradeError::NotAdvertised(HybridPq))
+        );
+        assert_eq!(
+            check_selection(&[Classical], &[HybridPq, Classical], HybridPq),
+            Err(DowngradeError::Unsupported(HybridPq))
+        );
+    }
+}
@@ -1,5 +1,15 @@
+use super::ciphersuite::{CipherSuite, SUPPORTED_SUITES};
+use super::contact_id::{ContactId, ContactIdError};
+use crate::crypto::pseudonym::{LinkProof, PseudonymKeys};
+use crate::storage::versioned::{
+    decode_layout, Persisted, StateKind, VersionError, LEGACY_VERSION,
+};
 use serde::{Deserialize, Serialize};
 
+/// Appended to the signed bytes of pseudonymous cards, so the flag cannot
+/// be stripped without breaking the signature.
+const PSEUDONYM_MARKER: &[u8] = b"SM-PSEUDONYM";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContactCard {
     pub public_key: Vec<u8>,
@@ -9,6 +19,15 @@ pub struct ContactCard {
     pub relay_preferences: RelayPreferences,
     pub timestamp: i64,
     pub signature: Vec<u8>,
+    /// Supported `CipherSuite` ids, covered by the signature. Empty on cards
+    /// that predate suite advertisement.
+    #[serde(default)]
+    pub cipher_suites: Vec<u16>,
+    /// `public_key` is a conversation-scoped pseudonym (see
+    /// [`crate::crypto::pseudonym`]), not the owner's master identity.
+    /// Covered by the signature.
+    #[serde(default)]
+    pub pseudonymous: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,9 +56,41 @@ impl ContactCard {
             },
             timestamp: Utc::now().timestamp(),
             signature: Vec::new(),
+            cipher_suites: SUPPORTED_SUITES.iter().map(|s| s.id()).collect(),
+            pseudonymous: false,
         }
     }
 
+    /// Card for one pseudonymous conversation. Carries no payment address,
+    /// which would link it to the owner's other cards.
+    pub fn new_pseudonymous(
+        keys: &PseudonymKeys,
+        handle: String,
+        onion_address: Option<String>,
+    ) -> Self {
+        Self {
+            pseudonymous: true,
+            ..Self::new(
+                keys.signing_public.to_vec(),
+                String::new(),
+                handle,
+                onion_address,
+            )
+        }
+    }
+
+    /// The master identity behind a pseudonymous card, if `proof` links it
+    /// and is addressed to `our_public_key`.
+    pub fn linked_identity(
+        &self,
+        proof: &LinkProof,
+        our_public_key: &[u8; 32],
+    ) -> Option<[u8; 32]> {
+        let pseudonym: [u8; 32] = self.public_key.as_slice().try_into().ok()?;
+        (self.pseudonymous && proof.verify(&pseudonym, our_public_key))
+            .then_some(proof.master_public)
+    }
+
     pub fn serialize(&self) -> Result<Vec<u8>, bincode::Error> {
         bincode::serialize(self)
     }
@@ -56,6 +107,25 @@ impl ContactCard {
         serde_json::from_str(json)
     }
 
+    /// Canonical id of the contact this card describes.
+    pub fn contact_id(&self) -> Result<ContactId, ContactIdError> {
+        ContactId::from_identity_key(&self.public_key)
+    }
+
+    /// Suites the card advertises, ignoring ids unknown to this build.
+    /// `None` for legacy cards, which cannot be checked for downgrades.
+    pub fn advertised_suites(&self) -> Option<Vec<CipherSuite>> {
+        if self.cipher_suites.is_empty() {
+            return None;
+        }
+        Some(
+            self.cipher_suites
+                .iter()
+                .filter_map(|id| CipherSuite::from_id(*id))
+                .collect(),
+        )
+    }
+
     pub fn serialize_for_signing(&self) -> Vec<u8> {
         let mut data = Vec::new();
         data.extend_from_slice(&self.public_key);
@@ -65,10 +135,74 @@ impl ContactCard {
             data.extend_from_slice(onion.as_bytes());
         }
         data.extend_from_slice(&self.timestamp.to_le_bytes());
+        // Appended only when present so legacy signatures still verify
+        for id in &self.cipher_suites {
+            data.extend_from_slice(&id.to_be_bytes());
+        }
+        if self.pseudonymous {
+            data.extend_from_slice(PSEUDONYM_MARKER);
+        }
         data
     }
 }
 
+/// Card layout before cipher suite advertisement. Cards stored with
+/// [`ContactCard::serialize`] since then append `cipher_suites`, and later
+/// `pseudonymous`.
+#[derive(Deserialize)]
+struct CardBeforeSuites {
+    public_key: Vec<u8>,
+    solana_address: String,
+    handle: String,
+    onion_address: Option<String>,
+    relay_preferences: RelayPreferences,
+    timestamp: i64,
+    signature: Vec<u8>,
+}
+
+impl CardBeforeSuites {
+    fn into_card(self, cipher_suites: Vec<u16>) -> ContactCard {
+        ContactCard {
+            public_key: self.public_key,
+            solana_address: self.solana_address,
+            handle: self.handle,
+            onion_address: self.onion_address,
+            relay_preferences: self.relay_preferences,
+            timestamp: self.timestamp,
+            signature: self.signature,
+            cipher_suites,
+            pseudonymous: false,
+        }
+    }
+}
+
+impl Persisted for ContactCard {
+    const KIND: StateKind = StateKind::ContactCard;
+    const VERSION: u16 = 1;
+
+    /// Unversioned cards were stored either as JSON (`to_json`, whose serde
+    /// defaults already cover older cards) or as bincode of any of three
+    /// layouts, tried longest first.
+    fn migrate(version: u16, body: &[u8]) -> Result<Self, VersionError> {
+        if version != LEGACY_VERSION {
+            return Err(VersionError::NoMigration {
+                kind: Self::KIND,
+                version,
+            });
+        }
+        if body.first() == Some(&b'{') {
+            return serde_json::from_slice(body)
+                .map_err(|e| VersionError::Malformed(e.to_string()));
+        }
+        decode_layout::<ContactCard>(body)
+            .or_else(|_| {
+                decode_layout::<(CardBeforeSuites, Vec<u16>)>(body)
+                    .map(|(card, suites)| card.into_card(suites))
+            })
+            .or_else(|_| decode_layout::<CardBeforeSuites>(body).map(|c| c.into_card(Vec::new())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +237,87 @@ mod tests {
 
         assert_eq!(card.handle, deserialized.handle);
     }
+
+    #[test]
+    fn test_cipher_suites_are_signed() {
+        let mut card = ContactCard::new(vec![1; 32], "SoL".to_string(), "u".to_string(), None);
+        assert_eq!(card.advertised_suites().unwrap(), SUPPORTED_SUITES);
+        let signed = card.serialize_for_signing();
+
+        // Stripping the strongest suite changes what the signature covers
+        card.cipher_suites
+            .retain(|id| *id != CipherSuite::HybridPq.id());
+        assert_ne!(card.serialize_for_signing(), signed);
+
+        // Legacy JSON without the field still parses and signs as before
+        let mut json: serde_json::Value = serde_json::from_str(&card.to_json().unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("cipher_suites");
+        let legacy = ContactCard::from_json(&json.to_string()).unwrap();
+        assert_eq!(legacy.advertised_suites(), None);
+        assert_eq!(
+            legacy.serialize_for_signing().len(),
+            signed.len() - 2 * SUPPORTED_SUITES.len()
+        );
+    }
+
+    /// Bincode of a card stored before cipher suites were advertised.
+    const CARD_BEFORE_SUITES_FIXTURE: &str = concat!(
+        "0400000000000000010203040300000000000000536f4c03000000000000006f",
+        "6c640109000000000000006162632e6f6e696f6e01000000000000000000f153",
+        "650000000002000000000000000909",
+    );
+
+    #[test]
+    fn test_legacy_cards_migrate() {
+        use crate::storage::versioned::{decode, encode};
+
+        let old = hex::decode(CARD_BEFORE_SUITES_FIXTURE).unwrap();
+        let card: ContactCard = decode(&old).unwrap();
+        assert_eq!(card.public_key, vec![1, 2, 3, 4]);
+        assert_eq!(card.handle, "old");
+        assert_eq!(card.onion_address.as_deref(), Some("abc.onion"));
+        assert_eq!(card.timestamp, 1_700_000_000);
+        assert_eq!(card.signature, vec![9, 9]);
+        assert_eq!(card.advertised_suites(), None);
+        assert!(!card.pseudonymous);
+
+        let json = r#"{"public_key":[1,2,3,4],"solana_address":"SoL","handle":"old","onion_address":null,"relay_preferences":{"accepts_relay_messages":false,"preferred_relays":["r1"]},"timestamp":5,"signature":[]}"#;
+        let card: ContactCard = decode(json.as_bytes()).unwrap();
+        assert_eq!(card.relay_preferences.preferred_relays, vec!["r1"]);
+        assert!(card.cipher_suites.is_empty());
+
+        // Cards stored by `serialize` since suites were added
+        let current = ContactCard::new(vec![7; 32], "SoL".into(), "new".into(), None);
+        let card: ContactCard = decode(&current.serialize().unwrap()).unwrap();
+        assert_eq!(card.cipher_suites, current.cipher_suites);
+
+        let blob = encode(&card).unwrap();
+        let card: ContactCard = decode(&blob).unwrap();
+        assert_eq!(
+            card.serialize_for_signing(),
+            current.serialize_for_signing()
+        );
+    }
+
+    #[test]
+    fn test_pseudonymous_card() {
+        use crate::crypto::pseudonym::{derive_pseudonym, ConversationTag};
+
+        let keys = derive_pseudonym(&[5u8; 64], &ConversationTag([1; 32])).unwrap();
+        let mut card = ContactCard::new_pseudonymous(&keys, "anon".into(), None);
+        assert_eq!(card.public_key, keys.signing_public);
+        assert!(card.solana_address.is_empty());
+
+        // The flag is signed
+        let signed = card.serialize_for_signing();
+        card.pseudonymous = false;
+        assert_ne!(card.serialize_for_signing(), signed);
+        card.pseudonymous = true;
+
+        let (master_public, master_secret) = crate::crypto::signing::generate_keypair();
+        let bob = [0xB0; 32];
+        let proof = keys.link_proof(&master_secret, &bob);
+        assert_eq!(card.linked_identity(&proof, &bob), Some(master_public));
+        assert_eq!(card.linked_identity(&proof, &[0xC0; 32]), None);
+    }
 }
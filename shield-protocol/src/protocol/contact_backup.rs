@@ -0,0 +1,792 @@
+/// End-to-end encrypted contact list backup.
+///
+/// After a reinstall the user has the recovery phrase and nothing else. A
+/// [`ContactSnapshot`] (every contact with its trust record) is sealed under
+/// a key derived from the master seed and stored on store-and-forward
+/// relays, so the contact list comes back with the seed while the relays
+/// only ever hold ciphertext.
+///
+/// [`BackupKeys::derive`] takes everything from the seed: the
+/// XChaCha20-Poly1305 key the snapshot is sealed under, and an Ed25519 key
+/// whose public half names the backup slot and signs every request for it.
+/// The slot key is unrelated to the identity key, so a relay cannot tell
+/// whose contact list it holds.
+///
+/// Snapshots are versioned. A write names the version it replaces and the
+/// relay ([`BackupVault`]) stores it only if that is still the current one;
+/// otherwise it answers with the current blob. The client merges the two
+/// ([`ContactSnapshot::merge`]) and writes again, so two devices backing up
+/// at once lose nothing: each contact keeps its most recently changed
+/// record, and removed contacts stay as tombstones so an older copy cannot
+/// bring them back.
+///
+/// ```text
+/// blob    = [version][nonce: 24][ciphertext of padded]
+/// padded  = [len: 4 BE][versioned ContactSnapshot][zeros to a BACKUP_PAD_BLOCK multiple]
+/// request = [version][bincode(BackupRequest)]
+/// ```
+///
+/// Relays speaking [`CONTACT_BACKUP_PROTOCOL`] accept requests; the backup
+/// is kept on up to [`MAX_BACKUP_REPLICAS`] of them, chosen by
+/// [`replica_relays`] so a reinstalled client finds the same ones.
+///
+/// The snapshot inside the seal carries its own format version
+/// ([`crate::storage::versioned`]), so the contact layout can change
+/// without relays or the outer blob version noticing.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::contact_id::ContactId;
+use super::relay::RelayDescriptor;
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::crypto::pqc::{ContactVerificationRecord, TrustLevel};
+use crate::crypto::signing::{derive_public_key, sign_data, verify_signature};
+use crate::rng::SecureRng;
+use crate::storage::versioned::{self, Persisted, StateKind, VersionError, LEGACY_VERSION};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContactBackupError {
+    #[error("Master seed must be at least 32 bytes")]
+    SeedTooShort,
+    #[error("Malformed contact backup")]
+    Malformed,
+    #[error("Unsupported contact backup version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Contact backup could not be decrypted")]
+    DecryptionFailed,
+    #[error("Contact backup too large: {0} bytes")]
+    TooLarge(usize),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Contact backup encoding failed: {0}")]
+    Encoding(String),
+    #[error("Contact backup state: {0}")]
+    State(VersionError),
+}
+
+pub type Result<T> = std::result::Result<T, ContactBackupError>;
+
+/// Backup blob and request wire version.
+pub const CONTACT_BACKUP_VERSION: u8 = 1;
+
+/// Relay protocol version that adds backup slots.
+pub const CONTACT_BACKUP_PROTOCOL: u16 = 3;
+
+/// Largest sealed backup a relay stores.
+pub const MAX_BACKUP_LEN: usize = 1024 * 1024;
+
+/// Padded plaintext is a multiple of this.
+pub const BACKUP_PAD_BLOCK: usize = 4096;
+
+/// Relays holding a copy of the backup.
+pub const MAX_BACKUP_REPLICAS: usize = 3;
+
+/// Requests whose timestamp is further than this from the relay's clock are
+/// rejected, so a captured request cannot be replayed later.
+pub const MAX_REQUEST_SKEW_SECS: u64 = 5 * 60;
+
+/// Tombstones older than this may be pruned (unix ms).
+pub const TOMBSTONE_TTL_MS: u64 = 180 * 24 * 60 * 60 * 1000;
+
+const SEAL_KEY_CONTEXT: &str = "ShieldMessenger-ContactBackup-Key-v1";
+const SLOT_KEY_CONTEXT: &str = "ShieldMessenger-ContactBackup-Slot-v1";
+const REPLICA_CONTEXT: &str = "ShieldMessenger-ContactBackup-Replica-v1";
+const REQUEST_CONTEXT: &[u8] = b"ShieldMessenger-ContactBackup-Request-v1";
+
+/// Length prefix of the padded plaintext.
+const LEN_PREFIX: usize = 4;
+
+// ---------------------------------------------------------------------------
+// Keys
+// ---------------------------------------------------------------------------
+
+/// Backup keys derived from the master seed. Wiped on drop.
+pub struct BackupKeys {
+    seal: [u8; 32],
+    signing: [u8; 32],
+    /// Slot name on the relay (Ed25519 public key of the signing key)
+    pub slot: [u8; 32],
+}
+
+impl BackupKeys {
+    /// Keys for the master seed (e.g. the 64-byte BIP39 seed).
+    pub fn derive(master_seed: &[u8]) -> Result<Self> {
+        if master_seed.len() < 32 {
+            return Err(ContactBackupError::SeedTooShort);
+        }
+        let seal = blake3::derive_key(SEAL_KEY_CONTEXT, master_seed);
+        let signing = blake3::derive_key(SLOT_KEY_CONTEXT, master_seed);
+        let slot =
+            derive_public_key(&signing).map_err(|e| ContactBackupError::Signing(e.to_string()))?;
+        Ok(Self {
+            seal,
+            signing,
+            slot,
+        })
+    }
+}
+
+impl Drop for BackupKeys {
+    fn drop(&mut self) {
+        self.seal.zeroize();
+        self.signing.zeroize();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Snapshot
+// ---------------------------------------------------------------------------
+
+/// One contact and its trust record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupContact {
+    pub contact_id: ContactId,
+    /// App-defined contact data (name, onion, keys, card), opaque here.
+    /// Empty on tombstones.
+    pub profile: String,
+    /// `TrustLevel` as stored (0 / 1 / 2)
+    pub trust_level: u8,
+    /// Unix ms of verification, 0 if never verified
+    pub verified_at: i64,
+    pub safety_number: String,
+    /// Unix ms of the last change; the newest record wins a merge
+    pub updated_at: u64,
+    pub deleted: bool,
+}
+
+impl BackupContact {
+    pub fn new(record: &ContactVerificationRecord, profile: String, updated_at: u64) -> Self {
+        Self {
+            contact_id: record.contact_id,
+            profile,
+            trust_level: record.trust_level as u8,
+            verified_at: record.verified_at,
+            safety_number: record.safety_number.clone(),
+            updated_at,
+            deleted: false,
+        }
+    }
+
+    pub fn trust_record(&self) -> ContactVerificationRecord {
+        ContactVerificationRecord {
+            contact_id: self.contact_id,
+            trust_level: TrustLevel::from_u8(self.trust_level),
+            verified_at: self.verified_at,
+            safety_number: self.safety_number.clone(),
+        }
+    }
+
+    /// Total order used by merges: newest change first, then a removal over
+    /// an edit made in the same millisecond, then the remaining fields so
+    /// every device picks the same record.
+    fn supersedes(&self, other: &Self) -> bool {
+        fn key(c: &BackupContact) -> (u64, bool, u8, i64, &str, &str) {
+            (
+                c.updated_at,
+                c.deleted,
+                c.trust_level,
+                c.verified_at,
+                &c.safety_number,
+                &c.profile,
+            )
+        }
+        key(self) > key(other)
+    }
+}
+
+/// The contact list as backed up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactSnapshot {
+    /// Version of the stored copy this snapshot came from or became
+    pub version: u64,
+    /// One record per contact, sorted by id, tombstones included
+    pub contacts: Vec<BackupContact>,
+}
+
+impl ContactSnapshot {
+    /// Snapshot of `contacts`; duplicates resolve as in a merge.
+    pub fn new(contacts: Vec<BackupContact>) -> Self {
+        let mut snapshot = Self::default();
+        for contact in contacts {
+            snapshot.upsert(contact);
+        }
+        snapshot
+    }
+
+    /// Insert or replace a record, unless the held one is newer.
+    pub fn upsert(&mut self, contact: BackupContact) {
+        match self
+            .contacts
+            .binary_search_by(|c| c.contact_id.cmp(&contact.contact_id))
+        {
+            Ok(i) => {
+                if contact.supersedes(&self.contacts[i]) {
+                    self.contacts[i] = contact;
+                }
+            }
+            Err(i) => self.contacts.insert(i, contact),
+        }
+    }
+
+    /// Replace the contact with a tombstone.
+    pub fn remove(&mut self, contact_id: &ContactId, now_ms: u64) {
+        self.upsert(BackupContact {
+            contact_id: *contact_id,
+            profile: String::new(),
+            trust_level: TrustLevel::Untrusted as u8,
+            verified_at: 0,
+            safety_number: String::new(),
+            updated_at: now_ms,
+            deleted: true,
+        });
+    }
+
+    /// Contacts that were not removed.
+    pub fn live(&self) -> impl Iterator<Item = &BackupContact> {
+        self.contacts.iter().filter(|c| !c.deleted)
+    }
+
+    /// Union of both, keeping the winning record per contact. Commutative,
+    /// so devices merging in different orders converge.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut merged: BTreeMap<ContactId, BackupContact> = self
+            .contacts
+            .iter()
+            .map(|c| (c.contact_id, c.clone()))
+            .collect();
+        for contact in &other.contacts {
+            match merged.get(&contact.contact_id) {
+                Some(held) if !contact.supersedes(held) => {}
+                _ => {
+                    merged.insert(contact.contact_id, contact.clone());
+                }
+            }
+        }
+        Self {
+            version: self.version.max(other.version),
+            contacts: merged.into_values().collect(),
+        }
+    }
+
+    /// Drop tombstones older than [`TOMBSTONE_TTL_MS`]; returns how many.
+    pub fn prune_tombstones(&mut self, now_ms: u64) -> usize {
+        let before = self.contacts.len();
+        self.contacts
+            .retain(|c| !c.deleted || now_ms.saturating_sub(c.updated_at) < TOMBSTONE_TTL_MS);
+        before - self.contacts.len()
+    }
+
+    /// Seal for upload.
+    pub fn seal(&self, keys: &BackupKeys, rng: &mut impl SecureRng) -> Result<Vec<u8>> {
+        let body =
+            versioned::encode(self).map_err(|e| ContactBackupError::Encoding(e.to_string()))?;
+        let padded_len = (LEN_PREFIX + body.len()).div_ceil(BACKUP_PAD_BLOCK) * BACKUP_PAD_BLOCK;
+        let mut padded = Vec::with_capacity(padded_len);
+        padded.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        padded.extend_from_slice(&body);
+        padded.resize(padded_len, 0);
+        let mut body = body;
+        body.zeroize();
+
+        let sealed = encrypt_message_with_rng(&padded, &keys.seal, rng)
+            .map_err(|e| ContactBackupError::Encoding(e.to_string()));
+        padded.zeroize();
+        let mut out = vec![CONTACT_BACKUP_VERSION];
+        out.extend_from_slice(&sealed?);
+        if out.len() > MAX_BACKUP_LEN {
+            return Err(ContactBackupError::TooLarge(out.len()));
+        }
+        Ok(out)
+    }
+
+    /// Decrypt a stored blob.
+    pub fn open(blob: &[u8], keys: &BackupKeys) -> Result<Self> {
+        if blob.len() > MAX_BACKUP_LEN {
+            return Err(ContactBackupError::TooLarge(blob.len()));
+        }
+        let (&version, sealed) = blob.split_first().ok_or(ContactBackupError::Malformed)?;
+        if version != CONTACT_BACKUP_VERSION {
+            return Err(ContactBackupError::UnsupportedVersion(version));
+        }
+        let mut padded = decrypt_message(sealed, &keys.seal)
+            .map_err(|_| ContactBackupError::DecryptionFailed)?;
+        let snapshot = unpad(&padded).and_then(|body| {
+            versioned::decode::<Self>(body).map_err(|e| match e {
+                VersionError::Newer { .. } => ContactBackupError::State(e),
+                _ => ContactBackupError::Malformed,
+            })
+        });
+        padded.zeroize();
+        // Re-insert so a crafted blob cannot smuggle in duplicates or disorder
+        snapshot.map(|s| Self {
+            version: s.version,
+            ..Self::new(s.contacts)
+        })
+    }
+}
+
+/// Backups sealed before the snapshot was versioned hold plain bincode of
+/// the same layout.
+impl Persisted for ContactSnapshot {
+    const KIND: StateKind = StateKind::ContactSnapshot;
+    const VERSION: u16 = 1;
+
+    fn migrate(version: u16, body: &[u8]) -> std::result::Result<Self, VersionError> {
+        match version {
+            LEGACY_VERSION => versioned::decode_layout(body),
+            _ => Err(VersionError::NoMigration {
+                kind: Self::KIND,
+                version,
+            }),
+        }
+    }
+}
+
+fn unpad(padded: &[u8]) -> Result<&[u8]> {
+    if padded.len() < LEN_PREFIX {
+        return Err(ContactBackupError::Malformed);
+    }
+    let mut len = [0u8; LEN_PREFIX];
+    len.copy_from_slice(&padded[..LEN_PREFIX]);
+    padded[LEN_PREFIX..]
+        .get(..u32::from_be_bytes(len) as usize)
+        .ok_or(ContactBackupError::Malformed)
+}
+
+// ---------------------------------------------------------------------------
+// Relay protocol
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupOp {
+    Get,
+    /// Store `blob` as `version`, replacing `base_version` (0 = empty slot)
+    Put {
+        base_version: u64,
+        version: u64,
+        blob: Vec<u8>,
+    },
+    /// Clear the slot if it is still at `base_version`
+    Delete {
+        base_version: u64,
+    },
+}
+
+/// A signed request for one backup slot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupRequest {
+    pub slot: [u8; 32],
+    pub timestamp: u64,
+    pub op: BackupOp,
+    /// Ed25519 over the context, slot, timestamp and op, by the slot key
+    pub signature: Vec<u8>,
+}
+
+impl BackupRequest {
+    pub fn new(keys: &BackupKeys, op: BackupOp, now: u64) -> Result<Self> {
+        let data = request_signed_bytes(&keys.slot, now, &op)?;
+        let signature = sign_data(&data, &keys.signing)
+            .map_err(|e| ContactBackupError::Signing(e.to_string()))?;
+        Ok(Self {
+            slot: keys.slot,
+            timestamp: now,
+            op,
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Relay-side check of signature and freshness.
+    pub fn verify(&self, now: u64) -> std::result::Result<(), BackupRejection> {
+        if self.timestamp.abs_diff(now) > MAX_REQUEST_SKEW_SECS {
+            return Err(BackupRejection::Stale);
+        }
+        let data = request_signed_bytes(&self.slot, self.timestamp, &self.op)
+            .map_err(|_| BackupRejection::Malformed)?;
+        match verify_signature(&data, &self.signature, &self.slot) {
+            Ok(true) => Ok(()),
+            _ => Err(BackupRejection::BadSignature),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        decode(data)
+    }
+}
+
+fn request_signed_bytes(slot: &[u8; 32], timestamp: u64, op: &BackupOp) -> Result<Vec<u8>> {
+    let op = bincode::serialize(op).map_err(|e| ContactBackupError::Encoding(e.to_string()))?;
+    let mut data = Vec::with_capacity(REQUEST_CONTEXT.len() + 1 + 32 + 8 + op.len());
+    data.extend_from_slice(REQUEST_CONTEXT);
+    data.push(CONTACT_BACKUP_VERSION);
+    data.extend_from_slice(slot);
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(&op);
+    Ok(data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupRejection {
+    Malformed,
+    BadSignature,
+    Stale,
+    TooLarge,
+    /// `version` of a put is not above its `base_version`
+    VersionNotNewer,
+    /// The relay holds no more slots
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupResponse {
+    /// Write applied; the slot is now at `version` (0 after a delete)
+    Stored {
+        version: u64,
+    },
+    /// What the slot holds: the answer to a get, or to a write whose base
+    /// version is no longer current
+    Current {
+        version: u64,
+        blob: Vec<u8>,
+    },
+    /// The slot is empty (a get, or a write based on a version since deleted)
+    Empty,
+    Rejected(BackupRejection),
+}
+
+impl BackupResponse {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        decode(data)
+    }
+}
+
+fn encode(value: &impl Serialize) -> Result<Vec<u8>> {
+    let body =
+        bincode::serialize(value).map_err(|e| ContactBackupError::Encoding(e.to_string()))?;
+    let mut out = Vec::with_capacity(1 + body.len());
+    out.push(CONTACT_BACKUP_VERSION);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T> {
+    let (&version, body) = data.split_first().ok_or(ContactBackupError::Malformed)?;
+    if version != CONTACT_BACKUP_VERSION {
+        return Err(ContactBackupError::UnsupportedVersion(version));
+    }
+    if body.len() > MAX_BACKUP_LEN + 1024 {
+        return Err(ContactBackupError::TooLarge(body.len()));
+    }
+    bincode::deserialize(body).map_err(|_| ContactBackupError::Malformed)
+}
+
+// ---------------------------------------------------------------------------
+// Relay side
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+struct StoredBackup {
+    version: u64,
+    blob: Vec<u8>,
+    written_at: u64,
+}
+
+/// Backup slots held by a relay. Blobs are opaque; only versions are
+/// compared.
+#[derive(Debug)]
+pub struct BackupVault {
+    slots: HashMap<[u8; 32], StoredBackup>,
+    max_slots: usize,
+}
+
+impl BackupVault {
+    pub fn new(max_slots: usize) -> Self {
+        Self {
+            slots: HashMap::new(),
+            max_slots,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Answer one request. Writes are compare-and-swap on the version.
+    pub fn handle(&mut self, request: &BackupRequest, now: u64) -> BackupResponse {
+        if let Err(rejection) = request.verify(now) {
+            return BackupResponse::Rejected(rejection);
+        }
+        let current = self.slots.get(&request.slot);
+        let current_version = current.map_or(0, |s| s.version);
+        let conflict = || match current {
+            Some(stored) => BackupResponse::Current {
+                version: stored.version,
+                blob: stored.blob.clone(),
+            },
+            None => BackupResponse::Empty,
+        };
+        match &request.op {
+            BackupOp::Get => conflict(),
+            BackupOp::Put {
+                base_version,
+                version,
+                blob,
+            } => {
+                if version <= base_version {
+                    return BackupResponse::Rejected(BackupRejection::VersionNotNewer);
+                }
+                if blob.len() > MAX_BACKUP_LEN {
+                    return BackupResponse::Rejected(BackupRejection::TooLarge);
+                }
+                if *base_version != current_version {
+                    return conflict();
+                }
+                if current.is_none() && self.slots.len() >= self.max_slots {
+                    return BackupResponse::Rejected(BackupRejection::Full);
+                }
+                self.slots.insert(
+                    request.slot,
+                    StoredBackup {
+                        version: *version,
+                        blob: blob.clone(),
+                        written_at: now,
+                    },
+                );
+                BackupResponse::Stored { version: *version }
+            }
+            BackupOp::Delete { base_version } => {
+                if *base_version != current_version {
+                    return conflict();
+                }
+                self.slots.remove(&request.slot);
+                BackupResponse::Stored { version: 0 }
+            }
+        }
+    }
+
+    /// Drop slots not written since `cutoff` (the relay's retention);
+    /// returns how many.
+    pub fn prune(&mut self, cutoff: u64) -> usize {
+        let before = self.slots.len();
+        self.slots.retain(|_, s| s.written_at >= cutoff);
+        before - self.slots.len()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Replica choice
+// ---------------------------------------------------------------------------
+
+/// The relays that hold the backup for `slot`: of those speaking
+/// [`CONTACT_BACKUP_PROTOCOL`], the [`MAX_BACKUP_REPLICAS`] ranking highest
+/// by rendezvous hash. The same relay set always gives the same choice,
+/// and a relay joining or leaving moves at most one replica.
+pub fn replica_relays<'a>(
+    slot: &[u8; 32],
+    relays: impl IntoIterator<Item = &'a RelayDescriptor>,
+) -> Vec<&'a RelayDescriptor> {
+    let mut ranked: Vec<([u8; 32], &RelayDescriptor)> = relays
+        .into_iter()
+        .filter(|r| r.info.protocol_versions.contains(&CONTACT_BACKUP_PROTOCOL))
+        .map(|r| {
+            let mut material = [0u8; 64];
+            material[..32].copy_from_slice(slot);
+            material[32..].copy_from_slice(&r.info.relay_key);
+            (blake3::derive_key(REPLICA_CONTEXT, &material), r)
+        })
+        .collect();
+    ranked.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    ranked
+        .into_iter()
+        .take(MAX_BACKUP_REPLICAS)
+        .map(|(_, r)| r)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn contact(n: u8, trust: TrustLevel, updated_at: u64) -> BackupContact {
+        let record = ContactVerificationRecord {
+            contact_id: test_contact(n),
+            trust_level: trust,
+            verified_at: 0,
+            safety_number: String::new(),
+        };
+        BackupContact::new(
+            &record,
+            format!("{{\"name\":\"contact {}\"}}", n),
+            updated_at,
+        )
+    }
+
+    #[test]
+    fn test_sealed_snapshot_roundtrip_and_merge() {
+        let mut rng = seeded(1);
+        let keys = BackupKeys::derive(&[7u8; 64]).unwrap();
+        let snapshot = ContactSnapshot {
+            version: 4,
+            ..ContactSnapshot::new(vec![
+                contact(2, TrustLevel::Verified, 100),
+                contact(1, TrustLevel::Encrypted, 100),
+            ])
+        };
+        let blob = snapshot.seal(&keys, &mut rng).unwrap();
+        assert_eq!((blob.len() - 1 - 24 - 16) % BACKUP_PAD_BLOCK, 0);
+        assert_eq!(ContactSnapshot::open(&blob, &keys).unwrap(), snapshot);
+
+        // Another seed cannot open it, nor does it share the slot
+        let other = BackupKeys::derive(&[8u8; 64]).unwrap();
+        assert_ne!(other.slot, keys.slot);
+        assert_eq!(
+            ContactSnapshot::open(&blob, &other),
+            Err(ContactBackupError::DecryptionFailed)
+        );
+        assert_eq!(
+            BackupKeys::derive(&[0u8; 16]).err(),
+            Some(ContactBackupError::SeedTooShort)
+        );
+
+        // Device A verifies contact 1 and removes contact 2; device B adds
+        // contact 3 and edits contact 2 earlier than A removed it
+        let mut a = snapshot.clone();
+        a.upsert(contact(1, TrustLevel::Verified, 200));
+        a.remove(&contact(2, TrustLevel::Verified, 0).contact_id, 300);
+        let mut b = snapshot.clone();
+        b.upsert(contact(3, TrustLevel::Encrypted, 250));
+        b.upsert(contact(2, TrustLevel::Encrypted, 250));
+
+        let merged = a.merge(&b);
+        assert_eq!(merged, b.merge(&a));
+        assert_eq!(merged.version, 4);
+        let live: Vec<_> = merged.live().map(|c| c.trust_record()).collect();
+        assert_eq!(live.len(), 2);
+        assert!(live.iter().any(|r| r.contact_id
+            == contact(1, TrustLevel::Untrusted, 0).contact_id
+            && r.trust_level == TrustLevel::Verified));
+        // An older edit does not resurrect a removed contact
+        assert!(!merged
+            .live()
+            .any(|c| c.contact_id == contact(2, TrustLevel::Untrusted, 0).contact_id));
+
+        let mut pruned = merged.clone();
+        assert_eq!(pruned.prune_tombstones(300 + TOMBSTONE_TTL_MS), 1);
+        assert_eq!(pruned.contacts.len(), 2);
+    }
+
+    #[test]
+    fn test_opens_backups_sealed_before_versioning() {
+        let mut rng = seeded(2);
+        let keys = BackupKeys::derive(&[7u8; 64]).unwrap();
+        let snapshot = ContactSnapshot {
+            version: 9,
+            ..ContactSnapshot::new(vec![contact(5, TrustLevel::Verified, 100)])
+        };
+
+        // Sealed the way earlier builds did: bare bincode inside the padding
+        let seal_body = |body: &[u8], rng: &mut _| {
+            let mut padded = (body.len() as u32).to_be_bytes().to_vec();
+            padded.extend_from_slice(body);
+            padded.resize(BACKUP_PAD_BLOCK, 0);
+            let mut blob = vec![CONTACT_BACKUP_VERSION];
+            blob.extend(encrypt_message_with_rng(&padded, &keys.seal, rng).unwrap());
+            blob
+        };
+        let legacy = seal_body(&bincode::serialize(&snapshot).unwrap(), &mut rng);
+        assert_eq!(ContactSnapshot::open(&legacy, &keys).unwrap(), snapshot);
+
+        // A snapshot layout from a newer build is refused, not misread
+        let mut newer = versioned::encode(&snapshot).unwrap();
+        newer[6] = 2;
+        assert!(matches!(
+            ContactSnapshot::open(&seal_body(&newer, &mut rng), &keys),
+            Err(ContactBackupError::State(VersionError::Newer {
+                version: 2,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_vault_writes_are_compare_and_swap() {
+        let keys = BackupKeys::derive(&[9u8; 32]).unwrap();
+        let mut vault = BackupVault::new(10);
+        let request = |op| {
+            let bytes = BackupRequest::new(&keys, op, NOW)
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+            BackupRequest::from_bytes(&bytes).unwrap()
+        };
+        let put = |base_version, version, blob: &[u8]| BackupOp::Put {
+            base_version,
+            version,
+            blob: blob.to_vec(),
+        };
+
+        assert_eq!(
+            vault.handle(&request(BackupOp::Get), NOW),
+            BackupResponse::Empty
+        );
+        assert_eq!(
+            vault.handle(&request(put(0, 1, b"first")), NOW),
+            BackupResponse::Stored { version: 1 }
+        );
+        // A second device still based on the empty slot gets the current copy
+        assert_eq!(
+            vault.handle(&request(put(0, 1, b"other")), NOW),
+            BackupResponse::Current {
+                version: 1,
+                blob: b"first".to_vec()
+            }
+        );
+        assert_eq!(
+            vault.handle(&request(put(1, 1, b"same")), NOW),
+            BackupResponse::Rejected(BackupRejection::VersionNotNewer)
+        );
+        assert_eq!(
+            vault.handle(&request(put(1, 2, b"merged")), NOW),
+            BackupResponse::Stored { version: 2 }
+        );
+
+        // Forged, tampered and replayed requests are refused
+        let mut tampered = request(put(2, 3, b"evil"));
+        tampered.op = put(2, 3, b"worse");
+        assert_eq!(
+            vault.handle(&tampered, NOW),
+            BackupResponse::Rejected(BackupRejection::BadSignature)
+        );
+        assert_eq!(
+            vault.handle(&request(BackupOp::Get), NOW + MAX_REQUEST_SKEW_SECS + 1),
+            BackupResponse::Rejected(BackupRejection::Stale)
+        );
+
+        let response = vault.handle(&request(BackupOp::Delete { base_version: 2 }), NOW);
+        let bytes = response.to_bytes().unwrap();
+        assert_eq!(
+            BackupResponse::from_bytes(&bytes).unwrap(),
+            BackupResponse::Stored { version: 0 }
+        );
+        assert!(vault.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+/// Canonical contact identifier.
+///
+/// Contacts used to be keyed by whatever was at hand — Ed25519 key, X25519
+/// key or onion address — so the same peer could end up under two keys in
+/// different maps. A `ContactId` is derived only from the contact's Ed25519
+/// identity key, so it is bound to that one key; a contact whose identity key
+/// changes gets a new `ContactId`:
+///
+/// `id = BLAKE3-derive-key("ShieldMessenger-ContactId-v1", ed25519_pubkey)[..20]`
+///
+/// The string form is `sl_` followed by 40 lowercase hex characters. Parsing
+/// is strict (no uppercase, no whitespace), so every contact has exactly one
+/// valid string and string keys cannot silently diverge.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContactIdError {
+    #[error("Identity key must be 32 bytes, got {0}")]
+    InvalidKeyLength(usize),
+    #[error("Malformed contact id")]
+    InvalidFormat,
+}
+
+/// Prefix of the string form.
+pub const CONTACT_ID_PREFIX: &str = "sl_";
+/// Length of the binary id in bytes.
+pub const CONTACT_ID_LEN: usize = 20;
+
+const CONTACT_ID_CONTEXT: &str = "ShieldMessenger-ContactId-v1";
+
+/// Canonical contact identifier (see module docs).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ContactId([u8; CONTACT_ID_LEN]);
+
+impl ContactId {
+    /// Derive the id from a contact's Ed25519 identity public key.
+    pub fn from_identity_key(ed25519_public_key: &[u8]) -> Result<Self, ContactIdError> {
+        if ed25519_public_key.len() != 32 {
+            return Err(ContactIdError::InvalidKeyLength(ed25519_public_key.len()));
+        }
+        let digest = blake3::derive_key(CONTACT_ID_CONTEXT, ed25519_public_key);
+        let mut id = [0u8; CONTACT_ID_LEN];
+        id.copy_from_slice(&digest[..CONTACT_ID_LEN]);
+        Ok(Self(id))
+    }
+
+    /// Parse the canonical string form (`sl_` + 40 lowercase hex).
+    pub fn parse(s: &str) -> Result<Self, ContactIdError> {
+        let hex_part = s
+            .strip_prefix(CONTACT_ID_PREFIX)
+            .ok_or(ContactIdError::InvalidFormat)?;
+        if hex_part.len() != CONTACT_ID_LEN * 2
+            || !hex_part
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        {
+            return Err(ContactIdError::InvalidFormat);
+        }
+        let bytes = hex::decode(hex_part).map_err(|_| ContactIdError::InvalidFormat)?;
+        let mut id = [0u8; CONTACT_ID_LEN];
+        id.copy_from_slice(&bytes);
+        Ok(Self(id))
+    }
+
+    /// Whether `ed25519_public_key` is the identity key this id was derived from.
+    pub fn matches_identity_key(&self, ed25519_public_key: &[u8]) -> bool {
+        use subtle::ConstantTimeEq;
+        Self::from_identity_key(ed25519_public_key)
+            .map(|other| bool::from(self.0.ct_eq(&other.0)))
+            .unwrap_or(false)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; CONTACT_ID_LEN] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ContactId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", CONTACT_ID_PREFIX, hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for ContactId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ContactId({})", self)
+    }
+}
+
+impl FromStr for ContactId {
+    type Err = ContactIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for ContactId {
+    type Error = ContactIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<ContactId> for String {
+    fn from(id: ContactId) -> Self {
+        id.to_string()
+    }
+}
+
+/// Test fixture shared across the crate: the id of identity key `[seed; 32]`.
+#[cfg(test)]
+pub(crate) fn test_contact(seed: u8) -> ContactId {
+    ContactId::from_identity_key(&[seed; 32]).expect("32-byte key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivation_and_round_trip() {
+        let key = [7u8; 32];
+        let id = ContactId::from_identity_key(&key).unwrap();
+        assert_eq!(id, ContactId::from_identity_key(&key).unwrap());
+        assert_ne!(id, ContactId::from_identity_key(&[8u8; 32]).unwrap());
+        assert!(id.matches_identity_key(&key));
+        assert!(!id.matches_identity_key(&[8u8; 32]));
+
+        let s = id.to_string();
+        assert!(s.starts_with(CONTACT_ID_PREFIX));
+        assert_eq!(s.len(), CONTACT_ID_PREFIX.len() + CONTACT_ID_LEN * 2);
+        assert_eq!(s.parse::<ContactId>().unwrap(), id);
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", s));
+        assert_eq!(serde_json::from_str::<ContactId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        let id = ContactId::from_identity_key(&[1u8; 32])
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            ContactId::from_identity_key(&[1u8; 31]),
+            Err(ContactIdError::InvalidKeyLength(31))
+        );
+        for bad in [
+            id.to_uppercase(),
+            id.trim_start_matches(CONTACT_ID_PREFIX).to_string(),
+            format!("{} ", id),
+            id[..id.len() - 2].to_string(),
+            "sl_test123".to_string(),
+        ] {
+            assert_eq!(ContactId::parse(&bad), Err(ContactIdError::InvalidFormat));
+        }
+    }
+}
@@ -0,0 +1,310 @@
+/// Signed proof of delivery.
+///
+/// An [`AckBatch`] tells the sender a message arrived, but nothing stops a
+/// recipient from later denying it. Where a conversation calls for
+/// non-repudiable evidence, the recipient's device additionally signs a
+/// [`DeliveryProof`] for each message: its Ed25519 device key over the
+/// BLAKE3 digest of the ciphertext as received, the sequence number, the
+/// time of receipt and the sender's [`ContactId`]. The proofs travel back
+/// with the ACK batch in a [`ProvedAck`].
+///
+/// The proof commits only to the ciphertext digest, so it can be shown to a
+/// third party without revealing the content; the sender proves what was
+/// delivered by producing the matching ciphertext. Binding the sender's id
+/// stops a proof from being replayed as delivery of someone else's message.
+///
+/// Proofs are off by default and enabled per conversation with a
+/// [`DeliveryProofPolicy`]; a recipient only signs for conversations where
+/// its own policy allows it.
+use super::contact_id::ContactId;
+use super::receipts::AckBatch;
+use crate::crypto::signing::{sign_data, verify_signature};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DeliveryProofError {
+    #[error("Malformed delivery proof")]
+    Malformed,
+    #[error("Unsupported delivery proof version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Invalid delivery proof signature")]
+    InvalidSignature,
+    #[error("Proof does not match the sent ciphertext")]
+    DigestMismatch,
+    #[error("Proof is for sequence {0}, not covered by the ACK")]
+    NotAcknowledged(u64),
+    #[error("Too many proofs in one ACK ({0})")]
+    TooManyProofs(usize),
+}
+
+/// Delivery proof wire version.
+pub const DELIVERY_PROOF_VERSION: u8 = 1;
+
+/// Most proofs carried with one ACK batch.
+pub const MAX_PROOFS_PER_ACK: usize = 16;
+
+const SIGN_CONTEXT: &[u8] = b"ShieldMessenger-DeliveryProof-v1";
+
+/// `[version][seq: u64 BE][received_at: u64 BE][digest: 32][signature: 64]`
+pub const DELIVERY_PROOF_LEN: usize = 1 + 8 + 8 + 32 + 64;
+
+/// Recipient's signed statement that message `seq` arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryProof {
+    pub seq: u64,
+    pub received_at: u64,
+    /// BLAKE3 of the ciphertext as received.
+    pub ciphertext_digest: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl DeliveryProof {
+    fn signed_bytes(sender: &ContactId, seq: u64, received_at: u64, digest: &[u8; 32]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SIGN_CONTEXT.len() + 20 + 8 + 8 + 32);
+        out.extend_from_slice(SIGN_CONTEXT);
+        out.extend_from_slice(sender.as_bytes());
+        out.extend_from_slice(&seq.to_be_bytes());
+        out.extend_from_slice(&received_at.to_be_bytes());
+        out.extend_from_slice(digest);
+        out
+    }
+
+    /// Recipient side: prove receipt of `ciphertext` (message `seq` from
+    /// `sender`) with the device's Ed25519 key.
+    pub fn sign(
+        sender: &ContactId,
+        seq: u64,
+        ciphertext: &[u8],
+        received_at: u64,
+        device_private_key: &[u8],
+    ) -> Result<Self, DeliveryProofError> {
+        let ciphertext_digest = *blake3::hash(ciphertext).as_bytes();
+        let signature = sign_data(
+            &Self::signed_bytes(sender, seq, received_at, &ciphertext_digest),
+            device_private_key,
+        )
+        .map_err(|e| DeliveryProofError::Signing(e.to_string()))?;
+        Ok(Self {
+            seq,
+            received_at,
+            ciphertext_digest,
+            signature,
+        })
+    }
+
+    /// Check the signature against the recipient's device key, for a proof
+    /// of a message from `sender`. Needs only the digest, so a third party
+    /// can verify without the ciphertext.
+    pub fn verify_signature(
+        &self,
+        sender: &ContactId,
+        device_public_key: &[u8],
+    ) -> Result<(), DeliveryProofError> {
+        let data = Self::signed_bytes(sender, self.seq, self.received_at, &self.ciphertext_digest);
+        match verify_signature(&data, &self.signature, device_public_key) {
+            Ok(true) => Ok(()),
+            _ => Err(DeliveryProofError::InvalidSignature),
+        }
+    }
+
+    /// Sender side: check the proof and that it covers exactly `ciphertext`.
+    pub fn verify(
+        &self,
+        sender: &ContactId,
+        ciphertext: &[u8],
+        device_public_key: &[u8],
+    ) -> Result<(), DeliveryProofError> {
+        self.verify_signature(sender, device_public_key)?;
+        let digest = blake3::hash(ciphertext);
+        if !crate::crypto::eq_32(digest.as_bytes(), &self.ciphertext_digest) {
+            return Err(DeliveryProofError::DigestMismatch);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> [u8; DELIVERY_PROOF_LEN] {
+        let mut out = [0u8; DELIVERY_PROOF_LEN];
+        out[0] = DELIVERY_PROOF_VERSION;
+        out[1..9].copy_from_slice(&self.seq.to_be_bytes());
+        out[9..17].copy_from_slice(&self.received_at.to_be_bytes());
+        out[17..49].copy_from_slice(&self.ciphertext_digest);
+        out[49..].copy_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, DeliveryProofError> {
+        if data.len() != DELIVERY_PROOF_LEN {
+            return Err(DeliveryProofError::Malformed);
+        }
+        if data[0] != DELIVERY_PROOF_VERSION {
+            return Err(DeliveryProofError::UnsupportedVersion(data[0]));
+        }
+        let mut seq = [0u8; 8];
+        seq.copy_from_slice(&data[1..9]);
+        let mut received_at = [0u8; 8];
+        received_at.copy_from_slice(&data[9..17]);
+        let mut ciphertext_digest = [0u8; 32];
+        ciphertext_digest.copy_from_slice(&data[17..49]);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&data[49..]);
+        Ok(Self {
+            seq: u64::from_be_bytes(seq),
+            received_at: u64::from_be_bytes(received_at),
+            ciphertext_digest,
+            signature,
+        })
+    }
+}
+
+/// An ACK batch with delivery proofs for some of the messages it confirms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvedAck {
+    pub batch: AckBatch,
+    pub proofs: Vec<DeliveryProof>,
+}
+
+impl ProvedAck {
+    pub fn new(batch: AckBatch, proofs: Vec<DeliveryProof>) -> Result<Self, DeliveryProofError> {
+        let ack = Self { batch, proofs };
+        ack.validate()?;
+        Ok(ack)
+    }
+
+    fn validate(&self) -> Result<(), DeliveryProofError> {
+        if self.proofs.len() > MAX_PROOFS_PER_ACK {
+            return Err(DeliveryProofError::TooManyProofs(self.proofs.len()));
+        }
+        match self.proofs.iter().find(|p| !self.batch.contains(p.seq)) {
+            Some(p) => Err(DeliveryProofError::NotAcknowledged(p.seq)),
+            None => Ok(()),
+        }
+    }
+
+    /// `[ack batch][proof count: u8][proof]*`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.batch.to_bytes();
+        out.push(self.proofs.len() as u8);
+        for proof in &self.proofs {
+            out.extend_from_slice(&proof.to_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, DeliveryProofError> {
+        // The batch is `[version][count][16 bytes per range]`
+        let ranges = *data.get(1).ok_or(DeliveryProofError::Malformed)? as usize;
+        let batch_len = 2 + ranges * 16;
+        let batch_bytes = data.get(..batch_len).ok_or(DeliveryProofError::Malformed)?;
+        let batch = AckBatch::from_bytes(batch_bytes).map_err(|_| DeliveryProofError::Malformed)?;
+        let (&count, rest) = data[batch_len..]
+            .split_first()
+            .ok_or(DeliveryProofError::Malformed)?;
+        if rest.len() != count as usize * DELIVERY_PROOF_LEN {
+            return Err(DeliveryProofError::Malformed);
+        }
+        let proofs = rest
+            .chunks_exact(DELIVERY_PROOF_LEN)
+            .map(DeliveryProof::from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(batch, proofs)
+    }
+}
+
+/// Which conversations use delivery proofs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeliveryProofPolicy {
+    default_enabled: bool,
+    overrides: HashMap<ContactId, bool>,
+}
+
+impl DeliveryProofPolicy {
+    /// Policy with proofs on or off for conversations without an override.
+    pub fn new(default_enabled: bool) -> Self {
+        Self {
+            default_enabled,
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, contact_id: ContactId, enabled: bool) {
+        self.overrides.insert(contact_id, enabled);
+    }
+
+    /// Drop the override for `contact_id`, returning it to the default.
+    pub fn reset(&mut self, contact_id: &ContactId) {
+        self.overrides.remove(contact_id);
+    }
+
+    pub fn is_enabled(&self, contact_id: &ContactId) -> bool {
+        self.overrides
+            .get(contact_id)
+            .copied()
+            .unwrap_or(self.default_enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_proof_binds_ciphertext_sender_and_device() {
+        let mut rng = seeded(1);
+        let (device_public, device_private) = generate_keypair_with_rng(&mut rng);
+        let sender = test_contact(1);
+        let ciphertext = b"sealed message bytes";
+
+        let proof = DeliveryProof::sign(&sender, 5, ciphertext, NOW, &device_private).unwrap();
+        let proof = DeliveryProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(proof.verify(&sender, ciphertext, &device_public), Ok(()));
+
+        assert_eq!(
+            proof.verify(&sender, b"other bytes", &device_public),
+            Err(DeliveryProofError::DigestMismatch)
+        );
+        assert_eq!(
+            proof.verify_signature(&test_contact(2), &device_public),
+            Err(DeliveryProofError::InvalidSignature)
+        );
+        let mut backdated = proof.clone();
+        backdated.received_at -= 60;
+        assert_eq!(
+            backdated.verify_signature(&sender, &device_public),
+            Err(DeliveryProofError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_proved_ack_round_trip_and_policy() {
+        let mut rng = seeded(2);
+        let (_, device_private) = generate_keypair_with_rng(&mut rng);
+        let sender = test_contact(1);
+        let proofs: Vec<_> = [3u64, 4]
+            .iter()
+            .map(|&seq| DeliveryProof::sign(&sender, seq, b"ct", NOW, &device_private).unwrap())
+            .collect();
+
+        let ack = ProvedAck::new(AckBatch::from_seqs([1, 2, 3, 4, 9]), proofs.clone()).unwrap();
+        assert_eq!(ProvedAck::from_bytes(&ack.to_bytes()).unwrap(), ack);
+        assert_eq!(
+            ProvedAck::new(AckBatch::from_seqs([1, 2]), proofs),
+            Err(DeliveryProofError::NotAcknowledged(3))
+        );
+
+        let mut policy = DeliveryProofPolicy::default();
+        assert!(!policy.is_enabled(&sender));
+        policy.set(sender, true);
+        assert!(policy.is_enabled(&sender));
+        policy.reset(&sender);
+        assert!(!policy.is_enabled(&sender));
+        assert!(DeliveryProofPolicy::new(true).is_enabled(&sender));
+    }
+}
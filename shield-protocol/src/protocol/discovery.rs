@@ -0,0 +1,757 @@
+/// Contact card discovery through a self-hosted well-known HTTPS endpoint.
+///
+/// An alternative to on-chain lookup: a user publishes their `ContactCard`
+/// on a web server they (or their organization) control, and a contact who
+/// knows the domain, the handle and the lookup PIN can fetch it.
+///
+/// - **Privacy:** the server only ever sees an opaque locator and a sealed
+///   blob. Both derive from Argon2id(PIN) salted with the domain and handle,
+///   so the server cannot tell which handle a record belongs to, and an
+///   observer cannot enumerate handles without grinding the PIN per guess.
+/// - **Integrity:** the card keeps its own self-signature, and the record
+///   adds a second signature by the same identity key over the domain,
+///   locator and validity window. A server cannot replay a card under a
+///   different handle, domain or after it expired.
+/// - **Ownership:** updates and deletions carry a write token derived from
+///   the same secret. The server stores a hash of the first token it sees
+///   for a locator and refuses later writes that do not match.
+///
+/// Any lookup backend implements [`NameRegistry`]; [`WellKnownRegistry`] is
+/// the HTTPS one, over an app-supplied [`HttpsTransport`] (the SDK does no
+/// networking of its own). [`MemoryRegistry`] keeps records in process, for
+/// tests and offline development.
+///
+/// Server contract, under `https://{domain}/.well-known/shieldmessenger/v1/`:
+/// `GET {locator}` returns the record or 404; `PUT {locator}` and
+/// `DELETE {locator}` carry `Authorization: Bearer {write_token}`.
+///
+/// Record wire form: `[version][nonce(24)][XChaCha20-Poly1305(bincode(SignedRecord))]`.
+use super::contact::ContactCard;
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::crypto::signing::{derive_public_key, sign_data, verify_signature};
+use crate::rng::SecureRng;
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+pub const DISCOVERY_VERSION: u8 = 1;
+
+/// Path prefix of discovery records on the publishing domain.
+pub const WELL_KNOWN_PATH: &str = "/.well-known/shieldmessenger/v1/";
+
+/// Records are refused above this size, before any decryption.
+pub const MAX_RECORD_BYTES: usize = 16 * 1024;
+
+/// Default lifetime of a published record.
+pub const DEFAULT_RECORD_TTL_SECS: u64 = 30 * 24 * 3600;
+
+/// Tolerated publisher clock drift into the future.
+const MAX_PUBLISH_SKEW_SECS: u64 = 300;
+
+const SALT_CONTEXT: &str = "ShieldMessenger discovery v1 salt";
+const LOCATOR_CONTEXT: &str = "ShieldMessenger discovery v1 locator";
+const RECORD_KEY_CONTEXT: &str = "ShieldMessenger discovery v1 record key";
+const WRITE_TOKEN_CONTEXT: &str = "ShieldMessenger discovery v1 write token";
+const RECORD_SIGNATURE_TAG: &[u8] = b"SM-DISCOVERY-RECORD-v1";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DiscoveryError {
+    #[error("Malformed discovery record")]
+    Malformed,
+    #[error("Unsupported discovery version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid domain or handle")]
+    InvalidName,
+    #[error("Key derivation failed")]
+    KeyDerivation,
+    #[error("Record does not decrypt under this lookup key")]
+    DecryptionFailed,
+    #[error("Invalid card or record signature")]
+    InvalidSignature,
+    #[error("Record is bound to a different name")]
+    NameMismatch,
+    #[error("Record expired or not yet valid")]
+    Expired,
+    #[error("Record exceeds {MAX_RECORD_BYTES} bytes")]
+    TooLarge,
+    #[error("Transport error: {0}")]
+    Transport(String),
+}
+
+/// Argon2id cost of deriving a lookup key from a PIN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupKdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+}
+
+impl Default for LookupKdfParams {
+    /// 64 MiB, 3 passes: PINs are short, so every guess must be expensive.
+    fn default() -> Self {
+        Self {
+            memory_kib: 65536,
+            iterations: 3,
+        }
+    }
+}
+
+/// Everything derived from (domain, handle, PIN). Both the publisher and
+/// the looking-up contact compute the same value.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct LookupKey {
+    #[zeroize(skip)]
+    domain: String,
+    #[zeroize(skip)]
+    handle: String,
+    #[zeroize(skip)]
+    locator: [u8; 32],
+    record_key: [u8; 32],
+    write_token: [u8; 32],
+}
+
+impl LookupKey {
+    /// Derive the lookup key. Domain and handle are normalized to lowercase
+    /// without surrounding whitespace, so "Alice" and "alice " meet.
+    pub fn derive(
+        domain: &str,
+        handle: &str,
+        pin: &str,
+        params: LookupKdfParams,
+    ) -> Result<Self, DiscoveryError> {
+        let domain = normalize_domain(domain)?;
+        let handle = handle.trim().to_lowercase();
+        if handle.is_empty() || pin.is_empty() {
+            return Err(DiscoveryError::InvalidName);
+        }
+
+        let mut hasher = blake3::Hasher::new_derive_key(SALT_CONTEXT);
+        hasher.update(&(domain.len() as u32).to_be_bytes());
+        hasher.update(domain.as_bytes());
+        hasher.update(handle.as_bytes());
+        let salt = hasher.finalize();
+
+        let argon_params = Params::new(params.memory_kib, params.iterations, 1, Some(32))
+            .map_err(|_| DiscoveryError::KeyDerivation)?;
+        let mut secret = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+            .hash_password_into(pin.as_bytes(), &salt.as_bytes()[..16], &mut secret)
+            .map_err(|_| DiscoveryError::KeyDerivation)?;
+
+        let key = Self {
+            domain,
+            handle,
+            locator: blake3::derive_key(LOCATOR_CONTEXT, &secret),
+            record_key: blake3::derive_key(RECORD_KEY_CONTEXT, &secret),
+            write_token: blake3::derive_key(WRITE_TOKEN_CONTEXT, &secret),
+        };
+        secret.zeroize();
+        Ok(key)
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn handle(&self) -> &str {
+        &self.handle
+    }
+
+    pub fn locator(&self) -> &[u8; 32] {
+        &self.locator
+    }
+
+    /// Full record URL on the publishing domain.
+    pub fn url(&self) -> String {
+        format!(
+            "https://{}{}{}",
+            self.domain,
+            WELL_KNOWN_PATH,
+            hex::encode(self.locator)
+        )
+    }
+
+    /// Bearer token for `PUT`/`DELETE`. Only the publisher needs it, but any
+    /// PIN holder can derive it: hand out the PIN, not a read-only secret.
+    pub fn write_token_hex(&self) -> String {
+        hex::encode(self.write_token)
+    }
+}
+
+impl std::fmt::Debug for LookupKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LookupKey")
+            .field("domain", &self.domain)
+            .field("locator", &hex::encode(self.locator))
+            .finish_non_exhaustive()
+    }
+}
+
+fn normalize_domain(domain: &str) -> Result<String, DiscoveryError> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    let valid = !domain.is_empty()
+        && domain.len() <= 253
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == ':');
+    if valid {
+        Ok(domain)
+    } else {
+        Err(DiscoveryError::InvalidName)
+    }
+}
+
+/// Plaintext of a published record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SignedRecord {
+    card: ContactCard,
+    published_at: u64,
+    expires_at: u64,
+    /// Ed25519 by `card.public_key` over [`record_signing_bytes`].
+    signature: Vec<u8>,
+}
+
+fn record_signing_bytes(
+    lookup: &LookupKey,
+    card: &ContactCard,
+    published_at: u64,
+    expires_at: u64,
+) -> Vec<u8> {
+    let card_bytes = card.serialize_for_signing();
+    let mut data = Vec::with_capacity(128 + card_bytes.len());
+    data.extend_from_slice(RECORD_SIGNATURE_TAG);
+    data.extend_from_slice(&(lookup.domain.len() as u32).to_be_bytes());
+    data.extend_from_slice(lookup.domain.as_bytes());
+    data.extend_from_slice(&lookup.locator);
+    data.extend_from_slice(&published_at.to_be_bytes());
+    data.extend_from_slice(&expires_at.to_be_bytes());
+    data.extend_from_slice(&card.signature);
+    data.extend_from_slice(&card_bytes);
+    data
+}
+
+fn card_signature_valid(card: &ContactCard) -> bool {
+    verify_signature(
+        &card.serialize_for_signing(),
+        &card.signature,
+        &card.public_key,
+    )
+    .unwrap_or(false)
+}
+
+/// Seal `card` for publication under `lookup`. `signing_key` must be the
+/// Ed25519 secret matching `card.public_key`.
+pub fn seal_record(
+    card: &ContactCard,
+    signing_key: &[u8; 32],
+    lookup: &LookupKey,
+    now: u64,
+    ttl_secs: u64,
+    rng: &mut impl SecureRng,
+) -> Result<Vec<u8>, DiscoveryError> {
+    let public = derive_public_key(signing_key).map_err(|_| DiscoveryError::InvalidSignature)?;
+    if card.public_key.as_slice() != public.as_slice() || !card_signature_valid(card) {
+        return Err(DiscoveryError::InvalidSignature);
+    }
+
+    let expires_at = now.saturating_add(ttl_secs);
+    let signature = sign_data(
+        &record_signing_bytes(lookup, card, now, expires_at),
+        signing_key,
+    )
+    .map_err(|_| DiscoveryError::InvalidSignature)?;
+    let record = SignedRecord {
+        card: card.clone(),
+        published_at: now,
+        expires_at,
+        signature: signature.to_vec(),
+    };
+
+    let plaintext = bincode::serialize(&record).map_err(|_| DiscoveryError::Malformed)?;
+    let sealed = encrypt_message_with_rng(&plaintext, &lookup.record_key, rng)
+        .map_err(|_| DiscoveryError::Malformed)?;
+    let mut out = Vec::with_capacity(1 + sealed.len());
+    out.push(DISCOVERY_VERSION);
+    out.extend_from_slice(&sealed);
+    if out.len() > MAX_RECORD_BYTES {
+        return Err(DiscoveryError::TooLarge);
+    }
+    Ok(out)
+}
+
+/// Open a fetched record and return the card once both signatures, the
+/// handle binding and the validity window check out.
+pub fn open_record(
+    data: &[u8],
+    lookup: &LookupKey,
+    now: u64,
+) -> Result<ContactCard, DiscoveryError> {
+    if data.len() > MAX_RECORD_BYTES {
+        return Err(DiscoveryError::TooLarge);
+    }
+    let (&version, sealed) = data.split_first().ok_or(DiscoveryError::Malformed)?;
+    if version != DISCOVERY_VERSION {
+        return Err(DiscoveryError::UnsupportedVersion(version));
+    }
+    let plaintext = decrypt_message(sealed, &lookup.record_key)
+        .map_err(|_| DiscoveryError::DecryptionFailed)?;
+    let record: SignedRecord =
+        bincode::deserialize(&plaintext).map_err(|_| DiscoveryError::Malformed)?;
+
+    if !card_signature_valid(&record.card) {
+        return Err(DiscoveryError::InvalidSignature);
+    }
+    let signed = record_signing_bytes(lookup, &record.card, record.published_at, record.expires_at);
+    if !verify_signature(&signed, &record.signature, &record.card.public_key).unwrap_or(false) {
+        return Err(DiscoveryError::InvalidSignature);
+    }
+    if record.card.handle.trim().to_lowercase() != lookup.handle {
+        return Err(DiscoveryError::NameMismatch);
+    }
+    if now >= record.expires_at || record.published_at > now + MAX_PUBLISH_SKEW_SECS {
+        return Err(DiscoveryError::Expired);
+    }
+    Ok(record.card)
+}
+
+/// A place contact cards can be published to and looked up from by name.
+pub trait NameRegistry {
+    /// Publish (or replace) our card under `handle`, retrievable by anyone
+    /// who knows `pin`.
+    fn publish(
+        &mut self,
+        card: &ContactCard,
+        signing_key: &[u8; 32],
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<(), DiscoveryError>;
+
+    /// Resolve `handle` to a verified card. `Ok(None)` when nothing is
+    /// published for this (handle, PIN) pair.
+    fn lookup(
+        &mut self,
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<Option<ContactCard>, DiscoveryError>;
+
+    /// Withdraw a published card.
+    fn unpublish(&mut self, handle: &str, pin: &str) -> Result<(), DiscoveryError>;
+}
+
+/// Minimal HTTPS client the app supplies (OkHttp over Tor on Android).
+/// Errors are transport failures; a missing record is `Ok(None)`, not an error.
+pub trait HttpsTransport {
+    fn get(&mut self, url: &str) -> Result<Option<Vec<u8>>, String>;
+    fn put(&mut self, url: &str, bearer: &str, body: &[u8]) -> Result<(), String>;
+    fn delete(&mut self, url: &str, bearer: &str) -> Result<(), String>;
+}
+
+/// [`NameRegistry`] backed by `https://{domain}/.well-known/shieldmessenger/v1/`.
+pub struct WellKnownRegistry<T: HttpsTransport> {
+    domain: String,
+    transport: T,
+    pub kdf: LookupKdfParams,
+    pub ttl_secs: u64,
+}
+
+impl<T: HttpsTransport> WellKnownRegistry<T> {
+    pub fn new(domain: &str, transport: T) -> Result<Self, DiscoveryError> {
+        Ok(Self {
+            domain: normalize_domain(domain)?,
+            transport,
+            kdf: LookupKdfParams::default(),
+            ttl_secs: DEFAULT_RECORD_TTL_SECS,
+        })
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn lookup_key(&self, handle: &str, pin: &str) -> Result<LookupKey, DiscoveryError> {
+        LookupKey::derive(&self.domain, handle, pin, self.kdf)
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: HttpsTransport> NameRegistry for WellKnownRegistry<T> {
+    fn publish(
+        &mut self,
+        card: &ContactCard,
+        signing_key: &[u8; 32],
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<(), DiscoveryError> {
+        let key = self.lookup_key(handle, pin)?;
+        if card.handle.trim().to_lowercase() != key.handle {
+            return Err(DiscoveryError::NameMismatch);
+        }
+        let record = seal_record(
+            card,
+            signing_key,
+            &key,
+            now,
+            self.ttl_secs,
+            &mut rand::rngs::OsRng,
+        )?;
+        self.transport
+            .put(&key.url(), &key.write_token_hex(), &record)
+            .map_err(DiscoveryError::Transport)
+    }
+
+    fn lookup(
+        &mut self,
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<Option<ContactCard>, DiscoveryError> {
+        let key = self.lookup_key(handle, pin)?;
+        match self
+            .transport
+            .get(&key.url())
+            .map_err(DiscoveryError::Transport)?
+        {
+            Some(data) => open_record(&data, &key, now).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn unpublish(&mut self, handle: &str, pin: &str) -> Result<(), DiscoveryError> {
+        let key = self.lookup_key(handle, pin)?;
+        self.transport
+            .delete(&key.url(), &key.write_token_hex())
+            .map_err(DiscoveryError::Transport)
+    }
+}
+
+/// A [`NameRegistry`] operation, for [`MemoryRegistry::fail_next`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistryOp {
+    Publish,
+    Lookup,
+    Unpublish,
+}
+
+/// [`NameRegistry`] held in memory.
+///
+/// Records are sealed and opened exactly as [`WellKnownRegistry`] does it
+/// (same lookup keys, signatures, expiry and first-writer rule), but stored
+/// in a map, so username flows run without a server. Sealing draws from a
+/// seeded RNG: the same seed and calls give the same stored bytes. Failures
+/// are injected with [`fail_next`](Self::fail_next) or
+/// [`set_offline`](Self::set_offline).
+///
+/// The seeded RNG makes record nonces predictable; never publish real
+/// cards through it.
+pub struct MemoryRegistry {
+    domain: String,
+    /// Locator → (record, hash of the owner's write token)
+    records: HashMap<[u8; 32], (Vec<u8>, [u8; 32])>,
+    failures: VecDeque<(RegistryOp, DiscoveryError)>,
+    offline: bool,
+    rng: ChaCha20Rng,
+    pub kdf: LookupKdfParams,
+    pub ttl_secs: u64,
+}
+
+impl MemoryRegistry {
+    pub fn new(domain: &str, seed: u64) -> Result<Self, DiscoveryError> {
+        Ok(Self {
+            domain: normalize_domain(domain)?,
+            records: HashMap::new(),
+            failures: VecDeque::new(),
+            offline: false,
+            rng: crate::rng::seeded(seed),
+            kdf: LookupKdfParams::default(),
+            ttl_secs: DEFAULT_RECORD_TTL_SECS,
+        })
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn lookup_key(&self, handle: &str, pin: &str) -> Result<LookupKey, DiscoveryError> {
+        LookupKey::derive(&self.domain, handle, pin, self.kdf)
+    }
+
+    /// Make the next `op` fail with `error`. Queued failures fire in order,
+    /// each once, on the first call of their operation.
+    pub fn fail_next(&mut self, op: RegistryOp, error: DiscoveryError) {
+        self.failures.push_back((op, error));
+    }
+
+    /// While offline every call fails with a transport error.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Number of published records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Stored record for `lookup`, as a server would return it.
+    pub fn raw_record(&self, lookup: &LookupKey) -> Option<&[u8]> {
+        self.records
+            .get(lookup.locator())
+            .map(|(record, _)| record.as_slice())
+    }
+
+    fn injected(&mut self, op: RegistryOp) -> Result<(), DiscoveryError> {
+        if self.offline {
+            return Err(DiscoveryError::Transport("registry offline".into()));
+        }
+        let queued = self.failures.iter().position(|(queued, _)| *queued == op);
+        match queued.and_then(|i| self.failures.remove(i)) {
+            Some((_, error)) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn authorize(&self, key: &LookupKey) -> Result<[u8; 32], DiscoveryError> {
+        let token = *blake3::hash(&key.write_token).as_bytes();
+        match self.records.get(key.locator()) {
+            Some((_, owner)) if *owner != token => {
+                Err(DiscoveryError::Transport("write token rejected".into()))
+            }
+            _ => Ok(token),
+        }
+    }
+}
+
+impl NameRegistry for MemoryRegistry {
+    fn publish(
+        &mut self,
+        card: &ContactCard,
+        signing_key: &[u8; 32],
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<(), DiscoveryError> {
+        self.injected(RegistryOp::Publish)?;
+        let key = self.lookup_key(handle, pin)?;
+        if card.handle.trim().to_lowercase() != key.handle {
+            return Err(DiscoveryError::NameMismatch);
+        }
+        let owner = self.authorize(&key)?;
+        let record = seal_record(card, signing_key, &key, now, self.ttl_secs, &mut self.rng)?;
+        self.records.insert(key.locator, (record, owner));
+        Ok(())
+    }
+
+    fn lookup(
+        &mut self,
+        handle: &str,
+        pin: &str,
+        now: u64,
+    ) -> Result<Option<ContactCard>, DiscoveryError> {
+        self.injected(RegistryOp::Lookup)?;
+        let key = self.lookup_key(handle, pin)?;
+        match self.raw_record(&key) {
+            Some(data) => open_record(data, &key, now).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn unpublish(&mut self, handle: &str, pin: &str) -> Result<(), DiscoveryError> {
+        self.injected(RegistryOp::Unpublish)?;
+        let key = self.lookup_key(handle, pin)?;
+        self.authorize(&key)?;
+        self.records.remove(key.locator());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::rng::seeded;
+    use std::collections::HashMap;
+
+    const FAST: LookupKdfParams = LookupKdfParams {
+        memory_kib: 256,
+        iterations: 1,
+    };
+
+    /// In-memory server enforcing the first-writer token rule.
+    #[derive(Default)]
+    struct MemoryServer {
+        records: HashMap<String, (Vec<u8>, [u8; 32])>,
+    }
+
+    impl HttpsTransport for MemoryServer {
+        fn get(&mut self, url: &str) -> Result<Option<Vec<u8>>, String> {
+            Ok(self.records.get(url).map(|(body, _)| body.clone()))
+        }
+
+        fn put(&mut self, url: &str, bearer: &str, body: &[u8]) -> Result<(), String> {
+            let token = *blake3::hash(bearer.as_bytes()).as_bytes();
+            match self.records.get(url) {
+                Some((_, owner)) if *owner != token => Err("403".into()),
+                _ => {
+                    self.records.insert(url.into(), (body.to_vec(), token));
+                    Ok(())
+                }
+            }
+        }
+
+        fn delete(&mut self, url: &str, bearer: &str) -> Result<(), String> {
+            let token = *blake3::hash(bearer.as_bytes()).as_bytes();
+            match self.records.get(url) {
+                Some((_, owner)) if *owner != token => Err("403".into()),
+                _ => {
+                    self.records.remove(url);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    fn signed_card(handle: &str, seed: u64) -> (ContactCard, [u8; 32]) {
+        let (public, secret) = generate_keypair_with_rng(&mut seeded(seed));
+        let mut card = ContactCard::new(
+            public.to_vec(),
+            String::new(),
+            handle.into(),
+            Some("example.onion".into()),
+        );
+        card.signature = sign_data(&card.serialize_for_signing(), &secret)
+            .unwrap()
+            .to_vec();
+        (card, secret)
+    }
+
+    #[test]
+    fn test_publish_lookup_unpublish() {
+        let mut registry = WellKnownRegistry::new("Example.ORG", MemoryServer::default()).unwrap();
+        registry.kdf = FAST;
+        let (card, secret) = signed_card("alice", 1);
+        registry
+            .publish(&card, &secret, "Alice", "4821", 1_000)
+            .unwrap();
+
+        let found = registry.lookup("alice ", "4821", 2_000).unwrap().unwrap();
+        assert_eq!(found.public_key, card.public_key);
+        assert!(registry.lookup("alice", "0000", 2_000).unwrap().is_none());
+        assert_eq!(
+            registry
+                .lookup("alice", "4821", 1_000 + DEFAULT_RECORD_TTL_SECS)
+                .unwrap_err(),
+            DiscoveryError::Expired
+        );
+
+        registry.unpublish("alice", "4821").unwrap();
+        assert!(registry.lookup("alice", "4821", 2_000).unwrap().is_none());
+
+        // The server sees only the locator, never the handle
+        let server = registry.into_transport();
+        assert!(server.records.keys().all(|url| !url.contains("alice")));
+    }
+
+    #[test]
+    fn test_record_bound_to_name_and_signer() {
+        let key = LookupKey::derive("example.org", "alice", "4821", FAST).unwrap();
+        let (card, secret) = signed_card("alice", 1);
+        let (_, other_secret) = signed_card("mallory", 2);
+        assert_eq!(
+            seal_record(&card, &other_secret, &key, 1_000, 60, &mut seeded(3)).unwrap_err(),
+            DiscoveryError::InvalidSignature
+        );
+
+        let sealed = seal_record(&card, &secret, &key, 1_000, 60, &mut seeded(3)).unwrap();
+        assert!(open_record(&sealed, &key, 1_010).is_ok());
+
+        // Same PIN on another domain derives different keys entirely
+        let moved = LookupKey::derive("evil.example", "alice", "4821", FAST).unwrap();
+        assert_ne!(moved.locator(), key.locator());
+        assert_eq!(
+            open_record(&sealed, &moved, 1_010).unwrap_err(),
+            DiscoveryError::DecryptionFailed
+        );
+
+        let mut tampered = sealed.clone();
+        tampered[0] = 9;
+        assert_eq!(
+            open_record(&tampered, &key, 1_010).unwrap_err(),
+            DiscoveryError::UnsupportedVersion(9)
+        );
+    }
+
+    fn memory_registry(seed: u64) -> MemoryRegistry {
+        let mut registry = MemoryRegistry::new("example.org", seed).unwrap();
+        registry.kdf = FAST;
+        registry
+    }
+
+    #[test]
+    fn test_memory_registry_is_deterministic() {
+        let (card, secret) = signed_card("alice", 1);
+        let mut stored = Vec::new();
+        for _ in 0..2 {
+            let mut registry = memory_registry(7);
+            registry
+                .publish(&card, &secret, "alice", "4821", 1_000)
+                .unwrap();
+            let key = registry.lookup_key("alice", "4821").unwrap();
+            stored.push(registry.raw_record(&key).unwrap().to_vec());
+
+            let found = registry.lookup("Alice", "4821", 2_000).unwrap().unwrap();
+            assert_eq!(found.public_key, card.public_key);
+            assert!(registry.lookup("bob", "4821", 2_000).unwrap().is_none());
+        }
+        assert_eq!(stored[0], stored[1]);
+
+        // Records are interchangeable with the HTTPS backend's
+        let key = LookupKey::derive("example.org", "alice", "4821", FAST).unwrap();
+        assert!(open_record(&stored[0], &key, 2_000).is_ok());
+    }
+
+    #[test]
+    fn test_memory_registry_failure_injection() {
+        let mut registry = memory_registry(7);
+        let (card, secret) = signed_card("alice", 1);
+
+        registry.fail_next(
+            RegistryOp::Publish,
+            DiscoveryError::Transport("timeout".into()),
+        );
+        assert_eq!(
+            registry.publish(&card, &secret, "alice", "4821", 1_000),
+            Err(DiscoveryError::Transport("timeout".into()))
+        );
+        assert!(registry.is_empty());
+        // Fires once; the retry goes through
+        registry
+            .publish(&card, &secret, "alice", "4821", 1_000)
+            .unwrap();
+
+        registry.set_offline(true);
+        assert!(matches!(
+            registry.lookup("alice", "4821", 2_000),
+            Err(DiscoveryError::Transport(_))
+        ));
+        registry.set_offline(false);
+
+        registry.fail_next(RegistryOp::Lookup, DiscoveryError::Expired);
+        assert_eq!(
+            registry.lookup("alice", "4821", 2_000).unwrap_err(),
+            DiscoveryError::Expired
+        );
+        registry.unpublish("alice", "4821").unwrap();
+        assert!(registry.lookup("alice", "4821", 2_000).unwrap().is_none());
+    }
+}
@@ -0,0 +1,258 @@
+/// Mixed fan-out of group messages through store-and-forward relays.
+///
+/// A group message dropped at a relay as one sealed envelope per member gives
+/// the relay the group's shape: the envelopes arrive in one burst, their
+/// count is the member count, sizes vary with per-member headers, and the
+/// target mailboxes come in the sender's member order. The fan-out mixer
+/// blurs all of that on the client side:
+///
+/// - **Uniform sizes:** every recipient's plaintext is padded with
+///   [`pad_plaintext`] to one size class before sealing, so all envelopes of
+///   a fan-out, and of any fan-out in the same class, are the same length.
+/// - **Dummy envelopes:** [`mix`] adds random envelopes of that length,
+///   rounding the batch up to a multiple of [`FanoutConfig::batch_quantum`]
+///   plus a random number of extras, so the count only loosely bounds the
+///   group size.
+/// - **Shuffled order:** real and dummy drops come back in a fresh random
+///   order, to be submitted (and spread over relays) in that order.
+///
+/// Dummies go to decoy mailboxes. Mailboxes of other contacts make the best
+/// decoys: their owners fail to open the dummy and drop it, exactly as for
+/// any undecryptable envelope. With no decoys available, dummies go to random
+/// mailboxes, which a relay can later tell apart because nobody polls them.
+use super::mailbox::MailboxId;
+use crate::rng::SecureRng;
+use rand::seq::SliceRandom;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum FanoutError {
+    #[error("Malformed padded envelope")]
+    Malformed,
+    #[error("Plaintext too large for any envelope size class ({0} bytes)")]
+    TooLarge(usize),
+    #[error("Invalid fan-out configuration")]
+    InvalidConfig,
+    #[error("Fan-out has no recipients")]
+    Empty,
+    #[error("Too many recipients in one fan-out ({0})")]
+    TooManyRecipients(usize),
+    #[error("Sealed envelopes differ in size; pad plaintexts to one class first")]
+    UnevenEnvelopes,
+}
+
+/// Envelope plaintext sizes after padding.
+pub const FANOUT_SIZE_CLASSES: [usize; 5] = [1 << 10, 1 << 12, 1 << 14, 1 << 16, 1 << 18];
+
+/// `[len: u32 BE]` in front of the padded plaintext.
+const LEN_FIELD: usize = 4;
+
+/// How a fan-out is mixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FanoutConfig {
+    /// Batches are rounded up to a multiple of this many envelopes.
+    pub batch_quantum: usize,
+    /// Up to this many further dummies are added at random.
+    pub max_extra_dummies: usize,
+    /// Largest number of real recipients in one fan-out.
+    pub max_recipients: usize,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        Self {
+            batch_quantum: 8,
+            max_extra_dummies: 4,
+            max_recipients: 1024,
+        }
+    }
+}
+
+impl FanoutConfig {
+    pub fn validate(&self) -> Result<(), FanoutError> {
+        if self.batch_quantum == 0 || self.max_recipients == 0 {
+            return Err(FanoutError::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    /// Dummies to add to a fan-out with `real` recipients.
+    pub fn dummy_count(&self, real: usize, rng: &mut impl SecureRng) -> usize {
+        let quantum = self.batch_quantum.max(1);
+        let rounded = real.div_ceil(quantum) * quantum;
+        let extra = match self.max_extra_dummies {
+            0 => 0,
+            max => (rng.next_u64() % (max as u64 + 1)) as usize,
+        };
+        rounded - real + extra
+    }
+}
+
+/// One envelope for one mailbox, as handed to a relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayDrop {
+    pub mailbox: MailboxId,
+    pub envelope: Vec<u8>,
+}
+
+/// Padded length for a plaintext of `len` bytes: the smallest size class
+/// that fits it and its length field.
+pub fn padded_len(len: usize) -> Result<usize, FanoutError> {
+    FANOUT_SIZE_CLASSES
+        .iter()
+        .copied()
+        .find(|class| len + LEN_FIELD <= *class)
+        .ok_or(FanoutError::TooLarge(len))
+}
+
+/// Pad `plaintext` to `padded_len` bytes (`[len][plaintext][random]`), to be
+/// sealed afterwards so the length stays hidden from the relay. Pad every
+/// recipient's plaintext of one fan-out to the same length, e.g. the
+/// [`padded_len`] of the longest.
+pub fn pad_plaintext(
+    plaintext: &[u8],
+    padded_len: usize,
+    rng: &mut impl SecureRng,
+) -> Result<Vec<u8>, FanoutError> {
+    if plaintext.len() + LEN_FIELD > padded_len || plaintext.len() > u32::MAX as usize {
+        return Err(FanoutError::TooLarge(plaintext.len()));
+    }
+    let mut out = Vec::with_capacity(padded_len);
+    out.extend_from_slice(&(plaintext.len() as u32).to_be_bytes());
+    out.extend_from_slice(plaintext);
+    let mut padding = vec![0u8; padded_len - out.len()];
+    rng.fill_bytes(&mut padding);
+    out.extend_from_slice(&padding);
+    Ok(out)
+}
+
+/// Recover the plaintext from an opened, padded envelope.
+pub fn unpad_plaintext(padded: &[u8]) -> Result<Vec<u8>, FanoutError> {
+    let len_bytes: [u8; LEN_FIELD] = padded
+        .get(..LEN_FIELD)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(FanoutError::Malformed)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    padded
+        .get(LEN_FIELD..LEN_FIELD + len)
+        .map(<[u8]>::to_vec)
+        .ok_or(FanoutError::Malformed)
+}
+
+/// Mix sealed envelopes for the group's members with dummies and shuffle
+/// them into submission order.
+///
+/// `decoys` are candidate mailboxes for dummies; recipients of the fan-out
+/// are never picked as decoys.
+pub fn mix(
+    real: Vec<RelayDrop>,
+    decoys: &[MailboxId],
+    config: &FanoutConfig,
+    rng: &mut impl SecureRng,
+) -> Result<Vec<RelayDrop>, FanoutError> {
+    config.validate()?;
+    let envelope_len = match real.first() {
+        Some(first) => first.envelope.len(),
+        None => return Err(FanoutError::Empty),
+    };
+    if real.len() > config.max_recipients {
+        return Err(FanoutError::TooManyRecipients(real.len()));
+    }
+    if real.iter().any(|d| d.envelope.len() != envelope_len) {
+        return Err(FanoutError::UnevenEnvelopes);
+    }
+
+    let pool: Vec<MailboxId> = decoys
+        .iter()
+        .filter(|m| !real.iter().any(|d| d.mailbox == **m))
+        .copied()
+        .collect();
+    let dummies = config.dummy_count(real.len(), rng);
+
+    let mut drops = real;
+    drops.reserve(dummies);
+    for _ in 0..dummies {
+        let mailbox = match pool.choose(rng) {
+            Some(decoy) => *decoy,
+            None => {
+                let mut id = [0u8; 32];
+                rng.fill_bytes(&mut id);
+                MailboxId(id)
+            }
+        };
+        let mut envelope = vec![0u8; envelope_len];
+        rng.fill_bytes(&mut envelope);
+        drops.push(RelayDrop { mailbox, envelope });
+    }
+    drops.shuffle(rng);
+    Ok(drops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    fn mailbox(fill: u8) -> MailboxId {
+        MailboxId([fill; 32])
+    }
+
+    #[test]
+    fn test_padding_hides_length_within_class() {
+        let mut rng = seeded(1);
+        assert_eq!(padded_len(10), Ok(1024));
+        assert_eq!(padded_len(1021), Ok(4096));
+        assert_eq!(padded_len(1 << 18), Err(FanoutError::TooLarge(1 << 18)));
+
+        let short = pad_plaintext(b"hi", 1024, &mut rng).unwrap();
+        let long = pad_plaintext(&[7u8; 900], 1024, &mut rng).unwrap();
+        assert_eq!(short.len(), long.len());
+        assert_eq!(unpad_plaintext(&short).unwrap(), b"hi");
+        assert_eq!(unpad_plaintext(&long).unwrap(), vec![7u8; 900]);
+        assert_eq!(unpad_plaintext(&[0, 0, 1]), Err(FanoutError::Malformed));
+        assert_eq!(
+            unpad_plaintext(&[0, 0, 0, 9, 1]),
+            Err(FanoutError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_mix_pads_count_and_shuffles() {
+        let mut rng = seeded(2);
+        let config = FanoutConfig::default();
+        let real: Vec<RelayDrop> = (1..=5)
+            .map(|i| RelayDrop {
+                mailbox: mailbox(i),
+                envelope: vec![i; 64],
+            })
+            .collect();
+        let decoys = [mailbox(1), mailbox(100), mailbox(101)];
+
+        let drops = mix(real.clone(), &decoys, &config, &mut rng).unwrap();
+        assert!((8..=12).contains(&drops.len()), "{}", drops.len());
+        assert!(drops.iter().all(|d| d.envelope.len() == 64));
+        for drop in &real {
+            assert!(drops.contains(drop));
+        }
+        // Dummies only go to decoys that are not recipients
+        let dummies: Vec<&RelayDrop> = drops.iter().filter(|d| !real.contains(d)).collect();
+        assert!(dummies
+            .iter()
+            .all(|d| d.mailbox == mailbox(100) || d.mailbox == mailbox(101)));
+
+        let orders: Vec<Vec<MailboxId>> = (0..4)
+            .map(|_| {
+                let drops = mix(real.clone(), &[], &config, &mut rng).unwrap();
+                drops.iter().map(|d| d.mailbox).collect()
+            })
+            .collect();
+        assert!(orders.windows(2).any(|w| w[0] != w[1]));
+
+        let mut uneven = real;
+        uneven[0].envelope.push(0);
+        assert_eq!(
+            mix(uneven, &[], &config, &mut rng),
+            Err(FanoutError::UnevenEnvelopes)
+        );
+    }
+}
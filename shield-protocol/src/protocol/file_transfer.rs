@@ -0,0 +1,801 @@
+/// Chunked, resumable file transfer.
+///
+/// Files used to go out as one `sendMessageBlob` call: a single connection
+/// carrying the whole blob, lost in full if the circuit dropped near the end
+/// and retried from byte zero. A transfer here is instead offered, accepted,
+/// and moved in fixed-size chunks:
+///
+/// ```text
+/// sender                          receiver
+///   Offer(name, size, hash)  ──▶
+///                            ◀──  Accept(next, window) | Decline
+///   Chunk(i) … Chunk(i+w-1)  ──▶
+///                            ◀──  Ack(next, window, sack)
+///   …                                  (hash checked)
+///                            ◀──  Complete | Cancel
+/// ```
+///
+/// Flow control is a sliding window set by the receiver: at most `window`
+/// chunks past the cumulative acknowledgment are in flight. Chunks may
+/// travel on separate connections and arrive out of order, so an [`Ack`]
+/// also carries a bitmap of the chunks held beyond `next`; only chunks
+/// neither acknowledged nor marked there are retransmitted, after
+/// [`RETRANSMIT_SECS`]. After a disconnect the sender sends `Resume` and the
+/// receiver answers with a fresh `Accept` from its last contiguous chunk,
+/// which also works after the receiver restarts with a partial file.
+///
+/// Frames are sealed with XChaCha20-Poly1305 under per-direction keys
+/// derived from the contact's shared secret ([`FileKeys::derive`]), like RPC
+/// frames and never with the message ratchet:
+///
+/// ```text
+/// sealed = [version][nonce: 24][ciphertext of bincode(FileFrame)]
+/// ```
+///
+/// [`Ack`]: FileFrame::Ack
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum FileTransferError {
+    #[error("Malformed file transfer frame")]
+    Malformed,
+    #[error("Unsupported file transfer version {0}")]
+    UnsupportedVersion(u8),
+    #[error("File transfer frame could not be decrypted")]
+    DecryptionFailed,
+    #[error("Invalid file offer: {0}")]
+    InvalidOffer(&'static str),
+    #[error("Chunk {index} has {len} bytes, expected {expected}")]
+    BadChunkLength {
+        index: u32,
+        len: usize,
+        expected: usize,
+    },
+    #[error("Frame not expected while the transfer is {0:?}")]
+    UnexpectedFrame(TransferState),
+    #[error("File transfer encoding failed: {0}")]
+    Encoding(String),
+}
+
+pub type Result<T> = std::result::Result<T, FileTransferError>;
+
+/// File transfer wire version.
+pub const FILE_TRANSFER_VERSION: u8 = 1;
+
+/// Default chunk size: a sealed chunk frame still fits one 4 KiB padded
+/// packet, so every chunk looks the same on the wire.
+pub const DEFAULT_CHUNK_SIZE: u32 = 3584;
+
+/// Largest chunk size a receiver accepts.
+pub const MAX_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Largest file that can be offered.
+pub const MAX_FILE_SIZE: u64 = 1 << 31;
+
+/// Receiver window when none is given; also the largest allowed (the
+/// selective-ack bitmap is 64 bits).
+pub const DEFAULT_WINDOW: u16 = 16;
+pub const MAX_WINDOW: u16 = 64;
+
+/// Unacknowledged chunks are sent again after this long.
+pub const RETRANSMIT_SECS: u64 = 30;
+
+pub const MAX_NAME_LEN: usize = 255;
+pub const MAX_MIME_LEN: usize = 127;
+
+const FILE_KEY_CONTEXT: &str = "ShieldMessenger-FileTransfer-Key-v1";
+
+pub type TransferId = [u8; 16];
+
+/// What the sender announces before any data moves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOffer {
+    pub transfer_id: TransferId,
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    pub chunk_size: u32,
+    /// BLAKE3 of the whole file, checked by the receiver at the end.
+    pub hash: [u8; 32],
+}
+
+impl FileOffer {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.len() > MAX_NAME_LEN {
+            return Err(FileTransferError::InvalidOffer("name"));
+        }
+        if self.name.chars().any(char::is_control) {
+            return Err(FileTransferError::InvalidOffer("name"));
+        }
+        if self.mime.len() > MAX_MIME_LEN {
+            return Err(FileTransferError::InvalidOffer("mime type"));
+        }
+        if self.size == 0 || self.size > MAX_FILE_SIZE {
+            return Err(FileTransferError::InvalidOffer("size"));
+        }
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(FileTransferError::InvalidOffer("chunk size"));
+        }
+        Ok(())
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.size.div_ceil(u64::from(self.chunk_size)) as u32
+    }
+
+    /// Byte offset of chunk `index`.
+    pub fn offset(&self, index: u32) -> u64 {
+        u64::from(index) * u64::from(self.chunk_size)
+    }
+
+    /// Length of chunk `index` (the last one may be short).
+    pub fn chunk_len(&self, index: u32) -> usize {
+        let remaining = self.size.saturating_sub(self.offset(index));
+        remaining.min(u64::from(self.chunk_size)) as usize
+    }
+
+    /// Bytes in the first `chunks` chunks.
+    pub fn bytes_in(&self, chunks: u32) -> u64 {
+        self.offset(chunks).min(self.size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileFrame {
+    Offer(FileOffer),
+    /// Send chunks from `next` on, at most `window` past the last ack.
+    Accept {
+        transfer_id: TransferId,
+        next: u32,
+        window: u16,
+    },
+    Decline {
+        transfer_id: TransferId,
+    },
+    Chunk {
+        transfer_id: TransferId,
+        index: u32,
+        data: Vec<u8>,
+    },
+    /// Every chunk below `next` is stored; bit `i` of `sack` marks chunk
+    /// `next + 1 + i` as stored too.
+    Ack {
+        transfer_id: TransferId,
+        next: u32,
+        window: u16,
+        sack: u64,
+    },
+    /// Sender reconnected; answered with `Accept`.
+    Resume {
+        transfer_id: TransferId,
+    },
+    /// Either side gives up (or the receiver's hash check failed).
+    Cancel {
+        transfer_id: TransferId,
+    },
+    /// Receiver stored every chunk and the hash matched.
+    Complete {
+        transfer_id: TransferId,
+    },
+}
+
+impl FileFrame {
+    pub fn transfer_id(&self) -> &TransferId {
+        match self {
+            Self::Offer(offer) => &offer.transfer_id,
+            Self::Accept { transfer_id, .. }
+            | Self::Decline { transfer_id }
+            | Self::Chunk { transfer_id, .. }
+            | Self::Ack { transfer_id, .. }
+            | Self::Resume { transfer_id }
+            | Self::Cancel { transfer_id }
+            | Self::Complete { transfer_id } => transfer_id,
+        }
+    }
+
+    /// Encrypt for the peer under our sending key.
+    pub fn seal(&self, keys: &FileKeys, rng: &mut impl SecureRng) -> Result<Vec<u8>> {
+        let mut body =
+            bincode::serialize(self).map_err(|e| FileTransferError::Encoding(e.to_string()))?;
+        let sealed = encrypt_message_with_rng(&body, &keys.send, rng)
+            .map_err(|e| FileTransferError::Encoding(e.to_string()));
+        body.zeroize();
+        let mut out = vec![FILE_TRANSFER_VERSION];
+        out.extend_from_slice(&sealed?);
+        Ok(out)
+    }
+
+    /// Decrypt a frame from the peer.
+    pub fn open(sealed: &[u8], keys: &FileKeys) -> Result<Self> {
+        let (&version, body) = sealed.split_first().ok_or(FileTransferError::Malformed)?;
+        if version != FILE_TRANSFER_VERSION {
+            return Err(FileTransferError::UnsupportedVersion(version));
+        }
+        let mut plaintext =
+            decrypt_message(body, &keys.recv).map_err(|_| FileTransferError::DecryptionFailed)?;
+        let frame =
+            bincode::deserialize::<Self>(&plaintext).map_err(|_| FileTransferError::Malformed);
+        plaintext.zeroize();
+        let frame = frame?;
+        if let Self::Offer(offer) = &frame {
+            offer.validate()?;
+        }
+        Ok(frame)
+    }
+}
+
+/// Per-direction file transfer keys for one contact. Wiped on drop.
+pub struct FileKeys {
+    send: [u8; 32],
+    recv: [u8; 32],
+}
+
+impl FileKeys {
+    /// Keys from the contact's X25519 shared secret and both public keys;
+    /// our `send` is the peer's `recv`.
+    pub fn derive(
+        shared_secret: &[u8; 32],
+        our_pubkey: &[u8; 32],
+        their_pubkey: &[u8; 32],
+    ) -> Self {
+        let direction = |from: &[u8; 32], to: &[u8; 32]| {
+            let mut material = [0u8; 96];
+            material[..32].copy_from_slice(shared_secret);
+            material[32..64].copy_from_slice(from);
+            material[64..].copy_from_slice(to);
+            let key = blake3::derive_key(FILE_KEY_CONTEXT, &material);
+            material.zeroize();
+            key
+        };
+        Self {
+            send: direction(our_pubkey, their_pubkey),
+            recv: direction(their_pubkey, our_pubkey),
+        }
+    }
+}
+
+impl Drop for FileKeys {
+    fn drop(&mut self) {
+        self.send.zeroize();
+        self.recv.zeroize();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+    /// Offer sent or received, no answer yet
+    Offered,
+    Active,
+    /// Sender lost the connection; waiting to resume
+    Paused,
+    Completed,
+    Declined,
+    Cancelled,
+    /// Receiver's hash check failed
+    Failed,
+}
+
+impl TransferState {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Declined | Self::Cancelled | Self::Failed
+        )
+    }
+}
+
+fn clamp_window(window: u16) -> u16 {
+    window.clamp(1, MAX_WINDOW)
+}
+
+// ---------------------------------------------------------------------------
+// Sender
+// ---------------------------------------------------------------------------
+
+/// Sending side of one transfer.
+#[derive(Debug)]
+pub struct OutgoingTransfer {
+    offer: FileOffer,
+    state: TransferState,
+    /// Every chunk below this is acknowledged
+    acked: u32,
+    /// Next chunk never sent
+    next_new: u32,
+    window: u16,
+    /// Sent, unacknowledged chunks and when they were last sent
+    in_flight: BTreeMap<u32, u64>,
+    retransmissions: u32,
+}
+
+impl OutgoingTransfer {
+    pub fn new(offer: FileOffer) -> Result<Self> {
+        offer.validate()?;
+        Ok(Self {
+            offer,
+            state: TransferState::Offered,
+            acked: 0,
+            next_new: 0,
+            window: DEFAULT_WINDOW,
+            in_flight: BTreeMap::new(),
+            retransmissions: 0,
+        })
+    }
+
+    pub fn offer(&self) -> &FileOffer {
+        &self.offer
+    }
+
+    pub fn state(&self) -> TransferState {
+        self.state
+    }
+
+    pub fn acked_chunks(&self) -> u32 {
+        self.acked
+    }
+
+    pub fn retransmissions(&self) -> u32 {
+        self.retransmissions
+    }
+
+    pub fn offer_frame(&self) -> FileFrame {
+        FileFrame::Offer(self.offer.clone())
+    }
+
+    /// Handle the receiver's answer; returns true if progress was made.
+    pub fn on_frame(&mut self, frame: &FileFrame) -> Result<bool> {
+        if self.state.is_finished() {
+            return Err(FileTransferError::UnexpectedFrame(self.state));
+        }
+        let count = self.offer.chunk_count();
+        match *frame {
+            FileFrame::Accept { next, window, .. } => {
+                if next > count {
+                    return Err(FileTransferError::Malformed);
+                }
+                // Everything the receiver has not stored is sent again
+                self.acked = next;
+                self.next_new = next;
+                self.window = clamp_window(window);
+                self.in_flight.clear();
+                self.state = TransferState::Active;
+                Ok(true)
+            }
+            FileFrame::Ack {
+                next, window, sack, ..
+            } => {
+                // Acks sent before a disconnect may arrive after the pause
+                if !matches!(self.state, TransferState::Active | TransferState::Paused) {
+                    return Err(FileTransferError::UnexpectedFrame(self.state));
+                }
+                if next > count {
+                    return Err(FileTransferError::Malformed);
+                }
+                self.window = clamp_window(window);
+                let advanced = next > self.acked;
+                self.acked = self.acked.max(next);
+                self.next_new = self.next_new.max(self.acked);
+                let acked = self.acked;
+                self.in_flight.retain(|&index, _| {
+                    let bit = index.checked_sub(acked + 1);
+                    index >= acked && !bit.is_some_and(|b| b < 64 && sack & (1 << b) != 0)
+                });
+                Ok(advanced)
+            }
+            FileFrame::Decline { .. } => {
+                self.state = TransferState::Declined;
+                Ok(false)
+            }
+            FileFrame::Cancel { .. } => {
+                self.state = TransferState::Cancelled;
+                Ok(false)
+            }
+            FileFrame::Complete { .. } => {
+                // May overtake the final Ack; it implies every chunk arrived
+                if !matches!(self.state, TransferState::Active | TransferState::Paused) {
+                    return Err(FileTransferError::UnexpectedFrame(self.state));
+                }
+                self.acked = count;
+                self.in_flight.clear();
+                self.state = TransferState::Completed;
+                Ok(true)
+            }
+            _ => Err(FileTransferError::UnexpectedFrame(self.state)),
+        }
+    }
+
+    /// Chunks to send at `now`: timed-out ones again, then new ones while
+    /// the window allows. Marks them as sent.
+    pub fn next_chunks(&mut self, now: u64) -> Vec<u32> {
+        if self.state != TransferState::Active {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        for (&index, sent_at) in self.in_flight.iter_mut() {
+            if now.saturating_sub(*sent_at) >= RETRANSMIT_SECS {
+                *sent_at = now;
+                out.push(index);
+                self.retransmissions += 1;
+            }
+        }
+        let limit = self
+            .acked
+            .saturating_add(u32::from(self.window))
+            .min(self.offer.chunk_count());
+        while self.next_new < limit {
+            self.in_flight.insert(self.next_new, now);
+            out.push(self.next_new);
+            self.next_new += 1;
+        }
+        out
+    }
+
+    /// The connection failed; chunks in flight are presumed lost.
+    pub fn pause(&mut self) {
+        if self.state == TransferState::Active {
+            self.state = TransferState::Paused;
+            self.in_flight.clear();
+        }
+    }
+
+    /// Ask the receiver where to continue.
+    pub fn resume_frame(&self) -> FileFrame {
+        FileFrame::Resume {
+            transfer_id: self.offer.transfer_id,
+        }
+    }
+
+    pub fn cancel(&mut self) -> FileFrame {
+        self.state = TransferState::Cancelled;
+        FileFrame::Cancel {
+            transfer_id: self.offer.transfer_id,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Receiver
+// ---------------------------------------------------------------------------
+
+/// What to do with a received chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkReceipt {
+    /// Write the data at this byte offset
+    pub store_at: Option<u64>,
+    /// Send this acknowledgment
+    pub ack: Option<FileFrame>,
+    /// Every chunk is stored; check the hash and call `finish`
+    pub complete: bool,
+}
+
+/// Receiving side of one transfer.
+#[derive(Debug)]
+pub struct IncomingTransfer {
+    offer: FileOffer,
+    state: TransferState,
+    window: u16,
+    /// Every chunk below this is stored
+    next: u32,
+    /// Stored chunks beyond `next`
+    ahead: BTreeSet<u32>,
+    since_ack: u16,
+}
+
+impl IncomingTransfer {
+    pub fn new(offer: FileOffer) -> Result<Self> {
+        offer.validate()?;
+        Ok(Self {
+            offer,
+            state: TransferState::Offered,
+            window: DEFAULT_WINDOW,
+            next: 0,
+            ahead: BTreeSet::new(),
+            since_ack: 0,
+        })
+    }
+
+    pub fn offer(&self) -> &FileOffer {
+        &self.offer
+    }
+
+    pub fn state(&self) -> TransferState {
+        self.state
+    }
+
+    /// Chunks stored from the start of the file
+    pub fn stored_chunks(&self) -> u32 {
+        self.next
+    }
+
+    fn accept_frame(&self) -> FileFrame {
+        FileFrame::Accept {
+            transfer_id: self.offer.transfer_id,
+            next: self.next,
+            window: self.window,
+        }
+    }
+
+    fn ack_frame(&self) -> FileFrame {
+        let mut sack = 0u64;
+        for &index in &self.ahead {
+            let bit = index - self.next - 1;
+            if bit < 64 {
+                sack |= 1 << bit;
+            }
+        }
+        FileFrame::Ack {
+            transfer_id: self.offer.transfer_id,
+            next: self.next,
+            window: self.window,
+            sack,
+        }
+    }
+
+    /// Accept, continuing after the first `stored_chunks` chunks (those
+    /// already in a partial file from an earlier attempt).
+    pub fn accept(&mut self, stored_chunks: u32, window: u16) -> FileFrame {
+        self.next = stored_chunks.min(self.offer.chunk_count());
+        self.ahead.clear();
+        self.window = clamp_window(window);
+        self.state = TransferState::Active;
+        self.accept_frame()
+    }
+
+    pub fn decline(&mut self) -> FileFrame {
+        self.state = TransferState::Declined;
+        FileFrame::Decline {
+            transfer_id: self.offer.transfer_id,
+        }
+    }
+
+    /// The sender reconnected: tell it where to continue.
+    pub fn on_resume(&mut self) -> Result<FileFrame> {
+        if self.state != TransferState::Active {
+            return Err(FileTransferError::UnexpectedFrame(self.state));
+        }
+        self.since_ack = 0;
+        Ok(self.accept_frame())
+    }
+
+    /// The sender gave up.
+    pub fn on_cancel(&mut self) {
+        if !self.state.is_finished() {
+            self.state = TransferState::Cancelled;
+        }
+    }
+
+    /// Handle chunk `index`. The caller writes the data where `store_at`
+    /// says before sending the acknowledgment.
+    pub fn on_chunk(&mut self, index: u32, len: usize) -> Result<ChunkReceipt> {
+        if self.state != TransferState::Active {
+            return Err(FileTransferError::UnexpectedFrame(self.state));
+        }
+        let count = self.offer.chunk_count();
+        if index >= count {
+            return Err(FileTransferError::Malformed);
+        }
+        let expected = self.offer.chunk_len(index);
+        if len != expected {
+            return Err(FileTransferError::BadChunkLength {
+                index,
+                len,
+                expected,
+            });
+        }
+
+        let in_window = index >= self.next && index < self.next + u32::from(self.window);
+        if !in_window || self.ahead.contains(&index) {
+            // A repeat or a chunk we cannot take: re-ack so the sender
+            // learns what we hold
+            return Ok(ChunkReceipt {
+                store_at: None,
+                ack: Some(self.ack_frame()),
+                complete: false,
+            });
+        }
+
+        let gap = index != self.next;
+        self.ahead.insert(index);
+        while self.ahead.remove(&self.next) {
+            self.next += 1;
+        }
+        let complete = self.next == count;
+        self.since_ack += 1;
+        let ack_every = (self.window / 4).max(1);
+        let ack = if complete || gap || self.since_ack >= ack_every {
+            self.since_ack = 0;
+            Some(self.ack_frame())
+        } else {
+            None
+        };
+        Ok(ChunkReceipt {
+            store_at: Some(self.offer.offset(index)),
+            ack,
+            complete,
+        })
+    }
+
+    /// Every chunk is stored; `hash_ok` is the result of checking the file
+    /// against the offer. Returns the final frame for the sender.
+    pub fn finish(&mut self, hash_ok: bool) -> FileFrame {
+        let transfer_id = self.offer.transfer_id;
+        if hash_ok && self.next == self.offer.chunk_count() {
+            self.state = TransferState::Completed;
+            FileFrame::Complete { transfer_id }
+        } else {
+            self.state = TransferState::Failed;
+            FileFrame::Cancel { transfer_id }
+        }
+    }
+
+    pub fn cancel(&mut self) -> FileFrame {
+        self.state = TransferState::Cancelled;
+        FileFrame::Cancel {
+            transfer_id: self.offer.transfer_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    const ALICE: [u8; 32] = [0xA1; 32];
+    const BOB: [u8; 32] = [0xB0; 32];
+
+    fn offer(size: u64, chunk_size: u32) -> FileOffer {
+        FileOffer {
+            transfer_id: [7; 16],
+            name: "photo.jpg".into(),
+            mime: "image/jpeg".into(),
+            size,
+            chunk_size,
+            hash: [0; 32],
+        }
+    }
+
+    #[test]
+    fn test_windowed_transfer_with_loss_and_resume() {
+        let mut rng = seeded(1);
+        let shared = [9u8; 32];
+        let alice_keys = FileKeys::derive(&shared, &ALICE, &BOB);
+        let bob_keys = FileKeys::derive(&shared, &BOB, &ALICE);
+
+        // 10 chunks, the last one 4 bytes
+        let mut sender = OutgoingTransfer::new(offer(9 * 100 + 4, 100)).unwrap();
+        let sealed = sender.offer_frame().seal(&alice_keys, &mut rng).unwrap();
+        assert_eq!(
+            FileFrame::open(&sealed, &alice_keys),
+            Err(FileTransferError::DecryptionFailed)
+        );
+        let FileFrame::Offer(received) = FileFrame::open(&sealed, &bob_keys).unwrap() else {
+            panic!("expected an offer");
+        };
+        assert_eq!(received.chunk_count(), 10);
+        let mut receiver = IncomingTransfer::new(received).unwrap();
+
+        sender.on_frame(&receiver.accept(0, 4)).unwrap();
+        assert_eq!(sender.next_chunks(0), vec![0, 1, 2, 3]);
+        assert!(sender.next_chunks(0).is_empty(), "window is full");
+
+        // Chunk 1 is lost; 2 and 3 arrive ahead of the gap
+        receiver.on_chunk(0, 100).unwrap();
+        let receipt = receiver.on_chunk(2, 100).unwrap();
+        assert_eq!(receipt.store_at, Some(200));
+        assert!(receipt.ack.is_some(), "a gap is acknowledged at once");
+        let ack = receiver.on_chunk(3, 100).unwrap().ack.unwrap();
+        assert!(matches!(
+            ack,
+            FileFrame::Ack {
+                next: 1,
+                sack: 0b11,
+                ..
+            }
+        ));
+        assert!(sender.on_frame(&ack).unwrap());
+        assert_eq!(sender.acked_chunks(), 1);
+        // Window slides by one; only chunk 1 is retransmitted once it times out
+        assert_eq!(sender.next_chunks(1), vec![4]);
+        assert_eq!(sender.next_chunks(RETRANSMIT_SECS), vec![1]);
+        assert_eq!(sender.retransmissions(), 1);
+
+        let receipt = receiver.on_chunk(1, 100).unwrap();
+        assert_eq!(receiver.stored_chunks(), 4);
+        assert!(receipt.ack.is_some());
+
+        // The connection drops; the sender resumes where the receiver is
+        sender.pause();
+        assert_eq!(sender.state(), TransferState::Paused);
+        assert!(sender.next_chunks(RETRANSMIT_SECS).is_empty());
+        assert!(matches!(sender.resume_frame(), FileFrame::Resume { .. }));
+        sender.on_frame(&receiver.on_resume().unwrap()).unwrap();
+        assert_eq!(sender.next_chunks(40), vec![4, 5, 6, 7]);
+
+        for index in 4..9 {
+            receiver.on_chunk(index, 100).unwrap();
+        }
+        assert_eq!(
+            receiver.on_chunk(9, 100),
+            Err(FileTransferError::BadChunkLength {
+                index: 9,
+                len: 100,
+                expected: 4
+            })
+        );
+        let receipt = receiver.on_chunk(9, 4).unwrap();
+        assert!(receipt.complete);
+        // Complete overtakes the final ack
+        sender.on_frame(&receiver.finish(true)).unwrap();
+        assert_eq!(sender.acked_chunks(), 10);
+        assert_eq!(sender.state(), TransferState::Completed);
+        assert_eq!(receiver.state(), TransferState::Completed);
+    }
+
+    #[test]
+    fn test_resume_from_partial_file_and_rejections() {
+        let mut receiver = IncomingTransfer::new(offer(1000, 100)).unwrap();
+        assert_eq!(
+            receiver.on_chunk(0, 100),
+            Err(FileTransferError::UnexpectedFrame(TransferState::Offered))
+        );
+
+        // Three chunks survived a restart
+        let accept = receiver.accept(3, 2);
+        let mut sender = OutgoingTransfer::new(offer(1000, 100)).unwrap();
+        sender.on_frame(&accept).unwrap();
+        assert_eq!(sender.next_chunks(0), vec![3, 4]);
+
+        // Out of window and repeated chunks are re-acknowledged, not stored
+        let receipt = receiver.on_chunk(7, 100).unwrap();
+        assert_eq!(receipt.store_at, None);
+        assert!(matches!(
+            receipt.ack,
+            Some(FileFrame::Ack {
+                next: 3,
+                sack: 0,
+                ..
+            })
+        ));
+        assert_eq!(receiver.on_chunk(1, 100).unwrap().store_at, None);
+
+        // A failed hash check cancels
+        assert!(matches!(receiver.finish(false), FileFrame::Cancel { .. }));
+        assert_eq!(receiver.state(), TransferState::Failed);
+
+        for (bad, field) in [
+            (
+                FileOffer {
+                    size: 0,
+                    ..offer(1, 1)
+                },
+                "size",
+            ),
+            (
+                FileOffer {
+                    chunk_size: MAX_CHUNK_SIZE + 1,
+                    ..offer(1, 1)
+                },
+                "chunk size",
+            ),
+            (
+                FileOffer {
+                    name: "a\nb".into(),
+                    ..offer(1, 1)
+                },
+                "name",
+            ),
+        ] {
+            assert_eq!(
+                OutgoingTransfer::new(bad).err(),
+                Some(FileTransferError::InvalidOffer(field))
+            );
+        }
+    }
+}
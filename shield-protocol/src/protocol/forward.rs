@@ -0,0 +1,232 @@
+/// Message forwarding — re-package a decrypted message for another conversation.
+///
+/// The forwarded payload is always marked as forwarded. Original sender,
+/// timestamp and message ID are carried only when the caller explicitly keeps
+/// provenance; the default strips them. Attachments are decrypted with their
+/// old per-attachment keys and re-encrypted under fresh ones, so the new
+/// conversation never learns a key that unlocks blobs in the old one.
+///
+/// The returned `MessagePayload` is plaintext; encrypt it for the destination
+/// 1:1 conversation or group exactly like a freshly composed message.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::crypto::encryption::{decrypt_message, encrypt_message, generate_key, EncryptionError};
+use crate::protocol::message::Message;
+
+#[derive(Error, Debug)]
+pub enum ForwardError {
+    #[error("Attachment {index} could not be re-encrypted: {source}")]
+    Attachment {
+        index: usize,
+        source: EncryptionError,
+    },
+    #[error("Payload encoding failed: {0}")]
+    Encoding(#[from] bincode::Error),
+}
+
+/// Whether a forward reveals where the message came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Provenance {
+    /// Drop original sender, timestamp and message ID (default).
+    #[default]
+    Strip,
+    /// Carry original sender, timestamp and message ID. A payload that was
+    /// already forwarded keeps its earlier marker, stripped or not.
+    Keep,
+}
+
+/// An attachment blob encrypted under its own random key. The key is wiped
+/// when the attachment is dropped and left out of `Debug` output.
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct Attachment {
+    #[zeroize(skip)]
+    pub mime_type: String,
+    /// 32-byte XChaCha20-Poly1305 key for `ciphertext`.
+    pub key: [u8; 32],
+    /// Nonce-prefixed ciphertext as produced by `encrypt_message`.
+    #[zeroize(skip)]
+    pub ciphertext: Vec<u8>,
+}
+
+impl Attachment {
+    /// Encrypt `plaintext` under a freshly generated key.
+    pub fn seal(mime_type: String, plaintext: &[u8]) -> Result<Self, EncryptionError> {
+        // Generated in place so no stray copy of the key outlives the struct
+        let mut attachment = Self {
+            mime_type,
+            key: generate_key(),
+            ciphertext: Vec::new(),
+        };
+        attachment.ciphertext = encrypt_message(plaintext, &attachment.key)?;
+        Ok(attachment)
+    }
+
+    /// Decrypt the attachment blob.
+    pub fn open(&self) -> Result<Vec<u8>, EncryptionError> {
+        decrypt_message(&self.ciphertext, &self.key)
+    }
+}
+
+impl fmt::Debug for Attachment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Attachment")
+            .field("mime_type", &self.mime_type)
+            .field("key", &"<redacted>")
+            .field("ciphertext_len", &self.ciphertext.len())
+            .finish()
+    }
+}
+
+/// Forwarding marker. All fields are `None` when provenance was stripped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardInfo {
+    pub original_sender: Option<Vec<u8>>,
+    pub original_timestamp: Option<i64>,
+    pub original_id: Option<String>,
+}
+
+/// Decrypted message body carried inside `Message::encrypted_content`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagePayload {
+    pub text: String,
+    pub attachments: Vec<Attachment>,
+    /// `Some` if this message was forwarded from another conversation.
+    pub forwarded: Option<ForwardInfo>,
+}
+
+impl MessagePayload {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            attachments: Vec::new(),
+            forwarded: None,
+        }
+    }
+
+    pub fn is_forwarded(&self) -> bool {
+        self.forwarded.is_some()
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(data)
+    }
+}
+
+/// Build the payload for forwarding `original` (whose decrypted body is `payload`).
+///
+/// When forwarding something that was itself forwarded with provenance, `Keep`
+/// preserves the earliest known origin rather than the intermediate hop.
+pub fn forward_message(
+    original: &Message,
+    payload: &MessagePayload,
+    provenance: Provenance,
+) -> Result<MessagePayload, ForwardError> {
+    let forwarded = match provenance {
+        Provenance::Strip => ForwardInfo::default(),
+        // An earlier forward decides provenance: if it stripped the origin,
+        // the hop we received it from must not be recorded in its place.
+        Provenance::Keep => match &payload.forwarded {
+            Some(prior) => prior.clone(),
+            None => ForwardInfo {
+                original_sender: Some(original.sender_public_key.clone()),
+                original_timestamp: Some(original.timestamp),
+                original_id: Some(original.id.clone()),
+            },
+        },
+    };
+
+    let mut attachments = Vec::with_capacity(payload.attachments.len());
+    for (index, attachment) in payload.attachments.iter().enumerate() {
+        let mut plaintext = attachment
+            .open()
+            .map_err(|source| ForwardError::Attachment { index, source })?;
+        let resealed = Attachment::seal(attachment.mime_type.clone(), &plaintext);
+        plaintext.zeroize();
+        attachments.push(resealed.map_err(|source| ForwardError::Attachment { index, source })?);
+    }
+
+    Ok(MessagePayload {
+        text: payload.text.clone(),
+        attachments,
+        forwarded: Some(forwarded),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn original_with_attachment() -> (Message, MessagePayload) {
+        let msg = Message::new(vec![1, 2, 3], vec![4, 5, 6], vec![], vec![]);
+        let mut payload = MessagePayload::new("hello".to_string());
+        payload
+            .attachments
+            .push(Attachment::seal("image/png".to_string(), b"png bytes").unwrap());
+        (msg, payload)
+    }
+
+    #[test]
+    fn test_forward_strips_provenance_and_rekeys() {
+        let (msg, payload) = original_with_attachment();
+        let fwd = forward_message(&msg, &payload, Provenance::Strip).unwrap();
+
+        assert_eq!(fwd.forwarded, Some(ForwardInfo::default()));
+        assert_eq!(fwd.text, "hello");
+        assert_ne!(fwd.attachments[0].key, payload.attachments[0].key);
+        assert_eq!(fwd.attachments[0].open().unwrap(), b"png bytes");
+        // Old key cannot open the new blob.
+        assert!(
+            decrypt_message(&fwd.attachments[0].ciphertext, &payload.attachments[0].key).is_err()
+        );
+    }
+
+    #[test]
+    fn test_forward_keeps_earliest_provenance() {
+        let (msg, payload) = original_with_attachment();
+        let first = forward_message(&msg, &payload, Provenance::Keep).unwrap();
+        let info = first.forwarded.clone().unwrap();
+        assert_eq!(info.original_sender, Some(vec![1, 2, 3]));
+        assert_eq!(info.original_id, Some(msg.id.clone()));
+
+        let hop = Message::new(vec![9, 9, 9], vec![], vec![], vec![]);
+        let second = forward_message(&hop, &first, Provenance::Keep).unwrap();
+        assert_eq!(second.forwarded, Some(info));
+    }
+
+    #[test]
+    fn test_forward_keep_after_strip_stays_stripped() {
+        let (msg, payload) = original_with_attachment();
+        let stripped = forward_message(&msg, &payload, Provenance::Strip).unwrap();
+
+        let hop = Message::new(vec![9, 9, 9], vec![], vec![], vec![]);
+        let kept = forward_message(&hop, &stripped, Provenance::Keep).unwrap();
+        assert_eq!(kept.forwarded, Some(ForwardInfo::default()));
+    }
+
+    #[test]
+    fn test_forward_corrupt_attachment_fails() {
+        let (msg, mut payload) = original_with_attachment();
+        payload.attachments[0].ciphertext[30] ^= 0xFF;
+        let err = forward_message(&msg, &payload, Provenance::Strip).unwrap_err();
+        assert!(matches!(err, ForwardError::Attachment { index: 0, .. }));
+    }
+
+    #[test]
+    fn test_attachment_key_hidden_and_wiped() {
+        let mut attachment = Attachment::seal("text/plain".to_string(), b"notes").unwrap();
+        let debug = format!("{:?}", attachment);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains(&format!("{:?}", attachment.key)));
+
+        attachment.zeroize();
+        assert_eq!(attachment.key, [0u8; 32]);
+        assert_eq!(attachment.mime_type, "text/plain");
+    }
+}
@@ -0,0 +1,658 @@
+/// Private mailbox checks against store-and-forward relays.
+///
+/// Asking a relay "is there mail for mailbox X?" tells it exactly who is
+/// online and when, which is enough to link a client to its mailbox across
+/// relays and sessions. Relays that speak [`MAILBOX_INDEX_PROTOCOL`] publish
+/// a [`MailboxIndex`]: mailboxes are hashed into `2^bucket_bits` buckets, and
+/// each bucket has a fixed-size [`BucketRecord`] listing short tags of the
+/// mailboxes in it that hold mail. A client checks its mailbox in one of
+/// three ways ([`CheckMode`]):
+///
+/// - **Pir**: two-server XOR private information retrieval. The client sends
+///   each of two relays that mirror the same index a random-looking bucket
+///   selection ([`PirQuery`]); XORing the two [`PirAnswer`]s yields its
+///   bucket's record. Neither relay learns anything about the bucket unless
+///   the two collude. Costs `2^bucket_bits / 8` bytes up and one record down
+///   per relay, and waits on the slower of the two.
+/// - **Bucketed**: the client fetches its whole bucket record from one relay.
+///   The relay learns the bucket, which every other mailbox hashed there
+///   shares. One small request, one record down.
+/// - **Plain**: an authenticated [`PlainPoll`] naming the mailbox. Used when
+///   no relay serves the index, or when the bucket overflowed and the record
+///   cannot answer.
+///
+/// More `bucket_bits` means fewer mailboxes per bucket: less overflow, but a
+/// smaller anonymity set for bucketed checks and a bigger PIR query.
+///
+/// [`check_request`] picks the best mode the available relays allow.
+use crate::crypto::signing::{sign_data, verify_signature};
+use crate::rng::SecureRng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MailboxError {
+    #[error("Malformed mailbox message")]
+    Malformed,
+    #[error("Unsupported mailbox message version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid mailbox poll configuration")]
+    InvalidConfig,
+    #[error("Mailbox poll signature is invalid")]
+    BadSignature,
+    #[error("Mailbox poll timestamp is outside the accepted window")]
+    Stale,
+    #[error("Relays answered from different mailbox indexes")]
+    IndexMismatch,
+    #[error("Signing failed: {0}")]
+    Signing(String),
+}
+
+/// Relay protocol version that adds the mailbox index.
+pub const MAILBOX_INDEX_PROTOCOL: u16 = 2;
+
+/// Mailbox message wire version.
+pub const MAILBOX_VERSION: u8 = 1;
+
+/// Most buckets an index may have, as a power of two.
+pub const MAX_BUCKET_BITS: u8 = 16;
+
+/// Size of one bucket record, whatever its content.
+pub const BUCKET_RECORD_LEN: usize = 256;
+
+/// Bytes of mailbox ID listed in a bucket record.
+pub const MAILBOX_TAG_LEN: usize = 8;
+
+/// `[version][count]`
+const RECORD_HEADER_LEN: usize = 2;
+
+/// Tags that fit in one record.
+pub const MAX_TAGS_PER_BUCKET: usize = (BUCKET_RECORD_LEN - RECORD_HEADER_LEN) / MAILBOX_TAG_LEN;
+
+/// Count byte of a bucket with more mail-holding mailboxes than fit.
+const OVERFLOW: u8 = 0xFF;
+
+/// `[version][public key 32][timestamp u64 BE][signature 64]`
+pub const PLAIN_POLL_LEN: usize = 1 + 32 + 8 + 64;
+
+const MAILBOX_CONTEXT: &str = "ShieldMessenger-Mailbox-v1";
+const POLL_CONTEXT: &[u8] = b"ShieldMessenger-MailboxPoll-v1";
+const INDEX_CONTEXT: &str = "ShieldMessenger-MailboxIndex-v1";
+
+/// Address of a mailbox at a relay, derived from the mailbox owner's
+/// Ed25519 key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MailboxId(pub [u8; 32]);
+
+impl MailboxId {
+    pub fn from_public_key(public_key: &[u8; 32]) -> Self {
+        Self(blake3::derive_key(MAILBOX_CONTEXT, public_key))
+    }
+
+    /// Bucket of this mailbox in an index of `2^bucket_bits` buckets.
+    pub fn bucket(&self, bucket_bits: u8) -> u32 {
+        if bucket_bits == 0 {
+            return 0;
+        }
+        let mut prefix = [0u8; 4];
+        prefix.copy_from_slice(&self.0[..4]);
+        u32::from_be_bytes(prefix) >> (32 - bucket_bits as u32)
+    }
+
+    /// Short tag listed in bucket records.
+    pub fn tag(&self) -> [u8; MAILBOX_TAG_LEN] {
+        let mut tag = [0u8; MAILBOX_TAG_LEN];
+        tag.copy_from_slice(&self.0[32 - MAILBOX_TAG_LEN..]);
+        tag
+    }
+}
+
+/// How the client checks its mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckMode {
+    Plain,
+    Bucketed,
+    Pir,
+}
+
+/// Client-side mailbox polling settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailboxPollConfig {
+    /// Preferred mode; falls back when the relays cannot serve it.
+    pub mode: CheckMode,
+    /// The index has `2^bucket_bits` buckets; must match the relays'.
+    pub bucket_bits: u8,
+    /// Time between checks: lower means faster delivery and more traffic.
+    pub poll_interval_ms: u64,
+}
+
+impl Default for MailboxPollConfig {
+    fn default() -> Self {
+        Self {
+            mode: CheckMode::Bucketed,
+            bucket_bits: 8,
+            poll_interval_ms: 60_000,
+        }
+    }
+}
+
+impl MailboxPollConfig {
+    pub fn validate(&self) -> Result<(), MailboxError> {
+        if self.bucket_bits > MAX_BUCKET_BITS || self.poll_interval_ms == 0 {
+            return Err(MailboxError::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    /// Best mode up to the preferred one, given how many relays serve the
+    /// mailbox index: PIR needs two, bucketed checks one.
+    pub fn effective_mode(&self, index_relays: usize) -> CheckMode {
+        match self.mode {
+            CheckMode::Pir if index_relays >= 2 => CheckMode::Pir,
+            CheckMode::Pir | CheckMode::Bucketed if index_relays >= 1 => CheckMode::Bucketed,
+            _ => CheckMode::Plain,
+        }
+    }
+}
+
+/// What an index check found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckResult {
+    HasMail,
+    Empty,
+    /// The bucket overflowed; fall back to a [`PlainPoll`].
+    Unknown,
+}
+
+/// Mail-holding mailboxes of one bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BucketRecord {
+    tags: Vec<[u8; MAILBOX_TAG_LEN]>,
+    overflow: bool,
+}
+
+impl BucketRecord {
+    pub fn check(&self, mailbox: &MailboxId) -> CheckResult {
+        if self.tags.contains(&mailbox.tag()) {
+            CheckResult::HasMail
+        } else if self.overflow {
+            CheckResult::Unknown
+        } else {
+            CheckResult::Empty
+        }
+    }
+
+    /// `[version][count][tags][zero padding]`; always [`BUCKET_RECORD_LEN`]
+    /// bytes so that records can be XORed and look alike on the wire.
+    pub fn to_bytes(&self) -> [u8; BUCKET_RECORD_LEN] {
+        let mut out = [0u8; BUCKET_RECORD_LEN];
+        out[0] = MAILBOX_VERSION;
+        if self.overflow {
+            out[1] = OVERFLOW;
+            return out;
+        }
+        out[1] = self.tags.len() as u8;
+        for (i, tag) in self.tags.iter().enumerate() {
+            let start = RECORD_HEADER_LEN + i * MAILBOX_TAG_LEN;
+            out[start..start + MAILBOX_TAG_LEN].copy_from_slice(tag);
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MailboxError> {
+        if data.len() != BUCKET_RECORD_LEN {
+            return Err(MailboxError::Malformed);
+        }
+        if data[0] != MAILBOX_VERSION {
+            return Err(MailboxError::UnsupportedVersion(data[0]));
+        }
+        if data[1] == OVERFLOW {
+            return Ok(Self {
+                tags: Vec::new(),
+                overflow: true,
+            });
+        }
+        let count = data[1] as usize;
+        if count > MAX_TAGS_PER_BUCKET {
+            return Err(MailboxError::Malformed);
+        }
+        let end = RECORD_HEADER_LEN + count * MAILBOX_TAG_LEN;
+        if data[end..].iter().any(|&b| b != 0) {
+            return Err(MailboxError::Malformed);
+        }
+        let tags = data[RECORD_HEADER_LEN..end]
+            .chunks_exact(MAILBOX_TAG_LEN)
+            .map(|chunk| {
+                let mut tag = [0u8; MAILBOX_TAG_LEN];
+                tag.copy_from_slice(chunk);
+                tag
+            })
+            .collect();
+        Ok(Self {
+            tags,
+            overflow: false,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Relay side
+// ---------------------------------------------------------------------------
+
+/// The relay's bucketed view of which mailboxes hold mail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailboxIndex {
+    bucket_bits: u8,
+    records: Vec<[u8; BUCKET_RECORD_LEN]>,
+    digest: [u8; 32],
+}
+
+impl MailboxIndex {
+    /// Index over the mailboxes that currently hold mail.
+    pub fn build(
+        bucket_bits: u8,
+        mailboxes: impl IntoIterator<Item = MailboxId>,
+    ) -> Result<Self, MailboxError> {
+        if bucket_bits > MAX_BUCKET_BITS {
+            return Err(MailboxError::InvalidConfig);
+        }
+        let mut buckets = vec![BucketRecord::default(); 1 << bucket_bits];
+        for mailbox in mailboxes {
+            let record = &mut buckets[mailbox.bucket(bucket_bits) as usize];
+            let tag = mailbox.tag();
+            if record.overflow || record.tags.contains(&tag) {
+                continue;
+            }
+            if record.tags.len() == MAX_TAGS_PER_BUCKET {
+                record.tags.clear();
+                record.overflow = true;
+            } else {
+                record.tags.push(tag);
+            }
+        }
+        // Sorted, so relays mirroring the same mailboxes build identical records
+        for record in &mut buckets {
+            record.tags.sort_unstable();
+        }
+        let records: Vec<[u8; BUCKET_RECORD_LEN]> =
+            buckets.iter().map(BucketRecord::to_bytes).collect();
+        let mut hasher = blake3::Hasher::new_derive_key(INDEX_CONTEXT);
+        hasher.update(&[bucket_bits]);
+        for record in &records {
+            hasher.update(record);
+        }
+        Ok(Self {
+            bucket_bits,
+            records,
+            digest: *hasher.finalize().as_bytes(),
+        })
+    }
+
+    pub fn bucket_bits(&self) -> u8 {
+        self.bucket_bits
+    }
+
+    /// Identifies the index content, so clients can tell two relays agree.
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.digest
+    }
+
+    /// Record for a bucketed check.
+    pub fn record(&self, bucket: u32) -> Option<&[u8; BUCKET_RECORD_LEN]> {
+        self.records.get(bucket as usize)
+    }
+
+    /// XOR of the records a PIR query selects.
+    pub fn answer(&self, query: &PirQuery) -> Result<PirAnswer, MailboxError> {
+        if query.bucket_bits != self.bucket_bits {
+            return Err(MailboxError::InvalidConfig);
+        }
+        let mut record = [0u8; BUCKET_RECORD_LEN];
+        for (bucket, data) in self.records.iter().enumerate() {
+            if query.selects(bucket) {
+                record.iter_mut().zip(data).for_each(|(r, d)| *r ^= d);
+            }
+        }
+        Ok(PirAnswer {
+            index_digest: self.digest,
+            record,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PIR
+// ---------------------------------------------------------------------------
+
+/// One relay's share of a PIR lookup: a bitset over all buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PirQuery {
+    pub bucket_bits: u8,
+    selection: Vec<u8>,
+}
+
+impl PirQuery {
+    /// The two queries for `bucket`. Each alone is uniformly random; they
+    /// differ only in the bit of `bucket`.
+    pub fn pair(bucket_bits: u8, bucket: u32, rng: &mut impl SecureRng) -> [Self; 2] {
+        let mut selection = vec![0u8; selection_len(bucket_bits)];
+        rng.fill_bytes(&mut selection);
+        if bucket_bits < 3 {
+            // Clear the padding bits past the last bucket
+            selection[0] &= (1u8 << (1 << bucket_bits)) - 1;
+        }
+        let first = Self {
+            bucket_bits,
+            selection: selection.clone(),
+        };
+        selection[bucket as usize / 8] ^= 1 << (bucket % 8);
+        [
+            first,
+            Self {
+                bucket_bits,
+                selection,
+            },
+        ]
+    }
+
+    fn selects(&self, bucket: usize) -> bool {
+        self.selection[bucket / 8] & (1 << (bucket % 8)) != 0
+    }
+
+    /// `[version][bucket_bits][bitset]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 + self.selection.len());
+        out.push(MAILBOX_VERSION);
+        out.push(self.bucket_bits);
+        out.extend_from_slice(&self.selection);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MailboxError> {
+        if data.len() < 2 {
+            return Err(MailboxError::Malformed);
+        }
+        if data[0] != MAILBOX_VERSION {
+            return Err(MailboxError::UnsupportedVersion(data[0]));
+        }
+        let bucket_bits = data[1];
+        if bucket_bits > MAX_BUCKET_BITS || data.len() != 2 + selection_len(bucket_bits) {
+            return Err(MailboxError::Malformed);
+        }
+        Ok(Self {
+            bucket_bits,
+            selection: data[2..].to_vec(),
+        })
+    }
+}
+
+fn selection_len(bucket_bits: u8) -> usize {
+    (1usize << bucket_bits).div_ceil(8)
+}
+
+/// A relay's reply to a [`PirQuery`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PirAnswer {
+    pub index_digest: [u8; 32],
+    pub record: [u8; BUCKET_RECORD_LEN],
+}
+
+impl PirAnswer {
+    /// `[version][index digest 32][record]`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 32 + BUCKET_RECORD_LEN);
+        out.push(MAILBOX_VERSION);
+        out.extend_from_slice(&self.index_digest);
+        out.extend_from_slice(&self.record);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MailboxError> {
+        if data.is_empty() {
+            return Err(MailboxError::Malformed);
+        }
+        if data[0] != MAILBOX_VERSION {
+            return Err(MailboxError::UnsupportedVersion(data[0]));
+        }
+        if data.len() != 1 + 32 + BUCKET_RECORD_LEN {
+            return Err(MailboxError::Malformed);
+        }
+        let mut index_digest = [0u8; 32];
+        index_digest.copy_from_slice(&data[1..33]);
+        let mut record = [0u8; BUCKET_RECORD_LEN];
+        record.copy_from_slice(&data[33..]);
+        Ok(Self {
+            index_digest,
+            record,
+        })
+    }
+
+    /// Recover the bucket record from both relays' answers.
+    pub fn combine(&self, other: &PirAnswer) -> Result<BucketRecord, MailboxError> {
+        if self.index_digest != other.index_digest {
+            return Err(MailboxError::IndexMismatch);
+        }
+        let mut record = self.record;
+        record
+            .iter_mut()
+            .zip(&other.record)
+            .for_each(|(r, o)| *r ^= o);
+        BucketRecord::from_bytes(&record)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Plain polling
+// ---------------------------------------------------------------------------
+
+/// Authenticated "any mail for me?" request that names the mailbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainPoll {
+    pub public_key: [u8; 32],
+    pub timestamp: u64,
+    pub signature: [u8; 64],
+}
+
+impl PlainPoll {
+    /// Poll signed with the mailbox owner's Ed25519 seed.
+    pub fn create(
+        signing_key: &[u8],
+        public_key: &[u8; 32],
+        now: u64,
+    ) -> Result<Self, MailboxError> {
+        let signature = sign_data(&poll_signed_bytes(public_key, now), signing_key)
+            .map_err(|e| MailboxError::Signing(e.to_string()))?;
+        Ok(Self {
+            public_key: *public_key,
+            timestamp: now,
+            signature,
+        })
+    }
+
+    /// Relay-side check; returns the mailbox being polled.
+    pub fn verify(&self, now: u64, max_clock_skew_secs: u64) -> Result<MailboxId, MailboxError> {
+        if self.timestamp.abs_diff(now) > max_clock_skew_secs {
+            return Err(MailboxError::Stale);
+        }
+        let data = poll_signed_bytes(&self.public_key, self.timestamp);
+        match verify_signature(&data, &self.signature, &self.public_key) {
+            Ok(true) => Ok(MailboxId::from_public_key(&self.public_key)),
+            _ => Err(MailboxError::BadSignature),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; PLAIN_POLL_LEN] {
+        let mut out = [0u8; PLAIN_POLL_LEN];
+        out[0] = MAILBOX_VERSION;
+        out[1..33].copy_from_slice(&self.public_key);
+        out[33..41].copy_from_slice(&self.timestamp.to_be_bytes());
+        out[41..].copy_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MailboxError> {
+        if data.is_empty() {
+            return Err(MailboxError::Malformed);
+        }
+        if data[0] != MAILBOX_VERSION {
+            return Err(MailboxError::UnsupportedVersion(data[0]));
+        }
+        if data.len() != PLAIN_POLL_LEN {
+            return Err(MailboxError::Malformed);
+        }
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(&data[1..33]);
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&data[33..41]);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&data[41..]);
+        Ok(Self {
+            public_key,
+            timestamp: u64::from_be_bytes(timestamp),
+            signature,
+        })
+    }
+}
+
+fn poll_signed_bytes(public_key: &[u8; 32], timestamp: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(POLL_CONTEXT.len() + 1 + 32 + 8);
+    data.extend_from_slice(POLL_CONTEXT);
+    data.push(MAILBOX_VERSION);
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data
+}
+
+// ---------------------------------------------------------------------------
+// Client
+// ---------------------------------------------------------------------------
+
+/// What to send for one mailbox check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckRequest {
+    Plain(PlainPoll),
+    Bucketed {
+        bucket_bits: u8,
+        bucket: u32,
+    },
+    /// One query for each of two relays serving the same index.
+    Pir([PirQuery; 2]),
+}
+
+/// Build the next check of the mailbox owned by `public_key`, in the best
+/// mode `index_relays` relays serving the mailbox index allow.
+pub fn check_request(
+    config: &MailboxPollConfig,
+    index_relays: usize,
+    signing_key: &[u8],
+    public_key: &[u8; 32],
+    now: u64,
+    rng: &mut impl SecureRng,
+) -> Result<CheckRequest, MailboxError> {
+    config.validate()?;
+    let bucket = MailboxId::from_public_key(public_key).bucket(config.bucket_bits);
+    Ok(match config.effective_mode(index_relays) {
+        CheckMode::Pir => CheckRequest::Pir(PirQuery::pair(config.bucket_bits, bucket, rng)),
+        CheckMode::Bucketed => CheckRequest::Bucketed {
+            bucket_bits: config.bucket_bits,
+            bucket,
+        },
+        CheckMode::Plain => CheckRequest::Plain(PlainPoll::create(signing_key, public_key, now)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::rng::seeded;
+
+    fn mailbox(seed: u8) -> MailboxId {
+        MailboxId::from_public_key(&[seed; 32])
+    }
+
+    #[test]
+    fn test_pir_recovers_bucket_without_revealing_it() {
+        let mut rng = seeded(3);
+        let bits = 4;
+        let with_mail: Vec<MailboxId> = (1..40).map(mailbox).collect();
+        let index = MailboxIndex::build(bits, with_mail.iter().copied()).unwrap();
+        let mirror = MailboxIndex::build(bits, with_mail.iter().rev().copied()).unwrap();
+        assert_eq!(index.digest(), mirror.digest());
+
+        for target in [mailbox(7), mailbox(200)] {
+            let bucket = target.bucket(bits);
+            let [a, b] = PirQuery::pair(bits, bucket, &mut rng);
+            // The queries differ in exactly the target bucket
+            let differing: Vec<usize> = (0..1 << bits)
+                .filter(|&i| a.selects(i) != b.selects(i))
+                .collect();
+            assert_eq!(differing, vec![bucket as usize]);
+
+            let a = PirQuery::from_bytes(&a.to_bytes()).unwrap();
+            let answer_a = PirAnswer::from_bytes(&index.answer(&a).unwrap().to_bytes()).unwrap();
+            let answer_b = mirror.answer(&b).unwrap();
+            let record = answer_a.combine(&answer_b).unwrap();
+            assert_eq!(record.to_bytes(), *index.record(bucket).unwrap());
+            let expected = if with_mail.contains(&target) {
+                CheckResult::HasMail
+            } else {
+                CheckResult::Empty
+            };
+            assert_eq!(record.check(&target), expected);
+        }
+
+        // Relays with diverging indexes are caught
+        let stale = MailboxIndex::build(bits, [mailbox(1)]).unwrap();
+        let [a, b] = PirQuery::pair(bits, 0, &mut rng);
+        assert_eq!(
+            index
+                .answer(&a)
+                .unwrap()
+                .combine(&stale.answer(&b).unwrap()),
+            Err(MailboxError::IndexMismatch)
+        );
+        assert_eq!(
+            PirQuery::from_bytes(&[1, 4, 0]),
+            Err(MailboxError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_fallback_to_plain_polling() {
+        let config = MailboxPollConfig {
+            mode: CheckMode::Pir,
+            ..MailboxPollConfig::default()
+        };
+        assert_eq!(config.effective_mode(2), CheckMode::Pir);
+        assert_eq!(config.effective_mode(1), CheckMode::Bucketed);
+        assert_eq!(config.effective_mode(0), CheckMode::Plain);
+        let bad = MailboxPollConfig {
+            bucket_bits: MAX_BUCKET_BITS + 1,
+            ..config
+        };
+        assert_eq!(bad.validate(), Err(MailboxError::InvalidConfig));
+
+        // An overflowed bucket cannot rule anything out
+        let crowded = (0..=255u8).chain(0..=255).map(|i| MailboxId([i; 32]));
+        let index = MailboxIndex::build(0, crowded).unwrap();
+        let record = BucketRecord::from_bytes(index.record(0).unwrap()).unwrap();
+        assert_eq!(record.check(&mailbox(1)), CheckResult::Unknown);
+
+        let (public, private) = generate_keypair_with_rng(&mut seeded(5));
+        let now = 1_700_000_000;
+        let request = check_request(&config, 0, &private, &public, now, &mut seeded(6)).unwrap();
+        let CheckRequest::Plain(poll) = request else {
+            panic!("expected a plain poll");
+        };
+        let poll = PlainPoll::from_bytes(&poll.to_bytes()).unwrap();
+        assert_eq!(
+            poll.verify(now + 10, 60),
+            Ok(MailboxId::from_public_key(&public))
+        );
+        assert_eq!(poll.verify(now + 61, 60), Err(MailboxError::Stale));
+        let mut forged = poll.clone();
+        forged.timestamp += 1;
+        assert_eq!(forged.verify(now, 60), Err(MailboxError::BadSignature));
+    }
+}
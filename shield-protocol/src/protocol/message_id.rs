@@ -0,0 +1,389 @@
+/// Deterministic message IDs, one scheme for direct and group messages.
+///
+/// ```text
+/// id = BLAKE3-derive-key("ShieldMessenger-MessageId-v1",
+///                        sender_ed25519_pubkey || counter (u64 BE) || salt)
+/// ```
+///
+/// - **Direct messages:** `counter` is the sender's per-conversation send
+///   counter and `salt` 16 random bytes held next to it in a
+///   [`MessageIdSequence`]. The salt is redrawn whenever the counter could
+///   repeat (fresh install, restored backup), so a reset counter never
+///   reissues an old ID.
+/// - **Group messages:** `counter` is the op's Lamport clock and `salt` the
+///   group id followed by the op nonce ([`MessageId::group`]).
+///
+/// The ID depends on neither the plaintext nor the clock, so a retried send
+/// keeps its ID and receipts, reactions and replies line up across devices.
+/// Its text form is `m1-` followed by 64 hex digits.
+///
+/// # Collision audit
+///
+/// A collision (one ID, two different messages) can only come from a counter
+/// reused under the same salt or from a peer forging someone else's ID. The
+/// first silently replaces or shadows a message; the second suppresses it.
+/// [`CollisionAudit`] remembers what each recent ID was claimed for and calls
+/// its hooks when a second, different claim shows up. Redelivery of the same
+/// message is reported as a duplicate, not a collision.
+///
+/// # Migrating existing IDs
+///
+/// IDs already stored (random UUIDs, the app's SHA-256 hex IDs, group IDs
+/// from [`generate_msg_id`](crate::crdt::generate_msg_id)) stay valid: they
+/// are only ever compared, never recomputed. Keep them as they are, issue
+/// new IDs from this scheme, and where a fixed 32-byte key is needed pass
+/// stored strings through [`MessageId::normalize`], which maps anything that
+/// is not an `m1-` ID into its own domain with [`MessageId::from_legacy`].
+use crate::rng::SecureRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MessageIdError {
+    #[error("Malformed message ID")]
+    Malformed,
+    #[error("Unsupported message ID sequence version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Message ID sequence encoding failed: {0}")]
+    Encoding(String),
+    #[error("Message ID counter exhausted; reseed the sequence")]
+    Exhausted,
+}
+
+/// Message ID scheme and sequence wire version.
+pub const MESSAGE_ID_VERSION: u8 = 1;
+
+/// Per-sequence salt length.
+pub const MESSAGE_ID_SALT_LEN: usize = 16;
+
+const MESSAGE_ID_CONTEXT: &str = "ShieldMessenger-MessageId-v1";
+const LEGACY_ID_CONTEXT: &str = "ShieldMessenger-MessageId-Legacy-v1";
+const TEXT_PREFIX: &str = "m1-";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MessageId(pub [u8; 32]);
+
+impl MessageId {
+    pub fn derive(sender_pubkey: &[u8; 32], counter: u64, salt: &[u8]) -> Self {
+        let mut material = Vec::with_capacity(32 + 8 + salt.len());
+        material.extend_from_slice(sender_pubkey);
+        material.extend_from_slice(&counter.to_be_bytes());
+        material.extend_from_slice(salt);
+        Self(blake3::derive_key(MESSAGE_ID_CONTEXT, &material))
+    }
+
+    /// ID of a group message authored as op `(lamport, op_nonce)`.
+    pub fn group(
+        author_pubkey: &[u8; 32],
+        group_id: &[u8; 32],
+        lamport: u64,
+        op_nonce: u64,
+    ) -> Self {
+        let mut salt = [0u8; 40];
+        salt[..32].copy_from_slice(group_id);
+        salt[32..].copy_from_slice(&op_nonce.to_be_bytes());
+        Self::derive(author_pubkey, lamport, &salt)
+    }
+
+    /// Whether this ID is the one `sender_pubkey` derives for `counter` and
+    /// `salt`, e.g. when a peer sends the inputs along.
+    pub fn verify(&self, sender_pubkey: &[u8; 32], counter: u64, salt: &[u8]) -> bool {
+        *self == Self::derive(sender_pubkey, counter, salt)
+    }
+
+    /// Fixed-size key for an ID stored before this scheme. Never equal to a
+    /// derived ID.
+    pub fn from_legacy(legacy: &str) -> Self {
+        Self(blake3::derive_key(LEGACY_ID_CONTEXT, legacy.as_bytes()))
+    }
+
+    /// Parse an `m1-` ID, mapping anything else with [`Self::from_legacy`].
+    pub fn normalize(id: &str) -> Self {
+        id.parse().unwrap_or_else(|_| Self::from_legacy(id))
+    }
+}
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", TEXT_PREFIX, hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MessageId({})", self)
+    }
+}
+
+impl FromStr for MessageId {
+    type Err = MessageIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix(TEXT_PREFIX)
+            .ok_or(MessageIdError::Malformed)?;
+        let mut id = [0u8; 32];
+        hex::decode_to_slice(digits, &mut id).map_err(|_| MessageIdError::Malformed)?;
+        Ok(Self(id))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sequence
+// ---------------------------------------------------------------------------
+
+/// Sender-side counter and salt for one conversation's direct messages.
+/// Persist it (see [`to_bytes`](Self::to_bytes)) after every
+/// [`issue`](Self::issue), before the message leaves the device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageIdSequence {
+    sender_pubkey: [u8; 32],
+    salt: [u8; MESSAGE_ID_SALT_LEN],
+    next_counter: u64,
+}
+
+impl MessageIdSequence {
+    pub fn new(sender_pubkey: [u8; 32], rng: &mut impl SecureRng) -> Self {
+        let mut salt = [0u8; MESSAGE_ID_SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        Self {
+            sender_pubkey,
+            salt,
+            next_counter: 0,
+        }
+    }
+
+    /// Issue the next ID and its counter.
+    pub fn issue(&mut self) -> Result<(u64, MessageId), MessageIdError> {
+        let counter = self.next_counter;
+        self.next_counter = counter.checked_add(1).ok_or(MessageIdError::Exhausted)?;
+        Ok((
+            counter,
+            MessageId::derive(&self.sender_pubkey, counter, &self.salt),
+        ))
+    }
+
+    /// Draw a new salt and restart the counter. Call whenever the persisted
+    /// counter may be behind IDs already issued, e.g. after restoring a
+    /// backup.
+    pub fn reseed(&mut self, rng: &mut impl SecureRng) {
+        rng.fill_bytes(&mut self.salt);
+        self.next_counter = 0;
+    }
+
+    pub fn salt(&self) -> &[u8; MESSAGE_ID_SALT_LEN] {
+        &self.salt
+    }
+
+    pub fn next_counter(&self) -> u64 {
+        self.next_counter
+    }
+
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MessageIdError> {
+        let body = bincode::serialize(self).map_err(|e| MessageIdError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(MESSAGE_ID_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MessageIdError> {
+        let (&version, body) = bytes.split_first().ok_or(MessageIdError::Malformed)?;
+        if version != MESSAGE_ID_VERSION {
+            return Err(MessageIdError::UnsupportedVersion(version));
+        }
+        bincode::deserialize(body).map_err(|e| MessageIdError::Encoding(e.to_string()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Collision audit
+// ---------------------------------------------------------------------------
+
+/// What a message ID was seen for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdClaim {
+    pub sender_pubkey: [u8; 32],
+    /// Hash of the message's plaintext (or, for groups, its ciphertext).
+    pub content_digest: [u8; 32],
+}
+
+/// Two different messages under one ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdCollision {
+    pub id: MessageId,
+    pub first: IdClaim,
+    pub second: IdClaim,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdAudit {
+    New,
+    /// Same ID, same sender, same content: a retry or redelivery.
+    Duplicate,
+    Collision(IdCollision),
+}
+
+type CollisionHook = Box<dyn FnMut(&IdCollision) + Send>;
+
+/// Bounded memory of recent message IDs and what they were claimed for.
+pub struct CollisionAudit {
+    claims: HashMap<MessageId, IdClaim>,
+    order: VecDeque<MessageId>,
+    capacity: usize,
+    hooks: Vec<CollisionHook>,
+}
+
+impl CollisionAudit {
+    /// Remember up to `capacity` IDs, forgetting the oldest first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            claims: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Call `hook` on every collision, e.g. to log it or flag the sender.
+    pub fn on_collision(&mut self, hook: impl FnMut(&IdCollision) + Send + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Record `claim` for `id` and report whether it clashes with an earlier
+    /// one. The first claim is kept on a collision.
+    pub fn observe(&mut self, id: MessageId, claim: IdClaim) -> IdAudit {
+        if let Some(first) = self.claims.get(&id) {
+            if *first == claim {
+                return IdAudit::Duplicate;
+            }
+            let collision = IdCollision {
+                id,
+                first: *first,
+                second: claim,
+            };
+            log::warn!("Message ID collision on {}", id);
+            for hook in &mut self.hooks {
+                hook(&collision);
+            }
+            return IdAudit::Collision(collision);
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.claims.remove(&oldest);
+            }
+        }
+        self.claims.insert(id, claim);
+        self.order.push_back(id);
+        IdAudit::New
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl fmt::Debug for CollisionAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CollisionAudit")
+            .field("ids", &self.order.len())
+            .field("capacity", &self.capacity)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_ids_are_deterministic_and_survive_reseed() {
+        let mut rng = seeded(1);
+        let alice = [1u8; 32];
+        let mut seq = MessageIdSequence::new(alice, &mut rng);
+        let (c0, id0) = seq.issue().unwrap();
+        let (c1, id1) = seq.issue().unwrap();
+        assert_eq!((c0, c1), (0, 1));
+        assert_ne!(id0, id1);
+        assert!(id0.verify(&alice, 0, seq.salt()));
+        assert!(!id0.verify(&[2u8; 32], 0, seq.salt()));
+
+        // A persisted sequence continues where it stopped
+        let mut restored = MessageIdSequence::from_bytes(&seq.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.issue().unwrap().0, 2);
+
+        // Restoring an old copy and reseeding never reissues an ID
+        let mut stale = MessageIdSequence::from_bytes(&seq.to_bytes().unwrap()).unwrap();
+        stale.reseed(&mut rng);
+        let (counter, reissued) = stale.issue().unwrap();
+        assert_eq!(counter, 0);
+        assert_ne!(reissued, id0);
+
+        // Text form round-trips; legacy IDs live in their own domain
+        assert_eq!(id0.to_string().len(), 3 + 64);
+        assert_eq!(id0.to_string().parse::<MessageId>(), Ok(id0));
+        assert_eq!(MessageId::normalize(&id0.to_string()), id0);
+        let uuid = "0b6f3f5e-8a59-4f39-a1de-8c1b4c4ab0a1";
+        assert_eq!(MessageId::normalize(uuid), MessageId::from_legacy(uuid));
+        assert_eq!(
+            hex::encode(id0.0).parse::<MessageId>(),
+            Err(MessageIdError::Malformed)
+        );
+
+        let gid = [9u8; 32];
+        assert_eq!(
+            MessageId::group(&alice, &gid, 5, 7),
+            MessageId::group(&alice, &gid, 5, 7)
+        );
+        assert_ne!(
+            MessageId::group(&alice, &gid, 5, 7),
+            MessageId::group(&alice, &[8u8; 32], 5, 7)
+        );
+    }
+
+    #[test]
+    fn test_audit_reports_collisions_not_redeliveries() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut audit = CollisionAudit::new(2);
+        let sink = Arc::clone(&seen);
+        audit.on_collision(move |c| sink.lock().unwrap().push(*c));
+
+        let id = MessageId::derive(&[1u8; 32], 0, &[0u8; 16]);
+        let claim = IdClaim {
+            sender_pubkey: [1u8; 32],
+            content_digest: [10u8; 32],
+        };
+        assert_eq!(audit.observe(id, claim), IdAudit::New);
+        assert_eq!(audit.observe(id, claim), IdAudit::Duplicate);
+
+        let forged = IdClaim {
+            sender_pubkey: [2u8; 32],
+            ..claim
+        };
+        assert!(matches!(
+            audit.observe(id, forged),
+            IdAudit::Collision(c) if c.first == claim && c.second == forged
+        ));
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        // Bounded: the oldest ID is forgotten
+        for counter in 1..=2 {
+            let other = MessageId::derive(&[1u8; 32], counter, &[0u8; 16]);
+            assert_eq!(audit.observe(other, claim), IdAudit::New);
+        }
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit.observe(id, forged), IdAudit::New);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,431 @@
+/// Message processing middleware.
+///
+/// Integrators add spam scoring, translation, archival and similar features
+/// by registering a [`MessageProcessor`] with a [`MessagePipeline`] instead
+/// of patching the dispatch code. Processors run in registration order on
+/// decrypted payloads, inbound (after decryption, before storage) and/or
+/// outbound (before encryption).
+///
+/// Each processor declares up front what it needs ([`Capabilities`]), and
+/// the pipeline holds it to that:
+///
+/// - It sees the payload only through a [`PayloadView`] whose accessors
+///   return nothing for undeclared capabilities, and every such attempt is
+///   recorded as a [`Violation`].
+/// - A [`Verdict::Drop`] without [`Capabilities::DROP`] is ignored.
+/// - A panicking processor is contained: its changes are discarded, the
+///   message continues down the pipeline, and the panic is reported.
+///
+/// The pipeline never sees keys or ciphertext, and processors cannot send
+/// anything themselves.
+use super::contact_id::ContactId;
+use std::collections::BTreeMap;
+use std::ops::BitOr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MiddlewareError {
+    #[error("A processor named {0} is already registered")]
+    DuplicateName(String),
+    #[error("Processor {0} declares no direction")]
+    NoDirection(String),
+    #[error("Pipeline already holds {MAX_PROCESSORS} processors")]
+    Full,
+}
+
+/// Most processors one pipeline accepts.
+pub const MAX_PROCESSORS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// What a processor may do with a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    /// Read the decrypted body.
+    pub const READ_BODY: Self = Self(1 << 0);
+    /// Replace the body (e.g. translation).
+    pub const MODIFY_BODY: Self = Self(1 << 1);
+    /// Stop the message (e.g. spam filter).
+    pub const DROP: Self = Self(1 << 2);
+    /// Attach annotations for the app (e.g. a spam score).
+    pub const ANNOTATE: Self = Self(1 << 3);
+    /// Learn which contact the message is from or to.
+    pub const READ_CONTACT: Self = Self(1 << 4);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A processor's self-description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessorInfo {
+    /// Unique name, used in violations and drop reports.
+    pub name: String,
+    pub capabilities: Capabilities,
+    pub inbound: bool,
+    pub outbound: bool,
+}
+
+/// Metadata of the message being processed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageContext {
+    pub direction: Direction,
+    pub contact: ContactId,
+    pub message_id: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Continue,
+    /// Stop the message; requires [`Capabilities::DROP`].
+    Drop {
+        reason: String,
+    },
+}
+
+/// A processor using a capability it did not declare, or panicking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub processor: String,
+    pub detail: &'static str,
+}
+
+/// Capability-checked access to a message for one processor.
+pub struct PayloadView<'a> {
+    ctx: &'a MessageContext,
+    body: &'a mut Vec<u8>,
+    annotations: &'a mut BTreeMap<String, String>,
+    capabilities: Capabilities,
+    denied: Vec<&'static str>,
+}
+
+impl PayloadView<'_> {
+    pub fn direction(&self) -> Direction {
+        self.ctx.direction
+    }
+
+    pub fn message_id(&self) -> &str {
+        &self.ctx.message_id
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.ctx.timestamp
+    }
+
+    fn allowed(&mut self, capability: Capabilities, what: &'static str) -> bool {
+        if self.capabilities.contains(capability) {
+            true
+        } else {
+            self.denied.push(what);
+            false
+        }
+    }
+
+    /// Requires [`Capabilities::READ_CONTACT`].
+    pub fn contact(&mut self) -> Option<ContactId> {
+        self.allowed(
+            Capabilities::READ_CONTACT,
+            "read contact without READ_CONTACT",
+        )
+        .then_some(self.ctx.contact)
+    }
+
+    /// Requires [`Capabilities::READ_BODY`].
+    pub fn body(&mut self) -> Option<&[u8]> {
+        if self.allowed(Capabilities::READ_BODY, "read body without READ_BODY") {
+            Some(self.body.as_slice())
+        } else {
+            None
+        }
+    }
+
+    /// Requires [`Capabilities::MODIFY_BODY`].
+    pub fn set_body(&mut self, body: Vec<u8>) -> bool {
+        if self.allowed(Capabilities::MODIFY_BODY, "modify body without MODIFY_BODY") {
+            *self.body = body;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Requires [`Capabilities::ANNOTATE`].
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<String>) -> bool {
+        if self.allowed(Capabilities::ANNOTATE, "annotate without ANNOTATE") {
+            self.annotations.insert(key.into(), value.into());
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A message processing extension.
+pub trait MessageProcessor: Send + Sync {
+    fn info(&self) -> ProcessorInfo;
+
+    fn process(&self, view: &mut PayloadView<'_>) -> Verdict;
+}
+
+/// Result of running the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineOutcome {
+    Deliver {
+        body: Vec<u8>,
+        annotations: BTreeMap<String, String>,
+    },
+    Dropped {
+        by: String,
+        reason: String,
+    },
+}
+
+/// Ordered processors, checked against their declared capabilities.
+#[derive(Default)]
+pub struct MessagePipeline {
+    processors: Vec<(ProcessorInfo, Box<dyn MessageProcessor>)>,
+}
+
+impl MessagePipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a processor. Its [`ProcessorInfo`] is read once, here.
+    pub fn register(
+        &mut self,
+        processor: Box<dyn MessageProcessor>,
+    ) -> Result<(), MiddlewareError> {
+        let info = processor.info();
+        if !info.inbound && !info.outbound {
+            return Err(MiddlewareError::NoDirection(info.name));
+        }
+        if self.processors.iter().any(|(i, _)| i.name == info.name) {
+            return Err(MiddlewareError::DuplicateName(info.name));
+        }
+        if self.processors.len() >= MAX_PROCESSORS {
+            return Err(MiddlewareError::Full);
+        }
+        log::info!(
+            "Registered message processor {} (capabilities {:#04x})",
+            info.name,
+            info.capabilities.0
+        );
+        self.processors.push((info, processor));
+        Ok(())
+    }
+
+    /// Remove a processor by name. Returns whether it was registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.processors.len();
+        self.processors.retain(|(i, _)| i.name != name);
+        self.processors.len() != before
+    }
+
+    pub fn processors(&self) -> impl Iterator<Item = &ProcessorInfo> {
+        self.processors.iter().map(|(i, _)| i)
+    }
+
+    /// Run the processors for `ctx.direction` over `body`.
+    pub fn run(&self, ctx: &MessageContext, body: Vec<u8>) -> (PipelineOutcome, Vec<Violation>) {
+        let mut body = body;
+        let mut annotations = BTreeMap::new();
+        let mut violations = Vec::new();
+
+        for (info, processor) in &self.processors {
+            let applies = match ctx.direction {
+                Direction::Inbound => info.inbound,
+                Direction::Outbound => info.outbound,
+            };
+            if !applies {
+                continue;
+            }
+            // Work on copies so a panic cannot leave half-applied changes
+            let mut scratch_body = body.clone();
+            let mut scratch_annotations = annotations.clone();
+            let mut view = PayloadView {
+                ctx,
+                body: &mut scratch_body,
+                annotations: &mut scratch_annotations,
+                capabilities: info.capabilities,
+                denied: Vec::new(),
+            };
+            let verdict = catch_unwind(AssertUnwindSafe(|| processor.process(&mut view)));
+            let denied = std::mem::take(&mut view.denied);
+            violations.extend(denied.into_iter().map(|detail| Violation {
+                processor: info.name.clone(),
+                detail,
+            }));
+
+            let verdict = match verdict {
+                Ok(verdict) => verdict,
+                Err(_) => {
+                    log::error!("Message processor {} panicked", info.name);
+                    violations.push(Violation {
+                        processor: info.name.clone(),
+                        detail: "panicked",
+                    });
+                    continue;
+                }
+            };
+            body = scratch_body;
+            annotations = scratch_annotations;
+            if let Verdict::Drop { reason } = verdict {
+                if info.capabilities.contains(Capabilities::DROP) {
+                    return (
+                        PipelineOutcome::Dropped {
+                            by: info.name.clone(),
+                            reason,
+                        },
+                        violations,
+                    );
+                }
+                violations.push(Violation {
+                    processor: info.name.clone(),
+                    detail: "drop without DROP",
+                });
+            }
+        }
+        for v in &violations {
+            log::warn!("Message processor {}: {}", v.processor, v.detail);
+        }
+        (PipelineOutcome::Deliver { body, annotations }, violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    struct SpamFilter;
+
+    impl MessageProcessor for SpamFilter {
+        fn info(&self) -> ProcessorInfo {
+            ProcessorInfo {
+                name: "spam".to_string(),
+                capabilities: Capabilities::READ_BODY | Capabilities::DROP | Capabilities::ANNOTATE,
+                inbound: true,
+                outbound: false,
+            }
+        }
+
+        fn process(&self, view: &mut PayloadView<'_>) -> Verdict {
+            let spam = view.body().is_some_and(|b| b.starts_with(b"BUY NOW"));
+            view.annotate("spam", if spam { "1" } else { "0" });
+            if spam {
+                Verdict::Drop {
+                    reason: "spam".to_string(),
+                }
+            } else {
+                Verdict::Continue
+            }
+        }
+    }
+
+    /// Declares only READ_BODY but tries to rewrite and drop.
+    struct Overreach;
+
+    impl MessageProcessor for Overreach {
+        fn info(&self) -> ProcessorInfo {
+            ProcessorInfo {
+                name: "overreach".to_string(),
+                capabilities: Capabilities::READ_BODY,
+                inbound: true,
+                outbound: true,
+            }
+        }
+
+        fn process(&self, view: &mut PayloadView<'_>) -> Verdict {
+            if view.body() == Some(&b"panic"[..]) {
+                panic!("processor bug");
+            }
+            view.set_body(b"rewritten".to_vec());
+            let _ = view.contact();
+            Verdict::Drop {
+                reason: "nope".to_string(),
+            }
+        }
+    }
+
+    fn ctx(direction: Direction) -> MessageContext {
+        MessageContext {
+            direction,
+            contact: test_contact(1),
+            message_id: "m1".to_string(),
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_processors_run_by_direction() {
+        let mut pipeline = MessagePipeline::new();
+        pipeline.register(Box::new(SpamFilter)).unwrap();
+        assert_eq!(
+            pipeline.register(Box::new(SpamFilter)),
+            Err(MiddlewareError::DuplicateName("spam".to_string()))
+        );
+
+        let (outcome, _) = pipeline.run(&ctx(Direction::Inbound), b"hello".to_vec());
+        let PipelineOutcome::Deliver { body, annotations } = outcome else {
+            panic!("dropped");
+        };
+        assert_eq!(body, b"hello");
+        assert_eq!(annotations["spam"], "0");
+
+        let (outcome, _) = pipeline.run(&ctx(Direction::Inbound), b"BUY NOW".to_vec());
+        assert!(matches!(outcome, PipelineOutcome::Dropped { ref by, .. } if by == "spam"));
+        // Inbound-only: outbound messages pass untouched
+        let (outcome, _) = pipeline.run(&ctx(Direction::Outbound), b"BUY NOW".to_vec());
+        assert!(matches!(outcome, PipelineOutcome::Deliver { .. }));
+
+        assert!(pipeline.unregister("spam"));
+        assert_eq!(pipeline.processors().count(), 0);
+    }
+
+    #[test]
+    fn test_undeclared_capabilities_and_panics_are_contained() {
+        let mut pipeline = MessagePipeline::new();
+        pipeline.register(Box::new(Overreach)).unwrap();
+
+        let (outcome, violations) = pipeline.run(&ctx(Direction::Outbound), b"hi".to_vec());
+        assert_eq!(
+            outcome,
+            PipelineOutcome::Deliver {
+                body: b"hi".to_vec(),
+                annotations: BTreeMap::new()
+            }
+        );
+        let details: Vec<_> = violations.iter().map(|v| v.detail).collect();
+        assert_eq!(
+            details,
+            [
+                "modify body without MODIFY_BODY",
+                "read contact without READ_CONTACT",
+                "drop without DROP"
+            ]
+        );
+
+        let (outcome, violations) = pipeline.run(&ctx(Direction::Inbound), b"panic".to_vec());
+        assert!(matches!(outcome, PipelineOutcome::Deliver { ref body, .. } if body == b"panic"));
+        assert_eq!(violations.last().unwrap().detail, "panicked");
+    }
+}
@@ -1,7 +1,115 @@
+pub mod annotation;
+pub mod broadcast;
+pub mod call;
+pub mod ciphersuite;
 pub mod contact;
+pub mod contact_backup;
+pub mod contact_id;
+pub mod delivery_proof;
+#[cfg(feature = "discovery")]
+pub mod discovery;
+pub mod fanout;
+pub mod file_transfer;
+pub mod forward;
+pub mod mailbox;
 pub mod message;
+pub mod message_id;
+pub mod middleware;
+pub mod ordering;
+pub mod pow_stamp;
+pub mod presence;
+pub mod reaction;
+pub mod recall;
+pub mod receipts;
+pub mod relay;
+pub mod relay_api;
+pub mod rpc;
 pub mod security_mode;
+pub mod silence;
+pub mod topic;
 
+pub use annotation::{AnnotationError, Annotations, MAX_ANNOTATIONS, MAX_ANNOTATIONS_LEN};
+pub use broadcast::{
+    Announcement, BroadcastError, BroadcastReceiver, BroadcastSender, ChannelKey, Delivered,
+};
+pub use call::{
+    CallCandidate, CallError, CallEvent, CallId, CallSession, CallSignal, CallState,
+    CandidateTransport, EndReason, MediaKind, PeerState, RejectReason,
+};
+pub use ciphersuite::{check_selection, negotiate, CipherSuite, DowngradeError, SUPPORTED_SUITES};
 pub use contact::ContactCard;
+pub use contact_backup::{
+    replica_relays, BackupContact, BackupKeys, BackupOp, BackupRejection, BackupRequest,
+    BackupResponse, BackupVault, ContactBackupError, ContactSnapshot, CONTACT_BACKUP_PROTOCOL,
+};
+pub use contact_id::{ContactId, ContactIdError};
+pub use delivery_proof::{
+    DeliveryProof, DeliveryProofError, DeliveryProofPolicy, ProvedAck, MAX_PROOFS_PER_ACK,
+};
+#[cfg(feature = "discovery")]
+pub use discovery::{
+    open_record, seal_record, DiscoveryError, HttpsTransport, LookupKdfParams, LookupKey,
+    MemoryRegistry, NameRegistry, RegistryOp, WellKnownRegistry, DISCOVERY_VERSION,
+};
+pub use fanout::{
+    mix, pad_plaintext, padded_len, unpad_plaintext, FanoutConfig, FanoutError, RelayDrop,
+    FANOUT_SIZE_CLASSES,
+};
+pub use file_transfer::{
+    ChunkReceipt, FileFrame, FileKeys, FileOffer, FileTransferError, IncomingTransfer,
+    OutgoingTransfer, TransferId, TransferState,
+};
+pub use forward::{
+    forward_message, Attachment, ForwardError, ForwardInfo, MessagePayload, Provenance,
+};
+pub use mailbox::{
+    BucketRecord, CheckMode, CheckRequest, CheckResult, MailboxError, MailboxId, MailboxIndex,
+    MailboxPollConfig, PirAnswer, PirQuery, PlainPoll,
+};
 pub use message::{Message, MessageType};
+pub use message_id::{
+    CollisionAudit, IdAudit, IdClaim, IdCollision, MessageId, MessageIdError, MessageIdSequence,
+    MESSAGE_ID_VERSION,
+};
+pub use middleware::{
+    Capabilities, Direction, MessageContext, MessagePipeline, MessageProcessor, MiddlewareError,
+    PayloadView, PipelineOutcome, ProcessorInfo, Verdict, Violation,
+};
+pub use ordering::{
+    ConversationOrdering, OrderingConfig, OrderingError, OrderingEvent, ReorderBuffer,
+    SequencedEnvelope,
+};
+pub use pow_stamp::{
+    attach_stamp, split_stamp, FirstContactGate, GateDecision, PowAdvert, PowError, PowParams,
+    PowPolicy, PowStamp,
+};
+pub use presence::{
+    ContactPresence, PresenceBeacon, PresenceBook, PresenceBucket, PresenceConfig, PresenceError,
+};
+pub use reaction::{MessageReactions, Reaction, ReactionBook, ReactionError, ReactionView};
+pub use recall::{
+    LocalMessageState, RecallAck, RecallError, RecallFrame, RecallGuard, RecallOutcome,
+    RecallReport, RecallRequest, RecallTracker, RecallVerdict,
+};
+pub use receipts::{
+    AckBatch, AckRange, ReceiptBatcher, ReceiptConfig, ReceiptError, MAX_ACK_RANGES,
+};
+pub use relay::{
+    RateLimits, RelayDescriptor, RelayError, RelayInfo, RelaySelector, RetentionPolicy,
+};
+pub use relay_api::{
+    check_requests, read_frame, write_frame, RelayApiError, RelayRejection, RelayRequest,
+    RelayResponse, MAX_ENVELOPE_LEN, MAX_RELAY_FRAME, RELAY_API_VERSION,
+};
+pub use rpc::{
+    RpcBody, RpcClient, RpcDispatch, RpcError, RpcFrame, RpcHandler, RpcKeys, RpcReply, RpcServer,
+    RpcStatus, MAX_RPC_PAYLOAD,
+};
 pub use security_mode::SecurityMode;
+pub use silence::{
+    NetworkSilence, SilenceError, SilenceSpec, TrafficClass, WakeMessage, WAKE_MESSAGE_LEN,
+};
+pub use topic::{
+    CapabilityToken, TopicBody, TopicError, TopicEvent, TopicFrame, TopicHub, TopicKeys,
+    MAX_TOPIC_PAYLOAD,
+};
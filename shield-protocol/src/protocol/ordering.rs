@@ -0,0 +1,618 @@
+/// In-order delivery for 1:1 conversations.
+///
+/// Tor circuits, retries and multiple send lanes all reorder messages. The
+/// sender puts a per-conversation sequence number inside the plaintext
+/// (`[version][seq: u64 BE][body]`), so it is covered by the message AEAD and
+/// invisible on the wire. The receiver feeds decrypted envelopes into a
+/// reorder buffer that releases messages strictly in sequence order.
+///
+/// Version 2 envelopes (`[2][seq: u64 BE][tag: 8][body]`) also carry the
+/// sender's identity continuity tag (see `crypto::key_continuity`). Checking
+/// it is up to the caller, before [`ConversationOrdering::receive_envelope`].
+///
+/// Version 3 envelopes carry the sender's annotations (see
+/// `protocol::annotation`): `[3][seq: u64 BE][flags][tag: 8 if flags & 1]
+/// [annotations][body]`. They are only sent when a message has annotations,
+/// so clients that never annotate stay readable by older ones. Annotations
+/// travel through the reorder buffer with their message and come out on its
+/// [`OrderingEvent`].
+///
+/// Missing messages do not stall a conversation forever. A gap is given up
+/// when either:
+///
+/// - the oldest held message has waited `gap_timeout_ms`, or
+/// - more than `window` sequence numbers would have to be held.
+///
+/// The receiver then emits [`OrderingEvent::Gap`] for the skipped range and
+/// continues. If a skipped message turns up later it is released as
+/// [`OrderingEvent::Late`] so the app can slot it into history; plain
+/// duplicates are dropped.
+use super::annotation::{AnnotationError, Annotations};
+use super::contact_id::ContactId;
+use crate::crypto::key_continuity::{ContinuityError, ContinuityTag, CONTINUITY_TAG_LEN};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum OrderingError {
+    #[error("Malformed sequenced envelope")]
+    Malformed,
+    #[error("Unsupported sequenced envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Ordering state encoding failed: {0}")]
+    Encoding(String),
+    #[error(transparent)]
+    Continuity(#[from] ContinuityError),
+    #[error(transparent)]
+    Annotation(#[from] AnnotationError),
+}
+
+/// Envelope wire version without a continuity tag.
+pub const SEQUENCED_ENVELOPE_VERSION: u8 = 1;
+
+/// Envelope wire version carrying a continuity tag.
+pub const TAGGED_ENVELOPE_VERSION: u8 = 2;
+
+/// Envelope wire version carrying annotations.
+pub const ANNOTATED_ENVELOPE_VERSION: u8 = 3;
+
+/// Version 3 flag: a continuity tag follows.
+const FLAG_CONTINUITY: u8 = 0x01;
+
+/// Skipped sequence numbers remembered per conversation for late delivery.
+pub const MAX_SKIPPED_TRACKED: usize = 1024;
+
+const HEADER_LEN: usize = 9;
+
+/// Plaintext wrapper carrying the conversation sequence number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequencedEnvelope {
+    pub seq: u64,
+    /// Sender's identity continuity tag; `None` from older clients.
+    pub continuity: Option<ContinuityTag>,
+    /// Empty unless the sender annotated the message.
+    pub annotations: Annotations,
+    pub body: Vec<u8>,
+}
+
+impl SequencedEnvelope {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            HEADER_LEN + 1 + CONTINUITY_TAG_LEN + self.annotations.encoded_len() + self.body.len(),
+        );
+        let annotated = !self.annotations.is_empty();
+        out.push(match (annotated, &self.continuity) {
+            (true, _) => ANNOTATED_ENVELOPE_VERSION,
+            (false, Some(_)) => TAGGED_ENVELOPE_VERSION,
+            (false, None) => SEQUENCED_ENVELOPE_VERSION,
+        });
+        out.extend_from_slice(&self.seq.to_be_bytes());
+        if annotated {
+            let flags = if self.continuity.is_some() {
+                FLAG_CONTINUITY
+            } else {
+                0
+            };
+            out.push(flags);
+        }
+        if let Some(tag) = &self.continuity {
+            out.extend_from_slice(&tag.0);
+        }
+        if annotated {
+            self.annotations.encode(&mut out);
+        }
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, OrderingError> {
+        if data.len() < HEADER_LEN {
+            return Err(OrderingError::Malformed);
+        }
+        let seq = u64::from_be_bytes(data[1..HEADER_LEN].try_into().expect("8 bytes"));
+        let rest = &data[HEADER_LEN..];
+        let (continuity, annotations, body) = match data[0] {
+            SEQUENCED_ENVELOPE_VERSION => (None, Annotations::new(), rest),
+            TAGGED_ENVELOPE_VERSION => {
+                let (tag, body) = Self::split_tag(rest)?;
+                (Some(tag), Annotations::new(), body)
+            }
+            ANNOTATED_ENVELOPE_VERSION => {
+                let (&flags, rest) = rest.split_first().ok_or(OrderingError::Malformed)?;
+                let (continuity, rest) = match flags {
+                    0 => (None, rest),
+                    FLAG_CONTINUITY => {
+                        let (tag, rest) = Self::split_tag(rest)?;
+                        (Some(tag), rest)
+                    }
+                    // Unknown flags would change how the rest is read
+                    _ => return Err(OrderingError::Malformed),
+                };
+                let (annotations, consumed) = Annotations::decode(rest)?;
+                (continuity, annotations, &rest[consumed..])
+            }
+            version => return Err(OrderingError::UnsupportedVersion(version)),
+        };
+        Ok(Self {
+            seq,
+            continuity,
+            annotations,
+            body: body.to_vec(),
+        })
+    }
+
+    fn split_tag(data: &[u8]) -> Result<(ContinuityTag, &[u8]), OrderingError> {
+        if data.len() < CONTINUITY_TAG_LEN {
+            return Err(OrderingError::Malformed);
+        }
+        let (tag, rest) = data.split_at(CONTINUITY_TAG_LEN);
+        Ok((ContinuityTag(tag.try_into().expect("tag length")), rest))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderingConfig {
+    /// Most sequence numbers held past a gap before it is given up.
+    pub window: u64,
+    /// How long the oldest held message waits for a gap to fill.
+    pub gap_timeout_ms: u64,
+}
+
+impl Default for OrderingConfig {
+    fn default() -> Self {
+        Self {
+            window: 64,
+            gap_timeout_ms: 30_000,
+        }
+    }
+}
+
+/// What the app should do next, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderingEvent {
+    /// Next message in sequence.
+    Deliver {
+        seq: u64,
+        body: Vec<u8>,
+        annotations: Annotations,
+    },
+    /// Messages `first..=last` were given up on.
+    Gap { first: u64, last: u64 },
+    /// A message from a range previously reported as a gap.
+    Late {
+        seq: u64,
+        body: Vec<u8>,
+        annotations: Annotations,
+    },
+}
+
+/// Receive-side reorder buffer for one conversation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorderBuffer {
+    next_expected: u64,
+    held: BTreeMap<u64, (Vec<u8>, Annotations)>,
+    /// When the current gap was first observed (ms).
+    gap_since: Option<u64>,
+    skipped: BTreeSet<u64>,
+}
+
+impl ReorderBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sequence number of the next message to be delivered.
+    pub fn next_expected(&self) -> u64 {
+        self.next_expected
+    }
+
+    /// Messages waiting for a gap to fill.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// When the current gap times out, if there is one.
+    pub fn gap_deadline(&self, config: &OrderingConfig) -> Option<u64> {
+        self.gap_since
+            .map(|since| since.saturating_add(config.gap_timeout_ms))
+    }
+
+    /// Accept a decrypted envelope and return whatever can now be released.
+    pub fn receive(
+        &mut self,
+        seq: u64,
+        body: Vec<u8>,
+        now: u64,
+        config: &OrderingConfig,
+    ) -> Vec<OrderingEvent> {
+        self.receive_annotated(seq, body, Annotations::new(), now, config)
+    }
+
+    /// [`receive`](Self::receive) for a message carrying annotations.
+    pub fn receive_annotated(
+        &mut self,
+        seq: u64,
+        body: Vec<u8>,
+        annotations: Annotations,
+        now: u64,
+        config: &OrderingConfig,
+    ) -> Vec<OrderingEvent> {
+        if seq < self.next_expected {
+            return if self.skipped.remove(&seq) {
+                vec![OrderingEvent::Late {
+                    seq,
+                    body,
+                    annotations,
+                }]
+            } else {
+                Vec::new()
+            };
+        }
+        self.held.entry(seq).or_insert((body, annotations));
+
+        let mut events = self.release(now);
+        let window = config.window.max(1);
+        while let Some((&last, _)) = self.held.last_key_value() {
+            if last - self.next_expected < window {
+                break;
+            }
+            events.extend(self.skip_gap(now));
+        }
+        events
+    }
+
+    /// Give up on a gap whose timeout has passed.
+    pub fn poll(&mut self, now: u64, config: &OrderingConfig) -> Vec<OrderingEvent> {
+        match self.gap_deadline(config) {
+            Some(deadline) if now >= deadline => self.skip_gap(now),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Report the gap before the oldest held message, then release from it.
+    fn skip_gap(&mut self, now: u64) -> Vec<OrderingEvent> {
+        let Some((&first_held, _)) = self.held.first_key_value() else {
+            return Vec::new();
+        };
+        let (first, last) = (self.next_expected, first_held - 1);
+        self.skipped.extend(first..=last);
+        while self.skipped.len() > MAX_SKIPPED_TRACKED {
+            self.skipped.pop_first();
+        }
+        self.next_expected = first_held;
+        let mut events = vec![OrderingEvent::Gap { first, last }];
+        events.extend(self.release(now));
+        events
+    }
+
+    /// Deliver the contiguous run starting at `next_expected`.
+    fn release(&mut self, now: u64) -> Vec<OrderingEvent> {
+        let mut events = Vec::new();
+        while let Some((body, annotations)) = self.held.remove(&self.next_expected) {
+            events.push(OrderingEvent::Deliver {
+                seq: self.next_expected,
+                body,
+                annotations,
+            });
+            self.next_expected += 1;
+        }
+        self.gap_since = match (self.held.is_empty(), events.is_empty(), self.gap_since) {
+            (true, _, _) => None,
+            // Progress restarts the clock for whatever is still missing
+            (false, false, _) | (false, true, None) => Some(now),
+            (false, true, since) => since,
+        };
+        events
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Conversation {
+    next_send_seq: u64,
+    recv: ReorderBuffer,
+}
+
+/// Ordering state for all 1:1 conversations.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationOrdering {
+    config: OrderingConfig,
+    conversations: HashMap<ContactId, Conversation>,
+}
+
+impl ConversationOrdering {
+    pub fn new(config: OrderingConfig) -> Self {
+        Self {
+            config,
+            conversations: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &OrderingConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: OrderingConfig) {
+        self.config = config;
+    }
+
+    /// Wrap an outgoing plaintext with the conversation's next sequence
+    /// number and our continuity tag, if we have one.
+    pub fn wrap_outgoing(
+        &mut self,
+        contact_id: &ContactId,
+        body: &[u8],
+        continuity: Option<ContinuityTag>,
+    ) -> Vec<u8> {
+        self.wrap_annotated(contact_id, body, continuity, Annotations::new())
+    }
+
+    /// [`wrap_outgoing`](Self::wrap_outgoing) with annotations; an empty set
+    /// produces the same envelope.
+    pub fn wrap_annotated(
+        &mut self,
+        contact_id: &ContactId,
+        body: &[u8],
+        continuity: Option<ContinuityTag>,
+        annotations: Annotations,
+    ) -> Vec<u8> {
+        let conversation = self.conversations.entry(*contact_id).or_default();
+        let seq = conversation.next_send_seq;
+        conversation.next_send_seq += 1;
+        SequencedEnvelope {
+            seq,
+            continuity,
+            annotations,
+            body: body.to_vec(),
+        }
+        .to_bytes()
+    }
+
+    /// Feed a decrypted envelope from `contact_id`. Its continuity tag is
+    /// not checked.
+    pub fn receive(
+        &mut self,
+        contact_id: &ContactId,
+        envelope: &[u8],
+        now: u64,
+    ) -> Result<Vec<OrderingEvent>, OrderingError> {
+        let envelope = SequencedEnvelope::from_bytes(envelope)?;
+        Ok(self.receive_envelope(contact_id, envelope, now))
+    }
+
+    /// Feed an envelope already parsed (and its tag checked) by the caller.
+    pub fn receive_envelope(
+        &mut self,
+        contact_id: &ContactId,
+        envelope: SequencedEnvelope,
+        now: u64,
+    ) -> Vec<OrderingEvent> {
+        let config = self.config;
+        self.conversations
+            .entry(*contact_id)
+            .or_default()
+            .recv
+            .receive_annotated(
+                envelope.seq,
+                envelope.body,
+                envelope.annotations,
+                now,
+                &config,
+            )
+    }
+
+    /// Time out stale gaps in every conversation.
+    pub fn poll(&mut self, now: u64) -> Vec<(ContactId, OrderingEvent)> {
+        let config = self.config;
+        let mut out = Vec::new();
+        for (id, conversation) in self.conversations.iter_mut() {
+            out.extend(
+                conversation
+                    .recv
+                    .poll(now, &config)
+                    .into_iter()
+                    .map(|e| (*id, e)),
+            );
+        }
+        out
+    }
+
+    /// Earliest pending gap deadline across conversations, for scheduling `poll`.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.conversations
+            .values()
+            .filter_map(|c| c.recv.gap_deadline(&self.config))
+            .min()
+    }
+
+    /// Forget a conversation (contact deleted or session reset).
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.conversations.remove(contact_id);
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OrderingError> {
+        bincode::serialize(self).map_err(|e| OrderingError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, OrderingError> {
+        bincode::deserialize(data).map_err(|e| OrderingError::Encoding(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    fn deliver(seq: u64) -> OrderingEvent {
+        OrderingEvent::Deliver {
+            seq,
+            body: vec![seq as u8],
+            annotations: Annotations::new(),
+        }
+    }
+
+    fn config(window: u64) -> OrderingConfig {
+        OrderingConfig {
+            window,
+            gap_timeout_ms: 1_000,
+        }
+    }
+
+    #[test]
+    fn test_reorders_and_drops_duplicates() {
+        let cfg = config(16);
+        let mut buf = ReorderBuffer::new();
+        assert!(buf.receive(1, vec![1], 0, &cfg).is_empty());
+        assert!(buf.receive(2, vec![2], 0, &cfg).is_empty());
+        assert_eq!(buf.gap_deadline(&cfg), Some(1_000));
+        assert_eq!(
+            buf.receive(0, vec![0], 10, &cfg),
+            vec![deliver(0), deliver(1), deliver(2)]
+        );
+        assert!(buf.receive(1, vec![1], 20, &cfg).is_empty());
+        assert_eq!(buf.gap_deadline(&cfg), None);
+    }
+
+    #[test]
+    fn test_gap_timeout_and_late_arrival() {
+        let cfg = config(16);
+        let mut buf = ReorderBuffer::new();
+        buf.receive(0, vec![0], 0, &cfg);
+        buf.receive(3, vec![3], 100, &cfg);
+        assert!(buf.poll(1_099, &cfg).is_empty());
+        assert_eq!(
+            buf.poll(1_100, &cfg),
+            vec![OrderingEvent::Gap { first: 1, last: 2 }, deliver(3)]
+        );
+        assert_eq!(
+            buf.receive(2, vec![2], 1_200, &cfg),
+            vec![OrderingEvent::Late {
+                seq: 2,
+                body: vec![2],
+                annotations: Annotations::new(),
+            }]
+        );
+        // A second copy of the late message is a plain duplicate
+        assert!(buf.receive(2, vec![2], 1_300, &cfg).is_empty());
+    }
+
+    #[test]
+    fn test_window_overflow_forces_gap() {
+        let cfg = config(4);
+        let mut buf = ReorderBuffer::new();
+        for seq in 1..4 {
+            assert!(buf.receive(seq, vec![seq as u8], 0, &cfg).is_empty());
+        }
+        assert_eq!(
+            buf.receive(4, vec![4], 0, &cfg),
+            vec![
+                OrderingEvent::Gap { first: 0, last: 0 },
+                deliver(1),
+                deliver(2),
+                deliver(3),
+                deliver(4)
+            ]
+        );
+        assert_eq!(buf.next_expected(), 5);
+    }
+
+    #[test]
+    fn test_conversation_round_trip_and_persistence() {
+        let alice = test_contact(1);
+        let mut sender = ConversationOrdering::default();
+        let mut receiver = ConversationOrdering::new(config(16));
+        let wire: Vec<_> = (0..3)
+            .map(|i| sender.wrap_outgoing(&alice, &[i], None))
+            .collect();
+        assert_eq!(SequencedEnvelope::from_bytes(&wire[2]).unwrap().seq, 2);
+
+        assert!(receiver.receive(&alice, &wire[2], 0).unwrap().is_empty());
+        let mut restored = ConversationOrdering::from_bytes(&receiver.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, receiver);
+        assert_eq!(restored.next_deadline(), Some(1_000));
+        assert_eq!(
+            restored.poll(1_000),
+            vec![
+                (alice, OrderingEvent::Gap { first: 0, last: 1 }),
+                (alice, deliver(2))
+            ]
+        );
+        assert_eq!(
+            SequencedEnvelope::from_bytes(&[9, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(OrderingError::UnsupportedVersion(9))
+        );
+    }
+
+    #[test]
+    fn test_tagged_envelope_round_trip() {
+        let tag = ContinuityTag([7u8; CONTINUITY_TAG_LEN]);
+        let envelope = SequencedEnvelope {
+            seq: 5,
+            continuity: Some(tag),
+            annotations: Annotations::new(),
+            body: b"hi".to_vec(),
+        };
+        let bytes = envelope.to_bytes();
+        assert_eq!(bytes[0], TAGGED_ENVELOPE_VERSION);
+        assert_eq!(bytes.len(), HEADER_LEN + CONTINUITY_TAG_LEN + 2);
+        assert_eq!(SequencedEnvelope::from_bytes(&bytes).unwrap(), envelope);
+        // Truncated tag
+        assert_eq!(
+            SequencedEnvelope::from_bytes(&bytes[..HEADER_LEN + 4]),
+            Err(OrderingError::Malformed)
+        );
+
+        // Untagged envelopes keep the version 1 layout
+        let legacy = SequencedEnvelope {
+            continuity: None,
+            ..envelope
+        };
+        assert_eq!(legacy.to_bytes()[0], SEQUENCED_ENVELOPE_VERSION);
+        assert_eq!(
+            SequencedEnvelope::from_bytes(&legacy.to_bytes()).unwrap(),
+            legacy
+        );
+    }
+
+    #[test]
+    fn test_annotated_envelope_round_trip() {
+        let mut annotations = Annotations::new();
+        annotations.insert("org.example:thread", "7").unwrap();
+        let alice = test_contact(1);
+        let tag = ContinuityTag([7u8; CONTINUITY_TAG_LEN]);
+        let mut sender = ConversationOrdering::default();
+        let mut receiver = ConversationOrdering::default();
+
+        let plain = sender.wrap_outgoing(&alice, b"a", Some(tag));
+        let annotated = sender.wrap_annotated(&alice, b"b", None, annotations.clone());
+        assert_eq!(plain[0], TAGGED_ENVELOPE_VERSION);
+        assert_eq!(annotated[0], ANNOTATED_ENVELOPE_VERSION);
+
+        // Annotations wait in the buffer with their message
+        assert!(receiver.receive(&alice, &annotated, 0).unwrap().is_empty());
+        let events = receiver.receive(&alice, &plain, 0).unwrap();
+        assert_eq!(
+            events[1],
+            OrderingEvent::Deliver {
+                seq: 1,
+                body: b"b".to_vec(),
+                annotations: annotations.clone(),
+            }
+        );
+
+        let tagged = SequencedEnvelope {
+            seq: 2,
+            continuity: Some(tag),
+            annotations,
+            body: b"c".to_vec(),
+        };
+        let mut bytes = tagged.to_bytes();
+        assert_eq!(SequencedEnvelope::from_bytes(&bytes).unwrap(), tagged);
+        bytes[HEADER_LEN] |= 0x80;
+        assert_eq!(
+            SequencedEnvelope::from_bytes(&bytes),
+            Err(OrderingError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,562 @@
+/// Proof-of-work stamps for unauthenticated first-contact messages.
+///
+/// Friend requests and pings from unknown senders cost the recipient a
+/// decryption attempt and a notification, while costing the sender nothing.
+/// A stamp makes each such message cost the sender a memory-hard search
+/// (Argon2id) that the recipient verifies with a single Argon2 evaluation.
+///
+/// - **Binding:** the stamp covers the message type, the full payload and a
+///   timestamp, so it cannot be moved to another message. Payloads are
+///   encrypted to the recipient, which binds the stamp to them as well.
+/// - **Negotiation:** the recipient publishes a `PowAdvert` (current
+///   difficulty and Argon2 parameters). Difficulty is 0 (stamps off) until
+///   the policy enables it, and rises by one bit per doubling of first-contact
+///   traffic above the surge threshold. Stamps minted against the last
+///   advertised difficulty stay valid while the surge raises it.
+/// - **Exemptions:** senders the app marks as trusted skip the gate.
+/// - **Verification budget:** a verification still costs the recipient one
+///   Argon2 evaluation, so bogus stamps could be used to burn CPU and memory.
+///   Cheap checks (difficulty, freshness, replay) run first, and Argon2 runs
+///   at most `MAX_VERIFIES_PER_SENDER_PER_MIN` times per claimed sender and
+///   `MAX_VERIFIES_PER_MIN` times overall; beyond that messages are rejected
+///   unverified.
+///
+/// Wire form: the stamp is a fixed-size trailer after the payload,
+/// `[payload][difficulty:1][timestamp:8 BE][nonce:8 BE]["SPW1"]`.
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PowError {
+    #[error("Proof-of-work stamp required (difficulty {0})")]
+    StampRequired(u8),
+    #[error("Stamp difficulty {got} below required {required}")]
+    InsufficientDifficulty { got: u8, required: u8 },
+    #[error("Stamp timestamp outside the accepted window")]
+    Expired,
+    #[error("Stamp does not meet its claimed difficulty")]
+    InvalidSolution,
+    #[error("Stamp already used")]
+    Replayed,
+    #[error("No solution found within {0} attempts")]
+    Exhausted(u64),
+    #[error("Invalid Argon2 parameters")]
+    InvalidParams,
+    #[error("Too many stamp verifications, try again later")]
+    RateLimited,
+}
+
+/// Trailer magic marking a stamped payload.
+pub const STAMP_MAGIC: [u8; 4] = *b"SPW1";
+/// Encoded trailer length (difficulty + timestamp + nonce + magic).
+pub const STAMP_TRAILER_LEN: usize = 1 + 8 + 8 + STAMP_MAGIC.len();
+/// Stamps older than this are rejected (and forgotten by the replay set).
+pub const MAX_STAMP_AGE_SECS: u64 = 600;
+/// Tolerated sender clock drift into the future.
+pub const MAX_STAMP_SKEW_SECS: u64 = 120;
+/// Hard upper bound on negotiated difficulty (leading zero bits).
+pub const MAX_DIFFICULTY: u8 = 24;
+/// Stamp verifications (Argon2 runs) per claimed sender per minute.
+pub const MAX_VERIFIES_PER_SENDER_PER_MIN: usize = 3;
+/// Stamp verifications per minute across all senders. The claimed sender is
+/// not authenticated yet, so this is what bounds the cost of forged keys.
+pub const MAX_VERIFIES_PER_MIN: usize = 120;
+
+/// Fixed Argon2 salt; the per-stamp challenge supplies the uniqueness.
+const STAMP_SALT: &[u8; 16] = b"ShieldPoWStamp01";
+
+/// Argon2id cost of a single stamp attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+}
+
+impl Default for PowParams {
+    /// 4 MiB, 1 pass: a few ms per attempt on a phone, so verification stays cheap.
+    fn default() -> Self {
+        Self {
+            memory_kib: 4096,
+            iterations: 1,
+        }
+    }
+}
+
+/// A solved stamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowStamp {
+    pub difficulty: u8,
+    /// Unix seconds when the stamp was minted.
+    pub timestamp: u64,
+    pub nonce: u64,
+}
+
+fn challenge(msg_type: u8, payload: &[u8], difficulty: u8, timestamp: u64) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key("ShieldMessenger-PoWStamp-v1");
+    hasher.update(&[msg_type, difficulty]);
+    hasher.update(&timestamp.to_be_bytes());
+    hasher.update(payload);
+    *hasher.finalize().as_bytes()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+fn solves(
+    challenge: &[u8; 32],
+    nonce: u64,
+    difficulty: u8,
+    params: &PowParams,
+) -> Result<bool, PowError> {
+    let argon_params = Params::new(params.memory_kib, params.iterations, 1, Some(32))
+        .map_err(|_| PowError::InvalidParams)?;
+    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params);
+
+    let mut input = [0u8; 40];
+    input[..32].copy_from_slice(challenge);
+    input[32..].copy_from_slice(&nonce.to_be_bytes());
+    let mut out = [0u8; 32];
+    argon
+        .hash_password_into(&input, STAMP_SALT, &mut out)
+        .map_err(|_| PowError::InvalidParams)?;
+    Ok(leading_zero_bits(&out) >= difficulty as u32)
+}
+
+impl PowStamp {
+    /// Search for a stamp over `msg_type || payload`. Expected cost is
+    /// `2^difficulty` Argon2 evaluations; gives up after `max_attempts`.
+    pub fn mint(
+        msg_type: u8,
+        payload: &[u8],
+        difficulty: u8,
+        now: u64,
+        params: &PowParams,
+        max_attempts: u64,
+    ) -> Result<Self, PowError> {
+        let c = challenge(msg_type, payload, difficulty, now);
+        for nonce in 0..max_attempts {
+            if solves(&c, nonce, difficulty, params)? {
+                return Ok(Self {
+                    difficulty,
+                    timestamp: now,
+                    nonce,
+                });
+            }
+        }
+        Err(PowError::Exhausted(max_attempts))
+    }
+
+    /// Check freshness and the solution itself (one Argon2 evaluation).
+    pub fn verify(
+        &self,
+        msg_type: u8,
+        payload: &[u8],
+        now: u64,
+        params: &PowParams,
+    ) -> Result<(), PowError> {
+        self.check_fresh(now)?;
+        let c = challenge(msg_type, payload, self.difficulty, self.timestamp);
+        if solves(&c, self.nonce, self.difficulty, params)? {
+            Ok(())
+        } else {
+            Err(PowError::InvalidSolution)
+        }
+    }
+
+    fn check_fresh(&self, now: u64) -> Result<(), PowError> {
+        if self.timestamp > now + MAX_STAMP_SKEW_SECS
+            || now.saturating_sub(self.timestamp) > MAX_STAMP_AGE_SECS
+        {
+            return Err(PowError::Expired);
+        }
+        Ok(())
+    }
+
+    fn to_trailer(self) -> [u8; STAMP_TRAILER_LEN] {
+        let mut out = [0u8; STAMP_TRAILER_LEN];
+        out[0] = self.difficulty;
+        out[1..9].copy_from_slice(&self.timestamp.to_be_bytes());
+        out[9..17].copy_from_slice(&self.nonce.to_be_bytes());
+        out[17..].copy_from_slice(&STAMP_MAGIC);
+        out
+    }
+}
+
+/// Append a stamp trailer to `payload`.
+pub fn attach_stamp(payload: &[u8], stamp: PowStamp) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + STAMP_TRAILER_LEN);
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&stamp.to_trailer());
+    out
+}
+
+/// Split a possibly stamped payload into `(payload, stamp)`.
+pub fn split_stamp(data: &[u8]) -> (&[u8], Option<PowStamp>) {
+    if data.len() < STAMP_TRAILER_LEN || data[data.len() - STAMP_MAGIC.len()..] != STAMP_MAGIC {
+        return (data, None);
+    }
+    let (payload, t) = data.split_at(data.len() - STAMP_TRAILER_LEN);
+    let stamp = PowStamp {
+        difficulty: t[0],
+        timestamp: u64::from_be_bytes(t[1..9].try_into().unwrap()),
+        nonce: u64::from_be_bytes(t[9..17].try_into().unwrap()),
+    };
+    (payload, Some(stamp))
+}
+
+// ---------------------------------------------------------------------------
+// Negotiation & inbound gate
+// ---------------------------------------------------------------------------
+
+/// Recipient-side stamp policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowPolicy {
+    /// Difficulty under normal load; 0 disables stamps entirely.
+    pub base_difficulty: u8,
+    /// Cap applied during a surge.
+    pub max_difficulty: u8,
+    /// First-contact messages per minute tolerated before difficulty rises.
+    pub surge_threshold_per_min: u32,
+    pub params: PowParams,
+}
+
+impl Default for PowPolicy {
+    fn default() -> Self {
+        Self {
+            base_difficulty: 0,
+            max_difficulty: 16,
+            surge_threshold_per_min: 10,
+            params: PowParams::default(),
+        }
+    }
+}
+
+impl PowPolicy {
+    pub fn enabled(&self) -> bool {
+        self.base_difficulty > 0
+    }
+
+    /// Difficulty for the given first-contact rate: one extra bit per
+    /// doubling above the surge threshold.
+    pub fn difficulty_for_rate(&self, per_min: usize) -> u8 {
+        if !self.enabled() {
+            return 0;
+        }
+        let threshold = self.surge_threshold_per_min.max(1) as usize;
+        let mut extra = 0u8;
+        let mut level = threshold;
+        while per_min > level && extra < MAX_DIFFICULTY {
+            extra += 1;
+            level = level.saturating_mul(2);
+        }
+        self.base_difficulty
+            .saturating_add(extra)
+            .min(self.max_difficulty)
+            .min(MAX_DIFFICULTY)
+    }
+}
+
+/// What a recipient publishes so senders know what to mint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowAdvert {
+    pub difficulty: u8,
+    pub params: PowParams,
+}
+
+impl PowAdvert {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Outcome of the inbound check.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GateDecision<'a> {
+    /// Stamp valid (or stamps disabled); payload with any trailer removed.
+    Accept(&'a [u8]),
+    /// Trusted sender; stamp not checked.
+    Exempt(&'a [u8]),
+}
+
+/// Inbound first-contact gate: tracks load, negotiates difficulty and
+/// rejects unstamped, weak or replayed stamps.
+#[derive(Debug)]
+pub struct FirstContactGate {
+    policy: PowPolicy,
+    /// Arrival times (unix secs) of first-contact messages in the last minute.
+    recent: VecDeque<u64>,
+    /// Lowest difficulty still honoured (the last one advertised).
+    advertised: u8,
+    /// Challenges already accepted, with their stamp timestamps.
+    seen: HashSet<[u8; 32]>,
+    seen_order: VecDeque<(u64, [u8; 32])>,
+    /// Start times (unix secs) of Argon2 verifications in the last minute,
+    /// per claimed sender and overall.
+    verifies: HashMap<Vec<u8>, VecDeque<u64>>,
+    verifies_total: VecDeque<u64>,
+}
+
+impl FirstContactGate {
+    pub fn new(policy: PowPolicy) -> Self {
+        Self {
+            advertised: policy.base_difficulty,
+            policy,
+            recent: VecDeque::new(),
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            verifies: HashMap::new(),
+            verifies_total: VecDeque::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &PowPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: PowPolicy) {
+        self.advertised = policy.base_difficulty;
+        self.policy = policy;
+    }
+
+    fn prune(&mut self, now: u64) {
+        let stale = |t: &u64| now.saturating_sub(*t) >= 60;
+        while self.recent.front().is_some_and(stale) {
+            self.recent.pop_front();
+        }
+        while self.verifies_total.front().is_some_and(stale) {
+            self.verifies_total.pop_front();
+        }
+        self.verifies.retain(|_, times| {
+            while times.front().is_some_and(stale) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        while let Some((ts, c)) = self.seen_order.front().copied() {
+            if now.saturating_sub(ts) <= MAX_STAMP_AGE_SECS {
+                break;
+            }
+            self.seen_order.pop_front();
+            self.seen.remove(&c);
+        }
+    }
+
+    /// Difficulty currently required of new senders.
+    pub fn current_difficulty(&mut self, now: u64) -> u8 {
+        self.prune(now);
+        self.policy.difficulty_for_rate(self.recent.len())
+    }
+
+    /// Advert to publish; also lowers the accepted floor to what is shown,
+    /// so senders who fetched it are not rejected by a later surge.
+    pub fn advert(&mut self, now: u64) -> PowAdvert {
+        let difficulty = self.current_difficulty(now);
+        self.advertised = difficulty;
+        PowAdvert {
+            difficulty,
+            params: self.policy.params,
+        }
+    }
+
+    /// Check an inbound first-contact message. `data` is the wire payload
+    /// after the type byte, possibly carrying a stamp trailer. `sender` is the
+    /// key the message claims to come from, used only for the verification
+    /// budget.
+    pub fn check<'a>(
+        &mut self,
+        msg_type: u8,
+        sender: &[u8],
+        data: &'a [u8],
+        trusted: bool,
+        now: u64,
+    ) -> Result<GateDecision<'a>, PowError> {
+        let (payload, stamp) = split_stamp(data);
+        if trusted {
+            return Ok(GateDecision::Exempt(payload));
+        }
+        if !self.policy.enabled() {
+            return Ok(GateDecision::Accept(payload));
+        }
+
+        let current = self.current_difficulty(now);
+        self.recent.push_back(now);
+        let required = current.min(self.advertised.max(self.policy.base_difficulty));
+        let stamp = stamp.ok_or(PowError::StampRequired(current))?;
+        if stamp.difficulty < required {
+            return Err(PowError::InsufficientDifficulty {
+                got: stamp.difficulty,
+                required,
+            });
+        }
+        stamp.check_fresh(now)?;
+        let c = challenge(msg_type, payload, stamp.difficulty, stamp.timestamp);
+        if self.seen.contains(&c) {
+            return Err(PowError::Replayed);
+        }
+
+        // Charge the budget before the memory-hard part
+        let per_sender = self.verifies.get(sender).map_or(0, VecDeque::len);
+        if per_sender >= MAX_VERIFIES_PER_SENDER_PER_MIN
+            || self.verifies_total.len() >= MAX_VERIFIES_PER_MIN
+        {
+            return Err(PowError::RateLimited);
+        }
+        self.verifies
+            .entry(sender.to_vec())
+            .or_default()
+            .push_back(now);
+        self.verifies_total.push_back(now);
+        stamp.verify(msg_type, payload, now, &self.policy.params)?;
+
+        self.seen.insert(c);
+        self.seen_order.push_back((stamp.timestamp, c));
+        Ok(GateDecision::Accept(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap Argon2 params so tests stay fast.
+    fn fast() -> PowParams {
+        PowParams {
+            memory_kib: 8,
+            iterations: 1,
+        }
+    }
+
+    fn policy(base: u8) -> PowPolicy {
+        PowPolicy {
+            base_difficulty: base,
+            max_difficulty: 8,
+            surge_threshold_per_min: 2,
+            params: fast(),
+        }
+    }
+
+    #[test]
+    fn test_mint_verify_and_binding() {
+        let now = 1_700_000_000;
+        // High enough that the mismatched inputs below cannot solve by chance.
+        let stamp = PowStamp::mint(0x07, b"request", 12, now, &fast(), 1 << 20).unwrap();
+        assert!(stamp.verify(0x07, b"request", now + 5, &fast()).is_ok());
+        assert!(stamp.verify(0x07, b"other", now, &fast()).is_err());
+        assert!(stamp.verify(0x01, b"request", now, &fast()).is_err());
+        assert_eq!(
+            stamp.verify(0x07, b"request", now + MAX_STAMP_AGE_SECS + 1, &fast()),
+            Err(PowError::Expired)
+        );
+
+        let wire = attach_stamp(b"request", stamp);
+        assert_eq!(split_stamp(&wire), (&b"request"[..], Some(stamp)));
+        assert_eq!(split_stamp(b"request"), (&b"request"[..], None));
+    }
+
+    #[test]
+    fn test_gate_requires_stamp_and_rejects_replay() {
+        let now = 1_700_000_000;
+        let mut gate = FirstContactGate::new(policy(3));
+        assert_eq!(
+            gate.check(0x07, b"alice", b"hello", false, now),
+            Err(PowError::StampRequired(3))
+        );
+        assert_eq!(
+            gate.check(0x07, b"alice", b"hello", true, now),
+            Ok(GateDecision::Exempt(&b"hello"[..]))
+        );
+
+        let stamp = PowStamp::mint(0x07, b"hello", 3, now, &fast(), 10_000).unwrap();
+        let wire = attach_stamp(b"hello", stamp);
+        assert_eq!(
+            gate.check(0x07, b"alice", &wire, false, now),
+            Ok(GateDecision::Accept(&b"hello"[..]))
+        );
+        assert_eq!(
+            gate.check(0x07, b"alice", &wire, false, now),
+            Err(PowError::Replayed)
+        );
+    }
+
+    #[test]
+    fn test_verification_is_rate_limited_per_sender() {
+        let now = 1_700_000_000;
+        let mut gate = FirstContactGate::new(policy(3));
+        let stamped = |payload: &[u8], at: u64| {
+            attach_stamp(
+                payload,
+                PowStamp::mint(0x07, payload, 3, at, &fast(), 10_000).unwrap(),
+            )
+        };
+
+        for i in 0..MAX_VERIFIES_PER_SENDER_PER_MIN as u8 {
+            assert!(gate
+                .check(0x07, b"mallory", &stamped(&[i], now), false, now)
+                .is_ok());
+        }
+        // Rejected before Argon2 runs, even with a valid stamp
+        assert_eq!(
+            gate.check(0x07, b"mallory", &stamped(b"more", now), false, now),
+            Err(PowError::RateLimited)
+        );
+        assert!(gate
+            .check(0x07, b"alice", &stamped(b"hi", now), false, now)
+            .is_ok());
+
+        let later = now + 60;
+        assert!(gate
+            .check(0x07, b"mallory", &stamped(b"more", later), false, later)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_surge_raises_difficulty_but_honours_advert() {
+        let now = 1_700_000_000;
+        let mut gate = FirstContactGate::new(policy(2));
+        let advert = gate.advert(now);
+        assert_eq!(advert.difficulty, 2);
+        assert_eq!(
+            PowAdvert::from_json(&advert.to_json().unwrap()).unwrap(),
+            advert
+        );
+
+        // Flood of unstamped requests: 2 → 3 → 4 bits.
+        for _ in 0..8 {
+            let _ = gate.check(0x07, b"alice", b"spam", false, now);
+        }
+        assert_eq!(gate.current_difficulty(now), 4);
+
+        // A sender who fetched the earlier advert still gets through...
+        let stamp = PowStamp::mint(0x07, b"legit", 2, now, &fast(), 10_000).unwrap();
+        assert!(gate
+            .check(0x07, b"alice", &attach_stamp(b"legit", stamp), false, now)
+            .is_ok());
+        // ...until a fresh advert is published.
+        assert!(gate.advert(now).difficulty >= 4);
+        let late = PowStamp::mint(0x07, b"late", 2, now, &fast(), 10_000).unwrap();
+        assert!(matches!(
+            gate.check(0x07, b"alice", &attach_stamp(b"late", late), false, now),
+            Err(PowError::InsufficientDifficulty { .. })
+        ));
+
+        // Load decays after a minute.
+        assert_eq!(gate.current_difficulty(now + 61), 2);
+        assert_eq!(FirstContactGate::new(policy(0)).current_difficulty(now), 0);
+    }
+}
@@ -0,0 +1,406 @@
+/// Opt-in contact presence ("last active" buckets).
+///
+/// Contacts exchange a two-byte beacon over the existing encrypted channel
+/// that carries only a coarse bucket (active now, within the hour, today,
+/// this week, away), never a timestamp. Leaks are limited on both ends:
+///
+/// - **Opt-in per contact:** nothing is sent unless presence is enabled
+///   globally *and* for that contact. Presence is reciprocal: beacons from a
+///   contact we do not share with are discarded, not stored.
+/// - **Fuzzing:** the sender adds a random delay (up to `jitter_secs`) to
+///   its real idle time before bucketing, so bucket transitions do not
+///   reveal the exact moment the app was last used. Fuzzing only ever makes
+///   the sender look *less* recent.
+/// - **Rate limiting:** at most one beacon per contact per
+///   `min_beacon_interval_secs`, so beacon timing is not an activity feed.
+/// - **Coarse storage:** stored times are floored to `storage_granularity_secs`,
+///   and the book persists as an opaque blob the app keeps encrypted.
+///
+/// Received presence ages locally: a contact seen "active" three hours ago
+/// reads as "today", not "active".
+use super::contact_id::ContactId;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PresenceError {
+    #[error("Malformed presence beacon")]
+    Malformed,
+    #[error("Unsupported presence beacon version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Presence state encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Beacon wire version.
+pub const PRESENCE_BEACON_VERSION: u8 = 1;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Coarse "last active" bucket, most recent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PresenceBucket {
+    /// Within 5 minutes.
+    Active = 0,
+    /// Within the hour.
+    Recent = 1,
+    /// Within 24 hours.
+    Today = 2,
+    /// Within 7 days.
+    ThisWeek = 3,
+    /// Longer ago.
+    Away = 4,
+}
+
+impl PresenceBucket {
+    /// Bucket for an idle time in seconds.
+    pub fn from_idle_secs(idle: u64) -> Self {
+        match idle {
+            i if i < 5 * MINUTE => PresenceBucket::Active,
+            i if i < HOUR => PresenceBucket::Recent,
+            i if i < DAY => PresenceBucket::Today,
+            i if i < 7 * DAY => PresenceBucket::ThisWeek,
+            _ => PresenceBucket::Away,
+        }
+    }
+
+    /// Smallest idle time this bucket stands for.
+    pub fn min_idle_secs(self) -> u64 {
+        match self {
+            PresenceBucket::Active => 0,
+            PresenceBucket::Recent => 5 * MINUTE,
+            PresenceBucket::Today => HOUR,
+            PresenceBucket::ThisWeek => DAY,
+            PresenceBucket::Away => 7 * DAY,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PresenceBucket::Active),
+            1 => Some(PresenceBucket::Recent),
+            2 => Some(PresenceBucket::Today),
+            3 => Some(PresenceBucket::ThisWeek),
+            4 => Some(PresenceBucket::Away),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PresenceBucket::Active => "active",
+            PresenceBucket::Recent => "recent",
+            PresenceBucket::Today => "today",
+            PresenceBucket::ThisWeek => "this_week",
+            PresenceBucket::Away => "away",
+        }
+    }
+}
+
+/// Plaintext beacon, sent encrypted like any other message: `[version][bucket]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceBeacon {
+    pub bucket: PresenceBucket,
+}
+
+impl PresenceBeacon {
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [PRESENCE_BEACON_VERSION, self.bucket as u8]
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PresenceError> {
+        match data {
+            [PRESENCE_BEACON_VERSION, bucket] => PresenceBucket::from_u8(*bucket)
+                .map(|bucket| Self { bucket })
+                .ok_or(PresenceError::Malformed),
+            [version, _] => Err(PresenceError::UnsupportedVersion(*version)),
+            _ => Err(PresenceError::Malformed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceConfig {
+    /// Master switch; off by default.
+    pub enabled: bool,
+    /// Upper bound of the random delay added to our idle time before bucketing.
+    pub jitter_secs: u64,
+    /// Minimum spacing between beacons to the same contact.
+    pub min_beacon_interval_secs: u64,
+    /// Stored timestamps are floored to this many seconds.
+    pub storage_granularity_secs: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jitter_secs: 10 * MINUTE,
+            min_beacon_interval_secs: 15 * MINUTE,
+            storage_granularity_secs: 5 * MINUTE,
+        }
+    }
+}
+
+/// Per-contact presence state.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContactPresence {
+    /// We share our presence with this contact (and accept theirs).
+    pub sharing: bool,
+    /// Bucket from their last beacon and when we received it (coarsened).
+    pub last_seen: Option<(PresenceBucket, u64)>,
+    /// When we last sent them a beacon (coarsened).
+    pub last_beacon_sent: Option<u64>,
+}
+
+/// Presence state for all contacts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceBook {
+    config: PresenceConfig,
+    /// Our own last activity (coarsened).
+    last_active: Option<u64>,
+    contacts: HashMap<ContactId, ContactPresence>,
+}
+
+impl PresenceBook {
+    pub fn new(config: PresenceConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &PresenceConfig {
+        &self.config
+    }
+
+    /// Replace the configuration. Disabling presence forgets everything
+    /// received so far.
+    pub fn set_config(&mut self, config: PresenceConfig) {
+        if !config.enabled {
+            for contact in self.contacts.values_mut() {
+                contact.last_seen = None;
+            }
+        }
+        self.config = config;
+    }
+
+    fn coarsen(&self, now: u64) -> u64 {
+        let granularity = self.config.storage_granularity_secs.max(1);
+        now - now % granularity
+    }
+
+    /// Opt a contact in or out. Opting out drops their stored presence.
+    pub fn set_sharing(&mut self, contact_id: &ContactId, sharing: bool) {
+        if sharing {
+            self.contacts.entry(*contact_id).or_default().sharing = true;
+        } else {
+            self.contacts.remove(contact_id);
+        }
+    }
+
+    pub fn is_sharing(&self, contact_id: &ContactId) -> bool {
+        self.config.enabled && self.contacts.get(contact_id).is_some_and(|c| c.sharing)
+    }
+
+    /// Forget a contact entirely (e.g. contact deleted).
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.contacts.remove(contact_id);
+    }
+
+    /// Record local user activity.
+    pub fn note_activity(&mut self, now: u64) {
+        self.last_active = Some(self.coarsen(now));
+    }
+
+    /// Beacon to send to `contact_id`, or `None` if presence is off for them
+    /// or a beacon went out too recently.
+    pub fn beacon_for<R: Rng + ?Sized>(
+        &mut self,
+        contact_id: &ContactId,
+        now: u64,
+        rng: &mut R,
+    ) -> Option<PresenceBeacon> {
+        if !self.is_sharing(contact_id) {
+            return None;
+        }
+        let interval = self.config.min_beacon_interval_secs;
+        let jitter = self.config.jitter_secs;
+        let idle = self
+            .last_active
+            .map(|t| now.saturating_sub(t))
+            .unwrap_or(u64::MAX);
+        let sent_at = self.coarsen(now);
+
+        let contact = self.contacts.get_mut(contact_id)?;
+        if contact
+            .last_beacon_sent
+            .is_some_and(|sent| now.saturating_sub(sent) < interval)
+        {
+            return None;
+        }
+        contact.last_beacon_sent = Some(sent_at);
+
+        let fuzz = if jitter == 0 {
+            0
+        } else {
+            rng.gen_range(0..=jitter)
+        };
+        Some(PresenceBeacon {
+            bucket: PresenceBucket::from_idle_secs(idle.saturating_add(fuzz)),
+        })
+    }
+
+    /// Store a beacon from `contact_id`. Returns `false` (and stores nothing)
+    /// if we do not share presence with them.
+    pub fn record_beacon(
+        &mut self,
+        contact_id: &ContactId,
+        beacon: PresenceBeacon,
+        now: u64,
+    ) -> bool {
+        if !self.is_sharing(contact_id) {
+            return false;
+        }
+        let received_at = self.coarsen(now);
+        if let Some(contact) = self.contacts.get_mut(contact_id) {
+            contact.last_seen = Some((beacon.bucket, received_at));
+        }
+        true
+    }
+
+    /// Current presence of a contact, aged by the time since their last
+    /// beacon. `None` if unknown or not shared.
+    pub fn presence_of(&self, contact_id: &ContactId, now: u64) -> Option<PresenceBucket> {
+        if !self.is_sharing(contact_id) {
+            return None;
+        }
+        let (bucket, received_at) = self.contacts.get(contact_id)?.last_seen?;
+        let idle = bucket
+            .min_idle_secs()
+            .saturating_add(now.saturating_sub(received_at));
+        Some(PresenceBucket::from_idle_secs(idle).max(bucket))
+    }
+
+    /// Presence of every shared contact with a known bucket.
+    pub fn snapshot(&self, now: u64) -> Vec<(ContactId, PresenceBucket)> {
+        let mut out: Vec<_> = self
+            .contacts
+            .keys()
+            .filter_map(|id| self.presence_of(id, now).map(|b| (*id, b)))
+            .collect();
+        out.sort();
+        out
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PresenceError> {
+        bincode::serialize(self).map_err(|e| PresenceError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PresenceError> {
+        bincode::deserialize(data).map_err(|e| PresenceError::Encoding(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    fn enabled_book() -> PresenceBook {
+        PresenceBook::new(PresenceConfig {
+            enabled: true,
+            ..PresenceConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_opt_in_and_reciprocity() {
+        let mut book = enabled_book();
+        let mut rng = crate::rng::seeded(1);
+        let alice = test_contact(1);
+        book.note_activity(1_000);
+        assert!(book.beacon_for(&alice, 1_000, &mut rng).is_none());
+
+        let beacon = PresenceBeacon {
+            bucket: PresenceBucket::Active,
+        };
+        assert!(!book.record_beacon(&alice, beacon, 1_000));
+        assert_eq!(book.presence_of(&alice, 1_000), None);
+
+        book.set_sharing(&alice, true);
+        assert!(book.beacon_for(&alice, 1_000, &mut rng).is_some());
+        // Rate limited until the interval passes.
+        assert!(book.beacon_for(&alice, 1_060, &mut rng).is_none());
+        assert!(book.record_beacon(&alice, beacon, 1_000));
+
+        book.set_sharing(&alice, false);
+        assert_eq!(book.presence_of(&alice, 1_000), None);
+    }
+
+    #[test]
+    fn test_fuzzing_only_delays_and_presence_ages() {
+        let mut book = PresenceBook::new(PresenceConfig {
+            enabled: true,
+            jitter_secs: 4 * MINUTE,
+            min_beacon_interval_secs: 0,
+            storage_granularity_secs: 1,
+        });
+        let bob = test_contact(2);
+        book.set_sharing(&bob, true);
+        book.note_activity(10_000);
+        let mut rng = crate::rng::seeded(2);
+        for _ in 0..50 {
+            let b = book
+                .beacon_for(&bob, 10_000 + 2 * MINUTE, &mut rng)
+                .unwrap();
+            assert!(matches!(
+                b.bucket,
+                PresenceBucket::Active | PresenceBucket::Recent
+            ));
+        }
+
+        let beacon = PresenceBeacon {
+            bucket: PresenceBucket::Active,
+        };
+        book.record_beacon(&bob, beacon, 10_000);
+        assert_eq!(book.presence_of(&bob, 10_060), Some(PresenceBucket::Active));
+        assert_eq!(
+            book.presence_of(&bob, 10_000 + 3 * HOUR),
+            Some(PresenceBucket::Today)
+        );
+    }
+
+    #[test]
+    fn test_beacon_wire_and_persistence() {
+        let beacon = PresenceBeacon {
+            bucket: PresenceBucket::ThisWeek,
+        };
+        assert_eq!(PresenceBeacon::from_bytes(&beacon.to_bytes()), Ok(beacon));
+        assert_eq!(
+            PresenceBeacon::from_bytes(&[9, 0]),
+            Err(PresenceError::UnsupportedVersion(9))
+        );
+        assert_eq!(
+            PresenceBeacon::from_bytes(&[PRESENCE_BEACON_VERSION, 7]),
+            Err(PresenceError::Malformed)
+        );
+
+        let mut book = enabled_book();
+        let carol = test_contact(3);
+        book.set_sharing(&carol, true);
+        book.note_activity(1_234);
+        book.record_beacon(&carol, beacon, 1_234);
+        let restored = PresenceBook::from_bytes(&book.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, book);
+        // Stored times are coarsened to the storage granularity.
+        assert_eq!(
+            restored.contacts[&carol].last_seen,
+            Some((beacon.bucket, 1_200))
+        );
+    }
+}
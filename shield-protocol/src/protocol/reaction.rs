@@ -0,0 +1,351 @@
+/// Reactions in 1:1 conversations.
+///
+/// Groups get reactions from the CRDT (`crdt::messages`). A direct chat has
+/// no op log, so a reaction travels as its own small [`Reaction`] message,
+/// encrypted like any other 1:1 plaintext, that names the target message by
+/// its ID.
+///
+/// Each side of a conversation holds at most one reaction per message. A
+/// new reaction replaces the previous one, and `emoji: None` removes it.
+/// Both are last-writer-wins registers ordered by a per-conversation Lamport
+/// clock, so a late or duplicated reaction can never undo a newer change.
+/// Reactions that arrive before their target message are kept and show up
+/// once the message does.
+///
+/// [`ReactionBook::fold`] turns the registers into per-message counts for
+/// the conversation view.
+use super::contact_id::ContactId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ReactionError {
+    #[error("Malformed reaction")]
+    Malformed,
+    #[error("Unsupported reaction version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid reaction emoji")]
+    InvalidEmoji,
+    #[error("Invalid target message ID")]
+    InvalidMessageId,
+    #[error("Reaction state encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Reaction wire version.
+pub const REACTION_VERSION: u8 = 1;
+
+/// Longest accepted emoji, in UTF-8 bytes (fits ZWJ sequences and flags).
+pub const MAX_EMOJI_BYTES: usize = 32;
+
+/// Longest accepted target message ID, in bytes.
+pub const MAX_MESSAGE_ID_BYTES: usize = 128;
+
+/// Set or clear one side's reaction to a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reaction {
+    /// ID of the message reacted to.
+    pub message_id: String,
+    /// The reaction, or `None` to remove it.
+    pub emoji: Option<String>,
+    /// Sender's Lamport clock for this conversation.
+    pub clock: u64,
+}
+
+impl Reaction {
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ReactionError> {
+        let body = bincode::serialize(self).map_err(|e| ReactionError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(REACTION_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Parse and validate a received reaction.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ReactionError> {
+        let (&version, body) = data.split_first().ok_or(ReactionError::Malformed)?;
+        if version != REACTION_VERSION {
+            return Err(ReactionError::UnsupportedVersion(version));
+        }
+        let reaction: Self = bincode::deserialize(body).map_err(|_| ReactionError::Malformed)?;
+        validate(&reaction.message_id, reaction.emoji.as_deref())?;
+        Ok(reaction)
+    }
+}
+
+fn validate(message_id: &str, emoji: Option<&str>) -> Result<(), ReactionError> {
+    if message_id.is_empty() || message_id.len() > MAX_MESSAGE_ID_BYTES {
+        return Err(ReactionError::InvalidMessageId);
+    }
+    if let Some(emoji) = emoji {
+        if emoji.is_empty()
+            || emoji.len() > MAX_EMOJI_BYTES
+            || emoji.chars().any(|c| c.is_control() || c.is_whitespace())
+        {
+            return Err(ReactionError::InvalidEmoji);
+        }
+    }
+    Ok(())
+}
+
+/// Last-writer-wins register for one side's reaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Register {
+    emoji: Option<String>,
+    clock: u64,
+}
+
+impl Register {
+    /// Newer clock wins; equal clocks fall back to comparing the emoji so
+    /// both sides settle on the same value.
+    fn supersedes(&self, current: &Option<Register>) -> bool {
+        match current {
+            None => true,
+            Some(current) => (self.clock, &self.emoji) > (current.clock, &current.emoji),
+        }
+    }
+}
+
+/// Both sides' reactions to one message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageReactions {
+    local: Option<Register>,
+    peer: Option<Register>,
+}
+
+impl MessageReactions {
+    /// Our reaction, if any.
+    pub fn local(&self) -> Option<&str> {
+        self.local.as_ref().and_then(|r| r.emoji.as_deref())
+    }
+
+    /// The contact's reaction, if any.
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_ref().and_then(|r| r.emoji.as_deref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local().is_none() && self.peer().is_none()
+    }
+}
+
+/// Reactions of one message, ready to render.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionView {
+    pub message_id: String,
+    /// `(emoji, count)`, sorted by emoji.
+    pub counts: Vec<(String, u8)>,
+    /// Our reaction, to highlight it and to toggle it off.
+    pub local: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Conversation {
+    /// Highest clock sent or seen.
+    clock: u64,
+    messages: HashMap<String, MessageReactions>,
+}
+
+/// Reaction state for all 1:1 conversations.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionBook {
+    conversations: HashMap<ContactId, Conversation>,
+}
+
+impl ReactionBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set (or with `None`, remove) our reaction to `message_id` and return
+    /// the [`Reaction`] to send to the contact.
+    pub fn set_local(
+        &mut self,
+        contact_id: &ContactId,
+        message_id: &str,
+        emoji: Option<&str>,
+    ) -> Result<Reaction, ReactionError> {
+        validate(message_id, emoji)?;
+        let conversation = self.conversations.entry(*contact_id).or_default();
+        conversation.clock += 1;
+        let reaction = Reaction {
+            message_id: message_id.to_string(),
+            emoji: emoji.map(str::to_string),
+            clock: conversation.clock,
+        };
+        conversation
+            .messages
+            .entry(reaction.message_id.clone())
+            .or_default()
+            .local = Some(Register {
+            emoji: reaction.emoji.clone(),
+            clock: reaction.clock,
+        });
+        Ok(reaction)
+    }
+
+    /// Apply a reaction received from `contact_id`. Returns whether the
+    /// visible state of the target message changed.
+    pub fn apply_remote(&mut self, contact_id: &ContactId, reaction: &Reaction) -> bool {
+        let conversation = self.conversations.entry(*contact_id).or_default();
+        conversation.clock = conversation.clock.max(reaction.clock);
+        let entry = conversation
+            .messages
+            .entry(reaction.message_id.clone())
+            .or_default();
+        let register = Register {
+            emoji: reaction.emoji.clone(),
+            clock: reaction.clock,
+        };
+        if !register.supersedes(&entry.peer) {
+            return false;
+        }
+        let before = entry.peer().map(str::to_string);
+        entry.peer = Some(register);
+        before.as_deref() != entry.peer()
+    }
+
+    /// Reactions to one message.
+    pub fn get(&self, contact_id: &ContactId, message_id: &str) -> Option<&MessageReactions> {
+        self.conversations.get(contact_id)?.messages.get(message_id)
+    }
+
+    /// View state for the given messages of a conversation, in the order
+    /// given. Messages without reactions are left out.
+    pub fn fold<'a>(
+        &self,
+        contact_id: &ContactId,
+        message_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<ReactionView> {
+        let Some(conversation) = self.conversations.get(contact_id) else {
+            return Vec::new();
+        };
+        message_ids
+            .into_iter()
+            .filter_map(|id| {
+                let reactions = conversation.messages.get(id)?;
+                let mut counts: BTreeMap<&str, u8> = BTreeMap::new();
+                for emoji in [reactions.local(), reactions.peer()].into_iter().flatten() {
+                    *counts.entry(emoji).or_default() += 1;
+                }
+                if counts.is_empty() {
+                    return None;
+                }
+                Some(ReactionView {
+                    message_id: id.to_string(),
+                    counts: counts
+                        .into_iter()
+                        .map(|(emoji, n)| (emoji.to_string(), n))
+                        .collect(),
+                    local: reactions.local().map(str::to_string),
+                })
+            })
+            .collect()
+    }
+
+    /// Drop reactions to a deleted message.
+    pub fn forget_message(&mut self, contact_id: &ContactId, message_id: &str) {
+        if let Some(conversation) = self.conversations.get_mut(contact_id) {
+            conversation.messages.remove(message_id);
+        }
+    }
+
+    /// Forget a conversation (contact deleted or history cleared).
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.conversations.remove(contact_id);
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ReactionError> {
+        bincode::serialize(self).map_err(|e| ReactionError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ReactionError> {
+        bincode::deserialize(data).map_err(|e| ReactionError::Encoding(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    #[test]
+    fn test_reaction_wire_roundtrip_and_validation() {
+        let reaction = Reaction {
+            message_id: "msg-1".to_string(),
+            emoji: Some("👍🏽".to_string()),
+            clock: 3,
+        };
+        let bytes = reaction.to_bytes().unwrap();
+        assert_eq!(Reaction::from_bytes(&bytes).unwrap(), reaction);
+
+        let mut bad = bytes.clone();
+        bad[0] = 9;
+        assert_eq!(
+            Reaction::from_bytes(&bad),
+            Err(ReactionError::UnsupportedVersion(9))
+        );
+        assert_eq!(Reaction::from_bytes(&[]), Err(ReactionError::Malformed));
+        let spoofed = Reaction {
+            emoji: Some("hi\n".to_string()),
+            ..reaction
+        };
+        assert_eq!(
+            Reaction::from_bytes(&spoofed.to_bytes().unwrap()),
+            Err(ReactionError::InvalidEmoji)
+        );
+
+        let mut book = ReactionBook::new();
+        assert_eq!(
+            book.set_local(&test_contact(4), "", Some("👍")),
+            Err(ReactionError::InvalidMessageId)
+        );
+    }
+
+    #[test]
+    fn test_last_writer_wins_per_side() {
+        let alice = test_contact(4);
+        let mut book = ReactionBook::new();
+
+        // Peer reacts, then changes its mind; the first update arrives last
+        let first = Reaction {
+            message_id: "m".to_string(),
+            emoji: Some("😂".to_string()),
+            clock: 1,
+        };
+        let second = Reaction {
+            emoji: Some("❤️".to_string()),
+            clock: 2,
+            ..first.clone()
+        };
+        assert!(book.apply_remote(&alice, &second));
+        assert!(!book.apply_remote(&alice, &first));
+        assert!(!book.apply_remote(&alice, &second));
+        assert_eq!(book.get(&alice, "m").unwrap().peer(), Some("❤️"));
+
+        // Our reaction is sent with a clock past everything seen
+        let sent = book.set_local(&alice, "m", Some("❤️")).unwrap();
+        assert_eq!(sent.clock, 3);
+        let view = book.fold(&alice, ["m", "other"]);
+        assert_eq!(view.len(), 1);
+        assert_eq!(view[0].counts, vec![("❤️".to_string(), 2)]);
+        assert_eq!(view[0].local.as_deref(), Some("❤️"));
+
+        // Removal beats the stale add, and empty messages drop out of the view
+        let removal = Reaction {
+            emoji: None,
+            clock: 4,
+            ..first.clone()
+        };
+        assert!(book.apply_remote(&alice, &removal));
+        assert!(!book.apply_remote(&alice, &first));
+        book.set_local(&alice, "m", None).unwrap();
+        assert!(book.get(&alice, "m").unwrap().is_empty());
+        assert!(book.fold(&alice, ["m"]).is_empty());
+
+        let restored = ReactionBook::from_bytes(&book.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, book);
+    }
+}
@@ -0,0 +1,605 @@
+/// Message recall ("unsend") in 1:1 conversations.
+///
+/// Within a window after sending, the sender may ask the recipient to
+/// delete one or more messages with a [`RecallRequest`]. Nothing can force a
+/// remote device to forget what it received, so recall is a request that a
+/// well-behaved client honors where it still matters: a message that has
+/// not been displayed yet is deleted unseen, a message already on screen is
+/// left alone. A message that has not arrived yet is remembered and dropped
+/// when it does ([`RecallGuard::is_recalled`]).
+///
+/// The recipient answers with a [`RecallAck`]: one [`RecallVerdict`] per
+/// message, signed with its Ed25519 identity key over the recaller's
+/// [`ContactId`], the recall ID and the verdicts. The signature does not
+/// prove the bytes are gone, but it is the recipient client's binding
+/// statement of what it did, which the sender keeps as evidence the same way
+/// as a [`DeliveryProof`](super::delivery_proof::DeliveryProof).
+///
+/// The window is measured on the sender's clock: every message must be
+/// younger than the window when the recall is requested, and the recipient
+/// compares the request time with the send time it stored for the message,
+/// so a recall queued while the recipient was offline is still honored.
+///
+/// The sender's [`RecallTracker`] matches acks to outstanding requests and
+/// folds the verdicts into a [`RecallOutcome`].
+use super::contact_id::ContactId;
+use crate::crypto::signing::{sign_data, verify_signature};
+use crate::rng::SecureRng;
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RecallError {
+    #[error("Malformed recall frame")]
+    Malformed,
+    #[error("Unsupported recall version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid message ID in recall")]
+    InvalidMessageId,
+    #[error("Recall names {0} messages")]
+    TooManyMessages(usize),
+    #[error("Recall window of {0}s out of range")]
+    InvalidWindow(u32),
+    #[error("Recall window for message {0} has closed")]
+    WindowClosed(String),
+    #[error("Recall request dated in the future")]
+    FromTheFuture,
+    #[error("No outstanding recall for this acknowledgment")]
+    UnknownRecall,
+    #[error("Acknowledgment does not answer the recalled messages")]
+    AckMismatch,
+    #[error("Invalid recall acknowledgment signature")]
+    InvalidSignature,
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Recall encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Recall wire version.
+pub const RECALL_VERSION: u8 = 1;
+
+/// Window used until the user configures one.
+pub const DEFAULT_RECALL_WINDOW_SECS: u32 = 15 * 60;
+
+/// Longest window a sender may use and a recipient accepts.
+pub const MAX_RECALL_WINDOW_SECS: u32 = 24 * 60 * 60;
+
+/// Most messages named by one request.
+pub const MAX_RECALL_MESSAGES: usize = 32;
+
+/// Accepted difference between the two devices' clocks.
+pub const RECALL_CLOCK_SKEW_SECS: u64 = 120;
+
+/// Longest accepted message ID, in bytes.
+pub const MAX_MESSAGE_ID_BYTES: usize = 128;
+
+/// Recalls of messages not received yet, kept per contact.
+const MAX_TOMBSTONES_PER_CONTACT: usize = 256;
+
+const SIGN_CONTEXT: &[u8] = b"ShieldMessenger-RecallAck-v1";
+
+/// Sender's request to delete messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecallRequest {
+    pub recall_id: [u8; 16],
+    pub message_ids: Vec<String>,
+    /// Window the sender applied, so the recipient checks the same one.
+    pub window_secs: u32,
+    /// Sender's clock, Unix seconds.
+    pub requested_at: u64,
+}
+
+/// What the recipient did with one recalled message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecallVerdict {
+    /// Deleted before it was displayed.
+    Deleted,
+    /// Not received yet; it will be dropped on arrival.
+    NotReceived,
+    /// Already displayed, kept.
+    AlreadyDisplayed,
+    /// Sent longer than the window before the recall, kept.
+    Expired,
+}
+
+impl RecallVerdict {
+    pub fn is_honored(self) -> bool {
+        matches!(self, RecallVerdict::Deleted | RecallVerdict::NotReceived)
+    }
+}
+
+/// Recipient's signed answer to a [`RecallRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecallAck {
+    pub recall_id: [u8; 16],
+    /// In request order.
+    pub verdicts: Vec<(String, RecallVerdict)>,
+    #[serde(with = "BigArray")]
+    pub signature: [u8; 64],
+}
+
+impl RecallAck {
+    fn signed_bytes(
+        recaller: &ContactId,
+        recall_id: &[u8; 16],
+        verdicts: &[(String, RecallVerdict)],
+    ) -> Result<Vec<u8>, RecallError> {
+        let body = bincode::serialize(&(recall_id, verdicts))
+            .map_err(|e| RecallError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(SIGN_CONTEXT.len() + 20 + body.len());
+        out.extend_from_slice(SIGN_CONTEXT);
+        out.extend_from_slice(recaller.as_bytes());
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Recipient side: sign the verdicts for a recall from `recaller`.
+    pub fn sign(
+        recaller: &ContactId,
+        recall_id: [u8; 16],
+        verdicts: Vec<(String, RecallVerdict)>,
+        identity_private_key: &[u8],
+    ) -> Result<Self, RecallError> {
+        let data = Self::signed_bytes(recaller, &recall_id, &verdicts)?;
+        let signature = sign_data(&data, identity_private_key)
+            .map_err(|e| RecallError::Signing(e.to_string()))?;
+        Ok(Self {
+            recall_id,
+            verdicts,
+            signature,
+        })
+    }
+
+    /// Check the signature of an ack answering a recall by `recaller`.
+    pub fn verify(
+        &self,
+        recaller: &ContactId,
+        identity_public_key: &[u8],
+    ) -> Result<(), RecallError> {
+        let data = Self::signed_bytes(recaller, &self.recall_id, &self.verdicts)?;
+        match verify_signature(&data, &self.signature, identity_public_key) {
+            Ok(true) => Ok(()),
+            _ => Err(RecallError::InvalidSignature),
+        }
+    }
+}
+
+/// Everything sent as a recall message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecallFrame {
+    Request(RecallRequest),
+    Ack(RecallAck),
+}
+
+impl RecallFrame {
+    /// `[version][bincode]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RecallError> {
+        let body = bincode::serialize(self).map_err(|e| RecallError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + body.len());
+        out.push(RECALL_VERSION);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Parse and validate a received frame.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RecallError> {
+        let (&version, body) = data.split_first().ok_or(RecallError::Malformed)?;
+        if version != RECALL_VERSION {
+            return Err(RecallError::UnsupportedVersion(version));
+        }
+        let frame: Self = bincode::deserialize(body).map_err(|_| RecallError::Malformed)?;
+        let ids: Vec<&str> = match &frame {
+            RecallFrame::Request(request) => {
+                validate_window(request.window_secs)?;
+                request.message_ids.iter().map(String::as_str).collect()
+            }
+            RecallFrame::Ack(ack) => ack.verdicts.iter().map(|(id, _)| id.as_str()).collect(),
+        };
+        validate_ids(&ids)?;
+        Ok(frame)
+    }
+}
+
+fn validate_window(window_secs: u32) -> Result<(), RecallError> {
+    if window_secs == 0 || window_secs > MAX_RECALL_WINDOW_SECS {
+        return Err(RecallError::InvalidWindow(window_secs));
+    }
+    Ok(())
+}
+
+fn validate_ids(ids: &[&str]) -> Result<(), RecallError> {
+    if ids.is_empty() || ids.len() > MAX_RECALL_MESSAGES {
+        return Err(RecallError::TooManyMessages(ids.len()));
+    }
+    if ids
+        .iter()
+        .any(|id| id.is_empty() || id.len() > MAX_MESSAGE_ID_BYTES)
+    {
+        return Err(RecallError::InvalidMessageId);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Recipient side
+// ---------------------------------------------------------------------------
+
+/// A recalled message as the recipient's app has it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalMessageState {
+    NotReceived,
+    /// Stored but not displayed; `sent_at` is the sender's timestamp.
+    Undisplayed {
+        sent_at: u64,
+    },
+    Displayed,
+}
+
+/// Recipient's record of recalls for messages that have not arrived.
+#[derive(Debug, Default)]
+pub struct RecallGuard {
+    /// contact → message ID → forget after (Unix seconds).
+    tombstones: HashMap<ContactId, HashMap<String, u64>>,
+}
+
+impl RecallGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide every message of a recall from `sender`. The app deletes the
+    /// messages judged [`RecallVerdict::Deleted`] and sends back the ack
+    /// signed from the returned verdicts.
+    pub fn handle(
+        &mut self,
+        sender: &ContactId,
+        request: &RecallRequest,
+        now: u64,
+        state_of: impl Fn(&str) -> LocalMessageState,
+    ) -> Result<Vec<(String, RecallVerdict)>, RecallError> {
+        if request.requested_at > now + RECALL_CLOCK_SKEW_SECS {
+            return Err(RecallError::FromTheFuture);
+        }
+        let window = u64::from(request.window_secs);
+        let verdicts = request
+            .message_ids
+            .iter()
+            .map(|id| {
+                let verdict = match state_of(id) {
+                    LocalMessageState::Displayed => RecallVerdict::AlreadyDisplayed,
+                    LocalMessageState::Undisplayed { sent_at }
+                        if request.requested_at > sent_at + window + RECALL_CLOCK_SKEW_SECS =>
+                    {
+                        RecallVerdict::Expired
+                    }
+                    LocalMessageState::Undisplayed { .. } => RecallVerdict::Deleted,
+                    LocalMessageState::NotReceived => {
+                        self.remember(sender, id, now + window);
+                        RecallVerdict::NotReceived
+                    }
+                };
+                (id.clone(), verdict)
+            })
+            .collect();
+        Ok(verdicts)
+    }
+
+    fn remember(&mut self, sender: &ContactId, message_id: &str, until: u64) {
+        let tombstones = self.tombstones.entry(*sender).or_default();
+        if tombstones.len() >= MAX_TOMBSTONES_PER_CONTACT {
+            // Evict the one closest to expiry
+            if let Some(oldest) = tombstones
+                .iter()
+                .min_by_key(|(_, until)| **until)
+                .map(|(id, _)| id.clone())
+            {
+                tombstones.remove(&oldest);
+            }
+        }
+        tombstones.insert(message_id.to_string(), until);
+    }
+
+    /// Whether an arriving message from `sender` was recalled before it got
+    /// here; the caller drops it unseen. Consumes the record.
+    pub fn is_recalled(&mut self, sender: &ContactId, message_id: &str, now: u64) -> bool {
+        let Some(tombstones) = self.tombstones.get_mut(sender) else {
+            return false;
+        };
+        let recalled = tombstones
+            .remove(message_id)
+            .is_some_and(|until| now <= until);
+        if tombstones.is_empty() {
+            self.tombstones.remove(sender);
+        }
+        recalled
+    }
+
+    /// Drop records past their window.
+    pub fn prune(&mut self, now: u64) {
+        self.tombstones.retain(|_, tombstones| {
+            tombstones.retain(|_, until| now <= *until);
+            !tombstones.is_empty()
+        });
+    }
+
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.tombstones.remove(contact_id);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sender side
+// ---------------------------------------------------------------------------
+
+/// How a recall went overall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecallOutcome {
+    /// Every message deleted (or dropped on arrival).
+    Honored,
+    /// Some messages deleted, some kept.
+    PartiallyHonored,
+    /// No message deleted.
+    Refused,
+}
+
+impl RecallOutcome {
+    pub fn of(verdicts: &[(String, RecallVerdict)]) -> Self {
+        let honored = verdicts.iter().filter(|(_, v)| v.is_honored()).count();
+        match honored {
+            0 => RecallOutcome::Refused,
+            n if n == verdicts.len() => RecallOutcome::Honored,
+            _ => RecallOutcome::PartiallyHonored,
+        }
+    }
+}
+
+/// Result of a verified ack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecallReport {
+    pub recall_id: [u8; 16],
+    pub outcome: RecallOutcome,
+    pub verdicts: Vec<(String, RecallVerdict)>,
+    /// The verified ack, kept as the recipient's signed statement.
+    pub ack: RecallAck,
+}
+
+#[derive(Debug, Clone)]
+struct PendingRecall {
+    contact_id: ContactId,
+    message_ids: Vec<String>,
+}
+
+/// Sender's outstanding recalls.
+#[derive(Debug)]
+pub struct RecallTracker {
+    window_secs: u32,
+    pending: HashMap<[u8; 16], PendingRecall>,
+}
+
+impl Default for RecallTracker {
+    fn default() -> Self {
+        Self {
+            window_secs: DEFAULT_RECALL_WINDOW_SECS,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl RecallTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn window_secs(&self) -> u32 {
+        self.window_secs
+    }
+
+    pub fn set_window(&mut self, window_secs: u32) -> Result<(), RecallError> {
+        validate_window(window_secs)?;
+        self.window_secs = window_secs;
+        Ok(())
+    }
+
+    /// Request recall of `messages` (`(message ID, sent_at)`) sent to
+    /// `contact_id`. Fails without side effects if any message is older
+    /// than the window.
+    pub fn request(
+        &mut self,
+        contact_id: &ContactId,
+        messages: &[(String, u64)],
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<RecallRequest, RecallError> {
+        let ids: Vec<&str> = messages.iter().map(|(id, _)| id.as_str()).collect();
+        validate_ids(&ids)?;
+        if let Some((id, _)) = messages
+            .iter()
+            .find(|(_, sent_at)| now > sent_at + u64::from(self.window_secs))
+        {
+            return Err(RecallError::WindowClosed(id.clone()));
+        }
+
+        let mut recall_id = [0u8; 16];
+        rng.fill_bytes(&mut recall_id);
+        let message_ids: Vec<String> = messages.iter().map(|(id, _)| id.clone()).collect();
+        self.pending.insert(
+            recall_id,
+            PendingRecall {
+                contact_id: *contact_id,
+                message_ids: message_ids.clone(),
+            },
+        );
+        Ok(RecallRequest {
+            recall_id,
+            message_ids,
+            window_secs: self.window_secs,
+            requested_at: now,
+        })
+    }
+
+    /// Check an ack from `contact_id` against its outstanding recall.
+    /// `us` is our own contact ID (the one the contact knows us by).
+    pub fn on_ack(
+        &mut self,
+        contact_id: &ContactId,
+        ack: RecallAck,
+        us: &ContactId,
+        contact_public_key: &[u8],
+    ) -> Result<RecallReport, RecallError> {
+        let pending = match self.pending.get(&ack.recall_id) {
+            Some(pending) if pending.contact_id == *contact_id => pending,
+            _ => return Err(RecallError::UnknownRecall),
+        };
+        let answered = ack
+            .verdicts
+            .iter()
+            .map(|(id, _)| id)
+            .eq(pending.message_ids.iter());
+        if !answered {
+            return Err(RecallError::AckMismatch);
+        }
+        ack.verify(us, contact_public_key)?;
+
+        self.pending.remove(&ack.recall_id);
+        Ok(RecallReport {
+            recall_id: ack.recall_id,
+            outcome: RecallOutcome::of(&ack.verdicts),
+            verdicts: ack.verdicts.clone(),
+            ack,
+        })
+    }
+
+    /// Recalls still waiting for an ack.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.pending.retain(|_, p| p.contact_id != *contact_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_recall_partially_honored_with_signed_ack() {
+        let mut rng = seeded(1);
+        let (alice_pub, _) = generate_keypair_with_rng(&mut rng);
+        let (bob_pub, bob_priv) = generate_keypair_with_rng(&mut rng);
+        let alice = ContactId::from_identity_key(&alice_pub).unwrap();
+        let bob = ContactId::from_identity_key(&bob_pub).unwrap();
+
+        // Alice recalls three messages sent a minute ago
+        let mut tracker = RecallTracker::new();
+        let sent: Vec<_> = ["m-unread", "m-seen", "m-in-flight"]
+            .iter()
+            .map(|id| (id.to_string(), NOW - 60))
+            .collect();
+        let request = tracker.request(&bob, &sent, NOW, &mut rng).unwrap();
+        let frame = RecallFrame::Request(request).to_bytes().unwrap();
+
+        // Bob, whose clock runs a bit behind
+        let RecallFrame::Request(request) = RecallFrame::from_bytes(&frame).unwrap() else {
+            panic!("expected a request");
+        };
+        let mut guard = RecallGuard::new();
+        let verdicts = guard
+            .handle(&alice, &request, NOW - 30, |id| match id {
+                "m-unread" => LocalMessageState::Undisplayed { sent_at: NOW - 60 },
+                "m-seen" => LocalMessageState::Displayed,
+                _ => LocalMessageState::NotReceived,
+            })
+            .unwrap();
+        assert!(guard.is_recalled(&alice, "m-in-flight", NOW));
+        assert!(!guard.is_recalled(&alice, "m-in-flight", NOW));
+        let ack = RecallAck::sign(&alice, request.recall_id, verdicts, &bob_priv).unwrap();
+        let frame = RecallFrame::Ack(ack).to_bytes().unwrap();
+
+        let RecallFrame::Ack(ack) = RecallFrame::from_bytes(&frame).unwrap() else {
+            panic!("expected an ack");
+        };
+        // Signed for Alice only, and by Bob only; a bad ack leaves the
+        // recall outstanding
+        assert_eq!(
+            tracker.on_ack(&bob, ack.clone(), &bob, &bob_pub),
+            Err(RecallError::InvalidSignature)
+        );
+        assert_eq!(
+            tracker.on_ack(&bob, ack.clone(), &alice, &alice_pub),
+            Err(RecallError::InvalidSignature)
+        );
+        assert_eq!(
+            tracker.on_ack(&alice, ack.clone(), &alice, &bob_pub),
+            Err(RecallError::UnknownRecall)
+        );
+        let report = tracker.on_ack(&bob, ack.clone(), &alice, &bob_pub).unwrap();
+        assert_eq!(report.outcome, RecallOutcome::PartiallyHonored);
+        assert_eq!(
+            report.verdicts,
+            vec![
+                ("m-unread".to_string(), RecallVerdict::Deleted),
+                ("m-seen".to_string(), RecallVerdict::AlreadyDisplayed),
+                ("m-in-flight".to_string(), RecallVerdict::NotReceived),
+            ]
+        );
+        assert_eq!(tracker.pending(), 0);
+        assert_eq!(
+            tracker.on_ack(&bob, ack, &alice, &bob_pub),
+            Err(RecallError::UnknownRecall)
+        );
+    }
+
+    #[test]
+    fn test_recall_window() {
+        let mut rng = seeded(2);
+        let bob = test_contact(2);
+        let alice = test_contact(1);
+        let mut tracker = RecallTracker::new();
+        assert_eq!(tracker.set_window(0), Err(RecallError::InvalidWindow(0)));
+        tracker.set_window(300).unwrap();
+
+        let old = vec![("m-old".to_string(), NOW - 301)];
+        assert_eq!(
+            tracker.request(&bob, &old, NOW, &mut rng),
+            Err(RecallError::WindowClosed("m-old".into()))
+        );
+        assert_eq!(tracker.pending(), 0);
+
+        // The recipient judges by the request time, not its own clock: a
+        // recall queued for a day is still honored, a backdated message is not
+        let request = tracker
+            .request(&bob, &[("m-1".into(), NOW - 10)], NOW, &mut rng)
+            .unwrap();
+        let mut guard = RecallGuard::new();
+        let late = NOW + 86_400;
+        let verdicts = guard
+            .handle(&alice, &request, late, |_| LocalMessageState::Undisplayed {
+                sent_at: NOW - 10,
+            })
+            .unwrap();
+        assert_eq!(verdicts[0].1, RecallVerdict::Deleted);
+        let verdicts = guard
+            .handle(&alice, &request, late, |_| LocalMessageState::Undisplayed {
+                sent_at: NOW - 1_000,
+            })
+            .unwrap();
+        assert_eq!(verdicts[0].1, RecallVerdict::Expired);
+        assert_eq!(RecallOutcome::of(&verdicts), RecallOutcome::Refused);
+        assert_eq!(
+            guard.handle(&alice, &request, NOW - 600, |_| {
+                LocalMessageState::Displayed
+            }),
+            Err(RecallError::FromTheFuture)
+        );
+    }
+}
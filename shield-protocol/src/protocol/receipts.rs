@@ -0,0 +1,336 @@
+/// Batched delivery receipts.
+///
+/// Acknowledging every message with its own connection makes receipts the
+/// noisiest traffic a node produces: each one is a fresh circuit whose timing
+/// mirrors the message it confirms. Instead, received sequence numbers (the
+/// per-conversation `seq` from [`ordering`](super::ordering)) are collected
+/// in a [`ReceiptBatcher`] and confirmed together as an [`AckBatch`] of
+/// inclusive ranges.
+///
+/// A batch goes out in one of two ways:
+///
+/// - **Piggybacked** ([`ReceiptBatcher::take`]) on a connection that is
+///   already open to the contact, typically the one their next message
+///   arrived on. This costs no extra connection at all.
+/// - **Standalone** ([`ReceiptBatcher::poll`]) once the oldest pending
+///   receipt has waited `max_delay_ms` or `max_pending` receipts have piled
+///   up, so senders are never left waiting indefinitely.
+///
+/// Batches are idempotent: confirming a range twice is harmless, so a lost
+/// batch only costs a retransmission on the sender side.
+use super::contact_id::ContactId;
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ReceiptError {
+    #[error("Malformed ACK batch")]
+    Malformed,
+    #[error("Unsupported ACK batch version {0}")]
+    UnsupportedVersion(u8),
+}
+
+/// ACK batch wire version.
+pub const ACK_BATCH_VERSION: u8 = 1;
+
+/// Most ranges in one batch; keeps an encrypted batch well inside a packet.
+pub const MAX_ACK_RANGES: usize = 64;
+
+/// `[version][count]`
+const HEADER_LEN: usize = 2;
+/// `[first: u64 BE][last: u64 BE]`
+const RANGE_LEN: usize = 16;
+
+/// Sequence numbers `first..=last`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl AckRange {
+    pub fn contains(&self, seq: u64) -> bool {
+        self.first <= seq && seq <= self.last
+    }
+}
+
+/// Receipt for a set of sequence numbers, as sorted, disjoint ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AckBatch {
+    ranges: Vec<AckRange>,
+}
+
+impl AckBatch {
+    /// Batch covering `seqs` (any order, duplicates allowed). May hold more
+    /// than [`MAX_ACK_RANGES`] ranges; use [`ReceiptBatcher`] for sendable
+    /// batches.
+    pub fn from_seqs(seqs: impl IntoIterator<Item = u64>) -> Self {
+        let sorted: BTreeSet<u64> = seqs.into_iter().collect();
+        let mut ranges: Vec<AckRange> = Vec::new();
+        for seq in sorted {
+            match ranges.last_mut() {
+                Some(range) if range.last.checked_add(1) == Some(seq) => range.last = seq,
+                _ => ranges.push(AckRange {
+                    first: seq,
+                    last: seq,
+                }),
+            }
+        }
+        Self { ranges }
+    }
+
+    pub fn ranges(&self) -> &[AckRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Whether `seq` is acknowledged by this batch.
+    pub fn contains(&self, seq: u64) -> bool {
+        let idx = self.ranges.partition_point(|r| r.last < seq);
+        self.ranges.get(idx).is_some_and(|r| r.contains(seq))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        debug_assert!(self.ranges.len() <= MAX_ACK_RANGES);
+        let mut out = Vec::with_capacity(HEADER_LEN + self.ranges.len() * RANGE_LEN);
+        out.push(ACK_BATCH_VERSION);
+        out.push(self.ranges.len() as u8);
+        for range in &self.ranges {
+            out.extend_from_slice(&range.first.to_be_bytes());
+            out.extend_from_slice(&range.last.to_be_bytes());
+        }
+        out
+    }
+
+    /// Parse a batch, rejecting unsorted, overlapping or inverted ranges.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ReceiptError> {
+        if data.len() < HEADER_LEN {
+            return Err(ReceiptError::Malformed);
+        }
+        if data[0] != ACK_BATCH_VERSION {
+            return Err(ReceiptError::UnsupportedVersion(data[0]));
+        }
+        let count = data[1] as usize;
+        if count > MAX_ACK_RANGES || data.len() != HEADER_LEN + count * RANGE_LEN {
+            return Err(ReceiptError::Malformed);
+        }
+
+        let mut ranges: Vec<AckRange> = Vec::with_capacity(count);
+        for chunk in data[HEADER_LEN..].chunks_exact(RANGE_LEN) {
+            let mut first = [0u8; 8];
+            first.copy_from_slice(&chunk[..8]);
+            let mut last = [0u8; 8];
+            last.copy_from_slice(&chunk[8..]);
+            let range = AckRange {
+                first: u64::from_be_bytes(first),
+                last: u64::from_be_bytes(last),
+            };
+            if range.first > range.last || ranges.last().is_some_and(|p| p.last >= range.first) {
+                return Err(ReceiptError::Malformed);
+            }
+            ranges.push(range);
+        }
+        Ok(Self { ranges })
+    }
+}
+
+/// When pending receipts must go out on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptConfig {
+    /// Longest a receipt waits for a connection to ride on.
+    pub max_delay_ms: u64,
+    /// Pending receipts per contact that force a standalone batch.
+    pub max_pending: usize,
+}
+
+impl Default for ReceiptConfig {
+    fn default() -> Self {
+        Self {
+            max_delay_ms: 3_000,
+            max_pending: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PendingReceipts {
+    seqs: BTreeSet<u64>,
+    /// When the oldest pending receipt was recorded (ms).
+    since: u64,
+}
+
+impl PendingReceipts {
+    /// Remove and return up to [`MAX_ACK_RANGES`] ranges, lowest first.
+    fn take_batch(&mut self) -> AckBatch {
+        let mut batch = AckBatch::from_seqs(self.seqs.iter().copied());
+        if batch.ranges.len() > MAX_ACK_RANGES {
+            batch.ranges.truncate(MAX_ACK_RANGES);
+            let covered = batch.ranges[MAX_ACK_RANGES - 1].last;
+            self.seqs = self.seqs.split_off(&(covered + 1));
+        } else {
+            self.seqs.clear();
+        }
+        batch
+    }
+}
+
+/// Receive-side receipt queue for all conversations.
+#[derive(Debug, Clone, Default)]
+pub struct ReceiptBatcher {
+    config: ReceiptConfig,
+    pending: HashMap<ContactId, PendingReceipts>,
+}
+
+impl ReceiptBatcher {
+    pub fn new(config: ReceiptConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ReceiptConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ReceiptConfig) {
+        self.config = config;
+    }
+
+    /// Queue a receipt for message `seq` from `contact_id`.
+    pub fn record(&mut self, contact_id: ContactId, seq: u64, now: u64) {
+        let pending = self.pending.entry(contact_id).or_default();
+        if pending.seqs.is_empty() {
+            pending.since = now;
+        }
+        pending.seqs.insert(seq);
+    }
+
+    /// Receipts waiting for `contact_id`.
+    pub fn pending_for(&self, contact_id: &ContactId) -> usize {
+        self.pending.get(contact_id).map_or(0, |p| p.seqs.len())
+    }
+
+    /// Everything pending for `contact_id`, to piggyback on a connection
+    /// that is open anyway. Anything beyond [`MAX_ACK_RANGES`] stays queued.
+    pub fn take(&mut self, contact_id: &ContactId) -> Option<AckBatch> {
+        let pending = self.pending.get_mut(contact_id)?;
+        let batch = pending.take_batch();
+        if pending.seqs.is_empty() {
+            self.pending.remove(contact_id);
+        }
+        Some(batch).filter(|b| !b.is_empty())
+    }
+
+    /// Batches that can no longer wait for a piggyback ride and need a
+    /// connection of their own.
+    pub fn poll(&mut self, now: u64) -> Vec<(ContactId, AckBatch)> {
+        let config = self.config;
+        let mut due = Vec::new();
+        self.pending.retain(|contact_id, pending| {
+            if pending.seqs.len() >= config.max_pending
+                || now >= pending.since.saturating_add(config.max_delay_ms)
+            {
+                due.push((*contact_id, pending.take_batch()));
+                pending.since = now;
+            }
+            !pending.seqs.is_empty()
+        });
+        due
+    }
+
+    /// Earliest time [`poll`](Self::poll) will return something.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.pending
+            .values()
+            .map(|p| p.since.saturating_add(self.config.max_delay_ms))
+            .min()
+    }
+
+    /// Drop pending receipts for a removed contact.
+    pub fn forget(&mut self, contact_id: &ContactId) {
+        self.pending.remove(contact_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    #[test]
+    fn test_ack_batch_ranges_roundtrip() {
+        let batch = AckBatch::from_seqs([7, 3, 4, 5, 9, 4, 10, 11]);
+        assert_eq!(
+            batch.ranges(),
+            &[
+                AckRange { first: 3, last: 5 },
+                AckRange { first: 7, last: 7 },
+                AckRange { first: 9, last: 11 },
+            ]
+        );
+        assert!(batch.contains(4) && batch.contains(7) && batch.contains(11));
+        assert!(!batch.contains(6) && !batch.contains(12) && !batch.contains(0));
+
+        let bytes = batch.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * RANGE_LEN);
+        assert_eq!(AckBatch::from_bytes(&bytes).unwrap(), batch);
+
+        // Overlapping ranges
+        let mut overlapping = AckBatch::from_seqs([1, 2, 5]).to_bytes();
+        overlapping[HEADER_LEN + RANGE_LEN + 7] = 2;
+        assert_eq!(
+            AckBatch::from_bytes(&overlapping),
+            Err(ReceiptError::Malformed)
+        );
+        assert_eq!(
+            AckBatch::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReceiptError::Malformed)
+        );
+        assert_eq!(
+            AckBatch::from_bytes(&[9, 0]),
+            Err(ReceiptError::UnsupportedVersion(9))
+        );
+    }
+
+    #[test]
+    fn test_batcher_piggybacks_then_flushes_on_deadline() {
+        let config = ReceiptConfig::default();
+        let mut batcher = ReceiptBatcher::new(config);
+        let (alice, bob) = (test_contact(1), test_contact(2));
+
+        for seq in 0..5 {
+            batcher.record(alice, seq, 1_000 + seq);
+        }
+        batcher.record(bob, 40, 1_500);
+        assert!(batcher.poll(1_000 + config.max_delay_ms - 1).is_empty());
+        assert_eq!(batcher.next_deadline(), Some(1_000 + config.max_delay_ms));
+
+        // A connection to alice opens: her receipts ride along
+        let piggyback = batcher.take(&alice).unwrap();
+        assert_eq!(piggyback.ranges(), &[AckRange { first: 0, last: 4 }]);
+        assert_eq!(batcher.pending_for(&alice), 0);
+        assert!(batcher.take(&alice).is_none());
+
+        // Bob's receipt goes out on its own once overdue
+        let due = batcher.poll(1_500 + config.max_delay_ms);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, bob);
+        assert!(due[0].1.contains(40));
+        assert_eq!(batcher.next_deadline(), None);
+
+        // Too many pending receipts flush early, in sendable chunks
+        for seq in 0..(2 * MAX_ACK_RANGES as u64 + 2) {
+            batcher.record(alice, seq * 2, 2_000);
+        }
+        let due = batcher.poll(2_001);
+        assert_eq!(due[0].1.ranges().len(), MAX_ACK_RANGES);
+        assert_eq!(
+            batcher.pending_for(&alice),
+            2 * MAX_ACK_RANGES + 2 - MAX_ACK_RANGES
+        );
+    }
+}
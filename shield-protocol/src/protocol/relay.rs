@@ -0,0 +1,490 @@
+/// Relay federation: signed relay descriptors and client-side relay choice.
+///
+/// A store-and-forward relay announces itself with a [`RelayDescriptor`]: its
+/// Ed25519 key, onion address, the relay protocol versions it speaks, how
+/// long it keeps messages ([`RetentionPolicy`]) and what it lets one client
+/// send ([`RateLimits`]). The descriptor is signed by the relay key and only
+/// valid for a bounded window, so a stale or forged descriptor cannot pin
+/// clients to a relay the operator has retired.
+///
+/// Descriptors can come from anywhere (a contact, a QR code, a directory);
+/// [`RelaySelector::add`] accepts only those that verify. Sending everything
+/// through one relay would hand it the whole timing pattern of the user's
+/// traffic, so [`RelaySelector::next`] rotates over all known relays in a
+/// fresh random order each cycle, never uses the same relay twice in a row
+/// when it has a choice, and stays within each relay's advertised limits.
+///
+/// All times are Unix seconds.
+use crate::crypto::signing::{sign_data, verify_signature};
+use crate::rng::SecureRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RelayError {
+    #[error("Malformed relay descriptor")]
+    Malformed,
+    #[error("Unsupported relay descriptor version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid relay onion address")]
+    InvalidOnion,
+    #[error("Relay descriptor signature is invalid")]
+    BadSignature,
+    #[error("Relay descriptor has expired")]
+    Expired,
+    #[error("Relay descriptor is not valid yet")]
+    NotYetValid,
+    #[error("Relay descriptor validity window is invalid")]
+    InvalidLifetime,
+    #[error("Relay speaks no supported protocol version")]
+    IncompatibleProtocol,
+    #[error("Relay retention or rate limits are invalid")]
+    InvalidPolicy,
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Relay descriptor encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Relay descriptor wire version.
+pub const RELAY_DESCRIPTOR_VERSION: u8 = 1;
+
+/// Relay protocol versions this client speaks. Version 2 adds the mailbox
+/// index (see [`mailbox`](super::mailbox)), version 3 contact backup slots
+/// (see [`contact_backup`](super::contact_backup)).
+pub const SUPPORTED_RELAY_PROTOCOLS: &[u16] = &[
+    1,
+    super::mailbox::MAILBOX_INDEX_PROTOCOL,
+    super::contact_backup::CONTACT_BACKUP_PROTOCOL,
+];
+
+/// Longest validity window a relay may give its descriptor.
+pub const MAX_DESCRIPTOR_LIFETIME_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Largest accepted encoded descriptor.
+pub const MAX_DESCRIPTOR_LEN: usize = 1024;
+
+/// How far in the future `issued_at` may lie before it counts as forged.
+const MAX_CLOCK_SKEW_SECS: u64 = 10 * 60;
+
+/// Most protocol versions one descriptor may list.
+const MAX_PROTOCOL_VERSIONS: usize = 16;
+
+const DESCRIPTOR_CONTEXT: &[u8] = b"ShieldMessenger-RelayDescriptor-v1";
+
+/// Rate limits apply per minute.
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// How the relay treats messages it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Longest a message waits for an offline recipient before deletion.
+    pub max_retention_secs: u64,
+    /// Messages are deleted as soon as the recipient fetches them.
+    pub delete_on_fetch: bool,
+}
+
+/// What the relay accepts from one client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    pub messages_per_minute: u32,
+    pub max_message_bytes: u32,
+}
+
+/// Signed content of a relay descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayInfo {
+    /// Ed25519 key that signs the descriptor.
+    pub relay_key: [u8; 32],
+    /// v3 onion address, with the `.onion` suffix.
+    pub onion_address: String,
+    pub protocol_versions: Vec<u16>,
+    pub retention: RetentionPolicy,
+    pub rate_limits: RateLimits,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// A relay's self-signed announcement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayDescriptor {
+    pub info: RelayInfo,
+    pub signature: [u8; 64],
+}
+
+impl RelayDescriptor {
+    /// Sign `info` with the relay's Ed25519 seed.
+    pub fn sign(info: RelayInfo, signing_key: &[u8]) -> Result<Self, RelayError> {
+        check_info(&info)?;
+        let signature = sign_data(&signed_bytes(&info)?, signing_key)
+            .map_err(|e| RelayError::Signing(e.to_string()))?;
+        Ok(Self { info, signature })
+    }
+
+    /// `[version][signature 64][bincode info]`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RelayError> {
+        let body =
+            bincode::serialize(&self.info).map_err(|e| RelayError::Encoding(e.to_string()))?;
+        let mut out = Vec::with_capacity(1 + 64 + body.len());
+        out.push(RELAY_DESCRIPTOR_VERSION);
+        out.extend_from_slice(&self.signature);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Parse a descriptor. Call [`verify`](Self::verify) before trusting it.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RelayError> {
+        let (&version, rest) = data.split_first().ok_or(RelayError::Malformed)?;
+        if version != RELAY_DESCRIPTOR_VERSION {
+            return Err(RelayError::UnsupportedVersion(version));
+        }
+        if data.len() > MAX_DESCRIPTOR_LEN || rest.len() < 64 {
+            return Err(RelayError::Malformed);
+        }
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&rest[..64]);
+        let info: RelayInfo =
+            bincode::deserialize(&rest[64..]).map_err(|_| RelayError::Malformed)?;
+        Ok(Self { info, signature })
+    }
+
+    /// Check the descriptor's content, signature and validity at `now`.
+    pub fn verify(&self, now: u64) -> Result<(), RelayError> {
+        check_info(&self.info)?;
+        match verify_signature(
+            &signed_bytes(&self.info)?,
+            &self.signature,
+            &self.info.relay_key,
+        ) {
+            Ok(true) => {}
+            _ => return Err(RelayError::BadSignature),
+        }
+        if self.info.issued_at > now.saturating_add(MAX_CLOCK_SKEW_SECS) {
+            return Err(RelayError::NotYetValid);
+        }
+        if now >= self.info.expires_at {
+            return Err(RelayError::Expired);
+        }
+        if self.protocol_version().is_none() {
+            return Err(RelayError::IncompatibleProtocol);
+        }
+        Ok(())
+    }
+
+    /// Highest protocol version both sides speak.
+    pub fn protocol_version(&self) -> Option<u16> {
+        self.info
+            .protocol_versions
+            .iter()
+            .copied()
+            .filter(|v| SUPPORTED_RELAY_PROTOCOLS.contains(v))
+            .max()
+    }
+}
+
+fn signed_bytes(info: &RelayInfo) -> Result<Vec<u8>, RelayError> {
+    let body = bincode::serialize(info).map_err(|e| RelayError::Encoding(e.to_string()))?;
+    let mut data = Vec::with_capacity(DESCRIPTOR_CONTEXT.len() + 1 + body.len());
+    data.extend_from_slice(DESCRIPTOR_CONTEXT);
+    data.push(RELAY_DESCRIPTOR_VERSION);
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+fn check_info(info: &RelayInfo) -> Result<(), RelayError> {
+    if !is_valid_onion_v3(&info.onion_address) {
+        return Err(RelayError::InvalidOnion);
+    }
+    if info.expires_at <= info.issued_at
+        || info.expires_at - info.issued_at > MAX_DESCRIPTOR_LIFETIME_SECS
+    {
+        return Err(RelayError::InvalidLifetime);
+    }
+    if info.protocol_versions.len() > MAX_PROTOCOL_VERSIONS {
+        return Err(RelayError::Malformed);
+    }
+    if info.retention.max_retention_secs == 0
+        || info.rate_limits.messages_per_minute == 0
+        || info.rate_limits.max_message_bytes == 0
+    {
+        return Err(RelayError::InvalidPolicy);
+    }
+    Ok(())
+}
+
+/// 56 lowercase base32 characters ending in the v3 version nibble `d`,
+/// followed by `.onion`.
+pub fn is_valid_onion_v3(address: &str) -> bool {
+    let Some(host) = address.strip_suffix(".onion") else {
+        return false;
+    };
+    host.len() == 56
+        && host.ends_with('d')
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || (b'2'..=b'7').contains(&b))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RelayUse {
+    window_start: u64,
+    count: u32,
+}
+
+/// Verified relays and the rotation over them.
+#[derive(Debug, Clone, Default)]
+pub struct RelaySelector {
+    relays: HashMap<[u8; 32], RelayDescriptor>,
+    /// Relays not yet used in the current cycle; the next one is at the end.
+    cycle: Vec<[u8; 32]>,
+    last: Option<[u8; 32]>,
+    usage: HashMap<[u8; 32], RelayUse>,
+}
+
+impl RelaySelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify and add a descriptor. Returns `false` if an equally new or newer
+    /// descriptor for the same relay is already known.
+    pub fn add(&mut self, descriptor: RelayDescriptor, now: u64) -> Result<bool, RelayError> {
+        descriptor.verify(now)?;
+        let key = descriptor.info.relay_key;
+        if let Some(known) = self.relays.get(&key) {
+            if known.info.issued_at >= descriptor.info.issued_at {
+                return Ok(false);
+            }
+        }
+        self.relays.insert(key, descriptor);
+        Ok(true)
+    }
+
+    pub fn remove(&mut self, relay_key: &[u8; 32]) -> bool {
+        self.usage.remove(relay_key);
+        self.cycle.retain(|k| k != relay_key);
+        self.relays.remove(relay_key).is_some()
+    }
+
+    /// Drop expired descriptors; returns how many were removed.
+    pub fn prune(&mut self, now: u64) -> usize {
+        let expired: Vec<[u8; 32]> = self
+            .relays
+            .iter()
+            .filter(|(_, d)| now >= d.info.expires_at)
+            .map(|(k, _)| *k)
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        expired.len()
+    }
+
+    pub fn get(&self, relay_key: &[u8; 32]) -> Option<&RelayDescriptor> {
+        self.relays.get(relay_key)
+    }
+
+    pub fn relays(&self) -> impl Iterator<Item = &RelayDescriptor> {
+        self.relays.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.relays.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relays.is_empty()
+    }
+
+    /// Relay for the next message of `message_len` bytes, counted against
+    /// its rate limit. `None` if no relay can take the message right now.
+    pub fn next(
+        &mut self,
+        message_len: usize,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Option<&RelayDescriptor> {
+        // One pass over what is left of this cycle, one over a fresh cycle
+        for _ in 0..2 {
+            while let Some(key) = self.cycle.pop() {
+                if self.usable(&key, message_len, now) {
+                    let usage = self.usage.entry(key).or_default();
+                    if now >= usage.window_start.saturating_add(RATE_WINDOW_SECS) {
+                        *usage = RelayUse {
+                            window_start: now,
+                            count: 0,
+                        };
+                    }
+                    usage.count += 1;
+                    self.last = Some(key);
+                    return self.relays.get(&key);
+                }
+            }
+
+            let mut keys: Vec<[u8; 32]> = self.relays.keys().copied().collect();
+            if keys.is_empty() {
+                return None;
+            }
+            // HashMap order is not stable; sort so a seeded RNG is reproducible
+            keys.sort_unstable();
+            keys.shuffle(rng);
+            if keys.len() > 1 && keys.last() == self.last.as_ref() {
+                let end = keys.len() - 1;
+                keys.swap(0, end);
+            }
+            self.cycle = keys;
+        }
+        None
+    }
+
+    fn usable(&self, key: &[u8; 32], message_len: usize, now: u64) -> bool {
+        let Some(relay) = self.relays.get(key) else {
+            return false;
+        };
+        if now >= relay.info.expires_at
+            || message_len > relay.info.rate_limits.max_message_bytes as usize
+        {
+            return false;
+        }
+        match self.usage.get(key) {
+            Some(usage) if now < usage.window_start.saturating_add(RATE_WINDOW_SECS) => {
+                usage.count < relay.info.rate_limits.messages_per_minute
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn onion(fill: char) -> String {
+        format!("{}d.onion", fill.to_string().repeat(55))
+    }
+
+    /// (descriptor, private key)
+    fn relay(seed: u64, fill: char) -> (RelayDescriptor, [u8; 32]) {
+        let (public, private) = generate_keypair_with_rng(&mut seeded(seed));
+        let info = RelayInfo {
+            relay_key: public,
+            onion_address: onion(fill),
+            protocol_versions: vec![1, 7],
+            retention: RetentionPolicy {
+                max_retention_secs: 7 * 24 * 60 * 60,
+                delete_on_fetch: true,
+            },
+            rate_limits: RateLimits {
+                messages_per_minute: 2,
+                max_message_bytes: 64 * 1024,
+            },
+            issued_at: NOW,
+            expires_at: NOW + 24 * 60 * 60,
+        };
+        (RelayDescriptor::sign(info, &private).unwrap(), private)
+    }
+
+    #[test]
+    fn test_descriptor_roundtrip_and_verification() {
+        let (descriptor, private) = relay(1, 'a');
+        let parsed = RelayDescriptor::from_bytes(&descriptor.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, descriptor);
+        assert_eq!(parsed.verify(NOW), Ok(()));
+        assert_eq!(parsed.protocol_version(), Some(1));
+
+        // Tampered content
+        let mut tampered = descriptor.clone();
+        tampered.info.rate_limits.messages_per_minute = 1_000;
+        assert_eq!(tampered.verify(NOW), Err(RelayError::BadSignature));
+
+        // Signed by someone other than the relay key
+        let (other, _) = relay(2, 'b');
+        let forged = RelayDescriptor {
+            info: other.info.clone(),
+            signature: RelayDescriptor::sign(other.info, &private)
+                .unwrap()
+                .signature,
+        };
+        assert_eq!(forged.verify(NOW), Err(RelayError::BadSignature));
+
+        assert_eq!(
+            descriptor.verify(descriptor.info.expires_at),
+            Err(RelayError::Expired)
+        );
+        assert_eq!(
+            descriptor.verify(NOW - MAX_CLOCK_SKEW_SECS - 1),
+            Err(RelayError::NotYetValid)
+        );
+
+        let mut info = descriptor.info.clone();
+        info.protocol_versions = vec![9];
+        let incompatible = RelayDescriptor::sign(info.clone(), &private).unwrap();
+        assert_eq!(
+            incompatible.verify(NOW),
+            Err(RelayError::IncompatibleProtocol)
+        );
+        info.protocol_versions = vec![1];
+        info.onion_address = "relay.example.com".to_string();
+        assert_eq!(
+            RelayDescriptor::sign(info.clone(), &private),
+            Err(RelayError::InvalidOnion)
+        );
+        info.onion_address = onion('c');
+        info.expires_at = NOW + MAX_DESCRIPTOR_LIFETIME_SECS + 1;
+        assert_eq!(
+            RelayDescriptor::sign(info, &private),
+            Err(RelayError::InvalidLifetime)
+        );
+
+        assert_eq!(RelayDescriptor::from_bytes(&[]), Err(RelayError::Malformed));
+        assert_eq!(
+            RelayDescriptor::from_bytes(&[2; 80]),
+            Err(RelayError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_selector_rotates_within_limits() {
+        let mut rng = seeded(9);
+        let mut selector = RelaySelector::new();
+        let relays: Vec<RelayDescriptor> = [(1, 'a'), (2, 'b'), (3, 'c')]
+            .into_iter()
+            .map(|(seed, fill)| relay(seed, fill).0)
+            .collect();
+        for descriptor in &relays {
+            assert_eq!(selector.add(descriptor.clone(), NOW), Ok(true));
+        }
+        assert_eq!(selector.add(relays[0].clone(), NOW), Ok(false));
+
+        // Each cycle uses every relay once, and never the same one twice in a row
+        let mut picks = Vec::new();
+        for _ in 0..6 {
+            picks.push(selector.next(100, NOW, &mut rng).unwrap().info.relay_key);
+        }
+        for cycle in picks.chunks(3) {
+            let mut sorted = cycle.to_vec();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted.len(), 3);
+        }
+        assert!(picks.windows(2).all(|w| w[0] != w[1]));
+
+        // Every relay has used up its two messages this minute
+        assert!(selector.next(100, NOW + 30, &mut rng).is_none());
+        assert!(selector.next(100, NOW + 60, &mut rng).is_some());
+
+        // Oversized messages find no relay
+        assert!(selector.next(1 << 20, NOW + 60, &mut rng).is_none());
+
+        assert!(selector.remove(&relays[1].info.relay_key));
+        assert_eq!(selector.prune(relays[0].info.expires_at), 2);
+        assert!(selector.is_empty());
+        assert!(selector.next(100, NOW, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,297 @@
+/// Client ↔ relay requests.
+///
+/// Everything a client asks a store-and-forward relay goes through one
+/// request/response exchange: a [`RelayRequest`] frame in, a
+/// [`RelayResponse`] frame out, normally over a Tor circuit to the relay's
+/// onion service. The relay is blind: it learns which mailbox an envelope is
+/// for, its size and when it arrived, never who sent it or what it says.
+///
+/// - [`RelayRequest::Descriptor`]: the relay's current signed
+///   [`RelayDescriptor`](super::relay::RelayDescriptor).
+/// - [`RelayRequest::Deposit`]: hold a sealed envelope for a mailbox.
+/// - [`RelayRequest::Bucket`] / [`RelayRequest::Pir`]: mailbox index checks
+///   (see [`mailbox`](super::mailbox)); [`check_requests`] turns a
+///   [`CheckRequest`] into the requests to send.
+/// - [`RelayRequest::Fetch`]: collect held mail, authorized by a
+///   [`PlainPoll`](super::mailbox::PlainPoll) from the mailbox owner. Each
+///   envelope carries a sequence number; fetching `after` the last one seen
+///   pages through mail a relay keeps past the first fetch.
+/// - [`RelayRequest::Backup`]: a contact backup slot request (see
+///   [`contact_backup`](super::contact_backup)).
+///
+/// Frames are `[version][bincode]`. On a byte stream each frame is preceded
+/// by its length as a big-endian `u32` ([`write_frame`], [`read_frame`]).
+use super::contact_backup::{BackupRequest, BackupResponse, MAX_BACKUP_LEN};
+use super::fanout::FANOUT_SIZE_CLASSES;
+use super::mailbox::{CheckRequest, MailboxId};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RelayApiError {
+    #[error("Malformed relay frame")]
+    Malformed,
+    #[error("Unsupported relay frame version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Relay frame too large: {0} bytes")]
+    TooLarge(usize),
+    #[error("Relay frame encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Relay request/response wire version.
+pub const RELAY_API_VERSION: u8 = 1;
+
+/// Largest sealed envelope a relay holds: the biggest fan-out size class
+/// plus sealing overhead.
+pub const MAX_ENVELOPE_LEN: usize = FANOUT_SIZE_CLASSES[FANOUT_SIZE_CLASSES.len() - 1] + 1024;
+
+/// Envelope bytes returned by one fetch; the rest waits for the next page.
+pub const MAX_FETCH_BYTES: usize = 1024 * 1024;
+
+/// Largest frame in either direction.
+pub const MAX_RELAY_FRAME: usize = 2 * MAX_BACKUP_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelayRequest {
+    Descriptor,
+    Deposit {
+        mailbox: MailboxId,
+        envelope: Vec<u8>,
+    },
+    Bucket {
+        bucket_bits: u8,
+        bucket: u32,
+    },
+    /// An encoded [`PirQuery`](super::mailbox::PirQuery).
+    Pir(Vec<u8>),
+    Fetch {
+        /// An encoded [`PlainPoll`](super::mailbox::PlainPoll).
+        poll: Vec<u8>,
+        /// Sequence number of the last envelope already fetched (0 = none).
+        after: u64,
+    },
+    Backup(BackupRequest),
+}
+
+/// Why a relay refused a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelayRejection {
+    Malformed,
+    /// Over the client's rate limit; retry after this many seconds.
+    RateLimited {
+        retry_after_secs: u64,
+    },
+    TooLarge {
+        max: u32,
+    },
+    /// The mailbox holds as many envelopes as the relay keeps for one.
+    MailboxFull,
+    /// The relay is out of storage.
+    StoreFull,
+    /// The relay's index has this many bucket bits.
+    BucketBits(u8),
+    /// The poll's signature does not verify.
+    Unauthorized,
+    /// The poll's timestamp is too far from the relay's clock.
+    Stale,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelayResponse {
+    /// An encoded [`RelayDescriptor`](super::relay::RelayDescriptor).
+    Descriptor(Vec<u8>),
+    Deposited,
+    /// An encoded [`BucketRecord`](super::mailbox::BucketRecord).
+    Record(Vec<u8>),
+    /// An encoded [`PirAnswer`](super::mailbox::PirAnswer).
+    Pir(Vec<u8>),
+    Mail {
+        /// Oldest first, with their sequence numbers.
+        envelopes: Vec<(u64, Vec<u8>)>,
+        /// More mail is held after the last envelope returned.
+        more: bool,
+    },
+    Backup(BackupResponse),
+    Rejected(RelayRejection),
+}
+
+impl RelayRequest {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RelayApiError> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RelayApiError> {
+        decode(data)
+    }
+}
+
+impl RelayResponse {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RelayApiError> {
+        encode(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RelayApiError> {
+        decode(data)
+    }
+}
+
+/// The requests that carry out `check`, one per relay: a PIR check goes to
+/// two relays serving the same index, the others to one.
+pub fn check_requests(check: &CheckRequest) -> Vec<RelayRequest> {
+    match check {
+        CheckRequest::Plain(poll) => vec![RelayRequest::Fetch {
+            poll: poll.to_bytes().to_vec(),
+            after: 0,
+        }],
+        CheckRequest::Bucketed {
+            bucket_bits,
+            bucket,
+        } => vec![RelayRequest::Bucket {
+            bucket_bits: *bucket_bits,
+            bucket: *bucket,
+        }],
+        CheckRequest::Pir(queries) => queries
+            .iter()
+            .map(|query| RelayRequest::Pir(query.to_bytes()))
+            .collect(),
+    }
+}
+
+/// Write one length-prefixed frame.
+pub fn write_frame(writer: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_RELAY_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            RelayApiError::TooLarge(frame.len()).to_string(),
+        ));
+    }
+    writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+/// Read one length-prefixed frame; `None` if the stream ended cleanly
+/// before it.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_RELAY_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            RelayApiError::TooLarge(len).to_string(),
+        ));
+    }
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn encode(value: &impl Serialize) -> Result<Vec<u8>, RelayApiError> {
+    let body = bincode::serialize(value).map_err(|e| RelayApiError::Encoding(e.to_string()))?;
+    if body.len() >= MAX_RELAY_FRAME {
+        return Err(RelayApiError::TooLarge(body.len() + 1));
+    }
+    let mut out = Vec::with_capacity(1 + body.len());
+    out.push(RELAY_API_VERSION);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, RelayApiError> {
+    let (&version, body) = data.split_first().ok_or(RelayApiError::Malformed)?;
+    if version != RELAY_API_VERSION {
+        return Err(RelayApiError::UnsupportedVersion(version));
+    }
+    if data.len() > MAX_RELAY_FRAME {
+        return Err(RelayApiError::TooLarge(data.len()));
+    }
+    bincode::deserialize(body).map_err(|_| RelayApiError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::protocol::mailbox::{MailboxPollConfig, PlainPoll};
+    use crate::rng::seeded;
+
+    #[test]
+    fn test_frames_roundtrip_over_a_stream() {
+        let requests = [
+            RelayRequest::Descriptor,
+            RelayRequest::Deposit {
+                mailbox: MailboxId([3; 32]),
+                envelope: vec![9; 1000],
+            },
+            RelayRequest::Bucket {
+                bucket_bits: 8,
+                bucket: 17,
+            },
+        ];
+        let mut stream = Vec::new();
+        for request in &requests {
+            write_frame(&mut stream, &request.to_bytes().unwrap()).unwrap();
+        }
+        let mut reader = stream.as_slice();
+        for request in &requests {
+            let frame = read_frame(&mut reader).unwrap().unwrap();
+            assert_eq!(&RelayRequest::from_bytes(&frame).unwrap(), request);
+        }
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        let response = RelayResponse::Rejected(RelayRejection::RateLimited {
+            retry_after_secs: 30,
+        });
+        let mut bytes = response.to_bytes().unwrap();
+        assert_eq!(RelayResponse::from_bytes(&bytes).unwrap(), response);
+        bytes[0] = 9;
+        assert_eq!(
+            RelayResponse::from_bytes(&bytes),
+            Err(RelayApiError::UnsupportedVersion(9))
+        );
+
+        // A length prefix past the limit is refused before allocating
+        let oversized = ((MAX_RELAY_FRAME + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut oversized.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_check_requests_per_mode() {
+        let mut rng = seeded(4);
+        let (public, secret) = generate_keypair_with_rng(&mut rng);
+        let config = MailboxPollConfig::default();
+
+        let plain = CheckRequest::Plain(PlainPoll::create(&secret, &public, 1_000).unwrap());
+        match check_requests(&plain).as_slice() {
+            [RelayRequest::Fetch { poll, after: 0 }] => {
+                assert_eq!(PlainPoll::from_bytes(poll).unwrap().public_key, public)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let pir = crate::protocol::mailbox::check_request(
+            &MailboxPollConfig {
+                mode: crate::protocol::mailbox::CheckMode::Pir,
+                ..config
+            },
+            2,
+            &secret,
+            &public,
+            1_000,
+            &mut rng,
+        )
+        .unwrap();
+        let requests = check_requests(&pir);
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|r| matches!(r, RelayRequest::Pir(q) if q.len() == 2 + 32)));
+    }
+}
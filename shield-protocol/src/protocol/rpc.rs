@@ -0,0 +1,537 @@
+/// Request/response RPC for auxiliary services.
+///
+/// Some exchanges are not conversation content: fetching a contact's updated
+/// card, asking a relay for its descriptor, probing a plugin. Sending them as
+/// messages would advance the ratchet, show up in delivery bookkeeping and
+/// need a dedicated message type each. An [`RpcFrame`] is instead a small
+/// request (method name + payload) or response (status + payload), matched by
+/// request ID and carried as its own wire type over the same multiplexed
+/// connection.
+///
+/// Frames are sealed with XChaCha20-Poly1305 under a per-direction key
+/// derived from the contact's shared secret ([`RpcKeys::derive`]), never
+/// with the message ratchet:
+///
+/// ```text
+/// sealed = [version][nonce: 24][ciphertext of bincode(RpcFrame)]
+/// ```
+///
+/// [`RpcClient`] issues requests and matches responses; [`RpcServer`] routes
+/// requests to registered handlers, drops replays and stale requests, and
+/// answers unknown methods with [`RpcStatus::NotFound`].
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RpcError {
+    #[error("Malformed RPC frame")]
+    Malformed,
+    #[error("Unsupported RPC version {0}")]
+    UnsupportedVersion(u8),
+    #[error("RPC frame could not be decrypted")]
+    DecryptionFailed,
+    #[error("Invalid RPC method name")]
+    InvalidMethod,
+    #[error("RPC payload too large: {0} bytes")]
+    PayloadTooLarge(usize),
+    #[error("No pending request {0}")]
+    UnknownRequest(u64),
+    #[error("RPC encoding failed: {0}")]
+    Encoding(String),
+}
+
+pub type Result<T> = std::result::Result<T, RpcError>;
+
+/// RPC wire version.
+pub const RPC_VERSION: u8 = 1;
+
+/// Largest payload in either direction; a sealed frame stays within one
+/// fixed-size packet.
+pub const MAX_RPC_PAYLOAD: usize = 6 * 1024;
+
+/// Longest method name, in bytes.
+pub const MAX_METHOD_LEN: usize = 64;
+
+/// Requests older than this (by the sender's clock) are ignored.
+pub const MAX_REQUEST_AGE_SECS: u64 = 120;
+
+/// Request IDs a server remembers for replay detection.
+const SEEN_CAPACITY: usize = 1024;
+
+const RPC_KEY_CONTEXT: &str = "ShieldMessenger-RPC-Key-v1";
+
+/// Outcome of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum RpcStatus {
+    Ok = 0,
+    /// No handler for the method.
+    NotFound = 1,
+    /// The handler refused (e.g. not shared with this contact).
+    Denied = 2,
+    BadRequest = 3,
+    /// Temporarily unable to answer; retry later.
+    Unavailable = 4,
+    Internal = 5,
+}
+
+impl RpcStatus {
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => Self::Ok,
+            1 => Self::NotFound,
+            2 => Self::Denied,
+            3 => Self::BadRequest,
+            4 => Self::Unavailable,
+            5 => Self::Internal,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpcBody {
+    Request {
+        method: String,
+        /// Sender's clock, Unix seconds.
+        issued_at: u64,
+        payload: Vec<u8>,
+    },
+    Response {
+        status: RpcStatus,
+        payload: Vec<u8>,
+    },
+}
+
+/// One request or response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcFrame {
+    pub request_id: u64,
+    pub body: RpcBody,
+}
+
+impl RpcFrame {
+    /// Response to `request_id`.
+    pub fn response(request_id: u64, status: RpcStatus, payload: Vec<u8>) -> Self {
+        Self {
+            request_id,
+            body: RpcBody::Response { status, payload },
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let payload = match &self.body {
+            RpcBody::Request {
+                method, payload, ..
+            } => {
+                let valid = !method.is_empty()
+                    && method.len() <= MAX_METHOD_LEN
+                    && method
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b"._-/".contains(&b));
+                if !valid {
+                    return Err(RpcError::InvalidMethod);
+                }
+                payload
+            }
+            RpcBody::Response { payload, .. } => payload,
+        };
+        if payload.len() > MAX_RPC_PAYLOAD {
+            return Err(RpcError::PayloadTooLarge(payload.len()));
+        }
+        Ok(())
+    }
+
+    /// Encrypt for the peer under our sending key.
+    pub fn seal(&self, keys: &RpcKeys, rng: &mut impl SecureRng) -> Result<Vec<u8>> {
+        self.validate()?;
+        let mut body = bincode::serialize(self).map_err(|e| RpcError::Encoding(e.to_string()))?;
+        let sealed = encrypt_message_with_rng(&body, &keys.send, rng)
+            .map_err(|e| RpcError::Encoding(e.to_string()));
+        body.zeroize();
+        let mut out = vec![RPC_VERSION];
+        out.extend_from_slice(&sealed?);
+        Ok(out)
+    }
+
+    /// Decrypt and validate a frame from the peer.
+    pub fn open(sealed: &[u8], keys: &RpcKeys) -> Result<Self> {
+        let (&version, body) = sealed.split_first().ok_or(RpcError::Malformed)?;
+        if version != RPC_VERSION {
+            return Err(RpcError::UnsupportedVersion(version));
+        }
+        let mut plaintext =
+            decrypt_message(body, &keys.recv).map_err(|_| RpcError::DecryptionFailed)?;
+        let frame = bincode::deserialize::<Self>(&plaintext).map_err(|_| RpcError::Malformed);
+        plaintext.zeroize();
+        let frame = frame?;
+        frame.validate()?;
+        Ok(frame)
+    }
+}
+
+/// Per-direction RPC keys for one contact. Wiped on drop.
+pub struct RpcKeys {
+    send: [u8; 32],
+    recv: [u8; 32],
+}
+
+impl RpcKeys {
+    /// Keys from the contact's X25519 shared secret and both identity keys.
+    /// The two sides derive mirrored keys: our `send` is their `recv`.
+    pub fn derive(
+        shared_secret: &[u8; 32],
+        our_pubkey: &[u8; 32],
+        their_pubkey: &[u8; 32],
+    ) -> Self {
+        let direction = |from: &[u8; 32], to: &[u8; 32]| {
+            let mut material = [0u8; 96];
+            material[..32].copy_from_slice(shared_secret);
+            material[32..64].copy_from_slice(from);
+            material[64..].copy_from_slice(to);
+            let key = blake3::derive_key(RPC_KEY_CONTEXT, &material);
+            material.zeroize();
+            key
+        };
+        Self {
+            send: direction(our_pubkey, their_pubkey),
+            recv: direction(their_pubkey, our_pubkey),
+        }
+    }
+}
+
+impl Drop for RpcKeys {
+    fn drop(&mut self) {
+        self.send.zeroize();
+        self.recv.zeroize();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Client
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+struct Pending {
+    peer: [u8; 32],
+    method: String,
+    deadline: u64,
+}
+
+/// Answer to one of our requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcReply {
+    pub peer: [u8; 32],
+    pub request_id: u64,
+    pub method: String,
+    pub status: RpcStatus,
+    pub payload: Vec<u8>,
+}
+
+/// Outstanding requests, each bound to the peer it was sent to.
+#[derive(Debug)]
+pub struct RpcClient {
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+}
+
+impl RpcClient {
+    /// Request IDs start at a random value so they reveal nothing about how
+    /// many calls were made before.
+    pub fn new(rng: &mut impl SecureRng) -> Self {
+        Self {
+            next_id: rng.next_u64(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Build a request to `peer` (identity key); it is pending until
+    /// answered or `timeout_secs` pass.
+    pub fn request(
+        &mut self,
+        peer: [u8; 32],
+        method: &str,
+        payload: Vec<u8>,
+        now: u64,
+        timeout_secs: u64,
+    ) -> Result<RpcFrame> {
+        let frame = RpcFrame {
+            request_id: self.next_id,
+            body: RpcBody::Request {
+                method: method.to_string(),
+                issued_at: now,
+                payload,
+            },
+        };
+        frame.validate()?;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending.insert(
+            frame.request_id,
+            Pending {
+                peer,
+                method: method.to_string(),
+                deadline: now.saturating_add(timeout_secs),
+            },
+        );
+        Ok(frame)
+    }
+
+    /// Match a response from `peer` to its request. Unsolicited or late
+    /// responses, and responses from anyone but the request's peer, fail
+    /// with [`RpcError::UnknownRequest`].
+    pub fn on_response(&mut self, peer: [u8; 32], frame: RpcFrame) -> Result<RpcReply> {
+        let RpcBody::Response { status, payload } = frame.body else {
+            return Err(RpcError::Malformed);
+        };
+        let unknown = RpcError::UnknownRequest(frame.request_id);
+        if self.pending.get(&frame.request_id).map(|p| p.peer) != Some(peer) {
+            return Err(unknown);
+        }
+        let pending = self.pending.remove(&frame.request_id).ok_or(unknown)?;
+        Ok(RpcReply {
+            peer,
+            request_id: frame.request_id,
+            method: pending.method,
+            status,
+            payload,
+        })
+    }
+
+    /// Drop requests past their deadline; returns their IDs.
+    pub fn expire(&mut self, now: u64) -> Vec<u64> {
+        let expired: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| p.deadline <= now)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in &expired {
+            self.pending.remove(id);
+        }
+        expired
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Server
+// ---------------------------------------------------------------------------
+
+/// Handler for one method: caller's identity key and request payload in,
+/// response payload (or an error status) out.
+pub type RpcHandler = Box<
+    dyn Fn(&[u8; 32], &[u8]) -> std::result::Result<Vec<u8>, RpcStatus> + Send + Sync + 'static,
+>;
+
+/// What the server did with an incoming request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcDispatch {
+    /// Send this response back.
+    Reply(RpcFrame),
+    /// Replayed or stale; send nothing.
+    Dropped,
+}
+
+/// Routes requests from all peers to method handlers.
+#[derive(Default)]
+pub struct RpcServer {
+    handlers: HashMap<String, RpcHandler>,
+    seen: HashSet<([u8; 32], u64)>,
+    seen_order: VecDeque<([u8; 32], u64)>,
+}
+
+impl RpcServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or replace) the handler for `method`.
+    pub fn register<F>(&mut self, method: &str, handler: F)
+    where
+        F: Fn(&[u8; 32], &[u8]) -> std::result::Result<Vec<u8>, RpcStatus> + Send + Sync + 'static,
+    {
+        self.handlers.insert(method.to_string(), Box::new(handler));
+    }
+
+    pub fn unregister(&mut self, method: &str) -> bool {
+        self.handlers.remove(method).is_some()
+    }
+
+    pub fn has_method(&self, method: &str) -> bool {
+        self.handlers.contains_key(method)
+    }
+
+    /// Whether `request_id` is new from `peer`, remembering it if so.
+    fn first_sight(&mut self, peer: [u8; 32], request_id: u64) -> bool {
+        if !self.seen.insert((peer, request_id)) {
+            return false;
+        }
+        if self.seen_order.len() == SEEN_CAPACITY {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen_order.push_back((peer, request_id));
+        true
+    }
+
+    /// Whether a request from `peer` is fresh and not a replay, remembering
+    /// it if so. [`dispatch`](Self::dispatch) checks this itself; call it
+    /// directly for requests answered outside the server (e.g. by the app).
+    /// Responses are not requests and fail with [`RpcError::Malformed`].
+    pub fn admit(&mut self, peer: [u8; 32], frame: &RpcFrame, now: u64) -> Result<bool> {
+        let RpcBody::Request { issued_at, .. } = &frame.body else {
+            return Err(RpcError::Malformed);
+        };
+        Ok(now.saturating_sub(*issued_at) <= MAX_REQUEST_AGE_SECS
+            && self.first_sight(peer, frame.request_id))
+    }
+
+    /// Handle a request from `peer` received at `now`.
+    pub fn dispatch(&mut self, peer: [u8; 32], frame: &RpcFrame, now: u64) -> Result<RpcDispatch> {
+        if !self.admit(peer, frame, now)? {
+            return Ok(RpcDispatch::Dropped);
+        }
+        let RpcBody::Request {
+            method, payload, ..
+        } = &frame.body
+        else {
+            return Err(RpcError::Malformed);
+        };
+        let (status, payload) = match self.handlers.get(method) {
+            None => (RpcStatus::NotFound, Vec::new()),
+            Some(handler) => match handler(&peer, payload) {
+                Ok(out) if out.len() > MAX_RPC_PAYLOAD => (RpcStatus::Internal, Vec::new()),
+                Ok(out) => (RpcStatus::Ok, out),
+                Err(status) => (status, Vec::new()),
+            },
+        };
+        Ok(RpcDispatch::Reply(RpcFrame::response(
+            frame.request_id,
+            status,
+            payload,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    const ALICE: [u8; 32] = [0xA1; 32];
+    const BOB: [u8; 32] = [0xB0; 32];
+
+    fn key_pair() -> (RpcKeys, RpcKeys) {
+        let shared = [9u8; 32];
+        (
+            RpcKeys::derive(&shared, &ALICE, &BOB),
+            RpcKeys::derive(&shared, &BOB, &ALICE),
+        )
+    }
+
+    #[test]
+    fn test_round_trip_through_sealed_frames() {
+        let mut rng = seeded(1);
+        let (alice_keys, bob_keys) = key_pair();
+        let mut client = RpcClient::new(&mut rng);
+        let mut server = RpcServer::new();
+        server.register("card.get", |_, _| Ok(b"card-bytes".to_vec()));
+        server.register("secret", |peer, _| {
+            if *peer == ALICE {
+                Err(RpcStatus::Denied)
+            } else {
+                Ok(vec![])
+            }
+        });
+
+        let request = client.request(BOB, "card.get", vec![], 1_000, 30).unwrap();
+        let sealed = request.seal(&alice_keys, &mut rng).unwrap();
+        // Only the peer's receiving key opens it
+        assert_eq!(
+            RpcFrame::open(&sealed, &alice_keys),
+            Err(RpcError::DecryptionFailed)
+        );
+        let received = RpcFrame::open(&sealed, &bob_keys).unwrap();
+        assert_eq!(received, request);
+
+        let RpcDispatch::Reply(response) = server.dispatch(ALICE, &received, 1_005).unwrap() else {
+            panic!("expected a reply");
+        };
+        let sealed = response.seal(&bob_keys, &mut rng).unwrap();
+        let reply = client
+            .on_response(BOB, RpcFrame::open(&sealed, &alice_keys).unwrap())
+            .unwrap();
+        assert_eq!(reply.method, "card.get");
+        assert_eq!(reply.status, RpcStatus::Ok);
+        assert_eq!(reply.payload, b"card-bytes");
+        assert_eq!(client.pending_len(), 0);
+
+        // Errors and unknown methods come back as statuses
+        for (method, status) in [("secret", RpcStatus::Denied), ("nope", RpcStatus::NotFound)] {
+            let request = client.request(BOB, method, vec![], 1_000, 30).unwrap();
+            let RpcDispatch::Reply(response) = server.dispatch(ALICE, &request, 1_000).unwrap()
+            else {
+                panic!("expected a reply");
+            };
+            assert_eq!(client.on_response(BOB, response).unwrap().status, status);
+        }
+    }
+
+    #[test]
+    fn test_replays_stale_requests_and_late_responses_are_dropped() {
+        let mut rng = seeded(2);
+        let mut client = RpcClient::new(&mut rng);
+        let mut server = RpcServer::new();
+        server.register("ping", |_, p| Ok(p.to_vec()));
+
+        let request = client.request(BOB, "ping", vec![1], 1_000, 10).unwrap();
+        assert!(matches!(
+            server.dispatch(ALICE, &request, 1_000).unwrap(),
+            RpcDispatch::Reply(_)
+        ));
+        assert_eq!(
+            server.dispatch(ALICE, &request, 1_000).unwrap(),
+            RpcDispatch::Dropped
+        );
+        let stale = client.request(BOB, "ping", vec![], 1_000, 10).unwrap();
+        assert_eq!(
+            server
+                .dispatch(ALICE, &stale, 1_000 + MAX_REQUEST_AGE_SECS + 1)
+                .unwrap(),
+            RpcDispatch::Dropped
+        );
+
+        // A response from anyone but the request's peer does not match
+        let forged = RpcFrame::response(stale.request_id, RpcStatus::Ok, vec![]);
+        assert_eq!(
+            client.on_response(ALICE, forged),
+            Err(RpcError::UnknownRequest(stale.request_id))
+        );
+
+        // Both requests time out; a response arriving afterwards is rejected
+        assert_eq!(client.expire(1_010).len(), 2);
+        let late = RpcFrame::response(request.request_id, RpcStatus::Ok, vec![]);
+        assert_eq!(
+            client.on_response(BOB, late),
+            Err(RpcError::UnknownRequest(request.request_id))
+        );
+
+        assert_eq!(
+            client.request(BOB, "bad method!", vec![], 0, 1),
+            Err(RpcError::InvalidMethod)
+        );
+        assert_eq!(
+            client.request(BOB, "ping", vec![0; MAX_RPC_PAYLOAD + 1], 0, 1),
+            Err(RpcError::PayloadTooLarge(MAX_RPC_PAYLOAD + 1))
+        );
+    }
+}
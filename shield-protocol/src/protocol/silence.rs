@@ -0,0 +1,331 @@
+/// Network silence: the protocol side of stealth mode's "go dark" switch.
+///
+/// While silence is active the node sends no traffic the user did not ask
+/// for. Cover packets, delivery receipts, presence beacons and background
+/// sync all stop ([`NetworkSilence::allows`]). Inbound connections get no
+/// reply of any kind, so the node is indistinguishable from a dead one.
+///
+/// Silence ends locally ([`NetworkSilence::end`]) or remotely, when the one
+/// contact designated in the [`SilenceSpec`] sends a [`WakeMessage`]. A wake
+/// message is signed with that contact's Ed25519 identity key. It is bound
+/// to the silenced node's identity key, so it cannot be replayed against
+/// anyone else, and its timestamp must be fresh and newer than any wake
+/// accepted before. A wake message gets no reply either; the contact learns
+/// the node is awake from its normal traffic.
+use super::contact_id::ContactId;
+use crate::crypto::signing::{sign_data, verify_signature};
+use crate::rng::SecureRng;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SilenceError {
+    #[error("Malformed wake message")]
+    Malformed,
+    #[error("Unsupported wake message version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Network silence is not active")]
+    NotSilent,
+    #[error("Wake message signature is invalid")]
+    BadSignature,
+    #[error("Wake message timestamp is outside the accepted window")]
+    Stale,
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Wake key does not belong to {0}")]
+    KeyMismatch(ContactId),
+}
+
+/// Wake message wire version.
+pub const WAKE_MESSAGE_VERSION: u8 = 1;
+
+/// `[version u8][timestamp u64 BE][nonce 16][signature 64]`
+pub const WAKE_MESSAGE_LEN: usize = 1 + 8 + 16 + 64;
+
+const WAKE_CONTEXT: &[u8] = b"ShieldMessenger-Wake-v1";
+
+/// Who may end a silence, and how strict the wake check is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SilenceSpec {
+    /// The only contact whose wake message is honored.
+    pub wake_contact: ContactId,
+    /// That contact's Ed25519 identity public key.
+    pub wake_public_key: [u8; 32],
+    /// Largest accepted difference between a wake timestamp and local time.
+    pub max_clock_skew_secs: u64,
+}
+
+impl SilenceSpec {
+    /// Spec for `wake_contact`, checking that the key is theirs.
+    pub fn new(wake_contact: ContactId, wake_public_key: [u8; 32]) -> Result<Self, SilenceError> {
+        if !wake_contact.matches_identity_key(&wake_public_key) {
+            return Err(SilenceError::KeyMismatch(wake_contact));
+        }
+        Ok(Self {
+            wake_contact,
+            wake_public_key,
+            max_clock_skew_secs: 300,
+        })
+    }
+}
+
+/// Kinds of outbound traffic, for [`NetworkSilence::allows`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficClass {
+    /// Something the user explicitly sent (messages, calls, wake messages).
+    UserInitiated,
+    /// Cover packets.
+    Cover,
+    /// Delivery and read receipts, typing taps.
+    Receipt,
+    /// Presence beacons.
+    Presence,
+    /// Sync, routing and profile updates the app sends on its own.
+    Background,
+}
+
+/// Signed request to end a contact's network silence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeMessage {
+    pub timestamp: u64,
+    pub nonce: [u8; 16],
+    pub signature: [u8; 64],
+}
+
+impl WakeMessage {
+    /// Wake message from the holder of `signing_key` (Ed25519 seed) to the
+    /// node with identity key `recipient_identity_key`.
+    pub fn create(
+        signing_key: &[u8],
+        recipient_identity_key: &[u8; 32],
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Self, SilenceError> {
+        let mut nonce = [0u8; 16];
+        rng.fill_bytes(&mut nonce);
+        let signature = sign_data(
+            &signed_bytes(recipient_identity_key, now, &nonce),
+            signing_key,
+        )
+        .map_err(|e| SilenceError::Signing(e.to_string()))?;
+        Ok(Self {
+            timestamp: now,
+            nonce,
+            signature,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; WAKE_MESSAGE_LEN] {
+        let mut out = [0u8; WAKE_MESSAGE_LEN];
+        out[0] = WAKE_MESSAGE_VERSION;
+        out[1..9].copy_from_slice(&self.timestamp.to_be_bytes());
+        out[9..25].copy_from_slice(&self.nonce);
+        out[25..].copy_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SilenceError> {
+        if bytes.is_empty() {
+            return Err(SilenceError::Malformed);
+        }
+        if bytes[0] != WAKE_MESSAGE_VERSION {
+            return Err(SilenceError::UnsupportedVersion(bytes[0]));
+        }
+        if bytes.len() != WAKE_MESSAGE_LEN {
+            return Err(SilenceError::Malformed);
+        }
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&bytes[1..9]);
+        let mut nonce = [0u8; 16];
+        nonce.copy_from_slice(&bytes[9..25]);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&bytes[25..]);
+        Ok(Self {
+            timestamp: u64::from_be_bytes(timestamp),
+            nonce,
+            signature,
+        })
+    }
+}
+
+fn signed_bytes(recipient_identity_key: &[u8; 32], timestamp: u64, nonce: &[u8; 16]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(WAKE_CONTEXT.len() + 1 + 32 + 8 + 16);
+    data.extend_from_slice(WAKE_CONTEXT);
+    data.push(WAKE_MESSAGE_VERSION);
+    data.extend_from_slice(recipient_identity_key);
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(nonce);
+    data
+}
+
+/// Silence state of the local node.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkSilence {
+    active: Option<ActiveSilence>,
+    /// Newest wake timestamp ever accepted; survives across silences.
+    last_wake: u64,
+}
+
+#[derive(Debug, Clone)]
+struct ActiveSilence {
+    spec: SilenceSpec,
+    local_identity_key: [u8; 32],
+    since: u64,
+}
+
+impl NetworkSilence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Go silent. `local_identity_key` is this node's Ed25519 identity
+    /// public key, which wake messages must be bound to.
+    pub fn enter(&mut self, spec: SilenceSpec, local_identity_key: [u8; 32], now: u64) {
+        self.active = Some(ActiveSilence {
+            spec,
+            local_identity_key,
+            since: now,
+        });
+    }
+
+    /// End silence locally.
+    pub fn end(&mut self) {
+        self.active = None;
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Unix time (seconds) silence began, if active.
+    pub fn since(&self) -> Option<u64> {
+        self.active.as_ref().map(|a| a.since)
+    }
+
+    /// The contact allowed to end the current silence.
+    pub fn wake_contact(&self) -> Option<ContactId> {
+        self.active.as_ref().map(|a| a.spec.wake_contact)
+    }
+
+    /// Whether outbound traffic of `class` may be sent right now.
+    pub fn allows(&self, class: TrafficClass) -> bool {
+        !self.is_silent() || class == TrafficClass::UserInitiated
+    }
+
+    /// Check a received wake message and end silence if it is valid.
+    ///
+    /// The timestamp must be within `max_clock_skew_secs` of `now`, no more
+    /// than that before silence began, and newer than the last accepted wake.
+    pub fn try_wake(&mut self, bytes: &[u8], now: u64) -> Result<(), SilenceError> {
+        let active = self.active.as_ref().ok_or(SilenceError::NotSilent)?;
+        let wake = WakeMessage::from_bytes(bytes)?;
+
+        let skew = active.spec.max_clock_skew_secs;
+        if wake.timestamp.abs_diff(now) > skew
+            || wake.timestamp.saturating_add(skew) < active.since
+            || wake.timestamp <= self.last_wake
+        {
+            return Err(SilenceError::Stale);
+        }
+        let data = signed_bytes(&active.local_identity_key, wake.timestamp, &wake.nonce);
+        match verify_signature(&data, &wake.signature, &active.spec.wake_public_key) {
+            Ok(true) => {}
+            _ => return Err(SilenceError::BadSignature),
+        }
+
+        self.last_wake = wake.timestamp;
+        self.active = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::rng::seeded;
+
+    /// (contact id, public key, private key)
+    fn identity(seed: u64) -> (ContactId, [u8; 32], [u8; 32]) {
+        let (public, private) = generate_keypair_with_rng(&mut seeded(seed));
+        (
+            ContactId::from_identity_key(&public).unwrap(),
+            public,
+            private,
+        )
+    }
+
+    #[test]
+    fn test_silence_blocks_background_traffic() {
+        let (friend, friend_pk, _) = identity(1);
+        let (_, local_pk, _) = identity(2);
+        let mut silence = NetworkSilence::new();
+        assert!(silence.allows(TrafficClass::Cover));
+
+        silence.enter(
+            SilenceSpec::new(friend, friend_pk).unwrap(),
+            local_pk,
+            1_000,
+        );
+        assert!(silence.is_silent());
+        assert_eq!(silence.wake_contact(), Some(friend));
+        for class in [
+            TrafficClass::Cover,
+            TrafficClass::Receipt,
+            TrafficClass::Presence,
+            TrafficClass::Background,
+        ] {
+            assert!(!silence.allows(class));
+        }
+        assert!(silence.allows(TrafficClass::UserInitiated));
+
+        silence.end();
+        assert!(silence.allows(TrafficClass::Presence));
+        assert!(matches!(
+            SilenceSpec::new(friend, local_pk),
+            Err(SilenceError::KeyMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_only_designated_fresh_wake_ends_silence() {
+        let mut rng = seeded(9);
+        let (friend, friend_pk, friend_sk) = identity(1);
+        let (_, local_pk, _) = identity(2);
+        let (_, other_pk, other_sk) = identity(3);
+        let spec = SilenceSpec::new(friend, friend_pk).unwrap();
+        let mut silence = NetworkSilence::new();
+        assert_eq!(silence.try_wake(&[1], 1_000), Err(SilenceError::NotSilent));
+        silence.enter(spec.clone(), local_pk, 1_000);
+
+        // Signed by someone else
+        let forged = WakeMessage::create(&other_sk, &local_pk, 1_100, &mut rng).unwrap();
+        assert_eq!(
+            silence.try_wake(&forged.to_bytes(), 1_100),
+            Err(SilenceError::BadSignature)
+        );
+        // Addressed to another node
+        let misdirected = WakeMessage::create(&friend_sk, &other_pk, 1_100, &mut rng).unwrap();
+        assert_eq!(
+            silence.try_wake(&misdirected.to_bytes(), 1_100),
+            Err(SilenceError::BadSignature)
+        );
+        // Issued well before silence began
+        let old = WakeMessage::create(&friend_sk, &local_pk, 100, &mut rng).unwrap();
+        assert_eq!(
+            silence.try_wake(&old.to_bytes(), 150),
+            Err(SilenceError::Stale)
+        );
+        assert!(silence.is_silent());
+
+        let wake = WakeMessage::create(&friend_sk, &local_pk, 1_100, &mut rng).unwrap();
+        let bytes = wake.to_bytes();
+        assert_eq!(WakeMessage::from_bytes(&bytes).unwrap(), wake);
+        assert_eq!(silence.try_wake(&bytes, 1_120), Ok(()));
+        assert!(!silence.is_silent());
+
+        // Replaying the same wake into a later silence fails
+        silence.enter(spec, local_pk, 1_150);
+        assert_eq!(silence.try_wake(&bytes, 1_160), Err(SilenceError::Stale));
+        assert!(silence.is_silent());
+    }
+}
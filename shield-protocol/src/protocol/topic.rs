@@ -0,0 +1,727 @@
+/// Encrypted publish/subscribe topics between contacts.
+///
+/// A topic is a named, one-way channel from a publisher to the contacts it
+/// admitted: status updates, "typing in group X", app-specific signals. Each
+/// sealed [`TopicFrame`] travels as its own wire type over the contact's
+/// existing connection, next to messages and RPC, without touching the
+/// message ratchet.
+///
+/// Admission is by capability: the publisher signs a [`CapabilityToken`]
+/// naming the topic, the subscriber's identity key and an expiry, and hands
+/// it over by any channel. The subscriber presents it in a `Subscribe` frame;
+/// the publisher accepts it only if it carries its own signature and names
+/// the contact that sent it, so a token is useless to anyone else.
+///
+/// Frames are sealed with XChaCha20-Poly1305 under a per-topic,
+/// per-direction key derived from the pair's shared secret and the topic
+/// name ([`TopicKeys`]). The wire carries a topic ID derived the same way
+/// instead of the name:
+///
+/// ```text
+/// sealed = [version][topic id: 16][nonce: 24][ciphertext of bincode(TopicFrame)]
+/// ```
+///
+/// Replays are caught by a sliding window over each (peer, topic)
+/// sequence number. Frames older than [`MAX_FRAME_AGE_SECS`] are dropped as
+/// well, which covers replays across a restart that forgot the window.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng};
+use crate::crypto::signing::{derive_public_key, sign_data, verify_signature};
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TopicError {
+    #[error("Malformed topic frame")]
+    Malformed,
+    #[error("Unsupported topic frame version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Topic frame could not be decrypted")]
+    DecryptionFailed,
+    #[error("Invalid topic name")]
+    InvalidTopic,
+    #[error("Topic payload too large: {0} bytes")]
+    PayloadTooLarge(usize),
+    #[error("Frame for a topic not shared with this contact")]
+    UnknownTopic,
+    #[error("Contact is not subscribed to this topic")]
+    NotSubscribed,
+    #[error("Capability token rejected: {0}")]
+    CapabilityRejected(&'static str),
+    #[error("Topic signing failed: {0}")]
+    Signing(String),
+    #[error("Topic encoding failed: {0}")]
+    Encoding(String),
+}
+
+pub type Result<T> = std::result::Result<T, TopicError>;
+
+/// Topic wire version.
+pub const TOPIC_VERSION: u8 = 1;
+
+/// Largest published payload; a sealed frame stays within one fixed-size
+/// packet.
+pub const MAX_TOPIC_PAYLOAD: usize = 4 * 1024;
+
+/// Longest topic name, in bytes.
+pub const MAX_TOPIC_LEN: usize = 64;
+
+/// Frames older than this (by the sender's clock) are dropped.
+pub const MAX_FRAME_AGE_SECS: u64 = 300;
+
+/// Sequence numbers behind the highest seen that are still accepted once.
+pub const REPLAY_WINDOW: u64 = 64;
+
+const TOPIC_ID_LEN: usize = 16;
+const HEADER_LEN: usize = 1 + TOPIC_ID_LEN;
+
+const ROOT_KEY_CONTEXT: &str = "ShieldMessenger-Topic-Root-v1";
+const TOPIC_KEY_CONTEXT: &str = "ShieldMessenger-Topic-Key-v1";
+const TOPIC_ID_CONTEXT: &str = "ShieldMessenger-Topic-Id-v1";
+const CAPABILITY_CONTEXT: &[u8] = b"ShieldMessenger-Topic-Capability-v1";
+
+fn validate_topic(topic: &str) -> Result<()> {
+    let valid = !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LEN
+        && topic
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"._-/".contains(&b));
+    if valid {
+        Ok(())
+    } else {
+        Err(TopicError::InvalidTopic)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Capabilities
+// ---------------------------------------------------------------------------
+
+/// Publisher-signed grant letting one contact subscribe to one topic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityToken {
+    pub topic: String,
+    /// Publisher's Ed25519 identity key.
+    pub publisher: [u8; 32],
+    /// Identity key of the contact allowed to subscribe.
+    pub subscriber: [u8; 32],
+    /// Unix seconds; subscriptions end with the token.
+    pub expires_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl CapabilityToken {
+    /// Sign a grant with the publisher's identity `signing_key`.
+    pub fn issue(
+        signing_key: &[u8],
+        subscriber: [u8; 32],
+        topic: &str,
+        expires_at: u64,
+    ) -> Result<Self> {
+        validate_topic(topic)?;
+        let publisher =
+            derive_public_key(signing_key).map_err(|e| TopicError::Signing(e.to_string()))?;
+        let mut token = Self {
+            topic: topic.to_string(),
+            publisher,
+            subscriber,
+            expires_at,
+            signature: Vec::new(),
+        };
+        token.signature = sign_data(&token.signed_bytes(), signing_key)
+            .map_err(|e| TopicError::Signing(e.to_string()))?
+            .to_vec();
+        Ok(token)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut data = CAPABILITY_CONTEXT.to_vec();
+        data.push(self.topic.len() as u8);
+        data.extend_from_slice(self.topic.as_bytes());
+        data.extend_from_slice(&self.publisher);
+        data.extend_from_slice(&self.subscriber);
+        data.extend_from_slice(&self.expires_at.to_be_bytes());
+        data
+    }
+
+    /// Check the token as the publisher `publisher`, presented by
+    /// `subscriber` for `topic` at `now`.
+    pub fn verify(
+        &self,
+        publisher: &[u8; 32],
+        subscriber: &[u8; 32],
+        topic: &str,
+        now: u64,
+    ) -> Result<()> {
+        if self.publisher != *publisher {
+            return Err(TopicError::CapabilityRejected("issued by someone else"));
+        }
+        if self.subscriber != *subscriber {
+            return Err(TopicError::CapabilityRejected("issued to someone else"));
+        }
+        if self.topic != topic {
+            return Err(TopicError::CapabilityRejected("issued for another topic"));
+        }
+        if self.expires_at <= now {
+            return Err(TopicError::CapabilityRejected("expired"));
+        }
+        match verify_signature(&self.signed_bytes(), &self.signature, &self.publisher) {
+            Ok(true) => Ok(()),
+            _ => Err(TopicError::CapabilityRejected("bad signature")),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self).map_err(|e| TopicError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let token: Self = bincode::deserialize(data).map_err(|_| TopicError::Malformed)?;
+        validate_topic(&token.topic)?;
+        Ok(token)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Frames and keys
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TopicBody {
+    /// Subscriber to publisher: start sending me this topic.
+    Subscribe { token: CapabilityToken },
+    /// Subscriber to publisher: stop.
+    Unsubscribe,
+    /// Publisher to subscriber.
+    Publish { payload: Vec<u8> },
+}
+
+/// One frame on a topic. `seq` counts per (peer, topic, direction).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicFrame {
+    pub seq: u64,
+    /// Sender's clock, Unix seconds.
+    pub sent_at: u64,
+    pub body: TopicBody,
+}
+
+/// Per-direction topic root keys for one contact, plus both identity keys.
+/// Wiped on drop.
+pub struct TopicKeys {
+    ours: [u8; 32],
+    theirs: [u8; 32],
+    send_root: [u8; 32],
+    recv_root: [u8; 32],
+}
+
+impl TopicKeys {
+    /// Keys from the contact's X25519 shared secret and both identity keys.
+    /// The two sides derive mirrored keys: our send root is their receive
+    /// root.
+    pub fn derive(
+        shared_secret: &[u8; 32],
+        our_pubkey: &[u8; 32],
+        their_pubkey: &[u8; 32],
+    ) -> Self {
+        let direction = |from: &[u8; 32], to: &[u8; 32]| {
+            let mut material = [0u8; 96];
+            material[..32].copy_from_slice(shared_secret);
+            material[32..64].copy_from_slice(from);
+            material[64..].copy_from_slice(to);
+            let key = blake3::derive_key(ROOT_KEY_CONTEXT, &material);
+            material.zeroize();
+            key
+        };
+        Self {
+            ours: *our_pubkey,
+            theirs: *their_pubkey,
+            send_root: direction(our_pubkey, their_pubkey),
+            recv_root: direction(their_pubkey, our_pubkey),
+        }
+    }
+
+    /// The contact's identity key.
+    pub fn peer(&self) -> [u8; 32] {
+        self.theirs
+    }
+
+    fn derive_for(context: &str, root: &[u8; 32], topic: &str) -> [u8; 32] {
+        let mut material = root.to_vec();
+        material.extend_from_slice(topic.as_bytes());
+        let key = blake3::derive_key(context, &material);
+        material.zeroize();
+        key
+    }
+
+    fn topic_id(root: &[u8; 32], topic: &str) -> [u8; TOPIC_ID_LEN] {
+        let mut id = [0u8; TOPIC_ID_LEN];
+        id.copy_from_slice(&Self::derive_for(TOPIC_ID_CONTEXT, root, topic)[..TOPIC_ID_LEN]);
+        id
+    }
+
+    fn seal(&self, topic: &str, frame: &TopicFrame, rng: &mut impl SecureRng) -> Result<Vec<u8>> {
+        let mut body =
+            bincode::serialize(frame).map_err(|e| TopicError::Encoding(e.to_string()))?;
+        let mut key = Self::derive_for(TOPIC_KEY_CONTEXT, &self.send_root, topic);
+        let sealed = encrypt_message_with_rng(&body, &key, rng)
+            .map_err(|e| TopicError::Encoding(e.to_string()));
+        key.zeroize();
+        body.zeroize();
+        let mut out = vec![TOPIC_VERSION];
+        out.extend_from_slice(&Self::topic_id(&self.send_root, topic));
+        out.extend_from_slice(&sealed?);
+        Ok(out)
+    }
+
+    /// Find which of `topics` a sealed frame belongs to and open it.
+    fn open<'a>(
+        &self,
+        sealed: &[u8],
+        topics: impl IntoIterator<Item = &'a String>,
+    ) -> Result<(String, TopicFrame)> {
+        if sealed.len() < HEADER_LEN {
+            return Err(TopicError::Malformed);
+        }
+        if sealed[0] != TOPIC_VERSION {
+            return Err(TopicError::UnsupportedVersion(sealed[0]));
+        }
+        let id = &sealed[1..HEADER_LEN];
+        let topic = topics
+            .into_iter()
+            .find(|t| Self::topic_id(&self.recv_root, t) == id)
+            .ok_or(TopicError::UnknownTopic)?;
+
+        let mut key = Self::derive_for(TOPIC_KEY_CONTEXT, &self.recv_root, topic);
+        let plaintext = decrypt_message(&sealed[HEADER_LEN..], &key);
+        key.zeroize();
+        let mut plaintext = plaintext.map_err(|_| TopicError::DecryptionFailed)?;
+        let frame =
+            bincode::deserialize::<TopicFrame>(&plaintext).map_err(|_| TopicError::Malformed);
+        plaintext.zeroize();
+        Ok((topic.clone(), frame?))
+    }
+}
+
+impl Drop for TopicKeys {
+    fn drop(&mut self) {
+        self.send_root.zeroize();
+        self.recv_root.zeroize();
+    }
+}
+
+/// Sliding-window replay check over sequence numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    /// Bit `i` set: `highest - i` was seen.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// Whether `seq` is new, remembering it if so.
+    fn accept(&mut self, seq: u64) -> bool {
+        let Some(highest) = self.highest else {
+            self.highest = Some(seq);
+            self.seen = 1;
+            return true;
+        };
+        if seq > highest {
+            let shift = seq - highest;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.highest = Some(seq);
+            return true;
+        }
+        let behind = highest - seq;
+        if behind >= REPLAY_WINDOW || self.seen & (1 << behind) != 0 {
+            return false;
+        }
+        self.seen |= 1 << behind;
+        true
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Hub
+// ---------------------------------------------------------------------------
+
+/// What a received frame means for the app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicEvent {
+    /// A contact subscribed to one of our topics with a valid token.
+    Subscribed {
+        topic: String,
+        expires_at: u64,
+    },
+    Unsubscribed {
+        topic: String,
+    },
+    /// A publication on a topic we subscribed to.
+    Message {
+        topic: String,
+        payload: Vec<u8>,
+    },
+    /// Replayed or stale; nothing to do.
+    Dropped,
+}
+
+type PeerTopic = ([u8; 32], String);
+
+/// Topics we publish and subscribe to, across all contacts.
+#[derive(Debug, Default)]
+pub struct TopicHub {
+    /// Topics we publish; subscribe frames for anything else are unknown.
+    offered: HashSet<String>,
+    /// Our subscribers per topic, with their token's expiry.
+    subscribers: HashMap<String, HashMap<[u8; 32], u64>>,
+    /// (publisher, topic) we subscribed to.
+    subscriptions: HashSet<PeerTopic>,
+    next_seq: HashMap<PeerTopic, u64>,
+    windows: HashMap<PeerTopic, ReplayWindow>,
+}
+
+impl TopicHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start publishing `topic`; subscribe frames for it are accepted from
+    /// now on.
+    pub fn offer(&mut self, topic: &str) -> Result<()> {
+        validate_topic(topic)?;
+        self.offered.insert(topic.to_string());
+        Ok(())
+    }
+
+    /// Stop publishing `topic` and forget its subscribers; returns how many
+    /// there were.
+    pub fn withdraw(&mut self, topic: &str) -> usize {
+        self.offered.remove(topic);
+        self.subscribers.remove(topic).map_or(0, |s| s.len())
+    }
+
+    /// Sign a token for `subscriber` and offer the topic.
+    pub fn grant(
+        &mut self,
+        signing_key: &[u8],
+        subscriber: [u8; 32],
+        topic: &str,
+        expires_at: u64,
+    ) -> Result<CapabilityToken> {
+        let token = CapabilityToken::issue(signing_key, subscriber, topic, expires_at)?;
+        self.offer(topic)?;
+        Ok(token)
+    }
+
+    /// Drop one subscriber; it must present a new token to come back.
+    pub fn revoke(&mut self, topic: &str, subscriber: &[u8; 32]) -> bool {
+        self.subscribers
+            .get_mut(topic)
+            .is_some_and(|s| s.remove(subscriber).is_some())
+    }
+
+    /// Current subscribers of `topic`, expired ones pruned.
+    pub fn subscribers(&mut self, topic: &str, now: u64) -> Vec<[u8; 32]> {
+        let Some(subscribers) = self.subscribers.get_mut(topic) else {
+            return Vec::new();
+        };
+        subscribers.retain(|_, expires_at| *expires_at > now);
+        subscribers.keys().copied().collect()
+    }
+
+    /// Whether we subscribed to `topic` from `publisher`.
+    pub fn is_subscribed(&self, publisher: &[u8; 32], topic: &str) -> bool {
+        self.subscriptions
+            .contains(&(*publisher, topic.to_string()))
+    }
+
+    fn frame(&mut self, peer: [u8; 32], topic: &str, body: TopicBody, now: u64) -> TopicFrame {
+        // Counters start from the clock so a restart never reuses numbers
+        // the peer's window has already seen
+        let next = self
+            .next_seq
+            .entry((peer, topic.to_string()))
+            .or_insert(now << 16);
+        let seq = (*next).max(now << 16);
+        *next = seq + 1;
+        TopicFrame {
+            seq,
+            sent_at: now,
+            body,
+        }
+    }
+
+    /// Subscribe with a token received from the publisher; returns the
+    /// sealed frame to send to it.
+    pub fn subscribe(
+        &mut self,
+        keys: &TopicKeys,
+        token: CapabilityToken,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>> {
+        if token.publisher != keys.theirs || token.subscriber != keys.ours {
+            return Err(TopicError::CapabilityRejected("not for this contact"));
+        }
+        if token.expires_at <= now {
+            return Err(TopicError::CapabilityRejected("expired"));
+        }
+        let topic = token.topic.clone();
+        validate_topic(&topic)?;
+        let frame = self.frame(keys.theirs, &topic, TopicBody::Subscribe { token }, now);
+        let sealed = keys.seal(&topic, &frame, rng)?;
+        self.subscriptions.insert((keys.theirs, topic));
+        Ok(sealed)
+    }
+
+    /// Leave a topic; returns the sealed frame telling the publisher.
+    pub fn unsubscribe(
+        &mut self,
+        keys: &TopicKeys,
+        topic: &str,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>> {
+        if !self.subscriptions.remove(&(keys.theirs, topic.to_string())) {
+            return Err(TopicError::NotSubscribed);
+        }
+        let frame = self.frame(keys.theirs, topic, TopicBody::Unsubscribe, now);
+        keys.seal(topic, &frame, rng)
+    }
+
+    /// Seal a publication on `topic` for one subscriber.
+    pub fn publish(
+        &mut self,
+        keys: &TopicKeys,
+        topic: &str,
+        payload: Vec<u8>,
+        now: u64,
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>> {
+        if payload.len() > MAX_TOPIC_PAYLOAD {
+            return Err(TopicError::PayloadTooLarge(payload.len()));
+        }
+        let subscribed = self
+            .subscribers
+            .get(topic)
+            .and_then(|s| s.get(&keys.theirs))
+            .is_some_and(|expires_at| *expires_at > now);
+        if !subscribed {
+            return Err(TopicError::NotSubscribed);
+        }
+        let frame = self.frame(keys.theirs, topic, TopicBody::Publish { payload }, now);
+        keys.seal(topic, &frame, rng)
+    }
+
+    /// Open and apply a sealed frame from the contact `keys` belong to.
+    pub fn receive(&mut self, keys: &TopicKeys, sealed: &[u8], now: u64) -> Result<TopicEvent> {
+        let peer = keys.theirs;
+        let candidates = self.offered.iter().chain(
+            self.subscriptions
+                .iter()
+                .filter(|(publisher, _)| *publisher == peer)
+                .map(|(_, topic)| topic),
+        );
+        let (topic, frame) = keys.open(sealed, candidates)?;
+
+        if now.saturating_sub(frame.sent_at) > MAX_FRAME_AGE_SECS {
+            return Ok(TopicEvent::Dropped);
+        }
+        let window = self.windows.entry((peer, topic.clone())).or_default();
+        if !window.accept(frame.seq) {
+            return Ok(TopicEvent::Dropped);
+        }
+
+        match frame.body {
+            TopicBody::Subscribe { token } => {
+                if !self.offered.contains(&topic) {
+                    return Err(TopicError::UnknownTopic);
+                }
+                token.verify(&keys.ours, &peer, &topic, now)?;
+                self.subscribers
+                    .entry(topic.clone())
+                    .or_default()
+                    .insert(peer, token.expires_at);
+                Ok(TopicEvent::Subscribed {
+                    topic,
+                    expires_at: token.expires_at,
+                })
+            }
+            TopicBody::Unsubscribe => {
+                self.revoke(&topic, &peer);
+                Ok(TopicEvent::Unsubscribed { topic })
+            }
+            TopicBody::Publish { payload } => {
+                if !self.is_subscribed(&peer, &topic) {
+                    return Err(TopicError::NotSubscribed);
+                }
+                if payload.len() > MAX_TOPIC_PAYLOAD {
+                    return Err(TopicError::PayloadTooLarge(payload.len()));
+                }
+                Ok(TopicEvent::Message { topic, payload })
+            }
+        }
+    }
+
+    /// Forget a deleted contact on both sides.
+    pub fn forget(&mut self, peer: &[u8; 32]) {
+        for subscribers in self.subscribers.values_mut() {
+            subscribers.remove(peer);
+        }
+        self.subscriptions.retain(|(p, _)| p != peer);
+        self.next_seq.retain(|(p, _), _| p != peer);
+        self.windows.retain(|(p, _), _| p != peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_subscribe_publish_round_trip() {
+        let mut rng = seeded(1);
+        let (alice, alice_secret) = generate_keypair_with_rng(&mut rng);
+        let (bob, _) = generate_keypair_with_rng(&mut rng);
+        let (carol, _) = generate_keypair_with_rng(&mut rng);
+        let shared = [5u8; 32];
+        let alice_keys = TopicKeys::derive(&shared, &alice, &bob);
+        let bob_keys = TopicKeys::derive(&shared, &bob, &alice);
+        let mut alice_hub = TopicHub::new();
+        let mut bob_hub = TopicHub::new();
+
+        let token = alice_hub
+            .grant(&alice_secret, bob, "status", NOW + 3_600)
+            .unwrap();
+        let token = CapabilityToken::from_bytes(&token.to_bytes().unwrap()).unwrap();
+        // Not publishable before Bob subscribes
+        assert_eq!(
+            alice_hub.publish(&alice_keys, "status", b"x".to_vec(), NOW, &mut rng),
+            Err(TopicError::NotSubscribed)
+        );
+
+        let subscribe = bob_hub
+            .subscribe(&bob_keys, token.clone(), NOW, &mut rng)
+            .unwrap();
+        assert_eq!(
+            alice_hub.receive(&alice_keys, &subscribe, NOW).unwrap(),
+            TopicEvent::Subscribed {
+                topic: "status".into(),
+                expires_at: NOW + 3_600
+            }
+        );
+        assert_eq!(alice_hub.subscribers("status", NOW), vec![bob]);
+        // The same frame again is a replay
+        assert_eq!(
+            alice_hub.receive(&alice_keys, &subscribe, NOW).unwrap(),
+            TopicEvent::Dropped
+        );
+
+        let published = alice_hub
+            .publish(&alice_keys, "status", b"away".to_vec(), NOW, &mut rng)
+            .unwrap();
+        assert!(!published.windows(6).any(|w| w == b"status"));
+        assert_eq!(
+            bob_hub.receive(&bob_keys, &published, NOW + 1).unwrap(),
+            TopicEvent::Message {
+                topic: "status".into(),
+                payload: b"away".to_vec()
+            }
+        );
+        assert_eq!(
+            bob_hub.receive(&bob_keys, &published, NOW + 1).unwrap(),
+            TopicEvent::Dropped
+        );
+
+        // Bob's token does not let Carol in, even over her own session
+        let carol_keys = TopicKeys::derive(&[6u8; 32], &carol, &alice);
+        let alice_carol = TopicKeys::derive(&[6u8; 32], &alice, &carol);
+        let mut carol_hub = TopicHub::new();
+        assert!(carol_hub
+            .subscribe(&carol_keys, token, NOW, &mut rng)
+            .is_err());
+        let forged = {
+            let frame = TopicFrame {
+                seq: 1,
+                sent_at: NOW,
+                body: TopicBody::Subscribe {
+                    token: alice_hub
+                        .grant(&alice_secret, bob, "status", NOW + 60)
+                        .unwrap(),
+                },
+            };
+            carol_keys.seal("status", &frame, &mut rng).unwrap()
+        };
+        assert_eq!(
+            alice_hub.receive(&alice_carol, &forged, NOW),
+            Err(TopicError::CapabilityRejected("issued to someone else"))
+        );
+
+        // Unsubscribing ends publication
+        let leave = bob_hub
+            .unsubscribe(&bob_keys, "status", NOW, &mut rng)
+            .unwrap();
+        assert_eq!(
+            alice_hub.receive(&alice_keys, &leave, NOW).unwrap(),
+            TopicEvent::Unsubscribed {
+                topic: "status".into()
+            }
+        );
+        assert!(alice_hub.subscribers("status", NOW).is_empty());
+    }
+
+    #[test]
+    fn test_replay_window_and_expiry() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(window.accept(98));
+        assert!(!window.accept(98));
+        assert!(window.accept(100 + REPLAY_WINDOW));
+        // 100 is now at the window edge and out
+        assert!(!window.accept(100));
+        assert!(window.accept(101));
+
+        let mut rng = seeded(2);
+        let (alice, alice_secret) = generate_keypair_with_rng(&mut rng);
+        let bob = [0xB0; 32];
+        let alice_keys = TopicKeys::derive(&[1u8; 32], &alice, &bob);
+        let bob_keys = TopicKeys::derive(&[1u8; 32], &bob, &alice);
+        let mut alice_hub = TopicHub::new();
+        let mut bob_hub = TopicHub::new();
+        let token = alice_hub
+            .grant(&alice_secret, bob, "typing", NOW + 10)
+            .unwrap();
+        let subscribe = bob_hub.subscribe(&bob_keys, token, NOW, &mut rng).unwrap();
+
+        // Stale frames are dropped
+        assert_eq!(
+            alice_hub
+                .receive(&alice_keys, &subscribe, NOW + MAX_FRAME_AGE_SECS + 1)
+                .unwrap(),
+            TopicEvent::Dropped
+        );
+        alice_hub.receive(&alice_keys, &subscribe, NOW).unwrap();
+        assert_eq!(alice_hub.subscribers("typing", NOW + 9), vec![bob]);
+        assert!(alice_hub.subscribers("typing", NOW + 10).is_empty());
+
+        assert_eq!(
+            alice_hub.grant(&alice_secret, bob, "bad topic!", NOW),
+            Err(TopicError::InvalidTopic)
+        );
+        assert_eq!(
+            bob_hub.receive(&bob_keys, &[TOPIC_VERSION; HEADER_LEN + 40], NOW),
+            Err(TopicError::UnknownTopic)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Per-client rate limiting.
+//!
+//! Clients reach the relay over Tor, so there is no address to limit by; a
+//! client is whatever the transport can tell apart (one connection, for the
+//! daemon). Each one gets a fixed one-minute window, the same accounting
+//! [`RelaySelector`](crate::protocol::relay::RelaySelector) applies on the
+//! client side against the advertised limits.
+
+use std::collections::HashMap;
+
+/// Length of one rate window.
+pub const RATE_WINDOW_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: u64,
+    count: u32,
+}
+
+/// Fixed-window counter of requests per client.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: u32,
+    windows: HashMap<u64, Window>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Count one request from `client`; `Err` holds the seconds until its
+    /// window resets.
+    pub fn check(&mut self, client: u64, now: u64) -> Result<(), u64> {
+        let window = self.windows.entry(client).or_insert(Window {
+            start: now,
+            count: 0,
+        });
+        let reset_at = window.start.saturating_add(RATE_WINDOW_SECS);
+        if now >= reset_at {
+            *window = Window {
+                start: now,
+                count: 0,
+            };
+        } else if window.count >= self.per_minute {
+            return Err(reset_at - now);
+        }
+        window.count += 1;
+        Ok(())
+    }
+
+    /// The client went away.
+    pub fn forget(&mut self, client: u64) {
+        self.windows.remove(&client);
+    }
+
+    /// Drop windows that ended before `now`.
+    pub fn prune(&mut self, now: u64) {
+        self.windows
+            .retain(|_, w| now < w.start.saturating_add(RATE_WINDOW_SECS));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_limits_and_resets() {
+        let mut limiter = RateLimiter::new(3);
+        for _ in 0..3 {
+            assert_eq!(limiter.check(1, 100), Ok(()));
+        }
+        assert_eq!(limiter.check(1, 130), Err(30));
+        // Other clients have their own budget
+        assert_eq!(limiter.check(2, 130), Ok(()));
+        assert_eq!(limiter.check(1, 160), Ok(()));
+
+        limiter.prune(190);
+        assert_eq!(limiter.windows.len(), 1);
+        limiter.forget(1);
+        assert!(limiter.windows.is_empty());
+    }
+}
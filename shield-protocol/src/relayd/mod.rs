@@ -0,0 +1,29 @@
+//! Reference store-and-forward relay (feature `relayd`, off by default).
+//!
+//! The server side of [`relay_api`](crate::protocol::relay_api), for
+//! integrators who want to host their own relay and for testing clients
+//! against a real one. [`Relay`] holds everything a relay does without any
+//! I/O; the `shield-relayd` binary serves it over TCP, normally behind a Tor
+//! onion service:
+//!
+//! - **Blind storage** ([`MailStore`]): sealed envelopes filed by mailbox,
+//!   with nothing kept about who deposited them.
+//! - **Expiry**: envelopes and backup slots are deleted after the advertised
+//!   retention, and on fetch if the policy says so.
+//! - **Rate limiting** ([`RateLimiter`]): deposits per client against the
+//!   advertised limit, plus a cap on requests of any kind.
+//! - **Descriptor publishing**: the relay signs its own
+//!   [`RelayDescriptor`](crate::protocol::relay::RelayDescriptor), serves it
+//!   on request and re-signs it before it expires.
+//!
+//! Mailbox checks (bucketed and PIR) are answered from a
+//! [`MailboxIndex`](crate::protocol::mailbox::MailboxIndex) rebuilt whenever
+//! a mailbox gains its first envelope or loses its last.
+
+pub mod limits;
+pub mod service;
+pub mod store;
+
+pub use limits::{RateLimiter, RATE_WINDOW_SECS};
+pub use service::{Relay, RelayConfig, MAX_POLL_SKEW_SECS};
+pub use store::MailStore;
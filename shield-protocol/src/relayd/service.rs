@@ -0,0 +1,431 @@
+//! Request handling for one relay: descriptor, mail store, index and backup
+//! slots behind [`RelayRequest`]s.
+
+use zeroize::Zeroize;
+
+use super::limits::RateLimiter;
+use super::store::MailStore;
+use crate::crypto::signing::derive_public_key;
+use crate::protocol::contact_backup::{BackupVault, CONTACT_BACKUP_PROTOCOL};
+use crate::protocol::mailbox::{
+    MailboxError, PirQuery, PlainPoll, MAILBOX_INDEX_PROTOCOL, MAX_BUCKET_BITS,
+};
+use crate::protocol::relay::{
+    RateLimits, RelayDescriptor, RelayError, RelayInfo, RetentionPolicy,
+    MAX_DESCRIPTOR_LIFETIME_SECS,
+};
+use crate::protocol::relay_api::{
+    RelayRejection, RelayRequest, RelayResponse, MAX_ENVELOPE_LEN, MAX_FETCH_BYTES,
+};
+
+/// Polls whose timestamp is further than this from the relay's clock are
+/// refused.
+pub const MAX_POLL_SKEW_SECS: u64 = 5 * 60;
+
+/// What the relay offers and how much it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayConfig {
+    /// Onion address of the relay's hidden service, with `.onion`.
+    pub onion_address: String,
+    pub retention: RetentionPolicy,
+    /// Deposits allowed per client, and the largest envelope.
+    pub rate_limits: RateLimits,
+    /// Requests of any kind allowed per client and minute.
+    pub requests_per_minute: u32,
+    /// Mailbox index size (`2^bucket_bits` buckets).
+    pub bucket_bits: u8,
+    pub max_per_mailbox: usize,
+    pub max_store_bytes: usize,
+    pub max_backup_slots: usize,
+    /// How long each signed descriptor is valid; it is re-signed halfway.
+    pub descriptor_lifetime_secs: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            onion_address: String::new(),
+            retention: RetentionPolicy {
+                max_retention_secs: 7 * 24 * 60 * 60,
+                delete_on_fetch: true,
+            },
+            rate_limits: RateLimits {
+                messages_per_minute: 60,
+                max_message_bytes: MAX_ENVELOPE_LEN as u32,
+            },
+            requests_per_minute: 600,
+            bucket_bits: 8,
+            max_per_mailbox: 1_000,
+            max_store_bytes: 1 << 30,
+            max_backup_slots: 10_000,
+            descriptor_lifetime_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// One relay's state. Not tied to any transport: feed it decoded requests
+/// (or raw frames) with a client handle and the current time.
+pub struct Relay {
+    config: RelayConfig,
+    signing_key: [u8; 32],
+    public_key: [u8; 32],
+    descriptor: Vec<u8>,
+    issued_at: u64,
+    store: MailStore,
+    vault: BackupVault,
+    deposits: RateLimiter,
+    requests: RateLimiter,
+}
+
+impl Relay {
+    /// Relay signing its descriptors with the Ed25519 seed `signing_key`.
+    pub fn new(config: RelayConfig, signing_key: [u8; 32], now: u64) -> Result<Self, RelayError> {
+        if config.bucket_bits > MAX_BUCKET_BITS
+            || config.descriptor_lifetime_secs == 0
+            || config.descriptor_lifetime_secs > MAX_DESCRIPTOR_LIFETIME_SECS
+            || config.rate_limits.max_message_bytes as usize > MAX_ENVELOPE_LEN
+        {
+            return Err(RelayError::InvalidPolicy);
+        }
+        let public_key =
+            derive_public_key(&signing_key).map_err(|e| RelayError::Signing(e.to_string()))?;
+        let store = MailStore::new(
+            config.max_per_mailbox,
+            config.max_store_bytes,
+            config.bucket_bits,
+        )
+        .map_err(|_| RelayError::InvalidPolicy)?;
+        let mut relay = Self {
+            vault: BackupVault::new(config.max_backup_slots),
+            deposits: RateLimiter::new(config.rate_limits.messages_per_minute),
+            requests: RateLimiter::new(config.requests_per_minute),
+            config,
+            signing_key,
+            public_key,
+            descriptor: Vec::new(),
+            issued_at: 0,
+            store,
+        };
+        relay.sign_descriptor(now)?;
+        Ok(relay)
+    }
+
+    pub fn public_key(&self) -> &[u8; 32] {
+        &self.public_key
+    }
+
+    pub fn config(&self) -> &RelayConfig {
+        &self.config
+    }
+
+    /// Current encoded descriptor, re-signed once half its lifetime is over.
+    pub fn descriptor(&mut self, now: u64) -> Result<&[u8], RelayError> {
+        if now >= self.issued_at + self.config.descriptor_lifetime_secs / 2 {
+            self.sign_descriptor(now)?;
+        }
+        Ok(&self.descriptor)
+    }
+
+    fn sign_descriptor(&mut self, now: u64) -> Result<(), RelayError> {
+        let info = RelayInfo {
+            relay_key: self.public_key,
+            onion_address: self.config.onion_address.clone(),
+            protocol_versions: vec![1, MAILBOX_INDEX_PROTOCOL, CONTACT_BACKUP_PROTOCOL],
+            retention: self.config.retention,
+            rate_limits: self.config.rate_limits,
+            issued_at: now,
+            expires_at: now + self.config.descriptor_lifetime_secs,
+        };
+        self.descriptor = RelayDescriptor::sign(info, &self.signing_key)?.to_bytes()?;
+        self.issued_at = now;
+        Ok(())
+    }
+
+    /// Answer one raw frame from `client`. Undecodable frames get a
+    /// [`RelayRejection::Malformed`] answer.
+    pub fn handle_frame(&mut self, client: u64, frame: &[u8], now: u64) -> Vec<u8> {
+        let response = match RelayRequest::from_bytes(frame) {
+            Ok(request) => self.handle(client, request, now),
+            Err(_) => RelayResponse::Rejected(RelayRejection::Malformed),
+        };
+        response.to_bytes().unwrap_or_else(|e| {
+            log::warn!("relayd: failed to encode response: {}", e);
+            RelayResponse::Rejected(RelayRejection::Malformed)
+                .to_bytes()
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn handle(&mut self, client: u64, request: RelayRequest, now: u64) -> RelayResponse {
+        if let Err(retry_after_secs) = self.requests.check(client, now) {
+            return RelayResponse::Rejected(RelayRejection::RateLimited { retry_after_secs });
+        }
+        match request {
+            RelayRequest::Descriptor => match self.descriptor(now) {
+                Ok(descriptor) => RelayResponse::Descriptor(descriptor.to_vec()),
+                Err(e) => {
+                    log::warn!("relayd: failed to sign descriptor: {}", e);
+                    RelayResponse::Descriptor(self.descriptor.clone())
+                }
+            },
+            RelayRequest::Deposit { mailbox, envelope } => {
+                let max = self.config.rate_limits.max_message_bytes;
+                if envelope.len() > max as usize {
+                    return RelayResponse::Rejected(RelayRejection::TooLarge { max });
+                }
+                if let Err(retry_after_secs) = self.deposits.check(client, now) {
+                    return RelayResponse::Rejected(RelayRejection::RateLimited {
+                        retry_after_secs,
+                    });
+                }
+                match self.store.deposit(mailbox, envelope, now) {
+                    Ok(()) => RelayResponse::Deposited,
+                    Err(rejection) => RelayResponse::Rejected(rejection),
+                }
+            }
+            RelayRequest::Bucket {
+                bucket_bits,
+                bucket,
+            } => {
+                if bucket_bits != self.config.bucket_bits {
+                    return RelayResponse::Rejected(RelayRejection::BucketBits(
+                        self.config.bucket_bits,
+                    ));
+                }
+                match self.store.index().record(bucket) {
+                    Some(record) => RelayResponse::Record(record.to_vec()),
+                    None => RelayResponse::Rejected(RelayRejection::Malformed),
+                }
+            }
+            RelayRequest::Pir(query) => {
+                let Ok(query) = PirQuery::from_bytes(&query) else {
+                    return RelayResponse::Rejected(RelayRejection::Malformed);
+                };
+                match self.store.index().answer(&query) {
+                    Ok(answer) => RelayResponse::Pir(answer.to_bytes()),
+                    Err(_) => {
+                        RelayResponse::Rejected(RelayRejection::BucketBits(self.config.bucket_bits))
+                    }
+                }
+            }
+            RelayRequest::Fetch { poll, after } => {
+                let mailbox = match PlainPoll::from_bytes(&poll)
+                    .and_then(|poll| poll.verify(now, MAX_POLL_SKEW_SECS))
+                {
+                    Ok(mailbox) => mailbox,
+                    Err(MailboxError::Stale) => {
+                        return RelayResponse::Rejected(RelayRejection::Stale)
+                    }
+                    Err(MailboxError::BadSignature) => {
+                        return RelayResponse::Rejected(RelayRejection::Unauthorized)
+                    }
+                    Err(_) => return RelayResponse::Rejected(RelayRejection::Malformed),
+                };
+                let (envelopes, more) = self.store.fetch(
+                    &mailbox,
+                    after,
+                    MAX_FETCH_BYTES,
+                    self.config.retention.delete_on_fetch,
+                );
+                RelayResponse::Mail { envelopes, more }
+            }
+            RelayRequest::Backup(request) => {
+                RelayResponse::Backup(self.vault.handle(&request, now))
+            }
+        }
+    }
+
+    /// The connection of `client` closed.
+    pub fn disconnect(&mut self, client: u64) {
+        self.deposits.forget(client);
+        self.requests.forget(client);
+    }
+
+    /// Periodic upkeep: drop mail and backup slots past retention and
+    /// stale rate windows. Returns how many envelopes were deleted.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let cutoff = now.saturating_sub(self.config.retention.max_retention_secs);
+        self.vault.prune(cutoff);
+        self.deposits.prune(now);
+        self.requests.prune(now);
+        self.store.expire(cutoff)
+    }
+
+    /// Mailboxes holding mail and the bytes they hold.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.store.len(), self.store.total_bytes())
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.signing_key.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signing::generate_keypair_with_rng;
+    use crate::protocol::mailbox::{
+        check_request, BucketRecord, CheckMode, CheckResult, MailboxId, MailboxPollConfig,
+        PirAnswer,
+    };
+    use crate::protocol::relay_api::check_requests;
+    use crate::rng::seeded;
+
+    const NOW: u64 = 1_700_000_000;
+    const ONION: &str = "abcdefghijklmnopqrstuvwxyz234567abcdefghijklmnopqrstuvwd.onion";
+
+    fn relay(seed: u64) -> Relay {
+        let (_, secret) = generate_keypair_with_rng(&mut seeded(seed));
+        let config = RelayConfig {
+            onion_address: ONION.into(),
+            bucket_bits: 4,
+            ..RelayConfig::default()
+        };
+        Relay::new(config, secret, NOW).unwrap()
+    }
+
+    fn exchange(relay: &mut Relay, request: &RelayRequest, now: u64) -> RelayResponse {
+        let frame = relay.handle_frame(7, &request.to_bytes().unwrap(), now);
+        RelayResponse::from_bytes(&frame).unwrap()
+    }
+
+    #[test]
+    fn test_deposit_check_and_fetch() {
+        let mut rng = seeded(11);
+        let (public, secret) = generate_keypair_with_rng(&mut rng);
+        let inbox = MailboxId::from_public_key(&public);
+        let (mut a, mut b) = (relay(1), relay(2));
+
+        let RelayResponse::Descriptor(bytes) = exchange(&mut a, &RelayRequest::Descriptor, NOW)
+        else {
+            panic!("no descriptor");
+        };
+        let descriptor = RelayDescriptor::from_bytes(&bytes).unwrap();
+        descriptor.verify(NOW).unwrap();
+        assert_eq!(&descriptor.info.relay_key, a.public_key());
+        assert_eq!(descriptor.protocol_version(), Some(CONTACT_BACKUP_PROTOCOL));
+
+        for relay in [&mut a, &mut b] {
+            let deposit = RelayRequest::Deposit {
+                mailbox: inbox,
+                envelope: b"sealed".to_vec(),
+            };
+            assert_eq!(exchange(relay, &deposit, NOW), RelayResponse::Deposited);
+        }
+
+        // Two relays mirroring the same mail answer a PIR check together
+        let config = MailboxPollConfig {
+            mode: CheckMode::Pir,
+            bucket_bits: 4,
+            ..MailboxPollConfig::default()
+        };
+        let check = check_request(&config, 2, &secret, &public, NOW, &mut rng).unwrap();
+        let answers: Vec<PirAnswer> = check_requests(&check)
+            .iter()
+            .zip([&mut a, &mut b])
+            .map(|(request, relay)| match exchange(relay, request, NOW) {
+                RelayResponse::Pir(answer) => PirAnswer::from_bytes(&answer).unwrap(),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        let record = answers[0].combine(&answers[1]).unwrap();
+        assert_eq!(record.check(&inbox), CheckResult::HasMail);
+
+        let wrong_bits = RelayRequest::Bucket {
+            bucket_bits: 8,
+            bucket: 0,
+        };
+        assert_eq!(
+            exchange(&mut a, &wrong_bits, NOW),
+            RelayResponse::Rejected(RelayRejection::BucketBits(4))
+        );
+
+        let poll = PlainPoll::create(&secret, &public, NOW).unwrap().to_bytes();
+        let fetch = RelayRequest::Fetch {
+            poll: poll.to_vec(),
+            after: 0,
+        };
+        assert_eq!(
+            exchange(&mut a, &fetch, NOW),
+            RelayResponse::Mail {
+                envelopes: vec![(NOW << 16, b"sealed".to_vec())],
+                more: false
+            }
+        );
+        // Deleted on fetch, and the index follows
+        assert_eq!(a.stats(), (0, 0));
+        let bucket = RelayRequest::Bucket {
+            bucket_bits: 4,
+            bucket: inbox.bucket(4),
+        };
+        let RelayResponse::Record(record) = exchange(&mut a, &bucket, NOW) else {
+            panic!("no record");
+        };
+        let record = BucketRecord::from_bytes(&record).unwrap();
+        assert_eq!(record.check(&inbox), CheckResult::Empty);
+
+        // A tampered poll does not open the mailbox, a replayed old one is
+        // stale
+        let mut forged = poll;
+        forged[50] ^= 1;
+        let forged = RelayRequest::Fetch {
+            poll: forged.to_vec(),
+            after: 0,
+        };
+        assert_eq!(
+            exchange(&mut b, &forged, NOW),
+            RelayResponse::Rejected(RelayRejection::Unauthorized)
+        );
+        assert_eq!(
+            exchange(&mut b, &fetch, NOW + MAX_POLL_SKEW_SECS + 1),
+            RelayResponse::Rejected(RelayRejection::Stale)
+        );
+    }
+
+    #[test]
+    fn test_limits_and_expiry() {
+        let mut relay = relay(3);
+        let deposit = |len: usize| RelayRequest::Deposit {
+            mailbox: MailboxId([1; 32]),
+            envelope: vec![0; len],
+        };
+        let max = relay.config().rate_limits.max_message_bytes;
+        assert_eq!(
+            relay.handle(1, deposit(max as usize + 1), NOW),
+            RelayResponse::Rejected(RelayRejection::TooLarge { max })
+        );
+        for _ in 0..60 {
+            assert_eq!(relay.handle(1, deposit(10), NOW), RelayResponse::Deposited);
+        }
+        assert_eq!(
+            relay.handle(1, deposit(10), NOW + 20),
+            RelayResponse::Rejected(RelayRejection::RateLimited {
+                retry_after_secs: 40
+            })
+        );
+        // A new connection starts with a fresh budget
+        assert_eq!(
+            relay.handle(2, deposit(10), NOW + 20),
+            RelayResponse::Deposited
+        );
+        assert_eq!(
+            RelayResponse::from_bytes(&relay.handle_frame(2, b"\x01junk", NOW)).unwrap(),
+            RelayResponse::Rejected(RelayRejection::Malformed)
+        );
+
+        let retention = relay.config().retention.max_retention_secs;
+        assert_eq!(relay.expire(NOW + retention), 0);
+        assert_eq!(relay.expire(NOW + retention + 1), 60);
+        assert_eq!(relay.stats(), (1, 10));
+
+        // The descriptor is re-signed halfway through its lifetime
+        let first = relay.descriptor(NOW).unwrap().to_vec();
+        let half = relay.config().descriptor_lifetime_secs / 2;
+        assert_eq!(relay.descriptor(NOW + half - 1).unwrap(), first);
+        let renewed = RelayDescriptor::from_bytes(relay.descriptor(NOW + half).unwrap()).unwrap();
+        assert_eq!(renewed.info.issued_at, NOW + half);
+    }
+}
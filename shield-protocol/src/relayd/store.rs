@@ -0,0 +1,261 @@
+//! Blind mail storage.
+//!
+//! Envelopes are opaque bytes filed under the [`MailboxId`] they were
+//! deposited for. The store never looks inside them and keeps nothing about
+//! the depositor; per envelope it knows only the arrival time (for expiry)
+//! and a per-mailbox sequence number (for paged fetches).
+//!
+//! Sequence numbers start from the arrival time rather than from one, so
+//! they keep growing after a mailbox empties and is forgotten: a client
+//! fetching after its last cursor never skips mail deposited since.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::protocol::mailbox::{MailboxError, MailboxId, MailboxIndex};
+use crate::protocol::relay_api::RelayRejection;
+
+/// Sequence numbers start at `arrival time << SEQ_TIME_SHIFT`, leaving room
+/// for 65536 deposits per second to one mailbox before they run ahead of
+/// the clock (which is harmless; they still increase).
+const SEQ_TIME_SHIFT: u32 = 16;
+
+#[derive(Debug)]
+struct Held {
+    seq: u64,
+    received_at: u64,
+    envelope: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct Mailbox {
+    held: VecDeque<Held>,
+    /// Sequence number of the last envelope deposited.
+    last_seq: u64,
+}
+
+/// Held mail, by mailbox.
+#[derive(Debug)]
+pub struct MailStore {
+    mailboxes: HashMap<MailboxId, Mailbox>,
+    max_per_mailbox: usize,
+    max_total_bytes: usize,
+    total_bytes: usize,
+    bucket_bits: u8,
+    /// Rebuilt on the next index read after the set of non-empty mailboxes
+    /// changes.
+    index: Option<MailboxIndex>,
+}
+
+impl MailStore {
+    /// Store holding at most `max_per_mailbox` envelopes per mailbox and
+    /// `max_total_bytes` overall, indexed into `2^bucket_bits` buckets.
+    pub fn new(
+        max_per_mailbox: usize,
+        max_total_bytes: usize,
+        bucket_bits: u8,
+    ) -> Result<Self, MailboxError> {
+        // Validates the bucket bits once, so later rebuilds cannot fail
+        let index = MailboxIndex::build(bucket_bits, [])?;
+        Ok(Self {
+            mailboxes: HashMap::new(),
+            max_per_mailbox,
+            max_total_bytes,
+            total_bytes: 0,
+            bucket_bits,
+            index: Some(index),
+        })
+    }
+
+    /// Mailboxes holding mail.
+    pub fn len(&self) -> usize {
+        self.mailboxes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mailboxes.is_empty()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    pub fn deposit(
+        &mut self,
+        mailbox: MailboxId,
+        envelope: Vec<u8>,
+        now: u64,
+    ) -> Result<(), RelayRejection> {
+        if self.total_bytes + envelope.len() > self.max_total_bytes {
+            return Err(RelayRejection::StoreFull);
+        }
+        let entry = self.mailboxes.entry(mailbox).or_default();
+        if entry.held.len() >= self.max_per_mailbox {
+            return Err(RelayRejection::MailboxFull);
+        }
+        if entry.held.is_empty() {
+            self.index = None;
+        }
+        entry.last_seq = (entry.last_seq + 1).max(now << SEQ_TIME_SHIFT);
+        self.total_bytes += envelope.len();
+        entry.held.push_back(Held {
+            seq: entry.last_seq,
+            received_at: now,
+            envelope,
+        });
+        Ok(())
+    }
+
+    /// Envelopes after sequence number `after`, oldest first, up to
+    /// `max_bytes` (but at least one). With `remove` they are deleted as
+    /// they are returned. Also returns whether more is held.
+    pub fn fetch(
+        &mut self,
+        mailbox: &MailboxId,
+        after: u64,
+        max_bytes: usize,
+        remove: bool,
+    ) -> (Vec<(u64, Vec<u8>)>, bool) {
+        let Some(entry) = self.mailboxes.get_mut(mailbox) else {
+            return (Vec::new(), false);
+        };
+        let mut out = Vec::new();
+        let mut bytes = 0;
+        let mut more = false;
+        for held in entry.held.iter().filter(|h| h.seq > after) {
+            if !out.is_empty() && bytes + held.envelope.len() > max_bytes {
+                more = true;
+                break;
+            }
+            bytes += held.envelope.len();
+            out.push((held.seq, held.envelope.clone()));
+        }
+        if remove {
+            let last = out.last().map_or(after, |(seq, _)| *seq);
+            entry.held.retain(|h| h.seq <= after || h.seq > last);
+            self.total_bytes -= bytes;
+            self.drop_if_empty(mailbox);
+        }
+        (out, more)
+    }
+
+    /// Delete envelopes received before `cutoff`; returns how many.
+    pub fn expire(&mut self, cutoff: u64) -> usize {
+        let mut removed = 0;
+        let mut freed = 0;
+        let mut emptied = false;
+        self.mailboxes.retain(|_, entry| {
+            entry.held.retain(|h| {
+                let keep = h.received_at >= cutoff;
+                if !keep {
+                    removed += 1;
+                    freed += h.envelope.len();
+                }
+                keep
+            });
+            emptied |= entry.held.is_empty();
+            !entry.held.is_empty()
+        });
+        self.total_bytes -= freed;
+        if emptied {
+            self.index = None;
+        }
+        removed
+    }
+
+    /// Index over the mailboxes currently holding mail.
+    pub fn index(&mut self) -> &MailboxIndex {
+        let bucket_bits = self.bucket_bits;
+        let mailboxes = &self.mailboxes;
+        self.index.get_or_insert_with(|| {
+            MailboxIndex::build(bucket_bits, mailboxes.keys().copied())
+                .expect("bucket bits checked in MailStore::new")
+        })
+    }
+
+    fn drop_if_empty(&mut self, mailbox: &MailboxId) {
+        if self
+            .mailboxes
+            .get(mailbox)
+            .is_some_and(|e| e.held.is_empty())
+        {
+            self.mailboxes.remove(mailbox);
+            self.index = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::mailbox::{BucketRecord, CheckResult};
+
+    fn mailbox(seed: u8) -> MailboxId {
+        MailboxId::from_public_key(&[seed; 32])
+    }
+
+    #[test]
+    fn test_fetch_pages_and_removes() {
+        let mut store = MailStore::new(10, 1 << 20, 4).unwrap();
+        let inbox = mailbox(1);
+        for i in 0..5u8 {
+            store.deposit(inbox, vec![i; 100], 1_000).unwrap();
+        }
+        assert_eq!(store.total_bytes(), 500);
+
+        // Kept mail pages by sequence number
+        let (page, more) = store.fetch(&inbox, 0, 250, false);
+        assert_eq!(page.len(), 2);
+        assert!(page[0].0 < page[1].0);
+        assert!(more);
+        let (rest, more) = store.fetch(&inbox, page[1].0, 1 << 20, false);
+        assert_eq!(rest.len(), 3);
+        assert!(!more);
+        assert_eq!(store.total_bytes(), 500);
+
+        // Removing fetches delete exactly what they return
+        let (first, _) = store.fetch(&inbox, 0, 150, true);
+        assert_eq!(first, vec![(page[0].0, vec![0; 100])]);
+        let (rest, more) = store.fetch(&inbox, first[0].0, 1 << 20, true);
+        assert_eq!(rest.len(), 4);
+        assert!(!more);
+        assert!(store.is_empty());
+        assert_eq!(store.total_bytes(), 0);
+
+        // The mailbox was forgotten, but its cursor still finds new mail
+        let cursor = rest[3].0;
+        store.deposit(inbox, vec![9], 1_001).unwrap();
+        assert_eq!(store.fetch(&inbox, cursor, 1 << 20, false).0.len(), 1);
+    }
+
+    #[test]
+    fn test_limits_expiry_and_index() {
+        let mut store = MailStore::new(2, 250, 4).unwrap();
+        let (a, b) = (mailbox(1), mailbox(2));
+        store.deposit(a, vec![0; 100], 1_000).unwrap();
+        store.deposit(a, vec![0; 100], 2_000).unwrap();
+        assert_eq!(
+            store.deposit(a, vec![0; 10], 2_000),
+            Err(RelayRejection::MailboxFull)
+        );
+        assert_eq!(
+            store.deposit(b, vec![0; 100], 2_000),
+            Err(RelayRejection::StoreFull)
+        );
+        store.deposit(b, vec![0; 50], 2_000).unwrap();
+
+        let record = |store: &mut MailStore, m: MailboxId| {
+            let index = store.index();
+            BucketRecord::from_bytes(index.record(m.bucket(4)).unwrap())
+                .unwrap()
+                .check(&m)
+        };
+        assert_eq!(record(&mut store, a), CheckResult::HasMail);
+        assert_eq!(record(&mut store, b), CheckResult::HasMail);
+
+        assert_eq!(store.expire(1_500), 1);
+        assert_eq!(store.expire(2_001), 2);
+        assert!(store.is_empty());
+        assert_eq!(store.total_bytes(), 0);
+        assert_eq!(record(&mut store, a), CheckResult::Empty);
+    }
+}
@@ -0,0 +1,355 @@
+/// Power-on self-test of the cryptographic stack.
+///
+/// [`selftest`] runs known-answer tests (published vectors from the RFCs and
+/// CFRG drafts) against every primitive the protocol relies on, through the
+/// same functions the protocol calls, plus a pairwise consistency test for
+/// ML-KEM-1024. A miscompiled backend, a wrong feature selection or a
+/// tampered dependency makes at least one check fail, and the returned
+/// [`SelfTestReport`] says which.
+///
+/// Deployments that must not run with a broken crypto stack call it once
+/// at startup, before any key material is touched, and refuse to continue
+/// if [`SelfTestReport::passed`] is false (or use
+/// [`SelfTestReport::into_result`]). It takes a few milliseconds.
+use crate::crypto::encryption::{decrypt_message, derive_root_key};
+use crate::crypto::key_exchange;
+use crate::crypto::pqc::{
+    generate_hybrid_keypair_from_seed, hybrid_decapsulate, hybrid_encapsulate,
+};
+use crate::crypto::signing::{derive_public_key, sign_data, verify_signature};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use serde::Serialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SelfTestError {
+    #[error("Cryptographic self-test failed: {0}")]
+    Failed(String),
+}
+
+/// Algorithm family a check covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Algorithm {
+    /// XChaCha20-Poly1305
+    Aead,
+    /// HKDF-SHA256 and BLAKE3
+    Kdf,
+    /// Ed25519
+    Signature,
+    /// X25519
+    KeyAgreement,
+    /// ML-KEM-1024 (hybrid with X25519)
+    Kem,
+}
+
+/// Outcome of one check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckOutcome {
+    pub algorithm: Algorithm,
+    pub name: &'static str,
+    pub passed: bool,
+    /// Why the check failed.
+    pub detail: Option<String>,
+}
+
+/// Results of a [`selftest`] run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckOutcome>,
+}
+
+impl SelfTestReport {
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckOutcome> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+
+    /// The report if everything passed, otherwise an error naming the
+    /// failed checks.
+    pub fn into_result(self) -> Result<Self, SelfTestError> {
+        if self.passed() {
+            return Ok(self);
+        }
+        let failed: Vec<&str> = self.failures().map(|c| c.name).collect();
+        Err(SelfTestError::Failed(failed.join(", ")))
+    }
+}
+
+/// Run all known-answer and consistency tests.
+pub fn selftest() -> SelfTestReport {
+    let checks: [(Algorithm, &'static str, fn() -> Result<(), String>); 9] = [
+        (Algorithm::Aead, "xchacha20poly1305-kat", aead_kat),
+        (Algorithm::Aead, "message-decrypt-kat", message_decrypt_kat),
+        (Algorithm::Kdf, "hkdf-sha256-kat", hkdf_kat),
+        (Algorithm::Kdf, "root-key-kat", root_key_kat),
+        (Algorithm::Kdf, "blake3-kat", blake3_kat),
+        (Algorithm::Signature, "ed25519-kat", ed25519_kat),
+        (Algorithm::KeyAgreement, "x25519-kat", x25519_kat),
+        (Algorithm::Kem, "ml-kem-1024-pairwise", kem_pairwise),
+        (
+            Algorithm::Kem,
+            "ml-kem-1024-implicit-rejection",
+            kem_rejection,
+        ),
+    ];
+    let checks = checks
+        .into_iter()
+        .map(|(algorithm, name, check)| {
+            let result = check();
+            if let Err(e) = &result {
+                log::error!("Self-test {} failed: {}", name, e);
+            }
+            CheckOutcome {
+                algorithm,
+                name,
+                passed: result.is_ok(),
+                detail: result.err(),
+            }
+        })
+        .collect();
+    SelfTestReport { checks }
+}
+
+// ---------------------------------------------------------------------------
+// Vectors
+// ---------------------------------------------------------------------------
+
+fn unhex(s: &str) -> Result<Vec<u8>, String> {
+    hex::decode(s).map_err(|e| format!("bad vector: {}", e))
+}
+
+fn expect_eq(what: &str, actual: &[u8], expected: &[u8]) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{} mismatch: got {}", what, hex::encode(actual)))
+    }
+}
+
+/// Key of the XChaCha20-Poly1305 vectors: 0x80..=0x9f.
+fn aead_key() -> [u8; 32] {
+    std::array::from_fn(|i| 0x80 + i as u8)
+}
+
+const AEAD_NONCE: &str = "404142434445464748494a4b4c4d4e4f5051525354555657";
+
+/// draft-irtf-cfrg-xchacha-03, A.3.1
+fn aead_kat() -> Result<(), String> {
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+only one tip for the future, sunscreen would be it.";
+    let aad = unhex("50515253c0c1c2c3c4c5c6c7")?;
+    let expected = unhex(
+        "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb\
+         731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452\
+         2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9\
+         21f9664c97637da9768812f615c68b13b52ec0875924c1c7987947deafd8780a\
+         cf49",
+    )?;
+    let cipher = XChaCha20Poly1305::new(&aead_key().into());
+    let nonce_bytes = unhex(AEAD_NONCE)?;
+    let nonce = XNonce::from_slice(&nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| "encryption failed".to_string())?;
+    expect_eq("ciphertext", &ciphertext, &expected)?;
+    let decrypted = cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: &ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| "decryption failed".to_string())?;
+    expect_eq("plaintext", &decrypted, plaintext)
+}
+
+/// `decrypt_message` on a vector built with the same key and nonce.
+fn message_decrypt_kat() -> Result<(), String> {
+    let mut sealed = unhex(AEAD_NONCE)?;
+    sealed.extend(unhex(
+        "a2641a913794d40a897d03bc95c1f17541221e082830f99fbee09d234bf1809c\
+         4c31723e5938e50a4e",
+    )?);
+    let plaintext = decrypt_message(&sealed, &aead_key()).map_err(|e| e.to_string())?;
+    expect_eq("plaintext", &plaintext, b"Shield Protocol self-test")?;
+
+    // A flipped tag bit must be rejected
+    let last = sealed.len() - 1;
+    sealed[last] ^= 1;
+    match decrypt_message(&sealed, &aead_key()) {
+        Ok(_) => Err("forged ciphertext accepted".to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+/// RFC 5869, test case 1
+fn hkdf_kat() -> Result<(), String> {
+    let ikm = [0x0b; 22];
+    let salt: Vec<u8> = (0x00..=0x0c).collect();
+    let info: Vec<u8> = (0xf0..=0xf9).collect();
+    let mut okm = [0u8; 42];
+    Hkdf::<Sha256>::new(Some(&salt), &ikm)
+        .expand(&info, &mut okm)
+        .map_err(|e| e.to_string())?;
+    expect_eq(
+        "OKM",
+        &okm,
+        &unhex(
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+             34007208d5b887185865",
+        )?,
+    )
+}
+
+/// `derive_root_key` (HKDF-SHA256, no salt) over 0x00..=0x1f.
+fn root_key_kat() -> Result<(), String> {
+    let shared: Vec<u8> = (0x00..=0x1f).collect();
+    let root =
+        derive_root_key(&shared, b"ShieldMessenger-RootKey-v1").map_err(|e| e.to_string())?;
+    expect_eq(
+        "root key",
+        &root,
+        &unhex("925c258772b7eeabb9469e393b9ccba7387036db08ddb16f3f82a5fb996a49cf")?,
+    )
+}
+
+/// BLAKE3 of the empty input (official test vectors)
+fn blake3_kat() -> Result<(), String> {
+    expect_eq(
+        "digest",
+        blake3::hash(b"").as_bytes(),
+        &unhex("af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262")?,
+    )
+}
+
+/// RFC 8032, section 7.1, test 2
+fn ed25519_kat() -> Result<(), String> {
+    let secret = unhex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb")?;
+    let public = derive_public_key(&secret).map_err(|e| e.to_string())?;
+    expect_eq(
+        "public key",
+        &public,
+        &unhex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c")?,
+    )?;
+    let message = [0x72];
+    let signature = sign_data(&message, &secret).map_err(|e| e.to_string())?;
+    expect_eq(
+        "signature",
+        &signature,
+        &unhex(
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        )?,
+    )?;
+    if !matches!(verify_signature(&message, &signature, &public), Ok(true)) {
+        return Err("valid signature rejected".to_string());
+    }
+    if matches!(verify_signature(&[0x73], &signature, &public), Ok(true)) {
+        return Err("signature accepted for another message".to_string());
+    }
+    Ok(())
+}
+
+/// RFC 7748, section 6.1
+fn x25519_kat() -> Result<(), String> {
+    let alice = unhex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")?;
+    let bob_public = unhex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f")?;
+    let alice_public = key_exchange::derive_public_key(&alice).map_err(|e| e.to_string())?;
+    expect_eq(
+        "public key",
+        &alice_public,
+        &unhex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")?,
+    )?;
+    let shared =
+        key_exchange::derive_shared_secret(&alice, &bob_public).map_err(|e| e.to_string())?;
+    expect_eq(
+        "shared secret",
+        &shared,
+        &unhex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742")?,
+    )
+}
+
+/// Encapsulating to a fixed-seed key pair and decapsulating must agree.
+fn kem_pairwise() -> Result<(), String> {
+    let keypair = generate_hybrid_keypair_from_seed(&[0x5e; 32]).map_err(|e| e.to_string())?;
+    let sealed = hybrid_encapsulate(&keypair.x25519_public, &keypair.kyber_public)
+        .map_err(|e| e.to_string())?;
+    let opened = hybrid_decapsulate(
+        &sealed.x25519_ephemeral_public,
+        &sealed.kyber_ciphertext,
+        &keypair.x25519_secret,
+        &keypair.kyber_secret,
+    )
+    .map_err(|e| e.to_string())?;
+    if opened != sealed.shared_secret {
+        return Err("decapsulated secret differs".to_string());
+    }
+    Ok(())
+}
+
+/// A modified ciphertext must decapsulate to an unrelated secret.
+fn kem_rejection() -> Result<(), String> {
+    let keypair = generate_hybrid_keypair_from_seed(&[0x5e; 32]).map_err(|e| e.to_string())?;
+    let mut sealed = hybrid_encapsulate(&keypair.x25519_public, &keypair.kyber_public)
+        .map_err(|e| e.to_string())?;
+    sealed.kyber_ciphertext[0] ^= 1;
+    let opened = hybrid_decapsulate(
+        &sealed.x25519_ephemeral_public,
+        &sealed.kyber_ciphertext,
+        &keypair.x25519_secret,
+        &keypair.kyber_secret,
+    )
+    .map_err(|e| e.to_string())?;
+    if opened == sealed.shared_secret {
+        return Err("tampered ciphertext yielded the shared secret".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_passes() {
+        let report = selftest();
+        let failures: Vec<_> = report.failures().collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert!(report.clone().into_result().is_ok());
+        for algorithm in [
+            Algorithm::Aead,
+            Algorithm::Kdf,
+            Algorithm::Signature,
+            Algorithm::KeyAgreement,
+            Algorithm::Kem,
+        ] {
+            assert!(report.checks.iter().any(|c| c.algorithm == algorithm));
+        }
+    }
+
+    #[test]
+    fn test_failed_check_fails_report() {
+        let mut report = selftest();
+        report.checks[0].passed = false;
+        report.checks[0].detail = Some("ciphertext mismatch".to_string());
+        assert!(!report.passed());
+        assert_eq!(
+            report.into_result(),
+            Err(SelfTestError::Failed("xchacha20poly1305-kat".to_string()))
+        );
+    }
+}
@@ -0,0 +1,826 @@
+//! Archive compaction for old conversation history.
+//!
+//! Long-lived conversations make the live database large, and every message
+//! in it is exposed whenever the database is unlocked. The archive moves old
+//! messages out of the live tables into *segments*. A segment is a run of one
+//! contact's messages, DEFLATE-compressed, padded and sealed with
+//! XChaCha20-Poly1305 under its own key, derived from the archive master key
+//! and the segment id.
+//!
+//! A sealed segment is a self-contained blob. The app can *detach* it, i.e.
+//! export it to external storage and drop the local copy, and *attach* it
+//! again later. Nothing in a detached blob beyond its id is readable without
+//! the master key.
+//!
+//! For every archived message an [`IndexStub`] stays in the live store. Stubs
+//! keep the fields that other rows point at (message id, reply reference,
+//! timestamp) and keyed search tokens, so search and reply-references still
+//! resolve while the segment is detached. The UI can then offer to attach the
+//! segment instead of showing a dangling reference.
+//!
+//! Persistence is app-provided through [`ArchiveStore`] (same model as
+//! [`IntentStore`](super::IntentStore)); [`MemoryArchiveStore`] is provided
+//! for tests.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::StorageError;
+use crate::protocol::contact_id::ContactId;
+use crate::rng::SecureRng;
+
+/// Current segment blob format.
+pub const SEGMENT_VERSION: u8 = 1;
+
+const SEGMENT_ID_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = 1 + SEGMENT_ID_LEN;
+
+/// Sealed plaintexts are padded to a multiple of this, so a detached blob
+/// only reveals its size to this granularity.
+const SEGMENT_PADDING: usize = 1024;
+
+/// Search tokens kept per message; longer messages are indexed by their
+/// first distinct words only.
+pub const MAX_TOKENS_PER_MESSAGE: usize = 64;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Archive store error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Archive record encoding error: {0}")]
+    Encoding(String),
+
+    #[error("Malformed archive segment")]
+    Malformed,
+
+    #[error("Unsupported archive segment version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unknown archive segment {0}")]
+    UnknownSegment(SegmentId),
+
+    #[error("Archive segment {0} is detached")]
+    Detached(SegmentId),
+
+    #[error("Archive segment failed to decrypt (wrong key or corrupted)")]
+    DecryptionFailed,
+
+    #[error("Archive segment does not decompress")]
+    Compression,
+
+    #[error("Archive segment {0} does not match its index")]
+    IndexMismatch(SegmentId),
+}
+
+// ---------------------------------------------------------------------------
+// Keys and ids
+// ---------------------------------------------------------------------------
+
+/// Random identifier of a segment. The only cleartext in a sealed blob.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentId(pub [u8; SEGMENT_ID_LEN]);
+
+impl SegmentId {
+    pub fn generate(rng: &mut impl SecureRng) -> Self {
+        let mut id = [0u8; SEGMENT_ID_LEN];
+        rng.fill_bytes(&mut id);
+        Self(id)
+    }
+}
+
+impl fmt::Display for SegmentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for SegmentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SegmentId({})", self)
+    }
+}
+
+/// Keyed hash of one normalized search word.
+pub type SearchToken = [u8; 16];
+
+/// Archive master key. Held by the app next to the database key; every
+/// segment key and search token is derived from it.
+pub struct ArchiveKey([u8; 32]);
+
+impl ArchiveKey {
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    pub fn generate(rng: &mut impl SecureRng) -> Self {
+        let mut key = [0u8; 32];
+        rng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    fn segment_key(&self, id: &SegmentId) -> [u8; 32] {
+        let mut material = [0u8; 32 + SEGMENT_ID_LEN];
+        material[..32].copy_from_slice(&self.0);
+        material[32..].copy_from_slice(&id.0);
+        let key = blake3::derive_key("ShieldMessenger-Archive-Segment-v1", &material);
+        material.zeroize();
+        key
+    }
+
+    /// Token for one normalized word. Keyed, so the stub index does not
+    /// reveal which words a conversation contains.
+    fn search_token(&self, word: &str) -> SearchToken {
+        let mut search_key = blake3::derive_key("ShieldMessenger-Archive-Search-v1", &self.0);
+        let hash = blake3::keyed_hash(&search_key, word.as_bytes());
+        search_key.zeroize();
+        let mut token = [0u8; 16];
+        token.copy_from_slice(&hash.as_bytes()[..16]);
+        token
+    }
+
+    /// Tokens for every searchable word in `text`: lowercased alphanumeric
+    /// runs of at least two characters, deduplicated.
+    pub fn tokenize(&self, text: &str) -> Vec<SearchToken> {
+        let mut seen = BTreeSet::new();
+        let mut tokens = Vec::new();
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= 2)
+        {
+            if tokens.len() == MAX_TOKENS_PER_MESSAGE {
+                break;
+            }
+            let word = word.to_lowercase();
+            if seen.insert(word.clone()) {
+                tokens.push(self.search_token(&word));
+            }
+        }
+        tokens
+    }
+}
+
+impl Drop for ArchiveKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for ArchiveKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ArchiveKey(..)")
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Records
+// ---------------------------------------------------------------------------
+
+/// A message as moved into (and read back from) a segment.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedMessage {
+    pub message_id: String,
+    /// Unix timestamp (seconds).
+    pub timestamp: i64,
+    pub is_outgoing: bool,
+    pub content: String,
+    /// Id of the message this one replies to, if any.
+    pub reply_to: Option<String>,
+}
+
+/// What stays in the live store for an archived message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IndexStub {
+    pub message_id: String,
+    pub contact_id: ContactId,
+    pub segment_id: SegmentId,
+    pub timestamp: i64,
+    pub is_outgoing: bool,
+    pub reply_to: Option<String>,
+    pub search_tokens: Vec<SearchToken>,
+}
+
+impl IndexStub {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArchiveError> {
+        bincode::serialize(self).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        bincode::deserialize(bytes).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+}
+
+/// Live-store record of a segment, kept whether or not it is attached.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SegmentMeta {
+    pub segment_id: SegmentId,
+    pub contact_id: ContactId,
+    pub first_timestamp: i64,
+    pub last_timestamp: i64,
+    pub message_count: u32,
+    /// `false` once the blob has been exported and dropped locally.
+    pub attached: bool,
+}
+
+impl SegmentMeta {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArchiveError> {
+        bincode::serialize(self).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        bincode::deserialize(bytes).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+}
+
+/// Sealed contents of a segment.
+#[derive(Serialize, Deserialize)]
+struct SegmentBody {
+    contact_id: ContactId,
+    messages: Vec<ArchivedMessage>,
+}
+
+/// Result of looking up a message id through the archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolved {
+    /// The segment is attached; here is the message.
+    Available(ArchivedMessage),
+    /// The segment is detached; attach `stub.segment_id` to read it.
+    Detached(IndexStub),
+}
+
+// ---------------------------------------------------------------------------
+// Segment sealing
+// ---------------------------------------------------------------------------
+
+/// Blob layout: `[version u8][segment id 16][nonce 24][ciphertext]`, with
+/// the first 17 bytes as associated data.
+fn seal_segment(
+    key: &ArchiveKey,
+    id: &SegmentId,
+    body: &SegmentBody,
+    rng: &mut impl SecureRng,
+) -> Result<Vec<u8>, ArchiveError> {
+    let encoded = bincode::serialize(body).map_err(|e| ArchiveError::Encoding(e.to_string()))?;
+    let compressed = miniz_oxide::deflate::compress_to_vec(&encoded, 6);
+
+    let mut plaintext = Vec::with_capacity(4 + compressed.len() + SEGMENT_PADDING);
+    plaintext.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    plaintext.extend_from_slice(&compressed);
+    let padded_len = plaintext.len().div_ceil(SEGMENT_PADDING) * SEGMENT_PADDING;
+    plaintext.resize(padded_len, 0);
+
+    let mut header = [0u8; HEADER_LEN];
+    header[0] = SEGMENT_VERSION;
+    header[1..].copy_from_slice(&id.0);
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+
+    let mut segment_key = key.segment_key(id);
+    let cipher = XChaCha20Poly1305::new_from_slice(&segment_key)
+        .map_err(|_| ArchiveError::DecryptionFailed)?;
+    segment_key.zeroize();
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| ArchiveError::DecryptionFailed)?;
+    plaintext.zeroize();
+
+    let mut blob = Vec::with_capacity(HEADER_LEN + NONCE_LEN + ciphertext.len());
+    blob.extend_from_slice(&header);
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&ciphertext);
+    Ok(blob)
+}
+
+/// Segment id of a sealed blob, without decrypting it.
+pub fn segment_id_of(blob: &[u8]) -> Result<SegmentId, ArchiveError> {
+    if blob.len() < HEADER_LEN + NONCE_LEN {
+        return Err(ArchiveError::Malformed);
+    }
+    if blob[0] != SEGMENT_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(blob[0]));
+    }
+    let mut id = [0u8; SEGMENT_ID_LEN];
+    id.copy_from_slice(&blob[1..HEADER_LEN]);
+    Ok(SegmentId(id))
+}
+
+fn open_segment_blob(
+    key: &ArchiveKey,
+    blob: &[u8],
+    max_bytes: usize,
+) -> Result<(SegmentId, SegmentBody), ArchiveError> {
+    let id = segment_id_of(blob)?;
+    let (header, rest) = blob.split_at(HEADER_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let mut segment_key = key.segment_key(&id);
+    let cipher = XChaCha20Poly1305::new_from_slice(&segment_key)
+        .map_err(|_| ArchiveError::DecryptionFailed)?;
+    segment_key.zeroize();
+    let mut plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| ArchiveError::DecryptionFailed)?;
+
+    let result = (|| {
+        if plaintext.len() < 4 {
+            return Err(ArchiveError::Malformed);
+        }
+        let len =
+            u32::from_be_bytes([plaintext[0], plaintext[1], plaintext[2], plaintext[3]]) as usize;
+        let compressed = plaintext.get(4..4 + len).ok_or(ArchiveError::Malformed)?;
+        let encoded = miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, max_bytes)
+            .map_err(|_| ArchiveError::Compression)?;
+        bincode::deserialize::<SegmentBody>(&encoded)
+            .map_err(|e| ArchiveError::Encoding(e.to_string()))
+    })();
+    plaintext.zeroize();
+    Ok((id, result?))
+}
+
+// ---------------------------------------------------------------------------
+// Persistence contract (app implements)
+// ---------------------------------------------------------------------------
+
+/// Live-database side of the archive: segment metadata, locally held blobs
+/// and index stubs.
+///
+/// Schema hint for SQLCipher:
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS archive_segments (
+///   segment_id BLOB PRIMARY KEY,
+///   contact_id TEXT NOT NULL,
+///   meta       BLOB NOT NULL,  -- SegmentMeta::to_bytes()
+///   blob       BLOB            -- NULL while detached
+/// );
+/// CREATE TABLE IF NOT EXISTS archive_stubs (
+///   message_id TEXT PRIMARY KEY,
+///   segment_id BLOB NOT NULL,
+///   stub       BLOB NOT NULL   -- IndexStub::to_bytes()
+/// );
+/// CREATE TABLE IF NOT EXISTS archive_tokens (
+///   token      BLOB NOT NULL,
+///   message_id TEXT NOT NULL
+/// );
+/// CREATE INDEX IF NOT EXISTS archive_tokens_token ON archive_tokens(token);
+/// ```
+pub trait ArchiveStore {
+    fn save_segment_meta(&mut self, meta: &SegmentMeta) -> super::Result<()>;
+    fn load_segment_meta(&self, id: &SegmentId) -> super::Result<Option<SegmentMeta>>;
+    /// All segments of a contact, in any order.
+    fn list_segments(&self, contact_id: &ContactId) -> super::Result<Vec<SegmentMeta>>;
+
+    fn save_segment_blob(&mut self, id: &SegmentId, blob: &[u8]) -> super::Result<()>;
+    /// `None` while the segment is detached.
+    fn load_segment_blob(&self, id: &SegmentId) -> super::Result<Option<Vec<u8>>>;
+    fn delete_segment_blob(&mut self, id: &SegmentId) -> super::Result<()>;
+
+    /// Insert or replace stubs, keyed by message id.
+    fn save_stubs(&mut self, stubs: &[IndexStub]) -> super::Result<()>;
+    fn load_stub(&self, message_id: &str) -> super::Result<Option<IndexStub>>;
+    /// Stubs whose `search_tokens` contain `token`, in any order.
+    fn stubs_with_token(&self, token: &SearchToken) -> super::Result<Vec<IndexStub>>;
+}
+
+/// Segments, blobs and index stubs in `BTreeMap`s; an archive kept here is
+/// gone when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryArchiveStore {
+    segments: BTreeMap<SegmentId, SegmentMeta>,
+    blobs: BTreeMap<SegmentId, Vec<u8>>,
+    stubs: BTreeMap<String, IndexStub>,
+}
+
+impl MemoryArchiveStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ArchiveStore for MemoryArchiveStore {
+    fn save_segment_meta(&mut self, meta: &SegmentMeta) -> super::Result<()> {
+        self.segments.insert(meta.segment_id, meta.clone());
+        Ok(())
+    }
+
+    fn load_segment_meta(&self, id: &SegmentId) -> super::Result<Option<SegmentMeta>> {
+        Ok(self.segments.get(id).cloned())
+    }
+
+    fn list_segments(&self, contact_id: &ContactId) -> super::Result<Vec<SegmentMeta>> {
+        Ok(self
+            .segments
+            .values()
+            .filter(|m| &m.contact_id == contact_id)
+            .cloned()
+            .collect())
+    }
+
+    fn save_segment_blob(&mut self, id: &SegmentId, blob: &[u8]) -> super::Result<()> {
+        self.blobs.insert(*id, blob.to_vec());
+        Ok(())
+    }
+
+    fn load_segment_blob(&self, id: &SegmentId) -> super::Result<Option<Vec<u8>>> {
+        Ok(self.blobs.get(id).cloned())
+    }
+
+    fn delete_segment_blob(&mut self, id: &SegmentId) -> super::Result<()> {
+        self.blobs.remove(id);
+        Ok(())
+    }
+
+    fn save_stubs(&mut self, stubs: &[IndexStub]) -> super::Result<()> {
+        for stub in stubs {
+            self.stubs.insert(stub.message_id.clone(), stub.clone());
+        }
+        Ok(())
+    }
+
+    fn load_stub(&self, message_id: &str) -> super::Result<Option<IndexStub>> {
+        Ok(self.stubs.get(message_id).cloned())
+    }
+
+    fn stubs_with_token(&self, token: &SearchToken) -> super::Result<Vec<IndexStub>> {
+        Ok(self
+            .stubs
+            .values()
+            .filter(|s| s.search_tokens.contains(token))
+            .cloned()
+            .collect())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Archive
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArchiveConfig {
+    /// Messages per segment; `compact` splits larger batches.
+    pub max_segment_messages: usize,
+    /// Upper bound on a segment's decompressed size, checked on open.
+    pub max_segment_bytes: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            max_segment_messages: 1000,
+            max_segment_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Archive compaction over an app-provided store.
+pub struct Archive<S: ArchiveStore> {
+    store: S,
+    key: ArchiveKey,
+    config: ArchiveConfig,
+}
+
+impl<S: ArchiveStore> Archive<S> {
+    pub fn new(store: S, key: ArchiveKey, config: ArchiveConfig) -> Self {
+        Self { store, key, config }
+    }
+
+    /// Move a contact's `messages` into new segments and index them.
+    ///
+    /// Returns the new segments' metadata. The app deletes the archived rows
+    /// from the live tables only after this returns `Ok` (ideally in the same
+    /// transaction as the store writes), so a crash never loses a message.
+    pub fn compact(
+        &mut self,
+        contact_id: &ContactId,
+        mut messages: Vec<ArchivedMessage>,
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<SegmentMeta>, ArchiveError> {
+        messages.sort_by_key(|m| m.timestamp);
+        let mut metas = Vec::new();
+        for chunk in messages.chunks(self.config.max_segment_messages.max(1)) {
+            let segment_id = SegmentId::generate(rng);
+            let body = SegmentBody {
+                contact_id: *contact_id,
+                messages: chunk.to_vec(),
+            };
+            let blob = seal_segment(&self.key, &segment_id, &body, rng)?;
+            let meta = self.index(segment_id, &body)?;
+            self.store.save_segment_blob(&segment_id, &blob)?;
+            self.store.save_segment_meta(&meta)?;
+            metas.push(meta);
+        }
+        if !metas.is_empty() {
+            log::info!(
+                "Archive: compacted {} messages for {} into {} segments",
+                messages.len(),
+                contact_id,
+                metas.len()
+            );
+        }
+        Ok(metas)
+    }
+
+    /// Write the stubs for `body` and return the matching (attached) meta.
+    fn index(
+        &mut self,
+        segment_id: SegmentId,
+        body: &SegmentBody,
+    ) -> Result<SegmentMeta, ArchiveError> {
+        let stubs: Vec<IndexStub> = body
+            .messages
+            .iter()
+            .map(|m| IndexStub {
+                message_id: m.message_id.clone(),
+                contact_id: body.contact_id,
+                segment_id,
+                timestamp: m.timestamp,
+                is_outgoing: m.is_outgoing,
+                reply_to: m.reply_to.clone(),
+                search_tokens: self.key.tokenize(&m.content),
+            })
+            .collect();
+        self.store.save_stubs(&stubs)?;
+        Ok(SegmentMeta {
+            segment_id,
+            contact_id: body.contact_id,
+            first_timestamp: body.messages.first().map_or(0, |m| m.timestamp),
+            last_timestamp: body.messages.last().map_or(0, |m| m.timestamp),
+            message_count: body.messages.len() as u32,
+            attached: true,
+        })
+    }
+
+    /// The sealed blob of an attached segment, for export.
+    pub fn export_segment(&self, id: &SegmentId) -> Result<Vec<u8>, ArchiveError> {
+        self.load_meta(id)?;
+        self.store
+            .load_segment_blob(id)?
+            .ok_or(ArchiveError::Detached(*id))
+    }
+
+    /// Drop the local copy of a segment, keeping its meta and stubs.
+    ///
+    /// Call only once the blob from [`export_segment`](Self::export_segment)
+    /// is safely stored elsewhere.
+    pub fn detach(&mut self, id: &SegmentId) -> Result<(), ArchiveError> {
+        let mut meta = self.load_meta(id)?;
+        meta.attached = false;
+        self.store.save_segment_meta(&meta)?;
+        self.store.delete_segment_blob(id)?;
+        Ok(())
+    }
+
+    /// Re-attach an exported blob.
+    ///
+    /// The blob must decrypt under this archive's key. A segment this store
+    /// has never seen (e.g. restored onto a fresh install) is re-indexed from
+    /// its contents; a known one must match its recorded meta.
+    pub fn attach(&mut self, blob: &[u8]) -> Result<SegmentMeta, ArchiveError> {
+        let (id, body) = open_segment_blob(&self.key, blob, self.config.max_segment_bytes)?;
+        let meta = match self.store.load_segment_meta(&id)? {
+            Some(mut meta) => {
+                if meta.contact_id != body.contact_id
+                    || meta.message_count as usize != body.messages.len()
+                {
+                    return Err(ArchiveError::IndexMismatch(id));
+                }
+                meta.attached = true;
+                meta
+            }
+            None => self.index(id, &body)?,
+        };
+        self.store.save_segment_blob(&id, blob)?;
+        self.store.save_segment_meta(&meta)?;
+        Ok(meta)
+    }
+
+    /// Messages of an attached segment, oldest first.
+    pub fn open_segment(&self, id: &SegmentId) -> Result<Vec<ArchivedMessage>, ArchiveError> {
+        let blob = self.export_segment(id)?;
+        let (opened, body) = open_segment_blob(&self.key, &blob, self.config.max_segment_bytes)?;
+        if opened != *id {
+            return Err(ArchiveError::IndexMismatch(*id));
+        }
+        Ok(body.messages)
+    }
+
+    /// Look up an archived message, e.g. the target of a reply reference.
+    /// `None` if the id was never archived.
+    pub fn resolve(&self, message_id: &str) -> Result<Option<Resolved>, ArchiveError> {
+        let Some(stub) = self.store.load_stub(message_id)? else {
+            return Ok(None);
+        };
+        if self.store.load_segment_blob(&stub.segment_id)?.is_none() {
+            return Ok(Some(Resolved::Detached(stub)));
+        }
+        let message = self
+            .open_segment(&stub.segment_id)?
+            .into_iter()
+            .find(|m| m.message_id == message_id)
+            .ok_or(ArchiveError::IndexMismatch(stub.segment_id))?;
+        Ok(Some(Resolved::Available(message)))
+    }
+
+    /// Archived messages containing every word of `query`, oldest first.
+    /// Matches whole words only; works whether or not segments are attached.
+    pub fn search(&self, query: &str) -> Result<Vec<IndexStub>, ArchiveError> {
+        let tokens = self.key.tokenize(query);
+        let Some((first, rest)) = tokens.split_first() else {
+            return Ok(Vec::new());
+        };
+        let mut hits: Vec<IndexStub> = self
+            .store
+            .stubs_with_token(first)?
+            .into_iter()
+            .filter(|s| rest.iter().all(|t| s.search_tokens.contains(t)))
+            .collect();
+        hits.sort_by(|a, b| (a.timestamp, &a.message_id).cmp(&(b.timestamp, &b.message_id)));
+        Ok(hits)
+    }
+
+    /// All segments of a contact, oldest first.
+    pub fn segments(&self, contact_id: &ContactId) -> Result<Vec<SegmentMeta>, ArchiveError> {
+        let mut segments = self.store.list_segments(contact_id)?;
+        segments.sort_by_key(|m| (m.first_timestamp, m.segment_id));
+        Ok(segments)
+    }
+
+    fn load_meta(&self, id: &SegmentId) -> Result<SegmentMeta, ArchiveError> {
+        self.store
+            .load_segment_meta(id)?
+            .ok_or(ArchiveError::UnknownSegment(*id))
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    fn message(id: &str, timestamp: i64, content: &str, reply_to: Option<&str>) -> ArchivedMessage {
+        ArchivedMessage {
+            message_id: id.into(),
+            timestamp,
+            is_outgoing: timestamp % 2 == 0,
+            content: content.into(),
+            reply_to: reply_to.map(Into::into),
+        }
+    }
+
+    fn archive() -> Archive<MemoryArchiveStore> {
+        Archive::new(
+            MemoryArchiveStore::new(),
+            ArchiveKey::from_bytes([9; 32]),
+            ArchiveConfig {
+                max_segment_messages: 2,
+                ..ArchiveConfig::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_compact_splits_and_reopens_segments() {
+        let mut rng = seeded(1);
+        let mut archive = archive();
+        let messages = vec![
+            message("m3", 30, "see you at the station", Some("m1")),
+            message("m1", 10, "Lunch tomorrow?", None),
+            message("m2", 20, "sure, lunch works", None),
+        ];
+        let metas = archive
+            .compact(&test_contact(1), messages, &mut rng)
+            .unwrap();
+        assert_eq!(metas.len(), 2);
+        assert_eq!(
+            (metas[0].first_timestamp, metas[0].last_timestamp),
+            (10, 20)
+        );
+        assert_eq!(metas[1].message_count, 1);
+        assert_eq!(archive.segments(&test_contact(1)).unwrap(), metas);
+
+        let opened = archive.open_segment(&metas[0].segment_id).unwrap();
+        assert_eq!(opened[0].content, "Lunch tomorrow?");
+        assert_eq!(opened[1].message_id, "m2");
+
+        let hits: Vec<String> = archive
+            .search("LUNCH")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.message_id)
+            .collect();
+        assert_eq!(hits, vec!["m1", "m2"]);
+        assert_eq!(archive.search("lunch works").unwrap().len(), 1);
+        assert!(archive.search("dinner").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_detached_segment_keeps_stubs_and_reattaches() {
+        let mut rng = seeded(2);
+        let mut archive = archive();
+        let metas = archive
+            .compact(
+                &test_contact(2),
+                vec![
+                    message("m1", 10, "the door code is 4471", None),
+                    message("m2", 20, "thanks", Some("m1")),
+                ],
+                &mut rng,
+            )
+            .unwrap();
+        let id = metas[0].segment_id;
+        let blob = archive.export_segment(&id).unwrap();
+        assert_eq!(segment_id_of(&blob).unwrap(), id);
+        assert!(!blob.windows(4).any(|w| w == b"door"));
+
+        archive.detach(&id).unwrap();
+        assert!(matches!(
+            archive.open_segment(&id),
+            Err(ArchiveError::Detached(_))
+        ));
+        // Reply reference and search still resolve to the stub
+        match archive.resolve("m1").unwrap() {
+            Some(Resolved::Detached(stub)) => assert_eq!(stub.segment_id, id),
+            other => panic!("expected detached stub, got {:?}", other),
+        }
+        assert_eq!(archive.search("door code").unwrap()[0].message_id, "m1");
+        assert_eq!(archive.resolve("m9").unwrap(), None);
+
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            archive.attach(&tampered),
+            Err(ArchiveError::DecryptionFailed)
+        ));
+
+        let meta = archive.attach(&blob).unwrap();
+        assert!(meta.attached);
+        match archive.resolve("m1").unwrap() {
+            Some(Resolved::Available(m)) => assert_eq!(m.content, "the door code is 4471"),
+            other => panic!("expected message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_attach_on_fresh_store_reindexes() {
+        let mut rng = seeded(3);
+        let mut archive = archive();
+        let metas = archive
+            .compact(
+                &test_contact(3),
+                vec![message("m1", 5, "hello there", None)],
+                &mut rng,
+            )
+            .unwrap();
+        let blob = archive.export_segment(&metas[0].segment_id).unwrap();
+
+        let mut restored = Archive::new(
+            MemoryArchiveStore::new(),
+            ArchiveKey::from_bytes([9; 32]),
+            ArchiveConfig::default(),
+        );
+        assert_eq!(restored.attach(&blob).unwrap(), metas[0]);
+        assert_eq!(restored.search("hello").unwrap()[0].message_id, "m1");
+
+        let mut stranger = Archive::new(
+            MemoryArchiveStore::new(),
+            ArchiveKey::from_bytes([8; 32]),
+            ArchiveConfig::default(),
+        );
+        assert!(matches!(
+            stranger.attach(&blob),
+            Err(ArchiveError::DecryptionFailed)
+        ));
+    }
+}
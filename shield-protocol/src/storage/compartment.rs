@@ -0,0 +1,412 @@
+//! Per-conversation storage keys (compartmentalization).
+//!
+//! With a single database key, recovering that key from one place (a memory
+//! dump while a chat is open, a key cached by the UI) opens every
+//! conversation. Here the database master key only derives keys. Each
+//! contact or group conversation is a [`Compartment`] whose rows the app
+//! encrypts under its own [`CompartmentKey`]:
+//!
+//! ```text
+//! key = BLAKE3-derive_key("ShieldMessenger-Storage-Compartment-v1",
+//!                         master ‖ compartment id ‖ salt)
+//! ```
+//!
+//! - A compartment key reveals neither the master key nor any other
+//!   compartment's key, so the app only has to hold keys for conversations
+//!   that are open.
+//! - The salt is random per compartment and kept in the live database.
+//!   Deleting it ([`CompartmentKeys::erase`]) makes the conversation's rows
+//!   unreadable even to someone holding the master key, which gives a
+//!   cryptographic erase for a single deleted chat.
+//!
+//! Databases that still encrypt everything under one key move over with
+//! [`CompartmentKeys::migrate_record`], one row at a time. It can be resumed
+//! at any point, because rows already under their compartment key are
+//! recognized and left alone.
+//!
+//! Salt persistence is app-provided through [`CompartmentStore`] (same model
+//! as [`IntentStore`](super::IntentStore)); [`MemoryCompartmentStore`] is
+//! provided for tests.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::archive::ArchiveKey;
+use super::StorageError;
+use crate::crypto::encryption::{decrypt_message, encrypt_message_with_rng, EncryptionError};
+use crate::protocol::contact_id::ContactId;
+use crate::rng::SecureRng;
+
+const SALT_LEN: usize = 16;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum CompartmentError {
+    #[error("Compartment store error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Compartment encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    #[error("No key for {0}: never created or erased")]
+    NoKey(Compartment),
+
+    #[error("Record decrypts under neither the legacy nor the compartment key")]
+    WrongKey,
+}
+
+// ---------------------------------------------------------------------------
+// Compartments and keys
+// ---------------------------------------------------------------------------
+
+/// A unit of storage with its own key.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Compartment {
+    /// Data that belongs to no single conversation (settings, contact list).
+    Shared,
+    /// A 1:1 conversation.
+    Contact(ContactId),
+    /// A group conversation, by group id.
+    Group([u8; 32]),
+}
+
+impl Compartment {
+    /// Stable, unambiguous byte form: a tag byte followed by the id.
+    pub fn id_bytes(&self) -> Vec<u8> {
+        match self {
+            Compartment::Shared => vec![0],
+            Compartment::Contact(id) => {
+                let mut out = vec![1];
+                out.extend_from_slice(id.as_bytes());
+                out
+            }
+            Compartment::Group(id) => {
+                let mut out = vec![2];
+                out.extend_from_slice(id);
+                out
+            }
+        }
+    }
+}
+
+impl fmt::Display for Compartment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compartment::Shared => f.write_str("shared storage"),
+            Compartment::Contact(id) => write!(f, "conversation {}", id),
+            Compartment::Group(id) => write!(f, "group {}", hex::encode(&id[..8])),
+        }
+    }
+}
+
+/// Database master key. Only ever used to derive other keys.
+pub struct StorageMasterKey([u8; 32]);
+
+impl StorageMasterKey {
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Key of `compartment` under `salt`.
+    fn derive(&self, compartment: &Compartment, salt: &[u8; SALT_LEN]) -> CompartmentKey {
+        let id = compartment.id_bytes();
+        let mut material = Vec::with_capacity(32 + id.len() + SALT_LEN);
+        material.extend_from_slice(&self.0);
+        material.extend_from_slice(&id);
+        material.extend_from_slice(salt);
+        let key = blake3::derive_key("ShieldMessenger-Storage-Compartment-v1", &material);
+        material.zeroize();
+        CompartmentKey(key)
+    }
+
+    /// Master key for the message [`archive`](super::archive), so apps need
+    /// not keep a second secret.
+    pub fn archive_key(&self) -> ArchiveKey {
+        ArchiveKey::from_bytes(blake3::derive_key(
+            "ShieldMessenger-Storage-Archive-v1",
+            &self.0,
+        ))
+    }
+}
+
+impl Drop for StorageMasterKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for StorageMasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StorageMasterKey(..)")
+    }
+}
+
+/// Encryption key of one compartment. Zeroized on drop.
+pub struct CompartmentKey([u8; 32]);
+
+impl CompartmentKey {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Encrypt a row (XChaCha20-Poly1305, same format as `encrypt_message`).
+    pub fn encrypt(
+        &self,
+        plaintext: &[u8],
+        rng: &mut impl SecureRng,
+    ) -> Result<Vec<u8>, CompartmentError> {
+        Ok(encrypt_message_with_rng(plaintext, &self.0, rng)?)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CompartmentError> {
+        Ok(decrypt_message(ciphertext, &self.0)?)
+    }
+}
+
+impl Drop for CompartmentKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for CompartmentKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CompartmentKey(..)")
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Persistence contract (app implements)
+// ---------------------------------------------------------------------------
+
+/// Durable store of compartment salts.
+///
+/// `delete_salt` is what erases a compartment: the app SHOULD make sure the
+/// old value does not survive in a journal or free pages (e.g. SQLCipher with
+/// `secure_delete`).
+///
+/// Schema hint for SQLCipher:
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS storage_compartments (
+///   compartment BLOB PRIMARY KEY,  -- Compartment::id_bytes()
+///   salt        BLOB NOT NULL
+/// );
+/// ```
+pub trait CompartmentStore {
+    fn load_salt(&self, compartment: &Compartment) -> super::Result<Option<[u8; SALT_LEN]>>;
+    fn save_salt(&mut self, compartment: &Compartment, salt: &[u8; SALT_LEN]) -> super::Result<()>;
+    fn delete_salt(&mut self, compartment: &Compartment) -> super::Result<()>;
+}
+
+/// Holds compartment salts in memory; keys derived from them cannot be
+/// re-derived once the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryCompartmentStore {
+    salts: BTreeMap<Compartment, [u8; SALT_LEN]>,
+}
+
+impl MemoryCompartmentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CompartmentStore for MemoryCompartmentStore {
+    fn load_salt(&self, compartment: &Compartment) -> super::Result<Option<[u8; SALT_LEN]>> {
+        Ok(self.salts.get(compartment).copied())
+    }
+
+    fn save_salt(&mut self, compartment: &Compartment, salt: &[u8; SALT_LEN]) -> super::Result<()> {
+        self.salts.insert(*compartment, *salt);
+        Ok(())
+    }
+
+    fn delete_salt(&mut self, compartment: &Compartment) -> super::Result<()> {
+        self.salts.remove(compartment);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Key manager
+// ---------------------------------------------------------------------------
+
+/// Outcome of [`CompartmentKeys::migrate_record`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Migrated {
+    /// Re-encrypted under the compartment key; store these bytes.
+    Rewritten(Vec<u8>),
+    /// Already under the compartment key; leave the row as it is.
+    AlreadyMigrated,
+}
+
+/// Compartment keys derived from a master key and app-stored salts.
+pub struct CompartmentKeys<S: CompartmentStore> {
+    store: S,
+    master: StorageMasterKey,
+}
+
+impl<S: CompartmentStore> CompartmentKeys<S> {
+    pub fn new(store: S, master: StorageMasterKey) -> Self {
+        Self { store, master }
+    }
+
+    /// Key for writing to `compartment`, creating its salt on first use.
+    pub fn key_for(
+        &mut self,
+        compartment: &Compartment,
+        rng: &mut impl SecureRng,
+    ) -> Result<CompartmentKey, CompartmentError> {
+        if let Some(salt) = self.store.load_salt(compartment)? {
+            return Ok(self.master.derive(compartment, &salt));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        self.store.save_salt(compartment, &salt)?;
+        Ok(self.master.derive(compartment, &salt))
+    }
+
+    /// Key for reading `compartment`. Unlike [`key_for`](Self::key_for) this
+    /// never creates a key, so an erased conversation stays unreadable.
+    pub fn existing_key(
+        &self,
+        compartment: &Compartment,
+    ) -> Result<CompartmentKey, CompartmentError> {
+        let salt = self
+            .store
+            .load_salt(compartment)?
+            .ok_or(CompartmentError::NoKey(*compartment))?;
+        Ok(self.master.derive(compartment, &salt))
+    }
+
+    /// Cryptographically erase `compartment`: its rows can no longer be
+    /// decrypted, even with the master key. The app still deletes the rows.
+    pub fn erase(&mut self, compartment: &Compartment) -> Result<(), CompartmentError> {
+        self.store.delete_salt(compartment)?;
+        log::info!("Storage: erased key for {}", compartment);
+        Ok(())
+    }
+
+    /// Move one row of a single-key database into its compartment.
+    ///
+    /// `ciphertext` is the stored row, in `encrypt_message` format, under
+    /// either `legacy_key` or (if a previous migration run already rewrote
+    /// it) the compartment key. Write back [`Migrated::Rewritten`] rows in
+    /// the same transaction as any other change to them; once every row is
+    /// migrated the legacy key can be destroyed.
+    pub fn migrate_record(
+        &mut self,
+        legacy_key: &[u8; 32],
+        compartment: &Compartment,
+        ciphertext: &[u8],
+        rng: &mut impl SecureRng,
+    ) -> Result<Migrated, CompartmentError> {
+        let key = self.key_for(compartment, rng)?;
+        if key.decrypt(ciphertext).is_ok() {
+            return Ok(Migrated::AlreadyMigrated);
+        }
+        let mut plaintext =
+            decrypt_message(ciphertext, legacy_key).map_err(|_| CompartmentError::WrongKey)?;
+        let rewritten = key.encrypt(&plaintext, rng);
+        plaintext.zeroize();
+        Ok(Migrated::Rewritten(rewritten?))
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption::encrypt_message;
+    use crate::protocol::contact_id::test_contact;
+    use crate::rng::seeded;
+
+    fn contact(seed: u8) -> Compartment {
+        Compartment::Contact(test_contact(seed))
+    }
+
+    fn keys() -> CompartmentKeys<MemoryCompartmentStore> {
+        CompartmentKeys::new(
+            MemoryCompartmentStore::new(),
+            StorageMasterKey::from_bytes([5; 32]),
+        )
+    }
+
+    #[test]
+    fn test_compartments_are_isolated_and_erasable() {
+        let mut rng = seeded(1);
+        let mut keys = keys();
+        let alice = keys.key_for(&contact(1), &mut rng).unwrap();
+        let bob = keys.key_for(&contact(2), &mut rng).unwrap();
+        let group = keys
+            .key_for(&Compartment::Group([1; 32]), &mut rng)
+            .unwrap();
+        assert_ne!(alice.as_bytes(), bob.as_bytes());
+        assert_ne!(alice.as_bytes(), group.as_bytes());
+        // Stable across calls
+        assert_eq!(
+            keys.existing_key(&contact(1)).unwrap().as_bytes(),
+            alice.as_bytes()
+        );
+
+        let row = alice.encrypt(b"see you at 6", &mut rng).unwrap();
+        assert_eq!(alice.decrypt(&row).unwrap(), b"see you at 6");
+        assert!(bob.decrypt(&row).is_err());
+
+        keys.erase(&contact(1)).unwrap();
+        assert!(matches!(
+            keys.existing_key(&contact(1)),
+            Err(CompartmentError::NoKey(_))
+        ));
+        // A fresh key after erasure does not open old rows
+        let fresh = keys.key_for(&contact(1), &mut rng).unwrap();
+        assert!(fresh.decrypt(&row).is_err());
+    }
+
+    #[test]
+    fn test_migration_is_resumable() {
+        let mut rng = seeded(2);
+        let mut keys = keys();
+        let legacy = [7u8; 32];
+        let old_row = encrypt_message(b"hello", &legacy).unwrap();
+
+        let new_row = match keys
+            .migrate_record(&legacy, &contact(3), &old_row, &mut rng)
+            .unwrap()
+        {
+            Migrated::Rewritten(bytes) => bytes,
+            other => panic!("expected rewrite, got {:?}", other),
+        };
+        assert!(decrypt_message(&new_row, &legacy).is_err());
+        assert_eq!(
+            keys.existing_key(&contact(3))
+                .unwrap()
+                .decrypt(&new_row)
+                .unwrap(),
+            b"hello"
+        );
+        // Second run over the same row
+        assert_eq!(
+            keys.migrate_record(&legacy, &contact(3), &new_row, &mut rng)
+                .unwrap(),
+            Migrated::AlreadyMigrated
+        );
+        assert!(matches!(
+            keys.migrate_record(&[8; 32], &contact(4), &old_row, &mut rng),
+            Err(CompartmentError::WrongKey)
+        ));
+    }
+}
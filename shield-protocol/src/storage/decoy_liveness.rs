@@ -0,0 +1,189 @@
+//! Protocol artifacts for decoy messages.
+//!
+//! A real conversation leaves more than message rows behind: every message
+//! went through Ping → Pong → message → ACK, and the database keeps the ping
+//! nonce, when each step happened and the resulting delivery state. Decoy
+//! messages without those records (or with a pong at the exact second of the
+//! ping, every time) give the fake database away to anyone who looks past
+//! the message text.
+//!
+//! Each [`DecoyMessage`](super::DecoyMessage) therefore carries a
+//! [`DecoyDelivery`] with timings drawn from what the real exchange over Tor
+//! produces: a few seconds of round trip when the contact is online, and now
+//! and then a contact that was offline, so the pong only came minutes or
+//! hours later. An outgoing message whose contact has not come back online
+//! by `now` stays [`DecoyDeliveryState::Pending`], with an open ping for the
+//! app to record like a real pending one; the refresh
+//! ([`generate_decoy_activity`](super::generate_decoy_activity)) later
+//! settles it.
+
+use super::random_range;
+use crate::rng::SecureRng;
+
+/// Bytes of a ping nonce (stored hex-encoded, like real ones).
+pub const PING_ID_LEN: usize = 24;
+
+/// Longest [`round_trip`], so a settled exchange never ends after `now`.
+pub(super) const MAX_ROUND_TRIP_SECS: i64 = 7;
+
+/// Chance (0–100) that the contact was offline when a message was sent.
+const OFFLINE_PERCENT: u32 = 12;
+
+/// Delivery state of a decoy message, as the app stores it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoyDeliveryState {
+    /// Outgoing, pinged, no pong yet.
+    Pending,
+    /// Outgoing, acknowledged by the contact.
+    Delivered,
+    /// Incoming, acknowledged by us.
+    Received,
+}
+
+/// Ping / Pong / ACK record of one decoy message. Timestamps are Unix
+/// seconds; for outgoing messages the ping was sent and the pong and ACK
+/// received, for incoming ones the other way round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoyDelivery {
+    /// Hex ping nonce ([`PING_ID_LEN`] bytes).
+    pub ping_id: String,
+    pub state: DecoyDeliveryState,
+    pub ping_at: i64,
+    /// `None` while pending.
+    pub pong_at: Option<i64>,
+    /// `None` while pending.
+    pub ack_at: Option<i64>,
+}
+
+impl DecoyDelivery {
+    /// Record for a message timestamped `timestamp`, as it stands at `now`.
+    pub(super) fn generate(
+        timestamp: i64,
+        is_outgoing: bool,
+        now: i64,
+        rng: &mut impl SecureRng,
+    ) -> Self {
+        let mut nonce = [0u8; PING_ID_LEN];
+        let _ = rng.try_fill_bytes(&mut nonce);
+        let ping_id = hex::encode(nonce);
+
+        if !is_outgoing {
+            // The contact's ping reached us shortly before the message
+            let ping_at = timestamp - random_range(rng, 2, 9) as i64;
+            return Self {
+                ping_id,
+                state: DecoyDeliveryState::Received,
+                ping_at,
+                pong_at: Some(ping_at + random_range(rng, 0, 2) as i64),
+                ack_at: Some((timestamp + random_range(rng, 0, 3) as i64).min(now.max(timestamp))),
+            };
+        }
+
+        let ping_at = timestamp + random_range(rng, 0, 3) as i64;
+        let mut pong_at = ping_at + round_trip(rng);
+        if random_range(rng, 0, 100) < OFFLINE_PERCENT {
+            pong_at += random_range(rng, 120, 8 * 3600) as i64;
+        }
+        let mut delivery = Self {
+            ping_id,
+            state: DecoyDeliveryState::Pending,
+            ping_at,
+            pong_at: None,
+            ack_at: None,
+        };
+        if pong_at + MAX_ROUND_TRIP_SECS <= now {
+            delivery.settle(pong_at, rng);
+        }
+        delivery
+    }
+
+    /// Mark a pending delivery as completed by a pong at `pong_at`.
+    pub(super) fn settle(&mut self, pong_at: i64, rng: &mut impl SecureRng) {
+        self.state = DecoyDeliveryState::Delivered;
+        self.pong_at = Some(pong_at);
+        self.ack_at = Some(pong_at + round_trip(rng));
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == DecoyDeliveryState::Pending
+    }
+
+    /// Move every timestamp by `offset_secs`.
+    pub(super) fn shift(&mut self, offset_secs: i64) {
+        self.ping_at += offset_secs;
+        if let Some(pong_at) = &mut self.pong_at {
+            *pong_at += offset_secs;
+        }
+        if let Some(ack_at) = &mut self.ack_at {
+            *ack_at += offset_secs;
+        }
+    }
+
+    /// Latest moment the contact was heard from through this exchange.
+    pub(super) fn contact_seen_at(&self) -> Option<i64> {
+        match self.state {
+            DecoyDeliveryState::Pending => None,
+            DecoyDeliveryState::Delivered => self.ack_at,
+            DecoyDeliveryState::Received => Some(self.ping_at),
+        }
+    }
+}
+
+/// One hop over Tor and back, in seconds.
+fn round_trip(rng: &mut impl SecureRng) -> i64 {
+    random_range(rng, 1, 4) as i64 + random_range(rng, 0, 5) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_delivery_timeline_is_ordered() {
+        let mut rng = seeded(21);
+        let mut offline = 0;
+        for i in 0..500 {
+            let timestamp = NOW - 7 * 24 * 3600 + i * 600;
+            let out = DecoyDelivery::generate(timestamp, true, NOW, &mut rng);
+            assert_eq!(out.ping_id.len(), 2 * PING_ID_LEN);
+            assert!(out.ping_at >= timestamp);
+            if let (Some(pong), Some(ack)) = (out.pong_at, out.ack_at) {
+                assert_eq!(out.state, DecoyDeliveryState::Delivered);
+                assert!(out.ping_at < pong && pong < ack);
+                if pong - out.ping_at > 60 {
+                    offline += 1;
+                }
+            }
+
+            let incoming = DecoyDelivery::generate(timestamp, false, NOW, &mut rng);
+            assert_eq!(incoming.state, DecoyDeliveryState::Received);
+            assert!(incoming.ping_at < timestamp);
+            assert!(incoming.pong_at.unwrap() >= incoming.ping_at);
+            assert!(incoming.ack_at.unwrap() >= timestamp);
+        }
+        // Some contacts were offline, but not most
+        assert!(offline > 0 && offline < 150);
+    }
+
+    #[test]
+    fn test_recent_offline_send_stays_pending() {
+        let mut rng = seeded(22);
+        let pending: Vec<_> = (0..200)
+            .map(|_| DecoyDelivery::generate(NOW - 30, true, NOW, &mut rng))
+            .filter(DecoyDelivery::is_pending)
+            .collect();
+        assert!(!pending.is_empty());
+        let mut delivery = pending[0].clone();
+        assert_eq!((delivery.pong_at, delivery.ack_at), (None, None));
+        assert_eq!(delivery.contact_seen_at(), None);
+
+        delivery.settle(NOW + 3600, &mut rng);
+        delivery.shift(-60);
+        assert_eq!(delivery.state, DecoyDeliveryState::Delivered);
+        assert_eq!(delivery.pong_at, Some(NOW + 3540));
+        assert!(delivery.contact_seen_at().unwrap() > NOW + 3540);
+    }
+}
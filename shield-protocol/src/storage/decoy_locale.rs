@@ -0,0 +1,567 @@
+//! Locale data for decoy generation.
+//!
+//! A duress database full of "Alex" and "Sam" chatting in English is an
+//! obvious fake on a phone whose owner writes Arabic or Russian. The
+//! [`DecoyLocale`] in [`DecoyConfig`](super::DecoyConfig) picks the name pool
+//! and the everyday chat phrases that decoy contacts and messages are built
+//! from. Regions only get their own variant where common names differ
+//! noticeably (US vs. UK English, Spain vs. Mexico).
+//!
+//! Messages are whole phrases joined until they reach a random length within
+//! the configured bounds, so they read like short chat lines rather than
+//! random characters.
+
+use super::random_range;
+use crate::rng::SecureRng;
+
+/// Language (and where it matters, region) of generated decoy data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecoyLocale {
+    #[default]
+    EnglishUs,
+    EnglishUk,
+    SpanishSpain,
+    SpanishLatinAmerica,
+    PortugueseBrazil,
+    French,
+    German,
+    Russian,
+    Arabic,
+    Persian,
+    Turkish,
+}
+
+struct LocaleData {
+    given_names: &'static [&'static str],
+    family_names: &'static [&'static str],
+    phrases: &'static [&'static str],
+}
+
+const EN_PHRASES: &[&str] = &[
+    "hey, you around?",
+    "running a bit late",
+    "sounds good to me",
+    "can you grab milk on the way?",
+    "haha yes",
+    "call me when you're free",
+    "did you see the game last night?",
+    "ok",
+    "thanks!",
+    "what time tomorrow?",
+    "I'll send it later",
+    "just got home",
+    "no worries",
+    "let's do Friday instead",
+    "on my way",
+];
+
+const ES_PHRASES: &[&str] = &[
+    "hola, ¿estás por ahí?",
+    "llego un poco tarde",
+    "me parece bien",
+    "¿puedes comprar pan?",
+    "jaja sí",
+    "llámame cuando puedas",
+    "¿a qué hora mañana?",
+    "vale",
+    "¡gracias!",
+    "te lo mando luego",
+    "ya llegué a casa",
+    "no pasa nada",
+    "mejor el viernes",
+    "voy para allá",
+];
+
+const EN_US: LocaleData = LocaleData {
+    given_names: &[
+        "Mike", "Jessica", "Chris", "Ashley", "Matt", "Sarah", "Josh", "Emily", "Tyler", "Megan",
+        "Kevin", "Lauren",
+    ],
+    family_names: &[
+        "Johnson", "Miller", "Davis", "Garcia", "Wilson", "Anderson", "Thomas", "Moore",
+    ],
+    phrases: EN_PHRASES,
+};
+
+const EN_UK: LocaleData = LocaleData {
+    given_names: &[
+        "Oliver", "Amelia", "Harry", "Sophie", "Jack", "Chloe", "Callum", "Lucy", "George",
+        "Ellie", "Jamie", "Hannah",
+    ],
+    family_names: &[
+        "Smith", "Jones", "Taylor", "Brown", "Evans", "Walker", "Wright", "Hughes",
+    ],
+    phrases: EN_PHRASES,
+};
+
+const ES_ES: LocaleData = LocaleData {
+    given_names: &[
+        "Javier", "Lucía", "Sergio", "Marta", "Pablo", "Laura", "Álvaro", "Paula", "Raúl",
+        "Carmen", "David", "Elena",
+    ],
+    family_names: &[
+        "García",
+        "Fernández",
+        "López",
+        "Martínez",
+        "Sánchez",
+        "Pérez",
+        "Gómez",
+        "Ruiz",
+    ],
+    phrases: ES_PHRASES,
+};
+
+const ES_LATAM: LocaleData = LocaleData {
+    given_names: &[
+        "José",
+        "Guadalupe",
+        "Luis",
+        "Mariana",
+        "Jorge",
+        "Daniela",
+        "Alejandro",
+        "Fernanda",
+        "Diego",
+        "Valeria",
+        "Carlos",
+        "Ximena",
+    ],
+    family_names: &[
+        "Hernández",
+        "González",
+        "Ramírez",
+        "Flores",
+        "Torres",
+        "Morales",
+        "Reyes",
+        "Castillo",
+    ],
+    phrases: ES_PHRASES,
+};
+
+const PT_BR: LocaleData = LocaleData {
+    given_names: &[
+        "João", "Ana", "Pedro", "Juliana", "Lucas", "Camila", "Gabriel", "Larissa", "Rafael",
+        "Beatriz", "Thiago", "Fernanda",
+    ],
+    family_names: &[
+        "Silva",
+        "Santos",
+        "Oliveira",
+        "Souza",
+        "Pereira",
+        "Costa",
+        "Rodrigues",
+        "Almeida",
+    ],
+    phrases: &[
+        "oi, tá por aí?",
+        "vou atrasar um pouco",
+        "beleza",
+        "pode comprar pão?",
+        "kkkk sim",
+        "me liga quando puder",
+        "que horas amanhã?",
+        "valeu!",
+        "te mando depois",
+        "acabei de chegar em casa",
+        "tranquilo",
+        "melhor sexta então",
+        "tô indo",
+    ],
+};
+
+const FR: LocaleData = LocaleData {
+    given_names: &[
+        "Thomas", "Camille", "Nicolas", "Léa", "Julien", "Manon", "Maxime", "Chloé", "Antoine",
+        "Sarah", "Romain", "Inès",
+    ],
+    family_names: &[
+        "Martin", "Bernard", "Dubois", "Durand", "Moreau", "Laurent", "Simon", "Lefebvre",
+    ],
+    phrases: &[
+        "salut, t'es dispo ?",
+        "j'arrive un peu en retard",
+        "ça marche",
+        "tu peux prendre du pain ?",
+        "mdr oui",
+        "appelle-moi quand tu peux",
+        "à quelle heure demain ?",
+        "ok",
+        "merci !",
+        "je te l'envoie plus tard",
+        "je viens de rentrer",
+        "pas de souci",
+        "plutôt vendredi alors",
+        "j'arrive",
+    ],
+};
+
+const DE: LocaleData = LocaleData {
+    given_names: &[
+        "Lukas",
+        "Anna",
+        "Jonas",
+        "Laura",
+        "Felix",
+        "Lena",
+        "Tobias",
+        "Julia",
+        "Jan",
+        "Sophie",
+        "Florian",
+        "Katharina",
+    ],
+    family_names: &[
+        "Müller",
+        "Schmidt",
+        "Schneider",
+        "Fischer",
+        "Weber",
+        "Becker",
+        "Wagner",
+        "Hoffmann",
+    ],
+    phrases: &[
+        "hey, bist du da?",
+        "ich komme etwas später",
+        "klingt gut",
+        "kannst du Brot mitbringen?",
+        "haha ja",
+        "ruf mich an, wenn du Zeit hast",
+        "wann morgen?",
+        "ok",
+        "danke!",
+        "ich schick's dir später",
+        "bin gerade heimgekommen",
+        "kein Problem",
+        "lieber Freitag",
+        "bin unterwegs",
+    ],
+};
+
+const RU: LocaleData = LocaleData {
+    given_names: &[
+        "Алексей",
+        "Анна",
+        "Дмитрий",
+        "Мария",
+        "Сергей",
+        "Елена",
+        "Иван",
+        "Ольга",
+        "Андрей",
+        "Наталья",
+        "Михаил",
+        "Татьяна",
+    ],
+    family_names: &[
+        "Иванов",
+        "Смирнов",
+        "Кузнецов",
+        "Попов",
+        "Волков",
+        "Соколов",
+        "Лебедев",
+        "Козлов",
+    ],
+    phrases: &[
+        "привет, ты тут?",
+        "немного опаздываю",
+        "хорошо",
+        "купишь хлеб?",
+        "ахах да",
+        "позвони, как будет время",
+        "во сколько завтра?",
+        "ок",
+        "спасибо!",
+        "скину позже",
+        "только пришёл домой",
+        "ничего страшного",
+        "давай лучше в пятницу",
+        "уже еду",
+    ],
+};
+
+const AR: LocaleData = LocaleData {
+    given_names: &[
+        "محمد",
+        "فاطمة",
+        "أحمد",
+        "مريم",
+        "علي",
+        "نور",
+        "عمر",
+        "سارة",
+        "يوسف",
+        "ليلى",
+        "خالد",
+        "هدى",
+    ],
+    family_names: &[
+        "حسن",
+        "إبراهيم",
+        "السيد",
+        "عبدالله",
+        "منصور",
+        "الخطيب",
+        "سليمان",
+        "يوسف",
+    ],
+    phrases: &[
+        "مرحبا، موجود؟",
+        "رح أتأخر شوي",
+        "تمام",
+        "ممكن تجيب خبز؟",
+        "هههه اي",
+        "اتصل فيني لما تفضى",
+        "الساعة كم بكرة؟",
+        "ماشي",
+        "شكرا!",
+        "ببعتلك ياه بعدين",
+        "هلق وصلت البيت",
+        "ولا يهمك",
+        "خليها يوم الجمعة",
+        "أنا بالطريق",
+    ],
+};
+
+const FA: LocaleData = LocaleData {
+    given_names: &[
+        "علی",
+        "زهرا",
+        "محمد",
+        "فاطمه",
+        "رضا",
+        "مریم",
+        "حسین",
+        "سارا",
+        "امیر",
+        "نگار",
+        "مهدی",
+        "الهام",
+    ],
+    family_names: &[
+        "محمدی",
+        "حسینی",
+        "احمدی",
+        "رضایی",
+        "کریمی",
+        "موسوی",
+        "جعفری",
+        "صادقی",
+    ],
+    phrases: &[
+        "سلام، هستی؟",
+        "یه کم دیر می‌رسم",
+        "باشه",
+        "میشه نون بگیری؟",
+        "خخخ آره",
+        "هر وقت وقت داشتی زنگ بزن",
+        "فردا ساعت چند؟",
+        "مرسی!",
+        "بعدا برات می‌فرستم",
+        "تازه رسیدم خونه",
+        "اشکال نداره",
+        "بذاریم برای جمعه",
+        "دارم میام",
+    ],
+};
+
+const TR: LocaleData = LocaleData {
+    given_names: &[
+        "Mehmet", "Ayşe", "Mustafa", "Elif", "Emre", "Zeynep", "Burak", "Merve", "Can", "Esra",
+        "Murat", "Selin",
+    ],
+    family_names: &[
+        "Yılmaz", "Kaya", "Demir", "Şahin", "Çelik", "Yıldız", "Aydın", "Öztürk",
+    ],
+    phrases: &[
+        "selam, müsait misin?",
+        "biraz gecikeceğim",
+        "olur",
+        "ekmek alabilir misin?",
+        "haha evet",
+        "müsait olunca ara",
+        "yarın saat kaçta?",
+        "tamam",
+        "teşekkürler!",
+        "sonra gönderirim",
+        "eve yeni geldim",
+        "sorun değil",
+        "cumaya alalım",
+        "yoldayım",
+    ],
+};
+
+impl DecoyLocale {
+    /// Locale for a BCP 47 tag such as `"ar"`, `"es-MX"` or `"en_GB"`.
+    /// `None` for languages without decoy data.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next()?.to_ascii_lowercase();
+        let region = parts
+            .find(|p| p.len() == 2 || p.len() == 3)
+            .map(|p| p.to_ascii_uppercase());
+        let region = region.as_deref();
+        Some(match language.as_str() {
+            "en" => match region {
+                Some("GB" | "IE" | "AU" | "NZ") => Self::EnglishUk,
+                _ => Self::EnglishUs,
+            },
+            "es" => match region {
+                Some("ES") => Self::SpanishSpain,
+                _ => Self::SpanishLatinAmerica,
+            },
+            "pt" => Self::PortugueseBrazil,
+            "fr" => Self::French,
+            "de" => Self::German,
+            "ru" => Self::Russian,
+            "ar" => Self::Arabic,
+            "fa" => Self::Persian,
+            "tr" => Self::Turkish,
+            _ => return None,
+        })
+    }
+
+    /// Canonical BCP 47 tag.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::EnglishUs => "en-US",
+            Self::EnglishUk => "en-GB",
+            Self::SpanishSpain => "es-ES",
+            Self::SpanishLatinAmerica => "es-419",
+            Self::PortugueseBrazil => "pt-BR",
+            Self::French => "fr",
+            Self::German => "de",
+            Self::Russian => "ru",
+            Self::Arabic => "ar",
+            Self::Persian => "fa",
+            Self::Turkish => "tr",
+        }
+    }
+
+    fn data(&self) -> &'static LocaleData {
+        match self {
+            Self::EnglishUs => &EN_US,
+            Self::EnglishUk => &EN_UK,
+            Self::SpanishSpain => &ES_ES,
+            Self::SpanishLatinAmerica => &ES_LATAM,
+            Self::PortugueseBrazil => &PT_BR,
+            Self::French => &FR,
+            Self::German => &DE,
+            Self::Russian => &RU,
+            Self::Arabic => &AR,
+            Self::Persian => &FA,
+            Self::Turkish => &TR,
+        }
+    }
+}
+
+/// Display name for the `index`-th decoy contact. Given names do not repeat
+/// until the pool is exhausted.
+pub(super) fn contact_name(locale: DecoyLocale, index: usize, rng: &mut impl SecureRng) -> String {
+    let data = locale.data();
+    let given = data.given_names[index % data.given_names.len()];
+    let family = data.family_names[random_range(rng, 0, data.family_names.len() as u32) as usize];
+    format!("{} {}", given, family)
+}
+
+/// Chat line of `min_len..=max_len` bytes made of whole phrases where possible.
+pub(super) fn message_text(
+    locale: DecoyLocale,
+    min_len: usize,
+    max_len: usize,
+    rng: &mut impl SecureRng,
+) -> String {
+    const PICKS: usize = 8;
+    let phrases = locale.data().phrases;
+    let max_len = max_len.max(min_len);
+    let target = random_range(rng, min_len as u32, max_len as u32 + 1) as usize;
+
+    let mut out = String::new();
+    while out.len() < target {
+        let sep = usize::from(!out.is_empty());
+        let room = max_len.saturating_sub(out.len() + sep);
+        if room == 0 {
+            break;
+        }
+        let fitting = (0..PICKS)
+            .map(|_| phrases[random_range(rng, 0, phrases.len() as u32) as usize])
+            .find(|p| p.len() <= room);
+        match fitting {
+            Some(phrase) => {
+                if sep == 1 {
+                    out.push(' ');
+                }
+                out.push_str(phrase);
+            }
+            None if out.len() >= min_len => break,
+            None => {
+                // Nothing fits whole: cut a phrase at a character boundary
+                let phrase = phrases[random_range(rng, 0, phrases.len() as u32) as usize];
+                let mut cut = room.min(phrase.len());
+                while !phrase.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                if sep == 1 {
+                    out.push(' ');
+                }
+                out.push_str(&phrase[..cut]);
+                break;
+            }
+        }
+    }
+    while out.len() < min_len {
+        out.push('.');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    #[test]
+    fn test_locale_tags() {
+        assert_eq!(DecoyLocale::from_tag("ar"), Some(DecoyLocale::Arabic));
+        assert_eq!(DecoyLocale::from_tag("ar-EG"), Some(DecoyLocale::Arabic));
+        assert_eq!(DecoyLocale::from_tag("en_GB"), Some(DecoyLocale::EnglishUk));
+        assert_eq!(DecoyLocale::from_tag("en"), Some(DecoyLocale::EnglishUs));
+        assert_eq!(
+            DecoyLocale::from_tag("es-ES"),
+            Some(DecoyLocale::SpanishSpain)
+        );
+        assert_eq!(
+            DecoyLocale::from_tag("es-MX"),
+            Some(DecoyLocale::SpanishLatinAmerica)
+        );
+        assert_eq!(DecoyLocale::from_tag("ja-JP"), None);
+        for locale in [DecoyLocale::Persian, DecoyLocale::SpanishLatinAmerica] {
+            assert_eq!(DecoyLocale::from_tag(locale.tag()), Some(locale));
+        }
+    }
+
+    #[test]
+    fn test_message_text_respects_bounds() {
+        let mut rng = seeded(21);
+        for locale in [
+            DecoyLocale::EnglishUs,
+            DecoyLocale::Russian,
+            DecoyLocale::Arabic,
+            DecoyLocale::Persian,
+        ] {
+            for (min, max) in [(10, 200), (10, 50), (1, 12), (30, 30)] {
+                for _ in 0..50 {
+                    let text = message_text(locale, min, max, &mut rng);
+                    assert!(text.len() >= min && text.len() <= max, "{:?}", text);
+                }
+            }
+            // Phrases come from the locale, not random characters
+            let text = message_text(locale, 10, 200, &mut rng);
+            assert!(locale.data().phrases.iter().any(|p| text.contains(p)));
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Periodic refresh of the decoy database.
+//!
+//! Decoy data generated once at duress time freezes: a week later the newest
+//! "conversation" is a week old, which is itself a tell. After populating the
+//! fake database the app keeps a [`DecoyRefreshSpec`] and wakes up at
+//! [`next_refresh_at`]. Each time it calls [`generate_decoy_activity`] for the
+//! time since the last refresh and writes the returned [`DecoyUpdate`]s into
+//! the fake database (or applies them in memory with
+//! [`apply_decoy_activity`]).
+//!
+//! Activity comes in short bursts per contact, alternates direction like a
+//! real exchange, and avoids the configured quiet hours. Conversations idle
+//! for longer than `max_idle_secs` are moved forward as a whole, keeping
+//! their internal spacing, so no contact looks abandoned. Messages still
+//! waiting for a pong are delivered once their contact "comes online" during
+//! a refresh.
+
+use super::decoy_liveness::MAX_ROUND_TRIP_SECS;
+use super::decoy_locale::message_text;
+use super::{
+    random_bool, random_range, DecoyConfig, DecoyContact, DecoyDelivery, DecoyDeliveryState,
+    DecoyMessage,
+};
+use crate::rng::SecureRng;
+
+/// Bursts never look back further than this, however long the app slept.
+const MAX_CATCH_UP_SECS: i64 = 7 * 24 * 3600;
+
+/// Attempts to place a timestamp outside quiet hours before giving up on it.
+const PLACEMENT_ATTEMPTS: usize = 8;
+
+/// Scheduling and volume of decoy refreshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoyRefreshSpec {
+    /// Shortest delay between two refreshes (seconds).
+    pub min_interval_secs: u32,
+    /// Longest delay between two refreshes (seconds).
+    pub max_interval_secs: u32,
+    /// Chance (0–100) that a given contact was active since the last refresh.
+    pub active_contact_percent: u8,
+    /// Upper bound on new messages per active contact and refresh.
+    pub max_new_messages: u16,
+    /// Conversations whose newest message is older than this are shifted
+    /// forward.
+    pub max_idle_secs: u32,
+    /// Local hours `[start, end)` with no activity, e.g. `(1, 7)`. The range
+    /// may wrap midnight.
+    pub quiet_hours: Option<(u8, u8)>,
+    /// Offset of the device's local time from UTC, for `quiet_hours`.
+    pub utc_offset_secs: i32,
+}
+
+impl Default for DecoyRefreshSpec {
+    fn default() -> Self {
+        Self {
+            min_interval_secs: 4 * 3600,
+            max_interval_secs: 30 * 3600,
+            active_contact_percent: 40,
+            max_new_messages: 8,
+            max_idle_secs: 10 * 24 * 3600,
+            quiet_hours: Some((1, 7)),
+            utc_offset_secs: 0,
+        }
+    }
+}
+
+impl DecoyRefreshSpec {
+    fn is_quiet(&self, timestamp: i64) -> bool {
+        let Some((start, end)) = self.quiet_hours else {
+            return false;
+        };
+        let hour = ((timestamp + self.utc_offset_secs as i64).rem_euclid(86_400) / 3600) as u8;
+        if start <= end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+/// One change to apply to the fake database. `contact_index` refers to the
+/// slice passed to [`generate_decoy_activity`].
+#[derive(Debug, Clone)]
+pub enum DecoyUpdate {
+    /// Insert a new message.
+    NewMessage {
+        contact_index: usize,
+        message: DecoyMessage,
+    },
+    /// Add `offset_secs` to the timestamp of every existing message of the
+    /// contact, and to its delivery record. Emitted before any `NewMessage`
+    /// for the same contact, and never moves history past the start of the
+    /// refresh window.
+    ShiftHistory {
+        contact_index: usize,
+        offset_secs: i64,
+    },
+    /// The pending message with this ping ID got its pong and ACK.
+    /// Emitted after any `ShiftHistory` for the same contact.
+    Delivered {
+        contact_index: usize,
+        ping_id: String,
+        pong_at: i64,
+        ack_at: i64,
+    },
+}
+
+/// Unix time (seconds) of the refresh after one at `last_refresh`, jittered
+/// uniformly within the spec's interval and moved out of quiet hours.
+pub fn next_refresh_at(
+    spec: &DecoyRefreshSpec,
+    last_refresh: i64,
+    rng: &mut impl SecureRng,
+) -> i64 {
+    let mut at = last_refresh
+        + random_range(
+            rng,
+            spec.min_interval_secs,
+            spec.max_interval_secs.max(spec.min_interval_secs),
+        ) as i64;
+    // Step to the end of the quiet period, if any
+    let mut steps = 0;
+    while spec.is_quiet(at) && steps < 24 {
+        at += 3600 - at.rem_euclid(3600);
+        steps += 1;
+    }
+    at
+}
+
+/// Plausible activity for the period `(last_refresh, now]`.
+///
+/// `contacts` is the current fake database (messages sorted by timestamp, as
+/// produced by [`generate_decoy_data`](super::generate_decoy_data)).
+/// Message lengths and language follow `config`.
+pub fn generate_decoy_activity(
+    contacts: &[DecoyContact],
+    spec: &DecoyRefreshSpec,
+    config: &DecoyConfig,
+    last_refresh: i64,
+    now: i64,
+    rng: &mut impl SecureRng,
+) -> Vec<DecoyUpdate> {
+    let since = last_refresh.max(now - MAX_CATCH_UP_SECS);
+    let mut updates = Vec::new();
+    if since >= now {
+        return updates;
+    }
+
+    for (contact_index, contact) in contacts.iter().enumerate() {
+        let newest = contact.messages.last().map(|m| m.timestamp);
+        let active = random_range(rng, 0, 100) < spec.active_contact_percent as u32;
+
+        let mut offset_secs = 0;
+        if let Some(newest) = newest {
+            let idle = now - newest;
+            if idle > spec.max_idle_secs as i64 {
+                // Land the newest message shortly before the window, so new
+                // activity still follows the existing history
+                let target = since - random_range(rng, 0, spec.max_interval_secs.max(1)) as i64;
+                offset_secs = (target - newest).max(0);
+                updates.push(DecoyUpdate::ShiftHistory {
+                    contact_index,
+                    offset_secs,
+                });
+            }
+        }
+        deliver_pending(
+            contact_index,
+            contact,
+            offset_secs,
+            spec,
+            since,
+            now,
+            rng,
+            &mut updates,
+        );
+        if !active || spec.max_new_messages == 0 {
+            continue;
+        }
+
+        let count = random_range(rng, 1, spec.max_new_messages as u32 + 1);
+        let Some(mut timestamp) = place(spec, since, now, rng) else {
+            continue;
+        };
+        let mut is_outgoing = random_bool(rng);
+        for _ in 0..count {
+            if timestamp > now || spec.is_quiet(timestamp) {
+                break;
+            }
+            let content = message_text(
+                config.locale,
+                config.min_message_len as usize,
+                config.max_message_len as usize,
+                rng,
+            );
+            let delivery = DecoyDelivery::generate(timestamp, is_outgoing, now, rng);
+            updates.push(DecoyUpdate::NewMessage {
+                contact_index,
+                message: DecoyMessage {
+                    content,
+                    timestamp,
+                    is_outgoing,
+                    delivery,
+                },
+            });
+            // Replies mostly switch sides, and come within minutes
+            if random_range(rng, 0, 100) < 70 {
+                is_outgoing = !is_outgoing;
+            }
+            timestamp += random_range(rng, 5, 15 * 60) as i64;
+        }
+    }
+    updates
+}
+
+/// Settle the contact's pending pings, most of the time. They all get their
+/// pong when the contact comes back online, seconds apart, as the app's
+/// retries would.
+fn deliver_pending(
+    contact_index: usize,
+    contact: &DecoyContact,
+    offset_secs: i64,
+    spec: &DecoyRefreshSpec,
+    since: i64,
+    now: i64,
+    rng: &mut impl SecureRng,
+    updates: &mut Vec<DecoyUpdate>,
+) {
+    let mut pending = contact
+        .messages
+        .iter()
+        .map(|m| &m.delivery)
+        .filter(|d| d.is_pending())
+        .peekable();
+    let Some(first) = pending.peek() else {
+        return;
+    };
+    if random_range(rng, 0, 100) >= 80 {
+        return;
+    }
+    let start = since.max(first.ping_at + offset_secs);
+    let latest = now - 4 * MAX_ROUND_TRIP_SECS;
+    if start >= latest {
+        return;
+    }
+    let Some(mut online_at) = place(spec, start, latest, rng) else {
+        return;
+    };
+    for delivery in pending {
+        let mut settled = delivery.clone();
+        settled.settle(online_at, rng);
+        let (Some(pong_at), Some(ack_at)) = (settled.pong_at, settled.ack_at) else {
+            continue;
+        };
+        updates.push(DecoyUpdate::Delivered {
+            contact_index,
+            ping_id: settled.ping_id,
+            pong_at,
+            ack_at,
+        });
+        online_at += random_range(rng, 1, 4) as i64;
+    }
+}
+
+/// Random timestamp in `(since, now]` outside quiet hours.
+fn place(spec: &DecoyRefreshSpec, since: i64, now: i64, rng: &mut impl SecureRng) -> Option<i64> {
+    let span = (now - since).clamp(1, u32::MAX as i64) as u32;
+    (0..PLACEMENT_ATTEMPTS)
+        .map(|_| since + 1 + random_range(rng, 0, span) as i64)
+        .find(|t| !spec.is_quiet(*t))
+}
+
+/// Apply updates to an in-memory decoy set, keeping messages sorted.
+pub fn apply_decoy_activity(contacts: &mut [DecoyContact], updates: &[DecoyUpdate]) {
+    for update in updates {
+        match update {
+            DecoyUpdate::ShiftHistory {
+                contact_index,
+                offset_secs,
+            } => {
+                if let Some(contact) = contacts.get_mut(*contact_index) {
+                    for message in &mut contact.messages {
+                        message.timestamp += offset_secs;
+                        message.delivery.shift(*offset_secs);
+                    }
+                }
+            }
+            DecoyUpdate::Delivered {
+                contact_index,
+                ping_id,
+                pong_at,
+                ack_at,
+            } => {
+                if let Some(message) = contacts.get_mut(*contact_index).and_then(|c| {
+                    c.messages
+                        .iter_mut()
+                        .find(|m| m.delivery.ping_id == *ping_id)
+                }) {
+                    let delivery = &mut message.delivery;
+                    delivery.state = DecoyDeliveryState::Delivered;
+                    delivery.pong_at = Some(*pong_at);
+                    delivery.ack_at = Some(*ack_at);
+                }
+            }
+            DecoyUpdate::NewMessage {
+                contact_index,
+                message,
+            } => {
+                if let Some(contact) = contacts.get_mut(*contact_index) {
+                    contact.messages.push(message.clone());
+                }
+            }
+        }
+    }
+    for contact in contacts {
+        contact.messages.sort_by_key(|m| m.timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+    use crate::storage::generate_decoy_data_with_rng;
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn test_next_refresh_within_interval_and_outside_quiet_hours() {
+        let spec = DecoyRefreshSpec::default();
+        let mut rng = seeded(11);
+        let start = 1_700_000_000 - 1_700_000_000 % DAY;
+        for i in 0..50 {
+            let last = start + i * 3 * 3600;
+            let next = next_refresh_at(&spec, last, &mut rng);
+            assert!(next >= last + spec.min_interval_secs as i64);
+            assert!(next < last + spec.max_interval_secs as i64 + 6 * 3600);
+            assert!(!spec.is_quiet(next));
+        }
+        // Quiet range wrapping midnight
+        let night = DecoyRefreshSpec {
+            quiet_hours: Some((22, 6)),
+            ..spec
+        };
+        assert!(night.is_quiet(start + 23 * 3600));
+        assert!(night.is_quiet(start + 3 * 3600));
+        assert!(!night.is_quiet(start + 12 * 3600));
+    }
+
+    #[test]
+    fn test_stale_decoys_are_refreshed() {
+        let mut rng = seeded(12);
+        let config = DecoyConfig::default();
+        let spec = DecoyRefreshSpec {
+            active_contact_percent: 100,
+            ..DecoyRefreshSpec::default()
+        };
+        let mut contacts = generate_decoy_data_with_rng(&config, &mut rng);
+        let generated_at = contacts
+            .iter()
+            .filter_map(|c| c.messages.last())
+            .map(|m| m.timestamp)
+            .max()
+            .unwrap();
+        let now = generated_at + 30 * DAY;
+
+        let updates =
+            generate_decoy_activity(&contacts, &spec, &config, generated_at, now, &mut rng);
+        assert!(updates
+            .iter()
+            .any(|u| matches!(u, DecoyUpdate::ShiftHistory { .. })));
+        for update in &updates {
+            if let DecoyUpdate::NewMessage { message, .. } = update {
+                assert!(message.timestamp > now - MAX_CATCH_UP_SECS);
+                assert!(message.timestamp <= now);
+                assert!(!spec.is_quiet(message.timestamp));
+            }
+        }
+
+        apply_decoy_activity(&mut contacts, &updates);
+        for contact in &contacts {
+            let newest = contact.messages.last().unwrap().timestamp;
+            assert!(now - newest <= spec.max_idle_secs as i64);
+            assert!(contact
+                .messages
+                .windows(2)
+                .all(|w| w[0].timestamp <= w[1].timestamp));
+            // Delivery records moved along with the history
+            for message in &contact.messages {
+                let delivery = &message.delivery;
+                assert_eq!(delivery.ping_at >= message.timestamp, message.is_outgoing);
+                assert!(delivery.ack_at.is_none_or(|ack| ack <= now));
+            }
+            assert!(contact.last_seen().unwrap() <= now);
+        }
+
+        // Nothing happens in an empty window
+        assert!(generate_decoy_activity(&contacts, &spec, &config, now, now, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,648 @@
+//! Crash-safe write-ahead intent log for protocol actions.
+//!
+//! Some protocol steps span a window where a crash leaves persistent state
+//! inconsistent with what went on the wire. The dangerous one is the send
+//! path: the message key at `sequence` has been used to encrypt, but the
+//! chain state has not been persisted past it and the ciphertext may never
+//! have left the device. Restarting from the old chain state would encrypt
+//! the next message under the same key (key/nonce reuse); dropping the
+//! ciphertext would lose the message.
+//!
+//! The intent log closes that window: the caller records an [`Intent`]
+//! *before* acting, and removes it once the effect is durable. On startup
+//! [`IntentLog::open`] reads whatever intents survived and turns them into
+//! [`RecoveryAction`]s the app applies before sending anything new.
+//!
+//! Persistence is app-provided through [`IntentStore`] (same model as
+//! [`DeniableStorage`](super::DeniableStorage)). [`FileIntentStore`] keeps
+//! one encrypted file per intent for apps without a database at hand;
+//! [`MemoryIntentStore`] is provided for tests and ephemeral sessions.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::StorageError;
+use crate::crypto::encryption::{
+    decrypt_message, encrypt_message, evolve_chain_key, EncryptionError,
+};
+use crate::protocol::contact_id::ContactId;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum IntentLogError {
+    #[error("Intent store error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Intent record encoding error: {0}")]
+    Encoding(String),
+
+    #[error("No pending intent with id {0}")]
+    UnknownIntent(u64),
+
+    #[error("Intent {0} is not a pending send")]
+    NotASend(u64),
+
+    #[error("Chain key evolution failed: {0}")]
+    Encryption(#[from] EncryptionError),
+}
+
+// ---------------------------------------------------------------------------
+// Records
+// ---------------------------------------------------------------------------
+
+/// An action that has started but whose effect is not yet durable.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Intent {
+    /// The send-chain key at `sequence` was used to produce `ciphertext`, and
+    /// the peer has not acknowledged it yet.
+    SendMessage {
+        contact_id: ContactId,
+        message_id: String,
+        sequence: u64,
+        ciphertext: Vec<u8>,
+    },
+    /// The peer acknowledged `message_id`; the chain state at
+    /// `next_sequence` has not been persisted yet.
+    CommitRatchet {
+        contact_id: ContactId,
+        message_id: String,
+        next_sequence: u64,
+    },
+}
+
+impl Intent {
+    pub fn contact_id(&self) -> ContactId {
+        match self {
+            Intent::SendMessage { contact_id, .. } | Intent::CommitRatchet { contact_id, .. } => {
+                *contact_id
+            }
+        }
+    }
+
+    /// Lowest send sequence the contact's chain may safely resume at.
+    fn min_next_sequence(&self) -> u64 {
+        match self {
+            Intent::SendMessage { sequence, .. } => sequence + 1,
+            Intent::CommitRatchet { next_sequence, .. } => *next_sequence,
+        }
+    }
+}
+
+/// One entry in the log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IntentRecord {
+    /// Monotonic id, unique within the log.
+    pub id: u64,
+    pub intent: Intent,
+    /// Unix timestamp (seconds) when the intent was recorded.
+    pub created_at: u64,
+}
+
+impl IntentRecord {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, IntentLogError> {
+        bincode::serialize(self).map_err(|e| IntentLogError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IntentLogError> {
+        bincode::deserialize(bytes).map_err(|e| IntentLogError::Encoding(e.to_string()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Persistence contract (app implements)
+// ---------------------------------------------------------------------------
+
+/// Durable backing store for the intent log.
+///
+/// `append` and `remove` MUST be durable when they return (fsync, or a
+/// committed SQLCipher transaction) — the log is only crash-safe if the
+/// intent hits disk before the action it guards.
+///
+/// Schema hint for SQLCipher:
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS intent_log (
+///   id     INTEGER PRIMARY KEY,
+///   record BLOB NOT NULL  -- IntentRecord::to_bytes()
+/// );
+/// ```
+pub trait IntentStore {
+    fn append(&mut self, record: &IntentRecord) -> super::Result<()>;
+    fn remove(&mut self, id: u64) -> super::Result<()>;
+    /// All records currently stored, in any order.
+    fn load_all(&self) -> super::Result<Vec<IntentRecord>>;
+}
+
+impl<S: IntentStore + ?Sized> IntentStore for Box<S> {
+    fn append(&mut self, record: &IntentRecord) -> super::Result<()> {
+        (**self).append(record)
+    }
+
+    fn remove(&mut self, id: u64) -> super::Result<()> {
+        (**self).remove(id)
+    }
+
+    fn load_all(&self) -> super::Result<Vec<IntentRecord>> {
+        (**self).load_all()
+    }
+}
+
+/// In-memory intent log. It does not survive the crash it exists to recover
+/// from, so it is only for tests or callers that opt out of recovery.
+#[derive(Debug, Default)]
+pub struct MemoryIntentStore {
+    records: BTreeMap<u64, IntentRecord>,
+}
+
+impl MemoryIntentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IntentStore for MemoryIntentStore {
+    fn append(&mut self, record: &IntentRecord) -> super::Result<()> {
+        self.records.insert(record.id, record.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, id: u64) -> super::Result<()> {
+        self.records.remove(&id);
+        Ok(())
+    }
+
+    fn load_all(&self) -> super::Result<Vec<IntentRecord>> {
+        Ok(self.records.values().cloned().collect())
+    }
+}
+
+/// Intent store in a directory, one file per record, each sealed with
+/// XChaCha20-Poly1305 under the store key.
+///
+/// A record is written to a temporary name, synced and renamed, so a crash
+/// leaves either the whole record or none of it. Stray temporary files are
+/// ignored by [`load_all`](IntentStore::load_all).
+pub struct FileIntentStore {
+    dir: PathBuf,
+    key: [u8; 32],
+}
+
+impl FileIntentStore {
+    /// Use `dir` (created if missing) for records sealed under `key`.
+    pub fn open(dir: impl Into<PathBuf>, key: &[u8; 32]) -> super::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|_| StorageError::Io)?;
+        Ok(Self { dir, key: *key })
+    }
+
+    fn record_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.intent", id))
+    }
+}
+
+impl Drop for FileIntentStore {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl IntentStore for FileIntentStore {
+    fn append(&mut self, record: &IntentRecord) -> super::Result<()> {
+        let plaintext = record.to_bytes().map_err(|_| StorageError::Io)?;
+        let sealed = encrypt_message(&plaintext, &self.key).map_err(|_| StorageError::Io)?;
+        let path = self.record_path(record.id);
+        let tmp = path.with_extension("tmp");
+        let write = || -> io::Result<()> {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&sealed)?;
+            file.sync_all()?;
+            fs::rename(&tmp, &path)?;
+            // Make the rename itself durable.
+            File::open(&self.dir)?.sync_all()
+        };
+        write().map_err(|_| StorageError::Io)
+    }
+
+    fn remove(&mut self, id: u64) -> super::Result<()> {
+        match fs::remove_file(self.record_path(id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(_) => Err(StorageError::Io),
+        }
+    }
+
+    /// Fails with [`StorageError::DecryptionFailed`] if a record does not
+    /// open under the store key.
+    fn load_all(&self) -> super::Result<Vec<IntentRecord>> {
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(|_| StorageError::Io)? {
+            let path = entry.map_err(|_| StorageError::Io)?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("intent") {
+                continue;
+            }
+            let sealed = fs::read(&path).map_err(|_| StorageError::Io)?;
+            let plaintext =
+                decrypt_message(&sealed, &self.key).map_err(|_| StorageError::DecryptionFailed)?;
+            let record =
+                IntentRecord::from_bytes(&plaintext).map_err(|_| StorageError::DecryptionFailed)?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Recovery
+// ---------------------------------------------------------------------------
+
+/// What the app must do for intents that survived a crash.
+///
+/// Actions are returned in the order they must be applied: all
+/// `AdvanceRatchet` actions come first, so no new message is encrypted under
+/// a key that may already have been used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// Persist the contact's send chain at `next_sequence` or later (see
+    /// [`fast_forward_chain`]) before encrypting anything new for them.
+    AdvanceRatchet {
+        contact_id: ContactId,
+        next_sequence: u64,
+    },
+    /// Send these exact bytes again. Never re-encrypt: that would reuse the
+    /// message key. Call [`IntentLog::acknowledge`] with `intent_id` once the
+    /// peer ACKs.
+    Resend {
+        intent_id: u64,
+        contact_id: ContactId,
+        message_id: String,
+        ciphertext: Vec<u8>,
+    },
+    /// The message was delivered before the crash. Mark it delivered, then
+    /// call [`IntentLog::complete`] with `intent_id`.
+    MarkDelivered {
+        intent_id: u64,
+        contact_id: ContactId,
+        message_id: String,
+    },
+}
+
+fn plan_recovery(pending: &BTreeMap<u64, Intent>) -> Vec<RecoveryAction> {
+    let mut advance: BTreeMap<ContactId, u64> = BTreeMap::new();
+    for intent in pending.values() {
+        let seq = advance.entry(intent.contact_id()).or_insert(0);
+        *seq = (*seq).max(intent.min_next_sequence());
+    }
+
+    let mut actions: Vec<RecoveryAction> = advance
+        .into_iter()
+        .map(
+            |(contact_id, next_sequence)| RecoveryAction::AdvanceRatchet {
+                contact_id,
+                next_sequence,
+            },
+        )
+        .collect();
+
+    for (&intent_id, intent) in pending {
+        actions.push(match intent {
+            Intent::SendMessage {
+                contact_id,
+                message_id,
+                ciphertext,
+                ..
+            } => RecoveryAction::Resend {
+                intent_id,
+                contact_id: *contact_id,
+                message_id: message_id.clone(),
+                ciphertext: ciphertext.clone(),
+            },
+            Intent::CommitRatchet {
+                contact_id,
+                message_id,
+                ..
+            } => RecoveryAction::MarkDelivered {
+                intent_id,
+                contact_id: *contact_id,
+                message_id: message_id.clone(),
+            },
+        });
+    }
+    actions
+}
+
+/// Evolve a persisted send chain from `current_sequence` up to
+/// `target_sequence`. Returns the chain unchanged if it is already there.
+pub fn fast_forward_chain(
+    chain_key: [u8; 32],
+    current_sequence: u64,
+    target_sequence: u64,
+) -> Result<([u8; 32], u64), IntentLogError> {
+    let mut key = chain_key;
+    let mut seq = current_sequence;
+    while seq < target_sequence {
+        key = evolve_chain_key(&mut key)?;
+        seq += 1;
+    }
+    Ok((key, seq))
+}
+
+// ---------------------------------------------------------------------------
+// Log
+// ---------------------------------------------------------------------------
+
+/// Write-ahead intent log over an app-provided store.
+pub struct IntentLog<S: IntentStore> {
+    store: S,
+    pending: BTreeMap<u64, Intent>,
+    next_id: u64,
+}
+
+impl<S: IntentStore> IntentLog<S> {
+    /// Open the log and compute recovery for intents left by a previous run.
+    ///
+    /// Call once at startup, and apply the returned actions before sending.
+    /// A send intent superseded by its own commit intent (crash inside
+    /// [`acknowledge`](Self::acknowledge)) is dropped here rather than resent.
+    pub fn open(mut store: S) -> Result<(Self, Vec<RecoveryAction>), IntentLogError> {
+        let mut pending = BTreeMap::new();
+        for record in store.load_all()? {
+            pending.insert(record.id, record.intent);
+        }
+
+        let acked: Vec<(ContactId, String)> = pending
+            .values()
+            .filter_map(|i| match i {
+                Intent::CommitRatchet {
+                    contact_id,
+                    message_id,
+                    ..
+                } => Some((*contact_id, message_id.clone())),
+                _ => None,
+            })
+            .collect();
+        let superseded: Vec<u64> = pending
+            .iter()
+            .filter_map(|(id, i)| match i {
+                Intent::SendMessage {
+                    contact_id,
+                    message_id,
+                    ..
+                } if acked.contains(&(*contact_id, message_id.clone())) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        for id in superseded {
+            store.remove(id)?;
+            pending.remove(&id);
+        }
+
+        if !pending.is_empty() {
+            log::warn!("Intent log: recovering {} pending intents", pending.len());
+        }
+        let actions = plan_recovery(&pending);
+        let next_id = pending.keys().next_back().map_or(1, |id| id + 1);
+        Ok((
+            Self {
+                store,
+                pending,
+                next_id,
+            },
+            actions,
+        ))
+    }
+
+    /// Durably record an intent. Perform the guarded action only after this
+    /// returns `Ok`.
+    pub fn begin(&mut self, intent: Intent) -> Result<u64, IntentLogError> {
+        let id = self.next_id;
+        let record = IntentRecord {
+            id,
+            intent,
+            created_at: now_secs(),
+        };
+        self.store.append(&record)?;
+        self.next_id += 1;
+        self.pending.insert(id, record.intent);
+        Ok(id)
+    }
+
+    /// The guarded action's effect is durable; drop the intent.
+    pub fn complete(&mut self, id: u64) -> Result<(), IntentLogError> {
+        if !self.pending.contains_key(&id) {
+            return Err(IntentLogError::UnknownIntent(id));
+        }
+        self.store.remove(id)?;
+        self.pending.remove(&id);
+        Ok(())
+    }
+
+    /// The peer ACKed a pending send: replace it with a `CommitRatchet`
+    /// intent and return that intent's id. Complete it once the advanced
+    /// chain state is persisted.
+    pub fn acknowledge(&mut self, send_id: u64) -> Result<u64, IntentLogError> {
+        let (contact_id, message_id, sequence) = match self.pending.get(&send_id) {
+            Some(Intent::SendMessage {
+                contact_id,
+                message_id,
+                sequence,
+                ..
+            }) => (*contact_id, message_id.clone(), *sequence),
+            Some(_) => return Err(IntentLogError::NotASend(send_id)),
+            None => return Err(IntentLogError::UnknownIntent(send_id)),
+        };
+        // Commit first: a crash in between leaves both, which `open` resolves.
+        let commit_id = self.begin(Intent::CommitRatchet {
+            contact_id,
+            message_id,
+            next_sequence: sequence + 1,
+        })?;
+        self.complete(send_id)?;
+        Ok(commit_id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Intent> {
+        self.pending.get(&id)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    fn send(contact_id: ContactId, msg: &str, sequence: u64) -> Intent {
+        Intent::SendMessage {
+            contact_id,
+            message_id: msg.into(),
+            sequence,
+            ciphertext: vec![sequence as u8; 8],
+        }
+    }
+
+    #[test]
+    fn test_crash_before_ack_resends_same_ciphertext() {
+        let (mut log, actions) = IntentLog::open(MemoryIntentStore::new()).unwrap();
+        assert!(actions.is_empty());
+        log.begin(send(test_contact(1), "m1", 4)).unwrap();
+        log.begin(send(test_contact(1), "m2", 5)).unwrap();
+
+        // Crash: reopen from the same store.
+        let (log, actions) = IntentLog::open(log.into_store()).unwrap();
+        assert_eq!(log.pending_count(), 2);
+        assert_eq!(
+            actions[0],
+            RecoveryAction::AdvanceRatchet {
+                contact_id: test_contact(1),
+                next_sequence: 6,
+            }
+        );
+        match &actions[1] {
+            RecoveryAction::Resend {
+                message_id,
+                ciphertext,
+                ..
+            } => {
+                assert_eq!(message_id, "m1");
+                assert_eq!(ciphertext, &vec![4u8; 8]);
+            }
+            other => panic!("expected resend, got {:?}", other),
+        }
+        assert_eq!(actions.len(), 3);
+    }
+
+    #[test]
+    fn test_ack_then_crash_marks_delivered() {
+        let (mut log, _) = IntentLog::open(MemoryIntentStore::new()).unwrap();
+        let send_id = log.begin(send(test_contact(2), "m1", 0)).unwrap();
+        let commit_id = log.acknowledge(send_id).unwrap();
+        assert!(matches!(
+            log.acknowledge(commit_id),
+            Err(IntentLogError::NotASend(_))
+        ));
+
+        let (mut log, actions) = IntentLog::open(log.into_store()).unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                RecoveryAction::AdvanceRatchet {
+                    contact_id: test_contact(2),
+                    next_sequence: 1,
+                },
+                RecoveryAction::MarkDelivered {
+                    intent_id: commit_id,
+                    contact_id: test_contact(2),
+                    message_id: "m1".into(),
+                },
+            ]
+        );
+        log.complete(commit_id).unwrap();
+        assert!(matches!(
+            log.complete(commit_id),
+            Err(IntentLogError::UnknownIntent(_))
+        ));
+
+        let (log, actions) = IntentLog::open(log.into_store()).unwrap();
+        assert!(actions.is_empty());
+        assert_eq!(log.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_crash_inside_acknowledge_does_not_resend() {
+        let mut store = MemoryIntentStore::new();
+        for (id, intent) in [
+            (1, send(test_contact(3), "m1", 9)),
+            (
+                2,
+                Intent::CommitRatchet {
+                    contact_id: test_contact(3),
+                    message_id: "m1".into(),
+                    next_sequence: 10,
+                },
+            ),
+        ] {
+            let record = IntentRecord {
+                id,
+                intent,
+                created_at: 0,
+            };
+            let bytes = record.to_bytes().unwrap();
+            store
+                .append(&IntentRecord::from_bytes(&bytes).unwrap())
+                .unwrap();
+        }
+
+        let (log, actions) = IntentLog::open(store).unwrap();
+        assert_eq!(log.pending_count(), 1);
+        assert!(!actions
+            .iter()
+            .any(|a| matches!(a, RecoveryAction::Resend { .. })));
+        assert_eq!(log.into_store().load_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_file_store_survives_reopen() {
+        let dir = std::env::temp_dir().join(format!("intents-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = FileIntentStore::open(&dir, &[4u8; 32]).unwrap();
+        let (mut log, _) = IntentLog::open(store).unwrap();
+        let done = log.begin(send(test_contact(4), "m1", 0)).unwrap();
+        log.begin(send(test_contact(4), "m2", 1)).unwrap();
+        log.complete(done).unwrap();
+        drop(log);
+        fs::write(dir.join("0000000000000009.tmp"), b"torn").unwrap();
+
+        let store = FileIntentStore::open(&dir, &[4u8; 32]).unwrap();
+        let (log, actions) = IntentLog::open(store).unwrap();
+        assert_eq!(log.pending_count(), 1);
+        assert!(matches!(
+            &actions[1],
+            RecoveryAction::Resend { message_id, .. } if message_id == "m2"
+        ));
+
+        let wrong = FileIntentStore::open(&dir, &[5u8; 32]).unwrap();
+        assert!(matches!(
+            IntentLog::open(wrong),
+            Err(IntentLogError::Storage(StorageError::DecryptionFailed))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fast_forward_chain() {
+        let start = [7u8; 32];
+        let (same, seq) = fast_forward_chain(start, 5, 3).unwrap();
+        assert_eq!((same, seq), (start, 5));
+
+        let (ff, seq) = fast_forward_chain(start, 5, 7).unwrap();
+        let mut expected = start;
+        expected = evolve_chain_key(&mut expected).unwrap();
+        expected = evolve_chain_key(&mut expected).unwrap();
+        assert_eq!((ff, seq), (expected, 7));
+    }
+}
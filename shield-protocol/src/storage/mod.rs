@@ -8,11 +8,66 @@
 //! 2. **Duress PIN:** A second PIN that, when entered, wipes real data and presents a
 //!    plausible fake database (decoy conversations and contacts).
 //! 3. **Stealth mode:** Optional app-layer behavior to hide the app icon after duress.
+//!
+//! It also hosts the write-ahead [`intent_log`] used to recover protocol state
+//! (ratchet position, unsent ciphertexts) after a crash, and the [`archive`]
+//! that compacts old messages into detachable encrypted segments. Per-conversation
+//! storage keys are derived by [`compartment`], and [`retention`] keeps
+//! encrypted attachment blobs within disk quotas. [`trust_file`] is a
+//! file-backed [`ContactTrustStore`] for builds without SQLCipher, and
+//! [`stats`] computes per-conversation usage figures, optionally noised.
+//! [`versioned`] puts a format version on persisted state and migrates
+//! blobs written by older builds.
 
-use getrandom::getrandom;
 use std::fmt;
 use thiserror::Error;
 
+use crate::protocol::silence::SilenceSpec;
+#[cfg(feature = "decoys")]
+use crate::rng::{OsRng, SecureRng};
+
+pub mod archive;
+pub mod compartment;
+#[cfg(feature = "decoys")]
+pub mod decoy_liveness;
+#[cfg(feature = "decoys")]
+pub mod decoy_locale;
+#[cfg(feature = "decoys")]
+pub mod decoy_refresh;
+pub mod intent_log;
+pub mod retention;
+pub mod stats;
+pub mod trust_file;
+pub mod versioned;
+
+pub use archive::{
+    segment_id_of, Archive, ArchiveConfig, ArchiveError, ArchiveKey, ArchiveStore, ArchivedMessage,
+    IndexStub, MemoryArchiveStore, Resolved, SearchToken, SegmentId, SegmentMeta,
+};
+pub use compartment::{
+    Compartment, CompartmentError, CompartmentKey, CompartmentKeys, CompartmentStore,
+    MemoryCompartmentStore, Migrated, StorageMasterKey,
+};
+#[cfg(feature = "decoys")]
+pub use decoy_liveness::{DecoyDelivery, DecoyDeliveryState};
+#[cfg(feature = "decoys")]
+pub use decoy_locale::DecoyLocale;
+#[cfg(feature = "decoys")]
+pub use decoy_refresh::{
+    apply_decoy_activity, generate_decoy_activity, next_refresh_at, DecoyRefreshSpec, DecoyUpdate,
+};
+pub use intent_log::{
+    fast_forward_chain, FileIntentStore, Intent, IntentLog, IntentLogError, IntentRecord,
+    IntentStore, MemoryIntentStore, RecoveryAction,
+};
+pub use retention::{
+    AttachmentStore, BlobId, BlobRecord, BlobStatus, MemoryAttachmentStore, RedownloadHint,
+    RetentionError, RetentionManager, RetentionPolicy, RetentionUsage,
+};
+pub use stats::{ConversationStats, MessageSample, NoiseConfig, StatsError};
+pub use trust_file::FileTrustStore;
+pub use versioned::{Persisted, StateKind, VersionError, Versioned};
+
 // ---------------------------------------------------------------------------
 // Errors
 // ---------------------------------------------------------------------------
@@ -45,16 +100,18 @@ pub trait DeniableStorage {
 // ---------------------------------------------------------------------------
 
 use crate::crypto::pqc::ContactVerificationRecord;
+use crate::protocol::contact_id::ContactId;
 
 /// Contract for persisting contact verification / trust-level state.
 ///
 /// The application layer (SQLCipher on mobile, IndexedDB on web) MUST implement
-/// this trait so that trust levels survive app restarts.
+/// this trait so that trust levels survive app restarts. Desktop builds and
+/// tests can use [`FileTrustStore`].
 ///
 /// Schema hint for SQLCipher:
 /// ```sql
 /// CREATE TABLE IF NOT EXISTS contact_trust (
-///   contact_id    TEXT PRIMARY KEY,
+///   contact_id    TEXT PRIMARY KEY,  -- ContactId string form (sl_…)
 ///   trust_level   INTEGER NOT NULL DEFAULT 1,
 ///   verified_at   INTEGER NOT NULL DEFAULT 0,
 ///   safety_number TEXT NOT NULL DEFAULT ''
@@ -63,16 +120,27 @@ use crate::crypto::pqc::ContactVerificationRecord;
 pub trait ContactTrustStore {
     /// Load the verification record for a contact. Returns `None` if the
     /// contact has never been seen (i.e. brand-new / Level 0).
-    fn load_trust(&self, contact_id: &str) -> Result<Option<ContactVerificationRecord>>;
+    fn load_trust(&self, contact_id: &ContactId) -> Result<Option<ContactVerificationRecord>>;
 
     /// Persist (insert or update) a contact's verification record.
     fn save_trust(&mut self, record: &ContactVerificationRecord) -> Result<()>;
 
     /// Delete the verification record (e.g. when removing a contact).
-    fn delete_trust(&mut self, contact_id: &str) -> Result<()>;
+    fn delete_trust(&mut self, contact_id: &ContactId) -> Result<()>;
 
     /// List all contacts that have been verified (Level 2).
     fn list_verified(&self) -> Result<Vec<ContactVerificationRecord>>;
+
+    /// Delete every record (panic wipe); returns how many were deleted.
+    /// The default only reaches what `list_verified` returns, so stores
+    /// that also hold unverified records must override it.
+    fn delete_all(&mut self) -> Result<usize> {
+        let verified = self.list_verified()?;
+        for record in &verified {
+            self.delete_trust(&record.contact_id)?;
+        }
+        Ok(verified.len())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -82,7 +150,8 @@ pub trait ContactTrustStore {
 /// Duress PIN behavior:
 /// 1. App MUST immediately wipe all real data (messages, keys, contacts).
 /// 2. App MUST call `on_duress_pin_entered()` so core clears in-memory state.
-/// 3. App MAY populate a decoy database (see `DecoyGenerator`).
+/// 3. App MAY populate a decoy database (see `DecoyGenerator`) and keep it
+///    fresh on the `decoy_refresh` schedule (see [`decoy_refresh`]).
 /// 4. App MAY activate stealth mode (see `StealthModeSpec`).
 /// 5. Real encryption key MUST be zeroized from memory — never persisted after wipe.
 #[derive(Debug, Clone)]
@@ -90,7 +159,11 @@ pub struct DuressPinSpec {
     pub show_plausible_fake: bool,
     pub fake_db_path: Option<String>,
     pub stealth_mode: StealthModeSpec,
+    #[cfg(feature = "decoys")]
     pub decoy_config: DecoyConfig,
+    /// `None` leaves the decoy database as generated.
+    #[cfg(feature = "decoys")]
+    pub decoy_refresh: Option<DecoyRefreshSpec>,
 }
 
 impl Default for DuressPinSpec {
@@ -99,19 +172,24 @@ impl Default for DuressPinSpec {
             show_plausible_fake: true,
             fake_db_path: None,
             stealth_mode: StealthModeSpec::default(),
+            #[cfg(feature = "decoys")]
             decoy_config: DecoyConfig::default(),
+            #[cfg(feature = "decoys")]
+            decoy_refresh: Some(DecoyRefreshSpec::default()),
         }
     }
 }
 
 impl fmt::Display for DuressPinSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "decoys")]
+        let decoy_contacts = self.decoy_config.contact_count;
+        #[cfg(not(feature = "decoys"))]
+        let decoy_contacts = 0;
         write!(
             f,
             "DuressPinSpec(show_fake={}, stealth={}, decoy_contacts={})",
-            self.show_plausible_fake,
-            self.stealth_mode.hide_app_icon,
-            self.decoy_config.contact_count
+            self.show_plausible_fake, self.stealth_mode.hide_app_icon, decoy_contacts
         )
     }
 }
@@ -121,20 +199,27 @@ impl fmt::Display for DuressPinSpec {
 // ---------------------------------------------------------------------------
 
 /// Stealth mode: optionally hide the app icon / launcher alias after duress.
-/// Implementation is app-layer (e.g. Android ComponentName disable, iOS app clip).
+/// Implementation is app-layer (e.g. Android ComponentName disable, iOS app clip),
+/// except network silence, which the core enforces.
 #[derive(Debug, Clone, Default)]
 pub struct StealthModeSpec {
     /// If true, the app should hide its launcher icon after duress.
     pub hide_app_icon: bool,
     /// Optional: alias component name for Android launcher icon toggle.
     pub launcher_alias: Option<String>,
+    /// If set, the app puts the core into network silence (see
+    /// [`NetworkSilence`](crate::protocol::silence::NetworkSilence)): no
+    /// cover, receipts or presence, no replies to inbound connections, until
+    /// the designated contact sends a wake message.
+    pub network_silence: Option<SilenceSpec>,
 }
 
 // ---------------------------------------------------------------------------
-// Decoy database generation
+// Decoy database generation (feature `decoys`)
 // ---------------------------------------------------------------------------
 
 /// Configuration for generating plausible decoy data.
+#[cfg(feature = "decoys")]
 #[derive(Debug, Clone)]
 pub struct DecoyConfig {
     /// Number of fake contacts to generate.
@@ -145,8 +230,12 @@ pub struct DecoyConfig {
     pub min_message_len: u16,
     /// Maximum message length (bytes) for fake messages.
     pub max_message_len: u16,
+    /// Language and region of contact names and message text. Apps should
+    /// pass the device locale (see [`DecoyLocale::from_tag`]).
+    pub locale: DecoyLocale,
 }
 
+#[cfg(feature = "decoys")]
 impl Default for DecoyConfig {
     fn default() -> Self {
         Self {
@@ -154,11 +243,13 @@ impl Default for DecoyConfig {
             messages_per_contact: 20,
             min_message_len: 10,
             max_message_len: 200,
+            locale: DecoyLocale::default(),
         }
     }
 }
 
 /// A single decoy contact for the fake database.
+#[cfg(feature = "decoys")]
 #[derive(Debug, Clone)]
 pub struct DecoyContact {
     pub display_name: String,
@@ -169,49 +260,74 @@ pub struct DecoyContact {
 }
 
 /// A single decoy message.
+#[cfg(feature = "decoys")]
 #[derive(Debug, Clone)]
 pub struct DecoyMessage {
-    /// Random realistic-looking content (base64-ish).
+    /// Everyday chat phrases in the configured locale.
     pub content: String,
     /// Unix timestamp (randomized within last 7 days).
     pub timestamp: i64,
     /// true = "sent by us", false = "received"
     pub is_outgoing: bool,
+    /// Ping / Pong / ACK record the app stores alongside the message.
+    pub delivery: DecoyDelivery,
+}
+
+#[cfg(feature = "decoys")]
+impl DecoyContact {
+    /// When the contact was last heard from (their latest ping, pong or
+    /// ACK), for the contact's "last seen" column.
+    pub fn last_seen(&self) -> Option<i64> {
+        self.messages
+            .iter()
+            .filter_map(|m| m.delivery.contact_seen_at())
+            .max()
+    }
 }
 
 /// Generate a set of decoy contacts and messages that look plausible.
 /// The app should insert these into the (new, empty) SQLCipher DB after wiping the real one.
+#[cfg(feature = "decoys")]
 pub fn generate_decoy_data(config: &DecoyConfig) -> Vec<DecoyContact> {
+    generate_decoy_data_with_rng(config, &mut OsRng)
+}
+
+/// [`generate_decoy_data`] drawing from `rng`. Timestamps are still relative
+/// to the current time.
+#[cfg(feature = "decoys")]
+pub fn generate_decoy_data_with_rng(
+    config: &DecoyConfig,
+    rng: &mut impl SecureRng,
+) -> Vec<DecoyContact> {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs() as i64;
 
-    let names = [
-        "Alex", "Sam", "Jordan", "Taylor", "Morgan", "Casey", "Quinn", "Riley", "Avery", "Jamie",
-        "Charlie", "Dakota", "Emery", "Finley", "Harper",
-    ];
-
     let mut contacts = Vec::with_capacity(config.contact_count as usize);
 
     for i in 0..config.contact_count {
-        let name_idx = (i as usize) % names.len();
-        let display_name = format!("{} {}", names[name_idx], random_suffix());
+        let display_name = decoy_locale::contact_name(config.locale, i as usize, rng);
 
-        let onion_address = generate_fake_onion();
+        let onion_address = generate_fake_onion(rng);
 
         let mut messages = Vec::with_capacity(config.messages_per_contact as usize);
         for _ in 0..config.messages_per_contact {
-            let ts_offset = random_range(0, 7 * 24 * 3600);
+            let ts_offset = random_range(rng, 0, 7 * 24 * 3600);
             let timestamp = now - ts_offset as i64;
-            let msg_len =
-                random_range(config.min_message_len as u32, config.max_message_len as u32) as usize;
-            let content = random_text(msg_len);
-            let is_outgoing = random_bool();
+            let content = decoy_locale::message_text(
+                config.locale,
+                config.min_message_len as usize,
+                config.max_message_len as usize,
+                rng,
+            );
+            let is_outgoing = random_bool(rng);
+            let delivery = DecoyDelivery::generate(timestamp, is_outgoing, now, rng);
             messages.push(DecoyMessage {
                 content,
                 timestamp,
                 is_outgoing,
+                delivery,
             });
         }
 
@@ -245,47 +361,35 @@ pub fn on_duress_pin_entered() -> Result<()> {
 }
 
 // ---------------------------------------------------------------------------
-// Random helpers (deterministic from getrandom, no external dependency)
+// Random helpers
 // ---------------------------------------------------------------------------
 
-fn random_range(min: u32, max: u32) -> u32 {
+#[cfg(feature = "decoys")]
+fn random_range(rng: &mut impl SecureRng, min: u32, max: u32) -> u32 {
     if min >= max {
         return min;
     }
     let mut buf = [0u8; 4];
-    let _ = getrandom(&mut buf);
+    let _ = rng.try_fill_bytes(&mut buf);
     let v = u32::from_ne_bytes(buf);
     min + (v % (max - min))
 }
 
-fn random_bool() -> bool {
+#[cfg(feature = "decoys")]
+fn random_bool(rng: &mut impl SecureRng) -> bool {
     let mut buf = [0u8; 1];
-    let _ = getrandom(&mut buf);
+    let _ = rng.try_fill_bytes(&mut buf);
     buf[0] & 1 == 1
 }
 
-fn random_suffix() -> String {
-    let mut buf = [0u8; 2];
-    let _ = getrandom(&mut buf);
-    format!("{:02x}{:02x}", buf[0], buf[1])
-}
-
-fn generate_fake_onion() -> String {
+#[cfg(feature = "decoys")]
+fn generate_fake_onion(rng: &mut impl SecureRng) -> String {
     let mut buf = [0u8; 35];
-    let _ = getrandom(&mut buf);
+    let _ = rng.try_fill_bytes(&mut buf);
     let encoded = base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &buf).to_lowercase();
     format!("{}.onion", encoded)
 }
 
-fn random_text(len: usize) -> String {
-    let charset = b"abcdefghijklmnopqrstuvwxyz .!?,";
-    let mut buf = vec![0u8; len];
-    let _ = getrandom(&mut buf);
-    buf.iter()
-        .map(|b| charset[(*b as usize) % charset.len()] as char)
-        .collect()
-}
-
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -299,16 +403,23 @@ mod tests {
         let spec = DuressPinSpec::default();
         assert!(spec.show_plausible_fake);
         assert!(!spec.stealth_mode.hide_app_icon);
-        assert_eq!(spec.decoy_config.contact_count, 5);
+        assert!(spec.stealth_mode.network_silence.is_none());
+        #[cfg(feature = "decoys")]
+        {
+            assert_eq!(spec.decoy_config.contact_count, 5);
+            assert!(spec.decoy_refresh.is_some());
+        }
     }
 
     #[test]
+    #[cfg(feature = "decoys")]
     fn test_generate_decoy_data() {
         let config = DecoyConfig {
             contact_count: 3,
             messages_per_contact: 5,
             min_message_len: 10,
             max_message_len: 50,
+            ..DecoyConfig::default()
         };
         let contacts = generate_decoy_data(&config);
         assert_eq!(contacts.len(), 3);
@@ -328,12 +439,48 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "decoys")]
+    fn test_localized_decoys() {
+        let config = DecoyConfig {
+            locale: DecoyLocale::Russian,
+            ..DecoyConfig::default()
+        };
+        let contacts = generate_decoy_data_with_rng(&config, &mut crate::rng::seeded(4));
+        let cyrillic = |s: &str| s.chars().any(|c| ('\u{0400}'..='\u{04FF}').contains(&c));
+        for c in &contacts {
+            assert!(cyrillic(&c.display_name));
+            assert!(c.messages.iter().all(|m| cyrillic(&m.content)));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "decoys")]
     fn test_generate_fake_onion() {
-        let onion = generate_fake_onion();
+        let onion = generate_fake_onion(&mut OsRng);
         assert!(onion.ends_with(".onion"));
         assert!(onion.len() > 20);
     }
 
+    #[test]
+    #[cfg(feature = "decoys")]
+    fn test_seeded_decoys_reproducible() {
+        let config = DecoyConfig::default();
+        let a = generate_decoy_data_with_rng(&config, &mut crate::rng::seeded(3));
+        let b = generate_decoy_data_with_rng(&config, &mut crate::rng::seeded(3));
+        let names = |c: &[DecoyContact]| -> Vec<String> {
+            c.iter()
+                .map(|d| format!("{} {}", d.display_name, d.onion_address))
+                .collect()
+        };
+        assert_eq!(names(&a), names(&b));
+        let content = |c: &[DecoyContact]| -> Vec<String> {
+            c.iter()
+                .flat_map(|d| d.messages.iter().map(|m| m.content.clone()))
+                .collect()
+        };
+        assert_eq!(content(&a), content(&b));
+    }
+
     #[test]
     fn test_on_duress_pin() {
         assert!(on_duress_pin_entered().is_ok());
@@ -0,0 +1,520 @@
+//! Disk-space-aware retention of encrypted attachment blobs.
+//!
+//! Attachments dominate an app's disk usage, and every platform used to grow
+//! its own cache policy. The [`RetentionManager`] keeps one: the app records
+//! each encrypted blob it writes (size, conversation, and an opaque
+//! *locator* telling it how to fetch the blob again) and touches the record
+//! whenever a message referencing the blob is shown, forwarded or replied to.
+//! [`RetentionManager::enforce`] then evicts blobs until
+//!
+//! - every conversation is within [`RetentionPolicy::per_conversation_bytes`],
+//! - all blobs together are within [`RetentionPolicy::global_bytes`], and
+//! - at least [`RetentionPolicy::min_free_bytes`] of the device stay free,
+//!
+//! least-recently-referenced first. Pinned blobs (saved to the gallery,
+//! starred, still uploading) are never evicted.
+//!
+//! An evicted blob keeps its record, marked absent. When the UI asks for it
+//! again, [`RetentionManager::lookup`] returns a [`RedownloadHint`] with the
+//! locator, so the app can show a "tap to download" placeholder instead of a
+//! broken attachment. Locators usually carry the attachment key; they live in
+//! the encrypted store next to the record and never leave the device.
+//!
+//! Persistence is app-provided through [`AttachmentStore`] (same model as
+//! [`ArchiveStore`](super::ArchiveStore)); [`MemoryAttachmentStore`] is
+//! provided for tests.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use thiserror::Error;
+
+use super::StorageError;
+use crate::protocol::contact_id::ContactId;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum RetentionError {
+    #[error("Attachment store error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Attachment record encoding error: {0}")]
+    Encoding(String),
+
+    #[error("Unknown attachment blob {0}")]
+    UnknownBlob(BlobId),
+
+    #[error("Invalid retention policy")]
+    InvalidPolicy,
+}
+
+// ---------------------------------------------------------------------------
+// Records
+// ---------------------------------------------------------------------------
+
+/// Identifier of an encrypted attachment blob, e.g. the hash of its
+/// ciphertext.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlobId(pub [u8; 32]);
+
+impl fmt::Display for BlobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for BlobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BlobId({})", self)
+    }
+}
+
+/// What the store knows about one attachment blob.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlobRecord {
+    pub blob_id: BlobId,
+    pub contact_id: ContactId,
+    /// Size of the encrypted blob on disk, in bytes.
+    pub size: u64,
+    /// Unix millis of the last reference (receive, view, forward, reply).
+    pub last_referenced_at: i64,
+    /// Never evicted while set.
+    pub pinned: bool,
+    /// `false` once evicted; the record stays for the re-download hint.
+    pub present: bool,
+    /// App-defined bytes for fetching the blob again (relay pointer, URL,
+    /// attachment key). Opaque to the manager.
+    pub locator: Vec<u8>,
+}
+
+impl BlobRecord {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RetentionError> {
+        bincode::serialize(self).map_err(|e| RetentionError::Encoding(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RetentionError> {
+        bincode::deserialize(bytes).map_err(|e| RetentionError::Encoding(e.to_string()))
+    }
+
+    fn hint(&self) -> RedownloadHint {
+        RedownloadHint {
+            blob_id: self.blob_id,
+            contact_id: self.contact_id,
+            size: self.size,
+            locator: self.locator.clone(),
+        }
+    }
+}
+
+/// Enough to offer "tap to download" for an evicted blob.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedownloadHint {
+    pub blob_id: BlobId,
+    pub contact_id: ContactId,
+    pub size: u64,
+    pub locator: Vec<u8>,
+}
+
+/// Result of [`RetentionManager::lookup`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlobStatus {
+    /// The blob is on disk; its reference time has been refreshed.
+    Present,
+    /// The blob was evicted and has to be fetched again.
+    Evicted(RedownloadHint),
+    /// Never recorded, or forgotten.
+    Unknown,
+}
+
+/// Bytes on disk, in total and per conversation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetentionUsage {
+    pub total_bytes: u64,
+    pub per_conversation: BTreeMap<ContactId, u64>,
+}
+
+impl RetentionUsage {
+    fn of(records: &[BlobRecord]) -> Self {
+        let mut usage = Self::default();
+        for record in records {
+            usage.total_bytes += record.size;
+            *usage.per_conversation.entry(record.contact_id).or_default() += record.size;
+        }
+        usage
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Store
+// ---------------------------------------------------------------------------
+
+/// Persistence for blob records and the blobs themselves.
+///
+/// Schema hint for SQLCipher:
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS attachment_blobs (
+///   blob_id    BLOB PRIMARY KEY,
+///   contact_id TEXT NOT NULL,
+///   record     BLOB NOT NULL   -- BlobRecord::to_bytes()
+/// );
+/// ```
+/// The encrypted blobs themselves usually live in files named by blob id.
+pub trait AttachmentStore {
+    /// Insert or replace a record, keyed by blob id.
+    fn save_record(&mut self, record: &BlobRecord) -> super::Result<()>;
+    fn load_record(&self, id: &BlobId) -> super::Result<Option<BlobRecord>>;
+    /// All records, present or not, in any order.
+    fn list_records(&self) -> super::Result<Vec<BlobRecord>>;
+    fn delete_record(&mut self, id: &BlobId) -> super::Result<()>;
+    /// Delete the blob's bytes from disk. Deleting a missing blob is not an
+    /// error.
+    fn delete_blob(&mut self, id: &BlobId) -> super::Result<()>;
+}
+
+/// Non-durable store for tests; tracks which blobs have been deleted.
+#[derive(Debug, Default)]
+pub struct MemoryAttachmentStore {
+    records: BTreeMap<BlobId, BlobRecord>,
+    deleted: BTreeSet<BlobId>,
+}
+
+impl MemoryAttachmentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether [`AttachmentStore::delete_blob`] was called for `id`.
+    pub fn was_deleted(&self, id: &BlobId) -> bool {
+        self.deleted.contains(id)
+    }
+}
+
+impl AttachmentStore for MemoryAttachmentStore {
+    fn save_record(&mut self, record: &BlobRecord) -> super::Result<()> {
+        self.records.insert(record.blob_id, record.clone());
+        Ok(())
+    }
+
+    fn load_record(&self, id: &BlobId) -> super::Result<Option<BlobRecord>> {
+        Ok(self.records.get(id).cloned())
+    }
+
+    fn list_records(&self) -> super::Result<Vec<BlobRecord>> {
+        Ok(self.records.values().cloned().collect())
+    }
+
+    fn delete_record(&mut self, id: &BlobId) -> super::Result<()> {
+        self.records.remove(id);
+        Ok(())
+    }
+
+    fn delete_blob(&mut self, id: &BlobId) -> super::Result<()> {
+        self.deleted.insert(*id);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Manager
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Upper bound on all attachment blobs together.
+    pub global_bytes: u64,
+    /// Upper bound on the blobs of any one conversation.
+    pub per_conversation_bytes: u64,
+    /// Free space to leave on the device; shrinks the effective global
+    /// bound when the disk fills up.
+    pub min_free_bytes: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            global_bytes: 2 * 1024 * 1024 * 1024,
+            per_conversation_bytes: 512 * 1024 * 1024,
+            min_free_bytes: 500 * 1024 * 1024,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn validate(&self) -> Result<(), RetentionError> {
+        if self.global_bytes == 0 || self.per_conversation_bytes == 0 {
+            return Err(RetentionError::InvalidPolicy);
+        }
+        Ok(())
+    }
+
+    /// Global bound given `free_bytes` currently free on the device and
+    /// `used` bytes held by attachments.
+    fn effective_global(&self, used: u64, free_bytes: Option<u64>) -> u64 {
+        match free_bytes {
+            Some(free) => {
+                let reachable = (used + free).saturating_sub(self.min_free_bytes);
+                self.global_bytes.min(reachable)
+            }
+            None => self.global_bytes,
+        }
+    }
+}
+
+/// Attachment retention over an app-provided store.
+pub struct RetentionManager<S: AttachmentStore> {
+    store: S,
+    policy: RetentionPolicy,
+}
+
+impl<S: AttachmentStore> RetentionManager<S> {
+    pub fn new(store: S, policy: RetentionPolicy) -> Result<Self, RetentionError> {
+        policy.validate()?;
+        Ok(Self { store, policy })
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RetentionPolicy) -> Result<(), RetentionError> {
+        policy.validate()?;
+        self.policy = policy;
+        Ok(())
+    }
+
+    /// Record a blob just written to disk (received, downloaded again, or
+    /// sent). Replaces any earlier record of the same blob, keeping its pin.
+    pub fn record(
+        &mut self,
+        blob_id: BlobId,
+        contact_id: ContactId,
+        size: u64,
+        locator: Vec<u8>,
+        now: i64,
+    ) -> Result<(), RetentionError> {
+        let pinned = self.store.load_record(&blob_id)?.is_some_and(|r| r.pinned);
+        self.store.save_record(&BlobRecord {
+            blob_id,
+            contact_id,
+            size,
+            last_referenced_at: now,
+            pinned,
+            present: true,
+            locator,
+        })?;
+        Ok(())
+    }
+
+    /// Note a reference to the blob (shown, forwarded, replied to) and say
+    /// whether it is still on disk.
+    pub fn lookup(&mut self, blob_id: &BlobId, now: i64) -> Result<BlobStatus, RetentionError> {
+        let Some(mut record) = self.store.load_record(blob_id)? else {
+            return Ok(BlobStatus::Unknown);
+        };
+        if !record.present {
+            return Ok(BlobStatus::Evicted(record.hint()));
+        }
+        if now > record.last_referenced_at {
+            record.last_referenced_at = now;
+            self.store.save_record(&record)?;
+        }
+        Ok(BlobStatus::Present)
+    }
+
+    pub fn set_pinned(&mut self, blob_id: &BlobId, pinned: bool) -> Result<(), RetentionError> {
+        let mut record = self
+            .store
+            .load_record(blob_id)?
+            .ok_or(RetentionError::UnknownBlob(*blob_id))?;
+        record.pinned = pinned;
+        self.store.save_record(&record)?;
+        Ok(())
+    }
+
+    /// Delete a blob and its record, e.g. when its message is deleted.
+    pub fn forget(&mut self, blob_id: &BlobId) -> Result<(), RetentionError> {
+        self.store.delete_blob(blob_id)?;
+        self.store.delete_record(blob_id)?;
+        Ok(())
+    }
+
+    /// Bytes held by blobs currently on disk.
+    pub fn usage(&self) -> Result<RetentionUsage, RetentionError> {
+        let records: Vec<BlobRecord> = self
+            .store
+            .list_records()?
+            .into_iter()
+            .filter(|r| r.present)
+            .collect();
+        Ok(RetentionUsage::of(&records))
+    }
+
+    /// Evict blobs until the policy holds, least-recently-referenced first.
+    ///
+    /// `free_bytes` is the device's free space, if the platform reports it.
+    /// Returns a re-download hint per evicted blob, oldest reference first;
+    /// the app swaps the affected attachments for download placeholders.
+    /// Pinned blobs are kept even if that leaves a bound exceeded.
+    pub fn enforce(
+        &mut self,
+        free_bytes: Option<u64>,
+    ) -> Result<Vec<RedownloadHint>, RetentionError> {
+        let mut candidates: Vec<BlobRecord> = self
+            .store
+            .list_records()?
+            .into_iter()
+            .filter(|r| r.present)
+            .collect();
+        let mut usage = RetentionUsage::of(&candidates);
+        let global = self.policy.effective_global(usage.total_bytes, free_bytes);
+        candidates.retain(|r| !r.pinned);
+        candidates.sort_by_key(|r| (r.last_referenced_at, r.blob_id));
+
+        let mut evict = Vec::new();
+        let mut kept = Vec::with_capacity(candidates.len());
+        // Conversations over their own bound give up their oldest blobs first
+        for record in candidates {
+            let conversation = usage.per_conversation.entry(record.contact_id).or_default();
+            if *conversation > self.policy.per_conversation_bytes {
+                *conversation -= record.size;
+                usage.total_bytes -= record.size;
+                evict.push(record);
+            } else {
+                kept.push(record);
+            }
+        }
+        for record in kept {
+            if usage.total_bytes <= global {
+                break;
+            }
+            usage.total_bytes -= record.size;
+            evict.push(record);
+        }
+        evict.sort_by_key(|r| (r.last_referenced_at, r.blob_id));
+
+        let mut hints = Vec::with_capacity(evict.len());
+        for mut record in evict {
+            self.store.delete_blob(&record.blob_id)?;
+            record.present = false;
+            self.store.save_record(&record)?;
+            hints.push(record.hint());
+        }
+        if !hints.is_empty() {
+            log::info!(
+                "Retention: evicted {} attachment blobs, {} bytes kept",
+                hints.len(),
+                usage.total_bytes
+            );
+        }
+        Ok(hints)
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    const KB: u64 = 1024;
+
+    fn manager(policy: RetentionPolicy) -> RetentionManager<MemoryAttachmentStore> {
+        RetentionManager::new(MemoryAttachmentStore::new(), policy).unwrap()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_referenced_first() {
+        let mut m = manager(RetentionPolicy {
+            global_bytes: 30 * KB,
+            per_conversation_bytes: 100 * KB,
+            min_free_bytes: 0,
+        });
+        for i in 1..=4u8 {
+            m.record(
+                BlobId([i; 32]),
+                test_contact(i % 2),
+                10 * KB,
+                vec![i],
+                i as i64,
+            )
+            .unwrap();
+        }
+        // Blob 1 is viewed again, so blob 2 is now the oldest reference
+        assert_eq!(m.lookup(&BlobId([1; 32]), 10).unwrap(), BlobStatus::Present);
+
+        let hints = m.enforce(None).unwrap();
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].blob_id, BlobId([2; 32]));
+        assert_eq!(hints[0].locator, vec![2]);
+        assert_eq!(m.usage().unwrap().total_bytes, 30 * KB);
+
+        assert_eq!(
+            m.lookup(&BlobId([2; 32]), 11).unwrap(),
+            BlobStatus::Evicted(hints[0].clone())
+        );
+        assert_eq!(m.lookup(&BlobId([9; 32]), 11).unwrap(), BlobStatus::Unknown);
+        assert!(m.enforce(None).unwrap().is_empty());
+
+        // Low disk space shrinks the budget; pinned blobs survive it
+        m.set_pinned(&BlobId([3; 32]), true).unwrap();
+        m.set_policy(RetentionPolicy {
+            min_free_bytes: 20 * KB,
+            ..*m.policy()
+        })
+        .unwrap();
+        let hints = m.enforce(Some(5 * KB)).unwrap();
+        let evicted: Vec<BlobId> = hints.iter().map(|h| h.blob_id).collect();
+        assert_eq!(evicted, vec![BlobId([4; 32]), BlobId([1; 32])]);
+        assert_eq!(m.usage().unwrap().total_bytes, 10 * KB);
+        assert!(m.into_store().was_deleted(&BlobId([4; 32])));
+    }
+
+    #[test]
+    fn test_per_conversation_quota() {
+        let mut m = manager(RetentionPolicy {
+            global_bytes: 1024 * KB,
+            per_conversation_bytes: 20 * KB,
+            min_free_bytes: 0,
+        });
+        let (alice, bob) = (test_contact(1), test_contact(2));
+        for i in 0..4u8 {
+            m.record(BlobId([i; 32]), alice, 10 * KB, Vec::new(), i as i64)
+                .unwrap();
+        }
+        // Bob's blob is older than all of Alice's but within Bob's quota
+        m.record(BlobId([9; 32]), bob, 10 * KB, Vec::new(), -1)
+            .unwrap();
+
+        let hints = m.enforce(None).unwrap();
+        let evicted: Vec<BlobId> = hints.iter().map(|h| h.blob_id).collect();
+        assert_eq!(evicted, vec![BlobId([0; 32]), BlobId([1; 32])]);
+        let usage = m.usage().unwrap();
+        assert_eq!(usage.per_conversation[&alice], 20 * KB);
+        assert_eq!(usage.per_conversation[&bob], 10 * KB);
+
+        // Downloading again restores the blob
+        m.record(BlobId([0; 32]), alice, 10 * KB, Vec::new(), 50)
+            .unwrap();
+        assert_eq!(m.lookup(&BlobId([0; 32]), 51).unwrap(), BlobStatus::Present);
+        m.forget(&BlobId([0; 32])).unwrap();
+        assert_eq!(m.lookup(&BlobId([0; 32]), 52).unwrap(), BlobStatus::Unknown);
+        assert!(RetentionManager::new(
+            MemoryAttachmentStore::new(),
+            RetentionPolicy {
+                global_bytes: 0,
+                ..RetentionPolicy::default()
+            }
+        )
+        .is_err());
+    }
+}
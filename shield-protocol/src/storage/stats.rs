@@ -0,0 +1,282 @@
+//! Per-conversation usage statistics, computed on the device.
+//!
+//! Apps want to show "1,204 messages, 38 MB of attachments" on a contact
+//! page, or report aggregate usage to their operator. The figures come from
+//! message rows the app already stores: it passes one [`MessageSample`] per
+//! message of a conversation and gets back [`ConversationStats`] (messages
+//! sent and received, median delivery latency, attachment bytes).
+//!
+//! Exact figures are fine to display, but once they leave the device (crash
+//! reports, usage pings, a screenshot) they are a precise record of how much
+//! two people talk. [`ConversationStats::compute_noisy`] releases the same
+//! figures under ε-differential privacy instead, so adding or removing any
+//! single message changes the distribution of the output by at most a factor
+//! of `e^ε`:
+//!
+//! - Counts and attachment bytes get two-sided geometric (discrete Laplace)
+//!   noise. Each message's attachment bytes are first clamped to
+//!   [`NoiseConfig::max_attachment_bytes`], which bounds what one message
+//!   can contribute.
+//! - The median latency is chosen by the exponential mechanism from a fixed
+//!   log-spaced grid, scored by how evenly it splits the latencies, so it is
+//!   always a grid value and is reported even for a conversation with no
+//!   delivered messages.
+//!
+//! The budget ε is split evenly between the four figures. Every call spends
+//! it again: releasing fresh noisy figures for the same messages repeatedly
+//! lets the noise be averaged away, so apps should keep a released value
+//! (e.g. per conversation per day) rather than recompute it on every view.
+
+use thiserror::Error;
+
+use crate::rng::SecureRng;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum StatsError {
+    #[error("Privacy budget must be positive and finite")]
+    InvalidEpsilon,
+}
+
+/// Latency grid steps per doubling.
+const GRID_STEPS_PER_DOUBLING: u32 = 4;
+
+/// Grid points: 1 ms up to 2^24 ms (about 4.7 hours).
+const GRID_POINTS: u32 = 24 * GRID_STEPS_PER_DOUBLING + 1;
+
+/// Statistics released by [`ConversationStats::compute_noisy`].
+const NOISY_FIGURES: f64 = 4.0;
+
+/// One message, as the app has it stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageSample {
+    pub outgoing: bool,
+    /// When it was sent (Unix ms).
+    pub sent_at_ms: u64,
+    /// When its delivery ACK arrived (Unix ms); outgoing messages only.
+    pub delivered_at_ms: Option<u64>,
+    /// Total size of its attachments.
+    pub attachment_bytes: u64,
+}
+
+impl MessageSample {
+    /// Time from send to delivery ACK.
+    fn latency_ms(&self) -> Option<u64> {
+        if !self.outgoing {
+            return None;
+        }
+        self.delivered_at_ms
+            .map(|at| at.saturating_sub(self.sent_at_ms))
+    }
+}
+
+/// Noise parameters for [`ConversationStats::compute_noisy`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseConfig {
+    /// Privacy budget per release; smaller is noisier.
+    pub epsilon: f64,
+    /// Attachment bytes counted per message at most.
+    pub max_attachment_bytes: u64,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        Self {
+            epsilon: 1.0,
+            max_attachment_bytes: 25 * 1024 * 1024,
+        }
+    }
+}
+
+/// Usage figures for one conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConversationStats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// Median time from sending to delivery ACK over delivered outgoing
+    /// messages; `None` if there are none.
+    pub median_latency_ms: Option<u64>,
+    pub attachment_bytes: u64,
+}
+
+impl ConversationStats {
+    /// Exact figures over `samples`.
+    pub fn compute<'a>(samples: impl IntoIterator<Item = &'a MessageSample>) -> Self {
+        let (mut stats, mut latencies) = Self::tally(samples, u64::MAX);
+        if !latencies.is_empty() {
+            let mid = latencies.len() / 2;
+            stats.median_latency_ms = Some(*latencies.select_nth_unstable(mid).1);
+        }
+        stats
+    }
+
+    /// Figures over `samples` under `config.epsilon`-differential privacy.
+    pub fn compute_noisy<'a>(
+        samples: impl IntoIterator<Item = &'a MessageSample>,
+        config: &NoiseConfig,
+        rng: &mut impl SecureRng,
+    ) -> Result<Self, StatsError> {
+        if !(config.epsilon.is_finite() && config.epsilon > 0.0) {
+            return Err(StatsError::InvalidEpsilon);
+        }
+        let epsilon = config.epsilon / NOISY_FIGURES;
+        let max_bytes = config.max_attachment_bytes.max(1);
+        let (exact, mut latencies) = Self::tally(samples, max_bytes);
+        Ok(Self {
+            messages_sent: add_noise(exact.messages_sent, 1, epsilon, rng),
+            messages_received: add_noise(exact.messages_received, 1, epsilon, rng),
+            median_latency_ms: Some(noisy_median(&mut latencies, epsilon, rng)),
+            attachment_bytes: add_noise(exact.attachment_bytes, max_bytes, epsilon, rng),
+        })
+    }
+
+    /// Counts and byte total (each message clamped to `max_bytes`), plus
+    /// the latencies the median is taken over.
+    fn tally<'a>(
+        samples: impl IntoIterator<Item = &'a MessageSample>,
+        max_bytes: u64,
+    ) -> (Self, Vec<u64>) {
+        let mut stats = Self::default();
+        let mut latencies = Vec::new();
+        for sample in samples {
+            if sample.outgoing {
+                stats.messages_sent += 1;
+            } else {
+                stats.messages_received += 1;
+            }
+            stats.attachment_bytes = stats
+                .attachment_bytes
+                .saturating_add(sample.attachment_bytes.min(max_bytes));
+            latencies.extend(sample.latency_ms());
+        }
+        (stats, latencies)
+    }
+}
+
+/// Uniform in (0, 1).
+fn uniform(rng: &mut impl SecureRng) -> f64 {
+    // 53 random bits, offset by half a step so neither end is reachable
+    ((rng.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+}
+
+/// `value` plus two-sided geometric noise for a figure one message can move
+/// by `sensitivity`, clamped at zero.
+fn add_noise(value: u64, sensitivity: u64, epsilon: f64, rng: &mut impl SecureRng) -> u64 {
+    // Difference of two geometric draws with P(k) ∝ α^k, α = e^(-ε/Δ),
+    // i.e. P(noise = k) ∝ e^(-ε|k|/Δ)
+    let ln_alpha = -epsilon / sensitivity as f64;
+    let mut geometric = || (uniform(rng).ln() / ln_alpha).floor();
+    let noise = geometric() - geometric();
+    (value as f64 + noise).max(0.0).round() as u64
+}
+
+/// Latency at grid point `i`: 2^(i / GRID_STEPS_PER_DOUBLING) ms.
+fn grid_ms(i: u32) -> u64 {
+    2f64.powf(i as f64 / GRID_STEPS_PER_DOUBLING as f64).round() as u64
+}
+
+/// Median of `latencies` by the exponential mechanism over the grid.
+///
+/// A candidate `c` scores `-|#{x < c} - #{x >= c}|`, which one message
+/// changes by at most 1, so it is drawn with probability ∝ e^(ε·score/2).
+fn noisy_median(latencies: &mut [u64], epsilon: f64, rng: &mut impl SecureRng) -> u64 {
+    latencies.sort_unstable();
+    let n = latencies.len() as i64;
+    let scores: Vec<f64> = (0..GRID_POINTS)
+        .map(|i| {
+            let below = latencies.partition_point(|&x| x < grid_ms(i)) as i64;
+            -((below - (n - below)).abs() as f64) * epsilon / 2.0
+        })
+        .collect();
+
+    // Weights relative to the best score, so the largest is exactly 1
+    let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = scores.iter().map(|s| (s - best).exp()).collect();
+    let mut target = uniform(rng) * weights.iter().sum::<f64>();
+    for (i, weight) in weights.iter().enumerate() {
+        target -= weight;
+        if target <= 0.0 {
+            return grid_ms(i as u32);
+        }
+    }
+    grid_ms(GRID_POINTS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    fn sample(outgoing: bool, latency_ms: Option<u64>, attachment_bytes: u64) -> MessageSample {
+        MessageSample {
+            outgoing,
+            sent_at_ms: 1_700_000_000_000,
+            delivered_at_ms: latency_ms.map(|l| 1_700_000_000_000 + l),
+            attachment_bytes,
+        }
+    }
+
+    #[test]
+    fn test_exact_stats() {
+        let samples = [
+            sample(true, Some(900), 0),
+            sample(true, Some(3_000), 1_000),
+            sample(true, None, 0),
+            sample(true, Some(1_200), 0),
+            // Incoming messages count but have no latency
+            sample(false, Some(5), 4_000),
+        ];
+        let stats = ConversationStats::compute(&samples);
+        assert_eq!(
+            stats,
+            ConversationStats {
+                messages_sent: 4,
+                messages_received: 1,
+                median_latency_ms: Some(1_200),
+                attachment_bytes: 5_000,
+            }
+        );
+        assert_eq!(ConversationStats::compute(&[]).median_latency_ms, None);
+    }
+
+    #[test]
+    fn test_noisy_stats_stay_near_exact() {
+        let samples: Vec<_> = (0..1_000u64)
+            .map(|i| sample(i % 4 != 0, Some(1_000 + i), 10_000))
+            .collect();
+        let exact = ConversationStats::compute(&samples);
+        let config = NoiseConfig {
+            epsilon: 2.0,
+            max_attachment_bytes: 10_000,
+        };
+        let mut rng = seeded(7);
+
+        let mut differs = false;
+        for _ in 0..20 {
+            let noisy = ConversationStats::compute_noisy(&samples, &config, &mut rng).unwrap();
+            assert!(noisy.messages_sent.abs_diff(exact.messages_sent) < 50);
+            assert!(noisy.messages_received.abs_diff(exact.messages_received) < 50);
+            assert!(noisy.attachment_bytes.abs_diff(exact.attachment_bytes) < 500_000);
+            let median = noisy.median_latency_ms.unwrap();
+            assert!((1_000..=2_000).contains(&median), "median {}", median);
+            differs |= noisy != exact;
+        }
+        assert!(differs);
+
+        // Clamping bounds what one message contributes
+        let big = [sample(true, None, u64::MAX)];
+        let noisy = ConversationStats::compute_noisy(&big, &config, &mut rng).unwrap();
+        assert!(noisy.attachment_bytes < 1_000_000);
+
+        assert_eq!(
+            ConversationStats::compute_noisy(
+                &samples,
+                &NoiseConfig {
+                    epsilon: 0.0,
+                    ..config
+                },
+                &mut rng
+            ),
+            Err(StatsError::InvalidEpsilon)
+        );
+    }
+}
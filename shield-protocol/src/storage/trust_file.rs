@@ -0,0 +1,406 @@
+//! Encrypted file backend for [`ContactTrustStore`].
+//!
+//! Mobile apps keep trust levels in SQLCipher; desktop builds and tests
+//! should not need a database for a few hundred small records.
+//! [`FileTrustStore`] keeps them in memory and persists every change to an
+//! append-only log:
+//!
+//! ```text
+//! file  = MAGIC "SMTS" | version | frame(KEY_CHECK) | frame(entry)*
+//! frame = len (u32 BE) | nonce (24) | XChaCha20-Poly1305(bincode(entry))
+//! entry = { seq, Save(record) | Delete(contact_id) }
+//! ```
+//!
+//! Every frame is authenticated on its own. On open, entries are replayed
+//! until the first frame that is short, fails to decrypt or breaks the
+//! sequence (a torn write from a crash); the file is truncated there so
+//! later appends do not land behind garbage. The key-check frame tells a
+//! wrong key apart from a damaged log, which is never truncated.
+//!
+//! Once the log holds more than twice as many entries as live records it is
+//! compacted: one `Save` per record is written to a temporary file, synced
+//! and renamed over the log, so a crash during compaction leaves either the
+//! old or the new file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+use super::{ContactTrustStore, Result, StorageError};
+use crate::crypto::encryption::{decrypt_message, encrypt_message};
+use crate::crypto::pqc::{ContactVerificationRecord, TrustLevel};
+use crate::protocol::contact_id::ContactId;
+
+const MAGIC: &[u8; 4] = b"SMTS";
+
+/// Trust log file format version.
+pub const TRUST_FILE_VERSION: u8 = 1;
+
+const KEY_CHECK: &[u8] = b"ShieldMessenger-TrustStore-v1";
+
+/// Frames longer than this are treated as corruption.
+const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// Logs shorter than this are never compacted.
+const MIN_COMPACT_ENTRIES: usize = 64;
+
+/// One row of the SQL schema on [`ContactTrustStore`].
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    contact_id: ContactId,
+    trust_level: u8,
+    verified_at: i64,
+    safety_number: String,
+}
+
+impl From<&ContactVerificationRecord> for StoredRecord {
+    fn from(record: &ContactVerificationRecord) -> Self {
+        Self {
+            contact_id: record.contact_id,
+            trust_level: record.trust_level as u8,
+            verified_at: record.verified_at,
+            safety_number: record.safety_number.clone(),
+        }
+    }
+}
+
+impl From<StoredRecord> for ContactVerificationRecord {
+    fn from(stored: StoredRecord) -> Self {
+        Self {
+            contact_id: stored.contact_id,
+            trust_level: TrustLevel::from_u8(stored.trust_level),
+            verified_at: stored.verified_at,
+            safety_number: stored.safety_number,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Op {
+    Save(StoredRecord),
+    Delete(ContactId),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    op: Op,
+}
+
+/// [`ContactTrustStore`] persisted to one encrypted, append-only file.
+pub struct FileTrustStore {
+    path: PathBuf,
+    key: [u8; 32],
+    records: BTreeMap<ContactId, ContactVerificationRecord>,
+    /// Entries in the log, and the sequence number of the last one
+    entries: usize,
+    last_seq: u64,
+}
+
+impl FileTrustStore {
+    /// Open (or create on first write) the log at `path` under `key`.
+    ///
+    /// Fails with [`StorageError::DecryptionFailed`] if the file was written
+    /// under another key, leaving it untouched.
+    pub fn open(path: impl Into<PathBuf>, key: &[u8; 32]) -> Result<Self> {
+        let mut store = Self {
+            path: path.into(),
+            key: *key,
+            records: BTreeMap::new(),
+            entries: 0,
+            last_seq: 0,
+        };
+        let bytes = match fs::read(&store.path) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_) => return Ok(store),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(store),
+            Err(_) => return Err(StorageError::Io),
+        };
+        let good_len = store.replay(&bytes)?;
+        if good_len < bytes.len() {
+            log::warn!(
+                "Trust store {}: dropping {} unreadable trailing bytes",
+                store.path.display(),
+                bytes.len() - good_len
+            );
+            OpenOptions::new()
+                .write(true)
+                .open(&store.path)
+                .and_then(|f| f.set_len(good_len as u64))
+                .map_err(|_| StorageError::Io)?;
+        }
+        Ok(store)
+    }
+
+    /// Apply the entries in `bytes`; returns the length of the readable prefix.
+    fn replay(&mut self, bytes: &[u8]) -> Result<usize> {
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(StorageError::DecryptionFailed);
+        }
+        if bytes[MAGIC.len()] != TRUST_FILE_VERSION {
+            return Err(StorageError::DecryptionFailed);
+        }
+        let mut offset = MAGIC.len() + 1;
+        match read_frame(bytes, offset, &self.key) {
+            Some((check, next)) if check == KEY_CHECK => offset = next,
+            _ => return Err(StorageError::DecryptionFailed),
+        }
+
+        while let Some((plaintext, next)) = read_frame(bytes, offset, &self.key) {
+            let Ok(entry) = bincode::deserialize::<Entry>(&plaintext) else {
+                break;
+            };
+            if entry.seq <= self.last_seq {
+                break;
+            }
+            self.apply(entry);
+            offset = next;
+        }
+        Ok(offset)
+    }
+
+    fn apply(&mut self, entry: Entry) {
+        match entry.op {
+            Op::Save(stored) => {
+                self.records.insert(stored.contact_id, stored.into());
+            }
+            Op::Delete(contact_id) => {
+                self.records.remove(&contact_id);
+            }
+        }
+        self.entries += 1;
+        self.last_seq = entry.seq;
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let sealed = encrypt_message(plaintext, &self.key).map_err(|_| StorageError::Io)?;
+        let mut frame = Vec::with_capacity(4 + sealed.len());
+        frame.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&sealed);
+        Ok(frame)
+    }
+
+    fn header(&self) -> Result<Vec<u8>> {
+        let mut header = MAGIC.to_vec();
+        header.push(TRUST_FILE_VERSION);
+        header.extend_from_slice(&self.seal(KEY_CHECK)?);
+        Ok(header)
+    }
+
+    fn encode(&self, seq: u64, op: Op) -> Result<Vec<u8>> {
+        let plaintext = bincode::serialize(&Entry { seq, op }).map_err(|_| StorageError::Io)?;
+        self.seal(&plaintext)
+    }
+
+    fn append(&mut self, op: Op) -> Result<()> {
+        let seq = self.last_seq + 1;
+        let frame = self.encode(seq, op)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|_| StorageError::Io)?;
+        if file.metadata().map_err(|_| StorageError::Io)?.len() == 0 {
+            file.write_all(&self.header()?)
+                .map_err(|_| StorageError::Io)?;
+        }
+        file.write_all(&frame)
+            .and_then(|_| file.sync_data())
+            .map_err(|_| StorageError::Io)?;
+        self.entries += 1;
+        self.last_seq = seq;
+        Ok(())
+    }
+
+    fn compact_if_due(&mut self) -> Result<()> {
+        if self.entries > MIN_COMPACT_ENTRIES.max(2 * self.records.len()) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the log as one entry per live record.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut bytes = self.header()?;
+        for (i, record) in self.records.values().enumerate() {
+            bytes.extend_from_slice(&self.encode(i as u64 + 1, Op::Save(record.into()))?);
+        }
+        let tmp = self.path.with_extension("tmp");
+        write_synced(&tmp, &bytes)
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|_| StorageError::Io)?;
+        self.entries = self.records.len();
+        self.last_seq = self.records.len() as u64;
+        Ok(())
+    }
+
+    /// Entries currently in the log file.
+    pub fn log_len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FileTrustStore {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl ContactTrustStore for FileTrustStore {
+    fn load_trust(&self, contact_id: &ContactId) -> Result<Option<ContactVerificationRecord>> {
+        Ok(self.records.get(contact_id).cloned())
+    }
+
+    fn save_trust(&mut self, record: &ContactVerificationRecord) -> Result<()> {
+        self.append(Op::Save(record.into()))?;
+        self.records.insert(record.contact_id, record.clone());
+        self.compact_if_due()
+    }
+
+    fn delete_trust(&mut self, contact_id: &ContactId) -> Result<()> {
+        if !self.records.contains_key(contact_id) {
+            return Ok(());
+        }
+        self.append(Op::Delete(*contact_id))?;
+        self.records.remove(contact_id);
+        self.compact_if_due()
+    }
+
+    fn list_verified(&self) -> Result<Vec<ContactVerificationRecord>> {
+        Ok(self
+            .records
+            .values()
+            .filter(|r| r.trust_level == TrustLevel::Verified)
+            .cloned()
+            .collect())
+    }
+
+    /// Removes the log file itself rather than appending a delete per record.
+    fn delete_all(&mut self) -> Result<usize> {
+        let deleted = self.records.len();
+        match fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(_) => return Err(StorageError::Io),
+        }
+        self.records.clear();
+        self.entries = 0;
+        self.last_seq = 0;
+        Ok(deleted)
+    }
+}
+
+/// Decrypt the frame at `offset`; returns its plaintext and the next offset.
+fn read_frame(bytes: &[u8], offset: usize, key: &[u8; 32]) -> Option<(Vec<u8>, usize)> {
+    let len = u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?) as usize;
+    if len > MAX_FRAME_LEN {
+        return None;
+    }
+    let start = offset + 4;
+    let sealed = bytes.get(start..start + len)?;
+    let plaintext = decrypt_message(sealed, key).ok()?;
+    Some((plaintext, start + len))
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::contact_id::test_contact;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("trust-{}-{}.log", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn record(b: u8, trust_level: TrustLevel) -> ContactVerificationRecord {
+        ContactVerificationRecord {
+            trust_level,
+            verified_at: i64::from(b),
+            safety_number: format!("{:05}", b),
+            ..ContactVerificationRecord::new_encrypted(test_contact(b))
+        }
+    }
+
+    #[test]
+    fn test_records_survive_reopen_and_compaction() {
+        let path = temp_path("reopen");
+        let key = [7u8; 32];
+        let mut store = FileTrustStore::open(&path, &key).unwrap();
+        store.save_trust(&record(1, TrustLevel::Encrypted)).unwrap();
+        store.save_trust(&record(2, TrustLevel::Verified)).unwrap();
+        store.save_trust(&record(1, TrustLevel::Verified)).unwrap();
+        store
+            .delete_trust(&record(2, TrustLevel::Verified).contact_id)
+            .unwrap();
+        drop(store);
+
+        let mut store = FileTrustStore::open(&path, &key).unwrap();
+        let one = record(1, TrustLevel::Verified);
+        let loaded = store.load_trust(&one.contact_id).unwrap().unwrap();
+        assert_eq!(loaded.trust_level, TrustLevel::Verified);
+        assert_eq!(loaded.safety_number, "00001");
+        assert_eq!(store.list_verified().unwrap().len(), 1);
+        assert_eq!(store.log_len(), 4);
+
+        // Repeated updates to one record trigger compaction
+        for _ in 0..MIN_COMPACT_ENTRIES {
+            store.save_trust(&one).unwrap();
+        }
+        assert!(store.log_len() < MIN_COMPACT_ENTRIES);
+        drop(store);
+        let store = FileTrustStore::open(&path, &key).unwrap();
+        assert_eq!(store.list_verified().unwrap().len(), 1);
+        assert!(!path.with_extension("tmp").exists());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_torn_tail_is_dropped_and_wrong_key_rejected() {
+        let path = temp_path("torn");
+        let key = [9u8; 32];
+        let mut store = FileTrustStore::open(&path, &key).unwrap();
+        store.save_trust(&record(1, TrustLevel::Verified)).unwrap();
+        store.save_trust(&record(2, TrustLevel::Verified)).unwrap();
+        drop(store);
+
+        // A crash cut the last frame short
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+
+        let mut store = FileTrustStore::open(&path, &key).unwrap();
+        assert_eq!(store.list_verified().unwrap().len(), 1);
+        // Appends after the truncation are readable
+        store.save_trust(&record(3, TrustLevel::Verified)).unwrap();
+        drop(store);
+        assert_eq!(
+            FileTrustStore::open(&path, &key)
+                .unwrap()
+                .list_verified()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let before = fs::read(&path).unwrap();
+        assert!(matches!(
+            FileTrustStore::open(&path, &[1u8; 32]),
+            Err(StorageError::DecryptionFailed)
+        ));
+        assert_eq!(fs::read(&path).unwrap(), before);
+        let _ = fs::remove_file(&path);
+    }
+}
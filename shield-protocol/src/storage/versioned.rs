@@ -0,0 +1,284 @@
+//! Format versions and migrations for persisted state.
+//!
+//! State the app keeps across upgrades (ratchet state, CRDT ops, contact
+//! cards, contact backups) is written with a short header naming what the
+//! blob holds and which layout its body uses:
+//!
+//! ```text
+//! blob = [magic "SMVS": 4][kind: 1][version: 2 BE][bincode body]
+//! ```
+//!
+//! Each persisted type implements [`Persisted`]: its [`StateKind`], the
+//! version of its current layout, and [`Persisted::migrate`], which decodes
+//! any older layout into the current type. [`Versioned::decode`] dispatches
+//! on the header, so a build reads everything earlier builds wrote; blobs
+//! from before headers existed carry no magic and reach `migrate` as
+//! [`LEGACY_VERSION`]. A blob from a newer build is refused rather than
+//! misread.
+//!
+//! Changing a persisted layout means bumping `VERSION`, keeping the old
+//! layout as a private struct next to the type, converting from it in
+//! `migrate`, and adding a test that decodes bytes written by the old build.
+//! When [`Versioned::is_upgraded`] is set, the app should write the value
+//! back so the migration runs once.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+/// First bytes of every versioned blob.
+pub const VERSION_MAGIC: [u8; 4] = *b"SMVS";
+
+/// Magic, kind and version.
+pub const HEADER_LEN: usize = 7;
+
+/// Version `migrate` receives for blobs written without a header.
+pub const LEGACY_VERSION: u16 = 0;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VersionError {
+    #[error("Versioned blob shorter than its header")]
+    Truncated,
+
+    #[error("Blob holds state kind {found}, expected {expected:?}")]
+    WrongKind { expected: StateKind, found: u8 },
+
+    #[error("{kind:?} version {version} was written by a newer build")]
+    Newer { kind: StateKind, version: u16 },
+
+    #[error("No migration from {kind:?} version {version}")]
+    NoMigration { kind: StateKind, version: u16 },
+
+    #[error("Persisted state encoding failed: {0}")]
+    Encoding(String),
+
+    #[error("Malformed persisted state: {0}")]
+    Malformed(String),
+}
+
+pub type Result<T> = std::result::Result<T, VersionError>;
+
+// ---------------------------------------------------------------------------
+// Kinds
+// ---------------------------------------------------------------------------
+
+/// What a versioned blob holds. Values are stored; never reuse one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum StateKind {
+    /// [`RatchetState`](crate::crypto::ratchet::RatchetState)
+    RatchetState = 1,
+    /// A CRDT `OpEnvelope` from a group's op log
+    CrdtOp = 2,
+    /// [`ContactCard`](crate::protocol::contact::ContactCard)
+    ContactCard = 3,
+    /// [`ContactSnapshot`](crate::protocol::contact_backup::ContactSnapshot)
+    ContactSnapshot = 4,
+}
+
+// ---------------------------------------------------------------------------
+// Persisted
+// ---------------------------------------------------------------------------
+
+/// A type stored behind a version header.
+pub trait Persisted: Serialize + DeserializeOwned {
+    const KIND: StateKind;
+
+    /// Layout written by this build. Starts at 1.
+    const VERSION: u16;
+
+    /// Decode a body written at `version`, which is below [`Self::VERSION`]
+    /// ([`LEGACY_VERSION`] for blobs without a header).
+    fn migrate(version: u16, body: &[u8]) -> Result<Self>;
+}
+
+/// Bincode-decode `body` as `L`; for `migrate` impls reading old layouts.
+pub fn decode_layout<L: DeserializeOwned>(body: &[u8]) -> Result<L> {
+    bincode::deserialize(body).map_err(|e| VersionError::Malformed(e.to_string()))
+}
+
+/// Encode `value` at its current version.
+pub fn encode<T: Persisted>(value: &T) -> Result<Vec<u8>> {
+    let body = bincode::serialize(value).map_err(|e| VersionError::Encoding(e.to_string()))?;
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend_from_slice(&VERSION_MAGIC);
+    out.push(T::KIND as u8);
+    out.extend_from_slice(&T::VERSION.to_be_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decode a blob written by this or any earlier build.
+pub fn decode<T: Persisted>(blob: &[u8]) -> Result<T> {
+    Versioned::decode(blob).map(Versioned::into_inner)
+}
+
+/// A value together with the version it was stored at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Versioned<T> {
+    pub value: T,
+    /// Version of the blob the value was read from; `T::VERSION` for new values
+    pub stored_version: u16,
+}
+
+impl<T: Persisted> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            stored_version: T::VERSION,
+        }
+    }
+
+    /// Encode at the current version, whatever the value was read at.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        encode(&self.value)
+    }
+
+    pub fn decode(blob: &[u8]) -> Result<Self> {
+        if !blob.starts_with(&VERSION_MAGIC) {
+            return T::migrate(LEGACY_VERSION, blob).map(|value| Self {
+                value,
+                stored_version: LEGACY_VERSION,
+            });
+        }
+        if blob.len() < HEADER_LEN {
+            return Err(VersionError::Truncated);
+        }
+        if blob[4] != T::KIND as u8 {
+            return Err(VersionError::WrongKind {
+                expected: T::KIND,
+                found: blob[4],
+            });
+        }
+        let version = u16::from_be_bytes([blob[5], blob[6]]);
+        let body = &blob[HEADER_LEN..];
+        let value = match version {
+            v if v == T::VERSION => decode_layout(body)?,
+            v if v > T::VERSION => {
+                return Err(VersionError::Newer {
+                    kind: T::KIND,
+                    version,
+                })
+            }
+            LEGACY_VERSION => {
+                return Err(VersionError::Malformed("header with version 0".to_string()))
+            }
+            _ => T::migrate(version, body)?,
+        };
+        Ok(Self {
+            value,
+            stored_version: version,
+        })
+    }
+
+    /// Read from an older layout; write it back to finish the migration.
+    pub fn is_upgraded(&self) -> bool {
+        self.stored_version < T::VERSION
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// Current layout; v1 had no `label`, legacy blobs were a bare `u32`.
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Note {
+        count: u32,
+        label: String,
+    }
+
+    #[derive(Deserialize)]
+    struct NoteV1 {
+        count: u32,
+    }
+
+    impl Persisted for Note {
+        const KIND: StateKind = StateKind::ContactCard;
+        const VERSION: u16 = 2;
+
+        fn migrate(version: u16, body: &[u8]) -> Result<Self> {
+            let count = match version {
+                LEGACY_VERSION => decode_layout::<u32>(body)?,
+                1 => decode_layout::<NoteV1>(body)?.count,
+                _ => {
+                    return Err(VersionError::NoMigration {
+                        kind: Self::KIND,
+                        version,
+                    })
+                }
+            };
+            Ok(Note {
+                count,
+                label: String::new(),
+            })
+        }
+    }
+
+    fn note() -> Note {
+        Note {
+            count: 7,
+            label: "seven".into(),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_at_current_version() {
+        let blob = encode(&note()).unwrap();
+        assert_eq!(&blob[..4], b"SMVS");
+        assert_eq!(blob[4], StateKind::ContactCard as u8);
+        assert_eq!(&blob[5..7], &[0, 2]);
+
+        let read = Versioned::<Note>::decode(&blob).unwrap();
+        assert_eq!(read.value, note());
+        assert!(!read.is_upgraded());
+    }
+
+    #[test]
+    fn test_old_layouts_are_migrated() {
+        let legacy = bincode::serialize(&7u32).unwrap();
+        let read = Versioned::<Note>::decode(&legacy).unwrap();
+        assert_eq!(read.stored_version, LEGACY_VERSION);
+        assert!(read.is_upgraded());
+        assert_eq!(read.value.count, 7);
+
+        let mut v1 = b"SMVS\x03\x00\x01".to_vec();
+        v1.extend_from_slice(&7u32.to_le_bytes());
+        let read = Versioned::<Note>::decode(&v1).unwrap();
+        assert_eq!(read.stored_version, 1);
+        assert_eq!(read.value.count, 7);
+
+        // Writing back upgrades the blob
+        let rewritten = read.encode().unwrap();
+        assert_eq!(&rewritten[5..7], &[0, 2]);
+    }
+
+    #[test]
+    fn test_refuses_newer_and_foreign_blobs() {
+        let mut blob = encode(&note()).unwrap();
+        blob[6] = 3;
+        assert_eq!(
+            decode::<Note>(&blob),
+            Err(VersionError::Newer {
+                kind: StateKind::ContactCard,
+                version: 3
+            })
+        );
+
+        blob[4] = StateKind::RatchetState as u8;
+        assert!(matches!(
+            decode::<Note>(&blob),
+            Err(VersionError::WrongKind { found: 1, .. })
+        ));
+        assert_eq!(decode::<Note>(b"SMVS\x03"), Err(VersionError::Truncated));
+    }
+}
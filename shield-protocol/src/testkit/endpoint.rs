@@ -0,0 +1,952 @@
+/// In-process protocol endpoint used by the testkit.
+///
+/// An endpoint owns an Ed25519 identity, an X25519 static key and one link
+/// per contact. Each link runs the real `PQDoubleRatchet`; sessions are opened
+/// with an ephemeral-static X25519 handshake that rides in-band on every frame
+/// the initiator sends, so a lost first frame does not stall the session.
+///
+/// Below the ratchet sits a small reliable channel (per-epoch sequence
+/// numbers, cumulative acks, retransmission), so the ratchet sees every
+/// ciphertext exactly once and in order however the network mangles
+/// delivery. A rekey starts a new epoch with a fresh handshake; payloads that
+/// were unacknowledged in the old epoch are re-sent under the new session.
+/// Simultaneous rekeys are resolved in favour of the lower identity key.
+///
+/// Every datagram is prefixed with the link's transport path. Switching
+/// transports moves a link to a new path without a handshake: the endpoint
+/// sends a resume request built from a resumption ticket (each side issues one
+/// per epoch over the ratchet) and holds its data until the peer accepts.
+/// Datagrams still arriving on an abandoned path are dropped; unacknowledged
+/// frames are re-sent on the new one under the same ratchet.
+#[cfg(feature = "groups")]
+use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use crate::crypto::key_exchange;
+use crate::crypto::ratchet::{PQDoubleRatchet, RatchetHeader};
+use crate::crypto::resumption::{ResumeAccept, ResumeRequest, ResumptionBook, ResumptionConfig};
+use crate::crypto::signing;
+use crate::protocol::ContactId;
+use crate::rng;
+use crate::testkit::network::EndpointId;
+use crate::testkit::TestkitError;
+
+#[cfg(feature = "groups")]
+use crate::crdt::{
+    group_msg_id, DeviceID, GroupCreatePayload, GroupID, GroupState, MemberAcceptPayload,
+    MemberInvitePayload, MsgAddPayload, OpEnvelope, OpType, Role,
+};
+
+/// Ticks before an unacknowledged frame is sent again.
+pub const DEFAULT_RETRANSMIT_TICKS: u64 = 20;
+
+const SESSION_CONTEXT: &str = "ShieldMessenger-Testkit-Session-v1";
+
+/// Public keys a contact needs to open a session with an endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerBundle {
+    /// Ed25519 identity key.
+    pub identity_public: [u8; 32],
+    /// X25519 static key the handshake is addressed to.
+    pub static_public: [u8; 32],
+}
+
+/// A text message delivered to the application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedText {
+    pub from: EndpointId,
+    pub body: Vec<u8>,
+}
+
+/// Wire frame (bincode).
+#[derive(Serialize, Deserialize)]
+enum Frame {
+    Data {
+        epoch: u32,
+        seq: u64,
+        /// Initiator's ephemeral X25519 key; set on every frame it sends.
+        init: Option<[u8; 32]>,
+        header: RatchetHeader,
+        ciphertext: Vec<u8>,
+    },
+    Ack {
+        epoch: u32,
+        /// All frames below this sequence number were received.
+        next_seq: u64,
+    },
+    /// Sent on the new path until answered.
+    Resume {
+        epoch: u32,
+        request: Vec<u8>,
+    },
+    ResumeAccept {
+        epoch: u32,
+        accept: Vec<u8>,
+    },
+}
+
+/// Ratchet plaintext (bincode).
+#[derive(Serialize, Deserialize, Clone)]
+enum Payload {
+    /// Carries the handshake when there is nothing else to send.
+    Handshake,
+    Text {
+        id: u64,
+        body: Vec<u8>,
+    },
+    /// Serialized `OpEnvelope`s; ignored without the `groups` feature.
+    #[cfg_attr(not(feature = "groups"), allow(dead_code))]
+    GroupOps(Vec<Vec<u8>>),
+    /// Resumption ticket issued by the sender.
+    Ticket(Vec<u8>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LinkRole {
+    Initiator { ephemeral_public: [u8; 32] },
+    Responder { peer_init: [u8; 32] },
+}
+
+struct Unacked {
+    payload: Payload,
+    frame: Vec<u8>,
+    sent_at: u64,
+}
+
+struct Link {
+    bundle: PeerBundle,
+    contact: ContactId,
+    epoch: u32,
+    /// Transport path in use.
+    path: u32,
+    /// Our pending resume frame and when it was last sent.
+    resuming: Option<(Vec<u8>, u64)>,
+    /// Last resume request we accepted and our encoded answer, re-sent if
+    /// the request arrives again because the answer was lost.
+    last_resume: Option<(Vec<u8>, Vec<u8>)>,
+    /// We issued a resumption ticket in this epoch.
+    ticket_issued: bool,
+    role: Option<LinkRole>,
+    session: Option<PQDoubleRatchet>,
+    /// The peer has proven it holds this epoch's session (ack or decrypted frame).
+    /// A responder may only send once confirmed: its send chain starts on first receive.
+    confirmed: bool,
+    next_send_seq: u64,
+    unacked: BTreeMap<u64, Unacked>,
+    queued: VecDeque<Payload>,
+    next_recv_seq: u64,
+    reorder: BTreeMap<u64, (RatchetHeader, Vec<u8>)>,
+    seen_text: HashSet<u64>,
+}
+
+impl Link {
+    fn new(bundle: PeerBundle) -> Self {
+        Self {
+            bundle,
+            contact: contact_id(&bundle),
+            epoch: 0,
+            path: 0,
+            resuming: None,
+            last_resume: None,
+            ticket_issued: false,
+            role: None,
+            session: None,
+            confirmed: false,
+            next_send_seq: 0,
+            unacked: BTreeMap::new(),
+            queued: VecDeque::new(),
+            next_recv_seq: 0,
+            reorder: BTreeMap::new(),
+            seen_text: HashSet::new(),
+        }
+    }
+
+    /// Switch to a new session; unacknowledged payloads go back to the queue.
+    /// Tickets from the old session are dropped: the new session issues its own.
+    fn reset_epoch(&mut self, epoch: u32, role: LinkRole, session: PQDoubleRatchet) {
+        let mut requeue: VecDeque<Payload> = std::mem::take(&mut self.unacked)
+            .into_values()
+            .map(|u| u.payload)
+            .collect();
+        requeue.extend(self.queued.drain(..));
+        requeue.retain(|p| !matches!(p, Payload::Ticket(_)));
+        self.queued = requeue;
+        self.epoch = epoch;
+        self.role = Some(role);
+        self.session = Some(session);
+        self.confirmed = false;
+        self.next_send_seq = 0;
+        self.next_recv_seq = 0;
+        self.reorder.clear();
+        self.resuming = None;
+        self.last_resume = None;
+        self.ticket_issued = false;
+    }
+
+    fn can_send(&self) -> bool {
+        if self.resuming.is_some() {
+            return false;
+        }
+        match self.role {
+            Some(LinkRole::Initiator { .. }) => true,
+            Some(LinkRole::Responder { .. }) => self.confirmed,
+            None => false,
+        }
+    }
+}
+
+#[cfg(feature = "groups")]
+struct GroupReplica {
+    state: GroupState,
+    log: Vec<OpEnvelope>,
+    /// Ops that could not be applied yet (e.g. a message before its author's accept).
+    pending: Vec<OpEnvelope>,
+}
+
+/// A protocol endpoint attached to a `SimNetwork`.
+pub struct Endpoint {
+    id: EndpointId,
+    identity_public: [u8; 32],
+    #[cfg_attr(not(feature = "groups"), allow(dead_code))]
+    identity_secret: [u8; 32],
+    static_public: [u8; 32],
+    static_secret: [u8; 32],
+    links: BTreeMap<EndpointId, Link>,
+    outbox: Vec<(EndpointId, Vec<u8>)>,
+    received: Vec<ReceivedText>,
+    next_text_id: u64,
+    retransmit_ticks: u64,
+    rng: ChaCha20Rng,
+    resumption: ResumptionBook,
+    /// Tick of the latest `poll`; the resumption clock.
+    now: u64,
+    #[cfg(feature = "groups")]
+    groups: BTreeMap<GroupID, GroupReplica>,
+}
+
+fn session_secret(
+    our_secret: &[u8; 32],
+    their_public: &[u8; 32],
+) -> Result<[u8; 64], TestkitError> {
+    let dh = key_exchange::derive_shared_secret(our_secret, their_public)
+        .map_err(|e| TestkitError::Handshake(e.to_string()))?;
+    let mut secret = [0u8; 64];
+    blake3::Hasher::new_derive_key(SESSION_CONTEXT)
+        .update(&dh)
+        .finalize_xof()
+        .fill(&mut secret);
+    Ok(secret)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, TestkitError> {
+    bincode::serialize(value).map_err(|e| TestkitError::Encode(e.to_string()))
+}
+
+fn contact_id(bundle: &PeerBundle) -> ContactId {
+    ContactId::from_identity_key(&bundle.identity_public).expect("identity key is 32 bytes")
+}
+
+/// Datagram layout: `[path: u32 BE][bincode Frame]`.
+fn datagram(path: u32, frame: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + frame.len());
+    out.extend_from_slice(&path.to_be_bytes());
+    out.extend_from_slice(frame);
+    out
+}
+
+fn split_datagram(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let path = u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?);
+    Some((path, &bytes[4..]))
+}
+
+/// Re-send every unacknowledged frame on the link's current path.
+fn resend_unacked(
+    peer: EndpointId,
+    link: &mut Link,
+    now: u64,
+    outbox: &mut Vec<(EndpointId, Vec<u8>)>,
+) {
+    for unacked in link.unacked.values_mut() {
+        unacked.sent_at = now;
+        outbox.push((peer, datagram(link.path, &unacked.frame)));
+    }
+}
+
+impl Endpoint {
+    /// Create an endpoint whose keys, handshake ephemerals, group ids and op
+    /// nonces are all drawn from a generator seeded with `seed`.
+    pub fn new(id: EndpointId, seed: u64) -> Self {
+        let mut rng = rng::seeded(seed);
+        let (identity_public, identity_secret) = signing::generate_keypair_with_rng(&mut rng);
+        let (static_public, static_secret) =
+            key_exchange::generate_static_keypair_with_rng(&mut rng);
+        Self {
+            id,
+            identity_public,
+            identity_secret,
+            static_public,
+            static_secret,
+            links: BTreeMap::new(),
+            outbox: Vec::new(),
+            received: Vec::new(),
+            next_text_id: 0,
+            retransmit_ticks: DEFAULT_RETRANSMIT_TICKS,
+            rng,
+            resumption: ResumptionBook::new(ResumptionConfig::default()),
+            now: 0,
+            #[cfg(feature = "groups")]
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> EndpointId {
+        self.id
+    }
+
+    pub fn bundle(&self) -> PeerBundle {
+        PeerBundle {
+            identity_public: self.identity_public,
+            static_public: self.static_public,
+        }
+    }
+
+    pub fn set_retransmit_ticks(&mut self, ticks: u64) {
+        self.retransmit_ticks = ticks.max(1);
+    }
+
+    /// Learn a contact's keys (the out-of-band contact exchange).
+    pub fn add_peer(&mut self, peer: EndpointId, bundle: PeerBundle) {
+        self.links
+            .entry(peer)
+            .and_modify(|l| {
+                l.bundle = bundle;
+                l.contact = contact_id(&bundle);
+            })
+            .or_insert_with(|| Link::new(bundle));
+    }
+
+    /// Open a session to a known contact as the initiator.
+    pub fn connect(&mut self, peer: EndpointId) -> Result<(), TestkitError> {
+        self.rekey(peer)
+    }
+
+    /// Start a new session epoch with a fresh handshake.
+    pub fn rekey(&mut self, peer: EndpointId) -> Result<(), TestkitError> {
+        let link = self
+            .links
+            .get_mut(&peer)
+            .ok_or(TestkitError::UnknownPeer(peer))?;
+        let (ephemeral_public, ephemeral_secret) =
+            key_exchange::generate_static_keypair_with_rng(&mut self.rng);
+        let secret = session_secret(&ephemeral_secret, &link.bundle.static_public)?;
+        let session = PQDoubleRatchet::init_alice_with_rng(
+            &secret,
+            &link.bundle.static_public,
+            None,
+            &mut self.rng,
+        )
+        .map_err(|e| TestkitError::Handshake(e.to_string()))?;
+        let epoch = link.epoch + 1;
+        self.resumption.invalidate(&link.contact);
+        link.reset_epoch(epoch, LinkRole::Initiator { ephemeral_public }, session);
+        link.queued.push_back(Payload::Handshake);
+        Ok(())
+    }
+
+    /// Move the link to a new transport path without a handshake. Data is
+    /// held until the peer accepts the resume request.
+    pub fn switch_transport(&mut self, peer: EndpointId) -> Result<(), TestkitError> {
+        let link = self
+            .links
+            .get_mut(&peer)
+            .ok_or(TestkitError::UnknownPeer(peer))?;
+        if link.session.is_none() {
+            return Err(TestkitError::NoSession(peer));
+        }
+        let request = self
+            .resumption
+            .begin_resume(&link.contact, self.now, &mut self.rng)
+            .map_err(|e| TestkitError::Resume(e.to_string()))?;
+        let frame = encode(&Frame::Resume {
+            epoch: link.epoch,
+            request: request.to_bytes(),
+        })?;
+        link.path += 1;
+        self.outbox.push((peer, datagram(link.path, &frame)));
+        link.resuming = Some((frame, self.now));
+        Ok(())
+    }
+
+    /// Transport path the link to a contact is on (0 = the original one).
+    pub fn path(&self, peer: EndpointId) -> Option<u32> {
+        self.links.get(&peer).map(|l| l.path)
+    }
+
+    /// Current session epoch with a contact (0 = never connected).
+    pub fn epoch(&self, peer: EndpointId) -> Option<u32> {
+        self.links.get(&peer).map(|l| l.epoch)
+    }
+
+    /// Whether the peer has confirmed the current session.
+    pub fn is_established(&self, peer: EndpointId) -> bool {
+        self.links
+            .get(&peer)
+            .is_some_and(|l| l.session.is_some() && l.confirmed)
+    }
+
+    /// Queue a text message. Returns its per-sender id.
+    pub fn send_text(&mut self, peer: EndpointId, body: &[u8]) -> Result<u64, TestkitError> {
+        let id = self.next_text_id;
+        self.enqueue(
+            peer,
+            Payload::Text {
+                id,
+                body: body.to_vec(),
+            },
+        )?;
+        self.next_text_id += 1;
+        Ok(id)
+    }
+
+    /// Drain delivered text messages.
+    pub fn take_received(&mut self) -> Vec<ReceivedText> {
+        std::mem::take(&mut self.received)
+    }
+
+    /// No queued, unacknowledged or outgoing frames.
+    pub fn is_idle(&self) -> bool {
+        self.outbox.is_empty()
+            && self
+                .links
+                .values()
+                .all(|l| l.unacked.is_empty() && l.queued.is_empty() && l.resuming.is_none())
+    }
+
+    fn enqueue(&mut self, peer: EndpointId, payload: Payload) -> Result<(), TestkitError> {
+        let link = self
+            .links
+            .get_mut(&peer)
+            .ok_or(TestkitError::UnknownPeer(peer))?;
+        if link.role.is_none() {
+            return Err(TestkitError::NoSession(peer));
+        }
+        link.queued.push_back(payload);
+        Ok(())
+    }
+
+    /// Encrypt queued payloads, resend overdue frames and return the datagrams to send.
+    /// [`Testnet`](super::Testnet) calls this every tick; drive it yourself to run an
+    /// endpoint over another transport.
+    pub fn poll(&mut self, now: u64) -> Vec<(EndpointId, Vec<u8>)> {
+        self.now = now;
+        for (peer, link) in self.links.iter_mut() {
+            if let Some((frame, sent_at)) = link.resuming.as_mut() {
+                if now.saturating_sub(*sent_at) >= self.retransmit_ticks {
+                    *sent_at = now;
+                    self.outbox.push((*peer, datagram(link.path, frame)));
+                }
+            }
+            if link.can_send() && !link.ticket_issued {
+                if let Some(session) = link.session.as_ref() {
+                    let ticket = self
+                        .resumption
+                        .issue(&link.contact, session, now, &mut self.rng);
+                    link.queued.push_back(Payload::Ticket(ticket.to_bytes()));
+                    link.ticket_issued = true;
+                }
+            }
+            while link.can_send() {
+                let Some(payload) = link.queued.pop_front() else {
+                    break;
+                };
+                let frame = match seal(*peer, link, &payload, &mut self.rng) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        log::warn!("testkit: failed to seal frame for {:?}: {}", peer, e);
+                        continue;
+                    }
+                };
+                self.outbox.push((*peer, datagram(link.path, &frame)));
+                link.unacked.insert(
+                    link.next_send_seq,
+                    Unacked {
+                        payload,
+                        frame,
+                        sent_at: now,
+                    },
+                );
+                link.next_send_seq += 1;
+            }
+            for unacked in link.unacked.values_mut() {
+                if now.saturating_sub(unacked.sent_at) >= self.retransmit_ticks {
+                    unacked.sent_at = now;
+                    self.outbox
+                        .push((*peer, datagram(link.path, &unacked.frame)));
+                }
+            }
+        }
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Process one datagram from the network. Malformed or stale frames are dropped.
+    pub fn handle_datagram(&mut self, from: EndpointId, bytes: &[u8]) {
+        let Some((path, frame)) = split_datagram(bytes) else {
+            return;
+        };
+        let Ok(frame) = bincode::deserialize::<Frame>(frame) else {
+            return;
+        };
+        match frame {
+            Frame::Ack { epoch, next_seq } => {
+                if let Some(link) = self.links.get_mut(&from) {
+                    if link.epoch == epoch && link.session.is_some() && link.path == path {
+                        link.unacked.retain(|seq, _| *seq >= next_seq);
+                        link.confirmed |= next_seq > 0;
+                    }
+                }
+            }
+            Frame::Data {
+                epoch,
+                seq,
+                init,
+                header,
+                ciphertext,
+            } => {
+                let plaintexts =
+                    self.receive_data(from, path, epoch, seq, init, header, ciphertext);
+                for plaintext in plaintexts {
+                    match bincode::deserialize::<Payload>(&plaintext) {
+                        Ok(payload) => self.deliver(from, payload),
+                        Err(e) => log::warn!("testkit: bad payload from {:?}: {}", from, e),
+                    }
+                }
+            }
+            Frame::Resume { epoch, request } => self.handle_resume(from, path, epoch, request),
+            Frame::ResumeAccept { epoch, accept } => {
+                self.handle_resume_accept(from, path, epoch, &accept)
+            }
+        }
+    }
+
+    /// Verify a peer's resume request and follow it to the new path.
+    fn handle_resume(&mut self, from: EndpointId, path: u32, epoch: u32, request: Vec<u8>) {
+        let Some(link) = self.links.get_mut(&from) else {
+            return;
+        };
+        if epoch != link.epoch || link.session.is_none() {
+            return;
+        }
+        if let Some((last, accept)) = &link.last_resume {
+            if *last == request {
+                self.outbox.push((from, datagram(link.path, accept)));
+                return;
+            }
+        }
+        // Stale path, unless both sides switched at once
+        if path < link.path || (path == link.path && link.resuming.is_none()) {
+            return;
+        }
+        let accept = match ResumeRequest::from_bytes(&request)
+            .and_then(|r| self.resumption.verify_resume(&link.contact, &r, self.now))
+        {
+            Ok(accept) => accept,
+            Err(e) => {
+                log::warn!("testkit: resume from {:?} rejected: {}", from, e);
+                return;
+            }
+        };
+        let Ok(frame) = encode(&Frame::ResumeAccept {
+            epoch,
+            accept: accept.to_bytes(),
+        }) else {
+            return;
+        };
+        link.path = path;
+        self.outbox.push((from, datagram(path, &frame)));
+        link.last_resume = Some((request, frame));
+        resend_unacked(from, link, self.now, &mut self.outbox);
+    }
+
+    /// Finish our own transport switch once the peer accepts.
+    fn handle_resume_accept(&mut self, from: EndpointId, path: u32, epoch: u32, accept: &[u8]) {
+        let Some(link) = self.links.get_mut(&from) else {
+            return;
+        };
+        if link.resuming.is_none() || epoch != link.epoch || path != link.path {
+            return;
+        }
+        match ResumeAccept::from_bytes(accept)
+            .and_then(|a| self.resumption.complete_resume(&link.contact, &a))
+        {
+            Ok(()) => {
+                link.resuming = None;
+                resend_unacked(from, link, self.now, &mut self.outbox);
+            }
+            Err(e) => log::warn!("testkit: resume accept from {:?} rejected: {}", from, e),
+        }
+    }
+
+    /// Handshake/epoch handling and in-order decryption. Returns plaintexts.
+    fn receive_data(
+        &mut self,
+        from: EndpointId,
+        path: u32,
+        epoch: u32,
+        seq: u64,
+        init: Option<[u8; 32]>,
+        header: RatchetHeader,
+        ciphertext: Vec<u8>,
+    ) -> Vec<Vec<u8>> {
+        let (static_public, static_secret) = (self.static_public, self.static_secret);
+        let our_identity = self.identity_public;
+        let Some(link) = self.links.get_mut(&from) else {
+            return Vec::new();
+        };
+        if path < link.path {
+            return Vec::new();
+        }
+
+        let accept = match (epoch.cmp(&link.epoch), link.role, init) {
+            (std::cmp::Ordering::Less, _, _) => return Vec::new(),
+            (std::cmp::Ordering::Greater, _, Some(peer_init)) => Some(peer_init),
+            (std::cmp::Ordering::Greater, _, None) => return Vec::new(),
+            (_, None, Some(peer_init)) => Some(peer_init),
+            (_, Some(LinkRole::Initiator { ephemeral_public }), Some(peer_init))
+                if peer_init != ephemeral_public =>
+            {
+                // Both sides opened this epoch; the lower identity key keeps its session
+                if our_identity < link.bundle.identity_public {
+                    return Vec::new();
+                }
+                Some(peer_init)
+            }
+            (_, Some(LinkRole::Responder { peer_init }), Some(init)) if init != peer_init => {
+                return Vec::new()
+            }
+            _ => None,
+        };
+        if let Some(peer_init) = accept {
+            let session = session_secret(&static_secret, &peer_init).and_then(|secret| {
+                PQDoubleRatchet::init_bob_with_rng(
+                    &secret,
+                    (static_public, static_secret),
+                    &mut self.rng,
+                )
+                .map_err(|e| TestkitError::Handshake(e.to_string()))
+            });
+            match session {
+                Ok(session) => {
+                    self.resumption.invalidate(&link.contact);
+                    link.reset_epoch(epoch, LinkRole::Responder { peer_init }, session);
+                    // A handshake authenticates whatever path it arrives on
+                    link.path = path;
+                }
+                Err(e) => {
+                    log::warn!("testkit: handshake from {:?} failed: {}", from, e);
+                    return Vec::new();
+                }
+            }
+        }
+        if link.session.is_none() || path != link.path {
+            return Vec::new();
+        }
+
+        if seq >= link.next_recv_seq {
+            link.reorder.entry(seq).or_insert((header, ciphertext));
+        }
+        let mut plaintexts = Vec::new();
+        while let Some((header, ciphertext)) = link.reorder.remove(&link.next_recv_seq) {
+            link.next_recv_seq += 1;
+            let session = link.session.as_mut().expect("checked above");
+            match session.decrypt_with_rng(&header, &ciphertext, &mut self.rng) {
+                Ok(plaintext) => {
+                    link.confirmed = true;
+                    plaintexts.push(plaintext);
+                }
+                Err(e) => log::warn!("testkit: decrypt from {:?} failed: {}", from, e),
+            }
+        }
+
+        // Ack everything, including duplicates, so a lost ack is repaired
+        if let Ok(ack) = encode(&Frame::Ack {
+            epoch: link.epoch,
+            next_seq: link.next_recv_seq,
+        }) {
+            self.outbox.push((from, datagram(link.path, &ack)));
+        }
+        plaintexts
+    }
+
+    fn deliver(&mut self, from: EndpointId, payload: Payload) {
+        match payload {
+            Payload::Handshake => {}
+            Payload::Text { id, body } => {
+                let fresh = self
+                    .links
+                    .get_mut(&from)
+                    .is_some_and(|l| l.seen_text.insert(id));
+                if fresh {
+                    self.received.push(ReceivedText { from, body });
+                }
+            }
+            #[cfg(feature = "groups")]
+            Payload::GroupOps(ops) => self.ingest_group_ops(ops),
+            #[cfg(not(feature = "groups"))]
+            Payload::GroupOps(_) => {}
+            Payload::Ticket(ticket) => {
+                let Some(link) = self.links.get(&from) else {
+                    return;
+                };
+                if let Err(e) = self
+                    .resumption
+                    .accept_ticket(&link.contact, &ticket, self.now)
+                {
+                    log::warn!("testkit: bad resumption ticket from {:?}: {}", from, e);
+                }
+            }
+        }
+    }
+}
+
+/// Encrypt a payload into a data frame for the link's current epoch.
+fn seal(
+    peer: EndpointId,
+    link: &mut Link,
+    payload: &Payload,
+    rng: &mut ChaCha20Rng,
+) -> Result<Vec<u8>, TestkitError> {
+    let plaintext = encode(payload)?;
+    let session = link.session.as_mut().ok_or(TestkitError::NoSession(peer))?;
+    let (header, ciphertext) = session
+        .encrypt_with_rng(&plaintext, rng)
+        .map_err(|e| TestkitError::Ratchet(e.to_string()))?;
+    let init = match link.role {
+        Some(LinkRole::Initiator { ephemeral_public }) => Some(ephemeral_public),
+        _ => None,
+    };
+    encode(&Frame::Data {
+        epoch: link.epoch,
+        seq: link.next_send_seq,
+        init,
+        header,
+        ciphertext,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Groups
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "groups")]
+impl Endpoint {
+    /// Create a group owned by this endpoint.
+    pub fn create_group(&mut self, name: &str) -> Result<GroupID, TestkitError> {
+        let mut random = [0u8; 32];
+        self.rng.fill(&mut random);
+        let gid = GroupID::new(&DeviceID::from_pubkey(&self.identity_public), &random);
+        let payload = GroupCreatePayload {
+            group_name: name.to_string(),
+            encrypted_group_secret: Vec::new(),
+        };
+        let op = OpEnvelope::create_signed(
+            gid,
+            OpType::GroupCreate,
+            &payload,
+            1,
+            self.rng.gen(),
+            self.identity_public,
+            &self.identity_secret,
+        )
+        .map_err(|e| TestkitError::Group(e.to_string()))?;
+        let mut replica = GroupReplica {
+            state: GroupState::new(gid),
+            log: Vec::new(),
+            pending: Vec::new(),
+        };
+        replica
+            .state
+            .apply_op(&op)
+            .map_err(|e| TestkitError::Group(e.to_string()))?;
+        replica.log.push(op);
+        self.groups.insert(gid, replica);
+        Ok(gid)
+    }
+
+    /// Invite a contact as a Member. The invitee receives the full op log.
+    pub fn invite(&mut self, gid: GroupID, peer: EndpointId) -> Result<(), TestkitError> {
+        let invited_pubkey = self
+            .links
+            .get(&peer)
+            .ok_or(TestkitError::UnknownPeer(peer))?
+            .bundle
+            .identity_public;
+        let payload = MemberInvitePayload {
+            invited_device_id: DeviceID::from_pubkey(&invited_pubkey),
+            invited_pubkey,
+            role: Role::Member,
+            encrypted_group_secret: Vec::new(),
+        };
+        let op = self.author_op(gid, OpType::MemberInvite, |_, _| payload)?;
+        self.broadcast(gid, &[op], Some(peer))?;
+        let log = self.groups[&gid].log.clone();
+        self.send_ops(peer, &log)
+    }
+
+    /// Accept a pending invite to `gid`.
+    pub fn accept_invite(&mut self, gid: GroupID) -> Result<(), TestkitError> {
+        let me = DeviceID::from_pubkey(&self.identity_public);
+        let invite_op_id = self
+            .groups
+            .get(&gid)
+            .ok_or(TestkitError::UnknownGroup)?
+            .state
+            .membership
+            .members()
+            .get(&me)
+            .filter(|m| !m.accepted)
+            .ok_or_else(|| TestkitError::Group("no pending invite".into()))?
+            .invited_by;
+        let op = self.author_op(gid, OpType::MemberAccept, |_, _| MemberAcceptPayload {
+            invite_op_id,
+            attribute_proof: None,
+        })?;
+        self.broadcast(gid, &[op], None)
+    }
+
+    /// Post a message to the group. The body is carried as-is; the testkit
+    /// does not model GroupSecret encryption.
+    pub fn post(&mut self, gid: GroupID, body: &[u8]) -> Result<[u8; 32], TestkitError> {
+        let author = self.identity_public;
+        let op = self.author_op(gid, OpType::MsgAdd, |lamport, nonce| MsgAddPayload {
+            msg_id: group_msg_id(&gid, &author, lamport, nonce),
+            ciphertext: body.to_vec(),
+            nonce: [0u8; 24],
+        })?;
+        let msg_id = group_msg_id(&gid, &author, op.lamport, op.op_id.nonce);
+        self.broadcast(gid, &[op], None)?;
+        Ok(msg_id)
+    }
+
+    /// This endpoint's replica of a group.
+    pub fn group(&self, gid: &GroupID) -> Option<&GroupState> {
+        self.groups.get(gid).map(|r| &r.state)
+    }
+
+    /// Sign, apply locally and log an op built from `(lamport, nonce)`.
+    fn author_op<P: Serialize>(
+        &mut self,
+        gid: GroupID,
+        op_type: OpType,
+        payload: impl FnOnce(u64, u64) -> P,
+    ) -> Result<OpEnvelope, TestkitError> {
+        let nonce: u64 = self.rng.gen();
+        let replica = self
+            .groups
+            .get_mut(&gid)
+            .ok_or(TestkitError::UnknownGroup)?;
+        let lamport = replica
+            .state
+            .max_lamport
+            .values()
+            .max()
+            .copied()
+            .unwrap_or(0)
+            + 1;
+        let op = OpEnvelope::create_signed(
+            gid,
+            op_type,
+            &payload(lamport, nonce),
+            lamport,
+            nonce,
+            self.identity_public,
+            &self.identity_secret,
+        )
+        .map_err(|e| TestkitError::Group(e.to_string()))?;
+        replica
+            .state
+            .apply_op(&op)
+            .map_err(|e| TestkitError::Group(e.to_string()))?;
+        replica.log.push(op.clone());
+        Ok(op)
+    }
+
+    /// Send ops to every other non-removed member except `skip`. Fails with
+    /// `NoSession` if a member has no session with this endpoint.
+    fn broadcast(
+        &mut self,
+        gid: GroupID,
+        ops: &[OpEnvelope],
+        skip: Option<EndpointId>,
+    ) -> Result<(), TestkitError> {
+        let members: Vec<[u8; 32]> = self.groups[&gid]
+            .state
+            .membership
+            .members()
+            .values()
+            .filter(|m| !m.removed && m.pubkey != self.identity_public)
+            .map(|m| m.pubkey)
+            .collect();
+        // BTreeMap order keeps network RNG draws stable across runs
+        let peers: Vec<EndpointId> = self
+            .links
+            .iter()
+            .filter(|(id, l)| Some(**id) != skip && members.contains(&l.bundle.identity_public))
+            .map(|(id, _)| *id)
+            .collect();
+        for peer in peers {
+            self.send_ops(peer, ops)?;
+        }
+        Ok(())
+    }
+
+    fn send_ops(&mut self, peer: EndpointId, ops: &[OpEnvelope]) -> Result<(), TestkitError> {
+        let encoded = ops
+            .iter()
+            .map(|op| {
+                op.to_bytes()
+                    .map_err(|e| TestkitError::Group(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.enqueue(peer, Payload::GroupOps(encoded))
+    }
+
+    /// Apply received ops, retrying deferred ones until no more progress is made.
+    fn ingest_group_ops(&mut self, ops: Vec<Vec<u8>>) {
+        for bytes in ops {
+            let Ok(op) = OpEnvelope::from_bytes(&bytes) else {
+                continue;
+            };
+            let replica = self
+                .groups
+                .entry(op.group_id)
+                .or_insert_with(|| GroupReplica {
+                    state: GroupState::new(op.group_id),
+                    log: Vec::new(),
+                    pending: Vec::new(),
+                });
+            if !replica.state.has_applied(&op.op_id) {
+                replica.pending.push(op);
+            }
+        }
+        for replica in self.groups.values_mut() {
+            replica.pending.sort_by_key(|op| op.op_id);
+            loop {
+                let before = replica.pending.len();
+                let mut still_pending = Vec::new();
+                for op in std::mem::take(&mut replica.pending) {
+                    match replica.state.apply_op(&op) {
+                        Ok(true) => replica.log.push(op),
+                        Ok(false) => {}
+                        Err(_) => still_pending.push(op),
+                    }
+                }
+                replica.pending = still_pending;
+                if replica.pending.is_empty() || replica.pending.len() == before {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+/// End-to-end test harness.
+///
+/// A [`Testnet`] runs several in-process [`Endpoint`]s over a seeded
+/// [`SimNetwork`] that drops, duplicates and reorders datagrams. Scenarios
+/// drive it step by step — connect, exchange messages, run group operations,
+/// rekey, switch transports — and call [`Testnet::run_until_idle`] between
+/// causally dependent steps. Network behaviour, endpoint keys, handshakes
+/// and every ratchet step are determined by the seed, so two runs with the
+/// same seed put byte-identical datagrams on the wire.
+///
+/// Enabled for this crate's own tests and, via the `testkit` feature, for
+/// downstream crates that want to exercise the protocol end to end.
+///
+/// ```ignore
+/// let mut net = Testnet::new(42, NetworkConfig::lossy());
+/// let (a, b) = (net.add_endpoint(), net.add_endpoint());
+/// net.connect(a, b)?;
+/// net.endpoint_mut(a).send_text(b, b"hello")?;
+/// net.run_until_idle(10_000)?;
+/// ```
+pub mod endpoint;
+pub mod network;
+
+pub use endpoint::{Endpoint, PeerBundle, ReceivedText, DEFAULT_RETRANSMIT_TICKS};
+pub use network::{Datagram, EndpointId, NetworkConfig, NetworkStats, SimNetwork};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TestkitError {
+    #[error("Unknown peer {0:?}")]
+    UnknownPeer(EndpointId),
+    #[error("No session with peer {0:?}")]
+    NoSession(EndpointId),
+    #[error("Unknown group")]
+    UnknownGroup,
+    #[error("Network not quiescent after {ticks} ticks")]
+    NotQuiescent { ticks: u64 },
+    #[error("Handshake failed: {0}")]
+    Handshake(String),
+    #[error("Ratchet error: {0}")]
+    Ratchet(String),
+    #[error("Encoding error: {0}")]
+    Encode(String),
+    #[error("Group operation failed: {0}")]
+    Group(String),
+    #[error("Session resumption failed: {0}")]
+    Resume(String),
+}
+
+/// A set of endpoints sharing one simulated network.
+pub struct Testnet {
+    seed: u64,
+    network: SimNetwork,
+    endpoints: Vec<Endpoint>,
+}
+
+impl Testnet {
+    pub fn new(seed: u64, config: NetworkConfig) -> Self {
+        Self {
+            seed,
+            network: SimNetwork::new(seed, config),
+            endpoints: Vec::new(),
+        }
+    }
+
+    /// Attach a new endpoint with fresh keys.
+    pub fn add_endpoint(&mut self) -> EndpointId {
+        let id = EndpointId(self.endpoints.len());
+        let seed = self.seed ^ ((id.0 as u64 + 1) << 32);
+        self.endpoints.push(Endpoint::new(id, seed));
+        id
+    }
+
+    /// Panics if `id` was not returned by `add_endpoint`.
+    pub fn endpoint(&self, id: EndpointId) -> &Endpoint {
+        &self.endpoints[id.0]
+    }
+
+    /// Panics if `id` was not returned by `add_endpoint`.
+    pub fn endpoint_mut(&mut self, id: EndpointId) -> &mut Endpoint {
+        &mut self.endpoints[id.0]
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    pub fn network_mut(&mut self) -> &mut SimNetwork {
+        &mut self.network
+    }
+
+    /// Exchange contact bundles and have `a` open a session to `b`.
+    pub fn connect(&mut self, a: EndpointId, b: EndpointId) -> Result<(), TestkitError> {
+        let (bundle_a, bundle_b) = (self.endpoint(a).bundle(), self.endpoint(b).bundle());
+        self.endpoint_mut(a).add_peer(b, bundle_b);
+        self.endpoint_mut(b).add_peer(a, bundle_a);
+        self.endpoint_mut(a).connect(b)
+    }
+
+    /// Let every endpoint transmit, advance the network one tick and deliver
+    /// what arrived.
+    pub fn step(&mut self) {
+        let now = self.network.now();
+        for endpoint in self.endpoints.iter_mut() {
+            let from = endpoint.id();
+            for (to, bytes) in endpoint.poll(now) {
+                self.network.send(from, to, bytes);
+            }
+        }
+        for datagram in self.network.tick() {
+            if let Some(endpoint) = self.endpoints.get_mut(datagram.to.0) {
+                endpoint.handle_datagram(datagram.from, &datagram.payload);
+            }
+        }
+    }
+
+    /// Step until nothing is queued, unacknowledged or in flight. Returns the
+    /// number of ticks taken.
+    pub fn run_until_idle(&mut self, max_ticks: u64) -> Result<u64, TestkitError> {
+        for tick in 0..max_ticks {
+            if self.is_idle() {
+                return Ok(tick);
+            }
+            self.step();
+        }
+        if self.is_idle() {
+            Ok(max_ticks)
+        } else {
+            Err(TestkitError::NotQuiescent { ticks: max_ticks })
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.network.is_idle() && self.endpoints.iter().all(|e| e.is_idle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_TICKS: u64 = 20_000;
+
+    fn texts(net: &mut Testnet, id: EndpointId) -> Vec<(EndpointId, Vec<u8>)> {
+        let mut received: Vec<_> = net
+            .endpoint_mut(id)
+            .take_received()
+            .into_iter()
+            .map(|t| (t.from, t.body))
+            .collect();
+        received.sort();
+        received
+    }
+
+    #[test]
+    fn test_direct_messages_survive_lossy_network_and_rekey() {
+        let mut net = Testnet::new(11, NetworkConfig::lossy());
+        let (a, b) = (net.add_endpoint(), net.add_endpoint());
+        net.connect(a, b).unwrap();
+        for i in 0..20u8 {
+            net.endpoint_mut(a).send_text(b, &[i]).unwrap();
+        }
+        net.run_until_idle(MAX_TICKS).unwrap();
+        assert!(net.endpoint(a).is_established(b));
+        assert!(net.endpoint(b).is_established(a));
+
+        // Replies, then a rekey with messages queued on both sides
+        net.endpoint_mut(b).send_text(a, b"reply").unwrap();
+        net.endpoint_mut(a).rekey(b).unwrap();
+        net.endpoint_mut(b).rekey(a).unwrap();
+        net.endpoint_mut(a).send_text(b, b"after rekey").unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        assert_eq!(net.endpoint(a).epoch(b), Some(2));
+        assert_eq!(net.endpoint(a).epoch(b), net.endpoint(b).epoch(a));
+        let mut expected: Vec<_> = (0..20u8).map(|i| (a, vec![i])).collect();
+        expected.push((a, b"after rekey".to_vec()));
+        expected.sort();
+        assert_eq!(texts(&mut net, b), expected);
+        assert_eq!(texts(&mut net, a), vec![(b, b"reply".to_vec())]);
+        assert!(net.network().stats().dropped > 0);
+    }
+
+    #[cfg(feature = "groups")]
+    #[test]
+    fn test_group_replicas_converge() {
+        let mut net = Testnet::new(5, NetworkConfig::lossy());
+        let ids: Vec<_> = (0..3).map(|_| net.add_endpoint()).collect();
+        for (i, &x) in ids.iter().enumerate() {
+            for &y in &ids[i + 1..] {
+                net.connect(x, y).unwrap();
+            }
+        }
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        let (a, b, c) = (ids[0], ids[1], ids[2]);
+        let gid = net.endpoint_mut(a).create_group("testkit").unwrap();
+        net.endpoint_mut(a).invite(gid, b).unwrap();
+        net.endpoint_mut(a).invite(gid, c).unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+        net.endpoint_mut(b).accept_invite(gid).unwrap();
+        net.endpoint_mut(c).accept_invite(gid).unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        for (n, &id) in ids.iter().enumerate() {
+            net.endpoint_mut(id).post(gid, &[n as u8]).unwrap();
+        }
+        net.endpoint_mut(b).rekey(c).unwrap();
+        net.endpoint_mut(c).post(gid, b"after rekey").unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        let hash = net.endpoint(a).group(&gid).unwrap().state_hash();
+        for &id in &ids {
+            let group = net.endpoint(id).group(&gid).unwrap();
+            assert_eq!(group.state_hash(), hash);
+            assert_eq!(group.renderable_messages().len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_transport_switch_resumes_without_handshake() {
+        let mut net = Testnet::new(21, NetworkConfig::lossy());
+        let (a, b) = (net.add_endpoint(), net.add_endpoint());
+        net.connect(a, b).unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        // Switch mid-conversation with frames in flight both ways
+        for i in 0..10u8 {
+            net.endpoint_mut(a).send_text(b, &[i]).unwrap();
+            net.endpoint_mut(b).send_text(a, &[i]).unwrap();
+        }
+        for _ in 0..3 {
+            net.step();
+        }
+        net.endpoint_mut(a).switch_transport(b).unwrap();
+        net.network_mut().set_config(NetworkConfig::reliable());
+        for i in 10..15u8 {
+            net.endpoint_mut(a).send_text(b, &[i]).unwrap();
+        }
+        net.run_until_idle(MAX_TICKS).unwrap();
+
+        assert_eq!(net.endpoint(a).path(b), Some(1));
+        assert_eq!(net.endpoint(b).path(a), Some(1));
+        let expected: Vec<_> = (0..15u8).map(|i| (a, vec![i])).collect();
+        assert_eq!(texts(&mut net, b), expected);
+        let expected: Vec<_> = (0..10u8).map(|i| (b, vec![i])).collect();
+        assert_eq!(texts(&mut net, a), expected);
+
+        // And back again, initiated by the other side, over a lossy link
+        net.network_mut().set_config(NetworkConfig::lossy());
+        net.endpoint_mut(b).switch_transport(a).unwrap();
+        net.endpoint_mut(b).send_text(a, b"back").unwrap();
+        net.run_until_idle(MAX_TICKS).unwrap();
+        assert_eq!(net.endpoint(a).path(b), Some(2));
+        assert_eq!(texts(&mut net, a), vec![(b, b"back".to_vec())]);
+
+        // Neither switch re-ran the handshake
+        assert_eq!(net.endpoint(a).epoch(b), Some(1));
+        assert_eq!(net.endpoint(b).epoch(a), Some(1));
+    }
+
+    #[test]
+    fn test_same_seed_same_schedule() {
+        let run = |seed: u64| {
+            let mut net = Testnet::new(seed, NetworkConfig::lossy());
+            let (a, b) = (net.add_endpoint(), net.add_endpoint());
+            net.connect(a, b).unwrap();
+            for i in 0..10u8 {
+                net.endpoint_mut(a).send_text(b, &[i]).unwrap();
+            }
+            let ticks = net.run_until_idle(MAX_TICKS).unwrap();
+            (ticks, net.network().stats())
+        };
+        assert_eq!(run(3), run(3));
+    }
+
+    #[test]
+    fn test_same_seed_same_ciphertext() {
+        // Like `run_until_idle`, but keeps every datagram put on the wire.
+        fn drain(net: &mut Testnet, wire: &mut Vec<Vec<u8>>) {
+            for _ in 0..MAX_TICKS {
+                if net.is_idle() {
+                    return;
+                }
+                let now = net.network.now();
+                for endpoint in net.endpoints.iter_mut() {
+                    let from = endpoint.id();
+                    for (to, bytes) in endpoint.poll(now) {
+                        wire.push(bytes.clone());
+                        net.network.send(from, to, bytes);
+                    }
+                }
+                for datagram in net.network.tick() {
+                    net.endpoints[datagram.to.0].handle_datagram(datagram.from, &datagram.payload);
+                }
+            }
+            panic!("network did not go idle");
+        }
+
+        let run = |seed: u64| {
+            let mut net = Testnet::new(seed, NetworkConfig::lossy());
+            let (a, b) = (net.add_endpoint(), net.add_endpoint());
+            let mut wire = Vec::new();
+            net.connect(a, b).unwrap();
+            drain(&mut net, &mut wire);
+            for i in 0..10u8 {
+                net.endpoint_mut(a).send_text(b, &[i]).unwrap();
+                net.endpoint_mut(b).send_text(a, &[i]).unwrap();
+            }
+            drain(&mut net, &mut wire);
+            wire
+        };
+        let first = run(5);
+        assert!(!first.is_empty());
+        assert_eq!(first, run(5));
+        assert_ne!(first, run(6));
+    }
+}
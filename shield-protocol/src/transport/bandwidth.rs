@@ -0,0 +1,449 @@
+//! Upstream bandwidth cap with fair scheduling.
+//!
+//! [`BandwidthShaper`] holds outbound items back so that the aggregate rate
+//! stays under `max_bytes_per_sec` (a token bucket allowing `burst_bytes`
+//! after an idle period), and shares that rate between flows (conversations,
+//! transfers) by deficit round robin: each flow may send `quantum_bytes` per
+//! turn, so one large upload cannot crowd out a second one started later.
+//!
+//! Capped bulk traffic leaves as a paced stream rather than line-rate bursts,
+//! but its start, gaps and end would still mark a transfer's timing and
+//! size. When a flow's queue drains, the shaper therefore keeps the flow's
+//! pace for a random tail of up to `max_cover_tail`, releasing
+//! [`Release::Cover`] slots the caller fills with cover packets. Gaps between
+//! windows of a transfer and its end look like the transfer itself; new
+//! items for the flow end the tail. Cover slots come last and use the same
+//! bandwidth budget, so they never delay real traffic beyond the cap.
+//!
+//! Traffic that must not wait (pings, ACKs) is [`charge`](BandwidthShaper::charge)d
+//! instead: it goes out at once and the budget is paid back by queued items.
+//!
+//! The bucket refills from the `now` passed to `charge` and `poll`, so a
+//! caller that polls late is credited for the whole gap, up to `burst_bytes`.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::rng::SecureRng;
+
+/// Default bytes per flow per round-robin turn.
+pub const DEFAULT_QUANTUM_BYTES: usize = 16 * 1024;
+
+/// Default bucket size.
+pub const DEFAULT_BURST_BYTES: u64 = 64 * 1024;
+
+/// Default longest cover tail after a flow drains.
+pub const DEFAULT_COVER_TAIL_SECS: u64 = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthConfig {
+    /// Aggregate upstream cap; 0 means unlimited (no shaping, no tails).
+    pub max_bytes_per_sec: u64,
+    /// Bytes that may leave back to back after an idle period.
+    pub burst_bytes: u64,
+    /// Bytes a flow may send per turn.
+    pub quantum_bytes: usize,
+    /// Longest cover tail; zero disables tails.
+    pub max_cover_tail: Duration,
+}
+
+impl BandwidthConfig {
+    pub const UNLIMITED: Self = Self {
+        max_bytes_per_sec: 0,
+        burst_bytes: DEFAULT_BURST_BYTES,
+        quantum_bytes: DEFAULT_QUANTUM_BYTES,
+        max_cover_tail: Duration::from_secs(DEFAULT_COVER_TAIL_SECS),
+    };
+
+    /// Capped at `bytes_per_sec` with the default burst, quantum and tail.
+    pub fn limited(bytes_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec: bytes_per_sec,
+            ..Self::UNLIMITED
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.max_bytes_per_sec > 0
+    }
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+/// What the caller sends next.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Release<K, T> {
+    /// A queued item.
+    Send { flow: K, item: T },
+    /// A cover packet toward `flow`, keeping its pace after the queue drained.
+    Cover { flow: K },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShaperStats {
+    /// Bytes released from the queues.
+    pub sent_bytes: u64,
+    /// Bytes charged without queueing.
+    pub charged_bytes: u64,
+    pub cover_packets: u64,
+    pub cover_bytes: u64,
+    /// Items queued behind other items or an empty bucket.
+    pub delayed: u64,
+    /// Items waiting now.
+    pub queued: usize,
+}
+
+#[derive(Debug)]
+struct Queue<T> {
+    items: VecDeque<(T, usize)>,
+    deficit: usize,
+    /// Whether this turn's quantum was already added.
+    credited: bool,
+    last_bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Tail {
+    until: Instant,
+    next: Instant,
+    interval: Duration,
+    packet_bytes: usize,
+}
+
+/// Token bucket plus deficit round robin over flows `K`.
+#[derive(Debug)]
+pub struct BandwidthShaper<K, T> {
+    config: BandwidthConfig,
+    tokens: f64,
+    refilled_at: Instant,
+    queues: HashMap<K, Queue<T>>,
+    /// Flows with queued items, in turn order.
+    round: VecDeque<K>,
+    tails: HashMap<K, Tail>,
+    stats: ShaperStats,
+}
+
+impl<K: Hash + Eq + Clone, T> BandwidthShaper<K, T> {
+    pub fn new(config: BandwidthConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst_bytes as f64,
+            config,
+            refilled_at: now,
+            queues: HashMap::new(),
+            round: VecDeque::new(),
+            tails: HashMap::new(),
+            stats: ShaperStats::default(),
+        }
+    }
+
+    pub fn config(&self) -> &BandwidthConfig {
+        &self.config
+    }
+
+    /// Change the cap. Queued items stay queued; with the cap removed the
+    /// next `poll` releases them all and running tails end.
+    pub fn set_config(&mut self, config: BandwidthConfig, now: Instant) {
+        self.refill(now);
+        if !config.is_limited() {
+            self.tails.clear();
+        }
+        self.tokens = self.tokens.min(config.burst_bytes as f64);
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> ShaperStats {
+        ShaperStats {
+            queued: self.queues.values().map(|q| q.items.len()).sum(),
+            ..self.stats
+        }
+    }
+
+    /// Nothing queued and no tail running.
+    pub fn is_idle(&self) -> bool {
+        self.round.is_empty() && self.tails.is_empty()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.config.is_limited() {
+            let elapsed = now
+                .saturating_duration_since(self.refilled_at)
+                .as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.config.max_bytes_per_sec as f64)
+                .min(self.config.burst_bytes as f64);
+        }
+        self.refilled_at = now;
+    }
+
+    fn has_budget(&self) -> bool {
+        !self.config.is_limited() || self.tokens > 0.0
+    }
+
+    /// Account for `bytes` sent without queueing (latency-critical traffic).
+    pub fn charge(&mut self, bytes: usize, now: Instant) {
+        self.refill(now);
+        if self.config.is_limited() {
+            self.tokens -= bytes as f64;
+        }
+        self.stats.charged_bytes += bytes as u64;
+    }
+
+    /// Queue `item` of `bytes` for `flow`; ends the flow's cover tail.
+    pub fn enqueue(&mut self, flow: K, item: T, bytes: usize) {
+        self.tails.remove(&flow);
+        let waits = !self.round.is_empty() || !self.has_budget();
+        let queue = self.queues.entry(flow.clone()).or_insert_with(|| Queue {
+            items: VecDeque::new(),
+            deficit: 0,
+            credited: false,
+            last_bytes: bytes,
+        });
+        if queue.items.is_empty() {
+            self.round.push_back(flow);
+        }
+        if waits {
+            self.stats.delayed += 1;
+        }
+        queue.items.push_back((item, bytes));
+    }
+
+    /// Drop `flow`'s queue and tail, returning its items.
+    pub fn remove_flow(&mut self, flow: &K) -> Vec<T> {
+        self.tails.remove(flow);
+        self.round.retain(|f| f != flow);
+        self.queues
+            .remove(flow)
+            .map(|q| q.items.into_iter().map(|(item, _)| item).collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop everything, returning the queued items.
+    pub fn clear(&mut self) -> Vec<T> {
+        self.tails.clear();
+        self.round.clear();
+        self.queues
+            .drain()
+            .flat_map(|(_, q)| q.items.into_iter().map(|(item, _)| item))
+            .collect()
+    }
+
+    fn start_tail(&mut self, flow: K, packet_bytes: usize, now: Instant, rng: &mut impl SecureRng) {
+        let max_ms = self.config.max_cover_tail.as_millis() as u64;
+        if !self.config.is_limited() || max_ms == 0 {
+            return;
+        }
+        // The flow's fair share while it was sending
+        let sharing = self.round.len() as u64 + 1;
+        let share = (self.config.max_bytes_per_sec / sharing).max(1);
+        let interval = Duration::from_secs_f64(packet_bytes as f64 / share as f64);
+        let length = Duration::from_millis(rng.next_u64() % (max_ms + 1));
+        self.tails.insert(
+            flow,
+            Tail {
+                until: now + length,
+                next: now + interval,
+                interval,
+                packet_bytes,
+            },
+        );
+    }
+
+    /// Items (fairly, within budget) and then cover slots due at `now`.
+    pub fn poll(&mut self, now: Instant, rng: &mut impl SecureRng) -> Vec<Release<K, T>> {
+        self.refill(now);
+        let mut out = Vec::new();
+
+        while let Some(flow) = self.round.front().cloned() {
+            if !self.has_budget() {
+                break;
+            }
+            let queue = self.queues.get_mut(&flow).expect("queued flow");
+            if !queue.credited {
+                queue.deficit += self.config.quantum_bytes.max(1);
+                queue.credited = true;
+            }
+            while let Some(&(_, bytes)) = queue.items.front() {
+                if bytes > queue.deficit || (self.config.is_limited() && self.tokens <= 0.0) {
+                    break;
+                }
+                let (item, bytes) = queue.items.pop_front().expect("front");
+                queue.deficit -= bytes;
+                queue.last_bytes = bytes;
+                if self.config.is_limited() {
+                    self.tokens -= bytes as f64;
+                }
+                self.stats.sent_bytes += bytes as u64;
+                out.push(Release::Send {
+                    flow: flow.clone(),
+                    item,
+                });
+            }
+
+            match queue.items.front() {
+                None => {
+                    let last_bytes = queue.last_bytes;
+                    self.queues.remove(&flow);
+                    self.round.pop_front();
+                    self.start_tail(flow, last_bytes, now, rng);
+                }
+                Some(&(_, bytes)) if bytes > queue.deficit => {
+                    // Turn used up
+                    queue.credited = false;
+                    self.round.rotate_left(1);
+                }
+                // Out of budget mid-turn; the flow keeps its turn
+                Some(_) => break,
+            }
+        }
+
+        self.tails.retain(|_, tail| tail.until > now);
+        for (flow, tail) in self.tails.iter_mut() {
+            if now < tail.next || self.tokens <= 0.0 {
+                continue;
+            }
+            self.tokens -= tail.packet_bytes as f64;
+            self.stats.cover_packets += 1;
+            self.stats.cover_bytes += tail.packet_bytes as u64;
+            tail.next = now + tail.interval;
+            out.push(Release::Cover { flow: flow.clone() });
+        }
+        out
+    }
+
+    /// When `poll` next has something to release, for the caller's timer.
+    pub fn next_wakeup(&self, now: Instant) -> Option<Instant> {
+        let budget_at = if self.has_budget() {
+            now
+        } else {
+            let missing = 1.0 - self.tokens;
+            now + Duration::from_secs_f64(missing / self.config.max_bytes_per_sec as f64)
+        };
+        let queued = (!self.round.is_empty()).then_some(budget_at);
+        let tail = self
+            .tails
+            .values()
+            .map(|tail| tail.next.max(budget_at).min(tail.until))
+            .min();
+        match (queued, tail) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    fn sends<K: Clone, T: Clone>(releases: &[Release<K, T>]) -> Vec<(K, T)> {
+        releases
+            .iter()
+            .filter_map(|r| match r {
+                Release::Send { flow, item } => Some((flow.clone(), item.clone())),
+                Release::Cover { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rate_cap_shares_fairly_between_flows() {
+        let mut rng = seeded(1);
+        let start = Instant::now();
+        let config = BandwidthConfig {
+            burst_bytes: 1000,
+            quantum_bytes: 1000,
+            max_cover_tail: Duration::ZERO,
+            ..BandwidthConfig::limited(1000)
+        };
+        let mut shaper = BandwidthShaper::new(config, start);
+        for i in 0..10 {
+            shaper.enqueue("upload", i, 1000);
+        }
+        for i in 0..2 {
+            shaper.enqueue("chat", 100 + i, 1000);
+        }
+
+        let mut released = Vec::new();
+        for second in 0..=5 {
+            let now = start + Duration::from_secs(second);
+            released.extend(sends(&shaper.poll(now, &mut rng)));
+            if let Some(wake) = shaper.next_wakeup(now) {
+                assert!(wake <= now + Duration::from_secs(1));
+            }
+        }
+        // One 1000-byte item per second, plus the initial burst
+        assert_eq!(released.len(), 6);
+        // The later flow is not stuck behind the upload
+        assert_eq!(
+            released.iter().map(|(_, i)| *i).collect::<Vec<_>>(),
+            vec![0, 100, 1, 101, 2, 3]
+        );
+        assert_eq!(shaper.stats().queued, 6);
+
+        // Unshaped traffic borrows from the budget
+        shaper.charge(3000, start + Duration::from_secs(5));
+        assert!(shaper
+            .poll(start + Duration::from_secs(7), &mut rng)
+            .is_empty());
+        assert_eq!(
+            shaper.next_wakeup(start + Duration::from_secs(7)),
+            Some(start + Duration::from_millis(8001))
+        );
+
+        // Removing the cap releases the rest at once
+        shaper.set_config(BandwidthConfig::UNLIMITED, start + Duration::from_secs(8));
+        assert_eq!(
+            shaper.poll(start + Duration::from_secs(8), &mut rng).len(),
+            6
+        );
+        assert!(shaper.is_idle());
+    }
+
+    #[test]
+    fn test_drained_flow_keeps_its_pace_with_cover() {
+        let mut rng = seeded(2);
+        let start = Instant::now();
+        let config = BandwidthConfig {
+            burst_bytes: 500,
+            quantum_bytes: 500,
+            max_cover_tail: Duration::from_secs(60),
+            ..BandwidthConfig::limited(500)
+        };
+        let mut shaper = BandwidthShaper::new(config, start);
+        shaper.enqueue("peer", 1, 500);
+        assert_eq!(sends(&shaper.poll(start, &mut rng)), vec![("peer", 1)]);
+        assert!(!shaper.is_idle(), "a tail follows the drained queue");
+
+        // One cover slot per second (500 bytes at 500 B/s) until the tail ends
+        let mut covers = 0;
+        let mut second = 1;
+        while !shaper.is_idle() {
+            let now = start + Duration::from_secs(second);
+            for release in shaper.poll(now, &mut rng) {
+                assert_eq!(release, Release::Cover { flow: "peer" });
+                covers += 1;
+            }
+            second += 1;
+            assert!(second <= 62);
+        }
+        assert!(covers > 0 && covers <= 60);
+        assert_eq!(shaper.stats().cover_bytes, covers * 500);
+
+        // Real traffic ends a running tail
+        shaper.enqueue("peer", 2, 500);
+        let now = start + Duration::from_secs(100);
+        assert_eq!(sends(&shaper.poll(now, &mut rng)), vec![("peer", 2)]);
+        shaper.enqueue("peer", 3, 500);
+        assert!(shaper
+            .poll(now, &mut rng)
+            .iter()
+            .all(|r| !matches!(r, Release::Cover { .. })));
+        assert_eq!(shaper.remove_flow(&"peer"), vec![3]);
+        assert!(shaper.is_idle());
+    }
+}
@@ -0,0 +1,213 @@
+//! Byte channels: the link a transport hands framed packets to.
+//!
+//! Everything above this layer (padding, coalescing, cover flows) produces
+//! whole frames and consumes whole frames, so a [`Channel`] only needs to
+//! carry opaque, ordered, reliable messages to one peer. Native builds reach
+//! peers through Tor sockets in the core; browsers cannot open SOCKS
+//! connections and use the adapters in [`super::web`] instead.
+//!
+//! Channels are polled rather than callback-driven: browser events land in a
+//! queue that [`Channel::poll_recv`] drains, so the same driving loop works
+//! over any implementation, including [`MemoryChannel`] in tests.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    #[error("Channel is not open yet")]
+    NotOpen,
+    #[error("Channel closed: {0}")]
+    Closed(String),
+    #[error("Frame of {size} bytes exceeds the channel limit of {max}")]
+    FrameTooLarge { size: usize, max: usize },
+    #[error("Send buffer full, retry after it drains")]
+    WouldBlock,
+    #[error("Invalid endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("Unsupported channel: {0}")]
+    Unsupported(String),
+    #[error("Send failed: {0}")]
+    Send(String),
+}
+
+/// Lifecycle of a channel. A closed channel never reopens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelState {
+    Connecting,
+    Open,
+    Closed,
+}
+
+/// An ordered, reliable, message-oriented link to one peer.
+pub trait Channel {
+    fn state(&self) -> ChannelState;
+
+    /// Largest frame [`send`](Self::send) accepts.
+    fn max_frame_len(&self) -> usize;
+
+    /// Queue one frame for delivery. Fails with
+    /// [`NotOpen`](ChannelError::NotOpen) while connecting and
+    /// [`WouldBlock`](ChannelError::WouldBlock) while the outgoing buffer is
+    /// over its limit; the caller keeps the frame and retries.
+    fn send(&mut self, frame: &[u8]) -> Result<(), ChannelError>;
+
+    /// Frames received since the last call, oldest first. Never blocks.
+    fn poll_recv(&mut self) -> Vec<Vec<u8>>;
+
+    /// Why the channel closed, once it has.
+    fn close_reason(&self) -> Option<String>;
+
+    fn close(&mut self);
+}
+
+/// Frames and state shared between a channel and the events feeding it.
+#[derive(Debug)]
+pub(crate) struct Inbox {
+    pub state: ChannelState,
+    pub frames: VecDeque<Vec<u8>>,
+    pub close_reason: Option<String>,
+    pub max_frame_len: usize,
+}
+
+impl Inbox {
+    pub fn new(state: ChannelState, max_frame_len: usize) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            state,
+            frames: VecDeque::new(),
+            close_reason: None,
+            max_frame_len,
+        }))
+    }
+
+    /// Queue an arriving frame; an oversized one closes the channel, since
+    /// the peer is not speaking our framing.
+    pub fn deliver(&mut self, frame: Vec<u8>) {
+        if self.state == ChannelState::Closed {
+            return;
+        }
+        if frame.len() > self.max_frame_len {
+            self.close(format!(
+                "peer sent a {}-byte frame (limit {})",
+                frame.len(),
+                self.max_frame_len
+            ));
+            return;
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn close(&mut self, reason: String) {
+        if self.state != ChannelState::Closed {
+            self.state = ChannelState::Closed;
+            self.close_reason = Some(reason);
+        }
+    }
+
+    pub fn check_send(&self, frame: &[u8]) -> Result<(), ChannelError> {
+        match self.state {
+            ChannelState::Connecting => return Err(ChannelError::NotOpen),
+            ChannelState::Closed => {
+                return Err(ChannelError::Closed(
+                    self.close_reason.clone().unwrap_or_default(),
+                ))
+            }
+            ChannelState::Open => {}
+        }
+        if frame.len() > self.max_frame_len {
+            return Err(ChannelError::FrameTooLarge {
+                size: frame.len(),
+                max: self.max_frame_len,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// In-process channel, for tests and for wiring two local endpoints.
+pub struct MemoryChannel {
+    own: Rc<RefCell<Inbox>>,
+    peer: Rc<RefCell<Inbox>>,
+}
+
+impl MemoryChannel {
+    /// Two open channels connected to each other.
+    pub fn pair(max_frame_len: usize) -> (Self, Self) {
+        let a = Inbox::new(ChannelState::Open, max_frame_len);
+        let b = Inbox::new(ChannelState::Open, max_frame_len);
+        (
+            Self {
+                own: a.clone(),
+                peer: b.clone(),
+            },
+            Self { own: b, peer: a },
+        )
+    }
+}
+
+impl Channel for MemoryChannel {
+    fn state(&self) -> ChannelState {
+        self.own.borrow().state
+    }
+
+    fn max_frame_len(&self) -> usize {
+        self.own.borrow().max_frame_len
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<(), ChannelError> {
+        self.own.borrow().check_send(frame)?;
+        self.peer.borrow_mut().deliver(frame.to_vec());
+        Ok(())
+    }
+
+    fn poll_recv(&mut self) -> Vec<Vec<u8>> {
+        self.own.borrow_mut().frames.drain(..).collect()
+    }
+
+    fn close_reason(&self) -> Option<String> {
+        self.own.borrow().close_reason.clone()
+    }
+
+    fn close(&mut self) {
+        self.own.borrow_mut().close("closed locally".into());
+        self.peer.borrow_mut().close("closed by peer".into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pair_delivers_in_order() {
+        let (mut a, mut b) = MemoryChannel::pair(16);
+        a.send(b"one").unwrap();
+        a.send(b"two").unwrap();
+        assert_eq!(b.poll_recv(), vec![b"one".to_vec(), b"two".to_vec()]);
+        assert!(b.poll_recv().is_empty());
+        assert_eq!(
+            a.send(&[0; 17]),
+            Err(ChannelError::FrameTooLarge { size: 17, max: 16 })
+        );
+
+        b.close();
+        assert_eq!(a.state(), ChannelState::Closed);
+        assert_eq!(a.close_reason().as_deref(), Some("closed by peer"));
+        assert!(matches!(a.send(b"late"), Err(ChannelError::Closed(_))));
+    }
+
+    #[test]
+    fn test_oversized_incoming_frame_closes() {
+        let inbox = Inbox::new(ChannelState::Connecting, 4);
+        assert_eq!(inbox.borrow().check_send(b"hi"), Err(ChannelError::NotOpen));
+        inbox.borrow_mut().state = ChannelState::Open;
+        inbox.borrow_mut().deliver(vec![1; 4]);
+        inbox.borrow_mut().deliver(vec![1; 5]);
+        inbox.borrow_mut().deliver(vec![1; 1]);
+        let inbox = inbox.borrow();
+        assert_eq!(inbox.state, ChannelState::Closed);
+        assert_eq!(inbox.frames.len(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Send coalescing: pack several small frames into one fixed-size packet.
+//!
+//! Every frame normally becomes its own padded packet, so a burst of ACKs,
+//! receipts, and reactions costs one full `fixed_packet_size()` each. The
+//! `Coalescer` holds small frames for up to a latency budget (Nagle-style)
+//! and packs as many as fit into a single padded packet. Under cover traffic
+//! the pending batch can also ride in the next cover slot instead of a dummy
+//! packet, so real frames cost no extra bandwidth at all.
+//!
+//! Coalesced layout (inside the padded payload):
+//! `[0xFE][count:1]` followed by `count × [len:2 BE][frame]`.
+//!
+//! Time is passed in by the caller (`Instant`) so the transport loop owns the
+//! clock and tests stay deterministic.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::padding::{generate_cover_packet, max_padded_payload, pad_to_fixed_size, PaddingError};
+
+/// Coalesced packet marker (0xFE — next to `MSG_TYPE_COVER`, not a real protocol type).
+pub const MSG_TYPE_COALESCED: u8 = 0xFE;
+
+/// Marker + frame count.
+const BATCH_HEADER: usize = 2;
+/// Per-frame length prefix.
+const FRAME_LEN_FIELD: usize = 2;
+/// Frame count is a single byte.
+const MAX_FRAMES_PER_PACKET: usize = u8::MAX as usize;
+
+/// Default time a frame may wait for companions.
+pub const DEFAULT_COALESCE_BUDGET_MS: u64 = 50;
+
+#[derive(Debug, Clone)]
+pub struct CoalesceConfig {
+    /// Maximum time the oldest pending frame may wait before a flush.
+    pub latency_budget: Duration,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self {
+            latency_budget: Duration::from_millis(DEFAULT_COALESCE_BUDGET_MS),
+        }
+    }
+}
+
+/// Largest frame that fits in a coalesced packet at the current packet size.
+pub fn max_coalesced_frame() -> usize {
+    max_padded_payload() - BATCH_HEADER - FRAME_LEN_FIELD
+}
+
+/// Pending-frame buffer for one connection.
+#[derive(Debug)]
+pub struct Coalescer {
+    config: CoalesceConfig,
+    pending: VecDeque<Vec<u8>>,
+    /// Encoded size of `pending` (header + length prefixes + frames).
+    pending_bytes: usize,
+    /// When the oldest pending frame was queued.
+    oldest: Option<Instant>,
+}
+
+impl Coalescer {
+    pub fn new(config: CoalesceConfig) -> Self {
+        Self {
+            config,
+            pending: VecDeque::new(),
+            pending_bytes: BATCH_HEADER,
+            oldest: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn pending_frames(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Queue a frame. Returns packets that must be sent now: the current batch
+    /// is flushed first if the new frame would not fit alongside it.
+    pub fn push(&mut self, frame: Vec<u8>, now: Instant) -> Result<Vec<Vec<u8>>, PaddingError> {
+        if frame.len() > max_coalesced_frame() {
+            return Err(PaddingError::PayloadTooLarge(max_coalesced_frame()));
+        }
+
+        let mut ready = Vec::new();
+        let frame_cost = FRAME_LEN_FIELD + frame.len();
+        if self.pending_bytes + frame_cost > max_padded_payload()
+            || self.pending.len() == MAX_FRAMES_PER_PACKET
+        {
+            if let Some(packet) = self.flush()? {
+                ready.push(packet);
+            }
+        }
+
+        self.pending_bytes += frame_cost;
+        self.pending.push_back(frame);
+        self.oldest.get_or_insert(now);
+        Ok(ready)
+    }
+
+    /// When the pending batch must be flushed, if anything is pending.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.oldest.map(|t| t + self.config.latency_budget)
+    }
+
+    /// Flush if the latency budget of the oldest frame has expired.
+    pub fn poll(&mut self, now: Instant) -> Result<Option<Vec<u8>>, PaddingError> {
+        match self.next_deadline() {
+            Some(deadline) if now >= deadline => self.flush(),
+            _ => Ok(None),
+        }
+    }
+
+    /// Packet for a cover-traffic slot: the pending batch if there is one,
+    /// otherwise an ordinary cover packet. Both look identical on the wire.
+    pub fn cover_slot(&mut self) -> Result<Vec<u8>, PaddingError> {
+        match self.flush()? {
+            Some(packet) => Ok(packet),
+            None => generate_cover_packet(),
+        }
+    }
+
+    /// Pack all pending frames into one padded packet.
+    pub fn flush(&mut self) -> Result<Option<Vec<u8>>, PaddingError> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+
+        let mut payload = Vec::with_capacity(self.pending_bytes);
+        payload.push(MSG_TYPE_COALESCED);
+        payload.push(self.pending.len() as u8);
+        for frame in self.pending.drain(..) {
+            payload.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+            payload.extend_from_slice(&frame);
+        }
+        self.pending_bytes = BATCH_HEADER;
+        self.oldest = None;
+
+        pad_to_fixed_size(&payload).map(Some)
+    }
+}
+
+/// Returns true if the (unpadded) payload is a coalesced batch.
+#[inline]
+pub fn is_coalesced_packet(unpadded: &[u8]) -> bool {
+    !unpadded.is_empty() && unpadded[0] == MSG_TYPE_COALESCED
+}
+
+/// Split an unpadded coalesced payload back into its frames.
+pub fn decode_coalesced(unpadded: &[u8]) -> Result<Vec<Vec<u8>>, PaddingError> {
+    if unpadded.len() < BATCH_HEADER || unpadded[0] != MSG_TYPE_COALESCED {
+        return Err(PaddingError::InvalidPaddedPayload);
+    }
+    let count = unpadded[1] as usize;
+    let mut frames = Vec::with_capacity(count);
+    let mut rest = &unpadded[BATCH_HEADER..];
+    for _ in 0..count {
+        if rest.len() < FRAME_LEN_FIELD {
+            return Err(PaddingError::InvalidPaddedPayload);
+        }
+        let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+        rest = &rest[FRAME_LEN_FIELD..];
+        if rest.len() < len {
+            return Err(PaddingError::InvalidPaddedPayload);
+        }
+        frames.push(rest[..len].to_vec());
+        rest = &rest[len..];
+    }
+    if !rest.is_empty() {
+        return Err(PaddingError::InvalidPaddedPayload);
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::padding::{is_cover_packet, strip_padding};
+
+    fn decode(packet: &[u8]) -> Vec<Vec<u8>> {
+        decode_coalesced(&strip_padding(packet).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_small_frames_share_one_packet() {
+        let mut c = Coalescer::new(CoalesceConfig::default());
+        let t0 = Instant::now();
+        for i in 0..5u8 {
+            assert!(c.push(vec![i; 20], t0).unwrap().is_empty());
+        }
+        let packet = c.flush().unwrap().unwrap();
+        let frames = decode(&packet);
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[3], vec![3u8; 20]);
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_latency_budget_triggers_flush() {
+        let mut c = Coalescer::new(CoalesceConfig {
+            latency_budget: Duration::from_millis(10),
+        });
+        let t0 = Instant::now();
+        c.push(b"ack".to_vec(), t0).unwrap();
+        assert!(c.poll(t0 + Duration::from_millis(5)).unwrap().is_none());
+        // A later frame does not extend the oldest frame's deadline.
+        c.push(b"receipt".to_vec(), t0 + Duration::from_millis(8))
+            .unwrap();
+        let packet = c.poll(t0 + Duration::from_millis(10)).unwrap().unwrap();
+        assert_eq!(decode(&packet), vec![b"ack".to_vec(), b"receipt".to_vec()]);
+        assert_eq!(c.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_overflow_flushes_full_batch_in_order() {
+        let mut c = Coalescer::new(CoalesceConfig::default());
+        let t0 = Instant::now();
+        let frame_len = max_coalesced_frame() / 3;
+        let mut sent = Vec::new();
+        for i in 0..4u8 {
+            sent.extend(c.push(vec![i; frame_len], t0).unwrap());
+        }
+        assert!(!sent.is_empty());
+        sent.extend(c.flush().unwrap());
+
+        let frames: Vec<Vec<u8>> = sent.iter().flat_map(|p| decode(p)).collect();
+        let firsts: Vec<u8> = frames.iter().map(|f| f[0]).collect();
+        assert_eq!(firsts, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_cover_slot_carries_pending_batch() {
+        let mut c = Coalescer::new(CoalesceConfig::default());
+        let idle = strip_padding(&c.cover_slot().unwrap()).unwrap();
+        assert!(is_cover_packet(&idle));
+
+        c.push(b"reaction".to_vec(), Instant::now()).unwrap();
+        let busy = strip_padding(&c.cover_slot().unwrap()).unwrap();
+        assert!(is_coalesced_packet(&busy));
+        assert_eq!(decode_coalesced(&busy).unwrap(), vec![b"reaction".to_vec()]);
+    }
+
+    #[test]
+    fn test_oversized_frame_and_truncated_batch_rejected() {
+        let mut c = Coalescer::new(CoalesceConfig::default());
+        let big = vec![0u8; max_coalesced_frame() + 1];
+        assert!(c.push(big, Instant::now()).is_err());
+
+        let truncated = [MSG_TYPE_COALESCED, 2, 0, 3, b'a', b'b', b'c', 0, 9];
+        assert!(decode_coalesced(&truncated).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Per-contact cover traffic flows.
+//!
+//! A single global cover timer hides *whether* the device is talking, but not
+//! *to whom*: an observer at one contact's onion service sees cover packets
+//! only now and then, and a steady stream while a conversation is active.
+//! `CoverFlows` instead keeps an independent flow per peer. Each flow sends
+//! one packet per slot, with every slot length drawn at random from the
+//! profile's cover interval; a real packet to the peer fills the current slot
+//! and a cover packet fills it otherwise. Idle and active peers therefore see
+//! the same packet rhythm, and because each flow draws its own intervals,
+//! traffic toward different peers is uncorrelated.
+//!
+//! Real traffic faster than one packet per slot still shows up as extra
+//! packets; pair flows with [`Coalescer::cover_slot`](super::Coalescer::cover_slot)
+//! to move pending frames into cover slots instead.
+//!
+//! Slot deadlines are computed from the `now` given to `add_peer` and
+//! `poll`; the caller sleeps until [`CoverFlows::next_deadline`].
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use super::padding::TrafficProfile;
+use crate::rng::SecureRng;
+
+/// Default cap on concurrent flows; beyond it new peers get no cover flow.
+pub const DEFAULT_MAX_COVER_FLOWS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverFlowConfig {
+    /// Shortest slot.
+    pub min_interval: Duration,
+    /// Longest slot.
+    pub max_interval: Duration,
+    pub max_flows: usize,
+}
+
+impl CoverFlowConfig {
+    /// Slot lengths from the profile's cover interval range.
+    pub fn for_profile(profile: &TrafficProfile) -> Self {
+        let (min, max) = profile.cover_interval_range();
+        Self {
+            min_interval: Duration::from_secs(min),
+            max_interval: Duration::from_secs(max.max(min)),
+            max_flows: DEFAULT_MAX_COVER_FLOWS,
+        }
+    }
+}
+
+impl Default for CoverFlowConfig {
+    fn default() -> Self {
+        Self::for_profile(&TrafficProfile::Balanced)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Flow {
+    /// End of the current slot.
+    deadline: Instant,
+    /// Whether a real packet already filled the current slot.
+    filled: bool,
+}
+
+/// Independent cover flows keyed by peer (onion address, contact id, ...).
+#[derive(Debug)]
+pub struct CoverFlows<K> {
+    config: CoverFlowConfig,
+    flows: HashMap<K, Flow>,
+}
+
+impl<K: Hash + Eq + Clone> CoverFlows<K> {
+    pub fn new(config: CoverFlowConfig) -> Self {
+        Self {
+            config,
+            flows: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &CoverFlowConfig {
+        &self.config
+    }
+
+    /// Change slot lengths. Existing slots run to their current deadline.
+    pub fn set_config(&mut self, config: CoverFlowConfig) {
+        self.config = config;
+    }
+
+    fn slot(&self, rng: &mut impl SecureRng) -> Duration {
+        let min = self.config.min_interval.as_millis() as u64;
+        let max = (self.config.max_interval.as_millis() as u64).max(min);
+        Duration::from_millis(min + rng.next_u64() % (max - min + 1))
+    }
+
+    /// Start a flow toward `peer`. Returns false if the flow cap is reached.
+    /// The first slot starts at `now`, so flows added together still drift
+    /// apart immediately.
+    pub fn add_peer(&mut self, peer: K, now: Instant, rng: &mut impl SecureRng) -> bool {
+        if self.flows.contains_key(&peer) {
+            return true;
+        }
+        if self.flows.len() >= self.config.max_flows {
+            return false;
+        }
+        let deadline = now + self.slot(rng);
+        self.flows.insert(
+            peer,
+            Flow {
+                deadline,
+                filled: false,
+            },
+        );
+        true
+    }
+
+    pub fn remove_peer(&mut self, peer: &K) -> bool {
+        self.flows.remove(peer).is_some()
+    }
+
+    pub fn has_flow(&self, peer: &K) -> bool {
+        self.flows.contains_key(peer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// A real packet went to `peer`; it fills the current slot.
+    pub fn record_real(&mut self, peer: &K) {
+        if let Some(flow) = self.flows.get_mut(peer) {
+            flow.filled = true;
+        }
+    }
+
+    /// Peers whose slot ended without real traffic and need a cover packet
+    /// now. Every ended slot, filled or not, is replaced by a fresh one.
+    pub fn poll(&mut self, now: Instant, rng: &mut impl SecureRng) -> Vec<K> {
+        let mut due = Vec::new();
+        let ended: Vec<K> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| now >= flow.deadline)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+        for peer in ended {
+            let slot = self.slot(rng);
+            let flow = self.flows.get_mut(&peer).expect("collected above");
+            if !flow.filled {
+                due.push(peer);
+            }
+            // Slots follow on from the missed deadline unless the loop fell a
+            // whole slot behind (e.g. the device slept)
+            let next = flow.deadline + slot;
+            flow.deadline = if next > now { next } else { now + slot };
+            flow.filled = false;
+        }
+        due
+    }
+
+    /// Earliest slot end, for the transport loop's timer.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.flows.values().map(|flow| flow.deadline).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded;
+
+    fn config() -> CoverFlowConfig {
+        CoverFlowConfig {
+            min_interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(30),
+            max_flows: 2,
+        }
+    }
+
+    #[test]
+    fn test_idle_peer_gets_cover_active_peer_does_not() {
+        let mut rng = seeded(1);
+        let mut flows = CoverFlows::new(config());
+        let start = Instant::now();
+        assert!(flows.add_peer("active", start, &mut rng));
+        assert!(flows.add_peer("idle", start, &mut rng));
+        assert!(!flows.add_peer("third", start, &mut rng));
+
+        let mut cover: HashMap<&str, u32> = HashMap::new();
+        for second in 1..=600 {
+            // The active conversation sends every second, filling each slot
+            flows.record_real(&"active");
+            for peer in flows.poll(start + Duration::from_secs(second), &mut rng) {
+                *cover.entry(peer).or_default() += 1;
+            }
+        }
+        // 600s of 10-30s slots: the idle peer sees a packet in every slot
+        let idle = cover["idle"];
+        assert!((20..=60).contains(&idle), "idle: {idle}");
+        assert!(!cover.contains_key("active"));
+    }
+
+    #[test]
+    fn test_filled_slot_suppresses_cover_and_flows_are_independent() {
+        let mut rng = seeded(2);
+        let mut flows = CoverFlows::new(config());
+        let start = Instant::now();
+        flows.add_peer(1u8, start, &mut rng);
+        flows.add_peer(2u8, start, &mut rng);
+
+        flows.record_real(&1);
+        let late = start + Duration::from_secs(31);
+        let due = flows.poll(late, &mut rng);
+        assert_eq!(due, vec![2]);
+
+        // Both flows were rescheduled with their own random slot
+        let deadlines: Vec<Instant> = flows.flows.values().map(|f| f.deadline).collect();
+        assert!(deadlines.iter().all(|&d| d > late));
+        assert!(flows.remove_peer(&2));
+        assert!(!flows.has_flow(&2));
+        assert_eq!(flows.len(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+//! Size and bandwidth estimates, for showing costs before sending.
+//!
+//! Every message is padded to the fixed packet size and cover traffic is
+//! sent whether or not anyone talks, so neither the plaintext length nor
+//! the message count says much about what a user actually pays in storage
+//! or data. These helpers walk the same layers as the send path, without
+//! encrypting anything:
+//!
+//! plaintext → sequenced envelope (`protocol::ordering`) → ratchet
+//! ciphertext → type byte → padded packets (fragmented past one packet, see
+//! [`fragment_and_pad`](super::padding::fragment_and_pad)) → 4-byte length
+//! prefix per packet on the stream.
+//!
+//! Apps can compare packet sizes ([`WireOptions::packet_size`]) and traffic
+//! profiles side by side before committing to one.
+
+use super::padding::{
+    fixed_packet_size, PaddingError, TrafficProfile, FRAGMENT_HEADER, PAYLOAD_LEN_FIELD,
+};
+use crate::crypto::key_continuity::CONTINUITY_TAG_LEN;
+
+/// Sequenced envelope header: `[version][seq: u64 BE]`.
+const ENVELOPE_HEADER: usize = 1 + 8;
+
+/// Ratchet ciphertext around the envelope: `[version][seq: 8][nonce: 24]`
+/// before and the 16-byte tag after.
+const RATCHET_OVERHEAD: usize = 1 + 8 + 24 + 16;
+
+/// Wire message type byte.
+const TYPE_BYTE: usize = 1;
+
+/// Length prefix in front of each packet on the stream.
+const STREAM_PREFIX: usize = 4;
+
+/// Cover estimates are per 30-day month.
+const SECONDS_PER_MONTH: u64 = 30 * 24 * 60 * 60;
+
+/// How a message would be sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireOptions {
+    /// Fixed packet size: 4096, 8192 or 16384.
+    pub packet_size: usize,
+    /// The envelope carries a key continuity tag.
+    pub continuity_tag: bool,
+    /// Cover packets sent around each message (burst padding).
+    pub burst_packets: usize,
+}
+
+impl Default for WireOptions {
+    /// Current packet size, no continuity tag, no burst padding.
+    fn default() -> Self {
+        Self {
+            packet_size: fixed_packet_size(),
+            continuity_tag: false,
+            burst_packets: 0,
+        }
+    }
+}
+
+impl WireOptions {
+    /// Current packet size with the profile's burst padding.
+    pub fn for_profile(profile: &TrafficProfile) -> Self {
+        let burst = profile.burst_config();
+        Self {
+            burst_packets: if burst.enabled {
+                burst.pre_burst_count as usize + burst.post_burst_count as usize
+            } else {
+                0
+            },
+            ..Self::default()
+        }
+    }
+}
+
+/// What one message costs on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireEstimate {
+    /// Packets carrying the message.
+    pub packets: usize,
+    /// Burst padding packets around it.
+    pub burst_packets: usize,
+    /// Bytes on the stream, burst padding included.
+    pub wire_bytes: u64,
+    /// `wire_bytes` minus the plaintext: headers, tags and padding.
+    pub overhead_bytes: u64,
+}
+
+/// Wire cost of sending `plaintext_len` bytes with `options`.
+pub fn estimate_wire_size(
+    plaintext_len: usize,
+    options: &WireOptions,
+) -> Result<WireEstimate, PaddingError> {
+    let packet_size = options.packet_size;
+    if !matches!(packet_size, 4096 | 8192 | 16384) {
+        return Err(PaddingError::InvalidPacketSize(packet_size));
+    }
+    let tag = if options.continuity_tag {
+        CONTINUITY_TAG_LEN
+    } else {
+        0
+    };
+    let payload = plaintext_len + ENVELOPE_HEADER + tag + RATCHET_OVERHEAD + TYPE_BYTE;
+
+    let packets = if payload <= packet_size - PAYLOAD_LEN_FIELD {
+        1
+    } else {
+        let per_fragment = packet_size - PAYLOAD_LEN_FIELD - FRAGMENT_HEADER;
+        let fragments = payload.div_ceil(per_fragment);
+        if fragments > u16::MAX as usize {
+            return Err(PaddingError::PayloadTooLarge(
+                per_fragment * u16::MAX as usize,
+            ));
+        }
+        fragments
+    };
+
+    let wire_bytes = ((packets + options.burst_packets) * (packet_size + STREAM_PREFIX)) as u64;
+    Ok(WireEstimate {
+        packets,
+        burst_packets: options.burst_packets,
+        wire_bytes,
+        overhead_bytes: wire_bytes - plaintext_len as u64,
+    })
+}
+
+/// Cover traffic a profile sends in a month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverTrafficEstimate {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl CoverTrafficEstimate {
+    /// The same cost for `flows` independent flows, e.g. per-contact
+    /// [`CoverFlows`](super::CoverFlows) to that many contacts.
+    pub fn for_flows(&self, flows: usize) -> Self {
+        Self {
+            packets: self.packets * flows as u64,
+            bytes: self.bytes * flows as u64,
+        }
+    }
+}
+
+/// Expected cover traffic for one flow under `profile` at the current
+/// packet size, over 30 days. Intervals are drawn uniformly from the
+/// profile's range, so packets go out once per mean interval on average.
+/// Real traffic that fills cover slots lowers the actual figure.
+pub fn estimate_monthly_cover_traffic(profile: &TrafficProfile) -> CoverTrafficEstimate {
+    let (min, max) = profile.cover_interval_range();
+    let mean_secs = ((min + max.max(min)) / 2).max(1);
+    let packets = SECONDS_PER_MONTH / mean_secs;
+    CoverTrafficEstimate {
+        packets,
+        bytes: packets * (fixed_packet_size() + STREAM_PREFIX) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption::encrypt_message_with_evolution;
+
+    fn options(packet_size: usize) -> WireOptions {
+        WireOptions {
+            packet_size,
+            continuity_tag: false,
+            burst_packets: 0,
+        }
+    }
+
+    #[test]
+    fn test_wire_size_matches_send_path() {
+        // The ratchet adds exactly the overhead assumed here
+        let envelope = vec![0u8; ENVELOPE_HEADER + 100];
+        let sealed = encrypt_message_with_evolution(&envelope, &mut [7u8; 32], 0).unwrap();
+        assert_eq!(sealed.ciphertext.len(), envelope.len() + RATCHET_OVERHEAD);
+
+        let short = estimate_wire_size(100, &options(4096)).unwrap();
+        assert_eq!(short.packets, 1);
+        assert_eq!(short.wire_bytes, 4100);
+        assert_eq!(short.overhead_bytes, 4000);
+
+        // Largest plaintext that fits one packet, then one byte more
+        let fits = 4096 - 2 - ENVELOPE_HEADER - RATCHET_OVERHEAD - TYPE_BYTE;
+        assert_eq!(estimate_wire_size(fits, &options(4096)).unwrap().packets, 1);
+        assert_eq!(
+            estimate_wire_size(fits + 1, &options(4096))
+                .unwrap()
+                .packets,
+            2
+        );
+        assert_eq!(
+            estimate_wire_size(fits, &options(8192)).unwrap().wire_bytes,
+            8196
+        );
+        let tagged = WireOptions {
+            continuity_tag: true,
+            ..options(4096)
+        };
+        assert_eq!(estimate_wire_size(fits, &tagged).unwrap().packets, 2);
+
+        // A 1 MB attachment spreads over fragments of 4090 data bytes
+        let big = estimate_wire_size(1 << 20, &options(4096)).unwrap();
+        assert_eq!(big.packets, ((1usize << 20) + 59).div_ceil(4090));
+        assert!(matches!(
+            estimate_wire_size(10, &options(5000)),
+            Err(PaddingError::InvalidPacketSize(5000))
+        ));
+    }
+
+    #[test]
+    fn test_profile_costs() {
+        let burst = WireOptions {
+            packet_size: 4096,
+            ..WireOptions::for_profile(&TrafficProfile::MaxPrivacy)
+        };
+        let estimate = estimate_wire_size(10, &burst).unwrap();
+        assert_eq!(estimate.burst_packets, 10);
+        assert_eq!(estimate.wire_bytes, 11 * 4100);
+        assert_eq!(
+            WireOptions::for_profile(&TrafficProfile::LowLatency).burst_packets,
+            0
+        );
+
+        let low = estimate_monthly_cover_traffic(&TrafficProfile::LowLatency);
+        let max = estimate_monthly_cover_traffic(&TrafficProfile::MaxPrivacy);
+        // One packet per 105 s vs. per 20 s on average
+        assert_eq!(low.packets, SECONDS_PER_MONTH / 105);
+        assert_eq!(max.packets, SECONDS_PER_MONTH / 20);
+        assert!(max.packets > low.packets * 5);
+        assert_eq!(max.for_flows(3).packets, max.packets * 3);
+    }
+}
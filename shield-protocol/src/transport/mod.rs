@@ -3,16 +3,50 @@
 //! This module provides packet formatting and traffic-analysis resistance
 //! utilities that are **transport-agnostic** — they work over Tor, TCP,
 //! WebSocket, or any other underlying channel.
+//!
+//! The links themselves implement [`Channel`]; [`web`] has the browser ones
+//! (WebSocket gateway, WebRTC data channel) for builds without Tor.
 
+pub mod bandwidth;
+pub mod channel;
+pub mod coalesce;
+pub mod cover;
+pub mod estimate;
 pub mod packet;
 pub mod padding;
+pub mod priority;
+pub mod web;
 
+pub use bandwidth::{
+    BandwidthConfig, BandwidthShaper, Release, ShaperStats, DEFAULT_BURST_BYTES,
+    DEFAULT_COVER_TAIL_SECS, DEFAULT_QUANTUM_BYTES,
+};
+pub use channel::{Channel, ChannelError, ChannelState, MemoryChannel};
+pub use coalesce::{
+    decode_coalesced, is_coalesced_packet, max_coalesced_frame, CoalesceConfig, Coalescer,
+    DEFAULT_COALESCE_BUDGET_MS, MSG_TYPE_COALESCED,
+};
+pub use cover::{CoverFlowConfig, CoverFlows, DEFAULT_MAX_COVER_FLOWS};
+pub use estimate::{
+    estimate_monthly_cover_traffic, estimate_wire_size, CoverTrafficEstimate, WireEstimate,
+    WireOptions,
+};
 pub use packet::{Packet, PacketType, MAX_PAYLOAD, PACKET_SIZE};
+#[cfg(feature = "std")]
+pub use padding::{apply_traffic_delay, random_traffic_delay_ms, random_traffic_delay_ms_with_rng};
 pub use padding::{
-    apply_traffic_delay, constant_time_eq, fixed_packet_size, fragment_and_pad,
-    generate_burst_padding, generate_cover_packet, is_cover_packet, max_padded_payload,
-    pad_to_fixed_size, random_burst_delay_ms, random_cover_interval_secs, random_traffic_delay_ms,
-    reassemble_fragments, set_fixed_packet_size, strip_padding, BurstPaddingConfig, PaddingError,
-    TrafficProfile, COVER_INTERVAL_MAX_SECS, COVER_INTERVAL_MIN_SECS, DEFAULT_PACKET_SIZE,
-    FIXED_PACKET_SIZE, MAX_PADDED_PAYLOAD, MSG_TYPE_COVER,
+    constant_time_eq, fixed_packet_size, fragment_and_pad, generate_burst_padding,
+    generate_burst_padding_with_rng, generate_cover_packet, generate_cover_packet_with_rng,
+    is_cover_packet, max_padded_payload, pad_to_fixed_size, pad_to_fixed_size_with_rng,
+    random_burst_delay_ms, random_burst_delay_ms_with_rng, random_cover_interval_secs,
+    random_cover_interval_secs_with_rng, reassemble_fragments, set_fixed_packet_size,
+    strip_padding, BurstPaddingConfig, PaddingError, TrafficProfile, COVER_INTERVAL_MAX_SECS,
+    COVER_INTERVAL_MIN_SECS, DEFAULT_PACKET_SIZE, FIXED_PACKET_SIZE, MAX_PADDED_PAYLOAD,
+    MSG_TYPE_COVER,
+};
+pub use priority::{
+    Lane, LaneMetrics, OutboxConfig, PriorityOutbox, TimingSmoothing, DEFAULT_STARVATION_MS,
 };
+pub use web::validate_gateway_url;
+#[cfg(feature = "wasm")]
+pub use web::{RtcChannel, WebSocketChannel};
@@ -13,11 +13,12 @@
 /// The HMAC covers version + type + payload_len + payload + padding to prevent
 /// truncation and bit-flipping attacks on the padding.
 use hmac::{Hmac, Mac};
-use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use thiserror::Error;
 
+use crate::rng::SecureRng;
+
 type HmacSha256 = Hmac<Sha256>;
 
 /// Fixed packet size in bytes (including HMAC)
@@ -106,8 +107,13 @@ impl Packet {
     ///
     /// The payload is random bytes, indistinguishable from real encrypted data.
     pub fn cover_traffic() -> Self {
+        Self::cover_traffic_with_rng(&mut rand::rngs::OsRng)
+    }
+
+    /// [`Packet::cover_traffic`] drawing its payload from `rng`.
+    pub fn cover_traffic_with_rng(rng: &mut impl SecureRng) -> Self {
         let mut payload = vec![0u8; 1024]; // 1KB random payload
-        rand::rngs::OsRng.fill_bytes(&mut payload);
+        rng.fill_bytes(&mut payload);
         Self {
             packet_type: PacketType::CoverTraffic,
             payload,
@@ -122,6 +128,15 @@ impl Packet {
     /// # Returns
     /// Exactly PACKET_SIZE bytes
     pub fn serialize(&self, hmac_key: &[u8; 32]) -> Result<[u8; PACKET_SIZE]> {
+        self.serialize_with_rng(hmac_key, &mut rand::rngs::OsRng)
+    }
+
+    /// [`Packet::serialize`] drawing its padding from `rng`.
+    pub fn serialize_with_rng(
+        &self,
+        hmac_key: &[u8; 32],
+        rng: &mut impl SecureRng,
+    ) -> Result<[u8; PACKET_SIZE]> {
         if self.payload.len() > MAX_PAYLOAD {
             return Err(PacketError::PayloadTooLarge {
                 size: self.payload.len(),
@@ -143,7 +158,7 @@ impl Packet {
         let padding_start = HEADER_SIZE + self.payload.len();
         let padding_end = PACKET_SIZE - HMAC_SIZE;
         if padding_start < padding_end {
-            rand::rngs::OsRng.fill_bytes(&mut buf[padding_start..padding_end]);
+            rng.fill_bytes(&mut buf[padding_start..padding_end]);
         }
 
         // HMAC over everything except the HMAC field itself
@@ -10,10 +10,11 @@
 //! - Burst padding to mask typing indicators
 //! - Traffic shaping with configurable profiles
 
-use getrandom::getrandom;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use thiserror::Error;
 
+use crate::rng::{OsRng, SecureRng};
+
 // ---------------------------------------------------------------------------
 // Configurable fixed packet size
 // ---------------------------------------------------------------------------
@@ -56,7 +57,10 @@ pub fn max_padded_payload() -> usize {
 pub const MAX_PADDED_PAYLOAD: usize = DEFAULT_PACKET_SIZE - 2;
 
 /// Length field: 2 bytes (BE) at start of padded buffer.
-const PAYLOAD_LEN_FIELD: usize = 2;
+pub(super) const PAYLOAD_LEN_FIELD: usize = 2;
+
+/// Fragment header inside the padded payload: [seq:2][total:2].
+pub(super) const FRAGMENT_HEADER: usize = 4;
 
 // ---------------------------------------------------------------------------
 // Errors
@@ -70,6 +74,8 @@ pub enum PaddingError {
     InvalidPaddedPayload,
     #[error("Burst padding error: {0}")]
     BurstError(String),
+    #[error("Invalid fixed packet size {0} (must be 4096, 8192 or 16384)")]
+    InvalidPacketSize(usize),
 }
 
 // ---------------------------------------------------------------------------
@@ -78,6 +84,14 @@ pub enum PaddingError {
 
 /// Pad `payload` to exactly `fixed_packet_size()`. Layout: [len:2 BE][payload][random_padding].
 pub fn pad_to_fixed_size(payload: &[u8]) -> Result<Vec<u8>, PaddingError> {
+    pad_to_fixed_size_with_rng(payload, &mut OsRng)
+}
+
+/// [`pad_to_fixed_size`] drawing its padding from `rng`.
+pub fn pad_to_fixed_size_with_rng(
+    payload: &[u8],
+    rng: &mut impl SecureRng,
+) -> Result<Vec<u8>, PaddingError> {
     let pkt = fixed_packet_size();
     let max_payload = pkt
         .checked_sub(PAYLOAD_LEN_FIELD)
@@ -90,7 +104,8 @@ pub fn pad_to_fixed_size(payload: &[u8]) -> Result<Vec<u8>, PaddingError> {
     out.extend_from_slice(payload);
     let padding_len = pkt - out.len();
     let mut pad = vec![0u8; padding_len];
-    getrandom(&mut pad).map_err(|_| PaddingError::InvalidPaddedPayload)?;
+    rng.try_fill_bytes(&mut pad)
+        .map_err(|_| PaddingError::InvalidPaddedPayload)?;
     out.extend_from_slice(&pad);
 
     debug_assert_eq!(out.len(), pkt);
@@ -130,8 +145,14 @@ const DELAY_LAMBDA: f64 = 0.005;
 /// Avoids the flat pattern of uniform distribution that could be fingerprinted.
 #[cfg(feature = "std")]
 pub fn random_traffic_delay_ms() -> u64 {
+    random_traffic_delay_ms_with_rng(&mut OsRng)
+}
+
+/// [`random_traffic_delay_ms`] sampling from `rng`.
+#[cfg(feature = "std")]
+pub fn random_traffic_delay_ms_with_rng(rng: &mut impl SecureRng) -> u64 {
     let mut buf = [0u8; 4];
-    if getrandom(&mut buf).is_err() {
+    if rng.try_fill_bytes(&mut buf).is_err() {
         return 400;
     }
     let u = u32::from_ne_bytes(buf) as f64 / u32::MAX as f64; // uniform [0,1)
@@ -161,11 +182,17 @@ pub const MSG_TYPE_COVER: u8 = 0xFF;
 /// Generate a cover (dummy) packet — padded to fixed size, with random nonce payload.
 /// Send periodically on idle connections to prevent timing analysis of silence gaps.
 pub fn generate_cover_packet() -> Result<Vec<u8>, PaddingError> {
+    generate_cover_packet_with_rng(&mut OsRng)
+}
+
+/// [`generate_cover_packet`] drawing from `rng`.
+pub fn generate_cover_packet_with_rng(rng: &mut impl SecureRng) -> Result<Vec<u8>, PaddingError> {
     let mut dummy = vec![MSG_TYPE_COVER];
     let mut nonce = [0u8; 24];
-    getrandom(&mut nonce).map_err(|_| PaddingError::InvalidPaddedPayload)?;
+    rng.try_fill_bytes(&mut nonce)
+        .map_err(|_| PaddingError::InvalidPaddedPayload)?;
     dummy.extend_from_slice(&nonce);
-    pad_to_fixed_size(&dummy)
+    pad_to_fixed_size_with_rng(&dummy, rng)
 }
 
 /// Returns true if the (unpadded) payload is a cover traffic packet to be silently discarded.
@@ -181,8 +208,13 @@ pub const COVER_INTERVAL_MAX_SECS: u64 = 90;
 
 /// Random cover traffic interval in [30, 90] seconds.
 pub fn random_cover_interval_secs() -> u64 {
+    random_cover_interval_secs_with_rng(&mut OsRng)
+}
+
+/// [`random_cover_interval_secs`] sampling from `rng`.
+pub fn random_cover_interval_secs_with_rng(rng: &mut impl SecureRng) -> u64 {
     let mut buf = [0u8; 1];
-    if getrandom(&mut buf).is_err() {
+    if rng.try_fill_bytes(&mut buf).is_err() {
         return 60;
     }
     COVER_INTERVAL_MIN_SECS
@@ -247,6 +279,14 @@ impl Default for BurstPaddingConfig {
 /// with random delays between each.
 pub fn generate_burst_padding(
     config: &BurstPaddingConfig,
+) -> Result<(Vec<Vec<u8>>, Vec<Vec<u8>>), PaddingError> {
+    generate_burst_padding_with_rng(config, &mut OsRng)
+}
+
+/// [`generate_burst_padding`] drawing from `rng`.
+pub fn generate_burst_padding_with_rng(
+    config: &BurstPaddingConfig,
+    rng: &mut impl SecureRng,
 ) -> Result<(Vec<Vec<u8>>, Vec<Vec<u8>>), PaddingError> {
     if !config.enabled {
         return Ok((Vec::new(), Vec::new()));
@@ -254,12 +294,12 @@ pub fn generate_burst_padding(
 
     let mut pre = Vec::with_capacity(config.pre_burst_count as usize);
     for _ in 0..config.pre_burst_count {
-        pre.push(generate_cover_packet()?);
+        pre.push(generate_cover_packet_with_rng(rng)?);
     }
 
     let mut post = Vec::with_capacity(config.post_burst_count as usize);
     for _ in 0..config.post_burst_count {
-        post.push(generate_cover_packet()?);
+        post.push(generate_cover_packet_with_rng(rng)?);
     }
 
     Ok((pre, post))
@@ -267,8 +307,16 @@ pub fn generate_burst_padding(
 
 /// Generate a random inter-packet delay for burst padding.
 pub fn random_burst_delay_ms(config: &BurstPaddingConfig) -> u64 {
+    random_burst_delay_ms_with_rng(config, &mut OsRng)
+}
+
+/// [`random_burst_delay_ms`] sampling from `rng`.
+pub fn random_burst_delay_ms_with_rng(
+    config: &BurstPaddingConfig,
+    rng: &mut impl SecureRng,
+) -> u64 {
     let mut buf = [0u8; 2];
-    if getrandom(&mut buf).is_err() {
+    if rng.try_fill_bytes(&mut buf).is_err() {
         return (config.inter_packet_delay_min_ms + config.inter_packet_delay_max_ms) / 2;
     }
     let range = config.inter_packet_delay_max_ms - config.inter_packet_delay_min_ms;
@@ -356,9 +404,14 @@ impl TrafficProfile {
 
     /// Generate a random delay according to this profile's parameters.
     pub fn random_delay_ms(&self) -> u64 {
+        self.random_delay_ms_with_rng(&mut OsRng)
+    }
+
+    /// [`random_delay_ms`](Self::random_delay_ms) sampling from `rng`.
+    pub fn random_delay_ms_with_rng(&self, rng: &mut impl SecureRng) -> u64 {
         let (min, max) = self.delay_range_ms();
         let mut buf = [0u8; 4];
-        if getrandom(&mut buf).is_err() {
+        if rng.try_fill_bytes(&mut buf).is_err() {
             return (min + max) / 2;
         }
         let u = u32::from_ne_bytes(buf) as f64 / u32::MAX as f64;
@@ -371,9 +424,15 @@ impl TrafficProfile {
 
     /// Generate a random cover traffic interval according to this profile.
     pub fn random_cover_interval_secs(&self) -> u64 {
+        self.random_cover_interval_secs_with_rng(&mut OsRng)
+    }
+
+    /// [`random_cover_interval_secs`](Self::random_cover_interval_secs)
+    /// sampling from `rng`.
+    pub fn random_cover_interval_secs_with_rng(&self, rng: &mut impl SecureRng) -> u64 {
         let (min, max) = self.cover_interval_range();
         let mut buf = [0u8; 2];
-        if getrandom(&mut buf).is_err() {
+        if rng.try_fill_bytes(&mut buf).is_err() {
             return (min + max) / 2;
         }
         let range = max - min;
@@ -400,8 +459,7 @@ impl TrafficProfile {
 pub fn fragment_and_pad(payload: &[u8]) -> Result<Vec<Vec<u8>>, PaddingError> {
     let pkt = fixed_packet_size();
     let _header_overhead = 6; // 2 (len) + 2 (seq) + 2 (total) — but len is already in pad_to_fixed_size
-    let fragment_overhead = 4; // seq:2 + total:2 (inside the payload passed to pad_to_fixed_size)
-    let max_fragment_data = pkt - PAYLOAD_LEN_FIELD - fragment_overhead;
+    let max_fragment_data = pkt - PAYLOAD_LEN_FIELD - FRAGMENT_HEADER;
 
     if payload.len() <= max_padded_payload() {
         // Single packet, no fragmentation needed
@@ -420,7 +478,7 @@ pub fn fragment_and_pad(payload: &[u8]) -> Result<Vec<Vec<u8>>, PaddingError> {
 
     for (i, chunk) in payload.chunks(max_fragment_data).enumerate() {
         let seq = i as u16;
-        let mut fragment_payload = Vec::with_capacity(fragment_overhead + chunk.len());
+        let mut fragment_payload = Vec::with_capacity(FRAGMENT_HEADER + chunk.len());
         fragment_payload.extend_from_slice(&seq.to_be_bytes());
         fragment_payload.extend_from_slice(&total.to_be_bytes());
         fragment_payload.extend_from_slice(chunk);
@@ -518,6 +576,34 @@ mod tests {
         assert!(is_cover_packet(&unpadded));
     }
 
+    #[test]
+    fn test_seeded_rng_reproduces_padding() {
+        use crate::rng::seeded;
+
+        let a = generate_cover_packet_with_rng(&mut seeded(4)).unwrap();
+        let b = generate_cover_packet_with_rng(&mut seeded(4)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, generate_cover_packet_with_rng(&mut seeded(5)).unwrap());
+        assert_eq!(
+            random_traffic_delay_ms_with_rng(&mut seeded(6)),
+            random_traffic_delay_ms_with_rng(&mut seeded(6))
+        );
+        let config = BurstPaddingConfig::default();
+        assert_eq!(
+            random_burst_delay_ms_with_rng(&config, &mut seeded(7)),
+            random_burst_delay_ms_with_rng(&config, &mut seeded(7))
+        );
+        let profile = TrafficProfile::MaxPrivacy;
+        assert_eq!(
+            profile.random_delay_ms_with_rng(&mut seeded(8)),
+            profile.random_delay_ms_with_rng(&mut seeded(8))
+        );
+        assert_eq!(
+            profile.random_cover_interval_secs_with_rng(&mut seeded(9)),
+            profile.random_cover_interval_secs_with_rng(&mut seeded(9))
+        );
+    }
+
     #[test]
     fn test_delay_distribution_range() {
         for _ in 0..100 {
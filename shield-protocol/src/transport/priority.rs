@@ -0,0 +1,465 @@
+//! Priority lanes for outbound traffic.
+//!
+//! One outbox serves four lanes, highest first:
+//! `Control` (pings, pongs, call signaling) > `Receipt` (delivery ACKs,
+//! taps) > `Text` > `Media` (voice, images, bulk sync). A multi-megabyte
+//! upload therefore never delays the ACK or pong queued behind it.
+//!
+//! Ordering guarantees:
+//! - Within a lane, items leave in FIFO order.
+//! - Content lanes (`Text`, `Media`) keep per-conversation order across
+//!   lanes: a text sent after an image in the same conversation waits for
+//!   the image. Other conversations are not held up.
+//! - `Control` and `Receipt` are not ordered against content.
+//!
+//! Strict priority could starve `Media` under a steady stream of text, so a
+//! lane whose oldest eligible item has waited `starvation_after` is served
+//! next regardless of priority.
+//!
+//! Sending each text the moment it is typed puts the user's keystroke
+//! rhythm on the wire: typing indicators and bursts of short messages leave
+//! at intervals an observer can match against typing patterns. With
+//! [`TimingSmoothing`] the `Text` lane is only released on fixed ticks, so
+//! everything queued within one tick leaves together at the next boundary
+//! and the observable send times carry nothing finer than the tick. The
+//! caller then has to queue every `Text` item (no [`PriorityOutbox::record_bypass`])
+//! and wake up at [`PriorityOutbox::next_release`]. [`OutboxConfig::for_tier`]
+//! picks the tick by security tier.
+//!
+//! The outbox never reads the clock: `push`, `pop` and `next_release` take
+//! `now`, which is what lets the tests step through starvation and tick
+//! boundaries exactly.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::protocol::security_mode::SecurityTier;
+
+/// Default wait after which a lower lane is served ahead of higher ones.
+pub const DEFAULT_STARVATION_MS: u64 = 2_000;
+
+/// Send tick for [`SecurityTier::HighRisk`].
+pub const HIGH_RISK_TICK_MS: u64 = 500;
+
+/// Send tick for [`SecurityTier::Normal`].
+pub const NORMAL_TICK_MS: u64 = 100;
+
+/// Items released per tick at most; a longer burst spills into later ticks.
+pub const DEFAULT_MAX_BATCH: usize = 16;
+
+/// Priority class, highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Lane {
+    Control = 0,
+    Receipt = 1,
+    Text = 2,
+    Media = 3,
+}
+
+impl Lane {
+    pub const ALL: [Lane; 4] = [Lane::Control, Lane::Receipt, Lane::Text, Lane::Media];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lane::Control => "control",
+            Lane::Receipt => "receipt",
+            Lane::Text => "text",
+            Lane::Media => "media",
+        }
+    }
+
+    /// Whether items in this lane keep per-conversation order.
+    pub fn is_content(self) -> bool {
+        matches!(self, Lane::Text | Lane::Media)
+    }
+}
+
+/// Per-lane counters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaneMetrics {
+    pub enqueued: u64,
+    pub dequeued: u64,
+    pub bytes: u64,
+    /// Items currently waiting.
+    pub depth: usize,
+    pub max_wait: Duration,
+    pub total_wait: Duration,
+}
+
+impl LaneMetrics {
+    pub fn mean_wait(&self) -> Duration {
+        if self.dequeued == 0 {
+            Duration::ZERO
+        } else {
+            self.total_wait / self.dequeued as u32
+        }
+    }
+}
+
+/// Quantized release of the `Text` lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingSmoothing {
+    /// Text leaves only at multiples of this after the outbox's first push.
+    pub tick: Duration,
+    /// Text items released in one tick at most.
+    pub max_batch: usize,
+}
+
+impl TimingSmoothing {
+    pub fn with_tick_ms(tick_ms: u64) -> Self {
+        Self {
+            tick: Duration::from_millis(tick_ms),
+            max_batch: DEFAULT_MAX_BATCH,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    /// Serve a lane out of priority order once its head has waited this long.
+    pub starvation_after: Duration,
+    /// Hold text to send ticks; `None` sends it as soon as it is first in line.
+    pub smoothing: Option<TimingSmoothing>,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            starvation_after: Duration::from_millis(DEFAULT_STARVATION_MS),
+            smoothing: None,
+        }
+    }
+}
+
+impl OutboxConfig {
+    /// Smoothing matched to the tier: coarse ticks for high-risk users, fine
+    /// ones for normal use, none for bulk transfers.
+    pub fn for_tier(tier: SecurityTier) -> Self {
+        let smoothing = match tier {
+            SecurityTier::HighRisk => Some(TimingSmoothing::with_tick_ms(HIGH_RISK_TICK_MS)),
+            SecurityTier::Normal => Some(TimingSmoothing::with_tick_ms(NORMAL_TICK_MS)),
+            SecurityTier::Bulk => None,
+        };
+        Self {
+            smoothing,
+            ..Self::default()
+        }
+    }
+
+    /// Whether items in `lane` are held to send ticks.
+    pub fn smooths(&self, lane: Lane) -> bool {
+        lane == Lane::Text && self.smoothing.is_some_and(|s| !s.tick.is_zero())
+    }
+}
+
+#[derive(Debug)]
+struct Entry<K, T> {
+    seq: u64,
+    conversation: K,
+    item: T,
+    bytes: usize,
+    enqueued_at: Instant,
+}
+
+/// Priority-aware outbox keyed by conversation `K`.
+#[derive(Debug)]
+pub struct PriorityOutbox<K, T> {
+    config: OutboxConfig,
+    lanes: [VecDeque<Entry<K, T>>; 4],
+    /// Pending content seqs per conversation, oldest first.
+    content_order: HashMap<K, VecDeque<u64>>,
+    next_seq: u64,
+    metrics: [LaneMetrics; 4],
+    /// Start of tick 0: the first push.
+    tick_origin: Option<Instant>,
+    /// Tick of the last smoothed release and items released in it.
+    batch: (u64, usize),
+}
+
+impl<K: Hash + Eq + Clone, T> PriorityOutbox<K, T> {
+    pub fn new(config: OutboxConfig) -> Self {
+        Self {
+            config,
+            lanes: Default::default(),
+            content_order: HashMap::new(),
+            next_seq: 0,
+            metrics: Default::default(),
+            tick_origin: None,
+            batch: (0, 0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lanes.iter().all(VecDeque::is_empty)
+    }
+
+    pub fn lane_len(&self, lane: Lane) -> usize {
+        self.lanes[lane.index()].len()
+    }
+
+    pub fn metrics(&self, lane: Lane) -> &LaneMetrics {
+        &self.metrics[lane.index()]
+    }
+
+    /// Queue an item.
+    pub fn push(&mut self, lane: Lane, conversation: K, item: T, bytes: usize, now: Instant) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.tick_origin.get_or_insert(now);
+        if lane.is_content() {
+            self.content_order
+                .entry(conversation.clone())
+                .or_default()
+                .push_back(seq);
+        }
+        self.lanes[lane.index()].push_back(Entry {
+            seq,
+            conversation,
+            item,
+            bytes,
+            enqueued_at: now,
+        });
+        let m = &mut self.metrics[lane.index()];
+        m.enqueued += 1;
+        m.depth += 1;
+    }
+
+    /// Count an item that was dispatched without queueing (nothing was waiting).
+    pub fn record_bypass(&mut self, lane: Lane, bytes: usize) {
+        let m = &mut self.metrics[lane.index()];
+        m.enqueued += 1;
+        m.dequeued += 1;
+        m.bytes += bytes as u64;
+    }
+
+    /// Tick containing `at`.
+    fn tick_index(&self, at: Instant, tick: Duration) -> u64 {
+        let origin = self.tick_origin.unwrap_or(at);
+        (at.saturating_duration_since(origin).as_nanos() / tick.as_nanos()) as u64
+    }
+
+    /// Start of tick `index`.
+    fn tick_start(&self, index: u64, tick: Duration) -> Option<Instant> {
+        let offset = tick.checked_mul(u32::try_from(index).ok()?)?;
+        self.tick_origin?.checked_add(offset)
+    }
+
+    /// Whether a smoothed item queued at `enqueued_at` may leave at `now`:
+    /// a later tick has begun and this tick's batch is not full.
+    fn released(&self, enqueued_at: Instant, now: Instant) -> bool {
+        let Some(smoothing) = self.config.smoothing else {
+            return true;
+        };
+        let current = self.tick_index(now, smoothing.tick);
+        if current <= self.tick_index(enqueued_at, smoothing.tick) {
+            return false;
+        }
+        let (batch_tick, sent) = self.batch;
+        batch_tick != current || sent < smoothing.max_batch
+    }
+
+    /// Count a smoothed release against the current tick's batch.
+    fn count_release(&mut self, now: Instant) {
+        let Some(smoothing) = self.config.smoothing else {
+            return;
+        };
+        let current = self.tick_index(now, smoothing.tick);
+        self.batch = match self.batch {
+            (tick, sent) if tick == current => (tick, sent + 1),
+            _ => (current, 1),
+        };
+    }
+
+    /// Index of the first item in `lane` that may leave at `now`.
+    fn eligible(&self, lane: Lane, now: Instant) -> Option<usize> {
+        let queue = &self.lanes[lane.index()];
+        if !lane.is_content() {
+            return (!queue.is_empty()).then_some(0);
+        }
+        let smoothed = self.config.smooths(lane);
+        queue.iter().position(|e| {
+            self.content_order
+                .get(&e.conversation)
+                .and_then(|order| order.front())
+                == Some(&e.seq)
+                && (!smoothed || self.released(e.enqueued_at, now))
+        })
+    }
+
+    /// When held text may next leave, if any is waiting: the start of the
+    /// next tick. The caller should pop again then.
+    pub fn next_release(&self, now: Instant) -> Option<Instant> {
+        let smoothing = self.config.smoothing?;
+        if !self.config.smooths(Lane::Text) || self.lane_len(Lane::Text) == 0 {
+            return None;
+        }
+        self.tick_start(self.tick_index(now, smoothing.tick) + 1, smoothing.tick)
+    }
+
+    /// Next item to send.
+    pub fn pop(&mut self, now: Instant) -> Option<(Lane, T)> {
+        let candidates: Vec<(Lane, usize)> = Lane::ALL
+            .iter()
+            .filter_map(|&lane| self.eligible(lane, now).map(|idx| (lane, idx)))
+            .collect();
+
+        let starved = candidates
+            .iter()
+            .filter(|(lane, idx)| {
+                let waited =
+                    now.saturating_duration_since(self.lanes[lane.index()][*idx].enqueued_at);
+                waited >= self.config.starvation_after
+            })
+            .min_by_key(|(lane, idx)| self.lanes[lane.index()][*idx].enqueued_at)
+            .copied();
+        let (lane, idx) = starved.or_else(|| candidates.first().copied())?;
+
+        let entry = self.lanes[lane.index()].remove(idx)?;
+        if self.config.smooths(lane) {
+            self.count_release(now);
+        }
+        if lane.is_content() {
+            if let Some(order) = self.content_order.get_mut(&entry.conversation) {
+                order.pop_front();
+                if order.is_empty() {
+                    self.content_order.remove(&entry.conversation);
+                }
+            }
+        }
+
+        let waited = now.saturating_duration_since(entry.enqueued_at);
+        let m = &mut self.metrics[lane.index()];
+        m.dequeued += 1;
+        m.depth -= 1;
+        m.bytes += entry.bytes as u64;
+        m.total_wait += waited;
+        m.max_wait = m.max_wait.max(waited);
+        Some((lane, entry.item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox() -> PriorityOutbox<&'static str, &'static str> {
+        PriorityOutbox::new(OutboxConfig::default())
+    }
+
+    fn drain(
+        o: &mut PriorityOutbox<&'static str, &'static str>,
+        now: Instant,
+    ) -> Vec<&'static str> {
+        std::iter::from_fn(|| o.pop(now).map(|(_, item)| item)).collect()
+    }
+
+    #[test]
+    fn test_control_and_receipts_overtake_media() {
+        let mut o = outbox();
+        let t0 = Instant::now();
+        o.push(Lane::Media, "alice", "photo", 5_000_000, t0);
+        o.push(Lane::Text, "bob", "hi", 40, t0);
+        o.push(Lane::Receipt, "alice", "ack", 8, t0);
+        o.push(Lane::Control, "carol", "pong", 8, t0);
+        assert_eq!(drain(&mut o, t0), vec!["pong", "ack", "hi", "photo"]);
+        assert!(o.is_empty());
+    }
+
+    #[test]
+    fn test_content_keeps_conversation_order() {
+        let mut o = outbox();
+        let t0 = Instant::now();
+        o.push(Lane::Media, "alice", "alice-photo", 1_000, t0);
+        o.push(Lane::Text, "alice", "alice-caption", 10, t0);
+        o.push(Lane::Text, "bob", "bob-text", 10, t0);
+        // Bob's text is not held up; Alice's caption waits for her photo.
+        assert_eq!(
+            drain(&mut o, t0),
+            vec!["bob-text", "alice-photo", "alice-caption"]
+        );
+    }
+
+    #[test]
+    fn test_starved_lane_served_and_metrics() {
+        let mut o = outbox();
+        let t0 = Instant::now();
+        o.push(Lane::Media, "alice", "voice", 100, t0);
+        let later = t0 + Duration::from_millis(DEFAULT_STARVATION_MS);
+        o.push(Lane::Text, "bob", "text", 10, later);
+        assert_eq!(o.pop(later), Some((Lane::Media, "voice")));
+        assert_eq!(o.pop(later), Some((Lane::Text, "text")));
+
+        let media = o.metrics(Lane::Media);
+        assert_eq!((media.enqueued, media.dequeued, media.depth), (1, 1, 0));
+        assert_eq!(media.bytes, 100);
+        assert_eq!(media.max_wait, Duration::from_millis(DEFAULT_STARVATION_MS));
+        o.record_bypass(Lane::Control, 8);
+        assert_eq!(o.metrics(Lane::Control).dequeued, 1);
+        assert_eq!(o.metrics(Lane::Text).mean_wait(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_smoothing_quantizes_text_to_ticks() {
+        let mut o = PriorityOutbox::new(OutboxConfig::for_tier(SecurityTier::HighRisk));
+        let tick = Duration::from_millis(HIGH_RISK_TICK_MS);
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        o.push(Lane::Text, "alice", "typing", 1, t0);
+        o.push(Lane::Text, "alice", "h", 10, ms(70));
+        o.push(Lane::Text, "bob", "yo", 10, ms(230));
+        o.push(Lane::Control, "carol", "pong", 8, ms(240));
+
+        // Control is not held; text waits for the tick boundary
+        assert_eq!(drain(&mut o, ms(250)), vec!["pong"]);
+        assert_eq!(o.next_release(ms(250)), Some(t0 + tick));
+        assert_eq!(drain(&mut o, t0 + tick), vec!["typing", "h", "yo"]);
+
+        // A send just after a boundary waits for the next one
+        o.push(Lane::Text, "alice", "hi", 10, ms(510));
+        assert_eq!(drain(&mut o, ms(999)), Vec::<&str>::new());
+        assert_eq!(o.next_release(ms(999)), Some(t0 + 2 * tick));
+        assert_eq!(drain(&mut o, t0 + 2 * tick), vec!["hi"]);
+        assert_eq!(o.next_release(t0 + 2 * tick), None);
+        assert_eq!(
+            o.metrics(Lane::Text).max_wait,
+            Duration::from_millis(HIGH_RISK_TICK_MS)
+        );
+    }
+
+    #[test]
+    fn test_smoothing_caps_batch_and_keeps_order() {
+        let mut o = PriorityOutbox::new(OutboxConfig {
+            smoothing: Some(TimingSmoothing {
+                tick: Duration::from_millis(100),
+                max_batch: 2,
+            }),
+            ..OutboxConfig::default()
+        });
+        let t0 = Instant::now();
+        for item in ["a", "b", "c"] {
+            o.push(Lane::Text, "alice", item, 1, t0);
+        }
+        // Media behind held text in the same conversation waits with it
+        o.push(Lane::Media, "alice", "photo", 1_000, t0);
+        o.push(Lane::Media, "bob", "voice", 1_000, t0);
+        assert_eq!(drain(&mut o, t0), vec!["voice"]);
+
+        let tick1 = t0 + Duration::from_millis(100);
+        assert_eq!(drain(&mut o, tick1), vec!["a", "b"]);
+        let tick2 = t0 + Duration::from_millis(200);
+        assert_eq!(drain(&mut o, tick2), vec!["c", "photo"]);
+
+        assert!(!OutboxConfig::default().smooths(Lane::Text));
+        assert!(!OutboxConfig::for_tier(SecurityTier::Bulk).smooths(Lane::Text));
+        assert!(!OutboxConfig::for_tier(SecurityTier::Normal).smooths(Lane::Media));
+    }
+}
@@ -65,6 +65,11 @@ interface ShieldMessengerWasm {
   verify_contact_fingerprint(ourIdentityB64: string, scannedQrData: string): string;
   detect_identity_key_change(ourIdentityB64: string, storedTheirIdentityB64: string, currentTheirIdentityB64: string): string;
 
+  // Mailbox Polling
+  mailbox_check_request(configJson: string, indexRelays: number, publicKeyB64: string, privateKeyB64: string, nowSecs: bigint): string;
+  mailbox_read_bucket(publicKeyB64: string, recordB64: string): string;
+  mailbox_read_pir(publicKeyB64: string, answerAB64: string, answerBB64: string): string;
+
   // Utility
   get_version(): string;
 }
@@ -340,6 +345,56 @@ export function detectIdentityKeyChange(
   );
 }
 
+// ─── Mailbox Polling ───
+
+export type MailboxCheckMode = 'Pir' | 'Bucketed' | 'Plain';
+
+export interface MailboxPollConfig {
+  mode: MailboxCheckMode;
+  /** The relays' mailbox index has 2^bucket_bits buckets. */
+  bucket_bits: number;
+  poll_interval_ms: number;
+}
+
+export type MailboxCheckRequest =
+  | { mode: 'Pir'; bucketBits: number; queries: [string, string] }
+  | { mode: 'Bucketed'; bucketBits: number; bucket: number }
+  | { mode: 'Plain'; poll: string };
+
+export type MailboxCheckResult = { result: 'HasMail' | 'Empty' | 'Unknown' };
+
+/**
+ * Build the next mailbox check. Falls back from PIR (two index relays) to
+ * bucketed (one) to a plain signed poll (none).
+ */
+export function mailboxCheckRequest(
+  config: MailboxPollConfig,
+  indexRelays: number,
+  publicKeyB64: string,
+  privateKeyB64: string,
+  nowSecs: number = Math.floor(Date.now() / 1000),
+): MailboxCheckRequest {
+  return JSON.parse(
+    getWasm().mailbox_check_request(JSON.stringify(config), indexRelays, publicKeyB64, privateKeyB64, BigInt(nowSecs)),
+  );
+}
+
+/**
+ * Check our mailbox in the bucket record a relay returned.
+ * 'Unknown' means the bucket overflowed: fall back to a plain poll.
+ */
+export function mailboxReadBucket(publicKeyB64: string, recordB64: string): MailboxCheckResult {
+  return JSON.parse(getWasm().mailbox_read_bucket(publicKeyB64, recordB64));
+}
+
+/**
+ * Combine both relays' PIR answers. Throws if the relays disagree on the
+ * index; retry with a bucketed check then.
+ */
+export function mailboxReadPir(publicKeyB64: string, answerAB64: string, answerBB64: string): MailboxCheckResult {
+  return JSON.parse(getWasm().mailbox_read_pir(publicKeyB64, answerAB64, answerBB64));
+}
+
 // ─── Version ───
 
 export function getVersion(): string {